@@ -0,0 +1,17 @@
+//! Generates the gRPC messages, servers and clients from `proto/`
+
+const PROTOS: &[&str] = &[
+    "proto/rustpress/blog/v1/posts.proto",
+    "proto/rustpress/auth/v1/tokens.proto",
+    "proto/rustpress/analytics/v1/ingest.proto",
+];
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // protoc comes with the build instead of being a system requirement
+    std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path()?);
+    let well_known = protoc_bin_vendored::include_path()?;
+
+    println!("cargo:rerun-if-changed=proto");
+    tonic_build::configure().compile_protos(PROTOS, &[std::path::PathBuf::from("proto"), well_known])?;
+    Ok(())
+}
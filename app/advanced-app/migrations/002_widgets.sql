@@ -0,0 +1,17 @@
+-- RustPress Blog API - Widgets
+--
+-- Widget instances placed in named sidebar areas. Widget types are
+-- registered in code; only placement and per-instance settings live here.
+
+CREATE TABLE IF NOT EXISTS blog_widget_instances (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    sidebar VARCHAR(50) NOT NULL,
+    widget_type VARCHAR(50) NOT NULL,
+    title VARCHAR(100),
+    position INTEGER NOT NULL DEFAULT 0,
+    settings JSONB NOT NULL DEFAULT '{}',
+    created_at TIMESTAMPTZ DEFAULT NOW(),
+    updated_at TIMESTAMPTZ DEFAULT NOW()
+);
+
+CREATE INDEX idx_widget_instances_sidebar ON blog_widget_instances(sidebar, position);
@@ -0,0 +1,17 @@
+-- RustPress Blog API - Settings Audit
+--
+-- Every change made through the settings API or an import is recorded here.
+-- Secret values are stored masked.
+
+CREATE TABLE IF NOT EXISTS blog_settings_audit (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    namespace VARCHAR(100) NOT NULL,
+    setting_key VARCHAR(100) NOT NULL,
+    old_value JSONB,
+    new_value JSONB,
+    changed_by UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    source VARCHAR(20) NOT NULL DEFAULT 'api',
+    changed_at TIMESTAMPTZ DEFAULT NOW()
+);
+
+CREATE INDEX idx_settings_audit_namespace ON blog_settings_audit(namespace, changed_at DESC);
@@ -0,0 +1,67 @@
+-- RustPress Blog API - Multisite
+--
+-- One deployment serves several blogs. Requests are resolved to a site by
+-- host or path prefix; content, widgets and settings changes are scoped by
+-- site_id. Existing rows are assigned to the default site, and inserts that
+-- don't name a site (older plugins) land there too.
+
+CREATE TABLE IF NOT EXISTS blog_sites (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    slug VARCHAR(60) NOT NULL UNIQUE,
+    name VARCHAR(200) NOT NULL,
+    host VARCHAR(255) UNIQUE,
+    path_prefix VARCHAR(100) UNIQUE,
+    is_default BOOLEAN NOT NULL DEFAULT false,
+    config JSONB NOT NULL DEFAULT '{}',
+    created_at TIMESTAMPTZ DEFAULT NOW(),
+    updated_at TIMESTAMPTZ DEFAULT NOW()
+);
+
+CREATE UNIQUE INDEX idx_sites_default ON blog_sites(is_default) WHERE is_default;
+
+INSERT INTO blog_sites (slug, name, is_default) VALUES ('default', 'Blog', true);
+
+CREATE FUNCTION blog_default_site_id() RETURNS UUID AS $$
+    SELECT id FROM blog_sites WHERE is_default
+$$ LANGUAGE sql STABLE;
+
+-- Users' memberships; the default site is open to every user
+CREATE TABLE IF NOT EXISTS blog_site_members (
+    site_id UUID NOT NULL REFERENCES blog_sites(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    role VARCHAR(20) NOT NULL DEFAULT 'author',
+    created_at TIMESTAMPTZ DEFAULT NOW(),
+    PRIMARY KEY (site_id, user_id)
+);
+
+CREATE INDEX idx_site_members_user ON blog_site_members(user_id);
+
+-- Site-scoped content
+ALTER TABLE blog_posts
+    ADD COLUMN site_id UUID NOT NULL DEFAULT blog_default_site_id() REFERENCES blog_sites(id) ON DELETE CASCADE;
+ALTER TABLE blog_categories
+    ADD COLUMN site_id UUID NOT NULL DEFAULT blog_default_site_id() REFERENCES blog_sites(id) ON DELETE CASCADE;
+ALTER TABLE blog_tags
+    ADD COLUMN site_id UUID NOT NULL DEFAULT blog_default_site_id() REFERENCES blog_sites(id) ON DELETE CASCADE;
+ALTER TABLE blog_comments
+    ADD COLUMN site_id UUID NOT NULL DEFAULT blog_default_site_id() REFERENCES blog_sites(id) ON DELETE CASCADE;
+ALTER TABLE blog_media
+    ADD COLUMN site_id UUID NOT NULL DEFAULT blog_default_site_id() REFERENCES blog_sites(id) ON DELETE CASCADE;
+ALTER TABLE blog_widget_instances
+    ADD COLUMN site_id UUID NOT NULL DEFAULT blog_default_site_id() REFERENCES blog_sites(id) ON DELETE CASCADE;
+ALTER TABLE blog_settings_audit
+    ADD COLUMN site_id UUID NOT NULL DEFAULT blog_default_site_id() REFERENCES blog_sites(id) ON DELETE CASCADE;
+
+-- Slugs only need to be unique within a site
+ALTER TABLE blog_posts DROP CONSTRAINT blog_posts_slug_key;
+ALTER TABLE blog_posts ADD CONSTRAINT blog_posts_site_slug_key UNIQUE (site_id, slug);
+ALTER TABLE blog_categories DROP CONSTRAINT blog_categories_slug_key;
+ALTER TABLE blog_categories ADD CONSTRAINT blog_categories_site_slug_key UNIQUE (site_id, slug);
+ALTER TABLE blog_tags DROP CONSTRAINT blog_tags_slug_key;
+ALTER TABLE blog_tags ADD CONSTRAINT blog_tags_site_slug_key UNIQUE (site_id, slug);
+
+CREATE INDEX idx_posts_site_published ON blog_posts(site_id, published_at DESC) WHERE status = 'published';
+CREATE INDEX idx_comments_site_status ON blog_comments(site_id, status);
+CREATE INDEX idx_media_site_uploader ON blog_media(site_id, uploader_id, created_at DESC);
+CREATE INDEX idx_widget_instances_site ON blog_widget_instances(site_id, sidebar, position);
+CREATE INDEX idx_settings_audit_site ON blog_settings_audit(site_id, namespace, changed_at DESC);
@@ -0,0 +1,15 @@
+-- RustPress Blog API - Private media
+--
+-- Private files are stored outside the publicly served prefix and reached
+-- only through signed download links.
+
+DO $$ BEGIN
+    CREATE TYPE media_visibility AS ENUM ('public', 'private');
+EXCEPTION
+    WHEN duplicate_object THEN null;
+END $$;
+
+ALTER TABLE blog_media
+    ADD COLUMN IF NOT EXISTS visibility media_visibility NOT NULL DEFAULT 'public';
+
+CREATE INDEX IF NOT EXISTS idx_media_private ON blog_media(site_id, visibility) WHERE visibility = 'private';
@@ -0,0 +1,39 @@
+-- RustPress Blog API - Content activity
+--
+-- Who created, changed, published or removed which post, comment or category.
+-- This is the editorial history shown to admins; sign-ins and other security
+-- events stay in the auth plugin's audit log.
+
+DO $$ BEGIN
+    CREATE TYPE content_object_type AS ENUM ('post', 'comment', 'category');
+EXCEPTION
+    WHEN duplicate_object THEN null;
+END $$;
+
+DO $$ BEGIN
+    CREATE TYPE content_action AS ENUM (
+        'created', 'updated', 'published', 'unpublished', 'approved', 'rejected', 'trashed'
+    );
+EXCEPTION
+    WHEN duplicate_object THEN null;
+END $$;
+
+CREATE TABLE IF NOT EXISTS content_activity (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    site_id UUID NOT NULL REFERENCES blog_sites(id) ON DELETE CASCADE,
+    -- NULL for guests and background jobs
+    actor_id UUID REFERENCES users(id) ON DELETE SET NULL,
+    object_type content_object_type NOT NULL,
+    -- Not a foreign key: entries outlive the objects they describe
+    object_id UUID NOT NULL,
+    action content_action NOT NULL,
+    -- Title or name at the time of the change
+    object_label TEXT,
+    -- Changed fields as {"field": {"from": ..., "to": ...}}
+    changes JSONB,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_content_activity_site ON content_activity(site_id, created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_content_activity_object ON content_activity(object_type, object_id, created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_content_activity_actor ON content_activity(actor_id, created_at DESC);
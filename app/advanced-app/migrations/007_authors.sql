@@ -0,0 +1,38 @@
+-- RustPress Blog API - Author profiles
+--
+-- Public author pages are addressed by slug. A slug is assigned when an
+-- author's first post is published and is kept when they rename themselves,
+-- so archive links stay stable. Social links come from `social_<network>`
+-- user meta entries.
+
+CREATE TABLE IF NOT EXISTS blog_authors (
+    user_id UUID PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    slug VARCHAR(120) NOT NULL UNIQUE,
+    created_at TIMESTAMPTZ DEFAULT NOW()
+);
+
+-- Free-form per-user settings, shared with plugins
+CREATE TABLE IF NOT EXISTS user_meta (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    meta_key VARCHAR(100) NOT NULL,
+    meta_value TEXT NOT NULL,
+    updated_at TIMESTAMPTZ DEFAULT NOW(),
+    PRIMARY KEY (user_id, meta_key)
+);
+
+-- Existing authors: slugified name, with the start of the user ID appended
+-- when two authors share a name
+WITH authors AS (
+    SELECT u.id,
+           COALESCE(NULLIF(TRIM(BOTH '-' FROM REGEXP_REPLACE(LOWER(u.name), '[^a-z0-9]+', '-', 'g')), ''), 'author') AS base
+    FROM users u
+    WHERE EXISTS (SELECT 1 FROM blog_posts p WHERE p.author_id = u.id AND p.status = 'published')
+),
+ranked AS (
+    SELECT id, base, ROW_NUMBER() OVER (PARTITION BY base ORDER BY id) AS n
+    FROM authors
+)
+INSERT INTO blog_authors (user_id, slug)
+SELECT id, CASE WHEN n = 1 THEN base ELSE base || '-' || LEFT(id::text, 8) END
+FROM ranked
+ON CONFLICT DO NOTHING;
@@ -0,0 +1,11 @@
+-- RustPress Blog API - Members-only posts
+--
+-- A post with a required level is shown in full only to readers holding at
+-- least that membership level (see `access`). Levels are defined and granted
+-- by a membership plugin, which keeps each reader's current level in the
+-- `membership_level` user meta entry.
+
+ALTER TABLE blog_posts ADD COLUMN IF NOT EXISTS required_level INTEGER;
+
+CREATE INDEX IF NOT EXISTS idx_blog_posts_required_level
+    ON blog_posts(required_level) WHERE required_level IS NOT NULL;
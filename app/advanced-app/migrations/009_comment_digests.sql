@@ -0,0 +1,44 @@
+-- RustPress Blog API - Comment digests
+--
+-- New comments and reactions on a post are queued for its author and sent as
+-- one daily or weekly summary instead of a message each.
+
+DO $$ BEGIN
+    CREATE TYPE digest_frequency AS ENUM ('off', 'daily', 'weekly');
+EXCEPTION
+    WHEN duplicate_object THEN null;
+END $$;
+
+DO $$ BEGIN
+    CREATE TYPE notification_kind AS ENUM ('comment', 'reaction');
+EXCEPTION
+    WHEN duplicate_object THEN null;
+END $$;
+
+CREATE TABLE IF NOT EXISTS notification_preferences (
+    user_id UUID PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    -- NULL follows the app's default frequency
+    digest_frequency digest_frequency,
+    last_digest_at TIMESTAMPTZ,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Removed once included in a digest
+CREATE TABLE IF NOT EXISTS pending_notifications (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    site_id UUID NOT NULL REFERENCES blog_sites(id) ON DELETE CASCADE,
+    recipient_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    post_id UUID NOT NULL REFERENCES blog_posts(id) ON DELETE CASCADE,
+    kind notification_kind NOT NULL,
+    -- The comment or reaction; not a foreign key, reactions live in plugins
+    object_id UUID,
+    actor_name TEXT NOT NULL,
+    -- Start of the comment, or the reaction itself
+    summary TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_pending_notifications_recipient ON pending_notifications(recipient_id, created_at);
+-- One pending entry per comment, so approving an already-queued comment
+-- doesn't queue it twice
+CREATE UNIQUE INDEX IF NOT EXISTS idx_pending_notifications_object ON pending_notifications(kind, object_id);
@@ -0,0 +1,29 @@
+-- RustPress Blog API - Media trash and orphan detection
+--
+-- Deleted media is moved to the trash and its file removed later by the
+-- cleanup job; the row is only dropped once the file is gone, so a failed
+-- storage delete is retried instead of leaving a dangling file.
+
+ALTER TABLE blog_media
+    ADD COLUMN IF NOT EXISTS trashed_at TIMESTAMPTZ,
+    ADD COLUMN IF NOT EXISTS trashed_by UUID REFERENCES users(id) ON DELETE SET NULL;
+
+CREATE INDEX IF NOT EXISTS idx_media_trashed ON blog_media(trashed_at) WHERE trashed_at IS NOT NULL;
+
+DO $$ BEGIN
+    CREATE TYPE media_orphan_kind AS ENUM ('missing_file', 'untracked_file');
+EXCEPTION
+    WHEN duplicate_object THEN null;
+END $$;
+
+-- Findings of the last orphan scan: rows whose file is missing, and files
+-- under the media prefixes that no row points at
+CREATE TABLE IF NOT EXISTS media_orphans (
+    storage_path TEXT PRIMARY KEY,
+    kind media_orphan_kind NOT NULL,
+    -- Set for missing files
+    media_id UUID REFERENCES blog_media(id) ON DELETE CASCADE,
+    site_id UUID REFERENCES blog_sites(id) ON DELETE CASCADE,
+    first_seen_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_seen_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,20 @@
+-- RustPress Blog API - Saved post views
+--
+-- Admins save filters of the admin post list (status, author, category,
+-- date range, search) with the columns they export, so editorial teams can
+-- keep their own queues. Views belong to one user on one site.
+
+CREATE TABLE IF NOT EXISTS admin_post_views (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    site_id UUID NOT NULL REFERENCES blog_sites(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    filters JSONB NOT NULL DEFAULT '{}',
+    -- Column names for CSV export, in order
+    columns JSONB NOT NULL DEFAULT '[]',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (site_id, user_id, name)
+);
+
+CREATE INDEX IF NOT EXISTS idx_posts_site_created ON blog_posts(site_id, created_at DESC);
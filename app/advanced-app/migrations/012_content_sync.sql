@@ -0,0 +1,18 @@
+-- RustPress Blog API - Files-based content sync
+--
+-- With `[app.sync]` enabled, posts are mirrored to Markdown files. Each
+-- synced post's file and the hash of its last synced contents are tracked
+-- here, so unchanged files aren't re-imported and renamed or deleted posts
+-- have their old files removed.
+
+CREATE TABLE IF NOT EXISTS content_sync_files (
+    -- Not a foreign key: the row outlives the post until its file is removed
+    post_id UUID PRIMARY KEY,
+    site_id UUID NOT NULL REFERENCES blog_sites(id) ON DELETE CASCADE,
+    -- Relative to the site's directory
+    path TEXT NOT NULL,
+    -- SHA-256 of the file as last written or imported
+    hash TEXT NOT NULL,
+    synced_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (site_id, path)
+);
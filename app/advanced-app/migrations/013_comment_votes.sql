@@ -0,0 +1,22 @@
+-- RustPress Blog API - Comment votes
+--
+-- One vote per user, or per guest visitor (a hash of site, address and
+-- user agent), per comment: 1 up (or a heart), -1 down. Comments keep the
+-- totals so threads can be sorted by score without counting votes.
+
+ALTER TABLE blog_comments
+    ADD COLUMN IF NOT EXISTS score INTEGER NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS upvotes INTEGER NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS downvotes INTEGER NOT NULL DEFAULT 0;
+
+CREATE TABLE IF NOT EXISTS blog_comment_votes (
+    comment_id UUID NOT NULL REFERENCES blog_comments(id) ON DELETE CASCADE,
+    -- `user:<id>` or `visitor:<hash>`
+    voter TEXT NOT NULL,
+    value SMALLINT NOT NULL CHECK (value IN (-1, 1)),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (comment_id, voter)
+);
+
+CREATE INDEX IF NOT EXISTS idx_comments_post_score ON blog_comments(post_id, score DESC);
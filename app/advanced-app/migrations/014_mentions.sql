@@ -0,0 +1,27 @@
+-- RustPress Blog API - Mentions
+--
+-- `@<author slug>` in a post or comment mentions that user. Each mention is
+-- recorded once per post or comment; it is notified (in-app here, and by
+-- email through the digest queue) once the post is published or the comment
+-- approved.
+
+ALTER TYPE notification_kind ADD VALUE IF NOT EXISTS 'mention';
+
+CREATE TABLE IF NOT EXISTS blog_mentions (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    site_id UUID NOT NULL REFERENCES blog_sites(id) ON DELETE CASCADE,
+    post_id UUID NOT NULL REFERENCES blog_posts(id) ON DELETE CASCADE,
+    -- The post itself or one of its comments
+    object_type content_object_type NOT NULL,
+    object_id UUID NOT NULL,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    actor_name TEXT NOT NULL,
+    -- Start of the post excerpt or comment
+    summary TEXT NOT NULL,
+    notified_at TIMESTAMPTZ,
+    read_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (object_id, user_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_blog_mentions_user ON blog_mentions(user_id, site_id, notified_at DESC);
@@ -0,0 +1,37 @@
+-- RustPress Blog API - Trusted commenters
+--
+-- Commenters with enough approved comments on a site, matched by account or
+-- by an account's verified email, skip moderation. Admins can override that
+-- per user, and every comment approved this way is recorded for spot checks.
+
+DO $$ BEGIN
+    CREATE TYPE comment_trust_reason AS ENUM ('history', 'override');
+EXCEPTION
+    WHEN duplicate_object THEN null;
+END $$;
+
+CREATE TABLE IF NOT EXISTS blog_commenter_trust (
+    site_id UUID NOT NULL REFERENCES blog_sites(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    -- TRUE always skips moderation, FALSE always holds comments
+    trusted BOOLEAN NOT NULL,
+    set_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (site_id, user_id)
+);
+
+CREATE TABLE IF NOT EXISTS blog_comment_auto_approvals (
+    comment_id UUID PRIMARY KEY REFERENCES blog_comments(id) ON DELETE CASCADE,
+    site_id UUID NOT NULL REFERENCES blog_sites(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    reason comment_trust_reason NOT NULL,
+    -- Approved comments the commenter had at the time
+    approved_comments INTEGER NOT NULL,
+    reviewed_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    reviewed_at TIMESTAMPTZ,
+    kept BOOLEAN,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_blog_comment_auto_approvals_site ON blog_comment_auto_approvals(site_id, created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_comments_author_email ON blog_comments(site_id, LOWER(author_email));
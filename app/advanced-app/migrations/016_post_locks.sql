@@ -0,0 +1,13 @@
+-- RustPress Blog API - Post edit locks
+--
+-- One lock per post, held by whoever has it open in the editor and renewed
+-- by heartbeats. A lock past `expires_at` is free to take.
+
+CREATE TABLE IF NOT EXISTS blog_post_locks (
+    post_id UUID PRIMARY KEY REFERENCES blog_posts(id) ON DELETE CASCADE,
+    site_id UUID NOT NULL REFERENCES blog_sites(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    acquired_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    heartbeat_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMPTZ NOT NULL
+);
@@ -0,0 +1,29 @@
+-- RustPress Blog API - Post revisions
+--
+-- A numbered copy of a post's text fields each time they change, so any two
+-- versions can be compared (`GET /posts/:id/diff`). Existing posts start with
+-- their current text as revision 1.
+
+CREATE TABLE IF NOT EXISTS blog_post_revisions (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    site_id UUID NOT NULL REFERENCES blog_sites(id) ON DELETE CASCADE,
+    post_id UUID NOT NULL REFERENCES blog_posts(id) ON DELETE CASCADE,
+    number INTEGER NOT NULL,
+    -- Who saved it; NULL for background jobs and the backfill
+    author_id UUID REFERENCES users(id) ON DELETE SET NULL,
+    title VARCHAR(200) NOT NULL,
+    slug VARCHAR(250) NOT NULL,
+    content TEXT NOT NULL,
+    excerpt TEXT,
+    featured_image VARCHAR(500),
+    meta_title VARCHAR(70),
+    meta_description VARCHAR(160),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (post_id, number)
+);
+
+INSERT INTO blog_post_revisions
+    (site_id, post_id, number, title, slug, content, excerpt, featured_image, meta_title, meta_description, created_at)
+SELECT site_id, id, 1, title, slug, content, excerpt, featured_image, meta_title, meta_description, COALESCE(updated_at, NOW())
+FROM blog_posts
+ON CONFLICT (post_id, number) DO NOTHING;
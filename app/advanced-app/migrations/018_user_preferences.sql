@@ -0,0 +1,12 @@
+-- RustPress Blog API - User display preferences
+--
+-- A user's choices, one row per key. Keys without a row use the site's
+-- default.
+
+CREATE TABLE IF NOT EXISTS blog_user_preferences (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    key VARCHAR(64) NOT NULL,
+    value JSONB NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_id, key)
+);
@@ -0,0 +1,22 @@
+-- RustPress Blog API - Tool API keys
+--
+-- Keys that agents and scripts call `/tools` with. Each acts as one user on
+-- one site, limited to its scopes. Only a SHA-256 hash of the key is kept;
+-- `prefix` identifies it in lists. Revoked keys stay for the record.
+
+CREATE TABLE IF NOT EXISTS blog_tool_keys (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    site_id UUID NOT NULL REFERENCES blog_sites(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    prefix TEXT NOT NULL,
+    key_hash TEXT NOT NULL UNIQUE,
+    -- Scope names, such as "posts:write"
+    scopes JSONB NOT NULL DEFAULT '[]',
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_used_at TIMESTAMPTZ,
+    revoked_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_tool_keys_site ON blog_tool_keys(site_id, created_at DESC);
@@ -0,0 +1,32 @@
+-- RustPress Blog API - Alt text suggestions
+--
+-- Images uploaded to sites that opted in, and images queued by a library
+-- backfill, get alt text written by a vision model. It's kept here as a
+-- suggestion until an editor accepts (possibly edited) or rejects it; only
+-- accepting writes `blog_media.alt_text`. The table doubles as the queue:
+-- workers claim queued rows with `claimed_at`.
+
+DO $$ BEGIN
+    CREATE TYPE alt_suggestion_status AS ENUM ('queued', 'pending', 'accepted', 'rejected', 'failed');
+EXCEPTION
+    WHEN duplicate_object THEN null;
+END $$;
+
+CREATE TABLE IF NOT EXISTS blog_media_alt_suggestions (
+    media_id UUID PRIMARY KEY REFERENCES blog_media(id) ON DELETE CASCADE,
+    site_id UUID NOT NULL REFERENCES blog_sites(id) ON DELETE CASCADE,
+    status alt_suggestion_status NOT NULL DEFAULT 'queued',
+    suggestion TEXT,
+    model TEXT,
+    error TEXT,
+    attempts INT NOT NULL DEFAULT 0,
+    -- Set while a worker generates the suggestion; stale claims are retaken
+    claimed_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    generated_at TIMESTAMPTZ,
+    reviewed_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    reviewed_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_alt_suggestions_queue ON blog_media_alt_suggestions(created_at) WHERE status = 'queued';
+CREATE INDEX IF NOT EXISTS idx_alt_suggestions_site ON blog_media_alt_suggestions(site_id, status, created_at DESC);
@@ -0,0 +1,10 @@
+-- RustPress Blog API - Comment toxicity
+--
+-- New comments are scored by a moderation provider; comments scoring above
+-- the thresholds are held or marked as spam. The score is kept with the
+-- comment, with each attribute's score and the model, for moderators.
+
+ALTER TABLE blog_comments
+    ADD COLUMN IF NOT EXISTS toxicity REAL,
+    ADD COLUMN IF NOT EXISTS toxicity_attributes JSONB,
+    ADD COLUMN IF NOT EXISTS toxicity_model TEXT;
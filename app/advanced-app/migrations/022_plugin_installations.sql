@@ -0,0 +1,18 @@
+-- RustPress Blog API - Plugin installations
+--
+-- Plugins installed from the marketplace registry, with the artifact they
+-- came from and its checksum, so an admin can tell what was installed when,
+-- by whom and over which version.
+
+CREATE TABLE IF NOT EXISTS plugin_installations (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    plugin_id VARCHAR(100) NOT NULL,
+    version VARCHAR(50) NOT NULL,
+    previous_version VARCHAR(50),
+    artifact_url TEXT NOT NULL,
+    sha256 CHAR(64) NOT NULL,
+    installed_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    installed_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_plugin_installations_plugin ON plugin_installations(plugin_id, installed_at DESC);
@@ -0,0 +1,35 @@
+-- RustPress Blog API - Backups
+--
+-- Each backup is a directory in storage holding the table dumps (or a
+-- pg_dump archive), a manifest of the media files and a checksum manifest.
+-- The row keeps the point in time the dump was taken at, so a restore can
+-- be checked against the schema versions it needs.
+
+DO $$ BEGIN
+    CREATE TYPE backup_status AS ENUM ('running', 'completed', 'failed', 'pruned');
+EXCEPTION
+    WHEN duplicate_object THEN null;
+END $$;
+
+CREATE TABLE IF NOT EXISTS blog_backups (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    status backup_status NOT NULL DEFAULT 'running',
+    method VARCHAR(20) NOT NULL,
+    -- Storage directory of the artifacts
+    storage_prefix TEXT NOT NULL,
+    size_bytes BIGINT NOT NULL DEFAULT 0,
+    -- [{ "path", "sha256", "size" }, ...]
+    artifacts JSONB NOT NULL DEFAULT '[]',
+    -- WAL position, server time and migration versions at the snapshot
+    point_in_time JSONB NOT NULL DEFAULT '{}',
+    error TEXT,
+    started_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    started_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    completed_at TIMESTAMPTZ,
+    -- Last integrity check of the stored artifacts
+    verified_at TIMESTAMPTZ,
+    verified BOOLEAN,
+    pruned_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_backups_started ON blog_backups(started_at DESC);
@@ -0,0 +1,154 @@
+//! Members-only Content
+//!
+//! A post with a `required_level` is shown in full only to readers whose
+//! membership level reaches it. Levels are granted by a membership plugin,
+//! which records the reader's current level in the `membership_level` user
+//! meta entry (and, for time-limited memberships, `membership_expires_at`).
+//! Editors, admins and the post's own author always see the full post.
+//!
+//! Everyone else gets a teaser: the post content is passed through the
+//! `members_only_content` filter, where the plugin truncates it and adds its
+//! sign-up prompt. If no filter changes the content, the first
+//! [`TEASER_WORDS`] words are used instead, so a missing or deactivated
+//! plugin never exposes the full post.
+
+use crate::extractors::User;
+use crate::models::{Post, PostWithRelations};
+use crate::services::ServiceError;
+use rustpress_apps::prelude::*;
+use rustpress_auth::db::DbPools;
+use std::collections::HashMap;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Filter applied to the content of posts the reader can't view
+pub const CONTENT_FILTER: &str = "members_only_content";
+
+/// Length of the built-in teaser
+pub const TEASER_WORDS: usize = 55;
+
+/// The reader a response is built for
+#[derive(Debug, Clone, Default)]
+pub struct Viewer {
+    pub user: Option<User>,
+    /// Membership level; 0 for non-members
+    pub level: i32,
+}
+
+impl Viewer {
+    pub fn anonymous() -> Self {
+        Self::default()
+    }
+}
+
+/// Whether `viewer` may read the whole of `post`
+pub fn can_view(post: &Post, viewer: &Viewer) -> bool {
+    let Some(required) = post.required_level else {
+        return true;
+    };
+
+    match &viewer.user {
+        Some(user) if user.can_moderate() || user.id == post.author_id => true,
+        _ => viewer.level >= required,
+    }
+}
+
+pub struct ContentAccess {
+    db: Arc<DbPools>,
+    hooks: Arc<HookRegistry>,
+}
+
+impl ContentAccess {
+    pub fn new(db: Arc<DbPools>, hooks: Arc<HookRegistry>) -> Self {
+        Self { db, hooks }
+    }
+
+    /// Resolve the request's user to a viewer with their membership level
+    pub async fn viewer(&self, user: Option<User>) -> Result<Viewer, ServiceError> {
+        let Some(user) = user else {
+            return Ok(Viewer::anonymous());
+        };
+
+        let level: Option<i32> = sqlx::query_scalar(
+            "SELECT l.meta_value::int FROM user_meta l
+             WHERE l.user_id = $1 AND l.meta_key = 'membership_level'
+               AND NOT EXISTS (
+                   SELECT 1 FROM user_meta e
+                   WHERE e.user_id = $1 AND e.meta_key = 'membership_expires_at'
+                     AND e.meta_value::timestamptz <= NOW()
+               )"
+        )
+        .bind(user.id)
+        .fetch_optional(self.db.read())
+        .await?;
+
+        Ok(Viewer {
+            user: Some(user),
+            level: level.unwrap_or(0),
+        })
+    }
+
+    /// Replace the content of posts `viewer` can't read with a teaser
+    ///
+    /// Required levels are re-read rather than taken from the (possibly
+    /// cached) posts, so marking a post members-only applies immediately.
+    pub async fn gate(&self, viewer: &Viewer, posts: &mut [PostWithRelations]) -> Result<(), ServiceError> {
+        let ids: Vec<Uuid> = posts.iter().map(|p| p.post.id).collect();
+        let levels: HashMap<Uuid, Option<i32>> = sqlx::query_as::<_, (Uuid, Option<i32>)>(
+            "SELECT id, required_level FROM blog_posts WHERE id = ANY($1)"
+        )
+        .bind(&ids)
+        .fetch_all(self.db.read())
+        .await?
+        .into_iter()
+        .collect();
+
+        for post in posts.iter_mut() {
+            if let Some(level) = levels.get(&post.post.id) {
+                post.post.required_level = *level;
+            }
+            if !can_view(&post.post, viewer) {
+                post.post.content = self.teaser(&post.post.content).await?;
+                post.locked = true;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn teaser(&self, content: &str) -> Result<String, ServiceError> {
+        let filtered = self
+            .hooks
+            .apply_filters(CONTENT_FILTER, &crate::hooks::filter_context(), content.to_string())
+            .await
+            .map_err(|e| ServiceError::Template(e.to_string()))?;
+
+        if filtered == content {
+            return Ok(teaser(content, TEASER_WORDS));
+        }
+        Ok(filtered)
+    }
+}
+
+/// First `words` words of `html` as a plain-text paragraph
+fn teaser(html: &str, words: usize) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => {
+                in_tag = false;
+                text.push(' ');
+            }
+            _ if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+
+    let mut teaser: Vec<&str> = text.split_whitespace().take(words + 1).collect();
+    let truncated = teaser.len() > words;
+    teaser.truncate(words);
+
+    format!("<p>{}{}</p>", teaser.join(" "), if truncated { "…" } else { "" })
+}
@@ -0,0 +1,230 @@
+//! Content Activity
+//!
+//! Services report every change to a post, comment or category to
+//! `ContentHooks` once it's written. `ActivityLog` is the built-in listener:
+//! it records who did what in `content_activity`, with a field-level diff
+//! for updates, and serves `GET /admin/activity`. Plugins can register their
+//! own listeners (notifications, webhooks) the same way, or handle the
+//! [`CONTENT_CHANGED`] action, which gets each event as JSON with the
+//! request's ID in its context.
+//!
+//! This is the editorial history of a site, separate from the auth plugin's
+//! security audit log.
+
+use crate::models::*;
+use crate::services::ServiceError;
+use crate::sites::Site;
+use axum::async_trait;
+use rustpress_apps::prelude::*;
+use serde_json::{Map, Value};
+use sqlx::{PgPool, Postgres, QueryBuilder};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+/// Action run after each content change, with the [`ContentEvent`] as JSON
+pub const CONTENT_CHANGED: &str = "content_changed";
+
+/// Fields recorded as changed without their values (too large to diff)
+const VALUELESS_FIELDS: &[&str] = &["content"];
+
+/// A change to a post, comment or category
+#[derive(Debug, Clone)]
+pub struct ContentEvent {
+    pub site_id: Uuid,
+    /// `None` for guests and background jobs
+    pub actor_id: Option<Uuid>,
+    pub object_type: ContentObject,
+    pub object_id: Uuid,
+    pub action: ContentAction,
+    /// Title or name, kept so entries stay readable after deletion
+    pub label: Option<String>,
+    /// Changed fields, for updates
+    pub changes: Option<Value>,
+}
+
+impl ContentEvent {
+    pub fn new(site: &Site, actor_id: Option<Uuid>, object_type: ContentObject, object_id: Uuid, action: ContentAction) -> Self {
+        Self {
+            site_id: site.id,
+            actor_id,
+            object_type,
+            object_id,
+            action,
+            label: None,
+            changes: None,
+        }
+    }
+
+    pub fn label(mut self, label: impl Into<String>) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    pub fn changes(mut self, changes: Option<Value>) -> Self {
+        self.changes = changes;
+        self
+    }
+
+    /// The event as handed to [`CONTENT_CHANGED`] handlers
+    pub fn to_json(&self) -> Value {
+        serde_json::json!({
+            "site_id": self.site_id,
+            "actor_id": self.actor_id,
+            "object_type": self.object_type,
+            "object_id": self.object_id,
+            "action": self.action,
+            "label": self.label,
+            "changes": self.changes,
+        })
+    }
+}
+
+/// Receives content changes after they're written
+#[async_trait]
+pub trait ContentListener: Send + Sync {
+    /// Failures are the listener's to log; they never undo the change
+    async fn on_change(&self, event: &ContentEvent);
+}
+
+/// Listeners notified of content changes
+#[derive(Default)]
+pub struct ContentHooks {
+    listeners: RwLock<Vec<Arc<dyn ContentListener>>>,
+    /// Registry running [`CONTENT_CHANGED`], if any
+    actions: Option<Arc<HookRegistry>>,
+}
+
+impl ContentHooks {
+    /// Hooks that also run the [`CONTENT_CHANGED`] action of `actions`
+    pub fn with_actions(actions: Arc<HookRegistry>) -> Self {
+        Self {
+            listeners: RwLock::default(),
+            actions: Some(actions),
+        }
+    }
+
+    pub async fn listen(&self, listener: Arc<dyn ContentListener>) {
+        self.listeners.write().await.push(listener);
+    }
+
+    pub async fn emit(&self, event: ContentEvent) {
+        for listener in self.listeners.read().await.iter() {
+            listener.on_change(&event).await;
+        }
+
+        if let Some(actions) = &self.actions {
+            let ctx = crate::hooks::action_context();
+            if let Err(e) = actions.do_action(CONTENT_CHANGED, &ctx, event.to_json()).await {
+                tracing::warn!("{} action failed: {}", CONTENT_CHANGED, e);
+            }
+        }
+    }
+}
+
+/// Fields of `fields` that differ between two serialized objects, as
+/// `{"field": {"from": ..., "to": ...}}`; `None` if nothing changed
+pub fn diff<T: serde::Serialize>(before: &T, after: &T, fields: &[&str]) -> Option<Value> {
+    let (Ok(before), Ok(after)) = (serde_json::to_value(before), serde_json::to_value(after)) else {
+        return None;
+    };
+
+    let mut changes = Map::new();
+    for field in fields {
+        let (from, to) = (&before[*field], &after[*field]);
+        if from == to {
+            continue;
+        }
+        let change = if VALUELESS_FIELDS.contains(field) {
+            serde_json::json!({ "changed": true })
+        } else {
+            serde_json::json!({ "from": from, "to": to })
+        };
+        changes.insert(field.to_string(), change);
+    }
+
+    (!changes.is_empty()).then_some(Value::Object(changes))
+}
+
+/// Records content changes in `content_activity`
+pub struct ActivityLog {
+    db: PgPool,
+}
+
+impl ActivityLog {
+    pub fn new(db: PgPool) -> Self {
+        Self { db }
+    }
+
+    /// Entries for a site, newest first
+    pub async fn list(&self, site: &Site, query: &ActivityQuery) -> Result<PaginatedResponse<ActivityEntry>, ServiceError> {
+        let mut entries = QueryBuilder::<Postgres>::new(
+            "SELECT a.id, a.actor_id, u.name AS actor_name, a.object_type, a.object_id, a.action,
+                    a.object_label, a.changes, a.created_at
+             FROM content_activity a
+             LEFT JOIN users u ON u.id = a.actor_id",
+        );
+        push_conditions(&mut entries, site, query);
+        entries
+            .push(" ORDER BY a.created_at DESC LIMIT ")
+            .push_bind(query.per_page())
+            .push(" OFFSET ")
+            .push_bind(query.offset());
+        let data: Vec<ActivityEntry> = entries.build_query_as().fetch_all(&self.db).await?;
+
+        let mut count = QueryBuilder::<Postgres>::new("SELECT COUNT(*) FROM content_activity a");
+        push_conditions(&mut count, site, query);
+        let total: i64 = count.build_query_scalar().fetch_one(&self.db).await?;
+
+        Ok(PaginatedResponse {
+            data,
+            pagination: PaginationMeta::new(total, query.page(), query.per_page()),
+        })
+    }
+}
+
+fn push_conditions(builder: &mut QueryBuilder<'_, Postgres>, site: &Site, query: &ActivityQuery) {
+    builder.push(" WHERE a.site_id = ").push_bind(site.id);
+    if let Some(actor) = query.actor {
+        builder.push(" AND a.actor_id = ").push_bind(actor);
+    }
+    if let Some(object_type) = query.object_type {
+        builder.push(" AND a.object_type = ").push_bind(object_type);
+    }
+    if let Some(object_id) = query.object_id {
+        builder.push(" AND a.object_id = ").push_bind(object_id);
+    }
+    if let Some(action) = query.action {
+        builder.push(" AND a.action = ").push_bind(action);
+    }
+    if let Some(from) = query.from {
+        builder.push(" AND a.created_at >= ").push_bind(from);
+    }
+    if let Some(to) = query.to {
+        builder.push(" AND a.created_at < ").push_bind(to);
+    }
+}
+
+#[async_trait]
+impl ContentListener for ActivityLog {
+    async fn on_change(&self, event: &ContentEvent) {
+        let result = sqlx::query(
+            r#"INSERT INTO content_activity
+               (site_id, actor_id, object_type, object_id, action, object_label, changes)
+               VALUES ($1, $2, $3, $4, $5, $6, $7)"#,
+        )
+        .bind(event.site_id)
+        .bind(event.actor_id)
+        .bind(event.object_type)
+        .bind(event.object_id)
+        .bind(event.action)
+        .bind(&event.label)
+        .bind(&event.changes)
+        .execute(&self.db)
+        .await;
+
+        if let Err(e) = result {
+            tracing::warn!(object_id = %event.object_id, "Failed to record content activity: {}", e);
+        }
+    }
+}
@@ -0,0 +1,446 @@
+//! Alt Text Suggestions
+//!
+//! A media pipeline stage after upload: images uploaded to a site that opted
+//! in (`alt_text_suggestions` in its config) are queued, and a background
+//! job asks a vision model to describe them. The description is stored in
+//! `blog_media_alt_suggestions` as a suggestion; it becomes the image's
+//! `alt_text` only once an editor accepts it, as written or edited, with
+//! `POST /media/:id/alt-text/accept`. Nothing is written to the image
+//! without that confirmation.
+//!
+//! `POST /admin/media/alt-text/backfill` queues the site's existing images
+//! that have no alt text. The job works through the queue `batch_size`
+//! images at a time, pausing `batch_pause_ms` between batches so a large
+//! library doesn't exceed the endpoint's rate limits. Failed images are
+//! retried up to [`MAX_ATTEMPTS`] times; a later backfill queues them again.
+//!
+//! The model is any OpenAI-compatible chat completions endpoint that accepts
+//! images; the file is sent inline as a `data:` URL, so private media works
+//! and the endpoint needs no access to storage.
+//!
+//! Off unless `[app.alt_text]` `enabled` and an `endpoint` is set.
+
+use crate::models::*;
+use crate::services::ServiceError;
+use crate::sites::Site;
+use crate::BlogServices;
+use base64::Engine;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+use tokio::sync::Notify;
+use uuid::Uuid;
+
+/// Tries per image before it's marked failed
+pub const MAX_ATTEMPTS: i32 = 3;
+
+/// Minutes after which an image claimed by a worker that never finished is
+/// taken up again
+const CLAIM_TIMEOUT_MINS: i32 = 10;
+
+const DEFAULT_PROMPT: &str = "Write alt text for this image on a blog: one sentence describing what it \
+                              shows, for readers who can't see it. No more than {max_chars} characters. \
+                              Don't start with \"Image of\" or \"Picture of\".";
+
+/// `[app.alt_text]` settings
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct AltTextConfig {
+    /// Offer suggestions to sites that opt in
+    pub enabled: bool,
+    /// OpenAI-compatible chat completions URL of a vision model
+    pub endpoint: Option<String>,
+    /// Sent as a bearer token, if set
+    pub api_key: Option<String>,
+    pub model: String,
+    /// `{max_chars}` is replaced with `max_chars`
+    pub prompt: String,
+    /// Longest suggestion kept; longer ones are cut at a word
+    pub max_chars: usize,
+    pub timeout_secs: u64,
+    /// Images larger than this aren't sent
+    pub max_image_bytes: usize,
+    /// Images described per batch
+    pub batch_size: i64,
+    /// Pause between batches while the queue isn't empty
+    pub batch_pause_ms: u64,
+    /// How often the queue is checked besides right after uploads
+    pub poll_secs: u64,
+}
+
+impl Default for AltTextConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            endpoint: None,
+            api_key: None,
+            model: "gpt-4o-mini".to_string(),
+            prompt: DEFAULT_PROMPT.to_string(),
+            max_chars: 125,
+            timeout_secs: 60,
+            max_image_bytes: 5 * 1024 * 1024,
+            batch_size: 10,
+            batch_pause_ms: 1000,
+            poll_secs: 60,
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum AltTextError {
+    #[error("Vision model request failed: {0}")]
+    Request(#[from] reqwest::Error),
+    #[error("Vision model returned {status}: {message}")]
+    Api { status: u16, message: String },
+    #[error("Vision model returned no text")]
+    Empty,
+    #[error("Image is larger than {0} bytes")]
+    TooLarge(usize),
+    #[error("{0}")]
+    Media(#[from] ServiceError),
+}
+
+/// Alt text suggestions: the queue, the vision model and editors' reviews
+pub struct AltTextService {
+    db: PgPool,
+    http: reqwest::Client,
+    config: AltTextConfig,
+    /// Wakes the job when an upload is queued
+    queued: Arc<Notify>,
+}
+
+impl AltTextService {
+    pub fn new(db: PgPool, config: AltTextConfig) -> Result<Self, AltTextError> {
+        Ok(Self {
+            db,
+            http: reqwest::Client::builder()
+                .timeout(Duration::from_secs(config.timeout_secs))
+                .build()?,
+            config,
+            queued: Arc::new(Notify::new()),
+        })
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.enabled && self.config.endpoint.is_some()
+    }
+
+    /// Whether `site` gets suggestions
+    pub fn enabled_for(&self, site: &Site) -> bool {
+        self.enabled() && site.config.alt_text_suggestions
+    }
+
+    /// Queue a freshly uploaded file, if it's an image on a site that opted
+    /// in; failures are logged, never failing the upload
+    pub async fn queue_upload(&self, site: &Site, media: &Media) {
+        if !self.enabled_for(site) || !media.mime_type.starts_with("image/") {
+            return;
+        }
+
+        let queued = sqlx::query(
+            "INSERT INTO blog_media_alt_suggestions (media_id, site_id) VALUES ($1, $2)
+             ON CONFLICT (media_id) DO NOTHING"
+        )
+        .bind(media.id)
+        .bind(site.id)
+        .execute(&self.db)
+        .await;
+        match queued {
+            Ok(_) => self.queued.notify_one(),
+            Err(e) => tracing::warn!(site = %site.slug, media = %media.id, "Failed to queue alt text: {}", e),
+        }
+    }
+
+    /// Queue the site's images that have no alt text and no suggestion;
+    /// failed ones are queued again
+    pub async fn backfill(&self, site: &Site) -> Result<u64, ServiceError> {
+        if !self.enabled() {
+            return Err(ServiceError::NotFound("Alt text suggestions are disabled".into()));
+        }
+        if !site.config.alt_text_suggestions {
+            return Err(ServiceError::Validation("Alt text suggestions are off for this site".into()));
+        }
+
+        let queued = sqlx::query(
+            "INSERT INTO blog_media_alt_suggestions (media_id, site_id)
+             SELECT id, site_id FROM blog_media
+             WHERE site_id = $1 AND mime_type LIKE 'image/%' AND trashed_at IS NULL AND alt_text IS NULL
+             ON CONFLICT (media_id) DO UPDATE
+                 SET status = 'queued', attempts = 0, error = NULL, claimed_at = NULL, created_at = NOW()
+                 WHERE blog_media_alt_suggestions.status = 'failed'"
+        )
+        .bind(site.id)
+        .execute(&self.db)
+        .await?
+        .rows_affected();
+
+        if queued > 0 {
+            self.queued.notify_one();
+        }
+        Ok(queued)
+    }
+
+    /// Suggestions of `site` in `status`, oldest first; uploaders only see
+    /// their own images unless `all`
+    pub async fn list(
+        &self,
+        site: &Site,
+        uploader: Uuid,
+        all: bool,
+        query: &AltSuggestionQuery,
+    ) -> Result<Vec<AltTextSuggestion>, ServiceError> {
+        let suggestions = sqlx::query_as(
+            "SELECT s.media_id, s.status, s.suggestion, s.model, s.error, s.attempts,
+                    m.url, m.original_name, m.alt_text,
+                    s.created_at, s.generated_at, s.reviewed_by, s.reviewed_at
+             FROM blog_media_alt_suggestions s
+             JOIN blog_media m ON m.id = s.media_id
+             WHERE s.site_id = $1 AND s.status = $2 AND m.trashed_at IS NULL
+               AND ($3 OR m.uploader_id = $4)
+             ORDER BY s.created_at
+             LIMIT $5"
+        )
+        .bind(site.id)
+        .bind(query.status.unwrap_or(AltSuggestionStatus::Pending))
+        .bind(all)
+        .bind(uploader)
+        .bind(query.limit.unwrap_or(50).clamp(1, 200))
+        .fetch_all(&self.db)
+        .await?;
+        Ok(suggestions)
+    }
+
+    /// Save the pending suggestion for `media`, or `edited` instead, as its
+    /// alt text
+    pub async fn accept(
+        &self,
+        media: &Media,
+        reviewer: Uuid,
+        edited: Option<String>,
+    ) -> Result<String, ServiceError> {
+        let mut tx = self.db.begin().await?;
+
+        let suggestion: Option<String> = sqlx::query_scalar(
+            "SELECT suggestion FROM blog_media_alt_suggestions
+             WHERE media_id = $1 AND status = 'pending'
+             FOR UPDATE"
+        )
+        .bind(media.id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .flatten();
+        let suggestion = suggestion.ok_or_else(|| ServiceError::NotFound("No pending alt text suggestion".into()))?;
+        let alt_text = edited.map(|text| text.trim().to_string()).unwrap_or(suggestion);
+        if alt_text.is_empty() {
+            return Err(ServiceError::Validation("Alt text can't be blank".into()));
+        }
+
+        sqlx::query("UPDATE blog_media SET alt_text = $2 WHERE id = $1")
+            .bind(media.id)
+            .bind(&alt_text)
+            .execute(&mut *tx)
+            .await?;
+        self.review(&mut tx, media.id, reviewer, AltSuggestionStatus::Accepted).await?;
+
+        tx.commit().await?;
+        Ok(alt_text)
+    }
+
+    /// Dismiss the pending suggestion for `media`; its alt text is unchanged
+    pub async fn reject(&self, media: &Media, reviewer: Uuid) -> Result<(), ServiceError> {
+        let mut tx = self.db.begin().await?;
+        if !self.review(&mut tx, media.id, reviewer, AltSuggestionStatus::Rejected).await? {
+            return Err(ServiceError::NotFound("No pending alt text suggestion".into()));
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn review(
+        &self,
+        tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+        media_id: Uuid,
+        reviewer: Uuid,
+        status: AltSuggestionStatus,
+    ) -> Result<bool, ServiceError> {
+        let reviewed = sqlx::query(
+            "UPDATE blog_media_alt_suggestions
+             SET status = $3, reviewed_by = $2, reviewed_at = NOW()
+             WHERE media_id = $1 AND status = 'pending'"
+        )
+        .bind(media_id)
+        .bind(reviewer)
+        .bind(status)
+        .execute(&mut **tx)
+        .await?;
+        Ok(reviewed.rows_affected() > 0)
+    }
+
+    /// Describe the next batch of queued images; returns how many were
+    /// taken from the queue
+    pub async fn process_batch(&self, services: &BlogServices) -> Result<usize, ServiceError> {
+        let batch: Vec<(Uuid, Uuid, i32)> = sqlx::query_as(
+            "UPDATE blog_media_alt_suggestions SET claimed_at = NOW(), attempts = attempts + 1
+             WHERE media_id IN (
+                 SELECT media_id FROM blog_media_alt_suggestions
+                 WHERE status = 'queued'
+                   AND (claimed_at IS NULL OR claimed_at < NOW() - make_interval(mins => $2))
+                 ORDER BY created_at
+                 LIMIT $1
+                 FOR UPDATE SKIP LOCKED
+             )
+             RETURNING media_id, site_id, attempts"
+        )
+        .bind(self.config.batch_size.max(1))
+        .bind(CLAIM_TIMEOUT_MINS)
+        .fetch_all(&self.db)
+        .await?;
+
+        for (media_id, site_id, attempts) in &batch {
+            let result = match services.sites.get(*site_id).await {
+                Some(site) => self.describe(services, &site, *media_id).await,
+                None => Err(ServiceError::NotFound("Site not found".into()).into()),
+            };
+            match result {
+                Ok(suggestion) => {
+                    sqlx::query(
+                        "UPDATE blog_media_alt_suggestions
+                         SET status = 'pending', suggestion = $2, model = $3, error = NULL,
+                             claimed_at = NULL, generated_at = NOW()
+                         WHERE media_id = $1"
+                    )
+                    .bind(media_id)
+                    .bind(suggestion)
+                    .bind(&self.config.model)
+                    .execute(&self.db)
+                    .await?;
+                }
+                Err(e) => {
+                    tracing::warn!(media = %media_id, attempts, "Alt text generation failed: {}", e);
+                    // Trashed and oversized images aren't retried
+                    let retry = *attempts < MAX_ATTEMPTS
+                        && !matches!(e, AltTextError::TooLarge(_) | AltTextError::Media(ServiceError::NotFound(_)));
+                    let status = if retry { AltSuggestionStatus::Queued } else { AltSuggestionStatus::Failed };
+                    sqlx::query(
+                        "UPDATE blog_media_alt_suggestions SET status = $2, error = $3, claimed_at = NULL
+                         WHERE media_id = $1"
+                    )
+                    .bind(media_id)
+                    .bind(status)
+                    .bind(e.to_string())
+                    .execute(&self.db)
+                    .await?;
+                }
+            }
+        }
+
+        Ok(batch.len())
+    }
+
+    /// Alt text for the image, from the vision model
+    async fn describe(&self, services: &BlogServices, site: &Site, media_id: Uuid) -> Result<String, AltTextError> {
+        let (media, data) = services.media.download(site, media_id).await?;
+        if data.len() > self.config.max_image_bytes {
+            return Err(AltTextError::TooLarge(self.config.max_image_bytes));
+        }
+
+        let image = format!(
+            "data:{};base64,{}",
+            media.mime_type,
+            base64::engine::general_purpose::STANDARD.encode(&data)
+        );
+        let prompt = self.config.prompt.replace("{max_chars}", &self.config.max_chars.to_string());
+        let text = self.complete(&prompt, &image).await?;
+        clean(&text, self.config.max_chars).ok_or(AltTextError::Empty)
+    }
+
+    async fn complete(&self, prompt: &str, image: &str) -> Result<String, AltTextError> {
+        let endpoint = self.config.endpoint.as_deref().unwrap_or_default();
+        let mut request = self.http.post(endpoint).json(&json!({
+            "model": self.config.model,
+            "max_tokens": 100,
+            "messages": [{
+                "role": "user",
+                "content": [
+                    { "type": "text", "text": prompt },
+                    { "type": "image_url", "image_url": { "url": image } },
+                ],
+            }],
+        }));
+        if let Some(key) = &self.config.api_key {
+            request = request.bearer_auth(key);
+        }
+
+        let response = request.send().await?;
+        let status = response.status();
+        let body: Value = response.json().await.unwrap_or_default();
+        if !status.is_success() {
+            return Err(AltTextError::Api {
+                status: status.as_u16(),
+                message: body["error"]["message"].as_str().unwrap_or("unknown error").to_string(),
+            });
+        }
+        body["choices"][0]["message"]["content"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or(AltTextError::Empty)
+    }
+}
+
+/// A model reply as alt text: one line without surrounding quotes, cut at a
+/// word to `max_chars`; `None` if nothing is left
+pub fn clean(reply: &str, max_chars: usize) -> Option<String> {
+    let line = reply.lines().map(str::trim).find(|line| !line.is_empty())?;
+    let text = line.trim_matches(|c: char| matches!(c, '"' | '\'' | '“' | '”')).trim();
+    if text.is_empty() {
+        return None;
+    }
+    if text.chars().count() <= max_chars {
+        return Some(text.to_string());
+    }
+
+    let cut: String = text.chars().take(max_chars).collect();
+    let cut = match cut.rfind(char::is_whitespace) {
+        Some(at) if at > 0 => &cut[..at],
+        _ => &cut,
+    };
+    Some(cut.trim_end_matches(|c: char| c.is_ascii_punctuation() || c.is_whitespace()).to_string())
+}
+
+// ============================================
+// Background Job
+// ============================================
+
+/// Work through the queue after uploads and backfills, and every
+/// `poll_secs`, until the services are dropped
+pub async fn run(services: Weak<BlogServices>, poll_secs: u64) {
+    let Some(queued) = services.upgrade().map(|services| services.alt_text.queued.clone()) else {
+        return;
+    };
+    let mut interval = tokio::time::interval(Duration::from_secs(poll_secs.max(1)));
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = queued.notified() => {}
+        }
+
+        loop {
+            let Some(services) = services.upgrade() else {
+                return;
+            };
+            match services.alt_text.process_batch(&services).await {
+                Ok(0) => break,
+                Ok(count) => tracing::debug!(count, "Generated alt text suggestions"),
+                Err(e) => {
+                    tracing::warn!("Alt text batch failed: {}", e);
+                    break;
+                }
+            }
+            let pause = Duration::from_millis(services.alt_text.config.batch_pause_ms);
+            drop(services);
+            tokio::time::sleep(pause).await;
+        }
+    }
+}
@@ -0,0 +1,296 @@
+//! AMP Rendering
+//!
+//! `GET /posts/:slug/amp` serves a lightweight AMP version of a post through
+//! the theme's `amp.html` template. The post content is passed through the
+//! `amp_content` filter first, where plugins can turn their own embeds and
+//! shortcodes into AMP components, and then through [`to_amp`], which:
+//!
+//! - converts `<img>`, `<video>` and `<audio>` to `amp-img`, `amp-video` and
+//!   `amp-audio`
+//! - converts YouTube and Vimeo iframes to `amp-youtube` / `amp-vimeo`, other
+//!   HTTPS iframes to `amp-iframe`, and drops the rest
+//! - removes elements AMP disallows (scripts, styles, forms, objects, ...)
+//!   and `style`, event handler and `javascript:` attributes
+//!
+//! The template loads the extension script of every component the result
+//! uses. Canonical post pages link to their AMP version with
+//! `<link rel="amphtml">`, added to the head of whichever theme renders them.
+
+use crate::services::ServiceError;
+use lol_html::html_content::{ContentType, Element};
+use lol_html::{element, rewrite_str, RewriteStrSettings};
+use rustpress_apps::prelude::*;
+use serde::Deserialize;
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+/// Filter applied to post content before the built-in conversion
+pub const CONTENT_FILTER: &str = "amp_content";
+
+/// Elements removed with their content
+const DISALLOWED: &[&str] = &[
+    "script", "noscript", "style", "link", "meta", "base", "form", "input", "textarea", "select", "object",
+    "param", "applet", "embed", "frame", "frameset",
+];
+
+/// Components AMP ships in the core runtime (no extension script)
+const BUILTIN_COMPONENTS: &[&str] = &["amp-img", "amp-layout", "amp-pixel"];
+
+/// Size used for responsive media without `width`/`height` (16:9)
+const DEFAULT_SIZE: (u32, u32) = (1600, 900);
+
+/// `[app.amp]` settings
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct AmpConfig {
+    /// Serve `/posts/:slug/amp` and link post pages to it
+    pub enabled: bool,
+}
+
+impl Default for AmpConfig {
+    fn default() -> Self {
+        Self { enabled: true }
+    }
+}
+
+/// Post content converted to AMP
+#[derive(Debug, Clone)]
+pub struct AmpContent {
+    pub html: String,
+    /// Extension components used, e.g. `amp-youtube`
+    pub components: Vec<String>,
+}
+
+pub struct AmpService {
+    hooks: Arc<HookRegistry>,
+    config: AmpConfig,
+}
+
+impl AmpService {
+    pub fn new(hooks: Arc<HookRegistry>, config: AmpConfig) -> Self {
+        Self { hooks, config }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    /// Run post content through the `amp_content` filter and [`to_amp`]
+    pub async fn content(&self, html: &str) -> Result<AmpContent, ServiceError> {
+        let filtered = self
+            .hooks
+            .apply_filters(CONTENT_FILTER, &crate::hooks::filter_context(), html.to_string())
+            .await
+            .map_err(|e| ServiceError::Template(e.to_string()))?;
+
+        let html = to_amp(&filtered)?;
+        let components = components(&html);
+        Ok(AmpContent { html, components })
+    }
+}
+
+/// Content Security Policy for AMP pages, which load the AMP runtime from
+/// its CDN and may frame embeds
+pub fn content_security_policy(nonce: &str) -> String {
+    format!(
+        "default-src 'self'; script-src 'nonce-{}' https://cdn.ampproject.org/ 'strict-dynamic'; \
+         style-src 'unsafe-inline' https://cdn.ampproject.org/rtv/; img-src 'self' data: https:; \
+         media-src 'self' https:; frame-src https:; object-src 'none'; base-uri 'self'",
+        nonce
+    )
+}
+
+/// Add `<link rel="amphtml">` to the head of a canonical page
+pub fn link_amphtml(html: String, href: &str) -> String {
+    let Some(head_end) = html.find("</head>") else {
+        return html;
+    };
+    if html[..head_end].contains(r#"rel="amphtml""#) {
+        return html;
+    }
+
+    let link = format!(r#"<link rel="amphtml" href="{}">"#, attr(href));
+    let mut html = html;
+    html.insert_str(head_end, &link);
+    html
+}
+
+/// Convert post HTML to AMP: media and embeds become AMP components, and
+/// disallowed elements and attributes are removed
+pub fn to_amp(html: &str) -> Result<String, ServiceError> {
+    let mut handlers = Vec::new();
+    for tag in DISALLOWED {
+        handlers.push(element!(*tag, |el| {
+            el.remove();
+            Ok(())
+        }));
+    }
+    handlers.push(element!("*", |el| {
+        strip_attributes(el);
+        Ok(())
+    }));
+    handlers.push(element!("img", |el| {
+        match amp_img(el) {
+            Some(amp) => el.replace(&amp, ContentType::Html),
+            None => el.remove(),
+        }
+        Ok(())
+    }));
+    handlers.push(element!("iframe", |el| {
+        match amp_embed(el) {
+            Some(amp) => el.replace(&amp, ContentType::Html),
+            None => el.remove(),
+        }
+        Ok(())
+    }));
+    handlers.push(element!("video", |el| {
+        el.set_tag_name("amp-video")?;
+        set_responsive(el)?;
+        Ok(())
+    }));
+    handlers.push(element!("audio", |el| {
+        el.set_tag_name("amp-audio")?;
+        Ok(())
+    }));
+
+    rewrite_str(
+        html,
+        RewriteStrSettings {
+            element_content_handlers: handlers,
+            ..RewriteStrSettings::default()
+        },
+    )
+    .map_err(|e| ServiceError::Template(e.to_string()))
+}
+
+/// Extension components used in AMP HTML, sorted
+fn components(html: &str) -> Vec<String> {
+    let mut found = BTreeSet::new();
+    for (start, _) in html.match_indices("<amp-") {
+        let name: String = html[start + 1..]
+            .chars()
+            .take_while(|c| c.is_ascii_alphanumeric() || *c == '-')
+            .collect();
+        if !BUILTIN_COMPONENTS.contains(&name.as_str()) {
+            found.insert(name);
+        }
+    }
+    found.into_iter().collect()
+}
+
+/// Drop inline styles, event handlers and `javascript:` links
+fn strip_attributes(el: &mut Element) {
+    let disallowed: Vec<String> = el
+        .attributes()
+        .iter()
+        .filter(|a| {
+            let name = a.name();
+            name == "style"
+                || name.starts_with("on")
+                || a.value().trim_start().to_ascii_lowercase().starts_with("javascript:")
+        })
+        .map(|a| a.name())
+        .collect();
+
+    for name in disallowed {
+        el.remove_attribute(&name);
+    }
+}
+
+fn amp_img(el: &Element) -> Option<String> {
+    let src = el.get_attribute("src")?;
+    let (width, height) = size(el);
+
+    let mut amp = format!(
+        r#"<amp-img src="{}" width="{}" height="{}" layout="responsive""#,
+        attr(&src),
+        width,
+        height
+    );
+    for name in ["alt", "srcset", "sizes", "title", "class"] {
+        if let Some(value) = el.get_attribute(name) {
+            amp.push_str(&format!(r#" {}="{}""#, name, attr(&value)));
+        }
+    }
+    amp.push_str("></amp-img>");
+    Some(amp)
+}
+
+/// YouTube and Vimeo players become their components, other HTTPS iframes
+/// `amp-iframe`; `None` for anything else
+fn amp_embed(el: &Element) -> Option<String> {
+    let src = el.get_attribute("src")?;
+    let (width, height) = size(el);
+
+    if let Some(id) = youtube_id(&src) {
+        return Some(format!(
+            r#"<amp-youtube data-videoid="{}" width="{}" height="{}" layout="responsive"></amp-youtube>"#,
+            attr(id),
+            width,
+            height
+        ));
+    }
+    if let Some(id) = vimeo_id(&src) {
+        return Some(format!(
+            r#"<amp-vimeo data-videoid="{}" width="{}" height="{}" layout="responsive"></amp-vimeo>"#,
+            attr(id),
+            width,
+            height
+        ));
+    }
+    if src.starts_with("https://") {
+        return Some(format!(
+            r#"<amp-iframe src="{}" width="{}" height="{}" layout="responsive" sandbox="allow-scripts allow-same-origin allow-popups" frameborder="0"></amp-iframe>"#,
+            attr(&src),
+            width,
+            height
+        ));
+    }
+    None
+}
+
+fn set_responsive(el: &mut Element) -> Result<(), lol_html::errors::AttributeNameError> {
+    let (width, height) = size(el);
+    el.set_attribute("width", &width.to_string())?;
+    el.set_attribute("height", &height.to_string())?;
+    el.set_attribute("layout", "responsive")
+}
+
+/// The element's `width` and `height`, or the default 16:9 size
+fn size(el: &Element) -> (u32, u32) {
+    let dimension = |name| el.get_attribute(name).and_then(|v| v.trim().trim_end_matches("px").parse::<u32>().ok());
+    match (dimension("width"), dimension("height")) {
+        (Some(width), Some(height)) if width > 0 && height > 0 => (width, height),
+        _ => DEFAULT_SIZE,
+    }
+}
+
+fn youtube_id(src: &str) -> Option<&str> {
+    let rest = strip_scheme(src);
+    let id = ["www.youtube.com/embed/", "youtube.com/embed/", "www.youtube-nocookie.com/embed/"]
+        .iter()
+        .find_map(|prefix| rest.strip_prefix(prefix))?;
+    video_id(id)
+}
+
+fn vimeo_id(src: &str) -> Option<&str> {
+    video_id(strip_scheme(src).strip_prefix("player.vimeo.com/video/")?)
+}
+
+fn strip_scheme(src: &str) -> &str {
+    src.strip_prefix("https://")
+        .or_else(|| src.strip_prefix("http://"))
+        .or_else(|| src.strip_prefix("//"))
+        .unwrap_or(src)
+}
+
+/// Leading path segment, without query or fragment
+fn video_id(path: &str) -> Option<&str> {
+    let id = path.split(['?', '#', '/']).next()?;
+    (!id.is_empty()).then_some(id)
+}
+
+/// Attribute values are copied as written; only quotes need escaping
+fn attr(value: &str) -> String {
+    value.replace('"', "&quot;")
+}
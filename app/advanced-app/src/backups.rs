@@ -0,0 +1,595 @@
+//! Backups
+//!
+//! With `enabled = true` under `[app.backups]`, a backup is taken every
+//! `interval_secs` and written to storage under `<root>/<backup-id>/`:
+//!
+//! | File | Contents |
+//! |------|----------|
+//! | `tables/<table>.copy` | One `COPY ... TO STDOUT` dump per table (`logical`) |
+//! | `database.dump` | A `pg_dump -Fc` archive (`pg_dump`) |
+//! | `media.json` | Every media row with its storage path and whether the file was there |
+//! | `manifest.json` | Checksums and sizes of the files above, and the point in time |
+//!
+//! Both methods read from one snapshot, so the tables agree with each other
+//! and with the recorded point in time: the server time, the WAL position
+//! (for replaying archived WAL up to it) and the latest migration of the app
+//! and of each plugin. `GET /admin/backups` lists each backup with the steps
+//! for restoring it into a staging database.
+//!
+//! Instances coordinate through an advisory lock, so only one of them takes
+//! the scheduled backup. After each run, backups beyond the newest
+//! `keep_last` that are older than `max_age_days` have their files deleted.
+
+use crate::models::*;
+use crate::services::ServiceError;
+use crate::{BlogServices, APP_ID};
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use futures_util::TryStreamExt;
+use rustpress_apps::prelude::Storage;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::{PgConnection, PgPool};
+use std::collections::{BTreeMap, HashSet};
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Advisory lock held while a backup runs
+const LOCK_KEY: i64 = 0x6261_636b_7570;
+
+/// Migration ledgers; a restore recreates them by running the migrations
+const LEDGER_TABLES: &[&str] = &["_sqlx_migrations", "plugin_migrations"];
+
+/// `[app.backups]` settings
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct BackupConfig {
+    /// Take scheduled backups
+    pub enabled: bool,
+    /// Time between scheduled backups
+    pub interval_secs: u64,
+    /// Storage prefix for backups, one directory per backup
+    pub root: String,
+    pub method: BackupMethod,
+    /// `pg_dump` binary for the `pg_dump` method
+    pub pg_dump: String,
+    /// Tables left out of logical dumps (caches, queues, ...)
+    pub exclude_tables: Vec<String>,
+    /// Newest completed backups kept regardless of age
+    pub keep_last: i64,
+    /// Older backups beyond `keep_last` are deleted
+    pub max_age_days: i64,
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_secs: 86400,
+            root: "backups".to_string(),
+            method: BackupMethod::Logical,
+            pg_dump: "pg_dump".to_string(),
+            exclude_tables: Vec::new(),
+            keep_last: 7,
+            max_age_days: 30,
+        }
+    }
+}
+
+/// How the database is dumped
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BackupMethod {
+    /// `COPY` of each table in the public schema, no external tools
+    #[default]
+    Logical,
+    /// A `pg_dump` custom-format archive, schema included
+    PgDump,
+}
+
+impl BackupMethod {
+    fn as_str(self) -> &'static str {
+        match self {
+            BackupMethod::Logical => "logical",
+            BackupMethod::PgDump => "pg_dump",
+        }
+    }
+}
+
+/// Media row as recorded in `media.json`
+#[derive(Debug, Serialize)]
+struct MediaEntry {
+    id: Uuid,
+    site_id: Uuid,
+    storage_path: String,
+    size: i64,
+    mime_type: String,
+    /// Whether the storage object existed when the backup was taken
+    present: bool,
+}
+
+/// `manifest.json`
+#[derive(Debug, Serialize)]
+struct Manifest<'a> {
+    id: Uuid,
+    method: &'static str,
+    point_in_time: &'a PointInTime,
+    artifacts: &'a [BackupArtifact],
+}
+
+/// Backup service
+pub struct BackupService {
+    db: PgPool,
+    storage: Arc<dyn Storage>,
+    config: BackupConfig,
+}
+
+impl BackupService {
+    pub fn new(db: PgPool, storage: Arc<dyn Storage>, config: BackupConfig) -> Self {
+        Self { db, storage, config }
+    }
+
+    /// Backups, newest first, with restore steps for completed ones
+    pub async fn list(&self) -> Result<Vec<BackupEntry>, ServiceError> {
+        let backups: Vec<Backup> = sqlx::query_as("SELECT * FROM blog_backups ORDER BY started_at DESC")
+            .fetch_all(&self.db)
+            .await?;
+
+        Ok(backups
+            .into_iter()
+            .map(|backup| BackupEntry {
+                restore: (backup.status == BackupStatus::Completed).then(|| restore_instructions(&backup)),
+                backup,
+            })
+            .collect())
+    }
+
+    /// Take a backup now, unless one is already running
+    pub async fn backup(&self, started_by: Option<Uuid>) -> Result<Backup, ServiceError> {
+        let mut lock = self.db.acquire().await?;
+        if !try_lock(&mut lock).await? {
+            return Err(ServiceError::Validation("A backup is already running".into()));
+        }
+        let result = self.take(started_by).await;
+        unlock(&mut lock).await;
+        result
+    }
+
+    /// Take the scheduled backup if no instance took one within the
+    /// interval, then apply the retention policy
+    pub async fn scheduled(&self) -> Result<Option<Backup>, ServiceError> {
+        let mut lock = self.db.acquire().await?;
+        if !try_lock(&mut lock).await? {
+            return Ok(None);
+        }
+
+        let since = Utc::now() - ChronoDuration::seconds(self.config.interval_secs as i64);
+        let result: Result<Option<Backup>, ServiceError> = async {
+            let recent: Option<(Uuid,)> = sqlx::query_as(
+                "SELECT id FROM blog_backups WHERE status IN ('running', 'completed') AND started_at > $1 LIMIT 1",
+            )
+            .bind(since)
+            .fetch_optional(&self.db)
+            .await?;
+            if recent.is_some() {
+                return Ok(None);
+            }
+            let backup = self.take(None).await?;
+            self.prune().await?;
+            Ok(Some(backup))
+        }
+        .await;
+
+        unlock(&mut lock).await;
+        result
+    }
+
+    async fn take(&self, started_by: Option<Uuid>) -> Result<Backup, ServiceError> {
+        let id = Uuid::new_v4();
+        let prefix = format!("{}/{}", self.config.root.trim_end_matches('/'), id);
+        sqlx::query("INSERT INTO blog_backups (id, method, storage_prefix, started_by) VALUES ($1, $2, $3, $4)")
+            .bind(id)
+            .bind(self.config.method.as_str())
+            .bind(&prefix)
+            .bind(started_by)
+            .execute(&self.db)
+            .await?;
+
+        match self.write(id, &prefix).await {
+            Ok((point_in_time, artifacts)) => {
+                let size: i64 = artifacts.iter().map(|artifact| artifact.size).sum();
+                tracing::info!(backup = %id, bytes = size, "Backup completed");
+                let backup: Backup = sqlx::query_as(
+                    r#"UPDATE blog_backups
+                       SET status = 'completed', completed_at = NOW(), size_bytes = $2, artifacts = $3, point_in_time = $4
+                       WHERE id = $1
+                       RETURNING *"#,
+                )
+                .bind(id)
+                .bind(size)
+                .bind(sqlx::types::Json(&artifacts))
+                .bind(sqlx::types::Json(&point_in_time))
+                .fetch_one(&self.db)
+                .await?;
+                Ok(backup)
+            }
+            Err(e) => {
+                tracing::warn!(backup = %id, "Backup failed: {}", e);
+                sqlx::query("UPDATE blog_backups SET status = 'failed', completed_at = NOW(), error = $2 WHERE id = $1")
+                    .bind(id)
+                    .bind(e.to_string())
+                    .execute(&self.db)
+                    .await?;
+                self.delete_files(&prefix).await;
+                Err(e)
+            }
+        }
+    }
+
+    /// Dump the database and media manifest from one snapshot
+    async fn write(&self, id: Uuid, prefix: &str) -> Result<(PointInTime, Vec<BackupArtifact>), ServiceError> {
+        let mut tx = self.db.begin().await?;
+        sqlx::query("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ, READ ONLY")
+            .execute(&mut *tx)
+            .await?;
+
+        let mut point_in_time = snapshot_point(&mut *tx).await?;
+        let mut artifacts = Vec::new();
+
+        match self.config.method {
+            BackupMethod::Logical => {
+                point_in_time.tables = self.tables(&mut *tx).await?;
+                for table in &point_in_time.tables {
+                    let mut body = Vec::new();
+                    let mut stream = tx
+                        .copy_out_raw(&format!("COPY {} TO STDOUT", quote_ident(table)))
+                        .await?;
+                    while let Some(chunk) = stream.try_next().await? {
+                        body.extend_from_slice(&chunk);
+                    }
+                    drop(stream);
+                    artifacts.push(self.put(prefix, &format!("tables/{}.copy", table), &body).await?);
+                }
+            }
+            BackupMethod::PgDump => {
+                // pg_dump reads the snapshot this transaction exported
+                let (snapshot,): (String,) = sqlx::query_as("SELECT pg_export_snapshot()")
+                    .fetch_one(&mut *tx)
+                    .await?;
+                let body = self.pg_dump(&snapshot).await?;
+                artifacts.push(self.put(prefix, "database.dump", &body).await?);
+            }
+        }
+
+        let media = self.media_manifest(&mut *tx).await?;
+        tx.commit().await?;
+        artifacts.push(self.put(prefix, "media.json", &media).await?);
+
+        let manifest = serde_json::to_vec_pretty(&Manifest {
+            id,
+            method: self.config.method.as_str(),
+            point_in_time: &point_in_time,
+            artifacts: &artifacts,
+        })
+        .map_err(|e| ServiceError::Storage(e.to_string()))?;
+        self.storage
+            .put(&format!("{}/manifest.json", prefix), &manifest)
+            .await
+            .map_err(|e| ServiceError::Storage(e.to_string()))?;
+
+        Ok((point_in_time, artifacts))
+    }
+
+    /// Tables of the public schema to dump, without the migration ledgers
+    async fn tables(&self, conn: &mut PgConnection) -> Result<Vec<String>, ServiceError> {
+        let tables: Vec<(String,)> = sqlx::query_as(
+            r#"SELECT c.relname
+               FROM pg_class c
+               JOIN pg_namespace n ON n.oid = c.relnamespace
+               WHERE n.nspname = 'public' AND c.relkind = 'r'
+               ORDER BY c.relname"#,
+        )
+        .fetch_all(conn)
+        .await?;
+
+        Ok(tables
+            .into_iter()
+            .map(|(table,)| table)
+            .filter(|table| !LEDGER_TABLES.contains(&table.as_str()) && !self.config.exclude_tables.contains(table))
+            .collect())
+    }
+
+    async fn pg_dump(&self, snapshot: &str) -> Result<Vec<u8>, ServiceError> {
+        let url = rustpress_auth::secrets::shared()
+            .await
+            .map_err(|e| ServiceError::Storage(e.to_string()))?
+            .require("database.url")
+            .await
+            .map_err(|e| ServiceError::Storage(e.to_string()))?;
+
+        let output = tokio::process::Command::new(&self.config.pg_dump)
+            .args(["--format=custom", "--no-owner", "--no-privileges"])
+            .arg(format!("--snapshot={}", snapshot))
+            .args(self.config.exclude_tables.iter().map(|table| format!("--exclude-table-data={}", table)))
+            .arg(format!("--dbname={}", url))
+            .output()
+            .await
+            .map_err(|e| ServiceError::Storage(format!("{}: {}", self.config.pg_dump, e)))?;
+        if !output.status.success() {
+            return Err(ServiceError::Storage(format!(
+                "pg_dump: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(output.stdout)
+    }
+
+    /// Media rows at the snapshot, each with whether its file is in storage
+    async fn media_manifest(&self, conn: &mut PgConnection) -> Result<Vec<u8>, ServiceError> {
+        let mut files = HashSet::new();
+        for prefix in MEDIA_STORAGE_PREFIXES {
+            let listed = self
+                .storage
+                .list(prefix)
+                .await
+                .map_err(|e| ServiceError::Storage(e.to_string()))?;
+            files.extend(listed);
+        }
+
+        let rows: Vec<(Uuid, Uuid, String, MediaVisibility, i64, String)> =
+            sqlx::query_as("SELECT id, site_id, filename, visibility, size, mime_type FROM blog_media ORDER BY created_at")
+                .fetch_all(conn)
+                .await?;
+        let media: Vec<MediaEntry> = rows
+            .into_iter()
+            .map(|(id, site_id, filename, visibility, size, mime_type)| {
+                let storage_path = media_storage_path(visibility, &filename);
+                MediaEntry {
+                    present: files.contains(&storage_path),
+                    id,
+                    site_id,
+                    storage_path,
+                    size,
+                    mime_type,
+                }
+            })
+            .collect();
+
+        serde_json::to_vec_pretty(&media).map_err(|e| ServiceError::Storage(e.to_string()))
+    }
+
+    async fn put(&self, prefix: &str, path: &str, body: &[u8]) -> Result<BackupArtifact, ServiceError> {
+        self.storage
+            .put(&format!("{}/{}", prefix, path), body)
+            .await
+            .map_err(|e| ServiceError::Storage(e.to_string()))?;
+        Ok(BackupArtifact {
+            path: path.to_string(),
+            sha256: hex::encode(Sha256::digest(body)),
+            size: body.len() as i64,
+        })
+    }
+
+    /// Re-read a backup's files and compare them with their checksums
+    pub async fn verify(&self, id: Uuid) -> Result<BackupVerification, ServiceError> {
+        let backup: Backup = sqlx::query_as("SELECT * FROM blog_backups WHERE id = $1")
+            .bind(id)
+            .fetch_optional(&self.db)
+            .await?
+            .ok_or_else(|| ServiceError::NotFound("Backup not found".into()))?;
+        if backup.status != BackupStatus::Completed {
+            return Err(ServiceError::Validation("Only completed backups can be verified".into()));
+        }
+
+        let mut mismatched = Vec::new();
+        for artifact in &backup.artifacts {
+            let stored = self
+                .storage
+                .get(&format!("{}/{}", backup.storage_prefix, artifact.path))
+                .await;
+            let intact = matches!(
+                stored,
+                Ok(body) if body.len() as i64 == artifact.size && hex::encode(Sha256::digest(&body)) == artifact.sha256
+            );
+            if !intact {
+                mismatched.push(artifact.path.clone());
+            }
+        }
+
+        let verified = mismatched.is_empty();
+        if !verified {
+            tracing::warn!(backup = %id, files = ?mismatched, "Backup failed verification");
+        }
+        let (verified_at,): (DateTime<Utc>,) =
+            sqlx::query_as("UPDATE blog_backups SET verified_at = NOW(), verified = $2 WHERE id = $1 RETURNING verified_at")
+                .bind(id)
+                .bind(verified)
+                .fetch_one(&self.db)
+                .await?;
+
+        Ok(BackupVerification {
+            id,
+            verified,
+            mismatched,
+            verified_at,
+        })
+    }
+
+    /// Delete the files of backups beyond the newest `keep_last` completed
+    /// ones that are older than `max_age_days`; failed backups go by age alone
+    pub async fn prune(&self) -> Result<usize, ServiceError> {
+        let cutoff = Utc::now() - ChronoDuration::days(self.config.max_age_days);
+        let expired: Vec<(Uuid, String)> = sqlx::query_as(
+            r#"SELECT id, storage_prefix FROM (
+                   SELECT id, storage_prefix, status, started_at,
+                          ROW_NUMBER() OVER (PARTITION BY status ORDER BY started_at DESC) AS newest
+                   FROM blog_backups
+                   WHERE status IN ('completed', 'failed')
+               ) b
+               WHERE started_at < $1 AND (status = 'failed' OR newest > $2)"#,
+        )
+        .bind(cutoff)
+        .bind(self.config.keep_last)
+        .fetch_all(&self.db)
+        .await?;
+
+        for (id, prefix) in &expired {
+            self.delete_files(prefix).await;
+            sqlx::query("UPDATE blog_backups SET status = 'pruned', pruned_at = NOW() WHERE id = $1")
+                .bind(id)
+                .execute(&self.db)
+                .await?;
+        }
+        if !expired.is_empty() {
+            tracing::info!(pruned = expired.len(), "Pruned old backups");
+        }
+        Ok(expired.len())
+    }
+
+    async fn delete_files(&self, prefix: &str) {
+        let files = match self.storage.list(&format!("{}/", prefix)).await {
+            Ok(files) => files,
+            Err(e) => {
+                tracing::warn!(prefix, "Could not list backup files: {}", e);
+                return;
+            }
+        };
+        for file in files {
+            if let Err(e) = self.storage.delete(&file).await {
+                tracing::warn!(file, "Could not delete backup file: {}", e);
+            }
+        }
+    }
+}
+
+/// Server time, WAL position and migration versions inside the snapshot
+async fn snapshot_point(conn: &mut PgConnection) -> Result<PointInTime, ServiceError> {
+    let (taken_at, wal_lsn): (DateTime<Utc>, Option<String>) = sqlx::query_as(
+        r#"SELECT NOW(),
+                  CASE WHEN pg_is_in_recovery() THEN pg_last_wal_replay_lsn()::text
+                       ELSE pg_current_wal_lsn()::text END"#,
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+
+    let mut migrations = BTreeMap::new();
+    let (app,): (Option<i64>,) = sqlx::query_as("SELECT MAX(version) FROM _sqlx_migrations WHERE success")
+        .fetch_one(&mut *conn)
+        .await?;
+    if let Some(version) = app {
+        migrations.insert(APP_ID.to_string(), version);
+    }
+
+    // The plugin ledger exists once the auth plugin has migrated
+    let (has_ledger,): (bool,) = sqlx::query_as("SELECT to_regclass('plugin_migrations') IS NOT NULL")
+        .fetch_one(&mut *conn)
+        .await?;
+    if has_ledger {
+        let plugins: Vec<(String, i64)> =
+            sqlx::query_as("SELECT plugin_id, MAX(version)::BIGINT FROM plugin_migrations GROUP BY plugin_id")
+                .fetch_all(&mut *conn)
+                .await?;
+        migrations.extend(plugins);
+    }
+
+    Ok(PointInTime {
+        taken_at: Some(taken_at),
+        wal_lsn,
+        migrations,
+        tables: Vec::new(),
+    })
+}
+
+/// Steps for loading a completed backup into a staging database
+pub fn restore_instructions(backup: &Backup) -> RestoreInstructions {
+    let prefix = &backup.storage_prefix;
+    let mut steps = vec![format!(
+        "Download {}/ from storage and check each file against manifest.json",
+        prefix
+    )];
+
+    if backup.method == BackupMethod::PgDump.as_str() {
+        // The archive carries the schema and the migration ledgers
+        steps.push("createdb staging (an empty database)".to_string());
+        steps.push(format!(
+            "pg_restore --no-owner --no-privileges --dbname \"$STAGING_DATABASE_URL\" {}/database.dump",
+            prefix
+        ));
+    } else {
+        let versions: Vec<String> = backup
+            .point_in_time
+            .migrations
+            .iter()
+            .map(|(id, version)| format!("{} {}", id, version))
+            .collect();
+        steps.push(format!(
+            "Create the staging database and run the migrations up to {}",
+            versions.join(", ")
+        ));
+
+        // Foreign keys and triggers are skipped while loading, so the tables
+        // can go in any order
+        let mut script = vec!["SET session_replication_role = replica;".to_string()];
+        for table in &backup.point_in_time.tables {
+            script.push(format!("TRUNCATE {} CASCADE;", quote_ident(table)));
+            script.push(format!("\\copy {} FROM '{}/tables/{}.copy'", quote_ident(table), prefix, table));
+        }
+        steps.push(format!(
+            "psql \"$STAGING_DATABASE_URL\" <<'SQL'\n{}\nSQL",
+            script.join("\n")
+        ));
+    }
+
+    steps.push(format!(
+        "Copy the files listed in {}/media.json (storage_path) to the staging storage; entries with present = false were already missing",
+        prefix
+    ));
+    if let Some(lsn) = &backup.point_in_time.wal_lsn {
+        steps.push(format!(
+            "For a later point in time, restore a base backup instead and replay archived WAL from {} (recovery_target_lsn)",
+            lsn
+        ));
+    }
+
+    RestoreInstructions {
+        migrations: backup.point_in_time.migrations.clone(),
+        steps,
+        media_manifest: format!("{}/media.json", prefix),
+    }
+}
+
+fn quote_ident(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+async fn try_lock(conn: &mut PgConnection) -> Result<bool, ServiceError> {
+    let (locked,): (bool,) = sqlx::query_as("SELECT pg_try_advisory_lock($1)")
+        .bind(LOCK_KEY)
+        .fetch_one(conn)
+        .await?;
+    Ok(locked)
+}
+
+async fn unlock(conn: &mut PgConnection) {
+    if let Err(e) = sqlx::query("SELECT pg_advisory_unlock($1)").bind(LOCK_KEY).execute(conn).await {
+        tracing::warn!("Could not release the backup lock: {}", e);
+    }
+}
+
+/// Take scheduled backups every `interval_secs` until the services are
+/// dropped
+pub async fn run(services: Weak<BlogServices>, interval_secs: u64) {
+    // Checked more often than the interval, so a restarted instance doesn't
+    // wait a full interval and several instances don't drift apart
+    let mut interval = tokio::time::interval(Duration::from_secs((interval_secs / 4).clamp(60, 3600)));
+    loop {
+        interval.tick().await;
+        let Some(services) = services.upgrade() else {
+            return;
+        };
+
+        if let Err(e) = services.backups.scheduled().await {
+            tracing::warn!("Scheduled backup failed: {}", e);
+        }
+    }
+}
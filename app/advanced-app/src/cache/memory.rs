@@ -0,0 +1,103 @@
+//! In-Process Cache (moka)
+
+use super::{pattern_tag, Cache, CacheConfig, SingleFlight};
+use axum::async_trait;
+use moka::future::Cache as MokaCache;
+use moka::Expiry;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[derive(Clone)]
+struct Entry {
+    value: Arc<Vec<u8>>,
+    ttl: Option<Duration>,
+}
+
+/// Expire each entry after its own TTL
+struct EntryExpiry;
+
+impl Expiry<String, Entry> for EntryExpiry {
+    fn expire_after_create(&self, _key: &String, entry: &Entry, _created_at: Instant) -> Option<Duration> {
+        entry.ttl
+    }
+
+    fn expire_after_update(
+        &self,
+        _key: &String,
+        entry: &Entry,
+        _updated_at: Instant,
+        _remaining: Option<Duration>,
+    ) -> Option<Duration> {
+        entry.ttl
+    }
+}
+
+/// Bounded per-process cache
+///
+/// Prefix invalidation uses moka's invalidation predicates, which are applied
+/// lazily; matching entries are no longer returned once the predicate is
+/// registered.
+pub struct MemoryCache {
+    entries: MokaCache<String, Entry>,
+    flights: SingleFlight,
+    ttl_jitter: f64,
+}
+
+impl MemoryCache {
+    pub fn new(config: &CacheConfig) -> Self {
+        let entries = MokaCache::builder()
+            .max_capacity(config.max_entries)
+            .expire_after(EntryExpiry)
+            .support_invalidation_closures()
+            .build();
+
+        Self {
+            entries,
+            flights: SingleFlight::default(),
+            ttl_jitter: config.ttl_jitter,
+        }
+    }
+
+    /// Drop every entry
+    pub fn clear(&self) {
+        self.entries.invalidate_all();
+    }
+}
+
+#[async_trait]
+impl Cache for MemoryCache {
+    async fn get_bytes(&self, key: &str) -> Option<Vec<u8>> {
+        self.entries.get(key).await.map(|entry| entry.value.as_ref().clone())
+    }
+
+    async fn set_bytes(&self, key: &str, value: Vec<u8>, ttl: Option<Duration>) {
+        let entry = Entry {
+            value: Arc::new(value),
+            ttl,
+        };
+        self.entries.insert(key.to_string(), entry).await;
+    }
+
+    async fn delete(&self, key: &str) {
+        self.entries.invalidate(key).await;
+    }
+
+    async fn delete_pattern(&self, pattern: &str) {
+        let Some(prefix) = pattern_tag(pattern).map(str::to_string) else {
+            tracing::warn!(pattern, "Unsupported cache pattern; expected `<prefix>:*`");
+            return;
+        };
+
+        if let Err(e) = self.entries.invalidate_entries_if(move |key, _| key.starts_with(&prefix)) {
+            tracing::warn!(pattern, "Cache invalidation failed: {}", e);
+        }
+    }
+
+    fn flights(&self) -> &SingleFlight {
+        &self.flights
+    }
+
+    fn ttl_jitter(&self) -> f64 {
+        self.ttl_jitter
+    }
+}
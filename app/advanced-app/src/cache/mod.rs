@@ -0,0 +1,257 @@
+//! Caching
+//!
+//! `Cache` stores serialized values behind an object-safe byte API; the typed
+//! `get`/`set`/`get_or_load` helpers live on `dyn Cache` so services keep
+//! holding an `Arc<dyn Cache>`. Three drivers ship with the app: [`MemoryCache`]
+//! (moka, per process), [`RedisCache`] (shared between instances) and
+//! [`TieredCache`] (a short-lived memory tier in front of Redis, kept coherent
+//! across instances over Redis pub/sub).
+//!
+//! `delete_pattern` takes `<prefix>:*` patterns. Rather than scanning the
+//! keyspace, the Redis driver tags every key with each of its `:`-terminated
+//! prefixes when written, and invalidating a prefix deletes the members of
+//! its tag.
+
+mod memory;
+mod redis;
+mod tiered;
+
+pub use self::memory::MemoryCache;
+pub use self::redis::RedisCache;
+pub use self::tiered::TieredCache;
+
+use axum::async_trait;
+use rand::Rng;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
+use rustpress_auth::metrics::{Counter, MetricsError};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+use tokio::sync::OwnedMutexGuard;
+
+/// Cache errors (only raised while connecting; reads and writes degrade to misses)
+#[derive(Debug, thiserror::Error)]
+pub enum CacheError {
+    #[error("Redis error: {0}")]
+    Redis(#[from] ::redis::RedisError),
+}
+
+/// Cache backend
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheDriver {
+    Memory,
+    Redis,
+    /// Memory in front of Redis
+    Tiered,
+}
+
+/// `[app.cache]` settings
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct CacheConfig {
+    pub driver: CacheDriver,
+    pub redis_url: String,
+    /// Entry limit of the memory driver (and the tiered driver's L1)
+    pub max_entries: u64,
+    /// TTLs are scaled by a random factor in `1 ± ttl_jitter` so entries
+    /// written together don't all expire together
+    pub ttl_jitter: f64,
+    /// Longest an entry stays in the tiered driver's L1
+    pub l1_ttl_secs: u64,
+    /// Pub/sub channel the tiered driver broadcasts invalidations on
+    pub invalidation_channel: String,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            driver: CacheDriver::Memory,
+            redis_url: "redis://127.0.0.1/".to_string(),
+            max_entries: 10_000,
+            ttl_jitter: 0.1,
+            l1_ttl_secs: 30,
+            invalidation_channel: "rustpress:cache:invalidate".to_string(),
+        }
+    }
+}
+
+/// Build the configured cache driver
+pub async fn connect(config: &CacheConfig) -> Result<Arc<dyn Cache>, CacheError> {
+    let cache: Arc<dyn Cache> = match config.driver {
+        CacheDriver::Memory => Arc::new(MemoryCache::new(config)),
+        CacheDriver::Redis => Arc::new(RedisCache::connect(config).await?),
+        CacheDriver::Tiered => Arc::new(TieredCache::connect(config).await?),
+    };
+    Ok(cache)
+}
+
+/// Byte-level cache operations implemented by each driver
+#[async_trait]
+pub trait Cache: Send + Sync {
+    async fn get_bytes(&self, key: &str) -> Option<Vec<u8>>;
+
+    /// Store `value`; `ttl` has already been jittered
+    async fn set_bytes(&self, key: &str, value: Vec<u8>, ttl: Option<Duration>);
+
+    async fn delete(&self, key: &str);
+
+    /// Delete every key under a `<prefix>:*` pattern
+    async fn delete_pattern(&self, pattern: &str);
+
+    /// In-flight loads, for request coalescing
+    fn flights(&self) -> &SingleFlight;
+
+    fn ttl_jitter(&self) -> f64;
+}
+
+impl dyn Cache {
+    /// Typed read; undecodable entries count as misses
+    pub async fn get<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        let value = self.read(key).await;
+        record_lookup(value.is_some());
+        value
+    }
+
+    /// `get` without counting the lookup in the hit ratio
+    async fn read<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        let bytes = self.get_bytes(key).await?;
+        match serde_json::from_slice(&bytes) {
+            Ok(value) => Some(value),
+            Err(e) => {
+                tracing::warn!(key, "Discarding undecodable cache entry: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Typed write with `ttl` in seconds
+    pub async fn set<T: Serialize + ?Sized>(&self, key: &str, value: &T, ttl: Option<u64>) {
+        match serde_json::to_vec(value) {
+            Ok(bytes) => {
+                let ttl = ttl.map(|secs| jitter(Duration::from_secs(secs), self.ttl_jitter()));
+                self.set_bytes(key, bytes, ttl).await;
+            }
+            Err(e) => tracing::warn!(key, "Failed to serialize cache entry: {}", e),
+        }
+    }
+
+    /// Read `key`, or run `load` and cache its result
+    ///
+    /// Concurrent misses for the same key in this process wait for a single
+    /// `load` instead of all hitting the database.
+    pub async fn get_or_load<T, E, F, Fut>(&self, key: &str, ttl: Option<u64>, load: F) -> Result<T, E>
+    where
+        T: Serialize + DeserializeOwned,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        if let Some(value) = self.get(key).await {
+            return Ok(value);
+        }
+
+        let _flight = self.flights().join(key).await;
+        // The leader may have filled the entry while we waited
+        if let Some(value) = self.read(key).await {
+            return Ok(value);
+        }
+
+        let value = load().await?;
+        self.set(key, &value, ttl).await;
+        Ok(value)
+    }
+}
+
+/// Hit and miss counters of the data cache, in the shared metrics registry
+/// (`None` if registration failed)
+static LOOKUPS: OnceLock<Option<(Counter, Counter)>> = OnceLock::new();
+
+fn record_lookup(hit: bool) {
+    let counters = LOOKUPS.get_or_init(|| {
+        let metrics = rustpress_auth::metrics::shared().plugin(crate::APP_ID);
+        let register = || -> Result<_, MetricsError> {
+            Ok((
+                metrics.counter("blog_cache_hits_total", "Data cache lookups served from the cache")?,
+                metrics.counter("blog_cache_misses_total", "Data cache lookups that missed")?,
+            ))
+        };
+        register()
+            .map_err(|e| tracing::warn!("Cache metrics not registered: {}", e))
+            .ok()
+    });
+
+    if let Some((hits, misses)) = counters {
+        if hit {
+            hits.inc();
+        } else {
+            misses.inc();
+        }
+    }
+}
+
+/// Scale `ttl` by a random factor in `1 ± fraction`
+fn jitter(ttl: Duration, fraction: f64) -> Duration {
+    let fraction = fraction.clamp(0.0, 0.9);
+    if fraction == 0.0 {
+        return ttl;
+    }
+    ttl.mul_f64(rand::thread_rng().gen_range(1.0 - fraction..=1.0 + fraction))
+}
+
+/// Tags of `key`: each prefix ending in `:`, up to the first whitespace
+/// (free-form suffixes such as a formatted query aren't worth tagging)
+fn tags(key: &str) -> impl Iterator<Item = &str> {
+    key.match_indices(':')
+        .map(move |(i, _)| &key[..=i])
+        .take_while(|prefix| !prefix.contains(char::is_whitespace))
+}
+
+/// Tag addressed by a `<prefix>:*` pattern
+fn pattern_tag(pattern: &str) -> Option<&str> {
+    pattern
+        .strip_suffix('*')
+        .filter(|prefix| prefix.ends_with(':') && !prefix.contains('*'))
+}
+
+// ============================================
+// Single-Flight
+// ============================================
+
+/// Per-key locks held while a value is being loaded
+#[derive(Default)]
+pub struct SingleFlight {
+    flights: Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>,
+}
+
+impl SingleFlight {
+    /// Wait for any in-flight load of `key`, then hold the key until the
+    /// returned guard drops
+    pub async fn join(&self, key: &str) -> FlightGuard<'_> {
+        let lock = self.flights.lock().unwrap().entry(key.to_string()).or_default().clone();
+        let guard = lock.clone().lock_owned().await;
+        FlightGuard {
+            flights: self,
+            key: key.to_string(),
+            lock,
+            _guard: guard,
+        }
+    }
+}
+
+pub struct FlightGuard<'a> {
+    flights: &'a SingleFlight,
+    key: String,
+    lock: Arc<tokio::sync::Mutex<()>>,
+    _guard: OwnedMutexGuard<()>,
+}
+
+impl Drop for FlightGuard<'_> {
+    fn drop(&mut self) {
+        let mut flights = self.flights.flights.lock().unwrap();
+        // The map, `self.lock` and the guard: nobody else is waiting
+        if Arc::strong_count(&self.lock) == 3 {
+            flights.remove(&self.key);
+        }
+    }
+}
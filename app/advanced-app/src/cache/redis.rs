@@ -0,0 +1,145 @@
+//! Shared Cache (Redis)
+
+use super::{pattern_tag, tags, Cache, CacheConfig, CacheError, SingleFlight};
+use axum::async_trait;
+use redis::aio::ConnectionManager;
+use redis::{AsyncCommands, Script};
+use std::time::Duration;
+
+/// Write a value and add it to its tag sets
+///
+/// KEYS[1] is the entry, KEYS[2..] its tag sets; ARGV is the value and the
+/// TTL in seconds (0 for none). A tag set lives as long as its longest-lived
+/// member, so invalidation can always reach every live key.
+const SET_SCRIPT: &str = r#"
+local ttl = tonumber(ARGV[2])
+if ttl > 0 then
+    redis.call('SET', KEYS[1], ARGV[1], 'EX', ttl)
+else
+    redis.call('SET', KEYS[1], ARGV[1])
+end
+for i = 2, #KEYS do
+    redis.call('SADD', KEYS[i], KEYS[1])
+    if ttl == 0 then
+        redis.call('PERSIST', KEYS[i])
+    else
+        local current = redis.call('TTL', KEYS[i])
+        if redis.call('SCARD', KEYS[i]) == 1 or (current >= 0 and current < ttl) then
+            redis.call('EXPIRE', KEYS[i], ttl)
+        end
+    end
+end
+"#;
+
+/// Delete every member of a tag set, then the set
+const INVALIDATE_SCRIPT: &str = r#"
+local keys = redis.call('SMEMBERS', KEYS[1])
+for i = 1, #keys, 500 do
+    redis.call('DEL', unpack(keys, i, math.min(i + 499, #keys)))
+end
+redis.call('DEL', KEYS[1])
+return #keys
+"#;
+
+/// Redis cache shared by all app instances
+///
+/// Scripts touch an entry and its tag sets together, so keys must live on one
+/// node (standalone or Sentinel, not Cluster).
+pub struct RedisCache {
+    conn: ConnectionManager,
+    set_script: Script,
+    invalidate_script: Script,
+    flights: SingleFlight,
+    ttl_jitter: f64,
+}
+
+impl RedisCache {
+    pub async fn connect(config: &CacheConfig) -> Result<Self, CacheError> {
+        let client = redis::Client::open(config.redis_url.as_str())?;
+        Self::with_client(client, config).await
+    }
+
+    pub async fn with_client(client: redis::Client, config: &CacheConfig) -> Result<Self, CacheError> {
+        let conn = ConnectionManager::new(client).await?;
+
+        Ok(Self {
+            conn,
+            set_script: Script::new(SET_SCRIPT),
+            invalidate_script: Script::new(INVALIDATE_SCRIPT),
+            flights: SingleFlight::default(),
+            ttl_jitter: config.ttl_jitter,
+        })
+    }
+
+    /// Shared connection, for commands outside the `Cache` API
+    pub(super) fn connection(&self) -> ConnectionManager {
+        self.conn.clone()
+    }
+}
+
+/// Redis key of a tag set
+fn tag_key(tag: &str) -> String {
+    format!("cache-tag:{}", tag)
+}
+
+#[async_trait]
+impl Cache for RedisCache {
+    async fn get_bytes(&self, key: &str) -> Option<Vec<u8>> {
+        let mut conn = self.conn.clone();
+        match conn.get::<_, Option<Vec<u8>>>(key).await {
+            Ok(value) => value,
+            Err(e) => {
+                tracing::warn!(key, "Cache read failed: {}", e);
+                None
+            }
+        }
+    }
+
+    async fn set_bytes(&self, key: &str, value: Vec<u8>, ttl: Option<Duration>) {
+        let mut conn = self.conn.clone();
+        let ttl = ttl.map(|ttl| ttl.as_secs().max(1)).unwrap_or(0);
+
+        let mut invocation = self.set_script.key(key);
+        for tag in tags(key) {
+            invocation.key(tag_key(tag));
+        }
+        invocation.arg(value).arg(ttl);
+
+        if let Err(e) = invocation.invoke_async::<_, ()>(&mut conn).await {
+            tracing::warn!(key, "Cache write failed: {}", e);
+        }
+    }
+
+    async fn delete(&self, key: &str) {
+        let mut conn = self.conn.clone();
+        if let Err(e) = conn.del::<_, ()>(key).await {
+            tracing::warn!(key, "Cache delete failed: {}", e);
+        }
+    }
+
+    async fn delete_pattern(&self, pattern: &str) {
+        let Some(tag) = pattern_tag(pattern) else {
+            tracing::warn!(pattern, "Unsupported cache pattern; expected `<prefix>:*`");
+            return;
+        };
+
+        let mut conn = self.conn.clone();
+        match self
+            .invalidate_script
+            .key(tag_key(tag))
+            .invoke_async::<_, i64>(&mut conn)
+            .await
+        {
+            Ok(deleted) => tracing::debug!(pattern, deleted, "Cache prefix invalidated"),
+            Err(e) => tracing::warn!(pattern, "Cache invalidation failed: {}", e),
+        }
+    }
+
+    fn flights(&self) -> &SingleFlight {
+        &self.flights
+    }
+
+    fn ttl_jitter(&self) -> f64 {
+        self.ttl_jitter
+    }
+}
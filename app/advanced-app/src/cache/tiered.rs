@@ -0,0 +1,188 @@
+//! Two-Tier Cache (in-process L1 over Redis L2)
+
+use super::{Cache, CacheConfig, CacheError, MemoryCache, RedisCache, SingleFlight};
+use axum::async_trait;
+use futures_util::StreamExt;
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Delay before resubscribing after the pub/sub connection drops
+const RESUBSCRIBE_DELAY: Duration = Duration::from_secs(1);
+
+/// Invalidation broadcast to every instance
+#[derive(Debug, Serialize, Deserialize)]
+struct Invalidation {
+    /// Instance that published it; it has already applied it locally
+    origin: Uuid,
+    #[serde(flatten)]
+    target: Target,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "op", content = "target", rename_all = "lowercase")]
+enum Target {
+    Key(String),
+    Pattern(String),
+}
+
+/// In-process cache in front of Redis
+///
+/// Reads hit the local L1 first and fall back to Redis; L1 entries live at
+/// most `l1_ttl_secs`. Deletes are applied to both tiers and published on
+/// `invalidation_channel`, and every instance drops the affected L1 entries
+/// when the message arrives, so a publish on one replica isn't masked by
+/// another replica's L1. If the subscription drops, L1 is cleared when it is
+/// re-established since messages may have been missed; `l1_ttl_secs` bounds
+/// staleness in the meantime.
+pub struct TieredCache {
+    l1: Arc<MemoryCache>,
+    l2: RedisCache,
+    l1_ttl: Duration,
+    channel: String,
+    instance: Uuid,
+    flights: SingleFlight,
+    ttl_jitter: f64,
+}
+
+impl TieredCache {
+    pub async fn connect(config: &CacheConfig) -> Result<Self, CacheError> {
+        let client = redis::Client::open(config.redis_url.as_str())?;
+        let l2 = RedisCache::with_client(client.clone(), config).await?;
+        let l1 = Arc::new(MemoryCache::new(config));
+        let instance = Uuid::new_v4();
+
+        tokio::spawn(subscribe(
+            client,
+            config.invalidation_channel.clone(),
+            instance,
+            Arc::downgrade(&l1),
+        ));
+
+        Ok(Self {
+            l1,
+            l2,
+            l1_ttl: Duration::from_secs(config.l1_ttl_secs),
+            channel: config.invalidation_channel.clone(),
+            instance,
+            flights: SingleFlight::default(),
+            ttl_jitter: config.ttl_jitter,
+        })
+    }
+
+    async fn publish(&self, target: Target) {
+        let message = Invalidation {
+            origin: self.instance,
+            target,
+        };
+        let payload = match serde_json::to_string(&message) {
+            Ok(payload) => payload,
+            Err(e) => {
+                tracing::warn!("Failed to encode cache invalidation: {}", e);
+                return;
+            }
+        };
+
+        let mut conn = self.l2.connection();
+        if let Err(e) = conn.publish::<_, _, ()>(&self.channel, payload).await {
+            tracing::warn!(channel = %self.channel, "Failed to publish cache invalidation: {}", e);
+        }
+    }
+}
+
+#[async_trait]
+impl Cache for TieredCache {
+    async fn get_bytes(&self, key: &str) -> Option<Vec<u8>> {
+        if let Some(value) = self.l1.get_bytes(key).await {
+            return Some(value);
+        }
+
+        let value = self.l2.get_bytes(key).await?;
+        self.l1.set_bytes(key, value.clone(), Some(self.l1_ttl)).await;
+        Some(value)
+    }
+
+    async fn set_bytes(&self, key: &str, value: Vec<u8>, ttl: Option<Duration>) {
+        let l1_ttl = ttl.map_or(self.l1_ttl, |ttl| ttl.min(self.l1_ttl));
+        self.l2.set_bytes(key, value.clone(), ttl).await;
+        self.l1.set_bytes(key, value, Some(l1_ttl)).await;
+    }
+
+    async fn delete(&self, key: &str) {
+        self.l2.delete(key).await;
+        self.l1.delete(key).await;
+        self.publish(Target::Key(key.to_string())).await;
+    }
+
+    async fn delete_pattern(&self, pattern: &str) {
+        self.l2.delete_pattern(pattern).await;
+        self.l1.delete_pattern(pattern).await;
+        self.publish(Target::Pattern(pattern.to_string())).await;
+    }
+
+    fn flights(&self) -> &SingleFlight {
+        &self.flights
+    }
+
+    fn ttl_jitter(&self) -> f64 {
+        self.ttl_jitter
+    }
+}
+
+/// Apply invalidations from other instances to `l1` until it is dropped
+async fn subscribe(client: redis::Client, channel: String, instance: Uuid, l1: Weak<MemoryCache>) {
+    let mut resubscribing = false;
+    loop {
+        let mut pubsub = match client.get_async_pubsub().await {
+            Ok(pubsub) => pubsub,
+            Err(e) => {
+                tracing::warn!(channel = %channel, "Cache invalidation subscription failed: {}", e);
+                tokio::time::sleep(RESUBSCRIBE_DELAY).await;
+                continue;
+            }
+        };
+        if let Err(e) = pubsub.subscribe(&channel).await {
+            tracing::warn!(channel = %channel, "Cache invalidation subscription failed: {}", e);
+            tokio::time::sleep(RESUBSCRIBE_DELAY).await;
+            continue;
+        }
+
+        if resubscribing {
+            match l1.upgrade() {
+                Some(l1) => l1.clear(),
+                None => return,
+            }
+            tracing::info!(channel = %channel, "Cache invalidation subscription restored; L1 cleared");
+        }
+        resubscribing = true;
+
+        let mut messages = std::pin::pin!(pubsub.on_message());
+        while let Some(message) = messages.next().await {
+            let Some(l1) = l1.upgrade() else {
+                return;
+            };
+
+            let invalidation = message
+                .get_payload::<String>()
+                .ok()
+                .and_then(|payload| serde_json::from_str::<Invalidation>(&payload).ok());
+            let Some(invalidation) = invalidation else {
+                tracing::warn!(channel = %channel, "Ignoring malformed cache invalidation");
+                continue;
+            };
+            if invalidation.origin == instance {
+                continue;
+            }
+
+            match invalidation.target {
+                Target::Key(key) => l1.delete(&key).await,
+                Target::Pattern(pattern) => l1.delete_pattern(&pattern).await,
+            }
+        }
+
+        tracing::warn!(channel = %channel, "Cache invalidation subscription lost; reconnecting");
+        tokio::time::sleep(RESUBSCRIBE_DELAY).await;
+    }
+}
@@ -0,0 +1,734 @@
+//! Files-Based Content Sync
+//!
+//! Optionally mirrors posts to Markdown files with TOML front matter, one
+//! directory per site (`<dir>/<site-slug>/<post-slug>.md`), and keeps both
+//! sides in step:
+//!
+//! - Saving a post writes its file (`SyncListener` queues the change,
+//!   [`run_export`] applies it), removing the old file on a rename or delete
+//! - Edited, added and removed files are imported by [`run_watcher`], which
+//!   polls the directory, or right away by `POST /sync/webhook`
+//!
+//! With `git = true` the directory is a Git working copy: exports are
+//! committed (and pushed with `push = true`), and the webhook pulls before
+//! importing. Pointing a repository's push webhook at it lets content
+//! changes be reviewed as pull requests and go live when merged.
+//!
+//! ```text
+//! +++
+//! id = "5f0c..."
+//! title = "Hello, world"
+//! status = "published"
+//! author = "8e1a..."
+//! categories = ["news"]
+//! tags = ["rust"]
+//! +++
+//!
+//! Markdown content
+//! ```
+//!
+//! Files without an `id` create posts (by `author`, else `default_author`)
+//! and are rewritten with the new post's id. Files are written in a
+//! canonical form, so a hand-edited file may be rewritten once after import.
+//! A file's last synced hash is kept in `content_sync_files`; files that
+//! still match it are skipped. Sync runs on a single instance.
+
+use crate::activity::{ContentEvent, ContentListener};
+use crate::models::*;
+use crate::services::{CategoryService, PostService, ServiceError, TagService};
+use crate::sites::Site;
+use crate::BlogServices;
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::{FromRow, PgPool};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Weak;
+use std::time::Duration;
+use tokio::sync::{mpsc, Mutex};
+use uuid::Uuid;
+use validator::Validate;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Delimiter around the front matter
+const FENCE: &str = "+++";
+
+/// `[app.sync]` settings
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SyncConfig {
+    /// Mirror posts to files
+    pub enabled: bool,
+    /// Directory (or Git working copy) holding one directory per site
+    pub dir: PathBuf,
+    /// How often to look for changed files, in seconds; 0 leaves imports to
+    /// the webhook
+    pub poll_secs: u64,
+    /// Author of new files that don't name one
+    pub default_author: Option<Uuid>,
+    /// Commit exports and pull before webhook imports
+    pub git: bool,
+    /// Push commits to `remote`
+    pub push: bool,
+    pub remote: String,
+    pub branch: String,
+    /// Identity of export commits
+    pub commit_name: String,
+    pub commit_email: String,
+    /// Secret of `POST /sync/webhook` (`X-Hub-Signature-256`); the webhook
+    /// is refused without one
+    pub webhook_secret: Option<String>,
+}
+
+impl Default for SyncConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            dir: PathBuf::from("content"),
+            poll_secs: 10,
+            default_author: None,
+            git: false,
+            push: false,
+            remote: "origin".to_string(),
+            branch: "main".to_string(),
+            commit_name: "RustPress".to_string(),
+            commit_email: "rustpress@localhost".to_string(),
+            webhook_secret: None,
+        }
+    }
+}
+
+/// Post fields stored in a file's front matter
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FrontMatter {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<Uuid>,
+    pub title: String,
+    #[serde(default = "draft")]
+    pub status: PostStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub author: Option<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub excerpt: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub featured_image: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub meta_title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub meta_description: Option<String>,
+    /// Category slugs
+    #[serde(default)]
+    pub categories: Vec<String>,
+    /// Tag slugs
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Written for reference; ignored on import
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub published_at: Option<DateTime<Utc>>,
+}
+
+fn draft() -> PostStatus {
+    PostStatus::Draft
+}
+
+impl FrontMatter {
+    fn from_post(post: &PostWithRelations) -> Self {
+        Self {
+            id: Some(post.post.id),
+            title: post.post.title.clone(),
+            status: post.post.status.clone(),
+            author: Some(post.post.author_id),
+            excerpt: post.post.excerpt.clone(),
+            featured_image: post.post.featured_image.clone(),
+            meta_title: post.post.meta_title.clone(),
+            meta_description: post.post.meta_description.clone(),
+            categories: post.categories.iter().map(|c| c.slug.clone()).collect(),
+            tags: post.tags.iter().map(|t| t.slug.clone()).collect(),
+            published_at: post.post.published_at,
+        }
+    }
+}
+
+/// A post as a Markdown file with front matter
+pub fn render(post: &PostWithRelations) -> Result<String, ServiceError> {
+    let front = toml::to_string(&FrontMatter::from_post(post)).map_err(|e| ServiceError::Storage(e.to_string()))?;
+    Ok(format!("{FENCE}\n{}{FENCE}\n\n{}\n", front, post.post.content.trim_end()))
+}
+
+/// Front matter and content of a file
+pub fn parse(text: &str) -> Result<(FrontMatter, String), ServiceError> {
+    let invalid = |msg: &str| ServiceError::Validation(msg.to_string());
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text).replace("\r\n", "\n");
+    let rest = text
+        .strip_prefix(FENCE)
+        .and_then(|rest| rest.strip_prefix('\n'))
+        .ok_or_else(|| invalid("File must start with +++ front matter"))?;
+    let (front, content) = rest
+        .split_once(&format!("\n{FENCE}"))
+        .ok_or_else(|| invalid("Front matter isn't closed with +++"))?;
+    let front: FrontMatter =
+        toml::from_str(front).map_err(|e| ServiceError::Validation(format!("Invalid front matter: {}", e.message())))?;
+    Ok((front, content.trim().to_string()))
+}
+
+/// Outcome of an import
+#[derive(Debug, Default, Serialize, utoipa::ToSchema)]
+pub struct SyncReport {
+    pub created: usize,
+    pub updated: usize,
+    pub deleted: usize,
+    /// Files skipped, with the reason
+    pub errors: Vec<SyncFileError>,
+}
+
+/// Outcome of an export
+#[derive(Debug, Serialize, utoipa::ToSchema)]
+pub struct SyncExport {
+    /// Files written or removed
+    pub written: usize,
+}
+
+/// A file that couldn't be imported
+#[derive(Debug, Serialize, utoipa::ToSchema)]
+pub struct SyncFileError {
+    pub path: String,
+    pub message: String,
+}
+
+#[derive(Debug, FromRow)]
+struct SyncedFile {
+    post_id: Uuid,
+    path: String,
+    hash: String,
+}
+
+/// The services imports and exports read and write posts through
+#[derive(Clone, Copy)]
+pub struct SyncServices<'a> {
+    pub posts: &'a PostService,
+    pub categories: &'a CategoryService,
+    pub tags: &'a TagService,
+}
+
+impl<'a> From<&'a BlogServices> for SyncServices<'a> {
+    fn from(services: &'a BlogServices) -> Self {
+        Self {
+            posts: &services.posts,
+            categories: &services.categories,
+            tags: &services.tags,
+        }
+    }
+}
+
+/// Mirrors posts to files and imports changed files
+pub struct ContentSync {
+    db: PgPool,
+    config: SyncConfig,
+    /// Imports and exports of the directory run one at a time
+    lock: Mutex<()>,
+}
+
+impl ContentSync {
+    pub fn new(db: PgPool, config: SyncConfig) -> Self {
+        Self {
+            db,
+            config,
+            lock: Mutex::new(()),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    fn site_dir(&self, site: &Site) -> PathBuf {
+        self.config.dir.join(&site.slug)
+    }
+
+    // ============================================
+    // Export
+    // ============================================
+
+    /// Write the files of `posts`, removing those of deleted or renamed ones
+    pub async fn export_posts(&self, services: SyncServices<'_>, site: &Site, posts: &[Uuid]) -> Result<usize, ServiceError> {
+        let _guard = self.lock.lock().await;
+        let dir = self.site_dir(site);
+        tokio::fs::create_dir_all(&dir).await.map_err(storage)?;
+
+        let mut written = 0;
+        for id in posts {
+            let synced = self.synced(site, *id).await?;
+            let post = match services.posts.get_with_relations(site, *id).await {
+                Ok(post) => post,
+                Err(ServiceError::NotFound(_)) => {
+                    if let Some(synced) = synced {
+                        remove_file(&dir.join(&synced.path)).await;
+                        self.forget(*id).await?;
+                        written += 1;
+                    }
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+
+            let path = format!("{}.md", post.post.slug);
+            let text = render(&post)?;
+            if let Some(synced) = &synced {
+                if synced.path != path {
+                    remove_file(&dir.join(&synced.path)).await;
+                }
+            }
+            let current = tokio::fs::read_to_string(dir.join(&path)).await.ok();
+            if current.as_deref() != Some(text.as_str()) {
+                tokio::fs::write(dir.join(&path), &text).await.map_err(storage)?;
+                written += 1;
+            }
+            self.record(site, *id, &path, &hash(&text)).await?;
+        }
+        Ok(written)
+    }
+
+    /// Write every post of a site
+    pub async fn export_site(&self, services: SyncServices<'_>, site: &Site) -> Result<usize, ServiceError> {
+        let ids: Vec<Uuid> = sqlx::query_scalar("SELECT id FROM blog_posts WHERE site_id = $1")
+            .bind(site.id)
+            .fetch_all(&self.db)
+            .await?;
+        self.export_posts(services, site, &ids).await
+    }
+
+    // ============================================
+    // Import
+    // ============================================
+
+    /// Apply added, changed and removed files of a site to its posts
+    pub async fn import_site(&self, services: SyncServices<'_>, site: &Site) -> Result<SyncReport, ServiceError> {
+        let _guard = self.lock.lock().await;
+        let dir = self.site_dir(site);
+        let mut report = SyncReport::default();
+
+        let synced: Vec<SyncedFile> = sqlx::query_as("SELECT post_id, path, hash FROM content_sync_files WHERE site_id = $1")
+            .bind(site.id)
+            .fetch_all(&self.db)
+            .await?;
+        let by_path: HashMap<&str, &SyncedFile> = synced.iter().map(|file| (file.path.as_str(), file)).collect();
+
+        let files = markdown_files(&dir).await?;
+        let mut seen = HashSet::new();
+        for path in &files {
+            let text = match tokio::fs::read_to_string(dir.join(path)).await {
+                Ok(text) => text,
+                Err(e) => {
+                    report.errors.push(SyncFileError { path: path.clone(), message: e.to_string() });
+                    continue;
+                }
+            };
+            let file_hash = hash(&text);
+            let tracked = by_path.get(path.as_str()).copied();
+            if let Some(tracked) = tracked {
+                seen.insert(tracked.post_id);
+                if tracked.hash == file_hash {
+                    continue;
+                }
+            }
+
+            match self.import_file(services, site, tracked, &text).await {
+                Ok((id, created)) => {
+                    seen.insert(id);
+                    if created {
+                        report.created += 1;
+                    } else {
+                        report.updated += 1;
+                    }
+                    self.record(site, id, path, &file_hash).await?;
+                }
+                Err(e) => report.errors.push(SyncFileError { path: path.clone(), message: e.to_string() }),
+            }
+        }
+
+        // An empty or missing directory is more likely a mistake than a
+        // request to delete every post
+        if !files.is_empty() {
+            for file in synced.iter().filter(|file| !seen.contains(&file.post_id)) {
+                match services.posts.get_by_id(site, file.post_id).await {
+                    Ok(post) => {
+                        let actor = post.author_id;
+                        services.posts.delete(site, post, actor).await?;
+                        report.deleted += 1;
+                    }
+                    Err(ServiceError::NotFound(_)) => {}
+                    Err(e) => return Err(e),
+                }
+                self.forget(file.post_id).await?;
+            }
+        }
+
+        if report.created + report.updated + report.deleted > 0 || !report.errors.is_empty() {
+            tracing::info!(
+                site = %site.slug,
+                created = report.created,
+                updated = report.updated,
+                deleted = report.deleted,
+                errors = report.errors.len(),
+                "Imported content files"
+            );
+        }
+        Ok(report)
+    }
+
+    /// Create or update the post of a file; returns its id and whether it's new
+    async fn import_file(
+        &self,
+        services: SyncServices<'_>,
+        site: &Site,
+        tracked: Option<&SyncedFile>,
+        text: &str,
+    ) -> Result<(Uuid, bool), ServiceError> {
+        let (front, content) = parse(text)?;
+        let category_ids = resolve_slugs(
+            &front.categories,
+            services.categories.list(site).await?.into_iter().map(|c| (c.slug, c.id)),
+            "category",
+        )?;
+        let tag_ids = resolve_slugs(
+            &front.tags,
+            services.tags.list(site).await?.into_iter().map(|t| (t.slug, t.id)),
+            "tag",
+        )?;
+
+        let existing = match front.id.or(tracked.map(|file| file.post_id)) {
+            Some(id) => match services.posts.get_with_relations(site, id).await {
+                Ok(post) => Some(post),
+                Err(ServiceError::NotFound(_)) if front.id.is_none() => None,
+                Err(e) => return Err(e),
+            },
+            None => None,
+        };
+
+        let Some(existing) = existing else {
+            let author = front
+                .author
+                .or(self.config.default_author)
+                .ok_or_else(|| ServiceError::Validation("New posts need an author".into()))?;
+            let req = CreatePostRequest {
+                title: front.title,
+                content,
+                excerpt: front.excerpt,
+                featured_image: front.featured_image,
+                category_ids: Some(category_ids),
+                tag_ids: Some(tag_ids),
+                meta_title: front.meta_title,
+                meta_description: front.meta_description,
+                scheduled_for: None,
+            };
+            req.validate()?;
+            let post = services.posts.create(site, author, req, None).await?;
+            if front.status == PostStatus::Published {
+                services.posts.publish(site, post.id, author).await?;
+            }
+            return Ok((post.id, true));
+        };
+
+        let id = existing.post.id;
+        let actor = existing.post.author_id;
+        let current = FrontMatter::from_post(&existing);
+        let changed = |new: &Option<String>, old: &Option<String>| (new != old).then(|| new.clone().unwrap_or_default());
+        let req = UpdatePostRequest {
+            title: (front.title != current.title).then(|| front.title.clone()),
+            content: (content != existing.post.content.trim()).then_some(content),
+            excerpt: changed(&front.excerpt, &current.excerpt),
+            featured_image: changed(&front.featured_image, &current.featured_image),
+            category_ids: (front.categories != current.categories).then_some(category_ids),
+            tag_ids: (front.tags != current.tags).then_some(tag_ids),
+            meta_title: changed(&front.meta_title, &current.meta_title),
+            meta_description: changed(&front.meta_description, &current.meta_description),
+        };
+        req.validate()?;
+        let has_changes = req.title.is_some()
+            || req.content.is_some()
+            || req.excerpt.is_some()
+            || req.featured_image.is_some()
+            || req.category_ids.is_some()
+            || req.tag_ids.is_some()
+            || req.meta_title.is_some()
+            || req.meta_description.is_some();
+        if has_changes {
+            services.posts.update(site, existing.post.clone(), actor, req).await?;
+        }
+
+        match (&current.status, &front.status) {
+            (PostStatus::Published, PostStatus::Published) => {}
+            (_, PostStatus::Published) => {
+                services.posts.publish(site, id, actor).await?;
+            }
+            (PostStatus::Published, _) => {
+                services.posts.unpublish(site, id, actor).await?;
+            }
+            _ => {}
+        }
+        Ok((id, false))
+    }
+
+    // ============================================
+    // Git
+    // ============================================
+
+    /// Commit (and push) the working copy, if anything changed
+    pub async fn commit(&self, message: &str) -> Result<(), ServiceError> {
+        if !self.config.git {
+            return Ok(());
+        }
+        let _guard = self.lock.lock().await;
+
+        self.git(&["add", "-A"]).await?;
+        if self.git(&["status", "--porcelain"]).await?.trim().is_empty() {
+            return Ok(());
+        }
+        let name = format!("user.name={}", self.config.commit_name);
+        let email = format!("user.email={}", self.config.commit_email);
+        self.git(&["-c", &name, "-c", &email, "commit", "-q", "-m", message]).await?;
+        if self.config.push {
+            self.git(&["push", "-q", &self.config.remote, &self.config.branch]).await?;
+        }
+        Ok(())
+    }
+
+    /// Fast-forward the working copy from the remote
+    pub async fn pull(&self) -> Result<(), ServiceError> {
+        if !self.config.git {
+            return Ok(());
+        }
+        let _guard = self.lock.lock().await;
+        self.git(&["pull", "-q", "--ff-only", &self.config.remote, &self.config.branch])
+            .await
+            .map(|_| ())
+    }
+
+    async fn git(&self, args: &[&str]) -> Result<String, ServiceError> {
+        let output = tokio::process::Command::new("git")
+            .arg("-C")
+            .arg(&self.config.dir)
+            .args(args)
+            .output()
+            .await
+            .map_err(|e| ServiceError::Storage(format!("git: {}", e)))?;
+        if !output.status.success() {
+            return Err(ServiceError::Storage(format!(
+                "git {}: {}",
+                args.first().unwrap_or(&""),
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    // ============================================
+    // Webhook
+    // ============================================
+
+    /// Check a webhook body against its `X-Hub-Signature-256` header
+    pub fn verify_webhook(&self, signature: Option<&str>, body: &[u8]) -> bool {
+        let (Some(secret), Some(signature)) = (&self.config.webhook_secret, signature) else {
+            return false;
+        };
+        let Some(sig) = signature.strip_prefix("sha256=").and_then(|sig| hex::decode(sig).ok()) else {
+            return false;
+        };
+        let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+        mac.update(body);
+        mac.verify_slice(&sig).is_ok()
+    }
+
+    // ============================================
+    // Tracking
+    // ============================================
+
+    async fn synced(&self, site: &Site, post_id: Uuid) -> Result<Option<SyncedFile>, ServiceError> {
+        let file = sqlx::query_as("SELECT post_id, path, hash FROM content_sync_files WHERE post_id = $1 AND site_id = $2")
+            .bind(post_id)
+            .bind(site.id)
+            .fetch_optional(&self.db)
+            .await?;
+        Ok(file)
+    }
+
+    async fn record(&self, site: &Site, post_id: Uuid, path: &str, hash: &str) -> Result<(), ServiceError> {
+        // A renamed post may take over the path of a deleted one
+        sqlx::query("DELETE FROM content_sync_files WHERE site_id = $1 AND path = $2 AND post_id <> $3")
+            .bind(site.id)
+            .bind(path)
+            .bind(post_id)
+            .execute(&self.db)
+            .await?;
+        sqlx::query(
+            "INSERT INTO content_sync_files (post_id, site_id, path, hash) VALUES ($1, $2, $3, $4)
+             ON CONFLICT (post_id) DO UPDATE SET path = $3, hash = $4, synced_at = NOW()",
+        )
+        .bind(post_id)
+        .bind(site.id)
+        .bind(path)
+        .bind(hash)
+        .execute(&self.db)
+        .await?;
+        Ok(())
+    }
+
+    async fn forget(&self, post_id: Uuid) -> Result<(), ServiceError> {
+        sqlx::query("DELETE FROM content_sync_files WHERE post_id = $1")
+            .bind(post_id)
+            .execute(&self.db)
+            .await?;
+        Ok(())
+    }
+}
+
+/// IDs of `slugs`, failing on the first unknown one
+fn resolve_slugs(slugs: &[String], known: impl Iterator<Item = (String, Uuid)>, kind: &str) -> Result<Vec<Uuid>, ServiceError> {
+    let known: HashMap<String, Uuid> = known.collect();
+    slugs
+        .iter()
+        .map(|slug| {
+            known
+                .get(slug)
+                .copied()
+                .ok_or_else(|| ServiceError::Validation(format!("Unknown {}: {}", kind, slug)))
+        })
+        .collect()
+}
+
+/// Names of the `.md` files directly in `dir`, sorted
+async fn markdown_files(dir: &Path) -> Result<Vec<String>, ServiceError> {
+    let mut entries = match tokio::fs::read_dir(dir).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(storage(e)),
+    };
+    let mut files = Vec::new();
+    while let Some(entry) = entries.next_entry().await.map_err(storage)? {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.ends_with(".md") && entry.file_type().await.map_err(storage)?.is_file() {
+            files.push(name);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+async fn remove_file(path: &Path) {
+    if let Err(e) = tokio::fs::remove_file(path).await {
+        if e.kind() != std::io::ErrorKind::NotFound {
+            tracing::warn!("Failed to remove {}: {}", path.display(), e);
+        }
+    }
+}
+
+fn hash(text: &str) -> String {
+    hex::encode(Sha256::digest(text.as_bytes()))
+}
+
+fn storage(e: std::io::Error) -> ServiceError {
+    ServiceError::Storage(e.to_string())
+}
+
+// ============================================
+// Background Jobs
+// ============================================
+
+/// Queues post changes for `run_export`
+pub struct SyncListener {
+    changes: mpsc::UnboundedSender<ContentEvent>,
+}
+
+impl SyncListener {
+    /// The listener and the queue it feeds
+    pub fn channel() -> (Self, mpsc::UnboundedReceiver<ContentEvent>) {
+        let (changes, receiver) = mpsc::unbounded_channel();
+        (Self { changes }, receiver)
+    }
+}
+
+#[async_trait]
+impl ContentListener for SyncListener {
+    async fn on_change(&self, event: &ContentEvent) {
+        if event.object_type == ContentObject::Post {
+            // Closed only once the services are gone
+            let _ = self.changes.send(event.clone());
+        }
+    }
+}
+
+/// Write queued post changes to files until the services are dropped
+///
+/// Changes that arrive together are written, and committed, in one pass.
+pub async fn run_export(services: Weak<BlogServices>, mut changes: mpsc::UnboundedReceiver<ContentEvent>) {
+    while let Some(event) = changes.recv().await {
+        let mut batch: HashMap<Uuid, Vec<Uuid>> = HashMap::new();
+        let mut labels = Vec::new();
+        let mut next = Some(event);
+        while let Some(event) = next {
+            let posts = batch.entry(event.site_id).or_default();
+            if !posts.contains(&event.object_id) {
+                posts.push(event.object_id);
+                labels.push(event.label.unwrap_or_default());
+            }
+            next = changes.try_recv().ok();
+        }
+
+        let Some(services) = services.upgrade() else {
+            return;
+        };
+        let mut written = 0;
+        for (site_id, posts) in batch {
+            let Some(site) = services.sites.get(site_id).await else {
+                continue;
+            };
+            match services.sync.export_posts(SyncServices::from(&*services), &site, &posts).await {
+                Ok(count) => written += count,
+                Err(e) => tracing::warn!(site = %site.slug, "Content file export failed: {}", e),
+            }
+        }
+
+        if written > 0 {
+            let message = match labels.as_slice() {
+                [label] => format!("Update \"{}\"", label),
+                labels => format!("Update {} posts", labels.len()),
+            };
+            if let Err(e) = services.sync.commit(&message).await {
+                tracing::warn!("Content commit failed: {}", e);
+            }
+        }
+    }
+}
+
+/// Import changed files every `poll_secs` until the services are dropped
+pub async fn run_watcher(services: Weak<BlogServices>, poll_secs: u64) {
+    let mut interval = tokio::time::interval(Duration::from_secs(poll_secs.max(1)));
+    loop {
+        interval.tick().await;
+        let Some(services) = services.upgrade() else {
+            return;
+        };
+        import_all(&services).await;
+    }
+}
+
+/// Pull, then import every site; run by the webhook
+pub async fn pull_and_import(services: &BlogServices) {
+    if let Err(e) = services.sync.pull().await {
+        tracing::warn!("Content pull failed: {}", e);
+        return;
+    }
+    import_all(services).await;
+}
+
+async fn import_all(services: &BlogServices) {
+    for site in services.sites.list().await {
+        if let Err(e) = services.sync.import_site(services.into(), &site).await {
+            tracing::warn!(site = %site.slug, "Content import failed: {}", e);
+        }
+    }
+}
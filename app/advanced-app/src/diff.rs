@@ -0,0 +1,150 @@
+//! Content Diffs
+//!
+//! Structured differences between two versions of a post, for editor UIs:
+//! each changed field with its old and new value, and the content as hunks
+//! of equal, inserted and deleted runs. Content is compared word by word
+//! with HTML tags and entities kept whole, so a hunk never starts or ends
+//! inside markup. `GET /posts/:id/diff` compares revisions with
+//! [`diff_revisions`]; anything else comparing post versions should build on
+//! it too.
+
+use crate::models::*;
+use similar::{Algorithm, DiffTag};
+
+/// Unchanged tokens kept on each side of a content hunk
+const CONTEXT_TOKENS: usize = 8;
+
+/// Fields compared, in the order they're reported
+const FIELDS: &[&str] = &[
+    "title",
+    "slug",
+    "excerpt",
+    "featured_image",
+    "meta_title",
+    "meta_description",
+    "content",
+];
+
+/// The fields that differ between two revisions
+pub fn diff_revisions(from: &PostRevision, to: &PostRevision) -> Vec<FieldDiff> {
+    FIELDS
+        .iter()
+        .filter_map(|field| {
+            let (old, new) = (field_value(from, field), field_value(to, field));
+            if old == new {
+                return None;
+            }
+            Some(match *field {
+                "content" => FieldDiff {
+                    field: field.to_string(),
+                    old: None,
+                    new: None,
+                    hunks: html_hunks(old.unwrap_or(""), new.unwrap_or(""), CONTEXT_TOKENS),
+                },
+                _ => FieldDiff {
+                    field: field.to_string(),
+                    old: old.map(String::from),
+                    new: new.map(String::from),
+                    hunks: Vec::new(),
+                },
+            })
+        })
+        .collect()
+}
+
+fn field_value<'a>(revision: &'a PostRevision, field: &str) -> Option<&'a str> {
+    match field {
+        "title" => Some(&revision.title),
+        "slug" => Some(&revision.slug),
+        "content" => Some(&revision.content),
+        "excerpt" => revision.excerpt.as_deref(),
+        "featured_image" => revision.featured_image.as_deref(),
+        "meta_title" => revision.meta_title.as_deref(),
+        "meta_description" => revision.meta_description.as_deref(),
+        _ => None,
+    }
+}
+
+/// Hunks turning `old` HTML into `new`, each with up to `context` unchanged
+/// tokens around its changes
+pub fn html_hunks(old: &str, new: &str, context: usize) -> Vec<DiffHunk> {
+    let (old_tokens, new_tokens) = (tokenize(old), tokenize(new));
+    let (old_offsets, new_offsets) = (offsets(&old_tokens), offsets(&new_tokens));
+    let ops = similar::capture_diff_slices(Algorithm::Myers, &old_tokens, &new_tokens);
+
+    similar::group_diff_ops(ops, context)
+        .into_iter()
+        .filter_map(|group| {
+            let (_, first_old, first_new) = group.first()?.as_tag_tuple();
+            let (_, last_old, last_new) = group.last()?.as_tag_tuple();
+
+            let mut changes = Vec::new();
+            for op in &group {
+                let (tag, old_range, new_range) = op.as_tag_tuple();
+                let old_text = old_tokens[old_range].concat();
+                let new_text = new_tokens[new_range].concat();
+                match tag {
+                    DiffTag::Equal => push_change(&mut changes, DiffOp::Equal, old_text),
+                    DiffTag::Delete => push_change(&mut changes, DiffOp::Delete, old_text),
+                    DiffTag::Insert => push_change(&mut changes, DiffOp::Insert, new_text),
+                    DiffTag::Replace => {
+                        push_change(&mut changes, DiffOp::Delete, old_text);
+                        push_change(&mut changes, DiffOp::Insert, new_text);
+                    }
+                }
+            }
+
+            Some(DiffHunk {
+                old_start: old_offsets[first_old.start],
+                old_end: old_offsets[last_old.end],
+                new_start: new_offsets[first_new.start],
+                new_end: new_offsets[last_new.end],
+                changes,
+            })
+        })
+        .collect()
+}
+
+fn push_change(changes: &mut Vec<DiffChange>, op: DiffOp, text: String) {
+    if text.is_empty() {
+        return;
+    }
+    match changes.last_mut() {
+        Some(last) if last.op == op => last.text.push_str(&text),
+        _ => changes.push(DiffChange { op, text }),
+    }
+}
+
+/// Byte offset of each token, and of the end
+fn offsets(tokens: &[&str]) -> Vec<usize> {
+    let mut offsets = Vec::with_capacity(tokens.len() + 1);
+    let mut offset = 0;
+    offsets.push(offset);
+    for token in tokens {
+        offset += token.len();
+        offsets.push(offset);
+    }
+    offsets
+}
+
+/// Split HTML into tags, entities, words, runs of whitespace and single
+/// other characters
+fn tokenize(html: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut rest = html;
+    while let Some(c) = rest.chars().next() {
+        let len = match c {
+            '<' => rest.find('>').map_or(rest.len(), |end| end + 1),
+            '&' => match rest.find(';') {
+                Some(end) if end <= 10 && rest[1..end].chars().all(|c| c.is_ascii_alphanumeric() || c == '#') => end + 1,
+                _ => 1,
+            },
+            c if c.is_whitespace() => rest.find(|c: char| !c.is_whitespace()).unwrap_or(rest.len()),
+            c if c.is_alphanumeric() => rest.find(|c: char| !c.is_alphanumeric()).unwrap_or(rest.len()),
+            c => c.len_utf8(),
+        };
+        tokens.push(&rest[..len]);
+        rest = &rest[len..];
+    }
+    tokens
+}
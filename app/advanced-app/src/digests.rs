@@ -0,0 +1,473 @@
+//! Comment Digests
+//!
+//! Post authors aren't mailed once per comment. New approved comments (and
+//! reactions, recorded by plugins through [`DigestService::record_reaction`])
+//! are queued in `pending_notifications` for the post's author, and
+//! [`run`] periodically sends each author whose period has passed a single
+//! summary grouped by post. Authors choose `daily`, `weekly` or `off` with
+//! `PUT /notifications/preferences`; `[app.digests] default_frequency`
+//! applies to everyone else.
+//!
+//! Mentions of a user (see `mentions`) are queued for them the same way.
+//!
+//! Digests go through the auth plugin's mail outbox (`rustpress_auth::mail`),
+//! so retries, throttling and the suppression list apply to them as well.
+
+use crate::activity::{ContentEvent, ContentListener};
+use crate::models::*;
+use crate::services::ServiceError;
+use crate::sites::{Site, SiteService};
+use crate::BlogServices;
+use axum::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use rustpress_auth::mail::{self, NewEmail};
+use serde::Deserialize;
+use sqlx::{FromRow, PgPool};
+use std::collections::BTreeMap;
+use std::sync::Weak;
+use uuid::Uuid;
+
+/// Outbox kind of digest messages
+pub const KIND_DIGEST: &str = "comment_digest";
+
+/// Length of the comment excerpt in a digest
+const SUMMARY_CHARS: usize = 200;
+
+/// `[app.digests]` settings
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct DigestConfig {
+    /// Queue notifications and send digests
+    pub enabled: bool,
+    /// Frequency of users who haven't chosen one
+    pub default_frequency: DigestFrequency,
+    /// How often to look for digests that are due
+    pub check_secs: u64,
+    /// Items listed per digest; the rest are counted
+    pub max_items: usize,
+    /// Origin for post links of sites without a host
+    /// (e.g. `https://blog.example.com`)
+    pub base_url: Option<String>,
+}
+
+impl Default for DigestConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            default_frequency: DigestFrequency::Daily,
+            check_secs: 900,
+            max_items: 50,
+            base_url: None,
+        }
+    }
+}
+
+impl DigestFrequency {
+    /// Time between two digests; `None` when digests are off
+    pub fn period(self) -> Option<Duration> {
+        match self {
+            DigestFrequency::Off => None,
+            DigestFrequency::Daily => Some(Duration::days(1)),
+            DigestFrequency::Weekly => Some(Duration::weeks(1)),
+        }
+    }
+}
+
+/// Whether a recipient's next digest is due at `now`
+pub fn is_due(frequency: DigestFrequency, last_digest_at: Option<DateTime<Utc>>, now: DateTime<Utc>) -> bool {
+    match (frequency.period(), last_digest_at) {
+        (None, _) => false,
+        (Some(_), None) => true,
+        (Some(period), Some(last)) => now - last >= period,
+    }
+}
+
+/// A recipient with something queued
+#[derive(Debug, FromRow)]
+struct Recipient {
+    user_id: Uuid,
+    email: String,
+    name: String,
+    frequency: Option<DigestFrequency>,
+    last_digest_at: Option<DateTime<Utc>>,
+}
+
+/// A queued notification with its post
+#[derive(Debug, Clone, FromRow)]
+pub struct DigestItem {
+    pub id: Uuid,
+    pub site_id: Uuid,
+    pub post_id: Uuid,
+    pub post_title: String,
+    pub post_slug: String,
+    pub kind: NotificationKind,
+    pub actor_name: String,
+    pub summary: String,
+    pub created_at: DateTime<Utc>,
+}
+
+pub struct DigestService {
+    db: PgPool,
+    config: DigestConfig,
+}
+
+impl DigestService {
+    pub fn new(db: PgPool, config: DigestConfig) -> Self {
+        Self { db, config }
+    }
+
+    /// Queue an approved comment for its post's author, unless the author
+    /// wrote it or has digests off
+    pub async fn record_comment(&self, comment_id: Uuid) -> Result<(), ServiceError> {
+        sqlx::query(
+            r#"INSERT INTO pending_notifications
+               (site_id, recipient_id, post_id, kind, object_id, actor_name, summary)
+               SELECT c.site_id, p.author_id, c.post_id, 'comment', c.id, c.author_name, LEFT(c.content, $2)
+               FROM blog_comments c
+               JOIN blog_posts p ON p.id = c.post_id
+               LEFT JOIN notification_preferences np ON np.user_id = p.author_id
+               WHERE c.id = $1 AND c.status = 'approved'
+                 AND c.author_id IS DISTINCT FROM p.author_id
+                 AND COALESCE(np.digest_frequency, $3) <> 'off'
+               ON CONFLICT (kind, object_id) DO NOTHING"#,
+        )
+        .bind(comment_id)
+        .bind(SUMMARY_CHARS as i32)
+        .bind(self.config.default_frequency)
+        .execute(&self.db)
+        .await?;
+        Ok(())
+    }
+
+    /// Queue a reaction (`reaction` is what's shown, e.g. an emoji) for the
+    /// post's author; for plugins that add reactions
+    pub async fn record_reaction(
+        &self,
+        site: &Site,
+        post_id: Uuid,
+        reaction_id: Option<Uuid>,
+        actor_id: Option<Uuid>,
+        actor_name: &str,
+        reaction: &str,
+    ) -> Result<(), ServiceError> {
+        if !self.config.enabled {
+            return Ok(());
+        }
+        sqlx::query(
+            r#"INSERT INTO pending_notifications
+               (site_id, recipient_id, post_id, kind, object_id, actor_name, summary)
+               SELECT p.site_id, p.author_id, p.id, 'reaction', $3, $5, $6
+               FROM blog_posts p
+               LEFT JOIN notification_preferences np ON np.user_id = p.author_id
+               WHERE p.id = $1 AND p.site_id = $2
+                 AND p.author_id IS DISTINCT FROM $4
+                 AND COALESCE(np.digest_frequency, $7) <> 'off'
+               ON CONFLICT (kind, object_id) DO NOTHING"#,
+        )
+        .bind(post_id)
+        .bind(site.id)
+        .bind(reaction_id)
+        .bind(actor_id)
+        .bind(actor_name)
+        .bind(reaction)
+        .bind(self.config.default_frequency)
+        .execute(&self.db)
+        .await?;
+        Ok(())
+    }
+
+    /// Queue a notified mention (see `mentions`) for the mentioned user;
+    /// a post's author already hears of comments on it as comments
+    pub async fn record_mention(&self, mention_id: Uuid) -> Result<(), ServiceError> {
+        if !self.config.enabled {
+            return Ok(());
+        }
+        sqlx::query(
+            r#"INSERT INTO pending_notifications
+               (site_id, recipient_id, post_id, kind, object_id, actor_name, summary)
+               SELECT m.site_id, m.user_id, m.post_id, 'mention', m.id, m.actor_name, m.summary
+               FROM blog_mentions m
+               JOIN blog_posts p ON p.id = m.post_id
+               LEFT JOIN notification_preferences np ON np.user_id = m.user_id
+               WHERE m.id = $1
+                 AND NOT (m.object_type = 'comment' AND m.user_id = p.author_id)
+                 AND COALESCE(np.digest_frequency, $2) <> 'off'
+               ON CONFLICT (kind, object_id) DO NOTHING"#,
+        )
+        .bind(mention_id)
+        .bind(self.config.default_frequency)
+        .execute(&self.db)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn preferences(&self, user_id: Uuid) -> Result<NotificationPreferences, ServiceError> {
+        let stored: Option<(Option<DigestFrequency>, Option<DateTime<Utc>>)> = sqlx::query_as(
+            "SELECT digest_frequency, last_digest_at FROM notification_preferences WHERE user_id = $1",
+        )
+        .bind(user_id)
+        .fetch_optional(&self.db)
+        .await?;
+        let (frequency, last_digest_at) = stored.unwrap_or_default();
+
+        let pending: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM pending_notifications WHERE recipient_id = $1")
+            .bind(user_id)
+            .fetch_one(&self.db)
+            .await?;
+
+        Ok(NotificationPreferences {
+            frequency: frequency.unwrap_or(self.config.default_frequency),
+            is_default: frequency.is_none(),
+            last_digest_at,
+            pending,
+        })
+    }
+
+    /// Choose a frequency (`None` for the default); turning digests off drops
+    /// whatever is queued
+    pub async fn set_frequency(
+        &self,
+        user_id: Uuid,
+        frequency: Option<DigestFrequency>,
+    ) -> Result<NotificationPreferences, ServiceError> {
+        sqlx::query(
+            r#"INSERT INTO notification_preferences (user_id, digest_frequency)
+               VALUES ($1, $2)
+               ON CONFLICT (user_id) DO UPDATE SET digest_frequency = $2, updated_at = NOW()"#,
+        )
+        .bind(user_id)
+        .bind(frequency)
+        .execute(&self.db)
+        .await?;
+
+        if frequency.unwrap_or(self.config.default_frequency) == DigestFrequency::Off {
+            sqlx::query("DELETE FROM pending_notifications WHERE recipient_id = $1")
+                .bind(user_id)
+                .execute(&self.db)
+                .await?;
+        }
+
+        self.preferences(user_id).await
+    }
+
+    /// Send every digest that is due; returns how many were queued for
+    /// delivery
+    pub async fn send_due(&self, sites: &SiteService) -> Result<usize, ServiceError> {
+        let recipients: Vec<Recipient> = sqlx::query_as(
+            r#"SELECT u.id AS user_id, u.email, u.name, np.digest_frequency AS frequency, np.last_digest_at
+               FROM users u
+               LEFT JOIN notification_preferences np ON np.user_id = u.id
+               WHERE EXISTS (SELECT 1 FROM pending_notifications n WHERE n.recipient_id = u.id)"#,
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        let now = Utc::now();
+        let mut sent = 0;
+        for recipient in recipients {
+            let frequency = recipient.frequency.unwrap_or(self.config.default_frequency);
+            if !is_due(frequency, recipient.last_digest_at, now) {
+                continue;
+            }
+            match self.send_digest(sites, &recipient, now).await {
+                Ok(true) => sent += 1,
+                Ok(false) => {}
+                Err(e) => tracing::warn!(user_id = %recipient.user_id, "Failed to send comment digest: {}", e),
+            }
+        }
+        Ok(sent)
+    }
+
+    async fn send_digest(&self, sites: &SiteService, recipient: &Recipient, now: DateTime<Utc>) -> Result<bool, ServiceError> {
+        // Claim the period first, so two instances never both send it
+        let claimed = sqlx::query(
+            r#"INSERT INTO notification_preferences (user_id, last_digest_at)
+               VALUES ($1, $2)
+               ON CONFLICT (user_id) DO UPDATE SET last_digest_at = $2
+               WHERE notification_preferences.last_digest_at IS NOT DISTINCT FROM $3"#,
+        )
+        .bind(recipient.user_id)
+        .bind(now)
+        .bind(recipient.last_digest_at)
+        .execute(&self.db)
+        .await?
+        .rows_affected()
+            > 0;
+        if !claimed {
+            return Ok(false);
+        }
+
+        let items: Vec<DigestItem> = sqlx::query_as(
+            r#"SELECT n.id, n.site_id, n.post_id, p.title AS post_title, p.slug AS post_slug,
+                      n.kind, n.actor_name, n.summary, n.created_at
+               FROM pending_notifications n
+               JOIN blog_posts p ON p.id = n.post_id
+               WHERE n.recipient_id = $1 AND n.created_at <= $2
+               ORDER BY n.created_at"#,
+        )
+        .bind(recipient.user_id)
+        .bind(now)
+        .fetch_all(&self.db)
+        .await?;
+        if items.is_empty() {
+            return Ok(false);
+        }
+
+        let mut links = BTreeMap::new();
+        for item in &items {
+            if !links.contains_key(&item.post_id) {
+                let link = match sites.get(item.site_id).await {
+                    Some(site) => self.post_url(&site, &item.post_slug),
+                    None => continue,
+                };
+                links.insert(item.post_id, link);
+            }
+        }
+
+        let email = NewEmail {
+            kind: KIND_DIGEST.to_string(),
+            recipient: recipient.email.clone(),
+            subject: digest_subject(&items),
+            body: digest_body(&recipient.name, &items, &links, self.config.max_items),
+        };
+        // Queued before the entries are dropped: a failure in between sends
+        // the next digest with them again rather than losing them
+        mail::enqueue(&self.db, &email)
+            .await
+            .map_err(|e| ServiceError::Storage(e.to_string()))?;
+
+        let ids: Vec<Uuid> = items.iter().map(|item| item.id).collect();
+        sqlx::query("DELETE FROM pending_notifications WHERE id = ANY($1)")
+            .bind(&ids)
+            .execute(&self.db)
+            .await?;
+
+        Ok(true)
+    }
+
+    fn post_url(&self, site: &Site, slug: &str) -> String {
+        let origin = match (&site.host, &self.config.base_url) {
+            (Some(host), _) => format!("https://{}", host),
+            (None, Some(base_url)) => base_url.trim_end_matches('/').to_string(),
+            (None, None) => String::new(),
+        };
+        format!("{}{}", origin, site.url(&format!("/read/{}", slug)))
+    }
+}
+
+/// Queues approved comments
+#[async_trait]
+impl ContentListener for DigestService {
+    async fn on_change(&self, event: &ContentEvent) {
+        // Comments held for moderation are queued once approved
+        let relevant = event.object_type == ContentObject::Comment
+            && matches!(event.action, ContentAction::Created | ContentAction::Approved);
+        if !relevant {
+            return;
+        }
+        if let Err(e) = self.record_comment(event.object_id).await {
+            tracing::warn!(comment_id = %event.object_id, "Failed to queue comment notification: {}", e);
+        }
+    }
+}
+
+fn digest_subject(items: &[DigestItem]) -> String {
+    let count = |kind: NotificationKind| items.iter().filter(|i| i.kind == kind).count();
+    let (comments, reactions, mentions) = (
+        count(NotificationKind::Comment),
+        count(NotificationKind::Reaction),
+        count(NotificationKind::Mention),
+    );
+    let activity = match (comments, reactions) {
+        (0, 0) => None,
+        (0, r) => Some(format!("{} new reaction{} on your posts", r, plural(r))),
+        (c, 0) => Some(format!("{} new comment{} on your posts", c, plural(c))),
+        (c, r) => Some(format!("{} new comment{} and {} reaction{} on your posts", c, plural(c), r, plural(r))),
+    };
+    match (activity, mentions) {
+        (Some(activity), 0) => activity,
+        (Some(activity), m) => format!("{}, {} new mention{}", activity, m, plural(m)),
+        (None, m) => format!("{} new mention{} of you", m, plural(m)),
+    }
+}
+
+/// Plain-text digest, grouped by post in the order of each post's first
+/// entry
+fn digest_body(name: &str, items: &[DigestItem], links: &BTreeMap<Uuid, String>, max_items: usize) -> String {
+    let mut posts: Vec<(Uuid, Vec<&DigestItem>)> = Vec::new();
+    for item in items.iter().take(max_items) {
+        match posts.iter_mut().find(|(id, _)| *id == item.post_id) {
+            Some((_, entries)) => entries.push(item),
+            None => posts.push((item.post_id, vec![item])),
+        }
+    }
+
+    let intro = if items.iter().any(|i| i.kind == NotificationKind::Mention) {
+        "Here's what happened since your last summary:"
+    } else {
+        "Here's what happened on your posts:"
+    };
+    let mut body = format!("Hello {},\n\n{}\n", name, intro);
+    for (post_id, entries) in posts {
+        body.push_str(&format!("\n{}\n", entries[0].post_title));
+        if let Some(link) = links.get(&post_id) {
+            body.push_str(&format!("{}\n", link));
+        }
+        for entry in entries {
+            match entry.kind {
+                NotificationKind::Comment => {
+                    body.push_str(&format!("- {} commented: \"{}\"\n", entry.actor_name, excerpt(&entry.summary)))
+                }
+                NotificationKind::Reaction => {
+                    body.push_str(&format!("- {} reacted {}\n", entry.actor_name, entry.summary))
+                }
+                NotificationKind::Mention => {
+                    body.push_str(&format!("- {} mentioned you: \"{}\"\n", entry.actor_name, excerpt(&entry.summary)))
+                }
+            }
+        }
+    }
+
+    if items.len() > max_items {
+        body.push_str(&format!("\n...and {} more.\n", items.len() - max_items));
+    }
+    body.push_str("\nChange how often you get this summary in your notification preferences.\n");
+    body
+}
+
+/// Single-line comment excerpt, cut at a word boundary
+fn excerpt(text: &str) -> String {
+    let flat = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if flat.chars().count() <= 120 {
+        return flat;
+    }
+    let cut: String = flat.chars().take(120).collect();
+    match cut.rfind(' ') {
+        Some(i) => format!("{}...", &cut[..i]),
+        None => format!("{}...", cut),
+    }
+}
+
+fn plural(n: usize) -> &'static str {
+    if n == 1 {
+        ""
+    } else {
+        "s"
+    }
+}
+
+/// Send due digests every `check_secs` until the services are dropped
+pub async fn run(services: Weak<BlogServices>, check_secs: u64) {
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(check_secs.max(1)));
+    loop {
+        interval.tick().await;
+        let Some(services) = services.upgrade() else {
+            return;
+        };
+        match services.digests.send_due(&services.sites).await {
+            Ok(0) => {}
+            Ok(sent) => tracing::info!(sent, "Sent comment digests"),
+            Err(e) => tracing::warn!("Comment digest run failed: {}", e),
+        }
+    }
+}
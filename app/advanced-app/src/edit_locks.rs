@@ -0,0 +1,137 @@
+//! Post Edit Locks
+//!
+//! Editors hold a post while its editor is open by calling
+//! `POST /posts/:id/lock` every few seconds. Each call takes the lock, or
+//! renews it for another `ttl_secs`; while someone else holds it the call
+//! answers 423 with who that is, and `PUT /posts/:id` refuses their changes
+//! the same way. Locks nobody renews lapse on their own, `DELETE
+//! /posts/:id/lock` hands one back, and admins take one over with
+//! `POST /admin/posts/:id/lock/takeover`.
+//!
+//! This keeps two editors from silently overwriting each other; it isn't
+//! merging.
+
+use crate::models::EditLock;
+use crate::services::ServiceError;
+use crate::sites::Site;
+use serde::Deserialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Columns of an `EditLock`, from `blog_post_locks l` joined with `users u`
+const LOCK_COLUMNS: &str = "l.post_id, l.user_id, u.name AS user_name, l.acquired_at, l.heartbeat_at, l.expires_at";
+
+/// `[app.edit_locks]` settings
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct EditLockConfig {
+    /// Seconds a lock lasts after its last heartbeat
+    pub ttl_secs: u64,
+}
+
+impl Default for EditLockConfig {
+    fn default() -> Self {
+        Self { ttl_secs: 60 }
+    }
+}
+
+pub struct EditLockService {
+    db: PgPool,
+    config: EditLockConfig,
+}
+
+impl EditLockService {
+    pub fn new(db: PgPool, config: EditLockConfig) -> Self {
+        Self { db, config }
+    }
+
+    /// Take or renew `user_id`'s lock on a post; `ServiceError::Locked` while
+    /// someone else holds it
+    #[tracing::instrument(name = "posts.lock", skip_all, fields(site = %site.id, post_id = %post_id))]
+    pub async fn heartbeat(&self, site: &Site, post_id: Uuid, user_id: Uuid) -> Result<EditLock, ServiceError> {
+        let taken: Option<Uuid> = sqlx::query_scalar(
+            "INSERT INTO blog_post_locks (post_id, site_id, user_id, expires_at)
+             VALUES ($1, $2, $3, NOW() + make_interval(secs => $4))
+             ON CONFLICT (post_id) DO UPDATE
+             SET user_id = EXCLUDED.user_id,
+                 acquired_at = CASE WHEN blog_post_locks.user_id = EXCLUDED.user_id
+                                    THEN blog_post_locks.acquired_at ELSE NOW() END,
+                 heartbeat_at = NOW(),
+                 expires_at = EXCLUDED.expires_at
+             WHERE blog_post_locks.user_id = EXCLUDED.user_id OR blog_post_locks.expires_at <= NOW()
+             RETURNING post_id",
+        )
+        .bind(post_id)
+        .bind(site.id)
+        .bind(user_id)
+        .bind(self.config.ttl_secs as f64)
+        .fetch_optional(&self.db)
+        .await?;
+
+        match (taken, self.current(site, post_id).await?) {
+            (Some(_), Some(lock)) => Ok(lock),
+            (None, Some(lock)) => Err(ServiceError::Locked(lock)),
+            // Released or lapsed in between
+            (_, None) => Err(ServiceError::NotFound("Lock not found".into())),
+        }
+    }
+
+    /// The live lock on a post, if any
+    pub async fn current(&self, site: &Site, post_id: Uuid) -> Result<Option<EditLock>, ServiceError> {
+        let lock = sqlx::query_as(&format!(
+            "SELECT {} FROM blog_post_locks l JOIN users u ON u.id = l.user_id
+             WHERE l.post_id = $1 AND l.site_id = $2 AND l.expires_at > NOW()",
+            LOCK_COLUMNS
+        ))
+        .bind(post_id)
+        .bind(site.id)
+        .fetch_optional(&self.db)
+        .await?;
+        Ok(lock)
+    }
+
+    /// `ServiceError::Locked` if someone other than `user_id` holds the post
+    pub async fn ensure_unlocked(&self, site: &Site, post_id: Uuid, user_id: Uuid) -> Result<(), ServiceError> {
+        match self.current(site, post_id).await? {
+            Some(lock) if lock.user_id != user_id => Err(ServiceError::Locked(lock)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Hand back `user_id`'s lock
+    pub async fn release(&self, site: &Site, post_id: Uuid, user_id: Uuid) -> Result<(), ServiceError> {
+        sqlx::query("DELETE FROM blog_post_locks WHERE post_id = $1 AND site_id = $2 AND user_id = $3")
+            .bind(post_id)
+            .bind(site.id)
+            .bind(user_id)
+            .execute(&self.db)
+            .await?;
+        Ok(())
+    }
+
+    /// Give the lock to `user_id` whoever holds it
+    #[tracing::instrument(name = "posts.lock_takeover", skip_all, fields(site = %site.id, post_id = %post_id))]
+    pub async fn take_over(&self, site: &Site, post_id: Uuid, user_id: Uuid) -> Result<EditLock, ServiceError> {
+        if let Some(previous) = self.current(site, post_id).await? {
+            if previous.user_id != user_id {
+                tracing::info!(from = %previous.user_id, to = %user_id, "Edit lock taken over");
+            }
+        }
+        sqlx::query(
+            "INSERT INTO blog_post_locks (post_id, site_id, user_id, expires_at)
+             VALUES ($1, $2, $3, NOW() + make_interval(secs => $4))
+             ON CONFLICT (post_id) DO UPDATE
+             SET user_id = EXCLUDED.user_id, acquired_at = NOW(), heartbeat_at = NOW(), expires_at = EXCLUDED.expires_at",
+        )
+        .bind(post_id)
+        .bind(site.id)
+        .bind(user_id)
+        .bind(self.config.ttl_secs as f64)
+        .execute(&self.db)
+        .await?;
+
+        self.current(site, post_id)
+            .await?
+            .ok_or_else(|| ServiceError::NotFound("Lock not found".into()))
+    }
+}
@@ -0,0 +1,660 @@
+//! Post Embeddings and Semantic Search
+//!
+//! Published posts are embedded as vectors by an [`EmbeddingProvider`] and
+//! stored in `blog_post_embeddings` (a pgvector `vector` column), so posts
+//! can be found by meaning rather than by shared words:
+//!
+//! - `GET /search/semantic?q=` ranks posts by the similarity of their vector
+//!   to the query's
+//! - `GET /posts/:id/related` ranks posts by the similarity of their vector
+//!   to the post's ("more like this")
+//!
+//! Both combine that ranking with the full-text ranking `GET /search` uses
+//! (for related posts, of the post's title words) by reciprocal rank fusion:
+//! a post scores `1 / (60 + rank)` in each ranking it appears in, so posts
+//! that do well in both come first and either ranking alone still finds
+//! something. When the query can't be embedded the keyword ranking is
+//! returned on its own.
+//!
+//! Providers:
+//!
+//! - `api`: an OpenAI-compatible `/v1/embeddings` endpoint, hosted or
+//!   self-hosted
+//! - `ollama`: a local model served by Ollama (`/api/embed`)
+//! - `hashing`: built in, no model; words are hashed into the vector, so
+//!   similarity is little more than shared vocabulary. Meant for development
+//!   and tests
+//!
+//! [`EmbeddingListener`] follows post changes: [`run`] embeds posts when
+//! they're published or their text changes, drops them when they're
+//! unpublished or trashed, and at startup backfills published posts that
+//! have no vector from the configured model.
+//!
+//! pgvector is an extension the database may not have, so the table and its
+//! HNSW index are created by [`EmbeddingService::prepare`] when the feature
+//! is enabled rather than by a migration. The index is an expression index
+//! for the configured `dimensions`; changing models re-embeds every post.
+//!
+//! Off unless `[app.embeddings]` `enabled`.
+
+use crate::activity::{ContentEvent, ContentListener};
+use crate::models::*;
+use crate::services::ServiceError;
+use crate::sites::Site;
+use crate::BlogServices;
+use axum::async_trait;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+/// Reciprocal rank fusion constant; dampens the lead of the top few ranks
+const RRF_K: u32 = 60;
+
+/// Embedding backend
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProviderKind {
+    /// Built-in feature hashing, no model
+    Hashing,
+    /// OpenAI-compatible embeddings endpoint
+    Api,
+    /// Local Ollama server
+    Ollama,
+}
+
+/// `[app.embeddings]` settings
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct EmbeddingConfig {
+    /// Embed published posts and serve semantic search; needs pgvector
+    pub enabled: bool,
+    pub provider: ProviderKind,
+    /// `api`: the embeddings URL (`https://.../v1/embeddings`); `ollama`:
+    /// the server (`http://localhost:11434`)
+    pub endpoint: String,
+    /// Sent as a bearer token by the `api` provider, if set
+    pub api_key: Option<String>,
+    /// Model name sent to the `api` and `ollama` providers
+    pub model: String,
+    /// Length of the model's vectors
+    pub dimensions: usize,
+    pub timeout_secs: u64,
+    /// Post text embedded at most, in characters
+    pub max_chars: usize,
+    /// Posts taken from each ranking before they're combined
+    pub candidates: i64,
+    /// Posts embedded per provider request
+    pub batch_size: usize,
+}
+
+impl Default for EmbeddingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            provider: ProviderKind::Hashing,
+            endpoint: String::new(),
+            api_key: None,
+            model: String::new(),
+            dimensions: 384,
+            timeout_secs: 30,
+            max_chars: 8000,
+            candidates: 50,
+            batch_size: 16,
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum EmbeddingError {
+    #[error("Embedding request failed: {0}")]
+    Request(#[from] reqwest::Error),
+    #[error("Embedding endpoint returned {status}: {message}")]
+    Api { status: u16, message: String },
+    #[error("Unexpected embedding response: {0}")]
+    Response(String),
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+// ============================================
+// Providers
+// ============================================
+
+/// Turns text into vectors
+#[async_trait]
+pub trait EmbeddingProvider: Send + Sync {
+    /// Stored with each vector; posts embedded by another model are
+    /// embedded again
+    fn model(&self) -> &str;
+
+    /// One vector per text, in order
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, EmbeddingError>;
+}
+
+/// The provider `config` selects
+pub fn provider(config: &EmbeddingConfig) -> Result<Arc<dyn EmbeddingProvider>, EmbeddingError> {
+    let timeout = Duration::from_secs(config.timeout_secs);
+    Ok(match config.provider {
+        ProviderKind::Hashing => Arc::new(HashingProvider::new(config.dimensions)),
+        ProviderKind::Api => Arc::new(ApiProvider {
+            http: reqwest::Client::builder().timeout(timeout).build()?,
+            endpoint: config.endpoint.clone(),
+            api_key: config.api_key.clone(),
+            model: config.model.clone(),
+        }),
+        ProviderKind::Ollama => Arc::new(OllamaProvider {
+            http: reqwest::Client::builder().timeout(timeout).build()?,
+            endpoint: format!("{}/api/embed", config.endpoint.trim_end_matches('/')),
+            model: config.model.clone(),
+        }),
+    })
+}
+
+/// Hashes each word into one of `dimensions` buckets
+pub struct HashingProvider {
+    dimensions: usize,
+    /// `hashing-<dimensions>`
+    model: String,
+}
+
+impl HashingProvider {
+    pub fn new(dimensions: usize) -> Self {
+        let dimensions = dimensions.max(1);
+        Self {
+            dimensions,
+            model: format!("hashing-{}", dimensions),
+        }
+    }
+
+    /// Unit vector of the word counts of `text`, with a hash-chosen sign per
+    /// word so colliding words tend to cancel out rather than add up
+    pub fn vector(&self, text: &str) -> Vec<f32> {
+        let mut vector = vec![0.0f32; self.dimensions];
+        for word in text.split(|c: char| !c.is_alphanumeric()).filter(|word| !word.is_empty()) {
+            let hash = fnv1a(&word.to_lowercase());
+            let bucket = (hash % self.dimensions as u64) as usize;
+            vector[bucket] += if hash >> 63 == 0 { 1.0 } else { -1.0 };
+        }
+
+        let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+        if norm > 0.0 {
+            vector.iter_mut().for_each(|x| *x /= norm);
+        }
+        vector
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for HashingProvider {
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, EmbeddingError> {
+        Ok(texts.iter().map(|text| self.vector(text)).collect())
+    }
+}
+
+fn fnv1a(text: &str) -> u64 {
+    text.bytes()
+        .fold(0xcbf2_9ce4_8422_2325, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3))
+}
+
+/// OpenAI-compatible `POST /v1/embeddings` (`{"model", "input": [...]}`)
+struct ApiProvider {
+    http: reqwest::Client,
+    endpoint: String,
+    api_key: Option<String>,
+    model: String,
+}
+
+#[async_trait]
+impl EmbeddingProvider for ApiProvider {
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, EmbeddingError> {
+        let mut request = self
+            .http
+            .post(&self.endpoint)
+            .json(&json!({ "model": self.model, "input": texts }));
+        if let Some(key) = &self.api_key {
+            request = request.bearer_auth(key);
+        }
+
+        let body = send(request).await?;
+        let mut data: Vec<(u64, Vec<f32>)> = body["data"]
+            .as_array()
+            .ok_or_else(|| EmbeddingError::Response("no data".into()))?
+            .iter()
+            .map(|item| Ok((item["index"].as_u64().unwrap_or(0), floats(&item["embedding"])?)))
+            .collect::<Result<_, EmbeddingError>>()?;
+        data.sort_by_key(|(index, _)| *index);
+        Ok(data.into_iter().map(|(_, vector)| vector).collect())
+    }
+}
+
+/// Ollama's `POST /api/embed` (`{"model", "input": [...]}`)
+struct OllamaProvider {
+    http: reqwest::Client,
+    endpoint: String,
+    model: String,
+}
+
+#[async_trait]
+impl EmbeddingProvider for OllamaProvider {
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, EmbeddingError> {
+        let request = self
+            .http
+            .post(&self.endpoint)
+            .json(&json!({ "model": self.model, "input": texts }));
+
+        let body = send(request).await?;
+        body["embeddings"]
+            .as_array()
+            .ok_or_else(|| EmbeddingError::Response("no embeddings".into()))?
+            .iter()
+            .map(floats)
+            .collect()
+    }
+}
+
+async fn send(request: reqwest::RequestBuilder) -> Result<Value, EmbeddingError> {
+    let response = request.send().await?;
+    let status = response.status();
+    if !status.is_success() {
+        let body: Value = response.json().await.unwrap_or_default();
+        let message = match &body["error"] {
+            Value::String(message) => message.clone(),
+            error => error["message"].as_str().unwrap_or("unknown error").to_string(),
+        };
+        return Err(EmbeddingError::Api {
+            status: status.as_u16(),
+            message,
+        });
+    }
+    Ok(response.json().await?)
+}
+
+fn floats(value: &Value) -> Result<Vec<f32>, EmbeddingError> {
+    value
+        .as_array()
+        .ok_or_else(|| EmbeddingError::Response("embedding is not an array".into()))?
+        .iter()
+        .map(|x| {
+            x.as_f64()
+                .map(|x| x as f32)
+                .ok_or_else(|| EmbeddingError::Response("embedding holds a non-number".into()))
+        })
+        .collect()
+}
+
+// ============================================
+// Service
+// ============================================
+
+/// Full-text query `GET /search` ranks by
+const KEYWORD_QUERY: &str = "plainto_tsquery('english', $4)";
+
+/// Matches posts sharing any of the words, for related posts
+const ANY_WORD_QUERY: &str = "NULLIF(replace(plainto_tsquery('english', $4)::text, '&', '|'), '')::tsquery";
+
+/// Post vectors and the rankings built on them
+pub struct EmbeddingService {
+    db: PgPool,
+    provider: Arc<dyn EmbeddingProvider>,
+    config: EmbeddingConfig,
+}
+
+impl EmbeddingService {
+    pub fn new(db: PgPool, config: EmbeddingConfig) -> Result<Self, EmbeddingError> {
+        Ok(Self::with_provider(db, provider(&config)?, config))
+    }
+
+    /// A service embedding with `provider` instead of the configured one
+    pub fn with_provider(db: PgPool, provider: Arc<dyn EmbeddingProvider>, config: EmbeddingConfig) -> Self {
+        Self { db, provider, config }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    /// Create the pgvector extension, the vector table and the index for the
+    /// configured dimensions, where missing
+    ///
+    /// Fails when pgvector isn't installed on the database server, or the
+    /// database user may not create the extension (an admin can run
+    /// `CREATE EXTENSION vector` instead).
+    pub async fn prepare(&self) -> Result<(), sqlx::Error> {
+        let dimensions = self.config.dimensions;
+        let statements = [
+            "CREATE EXTENSION IF NOT EXISTS vector".to_string(),
+            "CREATE TABLE IF NOT EXISTS blog_post_embeddings (
+                 post_id UUID PRIMARY KEY REFERENCES blog_posts(id) ON DELETE CASCADE,
+                 site_id UUID NOT NULL REFERENCES blog_sites(id) ON DELETE CASCADE,
+                 model TEXT NOT NULL,
+                 dimensions INT NOT NULL,
+                 embedding vector NOT NULL,
+                 -- SHA-256 of the embedded text, to skip saves that don't change it
+                 content_hash TEXT NOT NULL,
+                 updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+             )"
+            .to_string(),
+            "CREATE INDEX IF NOT EXISTS idx_post_embeddings_site ON blog_post_embeddings(site_id, model)".to_string(),
+            format!(
+                "CREATE INDEX IF NOT EXISTS idx_post_embeddings_hnsw_{dimensions} ON blog_post_embeddings
+                 USING hnsw ((embedding::vector({dimensions})) vector_cosine_ops)
+                 WHERE dimensions = {dimensions}"
+            ),
+        ];
+        for statement in &statements {
+            sqlx::query(statement).execute(&self.db).await?;
+        }
+        Ok(())
+    }
+
+    /// Bring the vectors of `post_ids` up to date: published posts whose
+    /// text or model changed are embedded, the others' vectors removed
+    ///
+    /// Returns the number of posts embedded.
+    pub async fn sync(&self, post_ids: &[Uuid]) -> Result<usize, EmbeddingError> {
+        let posts: Vec<(Uuid, Uuid, String, Option<String>, String)> = sqlx::query_as(
+            "SELECT id, site_id, title, excerpt, content FROM blog_posts
+             WHERE id = ANY($1) AND status = 'published'"
+        )
+        .bind(post_ids)
+        .fetch_all(&self.db)
+        .await?;
+
+        // Unpublished and trashed posts leave the rankings
+        let published: Vec<Uuid> = posts.iter().map(|(id, ..)| *id).collect();
+        sqlx::query("DELETE FROM blog_post_embeddings WHERE post_id = ANY($1) AND NOT post_id = ANY($2)")
+            .bind(post_ids)
+            .bind(&published)
+            .execute(&self.db)
+            .await?;
+
+        let stored: HashMap<Uuid, String> = sqlx::query_as(
+            "SELECT post_id, content_hash FROM blog_post_embeddings WHERE post_id = ANY($1) AND model = $2"
+        )
+        .bind(&published)
+        .bind(self.provider.model())
+        .fetch_all(&self.db)
+        .await?
+        .into_iter()
+        .collect();
+
+        let pending: Vec<(Uuid, Uuid, String, String)> = posts
+            .into_iter()
+            .filter_map(|(id, site_id, title, excerpt, content)| {
+                let text = document(&title, excerpt.as_deref(), &content, self.config.max_chars);
+                let hash = hex::encode(Sha256::digest(text.as_bytes()));
+                (stored.get(&id) != Some(&hash)).then_some((id, site_id, text, hash))
+            })
+            .collect();
+
+        for batch in pending.chunks(self.config.batch_size.max(1)) {
+            let texts: Vec<String> = batch.iter().map(|(_, _, text, _)| text.clone()).collect();
+            let vectors = self.provider.embed(&texts).await?;
+            if vectors.len() != batch.len() {
+                return Err(EmbeddingError::Response(format!(
+                    "{} vectors for {} texts",
+                    vectors.len(),
+                    batch.len()
+                )));
+            }
+
+            for ((post_id, site_id, _, hash), vector) in batch.iter().zip(vectors) {
+                if vector.len() != self.config.dimensions {
+                    return Err(EmbeddingError::Response(format!(
+                        "{} dimensions, configured for {}",
+                        vector.len(),
+                        self.config.dimensions
+                    )));
+                }
+                sqlx::query(
+                    "INSERT INTO blog_post_embeddings (post_id, site_id, model, dimensions, embedding, content_hash)
+                     VALUES ($1, $2, $3, $4, $5::vector, $6)
+                     ON CONFLICT (post_id) DO UPDATE SET
+                         site_id = EXCLUDED.site_id, model = EXCLUDED.model, dimensions = EXCLUDED.dimensions,
+                         embedding = EXCLUDED.embedding, content_hash = EXCLUDED.content_hash, updated_at = NOW()"
+                )
+                .bind(post_id)
+                .bind(site_id)
+                .bind(self.provider.model())
+                .bind(vector.len() as i32)
+                .bind(vector_literal(&vector))
+                .bind(hash)
+                .execute(&self.db)
+                .await?;
+            }
+        }
+
+        Ok(pending.len())
+    }
+
+    /// Embed every published post without a vector from the current model
+    ///
+    /// Returns the number of posts embedded.
+    pub async fn backfill(&self) -> Result<usize, EmbeddingError> {
+        let mut embedded = 0;
+        loop {
+            let batch: Vec<Uuid> = sqlx::query_scalar(
+                "SELECT p.id FROM blog_posts p
+                 LEFT JOIN blog_post_embeddings e ON e.post_id = p.id
+                 WHERE p.status = 'published' AND (e.post_id IS NULL OR e.model <> $1)
+                 ORDER BY p.published_at DESC
+                 LIMIT $2"
+            )
+            .bind(self.provider.model())
+            .bind(self.config.batch_size.max(1) as i64)
+            .fetch_all(&self.db)
+            .await?;
+            if batch.is_empty() {
+                return Ok(embedded);
+            }
+            embedded += self.sync(&batch).await?;
+        }
+    }
+
+    /// Ids of the published posts of `site` best matching `query`, best first
+    pub async fn search(&self, site: &Site, query: &str, limit: i64) -> Result<Vec<Uuid>, ServiceError> {
+        let vector = match self.provider.embed(&[query.to_string()]).await {
+            Ok(mut vectors) if vectors.len() == 1 => Some(vector_literal(&vectors.remove(0))),
+            Ok(_) => None,
+            Err(e) => {
+                tracing::warn!(site = %site.slug, "Search query not embedded, ranking by keywords only: {}", e);
+                None
+            }
+        };
+
+        self.rank(site, vector, query, KEYWORD_QUERY, None, limit).await
+    }
+
+    /// Ids of the published posts of `site` most like `post`, best first
+    pub async fn related(&self, site: &Site, post: &Post, limit: i64) -> Result<Vec<Uuid>, ServiceError> {
+        let vector: Option<String> = sqlx::query_scalar(
+            "SELECT embedding::text FROM blog_post_embeddings WHERE post_id = $1 AND model = $2"
+        )
+        .bind(post.id)
+        .bind(self.provider.model())
+        .fetch_optional(&self.db)
+        .await?;
+
+        self.rank(site, vector, &post.title, ANY_WORD_QUERY, Some(post.id), limit).await
+    }
+
+    /// Fuse the ranking by similarity to `vector` (when there is one) with
+    /// the full-text ranking of `keywords`, leaving out `exclude`
+    async fn rank(
+        &self,
+        site: &Site,
+        vector: Option<String>,
+        keywords: &str,
+        tsquery: &str,
+        exclude: Option<Uuid>,
+        limit: i64,
+    ) -> Result<Vec<Uuid>, ServiceError> {
+        let dimensions = self.config.dimensions;
+        let sql = format!(
+            r#"WITH semantic AS (
+                   SELECT post_id, ROW_NUMBER() OVER (ORDER BY distance) AS rank
+                   FROM (
+                       -- Ordered and limited on its own so the HNSW index is used
+                       SELECT e.post_id, e.embedding::vector({dimensions}) <=> $1::vector({dimensions}) AS distance
+                       FROM blog_post_embeddings e
+                       JOIN blog_posts p ON p.id = e.post_id
+                       WHERE $1::text IS NOT NULL AND e.site_id = $2 AND e.model = $3 AND e.dimensions = {dimensions}
+                         AND p.status = 'published' AND p.id IS DISTINCT FROM $5
+                       ORDER BY distance
+                       LIMIT $6
+                   ) nearest
+               ),
+               keyword AS (
+                   SELECT p.id AS post_id, ROW_NUMBER() OVER (ORDER BY ts_rank(doc, query) DESC) AS rank
+                   FROM blog_posts p,
+                        {tsquery} AS query,
+                        to_tsvector('english', p.title || ' ' || COALESCE(p.excerpt, '') || ' ' || p.content) AS doc
+                   WHERE p.site_id = $2 AND p.status = 'published' AND p.id IS DISTINCT FROM $5
+                     AND doc @@ query
+                   ORDER BY ts_rank(doc, query) DESC
+                   LIMIT $6
+               )
+               SELECT COALESCE(s.post_id, k.post_id)
+               FROM semantic s
+               FULL JOIN keyword k ON k.post_id = s.post_id
+               ORDER BY COALESCE(1.0 / ({RRF_K} + s.rank), 0) + COALESCE(1.0 / ({RRF_K} + k.rank), 0) DESC
+               LIMIT $7"#
+        );
+
+        let ids: Vec<Uuid> = sqlx::query_scalar(&sql)
+            .bind(vector)
+            .bind(site.id)
+            .bind(self.provider.model())
+            .bind(keywords)
+            .bind(exclude)
+            .bind(self.config.candidates.max(limit))
+            .bind(limit)
+            .fetch_all(&self.db)
+            .await?;
+        Ok(ids)
+    }
+}
+
+/// The text of a post that's embedded: title, excerpt and the content
+/// without markup, cut to `max_chars`
+pub fn document(title: &str, excerpt: Option<&str>, content: &str, max_chars: usize) -> String {
+    let mut text = title.to_string();
+    for part in [excerpt.map(plain_text), Some(plain_text(content))].into_iter().flatten() {
+        if !part.is_empty() {
+            text.push_str("\n\n");
+            text.push_str(&part);
+        }
+    }
+    text.chars().take(max_chars).collect()
+}
+
+/// `html` without its tags, with whitespace collapsed
+fn plain_text(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => {
+                in_tag = false;
+                text.push(' ');
+            }
+            c if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+
+    let text = text
+        .replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&");
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// pgvector's text form, `[0.1,0.2,...]`
+fn vector_literal(vector: &[f32]) -> String {
+    let values: Vec<String> = vector.iter().map(|x| x.to_string()).collect();
+    format!("[{}]", values.join(","))
+}
+
+// ============================================
+// Background Jobs
+// ============================================
+
+/// Queues posts whose vectors may be out of date for `run`
+pub struct EmbeddingListener {
+    changes: mpsc::UnboundedSender<Uuid>,
+}
+
+impl EmbeddingListener {
+    /// The listener and the queue it feeds
+    pub fn channel() -> (Self, mpsc::UnboundedReceiver<Uuid>) {
+        let (changes, receiver) = mpsc::unbounded_channel();
+        (Self { changes }, receiver)
+    }
+}
+
+#[async_trait]
+impl ContentListener for EmbeddingListener {
+    async fn on_change(&self, event: &ContentEvent) {
+        let relevant = event.object_type == ContentObject::Post
+            && matches!(
+                event.action,
+                ContentAction::Published | ContentAction::Updated | ContentAction::Unpublished | ContentAction::Trashed
+            );
+        if relevant {
+            // Closed only once the services are gone
+            let _ = self.changes.send(event.object_id);
+        }
+    }
+}
+
+/// Backfill, then keep the vectors of changed posts up to date until the
+/// services are dropped
+///
+/// Posts changed together (e.g. by a bulk edit) are embedded in one pass.
+pub async fn run(services: Weak<BlogServices>, mut changes: mpsc::UnboundedReceiver<Uuid>) {
+    if let Some(services) = services.upgrade() {
+        match services.embeddings.backfill().await {
+            Ok(0) => {}
+            Ok(count) => tracing::info!("Embedded {} published posts", count),
+            Err(e) => tracing::warn!("Embedding backfill failed: {}", e),
+        }
+    }
+
+    while let Some(post_id) = changes.recv().await {
+        let mut posts = HashSet::from([post_id]);
+        while let Ok(post_id) = changes.try_recv() {
+            posts.insert(post_id);
+        }
+
+        let Some(services) = services.upgrade() else {
+            return;
+        };
+        let posts: Vec<Uuid> = posts.into_iter().collect();
+        if let Err(e) = services.embeddings.sync(&posts).await {
+            tracing::warn!("Failed to embed {} posts: {}", posts.len(), e);
+        }
+    }
+}
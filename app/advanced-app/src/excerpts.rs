@@ -0,0 +1,352 @@
+//! Excerpts
+//!
+//! A post created without an excerpt gets one generated from its content:
+//!
+//! - everything before a `<!--more-->` marker, when the content has one
+//!   (`<!--more Keep reading-->` works too; the label is ignored)
+//! - otherwise the opening text, cut at the last sentence that fits within
+//!   [`AppConfig::excerpt_length`](crate::AppConfig) characters, or at a word
+//!   boundary followed by `…` when even the first sentence is too long
+//!
+//! Inline formatting (`<em>`, `<strong>`, `<a>`, `<code>`, ...) is kept and
+//! every tag left open by the cut is closed; block elements are flattened
+//! into the surrounding text and `script`/`style` content is dropped, so the
+//! result fits in a theme's `<p>`. Content is tokenized with `lol_html`, so
+//! malformed markup (unclosed or misnested tags, stray `<`) is read the way
+//! a browser would.
+
+use lol_html::html_content::Element;
+use lol_html::{doc_text, element, HtmlRewriter, Settings};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Elements kept in an excerpt
+const INLINE: &[&str] = &[
+    "a", "abbr", "b", "bdi", "bdo", "cite", "code", "data", "del", "dfn", "em", "i", "ins", "kbd", "mark", "q", "s",
+    "samp", "small", "span", "strong", "sub", "sup", "time", "u", "var",
+];
+
+/// Elements dropped together with their content
+const SKIPPED: &[&str] = &["script", "style", "template", "noscript", "iframe", "object", "svg", "math"];
+
+/// A sentence needs to cover this share of the length to be cut at instead of
+/// a word
+const MIN_SENTENCE_SHARE: usize = 2;
+
+/// Appended when the text is cut inside a sentence
+const ELLIPSIS: &str = "…";
+
+/// Excerpt of `content` of at most `max_chars` visible characters, unless a
+/// `<!--more-->` marker says where it ends
+pub fn generate(content: &str, max_chars: usize) -> String {
+    match split_more(content) {
+        Some(teaser) => Builder::new(usize::MAX).run(teaser),
+        None => Builder::new(max_chars.max(1)).run(content),
+    }
+}
+
+/// The content before the first `<!--more-->` marker
+pub fn split_more(content: &str) -> Option<&str> {
+    let mut from = 0;
+    while let Some(start) = content[from..].find("<!--").map(|i| from + i) {
+        let body = content[start + 4..].trim_start();
+        if body.get(..4).is_some_and(|word| word.eq_ignore_ascii_case("more")) {
+            let rest = &body[4..];
+            if rest.starts_with(|c: char| c.is_whitespace() || c == '-') && content[start..].contains("-->") {
+                return Some(&content[..start]);
+            }
+        }
+        from = start + 4;
+    }
+    None
+}
+
+/// A tag or text of the content, in document order
+enum Token {
+    Start {
+        name: String,
+        /// The start tag, rebuilt from its name and attributes
+        html: String,
+        /// Void or self-closing: no end tag follows
+        empty: bool,
+    },
+    End(String),
+    /// Raw text, character references included
+    Text(String),
+}
+
+/// Tags and text of `html`; comments are dropped
+fn tokens(html: &str) -> Vec<Token> {
+    let tokens = Rc::new(RefCell::new(Vec::new()));
+    let settings = Settings {
+        element_content_handlers: vec![element!("*", |el| {
+            let name = el.tag_name();
+            if let Some(handlers) = el.end_tag_handlers() {
+                let (tokens, name) = (tokens.clone(), name.clone());
+                handlers.push(Box::new(move |_| {
+                    tokens.borrow_mut().push(Token::End(name));
+                    Ok(())
+                }));
+            }
+            tokens.borrow_mut().push(Token::Start {
+                html: start_tag(el),
+                empty: !el.can_have_content(),
+                name,
+            });
+            Ok(())
+        })],
+        document_content_handlers: vec![doc_text!(|chunk| {
+            let text = chunk.as_str();
+            if !text.is_empty() {
+                let mut tokens = tokens.borrow_mut();
+                match tokens.last_mut() {
+                    Some(Token::Text(last)) => last.push_str(text),
+                    _ => tokens.push(Token::Text(text.to_string())),
+                }
+            }
+            Ok(())
+        })],
+        ..Settings::default()
+    };
+
+    let mut rewriter = HtmlRewriter::new(settings, |_: &[u8]| {});
+    // Markup is never rejected; only the rewriter's own limits fail
+    if let Err(e) = rewriter.write(html.as_bytes()).and_then(|()| rewriter.end()) {
+        tracing::warn!("Excerpt cut short, content couldn't be read: {}", e);
+    }
+    tokens.take()
+}
+
+/// `<name attr="value" ...>` of an element
+fn start_tag(el: &Element) -> String {
+    let mut html = format!("<{}", el.tag_name());
+    for attr in el.attributes() {
+        html.push_str(&format!(r#" {}="{}""#, attr.name(), attr.value().replace('"', "&quot;")));
+    }
+    html.push('>');
+    html
+}
+
+/// Where the excerpt may be cut
+struct CutPoint {
+    /// Byte length of the output at this point
+    at: usize,
+    /// Visible characters up to this point
+    visible: usize,
+    /// Inline elements open at this point
+    open: Vec<String>,
+}
+
+struct Builder {
+    max: usize,
+    out: String,
+    visible: usize,
+    open: Vec<String>,
+    /// Nesting depth inside a [`SKIPPED`] element
+    skipping: usize,
+    /// A space is due before the next visible character
+    pending_space: bool,
+    last_sentence: Option<CutPoint>,
+    last_word: Option<CutPoint>,
+}
+
+enum Flow {
+    Continue,
+    Full,
+}
+
+impl Builder {
+    fn new(max: usize) -> Self {
+        Self {
+            max,
+            out: String::new(),
+            visible: 0,
+            open: Vec::new(),
+            skipping: 0,
+            pending_space: false,
+            last_sentence: None,
+            last_word: None,
+        }
+    }
+
+    fn run(mut self, html: &str) -> String {
+        for token in tokens(html) {
+            let flow = match token {
+                Token::Start { name, html, empty } => self.start(name, &html, empty),
+                Token::End(name) => self.end(&name),
+                Token::Text(text) => self.text(&text),
+            };
+            if let Flow::Full = flow {
+                return self.cut();
+            }
+        }
+        self.finish()
+    }
+
+    fn start(&mut self, name: String, html: &str, empty: bool) -> Flow {
+        if SKIPPED.contains(&name.as_str()) {
+            if !empty {
+                self.skipping += 1;
+            }
+            return Flow::Continue;
+        }
+        if self.skipping > 0 {
+            return Flow::Continue;
+        }
+
+        if !INLINE.contains(&name.as_str()) {
+            self.separate();
+        } else if !empty {
+            self.flush_space();
+            self.out.push_str(html);
+            self.open.push(name);
+        }
+        Flow::Continue
+    }
+
+    fn end(&mut self, name: &str) -> Flow {
+        if SKIPPED.contains(&name) {
+            self.skipping = self.skipping.saturating_sub(1);
+            return Flow::Continue;
+        }
+        if self.skipping > 0 {
+            return Flow::Continue;
+        }
+
+        if !INLINE.contains(&name) {
+            self.separate();
+        } else if let Some(pos) = self.open.iter().rposition(|open| open == name) {
+            for open in self.open.drain(pos..).rev() {
+                self.out.push_str(&format!("</{}>", open));
+            }
+        }
+        Flow::Continue
+    }
+
+    /// Block boundaries and line breaks separate words
+    fn separate(&mut self) {
+        if self.visible > 0 {
+            self.pending_space = true;
+        }
+    }
+
+    fn text(&mut self, text: &str) -> Flow {
+        if self.skipping > 0 {
+            return Flow::Continue;
+        }
+
+        let mut chars = text.char_indices().peekable();
+        while let Some((i, c)) = chars.next() {
+            if c.is_whitespace() {
+                if self.visible > 0 {
+                    self.pending_space = true;
+                }
+                continue;
+            }
+
+            if self.pending_space {
+                self.mark_word();
+                if self.visible + 1 > self.max {
+                    return Flow::Full;
+                }
+                self.out.push(' ');
+                self.visible += 1;
+                self.pending_space = false;
+            }
+
+            // An entity counts as one character
+            let len = if c == '&' { entity_len(&text[i..]) } else { c.len_utf8() };
+            if self.visible + 1 > self.max {
+                return Flow::Full;
+            }
+            self.out.push_str(&text[i..i + len]);
+            self.visible += 1;
+            while chars.peek().is_some_and(|(j, _)| *j < i + len) {
+                chars.next();
+            }
+
+            if matches!(c, '.' | '!' | '?') {
+                let next = chars.peek().map(|(_, c)| *c);
+                if next.is_none_or(char::is_whitespace) {
+                    self.last_sentence = Some(self.cut_point());
+                }
+            }
+        }
+        Flow::Continue
+    }
+
+    fn flush_space(&mut self) {
+        if self.pending_space && self.visible < self.max {
+            self.mark_word();
+            self.out.push(' ');
+            self.visible += 1;
+        }
+        self.pending_space = false;
+    }
+
+    fn mark_word(&mut self) {
+        self.last_word = Some(self.cut_point());
+    }
+
+    fn cut_point(&self) -> CutPoint {
+        CutPoint {
+            at: self.out.len(),
+            visible: self.visible,
+            open: self.open.clone(),
+        }
+    }
+
+    /// Cut at the last sentence, or the last word when no long enough
+    /// sentence fits
+    fn cut(mut self) -> String {
+        let sentence = self
+            .last_sentence
+            .take()
+            .filter(|point| point.visible * MIN_SENTENCE_SHARE >= self.max);
+        let (point, ellipsis) = match (sentence, self.last_word.take()) {
+            (Some(point), _) => (point, false),
+            (None, Some(point)) => (point, true),
+            (None, None) => (self.cut_point(), true),
+        };
+
+        self.out.truncate(point.at);
+        let mut out = self.out.trim_end().to_string();
+        if ellipsis {
+            while out.ends_with([',', ';', ':']) && !ends_with_entity(&out) {
+                out.pop();
+            }
+            out.push_str(ELLIPSIS);
+        }
+        close(&mut out, &point.open);
+        out
+    }
+
+    fn finish(self) -> String {
+        let mut out = self.out.trim_end().to_string();
+        close(&mut out, &self.open);
+        out
+    }
+}
+
+fn close(out: &mut String, open: &[String]) {
+    for name in open.iter().rev() {
+        out.push_str(&format!("</{}>", name));
+    }
+}
+
+/// Byte length of the character reference at the start of `text`, or 1 for
+/// a bare `&`
+fn entity_len(text: &str) -> usize {
+    let body = &text[1..];
+    match body.find(';') {
+        Some(end) if end > 0 && end <= 32 && body[..end].chars().all(|c| c.is_ascii_alphanumeric() || c == '#') => {
+            end + 2
+        }
+        _ => 1,
+    }
+}
+
+/// Whether `text` ends with a character reference, whose `;` is no punctuation
+fn ends_with_entity(text: &str) -> bool {
+    text.rfind('&').is_some_and(|at| at + entity_len(&text[at..]) == text.len())
+}
@@ -0,0 +1,471 @@
+//! Static Site Export
+//!
+//! Renders a site's public pages through its theme to plain files, so the
+//! site can be served from object storage or a CDN with the API kept private
+//! ("headless + static"). The layout mirrors the site's URLs:
+//!
+//! | File | Page |
+//! |------|------|
+//! | `index.html`, `page/2/index.html` | Home |
+//! | `read/<slug>/index.html`, `posts/<slug>/amp/index.html` | Posts and their AMP versions |
+//! | `category/<slug>/index.html`, `tag/<slug>/...`, `author/<slug>/...` | Archives, paginated like home |
+//! | `feed.xml` | RSS feed |
+//! | `sitemap.xml` | Sitemap, with the same entries as `GET /sitemap.xml` |
+//! | `404.html` | Not-found page |
+//!
+//! Pages are rendered for an anonymous reader, so members-only posts are
+//! exported as teasers. Uploaded media keeps its storage or CDN URLs.
+//!
+//! `POST /admin/export` writes the files to storage under
+//! `<root>/<site-slug>/`; `GET /admin/export.zip` downloads them instead.
+//! Once a site has been exported to storage, `ExportListener` keeps the copy
+//! current: a published, edited, unpublished or deleted post has its own page
+//! rebuilt (or removed) along with the listings, without a full export.
+
+use crate::access::Viewer;
+use crate::activity::{ContentEvent, ContentListener};
+use crate::handlers::{feed, sitemap};
+use crate::models::*;
+use crate::services::ServiceError;
+use crate::sites::Site;
+use crate::theme::{RenderRequest, TemplateKind};
+use crate::BlogServices;
+use axum::async_trait;
+use rustpress_apps::prelude::Storage;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{Cursor, Write};
+use std::sync::{Arc, Weak};
+use tokio::sync::mpsc;
+use uuid::Uuid;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+/// Written beside each export; maps post IDs to the slugs of their pages
+const MANIFEST: &str = ".rustpress-export.json";
+
+/// `[app.export]` settings
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ExportConfig {
+    /// Storage prefix for exports, one directory per site
+    pub root: String,
+    /// Rebuild exported pages when posts change
+    pub incremental: bool,
+    /// Origin for sitemap links of sites without a host
+    /// (e.g. `https://blog.example.com`)
+    pub base_url: Option<String>,
+}
+
+impl Default for ExportConfig {
+    fn default() -> Self {
+        Self {
+            root: "static".to_string(),
+            incremental: true,
+            base_url: None,
+        }
+    }
+}
+
+/// A rendered file, relative to the export root
+#[derive(Debug, Clone)]
+pub struct StaticFile {
+    pub path: String,
+    pub body: Vec<u8>,
+}
+
+impl StaticFile {
+    fn new(path: impl Into<String>, body: impl Into<Vec<u8>>) -> Self {
+        Self {
+            path: path.into(),
+            body: body.into(),
+        }
+    }
+}
+
+/// Exported post pages, so later runs can remove renamed or withdrawn ones
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Manifest {
+    posts: HashMap<Uuid, String>,
+}
+
+/// Static site export service
+pub struct StaticExporter {
+    storage: Arc<dyn Storage>,
+    config: ExportConfig,
+}
+
+impl StaticExporter {
+    pub fn new(storage: Arc<dyn Storage>, config: ExportConfig) -> Self {
+        Self { storage, config }
+    }
+
+    /// Export every page of a site to storage, replacing an earlier export
+    pub async fn export(&self, services: &BlogServices, site: &Arc<Site>) -> Result<ExportReport, ServiceError> {
+        let previous = self.manifest(site).await.unwrap_or_default();
+        let (files, manifest) = self.render_site(services, site).await?;
+
+        for file in &files {
+            self.put(site, file).await?;
+        }
+        for (id, slug) in &previous.posts {
+            if manifest.posts.get(id) != Some(slug) {
+                self.remove_post(site, slug).await;
+            }
+        }
+        self.save_manifest(site, &manifest).await?;
+
+        tracing::info!(site = %site.slug, files = files.len(), "Exported static site");
+        Ok(ExportReport {
+            files: files.len(),
+            posts: manifest.posts.len(),
+            root: self.site_root(site),
+            url: self.storage.url(&self.storage_path(site, "index.html")),
+        })
+    }
+
+    /// Every page of a site as a zip archive
+    pub async fn export_zip(&self, services: &BlogServices, site: &Arc<Site>) -> Result<Vec<u8>, ServiceError> {
+        let (files, _) = self.render_site(services, site).await?;
+
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+        for file in &files {
+            zip.start_file(file.path.as_str(), options)
+                .map_err(|e| ServiceError::Storage(e.to_string()))?;
+            zip.write_all(&file.body)
+                .map_err(|e| ServiceError::Storage(e.to_string()))?;
+        }
+        let archive = zip.finish().map_err(|e| ServiceError::Storage(e.to_string()))?;
+        Ok(archive.into_inner())
+    }
+
+    /// Bring an exported site up to date after changes to `posts`
+    ///
+    /// Each post's page is rebuilt, or removed if it's no longer published,
+    /// and the listings are rebuilt if any page changed. An empty `posts`
+    /// (e.g. a renamed category) rebuilds just the listings. Sites that were
+    /// never exported to storage are left alone.
+    pub async fn update(&self, services: &BlogServices, site: &Arc<Site>, posts: &[Uuid]) -> Result<(), ServiceError> {
+        let Some(mut manifest) = self.manifest(site).await else {
+            return Ok(());
+        };
+        let request = render_request(site);
+
+        let mut changed = posts.is_empty();
+        for id in posts {
+            let previous = manifest.posts.remove(id);
+            let current = match services.posts.get_by_id(site, *id).await {
+                Ok(post) if post.status == PostStatus::Published => Some(post.slug),
+                Ok(_) | Err(ServiceError::NotFound(_)) => None,
+                Err(e) => return Err(e),
+            };
+
+            if let Some(slug) = &current {
+                let post = services.posts.get_by_slug(site, slug, &Viewer::anonymous()).await?;
+                for file in render_post(services, &post, &request).await? {
+                    self.put(site, &file).await?;
+                }
+                manifest.posts.insert(*id, slug.clone());
+            }
+            if let Some(slug) = &previous {
+                if current.as_ref() != Some(slug) {
+                    self.remove_post(site, slug).await;
+                }
+            }
+            changed |= previous.is_some() || current.is_some();
+        }
+
+        if changed {
+            for file in self.render_listings(services, site, &request).await? {
+                self.put(site, &file).await?;
+            }
+            self.save_manifest(site, &manifest).await?;
+            tracing::debug!(site = %site.slug, posts = posts.len(), "Updated static export");
+        }
+        Ok(())
+    }
+
+    /// Posts and listings of a site, with the manifest describing them
+    async fn render_site(&self, services: &BlogServices, site: &Arc<Site>) -> Result<(Vec<StaticFile>, Manifest), ServiceError> {
+        let request = render_request(site);
+        let mut files = Vec::new();
+        let mut manifest = Manifest::default();
+
+        // Same posts the sitemap lists
+        for (slug, _) in services.posts.sitemap_entries(site).await? {
+            let post = match services.posts.get_by_slug(site, &slug, &Viewer::anonymous()).await {
+                Ok(post) => post,
+                // Unpublished since the entries were cached
+                Err(ServiceError::NotFound(_)) => continue,
+                Err(e) => return Err(e),
+            };
+            files.extend(render_post(services, &post, &request).await?);
+            manifest.posts.insert(post.post.id, slug);
+        }
+
+        files.extend(self.render_listings(services, site, &request).await?);
+        Ok((files, manifest))
+    }
+
+    /// Home, archives, author pages, feed, sitemap and the not-found page
+    async fn render_listings(
+        &self,
+        services: &BlogServices,
+        site: &Arc<Site>,
+        request: &RenderRequest,
+    ) -> Result<Vec<StaticFile>, ServiceError> {
+        let mut files = render_archive(services, TemplateKind::Home, listing_query(), request).await?;
+
+        for category in services.categories.list(site).await? {
+            let query = PostQuery {
+                category: Some(category.slug.clone()),
+                ..listing_query()
+            };
+            let kind = TemplateKind::Category { slug: category.slug };
+            files.extend(render_archive(services, kind, query, request).await?);
+        }
+        for tag in services.tags.list(site).await? {
+            let query = PostQuery {
+                tag: Some(tag.slug.clone()),
+                ..listing_query()
+            };
+            let kind = TemplateKind::Tag { slug: tag.slug };
+            files.extend(render_archive(services, kind, query, request).await?);
+        }
+        for author in services.authors.list(site).await? {
+            files.extend(render_author_archive(services, &author, request).await?);
+        }
+
+        files.push(StaticFile::new("feed.xml", feed::render(services, site).await?));
+        files.push(StaticFile::new(
+            "sitemap.xml",
+            sitemap::render(services, site, &self.origin(site)).await?,
+        ));
+        files.push(StaticFile::new("404.html", services.theme.render_not_found(request).await?));
+        Ok(files)
+    }
+
+    /// Origin for absolute links: the site's host, else the configured base URL
+    fn origin(&self, site: &Site) -> String {
+        match (&site.host, &self.config.base_url) {
+            (Some(host), _) => format!("https://{}", host),
+            (None, Some(base_url)) => base_url.trim_end_matches('/').to_string(),
+            (None, None) => String::new(),
+        }
+    }
+
+    fn site_root(&self, site: &Site) -> String {
+        format!("{}/{}", self.config.root.trim_end_matches('/'), site.slug)
+    }
+
+    fn storage_path(&self, site: &Site, path: &str) -> String {
+        format!("{}/{}", self.site_root(site), path)
+    }
+
+    async fn put(&self, site: &Site, file: &StaticFile) -> Result<(), ServiceError> {
+        self.storage
+            .put(&self.storage_path(site, &file.path), &file.body)
+            .await
+            .map_err(|e| ServiceError::Storage(e.to_string()))
+    }
+
+    /// Remove a post's page and its AMP version
+    async fn remove_post(&self, site: &Site, slug: &str) {
+        let slug = slug.to_string();
+        for kind in [TemplateKind::Single { slug: slug.clone() }, TemplateKind::Amp { slug }] {
+            let path = self.storage_path(site, &page_file(&kind, 1));
+            if let Err(e) = self.storage.delete(&path).await {
+                tracing::warn!(site = %site.slug, "Failed to remove exported page {}: {}", path, e);
+            }
+        }
+    }
+
+    /// The site's manifest; `None` if it was never exported to storage
+    async fn manifest(&self, site: &Site) -> Option<Manifest> {
+        let data = self.storage.get(&self.storage_path(site, MANIFEST)).await.ok()?;
+        serde_json::from_slice(&data).ok()
+    }
+
+    async fn save_manifest(&self, site: &Site, manifest: &Manifest) -> Result<(), ServiceError> {
+        let data = serde_json::to_vec(manifest).map_err(|e| ServiceError::Storage(e.to_string()))?;
+        self.storage
+            .put(&self.storage_path(site, MANIFEST), &data)
+            .await
+            .map_err(|e| ServiceError::Storage(e.to_string()))
+    }
+}
+
+// ============================================
+// Incremental Export
+// ============================================
+
+/// Queues post and category changes for `run_incremental`
+pub struct ExportListener {
+    changes: mpsc::UnboundedSender<ContentEvent>,
+}
+
+impl ExportListener {
+    /// The listener and the queue it feeds
+    pub fn channel() -> (Self, mpsc::UnboundedReceiver<ContentEvent>) {
+        let (changes, receiver) = mpsc::unbounded_channel();
+        (Self { changes }, receiver)
+    }
+}
+
+#[async_trait]
+impl ContentListener for ExportListener {
+    async fn on_change(&self, event: &ContentEvent) {
+        let relevant = match event.object_type {
+            ContentObject::Post => matches!(
+                event.action,
+                ContentAction::Published | ContentAction::Updated | ContentAction::Unpublished | ContentAction::Trashed
+            ),
+            ContentObject::Category => true,
+            ContentObject::Comment => false,
+        };
+        if relevant {
+            // Closed only once the services are gone
+            let _ = self.changes.send(event.clone());
+        }
+    }
+}
+
+/// Apply queued changes to exported sites until the services are dropped
+///
+/// Changes that arrive together are applied in one pass per site, so a bulk
+/// edit rebuilds the listings once.
+pub async fn run_incremental(services: Weak<BlogServices>, mut changes: mpsc::UnboundedReceiver<ContentEvent>) {
+    while let Some(event) = changes.recv().await {
+        let mut batch: HashMap<Uuid, Vec<Uuid>> = HashMap::new();
+        let mut next = Some(event);
+        while let Some(event) = next {
+            let posts = batch.entry(event.site_id).or_default();
+            if event.object_type == ContentObject::Post && !posts.contains(&event.object_id) {
+                posts.push(event.object_id);
+            }
+            next = changes.try_recv().ok();
+        }
+
+        let Some(services) = services.upgrade() else {
+            return;
+        };
+        for (site_id, posts) in batch {
+            let Some(site) = services.sites.get(site_id).await else {
+                continue;
+            };
+            if let Err(e) = services.export.update(&services, &site, &posts).await {
+                tracing::warn!(site = %site.slug, "Incremental static export failed: {}", e);
+            }
+        }
+    }
+}
+
+// ============================================
+// Rendering
+// ============================================
+
+/// Pages are rendered as an anonymous visitor would see them
+fn render_request(site: &Arc<Site>) -> RenderRequest {
+    RenderRequest {
+        site: site.clone(),
+        user: None,
+        user_agent: None,
+        static_paths: true,
+    }
+}
+
+/// First page of a listing, at the site's page size
+fn listing_query() -> PostQuery {
+    PostQuery {
+        page: Some(1),
+        per_page: None,
+        category: None,
+        tag: None,
+        author: None,
+        status: None,
+        sort: None,
+        order: None,
+    }
+}
+
+/// A post's page, and its AMP version when AMP is enabled
+async fn render_post(
+    services: &BlogServices,
+    post: &PostWithRelations,
+    request: &RenderRequest,
+) -> Result<Vec<StaticFile>, ServiceError> {
+    let slug = post.post.slug.clone();
+    let kind = TemplateKind::Single { slug: slug.clone() };
+    let path = page_file(&kind, 1);
+    let html = services.theme.render_post(kind, post, request).await?;
+    let mut files = vec![StaticFile::new(path, html)];
+
+    if services.amp.enabled() {
+        let content = services.amp.content(&post.post.content).await?;
+        let html = services.theme.render_amp(post, &content, request).await?;
+        files.push(StaticFile::new(page_file(&TemplateKind::Amp { slug }, 1), html));
+    }
+    Ok(files)
+}
+
+/// Every page of an archive
+async fn render_archive(
+    services: &BlogServices,
+    kind: TemplateKind,
+    mut query: PostQuery,
+    request: &RenderRequest,
+) -> Result<Vec<StaticFile>, ServiceError> {
+    let mut files = Vec::new();
+    loop {
+        let posts = services.posts.list_published(&request.site, &query, &Viewer::anonymous()).await?;
+        let html = services.theme.render_archive(kind.clone(), &posts, request).await?;
+        files.push(StaticFile::new(page_file(&kind, query.page()), html));
+
+        if !posts.pagination.has_next {
+            return Ok(files);
+        }
+        query.page = Some(query.page() + 1);
+    }
+}
+
+/// Every page of an author's archive
+async fn render_author_archive(
+    services: &BlogServices,
+    author: &AuthorProfile,
+    request: &RenderRequest,
+) -> Result<Vec<StaticFile>, ServiceError> {
+    let kind = TemplateKind::Author { slug: author.slug.clone() };
+    let mut query = PostQuery {
+        author: Some(author.id),
+        ..listing_query()
+    };
+
+    let mut files = Vec::new();
+    loop {
+        let posts = services.posts.list_published(&request.site, &query, &Viewer::anonymous()).await?;
+        let html = services.theme.render_author_archive(author, &posts, request).await?;
+        files.push(StaticFile::new(page_file(&kind, query.page()), html));
+
+        if !posts.pagination.has_next {
+            return Ok(files);
+        }
+        query.page = Some(query.page() + 1);
+    }
+}
+
+/// File holding page `page` of the page at `kind`'s path
+fn page_file(kind: &TemplateKind, page: i64) -> String {
+    let dir = kind.path().unwrap_or_default();
+    let dir = dir.trim_matches('/');
+    let dir = if page > 1 {
+        format!("{}/page/{}", dir, page)
+    } else {
+        dir.to_string()
+    };
+
+    match dir.trim_start_matches('/') {
+        "" => "index.html".to_string(),
+        dir => format!("{}/index.html", dir),
+    }
+}
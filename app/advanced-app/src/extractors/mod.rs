@@ -1,19 +1,23 @@
 //! Custom Axum Extractors
 //!
-//! Extractors for authentication and request metadata.
+//! Extractors for authentication, the current site and request metadata.
+//! Request bodies use `ValidatedJson` from the auth plugin so validation
+//! failures carry the same per-field problem details across the API.
 
 use axum::{
     async_trait,
     extract::FromRequestParts,
     http::{request::Parts, StatusCode},
     response::{IntoResponse, Response},
-    Json,
 };
-use jsonwebtoken::{decode, DecodingKey, Validation};
-use std::env;
 use uuid::Uuid;
 
 use crate::auth::AccessTokenClaims;
+use crate::middleware::auth::jwt_decoding;
+use crate::models::ProblemDetails;
+
+pub use crate::sites::CurrentSite;
+pub use rustpress_auth::ValidatedJson;
 
 /// Authenticated user information extracted from JWT claims
 #[derive(Debug, Clone)]
@@ -79,60 +83,26 @@ where
             .and_then(|h| h.to_str().ok());
 
         let header = auth_header.ok_or_else(|| {
-            (
-                StatusCode::UNAUTHORIZED,
-                Json(serde_json::json!({
-                    "error": "unauthorized",
-                    "message": "Authentication required"
-                })),
-            )
+            ProblemDetails::new(StatusCode::UNAUTHORIZED, "unauthorized")
+                .detail("Authentication required")
                 .into_response()
         })?;
 
         if !header.starts_with("Bearer ") {
-            return Err((
-                StatusCode::UNAUTHORIZED,
-                Json(serde_json::json!({
-                    "error": "unauthorized",
-                    "message": "Invalid authorization header format"
-                })),
-            )
+            return Err(ProblemDetails::new(StatusCode::UNAUTHORIZED, "unauthorized")
+                .detail("Invalid authorization header format")
                 .into_response());
         }
 
         let token = header.trim_start_matches("Bearer ");
 
-        // Get JWT configuration from environment
-        let secret = env::var("JWT_SECRET").map_err(|_| {
-            tracing::error!("JWT_SECRET environment variable not set");
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(serde_json::json!({
-                    "error": "configuration_error",
-                    "message": "Server configuration error"
-                })),
-            )
-                .into_response()
-        })?;
-
-        let issuer = env::var("JWT_ISSUER").unwrap_or_else(|_| "rustpress".to_string());
-        let audience = env::var("JWT_AUDIENCE").unwrap_or_else(|_| "rustpress-api".to_string());
-
-        let decoding_key = DecodingKey::from_secret(secret.as_bytes());
-        let mut validation = Validation::default();
-        validation.set_issuer(&[issuer]);
-        validation.set_audience(&[audience]);
+        let (keys, validation) = jwt_decoding().await?;
 
         let token_data =
-            decode::<AccessTokenClaims>(token, &decoding_key, &validation).map_err(|e| {
+            keys.decode::<AccessTokenClaims>(token, &validation).map_err(|e| {
                 tracing::debug!("JWT validation failed: {:?}", e);
-                (
-                    StatusCode::UNAUTHORIZED,
-                    Json(serde_json::json!({
-                        "error": "invalid_token",
-                        "message": "Invalid or expired token"
-                    })),
-                )
+                ProblemDetails::new(StatusCode::UNAUTHORIZED, "invalid_token")
+                    .detail("Invalid or expired token")
                     .into_response()
             })?;
 
@@ -154,7 +124,7 @@ where
 }
 
 /// Client information (IP, user agent)
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct ClientInfo {
     pub ip: Option<String>,
     pub user_agent: Option<String>,
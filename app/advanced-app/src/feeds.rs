@@ -0,0 +1,244 @@
+//! Feed and Sitemap Caching
+//!
+//! `GET /feed` and `GET /sitemap.xml` serve documents stored in the data
+//! cache instead of querying posts, categories, tags and authors on every hit.
+//! A stored document is served as is while it's fresh; once it's older than
+//! `fresh_secs`, or the site's content changed after it was generated, it's
+//! still served and a background task replaces it (stale-while-revalidate).
+//! Only a site's first request, or one after `max_stale_secs` without any,
+//! waits for the document to be generated.
+//!
+//! [`FeedListener`] follows post and category changes: [`run`] marks the
+//! site's documents stale and regenerates the RSS feed and the sitemap for
+//! the site's host right away, so readers see a published post without
+//! waiting for the next request. Sitemaps for other origins (sites without a
+//! host are answered on the request's) are refreshed when next requested.
+
+use crate::activity::{ContentEvent, ContentListener};
+use crate::cache::Cache;
+use crate::handlers::{feed, sitemap};
+use crate::models::*;
+use crate::services::ServiceError;
+use crate::sites::Site;
+use crate::BlogServices;
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex, Weak};
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+/// `[app.feeds]` settings
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct FeedConfig {
+    /// Serve feeds and sitemaps from the cache; otherwise render per request
+    pub cached: bool,
+    /// Age in seconds after which a document is regenerated in the background
+    pub fresh_secs: u64,
+    /// Seconds a stored document is kept, and may be served stale
+    pub max_stale_secs: u64,
+}
+
+impl Default for FeedConfig {
+    fn default() -> Self {
+        Self {
+            cached: true,
+            fresh_secs: 300,
+            max_stale_secs: 86400,
+        }
+    }
+}
+
+/// A generated document of a site
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Feed {
+    Rss,
+    /// Sitemap with links under `origin`
+    Sitemap { origin: String },
+}
+
+impl Feed {
+    fn key(&self) -> String {
+        match self {
+            Feed::Rss => "feeds:rss".to_string(),
+            Feed::Sitemap { origin } => format!("feeds:sitemap:{}", origin),
+        }
+    }
+}
+
+/// Cache entry of a generated document
+#[derive(Debug, Serialize, Deserialize)]
+struct FeedDocument {
+    xml: String,
+    /// When rendering started, so changes made during it count as newer
+    generated_at: DateTime<Utc>,
+}
+
+/// Generated feeds and sitemaps, kept in the data cache
+pub struct FeedCache {
+    cache: Arc<dyn Cache>,
+    config: FeedConfig,
+    /// Keys being regenerated by this process
+    refreshing: Mutex<HashSet<String>>,
+}
+
+impl FeedCache {
+    pub fn new(cache: Arc<dyn Cache>, config: FeedConfig) -> Self {
+        Self {
+            cache,
+            config,
+            refreshing: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// The document, from the cache when there is one
+    ///
+    /// Stale documents are returned and regenerated in the background.
+    pub async fn get(&self, services: &Arc<BlogServices>, site: &Arc<Site>, feed: Feed) -> Result<String, ServiceError> {
+        if !self.config.cached {
+            return Ok(generate(services, site, &feed).await?.xml);
+        }
+
+        let key = site.cache_key(&feed.key());
+        if let Some(document) = self.cache.get::<FeedDocument>(&key).await {
+            if !self.is_fresh(site, &document).await {
+                self.refresh(services, site, feed);
+            }
+            return Ok(document.xml);
+        }
+
+        let document = self
+            .cache
+            .get_or_load(&key, Some(self.config.max_stale_secs), || generate(services, site, &feed))
+            .await?;
+        Ok(document.xml)
+    }
+
+    /// Regenerate a stored document now
+    pub async fn regenerate(&self, services: &BlogServices, site: &Site, feed: &Feed) -> Result<(), ServiceError> {
+        let document = generate(services, site, feed).await?;
+        self.cache
+            .set(&site.cache_key(&feed.key()), &document, Some(self.config.max_stale_secs))
+            .await;
+        Ok(())
+    }
+
+    /// Mark every stored document of a site stale
+    pub async fn mark_changed(&self, site: &Site) {
+        self.cache
+            .set(&site.cache_key(CHANGED), &Utc::now(), Some(self.config.max_stale_secs))
+            .await;
+    }
+
+    async fn is_fresh(&self, site: &Site, document: &FeedDocument) -> bool {
+        let age = Utc::now().signed_duration_since(document.generated_at);
+        if age.num_seconds() >= self.config.fresh_secs as i64 {
+            return false;
+        }
+        match self.cache.get::<DateTime<Utc>>(&site.cache_key(CHANGED)).await {
+            Some(changed_at) => changed_at < document.generated_at,
+            None => true,
+        }
+    }
+
+    /// Regenerate a document in the background, once per key at a time
+    fn refresh(&self, services: &Arc<BlogServices>, site: &Arc<Site>, feed: Feed) {
+        let key = site.cache_key(&feed.key());
+        if !self.refreshing.lock().unwrap().insert(key.clone()) {
+            return;
+        }
+
+        let services = services.clone();
+        let site = site.clone();
+        tokio::spawn(async move {
+            if let Err(e) = services.feeds.regenerate(&services, &site, &feed).await {
+                tracing::warn!(site = %site.slug, "Failed to regenerate {}: {}", key, e);
+            }
+            services.feeds.refreshing.lock().unwrap().remove(&key);
+        });
+    }
+}
+
+/// Time of a site's last post or category change
+const CHANGED: &str = "feeds:changed";
+
+async fn generate(services: &BlogServices, site: &Site, feed: &Feed) -> Result<FeedDocument, ServiceError> {
+    let generated_at = Utc::now();
+    let xml = match feed {
+        Feed::Rss => feed::render(services, site).await?,
+        Feed::Sitemap { origin } => sitemap::render(services, site, origin).await?,
+    };
+    Ok(FeedDocument { xml, generated_at })
+}
+
+// ============================================
+// Regeneration
+// ============================================
+
+/// Queues the sites whose feeds or sitemaps changed for `run`
+pub struct FeedListener {
+    changes: mpsc::UnboundedSender<Uuid>,
+}
+
+impl FeedListener {
+    /// The listener and the queue it feeds
+    pub fn channel() -> (Self, mpsc::UnboundedReceiver<Uuid>) {
+        let (changes, receiver) = mpsc::unbounded_channel();
+        (Self { changes }, receiver)
+    }
+}
+
+#[async_trait]
+impl ContentListener for FeedListener {
+    async fn on_change(&self, event: &ContentEvent) {
+        let relevant = match event.object_type {
+            ContentObject::Post => matches!(
+                event.action,
+                ContentAction::Published | ContentAction::Updated | ContentAction::Unpublished | ContentAction::Trashed
+            ),
+            // Categories are listed in the sitemap
+            ContentObject::Category => true,
+            ContentObject::Comment => false,
+        };
+        if relevant {
+            // Closed only once the services are gone
+            let _ = self.changes.send(event.site_id);
+        }
+    }
+}
+
+/// Regenerate the documents of changed sites until the services are dropped
+///
+/// Sites changed together (e.g. by a bulk edit) are regenerated once.
+pub async fn run(services: Weak<BlogServices>, mut changes: mpsc::UnboundedReceiver<Uuid>) {
+    while let Some(site_id) = changes.recv().await {
+        let mut sites = HashSet::from([site_id]);
+        while let Ok(site_id) = changes.try_recv() {
+            sites.insert(site_id);
+        }
+
+        let Some(services) = services.upgrade() else {
+            return;
+        };
+        for site_id in sites {
+            let Some(site) = services.sites.get(site_id).await else {
+                continue;
+            };
+            services.feeds.mark_changed(&site).await;
+
+            let mut feeds = vec![Feed::Rss];
+            if let Some(host) = &site.host {
+                feeds.push(Feed::Sitemap {
+                    origin: format!("https://{}", host),
+                });
+            }
+            for feed in &feeds {
+                if let Err(e) = services.feeds.regenerate(&services, &site, feed).await {
+                    tracing::warn!(site = %site.slug, "Failed to regenerate {}: {}", feed.key(), e);
+                }
+            }
+        }
+    }
+}
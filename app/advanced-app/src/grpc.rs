@@ -0,0 +1,512 @@
+//! gRPC Server
+//!
+//! For internal services that prefer gRPC over REST, `[app.grpc]` serves
+//! (off by default, on `127.0.0.1:50051`):
+//!
+//! - `rustpress.blog.v1.Posts`: published posts, as `GET /posts` and
+//!   `GET /posts/:slug` return them
+//! - `rustpress.auth.v1.Tokens`: access tokens, checked as the auth middleware
+//!   checks them
+//! - `rustpress.analytics.v1.Ingest`: backend events, as the analytics
+//!   plugin's `POST /collect` takes them, while that plugin is active
+//!
+//! Calls go through the same services as the REST API, so access rules,
+//! validation and results match. The definitions are in `proto/`; the
+//! messages, servers and clients generated from them are in [`proto`].
+//! Members-only posts need `authorization: Bearer <token>` metadata and
+//! ingestion an `x-api-key`, as over HTTP. The server has no TLS, so keep it
+//! on a private network.
+
+use crate::access::Viewer;
+use crate::auth::AccessTokenClaims;
+use crate::extractors::User;
+use crate::models::{PaginationMeta, PostQuery, PostWithRelations};
+use crate::services::ServiceError;
+use crate::sites::Site;
+use crate::BlogServices;
+use chrono::{DateTime, Utc};
+use proto::analytics::ingest_server::{Ingest, IngestServer};
+use proto::auth::tokens_server::{Tokens, TokensServer};
+use proto::blog::posts_server::{Posts, PostsServer};
+use rustpress_analytics::models::CollectEvent;
+use rustpress_analytics::services::{IngestError, IngestService, MAX_COLLECT_BATCH};
+use rustpress_auth::keys::{access_validation, JwtKeys};
+use rustpress_auth::{AuthError, Config};
+use serde::Deserialize;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::sync::oneshot;
+use tonic::transport::server::{Router, TcpIncoming};
+use tonic::{Request, Response, Status};
+use uuid::Uuid;
+
+/// Code generated from `proto/`
+pub mod proto {
+    pub mod blog {
+        tonic::include_proto!("rustpress.blog.v1");
+    }
+
+    pub mod auth {
+        tonic::include_proto!("rustpress.auth.v1");
+    }
+
+    pub mod analytics {
+        tonic::include_proto!("rustpress.analytics.v1");
+    }
+}
+
+/// `[app.grpc]` settings
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct GrpcConfig {
+    pub enabled: bool,
+    /// Address the server listens on
+    pub listen: SocketAddr,
+}
+
+impl Default for GrpcConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            listen: SocketAddr::from(([127, 0, 0, 1], 50051)),
+        }
+    }
+}
+
+/// Every service, ready to serve
+pub fn router(services: Arc<BlogServices>) -> Router {
+    tonic::transport::Server::builder()
+        .add_service(PostsServer::new(PostsApi::new(services)))
+        .add_service(TokensServer::new(TokensApi))
+        .add_service(IngestServer::new(IngestApi::shared()))
+}
+
+/// A running server; dropping it shuts the server down once the calls in
+/// progress are answered
+pub struct GrpcServer {
+    _shutdown: oneshot::Sender<()>,
+}
+
+/// Serve every service on `config.listen`
+pub fn start(services: Arc<BlogServices>, config: &GrpcConfig) -> Result<GrpcServer, String> {
+    let incoming = TcpIncoming::new(config.listen, true, None)
+        .map_err(|e| format!("gRPC server cannot listen on {}: {}", config.listen, e))?;
+    let (shutdown, stopped) = oneshot::channel::<()>();
+
+    let listen = config.listen;
+    tokio::spawn(async move {
+        tracing::info!("gRPC server listening on {}", listen);
+        let stopped = async {
+            let _ = stopped.await;
+        };
+        if let Err(e) = router(services).serve_with_incoming_shutdown(incoming, stopped).await {
+            tracing::error!("gRPC server failed: {}", e);
+        }
+    });
+    Ok(GrpcServer { _shutdown: shutdown })
+}
+
+// ============================================
+// Posts
+// ============================================
+
+/// `rustpress.blog.v1.Posts`
+pub struct PostsApi {
+    services: Arc<BlogServices>,
+}
+
+impl PostsApi {
+    pub fn new(services: Arc<BlogServices>) -> Self {
+        Self { services }
+    }
+
+    async fn site(&self, selector: Option<proto::blog::SiteSelector>) -> Result<Arc<Site>, Status> {
+        let selector = selector.unwrap_or_default();
+        if !selector.site_id.is_empty() {
+            let id = parse_uuid("site.site_id", &selector.site_id)?;
+            return self
+                .services
+                .sites
+                .get(id)
+                .await
+                .ok_or_else(|| Status::not_found(format!("Site not found: {}", id)));
+        }
+
+        let host = Some(selector.host.as_str()).filter(|host| !host.is_empty());
+        self.services
+            .sites
+            .resolve(host, "/")
+            .await
+            .map(|(site, _)| site)
+            .ok_or_else(|| Status::not_found("No site serves this host"))
+    }
+
+    /// The reader: the user of a bearer token, or a visitor
+    async fn viewer(&self, token: Option<&str>) -> Result<Viewer, Status> {
+        let user = match token {
+            Some(token) => match decode_token(token).await? {
+                Ok(claims) => Some(User::from_claims(&claims)),
+                Err(_) => return Err(Status::unauthenticated("Invalid or expired token")),
+            },
+            None => None,
+        };
+        Ok(self.services.access.viewer(user).await?)
+    }
+}
+
+#[tonic::async_trait]
+impl Posts for PostsApi {
+    async fn list_posts(
+        &self,
+        request: Request<proto::blog::ListPostsRequest>,
+    ) -> Result<Response<proto::blog::ListPostsResponse>, Status> {
+        let token = bearer(&request).map(String::from);
+        let request = request.into_inner();
+
+        let query = PostQuery {
+            page: Some(request.page).filter(|page| *page > 0),
+            per_page: Some(request.per_page).filter(|per_page| *per_page > 0),
+            category: request.category,
+            tag: request.tag,
+            author: request
+                .author_id
+                .as_deref()
+                .map(|id| parse_uuid("author_id", id))
+                .transpose()?,
+            status: None,
+            sort: request.sort,
+            order: request.order,
+        };
+        let site = self.site(request.site).await?;
+        let viewer = self.viewer(token.as_deref()).await?;
+        let page = self.services.posts.list_published(&site, &query, &viewer).await?;
+
+        Ok(Response::new(proto::blog::ListPostsResponse {
+            posts: page.data.into_iter().map(Into::into).collect(),
+            pagination: Some(page.pagination.into()),
+        }))
+    }
+
+    async fn get_post(&self, request: Request<proto::blog::GetPostRequest>) -> Result<Response<proto::blog::Post>, Status> {
+        let token = bearer(&request).map(String::from);
+        let request = request.into_inner();
+
+        let site = self.site(request.site).await?;
+        let viewer = self.viewer(token.as_deref()).await?;
+        let post = self.services.posts.get_by_slug(&site, &request.slug, &viewer).await?;
+        Ok(Response::new(post.into()))
+    }
+}
+
+impl From<PostWithRelations> for proto::blog::Post {
+    fn from(post: PostWithRelations) -> Self {
+        let PostWithRelations {
+            post,
+            author,
+            categories,
+            tags,
+            locked,
+        } = post;
+        Self {
+            id: post.id.to_string(),
+            site_id: post.site_id.to_string(),
+            title: post.title,
+            slug: post.slug,
+            content: post.content,
+            excerpt: post.excerpt,
+            featured_image: post.featured_image,
+            published_at: post.published_at.map(timestamp),
+            view_count: post.view_count,
+            comment_count: post.comment_count,
+            meta_title: post.meta_title,
+            meta_description: post.meta_description,
+            created_at: Some(timestamp(post.created_at)),
+            updated_at: Some(timestamp(post.updated_at)),
+            author: Some(proto::blog::Author {
+                id: author.id.to_string(),
+                name: author.name,
+                avatar: author.avatar,
+                bio: author.bio,
+            }),
+            categories: categories
+                .into_iter()
+                .map(|category| proto::blog::Term {
+                    id: category.id.to_string(),
+                    name: category.name,
+                    slug: category.slug,
+                })
+                .collect(),
+            tags: tags
+                .into_iter()
+                .map(|tag| proto::blog::Term {
+                    id: tag.id.to_string(),
+                    name: tag.name,
+                    slug: tag.slug,
+                })
+                .collect(),
+            locked,
+        }
+    }
+}
+
+impl From<PaginationMeta> for proto::blog::Pagination {
+    fn from(meta: PaginationMeta) -> Self {
+        Self {
+            total: meta.total,
+            page: meta.page,
+            per_page: meta.per_page,
+            total_pages: meta.total_pages,
+            has_next: meta.has_next,
+            has_prev: meta.has_prev,
+        }
+    }
+}
+
+// ============================================
+// Tokens
+// ============================================
+
+/// `rustpress.auth.v1.Tokens`
+pub struct TokensApi;
+
+#[tonic::async_trait]
+impl Tokens for TokensApi {
+    async fn validate_token(
+        &self,
+        request: Request<proto::auth::ValidateTokenRequest>,
+    ) -> Result<Response<proto::auth::ValidateTokenResponse>, Status> {
+        let token = request.into_inner().token;
+        let token = token.strip_prefix("Bearer ").unwrap_or(&token).trim();
+
+        let response = match decode_token(token).await? {
+            Ok(claims) => proto::auth::ValidateTokenResponse {
+                valid: true,
+                error: String::new(),
+                claims: Some(claims.into()),
+            },
+            Err(error) => proto::auth::ValidateTokenResponse {
+                valid: false,
+                error,
+                claims: None,
+            },
+        };
+        Ok(Response::new(response))
+    }
+}
+
+impl From<AccessTokenClaims> for proto::auth::Claims {
+    fn from(claims: AccessTokenClaims) -> Self {
+        Self {
+            user_id: claims.sub.to_string(),
+            email: claims.email,
+            name: claims.name,
+            role: claims.role,
+            token_id: claims.jti.to_string(),
+            issued_at: Some(prost_types::Timestamp {
+                seconds: claims.iat,
+                nanos: 0,
+            }),
+            expires_at: Some(prost_types::Timestamp {
+                seconds: claims.exp,
+                nanos: 0,
+            }),
+            locale: claims.locale,
+        }
+    }
+}
+
+/// Check an access token with the current keys; the inner error says why a
+/// token is rejected, the outer one is a server configuration problem
+async fn decode_token(token: &str) -> Result<Result<AccessTokenClaims, String>, Status> {
+    let configuration_error = |e: AuthError| {
+        tracing::error!("JWT configuration: {}", e);
+        Status::internal("Server configuration error")
+    };
+
+    let keys = JwtKeys::shared().await.map_err(configuration_error)?;
+    let validation = Config::load()
+        .map_err(AuthError::from)
+        .and_then(|config| access_validation(&config))
+        .map_err(configuration_error)?;
+    Ok(keys
+        .decode::<AccessTokenClaims>(token, &validation)
+        .map(|data| data.claims)
+        .map_err(|e| e.to_string()))
+}
+
+/// Token of `authorization: Bearer <token>` metadata
+fn bearer<T>(request: &Request<T>) -> Option<&str> {
+    request
+        .metadata()
+        .get("authorization")?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+}
+
+// ============================================
+// Analytics Ingestion
+// ============================================
+
+/// `rustpress.analytics.v1.Ingest`
+pub struct IngestApi {
+    ingest: Option<Arc<IngestService>>,
+}
+
+impl IngestApi {
+    /// Ingest through the analytics plugin active at the time of each call
+    pub fn shared() -> Self {
+        Self { ingest: None }
+    }
+
+    /// Ingest through `ingest`
+    pub fn new(ingest: Arc<IngestService>) -> Self {
+        Self { ingest: Some(ingest) }
+    }
+}
+
+#[tonic::async_trait]
+impl Ingest for IngestApi {
+    async fn collect(
+        &self,
+        request: Request<proto::analytics::CollectRequest>,
+    ) -> Result<Response<proto::analytics::CollectResponse>, Status> {
+        let ingest = self
+            .ingest
+            .clone()
+            .or_else(rustpress_analytics::shared_ingest)
+            .ok_or_else(|| Status::unavailable("Analytics is not active"))?;
+
+        let key = request
+            .metadata()
+            .get(rustpress_analytics::api::API_KEY_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .map(String::from)
+            .ok_or_else(|| Status::unauthenticated("x-api-key metadata required"))?;
+        if !ingest.authorize(&key).await.map_err(ingest_error)? {
+            return Err(Status::unauthenticated("Invalid API key"));
+        }
+
+        let events = request.into_inner().events;
+        if events.is_empty() || events.len() > MAX_COLLECT_BATCH {
+            return Err(Status::invalid_argument(format!(
+                "events must hold 1 to {} events",
+                MAX_COLLECT_BATCH
+            )));
+        }
+        let events = events
+            .into_iter()
+            .enumerate()
+            .map(|(index, event)| collect_event(index, event))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let collected = ingest.collect(&events).await.map_err(ingest_error)?;
+        Ok(Response::new(proto::analytics::CollectResponse {
+            accepted: collected.accepted as u32,
+            rejected: collected
+                .rejected
+                .into_iter()
+                .map(|rejected| proto::analytics::RejectedEvent {
+                    index: rejected.index as u32,
+                    error: rejected.error,
+                })
+                .collect(),
+        }))
+    }
+}
+
+/// A collected event; malformed ids and times fail the whole call, as a
+/// malformed JSON body does over HTTP
+fn collect_event(index: usize, event: proto::analytics::Event) -> Result<CollectEvent, Status> {
+    let field = |name: &str| format!("events[{}].{}", index, name);
+    let timestamp = event
+        .timestamp
+        .and_then(|at| DateTime::<Utc>::from_timestamp(at.seconds, u32::try_from(at.nanos).ok()?))
+        .ok_or_else(|| Status::invalid_argument(format!("{}: missing or out of range", field("timestamp"))))?;
+
+    Ok(CollectEvent {
+        timestamp,
+        visitor_id: parse_uuid(&field("visitor_id"), &event.visitor_id)?,
+        session_id: event
+            .session_id
+            .as_deref()
+            .map(|id| parse_uuid(&field("session_id"), id))
+            .transpose()?,
+        site_id: event
+            .site_id
+            .as_deref()
+            .map(|id| parse_uuid(&field("site_id"), id))
+            .transpose()?,
+        category: event.category,
+        action: event.action,
+        label: event.label,
+        value: event.value,
+        path: event.path,
+    })
+}
+
+fn ingest_error(e: IngestError) -> Status {
+    match e {
+        IngestError::Disabled => Status::unavailable("Server-side collection is not configured"),
+        IngestError::Secrets(msg) => {
+            tracing::error!("Failed to read collection keys: {}", msg);
+            Status::internal("Ingestion failed")
+        }
+        IngestError::Database(e) => {
+            tracing::error!("Ingestion database error: {}", e);
+            Status::internal("Ingestion failed")
+        }
+    }
+}
+
+// ============================================
+// Shared
+// ============================================
+
+/// Map service errors to gRPC statuses, like `ProblemDetails` for HTTP
+impl From<ServiceError> for Status {
+    fn from(err: ServiceError) -> Self {
+        match err {
+            ServiceError::NotFound(msg) => Status::not_found(msg),
+            ServiceError::Validation(msg) => Status::invalid_argument(msg),
+            ServiceError::InvalidFields(errors) => Status::invalid_argument(
+                errors
+                    .iter()
+                    .map(|error| format!("{}: {}", error.field, error.message))
+                    .collect::<Vec<_>>()
+                    .join("; "),
+            ),
+            ServiceError::PermissionDenied => {
+                Status::permission_denied("You don't have permission to perform this action")
+            }
+            ServiceError::RateLimited(msg) => Status::resource_exhausted(msg),
+            ServiceError::Unavailable(msg) => Status::unavailable(msg),
+            ServiceError::Locked(lock) => {
+                Status::failed_precondition(format!("{} is editing this post", lock.user_name))
+            }
+            ServiceError::Database(e) => {
+                tracing::error!("Database error: {}", e);
+                Status::internal("A database error occurred")
+            }
+            ServiceError::Storage(msg) => {
+                tracing::error!("Storage error: {}", msg);
+                Status::internal("A storage error occurred")
+            }
+            ServiceError::Template(msg) => {
+                tracing::error!("Template error: {}", msg);
+                Status::internal("A template rendering error occurred")
+            }
+        }
+    }
+}
+
+fn parse_uuid(field: &str, value: &str) -> Result<Uuid, Status> {
+    Uuid::parse_str(value).map_err(|_| Status::invalid_argument(format!("{}: not a UUID", field)))
+}
+
+fn timestamp(at: DateTime<Utc>) -> prost_types::Timestamp {
+    prost_types::Timestamp {
+        seconds: at.timestamp(),
+        nanos: at.timestamp_subsec_nanos() as i32,
+    }
+}
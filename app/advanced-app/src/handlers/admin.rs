@@ -1,38 +1,118 @@
 //! Admin Handlers
 
+use crate::extractors::{AuthUser, CurrentSite, ValidatedJson};
+use crate::handlers::Paginated;
 use crate::models::*;
 use crate::services::ServiceError;
+use crate::views;
 use crate::BlogServices;
 use axum::{
-    extract::{Query, State},
+    extract::{OriginalUri, Path, Query, State},
+    http::header,
     response::IntoResponse,
     Json,
 };
 use std::sync::Arc;
+use uuid::Uuid;
 
 /// GET /admin/posts - List all posts (admin view)
+#[utoipa::path(
+    get,
+    path = "/admin/posts",
+    tag = "admin",
+    params(AdminPostQuery),
+    responses(
+        (status = 200, description = "Posts in any status", body = PaginatedResponse<PostWithRelations>, headers(
+            ("link" = String, description = "Links to the first, previous, next and last pages"),
+            ("x-total-count" = i64, description = "Items across all pages"),
+            ("x-total-pages" = i64, description = "Number of pages"),
+        )),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Insufficient permissions", body = ProblemDetails),
+        (status = 404, description = "Saved view not found", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
 pub async fn list_all_posts(
     State(services): State<Arc<BlogServices>>,
-    Query(query): Query<PostQuery>,
+    CurrentSite(site): CurrentSite,
+    AuthUser(user): AuthUser,
+    Query(query): Query<AdminPostQuery>,
+    uri: OriginalUri,
 ) -> Result<impl IntoResponse, ServiceError> {
     // Admin can see all posts regardless of status
-    let posts = services.posts.list_published(&query).await?;
-    Ok(Json(posts))
+    let (filters, _) = services.views.resolve(&site, user.id, &query).await?;
+    let posts = services.posts.list_admin(&site, &filters, &query).await?;
+    Ok(Paginated::new(posts, uri))
+}
+
+/// GET /admin/posts.csv - Export the filtered post list
+#[utoipa::path(
+    get,
+    path = "/admin/posts.csv",
+    tag = "admin",
+    params(AdminPostQuery),
+    responses(
+        (status = 200, description = "Matching posts (up to 10,000) with the view's or requested columns", content_type = "text/csv", body = String),
+        (status = 400, description = "Unknown column", body = ProblemDetails),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Insufficient permissions", body = ProblemDetails),
+        (status = 404, description = "Saved view not found", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn export_posts(
+    State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
+    AuthUser(user): AuthUser,
+    Query(query): Query<AdminPostQuery>,
+) -> Result<impl IntoResponse, ServiceError> {
+    let (filters, columns) = services.views.resolve(&site, user.id, &query).await?;
+    let posts = services.posts.export_admin(&site, &filters, &query, views::EXPORT_LIMIT).await?;
+    let disposition = format!("attachment; filename=\"{}-posts.csv\"", site.slug);
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+            (header::CACHE_CONTROL, "no-store".to_string()),
+        ],
+        views::to_csv(&posts, &columns),
+    ))
 }
 
 /// GET /admin/comments/pending - List pending comments
+#[utoipa::path(
+    get,
+    path = "/admin/comments/pending",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Comments awaiting moderation", body = inline(DataResponse<Vec<Comment>>)),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Insufficient permissions", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
 pub async fn pending_comments(
     State(services): State<Arc<BlogServices>>,
 ) -> Result<impl IntoResponse, ServiceError> {
     // This would be implemented in CommentService
     // For now, return empty list
-    Ok(Json(serde_json::json!({
-        "data": [],
-        "count": 0
-    })))
+    Ok(Json(DataResponse::with_count(Vec::<Comment>::new())))
 }
 
 /// GET /admin/stats - Blog statistics
+#[utoipa::path(
+    get,
+    path = "/admin/stats",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Blog statistics", body = BlogStats),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Insufficient permissions", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
 pub async fn blog_stats(
     State(services): State<Arc<BlogServices>>,
 ) -> Result<impl IntoResponse, ServiceError> {
@@ -51,3 +131,178 @@ pub async fn blog_stats(
 
     Ok(Json(stats))
 }
+
+/// GET /admin/activity - Content activity log
+#[utoipa::path(
+    get,
+    path = "/admin/activity",
+    tag = "admin",
+    params(ActivityQuery),
+    responses(
+        (status = 200, description = "Changes to posts, comments and categories, newest first", body = PaginatedResponse<ActivityEntry>, headers(
+            ("link" = String, description = "Links to the first, previous, next and last pages"),
+            ("x-total-count" = i64, description = "Items across all pages"),
+            ("x-total-pages" = i64, description = "Number of pages"),
+        )),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Insufficient permissions", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn list_activity(
+    State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
+    Query(query): Query<ActivityQuery>,
+    uri: OriginalUri,
+) -> Result<impl IntoResponse, ServiceError> {
+    let entries = services.activity.list(&site, &query).await?;
+    Ok(Paginated::new(entries, uri))
+}
+
+/// POST /admin/moderation/test - Check text against the content policy
+#[utoipa::path(
+    post,
+    path = "/admin/moderation/test",
+    tag = "admin",
+    request_body = ModerationTestRequest,
+    responses(
+        (status = 200, description = "The rules the text matches, the strictest action and the censored text, with any rules that couldn't be read", body = ModerationResult),
+        (status = 400, description = "Validation failed", body = ProblemDetails),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Insufficient permissions", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn test_moderation(
+    State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
+    ValidatedJson(req): ValidatedJson<ModerationTestRequest>,
+) -> Result<impl IntoResponse, ServiceError> {
+    let result = services.moderation.test(&site, &req.text).await?;
+    Ok(Json(result))
+}
+
+/// GET /admin/comments/auto-approved - Comments that skipped moderation
+#[utoipa::path(
+    get,
+    path = "/admin/comments/auto-approved",
+    tag = "admin",
+    params(AutoApprovalQuery),
+    responses(
+        (status = 200, description = "Comments approved because their authors are trusted, newest first or sampled at random", body = inline(DataResponse<Vec<AutoApproval>>)),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Insufficient permissions", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn list_auto_approvals(
+    State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
+    Query(query): Query<AutoApprovalQuery>,
+) -> Result<impl IntoResponse, ServiceError> {
+    let approvals = services.trust.audit(&site, &query).await?;
+    Ok(Json(DataResponse::new(approvals)))
+}
+
+/// POST /admin/comments/auto-approved/:id/review - Record a spot check
+#[utoipa::path(
+    post,
+    path = "/admin/comments/auto-approved/{id}/review",
+    tag = "admin",
+    params(("id" = Uuid, Path, description = "Comment ID")),
+    request_body = ReviewAutoApprovalRequest,
+    responses(
+        (status = 200, description = "Review recorded; the comment is rejected unless kept", body = AutoApproval),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Insufficient permissions", body = ProblemDetails),
+        (status = 404, description = "No auto-approval for this comment", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn review_auto_approval(
+    State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
+    AuthUser(user): AuthUser,
+    Path(id): Path<Uuid>,
+    ValidatedJson(req): ValidatedJson<ReviewAutoApprovalRequest>,
+) -> Result<impl IntoResponse, ServiceError> {
+    let mut approval = services.trust.review(&site, id, user.id, req.keep).await?;
+    if !req.keep {
+        approval.status = services.comments.reject(&site, id, user.id).await?.status;
+    }
+    Ok(Json(approval))
+}
+
+/// GET /admin/commenters/:user_id/trust - Whether a user's comments skip moderation
+#[utoipa::path(
+    get,
+    path = "/admin/commenters/{user_id}/trust",
+    tag = "admin",
+    params(("user_id" = Uuid, Path, description = "User ID")),
+    responses(
+        (status = 200, description = "The user's override and approved comments", body = CommenterTrust),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Insufficient permissions", body = ProblemDetails),
+        (status = 404, description = "User not found", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn get_commenter_trust(
+    State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
+    Path(user_id): Path<Uuid>,
+) -> Result<impl IntoResponse, ServiceError> {
+    let trust = services.trust.get(&site, user_id).await?;
+    Ok(Json(trust))
+}
+
+/// PUT /admin/commenters/:user_id/trust - Set or clear a user's trust override
+#[utoipa::path(
+    put,
+    path = "/admin/commenters/{user_id}/trust",
+    tag = "admin",
+    params(("user_id" = Uuid, Path, description = "User ID")),
+    request_body = SetCommenterTrustRequest,
+    responses(
+        (status = 200, description = "Override saved", body = CommenterTrust),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Insufficient permissions", body = ProblemDetails),
+        (status = 404, description = "User not found", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn set_commenter_trust(
+    State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
+    AuthUser(user): AuthUser,
+    Path(user_id): Path<Uuid>,
+    ValidatedJson(req): ValidatedJson<SetCommenterTrustRequest>,
+) -> Result<impl IntoResponse, ServiceError> {
+    let trust = services.trust.set(&site, user_id, req.trusted, user.id).await?;
+    Ok(Json(trust))
+}
+
+/// POST /admin/posts/:id/lock/takeover - Take the edit lock from whoever holds it
+#[utoipa::path(
+    post,
+    path = "/admin/posts/{id}/lock/takeover",
+    tag = "admin",
+    params(("id" = Uuid, Path, description = "Post ID")),
+    responses(
+        (status = 200, description = "The current user holds the lock", body = EditLock),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Insufficient permissions", body = ProblemDetails),
+        (status = 404, description = "Post not found", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn take_over_lock(
+    State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
+    AuthUser(user): AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, ServiceError> {
+    let post = services.posts.get_by_id(&site, id).await?;
+    let lock = services.locks.take_over(&site, post.id, user.id).await?;
+    Ok(Json(lock))
+}
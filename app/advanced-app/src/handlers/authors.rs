@@ -0,0 +1,54 @@
+//! Author Handlers
+
+use crate::extractors::{AuthUser, CurrentSite};
+use crate::models::*;
+use crate::services::ServiceError;
+use crate::BlogServices;
+use axum::{
+    extract::{Path, Query, State},
+    response::IntoResponse,
+    Json,
+};
+use std::sync::Arc;
+
+/// GET /authors - List authors with published posts
+#[utoipa::path(
+    get,
+    path = "/authors",
+    tag = "authors",
+    responses(
+        (status = 200, description = "Public author profiles", body = inline(DataResponse<Vec<AuthorProfile>>)),
+    ),
+)]
+pub async fn list_authors(
+    State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
+) -> Result<impl IntoResponse, ServiceError> {
+    let authors = services.authors.list(&site).await?;
+    Ok(Json(DataResponse::new(authors)))
+}
+
+/// GET /authors/:slug - Author profile and published posts
+#[utoipa::path(
+    get,
+    path = "/authors/{slug}",
+    tag = "authors",
+    params(("slug" = String, Path, description = "Author slug"), PostQuery),
+    responses(
+        (status = 200, description = "Author profile with a page of their posts", body = AuthorArchive),
+        (status = 404, description = "Author not found", body = ProblemDetails),
+    ),
+)]
+pub async fn get_author(
+    State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
+    user: Option<AuthUser>,
+    Path(slug): Path<String>,
+    Query(mut query): Query<PostQuery>,
+) -> Result<impl IntoResponse, ServiceError> {
+    let author = services.authors.get_by_slug(&site, &slug).await?;
+    query.author = Some(author.id);
+    let viewer = services.access.viewer(user.map(|AuthUser(user)| user)).await?;
+    let posts = services.posts.list_published(&site, &query, &viewer).await?;
+    Ok(Json(AuthorArchive { author, posts }))
+}
@@ -0,0 +1,78 @@
+//! Backup Handlers
+
+use crate::extractors::AuthUser;
+use crate::models::*;
+use crate::services::ServiceError;
+use crate::BlogServices;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// GET /admin/backups - List backups with their restore steps
+#[utoipa::path(
+    get,
+    path = "/admin/backups",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Backups, newest first; completed ones include restore-to-staging steps", body = Vec<BackupEntry>),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Insufficient permissions", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn list_backups(State(services): State<Arc<BlogServices>>) -> Result<impl IntoResponse, ServiceError> {
+    let backups = services.backups.list().await?;
+    Ok(Json(serde_json::json!({
+        "data": backups
+    })))
+}
+
+/// POST /admin/backups - Take a backup now
+#[utoipa::path(
+    post,
+    path = "/admin/backups",
+    tag = "admin",
+    responses(
+        (status = 201, description = "Backup written to storage", body = Backup),
+        (status = 400, description = "A backup is already running", body = ProblemDetails),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Insufficient permissions", body = ProblemDetails),
+        (status = 500, description = "The dump or a storage write failed; the backup is recorded as failed", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn create_backup(
+    State(services): State<Arc<BlogServices>>,
+    AuthUser(user): AuthUser,
+) -> Result<impl IntoResponse, ServiceError> {
+    let backup = services.backups.backup(Some(user.id)).await?;
+    Ok((StatusCode::CREATED, Json(backup)))
+}
+
+/// POST /admin/backups/:id/verify - Check a backup's files against its checksums
+#[utoipa::path(
+    post,
+    path = "/admin/backups/{id}/verify",
+    tag = "admin",
+    params(("id" = Uuid, Path, description = "Backup ID")),
+    responses(
+        (status = 200, description = "Verification result, also recorded on the backup", body = BackupVerification),
+        (status = 400, description = "Backup isn't completed", body = ProblemDetails),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Insufficient permissions", body = ProblemDetails),
+        (status = 404, description = "Backup not found", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn verify_backup(
+    State(services): State<Arc<BlogServices>>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, ServiceError> {
+    let verification = services.backups.verify(id).await?;
+    Ok(Json(verification))
+}
@@ -1,5 +1,6 @@
 //! Category Handlers
 
+use crate::extractors::{AuthUser, CurrentSite, ValidatedJson};
 use crate::models::*;
 use crate::services::ServiceError;
 use crate::BlogServices;
@@ -11,48 +12,92 @@ use axum::{
 };
 use std::sync::Arc;
 use uuid::Uuid;
-use validator::Validate;
 
 /// GET /categories - List all categories
+#[utoipa::path(
+    get,
+    path = "/categories",
+    tag = "categories",
+    responses(
+        (status = 200, description = "All categories", body = inline(DataResponse<Vec<Category>>)),
+    ),
+)]
 pub async fn list_categories(
     State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
 ) -> Result<impl IntoResponse, ServiceError> {
-    let categories = services.categories.list().await?;
-    Ok(Json(serde_json::json!({
-        "data": categories
-    })))
+    let categories = services.categories.list(&site).await?;
+    Ok(Json(DataResponse::new(categories)))
 }
 
 /// POST /categories - Create a category
+#[utoipa::path(
+    post,
+    path = "/categories",
+    tag = "categories",
+    request_body = CategoryRequest,
+    responses(
+        (status = 201, description = "Category created", body = Category),
+        (status = 400, description = "Validation failed", body = ProblemDetails),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
 pub async fn create_category(
     State(services): State<Arc<BlogServices>>,
-    Json(req): Json<CategoryRequest>,
+    CurrentSite(site): CurrentSite,
+    AuthUser(user): AuthUser,
+    ValidatedJson(req): ValidatedJson<CategoryRequest>,
 ) -> Result<impl IntoResponse, ServiceError> {
-    req.validate()
-        .map_err(|e| ServiceError::Validation(e.to_string()))?;
-
-    let category = services.categories.create(req).await?;
+    let category = services.categories.create(&site, user.id, req).await?;
     Ok((StatusCode::CREATED, Json(category)))
 }
 
 /// PUT /categories/:id - Update a category
+#[utoipa::path(
+    put,
+    path = "/categories/{id}",
+    tag = "categories",
+    params(("id" = Uuid, Path, description = "Category ID")),
+    request_body = CategoryRequest,
+    responses(
+        (status = 200, description = "Category updated", body = Category),
+        (status = 400, description = "Validation failed", body = ProblemDetails),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 404, description = "Category not found", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
 pub async fn update_category(
     State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
+    AuthUser(user): AuthUser,
     Path(id): Path<Uuid>,
-    Json(req): Json<CategoryRequest>,
+    ValidatedJson(req): ValidatedJson<CategoryRequest>,
 ) -> Result<impl IntoResponse, ServiceError> {
-    req.validate()
-        .map_err(|e| ServiceError::Validation(e.to_string()))?;
-
-    let category = services.categories.update(id, req).await?;
+    let category = services.categories.update(&site, id, user.id, req).await?;
     Ok(Json(category))
 }
 
 /// DELETE /categories/:id - Delete a category
+#[utoipa::path(
+    delete,
+    path = "/categories/{id}",
+    tag = "categories",
+    params(("id" = Uuid, Path, description = "Category ID")),
+    responses(
+        (status = 204, description = "Category deleted"),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 404, description = "Category not found", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
 pub async fn delete_category(
     State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
+    AuthUser(user): AuthUser,
     Path(id): Path<Uuid>,
 ) -> Result<impl IntoResponse, ServiceError> {
-    services.categories.delete(id).await?;
+    services.categories.delete(&site, id, user.id).await?;
     Ok(StatusCode::NO_CONTENT)
 }
@@ -1,75 +1,204 @@
 //! Comment Handlers
 
-use crate::extractors::{AuthUser, ClientInfo};
+use crate::extractors::{AuthUser, ClientInfo, CurrentSite, ValidatedJson};
 use crate::models::*;
-use crate::services::ServiceError;
+use crate::moderation::ModerationTarget;
+use crate::services::{NewComment, ServiceError};
+use crate::trust::Trust;
+use crate::votes::Voter;
 use crate::BlogServices;
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     http::StatusCode,
     response::IntoResponse,
     Json,
 };
 use std::sync::Arc;
 use uuid::Uuid;
-use validator::Validate;
 
 /// GET /posts/:id/comments - List comments for a post
+#[utoipa::path(
+    get,
+    path = "/posts/{id}/comments",
+    tag = "comments",
+    params(("id" = Uuid, Path, description = "Post ID"), CommentQuery),
+    responses(
+        (status = 200, description = "Approved comment threads with the viewer's votes", body = inline(DataResponse<Vec<CommentThread>>)),
+    ),
+)]
 pub async fn list_comments(
     State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
     Path(post_id): Path<Uuid>,
+    Query(query): Query<CommentQuery>,
+    auth_user: Option<AuthUser>,
+    client: ClientInfo,
+) -> Result<impl IntoResponse, ServiceError> {
+    let viewer = Voter::new(&site, auth_user.as_ref(), &client);
+    let comments = services
+        .comments
+        .list_for_post(&site, post_id, query.sort.unwrap_or_default(), Some(&viewer))
+        .await?;
+    Ok(Json(DataResponse::with_count(comments)))
+}
+
+/// PUT /comments/:id/vote - Vote on a comment
+#[utoipa::path(
+    put,
+    path = "/comments/{id}/vote",
+    tag = "comments",
+    params(("id" = Uuid, Path, description = "Comment ID")),
+    request_body = VoteRequest,
+    responses(
+        (status = 200, description = "The comment's votes", body = CommentVotes),
+        (status = 400, description = "Validation failed, or a downvote on a site that only takes hearts", body = ProblemDetails),
+        (status = 403, description = "Votes are off, or a downvote by a guest or a new account", body = ProblemDetails),
+        (status = 404, description = "Comment not found", body = ProblemDetails),
+        (status = 429, description = "Too many votes", body = ProblemDetails),
+    ),
+)]
+pub async fn vote_comment(
+    State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
+    Path(id): Path<Uuid>,
+    auth_user: Option<AuthUser>,
+    client: ClientInfo,
+    ValidatedJson(req): ValidatedJson<VoteRequest>,
 ) -> Result<impl IntoResponse, ServiceError> {
-    let comments = services.comments.list_for_post(post_id).await?;
-    Ok(Json(serde_json::json!({
-        "data": comments,
-        "count": comments.len()
-    })))
+    let voter = Voter::new(&site, auth_user.as_ref(), &client);
+    let votes = services.votes.vote(&site, id, &voter, req.value).await?;
+    Ok(Json(votes))
 }
 
 /// POST /posts/:id/comments - Create a comment
+#[utoipa::path(
+    post,
+    path = "/posts/{id}/comments",
+    tag = "comments",
+    params(("id" = Uuid, Path, description = "Post ID")),
+    request_body = CreateCommentRequest,
+    responses(
+        (status = 201, description = "Comment published", body = Comment),
+        (status = 202, description = "Comment awaiting moderation, or marked as spam", body = Comment),
+        (status = 400, description = "Validation failed, or rejected by the content policy", body = ProblemDetails),
+        (status = 403, description = "Guest comments are disabled on this site", body = ProblemDetails),
+        (status = 404, description = "Post not found", body = ProblemDetails),
+    ),
+)]
 pub async fn create_comment(
     State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
     Path(post_id): Path<Uuid>,
     auth_user: Option<AuthUser>,
-    ClientInfo { ip, user_agent }: ClientInfo,
-    Json(req): Json<CreateCommentRequest>,
+    client: ClientInfo,
+    ValidatedJson(mut req): ValidatedJson<CreateCommentRequest>,
 ) -> Result<impl IntoResponse, ServiceError> {
-    req.validate()
-        .map_err(|e| ServiceError::Validation(e.to_string()))?;
+    let user = auth_user.map(|a| a.0);
+    let author_id = user.as_ref().map(|u| u.id);
 
-    let author_id = auth_user.map(|a| a.0.id);
+    if author_id.is_none() && !site.config.allow_guest_comments.unwrap_or(true) {
+        return Err(ServiceError::PermissionDenied);
+    }
 
-    // Comments require moderation unless from authenticated users
-    let requires_moderation = author_id.is_none();
+    let policy = services
+        .moderation
+        .check(&site, ModerationTarget::Comment, user.as_ref(), &req.content)
+        .await?;
+    if policy.action == Some(ModerationAction::Reject) {
+        return Err(ServiceError::Validation("The comment breaks this site's content policy".into()));
+    }
+    // Scored as written, before the policy censors it
+    let toxicity = services.toxicity.assess(user.as_ref(), &req.content).await;
+    req.content = policy.content;
+
+    // Guest comments require moderation unless the site turns it off or the
+    // commenter is trusted; toxic comments are held or marked as spam either way
+    let held_by_site = author_id.is_none() && site.config.comments_require_moderation.unwrap_or(true);
+    let trust = services.trust.evaluate(&site, user.as_ref(), &req.author_email).await?;
+    let requires_moderation = policy.action == Some(ModerationAction::Hold)
+        || match &trust {
+            Trust::Trusted(_) => false,
+            Trust::Distrusted => true,
+            Trust::Unknown => held_by_site,
+        };
+    let comment_status = match toxicity.route {
+        Some(status) => status,
+        None if requires_moderation => CommentStatus::Pending,
+        None => CommentStatus::Approved,
+    };
 
     let comment = services
         .comments
-        .create(post_id, author_id, req, ip, user_agent, requires_moderation)
+        .create(
+            &site,
+            post_id,
+            author_id,
+            req,
+            NewComment {
+                client,
+                status: comment_status.clone(),
+                toxicity: toxicity.score.as_ref(),
+            },
+        )
         .await?;
 
-    let status = if requires_moderation {
-        StatusCode::ACCEPTED
-    } else {
+    if let Trust::Trusted(grant) = &trust {
+        if held_by_site && comment_status == CommentStatus::Approved {
+            services.trust.record(&site, &comment, grant).await?;
+        }
+    }
+
+    let status = if comment_status == CommentStatus::Approved {
         StatusCode::CREATED
+    } else {
+        StatusCode::ACCEPTED
     };
 
     Ok((status, Json(comment)))
 }
 
 /// POST /comments/:id/approve - Approve a comment
+#[utoipa::path(
+    post,
+    path = "/comments/{id}/approve",
+    tag = "comments",
+    params(("id" = Uuid, Path, description = "Comment ID")),
+    responses(
+        (status = 200, description = "Comment approved", body = Comment),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 404, description = "Comment not found", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
 pub async fn approve_comment(
     State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
+    AuthUser(user): AuthUser,
     Path(id): Path<Uuid>,
 ) -> Result<impl IntoResponse, ServiceError> {
-    let comment = services.comments.approve(id).await?;
+    let comment = services.comments.approve(&site, id, user.id).await?;
     Ok(Json(comment))
 }
 
 /// POST /comments/:id/reject - Reject a comment
+#[utoipa::path(
+    post,
+    path = "/comments/{id}/reject",
+    tag = "comments",
+    params(("id" = Uuid, Path, description = "Comment ID")),
+    responses(
+        (status = 200, description = "Comment rejected", body = Comment),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 404, description = "Comment not found", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
 pub async fn reject_comment(
     State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
+    AuthUser(user): AuthUser,
     Path(id): Path<Uuid>,
 ) -> Result<impl IntoResponse, ServiceError> {
-    let comment = services.comments.reject(id).await?;
+    let comment = services.comments.reject(&site, id, user.id).await?;
     Ok(Json(comment))
 }
@@ -0,0 +1,62 @@
+//! Static Export Handlers
+
+use crate::extractors::CurrentSite;
+use crate::models::*;
+use crate::services::ServiceError;
+use crate::BlogServices;
+use axum::{
+    extract::State,
+    http::header,
+    response::IntoResponse,
+    Json,
+};
+use std::sync::Arc;
+
+/// POST /admin/export - Export the site as static files to storage
+#[utoipa::path(
+    post,
+    path = "/admin/export",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Site written to storage; later post changes are applied incrementally", body = ExportReport),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Insufficient permissions", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn export_site(
+    State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
+) -> Result<impl IntoResponse, ServiceError> {
+    let report = services.export.export(&services, &site).await?;
+    Ok(Json(report))
+}
+
+/// GET /admin/export.zip - Download the site as static files
+#[utoipa::path(
+    get,
+    path = "/admin/export.zip",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Zip archive of the rendered site", content_type = "application/zip", body = Vec<u8>),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Insufficient permissions", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn download_export(
+    State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
+) -> Result<impl IntoResponse, ServiceError> {
+    let archive = services.export.export_zip(&services, &site).await?;
+    let disposition = format!("attachment; filename=\"{}.zip\"", site.slug);
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/zip".to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+            (header::CACHE_CONTROL, "no-store".to_string()),
+        ],
+        archive,
+    ))
+}
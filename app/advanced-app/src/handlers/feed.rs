@@ -1,7 +1,11 @@
 //! RSS Feed Handler
 
+use crate::access::Viewer;
+use crate::extractors::CurrentSite;
+use crate::feeds::Feed;
 use crate::models::*;
 use crate::services::ServiceError;
+use crate::sites::Site;
 use crate::BlogServices;
 use axum::{
     extract::State,
@@ -12,9 +16,30 @@ use rss::{ChannelBuilder, ItemBuilder};
 use std::sync::Arc;
 
 /// GET /feed - RSS feed
+#[utoipa::path(
+    get,
+    path = "/feed",
+    tag = "posts",
+    responses(
+        (status = 200, description = "RSS 2.0 feed", content_type = "application/rss+xml", body = String),
+    ),
+)]
 pub async fn rss_feed(
     State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
 ) -> Result<impl IntoResponse, ServiceError> {
+    let xml = services.feeds.get(&services, &site, Feed::Rss).await?;
+
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "application/rss+xml; charset=utf-8")
+        .body(xml)
+        .unwrap())
+}
+
+/// RSS 2.0 document for a site's latest posts, cached by `FeedCache` and
+/// written by static export
+pub async fn render(services: &BlogServices, site: &Site) -> Result<String, ServiceError> {
     let query = PostQuery {
         page: Some(1),
         per_page: Some(20),
@@ -26,7 +51,8 @@ pub async fn rss_feed(
         order: Some("desc".into()),
     };
 
-    let posts = services.posts.list_published(&query).await?;
+    // Feeds are fetched anonymously, so members-only posts are teasers
+    let posts = services.posts.list_published(site, &query, &Viewer::anonymous()).await?;
 
     let items: Vec<_> = posts
         .data
@@ -34,7 +60,7 @@ pub async fn rss_feed(
         .map(|post| {
             ItemBuilder::default()
                 .title(Some(post.post.title.clone()))
-                .link(Some(format!("/posts/{}", post.post.slug)))
+                .link(Some(site.url(&format!("/posts/{}", post.post.slug))))
                 .description(post.post.excerpt.clone())
                 .author(Some(post.author.name.clone()))
                 .pub_date(post.post.published_at.map(|d| d.to_rfc2822()))
@@ -43,17 +69,11 @@ pub async fn rss_feed(
         .collect();
 
     let channel = ChannelBuilder::default()
-        .title("Blog")
-        .link("/")
-        .description("Latest blog posts")
+        .title(site.name.clone())
+        .link(site.url("/"))
+        .description(site.config.tagline.clone().unwrap_or_else(|| "Latest blog posts".to_string()))
         .items(items)
         .build();
 
-    let xml = channel.to_string();
-
-    Ok(Response::builder()
-        .status(StatusCode::OK)
-        .header(header::CONTENT_TYPE, "application/rss+xml; charset=utf-8")
-        .body(xml)
-        .unwrap())
+    Ok(channel.to_string())
 }
@@ -0,0 +1,45 @@
+//! Image Transformation Handlers
+
+use crate::extractors::CurrentSite;
+use crate::models::*;
+use crate::services::ServiceError;
+use crate::BlogServices;
+use axum::{
+    extract::{Path, State},
+    http::header,
+    response::IntoResponse,
+};
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// GET /img/:id/:transform - Resized or re-encoded image
+#[utoipa::path(
+    get,
+    path = "/img/{id}/{transform}",
+    tag = "media",
+    params(
+        ("id" = Uuid, Path, description = "Media ID"),
+        ("transform" = String, Path, description = "Comma-separated transform, e.g. `w_800,h_0,q_75,f_webp`"),
+    ),
+    responses(
+        (status = 200, description = "Transformed image"),
+        (status = 400, description = "Transform not allowed", body = ProblemDetails),
+        (status = 404, description = "Image not found", body = ProblemDetails),
+    ),
+)]
+pub async fn transform_image(
+    State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
+    Path((id, transform)): Path<(Uuid, String)>,
+) -> Result<impl IntoResponse, ServiceError> {
+    let image = services.images.transform(&site, id, &transform).await?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, image.mime_type),
+            // Stored files never change in place, so variants can be kept for good
+            (header::CACHE_CONTROL, "public, max-age=31536000, immutable"),
+        ],
+        image.data,
+    ))
+}
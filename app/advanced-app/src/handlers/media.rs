@@ -1,15 +1,18 @@
 //! Media Handlers
 
-use crate::extractors::AuthUser;
+use crate::extractors::{AuthUser, CurrentSite, ValidatedJson};
 use crate::models::*;
+use crate::policies::{DeleteMedia, ReviewAltText};
 use crate::services::ServiceError;
 use crate::BlogServices;
+use crate::signed_urls::SignedUrl;
 use axum::{
     extract::{Multipart, Path, Query, State},
-    http::StatusCode,
+    http::{header, StatusCode},
     response::IntoResponse,
     Json,
 };
+use rustpress_auth::Authorize;
 use std::sync::Arc;
 use uuid::Uuid;
 
@@ -26,24 +29,49 @@ const ALLOWED_TYPES: &[&str] = &[
 const MAX_FILE_SIZE: usize = 50 * 1024 * 1024;
 
 /// GET /media - List media files
+#[utoipa::path(
+    get,
+    path = "/media",
+    tag = "media",
+    params(MediaQuery),
+    responses(
+        (status = 200, description = "Uploaded media", body = inline(DataResponse<Vec<Media>>)),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
 pub async fn list_media(
     State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
     AuthUser(user): AuthUser,
     Query(query): Query<MediaQuery>,
 ) -> Result<impl IntoResponse, ServiceError> {
-    let media = services.media.list(user.id, &query).await?;
-    Ok(Json(serde_json::json!({
-        "data": media,
-        "count": media.len()
-    })))
+    let media = services.media.list(&site, user.id, &query).await?;
+    Ok(Json(DataResponse::with_count(media)))
 }
 
 /// POST /media - Upload media file
+#[utoipa::path(
+    post,
+    path = "/media",
+    tag = "media",
+    request_body(content = inline(MediaUpload), content_type = "multipart/form-data"),
+    responses(
+        (status = 201, description = "File uploaded", body = Media),
+        (status = 400, description = "Validation failed", body = ProblemDetails),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
 pub async fn upload_media(
     State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
     AuthUser(user): AuthUser,
     mut multipart: Multipart,
 ) -> Result<impl IntoResponse, ServiceError> {
+    let mut file = None;
+    let mut visibility = MediaVisibility::Public;
+
     while let Some(field) = multipart
         .next_field()
         .await
@@ -51,60 +79,358 @@ pub async fn upload_media(
     {
         let name = field.name().unwrap_or("file").to_string();
 
-        if name != "file" {
-            continue;
-        }
+        match name.as_str() {
+            "visibility" => {
+                let value = field.text().await.map_err(|e| ServiceError::Validation(e.to_string()))?;
+                visibility = match value.trim() {
+                    "public" => MediaVisibility::Public,
+                    "private" => MediaVisibility::Private,
+                    other => return Err(ServiceError::Validation(format!("Unknown visibility '{}'", other))),
+                };
+            }
+            "file" if file.is_none() => {
+                let filename = field
+                    .file_name()
+                    .ok_or_else(|| ServiceError::Validation("No filename provided".into()))?
+                    .to_string();
 
-        let filename = field
-            .file_name()
-            .ok_or_else(|| ServiceError::Validation("No filename provided".into()))?
-            .to_string();
-
-        let content_type = field
-            .content_type()
-            .ok_or_else(|| ServiceError::Validation("No content type provided".into()))?
-            .to_string();
-
-        // Validate MIME type
-        if !ALLOWED_TYPES.contains(&content_type.as_str()) {
-            return Err(ServiceError::Validation(format!(
-                "File type '{}' not allowed. Allowed types: {:?}",
-                content_type, ALLOWED_TYPES
-            )));
-        }
+                let content_type = field
+                    .content_type()
+                    .ok_or_else(|| ServiceError::Validation("No content type provided".into()))?
+                    .to_string();
+
+                // Validate MIME type
+                if !ALLOWED_TYPES.contains(&content_type.as_str()) {
+                    return Err(ServiceError::Validation(format!(
+                        "File type '{}' not allowed. Allowed types: {:?}",
+                        content_type, ALLOWED_TYPES
+                    )));
+                }
 
-        // Read file data
-        let data = field
-            .bytes()
-            .await
-            .map_err(|e| ServiceError::Validation(e.to_string()))?
-            .to_vec();
-
-        // Validate file size
-        if data.len() > MAX_FILE_SIZE {
-            return Err(ServiceError::Validation(format!(
-                "File too large. Max size: {}MB",
-                MAX_FILE_SIZE / 1024 / 1024
-            )));
+                // Read file data
+                let data = field
+                    .bytes()
+                    .await
+                    .map_err(|e| ServiceError::Validation(e.to_string()))?
+                    .to_vec();
+
+                // Validate file size
+                if data.len() > MAX_FILE_SIZE {
+                    return Err(ServiceError::Validation(format!(
+                        "File too large. Max size: {}MB",
+                        MAX_FILE_SIZE / 1024 / 1024
+                    )));
+                }
+
+                file = Some((filename, content_type, data));
+            }
+            _ => {}
         }
+    }
+
+    let (filename, content_type, data) = file.ok_or_else(|| ServiceError::Validation("No file uploaded".into()))?;
+    let media = services
+        .media
+        .upload(&site, user.id, filename, data, content_type, visibility)
+        .await?;
+    services.alt_text.queue_upload(&site, &media).await;
 
-        let media = services
-            .media
-            .upload(user.id, filename, data, content_type)
-            .await?;
+    Ok((StatusCode::CREATED, Json(media)))
+}
+
+/// GET /media/alt-text - Alt text suggestions
+#[utoipa::path(
+    get,
+    path = "/media/alt-text",
+    tag = "media",
+    params(AltSuggestionQuery),
+    responses(
+        (status = 200, description = "Suggestions, oldest first; editors see every image, others their own uploads", body = inline(DataResponse<Vec<AltTextSuggestion>>)),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn list_alt_text(
+    State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
+    AuthUser(user): AuthUser,
+    Query(query): Query<AltSuggestionQuery>,
+) -> Result<impl IntoResponse, ServiceError> {
+    let suggestions = services
+        .alt_text
+        .list(&site, user.id, user.can_moderate(), &query)
+        .await?;
+    Ok(Json(DataResponse::with_count(suggestions)))
+}
+
+/// POST /media/:id/alt-text/accept - Save the suggested alt text
+#[utoipa::path(
+    post,
+    path = "/media/{id}/alt-text/accept",
+    tag = "media",
+    params(("id" = Uuid, Path, description = "Media ID")),
+    request_body = AcceptAltText,
+    responses(
+        (status = 200, description = "Alt text saved; the suggestion, or the edited text if given", body = Media),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Insufficient permissions", body = ProblemDetails),
+        (status = 404, description = "Media not found, or no pending suggestion", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn accept_alt_text(
+    State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
+    AuthUser(user): AuthUser,
+    auth: Authorize<ReviewAltText>,
+    ValidatedJson(req): ValidatedJson<AcceptAltText>,
+) -> Result<impl IntoResponse, ServiceError> {
+    services.alt_text.accept(&auth.resource, user.id, req.alt_text).await?;
+    let media = services.media.find(&site, auth.resource.id).await?;
+    Ok(Json(media))
+}
+
+/// POST /media/:id/alt-text/reject - Dismiss the suggested alt text
+#[utoipa::path(
+    post,
+    path = "/media/{id}/alt-text/reject",
+    tag = "media",
+    params(("id" = Uuid, Path, description = "Media ID")),
+    responses(
+        (status = 204, description = "Suggestion dismissed"),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Insufficient permissions", body = ProblemDetails),
+        (status = 404, description = "Media not found, or no pending suggestion", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn reject_alt_text(
+    State(services): State<Arc<BlogServices>>,
+    AuthUser(user): AuthUser,
+    auth: Authorize<ReviewAltText>,
+) -> Result<impl IntoResponse, ServiceError> {
+    services.alt_text.reject(&auth.resource, user.id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// GET /media/:id/url - Signed download link
+#[utoipa::path(
+    get,
+    path = "/media/{id}/url",
+    tag = "media",
+    params(("id" = Uuid, Path, description = "Media ID")),
+    responses(
+        (status = 200, description = "Time-limited download link", body = SignedUrl),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Insufficient permissions", body = ProblemDetails),
+        (status = 404, description = "Media not found", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn get_media_url(
+    State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
+    AuthUser(user): AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, ServiceError> {
+    let url = services.media.signed_url(&site, &user, id).await?;
+    Ok(Json(url))
+}
 
-        return Ok((StatusCode::CREATED, Json(media)));
+/// PUT /media/:id/visibility - Make a file public or private
+#[utoipa::path(
+    put,
+    path = "/media/{id}/visibility",
+    tag = "media",
+    params(("id" = Uuid, Path, description = "Media ID")),
+    request_body = UpdateMediaVisibility,
+    responses(
+        (status = 200, description = "Visibility changed", body = Media),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Insufficient permissions", body = ProblemDetails),
+        (status = 404, description = "Media not found", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn update_media_visibility(
+    State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
+    AuthUser(user): AuthUser,
+    Path(id): Path<Uuid>,
+    Json(req): Json<UpdateMediaVisibility>,
+) -> Result<impl IntoResponse, ServiceError> {
+    let media = services.media.set_visibility(&site, &user, id, req.visibility).await?;
+    if media.visibility == MediaVisibility::Private {
+        services.images.purge(&site, id).await;
     }
+    Ok(Json(media))
+}
 
-    Err(ServiceError::Validation("No file uploaded".into()))
+/// GET /media/:id/download - Download through a signed link
+///
+/// The signature is checked by `middleware::signed_url::verify_download`.
+#[utoipa::path(
+    get,
+    path = "/media/{id}/download",
+    tag = "media",
+    params(
+        ("id" = Uuid, Path, description = "Media ID"),
+        ("expires" = i64, Query, description = "Link expiry (Unix time)"),
+        ("sig" = String, Query, description = "Link signature"),
+    ),
+    responses(
+        (status = 200, description = "File contents"),
+        (status = 403, description = "Invalid or expired link", body = ProblemDetails),
+        (status = 404, description = "Media not found", body = ProblemDetails),
+    ),
+)]
+pub async fn download_media(
+    State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, ServiceError> {
+    let (media, data) = services.media.download(&site, id).await?;
+    let disposition = format!(
+        "inline; filename=\"{}\"",
+        media.original_name.replace(['"', '\\', '\r', '\n'], "_")
+    );
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, media.mime_type),
+            (header::CONTENT_DISPOSITION, disposition),
+            // Links are per user; shared caches must not keep the file
+            (header::CACHE_CONTROL, "private, no-store".to_string()),
+        ],
+        data,
+    ))
 }
 
-/// DELETE /media/:id - Delete media file
+/// DELETE /media/:id - Move a media file to the trash
+#[utoipa::path(
+    delete,
+    path = "/media/{id}",
+    tag = "media",
+    params(("id" = Uuid, Path, description = "Media ID")),
+    responses(
+        (status = 204, description = "File moved to the trash"),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Insufficient permissions", body = ProblemDetails),
+        (status = 404, description = "Media not found", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
 pub async fn delete_media(
     State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
     AuthUser(user): AuthUser,
-    Path(id): Path<Uuid>,
+    auth: Authorize<DeleteMedia>,
 ) -> Result<impl IntoResponse, ServiceError> {
-    services.media.delete(id, user.id).await?;
+    services.media.trash(&auth.resource, user.id).await?;
+    services.images.purge(&site, auth.resource.id).await;
     Ok(StatusCode::NO_CONTENT)
 }
+
+/// GET /admin/media/trash - Trashed media awaiting deletion
+#[utoipa::path(
+    get,
+    path = "/admin/media/trash",
+    tag = "media",
+    params(MediaQuery),
+    responses(
+        (status = 200, description = "Trashed media, most recent first", body = inline(DataResponse<Vec<Media>>)),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Insufficient permissions", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn list_trash(
+    State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
+    Query(query): Query<MediaQuery>,
+) -> Result<impl IntoResponse, ServiceError> {
+    let media = services.media.list_trash(&site, &query).await?;
+    Ok(Json(DataResponse::with_count(media)))
+}
+
+/// POST /admin/media/:id/restore - Take a file back out of the trash
+#[utoipa::path(
+    post,
+    path = "/admin/media/{id}/restore",
+    tag = "media",
+    params(("id" = Uuid, Path, description = "Media ID")),
+    responses(
+        (status = 200, description = "File restored", body = Media),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Insufficient permissions", body = ProblemDetails),
+        (status = 404, description = "Media not found in trash", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn restore_media(
+    State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, ServiceError> {
+    let media = services.media.restore(&site, id).await?;
+    Ok(Json(media))
+}
+
+/// POST /admin/media/alt-text/backfill - Queue images without alt text
+#[utoipa::path(
+    post,
+    path = "/admin/media/alt-text/backfill",
+    tag = "media",
+    responses(
+        (status = 202, description = "Images queued for suggestions", body = AltTextBackfill),
+        (status = 400, description = "Alt text suggestions are off for this site", body = ProblemDetails),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Insufficient permissions", body = ProblemDetails),
+        (status = 404, description = "Alt text suggestions disabled", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn backfill_alt_text(
+    State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
+) -> Result<impl IntoResponse, ServiceError> {
+    let queued = services.alt_text.backfill(&site).await?;
+    Ok((StatusCode::ACCEPTED, Json(AltTextBackfill { queued })))
+}
+
+/// GET /admin/media/orphans - Findings of the last orphan scan
+#[utoipa::path(
+    get,
+    path = "/admin/media/orphans",
+    tag = "media",
+    responses(
+        (status = 200, description = "Media rows without files and files without rows", body = OrphanReport),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Insufficient permissions", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn media_orphans(State(services): State<Arc<BlogServices>>) -> Result<impl IntoResponse, ServiceError> {
+    let report = services.media.orphan_report().await?;
+    Ok(Json(report))
+}
+
+/// POST /admin/media/orphans/scan - Scan storage for orphans now
+#[utoipa::path(
+    post,
+    path = "/admin/media/orphans/scan",
+    tag = "media",
+    responses(
+        (status = 200, description = "Fresh orphan report", body = OrphanReport),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Insufficient permissions", body = ProblemDetails),
+        (status = 500, description = "Storage could not be listed", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn scan_media_orphans(
+    State(services): State<Arc<BlogServices>>,
+) -> Result<impl IntoResponse, ServiceError> {
+    let report = services.media.scan_orphans().await?;
+    Ok(Json(report))
+}
@@ -1,51 +1,149 @@
 //! Blog API Handlers
 
 pub mod admin;
+pub mod authors;
+pub mod backups;
 pub mod categories;
 pub mod comments;
+pub mod export;
 pub mod feed;
+pub mod images;
 pub mod media;
+pub mod notifications;
+pub mod pages;
+pub mod plugins;
 pub mod posts;
+pub mod preferences;
 pub mod search;
+pub mod settings;
+pub mod sitemap;
+pub mod sites;
+pub mod sync;
 pub mod tags;
+pub mod tools;
+pub mod views;
+pub mod widgets;
 
-use crate::models::ApiError;
+use crate::models::{PaginatedResponse, PaginationMeta, ProblemDetails};
 use crate::services::ServiceError;
 use axum::{
-    http::StatusCode,
+    extract::OriginalUri,
+    http::{header, HeaderName, HeaderValue, StatusCode, Uri},
     response::{IntoResponse, Response},
     Json,
 };
+use serde::Serialize;
 
-/// Convert service errors to HTTP responses
-impl IntoResponse for ServiceError {
-    fn into_response(self) -> Response {
-        let (status, error, message) = match self {
-            ServiceError::NotFound(msg) => (StatusCode::NOT_FOUND, "not_found", msg),
-            ServiceError::Validation(msg) => (StatusCode::BAD_REQUEST, "validation_error", msg),
-            ServiceError::PermissionDenied => (
-                StatusCode::FORBIDDEN,
-                "permission_denied",
-                "You don't have permission to perform this action".to_string(),
-            ),
-            ServiceError::Database(msg) => {
-                tracing::error!("Database error: {}", msg);
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    "database_error",
-                    "A database error occurred".to_string(),
-                )
+/// Total number of items across all pages of a list response
+pub const TOTAL_COUNT: HeaderName = HeaderName::from_static("x-total-count");
+/// Number of pages of a list response
+pub const TOTAL_PAGES: HeaderName = HeaderName::from_static("x-total-pages");
+
+/// Map service errors to problem details
+impl From<ServiceError> for ProblemDetails {
+    fn from(err: ServiceError) -> Self {
+        match err {
+            ServiceError::NotFound(msg) => ProblemDetails::not_found(msg),
+            ServiceError::Validation(msg) => ProblemDetails::validation(msg),
+            ServiceError::InvalidFields(errors) => {
+                ProblemDetails::validation("Request validation failed").with_errors(errors)
+            }
+            ServiceError::PermissionDenied => ProblemDetails::new(StatusCode::FORBIDDEN, "permission_denied")
+                .detail("You don't have permission to perform this action"),
+            ServiceError::Database(e) => {
+                let problem = ProblemDetails::new(StatusCode::INTERNAL_SERVER_ERROR, "database_error")
+                    .detail("A database error occurred");
+                tracing::error!(trace_id = ?problem.trace_id, "Database error: {}", e);
+                problem
             }
             ServiceError::Storage(msg) => {
-                tracing::error!("Storage error: {}", msg);
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    "storage_error",
-                    "A storage error occurred".to_string(),
-                )
+                let problem = ProblemDetails::new(StatusCode::INTERNAL_SERVER_ERROR, "storage_error")
+                    .detail("A storage error occurred");
+                tracing::error!(trace_id = ?problem.trace_id, "Storage error: {}", msg);
+                problem
+            }
+            ServiceError::Template(msg) => {
+                let problem = ProblemDetails::new(StatusCode::INTERNAL_SERVER_ERROR, "template_error")
+                    .detail("A template rendering error occurred");
+                tracing::error!(trace_id = ?problem.trace_id, "Template error: {}", msg);
+                problem
+            }
+            ServiceError::RateLimited(msg) => {
+                ProblemDetails::new(StatusCode::TOO_MANY_REQUESTS, "rate_limited").detail(msg)
             }
-        };
+            ServiceError::Unavailable(msg) => ProblemDetails::unavailable(msg),
+            ServiceError::Locked(lock) => ProblemDetails::new(StatusCode::LOCKED, "locked").detail(format!(
+                "{} ({}) is editing this post; their lock lapses at {}",
+                lock.user_name,
+                lock.user_id,
+                lock.expires_at.to_rfc3339()
+            )),
+        }
+    }
+}
 
-        (status, Json(ApiError::new(error, &message))).into_response()
+/// Convert service errors to HTTP responses
+impl IntoResponse for ServiceError {
+    fn into_response(self) -> Response {
+        ProblemDetails::from(self).into_response()
+    }
+}
+
+/// A page of a list, with RFC 8288 `Link` headers (`first`, `prev`, `next`,
+/// `last`) and the totals in `X-Total-Count` and `X-Total-Pages`, so clients
+/// can paginate without reading the body
+pub struct Paginated<T> {
+    page: PaginatedResponse<T>,
+    uri: Uri,
+}
+
+impl<T> Paginated<T> {
+    /// `uri` is the request's, including any mount path, so links keep the
+    /// other query parameters and resolve from the client's side
+    pub fn new(page: PaginatedResponse<T>, OriginalUri(uri): OriginalUri) -> Self {
+        Self { page, uri }
+    }
+}
+
+impl<T: Serialize> IntoResponse for Paginated<T> {
+    fn into_response(self) -> Response {
+        let meta = &self.page.pagination;
+        let mut headers = vec![
+            (TOTAL_COUNT, HeaderValue::from(meta.total)),
+            (TOTAL_PAGES, HeaderValue::from(meta.total_pages)),
+        ];
+        if let Ok(links) = HeaderValue::from_str(&page_links(&self.uri, meta)) {
+            headers.push((header::LINK, links));
+        }
+
+        let mut response = Json(self.page).into_response();
+        response.headers_mut().extend(headers);
+        response
+    }
+}
+
+/// `Link` header value pointing at the other pages of `uri`
+fn page_links(uri: &Uri, meta: &PaginationMeta) -> String {
+    let params: Vec<&str> = uri
+        .query()
+        .unwrap_or_default()
+        .split('&')
+        .filter(|param| !param.is_empty() && param.split('=').next() != Some("page"))
+        .collect();
+    let link = |page: i64, rel: &str| {
+        let mut query = params.clone();
+        let page = format!("page={}", page);
+        query.push(&page);
+        format!("<{}?{}>; rel=\"{}\"", uri.path(), query.join("&"), rel)
+    };
+
+    let mut links = vec![link(1, "first")];
+    if meta.has_prev {
+        links.push(link(meta.page - 1, "prev"));
+    }
+    if meta.has_next {
+        links.push(link(meta.page + 1, "next"));
     }
+    links.push(link(meta.total_pages.max(1), "last"));
+    links.join(", ")
 }
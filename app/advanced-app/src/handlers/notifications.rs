@@ -0,0 +1,96 @@
+//! Notification Handlers
+
+use crate::extractors::{AuthUser, CurrentSite, ValidatedJson};
+use crate::models::*;
+use crate::services::ServiceError;
+use crate::BlogServices;
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use std::sync::Arc;
+
+/// GET /notifications/preferences - The user's comment digest settings
+#[utoipa::path(
+    get,
+    path = "/notifications/preferences",
+    tag = "notifications",
+    responses(
+        (status = 200, description = "Digest settings", body = NotificationPreferences),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn get_preferences(
+    State(services): State<Arc<BlogServices>>,
+    AuthUser(user): AuthUser,
+) -> Result<impl IntoResponse, ServiceError> {
+    let preferences = services.digests.preferences(user.id).await?;
+    Ok(Json(preferences))
+}
+
+/// PUT /notifications/preferences - Choose how often to get comment digests
+#[utoipa::path(
+    put,
+    path = "/notifications/preferences",
+    tag = "notifications",
+    request_body = UpdateNotificationPreferencesRequest,
+    responses(
+        (status = 200, description = "Digest settings updated", body = NotificationPreferences),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 400, description = "Validation failed", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn update_preferences(
+    State(services): State<Arc<BlogServices>>,
+    AuthUser(user): AuthUser,
+    ValidatedJson(req): ValidatedJson<UpdateNotificationPreferencesRequest>,
+) -> Result<impl IntoResponse, ServiceError> {
+    let preferences = services.digests.set_frequency(user.id, req.frequency).await?;
+    Ok(Json(preferences))
+}
+
+/// GET /notifications/mentions - Where the user was mentioned on this site
+#[utoipa::path(
+    get,
+    path = "/notifications/mentions",
+    tag = "notifications",
+    params(MentionQuery),
+    responses(
+        (status = 200, description = "Mentions in published posts and approved comments, newest first", body = inline(DataResponse<Vec<Mention>>)),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn list_mentions(
+    State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
+    AuthUser(user): AuthUser,
+    Query(query): Query<MentionQuery>,
+) -> Result<impl IntoResponse, ServiceError> {
+    let mentions = services.mentions.list(&site, user.id, &query).await?;
+    Ok(Json(DataResponse::new(mentions)))
+}
+
+/// POST /notifications/mentions/read - Mark the user's mentions as read
+#[utoipa::path(
+    post,
+    path = "/notifications/mentions/read",
+    tag = "notifications",
+    responses(
+        (status = 204, description = "Mentions marked as read"),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn mark_mentions_read(
+    State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
+    AuthUser(user): AuthUser,
+) -> Result<impl IntoResponse, ServiceError> {
+    services.mentions.mark_read(&site, user.id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,188 @@
+//! HTML Page Handlers
+//!
+//! Server-rendered theme pages alongside the JSON API.
+
+use crate::access::Viewer;
+use crate::amp;
+use crate::extractors::{AuthUser, ClientInfo, CurrentSite};
+use crate::models::*;
+use crate::services::ServiceError;
+use crate::sites::Site;
+use crate::theme::{RenderRequest, TemplateKind};
+use crate::BlogServices;
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::{Html, IntoResponse, Response},
+};
+use std::sync::Arc;
+
+/// GET / - Home page with latest posts
+pub async fn home(
+    State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
+    user: Option<AuthUser>,
+    client: ClientInfo,
+    Query(query): Query<PostQuery>,
+) -> Result<Response, ServiceError> {
+    let request = render_request(site, user, client);
+    let viewer = viewer(&services, &request).await?;
+    let posts = services.posts.list_published(&request.site, &query, &viewer).await?;
+    let html = services.theme.render_archive(TemplateKind::Home, &posts, &request).await?;
+    Ok(Html(html).into_response())
+}
+
+/// GET /read/:slug - Single post page
+pub async fn single(
+    State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
+    user: Option<AuthUser>,
+    client: ClientInfo,
+    Path(slug): Path<String>,
+) -> Result<Response, ServiceError> {
+    let request = render_request(site, user, client);
+    let kind = TemplateKind::Single { slug: slug.clone() };
+    render_post(&services, kind, &slug, &request).await
+}
+
+/// GET /pages/:slug - Standalone page
+pub async fn page(
+    State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
+    user: Option<AuthUser>,
+    client: ClientInfo,
+    Path(slug): Path<String>,
+) -> Result<Response, ServiceError> {
+    let request = render_request(site, user, client);
+    let kind = TemplateKind::Page { slug: slug.clone() };
+    render_post(&services, kind, &slug, &request).await
+}
+
+/// GET /posts/:slug/amp - AMP version of a post
+pub async fn amp(
+    State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
+    user: Option<AuthUser>,
+    client: ClientInfo,
+    Path(slug): Path<String>,
+) -> Result<Response, ServiceError> {
+    let request = render_request(site, user, client);
+    let viewer = viewer(&services, &request).await?;
+    let post = match services.posts.get_by_slug(&request.site, &slug, &viewer).await {
+        Ok(post) if services.amp.enabled() => post,
+        // Disabled AMP pages don't exist either
+        Ok(_) | Err(ServiceError::NotFound(_)) => {
+            let html = services.theme.render_not_found(&request).await?;
+            return Ok((StatusCode::NOT_FOUND, Html(html)).into_response());
+        }
+        Err(e) => return Err(e),
+    };
+
+    let content = services.amp.content(&post.post.content).await?;
+    let html = services.theme.render_amp(&post, &content, &request).await?;
+
+    // The AMP runtime and embeds come from other origins
+    let nonce = rustpress_auth::security::current_csp_nonce().unwrap_or_default();
+    let policy = amp::content_security_policy(&nonce);
+    Ok(([(header::CONTENT_SECURITY_POLICY, policy)], Html(html)).into_response())
+}
+
+/// GET /category/:slug - Category archive
+pub async fn category_archive(
+    State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
+    user: Option<AuthUser>,
+    client: ClientInfo,
+    Path(slug): Path<String>,
+    Query(mut query): Query<PostQuery>,
+) -> Result<Response, ServiceError> {
+    let request = render_request(site, user, client);
+    query.category = Some(slug.clone());
+    let viewer = viewer(&services, &request).await?;
+    let posts = services.posts.list_published(&request.site, &query, &viewer).await?;
+    let html = services
+        .theme
+        .render_archive(TemplateKind::Category { slug }, &posts, &request)
+        .await?;
+    Ok(Html(html).into_response())
+}
+
+/// GET /tag/:slug - Tag archive
+pub async fn tag_archive(
+    State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
+    user: Option<AuthUser>,
+    client: ClientInfo,
+    Path(slug): Path<String>,
+    Query(mut query): Query<PostQuery>,
+) -> Result<Response, ServiceError> {
+    let request = render_request(site, user, client);
+    query.tag = Some(slug.clone());
+    let viewer = viewer(&services, &request).await?;
+    let posts = services.posts.list_published(&request.site, &query, &viewer).await?;
+    let html = services
+        .theme
+        .render_archive(TemplateKind::Tag { slug }, &posts, &request)
+        .await?;
+    Ok(Html(html).into_response())
+}
+
+/// GET /author/:slug - Author archive
+pub async fn author_archive(
+    State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
+    user: Option<AuthUser>,
+    client: ClientInfo,
+    Path(slug): Path<String>,
+    Query(mut query): Query<PostQuery>,
+) -> Result<Response, ServiceError> {
+    let request = render_request(site, user, client);
+    let author = match services.authors.get_by_slug(&request.site, &slug).await {
+        Ok(author) => author,
+        Err(ServiceError::NotFound(_)) => {
+            let html = services.theme.render_not_found(&request).await?;
+            return Ok((StatusCode::NOT_FOUND, Html(html)).into_response());
+        }
+        Err(e) => return Err(e),
+    };
+    query.author = Some(author.id);
+    let viewer = viewer(&services, &request).await?;
+    let posts = services.posts.list_published(&request.site, &query, &viewer).await?;
+    let html = services.theme.render_author_archive(&author, &posts, &request).await?;
+    Ok(Html(html).into_response())
+}
+
+/// Render a post, showing the theme's 404 page when it doesn't exist
+async fn render_post(
+    services: &BlogServices,
+    kind: TemplateKind,
+    slug: &str,
+    request: &RenderRequest,
+) -> Result<Response, ServiceError> {
+    let viewer = viewer(services, request).await?;
+    match services.posts.get_by_slug(&request.site, slug, &viewer).await {
+        Ok(post) => {
+            let html = services.theme.render_post(kind, &post, request).await?;
+            Ok(Html(html).into_response())
+        }
+        Err(ServiceError::NotFound(_)) => {
+            let html = services.theme.render_not_found(request).await?;
+            Ok((StatusCode::NOT_FOUND, Html(html)).into_response())
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// The request's user with their membership level
+async fn viewer(services: &BlogServices, request: &RenderRequest) -> Result<Viewer, ServiceError> {
+    services.access.viewer(request.user.clone()).await
+}
+
+fn render_request(site: Arc<Site>, user: Option<AuthUser>, client: ClientInfo) -> RenderRequest {
+    RenderRequest {
+        site,
+        user: user.map(|AuthUser(user)| user),
+        user_agent: client.user_agent,
+        static_paths: false,
+    }
+}
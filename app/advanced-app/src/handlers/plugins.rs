@@ -0,0 +1,158 @@
+//! Plugin Management Handlers
+
+use crate::extractors::AuthUser;
+use crate::plugins::PluginAction;
+use crate::services::ServiceError;
+use crate::BlogServices;
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use rustpress_auth::migrations::Direction;
+use serde::Deserialize;
+use std::sync::Arc;
+
+/// Lifecycle query parameters
+#[derive(Debug, Default, Deserialize)]
+pub struct LifecycleQuery {
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// GET /admin/plugins - List installed plugins
+pub async fn list_plugins(
+    State(services): State<Arc<BlogServices>>,
+) -> Result<impl IntoResponse, ServiceError> {
+    let plugins = services.plugins.list().await?;
+    Ok(Json(serde_json::json!({
+        "data": plugins
+    })))
+}
+
+/// POST /admin/plugins/:id/activate - Activate a plugin
+pub async fn activate_plugin(
+    State(services): State<Arc<BlogServices>>,
+    Path(id): Path<String>,
+    Query(query): Query<LifecycleQuery>,
+) -> Result<impl IntoResponse, ServiceError> {
+    let report = services.plugins.run(&id, PluginAction::Activate, query.dry_run).await?;
+    Ok(Json(report))
+}
+
+/// POST /admin/plugins/:id/deactivate - Deactivate a plugin
+pub async fn deactivate_plugin(
+    State(services): State<Arc<BlogServices>>,
+    Path(id): Path<String>,
+    Query(query): Query<LifecycleQuery>,
+) -> Result<impl IntoResponse, ServiceError> {
+    let report = services.plugins.run(&id, PluginAction::Deactivate, query.dry_run).await?;
+    Ok(Json(report))
+}
+
+/// POST /admin/plugins/:id/upgrade - Upgrade a plugin
+pub async fn upgrade_plugin(
+    State(services): State<Arc<BlogServices>>,
+    Path(id): Path<String>,
+    Query(query): Query<LifecycleQuery>,
+) -> Result<impl IntoResponse, ServiceError> {
+    let report = services.plugins.run(&id, PluginAction::Upgrade, query.dry_run).await?;
+    Ok(Json(report))
+}
+
+/// Migration run request
+#[derive(Debug, Deserialize)]
+pub struct MigrateRequest {
+    pub direction: Direction,
+    /// Version to migrate to; latest for `up`; for `down` the latest applied
+    /// migration alone is reverted, `0` reverts everything
+    pub target: Option<i64>,
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// GET /admin/plugins/:id/migrations - Migration status from the ledger
+pub async fn list_migrations(
+    State(services): State<Arc<BlogServices>>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, ServiceError> {
+    let migrations = services.plugins.migration_status(&id).await?;
+    Ok(Json(serde_json::json!({
+        "data": migrations
+    })))
+}
+
+/// POST /admin/plugins/:id/migrate - Run migrations up or down
+pub async fn migrate_plugin(
+    State(services): State<Arc<BlogServices>>,
+    Path(id): Path<String>,
+    Json(req): Json<MigrateRequest>,
+) -> Result<impl IntoResponse, ServiceError> {
+    let steps = services
+        .plugins
+        .migrate(&id, req.direction, req.target, req.dry_run)
+        .await?;
+    Ok(Json(serde_json::json!({
+        "plugin": id,
+        "dry_run": req.dry_run,
+        "steps": steps
+    })))
+}
+
+/// Marketplace listing query parameters
+#[derive(Debug, Default, Deserialize)]
+pub struct AvailableQuery {
+    /// Fetch the registry index even if the cached one is recent
+    #[serde(default)]
+    pub refresh: bool,
+}
+
+/// GET /plugins/available - Plugins offered by the registry
+pub async fn available_plugins(
+    State(services): State<Arc<BlogServices>>,
+    Query(query): Query<AvailableQuery>,
+) -> Result<impl IntoResponse, ServiceError> {
+    let plugins = services.marketplace.available(query.refresh).await?;
+    Ok(Json(serde_json::json!({
+        "data": plugins
+    })))
+}
+
+/// Install request
+#[derive(Debug, Default, Deserialize)]
+pub struct InstallRequest {
+    /// Version to install; the newest compatible one if omitted
+    pub version: Option<String>,
+}
+
+/// POST /plugins/:id/install - Install a plugin from the registry
+pub async fn install_plugin(
+    State(services): State<Arc<BlogServices>>,
+    AuthUser(user): AuthUser,
+    Path(id): Path<String>,
+    Json(req): Json<InstallRequest>,
+) -> Result<impl IntoResponse, ServiceError> {
+    let installation = services
+        .marketplace
+        .install(&id, req.version.as_deref(), user.id)
+        .await?;
+    Ok((StatusCode::CREATED, Json(installation)))
+}
+
+/// Installation history query parameters
+#[derive(Debug, Default, Deserialize)]
+pub struct InstallationQuery {
+    pub plugin: Option<String>,
+}
+
+/// GET /plugins/installations - Plugins installed from the registry
+pub async fn list_installations(
+    State(services): State<Arc<BlogServices>>,
+    Query(query): Query<InstallationQuery>,
+) -> Result<impl IntoResponse, ServiceError> {
+    let installations = services.marketplace.installations(query.plugin.as_deref()).await?;
+    Ok(Json(serde_json::json!({
+        "data": installations
+    })))
+}
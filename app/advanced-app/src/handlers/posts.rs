@@ -1,108 +1,424 @@
 //! Post Handlers
 
-use crate::extractors::AuthUser;
+use crate::extractors::{AuthUser, CurrentSite, User, ValidatedJson};
+use crate::handlers::Paginated;
 use crate::models::*;
+use crate::moderation::ModerationTarget;
+use crate::policies::{DeletePost, UpdatePost};
 use crate::services::ServiceError;
+use crate::sites::Site;
+use crate::timezones::{self, ZonedTime};
 use crate::BlogServices;
 use axum::{
-    extract::{Path, Query, State},
+    extract::{OriginalUri, Path, Query, State},
     http::StatusCode,
-    response::IntoResponse,
+    response::{IntoResponse, Response},
     Json,
 };
+use rustpress_auth::Authorize;
 use std::sync::Arc;
 use uuid::Uuid;
-use validator::Validate;
 
 /// GET /posts - List published posts
+#[utoipa::path(
+    get,
+    path = "/posts",
+    tag = "posts",
+    params(PostQuery),
+    responses(
+        (status = 200, description = "Published posts", body = PaginatedResponse<PostWithRelations>, headers(
+            ("link" = String, description = "Links to the first, previous, next and last pages"),
+            ("x-total-count" = i64, description = "Items across all pages"),
+            ("x-total-pages" = i64, description = "Number of pages"),
+        )),
+    ),
+)]
 pub async fn list_posts(
     State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
+    user: Option<AuthUser>,
     Query(query): Query<PostQuery>,
+    uri: OriginalUri,
 ) -> Result<impl IntoResponse, ServiceError> {
-    let posts = services.posts.list_published(&query).await?;
-    Ok(Json(posts))
+    let viewer = services.access.viewer(user.map(|AuthUser(user)| user)).await?;
+    let posts = services.posts.list_published(&site, &query, &viewer).await?;
+    Ok(Paginated::new(posts, uri))
 }
 
 /// GET /posts/:slug - Get post by slug
+#[utoipa::path(
+    get,
+    path = "/posts/{slug}",
+    tag = "posts",
+    params(("slug" = String, Path, description = "Post slug")),
+    responses(
+        (status = 200, description = "Post", body = PostWithRelations),
+        (status = 404, description = "Post not found", body = ProblemDetails),
+    ),
+)]
 pub async fn get_post_by_slug(
     State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
+    user: Option<AuthUser>,
     Path(slug): Path<String>,
 ) -> Result<impl IntoResponse, ServiceError> {
-    let post = services.posts.get_by_slug(&slug).await?;
+    let viewer = services.access.viewer(user.map(|AuthUser(user)| user)).await?;
+    let post = services.posts.get_by_slug(&site, &slug, &viewer).await?;
     Ok(Json(post))
 }
 
+/// GET /posts/:id/related - Posts like a published post
+#[utoipa::path(
+    get,
+    path = "/posts/{id}/related",
+    tag = "posts",
+    params(("id" = Uuid, Path, description = "Post ID"), RelatedQuery),
+    responses(
+        (status = 200, description = "Published posts most like this one, best first", body = inline(DataResponse<Vec<PostWithRelations>>)),
+        (status = 404, description = "Post not found, or semantic search disabled", body = ProblemDetails),
+    ),
+)]
+pub async fn related_posts(
+    State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
+    user: Option<AuthUser>,
+    Path(id): Path<Uuid>,
+    Query(query): Query<RelatedQuery>,
+) -> Result<impl IntoResponse, ServiceError> {
+    if !services.embeddings.enabled() {
+        return Err(ServiceError::NotFound("Semantic search is disabled".into()));
+    }
+    let post = services.posts.get_by_id(&site, id).await?;
+    if post.status != PostStatus::Published {
+        return Err(ServiceError::NotFound(format!("Post not found: {}", id)));
+    }
+
+    let viewer = services.access.viewer(user.map(|AuthUser(user)| user)).await?;
+    let ids = services.embeddings.related(&site, &post, query.limit()).await?;
+    let posts = services.posts.list_by_ids(&site, &ids, &viewer).await?;
+    Ok(Json(DataResponse::with_count(posts)))
+}
+
 /// POST /posts - Create a new post
+#[utoipa::path(
+    post,
+    path = "/posts",
+    tag = "posts",
+    request_body = CreatePostRequest,
+    responses(
+        (status = 201, description = "Post created", body = Post),
+        (status = 400, description = "Validation failed, or rejected by the content policy", body = ProblemDetails),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
 pub async fn create_post(
     State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
     AuthUser(user): AuthUser,
-    Json(req): Json<CreatePostRequest>,
+    ValidatedJson(mut req): ValidatedJson<CreatePostRequest>,
 ) -> Result<impl IntoResponse, ServiceError> {
-    req.validate()
-        .map_err(|e| ServiceError::Validation(e.to_string()))?;
+    let held = moderate(&services, &site, &user, &mut req.title).await?
+        | moderate(&services, &site, &user, &mut req.content).await?;
+    if held && req.scheduled_for.is_some() {
+        return Err(held_error());
+    }
+
+    // Local times are the author's
+    let timezone = services.preferences.timezone(&site, Some(user.id)).await?;
+    let scheduled_for = req
+        .scheduled_for
+        .as_deref()
+        .map(|value| timezones::resolve_time("scheduled_for", value, timezone))
+        .transpose()?;
 
-    let post = services.posts.create(user.id, req).await?;
+    services.save_filter.create(&site, user.id, &mut req).await?;
+    let mut post = services.posts.create(&site, user.id, req, scheduled_for).await?;
+    post.schedule = post.scheduled_for.map(|at| ZonedTime::new(at, timezone));
 
     Ok((StatusCode::CREATED, Json(post)))
 }
 
 /// PUT /posts/:id - Update a post
+#[utoipa::path(
+    put,
+    path = "/posts/{id}",
+    tag = "posts",
+    params(("id" = Uuid, Path, description = "Post ID")),
+    request_body = UpdatePostRequest,
+    responses(
+        (status = 200, description = "Post updated", body = Post),
+        (status = 400, description = "Validation failed, or rejected or held by the content policy", body = ProblemDetails),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Insufficient permissions", body = ProblemDetails),
+        (status = 404, description = "Post not found", body = ProblemDetails),
+        (status = 423, description = "Someone else is editing the post", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
 pub async fn update_post(
     State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
     AuthUser(user): AuthUser,
-    Path(id): Path<Uuid>,
-    Json(req): Json<UpdatePostRequest>,
+    auth: Authorize<UpdatePost>,
+    ValidatedJson(mut req): ValidatedJson<UpdatePostRequest>,
 ) -> Result<impl IntoResponse, ServiceError> {
-    req.validate()
-        .map_err(|e| ServiceError::Validation(e.to_string()))?;
+    services.locks.ensure_unlocked(&site, auth.resource.id, user.id).await?;
+
+    let mut held = false;
+    if let Some(title) = req.title.as_mut() {
+        held |= moderate(&services, &site, &user, title).await?;
+    }
+    if let Some(content) = req.content.as_mut() {
+        held |= moderate(&services, &site, &user, content).await?;
+    }
+    // Live posts aren't changed into held ones
+    if held && matches!(auth.resource.status, PostStatus::Published | PostStatus::Scheduled) {
+        return Err(held_error());
+    }
 
-    let post = services.posts.update(id, user.id, req).await?;
+    services.save_filter.update(&site, &auth.resource, &mut req).await?;
+    let mut post = services.posts.update(&site, auth.resource, user.id, req).await?;
+    if let Some(at) = post.scheduled_for {
+        let timezone = services.preferences.timezone(&site, Some(post.author_id)).await?;
+        post.schedule = Some(ZonedTime::new(at, timezone));
+    }
 
     Ok(Json(post))
 }
 
 /// DELETE /posts/:id - Delete a post
+#[utoipa::path(
+    delete,
+    path = "/posts/{id}",
+    tag = "posts",
+    params(("id" = Uuid, Path, description = "Post ID")),
+    responses(
+        (status = 204, description = "Post deleted"),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Insufficient permissions", body = ProblemDetails),
+        (status = 404, description = "Post not found", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
 pub async fn delete_post(
     State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
     AuthUser(user): AuthUser,
-    Path(id): Path<Uuid>,
+    auth: Authorize<DeletePost>,
+) -> Result<impl IntoResponse, ServiceError> {
+    services.posts.delete(&site, auth.resource, user.id).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// GET /posts/:id/revisions - Saved versions of a post
+#[utoipa::path(
+    get,
+    path = "/posts/{id}/revisions",
+    tag = "posts",
+    params(("id" = Uuid, Path, description = "Post ID")),
+    responses(
+        (status = 200, description = "Revisions, newest first", body = inline(DataResponse<Vec<RevisionSummary>>)),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Insufficient permissions", body = ProblemDetails),
+        (status = 404, description = "Post not found", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn list_revisions(
+    State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
+    auth: Authorize<UpdatePost>,
+) -> Result<impl IntoResponse, ServiceError> {
+    let revisions = services.revisions.list(&site, auth.resource.id).await?;
+    Ok(Json(DataResponse::new(revisions)))
+}
+
+/// GET /posts/:id/diff - Differences between two revisions
+#[utoipa::path(
+    get,
+    path = "/posts/{id}/diff",
+    tag = "posts",
+    params(("id" = Uuid, Path, description = "Post ID"), DiffQuery),
+    responses(
+        (status = 200, description = "Changed fields, with content as HTML-aware hunks", body = PostDiff),
+        (status = 400, description = "`from` isn't earlier than `to`", body = ProblemDetails),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Insufficient permissions", body = ProblemDetails),
+        (status = 404, description = "Post or revision not found", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn diff_post(
+    State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
+    auth: Authorize<UpdatePost>,
+    Query(query): Query<DiffQuery>,
+) -> Result<impl IntoResponse, ServiceError> {
+    let diff = services.revisions.diff(&site, auth.resource.id, &query).await?;
+    Ok(Json(diff))
+}
+
+/// POST /posts/:id/lock - Take or renew the edit lock
+#[utoipa::path(
+    post,
+    path = "/posts/{id}/lock",
+    tag = "posts",
+    params(("id" = Uuid, Path, description = "Post ID")),
+    responses(
+        (status = 200, description = "The current user holds the lock", body = EditLock),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Insufficient permissions", body = ProblemDetails),
+        (status = 404, description = "Post not found", body = ProblemDetails),
+        (status = 423, description = "Someone else holds the lock", body = EditLock),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn lock_post(
+    State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
+    AuthUser(user): AuthUser,
+    auth: Authorize<UpdatePost>,
+) -> Result<Response, ServiceError> {
+    match services.locks.heartbeat(&site, auth.resource.id, user.id).await {
+        Ok(lock) => Ok(Json(lock).into_response()),
+        Err(ServiceError::Locked(lock)) => Ok((StatusCode::LOCKED, Json(lock)).into_response()),
+        Err(e) => Err(e),
+    }
+}
+
+/// DELETE /posts/:id/lock - Hand back the edit lock
+#[utoipa::path(
+    delete,
+    path = "/posts/{id}/lock",
+    tag = "posts",
+    params(("id" = Uuid, Path, description = "Post ID")),
+    responses(
+        (status = 204, description = "Lock released, if the current user held it"),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Insufficient permissions", body = ProblemDetails),
+        (status = 404, description = "Post not found", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn unlock_post(
+    State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
+    AuthUser(user): AuthUser,
+    auth: Authorize<UpdatePost>,
 ) -> Result<impl IntoResponse, ServiceError> {
-    services.posts.delete(id, user.id).await?;
+    services.locks.release(&site, auth.resource.id, user.id).await?;
 
     Ok(StatusCode::NO_CONTENT)
 }
 
 /// POST /posts/:id/publish - Publish a post
+#[utoipa::path(
+    post,
+    path = "/posts/{id}/publish",
+    tag = "posts",
+    params(("id" = Uuid, Path, description = "Post ID")),
+    responses(
+        (status = 200, description = "Post published", body = Post),
+        (status = 400, description = "Held by the content policy for an editor to publish", body = ProblemDetails),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 404, description = "Post not found", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
 pub async fn publish_post(
     State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
+    AuthUser(user): AuthUser,
     Path(id): Path<Uuid>,
 ) -> Result<impl IntoResponse, ServiceError> {
-    let post = services.posts.publish(id).await?;
+    let existing = services.posts.get_by_id(&site, id).await?;
+    let text = format!("{}\n{}", existing.title, existing.content);
+    let policy = services
+        .moderation
+        .check(&site, ModerationTarget::Post, Some(&user), &text)
+        .await?;
+    if policy.action >= Some(ModerationAction::Hold) {
+        return Err(held_error());
+    }
+
+    let post = services.posts.publish(&site, id, user.id).await?;
 
     Ok(Json(post))
 }
 
 /// POST /posts/:id/unpublish - Unpublish a post
+#[utoipa::path(
+    post,
+    path = "/posts/{id}/unpublish",
+    tag = "posts",
+    params(("id" = Uuid, Path, description = "Post ID")),
+    responses(
+        (status = 200, description = "Post unpublished", body = Post),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 404, description = "Post not found", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
 pub async fn unpublish_post(
     State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
+    AuthUser(user): AuthUser,
     Path(id): Path<Uuid>,
 ) -> Result<impl IntoResponse, ServiceError> {
-    let post = services.posts.unpublish(id).await?;
+    let post = services.posts.unpublish(&site, id, user.id).await?;
 
     Ok(Json(post))
 }
 
 /// GET /drafts - List user's draft posts
+#[utoipa::path(
+    get,
+    path = "/drafts",
+    tag = "posts",
+    params(PostQuery),
+    responses(
+        (status = 200, description = "Current user's drafts", body = PaginatedResponse<PostWithRelations>, headers(
+            ("link" = String, description = "Links to the first, previous, next and last pages"),
+            ("x-total-count" = i64, description = "Items across all pages"),
+            ("x-total-pages" = i64, description = "Number of pages"),
+        )),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
 pub async fn list_drafts(
     State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
     AuthUser(user): AuthUser,
     Query(query): Query<PostQuery>,
+    uri: OriginalUri,
 ) -> Result<impl IntoResponse, ServiceError> {
     let mut query = query;
     query.author = Some(user.id);
     query.status = Some(PostStatus::Draft);
 
-    let posts = services.posts.list_published(&query).await?;
+    let viewer = services.access.viewer(Some(user)).await?;
+    let posts = services.posts.list_published(&site, &query, &viewer).await?;
+
+    Ok(Paginated::new(posts, uri))
+}
+
+/// Run a post field through the content policy, replacing censored text;
+/// returns whether the post is held for an editor
+pub(crate) async fn moderate(services: &BlogServices, site: &Site, user: &User, text: &mut String) -> Result<bool, ServiceError> {
+    let policy = services
+        .moderation
+        .check(site, ModerationTarget::Post, Some(user), text)
+        .await?;
+    if policy.action == Some(ModerationAction::Reject) {
+        return Err(ServiceError::Validation("The post breaks this site's content policy".into()));
+    }
+    *text = policy.content;
+    Ok(policy.action == Some(ModerationAction::Hold))
+}
 
-    Ok(Json(posts))
+pub(crate) fn held_error() -> ServiceError {
+    ServiceError::Validation("The post is held by this site's content policy; an editor must publish it".into())
 }
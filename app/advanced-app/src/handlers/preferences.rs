@@ -0,0 +1,51 @@
+//! User Preference Handlers
+
+use crate::extractors::{AuthUser, CurrentSite, ValidatedJson};
+use crate::models::*;
+use crate::services::ServiceError;
+use crate::BlogServices;
+use axum::{extract::State, response::IntoResponse, Json};
+use std::sync::Arc;
+
+/// GET /me/preferences - The user's display preferences on this site
+#[utoipa::path(
+    get,
+    path = "/me/preferences",
+    tag = "preferences",
+    responses(
+        (status = 200, description = "Preferences, with the site's defaults for keys the user hasn't chosen", body = UserPreferences),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn get_my_preferences(
+    State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
+    AuthUser(user): AuthUser,
+) -> Result<impl IntoResponse, ServiceError> {
+    let preferences = services.preferences.get(&site, user.id).await?;
+    Ok(Json(preferences))
+}
+
+/// PUT /me/preferences - Change display preferences
+#[utoipa::path(
+    put,
+    path = "/me/preferences",
+    tag = "preferences",
+    request_body = UpdatePreferencesRequest,
+    responses(
+        (status = 200, description = "Preferences updated", body = UserPreferences),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 400, description = "Unknown key or invalid value", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn update_my_preferences(
+    State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
+    AuthUser(user): AuthUser,
+    ValidatedJson(req): ValidatedJson<UpdatePreferencesRequest>,
+) -> Result<impl IntoResponse, ServiceError> {
+    let preferences = services.preferences.update(&site, user.id, &req.preferences).await?;
+    Ok(Json(preferences))
+}
@@ -1,5 +1,6 @@
 //! Search Handlers
 
+use crate::extractors::{AuthUser, CurrentSite};
 use crate::models::*;
 use crate::services::ServiceError;
 use crate::BlogServices;
@@ -11,8 +12,19 @@ use axum::{
 use std::sync::Arc;
 
 /// GET /search - Search posts
+#[utoipa::path(
+    get,
+    path = "/search",
+    tag = "search",
+    params(SearchQuery),
+    responses(
+        (status = 200, description = "Matching posts", body = SearchResult),
+        (status = 400, description = "Validation failed", body = ProblemDetails),
+    ),
+)]
 pub async fn search_posts(
     State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
     Query(query): Query<SearchQuery>,
 ) -> Result<impl IntoResponse, ServiceError> {
     // Validate minimum query length
@@ -22,7 +34,44 @@ pub async fn search_posts(
         ));
     }
 
-    let results = services.search.search(&query).await?;
+    let results = services.search.search(&site, &query).await?;
 
     Ok(Json(results))
 }
+
+/// GET /search/semantic - Search posts by meaning
+///
+/// Posts are ranked by how close their embedding is to the query's, fused
+/// with the full-text ranking of `GET /search`.
+#[utoipa::path(
+    get,
+    path = "/search/semantic",
+    tag = "search",
+    params(SemanticSearchQuery),
+    responses(
+        (status = 200, description = "Best matching posts, best first", body = inline(DataResponse<Vec<PostWithRelations>>)),
+        (status = 400, description = "Validation failed", body = ProblemDetails),
+        (status = 404, description = "Semantic search disabled", body = ProblemDetails),
+    ),
+)]
+pub async fn semantic_search(
+    State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
+    user: Option<AuthUser>,
+    Query(query): Query<SemanticSearchQuery>,
+) -> Result<impl IntoResponse, ServiceError> {
+    if !services.embeddings.enabled() {
+        return Err(ServiceError::NotFound("Semantic search is disabled".into()));
+    }
+    if query.q.trim().len() < 3 {
+        return Err(ServiceError::Validation(
+            "Search query must be at least 3 characters".into(),
+        ));
+    }
+
+    let viewer = services.access.viewer(user.map(|AuthUser(user)| user)).await?;
+    let ids = services.embeddings.search(&site, query.q.trim(), query.limit()).await?;
+    let posts = services.posts.list_by_ids(&site, &ids, &viewer).await?;
+
+    Ok(Json(DataResponse::with_count(posts)))
+}
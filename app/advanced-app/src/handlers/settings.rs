@@ -0,0 +1,100 @@
+//! Settings Handlers
+
+use crate::extractors::{AuthUser, CurrentSite};
+use crate::services::ServiceError;
+use crate::settings::SettingsExport;
+use crate::BlogServices;
+use axum::{
+    extract::{Path, Query, State},
+    response::IntoResponse,
+    Json,
+};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::sync::Arc;
+
+/// Audit log query parameters
+#[derive(Debug, Deserialize)]
+pub struct AuditQuery {
+    pub limit: Option<i64>,
+}
+
+/// GET /settings/:namespace - Current values for a namespace
+pub async fn get_settings(
+    State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
+    AuthUser(user): AuthUser,
+    Path(namespace): Path<String>,
+) -> Result<impl IntoResponse, ServiceError> {
+    let values = services.settings.get(&site, &user, &namespace).await?;
+    Ok(Json(serde_json::json!({
+        "namespace": namespace,
+        "data": values
+    })))
+}
+
+/// PUT /settings/:namespace - Update values in a namespace
+pub async fn update_settings(
+    State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
+    AuthUser(user): AuthUser,
+    Path(namespace): Path<String>,
+    Json(changes): Json<Map<String, Value>>,
+) -> Result<impl IntoResponse, ServiceError> {
+    let values = services.settings.update(&site, &user, &namespace, changes).await?;
+    Ok(Json(serde_json::json!({
+        "namespace": namespace,
+        "data": values
+    })))
+}
+
+/// GET /settings/:namespace/audit - Change history for a namespace
+pub async fn settings_audit(
+    State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
+    AuthUser(user): AuthUser,
+    Path(namespace): Path<String>,
+    Query(query): Query<AuditQuery>,
+) -> Result<impl IntoResponse, ServiceError> {
+    let entries = services
+        .settings
+        .audit_log(&site, &user, &namespace, query.limit.unwrap_or(50))
+        .await?;
+    Ok(Json(serde_json::json!({
+        "data": entries
+    })))
+}
+
+/// GET /plugins/:id/settings-schema - JSON Schema and form hints for a
+/// plugin's settings (values are at `/settings/:id`)
+pub async fn settings_schema(
+    State(services): State<Arc<BlogServices>>,
+    AuthUser(user): AuthUser,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, ServiceError> {
+    let schema = services.settings.schema(&user, &id).await?;
+    Ok(Json(schema))
+}
+
+/// GET /settings/export - Export settings for environment promotion
+pub async fn export_settings(
+    State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
+    AuthUser(user): AuthUser,
+) -> Result<impl IntoResponse, ServiceError> {
+    let export = services.settings.export(&site, &user).await?;
+    Ok(Json(export))
+}
+
+/// POST /settings/import - Import a settings export
+pub async fn import_settings(
+    State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
+    AuthUser(user): AuthUser,
+    Json(export): Json<SettingsExport>,
+) -> Result<impl IntoResponse, ServiceError> {
+    let imported = services.settings.import(&site, &user, export).await?;
+    Ok(Json(serde_json::json!({
+        "imported": imported
+    })))
+}
@@ -0,0 +1,96 @@
+//! XML Sitemap Handler
+
+use crate::extractors::CurrentSite;
+use crate::feeds::Feed;
+use crate::services::ServiceError;
+use crate::sites::Site;
+use crate::BlogServices;
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use std::fmt::Write;
+use std::sync::Arc;
+
+/// GET /sitemap.xml - Posts, archives and author pages
+#[utoipa::path(
+    get,
+    path = "/sitemap.xml",
+    tag = "posts",
+    responses(
+        (status = 200, description = "Sitemap of the site's public pages", content_type = "application/xml", body = String),
+    ),
+)]
+pub async fn sitemap(
+    State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, ServiceError> {
+    let origin = origin(&site, &headers);
+    let xml = services.feeds.get(&services, &site, Feed::Sitemap { origin }).await?;
+
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "application/xml; charset=utf-8")
+        .body(xml)
+        .unwrap())
+}
+
+/// Sitemap of a site's public pages with links under `origin`
+/// (`https://example.com`), cached by `FeedCache` and written by static export
+pub async fn render(services: &BlogServices, site: &Site, origin: &str) -> Result<String, ServiceError> {
+    let link = |path: &str| format!("{}{}", origin, site.url(path));
+
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n",
+    );
+    push_url(&mut xml, &link("/"), None);
+    for (slug, updated_at) in services.posts.sitemap_entries(site).await? {
+        push_url(&mut xml, &link(&format!("/read/{}", slug)), Some(updated_at));
+    }
+    for category in services.categories.list(site).await? {
+        push_url(&mut xml, &link(&format!("/category/{}", category.slug)), None);
+    }
+    for tag in services.tags.list(site).await? {
+        push_url(&mut xml, &link(&format!("/tag/{}", tag.slug)), None);
+    }
+    for author in services.authors.list(site).await? {
+        push_url(&mut xml, &link(&format!("/author/{}", author.slug)), None);
+    }
+    xml.push_str("</urlset>\n");
+    Ok(xml)
+}
+
+/// Scheme and host for absolute links: the site's own host when it has one,
+/// otherwise the host the request came in on
+fn origin(site: &Site, headers: &HeaderMap) -> String {
+    let scheme = headers
+        .get("x-forwarded-proto")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("https");
+    let host = site
+        .host
+        .clone()
+        .or_else(|| headers.get(header::HOST).and_then(|v| v.to_str().ok()).map(str::to_string))
+        .unwrap_or_default();
+    format!("{}://{}", scheme, host)
+}
+
+fn push_url(xml: &mut String, loc: &str, lastmod: Option<DateTime<Utc>>) {
+    let _ = write!(xml, "  <url><loc>{}</loc>", escape(loc));
+    if let Some(lastmod) = lastmod {
+        let _ = write!(xml, "<lastmod>{}</lastmod>", lastmod.format("%Y-%m-%d"));
+    }
+    xml.push_str("</url>\n");
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
@@ -0,0 +1,176 @@
+//! Site Management Handlers
+
+use crate::extractors::ValidatedJson;
+use crate::models::*;
+use crate::services::ServiceError;
+use crate::sites::{CreateSiteRequest, Site, SiteMember, SiteMemberRequest, UpdateSiteRequest};
+use crate::BlogServices;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// GET /admin/sites - List sites
+#[utoipa::path(
+    get,
+    path = "/admin/sites",
+    tag = "sites",
+    responses(
+        (status = 200, description = "Sites served by this deployment", body = inline(DataResponse<Vec<Site>>)),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Insufficient permissions", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn list_sites(
+    State(services): State<Arc<BlogServices>>,
+) -> Result<impl IntoResponse, ServiceError> {
+    let sites = services.sites.list().await;
+    Ok(Json(DataResponse::with_count(sites)))
+}
+
+/// POST /admin/sites - Create a site
+#[utoipa::path(
+    post,
+    path = "/admin/sites",
+    tag = "sites",
+    request_body = CreateSiteRequest,
+    responses(
+        (status = 201, description = "Site created", body = Site),
+        (status = 400, description = "Validation failed", body = ProblemDetails),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Insufficient permissions", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn create_site(
+    State(services): State<Arc<BlogServices>>,
+    ValidatedJson(req): ValidatedJson<CreateSiteRequest>,
+) -> Result<impl IntoResponse, ServiceError> {
+    let site = services.sites.create(req).await?;
+    Ok((StatusCode::CREATED, Json(site)))
+}
+
+/// PUT /admin/sites/:id - Update a site's name, binding or configuration
+#[utoipa::path(
+    put,
+    path = "/admin/sites/{id}",
+    tag = "sites",
+    params(("id" = Uuid, Path, description = "Site ID")),
+    request_body = UpdateSiteRequest,
+    responses(
+        (status = 200, description = "Site updated", body = Site),
+        (status = 400, description = "Validation failed", body = ProblemDetails),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Insufficient permissions", body = ProblemDetails),
+        (status = 404, description = "Site not found", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn update_site(
+    State(services): State<Arc<BlogServices>>,
+    Path(id): Path<Uuid>,
+    ValidatedJson(req): ValidatedJson<UpdateSiteRequest>,
+) -> Result<impl IntoResponse, ServiceError> {
+    let site = services.sites.update(id, req).await?;
+    Ok(Json(site))
+}
+
+/// DELETE /admin/sites/:id - Delete a site and all of its content
+#[utoipa::path(
+    delete,
+    path = "/admin/sites/{id}",
+    tag = "sites",
+    params(("id" = Uuid, Path, description = "Site ID")),
+    responses(
+        (status = 204, description = "Site deleted"),
+        (status = 400, description = "Site not found or is the default site", body = ProblemDetails),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Insufficient permissions", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn delete_site(
+    State(services): State<Arc<BlogServices>>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, ServiceError> {
+    services.sites.delete(id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// GET /admin/sites/:id/members - List a site's members
+#[utoipa::path(
+    get,
+    path = "/admin/sites/{id}/members",
+    tag = "sites",
+    params(("id" = Uuid, Path, description = "Site ID")),
+    responses(
+        (status = 200, description = "Site members", body = inline(DataResponse<Vec<SiteMember>>)),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Insufficient permissions", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn list_members(
+    State(services): State<Arc<BlogServices>>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, ServiceError> {
+    let members = services.sites.members(id).await?;
+    Ok(Json(DataResponse::with_count(members)))
+}
+
+/// PUT /admin/sites/:id/members/:user_id - Add a member or change their role
+#[utoipa::path(
+    put,
+    path = "/admin/sites/{id}/members/{user_id}",
+    tag = "sites",
+    params(
+        ("id" = Uuid, Path, description = "Site ID"),
+        ("user_id" = Uuid, Path, description = "User ID"),
+    ),
+    request_body = SiteMemberRequest,
+    responses(
+        (status = 200, description = "Membership saved", body = SiteMember),
+        (status = 400, description = "Unknown role", body = ProblemDetails),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Insufficient permissions", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn set_member(
+    State(services): State<Arc<BlogServices>>,
+    Path((id, user_id)): Path<(Uuid, Uuid)>,
+    ValidatedJson(req): ValidatedJson<SiteMemberRequest>,
+) -> Result<impl IntoResponse, ServiceError> {
+    let member = services.sites.set_member(id, user_id, &req.role).await?;
+    Ok(Json(member))
+}
+
+/// DELETE /admin/sites/:id/members/:user_id - Remove a member
+#[utoipa::path(
+    delete,
+    path = "/admin/sites/{id}/members/{user_id}",
+    tag = "sites",
+    params(
+        ("id" = Uuid, Path, description = "Site ID"),
+        ("user_id" = Uuid, Path, description = "User ID"),
+    ),
+    responses(
+        (status = 204, description = "Member removed"),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Insufficient permissions", body = ProblemDetails),
+        (status = 404, description = "Member not found", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn remove_member(
+    State(services): State<Arc<BlogServices>>,
+    Path((id, user_id)): Path<(Uuid, Uuid)>,
+) -> Result<impl IntoResponse, ServiceError> {
+    services.sites.remove_member(id, user_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
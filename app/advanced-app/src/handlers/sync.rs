@@ -0,0 +1,99 @@
+//! Content Sync Handlers
+
+use crate::content_sync::{self, SyncExport, SyncReport, SyncServices};
+use crate::extractors::CurrentSite;
+use crate::models::*;
+use crate::services::ServiceError;
+use crate::BlogServices;
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use std::sync::Arc;
+
+/// POST /admin/sync/import - Import the site's changed content files now
+#[utoipa::path(
+    post,
+    path = "/admin/sync/import",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Posts created, updated and deleted from files", body = SyncReport),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Insufficient permissions", body = ProblemDetails),
+        (status = 404, description = "Content sync is disabled", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn import_files(
+    State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
+) -> Result<impl IntoResponse, ServiceError> {
+    ensure_enabled(&services)?;
+    let report = services.sync.import_site(SyncServices::from(&*services), &site).await?;
+    Ok(Json(report))
+}
+
+/// POST /admin/sync/export - Write every post of the site to files
+#[utoipa::path(
+    post,
+    path = "/admin/sync/export",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Files written, and committed when Git is enabled", body = SyncExport),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Insufficient permissions", body = ProblemDetails),
+        (status = 404, description = "Content sync is disabled", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn export_files(
+    State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
+) -> Result<impl IntoResponse, ServiceError> {
+    ensure_enabled(&services)?;
+    let written = services.sync.export_site(SyncServices::from(&*services), &site).await?;
+    if written > 0 {
+        services.sync.commit(&format!("Export {}", site.name)).await?;
+    }
+    Ok(Json(SyncExport { written }))
+}
+
+/// POST /sync/webhook - Pull and import after a push to the content repository
+#[utoipa::path(
+    post,
+    path = "/sync/webhook",
+    tag = "admin",
+    params(("x-hub-signature-256" = String, Header, description = "`sha256=` HMAC of the body with the webhook secret")),
+    responses(
+        (status = 202, description = "Pull and import started"),
+        (status = 401, description = "Missing or invalid signature", body = ProblemDetails),
+        (status = 404, description = "Content sync is disabled", body = ProblemDetails),
+    ),
+)]
+pub async fn webhook(
+    State(services): State<Arc<BlogServices>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<impl IntoResponse, ServiceError> {
+    ensure_enabled(&services)?;
+    let signature = headers.get("x-hub-signature-256").and_then(|v| v.to_str().ok());
+    if !services.sync.verify_webhook(signature, &body) {
+        return Ok(ProblemDetails::new(StatusCode::UNAUTHORIZED, "invalid_signature")
+            .detail("Missing or invalid webhook signature")
+            .into_response());
+    }
+
+    tokio::spawn(async move { content_sync::pull_and_import(&services).await });
+    Ok(StatusCode::ACCEPTED.into_response())
+}
+
+fn ensure_enabled(services: &BlogServices) -> Result<(), ServiceError> {
+    if services.sync.enabled() {
+        Ok(())
+    } else {
+        Err(ServiceError::NotFound("Content sync is disabled".into()))
+    }
+}
@@ -1,5 +1,6 @@
 //! Tag Handlers
 
+use crate::extractors::{CurrentSite, ValidatedJson};
 use crate::models::*;
 use crate::services::ServiceError;
 use crate::BlogServices;
@@ -11,48 +12,89 @@ use axum::{
 };
 use std::sync::Arc;
 use uuid::Uuid;
-use validator::Validate;
 
 /// GET /tags - List all tags
+#[utoipa::path(
+    get,
+    path = "/tags",
+    tag = "tags",
+    responses(
+        (status = 200, description = "All tags", body = inline(DataResponse<Vec<Tag>>)),
+    ),
+)]
 pub async fn list_tags(
     State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
 ) -> Result<impl IntoResponse, ServiceError> {
-    let tags = services.tags.list().await?;
-    Ok(Json(serde_json::json!({
-        "data": tags
-    })))
+    let tags = services.tags.list(&site).await?;
+    Ok(Json(DataResponse::new(tags)))
 }
 
 /// POST /tags - Create a tag
+#[utoipa::path(
+    post,
+    path = "/tags",
+    tag = "tags",
+    request_body = TagRequest,
+    responses(
+        (status = 201, description = "Tag created", body = Tag),
+        (status = 400, description = "Validation failed", body = ProblemDetails),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
 pub async fn create_tag(
     State(services): State<Arc<BlogServices>>,
-    Json(req): Json<TagRequest>,
+    CurrentSite(site): CurrentSite,
+    ValidatedJson(req): ValidatedJson<TagRequest>,
 ) -> Result<impl IntoResponse, ServiceError> {
-    req.validate()
-        .map_err(|e| ServiceError::Validation(e.to_string()))?;
-
-    let tag = services.tags.create(req).await?;
+    let tag = services.tags.create(&site, req).await?;
     Ok((StatusCode::CREATED, Json(tag)))
 }
 
 /// PUT /tags/:id - Update a tag
+#[utoipa::path(
+    put,
+    path = "/tags/{id}",
+    tag = "tags",
+    params(("id" = Uuid, Path, description = "Tag ID")),
+    request_body = TagRequest,
+    responses(
+        (status = 200, description = "Tag updated", body = Tag),
+        (status = 400, description = "Validation failed", body = ProblemDetails),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 404, description = "Tag not found", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
 pub async fn update_tag(
     State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
     Path(id): Path<Uuid>,
-    Json(req): Json<TagRequest>,
+    ValidatedJson(req): ValidatedJson<TagRequest>,
 ) -> Result<impl IntoResponse, ServiceError> {
-    req.validate()
-        .map_err(|e| ServiceError::Validation(e.to_string()))?;
-
-    let tag = services.tags.update(id, req).await?;
+    let tag = services.tags.update(&site, id, req).await?;
     Ok(Json(tag))
 }
 
 /// DELETE /tags/:id - Delete a tag
+#[utoipa::path(
+    delete,
+    path = "/tags/{id}",
+    tag = "tags",
+    params(("id" = Uuid, Path, description = "Tag ID")),
+    responses(
+        (status = 204, description = "Tag deleted"),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 404, description = "Tag not found", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
 pub async fn delete_tag(
     State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
     Path(id): Path<Uuid>,
 ) -> Result<impl IntoResponse, ServiceError> {
-    services.tags.delete(id).await?;
+    services.tags.delete(&site, id).await?;
     Ok(StatusCode::NO_CONTENT)
 }
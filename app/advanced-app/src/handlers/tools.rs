@@ -0,0 +1,137 @@
+//! Tool API Handlers
+
+use crate::extractors::{AuthUser, CurrentSite, ValidatedJson};
+use crate::models::*;
+use crate::services::ServiceError;
+use crate::tools::{self, Tool, ToolCaller};
+use crate::BlogServices;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// GET /tools - Tools the key may call
+#[utoipa::path(
+    get,
+    path = "/tools",
+    tag = "tools",
+    params(("X-Api-Key" = String, Header, description = "Tool API key")),
+    responses(
+        (status = 200, description = "Tools the key's scopes and its user's role allow, with JSON Schemas of their input", body = inline(DataResponse<Vec<ToolDefinition>>)),
+        (status = 401, description = "Missing or invalid API key", body = ProblemDetails),
+        (status = 403, description = "The key's user is not a member of the site", body = ProblemDetails),
+        (status = 404, description = "Tool API not enabled", body = ProblemDetails),
+    ),
+)]
+pub async fn list_tools(caller: ToolCaller) -> Result<impl IntoResponse, ServiceError> {
+    Ok(Json(DataResponse::new(caller.tools())))
+}
+
+/// POST /tools/:name - Call a tool
+#[utoipa::path(
+    post,
+    path = "/tools/{name}",
+    tag = "tools",
+    params(
+        ("name" = String, Path, description = "Tool name, as listed by `GET /tools`"),
+        ("X-Api-Key" = String, Header, description = "Tool API key"),
+    ),
+    request_body = ToolCallRequest,
+    responses(
+        (status = 200, description = "What the tool did, or would do in a dry run", body = ToolCallResponse),
+        (status = 400, description = "Input doesn't match the tool's schema, or rejected or held by the content policy", body = ProblemDetails),
+        (status = 401, description = "Missing or invalid API key", body = ProblemDetails),
+        (status = 403, description = "The key or its user may not use the tool or change the post", body = ProblemDetails),
+        (status = 404, description = "Unknown tool, post not found, or analytics not active", body = ProblemDetails),
+        (status = 423, description = "Someone else is editing the post", body = ProblemDetails),
+        (status = 503, description = "The analytics report took too long", body = ProblemDetails),
+    ),
+)]
+pub async fn call_tool(
+    State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
+    Path(name): Path<String>,
+    caller: ToolCaller,
+    Json(req): Json<ToolCallRequest>,
+) -> Result<impl IntoResponse, ServiceError> {
+    let tool = Tool::from_name(&name).ok_or_else(|| ServiceError::NotFound(format!("Unknown tool: {}", name)))?;
+    let dry_run = req.dry_run && tool.writes();
+    let result = tools::call(&services, &site, &caller, tool, req.input, dry_run).await?;
+    Ok(Json(ToolCallResponse {
+        tool: name,
+        dry_run,
+        result,
+    }))
+}
+
+/// GET /admin/tool-keys - The site's tool API keys
+#[utoipa::path(
+    get,
+    path = "/admin/tool-keys",
+    tag = "tools",
+    responses(
+        (status = 200, description = "Keys, newest first, revoked ones included", body = inline(DataResponse<Vec<ToolKey>>)),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Insufficient permissions", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn list_tool_keys(
+    State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
+) -> Result<impl IntoResponse, ServiceError> {
+    let keys = services.tools.list_keys(&site).await?;
+    Ok(Json(DataResponse::with_count(keys)))
+}
+
+/// POST /admin/tool-keys - Create a tool API key
+#[utoipa::path(
+    post,
+    path = "/admin/tool-keys",
+    tag = "tools",
+    request_body = CreateToolKeyRequest,
+    responses(
+        (status = 201, description = "Key created; `secret` is only shown now", body = CreatedToolKey),
+        (status = 400, description = "Validation failed, or a scope the user's role doesn't allow", body = ProblemDetails),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Insufficient permissions", body = ProblemDetails),
+        (status = 404, description = "User not found or not active", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn create_tool_key(
+    State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
+    AuthUser(user): AuthUser,
+    ValidatedJson(req): ValidatedJson<CreateToolKeyRequest>,
+) -> Result<impl IntoResponse, ServiceError> {
+    let key = services.tools.create_key(&site, user.id, req).await?;
+    Ok((StatusCode::CREATED, Json(key)))
+}
+
+/// DELETE /admin/tool-keys/:id - Revoke a tool API key
+#[utoipa::path(
+    delete,
+    path = "/admin/tool-keys/{id}",
+    tag = "tools",
+    params(("id" = Uuid, Path, description = "Key ID")),
+    responses(
+        (status = 204, description = "Key revoked"),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Insufficient permissions", body = ProblemDetails),
+        (status = 404, description = "Key not found", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn revoke_tool_key(
+    State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, ServiceError> {
+    services.tools.revoke_key(&site, id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,110 @@
+//! Saved Post View Handlers
+
+use crate::extractors::{AuthUser, CurrentSite, ValidatedJson};
+use crate::models::*;
+use crate::services::ServiceError;
+use crate::BlogServices;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// GET /admin/post-views - The current user's saved views
+#[utoipa::path(
+    get,
+    path = "/admin/post-views",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Saved views of the admin post list, by name", body = inline(DataResponse<Vec<PostView>>)),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Insufficient permissions", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn list_views(
+    State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
+    AuthUser(user): AuthUser,
+) -> Result<impl IntoResponse, ServiceError> {
+    let views = services.views.list(&site, user.id).await?;
+    Ok(Json(DataResponse::new(views)))
+}
+
+/// POST /admin/post-views - Save a view
+#[utoipa::path(
+    post,
+    path = "/admin/post-views",
+    tag = "admin",
+    request_body = PostViewRequest,
+    responses(
+        (status = 201, description = "View saved", body = PostView),
+        (status = 400, description = "Validation failed or name taken", body = ProblemDetails),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Insufficient permissions", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn create_view(
+    State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
+    AuthUser(user): AuthUser,
+    ValidatedJson(req): ValidatedJson<PostViewRequest>,
+) -> Result<impl IntoResponse, ServiceError> {
+    let view = services.views.create(&site, user.id, req).await?;
+    Ok((StatusCode::CREATED, Json(view)))
+}
+
+/// PUT /admin/post-views/:id - Replace a view's name, filters and columns
+#[utoipa::path(
+    put,
+    path = "/admin/post-views/{id}",
+    tag = "admin",
+    params(("id" = Uuid, Path, description = "View ID")),
+    request_body = PostViewRequest,
+    responses(
+        (status = 200, description = "View updated", body = PostView),
+        (status = 400, description = "Validation failed or name taken", body = ProblemDetails),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Insufficient permissions", body = ProblemDetails),
+        (status = 404, description = "View not found", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn update_view(
+    State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
+    AuthUser(user): AuthUser,
+    Path(id): Path<Uuid>,
+    ValidatedJson(req): ValidatedJson<PostViewRequest>,
+) -> Result<impl IntoResponse, ServiceError> {
+    let view = services.views.update(&site, user.id, id, req).await?;
+    Ok(Json(view))
+}
+
+/// DELETE /admin/post-views/:id - Delete a view
+#[utoipa::path(
+    delete,
+    path = "/admin/post-views/{id}",
+    tag = "admin",
+    params(("id" = Uuid, Path, description = "View ID")),
+    responses(
+        (status = 204, description = "View deleted"),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Insufficient permissions", body = ProblemDetails),
+        (status = 404, description = "View not found", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn delete_view(
+    State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
+    AuthUser(user): AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, ServiceError> {
+    services.views.delete(&site, user.id, id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,146 @@
+//! Widget and Sidebar Handlers
+
+use crate::extractors::{CurrentSite, ValidatedJson};
+use crate::models::*;
+use crate::services::ServiceError;
+use crate::BlogServices;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{Html, IntoResponse},
+    Json,
+};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// GET /sidebars/:sidebar - Render a sidebar as HTML
+#[utoipa::path(
+    get,
+    path = "/sidebars/{sidebar}",
+    tag = "widgets",
+    params(("sidebar" = String, Path, description = "Sidebar ID")),
+    responses(
+        (status = 200, description = "Rendered sidebar HTML", content_type = "text/html", body = String),
+        (status = 404, description = "Sidebar not found", body = ProblemDetails),
+    ),
+)]
+pub async fn render_sidebar(
+    State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
+    Path(sidebar): Path<String>,
+) -> Result<impl IntoResponse, ServiceError> {
+    let html = services.widgets.render_sidebar(&site, &sidebar).await?;
+    Ok(Html(html))
+}
+
+/// GET /admin/widgets/types - List registered widget types
+#[utoipa::path(
+    get,
+    path = "/admin/widgets/types",
+    tag = "widgets",
+    responses(
+        (status = 200, description = "Registered widget types", body = inline(DataResponse<Vec<WidgetTypeInfo>>)),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Insufficient permissions", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn list_widget_types(
+    State(services): State<Arc<BlogServices>>,
+) -> Result<impl IntoResponse, ServiceError> {
+    let types = services.widgets.registry().list().await;
+    Ok(Json(DataResponse::new(types)))
+}
+
+/// GET /admin/sidebars - List sidebars with their widget instances
+#[utoipa::path(
+    get,
+    path = "/admin/sidebars",
+    tag = "widgets",
+    responses(
+        (status = 200, description = "Widget instances grouped by sidebar", body = inline(DataResponse<BTreeMap<String, Vec<WidgetInstance>>>)),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Insufficient permissions", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn list_sidebars(
+    State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
+) -> Result<impl IntoResponse, ServiceError> {
+    let sidebars = services.widgets.list_sidebars(&site).await?;
+    Ok(Json(DataResponse::new(sidebars)))
+}
+
+/// POST /admin/widgets - Add a widget to a sidebar
+#[utoipa::path(
+    post,
+    path = "/admin/widgets",
+    tag = "widgets",
+    request_body = CreateWidgetRequest,
+    responses(
+        (status = 201, description = "Widget added", body = WidgetInstance),
+        (status = 400, description = "Validation failed", body = ProblemDetails),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Insufficient permissions", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn create_widget(
+    State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
+    ValidatedJson(req): ValidatedJson<CreateWidgetRequest>,
+) -> Result<impl IntoResponse, ServiceError> {
+    let widget = services.widgets.create(&site, req).await?;
+    Ok((StatusCode::CREATED, Json(widget)))
+}
+
+/// PUT /admin/widgets/:id - Move or reconfigure a widget
+#[utoipa::path(
+    put,
+    path = "/admin/widgets/{id}",
+    tag = "widgets",
+    params(("id" = Uuid, Path, description = "Widget ID")),
+    request_body = UpdateWidgetRequest,
+    responses(
+        (status = 200, description = "Widget updated", body = WidgetInstance),
+        (status = 400, description = "Validation failed", body = ProblemDetails),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Insufficient permissions", body = ProblemDetails),
+        (status = 404, description = "Widget not found", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn update_widget(
+    State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
+    Path(id): Path<Uuid>,
+    ValidatedJson(req): ValidatedJson<UpdateWidgetRequest>,
+) -> Result<impl IntoResponse, ServiceError> {
+    let widget = services.widgets.update(&site, id, req).await?;
+    Ok(Json(widget))
+}
+
+/// DELETE /admin/widgets/:id - Remove a widget
+#[utoipa::path(
+    delete,
+    path = "/admin/widgets/{id}",
+    tag = "widgets",
+    params(("id" = Uuid, Path, description = "Widget ID")),
+    responses(
+        (status = 204, description = "Widget removed"),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Insufficient permissions", body = ProblemDetails),
+        (status = 404, description = "Widget not found", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn delete_widget(
+    State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, ServiceError> {
+    services.widgets.delete(&site, id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,28 @@
+//! Hook Contexts
+//!
+//! Contexts handed to the actions and filters the blog runs. They carry the
+//! ID of the request being handled (see `rustpress_auth::problem::trace_id`),
+//! so a function or plugin logging `ctx.request_id` can be matched with the
+//! request's own logs and the `trace_id` of its error response. Outside a
+//! request the ID is left empty.
+
+use rustpress_apps::prelude::*;
+use rustpress_auth::problem::current_trace_id;
+
+/// Filter context for the current request
+pub fn filter_context() -> FilterContext {
+    let mut ctx = FilterContext::default();
+    if let Some(id) = current_trace_id() {
+        ctx.request_id = id;
+    }
+    ctx
+}
+
+/// Action context for the current request
+pub fn action_context() -> ActionContext {
+    let mut ctx = ActionContext::default();
+    if let Some(id) = current_trace_id() {
+        ctx.request_id = id;
+    }
+    ctx
+}
@@ -0,0 +1,357 @@
+//! Image Transformations and CDN Rewriting
+//!
+//! `GET /img/:id/:transform` serves a resized or re-encoded copy of an
+//! uploaded image. Transforms are comma-separated `key_value` pairs:
+//!
+//! | Key | Meaning |
+//! |-----|---------|
+//! | `w` | Width in pixels (`0` = derive from the height) |
+//! | `h` | Height in pixels (`0` = derive from the width) |
+//! | `q` | JPEG quality |
+//! | `f` | Output format: `jpeg`, `png`, `webp` |
+//!
+//! Only values in `[app.images]` allowlists are accepted, so clients can't
+//! generate an unbounded number of variants. Results are cached under the
+//! canonical transform, so `h_0,w_800` and `w_800,h_0` share an entry.
+//!
+//! `cdn_url` rewrites links to uploaded files and transformed images in
+//! responses to an external CDN (see `middleware::cdn`).
+
+use crate::cache::Cache;
+use crate::models::{Media, MediaVisibility};
+use crate::services::ServiceError;
+use crate::sites::Site;
+use image::{imageops::FilterType, DynamicImage, ImageFormat, ImageReader, Limits};
+use rustpress_apps::prelude::Storage;
+use serde::Deserialize;
+use sqlx::PgPool;
+use std::fmt;
+use std::io::Cursor;
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Formats transforms may produce
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    Jpeg,
+    Png,
+    Webp,
+}
+
+impl OutputFormat {
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "jpeg" | "jpg" => Some(Self::Jpeg),
+            "png" => Some(Self::Png),
+            "webp" => Some(Self::Webp),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Jpeg => "jpeg",
+            Self::Png => "png",
+            Self::Webp => "webp",
+        }
+    }
+
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            Self::Jpeg => "image/jpeg",
+            Self::Png => "image/png",
+            Self::Webp => "image/webp",
+        }
+    }
+
+    fn from_mime(mime: &str) -> Option<Self> {
+        match mime {
+            "image/jpeg" => Some(Self::Jpeg),
+            "image/png" => Some(Self::Png),
+            "image/webp" => Some(Self::Webp),
+            _ => None,
+        }
+    }
+}
+
+/// `[app.images]` settings
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ImageConfig {
+    /// Widths clients may request (`0` is always allowed)
+    pub allowed_widths: Vec<u32>,
+    /// Heights clients may request (`0` is always allowed)
+    pub allowed_heights: Vec<u32>,
+    pub allowed_qualities: Vec<u8>,
+    pub allowed_formats: Vec<OutputFormat>,
+    /// Quality used when a JPEG transform doesn't set `q`
+    pub default_quality: u8,
+    /// Sources larger than this in either dimension are refused
+    pub max_source_dimension: u32,
+    /// How long transformed images stay in the cache
+    pub cache_ttl_secs: u64,
+    /// Serve media through this origin (e.g. `https://cdn.example.com`)
+    pub cdn_url: Option<String>,
+    /// Links rewritten to `cdn_url`: path prefixes, or full URLs whose path
+    /// is kept on the CDN
+    pub cdn_rewrite_prefixes: Vec<String>,
+}
+
+impl Default for ImageConfig {
+    fn default() -> Self {
+        Self {
+            allowed_widths: vec![160, 320, 640, 800, 1024, 1280, 1920],
+            allowed_heights: vec![160, 320, 640, 800, 1024, 1280, 1920],
+            allowed_qualities: vec![50, 60, 75, 85, 90],
+            allowed_formats: vec![OutputFormat::Jpeg, OutputFormat::Png, OutputFormat::Webp],
+            default_quality: 85,
+            max_source_dimension: 10_000,
+            cache_ttl_secs: 86_400,
+            cdn_url: None,
+            cdn_rewrite_prefixes: vec!["/uploads/".to_string(), "/api/blog/img/".to_string()],
+        }
+    }
+}
+
+impl ImageConfig {
+    /// Prefix pairs (`from`, `to`) applied by the CDN middleware; empty
+    /// without a `cdn_url`
+    pub fn cdn_rewrites(&self) -> Vec<(String, String)> {
+        let Some(cdn) = self.cdn_url.as_deref().map(|url| url.trim_end_matches('/')) else {
+            return Vec::new();
+        };
+
+        self.cdn_rewrite_prefixes
+            .iter()
+            .map(|prefix| {
+                let path = match prefix.split_once("://") {
+                    Some((_, rest)) => rest.find('/').map_or("/", |i| &rest[i..]),
+                    None => prefix.as_str(),
+                };
+                (prefix.clone(), format!("{}{}", cdn, path))
+            })
+            .collect()
+    }
+}
+
+/// A parsed `w_800,h_0,q_75,f_webp` transform
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Transform {
+    pub width: u32,
+    pub height: u32,
+    pub quality: Option<u8>,
+    pub format: Option<OutputFormat>,
+}
+
+impl Transform {
+    /// Parse and check a transform against the allowlists
+    pub fn parse(spec: &str, config: &ImageConfig) -> Result<Self, ServiceError> {
+        let invalid = |msg: String| ServiceError::Validation(format!("Invalid transform '{}': {}", spec, msg));
+
+        let mut transform = Transform::default();
+        let mut seen = Vec::new();
+
+        for part in spec.split(',') {
+            let (key, value) = part
+                .split_once('_')
+                .ok_or_else(|| invalid(format!("expected key_value, got '{}'", part)))?;
+            if seen.contains(&key) {
+                return Err(invalid(format!("'{}' given twice", key)));
+            }
+            seen.push(key);
+
+            match key {
+                "w" | "h" => {
+                    let pixels: u32 = value.parse().map_err(|_| invalid(format!("bad size '{}'", value)))?;
+                    let allowed = if key == "w" { &config.allowed_widths } else { &config.allowed_heights };
+                    if pixels != 0 && !allowed.contains(&pixels) {
+                        return Err(invalid(format!("{} {} is not allowed; use one of {:?}", key, pixels, allowed)));
+                    }
+                    if key == "w" {
+                        transform.width = pixels;
+                    } else {
+                        transform.height = pixels;
+                    }
+                }
+                "q" => {
+                    let quality: u8 = value.parse().map_err(|_| invalid(format!("bad quality '{}'", value)))?;
+                    if !config.allowed_qualities.contains(&quality) {
+                        return Err(invalid(format!(
+                            "quality {} is not allowed; use one of {:?}",
+                            quality, config.allowed_qualities
+                        )));
+                    }
+                    transform.quality = Some(quality);
+                }
+                "f" => {
+                    let format = OutputFormat::parse(value)
+                        .filter(|f| config.allowed_formats.contains(f))
+                        .ok_or_else(|| invalid(format!("format '{}' is not allowed", value)))?;
+                    transform.format = Some(format);
+                }
+                other => return Err(invalid(format!("unknown key '{}'", other))),
+            }
+        }
+
+        Ok(transform)
+    }
+}
+
+/// Canonical form, used as the cache key
+impl fmt::Display for Transform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "w_{},h_{}", self.width, self.height)?;
+        if let Some(quality) = self.quality {
+            write!(f, ",q_{}", quality)?;
+        }
+        if let Some(format) = self.format {
+            write!(f, ",f_{}", format.name())?;
+        }
+        Ok(())
+    }
+}
+
+/// A transformed image
+pub struct TransformedImage {
+    pub data: Vec<u8>,
+    pub mime_type: &'static str,
+}
+
+/// Produces and caches transformed images
+pub struct ImageService {
+    db: PgPool,
+    storage: Arc<dyn Storage>,
+    cache: Arc<dyn Cache>,
+    config: ImageConfig,
+}
+
+impl ImageService {
+    pub fn new(db: PgPool, storage: Arc<dyn Storage>, cache: Arc<dyn Cache>, config: ImageConfig) -> Self {
+        Self {
+            db,
+            storage,
+            cache,
+            config,
+        }
+    }
+
+    pub fn config(&self) -> &ImageConfig {
+        &self.config
+    }
+
+    /// Apply `spec` to a public image on `site`
+    pub async fn transform(&self, site: &Site, id: Uuid, spec: &str) -> Result<TransformedImage, ServiceError> {
+        let transform = Transform::parse(spec, &self.config)?;
+
+        // Private media is only reachable through signed links
+        let media: Media = sqlx::query_as("SELECT * FROM blog_media WHERE id = $1 AND site_id = $2 AND visibility = $3 AND trashed_at IS NULL")
+            .bind(id)
+            .bind(site.id)
+            .bind(MediaVisibility::Public)
+            .fetch_optional(&self.db)
+            .await?
+            .ok_or_else(|| ServiceError::NotFound("Image not found".into()))?;
+
+        let source_format = OutputFormat::from_mime(&media.mime_type)
+            .ok_or_else(|| ServiceError::Validation(format!("Cannot transform '{}' files", media.mime_type)))?;
+        let format = transform.format.unwrap_or(source_format);
+
+        let key = site.cache_key(&format!("img:{}:{}", id, transform));
+        if let Some(data) = self.cache.get_bytes(&key).await {
+            return Ok(TransformedImage {
+                data,
+                mime_type: format.mime_type(),
+            });
+        }
+
+        // Concurrent requests for a new variant share one resize
+        let _flight = self.cache.flights().join(&key).await;
+        if let Some(data) = self.cache.get_bytes(&key).await {
+            return Ok(TransformedImage {
+                data,
+                mime_type: format.mime_type(),
+            });
+        }
+
+        let source = self
+            .storage
+            .get(&media.storage_path())
+            .await
+            .map_err(|e| ServiceError::Storage(e.to_string()))?;
+        let quality = transform.quality.unwrap_or(self.config.default_quality);
+        let max_dimension = self.config.max_source_dimension;
+
+        let data = tokio::task::spawn_blocking(move || render(&source, transform, format, quality, max_dimension))
+            .await
+            .map_err(|e| ServiceError::Storage(e.to_string()))??;
+
+        self.cache
+            .set_bytes(&key, data.clone(), Some(Duration::from_secs(self.config.cache_ttl_secs)))
+            .await;
+
+        Ok(TransformedImage {
+            data,
+            mime_type: format.mime_type(),
+        })
+    }
+
+    /// Drop every cached variant of an image
+    pub async fn purge(&self, site: &Site, id: Uuid) {
+        self.cache.delete_pattern(&site.cache_key(&format!("img:{}:*", id))).await;
+    }
+}
+
+/// Decode, resize and encode; CPU-bound, so run off the async workers
+fn render(
+    source: &[u8],
+    transform: Transform,
+    format: OutputFormat,
+    quality: u8,
+    max_dimension: u32,
+) -> Result<Vec<u8>, ServiceError> {
+    let unreadable = |e: image::ImageError| ServiceError::Validation(format!("Unreadable image: {}", e));
+
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(max_dimension);
+    limits.max_image_height = Some(max_dimension);
+
+    let mut reader = ImageReader::new(Cursor::new(source))
+        .with_guessed_format()
+        .map_err(|e| ServiceError::Validation(e.to_string()))?;
+    reader.limits(limits);
+    let image = reader.decode().map_err(unreadable)?;
+
+    let image = resize(image, transform.width, transform.height);
+
+    let mut out = Cursor::new(Vec::new());
+    match format {
+        OutputFormat::Jpeg => {
+            // JPEG has no alpha channel
+            let encoder = image::codecs::jpeg::JpegEncoder::new_with_quality(&mut out, quality);
+            DynamicImage::ImageRgb8(image.to_rgb8())
+                .write_with_encoder(encoder)
+                .map_err(unreadable)?;
+        }
+        OutputFormat::Png => image.write_to(&mut out, ImageFormat::Png).map_err(unreadable)?,
+        // The bundled WebP encoder is lossless, so `q` doesn't apply
+        OutputFormat::Webp => DynamicImage::ImageRgba8(image.to_rgba8())
+            .write_to(&mut out, ImageFormat::WebP)
+            .map_err(unreadable)?,
+    }
+
+    Ok(out.into_inner())
+}
+
+/// Fit within `width` x `height` keeping the aspect ratio; `0` leaves a side
+/// unconstrained. Images are never enlarged.
+fn resize(image: DynamicImage, width: u32, height: u32) -> DynamicImage {
+    let width = if width == 0 { image.width() } else { width.min(image.width()) };
+    let height = if height == 0 { image.height() } else { height.min(image.height()) };
+
+    if width == image.width() && height == image.height() {
+        return image;
+    }
+    image.resize(width, height, FilterType::Lanczos3)
+}
@@ -4,15 +4,57 @@
 //!
 //! This app integrates with the `rustpress-auth` plugin for authentication.
 //! The auth plugin must be activated before this app to provide:
-//! - User authentication endpoints (/auth/*)
+//! - User authentication endpoints (/api/v1/auth/*, /api/v2/auth/*)
 //! - JWT validation middleware
 //! - User extractors
+//!
+//! One deployment can serve several blogs; see `sites` for how requests are
+//! resolved to a site.
 
+pub mod access;
+pub mod activity;
+pub mod alt_text;
+pub mod amp;
+pub mod backups;
+pub mod cache;
+pub mod content_sync;
+pub mod diff;
+pub mod digests;
+pub mod edit_locks;
+pub mod embeddings;
+pub mod excerpts;
+pub mod export;
 pub mod extractors;
+pub mod feeds;
+pub mod grpc;
 pub mod handlers;
+pub mod hooks;
+pub mod images;
+pub mod marketplace;
+pub mod media_cleanup;
+pub mod mentions;
 pub mod middleware;
 pub mod models;
+pub mod moderation;
+pub mod openapi;
+pub mod plugins;
+pub mod policies;
+pub mod preferences;
+pub mod revisions;
+pub mod save_filter;
 pub mod services;
+pub mod settings;
+pub mod signed_urls;
+pub mod sites;
+pub mod template_functions;
+pub mod theme;
+pub mod timezones;
+pub mod tools;
+pub mod toxicity;
+pub mod trust;
+pub mod views;
+pub mod votes;
+pub mod widgets;
 
 use axum::{
     middleware as axum_middleware,
@@ -20,12 +62,19 @@ use axum::{
     Router,
 };
 use rustpress_apps::prelude::*;
+use rustpress_auth::db::{DbPools, ReplicaConfig};
+use rustpress_auth::Config;
+use std::path::PathBuf;
 use std::sync::Arc;
 
+/// App id, used for its settings namespace and as the owner of its routes
+pub const APP_ID: &str = "blog-api";
+
 /// Blog API Application
 pub struct BlogApp {
     config: AppConfig,
     services: Option<Arc<BlogServices>>,
+    grpc: Option<grpc::GrpcServer>,
 }
 
 /// Application configuration
@@ -37,6 +86,32 @@ pub struct AppConfig {
     pub max_comment_depth: i32,
     pub excerpt_length: usize,
     pub feed_items: usize,
+    pub themes_dir: PathBuf,
+    pub active_theme: String,
+    pub plugins_dir: PathBuf,
+    pub marketplace: marketplace::MarketplaceConfig,
+    pub cache: cache::CacheConfig,
+    pub security: rustpress_auth::SecurityHeaders,
+    pub cors: rustpress_auth::CorsConfig,
+    pub media: signed_urls::MediaConfig,
+    pub images: images::ImageConfig,
+    pub alt_text: alt_text::AltTextConfig,
+    pub export: export::ExportConfig,
+    pub backups: backups::BackupConfig,
+    pub feeds: feeds::FeedConfig,
+    pub embeddings: embeddings::EmbeddingConfig,
+    pub sync: content_sync::SyncConfig,
+    pub amp: amp::AmpConfig,
+    pub digests: digests::DigestConfig,
+    pub comment_votes: votes::VoteConfig,
+    pub mentions: mentions::MentionConfig,
+    pub trusted_commenters: trust::TrustConfig,
+    pub toxicity: toxicity::ToxicityConfig,
+    pub edit_locks: edit_locks::EditLockConfig,
+    pub preferences: preferences::PreferenceConfig,
+    pub template_functions: template_functions::TemplateFunctionConfig,
+    pub grpc: grpc::GrpcConfig,
+    pub tools: tools::ToolConfig,
 }
 
 impl Default for AppConfig {
@@ -48,18 +123,81 @@ impl Default for AppConfig {
             max_comment_depth: 3,
             excerpt_length: 200,
             feed_items: 20,
+            themes_dir: PathBuf::from("themes"),
+            active_theme: "default".to_string(),
+            plugins_dir: PathBuf::from("plugins"),
+            marketplace: marketplace::MarketplaceConfig::default(),
+            cache: cache::CacheConfig::default(),
+            security: rustpress_auth::SecurityHeaders::default(),
+            cors: rustpress_auth::CorsConfig::default(),
+            media: signed_urls::MediaConfig::default(),
+            images: images::ImageConfig::default(),
+            alt_text: alt_text::AltTextConfig::default(),
+            export: export::ExportConfig::default(),
+            backups: backups::BackupConfig::default(),
+            feeds: feeds::FeedConfig::default(),
+            embeddings: embeddings::EmbeddingConfig::default(),
+            sync: content_sync::SyncConfig::default(),
+            amp: amp::AmpConfig::default(),
+            digests: digests::DigestConfig::default(),
+            comment_votes: votes::VoteConfig::default(),
+            mentions: mentions::MentionConfig::default(),
+            trusted_commenters: trust::TrustConfig::default(),
+            toxicity: toxicity::ToxicityConfig::default(),
+            edit_locks: edit_locks::EditLockConfig::default(),
+            preferences: preferences::PreferenceConfig::default(),
+            template_functions: template_functions::TemplateFunctionConfig::default(),
+            grpc: grpc::GrpcConfig::default(),
+            tools: tools::ToolConfig::default(),
         }
     }
 }
 
 /// Aggregated services container
 pub struct BlogServices {
+    pub hooks: Arc<activity::ContentHooks>,
+    pub activity: Arc<activity::ActivityLog>,
+    pub digests: Arc<digests::DigestService>,
+    pub mentions: Arc<mentions::MentionService>,
+    pub revisions: Arc<revisions::RevisionService>,
+    pub access: Arc<access::ContentAccess>,
     pub posts: services::PostService,
+    pub save_filter: save_filter::SaveFilter,
+    pub locks: edit_locks::EditLockService,
     pub comments: services::CommentService,
+    pub votes: votes::VoteService,
+    pub moderation: moderation::ModerationService,
+    pub trust: trust::TrustService,
+    pub toxicity: toxicity::ToxicityService,
     pub categories: services::CategoryService,
     pub tags: services::TagService,
+    pub authors: services::AuthorService,
     pub media: services::MediaService,
+    pub images: images::ImageService,
+    pub alt_text: alt_text::AltTextService,
     pub search: services::SearchService,
+    pub embeddings: embeddings::EmbeddingService,
+    pub theme: theme::ThemeService,
+    pub preferences: Arc<preferences::PreferenceService>,
+    pub amp: amp::AmpService,
+    pub export: export::StaticExporter,
+    pub backups: backups::BackupService,
+    pub feeds: feeds::FeedCache,
+    pub sync: content_sync::ContentSync,
+    pub widgets: Arc<widgets::WidgetService>,
+    pub views: views::PostViewService,
+    pub settings: settings::SettingsService,
+    pub plugins: plugins::PluginManagerService,
+    pub marketplace: marketplace::MarketplaceService,
+    pub sites: sites::SiteService,
+    pub tools: tools::ToolService,
+}
+
+impl BlogApp {
+    /// Schema migrations shipped with the app (`migrations/`)
+    pub fn migrations() -> sqlx::migrate::Migrator {
+        sqlx::migrate!("./migrations")
+    }
 }
 
 #[rustpress_apps::app]
@@ -68,29 +206,261 @@ impl App for BlogApp {
         Self {
             config: AppConfig::default(),
             services: None,
+            grpc: None,
         }
     }
 
     async fn activate(&mut self, ctx: &AppContext) -> Result<(), AppError> {
         tracing::info!("Activating Blog API");
 
+        self.config
+            .cors
+            .validate()
+            .map_err(|e| AppError::Internal(e.to_string()))?;
+
+        // Claim the blog's paths so plugins can't mount over them
+        rustpress_auth::routes::shared()
+            .register(APP_ID, openapi::route_paths())
+            .map_err(|e| AppError::Internal(e.to_string()))?;
+
+        // Who may edit and delete posts and media (`policies.*` overrides)
+        Config::load()
+            .map_err(rustpress_auth::policy::PolicyError::from)
+            .and_then(|config| rustpress_auth::policy::shared().define(&config, policies::DEFAULTS))
+            .map_err(|e| AppError::Internal(e.to_string()))?;
+
         // Run migrations
-        sqlx::migrate!("./migrations")
+        Self::migrations()
             .run(&ctx.db)
             .await
             .map_err(|e| AppError::Database(e.to_string()))?;
 
+        // Settings namespaces: the app's, installed plugins' and the auth
+        // plugin's configuration (read-only)
+        let settings_registry = settings::SettingsRegistry::default();
+        settings_registry
+            .register_manifest(APP_ID, include_str!("../app.toml"), "admin")
+            .await
+            .map_err(|e| AppError::Internal(e.to_string()))?;
+        settings_registry
+            .register_config(rustpress_auth::PLUGIN_ID, rustpress_auth::config::SETTINGS_SCHEMA)
+            .await
+            .map_err(|e| AppError::Internal(e.to_string()))?;
+        let installed = ctx.plugins.installed().await.into_iter().map(|plugin| plugin.info.id);
+        settings_registry.register_plugins(&self.config.plugins_dir, installed).await;
+
+        let sites = sites::SiteService::load(ctx.db.clone())
+            .await
+            .map_err(|e| AppError::Internal(e.to_string()))?;
+
+        let widgets = Arc::new(widgets::WidgetService::new(
+            ctx.db.clone(),
+            ctx.hooks.clone(),
+            Arc::new(widgets::WidgetRegistry::with_builtins()),
+        ));
+
+        // Readers' display preferences, rendered into theme pages
+        let preferences = Arc::new(preferences::PreferenceService::new(
+            ctx.db.clone(),
+            self.config.preferences.clone(),
+        ));
+
+        let theme = theme::ThemeService::new(
+            theme::ThemeConfig {
+                themes_dir: self.config.themes_dir.clone(),
+                active_theme: self.config.active_theme.clone(),
+                amp: self.config.amp.enabled,
+            },
+            ctx.db.clone(),
+            ctx.hooks.clone(),
+            widgets.clone(),
+            preferences.clone(),
+            Arc::new(template_functions::TemplateFunctionRegistry::with_builtins(
+                self.config.template_functions.clone(),
+            )),
+        )
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+
+        // Read replicas (`database.replica_urls`) for post listings
+        let replicas = Config::load()
+            .and_then(|config| ReplicaConfig::from_config(&config))
+            .map_err(|e| AppError::Internal(e.to_string()))?;
+        let pools = DbPools::connect(ctx.db.clone(), &replicas)
+            .await
+            .map_err(|e| AppError::Database(e.to_string()))?;
+
+        let cache = cache::connect(&self.config.cache)
+            .await
+            .map_err(|e| AppError::Internal(e.to_string()))?;
+
+        // Content changes are recorded by the activity log; plugins can
+        // listen on the same hooks or handle the `content_changed` action
+        let hooks = Arc::new(activity::ContentHooks::with_actions(ctx.hooks.clone()));
+        let activity = Arc::new(activity::ActivityLog::new(ctx.db.clone()));
+        hooks.listen(activity.clone()).await;
+
+        // Comments on a post are summarized for its author in a digest
+        let digests = Arc::new(digests::DigestService::new(ctx.db.clone(), self.config.digests.clone()));
+        if self.config.digests.enabled {
+            hooks.listen(digests.clone()).await;
+        }
+
+        // `@username` in posts and comments notifies that user
+        let mentions = Arc::new(mentions::MentionService::new(
+            ctx.db.clone(),
+            digests.clone(),
+            self.config.mentions.clone(),
+        ));
+        if self.config.mentions.enabled {
+            hooks.listen(mentions.clone()).await;
+        }
+
+        // Each save with new text becomes a numbered revision
+        let revisions = Arc::new(revisions::RevisionService::new(ctx.db.clone()));
+        hooks.listen(revisions.clone()).await;
+
+        // Static exports follow post changes once a site has been exported
+        let export_changes = if self.config.export.incremental {
+            let (listener, changes) = export::ExportListener::channel();
+            hooks.listen(Arc::new(listener)).await;
+            Some(changes)
+        } else {
+            None
+        };
+
+        // Cached feeds and sitemaps are regenerated when posts change
+        let feed_changes = if self.config.feeds.cached {
+            let (listener, changes) = feeds::FeedListener::channel();
+            hooks.listen(Arc::new(listener)).await;
+            Some(changes)
+        } else {
+            None
+        };
+
+        // Posts mirrored to Markdown files are written when saved
+        let sync_changes = if self.config.sync.enabled {
+            let (listener, changes) = content_sync::SyncListener::channel();
+            hooks.listen(Arc::new(listener)).await;
+            Some(changes)
+        } else {
+            None
+        };
+
+        // Published posts are embedded for semantic search and related posts
+        let embeddings = embeddings::EmbeddingService::new(ctx.db.clone(), self.config.embeddings.clone())
+            .map_err(|e| AppError::Internal(e.to_string()))?;
+        let embedding_changes = if self.config.embeddings.enabled {
+            embeddings.prepare().await.map_err(|e| {
+                AppError::Database(format!("semantic search needs the pgvector extension: {}", e))
+            })?;
+            let (listener, changes) = embeddings::EmbeddingListener::channel();
+            hooks.listen(Arc::new(listener)).await;
+            Some(changes)
+        } else {
+            None
+        };
+
+        // Members-only posts; a membership plugin supplies levels and teasers
+        let access = Arc::new(access::ContentAccess::new(pools.clone(), ctx.hooks.clone()));
+
         // Initialize services
         // Note: Authentication is handled by the rustpress-auth plugin
         let services = Arc::new(BlogServices {
-            posts: services::PostService::new(ctx.db.clone(), ctx.cache.clone()),
-            comments: services::CommentService::new(ctx.db.clone()),
-            categories: services::CategoryService::new(ctx.db.clone(), ctx.cache.clone()),
-            tags: services::TagService::new(ctx.db.clone(), ctx.cache.clone()),
-            media: services::MediaService::new(ctx.db.clone(), ctx.storage.clone()),
+            posts: services::PostService::new(pools, cache.clone(), hooks.clone(), access.clone(), mentions.clone(), self.config.excerpt_length),
+            // Plugins can fill in excerpts, meta descriptions, ... on save
+            save_filter: save_filter::SaveFilter::new(ctx.db.clone(), ctx.hooks.clone()),
+            locks: edit_locks::EditLockService::new(ctx.db.clone(), self.config.edit_locks.clone()),
+            comments: services::CommentService::new(ctx.db.clone(), hooks.clone()),
+            votes: votes::VoteService::new(ctx.db.clone(), self.config.comment_votes.clone()),
+            moderation: moderation::ModerationService::new(ctx.settings.clone()),
+            trust: trust::TrustService::new(ctx.db.clone(), self.config.trusted_commenters.clone()),
+            // Toxic comments are held or marked as spam
+            toxicity: toxicity::ToxicityService::new(self.config.toxicity.clone())
+                .map_err(|e| AppError::Internal(e.to_string()))?,
+            categories: services::CategoryService::new(ctx.db.clone(), cache.clone(), hooks.clone()),
+            tags: services::TagService::new(ctx.db.clone(), cache.clone()),
+            authors: services::AuthorService::new(ctx.db.clone(), cache.clone()),
+            media: services::MediaService::new(ctx.db.clone(), ctx.storage.clone(), &self.config.media),
+            images: images::ImageService::new(
+                ctx.db.clone(),
+                ctx.storage.clone(),
+                cache.clone(),
+                self.config.images.clone(),
+            ),
+            // Vision model alt text for sites that opt in, confirmed by editors
+            alt_text: alt_text::AltTextService::new(ctx.db.clone(), self.config.alt_text.clone())
+                .map_err(|e| AppError::Internal(e.to_string()))?,
             search: services::SearchService::new(ctx.db.clone()),
+            embeddings,
+            theme,
+            preferences,
+            amp: amp::AmpService::new(ctx.hooks.clone(), self.config.amp.clone()),
+            export: export::StaticExporter::new(ctx.storage.clone(), self.config.export.clone()),
+            // Table dumps and media manifests in storage, with checksums
+            backups: backups::BackupService::new(ctx.db.clone(), ctx.storage.clone(), self.config.backups.clone()),
+            feeds: feeds::FeedCache::new(cache, self.config.feeds.clone()),
+            sync: content_sync::ContentSync::new(ctx.db.clone(), self.config.sync.clone()),
+            widgets,
+            views: views::PostViewService::new(ctx.db.clone()),
+            settings: settings::SettingsService::new(ctx.db.clone(), ctx.settings.clone(), settings_registry),
+            plugins: plugins::PluginManagerService::new(
+                ctx.db.clone(),
+                ctx.plugins.clone(),
+                self.config.plugins_dir.clone(),
+            ),
+            // Signed plugins from the registry, installed into plugins_dir
+            marketplace: marketplace::MarketplaceService::new(
+                ctx.db.clone(),
+                ctx.plugins.clone(),
+                self.config.plugins_dir.clone(),
+                self.config.marketplace.clone(),
+            )
+            .map_err(|e| AppError::Internal(e.to_string()))?,
+            sites,
+            tools: tools::ToolService::new(ctx.db.clone(), self.config.tools.clone()),
+            hooks,
+            activity,
+            digests,
+            mentions,
+            revisions,
+            access,
         });
 
+        if let Some(changes) = export_changes {
+            tokio::spawn(export::run_incremental(Arc::downgrade(&services), changes));
+        }
+        if let Some(changes) = feed_changes {
+            tokio::spawn(feeds::run(Arc::downgrade(&services), changes));
+        }
+        if let Some(changes) = embedding_changes {
+            tokio::spawn(embeddings::run(Arc::downgrade(&services), changes));
+        }
+        if let Some(changes) = sync_changes {
+            tokio::spawn(content_sync::run_export(Arc::downgrade(&services), changes));
+            if self.config.sync.poll_secs > 0 {
+                tokio::spawn(content_sync::run_watcher(Arc::downgrade(&services), self.config.sync.poll_secs));
+            }
+        }
+        tokio::spawn(media_cleanup::run(
+            Arc::downgrade(&services),
+            self.config.media.cleanup_interval_secs,
+        ));
+        if services.alt_text.enabled() {
+            tokio::spawn(alt_text::run(Arc::downgrade(&services), self.config.alt_text.poll_secs));
+        }
+        if self.config.backups.enabled {
+            tokio::spawn(backups::run(Arc::downgrade(&services), self.config.backups.interval_secs));
+        }
+        if self.config.digests.enabled {
+            tokio::spawn(digests::run(Arc::downgrade(&services), self.config.digests.check_secs));
+        }
+
+        // Post reads, token checks and ingestion for internal gRPC clients
+        if self.config.grpc.enabled {
+            let server = grpc::start(services.clone(), &self.config.grpc).map_err(AppError::Internal)?;
+            self.grpc = Some(server);
+        }
+
         self.services = Some(services);
 
         tracing::info!("Blog API activated successfully");
@@ -99,6 +469,9 @@ impl App for BlogApp {
 
     async fn deactivate(&mut self, _ctx: &AppContext) -> Result<(), AppError> {
         tracing::info!("Deactivating Blog API");
+        rustpress_auth::routes::shared().unregister(APP_ID);
+        rustpress_auth::policy::shared().remove(policies::DEFAULTS.iter().map(|(name, _)| *name));
+        self.grpc = None;
         self.services = None;
         Ok(())
     }
@@ -110,25 +483,62 @@ impl App for BlogApp {
         let public = Router::new()
             .route("/posts", get(handlers::posts::list_posts))
             .route("/posts/:slug", get(handlers::posts::get_post_by_slug))
+            .route("/posts/:id/related", get(handlers::posts::related_posts))
             .route("/posts/:id/comments", get(handlers::comments::list_comments))
             .route("/posts/:id/comments", post(handlers::comments::create_comment))
+            .route("/comments/:id/vote", put(handlers::comments::vote_comment))
             .route("/categories", get(handlers::categories::list_categories))
             .route("/tags", get(handlers::tags::list_tags))
+            .route("/authors", get(handlers::authors::list_authors))
+            .route("/authors/:slug", get(handlers::authors::get_author))
             .route("/feed", get(handlers::feed::rss_feed))
+            .route("/sitemap.xml", get(handlers::sitemap::sitemap))
             .route("/search", get(handlers::search::search_posts))
-            .layer(axum_middleware::from_fn(middleware::view_counter::increment_views));
+            .route("/search/semantic", get(handlers::search::semantic_search))
+            .route("/sync/webhook", post(handlers::sync::webhook))
+            .route("/sidebars/:sidebar", get(handlers::widgets::render_sidebar))
+            .route(
+                "/media/:id/download",
+                get(handlers::media::download_media).route_layer(axum_middleware::from_fn_with_state(
+                    services.clone(),
+                    middleware::signed_url::verify_download,
+                )),
+            )
+            .route("/img/:id/:transform", get(handlers::images::transform_image))
+            .layer(axum_middleware::from_fn(middleware::view_counter::increment_views))
+            .layer(self.config.cors.public.layer());
+
+        // Server-rendered theme pages
+        let pages = Router::new()
+            .route("/", get(handlers::pages::home))
+            .route("/read/:slug", get(handlers::pages::single))
+            .route("/posts/:slug/amp", get(handlers::pages::amp))
+            .route("/pages/:slug", get(handlers::pages::page))
+            .route("/category/:slug", get(handlers::pages::category_archive))
+            .route("/tag/:slug", get(handlers::pages::tag_archive))
+            .route("/author/:slug", get(handlers::pages::author_archive))
+            .layer(self.config.cors.public.layer());
 
         // Protected routes (require authentication via rustpress-auth plugin)
         let protected = Router::new()
             .route("/posts", post(handlers::posts::create_post))
             .route("/posts/:id", put(handlers::posts::update_post))
             .route("/posts/:id", delete(handlers::posts::delete_post))
+            .route("/posts/:id/revisions", get(handlers::posts::list_revisions))
+            .route("/posts/:id/diff", get(handlers::posts::diff_post))
+            .route("/posts/:id/lock", post(handlers::posts::lock_post))
+            .route("/posts/:id/lock", delete(handlers::posts::unlock_post))
             .route("/posts/:id/publish", post(handlers::posts::publish_post))
             .route("/posts/:id/unpublish", post(handlers::posts::unpublish_post))
             .route("/drafts", get(handlers::posts::list_drafts))
             .route("/media", get(handlers::media::list_media))
             .route("/media", post(handlers::media::upload_media))
             .route("/media/:id", delete(handlers::media::delete_media))
+            .route("/media/alt-text", get(handlers::media::list_alt_text))
+            .route("/media/:id/alt-text/accept", post(handlers::media::accept_alt_text))
+            .route("/media/:id/alt-text/reject", post(handlers::media::reject_alt_text))
+            .route("/media/:id/url", get(handlers::media::get_media_url))
+            .route("/media/:id/visibility", put(handlers::media::update_media_visibility))
             .route("/comments/:id/approve", post(handlers::comments::approve_comment))
             .route("/comments/:id/reject", post(handlers::comments::reject_comment))
             .route("/categories", post(handlers::categories::create_category))
@@ -137,23 +547,115 @@ impl App for BlogApp {
             .route("/tags", post(handlers::tags::create_tag))
             .route("/tags/:id", put(handlers::tags::update_tag))
             .route("/tags/:id", delete(handlers::tags::delete_tag))
-            .layer(axum_middleware::from_fn(middleware::auth::require_auth));
+            .route("/settings/export", get(handlers::settings::export_settings))
+            .route("/settings/import", post(handlers::settings::import_settings))
+            .route("/settings/:namespace", get(handlers::settings::get_settings))
+            .route("/settings/:namespace", put(handlers::settings::update_settings))
+            .route("/settings/:namespace/audit", get(handlers::settings::settings_audit))
+            .route("/plugins/:id/settings-schema", get(handlers::settings::settings_schema))
+            .route("/notifications/preferences", get(handlers::notifications::get_preferences))
+            .route("/notifications/preferences", put(handlers::notifications::update_preferences))
+            .route("/notifications/mentions", get(handlers::notifications::list_mentions))
+            .route("/notifications/mentions/read", post(handlers::notifications::mark_mentions_read))
+            .route("/me/preferences", get(handlers::preferences::get_my_preferences))
+            .route("/me/preferences", put(handlers::preferences::update_my_preferences))
+            .layer(axum_middleware::from_fn_with_state(services.clone(), sites::require_member))
+            .layer(axum_middleware::from_fn(middleware::auth::require_auth))
+            .layer(self.config.cors.protected.layer());
+
+        // Tool API for agents, authenticated by tool keys instead of tokens
+        let tools = Router::new()
+            .route("/tools", get(handlers::tools::list_tools))
+            .route("/tools/:name", post(handlers::tools::call_tool))
+            .layer(self.config.cors.protected.layer());
 
         // Admin routes
         let admin = Router::new()
             .route("/admin/posts", get(handlers::admin::list_all_posts))
+            .route("/admin/posts.csv", get(handlers::admin::export_posts))
+            .route("/admin/posts/:id/lock/takeover", post(handlers::admin::take_over_lock))
+            .route("/admin/post-views", get(handlers::views::list_views))
+            .route("/admin/post-views", post(handlers::views::create_view))
+            .route("/admin/post-views/:id", put(handlers::views::update_view))
+            .route("/admin/post-views/:id", delete(handlers::views::delete_view))
             .route("/admin/comments/pending", get(handlers::admin::pending_comments))
+            .route("/admin/comments/auto-approved", get(handlers::admin::list_auto_approvals))
+            .route("/admin/comments/auto-approved/:id/review", post(handlers::admin::review_auto_approval))
+            .route("/admin/commenters/:user_id/trust", get(handlers::admin::get_commenter_trust))
+            .route("/admin/commenters/:user_id/trust", put(handlers::admin::set_commenter_trust))
             .route("/admin/stats", get(handlers::admin::blog_stats))
-            .layer(axum_middleware::from_fn(middleware::auth::require_admin));
+            .route("/admin/activity", get(handlers::admin::list_activity))
+            .route("/admin/moderation/test", post(handlers::admin::test_moderation))
+            .route("/admin/media/trash", get(handlers::media::list_trash))
+            .route("/admin/media/:id/restore", post(handlers::media::restore_media))
+            .route("/admin/media/orphans", get(handlers::media::media_orphans))
+            .route("/admin/media/orphans/scan", post(handlers::media::scan_media_orphans))
+            .route("/admin/media/alt-text/backfill", post(handlers::media::backfill_alt_text))
+            .route("/admin/export", post(handlers::export::export_site))
+            .route("/admin/export.zip", get(handlers::export::download_export))
+            .route("/admin/backups", get(handlers::backups::list_backups))
+            .route("/admin/backups", post(handlers::backups::create_backup))
+            .route("/admin/backups/:id/verify", post(handlers::backups::verify_backup))
+            .route("/admin/sync/import", post(handlers::sync::import_files))
+            .route("/admin/sync/export", post(handlers::sync::export_files))
+            .route("/admin/plugins", get(handlers::plugins::list_plugins))
+            .route("/admin/plugins/:id/activate", post(handlers::plugins::activate_plugin))
+            .route("/admin/plugins/:id/deactivate", post(handlers::plugins::deactivate_plugin))
+            .route("/admin/plugins/:id/upgrade", post(handlers::plugins::upgrade_plugin))
+            .route("/admin/plugins/:id/migrations", get(handlers::plugins::list_migrations))
+            .route("/admin/plugins/:id/migrate", post(handlers::plugins::migrate_plugin))
+            .route("/plugins/available", get(handlers::plugins::available_plugins))
+            .route("/plugins/installations", get(handlers::plugins::list_installations))
+            .route("/plugins/:id/install", post(handlers::plugins::install_plugin))
+            .route("/admin/widgets/types", get(handlers::widgets::list_widget_types))
+            .route("/admin/sidebars", get(handlers::widgets::list_sidebars))
+            .route("/admin/widgets", post(handlers::widgets::create_widget))
+            .route("/admin/widgets/:id", put(handlers::widgets::update_widget))
+            .route("/admin/widgets/:id", delete(handlers::widgets::delete_widget))
+            .route("/admin/sites", get(handlers::sites::list_sites))
+            .route("/admin/sites", post(handlers::sites::create_site))
+            .route("/admin/sites/:id", put(handlers::sites::update_site))
+            .route("/admin/sites/:id", delete(handlers::sites::delete_site))
+            .route("/admin/sites/:id/members", get(handlers::sites::list_members))
+            .route("/admin/sites/:id/members/:user_id", put(handlers::sites::set_member))
+            .route("/admin/sites/:id/members/:user_id", delete(handlers::sites::remove_member))
+            .route("/admin/tool-keys", get(handlers::tools::list_tool_keys))
+            .route("/admin/tool-keys", post(handlers::tools::create_tool_key))
+            .route("/admin/tool-keys/:id", delete(handlers::tools::revoke_tool_key))
+            .layer(axum_middleware::from_fn(middleware::auth::require_admin))
+            .layer(self.config.cors.admin.layer());
 
-        // Merge all routes
-        // Note: Auth routes (/auth/*) are provided by the rustpress-auth plugin
-        Router::new()
+        // Merge all routes. Preflights for a path shared by several groups are
+        // answered by the last one merged, so public routes go first.
+        // Note: Auth routes (/api/{version}/auth/*) are provided by the rustpress-auth plugin
+        let app = Router::new()
             .merge(public)
+            .merge(pages)
             .merge(protected)
+            .merge(tools)
             .merge(admin)
             .layer(axum_middleware::from_fn(middleware::cache::cache_response))
+            .layer(axum_middleware::from_fn_with_state(
+                Arc::new(self.config.images.cdn_rewrites()),
+                middleware::cdn::rewrite_media_urls,
+            ))
+            // Plugins' route filters see the path without the site prefix,
+            // and their answers are neither cached nor exempt from rate limits
+            .layer(axum_middleware::from_fn(rustpress_auth::route_filters::apply))
             .layer(axum_middleware::from_fn(middleware::rate_limit::rate_limiter))
-            .with_state(services)
+            .with_state(services.clone())
+            .merge(openapi::docs_routes());
+
+        // Site resolution strips path prefixes, so it must run before routing.
+        // Security headers wrap everything so every page can read the CSP nonce,
+        // and the trace ID wraps them too so every error response carries it.
+        Router::new()
+            .fallback_service(app)
+            .layer(axum_middleware::from_fn_with_state(services, sites::resolve_site))
+            .layer(axum_middleware::from_fn_with_state(
+                Arc::new(self.config.security.clone()),
+                rustpress_auth::security::security_headers,
+            ))
+            .layer(axum_middleware::from_fn(rustpress_auth::problem::trace_id))
     }
 }
@@ -0,0 +1,566 @@
+//! Plugin Marketplace
+//!
+//! Lists and installs plugins published by a registry. The registry at
+//! `[app.marketplace] registry_url` serves `index.json`, the plugins it
+//! offers with their versions, and `index.json.sig`, an Ed25519 signature of
+//! the index's exact bytes (base64). An index that no `public_keys` entry
+//! signed is refused, so a compromised mirror can't offer other plugins.
+//!
+//! Each version names an artifact: a zip of the plugin's directory
+//! (`plugin.toml`, migrations, the library) with its SHA-256 and its own
+//! Ed25519 signature. Installing downloads the artifact, checks both, and
+//! that `plugin.toml` is the plugin and version the index promised, then
+//! replaces `{plugins_dir}/{id}/` with it and records the installation in
+//! `plugin_installations`. The host loads installed plugins at startup;
+//! activation and upgrade stay with the lifecycle endpoints, which check
+//! dependencies as usual.
+//!
+//! Versions are compatible when the host (`host_version`) is at least their
+//! `min_rustpress_version`. WASM artifacts are listed but can't be installed
+//! until the host can sandbox them.
+//!
+//! Off unless `[app.marketplace]` `enabled`.
+
+use crate::plugins::PluginManifest;
+use crate::services::ServiceError;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use ed25519_dalek::{Signature, VerifyingKey};
+use rustpress_apps::prelude::*;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::{FromRow, PgPool};
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+/// `[app.marketplace]` settings
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct MarketplaceConfig {
+    /// List and install plugins from the registry
+    pub enabled: bool,
+    /// Base URL of the registry; `index.json` and `index.json.sig` are read
+    /// from it
+    pub registry_url: String,
+    /// Ed25519 keys (base64) the index and artifacts may be signed with
+    pub public_keys: Vec<String>,
+    /// Version of the RustPress host, compared with plugins'
+    /// `min_rustpress_version`
+    pub host_version: String,
+    /// How long a fetched index is used before it's fetched again
+    pub cache_secs: u64,
+    /// Artifacts larger than this are refused
+    pub max_artifact_bytes: usize,
+    pub timeout_secs: u64,
+}
+
+impl Default for MarketplaceConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            registry_url: String::new(),
+            public_keys: Vec::new(),
+            host_version: "1.0.0".to_string(),
+            cache_secs: 300,
+            max_artifact_bytes: 50 * 1024 * 1024,
+            timeout_secs: 60,
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum MarketplaceError {
+    #[error("The plugin marketplace is disabled")]
+    Disabled,
+    #[error("Registry request failed: {0}")]
+    Request(#[from] reqwest::Error),
+    #[error("Registry returned {status} for {url}")]
+    Status { status: u16, url: String },
+    #[error("Invalid registry index: {0}")]
+    Index(String),
+    #[error("Invalid public key: {0}")]
+    Key(String),
+    #[error("Signature check failed for {0}")]
+    Signature(String),
+    #[error("Invalid plugin artifact: {0}")]
+    Artifact(String),
+    #[error("Plugin not found in the registry: {0}")]
+    NotFound(String),
+    #[error("{0}")]
+    Incompatible(String),
+    #[error("Failed to install plugin: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+impl From<MarketplaceError> for ServiceError {
+    fn from(err: MarketplaceError) -> Self {
+        match err {
+            MarketplaceError::Disabled => ServiceError::NotFound(err.to_string()),
+            MarketplaceError::NotFound(_) => ServiceError::NotFound(err.to_string()),
+            MarketplaceError::Incompatible(_) | MarketplaceError::Artifact(_) | MarketplaceError::Signature(_) => {
+                ServiceError::Validation(err.to_string())
+            }
+            MarketplaceError::Database(e) => ServiceError::Database(e),
+            other => ServiceError::Storage(other.to_string()),
+        }
+    }
+}
+
+// ============================================
+// Registry index
+// ============================================
+
+/// `index.json`
+#[derive(Debug, Clone, Deserialize)]
+pub struct RegistryIndex {
+    pub plugins: Vec<RegistryPlugin>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RegistryPlugin {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub author: Option<String>,
+    #[serde(default)]
+    pub homepage: Option<String>,
+    pub versions: Vec<RegistryVersion>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RegistryVersion {
+    pub version: String,
+    pub min_rustpress_version: String,
+    #[serde(default)]
+    pub kind: ArtifactKind,
+    /// Absolute, or relative to the registry URL
+    pub url: String,
+    /// Hex SHA-256 of the artifact
+    pub sha256: String,
+    /// Ed25519 signature of the artifact (base64)
+    pub signature: String,
+    #[serde(default)]
+    pub size: Option<u64>,
+    #[serde(default)]
+    pub published_at: Option<DateTime<Utc>>,
+}
+
+/// What an artifact holds
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ArtifactKind {
+    /// Zip of the plugin's directory
+    #[default]
+    Bundle,
+    /// WebAssembly module, for the sandbox
+    Wasm,
+}
+
+/// A registry plugin as listed by `GET /plugins/available`
+#[derive(Debug, Clone, Serialize)]
+pub struct AvailablePlugin {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub author: Option<String>,
+    pub homepage: Option<String>,
+    /// Newest version the host can install
+    pub latest: Option<String>,
+    /// Version installed on this host, if any
+    pub installed: Option<String>,
+    pub update_available: bool,
+    pub versions: Vec<AvailableVersion>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AvailableVersion {
+    pub version: String,
+    pub min_rustpress_version: String,
+    pub kind: ArtifactKind,
+    pub size: Option<u64>,
+    pub published_at: Option<DateTime<Utc>>,
+    pub compatible: bool,
+    /// Why the version can't be installed
+    pub incompatible: Option<String>,
+}
+
+/// A plugin installed from the registry
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct PluginInstallation {
+    pub id: Uuid,
+    pub plugin_id: String,
+    pub version: String,
+    /// Version it replaced, if the plugin was installed before
+    pub previous_version: Option<String>,
+    pub artifact_url: String,
+    pub sha256: String,
+    pub installed_by: Option<Uuid>,
+    pub installed_at: DateTime<Utc>,
+}
+
+// ============================================
+// Service
+// ============================================
+
+/// Registry client and installer
+pub struct MarketplaceService {
+    db: PgPool,
+    http: reqwest::Client,
+    registry: Arc<PluginRegistry>,
+    plugins_dir: PathBuf,
+    config: MarketplaceConfig,
+    keys: Vec<VerifyingKey>,
+    /// The last verified index and when it was fetched
+    index: RwLock<Option<(Instant, Arc<RegistryIndex>)>>,
+}
+
+impl MarketplaceService {
+    pub fn new(
+        db: PgPool,
+        registry: Arc<PluginRegistry>,
+        plugins_dir: PathBuf,
+        config: MarketplaceConfig,
+    ) -> Result<Self, MarketplaceError> {
+        let keys = config
+            .public_keys
+            .iter()
+            .map(|key| verifying_key(key))
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            db,
+            http: reqwest::Client::builder()
+                .timeout(Duration::from_secs(config.timeout_secs))
+                .build()?,
+            registry,
+            plugins_dir,
+            config,
+            keys,
+            index: RwLock::new(None),
+        })
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.enabled && !self.config.registry_url.is_empty()
+    }
+
+    /// The registry's plugins with their compatibility and what's installed;
+    /// `refresh` fetches the index even if the cached one is recent
+    pub async fn available(&self, refresh: bool) -> Result<Vec<AvailablePlugin>, MarketplaceError> {
+        let index = self.index(refresh).await?;
+        let installed = self.registry.installed().await;
+
+        let mut plugins: Vec<AvailablePlugin> = index
+            .plugins
+            .iter()
+            .map(|plugin| {
+                let mut versions: Vec<AvailableVersion> = plugin
+                    .versions
+                    .iter()
+                    .map(|version| {
+                        let incompatible = self.check(version).err();
+                        AvailableVersion {
+                            version: version.version.clone(),
+                            min_rustpress_version: version.min_rustpress_version.clone(),
+                            kind: version.kind,
+                            size: version.size,
+                            published_at: version.published_at,
+                            compatible: incompatible.is_none(),
+                            incompatible,
+                        }
+                    })
+                    .collect();
+                versions.sort_by(|a, b| semver_cmp(&b.version, &a.version));
+
+                let latest = versions.iter().find(|v| v.compatible).map(|v| v.version.clone());
+                let installed = installed
+                    .iter()
+                    .find(|p| p.info.id == plugin.id)
+                    .map(|p| p.info.version.clone());
+                let update_available = match (&installed, &latest) {
+                    (Some(installed), Some(latest)) => semver_cmp(latest, installed).is_gt(),
+                    _ => false,
+                };
+                AvailablePlugin {
+                    id: plugin.id.clone(),
+                    name: plugin.name.clone(),
+                    description: plugin.description.clone(),
+                    author: plugin.author.clone(),
+                    homepage: plugin.homepage.clone(),
+                    latest,
+                    installed,
+                    update_available,
+                    versions,
+                }
+            })
+            .collect();
+        plugins.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(plugins)
+    }
+
+    /// Download, verify and unpack `version` of plugin `id` (the newest
+    /// compatible version if `None`) into the plugins directory
+    pub async fn install(
+        &self,
+        id: &str,
+        version: Option<&str>,
+        actor: Uuid,
+    ) -> Result<PluginInstallation, MarketplaceError> {
+        let index = self.index(false).await?;
+        let plugin = index
+            .plugins
+            .iter()
+            .find(|p| p.id == id)
+            .ok_or_else(|| MarketplaceError::NotFound(id.to_string()))?;
+        let release = match version {
+            Some(version) => {
+                let release = plugin
+                    .versions
+                    .iter()
+                    .find(|v| v.version == version)
+                    .ok_or_else(|| MarketplaceError::NotFound(format!("{} {}", id, version)))?;
+                self.check(release).map_err(MarketplaceError::Incompatible)?;
+                release
+            }
+            None => plugin
+                .versions
+                .iter()
+                .filter(|v| self.check(v).is_ok())
+                .max_by(|a, b| semver_cmp(&a.version, &b.version))
+                .ok_or_else(|| MarketplaceError::Incompatible(format!("No version of {} runs on this host", id)))?,
+        };
+        if release.kind == ArtifactKind::Wasm {
+            return Err(MarketplaceError::Incompatible(
+                "WASM plugins need the plugin sandbox, which this host doesn't have yet".into(),
+            ));
+        }
+
+        let url = self.url(&release.url);
+        let artifact = self.fetch(&url, self.config.max_artifact_bytes).await?;
+        let digest = hex::encode(Sha256::digest(&artifact));
+        if !digest.eq_ignore_ascii_case(&release.sha256) {
+            return Err(MarketplaceError::Signature(format!("{} (SHA-256 {} expected {})", url, digest, release.sha256)));
+        }
+        self.verify(&artifact, &release.signature, &url)?;
+
+        let previous_version = self
+            .registry
+            .installed()
+            .await
+            .into_iter()
+            .find(|p| p.info.id == id)
+            .map(|p| p.info.version);
+
+        let plugins_dir = self.plugins_dir.clone();
+        let (plugin_id, plugin_version) = (id.to_string(), release.version.clone());
+        tokio::task::spawn_blocking(move || unpack(&artifact, &plugins_dir, &plugin_id, &plugin_version))
+            .await
+            .map_err(|e| MarketplaceError::Artifact(e.to_string()))??;
+
+        let installation = sqlx::query_as(
+            "INSERT INTO plugin_installations (plugin_id, version, previous_version, artifact_url, sha256, installed_by)
+             VALUES ($1, $2, $3, $4, $5, $6)
+             RETURNING *",
+        )
+        .bind(id)
+        .bind(&release.version)
+        .bind(previous_version)
+        .bind(&url)
+        .bind(&digest)
+        .bind(actor)
+        .fetch_one(&self.db)
+        .await?;
+        tracing::info!(plugin = id, version = %release.version, "Plugin installed from the registry");
+        Ok(installation)
+    }
+
+    /// Installations from the registry, newest first
+    pub async fn installations(&self, plugin_id: Option<&str>) -> Result<Vec<PluginInstallation>, MarketplaceError> {
+        let installations = sqlx::query_as(
+            "SELECT * FROM plugin_installations
+             WHERE $1::text IS NULL OR plugin_id = $1
+             ORDER BY installed_at DESC
+             LIMIT 200",
+        )
+        .bind(plugin_id)
+        .fetch_all(&self.db)
+        .await?;
+        Ok(installations)
+    }
+
+    /// Whether the host can run `version`; the reason if not
+    fn check(&self, version: &RegistryVersion) -> Result<(), String> {
+        let host = Version::parse(&self.config.host_version)
+            .map_err(|e| format!("Invalid host_version {}: {}", self.config.host_version, e))?;
+        let minimum = Version::parse(&version.min_rustpress_version)
+            .map_err(|e| format!("Invalid min_rustpress_version {}: {}", version.min_rustpress_version, e))?;
+        if host < minimum {
+            return Err(format!("Needs RustPress {} or later (host is {})", minimum, host));
+        }
+        Ok(())
+    }
+
+    /// The verified index, fetched again once `cache_secs` have passed
+    async fn index(&self, refresh: bool) -> Result<Arc<RegistryIndex>, MarketplaceError> {
+        if !self.enabled() {
+            return Err(MarketplaceError::Disabled);
+        }
+        if !refresh {
+            if let Some((fetched, index)) = self.index.read().await.as_ref() {
+                if fetched.elapsed() < Duration::from_secs(self.config.cache_secs) {
+                    return Ok(index.clone());
+                }
+            }
+        }
+
+        let url = self.url("index.json");
+        let body = self.fetch(&url, self.config.max_artifact_bytes).await?;
+        let signature = self.fetch(&self.url("index.json.sig"), 1024).await?;
+        let signature = String::from_utf8_lossy(&signature);
+        self.verify(&body, signature.trim(), &url)?;
+
+        let index: Arc<RegistryIndex> =
+            Arc::new(serde_json::from_slice(&body).map_err(|e| MarketplaceError::Index(e.to_string()))?);
+        *self.index.write().await = Some((Instant::now(), index.clone()));
+        Ok(index)
+    }
+
+    /// `path` resolved against the registry URL, unless it's absolute
+    fn url(&self, path: &str) -> String {
+        if path.starts_with("https://") || path.starts_with("http://") {
+            path.to_string()
+        } else {
+            format!("{}/{}", self.config.registry_url.trim_end_matches('/'), path.trim_start_matches('/'))
+        }
+    }
+
+    async fn fetch(&self, url: &str, max_bytes: usize) -> Result<Vec<u8>, MarketplaceError> {
+        let mut response = self.http.get(url).send().await?;
+        if !response.status().is_success() {
+            return Err(MarketplaceError::Status {
+                status: response.status().as_u16(),
+                url: url.to_string(),
+            });
+        }
+
+        let mut body = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            if body.len() + chunk.len() > max_bytes {
+                return Err(MarketplaceError::Artifact(format!("{} is larger than {} bytes", url, max_bytes)));
+            }
+            body.extend_from_slice(&chunk);
+        }
+        Ok(body)
+    }
+
+    /// Accept `data` if any trusted key signed it
+    fn verify(&self, data: &[u8], signature: &str, what: &str) -> Result<(), MarketplaceError> {
+        if verify_signature(&self.keys, data, signature) {
+            Ok(())
+        } else {
+            Err(MarketplaceError::Signature(what.to_string()))
+        }
+    }
+}
+
+/// A base64 Ed25519 public key
+pub fn verifying_key(key: &str) -> Result<VerifyingKey, MarketplaceError> {
+    let bytes: [u8; 32] = STANDARD
+        .decode(key.trim())
+        .map_err(|e| MarketplaceError::Key(e.to_string()))?
+        .try_into()
+        .map_err(|_| MarketplaceError::Key("expected 32 bytes".into()))?;
+    VerifyingKey::from_bytes(&bytes).map_err(|e| MarketplaceError::Key(e.to_string()))
+}
+
+/// Whether `signature` (base64) is a signature of `data` by one of `keys`
+pub fn verify_signature(keys: &[VerifyingKey], data: &[u8], signature: &str) -> bool {
+    let Ok(bytes) = STANDARD.decode(signature.trim()) else {
+        return false;
+    };
+    let Ok(signature) = Signature::from_slice(&bytes) else {
+        return false;
+    };
+    keys.iter().any(|key| key.verify_strict(data, &signature).is_ok())
+}
+
+/// Semver order, unparseable versions first
+fn semver_cmp(a: &str, b: &str) -> std::cmp::Ordering {
+    Version::parse(a).ok().cmp(&Version::parse(b).ok())
+}
+
+/// Unpack a plugin zip into `{plugins_dir}/{id}/`, replacing what's there,
+/// once its `plugin.toml` is known to be `id` at `version`
+pub fn unpack(artifact: &[u8], plugins_dir: &Path, id: &str, version: &str) -> Result<(), MarketplaceError> {
+    if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err(MarketplaceError::Artifact(format!("invalid plugin id {:?}", id)));
+    }
+
+    let staging = plugins_dir.join(format!(".install-{}-{}", id, Uuid::new_v4()));
+    std::fs::create_dir_all(&staging)?;
+    if let Err(e) = extract(artifact, &staging, id, version) {
+        let _ = std::fs::remove_dir_all(&staging);
+        return Err(e);
+    }
+
+    // Swap the directories so a failure leaves the old version in place
+    let target = plugins_dir.join(id);
+    let replaced = plugins_dir.join(format!(".replaced-{}-{}", id, Uuid::new_v4()));
+    if target.exists() {
+        std::fs::rename(&target, &replaced)?;
+    }
+    if let Err(e) = std::fs::rename(&staging, &target) {
+        if replaced.exists() {
+            let _ = std::fs::rename(&replaced, &target);
+        }
+        let _ = std::fs::remove_dir_all(&staging);
+        return Err(e.into());
+    }
+    if replaced.exists() {
+        let _ = std::fs::remove_dir_all(&replaced);
+    }
+    Ok(())
+}
+
+/// Extract the zip into `dir` and check its `plugin.toml`
+fn extract(artifact: &[u8], dir: &Path, id: &str, version: &str) -> Result<(), MarketplaceError> {
+    let mut archive =
+        zip::ZipArchive::new(Cursor::new(artifact)).map_err(|e| MarketplaceError::Artifact(e.to_string()))?;
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i).map_err(|e| MarketplaceError::Artifact(e.to_string()))?;
+        // Entries escaping the directory (`../`, absolute paths) are refused
+        let Some(name) = file.enclosed_name() else {
+            return Err(MarketplaceError::Artifact(format!("unsafe path {}", file.name())));
+        };
+        let path = dir.join(name);
+        if file.is_dir() {
+            std::fs::create_dir_all(&path)?;
+            continue;
+        }
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::io::copy(&mut file, &mut std::fs::File::create(&path)?)?;
+    }
+
+    let manifest = std::fs::read_to_string(dir.join("plugin.toml"))
+        .map_err(|_| MarketplaceError::Artifact("no plugin.toml".into()))?;
+    let manifest: PluginManifest =
+        toml::from_str(&manifest).map_err(|e| MarketplaceError::Artifact(format!("plugin.toml: {}", e)))?;
+    if manifest.plugin.id != id || manifest.plugin.version != version {
+        return Err(MarketplaceError::Artifact(format!(
+            "plugin.toml is {} {}, the registry promised {} {}",
+            manifest.plugin.id, manifest.plugin.version, id, version
+        )));
+    }
+    Ok(())
+}
@@ -0,0 +1,36 @@
+//! Media Cleanup
+//!
+//! Deleting media only moves it to the trash. This job deletes the files of
+//! media trashed longer than `[app.media] trash_retention_days`, dropping
+//! each row once its file is gone, and then compares the media rows with the
+//! storage objects under the media prefixes. Mismatches (rows whose file is
+//! missing, files no row points at) are listed by `GET /admin/media/orphans`;
+//! nothing is deleted on their account.
+
+use crate::BlogServices;
+use std::sync::Weak;
+use std::time::Duration;
+
+/// Purge the trash and scan for orphans every `interval_secs` until the
+/// services are dropped
+pub async fn run(services: Weak<BlogServices>, interval_secs: u64) {
+    let mut interval = tokio::time::interval(Duration::from_secs(interval_secs.max(60)));
+    loop {
+        interval.tick().await;
+        let Some(services) = services.upgrade() else {
+            return;
+        };
+
+        match services.media.purge_trash().await {
+            Ok(purge) if purge.purged > 0 || purge.failed > 0 => {
+                tracing::info!(purged = purge.purged, failed = purge.failed, "Purged trashed media");
+            }
+            Ok(_) => {}
+            Err(e) => tracing::warn!("Media trash purge failed: {}", e),
+        }
+
+        if let Err(e) = services.media.scan_orphans().await {
+            tracing::warn!("Media orphan scan failed: {}", e);
+        }
+    }
+}
@@ -0,0 +1,413 @@
+//! Mentions
+//!
+//! `@username` in a post or comment mentions that user. Usernames are author
+//! slugs (`blog_authors`); besides authors of published posts, registered
+//! commenters get one with their first comment. [`MentionService`] listens
+//! to content changes and rescans each saved post or comment, so mentions
+//! removed in an edit are dropped and kept ones aren't notified twice.
+//!
+//! A mention is notified once its post is published or its comment approved:
+//! it is listed by `GET /notifications/mentions` and queued for the user's
+//! next digest (see `digests`). Published posts pass through
+//! [`MentionService::link`] when read, which links mentions of users with an
+//! author page on the site and wraps the others in `<span class="mention">`.
+//! Text inside links, `code` and `pre` is never scanned.
+
+use crate::activity::{ContentEvent, ContentListener};
+use crate::digests::DigestService;
+use crate::models::*;
+use crate::services::{ensure_author_slug, ServiceError};
+use crate::sites::Site;
+use axum::async_trait;
+use serde::Deserialize;
+use sqlx::{FromRow, PgPool};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Tags whose content isn't scanned for mentions
+const SKIPPED_TAGS: &[&str] = &["a", "code", "pre", "script", "style"];
+
+/// Longest username (the length of `blog_authors.slug`)
+const MAX_USERNAME: usize = 120;
+
+/// Length of the excerpt kept with a mention
+const SUMMARY_CHARS: i32 = 200;
+
+/// Most mentions listed at once
+const LIST_LIMIT: i64 = 100;
+
+/// `[app.mentions]` settings
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct MentionConfig {
+    /// Record, notify and link mentions
+    pub enabled: bool,
+    /// Users notified per post or comment; later mentions stay plain text
+    pub max_per_item: i64,
+    /// Queue mentions for the user's digest as well
+    pub email: bool,
+}
+
+impl Default for MentionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_per_item: 10,
+            email: true,
+        }
+    }
+}
+
+/// A mention in a text
+struct Match {
+    /// Byte range of `@name`
+    start: usize,
+    end: usize,
+    /// Lowercased name
+    username: String,
+}
+
+/// Mentions in `text`, skipping markup and text inside links when `html`
+fn scan(text: &str, html: bool) -> Vec<Match> {
+    let bytes = text.as_bytes();
+    let mut matches = Vec::new();
+    let mut skipped_depth = 0usize;
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'<' if html => {
+                let close = text[i..].find('>').map(|n| i + n);
+                let tag = &text[i + 1..close.unwrap_or(bytes.len())];
+                let name = tag
+                    .trim_start_matches('/')
+                    .split(|c: char| !c.is_ascii_alphanumeric())
+                    .next()
+                    .unwrap_or("")
+                    .to_ascii_lowercase();
+                if SKIPPED_TAGS.contains(&name.as_str()) {
+                    if tag.starts_with('/') {
+                        skipped_depth = skipped_depth.saturating_sub(1);
+                    } else if !tag.ends_with('/') {
+                        skipped_depth += 1;
+                    }
+                }
+                i = close.map_or(bytes.len(), |close| close + 1);
+                continue;
+            }
+            // Not part of an email address or a path
+            b'@' if skipped_depth == 0 && (i == 0 || !is_word_byte(bytes[i - 1])) => {
+                let len = bytes[i + 1..]
+                    .iter()
+                    .take_while(|b| b.is_ascii_alphanumeric() || **b == b'-')
+                    .count();
+                let name = text[i + 1..i + 1 + len].trim_end_matches('-');
+                if !name.is_empty() && name.len() <= MAX_USERNAME {
+                    matches.push(Match {
+                        start: i,
+                        end: i + 1 + name.len(),
+                        username: name.to_ascii_lowercase(),
+                    });
+                }
+                i += 1 + len;
+                continue;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    matches
+}
+
+fn is_word_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || matches!(b, b'_' | b'-' | b'.' | b'/' | b'@')
+}
+
+/// Usernames mentioned in `text`, once each, in order of appearance
+pub fn parse(text: &str, html: bool) -> Vec<String> {
+    let mut seen = HashSet::new();
+    scan(text, html)
+        .into_iter()
+        .map(|m| m.username)
+        .filter(|username| seen.insert(username.clone()))
+        .collect()
+}
+
+/// Replace the mentions in `html` for which `wrap` (given the username and
+/// the text as written) returns markup
+pub fn link_html(html: &str, wrap: impl Fn(&str, &str) -> Option<String>) -> String {
+    let mut linked = String::with_capacity(html.len());
+    let mut last = 0;
+    for m in scan(html, true) {
+        if let Some(markup) = wrap(&m.username, &html[m.start..m.end]) {
+            linked.push_str(&html[last..m.start]);
+            linked.push_str(&markup);
+            last = m.end;
+        }
+    }
+    linked.push_str(&html[last..]);
+    linked
+}
+
+/// The post or comment a mention is in
+#[derive(Debug, FromRow)]
+struct Source {
+    site_id: Uuid,
+    post_id: Uuid,
+    actor_id: Option<Uuid>,
+    actor_name: String,
+    summary: String,
+    content: String,
+    /// Published or approved
+    visible: bool,
+}
+
+pub struct MentionService {
+    db: PgPool,
+    digests: Arc<DigestService>,
+    config: MentionConfig,
+}
+
+impl MentionService {
+    pub fn new(db: PgPool, digests: Arc<DigestService>, config: MentionConfig) -> Self {
+        Self { db, digests, config }
+    }
+
+    /// Rescan a post and record its mentions; notified once published
+    pub async fn sync_post(&self, post_id: Uuid) -> Result<(), ServiceError> {
+        let source: Option<Source> = sqlx::query_as(
+            r#"SELECT p.site_id, p.id AS post_id, p.author_id AS actor_id, u.name AS actor_name,
+                      LEFT(COALESCE(NULLIF(p.excerpt, ''), p.title), $2) AS summary,
+                      p.content, p.status = 'published' AS visible
+               FROM blog_posts p
+               JOIN users u ON u.id = p.author_id
+               WHERE p.id = $1"#,
+        )
+        .bind(post_id)
+        .bind(SUMMARY_CHARS)
+        .fetch_optional(&self.db)
+        .await?;
+
+        match source {
+            Some(source) => self.sync(ContentObject::Post, post_id, source, true).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Rescan a comment and record its mentions; notified once approved
+    pub async fn sync_comment(&self, comment_id: Uuid) -> Result<(), ServiceError> {
+        let source: Option<Source> = sqlx::query_as(
+            r#"SELECT site_id, post_id, author_id AS actor_id, author_name AS actor_name,
+                      LEFT(content, $2) AS summary, content, status = 'approved' AS visible
+               FROM blog_comments
+               WHERE id = $1"#,
+        )
+        .bind(comment_id)
+        .bind(SUMMARY_CHARS)
+        .fetch_optional(&self.db)
+        .await?;
+
+        match source {
+            Some(source) => self.sync(ContentObject::Comment, comment_id, source, false).await,
+            None => Ok(()),
+        }
+    }
+
+    async fn sync(&self, object_type: ContentObject, object_id: Uuid, source: Source, html: bool) -> Result<(), ServiceError> {
+        let usernames = parse(&source.content, html);
+        // Users in order of their first mention; nobody is notified of
+        // mentioning themselves
+        let users: Vec<Uuid> = if usernames.is_empty() {
+            Vec::new()
+        } else {
+            sqlx::query_scalar(
+                r#"SELECT a.user_id
+                   FROM UNNEST($1::text[]) WITH ORDINALITY AS n(slug, position)
+                   JOIN blog_authors a ON a.slug = n.slug
+                   WHERE a.user_id IS DISTINCT FROM $2
+                   ORDER BY n.position
+                   LIMIT $3"#,
+            )
+            .bind(&usernames)
+            .bind(source.actor_id)
+            .bind(self.config.max_per_item)
+            .fetch_all(&self.db)
+            .await?
+        };
+
+        let mut tx = self.db.begin().await?;
+
+        let removed: Vec<Uuid> =
+            sqlx::query_scalar("DELETE FROM blog_mentions WHERE object_id = $1 AND user_id <> ALL($2) RETURNING id")
+                .bind(object_id)
+                .bind(&users)
+                .fetch_all(&mut *tx)
+                .await?;
+        if !removed.is_empty() {
+            sqlx::query("DELETE FROM pending_notifications WHERE kind = 'mention' AND object_id = ANY($1)")
+                .bind(&removed)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        sqlx::query(
+            r#"INSERT INTO blog_mentions (site_id, post_id, object_type, object_id, user_id, actor_name, summary)
+               SELECT $1, $2, $3, $4, user_id, $6, $7 FROM UNNEST($5::uuid[]) AS user_id
+               ON CONFLICT (object_id, user_id) DO UPDATE SET actor_name = $6, summary = $7"#,
+        )
+        .bind(source.site_id)
+        .bind(source.post_id)
+        .bind(object_type)
+        .bind(object_id)
+        .bind(&users)
+        .bind(&source.actor_name)
+        .bind(&source.summary)
+        .execute(&mut *tx)
+        .await?;
+
+        let notified: Vec<Uuid> = if source.visible {
+            sqlx::query_scalar(
+                "UPDATE blog_mentions SET notified_at = NOW() WHERE object_id = $1 AND notified_at IS NULL RETURNING id",
+            )
+            .bind(object_id)
+            .fetch_all(&mut *tx)
+            .await?
+        } else {
+            Vec::new()
+        };
+
+        tx.commit().await?;
+
+        if self.config.email {
+            for id in notified {
+                self.digests.record_mention(id).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Drop the mentions of a post or comment that is no longer shown
+    pub async fn remove(&self, object_id: Uuid) -> Result<(), ServiceError> {
+        let removed: Vec<Uuid> = sqlx::query_scalar("DELETE FROM blog_mentions WHERE object_id = $1 RETURNING id")
+            .bind(object_id)
+            .fetch_all(&self.db)
+            .await?;
+        if !removed.is_empty() {
+            sqlx::query("DELETE FROM pending_notifications WHERE kind = 'mention' AND object_id = ANY($1)")
+                .bind(&removed)
+                .execute(&self.db)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// A user's notified mentions on a site, newest first
+    pub async fn list(&self, site: &Site, user_id: Uuid, query: &MentionQuery) -> Result<Vec<Mention>, ServiceError> {
+        let mentions = sqlx::query_as(
+            r#"SELECT m.id, m.post_id, p.title AS post_title, p.slug AS post_slug, m.object_type, m.object_id,
+                      m.actor_name, m.summary, m.notified_at, m.read_at
+               FROM blog_mentions m
+               JOIN blog_posts p ON p.id = m.post_id
+               WHERE m.user_id = $1 AND m.site_id = $2 AND m.notified_at IS NOT NULL
+                 AND (NOT $3 OR m.read_at IS NULL)
+               ORDER BY m.notified_at DESC
+               LIMIT $4"#,
+        )
+        .bind(user_id)
+        .bind(site.id)
+        .bind(query.unread)
+        .bind(LIST_LIMIT)
+        .fetch_all(&self.db)
+        .await?;
+        Ok(mentions)
+    }
+
+    /// Mark all of a user's mentions on a site as read
+    pub async fn mark_read(&self, site: &Site, user_id: Uuid) -> Result<(), ServiceError> {
+        sqlx::query(
+            "UPDATE blog_mentions SET read_at = NOW()
+             WHERE user_id = $1 AND site_id = $2 AND notified_at IS NOT NULL AND read_at IS NULL",
+        )
+        .bind(user_id)
+        .bind(site.id)
+        .execute(&self.db)
+        .await?;
+        Ok(())
+    }
+
+    /// Link the mentions in the content of `posts`, except teasers
+    ///
+    /// Runs on every read rather than on save, so a mentioned user's link
+    /// appears once they have an author page.
+    pub async fn link(&self, site: &Site, posts: &mut [PostWithRelations]) -> Result<(), ServiceError> {
+        if !self.config.enabled {
+            return Ok(());
+        }
+        let ids: Vec<Uuid> = posts.iter().filter(|p| !p.locked).map(|p| p.post.id).collect();
+        if ids.is_empty() {
+            return Ok(());
+        }
+
+        let rows: Vec<(Uuid, String, bool)> = sqlx::query_as(
+            r#"SELECT m.object_id, a.slug,
+                      EXISTS (SELECT 1 FROM blog_posts p
+                              WHERE p.author_id = m.user_id AND p.site_id = m.site_id AND p.status = 'published')
+               FROM blog_mentions m
+               JOIN blog_authors a ON a.user_id = m.user_id
+               WHERE m.object_id = ANY($1)"#,
+        )
+        .bind(&ids)
+        .fetch_all(&self.db)
+        .await?;
+
+        let mut mentioned: HashMap<Uuid, HashMap<String, bool>> = HashMap::new();
+        for (post_id, username, has_page) in rows {
+            mentioned.entry(post_id).or_default().insert(username, has_page);
+        }
+
+        for post in posts.iter_mut().filter(|p| !p.locked) {
+            let Some(users) = mentioned.get(&post.post.id) else {
+                continue;
+            };
+            post.post.content = link_html(&post.post.content, |username, written| {
+                users.get(username).map(|has_page| {
+                    if *has_page {
+                        let url = site.url(&format!("/author/{}", username));
+                        format!("<a class=\"mention\" href=\"{}\">{}</a>", url, written)
+                    } else {
+                        format!("<span class=\"mention\">{}</span>", written)
+                    }
+                })
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Records mentions as posts and comments are saved
+#[async_trait]
+impl ContentListener for MentionService {
+    async fn on_change(&self, event: &ContentEvent) {
+        let result = match (event.object_type, event.action) {
+            (ContentObject::Post, ContentAction::Created | ContentAction::Updated | ContentAction::Published) => {
+                self.sync_post(event.object_id).await
+            }
+            (ContentObject::Comment, ContentAction::Created) => {
+                async {
+                    // Registered commenters become mentionable
+                    if let Some(actor) = event.actor_id {
+                        ensure_author_slug(&self.db, actor).await?;
+                    }
+                    self.sync_comment(event.object_id).await
+                }
+                .await
+            }
+            (ContentObject::Comment, ContentAction::Approved) => self.sync_comment(event.object_id).await,
+            (ContentObject::Comment, ContentAction::Rejected) => self.remove(event.object_id).await,
+            _ => return,
+        };
+        if let Err(e) = result {
+            tracing::warn!(object_id = %event.object_id, "Failed to record mentions: {}", e);
+        }
+    }
+}
@@ -7,77 +7,53 @@ use axum::{
     http::StatusCode,
     middleware::Next,
     response::{IntoResponse, Response},
-    Json,
 };
-use jsonwebtoken::{decode, DecodingKey, Validation};
-use std::env;
+use jsonwebtoken::Validation;
+use rustpress_auth::keys::{access_validation, JwtKeys};
+use rustpress_auth::{AuthError, Config};
+use std::sync::Arc;
 
 use crate::auth::AccessTokenClaims;
-
-/// Get JWT decoding key from environment
-fn get_decoding_key() -> Result<DecodingKey, Response> {
-    let secret = env::var("JWT_SECRET").map_err(|_| {
-        tracing::error!("JWT_SECRET environment variable not set");
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(serde_json::json!({
-                "error": "configuration_error",
-                "message": "Server configuration error"
-            })),
-        )
+use crate::models::ProblemDetails;
+
+/// Current JWT keys (following secret rotations) and validation settings
+pub(crate) async fn jwt_decoding() -> Result<(Arc<JwtKeys>, Validation), Response> {
+    let configuration_error = |e: AuthError| {
+        tracing::error!("JWT configuration: {}", e);
+        ProblemDetails::new(StatusCode::INTERNAL_SERVER_ERROR, "configuration_error")
+            .detail("Server configuration error")
             .into_response()
-    })?;
-    Ok(DecodingKey::from_secret(secret.as_bytes()))
-}
-
-/// Get JWT validation settings from environment
-fn get_validation() -> Validation {
-    let issuer = env::var("JWT_ISSUER").unwrap_or_else(|_| "rustpress".to_string());
-    let audience = env::var("JWT_AUDIENCE").unwrap_or_else(|_| "rustpress-api".to_string());
-
-    let mut validation = Validation::default();
-    validation.set_issuer(&[issuer]);
-    validation.set_audience(&[audience]);
-    validation
+    };
+
+    let keys = JwtKeys::shared().await.map_err(configuration_error)?;
+    let validation = Config::load()
+        .map_err(AuthError::from)
+        .and_then(|config| access_validation(&config))
+        .map_err(configuration_error)?;
+    Ok((keys, validation))
 }
 
 /// Extract and validate JWT token from Authorization header
-fn validate_token(auth_header: Option<&str>) -> Result<AccessTokenClaims, Response> {
+async fn validate_token(auth_header: Option<&str>) -> Result<AccessTokenClaims, Response> {
     let header = auth_header.ok_or_else(|| {
-        (
-            StatusCode::UNAUTHORIZED,
-            Json(serde_json::json!({
-                "error": "unauthorized",
-                "message": "Authentication required"
-            })),
-        )
+        ProblemDetails::new(StatusCode::UNAUTHORIZED, "unauthorized")
+            .detail("Authentication required")
             .into_response()
     })?;
 
     if !header.starts_with("Bearer ") {
-        return Err((
-            StatusCode::UNAUTHORIZED,
-            Json(serde_json::json!({
-                "error": "unauthorized",
-                "message": "Invalid authorization header format"
-            })),
-        )
+        return Err(ProblemDetails::new(StatusCode::UNAUTHORIZED, "unauthorized")
+            .detail("Invalid authorization header format")
             .into_response());
     }
 
     let token = header.trim_start_matches("Bearer ");
-    let decoding_key = get_decoding_key()?;
-    let validation = get_validation();
+    let (keys, validation) = jwt_decoding().await?;
 
-    let token_data = decode::<AccessTokenClaims>(token, &decoding_key, &validation).map_err(|e| {
+    let token_data = keys.decode::<AccessTokenClaims>(token, &validation).map_err(|e| {
         tracing::debug!("JWT validation failed: {:?}", e);
-        (
-            StatusCode::UNAUTHORIZED,
-            Json(serde_json::json!({
-                "error": "invalid_token",
-                "message": "Invalid or expired token"
-            })),
-        )
+        ProblemDetails::new(StatusCode::UNAUTHORIZED, "invalid_token")
+            .detail("Invalid or expired token")
             .into_response()
     })?;
 
@@ -94,7 +70,7 @@ pub async fn require_auth(mut req: Request, next: Next) -> Result<Response, Resp
         .get("Authorization")
         .and_then(|h| h.to_str().ok());
 
-    let claims = validate_token(auth_header)?;
+    let claims = validate_token(auth_header).await?;
 
     // Store claims in request extensions for extractors
     req.extensions_mut().insert(claims);
@@ -111,17 +87,12 @@ pub async fn require_admin(mut req: Request, next: Next) -> Result<Response, Res
         .get("Authorization")
         .and_then(|h| h.to_str().ok());
 
-    let claims = validate_token(auth_header)?;
+    let claims = validate_token(auth_header).await?;
 
     // Check admin role from JWT claims
     if claims.role != "admin" {
-        return Err((
-            StatusCode::FORBIDDEN,
-            Json(serde_json::json!({
-                "error": "forbidden",
-                "message": "Admin access required"
-            })),
-        )
+        return Err(ProblemDetails::new(StatusCode::FORBIDDEN, "forbidden")
+            .detail("Admin access required")
             .into_response());
     }
 
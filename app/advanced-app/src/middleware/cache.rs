@@ -31,11 +31,16 @@ pub async fn cache_response(req: Request, next: Next) -> Response {
     if response.status() == StatusCode::OK {
         let headers = response.headers_mut();
 
-        // Cache for 5 minutes
-        headers.insert(
-            header::CACHE_CONTROL,
-            "public, max-age=300".parse().unwrap(),
-        );
+        // Cache for 5 minutes, unless the handler chose its own policy
+        if !headers.contains_key(header::CACHE_CONTROL) {
+            headers.insert(
+                header::CACHE_CONTROL,
+                "public, max-age=300".parse().unwrap(),
+            );
+        }
+
+        // Sites share paths, so shared caches must key on the host too
+        headers.insert(header::VARY, "Host".parse().unwrap());
 
         // Add ETag for conditional requests
         // headers.insert(header::ETAG, "\"abc123\"".parse().unwrap());
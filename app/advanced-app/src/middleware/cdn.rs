@@ -0,0 +1,70 @@
+//! CDN URL Rewriting Middleware
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State},
+    http::header,
+    middleware::Next,
+    response::Response,
+};
+use std::sync::Arc;
+
+/// Largest body rewritten; bigger responses pass through unchanged
+const MAX_REWRITE_BYTES: usize = 8 * 1024 * 1024;
+
+/// Point media links in JSON, HTML and feed responses at the CDN
+///
+/// `rewrites` comes from `ImageConfig::cdn_rewrites`. Only links at the start
+/// of a quoted value (`"/uploads/..."`, `src="/uploads/..."`) are rewritten,
+/// so paths mentioned in running text are left alone.
+pub async fn rewrite_media_urls(
+    State(rewrites): State<Arc<Vec<(String, String)>>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let response = next.run(req).await;
+    if rewrites.is_empty() || !is_rewritable(&response) {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let bytes = match to_bytes(body, MAX_REWRITE_BYTES).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            tracing::warn!("Failed to buffer response for CDN rewriting: {}", e);
+            return Response::from_parts(parts, Body::empty());
+        }
+    };
+
+    let mut text = match String::from_utf8(bytes.to_vec()) {
+        Ok(text) => text,
+        Err(_) => return Response::from_parts(parts, Body::from(bytes)),
+    };
+    for (from, to) in rewrites.iter() {
+        text = text.replace(&format!("\"{}", from), &format!("\"{}", to));
+    }
+
+    parts.headers.remove(header::CONTENT_LENGTH);
+    Response::from_parts(parts, Body::from(text))
+}
+
+/// Uncompressed JSON, HTML or XML of a known, reasonable size
+fn is_rewritable(response: &Response) -> bool {
+    let headers = response.headers();
+    if headers.contains_key(header::CONTENT_ENCODING) {
+        return false;
+    }
+    let too_large = headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<usize>().ok())
+        .is_some_and(|len| len > MAX_REWRITE_BYTES);
+    if too_large {
+        return false;
+    }
+
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|ct| ct.contains("json") || ct.starts_with("text/html") || ct.contains("xml"))
+}
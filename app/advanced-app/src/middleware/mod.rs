@@ -2,5 +2,7 @@
 
 pub mod auth;
 pub mod cache;
+pub mod cdn;
 pub mod rate_limit;
+pub mod signed_url;
 pub mod view_counter;
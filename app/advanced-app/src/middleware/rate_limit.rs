@@ -1,11 +1,11 @@
 //! Rate Limiting Middleware
 
+use crate::models::ProblemDetails;
 use axum::{
     extract::Request,
     http::{header, StatusCode},
     middleware::Next,
     response::{IntoResponse, Response},
-    Json,
 };
 use std::collections::HashMap;
 use std::sync::Arc;
@@ -60,13 +60,9 @@ pub async fn rate_limiter(req: Request, next: Next) -> Result<Response, Response
             .as_secs();
 
         return Err((
-            StatusCode::TOO_MANY_REQUESTS,
             [(header::RETRY_AFTER, retry_after.to_string())],
-            Json(serde_json::json!({
-                "error": "rate_limited",
-                "message": "Too many requests. Please try again later.",
-                "retry_after": retry_after
-            })),
+            ProblemDetails::new(StatusCode::TOO_MANY_REQUESTS, "rate_limited")
+                .detail(format!("Too many requests. Please try again in {} seconds.", retry_after)),
         )
             .into_response());
     }
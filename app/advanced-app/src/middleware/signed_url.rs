@@ -0,0 +1,37 @@
+//! Signed Download Middleware
+
+use crate::extractors::CurrentSite;
+use crate::signed_urls::SignedQuery;
+use crate::BlogServices;
+use axum::{
+    extract::{Path, Query, Request, State},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use rustpress_auth::problem::ProblemDetails;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Reject download requests without a valid, unexpired signature
+///
+/// Installed with `route_layer` on `/media/:id/download` so the path is
+/// already matched. Every failure looks the same to the client.
+pub async fn verify_download(
+    State(services): State<Arc<BlogServices>>,
+    CurrentSite(site): CurrentSite,
+    Path(id): Path<Uuid>,
+    query: Option<Query<SignedQuery>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let valid = match (services.media.signer(), query) {
+        (Some(signer), Some(Query(query))) => signer.verify(site.id, id, &query),
+        _ => false,
+    };
+
+    if !valid {
+        return ProblemDetails::forbidden("Invalid or expired download link").into_response();
+    }
+
+    next.run(req).await
+}
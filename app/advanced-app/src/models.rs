@@ -1,13 +1,18 @@
 //! Blog Data Models
 
+use crate::timezones::ZonedTime;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
+use std::collections::BTreeMap;
 use uuid::Uuid;
+use utoipa::{IntoParams, ToSchema};
 use validator::Validate;
 
+pub use rustpress_auth::problem::{FieldError, ProblemDetails};
+
 /// Post status enum
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, sqlx::Type)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, sqlx::Type, ToSchema)]
 #[sqlx(type_name = "post_status", rename_all = "lowercase")]
 #[serde(rename_all = "lowercase")]
 pub enum PostStatus {
@@ -17,8 +22,20 @@ pub enum PostStatus {
     Archived,
 }
 
+/// Who can open a media file
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, sqlx::Type, ToSchema)]
+#[sqlx(type_name = "media_visibility", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum MediaVisibility {
+    /// Served from the storage backend's public URL
+    #[default]
+    Public,
+    /// Only reachable through signed download links
+    Private,
+}
+
 /// Comment status enum
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, sqlx::Type)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, sqlx::Type, ToSchema)]
 #[sqlx(type_name = "comment_status", rename_all = "lowercase")]
 #[serde(rename_all = "lowercase")]
 pub enum CommentStatus {
@@ -29,9 +46,10 @@ pub enum CommentStatus {
 }
 
 /// Blog post
-#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
 pub struct Post {
     pub id: Uuid,
+    pub site_id: Uuid,
     pub author_id: Uuid,
     pub title: String,
     pub slug: String,
@@ -45,22 +63,33 @@ pub struct Post {
     pub comment_count: i32,
     pub meta_title: Option<String>,
     pub meta_description: Option<String>,
+    /// Membership level needed to read the full post; `None` for public posts
+    #[sqlx(default)]
+    #[serde(default)]
+    pub required_level: Option<i32>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    /// `scheduled_for` in the author's time zone, in responses to changes
+    #[sqlx(skip)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schedule: Option<ZonedTime>,
 }
 
 /// Post with related data for API responses
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct PostWithRelations {
     #[serde(flatten)]
     pub post: Post,
     pub author: AuthorInfo,
     pub categories: Vec<Category>,
     pub tags: Vec<Tag>,
+    /// The content is a members-only teaser (see `access`)
+    #[serde(default)]
+    pub locked: bool,
 }
 
 /// Minimal author information
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct AuthorInfo {
     pub id: Uuid,
     pub name: String,
@@ -68,8 +97,31 @@ pub struct AuthorInfo {
     pub bio: Option<String>,
 }
 
+/// Public author profile
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct AuthorProfile {
+    pub id: Uuid,
+    pub slug: String,
+    pub name: String,
+    pub avatar: Option<String>,
+    pub bio: Option<String>,
+    pub website: Option<String>,
+    /// Links by network from `social_<network>` user meta, e.g. `{"github": "https://..."}`
+    pub social: serde_json::Value,
+    /// Published posts on the current site
+    pub post_count: i64,
+}
+
+/// Author profile with a page of their published posts
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct AuthorArchive {
+    #[serde(flatten)]
+    pub author: AuthorProfile,
+    pub posts: PaginatedResponse<PostWithRelations>,
+}
+
 /// Create post request
-#[derive(Debug, Clone, Deserialize, Validate)]
+#[derive(Debug, Clone, Deserialize, Validate, ToSchema)]
 pub struct CreatePostRequest {
     #[validate(length(min = 1, max = 200, message = "Title must be 1-200 characters"))]
     pub title: String,
@@ -92,11 +144,13 @@ pub struct CreatePostRequest {
     #[validate(length(max = 160))]
     pub meta_description: Option<String>,
 
-    pub scheduled_for: Option<DateTime<Utc>>,
+    /// When to publish: an RFC 3339 time, or a local time such as
+    /// `2026-03-01T09:00` in the author's time zone
+    pub scheduled_for: Option<String>,
 }
 
 /// Update post request
-#[derive(Debug, Clone, Deserialize, Validate)]
+#[derive(Debug, Clone, Deserialize, Validate, ToSchema)]
 pub struct UpdatePostRequest {
     #[validate(length(min = 1, max = 200))]
     pub title: Option<String>,
@@ -120,7 +174,8 @@ pub struct UpdatePostRequest {
 }
 
 /// Post query parameters
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct PostQuery {
     pub page: Option<i64>,
     pub per_page: Option<i64>,
@@ -147,9 +202,10 @@ impl PostQuery {
 }
 
 /// Category
-#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
 pub struct Category {
     pub id: Uuid,
+    pub site_id: Uuid,
     pub parent_id: Option<Uuid>,
     pub name: String,
     pub slug: String,
@@ -159,7 +215,7 @@ pub struct Category {
 }
 
 /// Create/Update category request
-#[derive(Debug, Clone, Deserialize, Validate)]
+#[derive(Debug, Clone, Deserialize, Validate, ToSchema)]
 pub struct CategoryRequest {
     #[validate(length(min = 1, max = 100))]
     pub name: String,
@@ -171,9 +227,10 @@ pub struct CategoryRequest {
 }
 
 /// Tag
-#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
 pub struct Tag {
     pub id: Uuid,
+    pub site_id: Uuid,
     pub name: String,
     pub slug: String,
     pub post_count: i32,
@@ -181,16 +238,17 @@ pub struct Tag {
 }
 
 /// Create/Update tag request
-#[derive(Debug, Clone, Deserialize, Validate)]
+#[derive(Debug, Clone, Deserialize, Validate, ToSchema)]
 pub struct TagRequest {
     #[validate(length(min = 1, max = 50))]
     pub name: String,
 }
 
 /// Comment
-#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
 pub struct Comment {
     pub id: Uuid,
+    pub site_id: Uuid,
     pub post_id: Uuid,
     pub parent_id: Option<Uuid>,
     pub author_id: Option<Uuid>,
@@ -202,18 +260,195 @@ pub struct Comment {
     pub ip_address: Option<String>,
     pub user_agent: Option<String>,
     pub created_at: DateTime<Utc>,
+    /// Upvotes (or hearts) minus downvotes
+    pub score: i32,
+    pub upvotes: i32,
+    pub downvotes: i32,
+    /// Toxicity score when the comment was posted, 0 to 1; `None` if it
+    /// wasn't scored
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub toxicity: Option<f32>,
+    /// Score of each attribute the model checked
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub toxicity_attributes: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub toxicity_model: Option<String>,
+}
+
+/// A comment's toxicity, as scored by the moderation provider
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToxicityScore {
+    /// The highest attribute score, 0 (benign) to 1
+    pub score: f32,
+    pub attributes: BTreeMap<String, f32>,
+    pub model: String,
 }
 
 /// Comment with nested replies
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct CommentThread {
     #[serde(flatten)]
     pub comment: Comment,
+    /// The requesting user's or visitor's vote: 1 or -1
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub viewer_vote: Option<i16>,
+    #[schema(no_recursion)]
     pub replies: Vec<CommentThread>,
 }
 
+/// Order of comments on each level of a thread
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum CommentSort {
+    #[default]
+    Oldest,
+    Newest,
+    /// Highest score first, older first among equals
+    Top,
+}
+
+/// Query parameters for comment lists
+#[derive(Debug, Clone, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct CommentQuery {
+    /// `oldest` (default), `newest` or `top`
+    pub sort: Option<CommentSort>,
+}
+
+/// How a site's visitors vote on comments
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum VoteStyle {
+    /// Upvotes and downvotes
+    #[default]
+    UpDown,
+    /// Upvotes only, shown as hearts
+    Hearts,
+    Off,
+}
+
+/// Vote on a comment
+#[derive(Debug, Clone, Deserialize, Validate, ToSchema)]
+pub struct VoteRequest {
+    /// 1 to upvote, -1 to downvote, 0 to take the vote back
+    #[validate(range(min = -1, max = 1))]
+    pub value: i16,
+}
+
+/// A comment's votes after voting
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct CommentVotes {
+    pub comment_id: Uuid,
+    pub score: i32,
+    pub upvotes: i32,
+    pub downvotes: i32,
+    /// The voter's vote, absent once taken back
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub viewer_vote: Option<i16>,
+}
+
+/// A saved version of a post's text
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct PostRevision {
+    pub post_id: Uuid,
+    /// 1 for the first version, counting up
+    pub number: i32,
+    pub author_id: Option<Uuid>,
+    pub author_name: Option<String>,
+    pub title: String,
+    pub slug: String,
+    pub content: String,
+    pub excerpt: Option<String>,
+    pub featured_image: Option<String>,
+    pub meta_title: Option<String>,
+    pub meta_description: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A revision in a post's history, without its text
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct RevisionSummary {
+    pub number: i32,
+    pub author_id: Option<Uuid>,
+    pub author_name: Option<String>,
+    pub title: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Query parameters for a post diff
+#[derive(Debug, Clone, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DiffQuery {
+    /// Older revision; defaults to the one before `to`
+    pub from: Option<i32>,
+    /// Newer revision; defaults to the latest
+    pub to: Option<i32>,
+}
+
+/// What a run of diffed text is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffOp {
+    Equal,
+    Insert,
+    Delete,
+}
+
+/// A run of text that is unchanged, added or removed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct DiffChange {
+    pub op: DiffOp,
+    pub text: String,
+}
+
+/// A changed region of a field, with some unchanged text around it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct DiffHunk {
+    /// Byte range of the hunk in the old text
+    pub old_start: usize,
+    pub old_end: usize,
+    /// Byte range of the hunk in the new text
+    pub new_start: usize,
+    pub new_end: usize,
+    pub changes: Vec<DiffChange>,
+}
+
+/// A field that differs between two versions
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct FieldDiff {
+    pub field: String,
+    /// Old and new values; `null` for `content`, which comes as hunks
+    pub old: Option<String>,
+    pub new: Option<String>,
+    /// HTML-aware hunks (tags are never split), for `content`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hunks: Vec<DiffHunk>,
+}
+
+/// Differences between two revisions of a post
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct PostDiff {
+    pub post_id: Uuid,
+    pub from: i32,
+    pub to: i32,
+    /// Only the fields that changed
+    pub fields: Vec<FieldDiff>,
+}
+
+/// Who is editing a post
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct EditLock {
+    pub post_id: Uuid,
+    pub user_id: Uuid,
+    pub user_name: String,
+    pub acquired_at: DateTime<Utc>,
+    pub heartbeat_at: DateTime<Utc>,
+    /// When the lock lapses unless renewed
+    pub expires_at: DateTime<Utc>,
+}
+
 /// Create comment request
-#[derive(Debug, Clone, Deserialize, Validate)]
+#[derive(Debug, Clone, Deserialize, Validate, ToSchema)]
 pub struct CreateCommentRequest {
     pub parent_id: Option<Uuid>,
 
@@ -230,10 +465,54 @@ pub struct CreateCommentRequest {
     pub content: String,
 }
 
+/// What a content rule does with text it matches, from mildest to strictest
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ModerationAction {
+    /// Replace the match with `*`s
+    Censor,
+    /// Hold comments for moderation; posts wait for an editor to publish
+    Hold,
+    /// Refuse the comment or post
+    Reject,
+}
+
+/// A rule that matched
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RuleMatch {
+    /// The rule's pattern as written
+    pub rule: String,
+    pub action: ModerationAction,
+    /// Language pack the rule is from; `None` for the site's own rules
+    pub pack: Option<String>,
+    /// The matched text
+    pub text: String,
+}
+
+/// Outcome of checking text against the content rules
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ModerationResult {
+    /// Strictest action of the rules that matched; `null` if none did
+    pub action: Option<ModerationAction>,
+    /// The text with censored matches replaced
+    pub content: String,
+    pub matches: Vec<RuleMatch>,
+    /// Rules that couldn't be read and were skipped
+    pub errors: Vec<String>,
+}
+
+/// Text to check against the active content rules
+#[derive(Debug, Clone, Deserialize, Validate, ToSchema)]
+pub struct ModerationTestRequest {
+    #[validate(length(min = 1, max = 100000))]
+    pub text: String,
+}
+
 /// Media file
-#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
 pub struct Media {
     pub id: Uuid,
+    pub site_id: Uuid,
     pub uploader_id: Uuid,
     pub filename: String,
     pub original_name: String,
@@ -243,13 +522,150 @@ pub struct Media {
     pub height: Option<i32>,
     pub alt_text: Option<String>,
     pub caption: Option<String>,
+    /// Public URL, or for private media a signed link that expires
     pub url: String,
     pub thumbnail_url: Option<String>,
+    pub visibility: MediaVisibility,
+    /// When the file was moved to the trash; its storage object is deleted
+    /// after the retention period
+    #[sqlx(default)]
+    #[serde(default)]
+    pub trashed_at: Option<DateTime<Utc>>,
+    #[sqlx(default)]
+    #[serde(default)]
+    pub trashed_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Media {
+    /// Storage key; private files live outside the publicly served prefix
+    pub fn storage_path(&self) -> String {
+        media_storage_path(self.visibility, &self.filename)
+    }
+}
+
+/// Storage key of a media file
+pub fn media_storage_path(visibility: MediaVisibility, filename: &str) -> String {
+    match visibility {
+        MediaVisibility::Public => format!("uploads/media/{}", filename),
+        MediaVisibility::Private => format!("private/media/{}", filename),
+    }
+}
+
+/// Storage prefixes holding media files
+pub const MEDIA_STORAGE_PREFIXES: &[&str] = &["uploads/media/", "private/media/"];
+
+/// What an orphan scan found
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type, ToSchema)]
+#[sqlx(type_name = "media_orphan_kind", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum OrphanKind {
+    /// A media row whose storage object is gone
+    MissingFile,
+    /// A storage object no media row points at
+    UntrackedFile,
+}
+
+/// A media row without a file, or a file without a row
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct MediaOrphan {
+    pub storage_path: String,
+    pub kind: OrphanKind,
+    /// Set for missing files
+    pub media_id: Option<Uuid>,
+    pub site_id: Option<Uuid>,
+    pub first_seen_at: DateTime<Utc>,
+    /// Last scan that still found it
+    pub last_seen_at: DateTime<Utc>,
+}
+
+/// Orphan report
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct OrphanReport {
+    pub missing_files: i64,
+    pub untracked_files: i64,
+    pub orphans: Vec<MediaOrphan>,
+}
+
+/// Result of a trash purge
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct TrashPurge {
+    /// Files deleted together with their rows
+    pub purged: usize,
+    /// Files whose storage delete failed; retried on the next run
+    pub failed: usize,
+}
+
+/// Change a media file's visibility
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct UpdateMediaVisibility {
+    pub visibility: MediaVisibility,
+}
+
+/// Where an alt text suggestion stands
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type, ToSchema)]
+#[sqlx(type_name = "alt_suggestion_status", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum AltSuggestionStatus {
+    /// Waiting for the vision model
+    Queued,
+    /// Generated, waiting for an editor
+    Pending,
+    /// Confirmed, possibly edited, and saved as the image's alt text
+    Accepted,
+    Rejected,
+    /// The model failed on every attempt
+    Failed,
+}
+
+/// Alt text suggested for an image, with the image
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct AltTextSuggestion {
+    pub media_id: Uuid,
+    pub status: AltSuggestionStatus,
+    pub suggestion: Option<String>,
+    /// Model that wrote the suggestion
+    pub model: Option<String>,
+    /// Last failure, for failed and retried suggestions
+    pub error: Option<String>,
+    pub attempts: i32,
+    /// The image's URL; private files are linked through `GET /media/:id/url`
+    pub url: String,
+    pub original_name: String,
+    /// The image's current alt text
+    pub alt_text: Option<String>,
     pub created_at: DateTime<Utc>,
+    pub generated_at: Option<DateTime<Utc>>,
+    pub reviewed_by: Option<Uuid>,
+    pub reviewed_at: Option<DateTime<Utc>>,
+}
+
+/// Alt text suggestion list parameters
+#[derive(Debug, Clone, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AltSuggestionQuery {
+    /// Default `pending`
+    pub status: Option<AltSuggestionStatus>,
+    pub limit: Option<i64>,
+}
+
+/// Confirm a suggestion, as is or edited
+#[derive(Debug, Clone, Default, Deserialize, Validate, ToSchema)]
+pub struct AcceptAltText {
+    /// Saved instead of the suggestion
+    #[validate(length(min = 1, max = 1000))]
+    pub alt_text: Option<String>,
+}
+
+/// Images queued by a backfill
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct AltTextBackfill {
+    pub queued: u64,
 }
 
 /// Media query parameters
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct MediaQuery {
     pub page: Option<i64>,
     pub per_page: Option<i64>,
@@ -258,7 +674,8 @@ pub struct MediaQuery {
 }
 
 /// Search query parameters
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct SearchQuery {
     pub q: String,
     pub page: Option<i64>,
@@ -268,7 +685,7 @@ pub struct SearchQuery {
 }
 
 /// Search result
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct SearchResult {
     pub posts: Vec<PostWithRelations>,
     pub total: i64,
@@ -277,14 +694,75 @@ pub struct SearchResult {
     pub total_pages: i64,
 }
 
+/// Semantic search query parameters
+#[derive(Debug, Clone, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SemanticSearchQuery {
+    pub q: String,
+    /// Posts returned (default 10, at most 50)
+    pub limit: Option<i64>,
+}
+
+impl SemanticSearchQuery {
+    pub fn limit(&self) -> i64 {
+        self.limit.unwrap_or(10).clamp(1, 50)
+    }
+}
+
+/// Related posts query parameters
+#[derive(Debug, Clone, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct RelatedQuery {
+    /// Posts returned (default 5, at most 20)
+    pub limit: Option<i64>,
+}
+
+impl RelatedQuery {
+    pub fn limit(&self) -> i64 {
+        self.limit.unwrap_or(5).clamp(1, 20)
+    }
+}
+
+/// Unpaginated list response wrapper
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct DataResponse<T> {
+    pub data: T,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub count: Option<usize>,
+}
+
+impl<T> DataResponse<T> {
+    pub fn new(data: T) -> Self {
+        Self { data, count: None }
+    }
+}
+
+impl<T> DataResponse<Vec<T>> {
+    pub fn with_count(data: Vec<T>) -> Self {
+        Self {
+            count: Some(data.len()),
+            data,
+        }
+    }
+}
+
+/// Multipart body for media uploads
+#[derive(Debug, ToSchema)]
+pub struct MediaUpload {
+    #[schema(value_type = String, format = Binary)]
+    pub file: Vec<u8>,
+    /// `public` (default) or `private`
+    pub visibility: Option<MediaVisibility>,
+}
+
 /// Paginated response wrapper
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct PaginatedResponse<T> {
     pub data: Vec<T>,
     pub pagination: PaginationMeta,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct PaginationMeta {
     pub total: i64,
     pub page: i64,
@@ -308,8 +786,234 @@ impl PaginationMeta {
     }
 }
 
+/// Kind of object a content activity entry describes
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, sqlx::Type, ToSchema)]
+#[sqlx(type_name = "content_object_type", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum ContentObject {
+    Post,
+    Comment,
+    Category,
+}
+
+/// What was done to a post, comment or category
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, sqlx::Type, ToSchema)]
+#[sqlx(type_name = "content_action", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum ContentAction {
+    Created,
+    Updated,
+    Published,
+    Unpublished,
+    Approved,
+    Rejected,
+    /// Deleted
+    Trashed,
+}
+
+/// Content activity entry
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct ActivityEntry {
+    pub id: Uuid,
+    /// `None` for guests and background jobs
+    pub actor_id: Option<Uuid>,
+    pub actor_name: Option<String>,
+    pub object_type: ContentObject,
+    pub object_id: Uuid,
+    pub action: ContentAction,
+    /// Title or name at the time of the change
+    pub object_label: Option<String>,
+    /// Changed fields as `{"field": {"from": ..., "to": ...}}`
+    pub changes: Option<serde_json::Value>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Content activity query parameters
+#[derive(Debug, Clone, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ActivityQuery {
+    pub actor: Option<Uuid>,
+    pub object_type: Option<ContentObject>,
+    pub object_id: Option<Uuid>,
+    pub action: Option<ContentAction>,
+    /// Entries at or after this time
+    pub from: Option<DateTime<Utc>>,
+    /// Entries before this time
+    pub to: Option<DateTime<Utc>>,
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+}
+
+impl ActivityQuery {
+    pub fn page(&self) -> i64 {
+        self.page.unwrap_or(1).max(1)
+    }
+
+    pub fn per_page(&self) -> i64 {
+        self.per_page.unwrap_or(50).min(200).max(1)
+    }
+
+    pub fn offset(&self) -> i64 {
+        (self.page() - 1) * self.per_page()
+    }
+}
+
+/// How often a user gets the digest of comments and reactions on their posts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type, ToSchema)]
+#[sqlx(type_name = "digest_frequency", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum DigestFrequency {
+    /// Nothing is queued or sent
+    Off,
+    Daily,
+    Weekly,
+}
+
+/// What a queued notification is about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type, ToSchema)]
+#[sqlx(type_name = "notification_kind", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum NotificationKind {
+    Comment,
+    Reaction,
+    Mention,
+}
+
+/// A user's digest settings
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct NotificationPreferences {
+    pub frequency: DigestFrequency,
+    /// Whether `frequency` is the app's default rather than the user's choice
+    pub is_default: bool,
+    pub last_digest_at: Option<DateTime<Utc>>,
+    /// Notifications waiting for the next digest
+    pub pending: i64,
+}
+
+/// Update digest settings
+#[derive(Debug, Clone, Deserialize, Validate, ToSchema)]
+pub struct UpdateNotificationPreferencesRequest {
+    /// `null` goes back to the app's default
+    pub frequency: Option<DigestFrequency>,
+}
+
+/// A user's display preferences on the current site
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct UserPreferences {
+    /// Every preference the site has, e.g. `{"color_scheme": "dark"}`: the
+    /// user's choice, or else the site's default
+    pub preferences: BTreeMap<String, serde_json::Value>,
+    /// Keys the user chose themselves
+    pub customized: Vec<String>,
+}
+
+/// Change display preferences
+#[derive(Debug, Clone, Deserialize, Validate, ToSchema)]
+pub struct UpdatePreferencesRequest {
+    /// Keys to change; `null` goes back to the default, keys left out stay
+    #[validate(length(max = 50))]
+    pub preferences: BTreeMap<String, serde_json::Value>,
+}
+
+/// A mention of the current user, as an in-app notification
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct Mention {
+    pub id: Uuid,
+    pub post_id: Uuid,
+    pub post_title: String,
+    pub post_slug: String,
+    /// `post`, or `comment` for mentions in one of its comments
+    pub object_type: ContentObject,
+    pub object_id: Uuid,
+    /// Who wrote the post or comment
+    pub actor_name: String,
+    /// Start of the post excerpt or comment
+    pub summary: String,
+    pub notified_at: DateTime<Utc>,
+    pub read_at: Option<DateTime<Utc>>,
+}
+
+/// Query parameters for the mention list
+#[derive(Debug, Clone, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct MentionQuery {
+    /// Only mentions not yet marked read
+    #[serde(default)]
+    pub unread: bool,
+}
+
+/// Why a comment skipped moderation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type, ToSchema)]
+#[sqlx(type_name = "comment_trust_reason", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum TrustReason {
+    /// The commenter had enough approved comments
+    History,
+    /// An admin marked the commenter trusted
+    Override,
+}
+
+/// Whether a user's comments skip moderation on a site
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CommenterTrust {
+    pub user_id: Uuid,
+    /// Admin override: `true` always skips moderation, `false` always holds
+    /// comments, `null` goes by approved comments
+    pub trusted: Option<bool>,
+    /// Approved comments by the account or its verified email
+    pub approved_comments: i64,
+    /// Whether their next comment skips moderation
+    pub auto_approved: bool,
+}
+
+/// Set or clear a commenter's trust override
+#[derive(Debug, Clone, Deserialize, Validate, ToSchema)]
+pub struct SetCommenterTrustRequest {
+    /// `null` clears the override
+    pub trusted: Option<bool>,
+}
+
+/// A comment that skipped moderation, for spot checks
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct AutoApproval {
+    pub comment_id: Uuid,
+    pub post_id: Uuid,
+    /// Account the commenter was matched to
+    pub user_id: Uuid,
+    pub author_name: String,
+    pub author_email: String,
+    pub content: String,
+    pub status: CommentStatus,
+    pub reason: TrustReason,
+    /// Approved comments the commenter had at the time
+    pub approved_comments: i32,
+    pub reviewed_by: Option<Uuid>,
+    pub reviewed_at: Option<DateTime<Utc>>,
+    /// Whether the reviewer kept the comment
+    pub kept: Option<bool>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Query parameters for the auto-approval audit
+#[derive(Debug, Clone, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AutoApprovalQuery {
+    /// Only auto-approvals nobody has reviewed
+    #[serde(default)]
+    pub unreviewed: bool,
+    /// Return this many at random instead of the newest 100
+    pub sample: Option<i64>,
+}
+
+/// Outcome of a spot check
+#[derive(Debug, Clone, Deserialize, Validate, ToSchema)]
+pub struct ReviewAutoApprovalRequest {
+    /// `false` rejects the comment
+    pub keep: bool,
+}
+
 /// Blog statistics
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct BlogStats {
     pub total_posts: i64,
     pub published_posts: i64,
@@ -322,26 +1026,404 @@ pub struct BlogStats {
     pub total_views: i64,
 }
 
-/// API error response
-#[derive(Debug, Clone, Serialize)]
-pub struct ApiError {
-    pub error: String,
-    pub message: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub details: Option<serde_json::Value>,
+/// Result of a static export to storage
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ExportReport {
+    /// Files written, including the feed and sitemap
+    pub files: usize,
+    pub posts: usize,
+    /// Storage prefix the site was written under
+    pub root: String,
+    /// Public URL of the exported home page
+    pub url: String,
+}
+
+/// Where a backup stands
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type, ToSchema)]
+#[sqlx(type_name = "backup_status", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum BackupStatus {
+    Running,
+    Completed,
+    Failed,
+    /// Removed by the retention policy; the row is kept for the record
+    Pruned,
+}
+
+/// A file of a backup, relative to its storage prefix
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct BackupArtifact {
+    pub path: String,
+    pub sha256: String,
+    pub size: i64,
+}
+
+/// The snapshot a backup was taken at
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+#[serde(default)]
+pub struct PointInTime {
+    /// Database server time inside the snapshot
+    pub taken_at: Option<DateTime<Utc>>,
+    /// WAL position at the snapshot, for replaying archived WAL up to it
+    pub wal_lsn: Option<String>,
+    /// Latest applied migration of the app and each plugin
+    pub migrations: BTreeMap<String, i64>,
+    /// Tables dumped, in restore order
+    pub tables: Vec<String>,
+}
+
+/// Backup with its artifacts and integrity state
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct Backup {
+    pub id: Uuid,
+    pub status: BackupStatus,
+    /// `logical` or `pg_dump`
+    pub method: String,
+    pub storage_prefix: String,
+    pub size_bytes: i64,
+    #[sqlx(json)]
+    pub artifacts: Vec<BackupArtifact>,
+    #[sqlx(json)]
+    pub point_in_time: PointInTime,
+    pub error: Option<String>,
+    pub started_by: Option<Uuid>,
+    pub started_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+    /// Last time the stored artifacts were checked against their checksums
+    pub verified_at: Option<DateTime<Utc>>,
+    pub verified: Option<bool>,
+    pub pruned_at: Option<DateTime<Utc>>,
+}
+
+/// How to restore a backup into a staging database
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RestoreInstructions {
+    /// Migrations the staging schema must be at before data is loaded
+    pub migrations: BTreeMap<String, i64>,
+    /// Shell commands, run in order against `$STAGING_DATABASE_URL`
+    pub steps: Vec<String>,
+    /// Media files to copy, from `media.json`
+    pub media_manifest: String,
+}
+
+/// Backup as listed by `GET /admin/backups`
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct BackupEntry {
+    #[serde(flatten)]
+    pub backup: Backup,
+    /// Set for completed backups
+    pub restore: Option<RestoreInstructions>,
+}
+
+/// Result of checking a backup's artifacts against its checksums
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct BackupVerification {
+    pub id: Uuid,
+    pub verified: bool,
+    /// Artifacts that are missing or whose checksum differs
+    pub mismatched: Vec<String>,
+    pub verified_at: DateTime<Utc>,
+}
+
+/// Widget instance placed in a sidebar
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct WidgetInstance {
+    pub id: Uuid,
+    pub site_id: Uuid,
+    pub sidebar: String,
+    pub widget_type: String,
+    pub title: Option<String>,
+    pub position: i32,
+    pub settings: serde_json::Value,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Registered widget type
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct WidgetTypeInfo {
+    pub id: String,
+    pub name: String,
+    pub default_settings: serde_json::Value,
+}
+
+/// Create widget instance request
+#[derive(Debug, Clone, Deserialize, Validate, ToSchema)]
+pub struct CreateWidgetRequest {
+    #[validate(length(min = 1, max = 50))]
+    pub sidebar: String,
+
+    #[validate(length(min = 1, max = 50))]
+    pub widget_type: String,
+
+    #[validate(length(max = 100))]
+    pub title: Option<String>,
+
+    pub position: Option<i32>,
+
+    pub settings: Option<serde_json::Value>,
+}
+
+/// Update widget instance request
+#[derive(Debug, Clone, Deserialize, Validate, ToSchema)]
+pub struct UpdateWidgetRequest {
+    #[validate(length(min = 1, max = 50))]
+    pub sidebar: Option<String>,
+
+    #[validate(length(max = 100))]
+    pub title: Option<String>,
+
+    pub position: Option<i32>,
+
+    pub settings: Option<serde_json::Value>,
+}
+
+/// Filters of the admin post list, as saved in a view
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(default)]
+pub struct PostFilters {
+    pub status: Option<PostStatus>,
+    pub author: Option<Uuid>,
+    /// Category slug
+    pub category: Option<String>,
+    /// Posts created at or after this time
+    pub from: Option<DateTime<Utc>>,
+    /// Posts created before this time
+    pub to: Option<DateTime<Utc>>,
+    /// Full-text search of title, excerpt and content
+    pub q: Option<String>,
 }
 
-impl ApiError {
-    pub fn new(error: &str, message: &str) -> Self {
+impl PostFilters {
+    /// These filters with any set in `overrides` replacing them
+    pub fn merge(self, overrides: PostFilters) -> Self {
         Self {
-            error: error.to_string(),
-            message: message.to_string(),
-            details: None,
+            status: overrides.status.or(self.status),
+            author: overrides.author.or(self.author),
+            category: overrides.category.or(self.category),
+            from: overrides.from.or(self.from),
+            to: overrides.to.or(self.to),
+            q: overrides.q.or(self.q),
+        }
+    }
+}
+
+/// Column of the admin post list's CSV export
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PostColumn {
+    Id,
+    Title,
+    Slug,
+    Status,
+    Author,
+    Categories,
+    Tags,
+    CreatedAt,
+    UpdatedAt,
+    PublishedAt,
+    Views,
+    Comments,
+}
+
+impl PostColumn {
+    /// Columns of views that don't choose any
+    pub const DEFAULT: &'static [PostColumn] = &[
+        PostColumn::Title,
+        PostColumn::Status,
+        PostColumn::Author,
+        PostColumn::Categories,
+        PostColumn::UpdatedAt,
+    ];
+
+    /// Name in requests and the CSV header
+    pub fn name(&self) -> &'static str {
+        match self {
+            PostColumn::Id => "id",
+            PostColumn::Title => "title",
+            PostColumn::Slug => "slug",
+            PostColumn::Status => "status",
+            PostColumn::Author => "author",
+            PostColumn::Categories => "categories",
+            PostColumn::Tags => "tags",
+            PostColumn::CreatedAt => "created_at",
+            PostColumn::UpdatedAt => "updated_at",
+            PostColumn::PublishedAt => "published_at",
+            PostColumn::Views => "views",
+            PostColumn::Comments => "comments",
         }
     }
+}
+
+/// Admin post list parameters
+///
+/// With `view`, the saved view's filters apply and any given here replace
+/// them.
+#[derive(Debug, Clone, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AdminPostQuery {
+    /// Saved view to start from
+    pub view: Option<Uuid>,
+    pub status: Option<PostStatus>,
+    pub author: Option<Uuid>,
+    /// Category slug
+    pub category: Option<String>,
+    /// Posts created at or after this time
+    pub from: Option<DateTime<Utc>>,
+    /// Posts created before this time
+    pub to: Option<DateTime<Utc>>,
+    /// Full-text search of title, excerpt and content
+    pub q: Option<String>,
+    /// Comma-separated CSV columns (e.g. `title,status,author`); defaults to
+    /// the view's
+    pub columns: Option<String>,
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+    /// `created` (default), `updated`, `published`, `views` or `comments`
+    pub sort: Option<String>,
+    /// `asc` or `desc` (default)
+    pub order: Option<String>,
+}
+
+impl AdminPostQuery {
+    pub fn page(&self) -> i64 {
+        self.page.unwrap_or(1).max(1)
+    }
 
-    pub fn with_details(mut self, details: serde_json::Value) -> Self {
-        self.details = Some(details);
-        self
+    pub fn per_page(&self) -> i64 {
+        self.per_page.unwrap_or(20).min(100).max(1)
     }
+
+    pub fn offset(&self) -> i64 {
+        (self.page() - 1) * self.per_page()
+    }
+
+    /// Filters given in the query itself
+    pub fn filters(&self) -> PostFilters {
+        PostFilters {
+            status: self.status.clone(),
+            author: self.author,
+            category: self.category.clone(),
+            from: self.from,
+            to: self.to,
+            q: self.q.clone(),
+        }
+    }
+}
+
+/// A saved view of the admin post list
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct PostView {
+    pub id: Uuid,
+    pub site_id: Uuid,
+    pub user_id: Uuid,
+    pub name: String,
+    #[sqlx(json)]
+    pub filters: PostFilters,
+    /// CSV export columns, in order
+    #[sqlx(json)]
+    pub columns: Vec<PostColumn>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Create or replace a saved view
+#[derive(Debug, Clone, Deserialize, Validate, ToSchema)]
+pub struct PostViewRequest {
+    #[validate(length(min = 1, max = 100))]
+    pub name: String,
+
+    #[serde(default)]
+    pub filters: PostFilters,
+
+    /// Defaults to title, status, author, categories and updated_at
+    #[serde(default)]
+    #[validate(length(max = 12))]
+    pub columns: Vec<PostColumn>,
+}
+
+/// What a tool API key may do
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub enum ToolScope {
+    /// Create drafts and edit posts (`create_draft`, `update_post`)
+    #[serde(rename = "posts:write")]
+    PostsWrite,
+    /// Read comments awaiting moderation (`list_pending_comments`)
+    #[serde(rename = "comments:read")]
+    CommentsRead,
+    /// Read analytics reports (`summarize_analytics`)
+    #[serde(rename = "analytics:read")]
+    AnalyticsRead,
+}
+
+/// A tool API key, without its secret
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct ToolKey {
+    pub id: Uuid,
+    pub site_id: Uuid,
+    /// User the key acts as
+    pub user_id: Uuid,
+    pub name: String,
+    /// Start of the key, to tell keys apart
+    pub prefix: String,
+    #[sqlx(json)]
+    pub scopes: Vec<ToolScope>,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+/// A new tool API key
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CreatedToolKey {
+    #[serde(flatten)]
+    pub key: ToolKey,
+    /// The key itself; shown only once
+    pub secret: String,
+}
+
+/// Create a tool API key
+#[derive(Debug, Clone, Deserialize, Validate, ToSchema)]
+pub struct CreateToolKeyRequest {
+    #[validate(length(min = 1, max = 100))]
+    pub name: String,
+
+    /// User the key acts as; defaults to the admin creating it
+    pub user_id: Option<Uuid>,
+
+    #[validate(length(min = 1))]
+    pub scopes: Vec<ToolScope>,
+}
+
+/// A tool, as listed for agents
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ToolDefinition {
+    pub name: String,
+    pub description: String,
+    /// Scope a key needs to call it
+    pub scope: ToolScope,
+    /// Whether the tool changes anything, and so honors `dry_run`
+    pub writes: bool,
+    /// JSON Schema of the tool's `input`
+    pub input_schema: serde_json::Value,
+}
+
+/// Call a tool
+#[derive(Debug, Clone, Default, Deserialize, ToSchema)]
+pub struct ToolCallRequest {
+    /// Arguments, as described by the tool's `input_schema`
+    #[serde(default)]
+    pub input: serde_json::Value,
+    /// Check and preview the call without changing anything
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// What a tool call did, or would do in a dry run
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ToolCallResponse {
+    pub tool: String,
+    pub dry_run: bool,
+    pub result: serde_json::Value,
 }
@@ -0,0 +1,294 @@
+//! Content Policy
+//!
+//! New comments, and posts when `moderation_posts` is on, are checked
+//! against the site's content rules before they're saved. A rule is a word
+//! or phrase, matched as a whole word in any case, or a `/regex/` (`/regex/i`
+//! ignores case), with an action:
+//!
+//! - `censor` replaces the match with `*`s (the default)
+//! - `hold` holds the comment for moderation; a post is saved, but only an
+//!   editor may publish it (or change it once published)
+//! - `reject` refuses the comment or post
+//!
+//! The strictest matching action wins. Rules are read from the site's
+//! `blog-api` settings: `moderation_rules` holds the site's own, one per line
+//! as `<action>: <pattern>`, and `moderation_packs` holds language packs in
+//! the same format, each started by a `[<language>]` line, of which
+//! `moderation_languages` picks the active ones. Lines that can't be read are
+//! skipped and reported by `POST /admin/moderation/test`. Editors and admins
+//! are exempt.
+
+use crate::extractors::User;
+use crate::models::*;
+use crate::services::ServiceError;
+use crate::sites::Site;
+use crate::APP_ID;
+use regex::Regex;
+use rustpress_apps::prelude::*;
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// Languages of sites that don't set `moderation_languages`
+const DEFAULT_LANGUAGES: &str = "en";
+
+/// What is being checked
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModerationTarget {
+    Comment,
+    Post,
+}
+
+/// A compiled rule
+#[derive(Debug)]
+struct Rule {
+    pattern: String,
+    action: ModerationAction,
+    pack: Option<String>,
+    regex: Regex,
+}
+
+/// A site's compiled rules
+#[derive(Debug, Default)]
+pub struct RuleSet {
+    rules: Vec<Rule>,
+    errors: Vec<String>,
+}
+
+impl RuleSet {
+    /// Compile the site's own rules and the packs of `languages` (`*` for
+    /// every pack)
+    pub fn compile(rules: &str, packs: &str, languages: &[String]) -> Self {
+        let mut set = Self::default();
+        for (n, line) in rules.lines().enumerate() {
+            set.add(None, n + 1, line);
+        }
+
+        let mut pack: Option<String> = None;
+        for (n, line) in packs.lines().enumerate() {
+            let trimmed = line.trim();
+            if let Some(language) = trimmed.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                pack = Some(language.trim().to_ascii_lowercase());
+                continue;
+            }
+            match &pack {
+                Some(language) if languages.iter().any(|l| l == "*" || l == language) => {
+                    set.add(Some(language.clone()), n + 1, line)
+                }
+                Some(_) => {}
+                None if trimmed.is_empty() || trimmed.starts_with('#') => {}
+                None => set.errors.push(format!("packs line {}: rule outside of a [language] section", n + 1)),
+            }
+        }
+        set
+    }
+
+    fn add(&mut self, pack: Option<String>, line_number: usize, line: &str) {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return;
+        }
+        let (action, pattern) = match line.split_once(':') {
+            Some((action, pattern)) => match parse_action(action.trim()) {
+                Some(action) => (action, pattern.trim()),
+                None => (ModerationAction::Censor, line),
+            },
+            None => (ModerationAction::Censor, line),
+        };
+
+        let source = match pattern.strip_prefix('/') {
+            Some(rest) if rest.ends_with("/i") && rest.len() > 2 => format!("(?i){}", &rest[..rest.len() - 2]),
+            Some(rest) if rest.ends_with('/') && rest.len() > 1 => rest[..rest.len() - 1].to_string(),
+            _ => word_pattern(pattern),
+        };
+        match Regex::new(&source) {
+            Ok(regex) => self.rules.push(Rule {
+                pattern: pattern.to_string(),
+                action,
+                pack,
+                regex,
+            }),
+            Err(e) => {
+                let origin = match &pack {
+                    Some(language) => format!("pack {} line {}", language, line_number),
+                    None => format!("rules line {}", line_number),
+                };
+                self.errors.push(format!("{}: {}", origin, e));
+            }
+        }
+    }
+
+    /// Check `text` against every rule
+    pub fn check(&self, text: &str) -> ModerationResult {
+        let mut matches = Vec::new();
+        let mut censored: Vec<Range<usize>> = Vec::new();
+        for rule in &self.rules {
+            for found in rule.regex.find_iter(text).filter(|m| !m.is_empty()) {
+                matches.push(RuleMatch {
+                    rule: rule.pattern.clone(),
+                    action: rule.action,
+                    pack: rule.pack.clone(),
+                    text: found.as_str().to_string(),
+                });
+                if rule.action == ModerationAction::Censor {
+                    censored.push(found.range());
+                }
+            }
+        }
+
+        ModerationResult {
+            action: matches.iter().map(|m| m.action).max(),
+            content: censor(text, censored),
+            matches,
+            errors: self.errors.clone(),
+        }
+    }
+}
+
+fn parse_action(action: &str) -> Option<ModerationAction> {
+    match action.to_ascii_lowercase().as_str() {
+        "censor" => Some(ModerationAction::Censor),
+        "hold" => Some(ModerationAction::Hold),
+        "reject" => Some(ModerationAction::Reject),
+        _ => None,
+    }
+}
+
+/// A word or phrase as a case-insensitive whole-word pattern
+fn word_pattern(phrase: &str) -> String {
+    let is_word = |c: Option<char>| c.map_or(false, |c| c.is_alphanumeric() || c == '_');
+    format!(
+        "(?i){}{}{}",
+        if is_word(phrase.chars().next()) { r"\b" } else { "" },
+        regex::escape(phrase),
+        if is_word(phrase.chars().last()) { r"\b" } else { "" },
+    )
+}
+
+/// Replace every character in `ranges` with `*`
+fn censor(text: &str, mut ranges: Vec<Range<usize>>) -> String {
+    if ranges.is_empty() {
+        return text.to_string();
+    }
+    ranges.sort_by_key(|r| r.start);
+
+    let mut censored = String::with_capacity(text.len());
+    let mut last = 0;
+    for range in ranges {
+        if range.end <= last {
+            continue;
+        }
+        let start = range.start.max(last);
+        censored.push_str(&text[last..start]);
+        censored.extend(text[start..range.end].chars().map(|_| '*'));
+        last = range.end;
+    }
+    censored.push_str(&text[last..]);
+    censored
+}
+
+/// A site's moderation settings
+#[derive(Debug, Clone, PartialEq)]
+struct PolicySettings {
+    enabled: bool,
+    posts: bool,
+    languages: String,
+    rules: String,
+    packs: String,
+}
+
+pub struct ModerationService {
+    settings: SettingsManager,
+    /// Compiled rules by settings namespace, with the settings they were
+    /// compiled from
+    compiled: RwLock<HashMap<String, (PolicySettings, Arc<RuleSet>)>>,
+}
+
+impl ModerationService {
+    pub fn new(settings: SettingsManager) -> Self {
+        Self {
+            settings,
+            compiled: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Check text that `user` (`None` for guests) is saving; passes
+    /// unchanged when the policy doesn't apply
+    pub async fn check(
+        &self,
+        site: &Site,
+        target: ModerationTarget,
+        user: Option<&User>,
+        text: &str,
+    ) -> Result<ModerationResult, ServiceError> {
+        let (settings, rules) = self.rules(site).await?;
+        let applies = settings.enabled
+            && (target == ModerationTarget::Comment || settings.posts)
+            && !user.map_or(false, User::can_moderate);
+        if !applies {
+            return Ok(ModerationResult {
+                action: None,
+                content: text.to_string(),
+                matches: Vec::new(),
+                errors: Vec::new(),
+            });
+        }
+        Ok(rules.check(text))
+    }
+
+    /// Check text against the site's active rules, whoever it's from
+    pub async fn test(&self, site: &Site, text: &str) -> Result<ModerationResult, ServiceError> {
+        let (_, rules) = self.rules(site).await?;
+        Ok(rules.check(text))
+    }
+
+    /// The site's rules, compiled again when its settings changed
+    async fn rules(&self, site: &Site) -> Result<(PolicySettings, Arc<RuleSet>), ServiceError> {
+        let namespace = site.settings_namespace(APP_ID);
+        let settings = self.load(&namespace).await?;
+
+        if let Some((compiled_from, rules)) = self.compiled.read().await.get(&namespace) {
+            if *compiled_from == settings {
+                return Ok((settings, rules.clone()));
+            }
+        }
+
+        let languages: Vec<String> = settings
+            .languages
+            .split(',')
+            .map(|l| l.trim().to_ascii_lowercase())
+            .filter(|l| !l.is_empty())
+            .collect();
+        let rules = Arc::new(RuleSet::compile(&settings.rules, &settings.packs, &languages));
+        for error in &rules.errors {
+            tracing::warn!(site = %site.id, "Skipped content rule: {}", error);
+        }
+        self.compiled
+            .write()
+            .await
+            .insert(namespace, (settings.clone(), rules.clone()));
+        Ok((settings, rules))
+    }
+
+    async fn load(&self, namespace: &str) -> Result<PolicySettings, ServiceError> {
+        Ok(PolicySettings {
+            enabled: self.setting(namespace, "moderation_enabled").await?.unwrap_or(true),
+            posts: self.setting(namespace, "moderation_posts").await?.unwrap_or(false),
+            languages: self
+                .setting(namespace, "moderation_languages")
+                .await?
+                .unwrap_or_else(|| DEFAULT_LANGUAGES.to_string()),
+            rules: self.setting(namespace, "moderation_rules").await?.unwrap_or_default(),
+            packs: self.setting(namespace, "moderation_packs").await?.unwrap_or_default(),
+        })
+    }
+
+    async fn setting<T: DeserializeOwned>(&self, namespace: &str, key: &str) -> Result<Option<T>, ServiceError> {
+        self.settings
+            .get(namespace, key)
+            .await
+            .map_err(|e| ServiceError::Storage(e.to_string()))
+    }
+}
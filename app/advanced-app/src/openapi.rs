@@ -0,0 +1,150 @@
+//! OpenAPI Documentation
+//!
+//! The specification is generated from the `#[utoipa::path]` annotations on
+//! the JSON handlers and the `ToSchema` derives in `models`. The bearer scheme
+//! and Swagger UI page come from the `rustpress-auth` plugin.
+
+use crate::handlers;
+use axum::{routing::get, Json, Router};
+use rustpress_auth::openapi::{swagger_ui, BearerAuth};
+use utoipa::OpenApi;
+
+/// Blog API specification
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "RustPress Blog API"),
+    servers((url = "/api/blog")),
+    paths(
+        handlers::posts::list_posts,
+        handlers::posts::get_post_by_slug,
+        handlers::posts::related_posts,
+        handlers::posts::create_post,
+        handlers::posts::update_post,
+        handlers::posts::delete_post,
+        handlers::posts::list_revisions,
+        handlers::posts::diff_post,
+        handlers::posts::lock_post,
+        handlers::posts::unlock_post,
+        handlers::posts::publish_post,
+        handlers::posts::unpublish_post,
+        handlers::posts::list_drafts,
+        handlers::comments::list_comments,
+        handlers::comments::create_comment,
+        handlers::comments::vote_comment,
+        handlers::comments::approve_comment,
+        handlers::comments::reject_comment,
+        handlers::categories::list_categories,
+        handlers::categories::create_category,
+        handlers::categories::update_category,
+        handlers::categories::delete_category,
+        handlers::tags::list_tags,
+        handlers::tags::create_tag,
+        handlers::tags::update_tag,
+        handlers::tags::delete_tag,
+        handlers::authors::list_authors,
+        handlers::authors::get_author,
+        handlers::search::search_posts,
+        handlers::search::semantic_search,
+        handlers::feed::rss_feed,
+        handlers::sitemap::sitemap,
+        handlers::media::list_media,
+        handlers::media::upload_media,
+        handlers::media::list_alt_text,
+        handlers::media::accept_alt_text,
+        handlers::media::reject_alt_text,
+        handlers::media::backfill_alt_text,
+        handlers::media::delete_media,
+        handlers::media::get_media_url,
+        handlers::media::update_media_visibility,
+        handlers::media::download_media,
+        handlers::media::list_trash,
+        handlers::media::restore_media,
+        handlers::media::media_orphans,
+        handlers::media::scan_media_orphans,
+        handlers::images::transform_image,
+        handlers::admin::list_all_posts,
+        handlers::admin::export_posts,
+        handlers::views::list_views,
+        handlers::views::create_view,
+        handlers::views::update_view,
+        handlers::views::delete_view,
+        handlers::admin::pending_comments,
+        handlers::admin::blog_stats,
+        handlers::admin::list_activity,
+        handlers::admin::test_moderation,
+        handlers::admin::list_auto_approvals,
+        handlers::admin::review_auto_approval,
+        handlers::admin::get_commenter_trust,
+        handlers::admin::set_commenter_trust,
+        handlers::admin::take_over_lock,
+        handlers::export::export_site,
+        handlers::export::download_export,
+        handlers::backups::list_backups,
+        handlers::backups::create_backup,
+        handlers::backups::verify_backup,
+        handlers::sync::import_files,
+        handlers::sync::export_files,
+        handlers::sync::webhook,
+        handlers::widgets::render_sidebar,
+        handlers::widgets::list_widget_types,
+        handlers::widgets::list_sidebars,
+        handlers::widgets::create_widget,
+        handlers::widgets::update_widget,
+        handlers::widgets::delete_widget,
+        handlers::sites::list_sites,
+        handlers::sites::create_site,
+        handlers::sites::update_site,
+        handlers::sites::delete_site,
+        handlers::sites::list_members,
+        handlers::sites::set_member,
+        handlers::sites::remove_member,
+        handlers::notifications::get_preferences,
+        handlers::notifications::update_preferences,
+        handlers::notifications::list_mentions,
+        handlers::notifications::mark_mentions_read,
+        handlers::preferences::get_my_preferences,
+        handlers::preferences::update_my_preferences,
+        handlers::tools::list_tools,
+        handlers::tools::call_tool,
+        handlers::tools::list_tool_keys,
+        handlers::tools::create_tool_key,
+        handlers::tools::revoke_tool_key,
+    ),
+    modifiers(&BearerAuth),
+    tags(
+        (name = "posts", description = "Posts, drafts and the RSS feed"),
+        (name = "comments", description = "Threaded comments and moderation"),
+        (name = "categories", description = "Category management"),
+        (name = "tags", description = "Tag management"),
+        (name = "authors", description = "Public author profiles and archives"),
+        (name = "search", description = "Full-text and semantic search"),
+        (name = "media", description = "Media library"),
+        (name = "admin", description = "Administration"),
+        (name = "widgets", description = "Sidebars and widgets"),
+        (name = "sites", description = "Multisite management and memberships"),
+        (name = "notifications", description = "Comment digest preferences and mentions"),
+        (name = "preferences", description = "Display preferences of the signed-in user"),
+        (name = "tools", description = "Content operations for agents, and their API keys"),
+    )
+)]
+pub struct ApiDoc;
+
+/// Routes serving the specification and Swagger UI (relative to the app's base path)
+pub fn docs_routes() -> Router {
+    Router::new()
+        .route("/openapi.json", get(|| async { Json(ApiDoc::openapi()) }))
+        .route("/docs", get(|| async { swagger_ui("openapi.json") }))
+}
+
+/// Full paths of the documented endpoints (`/api/blog/posts/{slug}`), as
+/// recorded in the shared route registry
+pub fn route_paths() -> Vec<String> {
+    let spec = ApiDoc::openapi();
+    let base = spec
+        .servers
+        .as_ref()
+        .and_then(|servers| servers.first())
+        .map(|server| server.url.clone())
+        .unwrap_or_default();
+    spec.paths.paths.keys().map(|path| format!("{}{}", base, path)).collect()
+}
@@ -0,0 +1,312 @@
+//! Plugin Management
+//!
+//! Lists installed plugins and drives their lifecycle (activate, deactivate,
+//! upgrade) through the host `PluginRegistry`. Each plugin's `plugin.toml` is
+//! read to check dependencies and conflicts and, in dry-run mode, to report
+//! which migrations would run without changing anything. Migrations can also
+//! be inspected and run (up or down) per plugin against the
+//! `plugin_migrations` ledger.
+
+use crate::services::ServiceError;
+use rustpress_apps::prelude::*;
+use rustpress_auth::migrations::{Direction, MigrationError, MigrationStatus, MigrationStep, PluginMigrations};
+use semver::{Version, VersionReq};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Lifecycle operations exposed over the API
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PluginAction {
+    Activate,
+    Deactivate,
+    Upgrade,
+}
+
+/// The parts of plugin.toml the manager needs
+#[derive(Debug, Clone, Deserialize)]
+pub struct PluginManifest {
+    pub plugin: ManifestPlugin,
+    #[serde(default)]
+    pub dependencies: ManifestDependencies,
+    #[serde(default)]
+    pub settings: Option<ManifestSettings>,
+    #[serde(default)]
+    pub migrations: Option<ManifestMigrations>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ManifestPlugin {
+    pub id: String,
+    pub name: String,
+    pub version: String,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ManifestDependencies {
+    #[serde(default)]
+    pub plugins: BTreeMap<String, String>,
+    #[serde(default)]
+    pub conflicts: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ManifestSettings {
+    #[serde(default)]
+    pub schema: BTreeMap<String, toml::Value>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ManifestMigrations {
+    #[serde(default = "default_migrations_dir")]
+    pub directory: String,
+}
+
+fn default_migrations_dir() -> String {
+    "migrations".to_string()
+}
+
+/// Installed plugin as returned by the API
+#[derive(Debug, Clone, Serialize)]
+pub struct PluginSummary {
+    pub info: PluginInfo,
+    pub state: PluginState,
+    pub settings_namespaces: Vec<String>,
+    pub dependencies: BTreeMap<String, String>,
+    pub conflicts: BTreeMap<String, String>,
+}
+
+/// Result of a lifecycle request
+#[derive(Debug, Clone, Serialize)]
+pub struct LifecycleReport {
+    pub plugin: String,
+    pub action: PluginAction,
+    pub dry_run: bool,
+    /// Migrations that would run (dry run) or were pending before the action
+    pub pending_migrations: Vec<String>,
+    pub state: PluginState,
+}
+
+/// Plugin management service
+pub struct PluginManagerService {
+    db: PgPool,
+    registry: Arc<PluginRegistry>,
+    plugins_dir: PathBuf,
+}
+
+impl PluginManagerService {
+    pub fn new(db: PgPool, registry: Arc<PluginRegistry>, plugins_dir: PathBuf) -> Self {
+        Self { db, registry, plugins_dir }
+    }
+
+    /// All installed plugins with their state and settings namespaces
+    pub async fn list(&self) -> Result<Vec<PluginSummary>, ServiceError> {
+        let mut plugins = Vec::new();
+        for installed in self.registry.installed().await {
+            let manifest = self.manifest(&installed.info.id).ok();
+            let (dependencies, conflicts) = manifest
+                .as_ref()
+                .map(|m| (m.dependencies.plugins.clone(), m.dependencies.conflicts.clone()))
+                .unwrap_or_default();
+            let has_settings = manifest
+                .as_ref()
+                .and_then(|m| m.settings.as_ref())
+                .map_or(false, |s| !s.schema.is_empty());
+
+            plugins.push(PluginSummary {
+                settings_namespaces: if has_settings { vec![installed.info.id.clone()] } else { vec![] },
+                info: installed.info,
+                state: installed.state,
+                dependencies,
+                conflicts,
+            });
+        }
+        plugins.sort_by(|a, b| a.info.id.cmp(&b.info.id));
+        Ok(plugins)
+    }
+
+    /// Run (or with `dry_run`, only plan) a lifecycle action
+    pub async fn run(&self, id: &str, action: PluginAction, dry_run: bool) -> Result<LifecycleReport, ServiceError> {
+        let plugins = self.list().await?;
+        let plugin = plugins
+            .iter()
+            .find(|p| p.info.id == id)
+            .ok_or_else(|| ServiceError::NotFound(format!("Plugin not found: {}", id)))?;
+
+        match action {
+            PluginAction::Activate | PluginAction::Upgrade => check_dependencies(plugin, &plugins)?,
+            PluginAction::Deactivate => check_dependents(plugin, &plugins)?,
+        }
+
+        let pending_migrations = match action {
+            PluginAction::Deactivate => Vec::new(),
+            _ => self.pending_migrations(id).await?,
+        };
+
+        if !dry_run {
+            let result = match action {
+                PluginAction::Activate => self.registry.activate(id).await,
+                PluginAction::Deactivate => self.registry.deactivate(id).await,
+                PluginAction::Upgrade => self.registry.upgrade(id).await,
+            };
+            result.map_err(|e| ServiceError::Validation(format!("Plugin {} failed to {:?}: {}", id, action, e)))?;
+            tracing::info!("Plugin {} {:?} completed", id, action);
+        }
+
+        let state = if dry_run {
+            plugin.state.clone()
+        } else {
+            self.registry
+                .state(id)
+                .await
+                .unwrap_or_else(|| plugin.state.clone())
+        };
+
+        Ok(LifecycleReport {
+            plugin: id.to_string(),
+            action,
+            dry_run,
+            pending_migrations,
+            state,
+        })
+    }
+
+    fn manifest(&self, id: &str) -> Result<PluginManifest, ServiceError> {
+        let path = self.plugins_dir.join(id).join("plugin.toml");
+        let raw = std::fs::read_to_string(&path)
+            .map_err(|e| ServiceError::NotFound(format!("{}: {}", path.display(), e)))?;
+        toml::from_str(&raw).map_err(|e| ServiceError::Validation(format!("Invalid plugin.toml for {}: {}", id, e)))
+    }
+
+    /// Migrations declared by the plugin's manifest, `None` if it has none
+    async fn plugin_migrations(&self, id: &str) -> Result<Option<PluginMigrations>, ServiceError> {
+        let manifest = self.manifest(id)?;
+        let Some(migrations) = manifest.migrations else {
+            return Ok(None);
+        };
+
+        let dir = self.plugins_dir.join(id).join(&migrations.directory);
+        Ok(Some(PluginMigrations::from_dir(id, &dir).await?))
+    }
+
+    /// Migrations not yet recorded in the ledger, as `NNN description`
+    async fn pending_migrations(&self, id: &str) -> Result<Vec<String>, ServiceError> {
+        let Some(migrations) = self.plugin_migrations(id).await? else {
+            return Ok(Vec::new());
+        };
+
+        Ok(migrations
+            .status(&self.db)
+            .await?
+            .into_iter()
+            .filter(|m| m.applied_at.is_none())
+            .map(|m| format!("{:03} {}", m.version, m.description))
+            .collect())
+    }
+
+    /// Every migration of a plugin with its ledger state
+    pub async fn migration_status(&self, id: &str) -> Result<Vec<MigrationStatus>, ServiceError> {
+        match self.plugin_migrations(id).await? {
+            Some(migrations) => Ok(migrations.status(&self.db).await?),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Migrate a plugin up to `target` (default: latest) or down to `target`
+    /// (default: the version below the latest applied one, as the CLI does)
+    pub async fn migrate(
+        &self,
+        id: &str,
+        direction: Direction,
+        target: Option<i64>,
+        dry_run: bool,
+    ) -> Result<Vec<MigrationStep>, ServiceError> {
+        let migrations = self
+            .plugin_migrations(id)
+            .await?
+            .ok_or_else(|| ServiceError::NotFound(format!("Plugin {} has no migrations", id)))?;
+
+        let steps = match direction {
+            Direction::Up => migrations.up(&self.db, target, dry_run).await?,
+            Direction::Down => match target {
+                Some(target) => migrations.down(&self.db, target, dry_run).await?,
+                None => migrations.rollback(&self.db, dry_run).await?,
+            },
+        };
+
+        if !dry_run {
+            tracing::info!("Plugin {} migrated {:?}: {} step(s)", id, direction, steps.len());
+        }
+        Ok(steps)
+    }
+}
+
+impl From<MigrationError> for ServiceError {
+    fn from(err: MigrationError) -> Self {
+        match err {
+            MigrationError::Database(e) => ServiceError::Database(e),
+            other => ServiceError::Validation(other.to_string()),
+        }
+    }
+}
+
+/// Ensure required plugins are active at compatible versions and no conflicts are active
+fn check_dependencies(plugin: &PluginSummary, all: &[PluginSummary]) -> Result<(), ServiceError> {
+    for (dep_id, requirement) in &plugin.dependencies {
+        let dep = all
+            .iter()
+            .find(|p| &p.info.id == dep_id)
+            .ok_or_else(|| ServiceError::Validation(format!("Missing dependency: {} {}", dep_id, requirement)))?;
+
+        if dep.state != PluginState::Active {
+            return Err(ServiceError::Validation(format!("Dependency {} is not active", dep_id)));
+        }
+        if !version_matches(&dep.info.version, requirement) {
+            return Err(ServiceError::Validation(format!(
+                "Dependency {} {} does not satisfy {}",
+                dep_id, dep.info.version, requirement
+            )));
+        }
+    }
+
+    for (conflict_id, requirement) in &plugin.conflicts {
+        if let Some(other) = all.iter().find(|p| &p.info.id == conflict_id && p.state == PluginState::Active) {
+            if version_matches(&other.info.version, requirement) {
+                return Err(ServiceError::Validation(format!(
+                    "Conflicts with active plugin {} {}",
+                    conflict_id, other.info.version
+                )));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Refuse to deactivate a plugin that active plugins depend on
+fn check_dependents(plugin: &PluginSummary, all: &[PluginSummary]) -> Result<(), ServiceError> {
+    let dependents: Vec<&str> = all
+        .iter()
+        .filter(|p| p.state == PluginState::Active && p.dependencies.contains_key(&plugin.info.id))
+        .map(|p| p.info.id.as_str())
+        .collect();
+
+    if dependents.is_empty() {
+        Ok(())
+    } else {
+        Err(ServiceError::Validation(format!(
+            "Required by active plugins: {}",
+            dependents.join(", ")
+        )))
+    }
+}
+
+fn version_matches(version: &str, requirement: &str) -> bool {
+    match (Version::parse(version), VersionReq::parse(requirement)) {
+        (Ok(version), Ok(requirement)) => requirement.matches(&version),
+        _ => false,
+    }
+}
@@ -0,0 +1,159 @@
+//! Authorization Policies
+//!
+//! Who may change a post or a media file is decided by rules in the auth
+//! plugin's policy engine (see `rustpress_auth::policy`), defined on
+//! activation from [`DEFAULTS`]. A deployment can override any of them:
+//!
+//! ```toml
+//! [policies.posts]
+//! delete = "owner || role == admin"
+//! ```
+//!
+//! Handlers take `Authorize<UpdatePost>` and friends, which load the post or
+//! media item for the `:id` path parameter and hand it over once allowed.
+//! `role` in a rule is the user's role on the current site (see
+//! `SiteService::role`), not the role on their account.
+
+use crate::models::{Media, Post};
+use crate::services::ServiceError;
+use crate::sites::CurrentSite;
+use crate::BlogServices;
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Path},
+    http::request::Parts,
+    response::{IntoResponse, Response},
+};
+use rustpress_auth::extractors::AuthUser;
+use rustpress_auth::policy::{Policy, Resource, ResourceLoader};
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Policies and their rules unless `policies.<name>` overrides them
+pub const DEFAULTS: &[(&str, &str)] = &[
+    (UpdatePost::NAME, "owner || role in [editor, admin]"),
+    (DeletePost::NAME, "owner || role in [editor, admin]"),
+    (DeleteMedia::NAME, "owner"),
+    (ReviewAltText::NAME, "owner || role in [editor, admin]"),
+];
+
+/// Editing a post
+pub struct UpdatePost;
+
+impl Policy for UpdatePost {
+    const NAME: &'static str = "posts.update";
+    type Resource = Post;
+}
+
+/// Deleting a post
+pub struct DeletePost;
+
+impl Policy for DeletePost {
+    const NAME: &'static str = "posts.delete";
+    type Resource = Post;
+}
+
+/// Deleting a media file
+pub struct DeleteMedia;
+
+impl Policy for DeleteMedia {
+    const NAME: &'static str = "media.delete";
+    type Resource = Media;
+}
+
+/// Accepting or rejecting the alt text suggested for an image
+pub struct ReviewAltText;
+
+impl Policy for ReviewAltText {
+    const NAME: &'static str = "media.review_alt_text";
+    type Resource = Media;
+}
+
+/// `resource.status` and `resource.required_level` are available to rules
+impl Resource for Post {
+    fn owner_id(&self) -> Option<Uuid> {
+        Some(self.author_id)
+    }
+
+    fn attribute(&self, name: &str) -> Option<String> {
+        match name {
+            "status" => serde_json::to_value(&self.status)
+                .ok()
+                .and_then(|v| v.as_str().map(String::from)),
+            "required_level" => self.required_level.map(|level| level.to_string()),
+            _ => None,
+        }
+    }
+}
+
+/// `resource.visibility` and `resource.mime_type` are available to rules
+impl Resource for Media {
+    fn owner_id(&self) -> Option<Uuid> {
+        Some(self.uploader_id)
+    }
+
+    fn attribute(&self, name: &str) -> Option<String> {
+        match name {
+            "visibility" => serde_json::to_value(self.visibility)
+                .ok()
+                .and_then(|v| v.as_str().map(String::from)),
+            "mime_type" => Some(self.mime_type.clone()),
+            _ => None,
+        }
+    }
+}
+
+/// The current site's post with the `:id` in the path
+#[async_trait]
+impl ResourceLoader<Arc<BlogServices>> for Post {
+    async fn load(parts: &mut Parts, services: &Arc<BlogServices>) -> Result<Self, Response> {
+        let (site, id) = site_and_id(parts, services).await?;
+        services
+            .posts
+            .get_by_id(&site.0, id)
+            .await
+            .map_err(ServiceError::into_response)
+    }
+
+    async fn role(parts: &mut Parts, services: &Arc<BlogServices>, user: &AuthUser) -> Result<Option<String>, Response> {
+        site_role(parts, services, user).await
+    }
+}
+
+/// The current site's media item with the `:id` in the path
+#[async_trait]
+impl ResourceLoader<Arc<BlogServices>> for Media {
+    async fn load(parts: &mut Parts, services: &Arc<BlogServices>) -> Result<Self, Response> {
+        let (site, id) = site_and_id(parts, services).await?;
+        services
+            .media
+            .find(&site.0, id)
+            .await
+            .map_err(ServiceError::into_response)
+    }
+
+    async fn role(parts: &mut Parts, services: &Arc<BlogServices>, user: &AuthUser) -> Result<Option<String>, Response> {
+        site_role(parts, services, user).await
+    }
+}
+
+async fn site_and_id(parts: &mut Parts, services: &Arc<BlogServices>) -> Result<(CurrentSite, Uuid), Response> {
+    let site = CurrentSite::from_request_parts(parts, services)
+        .await
+        .map_err(IntoResponse::into_response)?;
+    let Path(id) = Path::<Uuid>::from_request_parts(parts, services)
+        .await
+        .map_err(IntoResponse::into_response)?;
+    Ok((site, id))
+}
+
+async fn site_role(parts: &mut Parts, services: &Arc<BlogServices>, user: &AuthUser) -> Result<Option<String>, Response> {
+    let site = CurrentSite::from_request_parts(parts, services)
+        .await
+        .map_err(IntoResponse::into_response)?;
+    services
+        .sites
+        .role(&site.0, user.id, &user.role)
+        .await
+        .map_err(ServiceError::into_response)
+}
@@ -0,0 +1,201 @@
+//! User Preferences
+//!
+//! Display preferences of signed-in users, such as `color_scheme`, kept as
+//! key-value pairs per user. Every key has a default: the app's
+//! (`[app.preferences.defaults]`), overridden by the site's `preferences`
+//! config. Users only choose among keys with a default, and a choice takes the
+//! default's type. Pages render with the reader's preferences, which themes
+//! get as `preferences` and `body_class` filters as the `preferences` filter
+//! argument.
+//!
+//! `timezone` is always a key: the site's `timezone` is its default, and
+//! choices must name an IANA zone (see `timezones`).
+
+use crate::models::*;
+use crate::services::ServiceError;
+use crate::sites::Site;
+use crate::timezones;
+use chrono_tz::Tz;
+use serde::Deserialize;
+use serde_json::Value;
+use sqlx::PgPool;
+use std::collections::BTreeMap;
+use uuid::Uuid;
+
+/// Values `color_scheme` may take
+pub const COLOR_SCHEMES: &[&str] = &["light", "dark", "auto"];
+
+/// Longest string a preference may hold
+const MAX_VALUE_LENGTH: usize = 200;
+
+/// `[app.preferences]` settings
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct PreferenceConfig {
+    /// Defaults of sites that don't override them
+    pub defaults: BTreeMap<String, Value>,
+}
+
+impl Default for PreferenceConfig {
+    fn default() -> Self {
+        Self {
+            defaults: BTreeMap::from([
+                ("color_scheme".to_string(), Value::from("auto")),
+                ("timezone".to_string(), Value::from("UTC")),
+            ]),
+        }
+    }
+}
+
+pub struct PreferenceService {
+    db: PgPool,
+    config: PreferenceConfig,
+}
+
+impl PreferenceService {
+    pub fn new(db: PgPool, config: PreferenceConfig) -> Self {
+        Self { db, config }
+    }
+
+    /// The site's defaults
+    pub fn defaults(&self, site: &Site) -> BTreeMap<String, Value> {
+        let mut defaults = self.config.defaults.clone();
+        defaults.extend(site.config.preferences.clone());
+        if let Some(timezone) = &site.config.timezone {
+            defaults.insert("timezone".to_string(), Value::from(timezone.as_str()));
+        }
+        defaults.entry("timezone".to_string()).or_insert_with(|| Value::from("UTC"));
+        defaults
+    }
+
+    /// A user's preferences on the site
+    pub async fn get(&self, site: &Site, user_id: Uuid) -> Result<UserPreferences, ServiceError> {
+        let mut preferences = self.defaults(site);
+        let chosen: Vec<(String, Value)> =
+            sqlx::query_as("SELECT key, value FROM blog_user_preferences WHERE user_id = $1 ORDER BY key")
+                .bind(user_id)
+                .fetch_all(&self.db)
+                .await?;
+
+        // Choices for keys the site no longer has are kept, but not applied
+        let mut customized = Vec::new();
+        for (key, value) in chosen {
+            if let Some(default) = preferences.get_mut(&key) {
+                if same_type(default, &value) {
+                    *default = value;
+                    customized.push(key);
+                }
+            }
+        }
+        Ok(UserPreferences { preferences, customized })
+    }
+
+    /// What a page renders with: the reader's preferences, or the site's
+    /// defaults for visitors
+    pub async fn resolve(&self, site: &Site, user_id: Option<Uuid>) -> Result<BTreeMap<String, Value>, ServiceError> {
+        match user_id {
+            Some(user_id) => Ok(self.get(site, user_id).await?.preferences),
+            None => Ok(self.defaults(site)),
+        }
+    }
+
+    /// The time zone times are read and shown in for a user, or the site's
+    /// for visitors
+    pub async fn timezone(&self, site: &Site, user_id: Option<Uuid>) -> Result<Tz, ServiceError> {
+        let preferences = self.resolve(site, user_id).await?;
+        Ok(preferences
+            .get("timezone")
+            .and_then(Value::as_str)
+            .and_then(|name| timezones::parse_timezone(name).ok())
+            .unwrap_or(Tz::UTC))
+    }
+
+    /// Change some preferences; `null` goes back to the default
+    pub async fn update(
+        &self,
+        site: &Site,
+        user_id: Uuid,
+        changes: &BTreeMap<String, Value>,
+    ) -> Result<UserPreferences, ServiceError> {
+        let defaults = self.defaults(site);
+        let mut errors = Vec::new();
+        for (key, value) in changes {
+            if let Err((code, message)) = validate(&defaults, key, value) {
+                errors.push(FieldError {
+                    field: format!("preferences.{}", key),
+                    code: code.to_string(),
+                    message,
+                    rejected_value: Some(value.clone()),
+                });
+            }
+        }
+        if !errors.is_empty() {
+            return Err(ServiceError::InvalidFields(errors));
+        }
+
+        let mut tx = self.db.begin().await?;
+        for (key, value) in changes {
+            if value.is_null() {
+                sqlx::query("DELETE FROM blog_user_preferences WHERE user_id = $1 AND key = $2")
+                    .bind(user_id)
+                    .bind(key)
+                    .execute(&mut *tx)
+                    .await?;
+            } else {
+                sqlx::query(
+                    "INSERT INTO blog_user_preferences (user_id, key, value) VALUES ($1, $2, $3)
+                     ON CONFLICT (user_id, key) DO UPDATE SET value = $3, updated_at = NOW()",
+                )
+                .bind(user_id)
+                .bind(key)
+                .bind(value)
+                .execute(&mut *tx)
+                .await?;
+            }
+        }
+        tx.commit().await?;
+
+        self.get(site, user_id).await
+    }
+}
+
+/// Check a choice against the site's defaults; errors are a validation code
+/// and message
+fn validate(defaults: &BTreeMap<String, Value>, key: &str, value: &Value) -> Result<(), (&'static str, String)> {
+    let Some(default) = defaults.get(key) else {
+        return Err(("unknown", "Unknown preference".to_string()));
+    };
+    if value.is_null() {
+        return Ok(());
+    }
+    if !same_type(default, value) {
+        return Err(("type", format!("Must be a {}", type_name(default))));
+    }
+    if value.as_str().is_some_and(|s| s.len() > MAX_VALUE_LENGTH) {
+        return Err(("length", format!("Must be at most {} characters", MAX_VALUE_LENGTH)));
+    }
+    if key == "color_scheme" && !value.as_str().is_some_and(|s| COLOR_SCHEMES.contains(&s)) {
+        return Err(("one_of", format!("Must be one of: {}", COLOR_SCHEMES.join(", "))));
+    }
+    if key == "timezone" {
+        if let Some(name) = value.as_str() {
+            timezones::parse_timezone(name).map_err(|message| ("timezone", message))?;
+        }
+    }
+    Ok(())
+}
+
+fn same_type(a: &Value, b: &Value) -> bool {
+    std::mem::discriminant(a) == std::mem::discriminant(b)
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "list",
+        Value::Object(_) => "object",
+    }
+}
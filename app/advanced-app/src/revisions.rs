@@ -0,0 +1,125 @@
+//! Post Revisions
+//!
+//! Each time a post is saved with different text (title, slug, content,
+//! excerpt, featured image or meta fields), a numbered revision records the
+//! new version and who saved it. `GET /posts/:id/revisions` lists them and
+//! `GET /posts/:id/diff?from=1&to=3` compares two (see [`crate::diff`]).
+
+use crate::activity::{ContentEvent, ContentListener};
+use crate::diff;
+use crate::models::*;
+use crate::services::ServiceError;
+use crate::sites::Site;
+use axum::async_trait;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+pub struct RevisionService {
+    db: PgPool,
+}
+
+impl RevisionService {
+    pub fn new(db: PgPool) -> Self {
+        Self { db }
+    }
+
+    /// Record the post's current text as a new revision, unless it matches
+    /// the latest one
+    pub async fn record(&self, post_id: Uuid, author_id: Option<Uuid>) -> Result<(), ServiceError> {
+        sqlx::query(
+            "INSERT INTO blog_post_revisions
+                 (site_id, post_id, number, author_id, title, slug, content, excerpt, featured_image, meta_title, meta_description)
+             SELECT p.site_id, p.id, COALESCE(latest.number, 0) + 1, $2, p.title, p.slug, p.content, p.excerpt,
+                    p.featured_image, p.meta_title, p.meta_description
+             FROM blog_posts p
+             LEFT JOIN LATERAL (
+                 SELECT * FROM blog_post_revisions r WHERE r.post_id = p.id ORDER BY r.number DESC LIMIT 1
+             ) latest ON TRUE
+             WHERE p.id = $1
+               AND (latest.number IS NULL
+                    OR (latest.title, latest.slug, latest.content, latest.excerpt, latest.featured_image,
+                        latest.meta_title, latest.meta_description)
+                       IS DISTINCT FROM
+                       (p.title, p.slug, p.content, p.excerpt, p.featured_image, p.meta_title, p.meta_description))
+             ON CONFLICT (post_id, number) DO NOTHING",
+        )
+        .bind(post_id)
+        .bind(author_id)
+        .execute(&self.db)
+        .await?;
+        Ok(())
+    }
+
+    /// A post's revisions, newest first
+    pub async fn list(&self, site: &Site, post_id: Uuid) -> Result<Vec<RevisionSummary>, ServiceError> {
+        let revisions = sqlx::query_as(
+            "SELECT r.number, r.author_id, u.name AS author_name, r.title, r.created_at
+             FROM blog_post_revisions r
+             LEFT JOIN users u ON u.id = r.author_id
+             WHERE r.post_id = $1 AND r.site_id = $2
+             ORDER BY r.number DESC",
+        )
+        .bind(post_id)
+        .bind(site.id)
+        .fetch_all(&self.db)
+        .await?;
+        Ok(revisions)
+    }
+
+    /// One revision of a post
+    pub async fn get(&self, site: &Site, post_id: Uuid, number: i32) -> Result<PostRevision, ServiceError> {
+        sqlx::query_as(
+            "SELECT r.post_id, r.number, r.author_id, u.name AS author_name, r.title, r.slug, r.content, r.excerpt,
+                    r.featured_image, r.meta_title, r.meta_description, r.created_at
+             FROM blog_post_revisions r
+             LEFT JOIN users u ON u.id = r.author_id
+             WHERE r.post_id = $1 AND r.site_id = $2 AND r.number = $3",
+        )
+        .bind(post_id)
+        .bind(site.id)
+        .bind(number)
+        .fetch_optional(&self.db)
+        .await?
+        .ok_or_else(|| ServiceError::NotFound(format!("Revision not found: {}", number)))
+    }
+
+    /// Compare two revisions; `to` defaults to the latest and `from` to the
+    /// one before `to`
+    pub async fn diff(&self, site: &Site, post_id: Uuid, query: &DiffQuery) -> Result<PostDiff, ServiceError> {
+        let to = match query.to {
+            Some(to) => to,
+            None => sqlx::query_scalar("SELECT MAX(number) FROM blog_post_revisions WHERE post_id = $1 AND site_id = $2")
+                .bind(post_id)
+                .bind(site.id)
+                .fetch_one(&self.db)
+                .await?
+                .ok_or_else(|| ServiceError::NotFound("The post has no revisions".into()))?,
+        };
+        let from = query.from.unwrap_or(to - 1);
+        if from >= to {
+            return Err(ServiceError::Validation("`from` must be an earlier revision than `to`".into()));
+        }
+
+        let (old, new) = (self.get(site, post_id, from).await?, self.get(site, post_id, to).await?);
+        Ok(PostDiff {
+            post_id,
+            from,
+            to,
+            fields: diff::diff_revisions(&old, &new),
+        })
+    }
+}
+
+#[async_trait]
+impl ContentListener for RevisionService {
+    async fn on_change(&self, event: &ContentEvent) {
+        if event.object_type != ContentObject::Post
+            || !matches!(event.action, ContentAction::Created | ContentAction::Updated)
+        {
+            return;
+        }
+        if let Err(e) = self.record(event.object_id, event.actor_id).await {
+            tracing::warn!(post_id = %event.object_id, "Failed to record revision: {}", e);
+        }
+    }
+}
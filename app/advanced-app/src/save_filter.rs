@@ -0,0 +1,202 @@
+//! Post Save Filter
+//!
+//! Before a post is written, its text passes through the `post_before_save`
+//! filter as a [`PostDraft`], so a plugin can fill in what the author left
+//! out (see `plugin/assistant-plugin`). The draft is handed over as JSON, so
+//! plugins don't need to link the blog. What comes back is applied as:
+//!
+//! - `content`, `excerpt` and `meta_description`: saved when changed, with
+//!   excerpts and meta descriptions cut to the lengths requests allow
+//! - `suggested_tags` / `suggested_categories`: names of the site's existing
+//!   tags and categories, attached to posts created without any; names that
+//!   match nothing are ignored, so the filter never creates terms
+//!
+//! A failing filter doesn't stop the save: the post is saved as submitted
+//! and the error logged. Without any filter the draft comes back unchanged.
+
+use crate::models::{CreatePostRequest, Post, UpdatePostRequest};
+use crate::services::ServiceError;
+use crate::sites::Site;
+use rustpress_apps::prelude::*;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Filter applied to a post's fields before it's saved
+pub const SAVE_FILTER: &str = "post_before_save";
+
+/// Suggested tags or categories attached at most
+pub const MAX_SUGGESTED_TERMS: usize = 5;
+
+/// Longest excerpt and meta description kept, as in the post requests
+const EXCERPT_LENGTH: usize = 500;
+const META_DESCRIPTION_LENGTH: usize = 160;
+
+/// The fields of a post being saved, as passed through [`SAVE_FILTER`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PostDraft {
+    pub site_id: Uuid,
+    /// `None` for a post being created
+    pub post_id: Option<Uuid>,
+    pub author_id: Uuid,
+    pub title: String,
+    pub content: String,
+    pub excerpt: Option<String>,
+    pub meta_description: Option<String>,
+    /// Whether tags or categories are being set; suggestions are only
+    /// applied to new posts without either
+    pub has_terms: bool,
+    #[serde(default)]
+    pub suggested_tags: Vec<String>,
+    #[serde(default)]
+    pub suggested_categories: Vec<String>,
+}
+
+pub struct SaveFilter {
+    db: PgPool,
+    hooks: Arc<HookRegistry>,
+}
+
+impl SaveFilter {
+    pub fn new(db: PgPool, hooks: Arc<HookRegistry>) -> Self {
+        Self { db, hooks }
+    }
+
+    /// Run `draft` through [`SAVE_FILTER`]; the draft as given when the
+    /// filter fails or returns something else
+    pub async fn apply(&self, draft: PostDraft) -> PostDraft {
+        let value = match serde_json::to_value(&draft) {
+            Ok(value) => value,
+            Err(e) => {
+                tracing::warn!("Post draft not filtered: {}", e);
+                return draft;
+            }
+        };
+
+        let filtered = self
+            .hooks
+            .apply_filters(SAVE_FILTER, &crate::hooks::filter_context(), value)
+            .await
+            .map_err(|e| e.to_string())
+            .and_then(|value| serde_json::from_value::<PostDraft>(value).map_err(|e| e.to_string()));
+
+        match filtered {
+            // The filter can't move the draft to another post
+            Ok(filtered) if filtered.site_id == draft.site_id && filtered.post_id == draft.post_id => filtered,
+            Ok(_) => {
+                tracing::warn!("Ignoring {} result for another post", SAVE_FILTER);
+                draft
+            }
+            Err(e) => {
+                tracing::warn!(post_id = ?draft.post_id, "{} failed, saving the post as submitted: {}", SAVE_FILTER, e);
+                draft
+            }
+        }
+    }
+
+    /// Filter a post about to be created
+    pub async fn create(&self, site: &Site, author_id: Uuid, req: &mut CreatePostRequest) -> Result<(), ServiceError> {
+        let has_terms = req.category_ids.as_ref().is_some_and(|ids| !ids.is_empty())
+            || req.tag_ids.as_ref().is_some_and(|ids| !ids.is_empty());
+        let draft = PostDraft {
+            site_id: site.id,
+            post_id: None,
+            author_id,
+            title: req.title.clone(),
+            content: req.content.clone(),
+            excerpt: req.excerpt.clone(),
+            meta_description: req.meta_description.clone(),
+            has_terms,
+            suggested_tags: Vec::new(),
+            suggested_categories: Vec::new(),
+        };
+
+        let filtered = self.apply(draft.clone()).await;
+        if filtered == draft {
+            return Ok(());
+        }
+
+        req.content = filtered.content;
+        req.excerpt = filtered.excerpt.map(|e| truncate(e, EXCERPT_LENGTH));
+        req.meta_description = filtered.meta_description.map(|d| truncate(d, META_DESCRIPTION_LENGTH));
+
+        if !has_terms {
+            let tags = self.term_ids(site, "blog_tags", &filtered.suggested_tags).await?;
+            if !tags.is_empty() {
+                req.tag_ids = Some(tags);
+            }
+            let categories = self.term_ids(site, "blog_categories", &filtered.suggested_categories).await?;
+            if !categories.is_empty() {
+                req.category_ids = Some(categories);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Filter the changes to `existing`; fields the request leaves out are
+    /// passed as saved, and only changed ones are written back
+    pub async fn update(&self, site: &Site, existing: &Post, req: &mut UpdatePostRequest) -> Result<(), ServiceError> {
+        let draft = PostDraft {
+            site_id: site.id,
+            post_id: Some(existing.id),
+            author_id: existing.author_id,
+            title: req.title.clone().unwrap_or_else(|| existing.title.clone()),
+            content: req.content.clone().unwrap_or_else(|| existing.content.clone()),
+            excerpt: req.excerpt.clone().or_else(|| existing.excerpt.clone()),
+            meta_description: req.meta_description.clone().or_else(|| existing.meta_description.clone()),
+            has_terms: true,
+            suggested_tags: Vec::new(),
+            suggested_categories: Vec::new(),
+        };
+
+        let filtered = self.apply(draft.clone()).await;
+        if filtered.content != draft.content {
+            req.content = Some(filtered.content);
+        }
+        if filtered.excerpt != draft.excerpt {
+            req.excerpt = filtered.excerpt.map(|e| truncate(e, EXCERPT_LENGTH));
+        }
+        if filtered.meta_description != draft.meta_description {
+            req.meta_description = filtered.meta_description.map(|d| truncate(d, META_DESCRIPTION_LENGTH));
+        }
+
+        Ok(())
+    }
+
+    /// IDs of the site's terms in `table` named or slugged as in `names`
+    async fn term_ids(&self, site: &Site, table: &str, names: &[String]) -> Result<Vec<Uuid>, ServiceError> {
+        let names: Vec<String> = names
+            .iter()
+            .map(|name| name.trim())
+            .filter(|name| !name.is_empty())
+            .take(MAX_SUGGESTED_TERMS)
+            .map(str::to_string)
+            .collect();
+        if names.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let lowered: Vec<String> = names.iter().map(|name| name.to_lowercase()).collect();
+        let slugs: Vec<String> = names.iter().map(slug::slugify).collect();
+        let ids = sqlx::query_scalar(&format!(
+            "SELECT id FROM {} WHERE site_id = $1 AND (lower(name) = ANY($2) OR slug = ANY($3)) ORDER BY name",
+            table
+        ))
+        .bind(site.id)
+        .bind(&lowered)
+        .bind(&slugs)
+        .fetch_all(&self.db)
+        .await?;
+        Ok(ids)
+    }
+}
+
+/// `text` cut to at most `max` characters
+fn truncate(text: String, max: usize) -> String {
+    match text.char_indices().nth(max) {
+        Some((end, _)) => text[..end].trim_end().to_string(),
+        None => text,
+    }
+}
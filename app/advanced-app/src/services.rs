@@ -1,8 +1,20 @@
 //! Blog Services
 
+use crate::access::{ContentAccess, Viewer};
+use crate::activity::{self, ContentEvent, ContentHooks};
 use crate::models::*;
+use crate::excerpts;
+use crate::sites::Site;
+use crate::cache::Cache;
+use crate::extractors::{ClientInfo, User};
+use crate::mentions::MentionService;
+use crate::signed_urls::{MediaConfig, SignedUrl, UrlSigner};
+use crate::votes::Voter;
+use chrono::{DateTime, Utc};
 use rustpress_apps::prelude::*;
-use sqlx::PgPool;
+use rustpress_auth::db::{DbPool, DbPools};
+use sqlx::{FromRow, PgConnection, PgPool, Postgres, QueryBuilder};
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use uuid::Uuid;
 
@@ -18,47 +30,140 @@ pub enum ServiceError {
     #[error("Validation error: {0}")]
     Validation(String),
 
+    #[error("Validation failed")]
+    InvalidFields(Vec<FieldError>),
+
     #[error("Permission denied")]
     PermissionDenied,
 
     #[error("Storage error: {0}")]
     Storage(String),
+
+    #[error("Template error: {0}")]
+    Template(String),
+
+    #[error("Rate limited: {0}")]
+    RateLimited(String),
+
+    #[error("Unavailable: {0}")]
+    Unavailable(String),
+
+    #[error("Locked by {}", .0.user_name)]
+    Locked(EditLock),
+}
+
+impl From<validator::ValidationErrors> for ServiceError {
+    fn from(errors: validator::ValidationErrors) -> Self {
+        ServiceError::InvalidFields(rustpress_auth::problem::field_errors(&errors))
+    }
 }
 
 /// Post service
+///
+/// Public listings and permalinks read from replicas; everything else
+/// (including admin reads that must see their own writes) hits the primary.
+/// For the replicas' lag budget after a write, cache refills read from the
+/// primary too, so a row the replicas haven't caught up on isn't cached.
 pub struct PostService {
-    db: PgPool,
+    db: Arc<DbPools>,
     cache: Arc<dyn Cache>,
+    hooks: Arc<ContentHooks>,
+    access: Arc<ContentAccess>,
+    mentions: Arc<MentionService>,
+    /// Length of generated excerpts, in characters
+    excerpt_length: usize,
 }
 
+/// Cache key (under the site's prefix) marking a recent post write
+const POSTS_WRITTEN_KEY: &str = "posts-written";
+
+/// Post fields compared for the activity log
+const POST_DIFF_FIELDS: &[&str] = &[
+    "title",
+    "slug",
+    "content",
+    "excerpt",
+    "featured_image",
+    "meta_title",
+    "meta_description",
+];
+
 impl PostService {
-    pub fn new(db: PgPool, cache: Arc<dyn Cache>) -> Self {
-        Self { db, cache }
+    pub fn new(
+        db: Arc<DbPools>,
+        cache: Arc<dyn Cache>,
+        hooks: Arc<ContentHooks>,
+        access: Arc<ContentAccess>,
+        mentions: Arc<MentionService>,
+        excerpt_length: usize,
+    ) -> Self {
+        Self { db, cache, hooks, access, mentions, excerpt_length }
     }
 
-    /// List published posts with pagination
-    pub async fn list_published(&self, query: &PostQuery) -> Result<PaginatedResponse<PostWithRelations>, ServiceError> {
-        let cache_key = format!("posts:list:{:?}", query);
+    /// Drop the site's cached post reads after a write
+    async fn invalidate(&self, site: &Site) {
+        let staleness = self.db.staleness();
+        if !staleness.is_zero() {
+            let ttl = staleness.as_secs() + 1;
+            self.cache.set(&site.cache_key(POSTS_WRITTEN_KEY), &true, Some(ttl)).await;
+        }
+        self.cache.delete_pattern(&site.cache_key("posts:*")).await;
+    }
 
-        // Try cache first
-        if let Some(cached) = self.cache.get::<PaginatedResponse<PostWithRelations>>(&cache_key).await {
-            return Ok(cached);
+    /// Pool to refill the site's cached reads from: the primary while a write
+    /// may not have reached the replicas
+    async fn refill_pool(&self, site: &Site) -> &DbPool {
+        if self.cache.get::<bool>(&site.cache_key(POSTS_WRITTEN_KEY)).await.is_some() {
+            self.db.write()
+        } else {
+            self.db.read()
         }
+    }
 
+    async fn emit(&self, site: &Site, actor: Option<Uuid>, post: &Post, action: ContentAction, changes: Option<serde_json::Value>) {
+        let event = ContentEvent::new(site, actor, ContentObject::Post, post.id, action)
+            .label(post.title.clone())
+            .changes(changes);
+        self.hooks.emit(event).await;
+    }
+
+    /// List published posts with pagination
+    ///
+    /// Members-only posts `viewer` can't read come back as teasers; the
+    /// others with their mentions linked.
+    pub async fn list_published(
+        &self,
+        site: &Site,
+        query: &PostQuery,
+        viewer: &Viewer,
+    ) -> Result<PaginatedResponse<PostWithRelations>, ServiceError> {
+        let mut query = query.clone();
+        query.per_page = query.per_page.or(site.config.posts_per_page);
+        let cache_key = site.cache_key(&format!("posts:list:{:?}", query));
+
+        // Cache for 5 minutes
+        let mut posts = self
+            .cache
+            .get_or_load(&cache_key, Some(300), || self.load_published(site, &query))
+            .await?;
+
+        self.access.gate(viewer, &mut posts.data).await?;
+        self.mentions.link(site, &mut posts.data).await?;
+        Ok(posts)
+    }
+
+    async fn load_published(&self, site: &Site, query: &PostQuery) -> Result<PaginatedResponse<PostWithRelations>, ServiceError> {
+        let db = self.refill_pool(site).await;
         let mut sql = String::from(
             "SELECT p.*,
                     json_build_object('id', u.id, 'name', u.name, 'avatar', u.avatar, 'bio', u.bio) as author
              FROM blog_posts p
              JOIN users u ON u.id = p.author_id
-             WHERE p.status = 'published'"
+             WHERE p.status = 'published' AND p.site_id = $3"
         );
 
         // Apply filters
-        if let Some(ref category) = query.category {
-            sql.push_str(" AND EXISTS (SELECT 1 FROM blog_post_categories pc
-                          JOIN blog_categories c ON c.id = pc.category_id
-                          WHERE pc.post_id = p.id AND c.slug = $3)");
-        }
+        sql.push_str(&published_filters(query, 4));
 
         // Sort
         let order = query.order.as_deref().unwrap_or("desc");
@@ -70,80 +175,115 @@ impl PostService {
 
         sql.push_str(" LIMIT $1 OFFSET $2");
 
-        let posts: Vec<Post> = sqlx::query_as(&sql)
+        let mut posts_query = sqlx::query_as::<_, Post>(&sql)
             .bind(query.per_page())
             .bind(query.offset())
-            .fetch_all(&self.db)
-            .await?;
+            .bind(site.id);
+        if let Some(ref category) = query.category {
+            posts_query = posts_query.bind(category);
+        }
+        if let Some(ref tag) = query.tag {
+            posts_query = posts_query.bind(tag);
+        }
+        if let Some(author) = query.author {
+            posts_query = posts_query.bind(author);
+        }
+        let posts: Vec<Post> = posts_query.fetch_all(db).await?;
 
         // Get total count
-        let total: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM blog_posts WHERE status = 'published'")
-            .fetch_one(&self.db)
-            .await?;
+        let count_sql = format!(
+            "SELECT COUNT(*) FROM blog_posts p WHERE p.status = 'published' AND p.site_id = $1{}",
+            published_filters(query, 2)
+        );
+        let mut count_query = sqlx::query_scalar(&count_sql).bind(site.id);
+        if let Some(ref category) = query.category {
+            count_query = count_query.bind(category);
+        }
+        if let Some(ref tag) = query.tag {
+            count_query = count_query.bind(tag);
+        }
+        if let Some(author) = query.author {
+            count_query = count_query.bind(author);
+        }
+        let total: i64 = count_query.fetch_one(db).await?;
 
         // Fetch relations for each post
         let mut posts_with_relations = Vec::new();
         for post in posts {
-            let relations = self.get_post_relations(&post).await?;
+            let relations = self.get_post_relations(db, &post).await?;
             posts_with_relations.push(relations);
         }
 
-        let response = PaginatedResponse {
+        Ok(PaginatedResponse {
             data: posts_with_relations,
             pagination: PaginationMeta::new(total, query.page(), query.per_page()),
-        };
-
-        // Cache for 5 minutes
-        self.cache.set(&cache_key, &response, Some(300)).await;
-
-        Ok(response)
+        })
     }
 
-    /// Get a post by slug
-    pub async fn get_by_slug(&self, slug: &str) -> Result<PostWithRelations, ServiceError> {
-        let cache_key = format!("posts:slug:{}", slug);
+    /// Get a post by slug, as a teaser if `viewer` can't read all of it
+    pub async fn get_by_slug(&self, site: &Site, slug: &str, viewer: &Viewer) -> Result<PostWithRelations, ServiceError> {
+        let cache_key = site.cache_key(&format!("posts:slug:{}", slug));
 
-        if let Some(cached) = self.cache.get::<PostWithRelations>(&cache_key).await {
-            return Ok(cached);
-        }
+        // Hot permalinks: concurrent misses share one load
+        let post = self
+            .cache
+            .get_or_load(&cache_key, Some(600), || self.load_by_slug(site, slug))
+            .await?;
 
+        let mut posts = [post];
+        self.access.gate(viewer, &mut posts).await?;
+        self.mentions.link(site, &mut posts).await?;
+        let [post] = posts;
+        Ok(post)
+    }
+
+    async fn load_by_slug(&self, site: &Site, slug: &str) -> Result<PostWithRelations, ServiceError> {
+        let db = self.refill_pool(site).await;
         let post: Post = sqlx::query_as(
-            "SELECT * FROM blog_posts WHERE slug = $1 AND status = 'published'"
+            "SELECT * FROM blog_posts WHERE slug = $1 AND site_id = $2 AND status = 'published'"
         )
         .bind(slug)
-        .fetch_optional(&self.db)
+        .bind(site.id)
+        .fetch_optional(db)
         .await?
         .ok_or_else(|| ServiceError::NotFound(format!("Post not found: {}", slug)))?;
 
-        let result = self.get_post_relations(&post).await?;
-
-        self.cache.set(&cache_key, &result, Some(600)).await;
-
-        Ok(result)
+        self.get_post_relations(db, &post).await
     }
 
     /// Get a post by ID
-    pub async fn get_by_id(&self, id: Uuid) -> Result<Post, ServiceError> {
-        sqlx::query_as("SELECT * FROM blog_posts WHERE id = $1")
+    pub async fn get_by_id(&self, site: &Site, id: Uuid) -> Result<Post, ServiceError> {
+        sqlx::query_as("SELECT * FROM blog_posts WHERE id = $1 AND site_id = $2")
             .bind(id)
-            .fetch_optional(&self.db)
+            .bind(site.id)
+            .fetch_optional(self.db.write())
             .await?
             .ok_or_else(|| ServiceError::NotFound(format!("Post not found: {}", id)))
     }
 
-    /// Create a new post
-    pub async fn create(&self, author_id: Uuid, req: CreatePostRequest) -> Result<Post, ServiceError> {
+    /// Create a new post, to be published at `scheduled_for` (the request's
+    /// `scheduled_for` as read in the author's time zone)
+    #[tracing::instrument(name = "posts.create", skip_all, fields(site = %site.id))]
+    pub async fn create(
+        &self,
+        site: &Site,
+        author_id: Uuid,
+        req: CreatePostRequest,
+        scheduled_for: Option<DateTime<Utc>>,
+    ) -> Result<Post, ServiceError> {
         let slug = slug::slugify(&req.title);
-        let excerpt = req.excerpt.or_else(|| {
-            Some(req.content.chars().take(200).collect())
-        });
+        let excerpt = req
+            .excerpt
+            .or_else(|| Some(excerpts::generate(&req.content, self.excerpt_length)));
 
+        let mut tx = self.db.write().begin().await?;
         let post: Post = sqlx::query_as(
             r#"INSERT INTO blog_posts
-               (author_id, title, slug, content, excerpt, featured_image, status, meta_title, meta_description, scheduled_for)
-               VALUES ($1, $2, $3, $4, $5, $6, 'draft', $7, $8, $9)
+               (site_id, author_id, title, slug, content, excerpt, featured_image, status, meta_title, meta_description, scheduled_for)
+               VALUES ($1, $2, $3, $4, $5, $6, $7, 'draft', $8, $9, $10)
                RETURNING *"#
         )
+        .bind(site.id)
         .bind(author_id)
         .bind(&req.title)
         .bind(&slug)
@@ -152,36 +292,35 @@ impl PostService {
         .bind(&req.featured_image)
         .bind(&req.meta_title)
         .bind(&req.meta_description)
-        .bind(&req.scheduled_for)
-        .fetch_one(&self.db)
+        .bind(scheduled_for)
+        .fetch_one(&mut *tx)
         .await?;
 
-        // Attach categories and tags
+        // Attach categories and tags; the post isn't created if any is unknown
         if let Some(category_ids) = req.category_ids {
-            self.attach_categories(post.id, &category_ids).await?;
+            attach_categories(&mut tx, site, post.id, &category_ids).await?;
         }
         if let Some(tag_ids) = req.tag_ids {
-            self.attach_tags(post.id, &tag_ids).await?;
+            attach_tags(&mut tx, site, post.id, &tag_ids).await?;
         }
+        tx.commit().await?;
 
         // Invalidate cache
-        self.cache.delete_pattern("posts:*").await;
+        self.invalidate(site).await;
+        self.emit(site, Some(author_id), &post, ContentAction::Created, None).await;
 
         Ok(post)
     }
 
-    /// Update a post
-    pub async fn update(&self, id: Uuid, author_id: Uuid, req: UpdatePostRequest) -> Result<Post, ServiceError> {
-        let existing = self.get_by_id(id).await?;
-
-        // Check ownership
-        if existing.author_id != author_id {
-            return Err(ServiceError::PermissionDenied);
-        }
-
-        let title = req.title.unwrap_or(existing.title);
+    /// Update a post; `actor` must already be allowed by the `posts.update`
+    /// policy
+    #[tracing::instrument(name = "posts.update", skip_all, fields(site = %site.id, id = %existing.id))]
+    pub async fn update(&self, site: &Site, existing: Post, actor: Uuid, req: UpdatePostRequest) -> Result<Post, ServiceError> {
+        let id = existing.id;
+        let title = req.title.unwrap_or_else(|| existing.title.clone());
         let slug = slug::slugify(&title);
 
+        let mut tx = self.db.write().begin().await?;
         let post: Post = sqlx::query_as(
             r#"UPDATE blog_posts SET
                title = $2, slug = $3, content = COALESCE($4, content),
@@ -199,94 +338,163 @@ impl PostService {
         .bind(&req.featured_image)
         .bind(&req.meta_title)
         .bind(&req.meta_description)
-        .fetch_one(&self.db)
+        .fetch_one(&mut *tx)
         .await?;
 
+        let mut changes = activity::diff(&existing, &post, POST_DIFF_FIELDS);
+        if req.category_ids.is_some() || req.tag_ids.is_some() {
+            let fields = changes.get_or_insert_with(|| serde_json::json!({}));
+            if req.category_ids.is_some() {
+                fields["categories"] = serde_json::json!({ "changed": true });
+            }
+            if req.tag_ids.is_some() {
+                fields["tags"] = serde_json::json!({ "changed": true });
+            }
+        }
+
         // Update categories and tags if provided
         if let Some(category_ids) = req.category_ids {
             sqlx::query("DELETE FROM blog_post_categories WHERE post_id = $1")
                 .bind(id)
-                .execute(&self.db)
+                .execute(&mut *tx)
                 .await?;
-            self.attach_categories(id, &category_ids).await?;
+            attach_categories(&mut tx, site, id, &category_ids).await?;
         }
         if let Some(tag_ids) = req.tag_ids {
             sqlx::query("DELETE FROM blog_post_tags WHERE post_id = $1")
                 .bind(id)
-                .execute(&self.db)
+                .execute(&mut *tx)
                 .await?;
-            self.attach_tags(id, &tag_ids).await?;
+            attach_tags(&mut tx, site, id, &tag_ids).await?;
         }
+        tx.commit().await?;
 
         // Invalidate cache
-        self.cache.delete_pattern("posts:*").await;
+        self.invalidate(site).await;
+        self.emit(site, Some(actor), &post, ContentAction::Updated, changes).await;
 
         Ok(post)
     }
 
     /// Publish a post
-    pub async fn publish(&self, id: Uuid) -> Result<Post, ServiceError> {
+    #[tracing::instrument(name = "posts.publish", skip_all, fields(site = %site.id, id = %id))]
+    pub async fn publish(&self, site: &Site, id: Uuid, actor: Uuid) -> Result<Post, ServiceError> {
         let post: Post = sqlx::query_as(
             "UPDATE blog_posts SET status = 'published', published_at = NOW(), updated_at = NOW()
-             WHERE id = $1 RETURNING *"
+             WHERE id = $1 AND site_id = $2 RETURNING *"
         )
         .bind(id)
-        .fetch_one(&self.db)
-        .await?;
+        .bind(site.id)
+        .fetch_optional(self.db.write())
+        .await?
+        .ok_or_else(|| ServiceError::NotFound(format!("Post not found: {}", id)))?;
 
-        self.cache.delete_pattern("posts:*").await;
+        ensure_author_slug(self.db.write(), post.author_id).await?;
+        self.invalidate(site).await;
+        self.emit(site, Some(actor), &post, ContentAction::Published, None).await;
 
         Ok(post)
     }
 
     /// Unpublish a post
-    pub async fn unpublish(&self, id: Uuid) -> Result<Post, ServiceError> {
+    #[tracing::instrument(name = "posts.unpublish", skip_all, fields(site = %site.id, id = %id))]
+    pub async fn unpublish(&self, site: &Site, id: Uuid, actor: Uuid) -> Result<Post, ServiceError> {
         let post: Post = sqlx::query_as(
-            "UPDATE blog_posts SET status = 'draft', updated_at = NOW() WHERE id = $1 RETURNING *"
+            "UPDATE blog_posts SET status = 'draft', updated_at = NOW() WHERE id = $1 AND site_id = $2 RETURNING *"
         )
         .bind(id)
-        .fetch_one(&self.db)
-        .await?;
+        .bind(site.id)
+        .fetch_optional(self.db.write())
+        .await?
+        .ok_or_else(|| ServiceError::NotFound(format!("Post not found: {}", id)))?;
 
-        self.cache.delete_pattern("posts:*").await;
+        self.invalidate(site).await;
+        self.emit(site, Some(actor), &post, ContentAction::Unpublished, None).await;
 
         Ok(post)
     }
 
-    /// Delete a post
-    pub async fn delete(&self, id: Uuid, author_id: Uuid) -> Result<(), ServiceError> {
-        let existing = self.get_by_id(id).await?;
-
-        if existing.author_id != author_id {
-            return Err(ServiceError::PermissionDenied);
-        }
-
+    /// Delete a post; `actor` must already be allowed by the `posts.delete`
+    /// policy
+    #[tracing::instrument(name = "posts.delete", skip_all, fields(site = %site.id, id = %existing.id))]
+    pub async fn delete(&self, site: &Site, existing: Post, actor: Uuid) -> Result<(), ServiceError> {
         sqlx::query("DELETE FROM blog_posts WHERE id = $1")
-            .bind(id)
-            .execute(&self.db)
+            .bind(existing.id)
+            .execute(self.db.write())
             .await?;
 
-        self.cache.delete_pattern("posts:*").await;
+        self.invalidate(site).await;
+        self.emit(site, Some(actor), &existing, ContentAction::Trashed, None).await;
 
         Ok(())
     }
 
+    /// A post in any status with its author, categories and tags
+    pub async fn get_with_relations(&self, site: &Site, id: Uuid) -> Result<PostWithRelations, ServiceError> {
+        let post = self.get_by_id(site, id).await?;
+        self.get_post_relations(self.db.write(), &post).await
+    }
+
+    /// The published posts of `ids`, in that order, as teasers where `viewer`
+    /// can't read all of them
+    pub async fn list_by_ids(&self, site: &Site, ids: &[Uuid], viewer: &Viewer) -> Result<Vec<PostWithRelations>, ServiceError> {
+        let mut posts: Vec<Post> = sqlx::query_as(
+            "SELECT * FROM blog_posts WHERE id = ANY($1) AND site_id = $2 AND status = 'published'"
+        )
+        .bind(ids)
+        .bind(site.id)
+        .fetch_all(self.db.read())
+        .await?;
+        posts.sort_by_key(|post| ids.iter().position(|id| *id == post.id));
+
+        let mut posts_with_relations = Vec::with_capacity(posts.len());
+        for post in &posts {
+            posts_with_relations.push(self.get_post_relations(self.db.read(), post).await?);
+        }
+
+        self.access.gate(viewer, &mut posts_with_relations).await?;
+        self.mentions.link(site, &mut posts_with_relations).await?;
+        Ok(posts_with_relations)
+    }
+
     /// Increment view count
     pub async fn increment_views(&self, id: Uuid) -> Result<(), ServiceError> {
         sqlx::query("UPDATE blog_posts SET view_count = view_count + 1 WHERE id = $1")
             .bind(id)
-            .execute(&self.db)
+            .execute(self.db.write())
             .await?;
         Ok(())
     }
 
-    /// Get post with relations
-    async fn get_post_relations(&self, post: &Post) -> Result<PostWithRelations, ServiceError> {
+    /// Slugs and modification times of every published post, for the sitemap
+    pub async fn sitemap_entries(&self, site: &Site) -> Result<Vec<(String, DateTime<Utc>)>, ServiceError> {
+        let cache_key = site.cache_key("posts:sitemap");
+
+        self.cache
+            .get_or_load(&cache_key, Some(3600), || self.load_sitemap_entries(site))
+            .await
+    }
+
+    async fn load_sitemap_entries(&self, site: &Site) -> Result<Vec<(String, DateTime<Utc>)>, ServiceError> {
+        let db = self.refill_pool(site).await;
+        let entries = sqlx::query_as(
+            "SELECT slug, updated_at FROM blog_posts
+             WHERE status = 'published' AND site_id = $1
+             ORDER BY published_at DESC"
+        )
+        .bind(site.id)
+        .fetch_all(db)
+        .await?;
+
+        Ok(entries)
+    }
+
+    async fn get_post_relations(&self, db: &DbPool, post: &Post) -> Result<PostWithRelations, ServiceError> {
         let author: AuthorInfo = sqlx::query_as(
             "SELECT id, name, avatar, bio FROM users WHERE id = $1"
         )
         .bind(post.author_id)
-        .fetch_one(&self.db)
+        .fetch_one(db)
         .await?;
 
         let categories: Vec<Category> = sqlx::query_as(
@@ -295,7 +503,7 @@ impl PostService {
              WHERE pc.post_id = $1"
         )
         .bind(post.id)
-        .fetch_all(&self.db)
+        .fetch_all(db)
         .await?;
 
         let tags: Vec<Tag> = sqlx::query_as(
@@ -304,7 +512,7 @@ impl PostService {
              WHERE pt.post_id = $1"
         )
         .bind(post.id)
-        .fetch_all(&self.db)
+        .fetch_all(db)
         .await?;
 
         Ok(PostWithRelations {
@@ -312,76 +520,282 @@ impl PostService {
             author,
             categories,
             tags,
+            locked: false,
         })
     }
 
-    async fn attach_categories(&self, post_id: Uuid, category_ids: &[Uuid]) -> Result<(), ServiceError> {
-        for cat_id in category_ids {
-            sqlx::query("INSERT INTO blog_post_categories (post_id, category_id) VALUES ($1, $2)")
-                .bind(post_id)
-                .bind(cat_id)
-                .execute(&self.db)
-                .await?;
-        }
-        Ok(())
+    /// Posts in any status matching `filters`, for the admin post list
+    pub async fn list_admin(
+        &self,
+        site: &Site,
+        filters: &PostFilters,
+        query: &AdminPostQuery,
+    ) -> Result<PaginatedResponse<PostWithRelations>, ServiceError> {
+        let data = self.load_admin(site, filters, query, query.per_page(), query.offset()).await?;
+
+        let mut count = QueryBuilder::<Postgres>::new("SELECT COUNT(*) FROM blog_posts p");
+        push_admin_filters(&mut count, site, filters);
+        let total: i64 = count.build_query_scalar().fetch_one(self.db.write()).await?;
+
+        Ok(PaginatedResponse {
+            data,
+            pagination: PaginationMeta::new(total, query.page(), query.per_page()),
+        })
     }
 
-    async fn attach_tags(&self, post_id: Uuid, tag_ids: &[Uuid]) -> Result<(), ServiceError> {
-        for tag_id in tag_ids {
-            sqlx::query("INSERT INTO blog_post_tags (post_id, tag_id) VALUES ($1, $2)")
-                .bind(post_id)
-                .bind(tag_id)
-                .execute(&self.db)
-                .await?;
+    /// Every post matching `filters` up to `limit`, in the list's order, for
+    /// CSV export
+    pub async fn export_admin(
+        &self,
+        site: &Site,
+        filters: &PostFilters,
+        query: &AdminPostQuery,
+        limit: i64,
+    ) -> Result<Vec<PostWithRelations>, ServiceError> {
+        self.load_admin(site, filters, query, limit, 0).await
+    }
+
+    async fn load_admin(
+        &self,
+        site: &Site,
+        filters: &PostFilters,
+        query: &AdminPostQuery,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<PostWithRelations>, ServiceError> {
+        let column = match query.sort.as_deref() {
+            Some("updated") => "p.updated_at",
+            Some("published") => "p.published_at",
+            Some("views") => "p.view_count",
+            Some("comments") => "p.comment_count",
+            _ => "p.created_at",
+        };
+        let order = match query.order.as_deref() {
+            Some("asc") => "ASC",
+            _ => "DESC",
+        };
+
+        let mut posts = QueryBuilder::<Postgres>::new("SELECT p.* FROM blog_posts p");
+        push_admin_filters(&mut posts, site, filters);
+        posts
+            .push(format!(" ORDER BY {} {} NULLS LAST, p.id LIMIT ", column, order))
+            .push_bind(limit)
+            .push(" OFFSET ")
+            .push_bind(offset);
+        // Admin reads must see their own writes
+        let posts: Vec<Post> = posts.build_query_as().fetch_all(self.db.write()).await?;
+
+        let mut posts_with_relations = Vec::with_capacity(posts.len());
+        for post in posts {
+            posts_with_relations.push(self.get_post_relations(self.db.write(), &post).await?);
         }
-        Ok(())
+        Ok(posts_with_relations)
     }
 }
 
+/// Link a post to categories of its site; ids of missing categories or of
+/// another site's are rejected
+async fn attach_categories(conn: &mut PgConnection, site: &Site, post_id: Uuid, category_ids: &[Uuid]) -> Result<(), ServiceError> {
+    let attached: Vec<Uuid> = sqlx::query_scalar(
+        r#"INSERT INTO blog_post_categories (post_id, category_id)
+           SELECT $1, id FROM blog_categories WHERE id = ANY($2) AND site_id = $3
+           RETURNING category_id"#
+    )
+    .bind(post_id)
+    .bind(category_ids)
+    .bind(site.id)
+    .fetch_all(conn)
+    .await?;
+
+    reject_unattached("category_ids", category_ids, &attached)
+}
+
+/// Link a post to tags of its site; ids of missing tags or of another
+/// site's are rejected
+async fn attach_tags(conn: &mut PgConnection, site: &Site, post_id: Uuid, tag_ids: &[Uuid]) -> Result<(), ServiceError> {
+    let attached: Vec<Uuid> = sqlx::query_scalar(
+        r#"INSERT INTO blog_post_tags (post_id, tag_id)
+           SELECT $1, id FROM blog_tags WHERE id = ANY($2) AND site_id = $3
+           RETURNING tag_id"#
+    )
+    .bind(post_id)
+    .bind(tag_ids)
+    .bind(site.id)
+    .fetch_all(conn)
+    .await?;
+
+    reject_unattached("tag_ids", tag_ids, &attached)
+}
+
+fn reject_unattached(field: &str, requested: &[Uuid], attached: &[Uuid]) -> Result<(), ServiceError> {
+    let unknown: Vec<String> = requested
+        .iter()
+        .filter(|id| !attached.contains(id))
+        .map(Uuid::to_string)
+        .collect();
+    if unknown.is_empty() {
+        return Ok(());
+    }
+
+    Err(ServiceError::InvalidFields(vec![FieldError {
+        field: field.to_string(),
+        code: "not_found".to_string(),
+        message: format!("Not found on this site: {}", unknown.join(", ")),
+        rejected_value: Some(unknown.into()),
+    }]))
+}
+
+/// Conditions of the admin post list
+fn push_admin_filters(builder: &mut QueryBuilder<'_, Postgres>, site: &Site, filters: &PostFilters) {
+    builder.push(" WHERE p.site_id = ").push_bind(site.id);
+    if let Some(status) = &filters.status {
+        builder.push(" AND p.status = ").push_bind(status.clone());
+    }
+    if let Some(author) = filters.author {
+        builder.push(" AND p.author_id = ").push_bind(author);
+    }
+    if let Some(category) = &filters.category {
+        builder
+            .push(
+                " AND EXISTS (SELECT 1 FROM blog_post_categories pc
+                              JOIN blog_categories c ON c.id = pc.category_id
+                              WHERE pc.post_id = p.id AND c.slug = ",
+            )
+            .push_bind(category.clone())
+            .push(")");
+    }
+    if let Some(from) = filters.from {
+        builder.push(" AND p.created_at >= ").push_bind(from);
+    }
+    if let Some(to) = filters.to {
+        builder.push(" AND p.created_at < ").push_bind(to);
+    }
+    if let Some(q) = filters.q.as_deref().map(str::trim).filter(|q| !q.is_empty()) {
+        // Same expression as idx_posts_search
+        builder
+            .push(
+                " AND to_tsvector('english', p.title || ' ' || COALESCE(p.excerpt, '') || ' ' || p.content)
+                  @@ plainto_tsquery('english', ",
+            )
+            .push_bind(q.to_string())
+            .push(")");
+    }
+}
+
+/// Category, tag and author conditions for published post listings,
+/// numbering placeholders from `$first` in that order
+fn published_filters(query: &PostQuery, first: usize) -> String {
+    let mut filters = String::new();
+    let mut param = first;
+    if query.category.is_some() {
+        filters.push_str(&format!(
+            " AND EXISTS (SELECT 1 FROM blog_post_categories pc
+                          JOIN blog_categories c ON c.id = pc.category_id
+                          WHERE pc.post_id = p.id AND c.slug = ${})",
+            param
+        ));
+        param += 1;
+    }
+    if query.tag.is_some() {
+        filters.push_str(&format!(
+            " AND EXISTS (SELECT 1 FROM blog_post_tags pt
+                          JOIN blog_tags t ON t.id = pt.tag_id
+                          WHERE pt.post_id = p.id AND t.slug = ${})",
+            param
+        ));
+        param += 1;
+    }
+    if query.author.is_some() {
+        filters.push_str(&format!(" AND p.author_id = ${}", param));
+    }
+    filters
+}
+
+/// How a new comment was submitted and what moderation decided
+pub struct NewComment<'a> {
+    pub client: ClientInfo,
+    pub status: CommentStatus,
+    /// Toxicity score, if the comment was scored
+    pub toxicity: Option<&'a ToxicityScore>,
+}
+
 /// Comment service
 pub struct CommentService {
     db: PgPool,
+    hooks: Arc<ContentHooks>,
 }
 
 impl CommentService {
-    pub fn new(db: PgPool) -> Self {
-        Self { db }
+    pub fn new(db: PgPool, hooks: Arc<ContentHooks>) -> Self {
+        Self { db, hooks }
     }
 
-    /// List comments for a post
-    pub async fn list_for_post(&self, post_id: Uuid) -> Result<Vec<CommentThread>, ServiceError> {
-        let comments: Vec<Comment> = sqlx::query_as(
-            "SELECT * FROM blog_comments WHERE post_id = $1 AND status = 'approved' ORDER BY created_at ASC"
-        )
+    async fn emit(&self, site: &Site, actor: Option<Uuid>, comment: &Comment, action: ContentAction) {
+        let event = ContentEvent::new(site, actor, ContentObject::Comment, comment.id, action)
+            .label(format!("Comment by {}", comment.author_name));
+        self.hooks.emit(event).await;
+    }
+
+    /// List approved comments for a post, each level of the threads in
+    /// `sort` order, with the viewer's votes
+    pub async fn list_for_post(
+        &self,
+        site: &Site,
+        post_id: Uuid,
+        sort: CommentSort,
+        viewer: Option<&Voter>,
+    ) -> Result<Vec<CommentThread>, ServiceError> {
+        let order = match sort {
+            CommentSort::Oldest => "c.created_at ASC",
+            CommentSort::Newest => "c.created_at DESC",
+            CommentSort::Top => "c.score DESC, c.created_at ASC",
+        };
+        let rows: Vec<CommentRow> = sqlx::query_as(&format!(
+            "SELECT c.*, v.value AS viewer_vote
+             FROM blog_comments c
+             LEFT JOIN blog_comment_votes v ON v.comment_id = c.id AND v.voter = $3
+             WHERE c.post_id = $1 AND c.site_id = $2 AND c.status = 'approved'
+             ORDER BY {}",
+            order
+        ))
         .bind(post_id)
+        .bind(site.id)
+        .bind(viewer.map(Voter::key))
         .fetch_all(&self.db)
         .await?;
 
-        Ok(self.build_comment_tree(comments))
+        Ok(build_comment_tree(rows))
     }
 
-    /// Create a comment
+    /// Create a comment with the status and toxicity score moderation gave it
+    #[tracing::instrument(name = "comments.create", skip_all, fields(site = %site.id, post_id = %post_id))]
     pub async fn create(
         &self,
+        site: &Site,
         post_id: Uuid,
         author_id: Option<Uuid>,
         req: CreateCommentRequest,
-        ip: Option<String>,
-        user_agent: Option<String>,
-        requires_moderation: bool,
+        new: NewComment<'_>,
     ) -> Result<Comment, ServiceError> {
-        let status = if requires_moderation {
-            CommentStatus::Pending
-        } else {
-            CommentStatus::Approved
-        };
+        let NewComment { client, status, toxicity } = new;
+        // The post must belong to the same site
+        let exists: bool = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM blog_posts WHERE id = $1 AND site_id = $2)")
+            .bind(post_id)
+            .bind(site.id)
+            .fetch_one(&self.db)
+            .await?;
+        if !exists {
+            return Err(ServiceError::NotFound(format!("Post not found: {}", post_id)));
+        }
 
         let comment: Comment = sqlx::query_as(
             r#"INSERT INTO blog_comments
-               (post_id, parent_id, author_id, author_name, author_email, author_url, content, status, ip_address, user_agent)
-               VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+               (site_id, post_id, parent_id, author_id, author_name, author_email, author_url, content, status, ip_address, user_agent,
+                toxicity, toxicity_attributes, toxicity_model)
+               VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)
                RETURNING *"#
         )
+        .bind(site.id)
         .bind(post_id)
         .bind(req.parent_id)
         .bind(author_id)
@@ -390,8 +804,11 @@ impl CommentService {
         .bind(&req.author_url)
         .bind(&req.content)
         .bind(status)
-        .bind(ip)
-        .bind(user_agent)
+        .bind(client.ip)
+        .bind(client.user_agent)
+        .bind(toxicity.map(|t| t.score))
+        .bind(toxicity.map(|t| sqlx::types::Json(&t.attributes)))
+        .bind(toxicity.map(|t| &t.model))
         .fetch_one(&self.db)
         .await?;
 
@@ -401,93 +818,134 @@ impl CommentService {
             .execute(&self.db)
             .await?;
 
+        self.emit(site, author_id, &comment, ContentAction::Created).await;
+
         Ok(comment)
     }
 
     /// Approve a comment
-    pub async fn approve(&self, id: Uuid) -> Result<Comment, ServiceError> {
-        sqlx::query_as("UPDATE blog_comments SET status = 'approved' WHERE id = $1 RETURNING *")
+    #[tracing::instrument(name = "comments.approve", skip_all, fields(site = %site.id, id = %id))]
+    pub async fn approve(&self, site: &Site, id: Uuid, actor: Uuid) -> Result<Comment, ServiceError> {
+        let comment: Comment = sqlx::query_as("UPDATE blog_comments SET status = 'approved' WHERE id = $1 AND site_id = $2 RETURNING *")
             .bind(id)
+            .bind(site.id)
             .fetch_optional(&self.db)
             .await?
-            .ok_or_else(|| ServiceError::NotFound("Comment not found".into()))
+            .ok_or_else(|| ServiceError::NotFound("Comment not found".into()))?;
+
+        self.emit(site, Some(actor), &comment, ContentAction::Approved).await;
+        Ok(comment)
+    }
+
+    /// Comments awaiting moderation, oldest first, optionally on one post
+    pub async fn list_pending(&self, site: &Site, post_id: Option<Uuid>, limit: i64) -> Result<Vec<Comment>, ServiceError> {
+        let comments = sqlx::query_as(
+            "SELECT * FROM blog_comments
+             WHERE site_id = $1 AND status = 'pending' AND ($2::uuid IS NULL OR post_id = $2)
+             ORDER BY created_at ASC
+             LIMIT $3",
+        )
+        .bind(site.id)
+        .bind(post_id)
+        .bind(limit)
+        .fetch_all(&self.db)
+        .await?;
+        Ok(comments)
     }
 
     /// Reject a comment
-    pub async fn reject(&self, id: Uuid) -> Result<Comment, ServiceError> {
-        sqlx::query_as("UPDATE blog_comments SET status = 'rejected' WHERE id = $1 RETURNING *")
+    #[tracing::instrument(name = "comments.reject", skip_all, fields(site = %site.id, id = %id))]
+    pub async fn reject(&self, site: &Site, id: Uuid, actor: Uuid) -> Result<Comment, ServiceError> {
+        let comment: Comment = sqlx::query_as("UPDATE blog_comments SET status = 'rejected' WHERE id = $1 AND site_id = $2 RETURNING *")
             .bind(id)
+            .bind(site.id)
             .fetch_optional(&self.db)
             .await?
-            .ok_or_else(|| ServiceError::NotFound("Comment not found".into()))
-    }
-
-    fn build_comment_tree(&self, comments: Vec<Comment>) -> Vec<CommentThread> {
-        use std::collections::HashMap;
-
-        let mut map: HashMap<Uuid, CommentThread> = HashMap::new();
-        let mut roots = Vec::new();
-
-        // First pass: create all threads
-        for comment in comments {
-            map.insert(comment.id, CommentThread {
-                comment,
-                replies: Vec::new(),
-            });
-        }
-
-        // Second pass: build tree
-        let ids: Vec<Uuid> = map.keys().copied().collect();
-        for id in ids {
-            let thread = map.remove(&id).unwrap();
-            if let Some(parent_id) = thread.comment.parent_id {
-                if let Some(parent) = map.get_mut(&parent_id) {
-                    parent.replies.push(thread);
-                } else {
-                    roots.push(thread);
-                }
-            } else {
-                roots.push(thread);
-            }
-        }
+            .ok_or_else(|| ServiceError::NotFound("Comment not found".into()))?;
+
+        self.emit(site, Some(actor), &comment, ContentAction::Rejected).await;
+        Ok(comment)
+    }
+}
+
+/// A listed comment with the viewer's vote
+#[derive(FromRow)]
+struct CommentRow {
+    #[sqlx(flatten)]
+    comment: Comment,
+    viewer_vote: Option<i16>,
+}
 
-        roots
+/// Nest replies under their parents, keeping the order of `rows` on each
+/// level; replies whose parent isn't listed become roots
+fn build_comment_tree(rows: Vec<CommentRow>) -> Vec<CommentThread> {
+    let listed: HashSet<Uuid> = rows.iter().map(|row| row.comment.id).collect();
+    let mut children: HashMap<Option<Uuid>, Vec<CommentRow>> = HashMap::new();
+    for row in rows {
+        let parent = row.comment.parent_id.filter(|id| listed.contains(id));
+        children.entry(parent).or_default().push(row);
     }
+
+    fn nest(parent: Option<Uuid>, children: &mut HashMap<Option<Uuid>, Vec<CommentRow>>) -> Vec<CommentThread> {
+        children
+            .remove(&parent)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|row| CommentThread {
+                replies: nest(Some(row.comment.id), children),
+                viewer_vote: row.viewer_vote,
+                comment: row.comment,
+            })
+            .collect()
+    }
+    nest(None, &mut children)
 }
 
 /// Category service
 pub struct CategoryService {
     db: PgPool,
     cache: Arc<dyn Cache>,
+    hooks: Arc<ContentHooks>,
 }
 
 impl CategoryService {
-    pub fn new(db: PgPool, cache: Arc<dyn Cache>) -> Self {
-        Self { db, cache }
+    pub fn new(db: PgPool, cache: Arc<dyn Cache>, hooks: Arc<ContentHooks>) -> Self {
+        Self { db, cache, hooks }
+    }
+
+    async fn emit(&self, site: &Site, actor: Uuid, category: &Category, action: ContentAction, changes: Option<serde_json::Value>) {
+        let event = ContentEvent::new(site, Some(actor), ContentObject::Category, category.id, action)
+            .label(category.name.clone())
+            .changes(changes);
+        self.hooks.emit(event).await;
     }
 
-    pub async fn list(&self) -> Result<Vec<Category>, ServiceError> {
-        if let Some(cached) = self.cache.get::<Vec<Category>>("categories:all").await {
+    pub async fn list(&self, site: &Site) -> Result<Vec<Category>, ServiceError> {
+        let cache_key = site.cache_key("categories:all");
+        if let Some(cached) = self.cache.get::<Vec<Category>>(&cache_key).await {
             return Ok(cached);
         }
 
         let categories: Vec<Category> = sqlx::query_as(
-            "SELECT * FROM blog_categories ORDER BY name ASC"
+            "SELECT * FROM blog_categories WHERE site_id = $1 ORDER BY name ASC"
         )
+        .bind(site.id)
         .fetch_all(&self.db)
         .await?;
 
-        self.cache.set("categories:all", &categories, Some(3600)).await;
+        self.cache.set(&cache_key, &categories, Some(3600)).await;
 
         Ok(categories)
     }
 
-    pub async fn create(&self, req: CategoryRequest) -> Result<Category, ServiceError> {
+    #[tracing::instrument(name = "categories.create", skip_all, fields(site = %site.id))]
+    pub async fn create(&self, site: &Site, actor: Uuid, req: CategoryRequest) -> Result<Category, ServiceError> {
         let slug = slug::slugify(&req.name);
 
         let category: Category = sqlx::query_as(
-            "INSERT INTO blog_categories (name, slug, parent_id, description) VALUES ($1, $2, $3, $4) RETURNING *"
+            "INSERT INTO blog_categories (site_id, name, slug, parent_id, description) VALUES ($1, $2, $3, $4, $5) RETURNING *"
         )
+        .bind(site.id)
         .bind(&req.name)
         .bind(&slug)
         .bind(req.parent_id)
@@ -495,38 +953,54 @@ impl CategoryService {
         .fetch_one(&self.db)
         .await?;
 
-        self.cache.delete("categories:all").await;
+        self.cache.delete(&site.cache_key("categories:all")).await;
+        self.emit(site, actor, &category, ContentAction::Created, None).await;
 
         Ok(category)
     }
 
-    pub async fn update(&self, id: Uuid, req: CategoryRequest) -> Result<Category, ServiceError> {
+    #[tracing::instrument(name = "categories.update", skip_all, fields(site = %site.id, id = %id))]
+    pub async fn update(&self, site: &Site, id: Uuid, actor: Uuid, req: CategoryRequest) -> Result<Category, ServiceError> {
+        let existing: Category = sqlx::query_as("SELECT * FROM blog_categories WHERE id = $1 AND site_id = $2")
+            .bind(id)
+            .bind(site.id)
+            .fetch_optional(&self.db)
+            .await?
+            .ok_or_else(|| ServiceError::NotFound("Category not found".into()))?;
         let slug = slug::slugify(&req.name);
 
         let category: Category = sqlx::query_as(
-            "UPDATE blog_categories SET name = $2, slug = $3, parent_id = $4, description = $5 WHERE id = $1 RETURNING *"
+            "UPDATE blog_categories SET name = $2, slug = $3, parent_id = $4, description = $5 WHERE id = $1 AND site_id = $6 RETURNING *"
         )
         .bind(id)
         .bind(&req.name)
         .bind(&slug)
         .bind(req.parent_id)
         .bind(&req.description)
+        .bind(site.id)
         .fetch_optional(&self.db)
         .await?
         .ok_or_else(|| ServiceError::NotFound("Category not found".into()))?;
 
-        self.cache.delete("categories:all").await;
+        self.cache.delete(&site.cache_key("categories:all")).await;
+        let changes = activity::diff(&existing, &category, &["name", "slug", "parent_id", "description"]);
+        self.emit(site, actor, &category, ContentAction::Updated, changes).await;
 
         Ok(category)
     }
 
-    pub async fn delete(&self, id: Uuid) -> Result<(), ServiceError> {
-        sqlx::query("DELETE FROM blog_categories WHERE id = $1")
+    #[tracing::instrument(name = "categories.delete", skip_all, fields(site = %site.id, id = %id))]
+    pub async fn delete(&self, site: &Site, id: Uuid, actor: Uuid) -> Result<(), ServiceError> {
+        let deleted: Option<Category> = sqlx::query_as("DELETE FROM blog_categories WHERE id = $1 AND site_id = $2 RETURNING *")
             .bind(id)
-            .execute(&self.db)
+            .bind(site.id)
+            .fetch_optional(&self.db)
             .await?;
 
-        self.cache.delete("categories:all").await;
+        self.cache.delete(&site.cache_key("categories:all")).await;
+        if let Some(category) = deleted {
+            self.emit(site, actor, &category, ContentAction::Trashed, None).await;
+        }
 
         Ok(())
     }
@@ -543,105 +1017,281 @@ impl TagService {
         Self { db, cache }
     }
 
-    pub async fn list(&self) -> Result<Vec<Tag>, ServiceError> {
-        if let Some(cached) = self.cache.get::<Vec<Tag>>("tags:all").await {
+    pub async fn list(&self, site: &Site) -> Result<Vec<Tag>, ServiceError> {
+        let cache_key = site.cache_key("tags:all");
+        if let Some(cached) = self.cache.get::<Vec<Tag>>(&cache_key).await {
             return Ok(cached);
         }
 
-        let tags: Vec<Tag> = sqlx::query_as("SELECT * FROM blog_tags ORDER BY name ASC")
+        let tags: Vec<Tag> = sqlx::query_as("SELECT * FROM blog_tags WHERE site_id = $1 ORDER BY name ASC")
+            .bind(site.id)
             .fetch_all(&self.db)
             .await?;
 
-        self.cache.set("tags:all", &tags, Some(3600)).await;
+        self.cache.set(&cache_key, &tags, Some(3600)).await;
 
         Ok(tags)
     }
 
-    pub async fn create(&self, req: TagRequest) -> Result<Tag, ServiceError> {
+    #[tracing::instrument(name = "tags.create", skip_all, fields(site = %site.id))]
+    pub async fn create(&self, site: &Site, req: TagRequest) -> Result<Tag, ServiceError> {
         let slug = slug::slugify(&req.name);
 
         let tag: Tag = sqlx::query_as(
-            "INSERT INTO blog_tags (name, slug) VALUES ($1, $2) RETURNING *"
+            "INSERT INTO blog_tags (site_id, name, slug) VALUES ($1, $2, $3) RETURNING *"
         )
+        .bind(site.id)
         .bind(&req.name)
         .bind(&slug)
         .fetch_one(&self.db)
         .await?;
 
-        self.cache.delete("tags:all").await;
+        self.cache.delete(&site.cache_key("tags:all")).await;
 
         Ok(tag)
     }
 
-    pub async fn update(&self, id: Uuid, req: TagRequest) -> Result<Tag, ServiceError> {
+    #[tracing::instrument(name = "tags.update", skip_all, fields(site = %site.id, id = %id))]
+    pub async fn update(&self, site: &Site, id: Uuid, req: TagRequest) -> Result<Tag, ServiceError> {
         let slug = slug::slugify(&req.name);
 
         let tag: Tag = sqlx::query_as(
-            "UPDATE blog_tags SET name = $2, slug = $3 WHERE id = $1 RETURNING *"
+            "UPDATE blog_tags SET name = $2, slug = $3 WHERE id = $1 AND site_id = $4 RETURNING *"
         )
         .bind(id)
         .bind(&req.name)
         .bind(&slug)
+        .bind(site.id)
         .fetch_optional(&self.db)
         .await?
         .ok_or_else(|| ServiceError::NotFound("Tag not found".into()))?;
 
-        self.cache.delete("tags:all").await;
+        self.cache.delete(&site.cache_key("tags:all")).await;
 
         Ok(tag)
     }
 
-    pub async fn delete(&self, id: Uuid) -> Result<(), ServiceError> {
-        sqlx::query("DELETE FROM blog_tags WHERE id = $1")
+    #[tracing::instrument(name = "tags.delete", skip_all, fields(site = %site.id, id = %id))]
+    pub async fn delete(&self, site: &Site, id: Uuid) -> Result<(), ServiceError> {
+        sqlx::query("DELETE FROM blog_tags WHERE id = $1 AND site_id = $2")
             .bind(id)
+            .bind(site.id)
             .execute(&self.db)
             .await?;
 
-        self.cache.delete("tags:all").await;
+        self.cache.delete(&site.cache_key("tags:all")).await;
 
         Ok(())
     }
 }
 
+/// Author profiles
+///
+/// Authors are listed once they have a published post on the site. Entries
+/// are cached under `posts:` so any post change invalidates them.
+pub struct AuthorService {
+    db: PgPool,
+    cache: Arc<dyn Cache>,
+}
+
+const AUTHOR_SELECT: &str = "SELECT u.id, a.slug, u.name, u.avatar, u.bio, u.website,
+            COALESCE((SELECT jsonb_object_agg(SUBSTRING(m.meta_key FROM 8), m.meta_value)
+                      FROM user_meta m
+                      WHERE m.user_id = u.id AND m.meta_key LIKE 'social\\_%'), '{}'::jsonb) AS social,
+            COUNT(p.id) AS post_count
+     FROM blog_authors a
+     JOIN users u ON u.id = a.user_id
+     JOIN blog_posts p ON p.author_id = u.id AND p.status = 'published' AND p.site_id = $1";
+
+impl AuthorService {
+    pub fn new(db: PgPool, cache: Arc<dyn Cache>) -> Self {
+        Self { db, cache }
+    }
+
+    /// Authors with published posts on the site, by name
+    pub async fn list(&self, site: &Site) -> Result<Vec<AuthorProfile>, ServiceError> {
+        let cache_key = site.cache_key("posts:authors:all");
+
+        self.cache
+            .get_or_load(&cache_key, Some(3600), || self.load_all(site))
+            .await
+    }
+
+    async fn load_all(&self, site: &Site) -> Result<Vec<AuthorProfile>, ServiceError> {
+        let authors = sqlx::query_as(&format!("{} GROUP BY u.id, a.slug ORDER BY u.name ASC", AUTHOR_SELECT))
+            .bind(site.id)
+            .fetch_all(&self.db)
+            .await?;
+
+        Ok(authors)
+    }
+
+    /// Author by slug; authors without published posts on the site are not found
+    pub async fn get_by_slug(&self, site: &Site, slug: &str) -> Result<AuthorProfile, ServiceError> {
+        let cache_key = site.cache_key(&format!("posts:authors:slug:{}", slug));
+
+        self.cache
+            .get_or_load(&cache_key, Some(3600), || self.load_by_slug(site, slug))
+            .await
+    }
+
+    async fn load_by_slug(&self, site: &Site, slug: &str) -> Result<AuthorProfile, ServiceError> {
+        sqlx::query_as(&format!("{} WHERE a.slug = $2 GROUP BY u.id, a.slug", AUTHOR_SELECT))
+            .bind(site.id)
+            .bind(slug)
+            .fetch_optional(&self.db)
+            .await?
+            .ok_or_else(|| ServiceError::NotFound(format!("Author not found: {}", slug)))
+    }
+}
+
+/// Give a user an author slug unless they already have one
+///
+/// The slug is the slugified name, with `-2`, `-3`, ... appended when taken.
+pub async fn ensure_author_slug(db: &PgPool, user_id: Uuid) -> Result<(), ServiceError> {
+    let exists: bool = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM blog_authors WHERE user_id = $1)")
+        .bind(user_id)
+        .fetch_one(db)
+        .await?;
+    if exists {
+        return Ok(());
+    }
+
+    let name: String = sqlx::query_scalar("SELECT name FROM users WHERE id = $1")
+        .bind(user_id)
+        .fetch_one(db)
+        .await?;
+    let mut base = slug::slugify(&name);
+    if base.is_empty() {
+        base = "author".to_string();
+    }
+
+    for n in 1.. {
+        let candidate = if n == 1 { base.clone() } else { format!("{}-{}", base, n) };
+        // Conflicts on either key: the slug is taken, or a concurrent publish
+        // already assigned one
+        let inserted = sqlx::query("INSERT INTO blog_authors (user_id, slug) VALUES ($1, $2) ON CONFLICT DO NOTHING")
+            .bind(user_id)
+            .bind(&candidate)
+            .execute(db)
+            .await?
+            .rows_affected();
+        if inserted == 1 {
+            break;
+        }
+
+        let assigned: bool = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM blog_authors WHERE user_id = $1)")
+            .bind(user_id)
+            .fetch_one(db)
+            .await?;
+        if assigned {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
 /// Media service
+///
+/// Private media is stored under `private/` and handed out as signed links
+/// (see `signed_urls`) to the uploader and the configured roles.
 pub struct MediaService {
     db: PgPool,
     storage: Arc<dyn Storage>,
+    signer: Option<UrlSigner>,
+    private_access_roles: Vec<String>,
+    trash_retention_days: i64,
 }
 
 impl MediaService {
-    pub fn new(db: PgPool, storage: Arc<dyn Storage>) -> Self {
-        Self { db, storage }
+    pub fn new(db: PgPool, storage: Arc<dyn Storage>, config: &MediaConfig) -> Self {
+        let signer = UrlSigner::from_config(config);
+        if signer.is_none() {
+            tracing::warn!("No media signing key configured; private media links are disabled");
+        }
+
+        Self {
+            db,
+            storage,
+            signer,
+            private_access_roles: config.private_access_roles.clone(),
+            trash_retention_days: config.trash_retention_days,
+        }
+    }
+
+    /// Download link signer, if a key is configured
+    pub fn signer(&self) -> Option<&UrlSigner> {
+        self.signer.as_ref()
+    }
+
+    /// Whether `user` may open private media and change visibility: the
+    /// uploader and the configured roles
+    pub fn can_manage(&self, user: &User, media: &Media) -> bool {
+        media.uploader_id == user.id || self.private_access_roles.iter().any(|role| *role == user.role)
+    }
+
+    /// Whether `user` may open `media`
+    pub fn can_access(&self, user: &User, media: &Media) -> bool {
+        media.visibility == MediaVisibility::Public || self.can_manage(user, media)
+    }
+
+    /// Swap a private item's stored URL for a fresh signed link
+    fn with_signed_url(&self, site: &Site, mut media: Media) -> Media {
+        if media.visibility == MediaVisibility::Private {
+            if let Some(signer) = &self.signer {
+                media.url = signer.sign(site.id, &site.url(""), media.id).url;
+            }
+        }
+        media
+    }
+
+    pub async fn find(&self, site: &Site, id: Uuid) -> Result<Media, ServiceError> {
+        sqlx::query_as("SELECT * FROM blog_media WHERE id = $1 AND site_id = $2 AND trashed_at IS NULL")
+            .bind(id)
+            .bind(site.id)
+            .fetch_optional(&self.db)
+            .await?
+            .ok_or_else(|| ServiceError::NotFound("Media not found".into()))
     }
 
-    pub async fn list(&self, user_id: Uuid, query: &MediaQuery) -> Result<Vec<Media>, ServiceError> {
+    pub async fn list(&self, site: &Site, user_id: Uuid, query: &MediaQuery) -> Result<Vec<Media>, ServiceError> {
         let page = query.page.unwrap_or(1).max(1);
         let per_page = query.per_page.unwrap_or(20).min(100);
         let offset = (page - 1) * per_page;
 
         let media: Vec<Media> = sqlx::query_as(
-            "SELECT * FROM blog_media WHERE uploader_id = $1 ORDER BY created_at DESC LIMIT $2 OFFSET $3"
+            "SELECT * FROM blog_media WHERE uploader_id = $1 AND site_id = $4 AND trashed_at IS NULL
+             ORDER BY created_at DESC LIMIT $2 OFFSET $3"
         )
         .bind(user_id)
         .bind(per_page)
         .bind(offset)
+        .bind(site.id)
         .fetch_all(&self.db)
         .await?;
 
-        Ok(media)
+        Ok(media.into_iter().map(|m| self.with_signed_url(site, m)).collect())
     }
 
+    #[tracing::instrument(name = "media.upload", skip_all, fields(site = %site.id))]
     pub async fn upload(
         &self,
+        site: &Site,
         user_id: Uuid,
         filename: String,
         data: Vec<u8>,
         mime_type: String,
+        visibility: MediaVisibility,
     ) -> Result<Media, ServiceError> {
+        if visibility == MediaVisibility::Private && self.signer.is_none() {
+            return Err(ServiceError::Validation("Private media requires a signing key".into()));
+        }
+
         let id = Uuid::new_v4();
         let ext = filename.rsplit('.').next().unwrap_or("bin");
         let stored_name = format!("{}.{}", id, ext);
-        let path = format!("uploads/media/{}", stored_name);
+        let path = media_storage_path(visibility, &stored_name);
 
         // Upload to storage
         self.storage
@@ -649,53 +1299,261 @@ impl MediaService {
             .await
             .map_err(|e| ServiceError::Storage(e.to_string()))?;
 
-        let url = self.storage.url(&path);
+        // Private files have no public URL; links are signed per request
+        let url = match visibility {
+            MediaVisibility::Public => self.storage.url(&path),
+            MediaVisibility::Private => format!("/media/{}/download", id),
+        };
         let size = data.len() as i64;
 
         let media: Media = sqlx::query_as(
             r#"INSERT INTO blog_media
-               (id, uploader_id, filename, original_name, mime_type, size, url)
-               VALUES ($1, $2, $3, $4, $5, $6, $7)
+               (id, site_id, uploader_id, filename, original_name, mime_type, size, url, visibility)
+               VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
                RETURNING *"#
         )
         .bind(id)
+        .bind(site.id)
         .bind(user_id)
         .bind(&stored_name)
         .bind(&filename)
         .bind(&mime_type)
         .bind(size)
         .bind(&url)
+        .bind(visibility)
         .fetch_one(&self.db)
         .await?;
 
-        Ok(media)
+        Ok(self.with_signed_url(site, media))
     }
 
-    pub async fn delete(&self, id: Uuid, user_id: Uuid) -> Result<(), ServiceError> {
-        let media: Media = sqlx::query_as("SELECT * FROM blog_media WHERE id = $1")
-            .bind(id)
-            .fetch_optional(&self.db)
-            .await?
-            .ok_or_else(|| ServiceError::NotFound("Media not found".into()))?;
+    /// Signed download link for a media item the user may open
+    pub async fn signed_url(&self, site: &Site, user: &User, id: Uuid) -> Result<SignedUrl, ServiceError> {
+        let media = self.find(site, id).await?;
+        if !self.can_access(user, &media) {
+            return Err(ServiceError::PermissionDenied);
+        }
 
-        if media.uploader_id != user_id {
+        let signer = self
+            .signer
+            .as_ref()
+            .ok_or_else(|| ServiceError::Validation("Private media requires a signing key".into()))?;
+        Ok(signer.sign(site.id, &site.url(""), media.id))
+    }
+
+    /// Make a file public or private, moving it between storage prefixes
+    #[tracing::instrument(name = "media.set_visibility", skip_all, fields(site = %site.id, id = %id))]
+    pub async fn set_visibility(
+        &self,
+        site: &Site,
+        user: &User,
+        id: Uuid,
+        visibility: MediaVisibility,
+    ) -> Result<Media, ServiceError> {
+        let media = self.find(site, id).await?;
+        if !self.can_manage(user, &media) {
             return Err(ServiceError::PermissionDenied);
         }
+        if media.visibility == visibility {
+            return Ok(self.with_signed_url(site, media));
+        }
+        if visibility == MediaVisibility::Private && self.signer.is_none() {
+            return Err(ServiceError::Validation("Private media requires a signing key".into()));
+        }
 
-        // Delete from storage
-        let path = format!("uploads/media/{}", media.filename);
+        let from = media.storage_path();
+        let to = media_storage_path(visibility, &media.filename);
+        let data = self
+            .storage
+            .get(&from)
+            .await
+            .map_err(|e| ServiceError::Storage(e.to_string()))?;
         self.storage
-            .delete(&path)
+            .put(&to, &data)
             .await
             .map_err(|e| ServiceError::Storage(e.to_string()))?;
 
-        sqlx::query("DELETE FROM blog_media WHERE id = $1")
-            .bind(id)
+        let url = match visibility {
+            MediaVisibility::Public => self.storage.url(&to),
+            MediaVisibility::Private => format!("/media/{}/download", id),
+        };
+        let updated: Media = sqlx::query_as(
+            "UPDATE blog_media SET visibility = $1, url = $2 WHERE id = $3 RETURNING *"
+        )
+        .bind(visibility)
+        .bind(&url)
+        .bind(id)
+        .fetch_one(&self.db)
+        .await?;
+
+        // Only drop the old copy once the row points at the new one
+        if let Err(e) = self.storage.delete(&from).await {
+            tracing::warn!(media_id = %id, "Failed to delete previous media copy: {}", e);
+        }
+
+        Ok(self.with_signed_url(site, updated))
+    }
+
+    /// File contents for a verified download link
+    pub async fn download(&self, site: &Site, id: Uuid) -> Result<(Media, Vec<u8>), ServiceError> {
+        let media = self.find(site, id).await?;
+        let data = self
+            .storage
+            .get(&media.storage_path())
+            .await
+            .map_err(|e| ServiceError::Storage(e.to_string()))?;
+        Ok((media, data))
+    }
+
+    /// Move a media file the `media.delete` policy allowed to the trash;
+    /// the file stays in storage until `purge_trash` removes it
+    #[tracing::instrument(name = "media.trash", skip_all, fields(id = %media.id))]
+    pub async fn trash(&self, media: &Media, actor: Uuid) -> Result<(), ServiceError> {
+        sqlx::query("UPDATE blog_media SET trashed_at = NOW(), trashed_by = $2 WHERE id = $1 AND trashed_at IS NULL")
+            .bind(media.id)
+            .bind(actor)
             .execute(&self.db)
             .await?;
-
         Ok(())
     }
+
+    /// Trashed media of a site, most recently trashed first
+    pub async fn list_trash(&self, site: &Site, query: &MediaQuery) -> Result<Vec<Media>, ServiceError> {
+        let page = query.page.unwrap_or(1).max(1);
+        let per_page = query.per_page.unwrap_or(20).min(100);
+
+        let media = sqlx::query_as(
+            "SELECT * FROM blog_media WHERE site_id = $1 AND trashed_at IS NOT NULL
+             ORDER BY trashed_at DESC LIMIT $2 OFFSET $3"
+        )
+        .bind(site.id)
+        .bind(per_page)
+        .bind((page - 1) * per_page)
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(media)
+    }
+
+    /// Take a file back out of the trash
+    #[tracing::instrument(name = "media.restore", skip_all, fields(site = %site.id, id = %id))]
+    pub async fn restore(&self, site: &Site, id: Uuid) -> Result<Media, ServiceError> {
+        let media: Media = sqlx::query_as(
+            "UPDATE blog_media SET trashed_at = NULL, trashed_by = NULL
+             WHERE id = $1 AND site_id = $2 AND trashed_at IS NOT NULL RETURNING *"
+        )
+        .bind(id)
+        .bind(site.id)
+        .fetch_optional(&self.db)
+        .await?
+        .ok_or_else(|| ServiceError::NotFound("Media not found in trash".into()))?;
+
+        Ok(self.with_signed_url(site, media))
+    }
+
+    /// Delete the files of media trashed longer than the retention period
+    ///
+    /// A row is dropped only after its file is gone; when the storage delete
+    /// fails the row stays in the trash and the next run tries again.
+    pub async fn purge_trash(&self) -> Result<TrashPurge, ServiceError> {
+        let expired: Vec<Media> = sqlx::query_as(
+            "SELECT * FROM blog_media
+             WHERE trashed_at < NOW() - make_interval(days => $1)
+             ORDER BY trashed_at LIMIT 500"
+        )
+        .bind(self.trash_retention_days as i32)
+        .fetch_all(&self.db)
+        .await?;
+
+        let mut report = TrashPurge::default();
+        for media in expired {
+            if let Err(e) = self.storage.delete(&media.storage_path()).await {
+                tracing::warn!(media_id = %media.id, "Failed to delete trashed media file: {}", e);
+                report.failed += 1;
+                continue;
+            }
+            sqlx::query("DELETE FROM blog_media WHERE id = $1 AND trashed_at IS NOT NULL")
+                .bind(media.id)
+                .execute(&self.db)
+                .await?;
+            report.purged += 1;
+        }
+
+        Ok(report)
+    }
+
+    /// Compare the media rows with the files under the media prefixes and
+    /// record what doesn't match in `media_orphans`
+    pub async fn scan_orphans(&self) -> Result<OrphanReport, ServiceError> {
+        let started = Utc::now();
+
+        let mut files = std::collections::HashSet::new();
+        for prefix in MEDIA_STORAGE_PREFIXES {
+            let listed = self
+                .storage
+                .list(prefix)
+                .await
+                .map_err(|e| ServiceError::Storage(e.to_string()))?;
+            files.extend(listed);
+        }
+
+        let rows: Vec<(Uuid, Uuid, String, MediaVisibility)> =
+            sqlx::query_as("SELECT id, site_id, filename, visibility FROM blog_media")
+                .fetch_all(&self.db)
+                .await?;
+
+        let mut found: Vec<(String, OrphanKind, Option<Uuid>, Option<Uuid>)> = Vec::new();
+        for (id, site_id, filename, visibility) in rows {
+            let path = media_storage_path(visibility, &filename);
+            if !files.remove(&path) {
+                found.push((path, OrphanKind::MissingFile, Some(id), Some(site_id)));
+            }
+        }
+        // What's left has no row
+        found.extend(files.into_iter().map(|path| (path, OrphanKind::UntrackedFile, None, None)));
+
+        let mut tx = self.db.begin().await?;
+        for (path, kind, media_id, site_id) in &found {
+            sqlx::query(
+                r#"INSERT INTO media_orphans (storage_path, kind, media_id, site_id, first_seen_at, last_seen_at)
+                   VALUES ($1, $2, $3, $4, $5, $5)
+                   ON CONFLICT (storage_path) DO UPDATE
+                   SET kind = $2, media_id = $3, site_id = $4, last_seen_at = $5"#
+            )
+            .bind(path)
+            .bind(kind)
+            .bind(media_id)
+            .bind(site_id)
+            .bind(started)
+            .execute(&mut *tx)
+            .await?;
+        }
+        // Resolved since the previous scan
+        sqlx::query("DELETE FROM media_orphans WHERE last_seen_at < $1")
+            .bind(started)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+
+        if !found.is_empty() {
+            tracing::warn!(orphans = found.len(), "Media orphan scan found mismatches");
+        }
+        self.orphan_report().await
+    }
+
+    /// Findings of the last orphan scan
+    pub async fn orphan_report(&self) -> Result<OrphanReport, ServiceError> {
+        let orphans: Vec<MediaOrphan> = sqlx::query_as("SELECT * FROM media_orphans ORDER BY kind, storage_path")
+            .fetch_all(&self.db)
+            .await?;
+        let count = |kind| orphans.iter().filter(|o| o.kind == kind).count() as i64;
+
+        Ok(OrphanReport {
+            missing_files: count(OrphanKind::MissingFile),
+            untracked_files: count(OrphanKind::UntrackedFile),
+            orphans,
+        })
+    }
 }
 
 /// Search service
@@ -708,7 +1566,7 @@ impl SearchService {
         Self { db }
     }
 
-    pub async fn search(&self, query: &SearchQuery) -> Result<SearchResult, ServiceError> {
+    pub async fn search(&self, site: &Site, query: &SearchQuery) -> Result<SearchResult, ServiceError> {
         let page = query.page.unwrap_or(1).max(1);
         let per_page = query.per_page.unwrap_or(10).min(100);
         let offset = (page - 1) * per_page;
@@ -716,7 +1574,7 @@ impl SearchService {
         // Full-text search using PostgreSQL
         let posts: Vec<Post> = sqlx::query_as(
             r#"SELECT * FROM blog_posts
-               WHERE status = 'published'
+               WHERE status = 'published' AND site_id = $4
                AND (
                    to_tsvector('english', title || ' ' || COALESCE(excerpt, '') || ' ' || content)
                    @@ plainto_tsquery('english', $1)
@@ -730,16 +1588,18 @@ impl SearchService {
         .bind(&query.q)
         .bind(per_page)
         .bind(offset)
+        .bind(site.id)
         .fetch_all(&self.db)
         .await?;
 
         let total: i64 = sqlx::query_scalar(
             r#"SELECT COUNT(*) FROM blog_posts
-               WHERE status = 'published'
+               WHERE status = 'published' AND site_id = $2
                AND to_tsvector('english', title || ' ' || COALESCE(excerpt, '') || ' ' || content)
                @@ plainto_tsquery('english', $1)"#
         )
         .bind(&query.q)
+        .bind(site.id)
         .fetch_one(&self.db)
         .await?;
 
@@ -0,0 +1,605 @@
+//! Settings API
+//!
+//! Exposes the `SettingsManager` over REST. Each namespace (an app or plugin
+//! id) registers the `[settings.schema.*]` table from its manifest; values are
+//! validated against the JSON Schema generated from it, and every change is
+//! written to `blog_settings_audit`. Values are stored per site; the default
+//! site uses the plain namespace names.
+//!
+//! Installed plugins' manifests are registered at activation, and the auth
+//! plugin's configuration keys as a read-only namespace: its values come from
+//! `rustpress.toml`. `GET /plugins/:id/settings-schema` returns a namespace's
+//! JSON Schema with UI hints (widgets, sections, help texts), enough for a
+//! generic admin frontend to render the form.
+
+use crate::extractors::User;
+use crate::services::ServiceError;
+use crate::sites::Site;
+use rustpress_apps::prelude::*;
+use rustpress_auth::Config;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sqlx::PgPool;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+/// Placeholder returned instead of stored secrets
+pub const MASKED_VALUE: &str = "********";
+
+/// Setting types supported in manifests
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SettingType {
+    Boolean,
+    Integer,
+    String,
+    Text,
+    Select,
+    Password,
+    Url,
+    Color,
+    Image,
+    Code,
+}
+
+/// One entry of a `[settings.schema]` table
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SettingDefinition {
+    pub setting_type: SettingType,
+    pub label: String,
+    #[serde(default)]
+    pub default: Option<Value>,
+    #[serde(default)]
+    pub required: bool,
+    #[serde(default)]
+    pub options: Vec<String>,
+    #[serde(default)]
+    pub section: Option<String>,
+    /// Help text shown with the field
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub placeholder: Option<String>,
+    /// Bounds of `integer` settings
+    #[serde(default)]
+    pub min: Option<i64>,
+    #[serde(default)]
+    pub max: Option<i64>,
+}
+
+impl SettingDefinition {
+    fn json_schema(&self) -> Value {
+        let mut schema = match self.setting_type {
+            SettingType::Boolean => serde_json::json!({ "type": "boolean" }),
+            SettingType::Integer => serde_json::json!({ "type": "integer" }),
+            SettingType::Select => serde_json::json!({ "type": "string", "enum": self.options }),
+            SettingType::Url => serde_json::json!({ "type": "string", "format": "uri" }),
+            SettingType::Color => serde_json::json!({ "type": "string", "pattern": "^#[0-9a-fA-F]{6}$" }),
+            _ => serde_json::json!({ "type": "string" }),
+        };
+        schema["title"] = Value::String(self.label.clone());
+        if let Some(ref description) = self.description {
+            schema["description"] = Value::String(description.clone());
+        }
+        if let Some(ref default) = self.default {
+            schema["default"] = default.clone();
+        }
+        if self.setting_type == SettingType::Integer {
+            if let Some(min) = self.min {
+                schema["minimum"] = min.into();
+            }
+            if let Some(max) = self.max {
+                schema["maximum"] = max.into();
+            }
+        }
+        if self.setting_type == SettingType::Password {
+            schema["writeOnly"] = Value::Bool(true);
+        }
+        schema
+    }
+
+    /// How a form renders the setting
+    fn widget(&self) -> &'static str {
+        match self.setting_type {
+            SettingType::Boolean => "toggle",
+            SettingType::Integer => "number",
+            SettingType::String => "text",
+            SettingType::Text => "textarea",
+            SettingType::Select => "select",
+            SettingType::Password => "password",
+            SettingType::Url => "url",
+            SettingType::Color => "color",
+            SettingType::Image => "media",
+            SettingType::Code => "code",
+        }
+    }
+}
+
+/// Where a namespace's values live
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SettingsSource {
+    /// The settings store, per site; changed over the API
+    #[default]
+    Store,
+    /// The configuration file (setting keys are config keys); read-only
+    Config,
+}
+
+/// Settings registered for one namespace
+#[derive(Debug, Clone, Serialize)]
+pub struct NamespaceSchema {
+    pub namespace: String,
+    /// Minimum role allowed to read and change these settings
+    pub required_role: String,
+    pub source: SettingsSource,
+    pub settings: BTreeMap<String, SettingDefinition>,
+}
+
+impl NamespaceSchema {
+    /// JSON Schema describing the namespace's settings document
+    pub fn json_schema(&self) -> Value {
+        let properties: Map<String, Value> = self
+            .settings
+            .iter()
+            .map(|(key, def)| (key.clone(), def.json_schema()))
+            .collect();
+        let required: Vec<&String> = self
+            .settings
+            .iter()
+            .filter(|(_, def)| def.required)
+            .map(|(key, _)| key)
+            .collect();
+
+        serde_json::json!({
+            "$schema": "http://json-schema.org/draft-07/schema#",
+            "title": self.namespace,
+            "type": "object",
+            "properties": properties,
+            "required": required,
+            "additionalProperties": false,
+            "readOnly": self.source == SettingsSource::Config,
+        })
+    }
+
+    /// Values of a config-backed namespace, defaults for keys `config`
+    /// doesn't set
+    pub fn config_values(&self, config: &Config) -> Result<Map<String, Value>, ServiceError> {
+        let mut values = Map::new();
+        for (key, def) in &self.settings {
+            let value: Option<Value> = config.get(key).map_err(|e| ServiceError::Storage(e.to_string()))?;
+            values.insert(key.clone(), value.or_else(|| def.default.clone()).unwrap_or(Value::Null));
+        }
+        Ok(values)
+    }
+
+    /// Form layout for the namespace: its sections in order (settings
+    /// without a section first, as `general`), each with its fields, and a
+    /// widget, label and help text per field
+    pub fn ui_schema(&self) -> Value {
+        let mut sections: Vec<(String, Vec<&String>)> = Vec::new();
+        let mut keys: Vec<(&String, &SettingDefinition)> = self.settings.iter().collect();
+        keys.sort_by_key(|(key, def)| (def.section.is_some(), def.section.clone(), *key));
+        for (key, def) in keys {
+            let section = def.section.clone().unwrap_or_else(|| "general".to_string());
+            match sections.last_mut() {
+                Some((id, fields)) if *id == section => fields.push(key),
+                _ => sections.push((section, vec![key])),
+            }
+        }
+
+        let read_only = self.source == SettingsSource::Config;
+        let fields: Map<String, Value> = self
+            .settings
+            .iter()
+            .map(|(key, def)| {
+                let mut field = serde_json::json!({
+                    "widget": def.widget(),
+                    "label": def.label,
+                    "required": def.required,
+                    "read_only": read_only,
+                });
+                if let Some(ref description) = def.description {
+                    field["help"] = Value::String(description.clone());
+                }
+                if let Some(ref placeholder) = def.placeholder {
+                    field["placeholder"] = Value::String(placeholder.clone());
+                }
+                if !def.options.is_empty() {
+                    field["options"] = serde_json::json!(def.options);
+                }
+                if def.setting_type == SettingType::Password {
+                    // Sending the placeholder back keeps the stored secret
+                    field["masked_value"] = Value::String(MASKED_VALUE.to_string());
+                }
+                (key.clone(), field)
+            })
+            .collect();
+
+        serde_json::json!({
+            "sections": sections
+                .into_iter()
+                .map(|(id, fields)| serde_json::json!({ "id": id, "title": title_case(&id), "fields": fields }))
+                .collect::<Vec<_>>(),
+            "fields": fields,
+            "read_only": read_only,
+        })
+    }
+
+    fn is_secret(&self, key: &str) -> bool {
+        self.settings
+            .get(key)
+            .map_or(false, |def| def.setting_type == SettingType::Password)
+    }
+}
+
+/// `privacy_rules` as `Privacy Rules`
+fn title_case(id: &str) -> String {
+    id.split(['_', '-', '.'])
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .map(|first| first.to_uppercase().chain(chars).collect::<String>())
+                .unwrap_or_default()
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Manifest layout: only the settings table is read
+#[derive(Debug, Deserialize)]
+struct ManifestSettings {
+    #[serde(default)]
+    settings: Option<SettingsTable>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SettingsTable {
+    #[serde(default)]
+    schema: BTreeMap<String, SettingDefinition>,
+}
+
+/// Registry of settings schemas by namespace
+#[derive(Default)]
+pub struct SettingsRegistry {
+    namespaces: RwLock<HashMap<String, NamespaceSchema>>,
+}
+
+impl SettingsRegistry {
+    pub async fn register(&self, schema: NamespaceSchema) {
+        self.namespaces
+            .write()
+            .await
+            .insert(schema.namespace.clone(), schema);
+    }
+
+    /// Register the `[settings.schema]` table of an app.toml/plugin.toml
+    pub async fn register_manifest(
+        &self,
+        namespace: &str,
+        manifest: &str,
+        required_role: &str,
+    ) -> Result<(), ServiceError> {
+        self.register(parse_manifest(namespace, manifest, required_role, SettingsSource::Store)?)
+            .await;
+        Ok(())
+    }
+
+    /// Register configuration keys described in the manifest format, shown
+    /// read-only with their values from the configuration file
+    pub async fn register_config(&self, namespace: &str, manifest: &str) -> Result<(), ServiceError> {
+        self.register(parse_manifest(namespace, manifest, "admin", SettingsSource::Config)?)
+            .await;
+        Ok(())
+    }
+
+    /// Register the manifests of installed plugins that have settings;
+    /// unreadable manifests are logged and skipped
+    pub async fn register_plugins(&self, plugins_dir: &Path, plugin_ids: impl IntoIterator<Item = String>) {
+        for id in plugin_ids {
+            let Ok(manifest) = std::fs::read_to_string(plugins_dir.join(&id).join("plugin.toml")) else {
+                continue;
+            };
+            match parse_manifest(&id, &manifest, "admin", SettingsSource::Store) {
+                Ok(schema) if !schema.settings.is_empty() => self.register(schema).await,
+                Ok(_) => {}
+                Err(e) => tracing::warn!(plugin = %id, "Skipped plugin settings: {}", e),
+            }
+        }
+    }
+
+    pub async fn get(&self, namespace: &str) -> Option<NamespaceSchema> {
+        self.namespaces.read().await.get(namespace).cloned()
+    }
+
+    pub async fn list(&self) -> Vec<NamespaceSchema> {
+        let mut schemas: Vec<_> = self.namespaces.read().await.values().cloned().collect();
+        schemas.sort_by(|a, b| a.namespace.cmp(&b.namespace));
+        schemas
+    }
+}
+
+fn parse_manifest(
+    namespace: &str,
+    manifest: &str,
+    required_role: &str,
+    source: SettingsSource,
+) -> Result<NamespaceSchema, ServiceError> {
+    let parsed: ManifestSettings = toml::from_str(manifest)
+        .map_err(|e| ServiceError::Validation(format!("Invalid manifest for {}: {}", namespace, e)))?;
+
+    Ok(NamespaceSchema {
+        namespace: namespace.to_string(),
+        required_role: required_role.to_string(),
+        source,
+        settings: parsed.settings.map(|s| s.schema).unwrap_or_default(),
+    })
+}
+
+/// Audit log entry for a settings change
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct SettingsAuditEntry {
+    pub id: Uuid,
+    pub site_id: Uuid,
+    pub namespace: String,
+    pub setting_key: String,
+    pub old_value: Option<Value>,
+    pub new_value: Option<Value>,
+    pub changed_by: Uuid,
+    pub source: String,
+    pub changed_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Settings export document for environment promotion
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SettingsExport {
+    pub exported_at: chrono::DateTime<chrono::Utc>,
+    pub namespaces: BTreeMap<String, Map<String, Value>>,
+}
+
+/// Settings service
+pub struct SettingsService {
+    db: PgPool,
+    settings: SettingsManager,
+    registry: SettingsRegistry,
+}
+
+impl SettingsService {
+    pub fn new(db: PgPool, settings: SettingsManager, registry: SettingsRegistry) -> Self {
+        Self { db, settings, registry }
+    }
+
+    pub fn registry(&self) -> &SettingsRegistry {
+        &self.registry
+    }
+
+    /// A namespace's JSON Schema and UI hints
+    pub async fn schema(&self, user: &User, namespace: &str) -> Result<Value, ServiceError> {
+        let schema = self.authorize(user, namespace).await?;
+        Ok(serde_json::json!({
+            "namespace": schema.namespace,
+            "source": schema.source,
+            "schema": schema.json_schema(),
+            "ui": schema.ui_schema(),
+        }))
+    }
+
+    /// Current values for a namespace, with defaults applied and secrets masked
+    pub async fn get(&self, site: &Site, user: &User, namespace: &str) -> Result<Map<String, Value>, ServiceError> {
+        let schema = self.authorize(user, namespace).await?;
+        let mut values = self.load(site, &schema).await?;
+
+        for (key, value) in values.iter_mut() {
+            if schema.is_secret(key) && !value.is_null() {
+                *value = Value::String(MASKED_VALUE.to_string());
+            }
+        }
+        Ok(values)
+    }
+
+    /// Merge `changes` into a namespace after schema validation
+    pub async fn update(
+        &self,
+        site: &Site,
+        user: &User,
+        namespace: &str,
+        changes: Map<String, Value>,
+    ) -> Result<Map<String, Value>, ServiceError> {
+        let schema = self.authorize(user, namespace).await?;
+        self.apply(site, &schema, user, changes, "api").await?;
+        self.get(site, user, namespace).await
+    }
+
+    /// Recent changes for a namespace
+    pub async fn audit_log(
+        &self,
+        site: &Site,
+        user: &User,
+        namespace: &str,
+        limit: i64,
+    ) -> Result<Vec<SettingsAuditEntry>, ServiceError> {
+        self.authorize(user, namespace).await?;
+
+        let entries = sqlx::query_as(
+            "SELECT * FROM blog_settings_audit WHERE namespace = $1 AND site_id = $3 ORDER BY changed_at DESC LIMIT $2"
+        )
+        .bind(namespace)
+        .bind(limit.clamp(1, 500))
+        .bind(site.id)
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(entries)
+    }
+
+    /// Export every namespace the user may manage (secrets are omitted)
+    pub async fn export(&self, site: &Site, user: &User) -> Result<SettingsExport, ServiceError> {
+        let mut namespaces = BTreeMap::new();
+        for schema in self.registry.list().await {
+            if !role_allows(user, &schema.required_role) || schema.source == SettingsSource::Config {
+                continue;
+            }
+            let mut values = self.load(site, &schema).await?;
+            values.retain(|key, _| !schema.is_secret(key));
+            namespaces.insert(schema.namespace.clone(), values);
+        }
+
+        Ok(SettingsExport {
+            exported_at: chrono::Utc::now(),
+            namespaces,
+        })
+    }
+
+    /// Import an export document; every namespace is validated before any write
+    pub async fn import(&self, site: &Site, user: &User, export: SettingsExport) -> Result<Vec<String>, ServiceError> {
+        let mut plan = Vec::new();
+        for (namespace, values) in export.namespaces {
+            let schema = self.authorize(user, &namespace).await?;
+            writable(&schema)?;
+            let mut merged = self.load(site, &schema).await?;
+            merged.extend(values.clone());
+            validate(&schema, &merged)?;
+            plan.push((schema, values));
+        }
+
+        let mut imported = Vec::new();
+        for (schema, values) in plan {
+            self.apply(site, &schema, user, values, "import").await?;
+            imported.push(schema.namespace);
+        }
+        Ok(imported)
+    }
+
+    async fn authorize(&self, user: &User, namespace: &str) -> Result<NamespaceSchema, ServiceError> {
+        let schema = self
+            .registry
+            .get(namespace)
+            .await
+            .ok_or_else(|| ServiceError::NotFound(format!("Settings namespace not found: {}", namespace)))?;
+
+        if !role_allows(user, &schema.required_role) {
+            return Err(ServiceError::PermissionDenied);
+        }
+        Ok(schema)
+    }
+
+    async fn load(&self, site: &Site, schema: &NamespaceSchema) -> Result<Map<String, Value>, ServiceError> {
+        if schema.source == SettingsSource::Config {
+            let config = Config::load().map_err(|e| ServiceError::Storage(e.to_string()))?;
+            return schema.config_values(&config);
+        }
+
+        let namespace = site.settings_namespace(&schema.namespace);
+        let mut values = Map::new();
+        for (key, def) in &schema.settings {
+            let stored: Option<Value> = self
+                .settings
+                .get(&namespace, key)
+                .await
+                .map_err(|e| ServiceError::Storage(e.to_string()))?;
+            values.insert(key.clone(), stored.or_else(|| def.default.clone()).unwrap_or(Value::Null));
+        }
+        Ok(values)
+    }
+
+    async fn apply(
+        &self,
+        site: &Site,
+        schema: &NamespaceSchema,
+        user: &User,
+        mut changes: Map<String, Value>,
+        source: &str,
+    ) -> Result<(), ServiceError> {
+        writable(schema)?;
+
+        // Echoed placeholders mean "keep the current secret"
+        changes.retain(|key, value| !(schema.is_secret(key) && value.as_str() == Some(MASKED_VALUE)));
+
+        let current = self.load(site, schema).await?;
+        let mut merged = current.clone();
+        merged.extend(changes.clone());
+        validate(schema, &merged)?;
+
+        for (key, new_value) in changes {
+            let old_value = current.get(&key).cloned().unwrap_or(Value::Null);
+            if old_value == new_value {
+                continue;
+            }
+
+            self.settings
+                .set(&site.settings_namespace(&schema.namespace), &key, new_value.clone())
+                .await
+                .map_err(|e| ServiceError::Storage(e.to_string()))?;
+
+            let (old_logged, new_logged) = if schema.is_secret(&key) {
+                (Value::String(MASKED_VALUE.into()), Value::String(MASKED_VALUE.into()))
+            } else {
+                (old_value, new_value)
+            };
+
+            sqlx::query(
+                r#"INSERT INTO blog_settings_audit
+                   (site_id, namespace, setting_key, old_value, new_value, changed_by, source)
+                   VALUES ($1, $2, $3, $4, $5, $6, $7)"#
+            )
+            .bind(site.id)
+            .bind(&schema.namespace)
+            .bind(&key)
+            .bind(old_logged)
+            .bind(new_logged)
+            .bind(user.id)
+            .bind(source)
+            .execute(&self.db)
+            .await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Refuse changes to namespaces read from the configuration file
+fn writable(schema: &NamespaceSchema) -> Result<(), ServiceError> {
+    match schema.source {
+        SettingsSource::Store => Ok(()),
+        SettingsSource::Config => Err(ServiceError::Validation(format!(
+            "{} settings are set in rustpress.toml, not over the API",
+            schema.namespace
+        ))),
+    }
+}
+
+/// Validate a full settings document against the namespace schema
+fn validate(schema: &NamespaceSchema, document: &Map<String, Value>) -> Result<(), ServiceError> {
+    let json_schema = schema.json_schema();
+    let compiled = jsonschema::JSONSchema::compile(&json_schema)
+        .map_err(|e| ServiceError::Validation(format!("Invalid settings schema: {}", e)))?;
+
+    // Unset optional values are stored as null; don't validate them
+    let document: Map<String, Value> = document
+        .iter()
+        .filter(|(_, v)| !v.is_null())
+        .map(|(k, v)| (k.clone(), v.clone()))
+        .collect();
+    let instance = Value::Object(document);
+
+    if let Err(errors) = compiled.validate(&instance) {
+        let messages: Vec<String> = errors
+            .map(|e| format!("{}: {}", e.instance_path, e))
+            .collect();
+        return Err(ServiceError::Validation(messages.join("; ")));
+    }
+    Ok(())
+}
+
+/// Whether the user's role meets the required role
+fn role_allows(user: &User, required_role: &str) -> bool {
+    match required_role {
+        "author" => user.can_publish(),
+        "editor" => user.can_moderate(),
+        _ => user.is_admin(),
+    }
+}
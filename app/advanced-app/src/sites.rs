@@ -0,0 +1,509 @@
+//! Multisite
+//!
+//! One deployment can serve several blogs. `resolve_site` maps each request to
+//! a row in `blog_sites` by its Host header, then by a leading path prefix,
+//! falling back to the default site. Handlers receive the site through the
+//! `CurrentSite` extractor and pass it to the services, which scope queries by
+//! `site_id` and prefix cache keys with `Site::cache_key`.
+
+use crate::auth::AccessTokenClaims;
+use crate::models::{ProblemDetails, VoteStyle};
+use crate::services::ServiceError;
+use crate::timezones;
+use crate::BlogServices;
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Request, State},
+    http::{header, request::Parts, StatusCode, Uri},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use utoipa::ToSchema;
+use uuid::Uuid;
+use validator::Validate;
+
+/// Roles a user can hold on a site
+pub const SITE_ROLES: &[&str] = &["subscriber", "author", "editor", "admin"];
+
+// ============================================
+// Models
+// ============================================
+
+/// Per-site overrides of the app configuration
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+#[serde(default)]
+pub struct SiteConfig {
+    /// Tagline exposed to templates and used as the feed description
+    pub tagline: Option<String>,
+    /// Theme to render pages with instead of the app's active theme
+    pub theme: Option<String>,
+    /// Default page size for post lists
+    pub posts_per_page: Option<i64>,
+    /// Hold guest comments for moderation (default: true)
+    pub comments_require_moderation: Option<bool>,
+    /// Accept comments from visitors who aren't signed in (default: true)
+    pub allow_guest_comments: Option<bool>,
+    /// `up_down`, `hearts` or `off` (default: `[app.comment_votes] style`)
+    pub comment_votes: Option<VoteStyle>,
+    /// Display preference defaults over `[app.preferences.defaults]`, e.g.
+    /// `{"color_scheme": "dark"}`
+    pub preferences: BTreeMap<String, serde_json::Value>,
+    /// IANA time zone, e.g. `Europe/Berlin`, for users who didn't choose one
+    /// (default: the `timezone` preference default)
+    pub timezone: Option<String>,
+    /// Have a vision model suggest alt text for uploaded images (default:
+    /// false; needs `[app.alt_text]` `enabled`)
+    pub alt_text_suggestions: bool,
+}
+
+impl SiteConfig {
+    fn check(&self) -> Result<(), ServiceError> {
+        if let Some(timezone) = &self.timezone {
+            timezones::parse_timezone(timezone).map_err(ServiceError::Validation)?;
+        }
+        Ok(())
+    }
+}
+
+/// A blog served by this deployment
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct Site {
+    pub id: Uuid,
+    pub slug: String,
+    pub name: String,
+    /// Host name the site answers on, without port
+    pub host: Option<String>,
+    /// Leading path segment the site answers on, e.g. `/news`
+    pub path_prefix: Option<String>,
+    pub is_default: bool,
+    #[sqlx(json)]
+    pub config: SiteConfig,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Site {
+    /// Prefix a cache key so sites never share cached entries
+    pub fn cache_key(&self, key: &str) -> String {
+        format!("site:{}:{}", self.id, key)
+    }
+
+    /// Link to `path` on this site, including its path prefix
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{}", self.path_prefix.as_deref().unwrap_or(""), path)
+    }
+
+    /// Settings storage namespace; the default site keeps the plain names
+    pub fn settings_namespace(&self, namespace: &str) -> String {
+        if self.is_default {
+            namespace.to_string()
+        } else {
+            format!("{}@{}", namespace, self.slug)
+        }
+    }
+}
+
+/// A user's membership of a site
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct SiteMember {
+    pub site_id: Uuid,
+    pub user_id: Uuid,
+    pub role: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Create site request
+#[derive(Debug, Clone, Deserialize, Validate, ToSchema)]
+pub struct CreateSiteRequest {
+    #[validate(length(min = 1, max = 60))]
+    pub slug: String,
+
+    #[validate(length(min = 1, max = 200))]
+    pub name: String,
+
+    #[validate(length(min = 1, max = 255))]
+    pub host: Option<String>,
+
+    #[validate(length(min = 2, max = 100))]
+    pub path_prefix: Option<String>,
+
+    pub config: Option<SiteConfig>,
+}
+
+/// Update site request; omitted fields are kept
+#[derive(Debug, Clone, Deserialize, Validate, ToSchema)]
+pub struct UpdateSiteRequest {
+    #[validate(length(min = 1, max = 200))]
+    pub name: Option<String>,
+
+    #[validate(length(min = 1, max = 255))]
+    pub host: Option<String>,
+
+    #[validate(length(min = 2, max = 100))]
+    pub path_prefix: Option<String>,
+
+    pub config: Option<SiteConfig>,
+}
+
+/// Add or change a member's role
+#[derive(Debug, Clone, Deserialize, Validate, ToSchema)]
+pub struct SiteMemberRequest {
+    #[validate(length(min = 1, max = 20))]
+    pub role: String,
+}
+
+// ============================================
+// Site Service
+// ============================================
+
+/// Site registry and membership service
+///
+/// Sites are kept in memory for request resolution and reloaded whenever one
+/// is created, changed or removed through this service.
+pub struct SiteService {
+    db: PgPool,
+    sites: RwLock<Vec<Arc<Site>>>,
+}
+
+impl SiteService {
+    /// Load all sites from the database
+    pub async fn load(db: PgPool) -> Result<Self, ServiceError> {
+        let service = Self {
+            db,
+            sites: RwLock::new(Vec::new()),
+        };
+        service.reload().await?;
+        Ok(service)
+    }
+
+    /// Re-read sites (e.g. after editing `blog_sites` directly)
+    pub async fn reload(&self) -> Result<(), ServiceError> {
+        let sites: Vec<Site> = sqlx::query_as("SELECT * FROM blog_sites ORDER BY is_default DESC, name ASC")
+            .fetch_all(&self.db)
+            .await?;
+
+        *self.sites.write().await = sites.into_iter().map(Arc::new).collect();
+        Ok(())
+    }
+
+    /// Site for a request, with the length of the path prefix to strip
+    pub async fn resolve(&self, host: Option<&str>, path: &str) -> Option<(Arc<Site>, usize)> {
+        let sites = self.sites.read().await;
+        resolve(&sites, host, path).map(|(site, prefix_len)| (site.clone(), prefix_len))
+    }
+
+    pub async fn list(&self) -> Vec<Site> {
+        self.sites.read().await.iter().map(|site| site.as_ref().clone()).collect()
+    }
+
+    pub async fn get(&self, id: Uuid) -> Option<Arc<Site>> {
+        self.sites.read().await.iter().find(|site| site.id == id).cloned()
+    }
+
+    pub async fn create(&self, req: CreateSiteRequest) -> Result<Site, ServiceError> {
+        let host = req.host.as_deref().map(normalize_host);
+        let path_prefix = req.path_prefix.as_deref().map(normalize_prefix).transpose()?;
+        if let Some(config) = &req.config {
+            config.check()?;
+        }
+
+        let site: Site = sqlx::query_as(
+            r#"INSERT INTO blog_sites (slug, name, host, path_prefix, config)
+               VALUES ($1, $2, $3, $4, $5)
+               RETURNING *"#
+        )
+        .bind(slug::slugify(&req.slug))
+        .bind(&req.name)
+        .bind(host)
+        .bind(path_prefix)
+        .bind(sqlx::types::Json(req.config.unwrap_or_default()))
+        .fetch_one(&self.db)
+        .await?;
+
+        self.reload().await?;
+        Ok(site)
+    }
+
+    pub async fn update(&self, id: Uuid, req: UpdateSiteRequest) -> Result<Site, ServiceError> {
+        let host = req.host.as_deref().map(normalize_host);
+        let path_prefix = req.path_prefix.as_deref().map(normalize_prefix).transpose()?;
+        if let Some(config) = &req.config {
+            config.check()?;
+        }
+
+        let site: Site = sqlx::query_as(
+            r#"UPDATE blog_sites SET
+               name = COALESCE($2, name),
+               host = COALESCE($3, host),
+               path_prefix = COALESCE($4, path_prefix),
+               config = COALESCE($5, config),
+               updated_at = NOW()
+               WHERE id = $1
+               RETURNING *"#
+        )
+        .bind(id)
+        .bind(&req.name)
+        .bind(host)
+        .bind(path_prefix)
+        .bind(req.config.map(sqlx::types::Json))
+        .fetch_optional(&self.db)
+        .await?
+        .ok_or_else(|| ServiceError::NotFound("Site not found".into()))?;
+
+        self.reload().await?;
+        Ok(site)
+    }
+
+    /// Delete a site and everything scoped to it; the default site can't be removed
+    pub async fn delete(&self, id: Uuid) -> Result<(), ServiceError> {
+        let result = sqlx::query("DELETE FROM blog_sites WHERE id = $1 AND NOT is_default")
+            .bind(id)
+            .execute(&self.db)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(ServiceError::Validation("Site not found or is the default site".into()));
+        }
+
+        self.reload().await?;
+        Ok(())
+    }
+
+    pub async fn members(&self, site_id: Uuid) -> Result<Vec<SiteMember>, ServiceError> {
+        let members = sqlx::query_as(
+            "SELECT * FROM blog_site_members WHERE site_id = $1 ORDER BY created_at ASC"
+        )
+        .bind(site_id)
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(members)
+    }
+
+    /// The user's role on a site, if they are a member
+    pub async fn membership(&self, site_id: Uuid, user_id: Uuid) -> Result<Option<String>, ServiceError> {
+        let role = sqlx::query_scalar(
+            "SELECT role FROM blog_site_members WHERE site_id = $1 AND user_id = $2"
+        )
+        .bind(site_id)
+        .bind(user_id)
+        .fetch_optional(&self.db)
+        .await?;
+
+        Ok(role)
+    }
+
+    /// The role a user acts with on a site: their membership role there,
+    /// falling back to the account's role on the default site only. Admins
+    /// stay admins everywhere.
+    pub async fn role(&self, site: &Site, user_id: Uuid, account_role: &str) -> Result<Option<String>, ServiceError> {
+        if account_role == "admin" {
+            return Ok(Some(account_role.to_string()));
+        }
+        let role = self.membership(site.id, user_id).await?;
+        Ok(role.or_else(|| site.is_default.then(|| account_role.to_string())))
+    }
+
+    pub async fn set_member(&self, site_id: Uuid, user_id: Uuid, role: &str) -> Result<SiteMember, ServiceError> {
+        if !SITE_ROLES.contains(&role) {
+            return Err(ServiceError::Validation(format!(
+                "Unknown role '{}'. Allowed roles: {:?}",
+                role, SITE_ROLES
+            )));
+        }
+
+        let member = sqlx::query_as(
+            r#"INSERT INTO blog_site_members (site_id, user_id, role)
+               VALUES ($1, $2, $3)
+               ON CONFLICT (site_id, user_id) DO UPDATE SET role = EXCLUDED.role
+               RETURNING *"#
+        )
+        .bind(site_id)
+        .bind(user_id)
+        .bind(role)
+        .fetch_one(&self.db)
+        .await?;
+
+        Ok(member)
+    }
+
+    pub async fn remove_member(&self, site_id: Uuid, user_id: Uuid) -> Result<(), ServiceError> {
+        let result = sqlx::query("DELETE FROM blog_site_members WHERE site_id = $1 AND user_id = $2")
+            .bind(site_id)
+            .bind(user_id)
+            .execute(&self.db)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(ServiceError::NotFound("Member not found".into()));
+        }
+        Ok(())
+    }
+}
+
+/// Pick the site for a host and path
+///
+/// Sites bound to the request's host are preferred over host-less sites; among
+/// those the longest matching path prefix wins, then a site without a prefix,
+/// then the default site.
+fn resolve<'a>(sites: &'a [Arc<Site>], host: Option<&str>, path: &str) -> Option<(&'a Arc<Site>, usize)> {
+    let host = host.map(normalize_host);
+
+    let mut candidates: Vec<&Arc<Site>> = sites
+        .iter()
+        .filter(|s| s.host.is_some() && s.host == host)
+        .collect();
+    if candidates.is_empty() {
+        candidates = sites.iter().filter(|s| s.host.is_none()).collect();
+    }
+
+    let by_prefix = candidates
+        .iter()
+        .filter_map(|s| {
+            let prefix = s.path_prefix.as_deref()?;
+            let rest = path.strip_prefix(prefix)?;
+            (rest.is_empty() || rest.starts_with('/')).then_some((*s, prefix.len()))
+        })
+        .max_by_key(|(_, len)| *len);
+
+    by_prefix
+        .or_else(|| {
+            candidates
+                .iter()
+                .filter(|s| s.path_prefix.is_none())
+                .max_by_key(|s| s.is_default)
+                .map(|s| (*s, 0))
+        })
+        .or_else(|| sites.iter().find(|s| s.is_default).map(|s| (s, 0)))
+}
+
+/// Lowercase and drop the port
+fn normalize_host(host: &str) -> String {
+    let host = host.trim().to_ascii_lowercase();
+    match host.rsplit_once(':') {
+        Some((name, port)) if port.chars().all(|c| c.is_ascii_digit()) => name.to_string(),
+        _ => host,
+    }
+}
+
+/// `news`, `/news/` -> `/news`; prefixes are a single path segment
+fn normalize_prefix(prefix: &str) -> Result<String, ServiceError> {
+    let segment = prefix.trim().trim_matches('/');
+    if segment.is_empty() || segment.contains('/') {
+        return Err(ServiceError::Validation(format!(
+            "Path prefix must be a single path segment, got '{}'",
+            prefix
+        )));
+    }
+    Ok(format!("/{}", segment))
+}
+
+// ============================================
+// Middleware and Extractor
+// ============================================
+
+/// The site the current request was resolved to
+#[derive(Debug, Clone)]
+pub struct CurrentSite(pub Arc<Site>);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for CurrentSite
+where
+    S: Send + Sync,
+{
+    type Rejection = ProblemDetails;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts.extensions.get::<CurrentSite>().cloned().ok_or_else(|| {
+            tracing::error!("CurrentSite extracted on a route without the resolve_site middleware");
+            ProblemDetails::new(StatusCode::INTERNAL_SERVER_ERROR, "site_not_resolved")
+                .detail("Request was not resolved to a site")
+        })
+    }
+}
+
+/// Resolve the request's site and strip its path prefix
+///
+/// Must wrap the router from the outside (see `BlogApp::routes`) so the
+/// stripped path is what gets routed.
+pub async fn resolve_site(
+    State(services): State<Arc<BlogServices>>,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    let host = req
+        .headers()
+        .get(header::HOST)
+        .and_then(|h| h.to_str().ok())
+        .or_else(|| req.uri().host())
+        .map(String::from);
+
+    let Some((site, prefix_len)) = services.sites.resolve(host.as_deref(), req.uri().path()).await else {
+        return ProblemDetails::new(StatusCode::NOT_FOUND, "site_not_found")
+            .detail("No site is configured for this host")
+            .into_response();
+    };
+
+    if prefix_len > 0 {
+        if let Some(uri) = strip_path_prefix(req.uri(), prefix_len) {
+            *req.uri_mut() = uri;
+        }
+    }
+
+    req.extensions_mut().insert(CurrentSite(site));
+    next.run(req).await
+}
+
+/// Require the authenticated user to be a member of the current site
+///
+/// Runs after `require_auth`. Admins and the default site are exempt.
+pub async fn require_member(
+    State(services): State<Arc<BlogServices>>,
+    req: Request,
+    next: Next,
+) -> Result<Response, Response> {
+    let site = req.extensions().get::<CurrentSite>().map(|s| s.0.clone());
+    let user = req
+        .extensions()
+        .get::<AccessTokenClaims>()
+        .map(|claims| (claims.sub, claims.role == "admin"));
+
+    if let (Some(site), Some((user_id, false))) = (site, user) {
+        if !site.is_default {
+            let role = services
+                .sites
+                .membership(site.id, user_id)
+                .await
+                .map_err(IntoResponse::into_response)?;
+
+            if role.is_none() {
+                return Err(ProblemDetails::new(StatusCode::FORBIDDEN, "not_site_member")
+                    .detail(format!("You are not a member of site '{}'", site.slug))
+                    .into_response());
+            }
+        }
+    }
+
+    Ok(next.run(req).await)
+}
+
+fn strip_path_prefix(uri: &Uri, prefix_len: usize) -> Option<Uri> {
+    let path = match &uri.path()[prefix_len..] {
+        "" => "/",
+        rest => rest,
+    };
+    let path_and_query = match uri.query() {
+        Some(query) => format!("{}?{}", path, query),
+        None => path.to_string(),
+    };
+
+    let mut parts = uri.clone().into_parts();
+    parts.path_and_query = Some(path_and_query.parse().ok()?);
+    Uri::from_parts(parts).ok()
+}
@@ -0,0 +1,275 @@
+//! Template Functions
+//!
+//! Functions theme templates call while rendering, e.g.
+//! `{% for post in recent_posts(n=5) %}`. They're registered in a
+//! `TemplateFunctionRegistry` (built-ins plus any contributed by plugins,
+//! such as a `menu(name="main")` or an `analytics_counter(path=...)`), and
+//! take Tera's named arguments.
+//!
+//! Functions run sandboxed: each call gets `timeout_ms` and a page's calls
+//! together get `render_budget_ms`. A call that fails, panics or runs out
+//! of time renders as nothing and is logged, so a broken plugin can't take
+//! pages down. Output is escaped like any other value unless the function
+//! declares it `Escaping::Html`, in which case it must escape what it
+//! includes itself.
+
+use crate::extractors::User;
+use crate::services::ServiceError;
+use crate::sites::Site;
+use axum::async_trait;
+use rustpress_apps::prelude::*;
+use serde::Deserialize;
+use serde_json::Value;
+use sqlx::PgPool;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tera::Tera;
+use tokio::runtime::Handle;
+use tokio::sync::RwLock;
+
+/// Functions built into Tera, which plugins can't replace
+const TERA_FUNCTIONS: &[&str] = &["range", "now", "throw", "get_random", "get_env"];
+
+/// `[app.template_functions]` settings
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct TemplateFunctionConfig {
+    /// Longest a single call may run
+    pub timeout_ms: u64,
+    /// Longest all calls while rendering one page may run together
+    pub render_budget_ms: u64,
+}
+
+impl Default for TemplateFunctionConfig {
+    fn default() -> Self {
+        Self {
+            timeout_ms: 200,
+            render_budget_ms: 1000,
+        }
+    }
+}
+
+/// How a function's output is written into the page
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Escaping {
+    /// Escaped like any other value
+    Text,
+    /// Written as is; the function escapes what it includes
+    Html,
+}
+
+/// What a function may use while the page renders
+#[derive(Clone)]
+pub struct TemplateFunctionContext {
+    pub db: PgPool,
+    pub site: Arc<Site>,
+    /// The signed-in reader, if any
+    pub user: Option<User>,
+    pub hooks: Arc<HookRegistry>,
+}
+
+/// A function templates can call
+#[async_trait]
+pub trait TemplateFunction: Send + Sync {
+    /// Name templates call it by, e.g. `recent_posts`
+    fn name(&self) -> &'static str;
+
+    fn escaping(&self) -> Escaping {
+        Escaping::Text
+    }
+
+    async fn call(&self, ctx: &TemplateFunctionContext, args: &HashMap<String, Value>) -> Result<Value, ServiceError>;
+}
+
+/// Registry of template functions
+pub struct TemplateFunctionRegistry {
+    functions: RwLock<HashMap<String, Arc<dyn TemplateFunction>>>,
+    /// Bumped on every change, so themes pick functions up again
+    version: AtomicU64,
+    config: TemplateFunctionConfig,
+}
+
+impl TemplateFunctionRegistry {
+    /// Registry pre-populated with the built-in functions
+    pub fn with_builtins(config: TemplateFunctionConfig) -> Self {
+        let mut functions: HashMap<String, Arc<dyn TemplateFunction>> = HashMap::new();
+        for function in [Arc::new(RecentPostsFunction) as Arc<dyn TemplateFunction>] {
+            functions.insert(function.name().to_string(), function);
+        }
+        Self {
+            functions: RwLock::new(functions),
+            version: AtomicU64::new(0),
+            config,
+        }
+    }
+
+    /// Register a function (plugins call this on activation)
+    pub async fn register(&self, function: Arc<dyn TemplateFunction>) {
+        let name = function.name().to_string();
+        if TERA_FUNCTIONS.contains(&name.as_str()) {
+            tracing::warn!("Template function '{}' is built into Tera and can't be replaced", name);
+            return;
+        }
+        if self.functions.write().await.insert(name.clone(), function).is_some() {
+            tracing::warn!("Template function '{}' was replaced", name);
+        }
+        self.version.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Remove a function (plugins call this on deactivation)
+    pub async fn unregister(&self, name: &str) {
+        if self.functions.write().await.remove(name).is_some() {
+            self.version.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    /// Changes so far; themes installed at an older version install again
+    pub fn version(&self) -> u64 {
+        self.version.load(Ordering::SeqCst)
+    }
+
+    /// Names of the registered functions
+    pub async fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.functions.read().await.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// Make the functions callable from `tera`'s templates
+    pub async fn install(&self, tera: &mut Tera) {
+        let per_call = Duration::from_millis(self.config.timeout_ms);
+        for (name, function) in self.functions.read().await.iter() {
+            tera.register_function(
+                name,
+                Sandboxed {
+                    function: function.clone(),
+                    per_call,
+                },
+            );
+        }
+    }
+
+    /// Run `render` (which renders with installed functions) on the current
+    /// thread with `ctx` available to the functions; blocks, so call it from
+    /// a blocking task
+    pub fn scoped<T>(&self, ctx: TemplateFunctionContext, handle: Handle, render: impl FnOnce() -> T) -> T {
+        let scope = RenderScope {
+            ctx: Arc::new(ctx),
+            handle,
+            deadline: Instant::now() + Duration::from_millis(self.config.render_budget_ms),
+        };
+        let previous = SCOPE.with(|current| current.borrow_mut().replace(scope));
+        let output = render();
+        SCOPE.with(|current| *current.borrow_mut() = previous);
+        output
+    }
+}
+
+/// The page being rendered on this thread
+struct RenderScope {
+    ctx: Arc<TemplateFunctionContext>,
+    handle: Handle,
+    /// When the page's budget for function calls runs out
+    deadline: Instant,
+}
+
+thread_local! {
+    static SCOPE: RefCell<Option<RenderScope>> = const { RefCell::new(None) };
+}
+
+/// A registered function as Tera calls it
+struct Sandboxed {
+    function: Arc<dyn TemplateFunction>,
+    per_call: Duration,
+}
+
+impl tera::Function for Sandboxed {
+    fn call(&self, args: &HashMap<String, Value>) -> tera::Result<Value> {
+        let name = self.function.name();
+        let scope = SCOPE.with(|current| {
+            current
+                .borrow()
+                .as_ref()
+                .map(|scope| (scope.ctx.clone(), scope.handle.clone(), scope.deadline))
+        });
+        let Some((ctx, handle, deadline)) = scope else {
+            return Err(tera::Error::msg(format!("Template function '{}' called outside of a page render", name)));
+        };
+
+        let limit = self.per_call.min(deadline.saturating_duration_since(Instant::now()));
+        if limit.is_zero() {
+            tracing::warn!(site = %ctx.site.id, "Template function '{}' skipped: the page's time budget is spent", name);
+            return Ok(Value::String(String::new()));
+        }
+
+        let function = self.function.clone();
+        let args = args.clone();
+        let site_id = ctx.site.id;
+        // Spawned, so a panic or a timeout stays in the task
+        let outcome = handle.block_on(async move {
+            let mut task = tokio::spawn(async move { function.call(&ctx, &args).await });
+            match tokio::time::timeout(limit, &mut task).await {
+                Ok(joined) => Some(joined),
+                Err(_) => {
+                    task.abort();
+                    None
+                }
+            }
+        });
+
+        match outcome {
+            Some(Ok(Ok(value))) => return Ok(value),
+            Some(Ok(Err(e))) => tracing::warn!(site = %site_id, "Template function '{}' failed: {}", name, e),
+            Some(Err(e)) => tracing::warn!(site = %site_id, "Template function '{}' panicked: {}", name, e),
+            None => tracing::warn!(site = %site_id, "Template function '{}' timed out after {:?}", name, limit),
+        }
+        Ok(Value::String(String::new()))
+    }
+
+    fn is_safe(&self) -> bool {
+        self.function.escaping() == Escaping::Html
+    }
+}
+
+// ============================================
+// Built-in Functions
+// ============================================
+
+/// Latest published posts: `recent_posts(n=5)` gives up to 20 posts with
+/// `title`, `url` and `published_at`
+pub struct RecentPostsFunction;
+
+#[async_trait]
+impl TemplateFunction for RecentPostsFunction {
+    fn name(&self) -> &'static str {
+        "recent_posts"
+    }
+
+    async fn call(&self, ctx: &TemplateFunctionContext, args: &HashMap<String, Value>) -> Result<Value, ServiceError> {
+        let count = args.get("n").and_then(Value::as_i64).unwrap_or(5).clamp(1, 20);
+
+        let posts: Vec<(String, String, Option<chrono::DateTime<chrono::Utc>>)> = sqlx::query_as(
+            "SELECT title, slug, published_at FROM blog_posts
+             WHERE status = 'published' AND site_id = $2 AND required_level IS NULL
+             ORDER BY published_at DESC LIMIT $1",
+        )
+        .bind(count)
+        .bind(ctx.site.id)
+        .fetch_all(&ctx.db)
+        .await?;
+
+        Ok(posts
+            .into_iter()
+            .map(|(title, slug, published_at)| {
+                serde_json::json!({
+                    "title": title,
+                    "url": ctx.site.url(&format!("/read/{}", slug)),
+                    "published_at": published_at,
+                })
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,474 @@
+//! Theme Rendering
+//!
+//! Server-side HTML rendering with Tera. Templates are resolved through a
+//! WordPress-style hierarchy inside the active theme, falling back to the
+//! bundled default theme. Sites may pick their own theme in their
+//! configuration; each theme is loaded once and kept until `reload`.
+//! Templates can call the functions in the
+//! [`TemplateFunctionRegistry`](crate::template_functions), which are
+//! installed again whenever the registry changes.
+
+use crate::amp::{self, AmpContent};
+use crate::extractors::User;
+use crate::models::*;
+use crate::preferences::PreferenceService;
+use crate::services::ServiceError;
+use crate::sites::Site;
+use crate::template_functions::{TemplateFunctionContext, TemplateFunctionRegistry};
+use crate::widgets::WidgetService;
+use rustpress_apps::prelude::*;
+use serde::Serialize;
+use sqlx::PgPool;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tera::{Context, Tera};
+use tokio::sync::RwLock;
+
+/// Templates shipped with the app, used when the active theme lacks them
+const DEFAULT_TEMPLATES: &[(&str, &str)] = &[
+    ("base.html", include_str!("../themes/default/templates/base.html")),
+    ("index.html", include_str!("../themes/default/templates/index.html")),
+    ("archive.html", include_str!("../themes/default/templates/archive.html")),
+    ("single.html", include_str!("../themes/default/templates/single.html")),
+    ("page.html", include_str!("../themes/default/templates/page.html")),
+    ("404.html", include_str!("../themes/default/templates/404.html")),
+    ("amp.html", include_str!("../themes/default/templates/amp.html")),
+];
+
+/// Theme configuration
+#[derive(Debug, Clone)]
+pub struct ThemeConfig {
+    /// Directory containing one sub-directory per theme
+    pub themes_dir: PathBuf,
+    /// Theme for sites that don't configure their own
+    pub active_theme: String,
+    /// Link post pages to their AMP version
+    pub amp: bool,
+}
+
+/// Kind of page being rendered, used to pick a template
+#[derive(Debug, Clone)]
+pub enum TemplateKind {
+    Home,
+    Single { slug: String },
+    Page { slug: String },
+    Category { slug: String },
+    Tag { slug: String },
+    Author { slug: String },
+    /// AMP version of a post
+    Amp { slug: String },
+    NotFound,
+}
+
+impl TemplateKind {
+    /// Candidate templates, most specific first
+    pub fn hierarchy(&self) -> Vec<String> {
+        let mut candidates = match self {
+            TemplateKind::Home => vec!["home.html".to_string(), "archive.html".to_string()],
+            TemplateKind::Single { slug } => vec![
+                format!("single-{}.html", slug),
+                "single.html".to_string(),
+            ],
+            TemplateKind::Page { slug } => vec![
+                format!("page-{}.html", slug),
+                "page.html".to_string(),
+                "single.html".to_string(),
+            ],
+            TemplateKind::Category { slug } => vec![
+                format!("category-{}.html", slug),
+                "category.html".to_string(),
+                "archive.html".to_string(),
+            ],
+            TemplateKind::Tag { slug } => vec![
+                format!("tag-{}.html", slug),
+                "tag.html".to_string(),
+                "archive.html".to_string(),
+            ],
+            TemplateKind::Author { slug } => vec![
+                format!("author-{}.html", slug),
+                "author.html".to_string(),
+                "archive.html".to_string(),
+            ],
+            TemplateKind::Amp { slug } => vec![format!("amp-{}.html", slug), "amp.html".to_string()],
+            TemplateKind::NotFound => vec!["404.html".to_string()],
+        };
+        candidates.push("index.html".to_string());
+        candidates
+    }
+
+    /// Path the page is served at, relative to the site; `None` for the
+    /// not-found page
+    pub fn path(&self) -> Option<String> {
+        match self {
+            TemplateKind::Home => Some("/".to_string()),
+            TemplateKind::Single { slug } => Some(format!("/read/{}", slug)),
+            TemplateKind::Page { slug } => Some(format!("/pages/{}", slug)),
+            TemplateKind::Category { slug } => Some(format!("/category/{}", slug)),
+            TemplateKind::Tag { slug } => Some(format!("/tag/{}", slug)),
+            TemplateKind::Author { slug } => Some(format!("/author/{}", slug)),
+            TemplateKind::Amp { slug } => Some(format!("/posts/{}/amp", slug)),
+            TemplateKind::NotFound => None,
+        }
+    }
+
+    /// Base body classes before filters run
+    fn body_classes(&self) -> Vec<String> {
+        match self {
+            TemplateKind::Home => vec!["home".into(), "blog".into()],
+            TemplateKind::Single { slug } => vec!["single".into(), format!("single-{}", slug)],
+            TemplateKind::Page { slug } => vec!["page".into(), format!("page-{}", slug)],
+            TemplateKind::Category { slug } => vec!["archive".into(), "category".into(), format!("category-{}", slug)],
+            TemplateKind::Tag { slug } => vec!["archive".into(), "tag".into(), format!("tag-{}", slug)],
+            TemplateKind::Author { slug } => vec!["archive".into(), "author".into(), format!("author-{}", slug)],
+            TemplateKind::Amp { slug } => vec!["amp".into(), "single".into(), format!("single-{}", slug)],
+            TemplateKind::NotFound => vec!["error404".into()],
+        }
+    }
+}
+
+/// Per-request information used for class filters
+#[derive(Debug, Clone)]
+pub struct RenderRequest {
+    pub site: Arc<Site>,
+    pub user: Option<User>,
+    pub user_agent: Option<String>,
+    /// Link archive pages as `/page/2/` directories instead of `?page=2`,
+    /// for static export
+    pub static_paths: bool,
+}
+
+/// Post prepared for templates with its filtered CSS classes
+#[derive(Debug, Serialize)]
+struct TemplatePost<'a> {
+    #[serde(flatten)]
+    post: &'a PostWithRelations,
+    post_class: String,
+}
+
+/// A loaded theme
+struct LoadedTheme {
+    tera: Arc<Tera>,
+    /// Registry version its functions were installed at, if they were
+    functions: Option<u64>,
+}
+
+/// Theme rendering service
+pub struct ThemeService {
+    config: ThemeConfig,
+    db: PgPool,
+    themes: RwLock<HashMap<String, LoadedTheme>>,
+    hooks: Arc<HookRegistry>,
+    widgets: Arc<WidgetService>,
+    preferences: Arc<PreferenceService>,
+    functions: Arc<TemplateFunctionRegistry>,
+}
+
+impl ThemeService {
+    pub fn new(
+        config: ThemeConfig,
+        db: PgPool,
+        hooks: Arc<HookRegistry>,
+        widgets: Arc<WidgetService>,
+        preferences: Arc<PreferenceService>,
+        functions: Arc<TemplateFunctionRegistry>,
+    ) -> Result<Self, ServiceError> {
+        let tera = load_theme(&config, &config.active_theme)?;
+        let themes = HashMap::from([(
+            config.active_theme.clone(),
+            LoadedTheme {
+                tera: Arc::new(tera),
+                functions: None,
+            },
+        )]);
+        Ok(Self {
+            config,
+            db,
+            themes: RwLock::new(themes),
+            hooks,
+            widgets,
+            preferences,
+            functions,
+        })
+    }
+
+    /// Functions templates can call; plugins register theirs here
+    pub fn functions(&self) -> &Arc<TemplateFunctionRegistry> {
+        &self.functions
+    }
+
+    /// Re-read templates from disk (e.g. after switching themes)
+    pub async fn reload(&self) -> Result<(), ServiceError> {
+        let tera = load_theme(&self.config, &self.config.active_theme)?;
+        let loaded = LoadedTheme {
+            tera: Arc::new(tera),
+            functions: None,
+        };
+        *self.themes.write().await = HashMap::from([(self.config.active_theme.clone(), loaded)]);
+        Ok(())
+    }
+
+    /// Templates for a theme, loading it on first use and installing the
+    /// template functions when the registry has changed since
+    async fn theme(&self, name: &str) -> Result<Arc<Tera>, ServiceError> {
+        let version = self.functions.version();
+        let fresh = match self.themes.read().await.get(name) {
+            Some(loaded) if loaded.functions == Some(version) => return Ok(loaded.tera.clone()),
+            Some(loaded) if loaded.functions.is_none() => Some(loaded.tera.clone()),
+            _ => None,
+        };
+
+        // Functions from an older install may have been unregistered since,
+        // so those themes start over from disk
+        let mut tera = match fresh {
+            Some(tera) => (*tera).clone(),
+            None => load_theme(&self.config, name)?,
+        };
+        self.functions.install(&mut tera).await;
+
+        let tera = Arc::new(tera);
+        let loaded = LoadedTheme {
+            tera: tera.clone(),
+            functions: Some(version),
+        };
+        self.themes.write().await.insert(name.to_string(), loaded);
+        Ok(tera)
+    }
+
+    /// Render a single post or page
+    pub async fn render_post(
+        &self,
+        kind: TemplateKind,
+        post: &PostWithRelations,
+        request: &RenderRequest,
+    ) -> Result<String, ServiceError> {
+        let amp = match &kind {
+            TemplateKind::Single { slug } if self.config.amp => TemplateKind::Amp { slug: slug.clone() }.path(),
+            _ => None,
+        };
+
+        let mut context = Context::new();
+        context.insert("post", &self.template_post(post, request).await?);
+        let html = self.render(kind, context, request).await?;
+
+        Ok(match amp {
+            Some(path) => amp::link_amphtml(html, &request.site.url(&path)),
+            None => html,
+        })
+    }
+
+    /// Render the AMP version of a post with its converted content
+    pub async fn render_amp(
+        &self,
+        post: &PostWithRelations,
+        content: &AmpContent,
+        request: &RenderRequest,
+    ) -> Result<String, ServiceError> {
+        let mut context = Context::new();
+        context.insert("post", &self.template_post(post, request).await?);
+        context.insert("amp_content", &content.html);
+        context.insert("amp_components", &content.components);
+        context.insert("canonical_url", &request.site.url(&format!("/read/{}", post.post.slug)));
+        let kind = TemplateKind::Amp { slug: post.post.slug.clone() };
+        self.render(kind, context, request).await
+    }
+
+    /// Render a list of posts (home, category, tag archives)
+    pub async fn render_archive(
+        &self,
+        kind: TemplateKind,
+        posts: &PaginatedResponse<PostWithRelations>,
+        request: &RenderRequest,
+    ) -> Result<String, ServiceError> {
+        let context = self.archive_context(&kind, posts, request).await?;
+        self.render(kind, context, request).await
+    }
+
+    /// Render an author's archive, with their profile as `author`
+    pub async fn render_author_archive(
+        &self,
+        author: &AuthorProfile,
+        posts: &PaginatedResponse<PostWithRelations>,
+        request: &RenderRequest,
+    ) -> Result<String, ServiceError> {
+        let kind = TemplateKind::Author { slug: author.slug.clone() };
+        let mut context = self.archive_context(&kind, posts, request).await?;
+        context.insert("author", author);
+        self.render(kind, context, request).await
+    }
+
+    async fn archive_context(
+        &self,
+        kind: &TemplateKind,
+        posts: &PaginatedResponse<PostWithRelations>,
+        request: &RenderRequest,
+    ) -> Result<Context, ServiceError> {
+        let mut items = Vec::with_capacity(posts.data.len());
+        for post in &posts.data {
+            items.push(self.template_post(post, request).await?);
+        }
+
+        let mut context = Context::new();
+        context.insert("posts", &items);
+        context.insert("pagination", &posts.pagination);
+        let pagination = &posts.pagination;
+        // Each page is its own canonical URL, linked to its neighbours in the head
+        context.insert("canonical_url", &page_url(kind, pagination.page, request));
+        if pagination.has_prev {
+            context.insert("prev_page_url", &page_url(kind, pagination.page - 1, request));
+        }
+        if pagination.has_next {
+            context.insert("next_page_url", &page_url(kind, pagination.page + 1, request));
+        }
+        Ok(context)
+    }
+
+    /// Render the not-found page
+    pub async fn render_not_found(&self, request: &RenderRequest) -> Result<String, ServiceError> {
+        self.render(TemplateKind::NotFound, Context::new(), request).await
+    }
+
+    async fn render(
+        &self,
+        kind: TemplateKind,
+        mut context: Context,
+        request: &RenderRequest,
+    ) -> Result<String, ServiceError> {
+        let mut classes = kind.body_classes();
+        classes.push(if request.user.is_some() { "logged-in" } else { "logged-out" }.to_string());
+        // The reader's preferences (e.g. `color_scheme`) become body classes
+        let site = &request.site;
+        let preferences = self.preferences.resolve(site, request.user.as_ref().map(|u| u.id)).await?;
+        let args = HashMap::from([("preferences".to_string(), serde_json::json!(preferences))]);
+        let classes = self.apply_class_filter("body_class", classes, args, request).await?;
+
+        let theme = site.config.theme.as_deref().unwrap_or(&self.config.active_theme);
+
+        context.insert("site_id", &site.id);
+        context.insert("site_name", &site.name);
+        context.insert("site_tagline", &site.config.tagline);
+        context.insert("base_url", &site.url(""));
+        context.insert("theme", theme);
+        context.insert("body_class", &classes.join(" "));
+        context.insert("preferences", &preferences);
+        // Inline scripts must carry it: `<script nonce="{{ csp_nonce }}">`
+        context.insert("csp_nonce", &rustpress_auth::security::current_csp_nonce().unwrap_or_default());
+        context.insert("sidebars", &self.widgets.render_all(site).await?);
+
+        let tera = self.theme(theme).await?;
+        let template = kind
+            .hierarchy()
+            .into_iter()
+            .find(|name| tera.get_template_names().any(|t| t == name))
+            .ok_or_else(|| ServiceError::Template("No template matched".to_string()))?;
+
+        // Template functions block on their calls, so render off the runtime
+        let functions = self.functions.clone();
+        let scope = TemplateFunctionContext {
+            db: self.db.clone(),
+            site: site.clone(),
+            user: request.user.clone(),
+            hooks: self.hooks.clone(),
+        };
+        let handle = tokio::runtime::Handle::current();
+        tokio::task::spawn_blocking(move || functions.scoped(scope, handle, || tera.render(&template, &context)))
+            .await
+            .map_err(|e| ServiceError::Template(e.to_string()))?
+            .map_err(|e| ServiceError::Template(e.to_string()))
+    }
+
+    async fn template_post<'a>(
+        &self,
+        post: &'a PostWithRelations,
+        request: &RenderRequest,
+    ) -> Result<TemplatePost<'a>, ServiceError> {
+        let mut classes = vec![
+            "post".to_string(),
+            format!("post-{}", post.post.id),
+            format!("status-{}", serde_json::to_value(&post.post.status)
+                .ok()
+                .and_then(|v| v.as_str().map(String::from))
+                .unwrap_or_default()),
+        ];
+        classes.extend(post.categories.iter().map(|c| format!("category-{}", c.slug)));
+        classes.extend(post.tags.iter().map(|t| format!("tag-{}", t.slug)));
+        if post.post.featured_image.is_some() {
+            classes.push("has-post-thumbnail".to_string());
+        }
+
+        let classes = self.apply_class_filter("post_class", classes, HashMap::new(), request).await?;
+
+        Ok(TemplatePost {
+            post,
+            post_class: classes.join(" "),
+        })
+    }
+
+    /// Run `body_class` / `post_class` filters registered by functions and plugins
+    async fn apply_class_filter(
+        &self,
+        hook: &str,
+        classes: Vec<String>,
+        args: HashMap<String, serde_json::Value>,
+        request: &RenderRequest,
+    ) -> Result<Vec<String>, ServiceError> {
+        let mut ctx = crate::hooks::filter_context();
+        ctx.user_agent = request.user_agent.clone();
+        ctx.filter_args = args;
+
+        let classes = self
+            .hooks
+            .apply_filters(hook, &ctx, classes)
+            .await
+            .map_err(|e| ServiceError::Template(e.to_string()))?;
+
+        Ok(unique_classes(classes))
+    }
+}
+
+/// Drop repeated classes, keeping the first occurrence of each in order
+pub fn unique_classes(classes: Vec<String>) -> Vec<String> {
+    let mut seen = HashSet::new();
+    classes.into_iter().filter(|class| seen.insert(class.clone())).collect()
+}
+
+/// Link to another page of an archive
+fn page_url(kind: &TemplateKind, page: i64, request: &RenderRequest) -> String {
+    let dir = kind.path().unwrap_or_default();
+    if !request.static_paths {
+        let url = request.site.url(&dir);
+        return if page <= 1 { url } else { format!("{}?page={}", url, page) };
+    }
+
+    let dir = dir.trim_end_matches('/');
+    if page <= 1 {
+        request.site.url(&format!("{}/", dir))
+    } else {
+        request.site.url(&format!("{}/page/{}/", dir, page))
+    }
+}
+
+/// Load a theme, filling gaps with the bundled defaults
+fn load_theme(config: &ThemeConfig, theme: &str) -> Result<Tera, ServiceError> {
+    let theme_dir = config.themes_dir.join(theme).join("templates");
+
+    let mut tera = if theme_dir.is_dir() {
+        let glob = format!("{}/**/*.html", theme_dir.display());
+        Tera::new(&glob).map_err(|e| ServiceError::Template(e.to_string()))?
+    } else {
+        tracing::warn!("Theme '{}' not found, using default templates", theme);
+        Tera::default()
+    };
+
+    let existing: Vec<String> = tera.get_template_names().map(String::from).collect();
+    let missing: Vec<(&str, &str)> = DEFAULT_TEMPLATES
+        .iter()
+        .filter(|(name, _)| !existing.iter().any(|t| t == name))
+        .copied()
+        .collect();
+
+    tera.add_raw_templates(missing)
+        .map_err(|e| ServiceError::Template(e.to_string()))?;
+
+    Ok(tera)
+}
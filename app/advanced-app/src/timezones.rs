@@ -0,0 +1,78 @@
+//! Time Zones
+//!
+//! Every site has a time zone, `timezone` in its config (default: the
+//! `timezone` of `[app.preferences.defaults]`, `UTC`), and users may choose
+//! their own as their `timezone` preference. Times sent without an offset,
+//! such as a post's `scheduled_for`, are wall-clock times in the author's
+//! zone; times sent with one are taken as they are. Responses give such times
+//! with the offset they were read with, as a `ZonedTime`.
+
+use crate::models::FieldError;
+use crate::services::ServiceError;
+use chrono::{DateTime, FixedOffset, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Wall-clock formats accepted besides RFC 3339
+const LOCAL_FORMATS: &[&str] = &["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%dT%H:%M", "%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M"];
+
+/// An IANA time zone, e.g. `Europe/Berlin`
+pub fn parse_timezone(name: &str) -> Result<Tz, String> {
+    name.parse().map_err(|_| format!("Unknown time zone '{}'", name))
+}
+
+/// When a wall-clock time happens in `tz`: the earlier one when clocks go
+/// back, and an hour later when they skip it
+pub fn local_to_utc(local: NaiveDateTime, tz: Tz) -> DateTime<Utc> {
+    tz.from_local_datetime(&local)
+        .earliest()
+        .or_else(|| tz.from_local_datetime(&(local + chrono::Duration::hours(1))).earliest())
+        .map(|at| at.with_timezone(&Utc))
+        .unwrap_or_else(|| Utc.from_utc_datetime(&local))
+}
+
+/// Read a time sent by a client: RFC 3339 times are absolute, times without
+/// an offset are wall-clock times in `tz`
+pub fn resolve_time(field: &str, value: &str, tz: Tz) -> Result<DateTime<Utc>, ServiceError> {
+    let value = value.trim();
+    if let Ok(at) = DateTime::parse_from_rfc3339(value) {
+        return Ok(at.with_timezone(&Utc));
+    }
+
+    LOCAL_FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .map(|local| local_to_utc(local, tz))
+        .ok_or_else(|| {
+            ServiceError::InvalidFields(vec![FieldError {
+                field: field.to_string(),
+                code: "datetime".to_string(),
+                message: "Must be an RFC 3339 time, or a local time like 2026-03-01T09:00".to_string(),
+                rejected_value: Some(value.into()),
+            }])
+        })
+}
+
+/// A moment as seen in a time zone
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ZonedTime {
+    /// Local time with its offset, e.g. `2026-03-01T09:00:00+01:00`
+    #[schema(value_type = String, format = DateTime)]
+    pub at: DateTime<FixedOffset>,
+    /// IANA zone, e.g. `Europe/Berlin`
+    pub timezone: String,
+    /// The zone's offset from UTC then, e.g. `+01:00`
+    pub utc_offset: String,
+}
+
+impl ZonedTime {
+    pub fn new(at: DateTime<Utc>, tz: Tz) -> Self {
+        let at = at.with_timezone(&tz).fixed_offset();
+        Self {
+            utc_offset: at.format("%:z").to_string(),
+            timezone: tz.name().to_string(),
+            at,
+        }
+    }
+}
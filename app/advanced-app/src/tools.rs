@@ -0,0 +1,711 @@
+//! Tool API
+//!
+//! Content operations for LLM agents and scripts, described the way agent
+//! frameworks (MCP servers and the like) expect: `GET /tools` lists each tool
+//! with a JSON Schema of its input, and `POST /tools/:name` calls one with
+//! `{"input": {...}, "dry_run": false}`.
+//!
+//! - `create_draft` (`posts:write`): a new draft; tools never publish
+//! - `update_post` (`posts:write`): changes to a post, as `PUT /posts/:id`
+//! - `summarize_analytics` (`analytics:read`): traffic totals and top pages
+//!   of the key's site, while the analytics plugin is active
+//! - `list_pending_comments` (`comments:read`): comments awaiting moderation
+//!
+//! Callers send an `X-Api-Key` that admins create with
+//! `POST /admin/tool-keys`. A key acts as one user on one site and only
+//! within its scopes; the user's role, site membership, the site's policies,
+//! the content policy and edit locks still apply, so a key never does more
+//! than its user could. With `dry_run`, writing tools run every check and
+//! answer with what they would save (`update_post` as a diff) without saving
+//! it.
+//!
+//! Off unless `[app.tools]` `enabled`.
+
+use crate::diff;
+use crate::extractors::User;
+use crate::handlers::posts::{held_error, moderate};
+use crate::models::*;
+use crate::policies::UpdatePost;
+use crate::services::ServiceError;
+use crate::sites::{CurrentSite, Site};
+use crate::BlogServices;
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{request::Parts, StatusCode},
+    response::{IntoResponse, Response},
+};
+use rand::RngCore;
+use rustpress_analytics::models::ReportQuery;
+use rustpress_analytics::services::{ReportError, ReportService};
+use rustpress_auth::policy::{self, Policy, Subject};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use std::sync::Arc;
+use utoipa::{PartialSchema, ToSchema};
+use uuid::Uuid;
+use validator::Validate;
+
+/// Header carrying a tool API key
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// Start of every tool API key
+const KEY_PREFIX: &str = "rpt_";
+
+/// Periods `summarize_analytics` reports on
+const PERIODS: &[&str] = &["7d", "30d", "90d", "365d"];
+
+/// `[app.tools]` settings
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ToolConfig {
+    pub enabled: bool,
+}
+
+// ============================================
+// Tools
+// ============================================
+
+/// A tool agents can call
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tool {
+    CreateDraft,
+    UpdatePost,
+    SummarizeAnalytics,
+    ListPendingComments,
+}
+
+impl Tool {
+    pub const ALL: [Tool; 4] = [
+        Tool::CreateDraft,
+        Tool::UpdatePost,
+        Tool::SummarizeAnalytics,
+        Tool::ListPendingComments,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Tool::CreateDraft => "create_draft",
+            Tool::UpdatePost => "update_post",
+            Tool::SummarizeAnalytics => "summarize_analytics",
+            Tool::ListPendingComments => "list_pending_comments",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Tool> {
+        Tool::ALL.into_iter().find(|tool| tool.name() == name)
+    }
+
+    pub fn scope(self) -> ToolScope {
+        match self {
+            Tool::CreateDraft | Tool::UpdatePost => ToolScope::PostsWrite,
+            Tool::SummarizeAnalytics => ToolScope::AnalyticsRead,
+            Tool::ListPendingComments => ToolScope::CommentsRead,
+        }
+    }
+
+    /// Whether the tool changes anything, and so honors `dry_run`
+    pub fn writes(self) -> bool {
+        self.scope() == ToolScope::PostsWrite
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            Tool::CreateDraft => {
+                "Create a draft post. Drafts are never published by tools; an editor reviews and publishes them."
+            }
+            Tool::UpdatePost => {
+                "Change fields of a post by id; fields left out stay as they are. Fails while someone else is editing the post."
+            }
+            Tool::SummarizeAnalytics => "Summarize site traffic for a period: page views, visitors, sessions and top pages.",
+            Tool::ListPendingComments => "List comments awaiting moderation, oldest first, optionally on one post.",
+        }
+    }
+
+    fn input_schema(self) -> Value {
+        let schema = match self {
+            Tool::CreateDraft => CreateDraftInput::schema(),
+            Tool::UpdatePost => UpdatePostInput::schema(),
+            Tool::SummarizeAnalytics => SummarizeAnalyticsInput::schema(),
+            Tool::ListPendingComments => ListPendingCommentsInput::schema(),
+        };
+        serde_json::to_value(schema).unwrap_or_default()
+    }
+
+    pub fn definition(self) -> ToolDefinition {
+        ToolDefinition {
+            name: self.name().to_string(),
+            description: self.description().to_string(),
+            scope: self.scope(),
+            writes: self.writes(),
+            input_schema: self.input_schema(),
+        }
+    }
+}
+
+/// Whether `user`'s role may use a scope at all, whatever their keys say
+pub fn role_allows(scope: ToolScope, user: &User) -> bool {
+    match scope {
+        ToolScope::PostsWrite => user.can_publish(),
+        ToolScope::CommentsRead => user.can_moderate(),
+        ToolScope::AnalyticsRead => user.is_admin(),
+    }
+}
+
+/// Input of `create_draft`
+#[derive(Debug, Clone, Deserialize, Validate, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct CreateDraftInput {
+    #[validate(length(min = 1, max = 200))]
+    pub title: String,
+    /// HTML
+    #[validate(length(min = 1))]
+    pub content: String,
+    /// Generated from the content when left out
+    #[validate(length(max = 500))]
+    pub excerpt: Option<String>,
+    pub featured_image: Option<String>,
+    pub category_ids: Option<Vec<Uuid>>,
+    pub tag_ids: Option<Vec<Uuid>>,
+    #[validate(length(max = 70))]
+    pub meta_title: Option<String>,
+    #[validate(length(max = 160))]
+    pub meta_description: Option<String>,
+}
+
+impl From<CreateDraftInput> for CreatePostRequest {
+    fn from(input: CreateDraftInput) -> Self {
+        Self {
+            title: input.title,
+            content: input.content,
+            excerpt: input.excerpt,
+            featured_image: input.featured_image,
+            category_ids: input.category_ids,
+            tag_ids: input.tag_ids,
+            meta_title: input.meta_title,
+            meta_description: input.meta_description,
+            scheduled_for: None,
+        }
+    }
+}
+
+/// Input of `update_post`
+#[derive(Debug, Clone, Deserialize, Validate, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct UpdatePostInput {
+    /// Post to change
+    pub id: Uuid,
+    #[validate(length(min = 1, max = 200))]
+    pub title: Option<String>,
+    /// HTML
+    pub content: Option<String>,
+    #[validate(length(max = 500))]
+    pub excerpt: Option<String>,
+    pub featured_image: Option<String>,
+    /// Replaces the post's categories
+    pub category_ids: Option<Vec<Uuid>>,
+    /// Replaces the post's tags
+    pub tag_ids: Option<Vec<Uuid>>,
+    #[validate(length(max = 70))]
+    pub meta_title: Option<String>,
+    #[validate(length(max = 160))]
+    pub meta_description: Option<String>,
+}
+
+impl UpdatePostInput {
+    fn into_request(self) -> (Uuid, UpdatePostRequest) {
+        let request = UpdatePostRequest {
+            title: self.title,
+            content: self.content,
+            excerpt: self.excerpt,
+            featured_image: self.featured_image,
+            category_ids: self.category_ids,
+            tag_ids: self.tag_ids,
+            meta_title: self.meta_title,
+            meta_description: self.meta_description,
+        };
+        (self.id, request)
+    }
+}
+
+/// Input of `summarize_analytics`
+#[derive(Debug, Clone, Default, Deserialize, Validate, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct SummarizeAnalyticsInput {
+    /// `7d`, `30d`, `90d` or `365d`; defaults to `30d`
+    pub period: Option<String>,
+    /// Pages to list, by views; defaults to 10
+    #[validate(range(min = 1, max = 50))]
+    pub top_pages: Option<i64>,
+}
+
+/// Input of `list_pending_comments`
+#[derive(Debug, Clone, Default, Deserialize, Validate, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ListPendingCommentsInput {
+    /// Only comments on this post
+    pub post_id: Option<Uuid>,
+    /// Defaults to 20
+    #[validate(range(min = 1, max = 100))]
+    pub limit: Option<i64>,
+}
+
+// ============================================
+// Keys
+// ============================================
+
+/// A caller of the tool API: the key it sent and the user that key acts as
+pub struct ToolCaller {
+    pub key: ToolKey,
+    pub user: User,
+}
+
+impl ToolCaller {
+    /// Whether the key and its user's role allow a tool
+    pub fn may_use(&self, tool: Tool) -> bool {
+        self.key.scopes.contains(&tool.scope()) && role_allows(tool.scope(), &self.user)
+    }
+
+    /// Tools the caller may use
+    pub fn tools(&self) -> Vec<ToolDefinition> {
+        Tool::ALL
+            .into_iter()
+            .filter(|tool| self.may_use(*tool))
+            .map(Tool::definition)
+            .collect()
+    }
+}
+
+/// The current site's tool key from the `X-Api-Key` header; the key's user
+/// must still be active and a member of the site
+#[async_trait]
+impl FromRequestParts<Arc<BlogServices>> for ToolCaller {
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, services: &Arc<BlogServices>) -> Result<Self, Self::Rejection> {
+        if !services.tools.enabled() {
+            return Err(ProblemDetails::not_found("The tool API is not enabled").into_response());
+        }
+        let CurrentSite(site) = CurrentSite::from_request_parts(parts, services)
+            .await
+            .map_err(IntoResponse::into_response)?;
+
+        let secret = parts
+            .headers
+            .get(API_KEY_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .ok_or_else(|| ProblemDetails::unauthorized("X-Api-Key header required").into_response())?;
+        let (key, user) = services
+            .tools
+            .authenticate(&site, secret)
+            .await
+            .map_err(IntoResponse::into_response)?
+            .ok_or_else(|| ProblemDetails::unauthorized("Invalid API key").into_response())?;
+
+        if !site.is_default && !user.is_admin() {
+            let role = services
+                .sites
+                .membership(site.id, user.id)
+                .await
+                .map_err(IntoResponse::into_response)?;
+            if role.is_none() {
+                return Err(ProblemDetails::new(StatusCode::FORBIDDEN, "not_site_member")
+                    .detail(format!("The key's user is not a member of site '{}'", site.slug))
+                    .into_response());
+            }
+        }
+
+        Ok(ToolCaller { key, user })
+    }
+}
+
+pub struct ToolService {
+    db: PgPool,
+    config: ToolConfig,
+}
+
+impl ToolService {
+    pub fn new(db: PgPool, config: ToolConfig) -> Self {
+        Self { db, config }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    /// The site's keys, newest first, revoked ones included
+    pub async fn list_keys(&self, site: &Site) -> Result<Vec<ToolKey>, ServiceError> {
+        let keys = sqlx::query_as("SELECT * FROM blog_tool_keys WHERE site_id = $1 ORDER BY created_at DESC")
+            .bind(site.id)
+            .fetch_all(&self.db)
+            .await?;
+        Ok(keys)
+    }
+
+    /// A key acting as `req.user_id` (or `created_by`); its scopes must be
+    /// ones that user's role allows
+    #[tracing::instrument(name = "tools.create_key", skip_all, fields(site = %site.id))]
+    pub async fn create_key(&self, site: &Site, created_by: Uuid, req: CreateToolKeyRequest) -> Result<CreatedToolKey, ServiceError> {
+        let user_id = req.user_id.unwrap_or(created_by);
+        let user = self
+            .user(user_id)
+            .await?
+            .ok_or_else(|| ServiceError::NotFound(format!("Active user not found: {}", user_id)))?;
+
+        let errors: Vec<FieldError> = req
+            .scopes
+            .iter()
+            .enumerate()
+            .filter(|(_, scope)| !role_allows(**scope, &user))
+            .map(|(index, scope)| FieldError {
+                field: format!("scopes[{}]", index),
+                code: "role".to_string(),
+                message: format!("A {} can't be given this scope", user.role),
+                rejected_value: serde_json::to_value(scope).ok(),
+            })
+            .collect();
+        if !errors.is_empty() {
+            return Err(ServiceError::InvalidFields(errors));
+        }
+        let mut scopes = Vec::new();
+        for scope in req.scopes {
+            if !scopes.contains(&scope) {
+                scopes.push(scope);
+            }
+        }
+
+        let mut bytes = [0u8; 24];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        let secret = format!("{}{}", KEY_PREFIX, hex::encode(bytes));
+
+        let key: ToolKey = sqlx::query_as(
+            "INSERT INTO blog_tool_keys (site_id, user_id, name, prefix, key_hash, scopes, created_by)
+             VALUES ($1, $2, $3, $4, $5, $6, $7)
+             RETURNING *",
+        )
+        .bind(site.id)
+        .bind(user.id)
+        .bind(&req.name)
+        .bind(&secret[..KEY_PREFIX.len() + 8])
+        .bind(hash(&secret))
+        .bind(sqlx::types::Json(&scopes))
+        .bind(created_by)
+        .fetch_one(&self.db)
+        .await?;
+
+        tracing::info!(key = %key.id, user = %user.id, "Tool key created");
+        Ok(CreatedToolKey { key, secret })
+    }
+
+    /// Stop a key from working
+    pub async fn revoke_key(&self, site: &Site, id: Uuid) -> Result<(), ServiceError> {
+        let revoked = sqlx::query(
+            "UPDATE blog_tool_keys SET revoked_at = COALESCE(revoked_at, NOW()) WHERE id = $1 AND site_id = $2",
+        )
+        .bind(id)
+        .bind(site.id)
+        .execute(&self.db)
+        .await?;
+        if revoked.rows_affected() == 0 {
+            return Err(ServiceError::NotFound("Tool key not found".into()));
+        }
+        Ok(())
+    }
+
+    /// The site's live key `secret` and its active user, recording the use
+    pub async fn authenticate(&self, site: &Site, secret: &str) -> Result<Option<(ToolKey, User)>, ServiceError> {
+        let key: Option<ToolKey> = sqlx::query_as(
+            "UPDATE blog_tool_keys SET last_used_at = NOW()
+             WHERE key_hash = $1 AND site_id = $2 AND revoked_at IS NULL
+             RETURNING *",
+        )
+        .bind(hash(secret))
+        .bind(site.id)
+        .fetch_optional(&self.db)
+        .await?;
+
+        let Some(key) = key else {
+            return Ok(None);
+        };
+        Ok(self.user(key.user_id).await?.map(|user| (key, user)))
+    }
+
+    async fn user(&self, id: Uuid) -> Result<Option<User>, ServiceError> {
+        let user: Option<(Uuid, String, String, String)> = sqlx::query_as(
+            "SELECT id, email, name, role::text FROM users WHERE id = $1 AND status = 'active'",
+        )
+        .bind(id)
+        .fetch_optional(&self.db)
+        .await?;
+        Ok(user.map(|(id, email, name, role)| User {
+            id,
+            email,
+            name,
+            role,
+            email_verified_at: None,
+        }))
+    }
+}
+
+fn hash(secret: &str) -> String {
+    hex::encode(Sha256::digest(secret.as_bytes()))
+}
+
+// ============================================
+// Calls
+// ============================================
+
+/// Run a tool for `caller`; the result is what agents get as `result`
+#[tracing::instrument(name = "tools.call", skip_all, fields(site = %site.id, tool = tool.name(), key = %caller.key.id, dry_run = dry_run))]
+pub async fn call(
+    services: &BlogServices,
+    site: &Site,
+    caller: &ToolCaller,
+    tool: Tool,
+    input: Value,
+    dry_run: bool,
+) -> Result<Value, ServiceError> {
+    if !caller.may_use(tool) {
+        return Err(ServiceError::PermissionDenied);
+    }
+
+    match tool {
+        Tool::CreateDraft => create_draft(services, site, &caller.user, parse(input)?, dry_run).await,
+        Tool::UpdatePost => update_post(services, site, &caller.user, parse(input)?, dry_run).await,
+        Tool::SummarizeAnalytics => {
+            let reports = rustpress_analytics::shared_reports()
+                .ok_or_else(|| ServiceError::NotFound("Analytics is not active".into()))?;
+            summarize_analytics(&reports, site, parse(input)?).await
+        }
+        Tool::ListPendingComments => list_pending_comments(services, site, parse(input)?).await,
+    }
+}
+
+/// A tool's input; a missing input is an empty object
+fn parse<T: DeserializeOwned + Validate>(input: Value) -> Result<T, ServiceError> {
+    let input = if input.is_null() { json!({}) } else { input };
+    let parsed: T = serde_json::from_value(input.clone()).map_err(|e| {
+        ServiceError::InvalidFields(vec![FieldError {
+            field: "input".to_string(),
+            code: "schema".to_string(),
+            message: e.to_string(),
+            rejected_value: Some(input),
+        }])
+    })?;
+    parsed.validate()?;
+    Ok(parsed)
+}
+
+async fn create_draft(
+    services: &BlogServices,
+    site: &Site,
+    user: &User,
+    input: CreateDraftInput,
+    dry_run: bool,
+) -> Result<Value, ServiceError> {
+    let mut req = CreatePostRequest::from(input);
+    // Held drafts are saved; like any draft, an editor publishes them
+    let held = moderate(services, site, user, &mut req.title).await?
+        | moderate(services, site, user, &mut req.content).await?;
+
+    if dry_run {
+        return Ok(json!({
+            "post": {
+                "title": req.title,
+                "slug": slug::slugify(&req.title),
+                "content": req.content,
+                "excerpt": req.excerpt,
+                "featured_image": req.featured_image,
+                "category_ids": req.category_ids,
+                "tag_ids": req.tag_ids,
+                "meta_title": req.meta_title,
+                "meta_description": req.meta_description,
+                "status": PostStatus::Draft,
+            },
+            "held": held,
+        }));
+    }
+
+    services.save_filter.create(site, user.id, &mut req).await?;
+    let post = services.posts.create(site, user.id, req, None).await?;
+    Ok(json!({ "post": post, "held": held }))
+}
+
+async fn update_post(
+    services: &BlogServices,
+    site: &Site,
+    user: &User,
+    input: UpdatePostInput,
+    dry_run: bool,
+) -> Result<Value, ServiceError> {
+    let (id, mut req) = input.into_request();
+    let existing = services.posts.get_by_id(site, id).await?;
+
+    let subject = Subject {
+        id: Some(user.id),
+        role: Some(user.role.clone()),
+    };
+    let allowed = policy::shared()
+        .check(UpdatePost::NAME, &subject, &existing)
+        .unwrap_or_else(|e| {
+            tracing::error!(policy = UpdatePost::NAME, "Policy check failed: {}", e);
+            false
+        });
+    if !allowed {
+        return Err(ServiceError::PermissionDenied);
+    }
+    services.locks.ensure_unlocked(site, id, user.id).await?;
+
+    let mut held = false;
+    if let Some(title) = req.title.as_mut() {
+        held |= moderate(services, site, user, title).await?;
+    }
+    if let Some(content) = req.content.as_mut() {
+        held |= moderate(services, site, user, content).await?;
+    }
+    if held && matches!(existing.status, PostStatus::Published | PostStatus::Scheduled) {
+        return Err(held_error());
+    }
+
+    if dry_run {
+        let before = revision(&existing);
+        let mut after = before.clone();
+        if let Some(title) = &req.title {
+            after.title = title.clone();
+            after.slug = slug::slugify(title);
+        }
+        after.content = req.content.clone().unwrap_or(after.content);
+        after.excerpt = req.excerpt.clone().or(after.excerpt);
+        after.featured_image = req.featured_image.clone().or(after.featured_image);
+        after.meta_title = req.meta_title.clone().or(after.meta_title);
+        after.meta_description = req.meta_description.clone().or(after.meta_description);
+
+        return Ok(json!({
+            "post_id": id,
+            "changes": diff::diff_revisions(&before, &after),
+            "category_ids": req.category_ids,
+            "tag_ids": req.tag_ids,
+            "held": held,
+        }));
+    }
+
+    services.save_filter.update(site, &existing, &mut req).await?;
+    let post = services.posts.update(site, existing, user.id, req).await?;
+    Ok(json!({ "post": post, "held": held }))
+}
+
+/// A post as a revision, for diffing
+fn revision(post: &Post) -> PostRevision {
+    PostRevision {
+        post_id: post.id,
+        number: 0,
+        author_id: Some(post.author_id),
+        author_name: None,
+        title: post.title.clone(),
+        slug: post.slug.clone(),
+        content: post.content.clone(),
+        excerpt: post.excerpt.clone(),
+        featured_image: post.featured_image.clone(),
+        meta_title: post.meta_title.clone(),
+        meta_description: post.meta_description.clone(),
+        created_at: post.updated_at,
+    }
+}
+
+/// Traffic of `site` over the input's period, as `summarize_analytics` answers
+pub async fn summarize_analytics(
+    reports: &ReportService,
+    site: &Site,
+    input: SummarizeAnalyticsInput,
+) -> Result<Value, ServiceError> {
+    let period = input.period.unwrap_or_else(|| "30d".to_string());
+    if !PERIODS.contains(&period.as_str()) {
+        return Err(ServiceError::InvalidFields(vec![FieldError {
+            field: "period".to_string(),
+            code: "one_of".to_string(),
+            message: format!("Must be one of: {}", PERIODS.join(", ")),
+            rejected_value: Some(Value::from(period)),
+        }]));
+    }
+    let query = ReportQuery {
+        site_id: Some(site.id),
+        from: None,
+        to: None,
+        period: Some(period.clone()),
+        limit: Some(input.top_pages.unwrap_or(10)),
+        offset: None,
+    };
+    let overview = reports.get_overview(&query).await.map_err(report_error)?;
+    let pages = reports.get_pages(&query).await.map_err(report_error)?;
+
+    let mut summary = format!(
+        "{} page views from {} visitors in {} sessions over the last {}.",
+        overview.total_page_views, overview.unique_visitors, overview.total_sessions, period
+    );
+    if let Some(top) = pages.first() {
+        summary.push_str(&format!(" The top page was {} with {} views.", top.path, top.page_views));
+    }
+
+    Ok(json!({
+        "summary": summary,
+        "period": period,
+        "range": overview.range,
+        "page_views": overview.total_page_views,
+        "unique_visitors": overview.unique_visitors,
+        "sessions": overview.total_sessions,
+        "bounce_rate": overview.bounce_rate,
+        "avg_session_duration": overview.avg_session_duration,
+        "pages_per_session": overview.pages_per_session,
+        "new_visitors_percentage": overview.new_vs_returning.new_percentage,
+        "top_pages": pages
+            .iter()
+            .map(|page| json!({
+                "path": page.path,
+                "title": page.title,
+                "page_views": page.page_views,
+                "unique_visitors": page.unique_visitors,
+            }))
+            .collect::<Vec<_>>(),
+    }))
+}
+
+/// A report over its time limit is a 503, like the analytics API's, so
+/// agents can retry later or with a shorter period
+fn report_error(e: ReportError) -> ServiceError {
+    match e {
+        ReportError::Timeout(limit) => {
+            tracing::warn!("The analytics summary timed out after {:?}", limit);
+            ServiceError::Unavailable("The report took too long; try a shorter period".to_string())
+        }
+        e => ServiceError::Database(sqlx::Error::Protocol(e.to_string())),
+    }
+}
+
+/// Pending comments without commenters' emails, addresses and user agents
+async fn list_pending_comments(
+    services: &BlogServices,
+    site: &Site,
+    input: ListPendingCommentsInput,
+) -> Result<Value, ServiceError> {
+    let comments = services
+        .comments
+        .list_pending(site, input.post_id, input.limit.unwrap_or(20))
+        .await?;
+    Ok(json!({
+        "comments": comments
+            .iter()
+            .map(|comment| json!({
+                "id": comment.id,
+                "post_id": comment.post_id,
+                "parent_id": comment.parent_id,
+                "author_name": comment.author_name,
+                "content": comment.content,
+                "toxicity": comment.toxicity,
+                "created_at": comment.created_at,
+            }))
+            .collect::<Vec<_>>(),
+    }))
+}
@@ -0,0 +1,316 @@
+//! Comment Toxicity Scoring
+//!
+//! New comments are scored by a [`ModerationProvider`] before they're saved:
+//! a probability, from 0 (benign) to 1, that readers would find the comment
+//! toxic. Comments scoring `pending_threshold` or more are held for
+//! moderation, and `spam_threshold` or more go straight to spam, whether or
+//! not the commenter is trusted. The score, each attribute's score and the
+//! model are stored with the comment so moderators see why it was held.
+//!
+//! Providers:
+//!
+//! - `perspective`: the Perspective API (`comments:analyze`), or a service
+//!   with the same request and response; the comment scores the highest of
+//!   the requested `attributes`
+//! - `rules`: built in, no model; each listed term found in the comment (as
+//!   a whole word, in any case) adds its weight, combined as independent
+//!   probabilities, so a comment with terms weighing 0.5 and 0.6 scores 0.8
+//!
+//! When the provider fails the comment is moderated as if scoring were off,
+//! or held when `hold_on_error` is set. Editors and admins aren't scored.
+//!
+//! Off unless `[app.toxicity]` `enabled`.
+
+use crate::extractors::User;
+use crate::models::*;
+use axum::async_trait;
+use regex::Regex;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Moderation backend
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProviderKind {
+    /// Built-in weighted terms
+    Rules,
+    /// Perspective API, or a compatible service
+    Perspective,
+}
+
+/// `[app.toxicity]` settings
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ToxicityConfig {
+    /// Score new comments
+    pub enabled: bool,
+    pub provider: ProviderKind,
+    /// `perspective`: the `comments:analyze` URL
+    pub endpoint: String,
+    /// Sent as the `key` parameter by the `perspective` provider
+    pub api_key: Option<String>,
+    /// Attributes the `perspective` provider asks for
+    pub attributes: Vec<String>,
+    /// `rules`: terms and phrases with their weight, 0 to 1
+    pub terms: BTreeMap<String, f32>,
+    /// Comments scoring this or more are held for moderation
+    pub pending_threshold: f32,
+    /// Comments scoring this or more are marked as spam
+    pub spam_threshold: f32,
+    /// Hold comments that couldn't be scored, rather than moderating them
+    /// as usual
+    pub hold_on_error: bool,
+    pub timeout_secs: u64,
+}
+
+impl Default for ToxicityConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            provider: ProviderKind::Rules,
+            endpoint: "https://commentanalyzer.googleapis.com/v1alpha1/comments:analyze".to_string(),
+            api_key: None,
+            attributes: ["TOXICITY", "SEVERE_TOXICITY", "INSULT", "THREAT"]
+                .into_iter()
+                .map(String::from)
+                .collect(),
+            terms: [
+                ("idiot", 0.6),
+                ("moron", 0.6),
+                ("stupid", 0.4),
+                ("loser", 0.4),
+                ("shut up", 0.5),
+                ("kill yourself", 0.95),
+            ]
+            .into_iter()
+            .map(|(term, weight)| (term.to_string(), weight))
+            .collect(),
+            pending_threshold: 0.7,
+            spam_threshold: 0.95,
+            hold_on_error: false,
+            timeout_secs: 5,
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ToxicityError {
+    #[error("Moderation request failed: {0}")]
+    Request(#[from] reqwest::Error),
+    #[error("Moderation endpoint returned {status}: {message}")]
+    Api { status: u16, message: String },
+    #[error("Unexpected moderation response: {0}")]
+    Response(String),
+    #[error("Invalid moderation term {term:?}: {message}")]
+    Term { term: String, message: String },
+}
+
+// ============================================
+// Providers
+// ============================================
+
+/// Scores text for toxicity
+#[async_trait]
+pub trait ModerationProvider: Send + Sync {
+    /// Stored with each score
+    fn model(&self) -> &str;
+
+    /// Score of each attribute the provider checks, 0 to 1
+    async fn score(&self, text: &str) -> Result<BTreeMap<String, f32>, ToxicityError>;
+}
+
+/// The provider `config` selects
+pub fn provider(config: &ToxicityConfig) -> Result<Arc<dyn ModerationProvider>, ToxicityError> {
+    Ok(match config.provider {
+        ProviderKind::Rules => Arc::new(RulesProvider::new(&config.terms)?),
+        ProviderKind::Perspective => Arc::new(PerspectiveProvider {
+            http: reqwest::Client::builder()
+                .timeout(Duration::from_secs(config.timeout_secs))
+                .build()?,
+            endpoint: config.endpoint.clone(),
+            api_key: config.api_key.clone(),
+            attributes: config.attributes.clone(),
+        }),
+    })
+}
+
+/// Weighted terms, matched as whole words in any case
+pub struct RulesProvider {
+    terms: Vec<(Regex, f32)>,
+}
+
+impl RulesProvider {
+    pub fn new(terms: &BTreeMap<String, f32>) -> Result<Self, ToxicityError> {
+        let terms = terms
+            .iter()
+            .filter(|(term, _)| !term.trim().is_empty())
+            .map(|(term, weight)| {
+                let regex = Regex::new(&format!(r"(?i)\b{}\b", regex::escape(term.trim()))).map_err(|e| {
+                    ToxicityError::Term {
+                        term: term.clone(),
+                        message: e.to_string(),
+                    }
+                })?;
+                Ok((regex, weight.clamp(0.0, 1.0)))
+            })
+            .collect::<Result<_, ToxicityError>>()?;
+        Ok(Self { terms })
+    }
+
+    /// `1 - (1 - w1)(1 - w2)...` over the weights of the terms in `text`,
+    /// each counted once
+    pub fn toxicity(&self, text: &str) -> f32 {
+        let clean = self
+            .terms
+            .iter()
+            .filter(|(regex, _)| regex.is_match(text))
+            .fold(1.0f32, |clean, (_, weight)| clean * (1.0 - weight));
+        1.0 - clean
+    }
+}
+
+#[async_trait]
+impl ModerationProvider for RulesProvider {
+    fn model(&self) -> &str {
+        "rules"
+    }
+
+    async fn score(&self, text: &str) -> Result<BTreeMap<String, f32>, ToxicityError> {
+        Ok(BTreeMap::from([("toxicity".to_string(), self.toxicity(text))]))
+    }
+}
+
+/// Perspective's `POST comments:analyze`
+struct PerspectiveProvider {
+    http: reqwest::Client,
+    endpoint: String,
+    api_key: Option<String>,
+    attributes: Vec<String>,
+}
+
+#[async_trait]
+impl ModerationProvider for PerspectiveProvider {
+    fn model(&self) -> &str {
+        "perspective"
+    }
+
+    async fn score(&self, text: &str) -> Result<BTreeMap<String, f32>, ToxicityError> {
+        let attributes: Map<String, Value> = self
+            .attributes
+            .iter()
+            .map(|attribute| (attribute.to_ascii_uppercase(), json!({})))
+            .collect();
+        let mut request = self.http.post(&self.endpoint).json(&json!({
+            "comment": { "text": text },
+            "requestedAttributes": attributes,
+            "doNotStore": true,
+        }));
+        if let Some(key) = &self.api_key {
+            request = request.query(&[("key", key)]);
+        }
+
+        let response = request.send().await?;
+        let status = response.status();
+        if !status.is_success() {
+            let message = response.text().await.unwrap_or_default();
+            return Err(ToxicityError::Api {
+                status: status.as_u16(),
+                message: message.chars().take(500).collect(),
+            });
+        }
+        let body: Value = response.json().await?;
+        perspective_scores(&body)
+    }
+}
+
+/// `attributeScores.<ATTRIBUTE>.summaryScore.value` of each attribute, keyed
+/// in lower case
+pub fn perspective_scores(body: &Value) -> Result<BTreeMap<String, f32>, ToxicityError> {
+    let attributes = body["attributeScores"]
+        .as_object()
+        .ok_or_else(|| ToxicityError::Response("no attributeScores".into()))?;
+    attributes
+        .iter()
+        .map(|(attribute, scores)| {
+            let value = scores["summaryScore"]["value"]
+                .as_f64()
+                .ok_or_else(|| ToxicityError::Response(format!("no summary score for {}", attribute)))?;
+            Ok((attribute.to_ascii_lowercase(), value as f32))
+        })
+        .collect()
+}
+
+// ============================================
+// Service
+// ============================================
+
+/// How a new comment fared
+#[derive(Debug, Clone, Default)]
+pub struct Assessment {
+    /// `None` when the comment wasn't scored
+    pub score: Option<ToxicityScore>,
+    /// `Pending` or `Spam` when the score (or a failure to score) decides
+    /// the comment's status
+    pub route: Option<CommentStatus>,
+}
+
+pub struct ToxicityService {
+    provider: Arc<dyn ModerationProvider>,
+    config: ToxicityConfig,
+}
+
+impl ToxicityService {
+    pub fn new(config: ToxicityConfig) -> Result<Self, ToxicityError> {
+        Ok(Self::with_provider(provider(&config)?, config))
+    }
+
+    pub fn with_provider(provider: Arc<dyn ModerationProvider>, config: ToxicityConfig) -> Self {
+        Self { provider, config }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    /// Score a comment `user` (`None` for guests) is posting
+    pub async fn assess(&self, user: Option<&User>, text: &str) -> Assessment {
+        if !self.enabled() || user.map_or(false, User::can_moderate) {
+            return Assessment::default();
+        }
+
+        match self.provider.score(text).await {
+            Ok(attributes) => {
+                let score = ToxicityScore {
+                    score: attributes.values().copied().fold(0.0, f32::max),
+                    attributes,
+                    model: self.provider.model().to_string(),
+                };
+                Assessment {
+                    route: self.route(score.score),
+                    score: Some(score),
+                }
+            }
+            Err(e) => {
+                tracing::warn!(model = self.provider.model(), "Failed to score comment: {}", e);
+                Assessment {
+                    score: None,
+                    route: self.config.hold_on_error.then_some(CommentStatus::Pending),
+                }
+            }
+        }
+    }
+
+    /// The status a comment scoring `score` is given, if the score decides it
+    pub fn route(&self, score: f32) -> Option<CommentStatus> {
+        if score >= self.config.spam_threshold {
+            Some(CommentStatus::Spam)
+        } else if score >= self.config.pending_threshold {
+            Some(CommentStatus::Pending)
+        } else {
+            None
+        }
+    }
+}
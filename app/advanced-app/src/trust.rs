@@ -0,0 +1,259 @@
+//! Trusted Commenters
+//!
+//! Commenters whose comments would be held for moderation skip it once they
+//! have `approved_comments` approved comments on the site. They're matched by
+//! account: the signed-in user, or for guests the account whose verified
+//! email they comment with (unverified addresses never earn trust). Comments
+//! posted as the account and under its verified email both count.
+//!
+//! Admins override this per user with `PUT /admin/commenters/:user_id/trust`:
+//! `true` always skips moderation, `false` always holds the user's comments,
+//! even signed in. Every comment approved this way is recorded, and
+//! `GET /admin/comments/auto-approved?sample=20` picks some for spot checks.
+
+use crate::extractors::User;
+use crate::models::*;
+use crate::services::ServiceError;
+use crate::sites::Site;
+use serde::Deserialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Auto-approvals listed when no sample is asked for
+const AUDIT_LIMIT: i64 = 100;
+
+/// Columns of an `AutoApproval`, from `blog_comment_auto_approvals a`
+/// joined with `blog_comments c`
+const AUDIT_COLUMNS: &str = "a.comment_id, c.post_id, a.user_id, c.author_name, c.author_email, c.content, c.status,
+     a.reason, a.approved_comments, a.reviewed_by, a.reviewed_at, a.kept, a.created_at";
+
+/// `[app.trusted_commenters]` settings
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct TrustConfig {
+    /// Trust commenters by their approved comments (overrides apply either way)
+    pub enabled: bool,
+    /// Approved comments needed to skip moderation
+    pub approved_comments: i64,
+}
+
+impl Default for TrustConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            approved_comments: 3,
+        }
+    }
+}
+
+/// How a commenter's next comment is moderated
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Trust {
+    /// As the site's settings say
+    Unknown,
+    /// Skips moderation
+    Trusted(TrustGrant),
+    /// Always held (admin override)
+    Distrusted,
+}
+
+/// Why a commenter is trusted
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrustGrant {
+    pub user_id: Uuid,
+    pub reason: TrustReason,
+    pub approved_comments: i64,
+}
+
+pub struct TrustService {
+    db: PgPool,
+    config: TrustConfig,
+}
+
+impl TrustService {
+    pub fn new(db: PgPool, config: TrustConfig) -> Self {
+        Self { db, config }
+    }
+
+    /// How a comment by `user`, or a guest commenting as `email`, is moderated
+    pub async fn evaluate(&self, site: &Site, user: Option<&User>, email: &str) -> Result<Trust, ServiceError> {
+        let (user_id, verified_email): (Uuid, Option<String>) = match user {
+            Some(user) => {
+                let verified = sqlx::query_scalar("SELECT email FROM users WHERE id = $1 AND email_verified_at IS NOT NULL")
+                    .bind(user.id)
+                    .fetch_optional(&self.db)
+                    .await?;
+                (user.id, verified)
+            }
+            None => {
+                let account: Option<(Uuid, String)> = sqlx::query_as(
+                    "SELECT id, email FROM users WHERE LOWER(email) = LOWER($1) AND email_verified_at IS NOT NULL",
+                )
+                .bind(email)
+                .fetch_optional(&self.db)
+                .await?;
+                match account {
+                    Some((id, email)) => (id, Some(email)),
+                    None => return Ok(Trust::Unknown),
+                }
+            }
+        };
+
+        let trusted = self.override_for(site, user_id).await?;
+        if trusted == Some(false) {
+            return Ok(Trust::Distrusted);
+        }
+        let approved_comments = self.approved_comments(site, user_id, verified_email.as_deref()).await?;
+        let reason = match trusted {
+            Some(_) => TrustReason::Override,
+            None if self.config.enabled && approved_comments >= self.config.approved_comments => TrustReason::History,
+            None => return Ok(Trust::Unknown),
+        };
+        Ok(Trust::Trusted(TrustGrant {
+            user_id,
+            reason,
+            approved_comments,
+        }))
+    }
+
+    /// Record a comment that skipped moderation
+    pub async fn record(&self, site: &Site, comment: &Comment, grant: &TrustGrant) -> Result<(), ServiceError> {
+        sqlx::query(
+            "INSERT INTO blog_comment_auto_approvals (comment_id, site_id, user_id, reason, approved_comments)
+             VALUES ($1, $2, $3, $4, $5)",
+        )
+        .bind(comment.id)
+        .bind(site.id)
+        .bind(grant.user_id)
+        .bind(grant.reason)
+        .bind(grant.approved_comments as i32)
+        .execute(&self.db)
+        .await?;
+        tracing::info!(
+            site = %site.id,
+            comment = %comment.id,
+            user = %grant.user_id,
+            reason = ?grant.reason,
+            "Comment auto-approved"
+        );
+        Ok(())
+    }
+
+    /// A user's trust on the site
+    pub async fn get(&self, site: &Site, user_id: Uuid) -> Result<CommenterTrust, ServiceError> {
+        let email: Option<Option<String>> = sqlx::query_scalar(
+            "SELECT CASE WHEN email_verified_at IS NOT NULL THEN email END FROM users WHERE id = $1",
+        )
+        .bind(user_id)
+        .fetch_optional(&self.db)
+        .await?;
+        let Some(verified_email) = email else {
+            return Err(ServiceError::NotFound(format!("User not found: {}", user_id)));
+        };
+
+        let trusted = self.override_for(site, user_id).await?;
+        let approved_comments = self.approved_comments(site, user_id, verified_email.as_deref()).await?;
+        let auto_approved = trusted.unwrap_or(self.config.enabled && approved_comments >= self.config.approved_comments);
+        Ok(CommenterTrust {
+            user_id,
+            trusted,
+            approved_comments,
+            auto_approved,
+        })
+    }
+
+    /// Set (`None` clears) a user's override
+    #[tracing::instrument(name = "comments.trust", skip_all, fields(site = %site.id, user = %user_id))]
+    pub async fn set(&self, site: &Site, user_id: Uuid, trusted: Option<bool>, actor: Uuid) -> Result<CommenterTrust, ServiceError> {
+        // Checks the user exists
+        self.get(site, user_id).await?;
+        match trusted {
+            Some(trusted) => {
+                sqlx::query(
+                    "INSERT INTO blog_commenter_trust (site_id, user_id, trusted, set_by) VALUES ($1, $2, $3, $4)
+                     ON CONFLICT (site_id, user_id) DO UPDATE
+                     SET trusted = EXCLUDED.trusted, set_by = EXCLUDED.set_by, updated_at = NOW()",
+                )
+                .bind(site.id)
+                .bind(user_id)
+                .bind(trusted)
+                .bind(actor)
+                .execute(&self.db)
+                .await?;
+            }
+            None => {
+                sqlx::query("DELETE FROM blog_commenter_trust WHERE site_id = $1 AND user_id = $2")
+                    .bind(site.id)
+                    .bind(user_id)
+                    .execute(&self.db)
+                    .await?;
+            }
+        }
+        self.get(site, user_id).await
+    }
+
+    /// Auto-approved comments, newest first or a random sample
+    pub async fn audit(&self, site: &Site, query: &AutoApprovalQuery) -> Result<Vec<AutoApproval>, ServiceError> {
+        let (order, limit) = match query.sample {
+            Some(sample) => ("RANDOM()", sample.clamp(1, AUDIT_LIMIT)),
+            None => ("a.created_at DESC", AUDIT_LIMIT),
+        };
+        let approvals = sqlx::query_as(&format!(
+            "SELECT {} FROM blog_comment_auto_approvals a
+             JOIN blog_comments c ON c.id = a.comment_id
+             WHERE a.site_id = $1 AND (NOT $2 OR a.reviewed_at IS NULL)
+             ORDER BY {} LIMIT $3",
+            AUDIT_COLUMNS, order
+        ))
+        .bind(site.id)
+        .bind(query.unreviewed)
+        .bind(limit)
+        .fetch_all(&self.db)
+        .await?;
+        Ok(approvals)
+    }
+
+    /// Record a spot check of an auto-approved comment
+    pub async fn review(&self, site: &Site, comment_id: Uuid, actor: Uuid, keep: bool) -> Result<AutoApproval, ServiceError> {
+        sqlx::query_as(&format!(
+            "WITH reviewed AS (
+                 UPDATE blog_comment_auto_approvals
+                 SET reviewed_by = $3, reviewed_at = NOW(), kept = $4
+                 WHERE comment_id = $1 AND site_id = $2
+                 RETURNING *
+             )
+             SELECT {} FROM reviewed a JOIN blog_comments c ON c.id = a.comment_id",
+            AUDIT_COLUMNS
+        ))
+        .bind(comment_id)
+        .bind(site.id)
+        .bind(actor)
+        .bind(keep)
+        .fetch_optional(&self.db)
+        .await?
+        .ok_or_else(|| ServiceError::NotFound("Auto-approved comment not found".into()))
+    }
+
+    async fn override_for(&self, site: &Site, user_id: Uuid) -> Result<Option<bool>, ServiceError> {
+        let trusted = sqlx::query_scalar("SELECT trusted FROM blog_commenter_trust WHERE site_id = $1 AND user_id = $2")
+            .bind(site.id)
+            .bind(user_id)
+            .fetch_optional(&self.db)
+            .await?;
+        Ok(trusted)
+    }
+
+    async fn approved_comments(&self, site: &Site, user_id: Uuid, verified_email: Option<&str>) -> Result<i64, ServiceError> {
+        let count = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM blog_comments
+             WHERE site_id = $1 AND status = 'approved'
+               AND (author_id = $2 OR LOWER(author_email) = LOWER($3))",
+        )
+        .bind(site.id)
+        .bind(user_id)
+        .bind(verified_email)
+        .fetch_one(&self.db)
+        .await?;
+        Ok(count)
+    }
+}
@@ -0,0 +1,215 @@
+//! Saved Post Views
+//!
+//! Admins save filters of `GET /admin/posts` (status, author, category,
+//! created date range, search) together with the columns they export, and
+//! reopen them with `?view=<id>`, so each editor keeps their own queues
+//! ("my drafts", "scheduled this week"). Views are private to the user who
+//! saved them, per site. `GET /admin/posts.csv` exports the filtered list
+//! with the view's columns.
+
+use crate::models::*;
+use crate::services::ServiceError;
+use crate::sites::Site;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Most posts in one CSV export
+pub const EXPORT_LIMIT: i64 = 10_000;
+
+/// Saved views of the admin post list
+pub struct PostViewService {
+    db: PgPool,
+}
+
+impl PostViewService {
+    pub fn new(db: PgPool) -> Self {
+        Self { db }
+    }
+
+    /// A user's views on a site, by name
+    pub async fn list(&self, site: &Site, user_id: Uuid) -> Result<Vec<PostView>, ServiceError> {
+        let views = sqlx::query_as("SELECT * FROM admin_post_views WHERE site_id = $1 AND user_id = $2 ORDER BY name")
+            .bind(site.id)
+            .bind(user_id)
+            .fetch_all(&self.db)
+            .await?;
+        Ok(views)
+    }
+
+    /// One of a user's views
+    pub async fn get(&self, site: &Site, user_id: Uuid, id: Uuid) -> Result<PostView, ServiceError> {
+        sqlx::query_as("SELECT * FROM admin_post_views WHERE id = $1 AND site_id = $2 AND user_id = $3")
+            .bind(id)
+            .bind(site.id)
+            .bind(user_id)
+            .fetch_optional(&self.db)
+            .await?
+            .ok_or_else(|| ServiceError::NotFound("View not found".into()))
+    }
+
+    pub async fn create(&self, site: &Site, user_id: Uuid, req: PostViewRequest) -> Result<PostView, ServiceError> {
+        self.ensure_unique_name(site, user_id, &req.name, None).await?;
+
+        let view = sqlx::query_as(
+            "INSERT INTO admin_post_views (site_id, user_id, name, filters, columns)
+             VALUES ($1, $2, $3, $4, $5)
+             RETURNING *",
+        )
+        .bind(site.id)
+        .bind(user_id)
+        .bind(&req.name)
+        .bind(sqlx::types::Json(&req.filters))
+        .bind(sqlx::types::Json(columns(&req)))
+        .fetch_one(&self.db)
+        .await?;
+        Ok(view)
+    }
+
+    /// Replace a view's name, filters and columns
+    pub async fn update(&self, site: &Site, user_id: Uuid, id: Uuid, req: PostViewRequest) -> Result<PostView, ServiceError> {
+        self.ensure_unique_name(site, user_id, &req.name, Some(id)).await?;
+
+        sqlx::query_as(
+            "UPDATE admin_post_views SET name = $4, filters = $5, columns = $6, updated_at = NOW()
+             WHERE id = $1 AND site_id = $2 AND user_id = $3
+             RETURNING *",
+        )
+        .bind(id)
+        .bind(site.id)
+        .bind(user_id)
+        .bind(&req.name)
+        .bind(sqlx::types::Json(&req.filters))
+        .bind(sqlx::types::Json(columns(&req)))
+        .fetch_optional(&self.db)
+        .await?
+        .ok_or_else(|| ServiceError::NotFound("View not found".into()))
+    }
+
+    pub async fn delete(&self, site: &Site, user_id: Uuid, id: Uuid) -> Result<(), ServiceError> {
+        let result = sqlx::query("DELETE FROM admin_post_views WHERE id = $1 AND site_id = $2 AND user_id = $3")
+            .bind(id)
+            .bind(site.id)
+            .bind(user_id)
+            .execute(&self.db)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(ServiceError::NotFound("View not found".into()));
+        }
+        Ok(())
+    }
+
+    /// Filters and columns for a post list request: the view's, if one is
+    /// named, with those in the query taking precedence
+    pub async fn resolve(
+        &self,
+        site: &Site,
+        user_id: Uuid,
+        query: &AdminPostQuery,
+    ) -> Result<(PostFilters, Vec<PostColumn>), ServiceError> {
+        let (filters, mut columns) = match query.view {
+            Some(id) => {
+                let view = self.get(site, user_id, id).await?;
+                (view.filters.merge(query.filters()), view.columns)
+            }
+            None => (query.filters(), PostColumn::DEFAULT.to_vec()),
+        };
+        if let Some(list) = &query.columns {
+            columns = parse_columns(list)?;
+        }
+        Ok((filters, columns))
+    }
+
+    async fn ensure_unique_name(&self, site: &Site, user_id: Uuid, name: &str, except: Option<Uuid>) -> Result<(), ServiceError> {
+        let taken: bool = sqlx::query_scalar(
+            "SELECT EXISTS (SELECT 1 FROM admin_post_views
+                            WHERE site_id = $1 AND user_id = $2 AND name = $3 AND id IS DISTINCT FROM $4)",
+        )
+        .bind(site.id)
+        .bind(user_id)
+        .bind(name)
+        .bind(except)
+        .fetch_one(&self.db)
+        .await?;
+        if taken {
+            return Err(ServiceError::Validation(format!("A view named '{}' already exists", name)));
+        }
+        Ok(())
+    }
+}
+
+/// The request's columns, or the defaults when it names none
+fn columns(req: &PostViewRequest) -> Vec<PostColumn> {
+    if req.columns.is_empty() {
+        PostColumn::DEFAULT.to_vec()
+    } else {
+        req.columns.clone()
+    }
+}
+
+/// Columns from a comma-separated list of names
+fn parse_columns(list: &str) -> Result<Vec<PostColumn>, ServiceError> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(|name| {
+            serde_json::from_value(serde_json::Value::String(name.to_string()))
+                .map_err(|_| ServiceError::Validation(format!("Unknown column: {}", name)))
+        })
+        .collect()
+}
+
+// ============================================
+// CSV Export
+// ============================================
+
+/// RFC 4180 CSV of `posts` with a header row of `columns`
+pub fn to_csv(posts: &[PostWithRelations], columns: &[PostColumn]) -> String {
+    let mut csv = String::new();
+    push_row(&mut csv, columns.iter().map(|column| column.name().to_string()));
+    for post in posts {
+        push_row(&mut csv, columns.iter().map(|column| cell(post, *column)));
+    }
+    csv
+}
+
+fn cell(post: &PostWithRelations, column: PostColumn) -> String {
+    let names = |names: Vec<&str>| names.join("; ");
+    match column {
+        PostColumn::Id => post.post.id.to_string(),
+        PostColumn::Title => post.post.title.clone(),
+        PostColumn::Slug => post.post.slug.clone(),
+        PostColumn::Status => serde_json::to_value(&post.post.status)
+            .ok()
+            .and_then(|v| v.as_str().map(String::from))
+            .unwrap_or_default(),
+        PostColumn::Author => post.author.name.clone(),
+        PostColumn::Categories => names(post.categories.iter().map(|c| c.name.as_str()).collect()),
+        PostColumn::Tags => names(post.tags.iter().map(|t| t.name.as_str()).collect()),
+        PostColumn::CreatedAt => post.post.created_at.to_rfc3339(),
+        PostColumn::UpdatedAt => post.post.updated_at.to_rfc3339(),
+        PostColumn::PublishedAt => post.post.published_at.map(|d| d.to_rfc3339()).unwrap_or_default(),
+        PostColumn::Views => post.post.view_count.to_string(),
+        PostColumn::Comments => post.post.comment_count.to_string(),
+    }
+}
+
+fn push_row(csv: &mut String, cells: impl Iterator<Item = String>) {
+    let row: Vec<String> = cells.map(|cell| escape(&cell)).collect();
+    csv.push_str(&row.join(","));
+    csv.push_str("\r\n");
+}
+
+/// Quote a cell if needed; cells that spreadsheets would run as formulas
+/// are prefixed with `'`
+fn escape(cell: &str) -> String {
+    let cell = if cell.starts_with(['=', '+', '-', '@']) {
+        format!("'{}", cell)
+    } else {
+        cell.to_string()
+    };
+    if cell.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", cell.replace('"', "\"\""))
+    } else {
+        cell
+    }
+}
@@ -0,0 +1,212 @@
+//! Comment Votes
+//!
+//! Readers vote comments up or down (or, on sites set to `hearts`, only up)
+//! with `PUT /comments/:id/vote`. Votes are kept per user, or per visitor for
+//! guests (a hash of the site, IP address and user agent), so voting again
+//! changes the vote instead of adding one. Each comment carries its
+//! `score`, `upvotes` and `downvotes`, which `GET /posts/:id/comments?sort=top`
+//! orders by.
+//!
+//! Each voter gets `per_minute` votes a minute, and downvotes need an account
+//! at least `downvote_min_account_days` old, so fresh accounts and guests
+//! can't bury comments.
+
+use crate::extractors::{AuthUser, ClientInfo};
+use crate::models::*;
+use crate::services::ServiceError;
+use crate::sites::Site;
+use chrono::{Duration, Utc};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Instant;
+use uuid::Uuid;
+
+/// Voters tracked before expired rate limit windows are dropped
+const MAX_TRACKED_VOTERS: usize = 100_000;
+
+/// `[app.comment_votes]` settings
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct VoteConfig {
+    /// Voting style of sites that don't set `comment_votes`
+    pub style: VoteStyle,
+    /// Let visitors who aren't signed in upvote
+    pub guest_votes: bool,
+    /// Votes one voter may cast per minute
+    pub per_minute: u32,
+    /// Days an account must exist before it may downvote
+    pub downvote_min_account_days: i64,
+}
+
+impl Default for VoteConfig {
+    fn default() -> Self {
+        Self {
+            style: VoteStyle::UpDown,
+            guest_votes: true,
+            per_minute: 30,
+            downvote_min_account_days: 7,
+        }
+    }
+}
+
+/// Who is voting
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Voter {
+    User(Uuid),
+    /// Hash of a guest's site, address and user agent
+    Visitor(String),
+}
+
+impl Voter {
+    pub fn new(site: &Site, user: Option<&AuthUser>, client: &ClientInfo) -> Self {
+        match user {
+            Some(AuthUser(user)) => Voter::User(user.id),
+            None => {
+                let mut hasher = Sha256::new();
+                hasher.update(site.id.as_bytes());
+                hasher.update(client.ip.as_deref().unwrap_or("").as_bytes());
+                hasher.update(b"\n");
+                hasher.update(client.user_agent.as_deref().unwrap_or("").as_bytes());
+                Voter::Visitor(hex::encode(hasher.finalize()))
+            }
+        }
+    }
+
+    /// Key of the voter's votes in `blog_comment_votes`
+    pub(crate) fn key(&self) -> String {
+        match self {
+            Voter::User(id) => format!("user:{}", id),
+            Voter::Visitor(hash) => format!("visitor:{}", hash),
+        }
+    }
+}
+
+pub struct VoteService {
+    db: PgPool,
+    config: VoteConfig,
+    /// Votes per voter in the current minute
+    recent: Mutex<HashMap<String, (Instant, u32)>>,
+}
+
+impl VoteService {
+    pub fn new(db: PgPool, config: VoteConfig) -> Self {
+        Self {
+            db,
+            config,
+            recent: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The site's voting style
+    pub fn style(&self, site: &Site) -> VoteStyle {
+        site.config.comment_votes.unwrap_or(self.config.style)
+    }
+
+    /// Vote on an approved comment; `value` 0 takes the vote back
+    #[tracing::instrument(name = "comments.vote", skip_all, fields(site = %site.id, id = %comment_id))]
+    pub async fn vote(&self, site: &Site, comment_id: Uuid, voter: &Voter, value: i16) -> Result<CommentVotes, ServiceError> {
+        match (self.style(site), value) {
+            (VoteStyle::Off, _) => return Err(ServiceError::PermissionDenied),
+            (VoteStyle::Hearts, -1) => return Err(ServiceError::Validation("This site only takes upvotes".into())),
+            _ => {}
+        }
+        match voter {
+            Voter::Visitor(_) if !self.config.guest_votes => return Err(ServiceError::PermissionDenied),
+            Voter::Visitor(_) if value == -1 => return Err(ServiceError::PermissionDenied),
+            Voter::User(user_id) if value == -1 => self.ensure_can_downvote(*user_id).await?,
+            _ => {}
+        }
+        self.admit(voter)?;
+
+        let key = voter.key();
+        let mut tx = self.db.begin().await?;
+
+        // Locked, so concurrent votes count each other
+        let approved: Option<Uuid> = sqlx::query_scalar(
+            "SELECT id FROM blog_comments WHERE id = $1 AND site_id = $2 AND status = 'approved' FOR UPDATE",
+        )
+        .bind(comment_id)
+        .bind(site.id)
+        .fetch_optional(&mut *tx)
+        .await?;
+        if approved.is_none() {
+            return Err(ServiceError::NotFound("Comment not found".into()));
+        }
+
+        if value == 0 {
+            sqlx::query("DELETE FROM blog_comment_votes WHERE comment_id = $1 AND voter = $2")
+                .bind(comment_id)
+                .bind(&key)
+                .execute(&mut *tx)
+                .await?;
+        } else {
+            sqlx::query(
+                "INSERT INTO blog_comment_votes (comment_id, voter, value) VALUES ($1, $2, $3)
+                 ON CONFLICT (comment_id, voter) DO UPDATE SET value = EXCLUDED.value, updated_at = NOW()",
+            )
+            .bind(comment_id)
+            .bind(&key)
+            .bind(value)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        let (score, upvotes, downvotes): (i32, i32, i32) = sqlx::query_as(
+            "UPDATE blog_comments c
+             SET upvotes = v.up, downvotes = v.down, score = v.up - v.down
+             FROM (SELECT COUNT(*) FILTER (WHERE value > 0)::int AS up,
+                          COUNT(*) FILTER (WHERE value < 0)::int AS down
+                   FROM blog_comment_votes WHERE comment_id = $1) v
+             WHERE c.id = $1
+             RETURNING c.score, c.upvotes, c.downvotes",
+        )
+        .bind(comment_id)
+        .fetch_one(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(CommentVotes {
+            comment_id,
+            score,
+            upvotes,
+            downvotes,
+            viewer_vote: (value != 0).then_some(value),
+        })
+    }
+
+    async fn ensure_can_downvote(&self, user_id: Uuid) -> Result<(), ServiceError> {
+        let created_at: Option<chrono::DateTime<Utc>> = sqlx::query_scalar("SELECT created_at FROM users WHERE id = $1")
+            .bind(user_id)
+            .fetch_optional(&self.db)
+            .await?;
+        let min_age = Duration::days(self.config.downvote_min_account_days);
+        match created_at {
+            Some(created_at) if Utc::now() - created_at >= min_age => Ok(()),
+            _ => Err(ServiceError::PermissionDenied),
+        }
+    }
+
+    /// Count a vote against the voter's per-minute limit
+    fn admit(&self, voter: &Voter) -> Result<(), ServiceError> {
+        let window = std::time::Duration::from_secs(60);
+        let now = Instant::now();
+        let mut recent = self.recent.lock().unwrap();
+        if recent.len() >= MAX_TRACKED_VOTERS {
+            recent.retain(|_, (started, _)| now.duration_since(*started) < window);
+        }
+
+        let (started, count) = recent.entry(voter.key()).or_insert((now, 0));
+        if now.duration_since(*started) >= window {
+            *started = now;
+            *count = 0;
+        }
+        if *count >= self.config.per_minute {
+            return Err(ServiceError::RateLimited("Too many votes, try again in a minute".into()));
+        }
+        *count += 1;
+        Ok(())
+    }
+}
@@ -0,0 +1,441 @@
+//! Widgets and Sidebars
+//!
+//! Widget types are registered in a `WidgetRegistry` (built-ins plus any
+//! contributed by plugins). Admins place widget instances into named sidebar
+//! areas; each instance stores its own settings in `blog_widget_instances`.
+//! Sidebars are configured per site.
+
+use crate::models::*;
+use crate::services::ServiceError;
+use crate::sites::Site;
+use axum::async_trait;
+use rustpress_apps::prelude::*;
+use serde_json::Value;
+use sqlx::PgPool;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+/// Sidebar areas available to themes
+pub const SIDEBARS: &[(&str, &str)] = &[
+    ("primary", "Primary Sidebar"),
+    ("footer", "Footer"),
+];
+
+/// Data available to widgets while rendering
+pub struct WidgetContext<'a> {
+    pub db: &'a PgPool,
+    pub site: &'a Site,
+    pub hooks: &'a HookRegistry,
+}
+
+/// A widget type that can be placed in sidebars
+#[async_trait]
+pub trait Widget: Send + Sync {
+    /// Unique type identifier, e.g. `recent_posts`
+    fn id(&self) -> &'static str;
+
+    /// Human readable name for the admin UI
+    fn name(&self) -> &'static str;
+
+    /// Settings used when an instance doesn't override them
+    fn default_settings(&self) -> Value {
+        Value::Object(Default::default())
+    }
+
+    /// Render the widget body as HTML
+    async fn render(&self, ctx: &WidgetContext<'_>, settings: &Value) -> Result<String, ServiceError>;
+}
+
+/// Registry of widget types
+#[derive(Default)]
+pub struct WidgetRegistry {
+    widgets: RwLock<HashMap<String, Arc<dyn Widget>>>,
+}
+
+impl WidgetRegistry {
+    /// Registry pre-populated with the built-in widgets
+    pub fn with_builtins() -> Self {
+        let mut widgets: HashMap<String, Arc<dyn Widget>> = HashMap::new();
+        for widget in [
+            Arc::new(RecentPostsWidget) as Arc<dyn Widget>,
+            Arc::new(TagCloudWidget),
+            Arc::new(CustomHtmlWidget),
+        ] {
+            widgets.insert(widget.id().to_string(), widget);
+        }
+        Self {
+            widgets: RwLock::new(widgets),
+        }
+    }
+
+    /// Register a widget type (plugins call this on activation)
+    pub async fn register(&self, widget: Arc<dyn Widget>) {
+        let id = widget.id().to_string();
+        if self.widgets.write().await.insert(id.clone(), widget).is_some() {
+            tracing::warn!("Widget type '{}' was replaced", id);
+        }
+    }
+
+    pub async fn get(&self, id: &str) -> Option<Arc<dyn Widget>> {
+        self.widgets.read().await.get(id).cloned()
+    }
+
+    /// Registered widget types for the admin UI
+    pub async fn list(&self) -> Vec<WidgetTypeInfo> {
+        let mut types: Vec<WidgetTypeInfo> = self
+            .widgets
+            .read()
+            .await
+            .values()
+            .map(|w| WidgetTypeInfo {
+                id: w.id().to_string(),
+                name: w.name().to_string(),
+                default_settings: w.default_settings(),
+            })
+            .collect();
+        types.sort_by(|a, b| a.id.cmp(&b.id));
+        types
+    }
+}
+
+/// Widget service
+pub struct WidgetService {
+    db: PgPool,
+    hooks: Arc<HookRegistry>,
+    registry: Arc<WidgetRegistry>,
+}
+
+impl WidgetService {
+    pub fn new(db: PgPool, hooks: Arc<HookRegistry>, registry: Arc<WidgetRegistry>) -> Self {
+        Self { db, hooks, registry }
+    }
+
+    pub fn registry(&self) -> &Arc<WidgetRegistry> {
+        &self.registry
+    }
+
+    /// List instances grouped by sidebar, in display order
+    pub async fn list_sidebars(&self, site: &Site) -> Result<BTreeMap<String, Vec<WidgetInstance>>, ServiceError> {
+        let instances: Vec<WidgetInstance> = sqlx::query_as(
+            "SELECT * FROM blog_widget_instances WHERE site_id = $1 ORDER BY sidebar, position, created_at"
+        )
+        .bind(site.id)
+        .fetch_all(&self.db)
+        .await?;
+
+        let mut sidebars: BTreeMap<String, Vec<WidgetInstance>> = SIDEBARS
+            .iter()
+            .map(|(id, _)| (id.to_string(), Vec::new()))
+            .collect();
+        for instance in instances {
+            sidebars.entry(instance.sidebar.clone()).or_default().push(instance);
+        }
+
+        Ok(sidebars)
+    }
+
+    /// Add a widget instance to a sidebar
+    pub async fn create(&self, site: &Site, req: CreateWidgetRequest) -> Result<WidgetInstance, ServiceError> {
+        validate_sidebar(&req.sidebar)?;
+        if self.registry.get(&req.widget_type).await.is_none() {
+            return Err(ServiceError::Validation(format!("Unknown widget type: {}", req.widget_type)));
+        }
+
+        let position = match req.position {
+            Some(position) => position,
+            None => sqlx::query_scalar(
+                "SELECT COALESCE(MAX(position) + 1, 0) FROM blog_widget_instances WHERE sidebar = $1 AND site_id = $2"
+            )
+            .bind(&req.sidebar)
+            .bind(site.id)
+            .fetch_one(&self.db)
+            .await?,
+        };
+
+        let instance = sqlx::query_as(
+            r#"INSERT INTO blog_widget_instances (site_id, sidebar, widget_type, title, position, settings)
+               VALUES ($1, $2, $3, $4, $5, $6)
+               RETURNING *"#
+        )
+        .bind(site.id)
+        .bind(&req.sidebar)
+        .bind(&req.widget_type)
+        .bind(&req.title)
+        .bind(position)
+        .bind(req.settings.unwrap_or_else(|| Value::Object(Default::default())))
+        .fetch_one(&self.db)
+        .await?;
+
+        Ok(instance)
+    }
+
+    /// Update an instance's placement or settings
+    pub async fn update(&self, site: &Site, id: Uuid, req: UpdateWidgetRequest) -> Result<WidgetInstance, ServiceError> {
+        if let Some(ref sidebar) = req.sidebar {
+            validate_sidebar(sidebar)?;
+        }
+
+        sqlx::query_as(
+            r#"UPDATE blog_widget_instances SET
+               sidebar = COALESCE($2, sidebar),
+               title = COALESCE($3, title),
+               position = COALESCE($4, position),
+               settings = COALESCE($5, settings),
+               updated_at = NOW()
+               WHERE id = $1 AND site_id = $6
+               RETURNING *"#
+        )
+        .bind(id)
+        .bind(&req.sidebar)
+        .bind(&req.title)
+        .bind(req.position)
+        .bind(&req.settings)
+        .bind(site.id)
+        .fetch_optional(&self.db)
+        .await?
+        .ok_or_else(|| ServiceError::NotFound("Widget not found".into()))
+    }
+
+    /// Remove an instance
+    pub async fn delete(&self, site: &Site, id: Uuid) -> Result<(), ServiceError> {
+        let result = sqlx::query("DELETE FROM blog_widget_instances WHERE id = $1 AND site_id = $2")
+            .bind(id)
+            .bind(site.id)
+            .execute(&self.db)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(ServiceError::NotFound("Widget not found".into()));
+        }
+        Ok(())
+    }
+
+    /// Render one sidebar to HTML
+    pub async fn render_sidebar(&self, site: &Site, sidebar: &str) -> Result<String, ServiceError> {
+        validate_sidebar(sidebar)?;
+
+        let instances: Vec<WidgetInstance> = sqlx::query_as(
+            "SELECT * FROM blog_widget_instances WHERE sidebar = $1 AND site_id = $2 ORDER BY position, created_at"
+        )
+        .bind(sidebar)
+        .bind(site.id)
+        .fetch_all(&self.db)
+        .await?;
+
+        self.render_instances(site, sidebar, &instances).await
+    }
+
+    /// Render every sidebar, keyed by sidebar id (used by theme templates)
+    pub async fn render_all(&self, site: &Site) -> Result<HashMap<String, String>, ServiceError> {
+        let mut rendered = HashMap::new();
+        for (sidebar, instances) in self.list_sidebars(site).await? {
+            let html = self.render_instances(site, &sidebar, &instances).await?;
+            rendered.insert(sidebar, html);
+        }
+        Ok(rendered)
+    }
+
+    async fn render_instances(
+        &self,
+        site: &Site,
+        sidebar: &str,
+        instances: &[WidgetInstance],
+    ) -> Result<String, ServiceError> {
+        let ctx = WidgetContext {
+            db: &self.db,
+            site,
+            hooks: &self.hooks,
+        };
+        let filter_ctx = crate::hooks::filter_context();
+
+        let mut html = format!("<aside class=\"sidebar sidebar-{}\">", sidebar);
+        for instance in instances {
+            let Some(widget) = self.registry.get(&instance.widget_type).await else {
+                tracing::warn!("Skipping widget {} with unknown type '{}'", instance.id, instance.widget_type);
+                continue;
+            };
+
+            let settings = merge_settings(widget.default_settings(), &instance.settings);
+            let body = match widget.render(&ctx, &settings).await {
+                Ok(body) => body,
+                Err(e) => {
+                    // One broken widget shouldn't take the whole sidebar down
+                    tracing::error!("Widget {} failed to render: {}", instance.id, e);
+                    continue;
+                }
+            };
+
+            let mut output = format!(
+                "<section id=\"widget-{}\" class=\"widget widget-{}\">",
+                instance.id, instance.widget_type
+            );
+            if let Some(ref title) = instance.title {
+                output.push_str(&format!("<h2 class=\"widget-title\">{}</h2>", escape_html(title)));
+            }
+            output.push_str(&body);
+            output.push_str("</section>");
+
+            let output = self
+                .hooks
+                .apply_filters("widget_output", &filter_ctx, output)
+                .await
+                .map_err(|e| ServiceError::Template(e.to_string()))?;
+            html.push_str(&output);
+        }
+        html.push_str("</aside>");
+
+        self.hooks
+            .apply_filters("dynamic_sidebar", &filter_ctx, html)
+            .await
+            .map_err(|e| ServiceError::Template(e.to_string()))
+    }
+}
+
+// ============================================
+// Built-in Widgets
+// ============================================
+
+/// Latest published posts
+pub struct RecentPostsWidget;
+
+#[async_trait]
+impl Widget for RecentPostsWidget {
+    fn id(&self) -> &'static str {
+        "recent_posts"
+    }
+
+    fn name(&self) -> &'static str {
+        "Recent Posts"
+    }
+
+    fn default_settings(&self) -> Value {
+        serde_json::json!({ "count": 5 })
+    }
+
+    async fn render(&self, ctx: &WidgetContext<'_>, settings: &Value) -> Result<String, ServiceError> {
+        let count = settings["count"].as_i64().unwrap_or(5).clamp(1, 20);
+
+        let posts: Vec<(String, String)> = sqlx::query_as(
+            "SELECT title, slug FROM blog_posts WHERE status = 'published' AND site_id = $2 ORDER BY published_at DESC LIMIT $1"
+        )
+        .bind(count)
+        .bind(ctx.site.id)
+        .fetch_all(ctx.db)
+        .await?;
+
+        let items: String = posts
+            .iter()
+            .map(|(title, slug)| {
+                format!(
+                    "<li><a href=\"{}\">{}</a></li>",
+                    ctx.site.url(&format!("/read/{}", slug)),
+                    escape_html(title)
+                )
+            })
+            .collect();
+        Ok(format!("<ul class=\"recent-posts\">{}</ul>", items))
+    }
+}
+
+/// Tags sized by post count
+pub struct TagCloudWidget;
+
+#[async_trait]
+impl Widget for TagCloudWidget {
+    fn id(&self) -> &'static str {
+        "tag_cloud"
+    }
+
+    fn name(&self) -> &'static str {
+        "Tag Cloud"
+    }
+
+    fn default_settings(&self) -> Value {
+        serde_json::json!({ "limit": 30 })
+    }
+
+    async fn render(&self, ctx: &WidgetContext<'_>, settings: &Value) -> Result<String, ServiceError> {
+        let limit = settings["limit"].as_i64().unwrap_or(30).clamp(1, 100);
+
+        let tags: Vec<Tag> = sqlx::query_as(
+            "SELECT * FROM blog_tags WHERE post_count > 0 AND site_id = $2 ORDER BY post_count DESC, name LIMIT $1"
+        )
+        .bind(limit)
+        .bind(ctx.site.id)
+        .fetch_all(ctx.db)
+        .await?;
+
+        let max = tags.iter().map(|t| t.post_count).max().unwrap_or(1).max(1) as f64;
+        let links: Vec<String> = tags
+            .iter()
+            .map(|tag| {
+                // Scale from 1 (smallest) to 5 (largest)
+                let size = 1 + ((tag.post_count as f64 / max) * 4.0).round() as i32;
+                format!(
+                    "<a href=\"{}\" class=\"tag-size-{}\">{}</a>",
+                    ctx.site.url(&format!("/tag/{}", tag.slug)),
+                    size,
+                    escape_html(&tag.name)
+                )
+            })
+            .collect();
+        Ok(format!("<div class=\"tag-cloud\">{}</div>", links.join(" ")))
+    }
+}
+
+/// Arbitrary HTML, run through the `widget_text` filter (shortcodes, autop)
+pub struct CustomHtmlWidget;
+
+#[async_trait]
+impl Widget for CustomHtmlWidget {
+    fn id(&self) -> &'static str {
+        "custom_html"
+    }
+
+    fn name(&self) -> &'static str {
+        "Custom HTML"
+    }
+
+    fn default_settings(&self) -> Value {
+        serde_json::json!({ "content": "" })
+    }
+
+    async fn render(&self, ctx: &WidgetContext<'_>, settings: &Value) -> Result<String, ServiceError> {
+        let content = settings["content"].as_str().unwrap_or_default().to_string();
+        ctx.hooks
+            .apply_filters("widget_text", &crate::hooks::filter_context(), content)
+            .await
+            .map_err(|e| ServiceError::Template(e.to_string()))
+    }
+}
+
+fn validate_sidebar(sidebar: &str) -> Result<(), ServiceError> {
+    if SIDEBARS.iter().any(|(id, _)| *id == sidebar) {
+        Ok(())
+    } else {
+        Err(ServiceError::NotFound(format!("Sidebar not found: {}", sidebar)))
+    }
+}
+
+/// Overlay instance settings on the widget defaults
+fn merge_settings(defaults: Value, overrides: &Value) -> Value {
+    match (defaults, overrides) {
+        (Value::Object(mut base), Value::Object(extra)) => {
+            for (key, value) in extra {
+                base.insert(key.clone(), value.clone());
+            }
+            Value::Object(base)
+        }
+        (defaults, Value::Null) => defaults,
+        (_, overrides) => overrides.clone(),
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
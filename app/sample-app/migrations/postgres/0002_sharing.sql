@@ -0,0 +1,24 @@
+-- Todos belong to a user and may be filed under a list shared with others.
+-- Todos created before ownership existed keep a NULL owner and are no longer listed.
+CREATE TABLE IF NOT EXISTS todo_lists (
+    id BIGSERIAL PRIMARY KEY,
+    owner_id UUID NOT NULL,
+    name VARCHAR(100) NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_todo_lists_owner ON todo_lists(owner_id);
+
+CREATE TABLE IF NOT EXISTS todo_list_members (
+    list_id BIGINT NOT NULL REFERENCES todo_lists(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL,
+    permission VARCHAR(10) NOT NULL CHECK (permission IN ('view', 'edit')),
+    PRIMARY KEY (list_id, user_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_todo_list_members_user ON todo_list_members(user_id);
+
+ALTER TABLE todos ADD COLUMN user_id UUID;
+ALTER TABLE todos ADD COLUMN list_id BIGINT REFERENCES todo_lists(id) ON DELETE SET NULL;
+
+CREATE INDEX IF NOT EXISTS idx_todos_user ON todos(user_id);
+CREATE INDEX IF NOT EXISTS idx_todos_list ON todos(list_id);
@@ -0,0 +1,15 @@
+-- Change log for live sync. A stream is a shared list (`list:<id>`) or a
+-- user's unfiled todos (`user:<uuid>`); each has its own revision counter.
+CREATE TABLE IF NOT EXISTS todo_streams (
+    stream VARCHAR(64) PRIMARY KEY,
+    revision BIGINT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS todo_changes (
+    stream VARCHAR(64) NOT NULL,
+    revision BIGINT NOT NULL,
+    op VARCHAR(10) NOT NULL CHECK (op IN ('create', 'update', 'delete')),
+    todo_id BIGINT NOT NULL,
+    changed_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (stream, revision)
+);
@@ -0,0 +1,8 @@
+-- Batch sync from offline clients.
+-- UUID the client gave a todo it created, so retried batches don't duplicate it
+ALTER TABLE todos ADD COLUMN client_id UUID;
+-- Stream revision of the todo's last change; clients send it back as the
+-- base revision of their edits
+ALTER TABLE todos ADD COLUMN revision BIGINT NOT NULL DEFAULT 0;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_todos_client_id ON todos(client_id);
@@ -0,0 +1,17 @@
+-- Notes, title/notes search and saved filters.
+ALTER TABLE todos ADD COLUMN notes TEXT;
+
+-- Trigram indexes back the `<%` word-similarity search
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+CREATE INDEX IF NOT EXISTS idx_todos_title_trgm ON todos USING GIN (title gin_trgm_ops);
+CREATE INDEX IF NOT EXISTS idx_todos_notes_trgm ON todos USING GIN (notes gin_trgm_ops);
+
+-- Named filters; `definition` is a JSON `TodoFilter`
+CREATE TABLE IF NOT EXISTS todo_filters (
+    id BIGSERIAL PRIMARY KEY,
+    owner_id UUID NOT NULL,
+    name VARCHAR(100) NOT NULL,
+    definition TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_todo_filters_owner ON todo_filters(owner_id);
@@ -0,0 +1,25 @@
+-- Todos belong to a user and may be filed under a list shared with others.
+-- Todos created before ownership existed keep a NULL owner and are no longer listed.
+-- User IDs are UUIDs, which SQLx stores as 16-byte blobs on SQLite.
+CREATE TABLE IF NOT EXISTS todo_lists (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    owner_id BLOB NOT NULL,
+    name TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_todo_lists_owner ON todo_lists(owner_id);
+
+CREATE TABLE IF NOT EXISTS todo_list_members (
+    list_id INTEGER NOT NULL REFERENCES todo_lists(id) ON DELETE CASCADE,
+    user_id BLOB NOT NULL,
+    permission TEXT NOT NULL CHECK (permission IN ('view', 'edit')),
+    PRIMARY KEY (list_id, user_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_todo_list_members_user ON todo_list_members(user_id);
+
+ALTER TABLE todos ADD COLUMN user_id BLOB;
+ALTER TABLE todos ADD COLUMN list_id INTEGER REFERENCES todo_lists(id) ON DELETE SET NULL;
+
+CREATE INDEX IF NOT EXISTS idx_todos_user ON todos(user_id);
+CREATE INDEX IF NOT EXISTS idx_todos_list ON todos(list_id);
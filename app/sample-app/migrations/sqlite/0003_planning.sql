@@ -0,0 +1,35 @@
+-- Projects, labels, priorities, due dates and recurrence.
+CREATE TABLE IF NOT EXISTS projects (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    owner_id BLOB NOT NULL,
+    name TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_projects_owner ON projects(owner_id);
+
+ALTER TABLE todos ADD COLUMN project_id INTEGER REFERENCES projects(id) ON DELETE SET NULL;
+-- 0 = low, 1 = normal, 2 = high, 3 = urgent
+ALTER TABLE todos ADD COLUMN priority INTEGER NOT NULL DEFAULT 1;
+-- UTC timestamps as written by SQLx
+ALTER TABLE todos ADD COLUMN due_at TEXT;
+-- IANA zone the due time was entered in; recurrences keep its wall-clock time
+ALTER TABLE todos ADD COLUMN timezone TEXT NOT NULL DEFAULT 'UTC';
+-- RRULE subset, e.g. FREQ=WEEKLY;BYDAY=MO,WE
+ALTER TABLE todos ADD COLUMN recurrence TEXT;
+-- Occurrences left including this one (RRULE COUNT), NULL if unbounded
+ALTER TABLE todos ADD COLUMN recurrence_left INTEGER;
+-- Set once the next occurrence has been created
+ALTER TABLE todos ADD COLUMN recurrence_spawned INTEGER NOT NULL DEFAULT 0;
+
+CREATE INDEX IF NOT EXISTS idx_todos_project ON todos(project_id);
+CREATE INDEX IF NOT EXISTS idx_todos_due ON todos(due_at) WHERE completed = 0;
+CREATE INDEX IF NOT EXISTS idx_todos_recurring ON todos(due_at)
+    WHERE recurrence IS NOT NULL AND recurrence_spawned = 0;
+
+CREATE TABLE IF NOT EXISTS todo_labels (
+    todo_id INTEGER NOT NULL REFERENCES todos(id) ON DELETE CASCADE,
+    label TEXT NOT NULL,
+    PRIMARY KEY (todo_id, label)
+);
+
+CREATE INDEX IF NOT EXISTS idx_todo_labels_label ON todo_labels(label);
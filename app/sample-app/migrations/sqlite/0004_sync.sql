@@ -0,0 +1,15 @@
+-- Change log for live sync. A stream is a shared list (`list:<id>`) or a
+-- user's unfiled todos (`user:<uuid>`); each has its own revision counter.
+CREATE TABLE IF NOT EXISTS todo_streams (
+    stream TEXT PRIMARY KEY,
+    revision INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS todo_changes (
+    stream TEXT NOT NULL,
+    revision INTEGER NOT NULL,
+    op TEXT NOT NULL CHECK (op IN ('create', 'update', 'delete')),
+    todo_id INTEGER NOT NULL,
+    changed_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (stream, revision)
+);
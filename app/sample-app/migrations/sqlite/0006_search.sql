@@ -0,0 +1,30 @@
+-- Notes, title/notes search and saved filters.
+ALTER TABLE todos ADD COLUMN notes TEXT;
+
+-- FTS5 index over titles and notes, kept in sync by triggers
+CREATE VIRTUAL TABLE IF NOT EXISTS todos_fts USING fts5(title, notes, content='todos', content_rowid='id');
+
+CREATE TRIGGER IF NOT EXISTS todos_fts_insert AFTER INSERT ON todos BEGIN
+    INSERT INTO todos_fts(rowid, title, notes) VALUES (new.id, new.title, new.notes);
+END;
+
+CREATE TRIGGER IF NOT EXISTS todos_fts_delete AFTER DELETE ON todos BEGIN
+    INSERT INTO todos_fts(todos_fts, rowid, title, notes) VALUES ('delete', old.id, old.title, old.notes);
+END;
+
+CREATE TRIGGER IF NOT EXISTS todos_fts_update AFTER UPDATE OF title, notes ON todos BEGIN
+    INSERT INTO todos_fts(todos_fts, rowid, title, notes) VALUES ('delete', old.id, old.title, old.notes);
+    INSERT INTO todos_fts(rowid, title, notes) VALUES (new.id, new.title, new.notes);
+END;
+
+INSERT INTO todos_fts(todos_fts) VALUES ('rebuild');
+
+-- Named filters; `definition` is a JSON `TodoFilter`
+CREATE TABLE IF NOT EXISTS todo_filters (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    owner_id BLOB NOT NULL,
+    name TEXT NOT NULL,
+    definition TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_todo_filters_owner ON todo_filters(owner_id);
@@ -0,0 +1,393 @@
+//! Search and Saved Filters
+//!
+//! A [`TodoFilter`] describes a set of todos the user can see. It compiles to
+//! SQL with `QueryBuilder`, adding a condition and its bound values for each
+//! field that's set; `GET /todos`, search and saved filters all run through
+//! it. Saved filters store the definition as JSON, so results always reflect
+//! the current todos.
+//!
+//! Search matches words in titles and notes: trigram word similarity on
+//! PostgreSQL (`pg_trgm`), FTS5 prefix queries on SQLite.
+
+use crate::{attach_labels, normalize_labels, ApiError, AppState, Priority, Scope, Todo, TODO_COLUMNS};
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::Utc;
+use rustpress_auth::db::{Db, DbPool};
+use rustpress_auth::problem::ProblemDetails;
+use rustpress_auth::AuthUser;
+use serde::{Deserialize, Serialize};
+use sqlx::QueryBuilder;
+use std::sync::Arc;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+use validator::Validate;
+
+/// Todos returned per request
+const MAX_RESULTS: i64 = 100;
+
+/// Saved filters per user
+const MAX_FILTERS: i64 = 50;
+
+// ============================================
+// Filters
+// ============================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Sort {
+    /// Oldest first
+    Created,
+    /// Soonest due first, undated last
+    Due,
+    /// Most urgent first
+    Priority,
+    /// Best search match first; needs `q`
+    Relevance,
+}
+
+/// Conditions on the todos a user can see; unset fields don't filter
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+#[serde(default)]
+pub struct TodoFilter {
+    /// Words to find in titles and notes
+    pub q: Option<String>,
+    pub scope: Option<Scope>,
+    pub completed: Option<bool>,
+    pub list_id: Option<i64>,
+    pub project_id: Option<i64>,
+    /// Todos with any of these labels
+    pub labels: Vec<String>,
+    /// At least this priority
+    pub min_priority: Option<Priority>,
+    /// Open todos due within this many days, overdue ones included
+    pub due_within_days: Option<i64>,
+    /// Default `relevance` with `q`, `created` otherwise
+    pub sort: Option<Sort>,
+}
+
+impl TryFrom<String> for TodoFilter {
+    type Error = serde_json::Error;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        serde_json::from_str(&value)
+    }
+}
+
+impl TodoFilter {
+    /// Normalize a filter from a client, rejecting ones that can't run
+    fn validate(mut self) -> Result<Self, ApiError> {
+        self.q = self.q.map(|q| q.trim().to_string()).filter(|q| !q.is_empty());
+        if self.q.as_ref().is_some_and(|q| q.chars().count() > 200) {
+            return Err(ApiError::BadRequest("Search text is limited to 200 characters".into()));
+        }
+        if self.sort == Some(Sort::Relevance) && self.q.is_none() {
+            return Err(ApiError::BadRequest("Sorting by relevance needs `q`".into()));
+        }
+        if self.due_within_days.is_some_and(|days| !(0..=366).contains(&days)) {
+            return Err(ApiError::BadRequest("`due_within_days` must be between 0 and 366".into()));
+        }
+        self.labels = normalize_labels(&self.labels)?;
+        Ok(self)
+    }
+
+    /// Matching todos visible to `user`, at most `limit` (capped at 100)
+    pub async fn fetch(&self, db: &DbPool, user: Uuid, limit: Option<i64>) -> Result<Vec<Todo>, ApiError> {
+        let mut query = QueryBuilder::<Db>::new("");
+        if let Some(q) = &self.q {
+            query.push("WITH hits AS (");
+            push_hits(&mut query, q);
+            query.push(") ");
+        }
+
+        query.push(format!("SELECT {} FROM todos", TODO_COLUMNS));
+        if self.q.is_some() {
+            query.push(" JOIN hits ON hits.hit_id = todos.id");
+        }
+        query.push(" WHERE ");
+        self.scope.unwrap_or_default().push_condition(&mut query, user);
+
+        if let Some(completed) = self.completed {
+            query.push(" AND completed = ").push_bind(completed);
+        }
+        if let Some(list_id) = self.list_id {
+            query.push(" AND list_id = ").push_bind(list_id);
+        }
+        if let Some(project_id) = self.project_id {
+            query.push(" AND project_id = ").push_bind(project_id);
+        }
+        if !self.labels.is_empty() {
+            query.push(" AND id IN (SELECT todo_id FROM todo_labels WHERE label IN (");
+            let mut labels = query.separated(", ");
+            for label in &self.labels {
+                labels.push_bind(label.trim().to_lowercase());
+            }
+            labels.push_unseparated("))");
+        }
+        if let Some(priority) = self.min_priority {
+            query.push(" AND priority >= ").push_bind(i16::from(priority));
+        }
+        if let Some(days) = self.due_within_days {
+            query
+                .push(" AND completed = false AND due_at < ")
+                .push_bind(Utc::now() + chrono::Duration::days(days));
+        }
+
+        let sort = match (self.sort, &self.q) {
+            (Some(Sort::Relevance), None) | (None, None) => Sort::Created,
+            (None, Some(_)) => Sort::Relevance,
+            (Some(sort), _) => sort,
+        };
+        query.push(match sort {
+            Sort::Created => " ORDER BY id",
+            Sort::Due => " ORDER BY due_at IS NULL, due_at, priority DESC, id",
+            Sort::Priority => " ORDER BY priority DESC, due_at IS NULL, due_at, id",
+            Sort::Relevance => " ORDER BY hits.score DESC, id",
+        });
+        query.push(" LIMIT ").push_bind(limit.unwrap_or(MAX_RESULTS).clamp(1, MAX_RESULTS));
+
+        let mut todos: Vec<Todo> = query.build_query_as().fetch_all(db).await?;
+        attach_labels(db, &mut todos).await?;
+        Ok(todos)
+    }
+}
+
+/// Push a query yielding `hit_id` and `score` (higher is better) of the
+/// todos matching `q`
+#[cfg(feature = "postgres")]
+fn push_hits(query: &mut QueryBuilder<'_, Db>, q: &str) {
+    query
+        .push("SELECT id AS hit_id, GREATEST(word_similarity(")
+        .push_bind(q.to_string())
+        .push(", title), word_similarity(")
+        .push_bind(q.to_string())
+        .push(", notes)) AS score FROM todos WHERE ")
+        .push_bind(q.to_string())
+        .push(" <% title OR ")
+        .push_bind(q.to_string())
+        .push(" <% notes");
+}
+
+/// Push a query yielding `hit_id` and `score` (higher is better) of the
+/// todos matching `q`
+#[cfg(feature = "sqlite")]
+fn push_hits(query: &mut QueryBuilder<'_, Db>, q: &str) {
+    query
+        .push("SELECT rowid AS hit_id, -bm25(todos_fts) AS score FROM todos_fts WHERE todos_fts MATCH ")
+        .push_bind(fts_query(q));
+}
+
+/// Each word as a quoted FTS5 prefix term, so input can't use query syntax
+#[cfg(feature = "sqlite")]
+fn fts_query(q: &str) -> String {
+    q.split_whitespace()
+        .map(|word| format!("\"{}\"*", word.replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// ============================================
+// Models
+// ============================================
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SearchParams {
+    /// Words to find in titles and notes
+    pub q: String,
+    pub completed: Option<bool>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ResultsParams {
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow, ToSchema)]
+pub struct SavedFilter {
+    pub id: i64,
+    pub name: String,
+    #[sqlx(try_from = "String")]
+    pub filter: TodoFilter,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct CreateFilter {
+    #[validate(length(min = 1, max = 100))]
+    pub name: String,
+    pub filter: TodoFilter,
+}
+
+// ============================================
+// Handlers
+// ============================================
+
+/// GET /todos/search - Todos whose title or notes match, best first
+#[utoipa::path(
+    get,
+    path = "/todos/search",
+    tag = "filters",
+    params(SearchParams),
+    responses(
+        (status = 200, description = "Matching todos", body = Vec<Todo>),
+        (status = 400, description = "Missing or overlong search text", body = ProblemDetails),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn search_todos(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Query(params): Query<SearchParams>,
+) -> Result<Json<Vec<Todo>>, ApiError> {
+    let filter = TodoFilter {
+        q: Some(params.q),
+        completed: params.completed,
+        ..TodoFilter::default()
+    }
+    .validate()?;
+    if filter.q.is_none() {
+        return Err(ApiError::BadRequest("`q` is required".into()));
+    }
+
+    Ok(Json(filter.fetch(&state.db, user.id, params.limit).await?))
+}
+
+/// GET /todos/filters - The user's saved filters
+#[utoipa::path(
+    get,
+    path = "/todos/filters",
+    tag = "filters",
+    responses(
+        (status = 200, description = "Saved filters", body = Vec<SavedFilter>),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn list_filters(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+) -> Result<Json<Vec<SavedFilter>>, ApiError> {
+    let filters = sqlx::query_as::<_, SavedFilter>(
+        "SELECT id, name, definition AS filter FROM todo_filters WHERE owner_id = $1 ORDER BY name",
+    )
+    .bind(user.id)
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(Json(filters))
+}
+
+/// POST /todos/filters - Save a filter
+#[utoipa::path(
+    post,
+    path = "/todos/filters",
+    tag = "filters",
+    request_body = CreateFilter,
+    responses(
+        (status = 201, description = "Filter saved", body = SavedFilter),
+        (status = 400, description = "Validation failed or too many filters", body = ProblemDetails),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn create_filter(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Json(input): Json<CreateFilter>,
+) -> Result<(StatusCode, Json<SavedFilter>), ApiError> {
+    input.validate()?;
+    let filter = input.filter.validate()?;
+
+    let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM todo_filters WHERE owner_id = $1")
+        .bind(user.id)
+        .fetch_one(&state.db)
+        .await?;
+    if count >= MAX_FILTERS {
+        return Err(ApiError::BadRequest(format!("At most {} saved filters", MAX_FILTERS)));
+    }
+
+    let definition = serde_json::to_string(&filter).map_err(|e| ApiError::Internal(e.to_string()))?;
+    let (id,): (i64,) = sqlx::query_as("INSERT INTO todo_filters (owner_id, name, definition) VALUES ($1, $2, $3) RETURNING id")
+        .bind(user.id)
+        .bind(&input.name)
+        .bind(definition)
+        .fetch_one(&state.db)
+        .await?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(SavedFilter {
+            id,
+            name: input.name,
+            filter,
+        }),
+    ))
+}
+
+/// DELETE /todos/filters/:id - Delete a saved filter
+#[utoipa::path(
+    delete,
+    path = "/todos/filters/{id}",
+    tag = "filters",
+    params(("id" = i64, Path, description = "Filter ID")),
+    responses(
+        (status = 204, description = "Filter deleted"),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 404, description = "Filter not found", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn delete_filter(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(id): Path<i64>,
+) -> Result<StatusCode, ApiError> {
+    let result = sqlx::query("DELETE FROM todo_filters WHERE id = $1 AND owner_id = $2")
+        .bind(id)
+        .bind(user.id)
+        .execute(&state.db)
+        .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(ApiError::NotFound("Filter not found"));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// GET /todos/filters/:id/results - Run a saved filter
+#[utoipa::path(
+    get,
+    path = "/todos/filters/{id}/results",
+    tag = "filters",
+    params(("id" = i64, Path, description = "Filter ID"), ResultsParams),
+    responses(
+        (status = 200, description = "Matching todos", body = Vec<Todo>),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 404, description = "Filter not found", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn filter_results(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(id): Path<i64>,
+    Query(params): Query<ResultsParams>,
+) -> Result<Json<Vec<Todo>>, ApiError> {
+    let saved = sqlx::query_as::<_, SavedFilter>(
+        "SELECT id, name, definition AS filter FROM todo_filters WHERE id = $1 AND owner_id = $2",
+    )
+    .bind(id)
+    .bind(user.id)
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or(ApiError::NotFound("Filter not found"))?;
+
+    Ok(Json(saved.filter.fetch(&state.db, user.id, params.limit).await?))
+}
@@ -0,0 +1,350 @@
+//! Shared Lists
+//!
+//! Todos belong to the user who created them. A todo may also be filed under
+//! a list; the list's owner can share it with other users, each with `view`
+//! or `edit` permission. Access to a todo is the strongest of:
+//!
+//! - `owner`: the todo's creator or the owner of its list
+//! - the member's permission on the todo's list
+//!
+//! Todos a user can't see are reported as missing rather than forbidden.
+
+use crate::{ApiError, AppState};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use rustpress_auth::db::DbPool;
+use rustpress_auth::problem::ProblemDetails;
+use rustpress_auth::AuthUser;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use utoipa::ToSchema;
+use uuid::Uuid;
+use validator::Validate;
+
+// ============================================
+// Models
+// ============================================
+
+/// What a user may do with a list or todo, weakest first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Access {
+    View,
+    Edit,
+    Owner,
+}
+
+impl Access {
+    /// Parse a stored `view`/`edit` member permission
+    fn from_permission(permission: &str) -> Option<Self> {
+        match permission {
+            "view" => Some(Self::View),
+            "edit" => Some(Self::Edit),
+            _ => None,
+        }
+    }
+}
+
+impl TryFrom<String> for Access {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        match value.as_str() {
+            "owner" => Ok(Self::Owner),
+            other => Self::from_permission(other).ok_or_else(|| format!("Unknown access level '{}'", other)),
+        }
+    }
+}
+
+/// Permission granted to a list member
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Permission {
+    View,
+    Edit,
+}
+
+impl Permission {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::View => "view",
+            Self::Edit => "edit",
+        }
+    }
+}
+
+/// A list as seen by the requesting user
+#[derive(Debug, Serialize, sqlx::FromRow, ToSchema)]
+pub struct TodoList {
+    pub id: i64,
+    pub owner_id: Uuid,
+    pub name: String,
+    /// The requesting user's access
+    #[sqlx(try_from = "String")]
+    pub access: Access,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct CreateList {
+    #[validate(length(min = 1, max = 100))]
+    pub name: String,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow, ToSchema)]
+pub struct ListMember {
+    pub user_id: Uuid,
+    #[sqlx(try_from = "String")]
+    pub permission: Access,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SetMember {
+    pub permission: Permission,
+}
+
+// ============================================
+// Access Checks
+// ============================================
+
+/// `user`'s access to a list, `None` if they can't see it
+pub async fn list_access(db: &DbPool, list_id: i64, user: Uuid) -> Result<Option<Access>, sqlx::Error> {
+    let row: Option<(Uuid, Option<String>)> = sqlx::query_as(
+        r#"
+        SELECT l.owner_id, m.permission
+        FROM todo_lists l
+        LEFT JOIN todo_list_members m ON m.list_id = l.id AND m.user_id = $2
+        WHERE l.id = $1
+        "#,
+    )
+    .bind(list_id)
+    .bind(user)
+    .fetch_optional(db)
+    .await?;
+
+    Ok(row.and_then(|(owner, permission)| {
+        if owner == user {
+            Some(Access::Owner)
+        } else {
+            permission.as_deref().and_then(Access::from_permission)
+        }
+    }))
+}
+
+/// `user`'s access to a todo, `None` if it doesn't exist or they can't see it
+pub async fn todo_access(db: &DbPool, todo_id: i64, user: Uuid) -> Result<Option<Access>, sqlx::Error> {
+    let row: Option<(Option<Uuid>, Option<Uuid>, Option<String>)> = sqlx::query_as(
+        r#"
+        SELECT t.user_id, l.owner_id, m.permission
+        FROM todos t
+        LEFT JOIN todo_lists l ON l.id = t.list_id
+        LEFT JOIN todo_list_members m ON m.list_id = t.list_id AND m.user_id = $2
+        WHERE t.id = $1
+        "#,
+    )
+    .bind(todo_id)
+    .bind(user)
+    .fetch_optional(db)
+    .await?;
+
+    Ok(row.and_then(|(owner, list_owner, permission)| {
+        if owner == Some(user) || list_owner == Some(user) {
+            Some(Access::Owner)
+        } else {
+            permission.as_deref().and_then(Access::from_permission)
+        }
+    }))
+}
+
+/// Fail unless `access` is at least `required`; invisible items are missing
+pub fn require(access: Option<Access>, required: Access, what: &'static str) -> Result<Access, ApiError> {
+    match access {
+        None => Err(ApiError::NotFound(what)),
+        Some(access) if access < required => Err(ApiError::Forbidden),
+        Some(access) => Ok(access),
+    }
+}
+
+// ============================================
+// Handlers
+// ============================================
+
+/// GET /lists - Lists the user owns or is a member of
+#[utoipa::path(
+    get,
+    path = "/lists",
+    tag = "lists",
+    responses(
+        (status = 200, description = "Lists", body = Vec<TodoList>),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn list_lists(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+) -> Result<Json<Vec<TodoList>>, ApiError> {
+    let lists = sqlx::query_as::<_, TodoList>(
+        r#"
+        SELECT l.id, l.owner_id, l.name,
+               CASE WHEN l.owner_id = $1 THEN 'owner' ELSE m.permission END AS access
+        FROM todo_lists l
+        LEFT JOIN todo_list_members m ON m.list_id = l.id AND m.user_id = $1
+        WHERE l.owner_id = $1 OR m.user_id IS NOT NULL
+        ORDER BY l.id
+        "#,
+    )
+    .bind(user.id)
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(Json(lists))
+}
+
+/// POST /lists - Create a list owned by the user
+#[utoipa::path(
+    post,
+    path = "/lists",
+    tag = "lists",
+    request_body = CreateList,
+    responses(
+        (status = 201, description = "List created", body = TodoList),
+        (status = 400, description = "Validation failed", body = ProblemDetails),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn create_list(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Json(input): Json<CreateList>,
+) -> Result<(StatusCode, Json<TodoList>), ApiError> {
+    input.validate()?;
+
+    let list = sqlx::query_as::<_, TodoList>(
+        "INSERT INTO todo_lists (owner_id, name) VALUES ($1, $2) RETURNING id, owner_id, name, 'owner' AS access",
+    )
+    .bind(user.id)
+    .bind(&input.name)
+    .fetch_one(&state.db)
+    .await?;
+
+    Ok((StatusCode::CREATED, Json(list)))
+}
+
+/// GET /lists/:id/members - Members of a list
+#[utoipa::path(
+    get,
+    path = "/lists/{id}/members",
+    tag = "lists",
+    params(("id" = i64, Path, description = "List ID")),
+    responses(
+        (status = 200, description = "Members", body = Vec<ListMember>),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 404, description = "List not found", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn list_members(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(id): Path<i64>,
+) -> Result<Json<Vec<ListMember>>, ApiError> {
+    require(list_access(&state.db, id, user.id).await?, Access::View, "List not found")?;
+
+    let members = sqlx::query_as::<_, ListMember>(
+        "SELECT user_id, permission FROM todo_list_members WHERE list_id = $1 ORDER BY user_id",
+    )
+    .bind(id)
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(Json(members))
+}
+
+/// PUT /lists/:id/members/:user_id - Share a list or change a member's permission
+#[utoipa::path(
+    put,
+    path = "/lists/{id}/members/{user_id}",
+    tag = "lists",
+    params(
+        ("id" = i64, Path, description = "List ID"),
+        ("user_id" = Uuid, Path, description = "Member's user ID"),
+    ),
+    request_body = SetMember,
+    responses(
+        (status = 200, description = "Member saved", body = ListMember),
+        (status = 400, description = "Owner can't be a member", body = ProblemDetails),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Only the owner can share", body = ProblemDetails),
+        (status = 404, description = "List not found", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn set_member(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path((id, member)): Path<(i64, Uuid)>,
+    Json(input): Json<SetMember>,
+) -> Result<Json<ListMember>, ApiError> {
+    require(list_access(&state.db, id, user.id).await?, Access::Owner, "List not found")?;
+    if member == user.id {
+        return Err(ApiError::BadRequest("The owner already has full access".into()));
+    }
+
+    let saved = sqlx::query_as::<_, ListMember>(
+        r#"
+        INSERT INTO todo_list_members (list_id, user_id, permission) VALUES ($1, $2, $3)
+        ON CONFLICT (list_id, user_id) DO UPDATE SET permission = excluded.permission
+        RETURNING user_id, permission
+        "#,
+    )
+    .bind(id)
+    .bind(member)
+    .bind(input.permission.as_str())
+    .fetch_one(&state.db)
+    .await?;
+
+    Ok(Json(saved))
+}
+
+/// DELETE /lists/:id/members/:user_id - Stop sharing a list with a user
+#[utoipa::path(
+    delete,
+    path = "/lists/{id}/members/{user_id}",
+    tag = "lists",
+    params(
+        ("id" = i64, Path, description = "List ID"),
+        ("user_id" = Uuid, Path, description = "Member's user ID"),
+    ),
+    responses(
+        (status = 204, description = "Member removed"),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Only the owner can unshare", body = ProblemDetails),
+        (status = 404, description = "List or member not found", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn remove_member(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path((id, member)): Path<(i64, Uuid)>,
+) -> Result<StatusCode, ApiError> {
+    // Members may leave a list themselves
+    let required = if member == user.id { Access::View } else { Access::Owner };
+    require(list_access(&state.db, id, user.id).await?, required, "List not found")?;
+
+    let result = sqlx::query("DELETE FROM todo_list_members WHERE list_id = $1 AND user_id = $2")
+        .bind(id)
+        .bind(member)
+        .execute(&state.db)
+        .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(ApiError::NotFound("Member not found"));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -4,56 +4,234 @@
 //! - Router setup with Axum
 //! - CRUD handlers
 //! - Request validation
+//! - Per-user ownership and shared lists on top of the `rustpress-auth` plugin
+//! - Projects, labels, priorities, time-zone-aware due dates and recurring
+//!   todos materialized by a background job
+//! - Full-text search and saved filters built with `QueryBuilder`
+//! - Live sync over WebSockets with resumable per-list revisions, and batch
+//!   sync with conflict detection for offline clients
 //! - Error handling
-//! - Database queries with SQLx
+//! - Database queries with SQLx on PostgreSQL or SQLite (`sqlite` feature)
+//! - OpenAPI spec generated from handler annotations
+//! - Graceful shutdown
 
 use axum::{
     extract::{Path, Query, State},
     http::StatusCode,
-    response::{IntoResponse, Response},
-    routing::{get, post},
+    response::{Html, IntoResponse, Response},
+    routing::{delete, get, post, put},
     Json, Router,
 };
+use rustpress_auth::openapi::BearerAuth;
+use rustpress_auth::problem::ProblemDetails;
+use rustpress_auth::{AuthUser, Config};
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use sqlx::PgPool;
+use rustpress_auth::db::{self, Db, DbPool};
 use std::sync::Arc;
+use utoipa::{IntoParams, OpenApi, ToSchema};
+use uuid::Uuid;
 use validator::Validate;
 
+mod filters;
+mod lists;
+mod projects;
+mod runtime;
+mod schedule;
+mod sync;
+
+use filters::TodoFilter;
+use lists::{require, todo_access, Access};
+use runtime::{Runtime, ShutdownPhase};
+use sync::{ChangeOp, EventBus};
+
 // ============================================
 // App State
 // ============================================
 
 #[derive(Clone)]
 pub struct AppState {
-    pub db: PgPool,
+    pub db: DbPool,
+    pub events: Arc<EventBus>,
 }
 
 // ============================================
 // Models
 // ============================================
 
-#[derive(Debug, Serialize, sqlx::FromRow)]
+/// Columns selected into `Todo`
+const TODO_COLUMNS: &str =
+    "id, title, notes, completed, user_id, list_id, project_id, priority, due_at, timezone, recurrence, client_id, revision";
+
+/// Labels per todo
+const MAX_LABELS: usize = 20;
+
+/// Characters of notes per todo
+const MAX_NOTES: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    Low,
+    #[default]
+    Normal,
+    High,
+    Urgent,
+}
+
+impl From<Priority> for i16 {
+    fn from(priority: Priority) -> Self {
+        priority as i16
+    }
+}
+
+impl TryFrom<i16> for Priority {
+    type Error = String;
+
+    fn try_from(value: i16) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::Low),
+            1 => Ok(Self::Normal),
+            2 => Ok(Self::High),
+            3 => Ok(Self::Urgent),
+            other => Err(format!("Unknown priority {}", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow, ToSchema)]
 pub struct Todo {
     pub id: i64,
     pub title: String,
+    pub notes: Option<String>,
     pub completed: bool,
+    /// Creator (`None` for todos from before ownership existed)
+    pub user_id: Option<Uuid>,
+    /// Shared list the todo is filed under
+    pub list_id: Option<i64>,
+    /// The creator's project
+    pub project_id: Option<i64>,
+    #[sqlx(try_from = "i16")]
+    pub priority: Priority,
+    /// Due time (UTC)
+    pub due_at: Option<DateTime<Utc>>,
+    /// IANA zone the due time was entered in
+    pub timezone: String,
+    /// Recurrence rule (RRULE subset, see `schedule`)
+    pub recurrence: Option<String>,
+    /// UUID given by the client that created it offline
+    pub client_id: Option<Uuid>,
+    /// Revision of its last change; the base revision for batch sync edits
+    pub revision: i64,
+    #[sqlx(skip)]
+    pub labels: Vec<String>,
 }
 
-#[derive(Debug, Deserialize, Validate)]
+#[derive(Debug, Deserialize, Validate, ToSchema)]
 pub struct CreateTodo {
     #[validate(length(min = 1, max = 200))]
     pub title: String,
+    #[validate(length(max = 10000))]
+    pub notes: Option<String>,
+    /// File under a list the user can edit
+    pub list_id: Option<i64>,
+    /// File under one of the user's projects
+    pub project_id: Option<i64>,
+    pub priority: Option<Priority>,
+    /// RFC 3339 time, or wall-clock time (`2026-10-20T09:00`) or date in `timezone`
+    pub due_at: Option<String>,
+    /// IANA zone, default `UTC`
+    pub timezone: Option<String>,
+    /// e.g. `FREQ=WEEKLY;BYDAY=MO,WE`; needs `due_at`
+    pub recurrence: Option<String>,
+    #[serde(default)]
+    pub labels: Vec<String>,
 }
 
-#[derive(Debug, Deserialize)]
+/// Omitted fields are unchanged; an empty `notes`, `due_at` or `recurrence` clears it
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct UpdateTodo {
     pub title: Option<String>,
+    pub notes: Option<String>,
     pub completed: Option<bool>,
+    pub project_id: Option<i64>,
+    pub priority: Option<Priority>,
+    pub due_at: Option<String>,
+    pub timezone: Option<String>,
+    pub recurrence: Option<String>,
+    /// Replaces all labels
+    pub labels: Option<Vec<String>>,
+}
+
+/// Which todos `GET /todos` returns
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    /// Created by the user
+    Owned,
+    /// Created by others, in lists the user owns or is a member of
+    Shared,
+    /// Both
+    #[default]
+    All,
 }
 
-#[derive(Debug, Deserialize)]
+impl Scope {
+    /// SQL condition selecting the scope for the user bound to `$1`
+    fn condition(self) -> String {
+        // Lists the user can see: their own and those shared with them
+        let visible_lists = "SELECT id FROM todo_lists WHERE owner_id = $1 \
+                             UNION SELECT list_id FROM todo_list_members WHERE user_id = $1";
+        match self {
+            Scope::Owned => "user_id = $1".to_string(),
+            Scope::Shared => format!("user_id <> $1 AND list_id IN ({})", visible_lists),
+            Scope::All => format!("(user_id = $1 OR list_id IN ({}))", visible_lists),
+        }
+    }
+
+    /// Push the condition selecting the scope for `user`
+    fn push_condition(self, query: &mut sqlx::QueryBuilder<'_, Db>, user: Uuid) {
+        let push_visible_lists = |query: &mut sqlx::QueryBuilder<'_, Db>| {
+            query.push("list_id IN (SELECT id FROM todo_lists WHERE owner_id = ");
+            query.push_bind(user);
+            query.push(" UNION SELECT list_id FROM todo_list_members WHERE user_id = ");
+            query.push_bind(user);
+            query.push(")");
+        };
+        match self {
+            Scope::Owned => {
+                query.push("user_id = ").push_bind(user);
+            }
+            Scope::Shared => {
+                query.push("user_id <> ").push_bind(user).push(" AND ");
+                push_visible_lists(query);
+            }
+            Scope::All => {
+                query.push("(user_id = ").push_bind(user).push(" OR ");
+                push_visible_lists(query);
+                query.push(")");
+            }
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct ListParams {
     pub completed: Option<bool>,
+    #[param(inline)]
+    pub scope: Option<Scope>,
+    pub list_id: Option<i64>,
+    pub project_id: Option<i64>,
+    pub label: Option<String>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DueParams {
+    /// How far ahead `/todos/upcoming` looks (default 7, at most 90)
+    pub days: Option<i64>,
     pub limit: Option<i64>,
 }
 
@@ -63,27 +241,46 @@ pub struct ListParams {
 
 #[derive(Debug)]
 pub enum ApiError {
-    NotFound,
+    NotFound(&'static str),
+    Forbidden,
     BadRequest(String),
+    Conflict(&'static str),
+    Validation(validator::ValidationErrors),
     Internal(String),
 }
 
+impl From<ApiError> for ProblemDetails {
+    fn from(err: ApiError) -> Self {
+        match err {
+            ApiError::NotFound(what) => ProblemDetails::not_found(what),
+            ApiError::Forbidden => ProblemDetails::forbidden("You don't have permission to change this"),
+            ApiError::BadRequest(detail) => ProblemDetails::validation(detail),
+            ApiError::Conflict(detail) => ProblemDetails::new(StatusCode::CONFLICT, "conflict").detail(detail),
+            ApiError::Validation(errors) => ProblemDetails::from(errors),
+            ApiError::Internal(msg) => {
+                tracing::error!("Internal error: {}", msg);
+                ProblemDetails::internal("Internal error")
+            }
+        }
+    }
+}
+
 impl IntoResponse for ApiError {
     fn into_response(self) -> Response {
-        let (status, message) = match self {
-            Self::NotFound => (StatusCode::NOT_FOUND, "Not found"),
-            Self::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg.leak()),
-            Self::Internal(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Internal error"),
-        };
+        ProblemDetails::from(self).into_response()
+    }
+}
 
-        (status, Json(serde_json::json!({ "error": message }))).into_response()
+impl From<validator::ValidationErrors> for ApiError {
+    fn from(errors: validator::ValidationErrors) -> Self {
+        Self::Validation(errors)
     }
 }
 
 impl From<sqlx::Error> for ApiError {
     fn from(e: sqlx::Error) -> Self {
         match e {
-            sqlx::Error::RowNotFound => Self::NotFound,
+            sqlx::Error::RowNotFound => Self::NotFound("Not found"),
             _ => Self::Internal(e.to_string()),
         }
     }
@@ -93,116 +290,538 @@ impl From<sqlx::Error> for ApiError {
 // Handlers
 // ============================================
 
-/// GET /todos - List all todos
+/// GET /todos - List the user's own and shared todos
+#[utoipa::path(
+    get,
+    path = "/todos",
+    tag = "todos",
+    params(ListParams),
+    responses(
+        (status = 200, description = "Todos", body = Vec<Todo>),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
 pub async fn list_todos(
     State(state): State<Arc<AppState>>,
+    user: AuthUser,
     Query(params): Query<ListParams>,
 ) -> Result<Json<Vec<Todo>>, ApiError> {
-    let limit = params.limit.unwrap_or(100).min(100);
-
-    let todos = match params.completed {
-        Some(completed) => {
-            sqlx::query_as!(
-                Todo,
-                "SELECT id, title, completed FROM todos WHERE completed = $1 LIMIT $2",
-                completed,
-                limit
-            )
-            .fetch_all(&state.db)
-            .await?
-        }
-        None => {
-            sqlx::query_as!(
-                Todo,
-                "SELECT id, title, completed FROM todos LIMIT $1",
-                limit
-            )
-            .fetch_all(&state.db)
-            .await?
-        }
+    let filter = TodoFilter {
+        scope: params.scope,
+        completed: params.completed,
+        list_id: params.list_id,
+        project_id: params.project_id,
+        labels: params.label.into_iter().collect(),
+        ..TodoFilter::default()
     };
+    Ok(Json(filter.fetch(&state.db, user.id, params.limit).await?))
+}
+
+/// GET /todos/upcoming - Open todos due in the next few days, soonest first
+#[utoipa::path(
+    get,
+    path = "/todos/upcoming",
+    tag = "todos",
+    params(DueParams),
+    responses(
+        (status = 200, description = "Todos", body = Vec<Todo>),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn upcoming_todos(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Query(params): Query<DueParams>,
+) -> Result<Json<Vec<Todo>>, ApiError> {
+    let now = Utc::now();
+    let days = params.days.unwrap_or(7).clamp(1, 90);
+    due_todos(&state.db, user.id, Some(now), now + chrono::Duration::days(days), params.limit).await
+}
 
+/// GET /todos/overdue - Open todos past their due time, oldest first
+#[utoipa::path(
+    get,
+    path = "/todos/overdue",
+    tag = "todos",
+    params(DueParams),
+    responses(
+        (status = 200, description = "Todos", body = Vec<Todo>),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn overdue_todos(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Query(params): Query<DueParams>,
+) -> Result<Json<Vec<Todo>>, ApiError> {
+    due_todos(&state.db, user.id, None, Utc::now(), params.limit).await
+}
+
+/// Visible open todos due in `[from, to)`, by due time then priority
+async fn due_todos(
+    db: &DbPool,
+    user: Uuid,
+    from: Option<DateTime<Utc>>,
+    to: DateTime<Utc>,
+    limit: Option<i64>,
+) -> Result<Json<Vec<Todo>>, ApiError> {
+    let mut todos = sqlx::query_as::<_, Todo>(&format!(
+        "SELECT {} FROM todos WHERE {} \
+         AND completed = false AND ($2 IS NULL OR due_at >= $2) AND due_at < $3 \
+         ORDER BY due_at, priority DESC LIMIT $4",
+        TODO_COLUMNS,
+        Scope::All.condition()
+    ))
+    .bind(user)
+    .bind(from)
+    .bind(to)
+    .bind(limit.unwrap_or(100).min(100))
+    .fetch_all(db)
+    .await?;
+
+    attach_labels(db, &mut todos).await?;
     Ok(Json(todos))
 }
 
 /// GET /todos/:id - Get single todo
+#[utoipa::path(
+    get,
+    path = "/todos/{id}",
+    tag = "todos",
+    params(("id" = i64, Path, description = "Todo ID")),
+    responses(
+        (status = 200, description = "Todo", body = Todo),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 404, description = "Todo not found", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
 pub async fn get_todo(
     State(state): State<Arc<AppState>>,
+    user: AuthUser,
     Path(id): Path<i64>,
 ) -> Result<Json<Todo>, ApiError> {
-    let todo = sqlx::query_as!(
-        Todo,
-        "SELECT id, title, completed FROM todos WHERE id = $1",
-        id
-    )
-    .fetch_optional(&state.db)
-    .await?
-    .ok_or(ApiError::NotFound)?;
-
-    Ok(Json(todo))
+    require(todo_access(&state.db, id, user.id).await?, Access::View, "Todo not found")?;
+    Ok(Json(find_todo(&state.db, id).await?))
 }
 
 /// POST /todos - Create todo
+#[utoipa::path(
+    post,
+    path = "/todos",
+    tag = "todos",
+    request_body = CreateTodo,
+    responses(
+        (status = 201, description = "Todo created", body = Todo),
+        (status = 400, description = "Validation failed", body = ProblemDetails),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "List is view-only", body = ProblemDetails),
+        (status = 404, description = "List or project not found", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
 pub async fn create_todo(
     State(state): State<Arc<AppState>>,
+    user: AuthUser,
     Json(input): Json<CreateTodo>,
 ) -> Result<(StatusCode, Json<Todo>), ApiError> {
+    let todo = insert_todo(&state, user.id, input, None).await?;
+    Ok((StatusCode::CREATED, Json(todo)))
+}
+
+/// Create a todo for `user`, publishing the change
+async fn insert_todo(state: &AppState, user: Uuid, input: CreateTodo, client_id: Option<Uuid>) -> Result<Todo, ApiError> {
     // Validate input
-    input
-        .validate()
-        .map_err(|e| ApiError::BadRequest(e.to_string()))?;
-
-    let todo = sqlx::query_as!(
-        Todo,
-        "INSERT INTO todos (title, completed) VALUES ($1, false) RETURNING id, title, completed",
-        input.title
+    input.validate()?;
+    let labels = normalize_labels(&input.labels)?;
+
+    if let Some(list_id) = input.list_id {
+        require(lists::list_access(&state.db, list_id, user).await?, Access::Edit, "List not found")?;
+    }
+    if let Some(project_id) = input.project_id {
+        projects::require_owned(&state.db, project_id, user).await?;
+    }
+
+    let timezone = input.timezone.unwrap_or_else(|| "UTC".to_string());
+    let due = Due::resolve(&timezone, input.due_at.as_deref(), input.recurrence.as_deref())?;
+
+    let mut tx = state.db.begin().await?;
+    let (id,): (i64,) = sqlx::query_as(
+        r#"
+        INSERT INTO todos
+            (title, completed, user_id, list_id, project_id, priority, due_at, timezone, recurrence, recurrence_left,
+             client_id, notes)
+        VALUES ($1, false, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+        RETURNING id
+        "#,
     )
-    .fetch_one(&state.db)
+    .bind(&input.title)
+    .bind(user)
+    .bind(input.list_id)
+    .bind(input.project_id)
+    .bind(i16::from(input.priority.unwrap_or_default()))
+    .bind(due.due_at)
+    .bind(&timezone)
+    .bind(&due.recurrence)
+    .bind(due.recurrence_left)
+    .bind(client_id)
+    .bind(input.notes.as_deref().map(str::trim).filter(|notes| !notes.is_empty()))
+    .fetch_one(&mut *tx)
     .await?;
+    set_labels(&mut tx, id, &labels).await?;
+    let change = sync::record(&mut tx, sync::stream_for(input.list_id, Some(user)), ChangeOp::Create, id).await?;
+    tx.commit().await?;
 
-    Ok((StatusCode::CREATED, Json(todo)))
+    let todo = find_todo(&state.db, id).await?;
+    if let Some(change) = change {
+        change.publish(&state.events, Some(todo.clone()));
+    }
+    Ok(todo)
 }
 
 /// PUT /todos/:id - Update todo
+#[utoipa::path(
+    put,
+    path = "/todos/{id}",
+    tag = "todos",
+    params(("id" = i64, Path, description = "Todo ID")),
+    request_body = UpdateTodo,
+    responses(
+        (status = 200, description = "Todo updated", body = Todo),
+        (status = 400, description = "Validation failed", body = ProblemDetails),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Todo is view-only", body = ProblemDetails),
+        (status = 404, description = "Todo or project not found", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
 pub async fn update_todo(
     State(state): State<Arc<AppState>>,
+    user: AuthUser,
     Path(id): Path<i64>,
     Json(input): Json<UpdateTodo>,
 ) -> Result<Json<Todo>, ApiError> {
-    let todo = sqlx::query_as!(
-        Todo,
+    Ok(Json(change_todo(&state, user.id, id, input, None).await?))
+}
+
+/// Apply `input` to todo `id` as `user`, publishing the change. With a
+/// `base_revision`, fails with a conflict if the todo changed after it.
+async fn change_todo(
+    state: &AppState,
+    user: Uuid,
+    id: i64,
+    input: UpdateTodo,
+    base_revision: Option<i64>,
+) -> Result<Todo, ApiError> {
+    require(todo_access(&state.db, id, user).await?, Access::Edit, "Todo not found")?;
+    let current = find_todo(&state.db, id).await?;
+
+    if let Some(project_id) = input.project_id {
+        // Projects are personal, so only the creator files todos under them
+        if current.user_id != Some(user) {
+            return Err(ApiError::Forbidden);
+        }
+        projects::require_owned(&state.db, project_id, user).await?;
+    }
+    let labels = input.labels.as_deref().map(normalize_labels).transpose()?;
+    if input.notes.as_ref().is_some_and(|notes| notes.chars().count() > MAX_NOTES) {
+        return Err(ApiError::BadRequest(format!("Notes are limited to {} characters", MAX_NOTES)));
+    }
+
+    // Due time and recurrence are resolved together in the (possibly new) zone
+    let recurrence_changed = input.recurrence.is_some();
+    let planning_changed = input.due_at.is_some() || input.timezone.is_some() || recurrence_changed;
+    let timezone = input.timezone.unwrap_or_else(|| current.timezone.clone());
+    let due = if planning_changed {
+        let due_at = match input.due_at {
+            Some(due_at) => due_at,
+            None => current.due_at.map(|at| at.to_rfc3339()).unwrap_or_default(),
+        };
+        let recurrence = input.recurrence.or_else(|| current.recurrence.clone()).unwrap_or_default();
+        Some(Due::resolve(&timezone, Some(&due_at), Some(&recurrence))?)
+    } else {
+        None
+    };
+
+    let mut tx = state.db.begin().await?;
+    // Locks the row, so the revision can't move until the change is recorded
+    let updated = sqlx::query(
         r#"
         UPDATE todos SET
             title = COALESCE($1, title),
-            completed = COALESCE($2, completed)
-        WHERE id = $3
-        RETURNING id, title, completed
+            completed = COALESCE($2, completed),
+            project_id = COALESCE($3, project_id),
+            priority = COALESCE($4, priority),
+            notes = NULLIF(COALESCE($5, notes), '')
+        WHERE id = $6 AND ($7 IS NULL OR revision <= $7)
         "#,
-        input.title,
-        input.completed,
-        id
     )
-    .fetch_optional(&state.db)
-    .await?
-    .ok_or(ApiError::NotFound)?;
+    .bind(&input.title)
+    .bind(input.completed)
+    .bind(input.project_id)
+    .bind(input.priority.map(i16::from))
+    .bind(input.notes.as_deref().map(str::trim))
+    .bind(id)
+    .bind(base_revision)
+    .execute(&mut *tx)
+    .await?;
+    if updated.rows_affected() == 0 {
+        return Err(ApiError::Conflict("Todo changed after the base revision"));
+    }
 
-    Ok(Json(todo))
+    if let Some(due) = due {
+        sqlx::query("UPDATE todos SET due_at = $1, timezone = $2 WHERE id = $3")
+            .bind(due.due_at)
+            .bind(&timezone)
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
+        // A new rule restarts its COUNT; an unchanged one keeps its progress
+        if recurrence_changed {
+            sqlx::query("UPDATE todos SET recurrence = $1, recurrence_left = $2 WHERE id = $3")
+                .bind(&due.recurrence)
+                .bind(due.recurrence_left)
+                .bind(id)
+                .execute(&mut *tx)
+                .await?;
+        }
+    }
+    if let Some(labels) = labels {
+        set_labels(&mut tx, id, &labels).await?;
+    }
+    let stream = sync::stream_for(current.list_id, current.user_id);
+    let change = sync::record(&mut tx, stream, ChangeOp::Update, id).await?;
+    tx.commit().await?;
+
+    let todo = find_todo(&state.db, id).await?;
+    if let Some(change) = change {
+        change.publish(&state.events, Some(todo.clone()));
+    }
+    Ok(todo)
 }
 
 /// DELETE /todos/:id - Delete todo
+#[utoipa::path(
+    delete,
+    path = "/todos/{id}",
+    tag = "todos",
+    params(("id" = i64, Path, description = "Todo ID")),
+    responses(
+        (status = 204, description = "Todo deleted"),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Todo is view-only", body = ProblemDetails),
+        (status = 404, description = "Todo not found", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
 pub async fn delete_todo(
     State(state): State<Arc<AppState>>,
+    user: AuthUser,
     Path(id): Path<i64>,
 ) -> Result<StatusCode, ApiError> {
-    let result = sqlx::query!("DELETE FROM todos WHERE id = $1", id)
-        .execute(&state.db)
+    remove_todo(&state, user.id, id, None).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Delete todo `id` as `user`, publishing the change; returns the revision
+/// of the deletion. `base_revision` is checked as in `change_todo`.
+async fn remove_todo(state: &AppState, user: Uuid, id: i64, base_revision: Option<i64>) -> Result<Option<i64>, ApiError> {
+    require(todo_access(&state.db, id, user).await?, Access::Edit, "Todo not found")?;
+    let current = find_todo(&state.db, id).await?;
+
+    let mut tx = state.db.begin().await?;
+    let result = sqlx::query("DELETE FROM todos WHERE id = $1 AND ($2 IS NULL OR revision <= $2)")
+        .bind(id)
+        .bind(base_revision)
+        .execute(&mut *tx)
         .await?;
 
     if result.rows_affected() == 0 {
-        return Err(ApiError::NotFound);
+        return Err(match base_revision {
+            Some(_) => ApiError::Conflict("Todo changed after the base revision"),
+            None => ApiError::NotFound("Todo not found"),
+        });
     }
+    let stream = sync::stream_for(current.list_id, current.user_id);
+    let change = sync::record(&mut tx, stream, ChangeOp::Delete, id).await?;
+    tx.commit().await?;
 
-    Ok(StatusCode::NO_CONTENT)
+    let revision = change.as_ref().map(|c| c.revision());
+    if let Some(change) = change {
+        change.publish(&state.events, None);
+    }
+    Ok(revision)
+}
+
+// ============================================
+// Todo Helpers
+// ============================================
+
+async fn find_todo(db: &DbPool, id: i64) -> Result<Todo, ApiError> {
+    let mut todo = sqlx::query_as::<_, Todo>(&format!("SELECT {} FROM todos WHERE id = $1", TODO_COLUMNS))
+        .bind(id)
+        .fetch_optional(db)
+        .await?
+        .ok_or(ApiError::NotFound("Todo not found"))?;
+
+    attach_labels(db, std::slice::from_mut(&mut todo)).await?;
+    Ok(todo)
+}
+
+/// Due time and recurrence as stored
+struct Due {
+    due_at: Option<DateTime<Utc>>,
+    recurrence: Option<String>,
+    recurrence_left: Option<i32>,
+}
+
+impl Due {
+    /// Resolve request values in `timezone`; empty strings mean "none"
+    fn resolve(timezone: &str, due_at: Option<&str>, recurrence: Option<&str>) -> Result<Self, ApiError> {
+        let tz = schedule::parse_timezone(timezone).map_err(ApiError::BadRequest)?;
+        let due_at = due_at
+            .filter(|v| !v.trim().is_empty())
+            .map(|v| schedule::resolve_due(v.trim(), tz))
+            .transpose()
+            .map_err(ApiError::BadRequest)?;
+
+        let (recurrence, recurrence_left) = match recurrence.filter(|r| !r.trim().is_empty()) {
+            None => (None, None),
+            Some(rule) => {
+                let first_due = due_at.ok_or_else(|| ApiError::BadRequest("Recurring todos need a due time".into()))?;
+                let (rule, left) = schedule::normalize_rule(rule, first_due, tz).map_err(ApiError::BadRequest)?;
+                (Some(rule), left)
+            }
+        };
+
+        Ok(Self {
+            due_at,
+            recurrence,
+            recurrence_left,
+        })
+    }
+}
+
+/// Trim, lowercase and de-duplicate labels
+fn normalize_labels(labels: &[String]) -> Result<Vec<String>, ApiError> {
+    let mut labels: Vec<String> = labels
+        .iter()
+        .map(|label| label.trim().to_lowercase())
+        .filter(|label| !label.is_empty())
+        .collect();
+    labels.sort();
+    labels.dedup();
+
+    if labels.len() > MAX_LABELS {
+        return Err(ApiError::BadRequest(format!("At most {} labels per todo", MAX_LABELS)));
+    }
+    if let Some(label) = labels.iter().find(|label| label.chars().count() > 50) {
+        return Err(ApiError::BadRequest(format!("Label '{}' is longer than 50 characters", label)));
+    }
+    Ok(labels)
+}
+
+async fn set_labels(tx: &mut sqlx::Transaction<'_, Db>, todo_id: i64, labels: &[String]) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM todo_labels WHERE todo_id = $1")
+        .bind(todo_id)
+        .execute(&mut **tx)
+        .await?;
+
+    for label in labels {
+        sqlx::query("INSERT INTO todo_labels (todo_id, label) VALUES ($1, $2)")
+            .bind(todo_id)
+            .bind(label)
+            .execute(&mut **tx)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Fill in `labels` with one query for all `todos`
+async fn attach_labels(db: &DbPool, todos: &mut [Todo]) -> Result<(), sqlx::Error> {
+    if todos.is_empty() {
+        return Ok(());
+    }
+
+    let mut query = sqlx::QueryBuilder::<Db>::new("SELECT todo_id, label FROM todo_labels WHERE todo_id IN (");
+    let mut ids = query.separated(", ");
+    for todo in todos.iter() {
+        ids.push_bind(todo.id);
+    }
+    ids.push_unseparated(") ORDER BY label");
+
+    let rows: Vec<(i64, String)> = query.build_query_as().fetch_all(db).await?;
+    for (todo_id, label) in rows {
+        if let Some(todo) = todos.iter_mut().find(|todo| todo.id == todo_id) {
+            todo.labels.push(label);
+        }
+    }
+    Ok(())
+}
+
+// ============================================
+// OpenAPI
+// ============================================
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Todo API"),
+    paths(
+        list_todos,
+        upcoming_todos,
+        overdue_todos,
+        get_todo,
+        create_todo,
+        update_todo,
+        delete_todo,
+        filters::search_todos,
+        filters::list_filters,
+        filters::create_filter,
+        filters::delete_filter,
+        filters::filter_results,
+        lists::list_lists,
+        lists::create_list,
+        lists::list_members,
+        lists::set_member,
+        lists::remove_member,
+        projects::list_projects,
+        projects::create_project,
+        projects::delete_project,
+        sync::list_changes,
+        sync::batch_sync,
+    ),
+    modifiers(&BearerAuth),
+    tags(
+        (name = "todos", description = "Todo CRUD"),
+        (name = "lists", description = "Shared lists and their members"),
+        (name = "projects", description = "Personal projects"),
+        (name = "filters", description = "Search and saved filters"),
+        (name = "sync", description = "Change log for live sync (live changes: `GET /todos/ws`)")
+    )
+)]
+pub struct ApiDoc;
+
+/// GET /api/v1/docs - Swagger UI for the generated spec
+pub async fn swagger_ui() -> Html<&'static str> {
+    Html(r##"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta charset="utf-8">
+    <title>Todo API</title>
+    <link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5/swagger-ui.css">
+</head>
+<body>
+    <div id="swagger-ui"></div>
+    <script src="https://unpkg.com/swagger-ui-dist@5/swagger-ui-bundle.js"></script>
+    <script>
+        window.ui = SwaggerUIBundle({ url: "/api/v1/openapi.json", dom_id: "#swagger-ui" });
+    </script>
+</body>
+</html>"##)
 }
 
 // ============================================
@@ -212,7 +831,30 @@ pub async fn delete_todo(
 pub fn create_router(state: Arc<AppState>) -> Router {
     Router::new()
         .route("/todos", get(list_todos).post(create_todo))
+        .route("/todos/upcoming", get(upcoming_todos))
+        .route("/todos/overdue", get(overdue_todos))
+        .route("/todos/search", get(filters::search_todos))
+        .route("/todos/filters", get(filters::list_filters).post(filters::create_filter))
+        .route("/todos/filters/:id", delete(filters::delete_filter))
+        .route("/todos/filters/:id/results", get(filters::filter_results))
+        .route("/todos/changes", get(sync::list_changes))
+        .route("/todos/sync", post(sync::batch_sync))
+        .route(
+            "/todos/ws",
+            get(sync::todos_ws).layer(axum::middleware::from_fn(sync::token_from_query)),
+        )
         .route("/todos/:id", get(get_todo).put(update_todo).delete(delete_todo))
+        .route("/projects", get(projects::list_projects).post(projects::create_project))
+        .route("/projects/:id", delete(projects::delete_project))
+        .route("/lists", get(lists::list_lists).post(lists::create_list))
+        .route("/lists/:id/members", get(lists::list_members))
+        .route(
+            "/lists/:id/members/:user_id",
+            put(lists::set_member).delete(lists::remove_member),
+        )
+        .route("/api/v1/openapi.json", get(|| async { Json(ApiDoc::openapi()) }))
+        .route("/api/v1/docs", get(swagger_ui))
+        .layer(axum::middleware::from_fn(rustpress_auth::problem::trace_id))
         .with_state(state)
 }
 
@@ -220,20 +862,98 @@ pub fn create_router(state: Arc<AppState>) -> Router {
 // Main (for standalone testing)
 // ============================================
 
+/// Schema for the compiled-in backend (`migrations/<backend>/`)
+#[cfg(feature = "postgres")]
+fn migrator() -> sqlx::migrate::Migrator {
+    sqlx::migrate!("./migrations/postgres")
+}
+
+/// Schema for the compiled-in backend (`migrations/<backend>/`)
+#[cfg(feature = "sqlite")]
+fn migrator() -> sqlx::migrate::Migrator {
+    sqlx::migrate!("./migrations/sqlite")
+}
+
+fn default_database_url() -> &'static str {
+    if cfg!(feature = "sqlite") {
+        "sqlite://todos.db"
+    } else {
+        "postgres://localhost/rustpress"
+    }
+}
+
 #[tokio::main]
 async fn main() {
-    // In real app, get this from config
-    let database_url = std::env::var("DATABASE_URL")
-        .unwrap_or_else(|_| "postgres://localhost/rustpress".into());
+    // rustpress.toml, environment variables (`DATABASE_URL`, ...) and `--set` overrides
+    let config = Config::load().expect("Invalid configuration");
+    // `database.url` may come from a secrets manager, which also rotates it
+    let secrets = rustpress_auth::secrets::shared().await.expect("Invalid secrets configuration");
+    let database_url = secrets
+        .get("database.url")
+        .await
+        .expect("Failed to read database.url")
+        .unwrap_or_else(|| default_database_url().to_string());
 
-    let pool = PgPool::connect(&database_url)
+    let pool = db::connect(&database_url)
         .await
         .expect("Failed to connect to database");
+    if let Some(interval) = rustpress_auth::secrets::refresh_interval(&config).expect("Invalid secrets.refresh_secs") {
+        db::rotate_credentials(&pool, secrets, interval);
+    }
+
+    migrator()
+        .run(&pool)
+        .await
+        .expect("Failed to run migrations");
+
+    let drain_secs = config
+        .get_or("shutdown.timeout_secs", 30)
+        .expect("Invalid shutdown.timeout_secs");
+
+    let task_secs = config
+        .get_or("shutdown.task_timeout_secs", 10)
+        .expect("Invalid shutdown.task_timeout_secs");
+
+    let mut runtime = Runtime::new()
+        .drain_timeout(std::time::Duration::from_secs(drain_secs))
+        .task_timeout(std::time::Duration::from_secs(task_secs));
+    let db = pool.clone();
+    runtime.on_shutdown(ShutdownPhase::Resources, "database pool", move || async move {
+        db.close().await;
+        Ok(())
+    });
+
+    // Create the next occurrence of recurring todos shortly before it's due
+    let lookahead_hours = config
+        .get_or("recurrence.lookahead_hours", 24)
+        .expect("Invalid recurrence.lookahead_hours");
+    let events = Arc::new(EventBus::default());
+    let jobs = runtime.jobs();
+    let db = pool.clone();
+    let bus = events.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(60));
+        loop {
+            interval.tick().await;
+            let db = db.clone();
+            let bus = bus.clone();
+            let started = jobs.spawn("recurring todos", async move {
+                match schedule::materialize_recurring(&db, &bus, chrono::Duration::hours(lookahead_hours)).await {
+                    Ok(0) => {}
+                    Ok(created) => tracing::info!(created, "Created recurring todo occurrences"),
+                    Err(e) => tracing::error!("Recurring todo job failed: {}", e),
+                }
+            });
+            if !started {
+                break;
+            }
+        }
+    });
 
-    let state = Arc::new(AppState { db: pool });
+    let state = Arc::new(AppState { db: pool, events });
     let app = create_router(state);
 
     let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await.unwrap();
     println!("Server running on http://localhost:3000");
-    axum::serve(listener, app).await.unwrap();
+    runtime.serve(listener, app).await.unwrap();
 }
@@ -0,0 +1,125 @@
+//! Projects
+//!
+//! Personal groupings of todos. Unlike lists, projects aren't shared: a todo
+//! can only be filed under a project its creator owns.
+
+use crate::{ApiError, AppState};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use rustpress_auth::db::DbPool;
+use rustpress_auth::problem::ProblemDetails;
+use rustpress_auth::AuthUser;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use utoipa::ToSchema;
+use uuid::Uuid;
+use validator::Validate;
+
+#[derive(Debug, Serialize, sqlx::FromRow, ToSchema)]
+pub struct Project {
+    pub id: i64,
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct CreateProject {
+    #[validate(length(min = 1, max = 100))]
+    pub name: String,
+}
+
+/// Fail unless `user` owns project `id`
+pub async fn require_owned(db: &DbPool, id: i64, user: Uuid) -> Result<(), ApiError> {
+    let found: Option<(i64,)> = sqlx::query_as("SELECT id FROM projects WHERE id = $1 AND owner_id = $2")
+        .bind(id)
+        .bind(user)
+        .fetch_optional(db)
+        .await?;
+
+    found.map(|_| ()).ok_or(ApiError::NotFound("Project not found"))
+}
+
+/// GET /projects - The user's projects
+#[utoipa::path(
+    get,
+    path = "/projects",
+    tag = "projects",
+    responses(
+        (status = 200, description = "Projects", body = Vec<Project>),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn list_projects(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+) -> Result<Json<Vec<Project>>, ApiError> {
+    let projects = sqlx::query_as::<_, Project>("SELECT id, name FROM projects WHERE owner_id = $1 ORDER BY name")
+        .bind(user.id)
+        .fetch_all(&state.db)
+        .await?;
+
+    Ok(Json(projects))
+}
+
+/// POST /projects - Create project
+#[utoipa::path(
+    post,
+    path = "/projects",
+    tag = "projects",
+    request_body = CreateProject,
+    responses(
+        (status = 201, description = "Project created", body = Project),
+        (status = 400, description = "Validation failed", body = ProblemDetails),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn create_project(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Json(input): Json<CreateProject>,
+) -> Result<(StatusCode, Json<Project>), ApiError> {
+    input.validate()?;
+
+    let project = sqlx::query_as::<_, Project>("INSERT INTO projects (owner_id, name) VALUES ($1, $2) RETURNING id, name")
+        .bind(user.id)
+        .bind(&input.name)
+        .fetch_one(&state.db)
+        .await?;
+
+    Ok((StatusCode::CREATED, Json(project)))
+}
+
+/// DELETE /projects/:id - Delete project (its todos are kept, unfiled)
+#[utoipa::path(
+    delete,
+    path = "/projects/{id}",
+    tag = "projects",
+    params(("id" = i64, Path, description = "Project ID")),
+    responses(
+        (status = 204, description = "Project deleted"),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 404, description = "Project not found", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn delete_project(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(id): Path<i64>,
+) -> Result<StatusCode, ApiError> {
+    let result = sqlx::query("DELETE FROM projects WHERE id = $1 AND owner_id = $2")
+        .bind(id)
+        .bind(user.id)
+        .execute(&state.db)
+        .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(ApiError::NotFound("Project not found"));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,423 @@
+//! Runtime Orchestration
+//!
+//! Wraps `axum::serve` with an ordered shutdown. On SIGTERM or SIGINT the
+//! listener stops accepting connections and in-flight requests drain until the
+//! deadline. Registered shutdown work then runs phase by phase: buffer flushes
+//! (analytics ingestion, view counters), running cron jobs, plugin
+//! `on_deactivate` in reverse dependency order, and finally shared resources
+//! such as the database pool.
+//!
+//! The sample only registers its database pool; plugin and job registration is
+//! there for hosts that embed the app.
+
+use axum::Router;
+use std::collections::HashSet;
+use std::future::{Future, IntoFuture};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::sync::watch;
+use tokio::task::JoinSet;
+
+/// Result of one piece of shutdown work
+pub type ShutdownFuture = Pin<Box<dyn Future<Output = Result<(), String>> + Send>>;
+
+type ShutdownFn = Box<dyn FnOnce() -> ShutdownFuture + Send>;
+
+/// When a shutdown task runs relative to the job and plugin steps
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShutdownPhase {
+    /// Before jobs and plugins stop: flush in-memory buffers (analytics
+    /// ingestion, view counters)
+    #[allow(dead_code)] // for hosts embedding the app
+    Flush,
+    /// After plugins are deactivated: close shared resources (database
+    /// pools, caches)
+    Resources,
+}
+
+struct ShutdownTask {
+    phase: ShutdownPhase,
+    name: String,
+    run: ShutdownFn,
+}
+
+struct PluginHandle {
+    id: String,
+    depends_on: Vec<String>,
+    deactivate: ShutdownFn,
+}
+
+/// Server runtime with graceful startup and shutdown
+pub struct Runtime {
+    drain_timeout: Duration,
+    task_timeout: Duration,
+    tasks: Vec<ShutdownTask>,
+    plugins: Vec<PluginHandle>,
+    jobs: JobTracker,
+}
+
+impl Default for Runtime {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Runtime {
+    pub fn new() -> Self {
+        Self {
+            drain_timeout: Duration::from_secs(30),
+            task_timeout: Duration::from_secs(10),
+            tasks: Vec::new(),
+            plugins: Vec::new(),
+            jobs: JobTracker::default(),
+        }
+    }
+
+    /// How long in-flight requests (and running jobs) may take to finish
+    pub fn drain_timeout(mut self, timeout: Duration) -> Self {
+        self.drain_timeout = timeout;
+        self
+    }
+
+    /// Deadline for each shutdown task
+    pub fn task_timeout(mut self, timeout: Duration) -> Self {
+        self.task_timeout = timeout;
+        self
+    }
+
+    /// Run `task` during `phase` of shutdown
+    pub fn on_shutdown<F, Fut>(&mut self, phase: ShutdownPhase, name: &str, task: F)
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = Result<(), String>> + Send + 'static,
+    {
+        self.tasks.push(ShutdownTask {
+            phase,
+            name: name.to_string(),
+            run: Box::new(move || Box::pin(task())),
+        });
+    }
+
+    /// Register an active plugin; dependents are deactivated before their dependencies
+    #[allow(dead_code)] // for hosts embedding the app
+    pub fn plugin<F, Fut>(&mut self, id: &str, depends_on: &[&str], on_deactivate: F)
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = Result<(), String>> + Send + 'static,
+    {
+        self.plugins.push(PluginHandle {
+            id: id.to_string(),
+            depends_on: depends_on.iter().map(|d| d.to_string()).collect(),
+            deactivate: Box::new(move || Box::pin(on_deactivate())),
+        });
+    }
+
+    /// Tracker for cron jobs; shutdown waits for running jobs to finish
+    pub fn jobs(&self) -> JobTracker {
+        self.jobs.clone()
+    }
+
+    /// Serve `app` until SIGTERM/SIGINT, then shut down in order
+    pub async fn serve(self, listener: TcpListener, app: Router) -> std::io::Result<()> {
+        let (signal_tx, mut signal_rx) = watch::channel(false);
+
+        let server = axum::serve(listener, app)
+            .with_graceful_shutdown(async move {
+                shutdown_signal().await;
+                let _ = signal_tx.send(true);
+            })
+            .into_future();
+        tokio::pin!(server);
+
+        tokio::select! {
+            result = &mut server => result?,
+            _ = signal_rx.changed() => {
+                tracing::info!("Shutdown signal received, draining in-flight requests");
+                if tokio::time::timeout(self.drain_timeout, &mut server).await.is_err() {
+                    tracing::warn!(
+                        timeout_secs = self.drain_timeout.as_secs(),
+                        "Drain deadline exceeded, dropping remaining connections"
+                    );
+                }
+            }
+        }
+
+        self.shutdown().await;
+        Ok(())
+    }
+
+    /// Run the shutdown sequence without serving (also used after `serve`)
+    pub async fn shutdown(self) {
+        let Runtime {
+            drain_timeout,
+            task_timeout,
+            tasks,
+            plugins,
+            jobs,
+        } = self;
+
+        let (flush, resources): (Vec<_>, Vec<_>) =
+            tasks.into_iter().partition(|t| t.phase == ShutdownPhase::Flush);
+
+        for task in flush {
+            run_task(&task.name, task.run, task_timeout).await;
+        }
+
+        jobs.close_and_wait(drain_timeout).await;
+
+        for plugin in deactivation_order(plugins) {
+            run_task(&format!("plugin {}", plugin.id), plugin.deactivate, task_timeout).await;
+        }
+
+        for task in resources {
+            run_task(&task.name, task.run, task_timeout).await;
+        }
+
+        tracing::info!("Shutdown complete");
+    }
+}
+
+async fn run_task(name: &str, run: ShutdownFn, timeout: Duration) {
+    match tokio::time::timeout(timeout, run()).await {
+        Ok(Ok(())) => tracing::info!(task = %name, "Shutdown task finished"),
+        Ok(Err(e)) => tracing::error!(task = %name, error = %e, "Shutdown task failed"),
+        Err(_) => tracing::warn!(task = %name, "Shutdown task timed out"),
+    }
+}
+
+/// Order plugins so nothing is deactivated while an active plugin depends on it
+///
+/// Dependency cycles are broken by falling back to reverse registration order.
+fn deactivation_order(mut remaining: Vec<PluginHandle>) -> Vec<PluginHandle> {
+    let mut ordered = Vec::with_capacity(remaining.len());
+
+    while !remaining.is_empty() {
+        let needed: HashSet<&str> = remaining
+            .iter()
+            .flat_map(|p| p.depends_on.iter().map(String::as_str))
+            .collect();
+
+        let next = remaining
+            .iter()
+            .rposition(|p| !needed.contains(p.id.as_str()))
+            .unwrap_or_else(|| {
+                tracing::warn!("Plugin dependency cycle, deactivating in reverse registration order");
+                remaining.len() - 1
+            });
+
+        ordered.push(remaining.remove(next));
+    }
+
+    ordered
+}
+
+/// Resolves on SIGINT (Ctrl+C) or, on Unix, SIGTERM
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Failed to install Ctrl+C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+
+// ============================================
+// Cron Jobs
+// ============================================
+
+/// Tracks running cron jobs so shutdown can wait for them
+///
+/// Schedulers should check `is_closed` before starting a run; `spawn` refuses
+/// new jobs once shutdown has begun.
+#[derive(Clone, Default)]
+pub struct JobTracker {
+    closed: Arc<AtomicBool>,
+    running: Arc<Mutex<JoinSet<()>>>,
+}
+
+impl JobTracker {
+    /// Start a job run; returns false once shutdown has begun
+    pub fn spawn<F>(&self, name: &str, job: F) -> bool
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        if self.is_closed() {
+            tracing::debug!(job = %name, "Not starting job during shutdown");
+            return false;
+        }
+
+        let mut running = self.running.lock().unwrap_or_else(|e| e.into_inner());
+        while running.try_join_next().is_some() {}
+        running.spawn(job);
+        true
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::SeqCst)
+    }
+
+    async fn close_and_wait(&self, timeout: Duration) {
+        self.closed.store(true, Ordering::SeqCst);
+
+        let mut running = std::mem::take(&mut *self.running.lock().unwrap_or_else(|e| e.into_inner()));
+        if running.is_empty() {
+            return;
+        }
+
+        tracing::info!(jobs = running.len(), "Waiting for running jobs");
+        let wait = async { while running.join_next().await.is_some() {} };
+        if tokio::time::timeout(timeout, wait).await.is_err() {
+            tracing::warn!(jobs = running.len(), "Jobs still running at deadline, aborting");
+            running.abort_all();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    fn plugin(id: &str, depends_on: &[&str]) -> PluginHandle {
+        PluginHandle {
+            id: id.to_string(),
+            depends_on: depends_on.iter().map(|d| d.to_string()).collect(),
+            deactivate: Box::new(|| Box::pin(async { Ok(()) })),
+        }
+    }
+
+    fn order(plugins: Vec<PluginHandle>) -> Vec<String> {
+        deactivation_order(plugins).into_iter().map(|p| p.id).collect()
+    }
+
+    #[test]
+    fn test_dependents_deactivate_first() {
+        let plugins = vec![
+            plugin("base", &[]),
+            plugin("auth", &["base"]),
+            plugin("shop", &["auth", "base"]),
+            plugin("blog", &["base"]),
+        ];
+        assert_eq!(order(plugins), ["blog", "shop", "auth", "base"]);
+
+        // Registration order doesn't matter, only dependencies
+        let plugins = vec![plugin("shop", &["auth"]), plugin("auth", &["base"]), plugin("base", &[])];
+        assert_eq!(order(plugins), ["shop", "auth", "base"]);
+    }
+
+    #[test]
+    fn test_cycles_and_missing_dependencies() {
+        // `a` and `b` need each other: `c` goes first, then the cycle in
+        // reverse registration order
+        let plugins = vec![plugin("a", &["b"]), plugin("b", &["a"]), plugin("c", &["a"])];
+        assert_eq!(order(plugins), ["c", "b", "a"]);
+
+        // A dependency that isn't registered holds nothing up
+        let plugins = vec![plugin("x", &["missing"]), plugin("y", &[]), plugin("z", &["x", "gone"])];
+        assert_eq!(order(plugins), ["z", "y", "x"]);
+
+        assert!(order(Vec::new()).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_runs_phases_in_order() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let step = |name: &'static str| {
+            let log = log.clone();
+            move || async move {
+                log.lock().unwrap().push(name);
+                Ok(())
+            }
+        };
+
+        let mut runtime = Runtime::new();
+        runtime.on_shutdown(ShutdownPhase::Resources, "pool", step("pool"));
+        runtime.plugin("base", &[], step("base"));
+        runtime.plugin("shop", &["base"], step("shop"));
+        runtime.on_shutdown(ShutdownPhase::Flush, "buffers", step("buffers"));
+        let jobs = runtime.jobs();
+        let job_log = log.clone();
+        assert!(jobs.spawn("report", async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            job_log.lock().unwrap().push("job");
+        }));
+
+        runtime.shutdown().await;
+        assert_eq!(*log.lock().unwrap(), ["buffers", "job", "shop", "base", "pool"]);
+    }
+
+    #[tokio::test]
+    async fn test_failed_and_slow_tasks_dont_stop_shutdown() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut runtime = Runtime::new().task_timeout(Duration::from_millis(20));
+        runtime.on_shutdown(ShutdownPhase::Flush, "failing", || async { Err("disk full".to_string()) });
+        runtime.on_shutdown(ShutdownPhase::Flush, "slow", || async {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            Ok(())
+        });
+        let done = log.clone();
+        runtime.on_shutdown(ShutdownPhase::Resources, "pool", move || async move {
+            done.lock().unwrap().push("pool");
+            Ok(())
+        });
+
+        tokio::time::timeout(Duration::from_secs(5), runtime.shutdown()).await.unwrap();
+        assert_eq!(*log.lock().unwrap(), ["pool"]);
+    }
+
+    #[tokio::test]
+    async fn test_jobs_finish_before_shutdown_continues() {
+        let jobs = JobTracker::default();
+        let finished = Arc::new(AtomicUsize::new(0));
+        for _ in 0..3 {
+            let finished = finished.clone();
+            assert!(jobs.spawn("count", async move {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                finished.fetch_add(1, Ordering::SeqCst);
+            }));
+        }
+        assert!(!jobs.is_closed());
+
+        jobs.close_and_wait(Duration::from_secs(5)).await;
+        assert_eq!(finished.load(Ordering::SeqCst), 3);
+
+        // No new runs once shutdown has begun, also through clones
+        assert!(jobs.is_closed());
+        assert!(!jobs.clone().spawn("late", async {}));
+    }
+
+    #[tokio::test]
+    async fn test_jobs_past_the_deadline_are_aborted() {
+        let jobs = JobTracker::default();
+        let finished = Arc::new(AtomicBool::new(false));
+        let flag = finished.clone();
+        jobs.spawn("stuck", async move {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            flag.store(true, Ordering::SeqCst);
+        });
+
+        tokio::time::timeout(Duration::from_secs(5), jobs.close_and_wait(Duration::from_millis(20)))
+            .await
+            .unwrap();
+        assert!(!finished.load(Ordering::SeqCst));
+        assert!(jobs.running.lock().unwrap().is_empty());
+    }
+}
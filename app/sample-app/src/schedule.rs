@@ -0,0 +1,542 @@
+//! Due Dates and Recurring Todos
+//!
+//! Due times are stored in UTC together with the IANA zone they were entered
+//! in. Recurring todos carry a rule from a subset of RFC 5545 RRULE:
+//!
+//! - `FREQ=DAILY|WEEKLY|MONTHLY|YEARLY` (required)
+//! - `INTERVAL=n`
+//! - `BYDAY=MO,WE,...` (weekly rules)
+//! - `BYMONTHDAY=n` (monthly and yearly rules; clamped to short months)
+//! - `COUNT=n` or `UNTIL=YYYYMMDD[THHMMSSZ]`
+//!
+//! Occurrences are computed in the todo's zone, so "every day at 09:00"
+//! stays at 09:00 across DST changes. Only the next occurrence exists at any
+//! time: `materialize_recurring`, run periodically as a job, creates it once
+//! the current one is completed or coming due.
+
+use chrono::{
+    DateTime, Datelike, Days, Months, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc, Weekday,
+};
+use chrono_tz::Tz;
+use crate::sync::{self, ChangeOp, EventBus};
+use rustpress_auth::db::DbPool;
+use std::fmt;
+use std::str::FromStr;
+use uuid::Uuid;
+
+/// Most todos handled per job run
+const BATCH_SIZE: i64 = 500;
+
+// ============================================
+// Time Zones
+// ============================================
+
+/// Parse an IANA zone name such as `Europe/Berlin`
+pub fn parse_timezone(name: &str) -> Result<Tz, String> {
+    name.parse().map_err(|_| format!("Unknown time zone '{}'", name))
+}
+
+/// Wall-clock time in `tz` as UTC
+///
+/// Ambiguous times (clocks going back) resolve to the first occurrence; times
+/// skipped by a DST jump move forward an hour.
+pub fn local_to_utc(local: NaiveDateTime, tz: Tz) -> DateTime<Utc> {
+    tz.from_local_datetime(&local)
+        .earliest()
+        .or_else(|| tz.from_local_datetime(&(local + chrono::Duration::hours(1))).earliest())
+        .map(|at| at.with_timezone(&Utc))
+        .unwrap_or_else(|| Utc.from_utc_datetime(&local))
+}
+
+/// Resolve a due time: RFC 3339 with an offset is absolute, anything else
+/// (`2026-10-20T09:00`, or `2026-10-20` for the end of that day) is
+/// wall-clock time in `tz`
+pub fn resolve_due(value: &str, tz: Tz) -> Result<DateTime<Utc>, String> {
+    if let Ok(at) = DateTime::parse_from_rfc3339(value) {
+        return Ok(at.with_timezone(&Utc));
+    }
+
+    let local = NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S")
+        .or_else(|_| NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M"))
+        .or_else(|_| {
+            NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .map(|date| date.and_time(NaiveTime::from_hms_opt(23, 59, 59).unwrap()))
+        })
+        .map_err(|_| format!("Invalid due time '{}'", value))?;
+
+    Ok(local_to_utc(local, tz))
+}
+
+// ============================================
+// Recurrence Rules
+// ============================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Freq {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+/// A parsed recurrence rule
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rule {
+    pub freq: Freq,
+    pub interval: u32,
+    pub by_day: Vec<Weekday>,
+    pub by_month_day: Option<u32>,
+    pub count: Option<u32>,
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl FromStr for Rule {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let value = value.trim();
+        let value = value.strip_prefix("RRULE:").unwrap_or(value);
+
+        let mut freq = None;
+        let mut rule = Rule {
+            freq: Freq::Daily,
+            interval: 1,
+            by_day: Vec::new(),
+            by_month_day: None,
+            count: None,
+            until: None,
+        };
+
+        for part in value.split(';').filter(|p| !p.is_empty()) {
+            let (key, val) = part
+                .split_once('=')
+                .ok_or_else(|| format!("Invalid rule part '{}'", part))?;
+            let number = || val.parse::<u32>().map_err(|_| format!("Invalid {} '{}'", key, val));
+
+            match key.to_ascii_uppercase().as_str() {
+                "FREQ" => {
+                    freq = Some(match val.to_ascii_uppercase().as_str() {
+                        "DAILY" => Freq::Daily,
+                        "WEEKLY" => Freq::Weekly,
+                        "MONTHLY" => Freq::Monthly,
+                        "YEARLY" => Freq::Yearly,
+                        other => return Err(format!("Unsupported FREQ '{}'", other)),
+                    })
+                }
+                "INTERVAL" => rule.interval = number()?,
+                "COUNT" => rule.count = Some(number()?),
+                "BYMONTHDAY" => rule.by_month_day = Some(number()?),
+                "BYDAY" => {
+                    rule.by_day = val
+                        .split(',')
+                        .map(parse_weekday)
+                        .collect::<Result<_, _>>()?;
+                }
+                "UNTIL" => rule.until = Some(parse_until(val)?),
+                other => {
+                    return Err(format!(
+                        "Unsupported rule part '{}' (supported: FREQ, INTERVAL, BYDAY, BYMONTHDAY, COUNT, UNTIL)",
+                        other
+                    ))
+                }
+            }
+        }
+
+        rule.freq = freq.ok_or("Rule needs FREQ")?;
+
+        if !(1..=366).contains(&rule.interval) {
+            return Err("INTERVAL must be between 1 and 366".into());
+        }
+        if rule.count == Some(0) {
+            return Err("COUNT must be at least 1".into());
+        }
+        if rule.count.is_some() && rule.until.is_some() {
+            return Err("COUNT and UNTIL can't be combined".into());
+        }
+        if !rule.by_day.is_empty() && rule.freq != Freq::Weekly {
+            return Err("BYDAY is only supported with FREQ=WEEKLY".into());
+        }
+        if let Some(day) = rule.by_month_day {
+            if !matches!(rule.freq, Freq::Monthly | Freq::Yearly) || !(1..=31).contains(&day) {
+                return Err("BYMONTHDAY must be 1-31 with FREQ=MONTHLY or YEARLY".into());
+            }
+        }
+
+        Ok(rule)
+    }
+}
+
+/// Canonical RRULE form, as stored
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let freq = match self.freq {
+            Freq::Daily => "DAILY",
+            Freq::Weekly => "WEEKLY",
+            Freq::Monthly => "MONTHLY",
+            Freq::Yearly => "YEARLY",
+        };
+        write!(f, "FREQ={}", freq)?;
+        if self.interval != 1 {
+            write!(f, ";INTERVAL={}", self.interval)?;
+        }
+        if !self.by_day.is_empty() {
+            let days: Vec<_> = self.by_day.iter().map(|d| d.to_string()[..2].to_uppercase()).collect();
+            write!(f, ";BYDAY={}", days.join(","))?;
+        }
+        if let Some(day) = self.by_month_day {
+            write!(f, ";BYMONTHDAY={}", day)?;
+        }
+        if let Some(count) = self.count {
+            write!(f, ";COUNT={}", count)?;
+        }
+        if let Some(until) = self.until {
+            write!(f, ";UNTIL={}", until.format("%Y%m%dT%H%M%SZ"))?;
+        }
+        Ok(())
+    }
+}
+
+impl Rule {
+    /// Pin monthly and yearly rules to the first occurrence's day of month,
+    /// so a todo due on the 31st returns to the 31st after a short month
+    pub fn anchored(mut self, first: NaiveDateTime) -> Self {
+        if matches!(self.freq, Freq::Monthly | Freq::Yearly) && self.by_month_day.is_none() {
+            self.by_month_day = Some(first.day());
+        }
+        self
+    }
+
+    /// Occurrence following `prev`, in the same wall-clock terms
+    pub fn next_after(&self, prev: NaiveDateTime) -> NaiveDateTime {
+        let time = prev.time();
+        let date = prev.date();
+
+        let next = match self.freq {
+            Freq::Daily => date + Days::new(self.interval.into()),
+            Freq::Weekly if self.by_day.is_empty() => date + Days::new(7 * u64::from(self.interval)),
+            Freq::Weekly => {
+                let week_start = date - Days::new(date.weekday().num_days_from_monday().into());
+                // Later days of this week, then the first listed day `interval` weeks on
+                let rest_of_week = (1..7)
+                    .map(|offset| date + Days::new(offset))
+                    .take_while(|d| *d < week_start + Days::new(7));
+                let next_week = week_start + Days::new(7 * u64::from(self.interval));
+                rest_of_week
+                    .chain((0..7).map(|offset| next_week + Days::new(offset)))
+                    .find(|d| self.by_day.contains(&d.weekday()))
+                    .unwrap_or(next_week)
+            }
+            Freq::Monthly | Freq::Yearly => {
+                let months = match self.freq {
+                    Freq::Monthly => self.interval,
+                    _ => 12 * self.interval,
+                };
+                let month = date.with_day(1).unwrap() + Months::new(months);
+                let last_day = (month + Months::new(1)).pred_opt().unwrap().day();
+                let day = self.by_month_day.unwrap_or(date.day()).min(last_day);
+                month.with_day(day).unwrap()
+            }
+        };
+
+        next.and_time(time)
+    }
+}
+
+/// Validate `rule` for a todo first due at `first_due` in `tz`; returns the
+/// canonical rule and the number of occurrences left (from `COUNT`)
+pub fn normalize_rule(rule: &str, first_due: DateTime<Utc>, tz: Tz) -> Result<(String, Option<i32>), String> {
+    let rule = rule
+        .parse::<Rule>()?
+        .anchored(first_due.with_timezone(&tz).naive_local());
+    let left = rule.count.map(|count| count.min(i32::MAX as u32) as i32);
+    Ok((rule.to_string(), left))
+}
+
+fn parse_weekday(value: &str) -> Result<Weekday, String> {
+    match value.trim().to_ascii_uppercase().as_str() {
+        "MO" => Ok(Weekday::Mon),
+        "TU" => Ok(Weekday::Tue),
+        "WE" => Ok(Weekday::Wed),
+        "TH" => Ok(Weekday::Thu),
+        "FR" => Ok(Weekday::Fri),
+        "SA" => Ok(Weekday::Sat),
+        "SU" => Ok(Weekday::Sun),
+        other => Err(format!("Invalid BYDAY '{}'", other)),
+    }
+}
+
+fn parse_until(value: &str) -> Result<DateTime<Utc>, String> {
+    NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%SZ")
+        .or_else(|_| {
+            NaiveDate::parse_from_str(value, "%Y%m%d")
+                .map(|date| date.and_time(NaiveTime::from_hms_opt(23, 59, 59).unwrap()))
+        })
+        .map(|at| Utc.from_utc_datetime(&at))
+        .map_err(|_| format!("Invalid UNTIL '{}'", value))
+}
+
+// ============================================
+// Materialization Job
+// ============================================
+
+/// The fields copied into the next occurrence
+#[derive(sqlx::FromRow)]
+struct Occurrence {
+    title: String,
+    notes: Option<String>,
+    user_id: Option<Uuid>,
+    list_id: Option<i64>,
+    project_id: Option<i64>,
+    priority: i16,
+    due_at: DateTime<Utc>,
+    timezone: String,
+    recurrence: String,
+    recurrence_left: Option<i32>,
+}
+
+impl Occurrence {
+    /// Due time of the following occurrence, `None` when the series is over
+    fn next_due(&self) -> Result<Option<DateTime<Utc>>, String> {
+        let rule: Rule = self.recurrence.parse()?;
+        if self.recurrence_left.is_some_and(|left| left <= 1) {
+            return Ok(None);
+        }
+
+        let tz = parse_timezone(&self.timezone)?;
+        let local = self.due_at.with_timezone(&tz).naive_local();
+        let next = local_to_utc(rule.next_after(local), tz);
+
+        Ok(rule.until.map_or(Some(next), |until| (next <= until).then_some(next)))
+    }
+}
+
+/// Create the next occurrence of recurring todos that are completed or due
+/// before `now + lookahead`; returns how many were created
+pub async fn materialize_recurring(
+    db: &DbPool,
+    events: &EventBus,
+    lookahead: chrono::Duration,
+) -> Result<usize, sqlx::Error> {
+    let ids: Vec<(i64,)> = sqlx::query_as(
+        r#"
+        SELECT id FROM todos
+        WHERE recurrence IS NOT NULL AND recurrence_spawned = false AND due_at IS NOT NULL
+          AND (completed = true OR due_at <= $1)
+        ORDER BY due_at
+        LIMIT $2
+        "#,
+    )
+    .bind(Utc::now() + lookahead)
+    .bind(BATCH_SIZE)
+    .fetch_all(db)
+    .await?;
+
+    let mut created = 0;
+    for (id,) in ids {
+        if spawn_next(db, events, id).await? {
+            created += 1;
+        }
+    }
+    Ok(created)
+}
+
+async fn spawn_next(db: &DbPool, events: &EventBus, id: i64) -> Result<bool, sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    // Claim the todo so overlapping runs don't both create the occurrence
+    let claimed = sqlx::query("UPDATE todos SET recurrence_spawned = true WHERE id = $1 AND recurrence_spawned = false")
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+    if claimed.rows_affected() == 0 {
+        return Ok(false);
+    }
+
+    let current: Occurrence = sqlx::query_as(
+        "SELECT title, notes, user_id, list_id, project_id, priority, due_at, timezone, recurrence, recurrence_left \
+         FROM todos WHERE id = $1",
+    )
+    .bind(id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    let next_due = match current.next_due() {
+        Ok(Some(next_due)) => next_due,
+        Ok(None) => {
+            tx.commit().await?;
+            return Ok(false);
+        }
+        Err(e) => {
+            // Left claimed so it isn't retried every run
+            tracing::warn!(todo_id = id, "Skipping recurring todo: {}", e);
+            tx.commit().await?;
+            return Ok(false);
+        }
+    };
+
+    let (next_id,): (i64,) = sqlx::query_as(
+        r#"
+        INSERT INTO todos
+            (title, completed, user_id, list_id, project_id, priority, due_at, timezone, recurrence, recurrence_left,
+             notes)
+        VALUES ($1, false, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+        RETURNING id
+        "#,
+    )
+    .bind(&current.title)
+    .bind(current.user_id)
+    .bind(current.list_id)
+    .bind(current.project_id)
+    .bind(current.priority)
+    .bind(next_due)
+    .bind(&current.timezone)
+    .bind(&current.recurrence)
+    .bind(current.recurrence_left.map(|left| left - 1))
+    .bind(&current.notes)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    sqlx::query("INSERT INTO todo_labels (todo_id, label) SELECT $1, label FROM todo_labels WHERE todo_id = $2")
+        .bind(next_id)
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+
+    let stream = sync::stream_for(current.list_id, current.user_id);
+    let change = sync::record(&mut tx, stream, ChangeOp::Create, next_id).await?;
+    tx.commit().await?;
+
+    if let Some(change) = change {
+        change.publish(events, crate::find_todo(db, next_id).await.ok());
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(value: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M").unwrap()
+    }
+
+    fn utc(value: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(value).unwrap().with_timezone(&Utc)
+    }
+
+    /// Occurrences after `first`, as wall-clock times
+    fn occurrences(rule: &str, first: &str, n: usize) -> Vec<NaiveDateTime> {
+        let first = at(first);
+        let rule = rule.parse::<Rule>().unwrap().anchored(first);
+        std::iter::successors(Some(first), |prev| Some(rule.next_after(*prev)))
+            .skip(1)
+            .take(n)
+            .collect()
+    }
+
+    fn occurrence(due_at: &str, timezone: &str, recurrence: &str, recurrence_left: Option<i32>) -> Occurrence {
+        Occurrence {
+            title: "Water the plants".into(),
+            notes: None,
+            user_id: None,
+            list_id: None,
+            project_id: None,
+            priority: 0,
+            due_at: utc(due_at),
+            timezone: timezone.into(),
+            recurrence: recurrence.into(),
+            recurrence_left,
+        }
+    }
+
+    #[test]
+    fn test_byday_expands_within_and_across_weeks() {
+        // 2026-10-12 is a Monday
+        assert_eq!(
+            occurrences("FREQ=WEEKLY;BYDAY=MO,WE,FR", "2026-10-12T09:00", 4),
+            [at("2026-10-14T09:00"), at("2026-10-16T09:00"), at("2026-10-19T09:00"), at("2026-10-21T09:00")]
+        );
+        // Every other week, starting over on the first listed day
+        assert_eq!(
+            occurrences("FREQ=WEEKLY;INTERVAL=2;BYDAY=FR,MO", "2026-10-16T18:30", 3),
+            [at("2026-10-26T18:30"), at("2026-10-30T18:30"), at("2026-11-09T18:30")]
+        );
+        // Without BYDAY the first occurrence's weekday repeats
+        assert_eq!(occurrences("FREQ=WEEKLY", "2026-10-16T09:00", 1), [at("2026-10-23T09:00")]);
+
+        let rule: Rule = "RRULE:FREQ=WEEKLY;BYDAY=mo,fr".parse().unwrap();
+        assert_eq!(rule.to_string(), "FREQ=WEEKLY;BYDAY=MO,FR");
+        assert!("FREQ=DAILY;BYDAY=MO".parse::<Rule>().is_err());
+        assert!("FREQ=WEEKLY;BYDAY=XX".parse::<Rule>().is_err());
+    }
+
+    #[test]
+    fn test_month_end_is_clamped_and_restored() {
+        assert_eq!(
+            occurrences("FREQ=MONTHLY", "2026-01-31T09:00", 4),
+            [at("2026-02-28T09:00"), at("2026-03-31T09:00"), at("2026-04-30T09:00"), at("2026-05-31T09:00")]
+        );
+        assert_eq!(occurrences("FREQ=MONTHLY", "2028-01-31T09:00", 1), [at("2028-02-29T09:00")]);
+        assert_eq!(
+            occurrences("FREQ=YEARLY", "2024-02-29T09:00", 4),
+            [at("2025-02-28T09:00"), at("2026-02-28T09:00"), at("2027-02-28T09:00"), at("2028-02-29T09:00")]
+        );
+
+        // The anchor is stored with the rule
+        let (rule, left) = normalize_rule("FREQ=MONTHLY", utc("2026-01-31T08:00:00Z"), chrono_tz::UTC).unwrap();
+        assert_eq!((rule.as_str(), left), ("FREQ=MONTHLY;BYMONTHDAY=31", None));
+        assert!("FREQ=MONTHLY;BYMONTHDAY=32".parse::<Rule>().is_err());
+    }
+
+    #[test]
+    fn test_count_ends_series() {
+        let (rule, left) = normalize_rule("FREQ=DAILY;COUNT=3", utc("2026-10-16T09:00:00Z"), chrono_tz::UTC).unwrap();
+        assert_eq!((rule.as_str(), left), ("FREQ=DAILY;COUNT=3", Some(3)));
+
+        // `recurrence_left` counts the current occurrence
+        let next = occurrence("2026-10-16T09:00:00Z", "UTC", &rule, Some(2)).next_due().unwrap();
+        assert_eq!(next, Some(utc("2026-10-17T09:00:00Z")));
+        assert_eq!(occurrence("2026-10-17T09:00:00Z", "UTC", &rule, Some(1)).next_due().unwrap(), None);
+
+        assert!("FREQ=DAILY;COUNT=0".parse::<Rule>().is_err());
+        assert!("FREQ=DAILY;COUNT=2;UNTIL=20261031".parse::<Rule>().is_err());
+    }
+
+    #[test]
+    fn test_until_ends_series() {
+        let rule = "FREQ=DAILY;UNTIL=20261018T090000Z";
+        assert_eq!(
+            occurrence("2026-10-17T09:00:00Z", "UTC", rule, None).next_due().unwrap(),
+            Some(utc("2026-10-18T09:00:00Z"))
+        );
+        assert_eq!(occurrence("2026-10-18T09:00:00Z", "UTC", rule, None).next_due().unwrap(), None);
+
+        // A date alone runs to the end of that day in UTC
+        let until: Rule = "FREQ=DAILY;UNTIL=20261018".parse().unwrap();
+        assert_eq!(until.until, Some(utc("2026-10-18T23:59:59Z")));
+        assert_eq!(until.to_string(), "FREQ=DAILY;UNTIL=20261018T235959Z");
+        assert!("FREQ=DAILY;UNTIL=tomorrow".parse::<Rule>().is_err());
+    }
+
+    #[test]
+    fn test_occurrences_keep_wall_clock_time_across_dst() {
+        let berlin = parse_timezone("Europe/Berlin").unwrap();
+
+        // Summer time ends on 2026-10-25: 09:00 moves from UTC+2 to UTC+1
+        let autumn = occurrence("2026-10-24T07:00:00Z", "Europe/Berlin", "FREQ=DAILY", None);
+        assert_eq!(autumn.next_due().unwrap(), Some(utc("2026-10-25T08:00:00Z")));
+
+        // And starts on 2026-03-29, when 09:00 moves back to UTC+2
+        let spring = occurrence("2026-03-28T08:00:00Z", "Europe/Berlin", "FREQ=WEEKLY;BYDAY=SA,SU", None);
+        assert_eq!(spring.next_due().unwrap(), Some(utc("2026-03-29T07:00:00Z")));
+
+        // Times the clocks skip move forward an hour; repeated ones take the first
+        assert_eq!(local_to_utc(at("2026-03-29T02:30"), berlin), utc("2026-03-29T01:30:00Z"));
+        assert_eq!(local_to_utc(at("2026-10-25T02:30"), berlin), utc("2026-10-25T00:30:00Z"));
+
+        assert_eq!(resolve_due("2026-10-25", berlin).unwrap(), utc("2026-10-25T22:59:59Z"));
+        assert_eq!(resolve_due("2026-10-25T09:00", berlin).unwrap(), utc("2026-10-25T08:00:00Z"));
+        assert_eq!(resolve_due("2026-10-25T09:00:00-04:00", berlin).unwrap(), utc("2026-10-25T13:00:00Z"));
+        assert!(parse_timezone("Mars/Olympus_Mons").is_err());
+    }
+}
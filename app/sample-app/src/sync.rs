@@ -0,0 +1,633 @@
+//! Live Sync
+//!
+//! Todos are grouped into streams: a shared list (`list:<id>`) or a user's
+//! unfiled todos (`user:<uuid>`). Every create, update and delete bumps the
+//! stream's revision in the same transaction and is logged in
+//! `todo_changes`; once committed it is published on the [`EventBus`].
+//!
+//! Clients connect to `GET /todos/ws?list_id=&since=` and receive every change
+//! after `since`, then live changes, in revision order. Anything missed while
+//! disconnected is fetched with `GET /todos/changes?list_id=&since=` or by
+//! reconnecting with the last revision seen.
+//!
+//! Offline clients queue mutations and send them to `POST /todos/sync`. Each
+//! todo carries the revision of its last change; an edit based on an older
+//! revision than the todo's current one is a conflict and isn't applied.
+//! Todos created offline are identified by a client-generated UUID, which
+//! also makes retried creates idempotent.
+
+use crate::lists::{list_access, require, todo_access, Access};
+use crate::{change_todo, find_todo, insert_todo, remove_todo, ApiError, AppState, CreateTodo, Todo, UpdateTodo};
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Query, Request, State,
+    },
+    http::{header, HeaderValue},
+    middleware::Next,
+    response::Response,
+    Json,
+};
+use rustpress_auth::db::{Db, DbPool};
+use rustpress_auth::problem::ProblemDetails;
+use rustpress_auth::AuthUser;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use tokio::sync::broadcast::{self, error::RecvError};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+/// Buffered changes per stream before slow sockets fall back to catch-up
+const CHANNEL_CAPACITY: usize = 256;
+
+/// Changes returned per catch-up page
+const PAGE_SIZE: i64 = 500;
+
+/// Mutations accepted per batch sync
+const MAX_MUTATIONS: usize = 100;
+
+// ============================================
+// Streams and Changes
+// ============================================
+
+/// Stream of a todo filed under `list_id` (or unfiled, created by `user_id`);
+/// `None` for todos from before ownership existed
+pub fn stream_for(list_id: Option<i64>, user_id: Option<Uuid>) -> Option<String> {
+    match (list_id, user_id) {
+        (Some(list_id), _) => Some(format!("list:{}", list_id)),
+        (None, Some(user_id)) => Some(format!("user:{}", user_id)),
+        (None, None) => None,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeOp {
+    Create,
+    Update,
+    Delete,
+}
+
+impl ChangeOp {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Create => "create",
+            Self::Update => "update",
+            Self::Delete => "delete",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "create" => Some(Self::Create),
+            "update" => Some(Self::Update),
+            "delete" => Some(Self::Delete),
+            _ => None,
+        }
+    }
+}
+
+/// One change to a stream
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Change {
+    pub stream: String,
+    pub revision: i64,
+    pub op: ChangeOp,
+    pub todo_id: i64,
+    /// The todo as it is now; absent for deletes and todos deleted since
+    pub todo: Option<Todo>,
+}
+
+/// A change logged by [`record`], to publish once its transaction commits
+pub struct Recorded {
+    stream: String,
+    revision: i64,
+    op: ChangeOp,
+    todo_id: i64,
+}
+
+impl Recorded {
+    pub fn revision(&self) -> i64 {
+        self.revision
+    }
+
+    /// Send the change to connected clients; `todo` is its state after the change
+    pub fn publish(self, events: &EventBus, todo: Option<Todo>) {
+        events.emit(Change {
+            stream: self.stream,
+            revision: self.revision,
+            op: self.op,
+            todo_id: self.todo_id,
+            todo,
+        });
+    }
+}
+
+/// Bump `stream`'s revision and log the change; call inside the transaction
+/// making the change so revisions follow commit order. Todos without a
+/// stream aren't synced.
+pub async fn record(
+    tx: &mut sqlx::Transaction<'_, Db>,
+    stream: Option<String>,
+    op: ChangeOp,
+    todo_id: i64,
+) -> Result<Option<Recorded>, sqlx::Error> {
+    let Some(stream) = stream else {
+        return Ok(None);
+    };
+
+    // The row lock taken here serializes writers to the stream
+    let (revision,): (i64,) = sqlx::query_as(
+        r#"
+        INSERT INTO todo_streams (stream, revision) VALUES ($1, 1)
+        ON CONFLICT (stream) DO UPDATE SET revision = todo_streams.revision + 1
+        RETURNING revision
+        "#,
+    )
+    .bind(&stream)
+    .fetch_one(&mut **tx)
+    .await?;
+
+    sqlx::query("INSERT INTO todo_changes (stream, revision, op, todo_id) VALUES ($1, $2, $3, $4)")
+        .bind(&stream)
+        .bind(revision)
+        .bind(op.as_str())
+        .bind(todo_id)
+        .execute(&mut **tx)
+        .await?;
+
+    if op != ChangeOp::Delete {
+        sqlx::query("UPDATE todos SET revision = $1 WHERE id = $2")
+            .bind(revision)
+            .bind(todo_id)
+            .execute(&mut **tx)
+            .await?;
+    }
+
+    Ok(Some(Recorded {
+        stream,
+        revision,
+        op,
+        todo_id,
+    }))
+}
+
+/// Up to `limit` changes to `stream` after `since`, oldest first
+async fn changes_since(db: &DbPool, stream: &str, since: i64, limit: i64) -> Result<Vec<Change>, ApiError> {
+    let rows: Vec<(i64, String, i64)> = sqlx::query_as(
+        "SELECT revision, op, todo_id FROM todo_changes WHERE stream = $1 AND revision > $2 ORDER BY revision LIMIT $3",
+    )
+    .bind(stream)
+    .bind(since)
+    .bind(limit)
+    .fetch_all(db)
+    .await?;
+
+    let mut changes = Vec::with_capacity(rows.len());
+    for (revision, op, todo_id) in rows {
+        let op = ChangeOp::parse(&op).ok_or_else(|| ApiError::Internal(format!("Unknown change op '{}'", op)))?;
+        let todo = match op {
+            ChangeOp::Delete => None,
+            _ => match find_todo(db, todo_id).await {
+                Ok(todo) => Some(todo),
+                Err(ApiError::NotFound(_)) => None,
+                Err(e) => return Err(e),
+            },
+        };
+        changes.push(Change {
+            stream: stream.to_string(),
+            revision,
+            op,
+            todo_id,
+            todo,
+        });
+    }
+    Ok(changes)
+}
+
+/// Stream `user` asked for, if they can see it
+async fn authorize_stream(db: &DbPool, user: Uuid, list_id: Option<i64>) -> Result<String, ApiError> {
+    if let Some(list_id) = list_id {
+        require(list_access(db, list_id, user).await?, Access::View, "List not found")?;
+    }
+    Ok(stream_for(list_id, Some(user)).expect("user streams always exist"))
+}
+
+// ============================================
+// Event Bus
+// ============================================
+
+/// In-process publish/subscribe of committed changes, one channel per stream
+#[derive(Default)]
+pub struct EventBus {
+    channels: RwLock<HashMap<String, broadcast::Sender<Arc<Change>>>>,
+}
+
+impl EventBus {
+    pub fn emit(&self, change: Change) {
+        let channels = self.channels.read().unwrap_or_else(|e| e.into_inner());
+        if let Some(tx) = channels.get(&change.stream) {
+            // No receivers just means nobody is connected
+            let _ = tx.send(Arc::new(change));
+        }
+    }
+
+    pub fn subscribe(&self, stream: &str) -> broadcast::Receiver<Arc<Change>> {
+        let mut channels = self.channels.write().unwrap_or_else(|e| e.into_inner());
+        channels.retain(|_, tx| tx.receiver_count() > 0);
+        channels
+            .entry(stream.to_string())
+            .or_insert_with(|| broadcast::channel(CHANNEL_CAPACITY).0)
+            .subscribe()
+    }
+}
+
+// ============================================
+// Handlers
+// ============================================
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ChangesParams {
+    /// Shared list; omit for your unfiled todos
+    pub list_id: Option<i64>,
+    /// Last revision the client has (default 0)
+    pub since: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ChangesPage {
+    pub stream: String,
+    /// Revision to pass as `since` next time
+    pub revision: i64,
+    pub changes: Vec<Change>,
+    /// More changes follow; fetch again from `revision`
+    pub has_more: bool,
+}
+
+/// GET /todos/changes - Changes to a stream after a revision
+#[utoipa::path(
+    get,
+    path = "/todos/changes",
+    tag = "sync",
+    params(ChangesParams),
+    responses(
+        (status = 200, description = "Changes, oldest first", body = ChangesPage),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 404, description = "List not found", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn list_changes(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Query(params): Query<ChangesParams>,
+) -> Result<Json<ChangesPage>, ApiError> {
+    let stream = authorize_stream(&state.db, user.id, params.list_id).await?;
+    let since = params.since.unwrap_or(0).max(0);
+    let limit = params.limit.unwrap_or(PAGE_SIZE).clamp(1, PAGE_SIZE);
+
+    let changes = changes_since(&state.db, &stream, since, limit).await?;
+    Ok(Json(ChangesPage {
+        revision: changes.last().map_or(since, |c| c.revision),
+        has_more: changes.len() as i64 == limit,
+        stream,
+        changes,
+    }))
+}
+
+/// One client-side mutation, replayed in order
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum Mutation {
+    Create {
+        /// Client-generated; a retried create returns the existing todo
+        client_id: Uuid,
+        todo: CreateTodo,
+    },
+    Update {
+        id: Option<i64>,
+        /// Alternative to `id` for todos created offline
+        client_id: Option<Uuid>,
+        /// The todo's `revision` the edit was made against
+        base_revision: Option<i64>,
+        changes: UpdateTodo,
+    },
+    Delete {
+        id: Option<i64>,
+        client_id: Option<Uuid>,
+        base_revision: Option<i64>,
+    },
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SyncRequest {
+    /// Stream to return changes for: a shared list, or omit for your unfiled todos
+    pub list_id: Option<i64>,
+    /// `sync_token` from the previous sync (default 0, everything)
+    pub sync_token: Option<i64>,
+    #[serde(default)]
+    pub mutations: Vec<Mutation>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum MutationStatus {
+    Applied,
+    /// A create whose `client_id` already exists; nothing was changed
+    Duplicate,
+    /// The todo changed after `base_revision`; `todo` is the server's version
+    Conflict,
+    Rejected,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct MutationResult {
+    /// Position in `mutations`
+    pub index: usize,
+    pub status: MutationStatus,
+    pub id: Option<i64>,
+    pub client_id: Option<Uuid>,
+    /// Revision of the applied change
+    pub revision: Option<i64>,
+    /// The todo as it is now
+    pub todo: Option<Todo>,
+    pub error: Option<ProblemDetails>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SyncResponse {
+    pub results: Vec<MutationResult>,
+    /// Changes after the request's `sync_token`, including the ones just applied
+    pub changes: Vec<Change>,
+    /// Send as `sync_token` next time
+    pub sync_token: i64,
+    /// More changes follow; sync again (with no mutations) from `sync_token`
+    pub has_more: bool,
+}
+
+/// POST /todos/sync - Apply queued offline mutations and fetch changes
+#[utoipa::path(
+    post,
+    path = "/todos/sync",
+    tag = "sync",
+    request_body = SyncRequest,
+    responses(
+        (status = 200, description = "Per-mutation results and changes since the sync token", body = SyncResponse),
+        (status = 400, description = "Too many mutations", body = ProblemDetails),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 404, description = "List not found", body = ProblemDetails),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn batch_sync(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Json(input): Json<SyncRequest>,
+) -> Result<Json<SyncResponse>, ApiError> {
+    if input.mutations.len() > MAX_MUTATIONS {
+        return Err(ApiError::BadRequest(format!("At most {} mutations per sync", MAX_MUTATIONS)));
+    }
+    let stream = authorize_stream(&state.db, user.id, input.list_id).await?;
+
+    // Each mutation commits on its own, so one failure doesn't undo the rest
+    let mut results = Vec::with_capacity(input.mutations.len());
+    for (index, mutation) in input.mutations.into_iter().enumerate() {
+        results.push(apply(&state, user.id, index, mutation).await);
+    }
+
+    let since = input.sync_token.unwrap_or(0).max(0);
+    let changes = changes_since(&state.db, &stream, since, PAGE_SIZE).await?;
+    Ok(Json(SyncResponse {
+        results,
+        sync_token: changes.last().map_or(since, |c| c.revision),
+        has_more: changes.len() as i64 == PAGE_SIZE,
+        changes,
+    }))
+}
+
+async fn apply(state: &AppState, user: Uuid, index: usize, mutation: Mutation) -> MutationResult {
+    let mut result = MutationResult {
+        index,
+        status: MutationStatus::Applied,
+        id: None,
+        client_id: None,
+        revision: None,
+        todo: None,
+        error: None,
+    };
+
+    let outcome = match mutation {
+        Mutation::Create { client_id, todo } => {
+            result.client_id = Some(client_id);
+            match todo_by_client_id(&state.db, client_id).await {
+                Ok(Some(id)) => {
+                    result.id = Some(id);
+                    match todo_access(&state.db, id, user).await {
+                        Ok(Some(_)) => {
+                            result.status = MutationStatus::Duplicate;
+                            find_todo(&state.db, id).await.map(Some)
+                        }
+                        // Someone else's todo: the client must pick a new UUID
+                        Ok(None) => {
+                            result.status = MutationStatus::Rejected;
+                            Err(ApiError::Conflict("client_id is already in use"))
+                        }
+                        Err(e) => Err(e.into()),
+                    }
+                }
+                Ok(None) => insert_todo(state, user, todo, Some(client_id)).await.map(Some),
+                Err(e) => Err(e),
+            }
+        }
+        Mutation::Update { id, client_id, base_revision, changes } => {
+            result.client_id = client_id;
+            match resolve_target(&state.db, id, client_id).await {
+                Ok(id) => {
+                    result.id = Some(id);
+                    change_todo(state, user, id, changes, base_revision).await.map(Some)
+                }
+                Err(e) => Err(e),
+            }
+        }
+        Mutation::Delete { id, client_id, base_revision } => {
+            result.client_id = client_id;
+            match resolve_target(&state.db, id, client_id).await {
+                Ok(id) => {
+                    result.id = Some(id);
+                    remove_todo(state, user, id, base_revision).await.map(|revision| {
+                        result.revision = revision;
+                        None
+                    })
+                }
+                Err(e) => Err(e),
+            }
+        }
+    };
+
+    match outcome {
+        Ok(todo) => {
+            if let Some(todo) = &todo {
+                result.id = Some(todo.id);
+                if result.status == MutationStatus::Applied {
+                    result.revision = Some(todo.revision);
+                }
+            }
+            result.todo = todo;
+        }
+        Err(e) => {
+            if matches!(e, ApiError::Conflict(_)) && result.status == MutationStatus::Applied {
+                result.status = MutationStatus::Conflict;
+                // Show the version that won, when the user can still see it
+                if let Some(id) = result.id {
+                    if let Ok(Some(_)) = todo_access(&state.db, id, user).await {
+                        result.todo = find_todo(&state.db, id).await.ok();
+                    }
+                }
+            } else {
+                result.status = MutationStatus::Rejected;
+            }
+            result.error = Some(ProblemDetails::from(e));
+        }
+    }
+    result
+}
+
+/// Todo a mutation refers to, by server or client ID
+async fn resolve_target(db: &DbPool, id: Option<i64>, client_id: Option<Uuid>) -> Result<i64, ApiError> {
+    match (id, client_id) {
+        (Some(id), _) => Ok(id),
+        (None, Some(client_id)) => todo_by_client_id(db, client_id)
+            .await?
+            .ok_or(ApiError::NotFound("Todo not found")),
+        (None, None) => Err(ApiError::BadRequest("Give the todo's id or client_id".into())),
+    }
+}
+
+async fn todo_by_client_id(db: &DbPool, client_id: Uuid) -> Result<Option<i64>, ApiError> {
+    let row: Option<(i64,)> = sqlx::query_as("SELECT id FROM todos WHERE client_id = $1")
+        .bind(client_id)
+        .fetch_optional(db)
+        .await?;
+    Ok(row.map(|(id,)| id))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SocketParams {
+    pub list_id: Option<i64>,
+    /// Replay changes after this revision before going live
+    pub since: Option<i64>,
+}
+
+/// Messages sent to WebSocket clients
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum ServerMessage<'a> {
+    /// Caught up; live changes follow
+    Ready { stream: &'a str, revision: i64 },
+    Change(&'a Change),
+}
+
+/// GET /todos/ws - Live changes to a stream
+pub async fn todos_ws(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Query(params): Query<SocketParams>,
+    ws: WebSocketUpgrade,
+) -> Result<Response, ApiError> {
+    let stream = authorize_stream(&state.db, user.id, params.list_id).await?;
+    Ok(ws.on_upgrade(move |socket| run_socket(socket, state, stream, params.since)))
+}
+
+async fn run_socket(mut socket: WebSocket, state: Arc<AppState>, stream: String, since: Option<i64>) {
+    // Subscribe before reading the log so nothing committed in between is lost
+    let mut events = state.events.subscribe(&stream);
+
+    let mut last = match since {
+        Some(since) => since.max(0),
+        None => current_revision(&state.db, &stream).await.unwrap_or(0),
+    };
+    if catch_up(&mut socket, &state.db, &stream, &mut last).await.is_err() {
+        return;
+    }
+    let ready = ServerMessage::Ready {
+        stream: &stream,
+        revision: last,
+    };
+    if send(&mut socket, &ready).await.is_err() {
+        return;
+    }
+
+    loop {
+        tokio::select! {
+            event = events.recv() => {
+                let result = match event {
+                    Ok(change) if change.revision <= last => Ok(()),
+                    Ok(change) if change.revision == last + 1 => {
+                        last = change.revision;
+                        send(&mut socket, &ServerMessage::Change(&change)).await
+                    }
+                    // Published out of order or dropped: replay from the log
+                    Ok(_) | Err(RecvError::Lagged(_)) => catch_up(&mut socket, &state.db, &stream, &mut last).await,
+                    Err(RecvError::Closed) => break,
+                };
+                if result.is_err() {
+                    break;
+                }
+            }
+            message = socket.recv() => match message {
+                // Pings are answered by axum; clients have nothing else to say
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+}
+
+/// Send every logged change after `last`, advancing it
+async fn catch_up(socket: &mut WebSocket, db: &DbPool, stream: &str, last: &mut i64) -> Result<(), ()> {
+    loop {
+        let changes = changes_since(db, stream, *last, PAGE_SIZE).await.map_err(|e| {
+            tracing::error!(stream, "Sync catch-up failed: {:?}", e);
+        })?;
+        let done = (changes.len() as i64) < PAGE_SIZE;
+
+        for change in &changes {
+            send(socket, &ServerMessage::Change(change)).await?;
+            *last = change.revision;
+        }
+        if done {
+            return Ok(());
+        }
+    }
+}
+
+async fn send(socket: &mut WebSocket, message: &ServerMessage<'_>) -> Result<(), ()> {
+    let text = serde_json::to_string(message).map_err(|_| ())?;
+    socket.send(Message::Text(text)).await.map_err(|_| ())
+}
+
+async fn current_revision(db: &DbPool, stream: &str) -> Result<i64, sqlx::Error> {
+    let row: Option<(i64,)> = sqlx::query_as("SELECT revision FROM todo_streams WHERE stream = $1")
+        .bind(stream)
+        .fetch_optional(db)
+        .await?;
+    Ok(row.map_or(0, |(revision,)| revision))
+}
+
+/// Accept the access token as `?access_token=` when there's no
+/// `Authorization` header, since browsers can't set headers on WebSocket
+/// requests. Tokens in URLs can end up in logs; keep them short-lived.
+pub async fn token_from_query(mut req: Request, next: Next) -> Response {
+    if !req.headers().contains_key(header::AUTHORIZATION) {
+        let token = Query::<HashMap<String, String>>::try_from_uri(req.uri())
+            .ok()
+            .and_then(|Query(mut query)| query.remove("access_token"));
+        if let Some(value) = token.and_then(|t| HeaderValue::from_str(&format!("Bearer {}", t)).ok()) {
+            req.headers_mut().insert(header::AUTHORIZATION, value);
+        }
+    }
+    next.run(req).await
+}
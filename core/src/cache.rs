@@ -0,0 +1,145 @@
+//! Caching
+//!
+//! `Cache` stores JSON values behind an object-safe API; the typed
+//! `get_json`/`set_json`/`remember` helpers live on `dyn Cache` so callers
+//! keep holding an `Arc<dyn Cache>`. Hosts back it with Redis or similar;
+//! [`MemoryCache`] keeps entries in the process for tests.
+
+use crate::error::HookError;
+
+use async_trait::async_trait;
+use serde::{de::DeserializeOwned, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
+use std::time::Duration;
+use tokio::sync::RwLock;
+use tokio::time::Instant;
+
+/// Cache backend
+#[async_trait]
+pub trait Cache: Send + Sync {
+    async fn get(&self, key: &str) -> Option<serde_json::Value>;
+
+    /// Store `value`, for `ttl` or until deleted
+    async fn set(&self, key: &str, value: serde_json::Value, ttl: Option<Duration>);
+
+    async fn delete(&self, key: &str);
+
+    /// Delete the keys matching a `<prefix>*` pattern
+    async fn delete_pattern(&self, pattern: &str);
+}
+
+impl dyn Cache {
+    /// Get and deserialize a value; undecodable entries count as misses
+    pub async fn get_json<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        serde_json::from_value(self.get(key).await?).ok()
+    }
+
+    pub async fn set_json<T: Serialize + ?Sized>(&self, key: &str, value: &T, ttl: Option<Duration>) -> Result<(), HookError> {
+        self.set(key, serde_json::to_value(value)?, ttl).await;
+        Ok(())
+    }
+
+    /// The cached value, or `load`'s result, stored for `ttl`
+    pub async fn remember<T, F, Fut>(&self, key: &str, ttl: Option<Duration>, load: F) -> Result<T, HookError>
+    where
+        T: Serialize + DeserializeOwned,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, HookError>>,
+    {
+        if let Some(value) = self.get_json(key).await {
+            return Ok(value);
+        }
+        let value = load().await?;
+        self.set_json(key, &value, ttl).await?;
+        Ok(value)
+    }
+}
+
+/// Entries kept in memory, expired on read
+#[derive(Debug, Default)]
+pub struct MemoryCache {
+    entries: RwLock<HashMap<String, (serde_json::Value, Option<Instant>)>>,
+}
+
+impl MemoryCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of entries, expired ones included until read
+    pub async fn len(&self) -> usize {
+        self.entries.read().await.len()
+    }
+
+    pub async fn is_empty(&self) -> bool {
+        self.entries.read().await.is_empty()
+    }
+}
+
+#[async_trait]
+impl Cache for MemoryCache {
+    async fn get(&self, key: &str) -> Option<serde_json::Value> {
+        let now = Instant::now();
+        let mut entries = self.entries.write().await;
+        match entries.get(key) {
+            Some((_, Some(expires_at))) if *expires_at <= now => {
+                entries.remove(key);
+                None
+            }
+            Some((value, _)) => Some(value.clone()),
+            None => None,
+        }
+    }
+
+    async fn set(&self, key: &str, value: serde_json::Value, ttl: Option<Duration>) {
+        let expires_at = ttl.map(|ttl| Instant::now() + ttl);
+        self.entries.write().await.insert(key.to_string(), (value, expires_at));
+    }
+
+    async fn delete(&self, key: &str) {
+        self.entries.write().await.remove(key);
+    }
+
+    async fn delete_pattern(&self, pattern: &str) {
+        let prefix = pattern.trim_end_matches('*');
+        self.entries.write().await.retain(|key, _| !key.starts_with(prefix));
+    }
+}
+
+// ============================================
+// Tests
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_memory_cache_expiry_and_patterns() {
+        let cache: Arc<dyn Cache> = Arc::new(MemoryCache::new());
+        cache.set_json("posts:1", &"one", Some(Duration::from_secs(60))).await.unwrap();
+        cache.set_json("posts:2", &"two", None).await.unwrap();
+        cache.set_json("users:1", &"ada", None).await.unwrap();
+
+        assert_eq!(cache.get_json::<String>("posts:1").await.as_deref(), Some("one"));
+        tokio::time::advance(Duration::from_secs(61)).await;
+        assert!(cache.get("posts:1").await.is_none());
+
+        cache.delete_pattern("posts:*").await;
+        assert!(cache.get("posts:2").await.is_none());
+        assert!(cache.get("users:1").await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_remember_loads_once() {
+        let cache: Arc<dyn Cache> = Arc::new(MemoryCache::new());
+        let first: u32 = cache.remember("answer", None, || async { Ok(42) }).await.unwrap();
+        let second: u32 = cache
+            .remember("answer", None, || async { Err(HookError::Internal("loaded twice".into())) })
+            .await
+            .unwrap();
+        assert_eq!((first, second), (42, 42));
+    }
+}
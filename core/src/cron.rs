@@ -0,0 +1,37 @@
+//! Cron Jobs
+//!
+//! Plugins declare jobs in `plugin.toml` (`[[cron]]` with a schedule and a
+//! handler); the host calls the handler with a [`CronContext`] at each due
+//! time:
+//!
+//! ```rust,ignore
+//! pub async fn cleanup(ctx: CronContext, plugin: Arc<MyPlugin>) -> Result<(), HookError> {
+//!     tracing::info!(job = %ctx.job, "Cleaning up");
+//!     Ok(())
+//! }
+//! ```
+
+use crate::settings::SettingsManager;
+use crate::DbPool;
+
+use chrono::{DateTime, Utc};
+
+/// Context of a cron job run
+#[derive(Debug, Clone)]
+pub struct CronContext {
+    pub db: DbPool,
+    pub settings: SettingsManager,
+    /// Plugin owning the job
+    pub plugin_id: String,
+    /// Handler name from `plugin.toml`
+    pub job: String,
+    /// When the run was due; later than scheduled when the host was busy
+    pub scheduled_at: DateTime<Utc>,
+}
+
+impl CronContext {
+    /// How late the run started
+    pub fn delay(&self) -> chrono::Duration {
+        (Utc::now() - self.scheduled_at).max(chrono::Duration::zero())
+    }
+}
@@ -0,0 +1,65 @@
+//! Hook Errors
+//!
+//! What lifecycle hooks, actions, filters and cron jobs fail with.
+
+use crate::plugin::Capability;
+
+/// Hook errors
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum HookError {
+    #[error("Database error: {0}")]
+    Database(String),
+
+    #[error("Configuration error: {0}")]
+    Config(String),
+
+    #[error("Migration error: {0}")]
+    Migration(String),
+
+    #[error("File system error: {0}")]
+    FileSystem(String),
+
+    #[error("Invalid data: {0}")]
+    InvalidData(String),
+
+    #[error("Permission denied: {0}")]
+    PermissionDenied(String),
+
+    #[error("External service error: {0}")]
+    ExternalService(String),
+
+    #[error("Validation error: {0}")]
+    Validation(String),
+
+    #[error("Hook aborted: {0}")]
+    Aborted(String),
+
+    #[error("Internal error: {0}")]
+    Internal(String),
+
+    #[error("Plugin {plugin} needs {}, which the host doesn't provide", list(.missing))]
+    MissingCapabilities { plugin: String, missing: Vec<Capability> },
+}
+
+/// `redis, email`
+fn list(capabilities: &[Capability]) -> String {
+    capabilities.iter().map(|c| c.to_string()).collect::<Vec<_>>().join(", ")
+}
+
+impl From<sqlx::Error> for HookError {
+    fn from(e: sqlx::Error) -> Self {
+        HookError::Database(e.to_string())
+    }
+}
+
+impl From<std::io::Error> for HookError {
+    fn from(e: std::io::Error) -> Self {
+        HookError::FileSystem(e.to_string())
+    }
+}
+
+impl From<serde_json::Error> for HookError {
+    fn from(e: serde_json::Error) -> Self {
+        HookError::InvalidData(e.to_string())
+    }
+}
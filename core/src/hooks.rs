@@ -0,0 +1,242 @@
+//! Hook Registry
+//!
+//! Actions announce events to any number of handlers; filters pass a value
+//! through handlers that may change it. Handlers run by priority, highest
+//! first, and the first error stops the run.
+//!
+//! ```rust,ignore
+//! hooks.add_filter("the_content", |_ctx, html: String| async move {
+//!     Ok(html.replace("RustPress", "<b>RustPress</b>"))
+//! }, priority::NORMAL).await;
+//!
+//! let html = hooks.apply_filters("the_content", &FilterContext::default(), html).await?;
+//! ```
+//!
+//! A filter hook carries one value type; handlers registered for another
+//! type make `apply_filters` fail.
+
+use crate::error::HookError;
+
+use std::any::Any;
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// Hook execution priority
+pub mod priority {
+    pub const HIGHEST: i32 = 100;
+    pub const HIGH: i32 = 50;
+    pub const NORMAL: i32 = 0;
+    pub const LOW: i32 = -50;
+    pub const LOWEST: i32 = -100;
+}
+
+/// The signed-in user a hook runs for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CurrentUser {
+    pub id: i64,
+    pub roles: Vec<String>,
+}
+
+impl CurrentUser {
+    pub fn has_role(&self, role: &str) -> bool {
+        self.roles.iter().any(|r| r == role)
+    }
+
+    pub fn is_admin(&self) -> bool {
+        self.has_role("admin")
+    }
+}
+
+/// Context of action handlers
+///
+/// `request_id` is empty outside a request (cron jobs, CLI, tests).
+#[derive(Debug, Clone, Default)]
+pub struct ActionContext {
+    pub request_id: String,
+    /// Plugin that fired the hook, if any
+    pub plugin_id: String,
+    pub user: Option<CurrentUser>,
+}
+
+/// Context of filter handlers; the same fields as [`ActionContext`]
+pub type FilterContext = ActionContext;
+
+type BoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send>>;
+
+/// Action handler type
+type ActionFn = Arc<dyn Fn(ActionContext, Box<dyn Any + Send>) -> BoxFuture<Result<(), HookError>> + Send + Sync>;
+
+/// Filter handler type
+type FilterFn<T> = Arc<dyn Fn(FilterContext, T) -> BoxFuture<Result<T, HookError>> + Send + Sync>;
+
+/// A `FilterFn<T>` of the filter's value type
+type AnyFilterFn = Arc<dyn Any + Send + Sync>;
+
+struct Handler<F> {
+    callback: F,
+    priority: i32,
+}
+
+/// Registered actions and filters
+#[derive(Default)]
+pub struct HookRegistry {
+    actions: RwLock<HashMap<String, Vec<Handler<ActionFn>>>>,
+    filters: RwLock<HashMap<String, Vec<Handler<AnyFilterFn>>>>,
+}
+
+impl HookRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register an action handler
+    pub async fn add_action<F, Fut>(&self, hook: &str, callback: F, priority: i32)
+    where
+        F: Fn(ActionContext, Box<dyn Any + Send>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), HookError>> + Send + 'static,
+    {
+        let callback: ActionFn = Arc::new(move |ctx, data| Box::pin(callback(ctx, data)));
+        let mut actions = self.actions.write().await;
+        let handlers = actions.entry(hook.to_string()).or_default();
+        handlers.push(Handler { callback, priority });
+        handlers.sort_by_key(|h| std::cmp::Reverse(h.priority));
+    }
+
+    /// Run the handlers of an action, each with its own copy of `data`
+    pub async fn do_action<T: Any + Send + Clone>(&self, hook: &str, ctx: &ActionContext, data: T) -> Result<(), HookError> {
+        let handlers: Vec<ActionFn> = match self.actions.read().await.get(hook) {
+            Some(handlers) => handlers.iter().map(|h| h.callback.clone()).collect(),
+            None => return Ok(()),
+        };
+
+        for callback in handlers {
+            callback(ctx.clone(), Box::new(data.clone())).await?;
+        }
+        Ok(())
+    }
+
+    /// Register a filter handler for values of type `T`
+    pub async fn add_filter<T, F, Fut>(&self, hook: &str, callback: F, priority: i32)
+    where
+        T: Send + 'static,
+        F: Fn(FilterContext, T) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<T, HookError>> + Send + 'static,
+    {
+        let callback: FilterFn<T> = Arc::new(move |ctx, value| Box::pin(callback(ctx, value)));
+        let mut filters = self.filters.write().await;
+        let handlers = filters.entry(hook.to_string()).or_default();
+        handlers.push(Handler {
+            callback: Arc::new(callback),
+            priority,
+        });
+        handlers.sort_by_key(|h| std::cmp::Reverse(h.priority));
+    }
+
+    /// Pass `value` through the handlers of a filter
+    pub async fn apply_filters<T: Send + 'static>(&self, hook: &str, ctx: &FilterContext, value: T) -> Result<T, HookError> {
+        let handlers = match self.filters.read().await.get(hook) {
+            Some(handlers) => handlers
+                .iter()
+                .map(|h| {
+                    h.callback.downcast_ref::<FilterFn<T>>().cloned().ok_or_else(|| {
+                        HookError::InvalidData(format!(
+                            "filter {} has handlers for a type other than {}",
+                            hook,
+                            std::any::type_name::<T>()
+                        ))
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            None => return Ok(value),
+        };
+
+        let mut value = value;
+        for callback in handlers {
+            value = callback(ctx.clone(), value).await?;
+        }
+        Ok(value)
+    }
+
+    /// Whether any handler is registered for an action or filter
+    pub async fn has_hook(&self, hook: &str) -> bool {
+        self.actions.read().await.contains_key(hook) || self.filters.read().await.contains_key(hook)
+    }
+
+    /// Remove every handler of an action or filter
+    pub async fn remove_hook(&self, hook: &str) {
+        self.actions.write().await.remove(hook);
+        self.filters.write().await.remove(hook);
+    }
+}
+
+// ============================================
+// Tests
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[tokio::test]
+    async fn test_filters_run_by_priority() {
+        let hooks = HookRegistry::new();
+        hooks
+            .add_filter("title", |_, title: String| async move { Ok(format!("{}!", title)) }, priority::LOW)
+            .await;
+        hooks
+            .add_filter("title", |_, title: String| async move { Ok(title.to_uppercase()) }, priority::HIGH)
+            .await;
+
+        let ctx = FilterContext::default();
+        assert_eq!(hooks.apply_filters("title", &ctx, "hello".to_string()).await.unwrap(), "HELLO!");
+        assert_eq!(hooks.apply_filters("other", &ctx, 3).await.unwrap(), 3);
+        assert!(matches!(
+            hooks.apply_filters("title", &ctx, 3).await,
+            Err(HookError::InvalidData(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_actions_receive_data_and_stop_on_error() {
+        let hooks = HookRegistry::new();
+        let seen = Arc::new(Mutex::new(Vec::new()));
+
+        let log = seen.clone();
+        hooks
+            .add_action(
+                "saved",
+                move |ctx: ActionContext, data: Box<dyn Any + Send>| {
+                    let log = log.clone();
+                    async move {
+                        let id = data.downcast_ref::<i64>().copied().unwrap_or_default();
+                        log.lock().unwrap().push(format!("{}:{}", ctx.request_id, id));
+                        Ok(())
+                    }
+                },
+                priority::NORMAL,
+            )
+            .await;
+        hooks
+            .add_action(
+                "saved",
+                |_, _| async { Err(HookError::Aborted("read only".into())) },
+                priority::LOWEST,
+            )
+            .await;
+
+        let ctx = ActionContext {
+            request_id: "req-1".into(),
+            ..Default::default()
+        };
+        assert!(matches!(hooks.do_action("saved", &ctx, 7i64).await, Err(HookError::Aborted(_))));
+        assert_eq!(*seen.lock().unwrap(), ["req-1:7"]);
+
+        hooks.remove_hook("saved").await;
+        assert!(!hooks.has_hook("saved").await);
+        hooks.do_action("saved", &ctx, 7i64).await.unwrap();
+    }
+}
@@ -0,0 +1,72 @@
+//! RustPress Core
+//!
+//! The types plugins, functions and apps are written against, shipped with
+//! the samples so they build and test without a RustPress host:
+//!
+//! - Plugin lifecycle: [`LifecycleHook`](plugin::LifecycleHook) and its
+//!   activation, deactivation, upgrade and uninstall contexts, handed out by
+//!   a [`PluginHost`](plugin::PluginHost), which checks the
+//!   [`Capability`](plugin::Capability)s plugins need before activating them
+//! - Settings through [`SettingsManager`](settings::SettingsManager)
+//! - Actions and filters in a [`HookRegistry`](hooks::HookRegistry)
+//! - Cron jobs with a [`CronContext`](cron::CronContext)
+//! - [`Cache`](cache::Cache) and file [`Storage`](storage::Storage) backends
+//! - Shortcode attributes and the [`rustpress_plugin!`] entry point
+//!
+//! Hosts back settings, cache and storage with their own stores; the
+//! in-memory ones here are test doubles, and [`testing`] wires them into a
+//! host for unit tests:
+//!
+//! ```rust,ignore
+//! let host = PluginHost::for_tests();
+//! host.settings.set("hello-world", "greeting", &"Hi!").await?;
+//!
+//! let plugin = HelloWorldPlugin::new();
+//! plugin.on_activate(&host.activation_context("hello-world")).await?;
+//! let app = host.router().await;
+//! ```
+//!
+//! Samples depend on this crate under the names of the host's crates, so
+//! `use rustpress_plugins::prelude::*;` keeps working:
+//!
+//! ```toml
+//! [dependencies]
+//! rustpress-plugins = { package = "rustpress-core", path = "../../core" }
+//! ```
+
+pub mod cache;
+pub mod cron;
+pub mod error;
+pub mod hooks;
+pub mod plugin;
+pub mod prelude;
+pub mod settings;
+pub mod shortcode;
+pub mod storage;
+pub mod testing;
+
+/// Database pool handed to plugins
+pub type DbPool = sqlx::PgPool;
+
+/// Export a plugin's entry point
+///
+/// The plugin type must implement [`LifecycleHook`](plugin::LifecycleHook)
+/// and `Default`. The host loads the `cdylib` and calls
+/// `_rustpress_plugin_create` for a boxed `Box<dyn LifecycleHook>`; Rust
+/// callers use `rustpress_plugin_create` instead.
+#[macro_export]
+macro_rules! rustpress_plugin {
+    ($plugin:ty) => {
+        /// Create the plugin
+        pub fn rustpress_plugin_create() -> ::std::boxed::Box<dyn $crate::plugin::LifecycleHook> {
+            ::std::boxed::Box::new(<$plugin as ::std::default::Default>::default())
+        }
+
+        /// Create the plugin for a host loading the library; the pointer
+        /// owns a `Box<Box<dyn LifecycleHook>>`
+        #[no_mangle]
+        pub extern "C" fn _rustpress_plugin_create() -> *mut ::std::ffi::c_void {
+            ::std::boxed::Box::into_raw(::std::boxed::Box::new(rustpress_plugin_create())) as *mut ::std::ffi::c_void
+        }
+    };
+}
@@ -0,0 +1,283 @@
+//! Plugin Lifecycle
+//!
+//! A plugin implements [`LifecycleHook`]; the host calls `on_activate` when
+//! it is switched on, `on_deactivate` when switched off, `on_upgrade` when a
+//! new version is installed over an old one and `on_uninstall` before its
+//! files are removed. Each hook gets a context with the services of the
+//! [`PluginHost`]: the database, settings, hooks, cache and storage.
+//!
+//! Routes a plugin registers while activating are served by the host's
+//! router (see [`PluginHost::router`]) until it deactivates.
+//!
+//! A plugin lists the host services it can't run without in
+//! [`PluginInfo::needs`]. [`PluginHost::activate`] checks them against what
+//! the host provides before calling `on_activate`, and fails with
+//! [`HookError::MissingCapabilities`] naming the missing ones, rather than
+//! letting the plugin fail later on a service that isn't there.
+
+use crate::cache::{Cache, MemoryCache};
+use crate::error::HookError;
+use crate::hooks::HookRegistry;
+use crate::settings::SettingsManager;
+use crate::storage::{MemoryStorage, Storage};
+use crate::DbPool;
+
+use async_trait::async_trait;
+use axum::Router;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// Plugin metadata
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PluginInfo {
+    pub id: String,
+    pub name: String,
+    pub version: String,
+    /// Host services the plugin needs (`needs` in plugin.toml)
+    pub needs: Vec<Capability>,
+}
+
+/// A host service plugins can depend on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Capability {
+    /// The database pool
+    Db,
+    /// A Redis connection pool
+    Redis,
+    /// File storage
+    Storage,
+    /// Scheduled `[[cron]]` jobs
+    Cron,
+    /// Outgoing mail
+    Email,
+}
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Capability::Db => "db",
+            Capability::Redis => "redis",
+            Capability::Storage => "storage",
+            Capability::Cron => "cron",
+            Capability::Email => "email",
+        })
+    }
+}
+
+/// The capabilities in `needs` that `provided` lacks, in order, each once
+pub fn missing_capabilities(needs: &[Capability], provided: &BTreeSet<Capability>) -> Vec<Capability> {
+    needs
+        .iter()
+        .filter(|need| !provided.contains(need))
+        .copied()
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect()
+}
+
+/// Plugin state enumeration
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PluginState {
+    Inactive,
+    Active,
+    Error,
+}
+
+/// Plugin lifecycle trait
+#[async_trait]
+pub trait LifecycleHook: Send + Sync {
+    /// Called when plugin is activated
+    async fn on_activate(&self, ctx: &ActivationContext) -> Result<(), HookError>;
+
+    /// Called when plugin is deactivated
+    async fn on_deactivate(&self, ctx: &DeactivationContext) -> Result<(), HookError>;
+
+    /// Called when plugin version changes
+    async fn on_upgrade(&self, _ctx: &UpgradeContext) -> Result<(), HookError> {
+        Ok(())
+    }
+
+    /// Called when plugin is completely removed
+    async fn on_uninstall(&self, _ctx: &UninstallContext) -> Result<(), HookError> {
+        Ok(())
+    }
+}
+
+/// Routers registered by active plugins, by plugin id
+type MountedRoutes = Arc<RwLock<BTreeMap<String, Router>>>;
+
+/// Activation context
+pub struct ActivationContext {
+    pub plugin_id: String,
+    pub db: DbPool,
+    pub settings: SettingsManager,
+    pub hooks: Arc<HookRegistry>,
+    pub cache: Arc<dyn Cache>,
+    pub storage: Arc<dyn Storage>,
+    /// Services the host provides
+    pub capabilities: BTreeSet<Capability>,
+    /// Version active before, when re-activating after an upgrade
+    pub previous_version: Option<String>,
+    routes: MountedRoutes,
+}
+
+impl ActivationContext {
+    /// Fail unless the host provides everything in `needs`
+    pub fn require(&self, needs: &[Capability]) -> Result<(), HookError> {
+        let missing = missing_capabilities(needs, &self.capabilities);
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(HookError::MissingCapabilities {
+                plugin: self.plugin_id.clone(),
+                missing,
+            })
+        }
+    }
+
+    /// Serve `routes` from the host's router, replacing any the plugin
+    /// registered before
+    pub async fn register_routes(&self, routes: Router) -> Result<(), HookError> {
+        self.routes.write().await.insert(self.plugin_id.clone(), routes);
+        Ok(())
+    }
+}
+
+/// Deactivation context
+pub struct DeactivationContext {
+    pub plugin_id: String,
+    pub db: DbPool,
+    pub settings: SettingsManager,
+    pub hooks: Arc<HookRegistry>,
+    routes: MountedRoutes,
+}
+
+impl DeactivationContext {
+    /// Stop serving the plugin's routes
+    pub async fn unregister_routes(&self) -> Result<(), HookError> {
+        self.routes.write().await.remove(&self.plugin_id);
+        Ok(())
+    }
+}
+
+/// Upgrade context
+pub struct UpgradeContext {
+    pub plugin_id: String,
+    pub db: DbPool,
+    pub settings: SettingsManager,
+    pub from_version: String,
+    pub to_version: String,
+}
+
+/// Uninstall context
+pub struct UninstallContext {
+    pub plugin_id: String,
+    pub db: DbPool,
+    pub settings: SettingsManager,
+    pub storage: Arc<dyn Storage>,
+}
+
+impl UninstallContext {
+    /// Remove all plugin settings
+    pub async fn remove_all_settings(&self) -> Result<(), HookError> {
+        self.settings.remove_all(&self.plugin_id).await
+    }
+}
+
+/// Services shared with plugins, and the routes they registered
+#[derive(Clone)]
+pub struct PluginHost {
+    pub db: DbPool,
+    pub settings: SettingsManager,
+    pub hooks: Arc<HookRegistry>,
+    pub cache: Arc<dyn Cache>,
+    pub storage: Arc<dyn Storage>,
+    /// Services the host provides; the database and storage always, the
+    /// others once the host has set them up
+    pub capabilities: BTreeSet<Capability>,
+    routes: MountedRoutes,
+}
+
+impl PluginHost {
+    /// A host on `db` with in-memory settings, cache and storage; replace
+    /// the public fields to use other backends
+    pub fn new(db: DbPool) -> Self {
+        Self {
+            db,
+            settings: SettingsManager::memory(),
+            hooks: Arc::new(HookRegistry::new()),
+            cache: Arc::new(MemoryCache::new()),
+            storage: Arc::new(MemoryStorage::default()),
+            capabilities: BTreeSet::from([Capability::Db, Capability::Storage]),
+            routes: MountedRoutes::default(),
+        }
+    }
+
+    /// Activate `plugin` after checking the host provides what `info` needs
+    pub async fn activate(&self, info: &PluginInfo, plugin: &dyn LifecycleHook) -> Result<(), HookError> {
+        let ctx = self.activation_context(&info.id);
+        ctx.require(&info.needs)?;
+        plugin.on_activate(&ctx).await
+    }
+
+    pub fn activation_context(&self, plugin_id: &str) -> ActivationContext {
+        ActivationContext {
+            plugin_id: plugin_id.to_string(),
+            db: self.db.clone(),
+            settings: self.settings.clone(),
+            hooks: self.hooks.clone(),
+            cache: self.cache.clone(),
+            storage: self.storage.clone(),
+            capabilities: self.capabilities.clone(),
+            previous_version: None,
+            routes: self.routes.clone(),
+        }
+    }
+
+    pub fn deactivation_context(&self, plugin_id: &str) -> DeactivationContext {
+        DeactivationContext {
+            plugin_id: plugin_id.to_string(),
+            db: self.db.clone(),
+            settings: self.settings.clone(),
+            hooks: self.hooks.clone(),
+            routes: self.routes.clone(),
+        }
+    }
+
+    pub fn upgrade_context(&self, plugin_id: &str, from_version: &str, to_version: &str) -> UpgradeContext {
+        UpgradeContext {
+            plugin_id: plugin_id.to_string(),
+            db: self.db.clone(),
+            settings: self.settings.clone(),
+            from_version: from_version.to_string(),
+            to_version: to_version.to_string(),
+        }
+    }
+
+    pub fn uninstall_context(&self, plugin_id: &str) -> UninstallContext {
+        UninstallContext {
+            plugin_id: plugin_id.to_string(),
+            db: self.db.clone(),
+            settings: self.settings.clone(),
+            storage: self.storage.clone(),
+        }
+    }
+
+    /// Ids of the plugins serving routes
+    pub async fn mounted(&self) -> Vec<String> {
+        self.routes.read().await.keys().cloned().collect()
+    }
+
+    /// The routes of every active plugin
+    pub async fn router(&self) -> Router {
+        self.routes
+            .read()
+            .await
+            .values()
+            .fold(Router::new(), |router, routes| router.merge(routes.clone()))
+    }
+}
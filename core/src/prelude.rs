@@ -0,0 +1,22 @@
+//! Everything a plugin, function or app usually needs
+//!
+//! ```rust,ignore
+//! use rustpress_plugins::prelude::*;
+//! ```
+
+pub use crate::cache::{Cache, MemoryCache};
+pub use crate::cron::CronContext;
+pub use crate::error::HookError;
+pub use crate::hooks::{priority, ActionContext, CurrentUser, FilterContext, HookRegistry};
+pub use crate::plugin::{
+    ActivationContext, Capability, DeactivationContext, LifecycleHook, PluginHost, PluginInfo, PluginState,
+    UninstallContext, UpgradeContext,
+};
+pub use crate::rustpress_plugin;
+pub use crate::settings::{MemorySettings, SettingsManager, SettingsStore};
+pub use crate::shortcode::{parse_attributes, ShortcodeAttributes, ShortcodeHandler};
+pub use crate::storage::{MemoryStorage, Storage, StorageError};
+pub use crate::DbPool;
+
+pub use async_trait::async_trait;
+pub use axum::Router;
@@ -0,0 +1,139 @@
+//! Settings
+//!
+//! Plugin settings are JSON values stored per namespace (the plugin id) and
+//! key. [`SettingsManager`] reads and writes them typed; where they live is
+//! up to the host's [`SettingsStore`]. [`MemorySettings`] keeps them in
+//! memory for tests.
+
+use crate::error::HookError;
+
+use async_trait::async_trait;
+use serde::{de::DeserializeOwned, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// Where settings are stored
+#[async_trait]
+pub trait SettingsStore: Send + Sync {
+    async fn get(&self, namespace: &str, key: &str) -> Result<Option<serde_json::Value>, HookError>;
+
+    async fn set(&self, namespace: &str, key: &str, value: serde_json::Value) -> Result<(), HookError>;
+
+    async fn remove(&self, namespace: &str, key: &str) -> Result<(), HookError>;
+
+    /// Remove every setting of `namespace`
+    async fn remove_all(&self, namespace: &str) -> Result<(), HookError>;
+}
+
+/// Typed access to the settings store; clones share the store
+#[derive(Clone)]
+pub struct SettingsManager {
+    store: Arc<dyn SettingsStore>,
+}
+
+impl SettingsManager {
+    pub fn new(store: Arc<dyn SettingsStore>) -> Self {
+        Self { store }
+    }
+
+    /// Settings kept in memory
+    pub fn memory() -> Self {
+        Self::new(Arc::new(MemorySettings::default()))
+    }
+
+    /// Get a setting; `None` when unset
+    pub async fn get<T: DeserializeOwned>(&self, namespace: &str, key: &str) -> Result<Option<T>, HookError> {
+        match self.store.get(namespace, key).await? {
+            Some(value) => Ok(Some(serde_json::from_value(value)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Get a setting, or `default` when unset
+    pub async fn get_or<T: DeserializeOwned>(&self, namespace: &str, key: &str, default: T) -> Result<T, HookError> {
+        Ok(self.get(namespace, key).await?.unwrap_or(default))
+    }
+
+    pub async fn set<T: Serialize + ?Sized>(&self, namespace: &str, key: &str, value: &T) -> Result<(), HookError> {
+        self.store.set(namespace, key, serde_json::to_value(value)?).await
+    }
+
+    pub async fn remove(&self, namespace: &str, key: &str) -> Result<(), HookError> {
+        self.store.remove(namespace, key).await
+    }
+
+    /// Remove every setting of `namespace`, e.g. when a plugin is uninstalled
+    pub async fn remove_all(&self, namespace: &str) -> Result<(), HookError> {
+        self.store.remove_all(namespace).await
+    }
+}
+
+impl std::fmt::Debug for SettingsManager {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SettingsManager").finish_non_exhaustive()
+    }
+}
+
+/// Settings kept in memory
+#[derive(Debug, Default)]
+pub struct MemorySettings {
+    values: RwLock<BTreeMap<(String, String), serde_json::Value>>,
+}
+
+impl MemorySettings {
+    /// Number of stored settings of `namespace`
+    pub async fn count(&self, namespace: &str) -> usize {
+        self.values.read().await.keys().filter(|(ns, _)| ns == namespace).count()
+    }
+}
+
+#[async_trait]
+impl SettingsStore for MemorySettings {
+    async fn get(&self, namespace: &str, key: &str) -> Result<Option<serde_json::Value>, HookError> {
+        Ok(self.values.read().await.get(&(namespace.to_string(), key.to_string())).cloned())
+    }
+
+    async fn set(&self, namespace: &str, key: &str, value: serde_json::Value) -> Result<(), HookError> {
+        self.values.write().await.insert((namespace.to_string(), key.to_string()), value);
+        Ok(())
+    }
+
+    async fn remove(&self, namespace: &str, key: &str) -> Result<(), HookError> {
+        self.values.write().await.remove(&(namespace.to_string(), key.to_string()));
+        Ok(())
+    }
+
+    async fn remove_all(&self, namespace: &str) -> Result<(), HookError> {
+        self.values.write().await.retain(|(ns, _), _| ns != namespace);
+        Ok(())
+    }
+}
+
+// ============================================
+// Tests
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_typed_settings() {
+        let settings = SettingsManager::memory();
+        settings.set("shop", "currency", "EUR").await.unwrap();
+        settings.set("shop", "tax_rate", &19).await.unwrap();
+        settings.set("blog", "title", "News").await.unwrap();
+
+        assert_eq!(settings.get::<String>("shop", "currency").await.unwrap().as_deref(), Some("EUR"));
+        assert_eq!(settings.get_or("shop", "missing", 7u32).await.unwrap(), 7);
+        assert!(matches!(
+            settings.get::<u32>("shop", "currency").await,
+            Err(HookError::InvalidData(_))
+        ));
+
+        settings.remove_all("shop").await.unwrap();
+        assert!(settings.get::<u32>("shop", "tax_rate").await.unwrap().is_none());
+        assert!(settings.get::<String>("blog", "title").await.unwrap().is_some());
+    }
+}
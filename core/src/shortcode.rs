@@ -0,0 +1,73 @@
+//! Shortcodes
+//!
+//! `[hello name="Ada" formal]` calls the `hello` handler with the attributes
+//! `name = "Ada"` and `formal = ""`, and the content between an opening and
+//! closing tag, if any.
+
+use std::collections::HashMap;
+
+/// Attributes of a shortcode
+pub type ShortcodeAttributes = HashMap<String, String>;
+
+/// Shortcode handler: attributes and enclosed content to HTML
+pub type ShortcodeHandler = fn(&ShortcodeAttributes, Option<&str>) -> String;
+
+/// Parse the attributes of a shortcode tag, e.g. `name="Ada" size=3 formal`
+///
+/// Values may be double quoted, single quoted or bare; attributes without a
+/// value are set to the empty string. Names are lower-cased.
+pub fn parse_attributes(input: &str) -> ShortcodeAttributes {
+    let mut attrs = ShortcodeAttributes::new();
+    let mut rest = input.trim();
+
+    while !rest.is_empty() {
+        let name_end = rest.find(|c: char| c == '=' || c.is_whitespace()).unwrap_or(rest.len());
+        let name = rest[..name_end].to_ascii_lowercase();
+        rest = rest[name_end..].trim_start();
+
+        let value = match rest.strip_prefix('=') {
+            Some(after) => {
+                let after = after.trim_start();
+                let (value, remaining) = match after.chars().next() {
+                    Some(quote @ ('"' | '\'')) => {
+                        let body = &after[1..];
+                        let end = body.find(quote).unwrap_or(body.len());
+                        (&body[..end], body.get(end + 1..).unwrap_or(""))
+                    }
+                    _ => {
+                        let end = after.find(char::is_whitespace).unwrap_or(after.len());
+                        (&after[..end], &after[end..])
+                    }
+                };
+                rest = remaining.trim_start();
+                value.to_string()
+            }
+            None => String::new(),
+        };
+
+        if !name.is_empty() {
+            attrs.insert(name, value);
+        }
+    }
+
+    attrs
+}
+
+// ============================================
+// Tests
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_attributes() {
+        let attrs = parse_attributes(r#"Name="Ada Lovelace" size=3 title='Hi there' formal"#);
+        assert_eq!(attrs["name"], "Ada Lovelace");
+        assert_eq!(attrs["size"], "3");
+        assert_eq!(attrs["title"], "Hi there");
+        assert_eq!(attrs["formal"], "");
+        assert!(parse_attributes("  ").is_empty());
+    }
+}
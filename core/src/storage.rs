@@ -0,0 +1,123 @@
+//! File Storage
+//!
+//! Uploads, exports and other files go through a [`Storage`] backend (local
+//! disk, S3, ...) chosen by the host, addressed by `/`-separated keys.
+//! [`MemoryStorage`] keeps files in memory for tests.
+
+use async_trait::async_trait;
+use std::collections::BTreeMap;
+use tokio::sync::RwLock;
+
+/// Storage errors
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum StorageError {
+    #[error("File not found: {0}")]
+    NotFound(String),
+
+    #[error("Storage backend error: {0}")]
+    Backend(String),
+}
+
+/// File storage backend
+#[async_trait]
+pub trait Storage: Send + Sync {
+    /// Store `data` under `key`, replacing any file there
+    async fn put(&self, key: &str, data: &[u8]) -> Result<(), StorageError>;
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>, StorageError>;
+
+    /// Delete the file under `key`; deleting a missing file succeeds
+    async fn delete(&self, key: &str) -> Result<(), StorageError>;
+
+    /// Keys starting with `prefix`, in order
+    async fn list(&self, prefix: &str) -> Result<Vec<String>, StorageError>;
+
+    /// Public URL of the file under `key`
+    fn url(&self, key: &str) -> String;
+}
+
+/// Files kept in memory, served under `base_url`
+#[derive(Debug)]
+pub struct MemoryStorage {
+    base_url: String,
+    files: RwLock<BTreeMap<String, Vec<u8>>>,
+}
+
+impl MemoryStorage {
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            files: RwLock::new(BTreeMap::new()),
+        }
+    }
+}
+
+impl Default for MemoryStorage {
+    fn default() -> Self {
+        Self::new("/uploads")
+    }
+}
+
+#[async_trait]
+impl Storage for MemoryStorage {
+    async fn put(&self, key: &str, data: &[u8]) -> Result<(), StorageError> {
+        self.files.write().await.insert(key.to_string(), data.to_vec());
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>, StorageError> {
+        self.files
+            .read()
+            .await
+            .get(key)
+            .cloned()
+            .ok_or_else(|| StorageError::NotFound(key.to_string()))
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StorageError> {
+        self.files.write().await.remove(key);
+        Ok(())
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<String>, StorageError> {
+        Ok(self
+            .files
+            .read()
+            .await
+            .keys()
+            .filter(|key| key.starts_with(prefix))
+            .cloned()
+            .collect())
+    }
+
+    fn url(&self, key: &str) -> String {
+        format!("{}/{}", self.base_url, key.trim_start_matches('/'))
+    }
+}
+
+// ============================================
+// Tests
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_memory_storage() {
+        let storage = MemoryStorage::new("https://cdn.example.com/");
+        storage.put("exports/a/index.html", b"<html>").await.unwrap();
+        storage.put("exports/b/index.html", b"<html>").await.unwrap();
+
+        assert_eq!(storage.get("exports/a/index.html").await.unwrap(), b"<html>");
+        assert_eq!(storage.list("exports/a/").await.unwrap(), ["exports/a/index.html"]);
+        assert_eq!(storage.url("exports/a/index.html"), "https://cdn.example.com/exports/a/index.html");
+
+        storage.delete("exports/a/index.html").await.unwrap();
+        storage.delete("exports/a/index.html").await.unwrap();
+        assert!(matches!(
+            storage.get("exports/a/index.html").await,
+            Err(StorageError::NotFound(_))
+        ));
+    }
+}
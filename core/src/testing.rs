@@ -0,0 +1,121 @@
+//! Test Doubles
+//!
+//! [`PluginHost::for_tests`] builds a host whose settings, cache and storage
+//! live in memory and whose database pool never connects until a query
+//! runs, so lifecycle hooks that don't touch the database can be tested
+//! without one:
+//!
+//! ```rust,ignore
+//! #[tokio::test]
+//! async fn test_activate() {
+//!     let host = PluginHost::for_tests();
+//!     let plugin = MyPlugin::new();
+//!     plugin.on_activate(&host.activation_context("my-plugin")).await.unwrap();
+//!     assert_eq!(host.mounted().await, ["my-plugin"]);
+//! }
+//! ```
+//!
+//! Point `TEST_DATABASE_URL` at a scratch database for hooks that do.
+
+use crate::plugin::PluginHost;
+use crate::DbPool;
+
+/// Database used when `TEST_DATABASE_URL` is unset
+pub const DEFAULT_TEST_DATABASE_URL: &str = "postgres://localhost/rustpress_test";
+
+/// A pool on `TEST_DATABASE_URL` that connects on first use
+pub fn lazy_pool() -> DbPool {
+    let url = std::env::var("TEST_DATABASE_URL").unwrap_or_else(|_| DEFAULT_TEST_DATABASE_URL.to_string());
+    sqlx::postgres::PgPoolOptions::new()
+        .max_connections(1)
+        .connect_lazy(&url)
+        .expect("invalid TEST_DATABASE_URL")
+}
+
+impl PluginHost {
+    /// A host with in-memory services and a [`lazy_pool`]; needs a Tokio
+    /// runtime
+    pub fn for_tests() -> Self {
+        Self::new(lazy_pool())
+    }
+}
+
+// ============================================
+// Tests
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+    use axum::{body::Body, http::Request, routing::get};
+    use tower::ServiceExt;
+
+    struct Echo;
+
+    #[async_trait]
+    impl LifecycleHook for Echo {
+        async fn on_activate(&self, ctx: &ActivationContext) -> Result<(), HookError> {
+            let word: String = ctx.settings.get_or("echo", "word", "hi".to_string()).await?;
+            ctx.register_routes(Router::new().route("/echo", get(move || async move { word })))
+                .await
+        }
+
+        async fn on_deactivate(&self, ctx: &DeactivationContext) -> Result<(), HookError> {
+            ctx.unregister_routes().await
+        }
+    }
+
+    async fn call(host: &PluginHost) -> axum::http::StatusCode {
+        let request = Request::get("/echo").body(Body::empty()).unwrap();
+        host.router().await.oneshot(request).await.unwrap().status()
+    }
+
+    fn info(needs: Vec<Capability>) -> PluginInfo {
+        PluginInfo {
+            id: "echo".into(),
+            name: "Echo".into(),
+            version: "1.0.0".into(),
+            needs,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_activation_checks_capabilities() {
+        let mut host = PluginHost::for_tests();
+        let needs = vec![Capability::Db, Capability::Redis, Capability::Email, Capability::Redis];
+
+        let err = host.activate(&info(needs.clone()), &Echo).await.unwrap_err();
+        assert_eq!(
+            err,
+            HookError::MissingCapabilities {
+                plugin: "echo".into(),
+                missing: vec![Capability::Redis, Capability::Email],
+            }
+        );
+        assert_eq!(err.to_string(), "Plugin echo needs redis, email, which the host doesn't provide");
+        assert!(host.mounted().await.is_empty());
+
+        host.capabilities.extend([Capability::Redis, Capability::Email]);
+        host.activate(&info(needs), &Echo).await.unwrap();
+        assert_eq!(host.mounted().await, ["echo"]);
+    }
+
+    #[tokio::test]
+    async fn test_lifecycle_mounts_and_unmounts_routes() {
+        let host = PluginHost::for_tests();
+        host.settings.set("echo", "word", "hello").await.unwrap();
+
+        Echo.on_activate(&host.activation_context("echo")).await.unwrap();
+        assert_eq!(host.mounted().await, ["echo"]);
+        assert_eq!(call(&host).await, 200);
+
+        Echo.on_deactivate(&host.deactivation_context("echo")).await.unwrap();
+        assert!(host.mounted().await.is_empty());
+        assert_eq!(call(&host).await, 404);
+
+        Echo.on_upgrade(&host.upgrade_context("echo", "1.0.0", "1.1.0")).await.unwrap();
+        let uninstall = host.uninstall_context("echo");
+        uninstall.remove_all_settings().await.unwrap();
+        assert!(host.settings.get::<String>("echo", "word").await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,117 @@
+//! `#[derive(Event)]` for the advanced hooks event bus
+//!
+//! ```rust,ignore
+//! #[derive(Debug, Clone, Serialize, Deserialize, Event)]
+//! #[event(name = "post_published")]
+//! pub struct PostPublished {
+//!     pub post_id: i64,
+//! }
+//! ```
+//!
+//! Without `#[event(name = ...)]` the name is the type name in snake_case.
+//! `#[event(version = N)]` sets the payload version (default 1), which
+//! transports put in topic names; bump it on breaking payload changes.
+//! Names are checked here, so a malformed one fails the build, and every
+//! derived event is registered with `events::registered()`.
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::quote;
+use syn::{parse_macro_input, DeriveInput, LitInt, LitStr};
+
+#[proc_macro_derive(Event, attributes(event))]
+pub fn derive_event(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand(input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &input.generics,
+            "events cannot be generic; each event type needs a single name",
+        ));
+    }
+
+    let ident = &input.ident;
+    let EventAttrs { name, version } = event_attrs(&input)?;
+    let type_name = ident.to_string();
+
+    Ok(quote! {
+        impl ::rustpress_advanced_hooks::events::Event for #ident {
+            const NAME: &'static str = #name;
+            const VERSION: u32 = #version;
+        }
+
+        ::rustpress_advanced_hooks::events::__private::inventory::submit! {
+            ::rustpress_advanced_hooks::events::Registration {
+                name: #name,
+                type_name: #type_name,
+                version: #version,
+            }
+        }
+    })
+}
+
+struct EventAttrs {
+    name: LitStr,
+    version: u32,
+}
+
+/// `#[event(name = "...", version = N)]`; the name defaults to the snake_cased
+/// type name, the version to 1
+fn event_attrs(input: &DeriveInput) -> syn::Result<EventAttrs> {
+    let mut name = None;
+    let mut version = 1;
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("event")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("name") {
+                name = Some(meta.value()?.parse::<LitStr>()?);
+                Ok(())
+            } else if meta.path.is_ident("version") {
+                let lit = meta.value()?.parse::<LitInt>()?;
+                version = lit.base10_parse::<u32>()?;
+                if version == 0 {
+                    return Err(syn::Error::new(lit.span(), "event versions start at 1"));
+                }
+                Ok(())
+            } else {
+                Err(meta.error("expected `name = \"...\"` or `version = N`"))
+            }
+        })?;
+    }
+
+    let name = name.unwrap_or_else(|| LitStr::new(&snake_case(&input.ident.to_string()), Span::call_site()));
+    let value = name.value();
+    let valid = value.starts_with(|c: char| c.is_ascii_lowercase())
+        && value
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '.');
+    if !valid {
+        return Err(syn::Error::new(
+            name.span(),
+            format!(
+                "invalid event name \"{}\" (use lowercase letters, digits, '_' and '.', starting with a letter)",
+                value
+            ),
+        ));
+    }
+    Ok(EventAttrs { name, version })
+}
+
+fn snake_case(ident: &str) -> String {
+    let mut out = String::with_capacity(ident.len() + 4);
+    for (i, c) in ident.chars().enumerate() {
+        if c.is_ascii_uppercase() {
+            if i > 0 {
+                out.push('_');
+            }
+            out.push(c.to_ascii_lowercase());
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
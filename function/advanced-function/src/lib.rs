@@ -8,6 +8,9 @@
 //! - Event handling
 //! - Middleware patterns
 
+// Lets `#[derive(Event)]` refer to this crate by name from inside it
+extern crate self as rustpress_advanced_hooks;
+
 use rustpress_functions::prelude::*;
 use std::collections::HashMap;
 use std::sync::Arc;
@@ -30,11 +33,11 @@ pub mod utils;
 
 /// Global cache for option values
 lazy_static::lazy_static! {
-    static ref OPTION_CACHE: Arc<RwLock<HashMap<String, serde_json::Value>>> =
-        Arc::new(RwLock::new(HashMap::new()));
+    static ref OPTION_CACHE: Arc<cache::OptionCache> =
+        Arc::new(cache::OptionCache::new(cache::DEFAULT_OPTION_TTL));
 
     static ref EVENT_BUS: Arc<events::EventBus> =
-        Arc::new(events::EventBus::new());
+        Arc::new(events::EventBus::with_config(events::shared_config()));
 }
 
 // ============================================
@@ -55,8 +58,12 @@ pub mod actions {
         setup_event_listeners().await;
 
         // Warm up caches
+        cache::configure(&ctx.db).await;
         cache::warm_up(&ctx.db).await?;
 
+        // Publish events to NATS or Kafka if configured
+        transport::configure(&ctx.db).await;
+
         Ok(())
     }
 
@@ -93,11 +100,11 @@ pub mod actions {
         cache::invalidate_post(*post_id).await;
 
         // Emit event for other systems
-        EVENT_BUS.emit("post_saved", serde_json::json!({
-            "post_id": post_id,
-            "user_id": ctx.user.as_ref().map(|u| u.id),
-            "timestamp": chrono::Utc::now()
-        })).await;
+        EVENT_BUS.emit_typed(&events::PostSaved {
+            post_id: *post_id,
+            user_id: ctx.user.as_ref().map(|u| u.id),
+            timestamp: chrono::Utc::now(),
+        }).await;
 
         // Ping search engines for new content
         if let Some(post) = ctx.db.get_post(*post_id).await.ok().flatten() {
@@ -203,9 +210,9 @@ pub mod actions {
         match (change.old_status.as_str(), change.new_status.as_str()) {
             ("draft", "published") => {
                 // New publication - notify subscribers, social sharing
-                EVENT_BUS.emit("post_published", serde_json::json!({
-                    "post_id": change.post_id
-                })).await;
+                EVENT_BUS.emit_typed(&events::PostPublished {
+                    post_id: change.post_id,
+                }).await;
             }
             ("published", "draft") => {
                 // Unpublished - update caches
@@ -226,6 +233,16 @@ pub mod actions {
         Ok(())
     }
 
+    /// Evict a changed option from every instance's cache
+    pub async fn on_option_updated(_ctx: ActionContext, data: ActionData) -> Result<(), HookError> {
+        let update = data.get::<cache::OptionUpdated>().ok_or(HookError::InvalidData)?;
+
+        tracing::debug!("Option updated: {}:{}", update.namespace, update.name);
+        OPTION_CACHE.invalidate(&update.namespace, &update.name).await;
+
+        Ok(())
+    }
+
     // Helper functions
 
     async fn register_custom_types(ctx: &ActionContext) -> Result<(), HookError> {
@@ -260,18 +277,18 @@ pub mod filters {
         let mut result = content;
 
         // Add table of contents for long posts
-        if result.matches("<h2").count() >= 3 {
-            result = add_table_of_contents(&result);
-        }
+        let toc_options = toc::TocOptions::from_filter_args(&ctx.filter_args);
+        result = add_table_of_contents(&result, &toc_options);
+
+        // Add anchor links to headings (same IDs as the TOC links)
+        result = add_heading_anchors(&result);
 
         // Auto-link URLs
         result = utils::auto_link_urls(&result);
 
-        // Add lazy loading to images
-        result = add_lazy_loading(&result);
-
-        // Add anchor links to headings
-        result = add_heading_anchors(&result);
+        // Responsive images: srcset, dimensions, CDN, lazy loading
+        let image_options = images::ImageOptions::from_filter_args(&ctx.filter_args, &ctx.site_url);
+        result = add_responsive_images(&result, &image_options);
 
         // Process shortcodes (if not already done)
         result = process_shortcodes(&result).await;
@@ -290,6 +307,40 @@ pub mod filters {
         Ok(result)
     }
 
+    /// Add paragraphs and line breaks to post content
+    ///
+    /// Registered after `process_content` so expanded shortcode markup is
+    /// seen as the blocks it produces.
+    pub async fn auto_paragraphs(ctx: FilterContext, content: String) -> Result<String, HookError> {
+        let enabled = ctx.filter_args.get("autop")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(true);
+        if !enabled {
+            return Ok(content);
+        }
+
+        let options = autop::AutopOptions::from_filter_args(&ctx.filter_args);
+        Ok(autop::autop(&content, &options))
+    }
+
+    /// Typographic quotes, dashes and ellipses in post content
+    pub async fn typographic_content(ctx: FilterContext, content: String) -> Result<String, HookError> {
+        let enabled = ctx.filter_args.get("smart_typography")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(true);
+        if !enabled {
+            return Ok(content);
+        }
+
+        match typography::smarten_html(&content) {
+            Ok(result) => Ok(result),
+            Err(e) => {
+                tracing::warn!("Failed to apply typography: {}", e);
+                Ok(content)
+            }
+        }
+    }
+
     /// Generate smart excerpts
     pub async fn auto_excerpt(ctx: FilterContext, excerpt: String) -> Result<String, HookError> {
         if !excerpt.is_empty() {
@@ -329,9 +380,12 @@ pub mod filters {
             }
         }
 
-        // Add theme class
-        if let Some(theme) = ctx.get_option("color_scheme").await {
-            result.push(format!("theme-{}", theme));
+        // Add the reader's color scheme class (`preferences` is passed by the host)
+        if let Some(scheme) = ctx.filter_args.get("preferences")
+            .and_then(|p| p.get("color_scheme"))
+            .and_then(|v| v.as_str())
+        {
+            result.push(format!("theme-{}", scheme));
         }
 
         Ok(result)
@@ -426,24 +480,44 @@ pub mod filters {
         Ok(result)
     }
 
-    /// Cache option lookups
+    /// Serve option lookups from the cache
     pub async fn cache_option(ctx: FilterContext, value: Option<String>) -> Result<Option<String>, HookError> {
-        // Check cache first
-        let option_name = ctx.filter_args.get("option_name")
-            .and_then(|v| v.as_str())
-            .unwrap_or("");
+        let (namespace, option_name) = option_key(&ctx);
 
         if !option_name.is_empty() {
-            let cache = OPTION_CACHE.read().await;
-            if let Some(cached) = cache.get(option_name) {
-                return Ok(Some(cached.to_string()));
+            if let Some(cached) = OPTION_CACHE.get(namespace, option_name).await {
+                return Ok(Some(cached.as_str().map(String::from).unwrap_or_else(|| cached.to_string())));
+            }
+        }
+
+        // Return original value (cached by `remember_option` once loaded)
+        Ok(value)
+    }
+
+    /// Cache option values loaded from the database
+    pub async fn remember_option(ctx: FilterContext, value: Option<String>) -> Result<Option<String>, HookError> {
+        let (namespace, option_name) = option_key(&ctx);
+
+        if let Some(value) = &value {
+            if !option_name.is_empty() {
+                OPTION_CACHE.insert(namespace, option_name, serde_json::json!(value)).await;
             }
         }
 
-        // Return original value (will be cached by post_option filter)
         Ok(value)
     }
 
+    /// Namespace and name of the option a lookup filter runs for
+    fn option_key(ctx: &FilterContext) -> (&str, &str) {
+        let namespace = ctx.filter_args.get("namespace")
+            .and_then(|v| v.as_str())
+            .unwrap_or(cache::DEFAULT_NAMESPACE);
+        let option_name = ctx.filter_args.get("option_name")
+            .and_then(|v| v.as_str())
+            .unwrap_or("");
+        (namespace, option_name)
+    }
+
     /// Process text widget content
     pub async fn process_widget_text(ctx: FilterContext, text: String) -> Result<String, HookError> {
         // Apply content filters to widget text
@@ -525,18 +599,34 @@ pub mod filters {
 
     // Helper functions
 
-    fn add_table_of_contents(content: &str) -> String {
-        // Parse headings and generate TOC
-        content.to_string()
+    fn add_table_of_contents(content: &str, options: &toc::TocOptions) -> String {
+        match toc::insert_table_of_contents(content, options) {
+            Ok(result) => result,
+            Err(e) => {
+                tracing::warn!("Failed to build table of contents: {}", e);
+                content.to_string()
+            }
+        }
     }
 
-    fn add_lazy_loading(content: &str) -> String {
-        content.replace("<img ", "<img loading=\"lazy\" ")
+    fn add_responsive_images(content: &str, options: &images::ImageOptions) -> String {
+        match images::rewrite_images(content, options) {
+            Ok(result) => result,
+            Err(e) => {
+                tracing::warn!("Failed to rewrite images: {}", e);
+                content.to_string()
+            }
+        }
     }
 
     fn add_heading_anchors(content: &str) -> String {
-        // Add anchor links to h2, h3, etc.
-        content.to_string()
+        match toc::add_heading_anchors(content) {
+            Ok(result) => result,
+            Err(e) => {
+                tracing::warn!("Failed to add heading anchors: {}", e);
+                content.to_string()
+            }
+        }
     }
 
     async fn process_shortcodes(content: &str) -> String {
@@ -815,7 +905,223 @@ pub mod shortcodes {
 // ============================================
 
 pub mod cache {
+    //! Option cache
+    //!
+    //! Options are cached per namespace (`core`, a theme, a plugin id) for a
+    //! TTL (`option_cache_ttl`, 300s by default). `update_option` writes
+    //! through to the database; it and the `option_updated` action evict the
+    //! key on every instance. Instances share evictions over Redis pub/sub
+    //! when `option_cache_redis_url` is set; if the subscription drops, the
+    //! cache is cleared once it is restored since evictions may have been
+    //! missed, and the TTL bounds staleness in the meantime.
+
     use super::*;
+    use futures_util::StreamExt;
+    use redis::AsyncCommands;
+    use serde::{Deserialize, Serialize};
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Weak;
+    use std::time::{Duration, Instant};
+    use uuid::Uuid;
+
+    /// Namespace of options looked up without one
+    pub const DEFAULT_NAMESPACE: &str = "core";
+
+    /// How long an option stays cached unless `option_cache_ttl` says otherwise
+    pub const DEFAULT_OPTION_TTL: Duration = Duration::from_secs(300);
+
+    /// Redis channel carrying evictions between instances
+    pub const INVALIDATION_CHANNEL: &str = "rustpress:options:invalidate";
+
+    /// Delay before resubscribing after the pub/sub connection drops
+    const RESUBSCRIBE_DELAY: Duration = Duration::from_secs(1);
+
+    /// Payload of the `option_updated` action
+    #[derive(Debug, Clone)]
+    pub struct OptionUpdated {
+        pub namespace: String,
+        pub name: String,
+    }
+
+    /// Eviction broadcast to every instance
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Invalidation {
+        /// Instance that published it; it has already evicted locally
+        origin: Uuid,
+        namespace: String,
+        name: String,
+    }
+
+    struct CachedOption {
+        value: serde_json::Value,
+        expires_at: Instant,
+    }
+
+    /// Cached option values by namespace and name
+    pub struct OptionCache {
+        entries: RwLock<HashMap<(String, String), CachedOption>>,
+        ttl_secs: AtomicU64,
+        instance: Uuid,
+        publisher: RwLock<Option<redis::aio::ConnectionManager>>,
+    }
+
+    impl OptionCache {
+        pub fn new(ttl: Duration) -> Self {
+            Self {
+                entries: RwLock::new(HashMap::new()),
+                ttl_secs: AtomicU64::new(ttl.as_secs()),
+                instance: Uuid::new_v4(),
+                publisher: RwLock::new(None),
+            }
+        }
+
+        pub fn set_ttl(&self, ttl: Duration) {
+            self.ttl_secs.store(ttl.as_secs(), Ordering::Relaxed);
+        }
+
+        /// The cached value, unless missing or expired
+        pub async fn get(&self, namespace: &str, name: &str) -> Option<serde_json::Value> {
+            let key = (namespace.to_string(), name.to_string());
+            {
+                let entries = self.entries.read().await;
+                match entries.get(&key) {
+                    Some(entry) if entry.expires_at > Instant::now() => return Some(entry.value.clone()),
+                    Some(_) => {}
+                    None => return None,
+                }
+            }
+            self.entries.write().await.remove(&key);
+            None
+        }
+
+        pub async fn insert(&self, namespace: &str, name: &str, value: serde_json::Value) {
+            let ttl = Duration::from_secs(self.ttl_secs.load(Ordering::Relaxed));
+            self.entries.write().await.insert(
+                (namespace.to_string(), name.to_string()),
+                CachedOption {
+                    value,
+                    expires_at: Instant::now() + ttl,
+                },
+            );
+        }
+
+        /// Drop the key on this instance only
+        pub async fn evict(&self, namespace: &str, name: &str) {
+            self.entries
+                .write()
+                .await
+                .remove(&(namespace.to_string(), name.to_string()));
+        }
+
+        /// Drop the key on every instance
+        pub async fn invalidate(&self, namespace: &str, name: &str) {
+            self.evict(namespace, name).await;
+            self.publish(namespace, name).await;
+        }
+
+        pub async fn clear(&self) {
+            self.entries.write().await.clear();
+        }
+
+        /// Share evictions with other instances through `redis_url`
+        pub async fn connect(self: &Arc<Self>, redis_url: &str) -> Result<(), redis::RedisError> {
+            let client = redis::Client::open(redis_url)?;
+            let publisher = redis::aio::ConnectionManager::new(client.clone()).await?;
+            *self.publisher.write().await = Some(publisher);
+            tokio::spawn(subscribe(client, self.instance, Arc::downgrade(self)));
+            Ok(())
+        }
+
+        async fn publish(&self, namespace: &str, name: &str) {
+            let Some(mut conn) = self.publisher.read().await.clone() else {
+                return;
+            };
+            let message = Invalidation {
+                origin: self.instance,
+                namespace: namespace.to_string(),
+                name: name.to_string(),
+            };
+            let payload = match serde_json::to_string(&message) {
+                Ok(payload) => payload,
+                Err(e) => {
+                    tracing::warn!("Failed to encode option eviction: {}", e);
+                    return;
+                }
+            };
+            if let Err(e) = conn.publish::<_, _, ()>(INVALIDATION_CHANNEL, payload).await {
+                tracing::warn!("Failed to publish option eviction: {}", e);
+            }
+        }
+    }
+
+    /// Apply evictions from other instances until the cache is dropped
+    async fn subscribe(client: redis::Client, instance: Uuid, cache: Weak<OptionCache>) {
+        let mut resubscribing = false;
+        loop {
+            let mut pubsub = match client.get_async_pubsub().await {
+                Ok(pubsub) => pubsub,
+                Err(e) => {
+                    tracing::warn!("Option eviction subscription failed: {}", e);
+                    tokio::time::sleep(RESUBSCRIBE_DELAY).await;
+                    continue;
+                }
+            };
+            if let Err(e) = pubsub.subscribe(INVALIDATION_CHANNEL).await {
+                tracing::warn!("Option eviction subscription failed: {}", e);
+                tokio::time::sleep(RESUBSCRIBE_DELAY).await;
+                continue;
+            }
+
+            if resubscribing {
+                match cache.upgrade() {
+                    Some(cache) => cache.clear().await,
+                    None => return,
+                }
+                tracing::info!("Option eviction subscription restored; option cache cleared");
+            }
+            resubscribing = true;
+
+            let mut messages = std::pin::pin!(pubsub.on_message());
+            while let Some(message) = messages.next().await {
+                let Some(cache) = cache.upgrade() else {
+                    return;
+                };
+
+                let invalidation = message
+                    .get_payload::<String>()
+                    .ok()
+                    .and_then(|payload| serde_json::from_str::<Invalidation>(&payload).ok());
+                let Some(invalidation) = invalidation else {
+                    tracing::warn!("Ignoring malformed option eviction");
+                    continue;
+                };
+                if invalidation.origin != instance {
+                    cache.evict(&invalidation.namespace, &invalidation.name).await;
+                }
+            }
+
+            tracing::warn!("Option eviction subscription lost; reconnecting");
+            tokio::time::sleep(RESUBSCRIBE_DELAY).await;
+        }
+    }
+
+    /// Apply `option_cache_ttl` and `option_cache_redis_url`
+    pub async fn configure(db: &Database) {
+        if let Ok(Some(ttl)) = db.get_option("option_cache_ttl").await {
+            match ttl.trim().parse::<u64>() {
+                Ok(secs) => OPTION_CACHE.set_ttl(Duration::from_secs(secs)),
+                Err(_) => tracing::warn!("Ignoring invalid option_cache_ttl: {}", ttl),
+            }
+        }
+
+        if let Ok(Some(url)) = db.get_option("option_cache_redis_url").await {
+            if !url.trim().is_empty() {
+                if let Err(e) = OPTION_CACHE.connect(url.trim()).await {
+                    tracing::warn!("Option cache evictions stay local; Redis is unavailable: {}", e);
+                }
+            }
+        }
+    }
 
     /// Warm up caches on initialization
     pub async fn warm_up(db: &Database) -> Result<(), HookError> {
@@ -825,14 +1131,25 @@ pub mod cache {
         let options = ["site_name", "site_description", "admin_email", "posts_per_page"];
         for name in options {
             if let Ok(Some(value)) = db.get_option(name).await {
-                let mut cache = OPTION_CACHE.write().await;
-                cache.insert(name.to_string(), serde_json::json!(value));
+                OPTION_CACHE
+                    .insert(DEFAULT_NAMESPACE, name, serde_json::json!(value))
+                    .await;
             }
         }
 
         Ok(())
     }
 
+    /// Write an option to the database and the cache, evicting it elsewhere
+    pub async fn update_option(db: &Database, namespace: &str, name: &str, value: &str) -> Result<(), HookError> {
+        db.set_option(name, value)
+            .await
+            .map_err(|e| HookError::Internal(e.to_string()))?;
+        OPTION_CACHE.insert(namespace, name, serde_json::json!(value)).await;
+        OPTION_CACHE.publish(namespace, name).await;
+        Ok(())
+    }
+
     /// Invalidate caches related to a post
     pub async fn invalidate_post(post_id: i64) {
         tracing::debug!("Invalidating cache for post: {}", post_id);
@@ -843,8 +1160,7 @@ pub mod cache {
 
     /// Clear all caches
     pub async fn clear_all() {
-        let mut cache = OPTION_CACHE.write().await;
-        cache.clear();
+        OPTION_CACHE.clear().await;
         tracing::info!("All caches cleared");
     }
 }
@@ -854,71 +1170,3503 @@ pub mod cache {
 // ============================================
 
 pub mod events {
+    //! Cross-system events
+    //!
+    //! Events are declared as types so emitters and subscribers agree on the
+    //! name and payload at compile time:
+    //!
+    //! ```rust,ignore
+    //! EVENT_BUS.emit_typed(&PostPublished { post_id }).await;
+    //!
+    //! let mut rx = EVENT_BUS.subscribe_typed::<PostPublished>().await;
+    //! while let Ok(event) = rx.recv().await {
+    //!     tracing::info!("Published: {}", event.post_id);
+    //! }
+    //! ```
+    //!
+    //! `emit` / `subscribe` still take names and JSON values; typed and
+    //! untyped subscribers of the same event see the same payloads.
+    //!
+    //! Every subscriber has its own queue of `capacity` events (per event,
+    //! see [`EventBusConfig`]). What happens when it is full depends on the
+    //! event's [`OverflowPolicy`]; [`EventBus::stats`] reports drops, spills
+    //! and how far the slowest subscriber is behind.
+    //!
+    //! Consumers that must not miss events use [`EventBus::subscribe_durable`]:
+    //! once an event has a durable consumer, every emit is appended to its
+    //! outbox log on disk, and the consumer resumes after the last entry it
+    //! acknowledged, also across restarts.
+    //!
+    //! With a transport attached ([`EventBus::attach_transport`], see
+    //! [`crate::transport`]), every emit is also published to NATS or Kafka
+    //! for other services.
+
     use super::*;
-    use std::collections::HashMap;
-    use tokio::sync::broadcast;
+    use serde::{de::DeserializeOwned, Deserialize, Serialize};
+    use std::collections::{BTreeMap, HashMap, VecDeque};
+    use std::fs::{self, File, OpenOptions};
+    use std::io::{self, BufRead, BufReader, SeekFrom, Write};
+    use std::marker::PhantomData;
+    use std::path::{Path, PathBuf};
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::{Mutex, OnceLock, Weak};
+    use tokio::io::{AsyncBufReadExt, AsyncSeekExt, AsyncWriteExt};
+    use tokio::sync::{watch, Notify};
+
+    pub use rustpress_advanced_hooks_derive::Event;
+    pub use tokio::sync::broadcast::error::RecvError;
+
+    #[doc(hidden)]
+    pub mod __private {
+        pub use inventory;
+    }
+
+    /// An event with a fixed name and payload; use `#[derive(Event)]`
+    pub trait Event: Serialize + DeserializeOwned + Send + 'static {
+        const NAME: &'static str;
+        /// Payload version, bumped on breaking changes to the payload
+        const VERSION: u32 = 1;
+    }
+
+    /// An event type declared with `#[derive(Event)]`
+    #[derive(Debug, Clone, Copy)]
+    pub struct Registration {
+        pub name: &'static str,
+        pub type_name: &'static str,
+        pub version: u32,
+    }
+
+    inventory::collect!(Registration);
+
+    /// Every event type declared with `#[derive(Event)]`
+    pub fn registered() -> impl Iterator<Item = &'static Registration> {
+        inventory::iter::<Registration>.into_iter()
+    }
+
+    /// Whether an event type is declared under `name`
+    pub fn is_registered(name: &str) -> bool {
+        registered().any(|r| r.name == name)
+    }
+
+    /// Payload version of the event type declared under `name`; 1 for
+    /// undeclared names
+    pub fn version_of(name: &str) -> u32 {
+        registered().find(|r| r.name == name).map_or(1, |r| r.version)
+    }
+
+    /// A post was created or updated
+    #[derive(Debug, Clone, Serialize, Deserialize, Event)]
+    #[event(name = "post_saved")]
+    pub struct PostSaved {
+        pub post_id: i64,
+        pub user_id: Option<i64>,
+        pub timestamp: chrono::DateTime<chrono::Utc>,
+    }
+
+    /// A draft was published
+    #[derive(Debug, Clone, Serialize, Deserialize, Event)]
+    #[event(name = "post_published")]
+    pub struct PostPublished {
+        pub post_id: i64,
+    }
+
+    // ----------------------------------------
+    // Configuration
+    // ----------------------------------------
+
+    /// Queue size of subscribers to events without their own options
+    const DEFAULT_CAPACITY: usize = 100;
+
+    /// Acknowledged outbox entries kept before the log is rewritten
+    const COMPACT_AFTER: u64 = 1000;
+
+    const LOG_FILE: &str = "log.jsonl";
+    const CONSUMERS_DIR: &str = "consumers";
+
+    static CONFIG: OnceLock<EventBusConfig> = OnceLock::new();
+
+    /// Configure the shared `EVENT_BUS`; only effective before its first use
+    pub fn configure(config: EventBusConfig) -> Result<(), EventBusConfig> {
+        CONFIG.set(config)
+    }
+
+    pub(crate) fn shared_config() -> EventBusConfig {
+        CONFIG.get().cloned().unwrap_or_default()
+    }
+
+    /// What happens to an event when a subscriber's queue is full
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+    #[serde(rename_all = "snake_case")]
+    pub enum OverflowPolicy {
+        /// Discard the oldest queued event; the subscriber's next `recv`
+        /// returns `RecvError::Lagged` with the number lost
+        #[default]
+        DropOldest,
+        /// Make `emit` wait until the subscriber catches up
+        Block,
+        /// Queue further events in a file until the subscriber catches up
+        SpillToDisk,
+    }
+
+    /// Queueing of one event's subscribers
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+    #[serde(default)]
+    pub struct ChannelOptions {
+        pub capacity: usize,
+        pub overflow: OverflowPolicy,
+    }
+
+    impl Default for ChannelOptions {
+        fn default() -> Self {
+            Self {
+                capacity: DEFAULT_CAPACITY,
+                overflow: OverflowPolicy::DropOldest,
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    #[serde(default)]
+    pub struct EventBusConfig {
+        /// Options of events not listed in `events`
+        pub default: ChannelOptions,
+        /// Options by event name
+        pub events: HashMap<String, ChannelOptions>,
+        /// Spill files and the durable outbox live here; each bus spills
+        /// into its own directory under `spill/`
+        pub data_dir: PathBuf,
+    }
+
+    impl Default for EventBusConfig {
+        fn default() -> Self {
+            Self {
+                default: ChannelOptions::default(),
+                events: HashMap::new(),
+                data_dir: PathBuf::from("data/events"),
+            }
+        }
+    }
+
+    impl EventBusConfig {
+        /// Set the options of one event
+        pub fn event(mut self, name: impl Into<String>, options: ChannelOptions) -> Self {
+            self.events.insert(name.into(), options);
+            self
+        }
+
+        fn options(&self, event: &str) -> ChannelOptions {
+            let mut options = self.events.get(event).copied().unwrap_or(self.default);
+            options.capacity = options.capacity.max(1);
+            options
+        }
+    }
+
+    /// Delivery counters of one event
+    #[derive(Debug, Clone, Default, Serialize)]
+    pub struct EventStats {
+        pub event: String,
+        pub subscribers: usize,
+        pub emitted: u64,
+        /// Events discarded from full queues (`drop_oldest`)
+        pub dropped: u64,
+        /// Events written to spill files (`spill_to_disk`)
+        pub spilled: u64,
+        /// Emits that had to wait for a full queue (`block`)
+        pub blocked: u64,
+        /// Events queued for the slowest subscriber, spilled ones included
+        pub max_lag: usize,
+        /// Outbox entries the slowest durable consumer hasn't acknowledged
+        pub durable_lag: u64,
+    }
+
+    // ----------------------------------------
+    // Bus
+    // ----------------------------------------
 
     pub struct EventBus {
-        channels: RwLock<HashMap<String, broadcast::Sender<serde_json::Value>>>,
+        config: EventBusConfig,
+        /// This bus's spill files, apart from those of other buses and
+        /// processes using the same `data_dir`
+        spill_dir: PathBuf,
+        channels: RwLock<HashMap<String, Arc<Channel>>>,
+        outbox: Outbox,
+        transport: Mutex<Option<crate::transport::Forwarder>>,
     }
 
     impl EventBus {
         pub fn new() -> Self {
+            Self::with_config(EventBusConfig::default())
+        }
+
+        pub fn with_config(config: EventBusConfig) -> Self {
+            // Two types claiming one name would decode each other's payloads
+            let mut names: HashMap<&str, &str> = HashMap::new();
+            for registration in registered() {
+                if let Some(other) = names.insert(registration.name, registration.type_name) {
+                    tracing::error!(
+                        "Event \"{}\" is declared by both {} and {}",
+                        registration.name,
+                        other,
+                        registration.type_name
+                    );
+                }
+            }
+
             Self {
+                spill_dir: config.data_dir.join("spill").join(uuid::Uuid::new_v4().to_string()),
+                outbox: Outbox::open(config.data_dir.join("outbox")),
                 channels: RwLock::new(HashMap::new()),
+                transport: Mutex::new(None),
+                config,
             }
         }
 
         pub async fn emit(&self, event: &str, data: serde_json::Value) {
-            let channels = self.channels.read().await;
-            if let Some(tx) = channels.get(event) {
-                let _ = tx.send(data);
+            let channel = self.channels.read().await.get(event).cloned();
+            if let Some(channel) = channel {
+                channel.counters.emitted.fetch_add(1, Ordering::Relaxed);
+                for queue in channel.live() {
+                    self.deliver(&channel, &queue, &data).await;
+                }
+            }
+            self.outbox.append(event, &data).await;
+            if let Some(forwarder) = self.transport.lock().unwrap().as_ref() {
+                forwarder.forward(event, &data);
             }
             tracing::debug!("Event emitted: {}", event);
         }
 
-        pub async fn subscribe(&self, event: &str) -> broadcast::Receiver<serde_json::Value> {
+        pub async fn subscribe(&self, event: &str) -> Receiver {
+            if !is_registered(event) {
+                tracing::warn!("Subscribing to undeclared event \"{}\"; is the name misspelled?", event);
+            }
+            self.channel(event).await
+        }
+
+        /// Emit `event` under its declared name
+        pub async fn emit_typed<E: Event>(&self, event: &E) {
+            match serde_json::to_value(event) {
+                Ok(data) => self.emit(E::NAME, data).await,
+                Err(e) => tracing::warn!("Failed to serialize event {}: {}", E::NAME, e),
+            }
+        }
+
+        /// Receive every `E` emitted from now on
+        pub async fn subscribe_typed<E: Event>(&self) -> TypedReceiver<E> {
+            TypedReceiver {
+                rx: self.channel(E::NAME).await,
+                _event: PhantomData,
+            }
+        }
+
+        /// Receive every `E` emitted since `consumer` first subscribed,
+        /// resuming after its last acknowledged event on the next run
+        ///
+        /// Delivery is at-least-once: events handled but not yet acknowledged
+        /// when the process stops are delivered again.
+        pub async fn subscribe_durable<E: Event>(&self, consumer: &str) -> io::Result<DurableReceiver<E>> {
+            let valid = !consumer.is_empty()
+                && consumer
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.');
+            if !valid || consumer.starts_with('.') {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("Invalid consumer name \"{}\"", consumer),
+                ));
+            }
+
+            let log = self.outbox.log(E::NAME).await?;
+            let acked = log.register(consumer).await?;
+            Ok(DurableReceiver {
+                updates: log.last_seq.subscribe(),
+                cursor: ReadCursor {
+                    generation: 0,
+                    reader: None,
+                    next_seq: acked + 1,
+                },
+                log,
+                consumer: consumer.to_string(),
+                _event: PhantomData,
+            })
+        }
+
+        /// Delivery counters of every event with subscribers, by name
+        pub async fn stats(&self) -> Vec<EventStats> {
+            let mut stats = BTreeMap::new();
+            for (event, channel) in self.channels.read().await.iter() {
+                let queues = channel.live();
+                let counters = &channel.counters;
+                stats.insert(
+                    event.clone(),
+                    EventStats {
+                        event: event.clone(),
+                        subscribers: queues.len(),
+                        emitted: counters.emitted.load(Ordering::Relaxed),
+                        dropped: counters.dropped.load(Ordering::Relaxed),
+                        spilled: counters.spilled.load(Ordering::Relaxed),
+                        blocked: counters.blocked.load(Ordering::Relaxed),
+                        max_lag: queues.iter().map(|q| q.depth()).max().unwrap_or(0),
+                        durable_lag: 0,
+                    },
+                );
+            }
+            for (event, log) in self.outbox.logs() {
+                stats
+                    .entry(event.clone())
+                    .or_insert_with(|| EventStats {
+                        event,
+                        ..Default::default()
+                    })
+                    .durable_lag = log.lag().await;
+            }
+            stats.into_values().collect()
+        }
+
+        /// Also publish every emitted event through `transport`, under
+        /// topics starting with `topic_prefix`; replaces the transport
+        /// attached before, which publishes what it has queued and stops
+        ///
+        /// Publishing runs in the background: `emit` never waits for the
+        /// broker, and events are dropped when too many are waiting.
+        pub fn attach_transport(&self, transport: Arc<dyn crate::transport::Transport>, topic_prefix: &str) {
+            let forwarder = crate::transport::Forwarder::start(transport, topic_prefix);
+            *self.transport.lock().unwrap() = Some(forwarder);
+        }
+
+        /// Stop publishing emitted events outside the process
+        pub fn detach_transport(&self) {
+            self.transport.lock().unwrap().take();
+        }
+
+        /// Publishing counters of the attached transport
+        pub fn transport_stats(&self) -> Option<crate::transport::TransportStats> {
+            self.transport.lock().unwrap().as_ref().map(|forwarder| forwarder.stats())
+        }
+
+        async fn channel(&self, event: &str) -> Receiver {
             let mut channels = self.channels.write().await;
-            let tx = channels
+            let channel = channels
                 .entry(event.to_string())
-                .or_insert_with(|| broadcast::channel(100).0);
-            tx.subscribe()
+                .or_insert_with(|| Arc::new(Channel::new(self.config.options(event))));
+
+            let queue = Arc::new(Queue::new(channel.options));
+            channel.subscribers.lock().unwrap().push(Arc::downgrade(&queue));
+            Receiver { queue }
+        }
+
+        async fn deliver(&self, channel: &Channel, queue: &Queue, data: &serde_json::Value) {
+            let mut waited = false;
+            loop {
+                // Registered before the check so a wakeup in between isn't lost
+                let writable = queue.writable.notified();
+                match queue.try_push(data, &channel.counters) {
+                    Push::Queued => return,
+                    Push::Spill => return queue.spill(data, &channel.counters, &self.spill_dir).await,
+                    Push::Full => {}
+                }
+                if !waited {
+                    channel.counters.blocked.fetch_add(1, Ordering::Relaxed);
+                    waited = true;
+                }
+                writable.await;
+            }
         }
     }
-}
 
-// ============================================
-// Utilities Module
-// ============================================
+    impl Default for EventBus {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
 
-pub mod utils {
-    /// Convert plain text URLs to clickable links
-    pub fn auto_link_urls(text: &str) -> String {
-        let url_regex = regex::Regex::new(
-            r"(https?://[^\s<>\[\]]+)"
-        ).unwrap();
+    impl Drop for EventBus {
+        fn drop(&mut self) {
+            // Spill files are removed with their queues; only empty if none outlive the bus
+            let _ = fs::remove_dir(&self.spill_dir);
+        }
+    }
 
-        url_regex.replace_all(text, r#"<a href="$1">$1</a>"#).to_string()
+    #[derive(Default)]
+    struct Counters {
+        emitted: AtomicU64,
+        dropped: AtomicU64,
+        spilled: AtomicU64,
+        blocked: AtomicU64,
     }
 
-    /// Convert straight quotes to smart quotes
-    pub fn smart_quotes(text: &str) -> String {
-        text.replace("\"", """)
-            .replace("'", "'")
+    /// Subscribers of one event
+    struct Channel {
+        options: ChannelOptions,
+        subscribers: Mutex<Vec<Weak<Queue>>>,
+        counters: Counters,
     }
 
-    /// Strip HTML tags from text
-    pub fn strip_html(html: &str) -> String {
-        let re = regex::Regex::new(r"<[^>]+>").unwrap();
-        re.replace_all(html, "").to_string()
+    impl Channel {
+        fn new(options: ChannelOptions) -> Self {
+            Self {
+                options,
+                subscribers: Mutex::new(Vec::new()),
+                counters: Counters::default(),
+            }
+        }
+
+        /// Queues of subscribers that haven't dropped their receiver
+        fn live(&self) -> Vec<Arc<Queue>> {
+            let mut subscribers = self.subscribers.lock().unwrap();
+            subscribers.retain(|queue| queue.strong_count() > 0);
+            subscribers.iter().filter_map(Weak::upgrade).collect()
+        }
     }
 
-    /// WordPress-style auto paragraphs
-    pub fn wpautop(text: &str) -> String {
-        let paragraphs: Vec<&str> = text.split("\n\n").collect();
-        paragraphs.iter()
-            .map(|p| format!("<p>{}</p>", p.trim()))
-            .collect::<Vec<_>>()
-            .join("\n")
+    // ----------------------------------------
+    // Subscriber queues
+    // ----------------------------------------
+
+    struct Queue {
+        options: ChannelOptions,
+        state: Mutex<QueueState>,
+        /// Overflow file; its IO runs outside `state`
+        spill: tokio::sync::Mutex<Option<Spill>>,
+        readable: Notify,
+        writable: Notify,
+    }
+
+    #[derive(Default)]
+    struct QueueState {
+        items: VecDeque<serde_json::Value>,
+        /// Events dropped since the subscriber last heard about it
+        lagged: u64,
+        /// Events in the spill file that weren't read back yet
+        spilled: usize,
+        /// The receiver is gone; blocked emitters give up
+        closed: bool,
+    }
+
+    /// What became of an event offered to a queue
+    enum Push {
+        Queued,
+        /// The queue is full and the policy is `Block`
+        Full,
+        /// The event goes to the spill file (`spill_to_disk`)
+        Spill,
+    }
+
+    impl Queue {
+        fn new(options: ChannelOptions) -> Self {
+            Self {
+                options,
+                state: Mutex::new(QueueState::default()),
+                spill: tokio::sync::Mutex::new(None),
+                readable: Notify::new(),
+                writable: Notify::new(),
+            }
+        }
+
+        fn try_push(&self, data: &serde_json::Value, counters: &Counters) -> Push {
+            let mut state = self.state.lock().unwrap();
+            if state.closed {
+                return Push::Queued;
+            }
+
+            // Once spilling, later events go to the file too so order holds
+            if state.items.len() < self.options.capacity && state.spilled == 0 {
+                state.items.push_back(data.clone());
+            } else {
+                match self.options.overflow {
+                    OverflowPolicy::Block => return Push::Full,
+                    OverflowPolicy::DropOldest => Self::drop_oldest(&mut state, data, counters),
+                    OverflowPolicy::SpillToDisk => return Push::Spill,
+                }
+            }
+
+            drop(state);
+            self.readable.notify_one();
+            Push::Queued
+        }
+
+        fn drop_oldest(state: &mut QueueState, data: &serde_json::Value, counters: &Counters) {
+            state.items.pop_front();
+            state.items.push_back(data.clone());
+            state.lagged += 1;
+            counters.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+
+        /// Append `data` to the spill file, created in `dir` on first use
+        async fn spill(&self, data: &serde_json::Value, counters: &Counters, dir: &Path) {
+            let mut spill = self.spill.lock().await;
+            let written = match spill.as_mut() {
+                Some(file) => file.push(data).await,
+                None => match Spill::create(dir).await {
+                    Ok(file) => spill.insert(file).push(data).await,
+                    Err(e) => Err(e),
+                },
+            };
+
+            let mut state = self.state.lock().unwrap();
+            if state.closed {
+                return;
+            }
+            match written {
+                Ok(()) => {
+                    state.spilled += 1;
+                    counters.spilled.fetch_add(1, Ordering::Relaxed);
+                }
+                Err(e) => {
+                    tracing::error!("Failed to spill event, dropping the oldest instead: {}", e);
+                    Self::drop_oldest(&mut state, data, counters);
+                }
+            }
+            drop(state);
+            self.readable.notify_one();
+        }
+
+        /// Read spilled events back into the queue, up to its capacity
+        async fn unspill(&self) {
+            let mut spill = self.spill.lock().await;
+            let Some(file) = spill.as_mut() else {
+                return;
+            };
+            let count = {
+                let state = self.state.lock().unwrap();
+                state.spilled.min(self.options.capacity.saturating_sub(state.items.len()))
+            };
+            let read = file.read(count).await;
+
+            let caught_up = {
+                let mut state = self.state.lock().unwrap();
+                match read {
+                    Ok(items) => {
+                        state.spilled -= count;
+                        state.items.extend(items);
+                    }
+                    Err(e) => {
+                        tracing::error!("Failed to read spilled events: {}", e);
+                        state.lagged += std::mem::take(&mut state.spilled) as u64;
+                    }
+                }
+                state.spilled == 0
+            };
+
+            // Caught up; start the file over instead of growing it
+            if caught_up {
+                if let Err(e) = file.reset().await {
+                    tracing::error!("Failed to truncate event spill file: {}", e);
+                    spill.take();
+                }
+            }
+        }
+
+        fn pop(&self) -> Option<Result<serde_json::Value, RecvError>> {
+            let mut state = self.state.lock().unwrap();
+            if state.lagged > 0 {
+                return Some(Err(RecvError::Lagged(std::mem::take(&mut state.lagged))));
+            }
+
+            let data = state.items.pop_front()?;
+            drop(state);
+            self.writable.notify_one();
+            Some(Ok(data))
+        }
+
+        fn has_spilled(&self) -> bool {
+            self.state.lock().unwrap().spilled > 0
+        }
+
+        fn depth(&self) -> usize {
+            let state = self.state.lock().unwrap();
+            state.items.len() + state.spilled
+        }
+    }
+
+    /// Overflow of one subscriber's queue, oldest first
+    struct Spill {
+        path: PathBuf,
+        writer: tokio::fs::File,
+        reader: tokio::io::BufReader<tokio::fs::File>,
+    }
+
+    impl Spill {
+        async fn create(dir: &Path) -> io::Result<Self> {
+            tokio::fs::create_dir_all(dir).await?;
+            let path = dir.join(format!("{}.jsonl", uuid::Uuid::new_v4()));
+            let writer = tokio::fs::OpenOptions::new().create(true).append(true).open(&path).await?;
+            let reader = tokio::io::BufReader::new(tokio::fs::File::open(&path).await?);
+            Ok(Self { path, writer, reader })
+        }
+
+        async fn push(&mut self, data: &serde_json::Value) -> io::Result<()> {
+            let mut line = serde_json::to_vec(data)?;
+            line.push(b'\n');
+            self.writer.write_all(&line).await?;
+            self.writer.flush().await
+        }
+
+        /// The next `count` spilled events; unreadable ones are skipped
+        async fn read(&mut self, count: usize) -> io::Result<Vec<serde_json::Value>> {
+            let mut items = Vec::with_capacity(count);
+            let mut line = String::new();
+            for _ in 0..count {
+                line.clear();
+                if self.reader.read_line(&mut line).await? == 0 {
+                    return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "spill file ended early"));
+                }
+                match serde_json::from_str(&line) {
+                    Ok(data) => items.push(data),
+                    Err(e) => tracing::warn!("Skipping unreadable spilled event: {}", e),
+                }
+            }
+            Ok(items)
+        }
+
+        async fn reset(&mut self) -> io::Result<()> {
+            self.writer.set_len(0).await?;
+            self.reader.seek(SeekFrom::Start(0)).await?;
+            Ok(())
+        }
+    }
+
+    impl Drop for Spill {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+
+    /// Receives one event's payloads as JSON
+    pub struct Receiver {
+        queue: Arc<Queue>,
+    }
+
+    impl Receiver {
+        /// The next payload; after events were dropped (`drop_oldest`) this
+        /// returns `RecvError::Lagged` once with their number
+        pub async fn recv(&mut self) -> Result<serde_json::Value, RecvError> {
+            loop {
+                let readable = self.queue.readable.notified();
+                if let Some(result) = self.queue.pop() {
+                    return result;
+                }
+                if self.queue.has_spilled() {
+                    self.queue.unspill().await;
+                    continue;
+                }
+                readable.await;
+            }
+        }
+    }
+
+    impl Drop for Receiver {
+        fn drop(&mut self) {
+            let mut state = self.queue.state.lock().unwrap();
+            state.closed = true;
+            state.items.clear();
+            state.spilled = 0;
+            drop(state);
+            // An emitter busy spilling leaves the file to the queue's drop
+            if let Ok(mut spill) = self.queue.spill.try_lock() {
+                spill.take();
+            }
+            self.queue.writable.notify_waiters();
+        }
+    }
+
+    /// Receives the payloads of one event type
+    pub struct TypedReceiver<E> {
+        rx: Receiver,
+        _event: PhantomData<fn() -> E>,
+    }
+
+    impl<E: Event> TypedReceiver<E> {
+        /// The next event; payloads that don't match `E` (from an untyped
+        /// `emit`) are logged and skipped
+        pub async fn recv(&mut self) -> Result<E, RecvError> {
+            loop {
+                let data = self.rx.recv().await?;
+                match serde_json::from_value(data) {
+                    Ok(event) => return Ok(event),
+                    Err(e) => tracing::warn!("Skipping malformed {} event: {}", E::NAME, e),
+                }
+            }
+        }
+    }
+
+    // ----------------------------------------
+    // Durable outbox
+    // ----------------------------------------
+
+    /// One line of an outbox log
+    #[derive(Serialize, Deserialize)]
+    struct Entry<D = serde_json::Value> {
+        seq: u64,
+        data: D,
+    }
+
+    /// Outbox logs of events with durable consumers, at
+    /// `<dir>/<event>/log.jsonl` with cursors in `<dir>/<event>/consumers/`
+    struct Outbox {
+        dir: PathBuf,
+        logs: Mutex<HashMap<String, Arc<OutboxLog>>>,
+    }
+
+    impl Outbox {
+        fn open(dir: PathBuf) -> Self {
+            let mut logs = HashMap::new();
+            for entry in fs::read_dir(&dir).into_iter().flatten().flatten() {
+                let Some(event) = entry.file_name().to_str().map(String::from) else {
+                    continue;
+                };
+                match OutboxLog::open(&dir, &event) {
+                    Ok(log) => {
+                        logs.insert(event, Arc::new(log));
+                    }
+                    Err(e) => tracing::error!("Failed to open outbox of event {}: {}", event, e),
+                }
+            }
+            Self {
+                dir,
+                logs: Mutex::new(logs),
+            }
+        }
+
+        async fn log(&self, event: &str) -> io::Result<Arc<OutboxLog>> {
+            if let Some(log) = self.logs.lock().unwrap().get(event) {
+                return Ok(log.clone());
+            }
+
+            // Opening reads the whole log, so it runs off the async threads
+            let (dir, name) = (self.dir.clone(), event.to_string());
+            let log = tokio::task::spawn_blocking(move || OutboxLog::open(&dir, &name))
+                .await
+                .map_err(io::Error::other)??;
+            let mut logs = self.logs.lock().unwrap();
+            Ok(logs.entry(event.to_string()).or_insert_with(|| Arc::new(log)).clone())
+        }
+
+        fn logs(&self) -> Vec<(String, Arc<OutboxLog>)> {
+            let logs = self.logs.lock().unwrap();
+            logs.iter().map(|(event, log)| (event.clone(), log.clone())).collect()
+        }
+
+        /// Record `data` if `event` has durable consumers
+        async fn append(&self, event: &str, data: &serde_json::Value) {
+            let log = self.logs.lock().unwrap().get(event).cloned();
+            if let Some(log) = log {
+                if let Err(e) = log.append(data).await {
+                    tracing::error!("Failed to append event {} to the outbox: {}", event, e);
+                }
+            }
+        }
+    }
+
+    struct OutboxLog {
+        dir: PathBuf,
+        /// Held across writes so entries keep their order
+        state: tokio::sync::Mutex<LogState>,
+        /// Sequence number of the newest entry, for waiting receivers
+        last_seq: watch::Sender<u64>,
+    }
+
+    struct LogState {
+        file: tokio::fs::File,
+        next_seq: u64,
+        /// Oldest sequence number still in the file
+        first_seq: u64,
+        /// Bumped whenever compaction rewrites the file
+        generation: u64,
+        /// Last acknowledged sequence number by consumer
+        cursors: HashMap<String, u64>,
+    }
+
+    /// Where a durable receiver is in its log
+    struct ReadCursor {
+        generation: u64,
+        reader: Option<tokio::io::BufReader<tokio::fs::File>>,
+        next_seq: u64,
+    }
+
+    impl OutboxLog {
+        fn open(root: &Path, event: &str) -> io::Result<Self> {
+            let dir = root.join(event);
+            fs::create_dir_all(dir.join(CONSUMERS_DIR))?;
+            let path = dir.join(LOG_FILE);
+
+            let mut first = None;
+            let mut last = 0;
+            let mut torn = false;
+            if let Ok(file) = File::open(&path) {
+                for line in BufReader::new(file).split(b'\n') {
+                    let line = line?;
+                    match serde_json::from_slice::<Entry>(&line) {
+                        Ok(entry) => {
+                            first.get_or_insert(entry.seq);
+                            last = entry.seq;
+                            torn = false;
+                        }
+                        // A crash mid-append leaves a partial last line
+                        Err(_) => torn = true,
+                    }
+                }
+            }
+
+            let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+            if torn {
+                file.write_all(b"\n")?;
+            }
+
+            let mut cursors = HashMap::new();
+            for entry in fs::read_dir(dir.join(CONSUMERS_DIR))?.flatten() {
+                let consumer = entry.file_name().to_string_lossy().into_owned();
+                if let Ok(cursor) = fs::read_to_string(entry.path())?.trim().parse::<u64>() {
+                    cursors.insert(consumer, cursor);
+                }
+            }
+
+            Ok(Self {
+                dir,
+                state: tokio::sync::Mutex::new(LogState {
+                    file: tokio::fs::File::from_std(file),
+                    next_seq: last + 1,
+                    first_seq: first.unwrap_or(last + 1),
+                    generation: 0,
+                    cursors,
+                }),
+                last_seq: watch::channel(last).0,
+            })
+        }
+
+        async fn append(&self, data: &serde_json::Value) -> io::Result<()> {
+            let mut state = self.state.lock().await;
+            let seq = state.next_seq;
+            let mut line = serde_json::to_vec(&Entry { seq, data })?;
+            line.push(b'\n');
+            state.file.write_all(&line).await?;
+            // On disk before receivers are told about it
+            state.file.flush().await?;
+            state.next_seq += 1;
+            drop(state);
+            self.last_seq.send_replace(seq);
+            Ok(())
+        }
+
+        /// Last sequence number `consumer` acknowledged; new consumers start
+        /// after the newest entry
+        async fn register(&self, consumer: &str) -> io::Result<u64> {
+            let mut state = self.state.lock().await;
+            if let Some(&cursor) = state.cursors.get(consumer) {
+                return Ok(cursor);
+            }
+            let cursor = state.next_seq - 1;
+            self.write_cursor(consumer, cursor).await?;
+            state.cursors.insert(consumer.to_string(), cursor);
+            Ok(cursor)
+        }
+
+        async fn ack(&self, consumer: &str, seq: u64) -> io::Result<()> {
+            let mut state = self.state.lock().await;
+            let cursor = state.cursors.entry(consumer.to_string()).or_default();
+            if seq <= *cursor {
+                return Ok(());
+            }
+            *cursor = seq;
+            self.write_cursor(consumer, seq).await?;
+
+            let acked = state.cursors.values().copied().min().unwrap_or(seq);
+            if (acked + 1).saturating_sub(state.first_seq) >= COMPACT_AFTER {
+                self.compact(&mut state, acked).await?;
+            }
+            Ok(())
+        }
+
+        /// Entries the slowest consumer hasn't acknowledged
+        async fn lag(&self) -> u64 {
+            let state = self.state.lock().await;
+            let acked = state.cursors.values().copied().min().unwrap_or(state.next_seq - 1);
+            (state.next_seq - 1).saturating_sub(acked)
+        }
+
+        async fn write_cursor(&self, consumer: &str, seq: u64) -> io::Result<()> {
+            let path = self.dir.join(CONSUMERS_DIR).join(consumer);
+            let tmp = path.with_extension("tmp");
+            tokio::fs::write(&tmp, seq.to_string()).await?;
+            tokio::fs::rename(tmp, path).await
+        }
+
+        /// Rewrite the log without entries every consumer acknowledged
+        async fn compact(&self, state: &mut LogState, acked: u64) -> io::Result<()> {
+            let dir = self.dir.clone();
+            let file = tokio::task::spawn_blocking(move || rewrite_log(&dir, acked))
+                .await
+                .map_err(io::Error::other)??;
+
+            state.file = tokio::fs::File::from_std(file);
+            state.first_seq = acked + 1;
+            state.generation += 1;
+            Ok(())
+        }
+
+        /// The entry after `cursor`, if one was appended
+        async fn read_next(&self, cursor: &mut ReadCursor) -> io::Result<Option<Entry>> {
+            // Holding the lock keeps appends and compaction out while reading
+            let state = self.state.lock().await;
+            if cursor.generation != state.generation {
+                cursor.reader = None;
+                cursor.generation = state.generation;
+            }
+            let reader = match &mut cursor.reader {
+                Some(reader) => reader,
+                None => {
+                    let file = tokio::fs::File::open(self.dir.join(LOG_FILE)).await?;
+                    cursor.reader.insert(tokio::io::BufReader::new(file))
+                }
+            };
+
+            let mut line = Vec::new();
+            loop {
+                line.clear();
+                if reader.read_until(b'\n', &mut line).await? == 0 {
+                    return Ok(None);
+                }
+                match serde_json::from_slice::<Entry>(&line) {
+                    Ok(entry) if entry.seq >= cursor.next_seq => {
+                        cursor.next_seq = entry.seq + 1;
+                        return Ok(Some(entry));
+                    }
+                    Ok(_) => {}
+                    Err(_) if line.iter().all(u8::is_ascii_whitespace) => {}
+                    Err(e) => tracing::warn!("Skipping unreadable outbox entry: {}", e),
+                }
+            }
+        }
+    }
+
+    /// Rewrite the log in `dir` without the entries up to `acked`, and open
+    /// the result for appending
+    fn rewrite_log(dir: &Path, acked: u64) -> io::Result<File> {
+        let path = dir.join(LOG_FILE);
+        let tmp = dir.join("log.jsonl.tmp");
+        {
+            let mut out = File::create(&tmp)?;
+            for line in BufReader::new(File::open(&path)?).split(b'\n') {
+                let line = line?;
+                if matches!(serde_json::from_slice::<Entry>(&line), Ok(entry) if entry.seq > acked) {
+                    out.write_all(&line)?;
+                    out.write_all(b"\n")?;
+                }
+            }
+            out.sync_all()?;
+        }
+        fs::rename(&tmp, &path)?;
+        OpenOptions::new().append(true).open(&path)
+    }
+
+    /// An event from the outbox; `ack` its `seq` once handled
+    #[derive(Debug, Clone)]
+    pub struct Delivery<E> {
+        pub seq: u64,
+        pub event: E,
+    }
+
+    /// Receives one event type from its outbox log
+    pub struct DurableReceiver<E> {
+        log: Arc<OutboxLog>,
+        consumer: String,
+        cursor: ReadCursor,
+        updates: watch::Receiver<u64>,
+        _event: PhantomData<fn() -> E>,
+    }
+
+    impl<E: Event> DurableReceiver<E> {
+        /// The next event after the last one received, waiting for it if
+        /// needed; entries that don't decode as `E` are logged and skipped
+        pub async fn recv(&mut self) -> io::Result<Delivery<E>> {
+            loop {
+                self.updates.borrow_and_update();
+                while let Some(entry) = self.log.read_next(&mut self.cursor).await? {
+                    match serde_json::from_value(entry.data) {
+                        Ok(event) => return Ok(Delivery { seq: entry.seq, event }),
+                        Err(e) => tracing::warn!("Skipping malformed {} event: {}", E::NAME, e),
+                    }
+                }
+                // The sender lives in the log this receiver holds
+                let _ = self.updates.changed().await;
+            }
+        }
+
+        /// Acknowledge every event up to `seq`; they won't be delivered again
+        pub async fn ack(&self, seq: u64) -> io::Result<()> {
+            self.log.ack(&self.consumer, seq).await
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use serde_json::json;
+        use std::time::Duration;
+
+        /// Two-event queues for `tick` in a fresh data directory
+        fn config(overflow: OverflowPolicy) -> EventBusConfig {
+            EventBusConfig {
+                data_dir: std::env::temp_dir().join(format!("rustpress-events-{}", uuid::Uuid::new_v4())),
+                ..Default::default()
+            }
+            .event("tick", ChannelOptions { capacity: 2, overflow })
+        }
+
+        async fn stats_of(bus: &EventBus, event: &str) -> EventStats {
+            bus.stats().await.into_iter().find(|s| s.event == event).unwrap()
+        }
+
+        fn files_in(dir: &Path) -> usize {
+            fs::read_dir(dir).map_or(0, |entries| entries.count())
+        }
+
+        #[tokio::test]
+        async fn test_drop_oldest_reports_lag() {
+            let config = config(OverflowPolicy::DropOldest);
+            let bus = EventBus::with_config(config.clone());
+            let mut rx = bus.subscribe("tick").await;
+            for i in 0..5 {
+                bus.emit("tick", json!(i)).await;
+            }
+
+            assert!(matches!(rx.recv().await, Err(RecvError::Lagged(3))));
+            assert_eq!(rx.recv().await.unwrap(), json!(3));
+            assert_eq!(rx.recv().await.unwrap(), json!(4));
+            let stats = stats_of(&bus, "tick").await;
+            assert_eq!((stats.emitted, stats.dropped, stats.max_lag), (5, 3, 0));
+            let _ = fs::remove_dir_all(&config.data_dir);
+        }
+
+        #[tokio::test]
+        async fn test_block_waits_for_subscriber() {
+            let config = config(OverflowPolicy::Block);
+            let bus = Arc::new(EventBus::with_config(config.clone()));
+            let mut rx = bus.subscribe("tick").await;
+            bus.emit("tick", json!(0)).await;
+            bus.emit("tick", json!(1)).await;
+
+            let emitter = tokio::spawn({
+                let bus = bus.clone();
+                async move { bus.emit("tick", json!(2)).await }
+            });
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            assert!(!emitter.is_finished());
+
+            assert_eq!(rx.recv().await.unwrap(), json!(0));
+            emitter.await.unwrap();
+            assert_eq!(rx.recv().await.unwrap(), json!(1));
+            assert_eq!(rx.recv().await.unwrap(), json!(2));
+            assert_eq!(stats_of(&bus, "tick").await.blocked, 1);
+
+            // Emitters waiting on a subscriber that goes away give up
+            bus.emit("tick", json!(3)).await;
+            bus.emit("tick", json!(4)).await;
+            let emitter = tokio::spawn({
+                let bus = bus.clone();
+                async move { bus.emit("tick", json!(5)).await }
+            });
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            drop(rx);
+            tokio::time::timeout(Duration::from_secs(1), emitter).await.unwrap().unwrap();
+            let _ = fs::remove_dir_all(&config.data_dir);
+        }
+
+        #[tokio::test]
+        async fn test_spill_replays_in_order() {
+            let config = config(OverflowPolicy::SpillToDisk);
+            let bus = EventBus::with_config(config.clone());
+            let mut rx = bus.subscribe("tick").await;
+            for i in 0..10 {
+                bus.emit("tick", json!(i)).await;
+            }
+
+            let stats = stats_of(&bus, "tick").await;
+            assert_eq!((stats.spilled, stats.dropped, stats.max_lag), (8, 0, 10));
+            assert_eq!(files_in(&bus.spill_dir), 1);
+            for i in 0..10 {
+                assert_eq!(rx.recv().await.unwrap(), json!(i));
+            }
+            assert_eq!(stats_of(&bus, "tick").await.max_lag, 0);
+
+            // Emits while catching up stay behind the spilled events
+            for i in 10..15 {
+                bus.emit("tick", json!(i)).await;
+            }
+            assert_eq!(rx.recv().await.unwrap(), json!(10));
+            for i in 15..20 {
+                bus.emit("tick", json!(i)).await;
+            }
+            for i in 11..20 {
+                assert_eq!(rx.recv().await.unwrap(), json!(i));
+            }
+
+            drop(rx);
+            assert_eq!(files_in(&bus.spill_dir), 0);
+            drop(bus);
+            assert_eq!(files_in(&config.data_dir.join("spill")), 0);
+            let _ = fs::remove_dir_all(&config.data_dir);
+        }
+
+        #[tokio::test]
+        async fn test_buses_keep_their_own_spill_files() {
+            let config = config(OverflowPolicy::SpillToDisk);
+            let bus = EventBus::with_config(config.clone());
+            let mut rx = bus.subscribe("tick").await;
+            for i in 0..5 {
+                bus.emit("tick", json!(i)).await;
+            }
+
+            // Another process starting on the same data directory
+            drop(EventBus::with_config(config.clone()));
+
+            for i in 0..5 {
+                assert_eq!(rx.recv().await.unwrap(), json!(i));
+            }
+            let _ = fs::remove_dir_all(&config.data_dir);
+        }
+
+        #[tokio::test]
+        async fn test_outbox_resumes_after_restart() {
+            let config = config(OverflowPolicy::DropOldest);
+            {
+                let bus = EventBus::with_config(config.clone());
+                // Emitted before the consumer's first subscription
+                bus.emit_typed(&PostPublished { post_id: 0 }).await;
+                let mut rx = bus.subscribe_durable::<PostPublished>("indexer").await.unwrap();
+                for post_id in 1..=3 {
+                    bus.emit_typed(&PostPublished { post_id }).await;
+                }
+
+                let first = rx.recv().await.unwrap();
+                assert_eq!(first.event.post_id, 1);
+                rx.ack(first.seq).await.unwrap();
+                // Handled but never acknowledged
+                assert_eq!(rx.recv().await.unwrap().event.post_id, 2);
+                assert_eq!(stats_of(&bus, "post_published").await.durable_lag, 2);
+            }
+
+            let bus = EventBus::with_config(config.clone());
+            let mut rx = bus.subscribe_durable::<PostPublished>("indexer").await.unwrap();
+            assert_eq!(rx.recv().await.unwrap().event.post_id, 2);
+            let last = rx.recv().await.unwrap();
+            assert_eq!(last.event.post_id, 3);
+            rx.ack(last.seq).await.unwrap();
+            assert_eq!(stats_of(&bus, "post_published").await.durable_lag, 0);
+
+            // Waits for the next emit
+            let next = tokio::spawn(async move { rx.recv().await.unwrap().event.post_id });
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            bus.emit_typed(&PostPublished { post_id: 4 }).await;
+            assert_eq!(next.await.unwrap(), 4);
+
+            assert!(bus.subscribe_durable::<PostPublished>("../indexer").await.is_err());
+            let _ = fs::remove_dir_all(&config.data_dir);
+        }
+
+        #[tokio::test]
+        async fn test_outbox_compaction_keeps_unacknowledged() {
+            let config = config(OverflowPolicy::DropOldest);
+            let log_path = config.data_dir.join("outbox").join("post_published").join(LOG_FILE);
+            {
+                let bus = EventBus::with_config(config.clone());
+                let mut rx = bus.subscribe_durable::<PostPublished>("indexer").await.unwrap();
+                let total = COMPACT_AFTER as i64 + 5;
+                for post_id in 1..=total {
+                    bus.emit_typed(&PostPublished { post_id }).await;
+                }
+                for _ in 0..COMPACT_AFTER {
+                    let delivery = rx.recv().await.unwrap();
+                    rx.ack(delivery.seq).await.unwrap();
+                }
+                // The reader follows the rewritten log
+                assert_eq!(rx.recv().await.unwrap().event.post_id, COMPACT_AFTER as i64 + 1);
+            }
+            assert_eq!(fs::read_to_string(&log_path).unwrap().lines().count(), 5);
+
+            let bus = EventBus::with_config(config.clone());
+            let mut rx = bus.subscribe_durable::<PostPublished>("indexer").await.unwrap();
+            assert_eq!(rx.recv().await.unwrap().event.post_id, COMPACT_AFTER as i64 + 1);
+            let _ = fs::remove_dir_all(&config.data_dir);
+        }
+    }
+}
+
+// ============================================
+// Event Transport Module
+// ============================================
+
+pub mod transport {
+    //! Event bus transports
+    //!
+    //! A transport publishes what `EVENT_BUS` emits to a broker so other
+    //! services can consume it: NATS, or Kafka through a Kafka REST Proxy
+    //! (v2 API). Other brokers plug in by implementing [`Transport`] and
+    //! passing it to [`EventBus::attach_transport`](crate::events::EventBus::attach_transport).
+    //! Publishing runs on a background task behind a bounded queue, so a slow
+    //! or unreachable broker drops events (counted in [`TransportStats`])
+    //! rather than slowing `emit` down; in-process delivery is unaffected.
+    //!
+    //! Each event has its own topic (NATS subject or Kafka topic),
+    //! `<prefix>.<event>.v<version>`, e.g. `rustpress.post_saved.v1`. The
+    //! version is the event type's `#[event(version = N)]`; a breaking payload
+    //! change bumps it, so consumers of the old payload keep reading the old
+    //! topic. Messages are JSON [`Envelope`]s, whose own layout is versioned by
+    //! `schema`. Kafka messages are keyed by event name, keeping each event
+    //! in order.
+    //!
+    //! Plugins running in other processes read events with
+    //! [`RemoteConsumer`].
+
+    use super::*;
+    use crate::events::{self, Event};
+    use chrono::{DateTime, Utc};
+    use futures_util::future::BoxFuture;
+    use futures_util::StreamExt;
+    use serde::{Deserialize, Serialize};
+    use std::collections::{BTreeMap, VecDeque};
+    use std::fmt;
+    use std::marker::PhantomData;
+    use std::sync::Mutex;
+    use std::time::Duration;
+    use tokio::sync::mpsc;
+    use uuid::Uuid;
+
+    /// Layout version of [`Envelope`]; consumers skip envelopes newer than
+    /// the one they were built with
+    pub const ENVELOPE_SCHEMA: u32 = 1;
+
+    /// Start of topic names unless `event_topic_prefix` says otherwise
+    pub const DEFAULT_TOPIC_PREFIX: &str = "rustpress";
+
+    /// Events waiting to be published before further ones are dropped
+    const QUEUE_SIZE: usize = 10_000;
+
+    /// Most events published at once
+    const MAX_BATCH: usize = 100;
+
+    /// Attempts at publishing a batch before its events count as failed
+    const MAX_ATTEMPTS: u32 = 3;
+
+    /// Delay before retrying a failed publish, doubled on every attempt
+    const RETRY_DELAY: Duration = Duration::from_millis(500);
+
+    const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// How long a Kafka REST Proxy poll waits for records
+    const POLL_TIMEOUT_MS: u64 = 1000;
+
+    const KAFKA_V2: &str = "application/vnd.kafka.v2+json";
+    const KAFKA_JSON: &str = "application/vnd.kafka.json.v2+json";
+
+    #[derive(Debug)]
+    pub enum TransportError {
+        /// The transport settings are unusable
+        Config(String),
+        /// The broker could not be reached
+        Connect(String),
+        /// The broker refused or lost a message
+        Publish(String),
+        /// Reading from the broker failed
+        Receive(String),
+        /// The subscription ended
+        Closed,
+    }
+
+    impl fmt::Display for TransportError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                Self::Config(e) => write!(f, "invalid event transport: {}", e),
+                Self::Connect(e) => write!(f, "failed to connect to the broker: {}", e),
+                Self::Publish(e) => write!(f, "failed to publish events: {}", e),
+                Self::Receive(e) => write!(f, "failed to receive events: {}", e),
+                Self::Closed => write!(f, "subscription closed"),
+            }
+        }
+    }
+
+    impl std::error::Error for TransportError {}
+
+    /// An event as published to a broker
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct Envelope<D = serde_json::Value> {
+        /// Layout version of the envelope, [`ENVELOPE_SCHEMA`]
+        pub schema: u32,
+        pub id: Uuid,
+        pub event: String,
+        /// Payload version of the event type
+        pub version: u32,
+        /// Instance that emitted it
+        pub source: Uuid,
+        pub emitted_at: DateTime<Utc>,
+        pub data: D,
+    }
+
+    /// An envelope bound for its topic
+    #[derive(Debug, Clone)]
+    pub struct Message {
+        pub topic: String,
+        /// Partitioning key: the event name
+        pub key: String,
+        pub envelope: Envelope,
+    }
+
+    /// Publishes messages to a broker
+    pub trait Transport: Send + Sync {
+        /// Broker kind, for stats and logs
+        fn name(&self) -> &str;
+
+        /// Publish a batch, each message to its topic; on error the whole
+        /// batch is retried
+        fn publish<'a>(&'a self, batch: &'a [Message]) -> BoxFuture<'a, Result<(), TransportError>>;
+    }
+
+    /// Where events are published
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub enum Broker {
+        /// A NATS server, e.g. `nats://localhost:4222`
+        Nats { url: String },
+        /// A Kafka REST Proxy, e.g. `http://kafka-rest:8082`
+        Kafka { rest_url: String },
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct TransportConfig {
+        pub broker: Broker,
+        pub topic_prefix: String,
+    }
+
+    impl TransportConfig {
+        pub fn nats(url: impl Into<String>) -> Self {
+            Self {
+                broker: Broker::Nats { url: url.into() },
+                topic_prefix: DEFAULT_TOPIC_PREFIX.to_string(),
+            }
+        }
+
+        pub fn kafka(rest_url: impl Into<String>) -> Self {
+            Self {
+                broker: Broker::Kafka {
+                    rest_url: rest_url.into().trim_end_matches('/').to_string(),
+                },
+                topic_prefix: DEFAULT_TOPIC_PREFIX.to_string(),
+            }
+        }
+
+        /// `kind` is `nats` or `kafka`
+        pub fn parse(kind: &str, url: &str) -> Result<Self, TransportError> {
+            match kind {
+                "nats" => Ok(Self::nats(url)),
+                "kafka" => Ok(Self::kafka(url)),
+                other => Err(TransportError::Config(format!(
+                    "unknown transport \"{}\" (expected nats or kafka)",
+                    other
+                ))),
+            }
+        }
+
+        pub fn topic_prefix(mut self, prefix: impl Into<String>) -> Self {
+            self.topic_prefix = prefix.into();
+            self
+        }
+
+        /// Topic of `event` at payload `version`
+        pub fn topic(&self, event: &str, version: u32) -> String {
+            topic(&self.topic_prefix, event, version)
+        }
+
+        pub fn validate(&self) -> Result<(), TransportError> {
+            let prefix = &self.topic_prefix;
+            let valid = !prefix.is_empty()
+                && !prefix.starts_with('.')
+                && !prefix.ends_with('.')
+                && prefix
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.');
+            if !valid {
+                return Err(TransportError::Config(format!(
+                    "invalid topic prefix \"{}\" (use letters, digits, '_', '-' and '.')",
+                    prefix
+                )));
+            }
+
+            let url = match &self.broker {
+                Broker::Nats { url } => url,
+                Broker::Kafka { rest_url } => {
+                    if !rest_url.starts_with("http://") && !rest_url.starts_with("https://") {
+                        return Err(TransportError::Config(format!(
+                            "Kafka REST Proxy URL \"{}\" must be http(s)",
+                            rest_url
+                        )));
+                    }
+                    rest_url
+                }
+            };
+            if url.trim().is_empty() {
+                return Err(TransportError::Config("missing broker URL".to_string()));
+            }
+            Ok(())
+        }
+
+        /// Connect a transport publishing to the broker
+        pub async fn connect(&self) -> Result<Arc<dyn Transport>, TransportError> {
+            self.validate()?;
+            Ok(match &self.broker {
+                Broker::Nats { url } => Arc::new(NatsTransport::connect(url).await?),
+                Broker::Kafka { rest_url } => Arc::new(KafkaTransport::connect(rest_url).await?),
+            })
+        }
+    }
+
+    /// Topic of `event` at payload `version` under `prefix`
+    pub fn topic(prefix: &str, event: &str, version: u32) -> String {
+        format!("{}.{}.v{}", prefix, event, version)
+    }
+
+    /// Publishing counters of a transport
+    #[derive(Debug, Clone, Default, Serialize)]
+    pub struct TransportStats {
+        pub transport: String,
+        pub topic_prefix: String,
+        pub published: u64,
+        /// Events dropped because too many were waiting
+        pub dropped: u64,
+        /// Events given up on after every attempt failed
+        pub failed: u64,
+        /// Events waiting to be published
+        pub queued: usize,
+        pub last_error: Option<String>,
+        pub last_published_at: Option<DateTime<Utc>>,
+    }
+
+    // ----------------------------------------
+    // Publishing
+    // ----------------------------------------
+
+    /// Queues emitted events for a transport's background task
+    pub(crate) struct Forwarder {
+        topic_prefix: String,
+        /// This instance, as the `source` of its envelopes
+        source: Uuid,
+        queue: mpsc::Sender<Message>,
+        stats: Arc<Mutex<TransportStats>>,
+    }
+
+    impl Forwarder {
+        /// Publish queued events until the forwarder is dropped
+        pub(crate) fn start(transport: Arc<dyn Transport>, topic_prefix: &str) -> Self {
+            let (queue, rx) = mpsc::channel(QUEUE_SIZE);
+            let stats = Arc::new(Mutex::new(TransportStats {
+                transport: transport.name().to_string(),
+                topic_prefix: topic_prefix.to_string(),
+                ..Default::default()
+            }));
+            tokio::spawn(run(rx, transport, stats.clone()));
+            Self {
+                topic_prefix: topic_prefix.to_string(),
+                source: Uuid::new_v4(),
+                queue,
+                stats,
+            }
+        }
+
+        pub(crate) fn forward(&self, event: &str, data: &serde_json::Value) {
+            let version = events::version_of(event);
+            let message = Message {
+                topic: topic(&self.topic_prefix, event, version),
+                key: event.to_string(),
+                envelope: Envelope {
+                    schema: ENVELOPE_SCHEMA,
+                    id: Uuid::new_v4(),
+                    event: event.to_string(),
+                    version,
+                    source: self.source,
+                    emitted_at: Utc::now(),
+                    data: data.clone(),
+                },
+            };
+            if self.queue.try_send(message).is_err() {
+                self.stats.lock().unwrap().dropped += 1;
+            }
+        }
+
+        pub(crate) fn stats(&self) -> TransportStats {
+            let mut stats = self.stats.lock().unwrap().clone();
+            stats.queued = QUEUE_SIZE - self.queue.capacity();
+            stats
+        }
+    }
+
+    async fn run(mut rx: mpsc::Receiver<Message>, transport: Arc<dyn Transport>, stats: Arc<Mutex<TransportStats>>) {
+        let mut batch = Vec::with_capacity(MAX_BATCH);
+        while let Some(message) = rx.recv().await {
+            batch.push(message);
+            while batch.len() < MAX_BATCH {
+                match rx.try_recv() {
+                    Ok(message) => batch.push(message),
+                    Err(_) => break,
+                }
+            }
+            publish(transport.as_ref(), &batch, &stats).await;
+            batch.clear();
+        }
+    }
+
+    async fn publish(transport: &dyn Transport, batch: &[Message], stats: &Mutex<TransportStats>) {
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            match transport.publish(batch).await {
+                Ok(()) => {
+                    let mut stats = stats.lock().unwrap();
+                    stats.published += batch.len() as u64;
+                    stats.last_published_at = Some(Utc::now());
+                    return;
+                }
+                Err(e) if attempt < MAX_ATTEMPTS => {
+                    tracing::debug!("Retrying {} events on {}: {}", batch.len(), transport.name(), e);
+                    tokio::time::sleep(RETRY_DELAY * 2u32.pow(attempt - 1)).await;
+                }
+                Err(e) => {
+                    tracing::warn!("Dropping {} events, {} failed: {}", batch.len(), transport.name(), e);
+                    let mut stats = stats.lock().unwrap();
+                    stats.failed += batch.len() as u64;
+                    stats.last_error = Some(e.to_string());
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Publishes to NATS subjects
+    pub struct NatsTransport {
+        client: async_nats::Client,
+    }
+
+    impl NatsTransport {
+        pub async fn connect(url: &str) -> Result<Self, TransportError> {
+            let client = async_nats::connect(url)
+                .await
+                .map_err(|e| TransportError::Connect(e.to_string()))?;
+            Ok(Self { client })
+        }
+    }
+
+    impl Transport for NatsTransport {
+        fn name(&self) -> &str {
+            "nats"
+        }
+
+        fn publish<'a>(&'a self, batch: &'a [Message]) -> BoxFuture<'a, Result<(), TransportError>> {
+            Box::pin(async move {
+                for message in batch {
+                    let payload =
+                        serde_json::to_vec(&message.envelope).map_err(|e| TransportError::Publish(e.to_string()))?;
+                    self.client
+                        .publish(message.topic.clone(), payload.into())
+                        .await
+                        .map_err(|e| TransportError::Publish(e.to_string()))?;
+                }
+                self.client
+                    .flush()
+                    .await
+                    .map_err(|e| TransportError::Publish(e.to_string()))
+            })
+        }
+    }
+
+    /// Publishes to Kafka topics through a Kafka REST Proxy
+    pub struct KafkaTransport {
+        http: reqwest::Client,
+        rest_url: String,
+    }
+
+    impl KafkaTransport {
+        /// Check that the proxy at `rest_url` answers
+        pub async fn connect(rest_url: &str) -> Result<Self, TransportError> {
+            let http = http_client()?;
+            let rest_url = rest_url.trim_end_matches('/').to_string();
+            http.get(format!("{}/topics", rest_url))
+                .header("Accept", KAFKA_V2)
+                .send()
+                .await
+                .and_then(reqwest::Response::error_for_status)
+                .map_err(|e| TransportError::Connect(e.to_string()))?;
+            Ok(Self { http, rest_url })
+        }
+    }
+
+    impl Transport for KafkaTransport {
+        fn name(&self) -> &str {
+            "kafka"
+        }
+
+        fn publish<'a>(&'a self, batch: &'a [Message]) -> BoxFuture<'a, Result<(), TransportError>> {
+            Box::pin(async move {
+                let mut topics: BTreeMap<&str, Vec<serde_json::Value>> = BTreeMap::new();
+                for message in batch {
+                    topics
+                        .entry(&message.topic)
+                        .or_default()
+                        .push(serde_json::json!({ "key": message.key, "value": message.envelope }));
+                }
+
+                for (topic, records) in topics {
+                    let response: serde_json::Value = self
+                        .http
+                        .post(format!("{}/topics/{}", self.rest_url, topic))
+                        .header("Content-Type", KAFKA_JSON)
+                        .header("Accept", KAFKA_V2)
+                        .json(&serde_json::json!({ "records": records }))
+                        .send()
+                        .await
+                        .and_then(reqwest::Response::error_for_status)
+                        .map_err(|e| TransportError::Publish(e.to_string()))?
+                        .json()
+                        .await
+                        .map_err(|e| TransportError::Publish(e.to_string()))?;
+
+                    // Records can fail one by one behind a successful response
+                    let failed = response["offsets"]
+                        .as_array()
+                        .into_iter()
+                        .flatten()
+                        .find_map(|offset| offset["error"].as_str());
+                    if let Some(error) = failed {
+                        return Err(TransportError::Publish(format!("{}: {}", topic, error)));
+                    }
+                }
+                Ok(())
+            })
+        }
+    }
+
+    fn http_client() -> Result<reqwest::Client, TransportError> {
+        reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .map_err(|e| TransportError::Config(e.to_string()))
+    }
+
+    async fn option(db: &Database, name: &str) -> Option<String> {
+        db.get_option(name)
+            .await
+            .ok()
+            .flatten()
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+    }
+
+    /// Attach the transport named by `event_transport` (`nats` or `kafka`) at
+    /// `event_transport_url`, publishing under `event_topic_prefix`
+    pub async fn configure(db: &Database) {
+        let Some(kind) = option(db, "event_transport").await else {
+            return;
+        };
+        let Some(url) = option(db, "event_transport_url").await else {
+            tracing::warn!("Events stay in-process; event_transport_url is not set");
+            return;
+        };
+        let prefix = option(db, "event_topic_prefix")
+            .await
+            .unwrap_or_else(|| DEFAULT_TOPIC_PREFIX.to_string());
+
+        let config = match TransportConfig::parse(&kind, &url) {
+            Ok(config) => config.topic_prefix(prefix),
+            Err(e) => {
+                tracing::warn!("Events stay in-process: {}", e);
+                return;
+            }
+        };
+        match config.connect().await {
+            Ok(transport) => {
+                EVENT_BUS.attach_transport(transport, &config.topic_prefix);
+                tracing::info!("Publishing events to {} under {}.*", kind, config.topic_prefix);
+            }
+            Err(e) => tracing::warn!("Events stay in-process: {}", e),
+        }
+    }
+
+    // ----------------------------------------
+    // Consuming in other processes
+    // ----------------------------------------
+
+    /// Reads events a RustPress instance published, for plugins running in
+    /// their own process
+    ///
+    /// ```rust,ignore
+    /// let consumer = RemoteConsumer::connect(TransportConfig::nats("nats://localhost:4222"), "search-indexer").await?;
+    /// let mut rx = consumer.subscribe::<PostPublished>().await?;
+    /// loop {
+    ///     let envelope = rx.recv().await?;
+    ///     reindex(envelope.data.post_id).await;
+    ///     rx.ack().await?;
+    /// }
+    /// ```
+    ///
+    /// Consumers sharing a `group` share the work: each event goes to one of
+    /// them. On NATS (a queue group) delivery is at-most-once and only while
+    /// connected; on Kafka (a consumer group) it is at-least-once, resuming
+    /// after the last `ack` of the group.
+    pub struct RemoteConsumer {
+        config: TransportConfig,
+        group: String,
+        connection: Connection,
+    }
+
+    enum Connection {
+        Nats(async_nats::Client),
+        Kafka { http: reqwest::Client, rest_url: String },
+    }
+
+    impl RemoteConsumer {
+        pub async fn connect(config: TransportConfig, group: &str) -> Result<Self, TransportError> {
+            config.validate()?;
+            let valid = !group.is_empty()
+                && group
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.');
+            if !valid {
+                return Err(TransportError::Config(format!("invalid consumer group \"{}\"", group)));
+            }
+
+            let connection = match &config.broker {
+                Broker::Nats { url } => Connection::Nats(
+                    async_nats::connect(url.as_str())
+                        .await
+                        .map_err(|e| TransportError::Connect(e.to_string()))?,
+                ),
+                Broker::Kafka { rest_url } => Connection::Kafka {
+                    http: http_client()?,
+                    rest_url: rest_url.clone(),
+                },
+            };
+            Ok(Self {
+                config,
+                group: group.to_string(),
+                connection,
+            })
+        }
+
+        /// Receive every `E` published from now on (NATS), or since the
+        /// group's last acknowledged one (Kafka)
+        pub async fn subscribe<E: Event>(&self) -> Result<RemoteReceiver<E>, TransportError> {
+            let topic = self.config.topic(E::NAME, E::VERSION);
+            let subscription = match &self.connection {
+                Connection::Nats(client) => Subscription::Nats(
+                    client
+                        .queue_subscribe(topic, self.group.clone())
+                        .await
+                        .map_err(|e| TransportError::Connect(e.to_string()))?,
+                ),
+                Connection::Kafka { http, rest_url } => {
+                    Subscription::Kafka(KafkaSubscription::create(http.clone(), rest_url, &self.group, &topic).await?)
+                }
+            };
+            Ok(RemoteReceiver {
+                subscription,
+                _event: PhantomData,
+            })
+        }
+    }
+
+    enum Subscription {
+        Nats(async_nats::Subscriber),
+        Kafka(KafkaSubscription),
+    }
+
+    /// Receives one event type from a broker
+    pub struct RemoteReceiver<E> {
+        subscription: Subscription,
+        _event: PhantomData<fn() -> E>,
+    }
+
+    impl<E: Event> RemoteReceiver<E> {
+        /// The next event, waiting for it if needed; envelopes of a newer
+        /// schema, another payload version or a payload that doesn't decode
+        /// as `E` are logged and skipped
+        pub async fn recv(&mut self) -> Result<Envelope<E>, TransportError> {
+            loop {
+                let payload = match &mut self.subscription {
+                    Subscription::Nats(subscriber) => {
+                        let message = subscriber.next().await.ok_or(TransportError::Closed)?;
+                        match serde_json::from_slice(&message.payload) {
+                            Ok(payload) => payload,
+                            Err(e) => {
+                                tracing::warn!("Skipping unreadable {} message: {}", E::NAME, e);
+                                continue;
+                            }
+                        }
+                    }
+                    Subscription::Kafka(kafka) => kafka.next().await?,
+                };
+                if let Some(envelope) = decode::<E>(payload) {
+                    return Ok(envelope);
+                }
+            }
+        }
+
+        /// Acknowledge every event received so far; on Kafka the group
+        /// resumes after them, on NATS this does nothing
+        pub async fn ack(&mut self) -> Result<(), TransportError> {
+            match &mut self.subscription {
+                Subscription::Nats(_) => Ok(()),
+                Subscription::Kafka(kafka) => kafka.commit().await,
+            }
+        }
+
+        /// Leave the group; on Kafka, dropping the receiver instead leaves the
+        /// proxy's consumer instance to expire on its own
+        pub async fn close(self) -> Result<(), TransportError> {
+            match self.subscription {
+                Subscription::Nats(mut subscriber) => subscriber
+                    .unsubscribe()
+                    .await
+                    .map_err(|e| TransportError::Receive(e.to_string())),
+                Subscription::Kafka(kafka) => kafka.delete().await,
+            }
+        }
+    }
+
+    fn decode<E: Event>(payload: serde_json::Value) -> Option<Envelope<E>> {
+        let envelope: Envelope = match serde_json::from_value(payload) {
+            Ok(envelope) => envelope,
+            Err(e) => {
+                tracing::warn!("Skipping malformed {} envelope: {}", E::NAME, e);
+                return None;
+            }
+        };
+        if envelope.schema > ENVELOPE_SCHEMA {
+            tracing::warn!(
+                "Skipping {} envelope of schema {}; this consumer reads up to {}",
+                E::NAME,
+                envelope.schema,
+                ENVELOPE_SCHEMA
+            );
+            return None;
+        }
+        if envelope.event != E::NAME || envelope.version != E::VERSION {
+            tracing::warn!(
+                "Skipping {} v{} event; expected {} v{}",
+                envelope.event,
+                envelope.version,
+                E::NAME,
+                E::VERSION
+            );
+            return None;
+        }
+        match serde_json::from_value(envelope.data) {
+            Ok(data) => Some(Envelope {
+                schema: envelope.schema,
+                id: envelope.id,
+                event: envelope.event,
+                version: envelope.version,
+                source: envelope.source,
+                emitted_at: envelope.emitted_at,
+                data,
+            }),
+            Err(e) => {
+                tracing::warn!("Skipping malformed {} event: {}", E::NAME, e);
+                None
+            }
+        }
+    }
+
+    /// A record polled from the Kafka REST Proxy
+    #[derive(Deserialize)]
+    struct Record {
+        topic: String,
+        partition: i32,
+        offset: i64,
+        value: serde_json::Value,
+    }
+
+    #[derive(Deserialize)]
+    struct ConsumerInstance {
+        base_uri: String,
+    }
+
+    /// A consumer instance on the Kafka REST Proxy
+    struct KafkaSubscription {
+        http: reqwest::Client,
+        base_uri: String,
+        records: VecDeque<Record>,
+        /// Offset of the last record handed out, by topic and partition
+        received: BTreeMap<(String, i32), i64>,
+    }
+
+    impl KafkaSubscription {
+        async fn create(http: reqwest::Client, rest_url: &str, group: &str, topic: &str) -> Result<Self, TransportError> {
+            let instance: ConsumerInstance = http
+                .post(format!("{}/consumers/{}", rest_url, group))
+                .header("Content-Type", KAFKA_V2)
+                .json(&serde_json::json!({
+                    "name": format!("{}-{}", group, Uuid::new_v4()),
+                    "format": "json",
+                    "auto.offset.reset": "latest",
+                    "auto.commit.enable": "false",
+                }))
+                .send()
+                .await
+                .and_then(reqwest::Response::error_for_status)
+                .map_err(|e| TransportError::Connect(e.to_string()))?
+                .json()
+                .await
+                .map_err(|e| TransportError::Connect(e.to_string()))?;
+
+            let subscription = Self {
+                http,
+                base_uri: instance.base_uri,
+                records: VecDeque::new(),
+                received: BTreeMap::new(),
+            };
+            subscription
+                .http
+                .post(format!("{}/subscription", subscription.base_uri))
+                .header("Content-Type", KAFKA_V2)
+                .json(&serde_json::json!({ "topics": [topic] }))
+                .send()
+                .await
+                .and_then(reqwest::Response::error_for_status)
+                .map_err(|e| TransportError::Connect(e.to_string()))?;
+            Ok(subscription)
+        }
+
+        /// The next record's value, polling until there is one
+        async fn next(&mut self) -> Result<serde_json::Value, TransportError> {
+            loop {
+                if let Some(record) = self.records.pop_front() {
+                    self.received.insert((record.topic, record.partition), record.offset);
+                    return Ok(record.value);
+                }
+                let records: Vec<Record> = self
+                    .http
+                    .get(format!("{}/records?timeout={}", self.base_uri, POLL_TIMEOUT_MS))
+                    .header("Accept", KAFKA_JSON)
+                    .send()
+                    .await
+                    .and_then(reqwest::Response::error_for_status)
+                    .map_err(|e| TransportError::Receive(e.to_string()))?
+                    .json()
+                    .await
+                    .map_err(|e| TransportError::Receive(e.to_string()))?;
+                self.records.extend(records);
+            }
+        }
+
+        async fn commit(&mut self) -> Result<(), TransportError> {
+            if self.received.is_empty() {
+                return Ok(());
+            }
+            let offsets: Vec<serde_json::Value> = self
+                .received
+                .iter()
+                .map(|((topic, partition), offset)| {
+                    serde_json::json!({ "topic": topic, "partition": partition, "offset": offset })
+                })
+                .collect();
+            self.http
+                .post(format!("{}/offsets", self.base_uri))
+                .header("Content-Type", KAFKA_V2)
+                .json(&serde_json::json!({ "offsets": offsets }))
+                .send()
+                .await
+                .and_then(reqwest::Response::error_for_status)
+                .map_err(|e| TransportError::Receive(e.to_string()))?;
+            self.received.clear();
+            Ok(())
+        }
+
+        async fn delete(self) -> Result<(), TransportError> {
+            self.http
+                .delete(&self.base_uri)
+                .header("Content-Type", KAFKA_V2)
+                .send()
+                .await
+                .and_then(reqwest::Response::error_for_status)
+                .map_err(|e| TransportError::Receive(e.to_string()))?;
+            Ok(())
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use serde_json::json;
+        use tokio::time::Instant;
+
+        #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Event)]
+        #[event(name = "transport_test_renamed", version = 2)]
+        struct Renamed {
+            post_id: i64,
+            slug: String,
+        }
+
+        /// Records what it's asked to publish, failing the first `failures`
+        /// attempts
+        #[derive(Default)]
+        struct FakeTransport {
+            failures: Mutex<u32>,
+            attempts: Mutex<Vec<Instant>>,
+            published: Mutex<Vec<Message>>,
+        }
+
+        impl FakeTransport {
+            fn failing(failures: u32) -> Arc<Self> {
+                Arc::new(Self {
+                    failures: Mutex::new(failures),
+                    ..Default::default()
+                })
+            }
+
+            /// Gaps between consecutive attempts
+            fn delays(&self) -> Vec<Duration> {
+                let attempts = self.attempts.lock().unwrap();
+                attempts.windows(2).map(|pair| pair[1] - pair[0]).collect()
+            }
+        }
+
+        impl Transport for FakeTransport {
+            fn name(&self) -> &str {
+                "fake"
+            }
+
+            fn publish<'a>(&'a self, batch: &'a [Message]) -> BoxFuture<'a, Result<(), TransportError>> {
+                Box::pin(async move {
+                    self.attempts.lock().unwrap().push(Instant::now());
+                    let mut failures = self.failures.lock().unwrap();
+                    if *failures > 0 {
+                        *failures -= 1;
+                        return Err(TransportError::Publish("broker unavailable".to_string()));
+                    }
+                    self.published.lock().unwrap().extend_from_slice(batch);
+                    Ok(())
+                })
+            }
+        }
+
+        /// Stats once every queued event has been published or given up on
+        async fn settled(forwarder: &Forwarder, events: u64) -> TransportStats {
+            loop {
+                let stats = forwarder.stats();
+                if stats.published + stats.failed + stats.dropped >= events {
+                    return stats;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        }
+
+        fn envelope(event: &str, version: u32, data: serde_json::Value) -> serde_json::Value {
+            json!({
+                "schema": ENVELOPE_SCHEMA,
+                "id": Uuid::new_v4(),
+                "event": event,
+                "version": version,
+                "source": Uuid::new_v4(),
+                "emitted_at": "2026-10-16T09:30:00Z",
+                "data": data,
+            })
+        }
+
+        #[test]
+        fn test_topics_carry_prefix_event_and_version() {
+            assert_eq!(topic("rustpress", "post_saved", 1), "rustpress.post_saved.v1");
+            let config = TransportConfig::nats("nats://localhost:4222");
+            assert_eq!(config.topic("post_saved", 1), "rustpress.post_saved.v1");
+            let config = config.topic_prefix("blog.prod");
+            assert_eq!(config.topic(Renamed::NAME, Renamed::VERSION), "blog.prod.transport_test_renamed.v2");
+            assert!(config.validate().is_ok());
+
+            let kafka = TransportConfig::parse("kafka", "http://kafka-rest:8082/").unwrap();
+            assert_eq!(kafka.broker, Broker::Kafka { rest_url: "http://kafka-rest:8082".to_string() });
+            assert!(matches!(TransportConfig::parse("amqp", "amqp://localhost"), Err(TransportError::Config(_))));
+
+            for prefix in ["", ".blog", "blog.", "blog posts", "blog/posts"] {
+                let config = TransportConfig::nats("nats://localhost:4222").topic_prefix(prefix);
+                assert!(config.validate().is_err(), "{:?} is not a valid prefix", prefix);
+            }
+            assert!(TransportConfig::kafka("kafka-rest:8082").validate().is_err());
+            assert!(TransportConfig::nats(" ").validate().is_err());
+        }
+
+        #[tokio::test]
+        async fn test_forwarded_events_are_enveloped_per_topic() {
+            let transport = FakeTransport::failing(0);
+            let forwarder = Forwarder::start(transport.clone(), "blog");
+            forwarder.forward(Renamed::NAME, &json!({ "post_id": 7, "slug": "kites" }));
+            forwarder.forward("cache_cleared", &json!(null));
+            let stats = settled(&forwarder, 2).await;
+            assert_eq!((stats.published, stats.failed, stats.dropped), (2, 0, 0));
+            assert_eq!(stats.transport, "fake");
+            assert!(stats.last_published_at.is_some());
+
+            let published = transport.published.lock().unwrap();
+            let renamed = &published[0];
+            assert_eq!(renamed.topic, "blog.transport_test_renamed.v2");
+            assert_eq!(renamed.key, Renamed::NAME);
+            assert_eq!(renamed.envelope.schema, ENVELOPE_SCHEMA);
+            assert_eq!(renamed.envelope.version, 2);
+            // Undeclared events are at version 1
+            assert_eq!(published[1].topic, "blog.cache_cleared.v1");
+            assert_eq!(published[0].envelope.source, published[1].envelope.source);
+            assert_ne!(published[0].envelope.id, published[1].envelope.id);
+
+            // What goes on the wire reads back as the event
+            let wire = serde_json::to_value(&renamed.envelope).unwrap();
+            assert_eq!(wire["event"], Renamed::NAME);
+            assert_eq!(wire["data"], json!({ "post_id": 7, "slug": "kites" }));
+            let decoded = decode::<Renamed>(wire).unwrap();
+            assert_eq!(decoded.id, renamed.envelope.id);
+            assert_eq!(decoded.emitted_at, renamed.envelope.emitted_at);
+            assert_eq!(decoded.data, Renamed { post_id: 7, slug: "kites".to_string() });
+        }
+
+        #[test]
+        fn test_decode_skips_envelopes_it_cannot_read() {
+            let data = json!({ "post_id": 7, "slug": "kites" });
+            assert!(decode::<Renamed>(envelope(Renamed::NAME, 2, data.clone())).is_some());
+
+            let mut newer = envelope(Renamed::NAME, 2, data.clone());
+            newer["schema"] = json!(ENVELOPE_SCHEMA + 1);
+            assert!(decode::<Renamed>(newer).is_none());
+            assert!(decode::<Renamed>(envelope(Renamed::NAME, 1, data.clone())).is_none());
+            assert!(decode::<Renamed>(envelope("post_saved", 2, data)).is_none());
+            assert!(decode::<Renamed>(envelope(Renamed::NAME, 2, json!({ "post_id": "seven" }))).is_none());
+            assert!(decode::<Renamed>(json!({ "event": Renamed::NAME })).is_none());
+        }
+
+        #[tokio::test(start_paused = true)]
+        async fn test_failed_publishes_back_off_then_succeed() {
+            let transport = FakeTransport::failing(MAX_ATTEMPTS - 1);
+            let forwarder = Forwarder::start(transport.clone(), DEFAULT_TOPIC_PREFIX);
+            forwarder.forward("post_saved", &json!({ "post_id": 1 }));
+            let stats = settled(&forwarder, 1).await;
+
+            assert_eq!((stats.published, stats.failed), (1, 0));
+            assert_eq!(transport.published.lock().unwrap().len(), 1);
+            let delays = transport.delays();
+            assert_eq!(delays.len(), MAX_ATTEMPTS as usize - 1);
+            for (attempt, delay) in delays.into_iter().enumerate() {
+                // Doubled every attempt; the poll in `settled` may add a tick
+                let expected = RETRY_DELAY * 2u32.pow(attempt as u32);
+                assert!(delay >= expected && delay < expected + Duration::from_millis(50), "{:?}", delay);
+            }
+        }
+
+        #[tokio::test(start_paused = true)]
+        async fn test_batch_counts_as_failed_after_last_attempt() {
+            let transport = FakeTransport::failing(u32::MAX);
+            let forwarder = Forwarder::start(transport.clone(), DEFAULT_TOPIC_PREFIX);
+            for post_id in 0..3 {
+                forwarder.forward("post_saved", &json!({ "post_id": post_id }));
+            }
+            let stats = settled(&forwarder, 3).await;
+
+            assert_eq!((stats.published, stats.failed), (0, 3));
+            assert_eq!(stats.last_error.as_deref(), Some("failed to publish events: broker unavailable"));
+            assert_eq!(transport.attempts.lock().unwrap().len(), MAX_ATTEMPTS as usize);
+            assert!(transport.published.lock().unwrap().is_empty());
+
+            // The next event gets attempts of its own
+            *transport.failures.lock().unwrap() = 0;
+            forwarder.forward("post_saved", &json!({ "post_id": 3 }));
+            let stats = settled(&forwarder, 4).await;
+            assert_eq!((stats.published, stats.failed), (1, 3));
+        }
+    }
+}
+
+// ============================================
+// Utilities Module
+// ============================================
+
+pub mod utils {
+    /// Convert plain text URLs to clickable links
+    pub fn auto_link_urls(text: &str) -> String {
+        let url_regex = regex::Regex::new(
+            r"(https?://[^\s<>\[\]]+)"
+        ).unwrap();
+
+        url_regex.replace_all(text, r#"<a href="$1">$1</a>"#).to_string()
+    }
+
+    /// Convert straight quotes, dashes and ellipses in plain text; use
+    /// `typography::smarten_html` for markup
+    pub fn smart_quotes(text: &str) -> String {
+        crate::typography::smarten(text)
+    }
+
+    /// Strip HTML tags from text
+    pub fn strip_html(html: &str) -> String {
+        let re = regex::Regex::new(r"<[^>]+>").unwrap();
+        re.replace_all(html, "").to_string()
+    }
+
+    /// WordPress-style auto paragraphs that leave block-level markup alone
+    pub fn wpautop(text: &str) -> String {
+        crate::autop::autop(text, &crate::autop::AutopOptions::default())
+    }
+}
+
+// ============================================
+// Table of Contents Module
+// ============================================
+
+pub mod toc {
+    use lol_html::html_content::ContentType;
+    use lol_html::{element, rewrite_str, text, RewriteStrSettings};
+    use std::cell::RefCell;
+    use std::collections::{HashMap, HashSet};
+
+    /// Headings that receive anchors and may appear in the TOC
+    const HEADING_SELECTOR: &str = "h2, h3, h4";
+
+    /// Marker replaced by the generated TOC when present in content
+    pub const TOC_MARKER: &str = "[toc]";
+
+    /// Options controlling table of contents generation
+    #[derive(Debug, Clone)]
+    pub struct TocOptions {
+        /// Number of heading levels to include, starting at h2 (1-3)
+        pub depth: u8,
+        /// Minimum number of headings before a TOC is generated
+        pub min_headings: usize,
+    }
+
+    impl Default for TocOptions {
+        fn default() -> Self {
+            Self {
+                depth: 3,
+                min_headings: 3,
+            }
+        }
+    }
+
+    impl TocOptions {
+        /// Read `toc_depth` and `toc_min_headings` from filter arguments
+        pub fn from_filter_args(args: &HashMap<String, serde_json::Value>) -> Self {
+            let defaults = Self::default();
+            Self {
+                depth: args.get("toc_depth")
+                    .and_then(|v| v.as_u64())
+                    .map(|d| d.clamp(1, 3) as u8)
+                    .unwrap_or(defaults.depth),
+                min_headings: args.get("toc_min_headings")
+                    .and_then(|v| v.as_u64())
+                    .map(|n| n as usize)
+                    .unwrap_or(defaults.min_headings),
+            }
+        }
+
+        fn max_level(&self) -> u8 {
+            1 + self.depth
+        }
+    }
+
+    /// A heading found in post content
+    #[derive(Debug, Clone)]
+    pub struct Heading {
+        pub level: u8,
+        pub id: String,
+        pub text: String,
+    }
+
+    /// Assign stable IDs to h2-h4 and append a self-link to each
+    pub fn add_heading_anchors(html: &str) -> Result<String, lol_html::errors::RewritingError> {
+        let headings = collect_headings(html)?;
+        let index = RefCell::new(0usize);
+
+        rewrite_str(html, RewriteStrSettings {
+            element_content_handlers: vec![element!(HEADING_SELECTOR, |el| {
+                let mut i = index.borrow_mut();
+                if let Some(heading) = headings.get(*i) {
+                    if el.get_attribute("id").is_none() {
+                        el.set_attribute("id", &heading.id)?;
+                    }
+                    el.append(
+                        &format!(
+                            "<a class=\"heading-anchor\" href=\"#{}\" aria-hidden=\"true\">#</a>",
+                            heading.id
+                        ),
+                        ContentType::Html,
+                    );
+                }
+                *i += 1;
+                Ok(())
+            })],
+            ..RewriteStrSettings::default()
+        })
+    }
+
+    /// Build a TOC and inject it at the `[toc]` marker or before the first heading
+    pub fn insert_table_of_contents(
+        html: &str,
+        options: &TocOptions,
+    ) -> Result<String, lol_html::errors::RewritingError> {
+        let headings: Vec<Heading> = collect_headings(html)?
+            .into_iter()
+            .filter(|h| h.level <= options.max_level())
+            .collect();
+
+        let has_marker = html.contains(TOC_MARKER);
+        if headings.is_empty() || headings.len() < options.min_headings {
+            // Never leave the raw marker behind
+            return Ok(if has_marker { remove_marker(html, "") } else { html.to_string() });
+        }
+
+        let toc = render_toc(&headings);
+        if has_marker {
+            return Ok(remove_marker(html, &toc));
+        }
+
+        let inserted = RefCell::new(false);
+        rewrite_str(html, RewriteStrSettings {
+            element_content_handlers: vec![element!(HEADING_SELECTOR, |el| {
+                let mut done = inserted.borrow_mut();
+                if !*done {
+                    el.before(&toc, ContentType::Html);
+                    *done = true;
+                }
+                Ok(())
+            })],
+            ..RewriteStrSettings::default()
+        })
+    }
+
+    /// Collect h2-h4 headings with de-duplicated slug IDs in document order
+    pub fn collect_headings(html: &str) -> Result<Vec<Heading>, lol_html::errors::RewritingError> {
+        let found: RefCell<Vec<(u8, Option<String>, String)>> = RefCell::new(Vec::new());
+
+        rewrite_str(html, RewriteStrSettings {
+            element_content_handlers: vec![
+                element!(HEADING_SELECTOR, |el| {
+                    let level = el.tag_name()[1..].parse().unwrap_or(2);
+                    found.borrow_mut().push((level, el.get_attribute("id"), String::new()));
+                    Ok(())
+                }),
+                text!(HEADING_SELECTOR, |chunk| {
+                    if let Some(last) = found.borrow_mut().last_mut() {
+                        last.2.push_str(chunk.as_str());
+                    }
+                    Ok(())
+                }),
+            ],
+            ..RewriteStrSettings::default()
+        })?;
+
+        // Reserve explicit IDs first so generated slugs never collide with them
+        let found = found.into_inner();
+        let mut used: HashSet<String> = found.iter().filter_map(|(_, id, _)| id.clone()).collect();
+
+        Ok(found
+            .into_iter()
+            .map(|(level, id, raw_text)| {
+                let text = html_escape::decode_html_entities(raw_text.trim()).to_string();
+                let id = id.unwrap_or_else(|| unique_slug(&slugify(&text), &mut used));
+                Heading { level, id, text }
+            })
+            .collect())
+    }
+
+    /// Render headings as nested ordered lists
+    ///
+    /// A heading deeper than the one before it nests one list down however
+    /// many levels it skips, so every `<ol>` sits in an `<li>`.
+    pub fn render_toc(headings: &[Heading]) -> String {
+        let mut html = String::from("<nav class=\"toc\" aria-label=\"Table of contents\">");
+        // Level of the headings in each open list, outermost first
+        let mut open: Vec<u8> = Vec::new();
+
+        for heading in headings {
+            match open.last() {
+                Some(&last) if heading.level <= last => {
+                    html.push_str("</li>");
+                    while open.len() > 1 && open[open.len() - 2] >= heading.level {
+                        html.push_str("</ol></li>");
+                        open.pop();
+                    }
+                    // Shallower than its list's headings: later ones nest under it
+                    if let Some(last) = open.last_mut() {
+                        *last = (*last).min(heading.level);
+                    }
+                }
+                _ => {
+                    html.push_str("<ol>");
+                    open.push(heading.level);
+                }
+            }
+            html.push_str(&format!(
+                "<li><a href=\"#{}\">{}</a>",
+                heading.id,
+                html_escape::encode_text(&heading.text)
+            ));
+        }
+
+        if !open.is_empty() {
+            html.push_str("</li>");
+            for _ in 1..open.len() {
+                html.push_str("</ol></li>");
+            }
+            html.push_str("</ol>");
+        }
+        html.push_str("</nav>");
+        html
+    }
+
+    /// Convert heading text into a URL-safe fragment
+    pub fn slugify(text: &str) -> String {
+        let mut slug = String::with_capacity(text.len());
+        for c in text.chars().flat_map(char::to_lowercase) {
+            if c.is_alphanumeric() {
+                slug.push(c);
+            } else if (c.is_whitespace() || c == '-' || c == '_') && !slug.ends_with('-') {
+                slug.push('-');
+            }
+        }
+
+        let slug = slug.trim_matches('-');
+        if slug.is_empty() {
+            "section".to_string()
+        } else {
+            slug.to_string()
+        }
+    }
+
+    fn unique_slug(base: &str, used: &mut HashSet<String>) -> String {
+        let mut candidate = base.to_string();
+        let mut n = 2;
+        while used.contains(&candidate) {
+            candidate = format!("{}-{}", base, n);
+            n += 1;
+        }
+        used.insert(candidate.clone());
+        candidate
+    }
+
+    fn remove_marker(html: &str, replacement: &str) -> String {
+        // Editors usually wrap the marker in its own paragraph
+        let wrapped = format!("<p>{}</p>", TOC_MARKER);
+        if html.contains(&wrapped) {
+            html.replacen(&wrapped, replacement, 1).replace(&wrapped, "")
+        } else {
+            html.replacen(TOC_MARKER, replacement, 1).replace(TOC_MARKER, "")
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn ids(html: &str) -> Vec<String> {
+            collect_headings(html).unwrap().into_iter().map(|h| h.id).collect()
+        }
+
+        #[test]
+        fn test_slugify() {
+            assert_eq!(slugify("Getting Started"), "getting-started");
+            assert_eq!(slugify("  What's new -- in 2.0?  "), "whats-new-in-20");
+            assert_eq!(slugify("Übersicht"), "übersicht");
+            assert_eq!(slugify("!!!"), "section");
+        }
+
+        #[test]
+        fn test_duplicate_headings_get_numbered_slugs() {
+            assert_eq!(
+                ids("<h2>Setup</h2><h3>Setup</h3><h2>Setup</h2>"),
+                ["setup", "setup-2", "setup-3"]
+            );
+            assert_eq!(ids("<h2>!!!</h2><h2>???</h2>"), ["section", "section-2"]);
+        }
+
+        #[test]
+        fn test_generated_slugs_avoid_explicit_ids() {
+            // The explicit id comes later but is reserved first
+            assert_eq!(
+                ids("<h2>Intro</h2><h2>Intro</h2><h2 id=\"intro-2\">Other</h2>"),
+                ["intro", "intro-3", "intro-2"]
+            );
+        }
+
+        #[test]
+        fn test_anchors_keep_explicit_ids() {
+            let html = add_heading_anchors("<h2 id=\"custom\">A &amp; B</h2><h3>A &amp; B</h3>").unwrap();
+            assert!(html.contains("<h2 id=\"custom\">A &amp; B<a class=\"heading-anchor\" href=\"#custom\""));
+            assert!(html.contains("<h3 id=\"a-b\">"));
+            assert!(html.contains("href=\"#a-b\""));
+        }
+
+        #[test]
+        fn test_minimum_headings() {
+            let two = "<h2>One</h2><h2>Two</h2>";
+            assert_eq!(insert_table_of_contents(two, &TocOptions::default()).unwrap(), two);
+
+            let options = TocOptions { min_headings: 2, ..TocOptions::default() };
+            assert!(insert_table_of_contents(two, &options).unwrap().starts_with("<nav class=\"toc\""));
+
+            // Headings below the depth don't count towards the minimum
+            let deep = "<h2>One</h2><h4>Two</h4><h4>Three</h4>";
+            let options = TocOptions { depth: 1, min_headings: 2 };
+            assert_eq!(insert_table_of_contents(deep, &options).unwrap(), deep);
+        }
+
+        #[test]
+        fn test_marker_is_replaced_or_removed() {
+            let html = "<p>[toc]</p><h2>One</h2><h2>Two</h2><h2>Three</h2>";
+            let out = insert_table_of_contents(html, &TocOptions::default()).unwrap();
+            assert!(out.starts_with("<nav class=\"toc\""));
+            assert!(!out.contains(TOC_MARKER));
+
+            let out = insert_table_of_contents("<p>[toc]</p><h2>Only</h2>", &TocOptions::default()).unwrap();
+            assert_eq!(out, "<h2>Only</h2>");
+        }
+
+        #[test]
+        fn test_nested_toc() {
+            let headings = collect_headings("<h2>A</h2><h3>B</h3><h4>C</h4><h2>D</h2>").unwrap();
+            assert_eq!(
+                render_toc(&headings),
+                "<nav class=\"toc\" aria-label=\"Table of contents\"><ol>\
+                 <li><a href=\"#a\">A</a><ol><li><a href=\"#b\">B</a><ol><li><a href=\"#c\">C</a></li></ol></li></ol></li>\
+                 <li><a href=\"#d\">D</a></li></ol></nav>"
+            );
+        }
+
+        #[test]
+        fn test_toc_with_skipped_levels() {
+            let toc = |html| render_toc(&collect_headings(html).unwrap());
+            let nav = |lists: &str| format!("<nav class=\"toc\" aria-label=\"Table of contents\">{}</nav>", lists);
+
+            // A skipped level nests once, and the next heading between the two
+            // levels is a sibling of the deeper one
+            assert_eq!(
+                toc("<h2>A</h2><h4>B</h4><h3>C</h3><h4>D</h4><h2>E</h2>"),
+                nav("<ol><li><a href=\"#a\">A</a><ol><li><a href=\"#b\">B</a></li>\
+                     <li><a href=\"#c\">C</a><ol><li><a href=\"#d\">D</a></li></ol></li></ol></li>\
+                     <li><a href=\"#e\">E</a></li></ol>")
+            );
+
+            // Starting deeper than later headings: they share the outer list
+            assert_eq!(
+                toc("<h4>A</h4><h2>B</h2><h3>C</h3>"),
+                nav("<ol><li><a href=\"#a\">A</a></li>\
+                     <li><a href=\"#b\">B</a><ol><li><a href=\"#c\">C</a></li></ol></li></ol>")
+            );
+
+            // No list is ever opened directly inside another
+            for html in ["<h2>A</h2><h4>B</h4>", "<h3>A</h3><h2>B</h2><h4>C</h4><h2>D</h2>"] {
+                assert!(!toc(html).contains("<ol><ol>"), "{}", toc(html));
+            }
+        }
+    }
+}
+
+// ============================================
+// Paragraphs Module
+// ============================================
+
+pub mod autop {
+    //! Automatic paragraphs
+    //!
+    //! Blank-line separated text becomes `<p>` elements and single line
+    //! breaks become `<br />`, like WordPress's `wpautop`, but block-level
+    //! markup is recognised as such: it is never wrapped in a paragraph, and
+    //! text inside it is only split into paragraphs where the author left a
+    //! blank line (always inside `<blockquote>`). `<pre>`, `<script>` and
+    //! `<style>` blocks, comments and `<textarea>` are passed through as is,
+    //! and a paragraph consisting of just a shortcode is left unwrapped.
+
+    use std::collections::HashMap;
+
+    /// Elements that start a new block; never wrapped in `<p>`
+    const BLOCK_ELEMENTS: &[&str] = &[
+        "address", "area", "article", "aside", "blockquote", "caption", "col", "colgroup", "dd",
+        "details", "div", "dl", "dt", "fieldset", "figcaption", "figure", "footer", "form", "h1",
+        "h2", "h3", "h4", "h5", "h6", "header", "hgroup", "hr", "legend", "li", "main", "map",
+        "math", "menu", "nav", "ol", "p", "section", "summary", "table",
+        "tbody", "td", "tfoot", "th", "thead", "tr", "ul",
+    ];
+
+    /// Blocks whose content is copied verbatim
+    const RAW_BLOCKS: &[&str] = &["pre", "script", "style"];
+
+    /// Inline elements whose content is copied verbatim
+    const RAW_INLINE: &[&str] = &["textarea"];
+
+    /// Blocks that may only hold inline content, so are never given paragraphs
+    const PHRASING_BLOCKS: &[&str] = &[
+        "caption", "dt", "h1", "h2", "h3", "h4", "h5", "h6", "legend", "p", "summary",
+    ];
+
+    /// Options for [`autop`]
+    #[derive(Debug, Clone)]
+    pub struct AutopOptions {
+        /// Convert single line breaks into `<br />`
+        pub br: bool,
+    }
+
+    impl Default for AutopOptions {
+        fn default() -> Self {
+            Self { br: true }
+        }
+    }
+
+    impl AutopOptions {
+        /// Read `autop_br` from filter arguments
+        pub fn from_filter_args(args: &HashMap<String, serde_json::Value>) -> Self {
+            Self {
+                br: args.get("autop_br")
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(true),
+            }
+        }
+    }
+
+    #[derive(Debug)]
+    enum Token<'a> {
+        Text(&'a str),
+        /// Inline tag, comment or raw inline element
+        Inline(&'a str),
+        /// Opening or closing block tag, or a raw block
+        Block { raw: &'a str, name: String, closing: bool },
+    }
+
+    /// Add paragraphs and line breaks to `text`
+    pub fn autop(text: &str, options: &AutopOptions) -> String {
+        let text = text.replace("\r\n", "\n").replace('\r', "\n");
+        if text.trim().is_empty() {
+            return String::new();
+        }
+
+        let mut out = String::with_capacity(text.len() + text.len() / 4);
+        let mut open: Vec<String> = Vec::new();
+        let mut run: Vec<&str> = Vec::new();
+        let mut run_inline: Vec<bool> = Vec::new();
+
+        for token in tokenize(&text) {
+            match token {
+                Token::Text(s) => {
+                    run.push(s);
+                    run_inline.push(false);
+                }
+                Token::Inline(s) => {
+                    run.push(s);
+                    run_inline.push(true);
+                }
+                Token::Block { raw, name, closing } => {
+                    flush(&mut out, &run, &run_inline, open.last().map(String::as_str), options);
+                    run.clear();
+                    run_inline.clear();
+                    out.push_str(raw);
+
+                    if RAW_BLOCKS.contains(&name.as_str()) || is_void(&name) || raw.ends_with("/>") {
+                        continue;
+                    }
+                    if closing {
+                        if let Some(pos) = open.iter().rposition(|n| *n == name) {
+                            open.truncate(pos);
+                        }
+                    } else {
+                        open.push(name);
+                    }
+                }
+            }
+        }
+        flush(&mut out, &run, &run_inline, open.last().map(String::as_str), options);
+
+        out.trim().to_string()
+    }
+
+    /// Write an inline run, as paragraphs where it belongs in them
+    fn flush(out: &mut String, run: &[&str], inline: &[bool], parent: Option<&str>, options: &AutopOptions) {
+        let pieces: Vec<(&str, bool)> = run.iter().copied().zip(inline.iter().copied()).collect();
+        let joined: String = run.concat();
+        if joined.trim().is_empty() {
+            out.push_str(&joined);
+            return;
+        }
+
+        // Split into paragraphs at blank lines in text (not inside markup)
+        let mut paragraphs: Vec<Vec<(&str, bool)>> = vec![Vec::new()];
+        for &(piece, is_inline) in &pieces {
+            let mut rest = piece;
+            if !is_inline {
+                while let Some((before, after)) = split_blank_line(rest) {
+                    paragraphs.last_mut().unwrap().push((before, false));
+                    paragraphs.push(Vec::new());
+                    rest = after;
+                }
+            }
+            paragraphs.last_mut().unwrap().push((rest, is_inline));
+        }
+
+        let wrap = match parent {
+            None | Some("blockquote") => true,
+            Some(name) if PHRASING_BLOCKS.contains(&name) => false,
+            Some(_) => paragraphs.len() > 1,
+        };
+        if !wrap {
+            out.push_str(&line_breaks(&pieces, options));
+            return;
+        }
+
+        // Keep a line break on either side when the author had one
+        let leading = &joined[..joined.len() - joined.trim_start().len()];
+        if leading.contains('\n') && !out.is_empty() && !out.ends_with('\n') {
+            out.push('\n');
+        }
+
+        let mut first = true;
+        for paragraph in &paragraphs {
+            let content = line_breaks(paragraph, options);
+            let content = content.trim();
+            if content.is_empty() {
+                continue;
+            }
+            if !first {
+                out.push('\n');
+            }
+            first = false;
+
+            if is_shortcode(content) {
+                out.push_str(content);
+            } else {
+                out.push_str("<p>");
+                out.push_str(content);
+                out.push_str("</p>");
+            }
+        }
+
+        let trailing = &joined[joined.trim_end().len()..];
+        if trailing.contains('\n') {
+            out.push('\n');
+        }
+    }
+
+    /// Join pieces, turning line breaks inside the text into `<br />`
+    fn line_breaks(pieces: &[(&str, bool)], options: &AutopOptions) -> String {
+        let joined: String = pieces.iter().map(|(piece, _)| *piece).collect();
+        if !options.br {
+            return joined;
+        }
+
+        // Only breaks between content count, not surrounding whitespace
+        let first = joined.len() - joined.trim_start().len();
+        let last = joined.trim_end().len();
+
+        let mut out = String::with_capacity(joined.len());
+        let mut offset = 0;
+        for &(piece, is_inline) in pieces {
+            if is_inline {
+                out.push_str(piece);
+            } else {
+                for (i, c) in piece.char_indices() {
+                    let at = offset + i;
+                    let written = out.trim_end_matches([' ', '\t']);
+                    if c == '\n' && at > first && at < last && !written.ends_with('\n') && !ends_with_br(written) {
+                        out.truncate(written.len());
+                        out.push_str("<br />\n");
+                    } else {
+                        out.push(c);
+                    }
+                }
+            }
+            offset += piece.len();
+        }
+        out
+    }
+
+    fn ends_with_br(s: &str) -> bool {
+        let lower = s.to_ascii_lowercase();
+        lower.ends_with("<br>") || lower.ends_with("<br/>") || lower.ends_with("<br />")
+    }
+
+    /// Split at the first blank line (a line break, optional spaces, a line break)
+    fn split_blank_line(s: &str) -> Option<(&str, &str)> {
+        let bytes = s.as_bytes();
+        let mut i = 0;
+        while let Some(pos) = s[i..].find('\n') {
+            let start = i + pos;
+            let mut j = start + 1;
+            while j < bytes.len() && (bytes[j] == b' ' || bytes[j] == b'\t') {
+                j += 1;
+            }
+            if j < bytes.len() && bytes[j] == b'\n' {
+                let mut end = j + 1;
+                while end < bytes.len() && bytes[end].is_ascii_whitespace() {
+                    end += 1;
+                }
+                return Some((&s[..start], &s[end..]));
+            }
+            i = start + 1;
+        }
+        None
+    }
+
+    /// `[gallery ids="1,2"]` or `[/caption]` on its own
+    fn is_shortcode(content: &str) -> bool {
+        let Some(inner) = content.strip_prefix('[').and_then(|c| c.strip_suffix(']')) else {
+            return false;
+        };
+        let name = inner.strip_prefix('/').unwrap_or(inner);
+        let name_end = name.find(|c: char| c.is_whitespace() || c == '/').unwrap_or(name.len());
+        name_end > 0
+            && name.starts_with(|c: char| c.is_ascii_alphabetic())
+            && name[..name_end].chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+            && !inner.contains(['[', ']'])
+    }
+
+    fn is_void(name: &str) -> bool {
+        matches!(name, "area" | "col" | "hr")
+    }
+
+    /// Split HTML into text, inline markup and block tags
+    fn tokenize(html: &str) -> Vec<Token<'_>> {
+        let mut tokens = Vec::new();
+        let mut text_start = 0;
+        let mut i = 0;
+
+        while let Some(pos) = html[i..].find('<') {
+            let start = i + pos;
+            let Some((end, name, closing)) = scan_markup(html, start) else {
+                i = start + 1;
+                continue;
+            };
+
+            if text_start < start {
+                tokens.push(Token::Text(&html[text_start..start]));
+            }
+
+            let raw_block = !closing && RAW_BLOCKS.contains(&name.as_str());
+            let raw_inline = !closing && RAW_INLINE.contains(&name.as_str());
+            let end = if raw_block || raw_inline {
+                find_close(html, end, &name)
+            } else {
+                end
+            };
+
+            let raw = &html[start..end];
+            if name.is_empty() || raw_inline || !(raw_block || BLOCK_ELEMENTS.contains(&name.as_str())) {
+                tokens.push(Token::Inline(raw));
+            } else {
+                tokens.push(Token::Block { raw, name, closing });
+            }
+            text_start = end;
+            i = end;
+        }
+
+        if text_start < html.len() {
+            tokens.push(Token::Text(&html[text_start..]));
+        }
+        tokens
+    }
+
+    /// End, lowercase name and closing flag of the tag or comment at
+    /// `start`; comments and declarations have an empty name
+    fn scan_markup(html: &str, start: usize) -> Option<(usize, String, bool)> {
+        let rest = &html[start..];
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            let end = comment.find("-->").map_or(html.len(), |p| start + 4 + p + 3);
+            return Some((end, String::new(), false));
+        }
+
+        let bytes = rest.as_bytes();
+        let closing = bytes.get(1) == Some(&b'/');
+        let name_start = if closing { 2 } else { 1 };
+        let first = *bytes.get(name_start)?;
+        if !(first.is_ascii_alphabetic() || (first == b'!' && !closing)) {
+            return None;
+        }
+
+        let name_end = rest[name_start..]
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == ':'))
+            .map_or(rest.len(), |p| name_start + p);
+        let name = rest[name_start..name_end].to_ascii_lowercase();
+
+        // Find the closing '>' outside quoted attribute values
+        let mut quote = None;
+        for (offset, c) in rest[name_end..].char_indices() {
+            match (quote, c) {
+                (Some(q), c) if c == q => quote = None,
+                (Some(_), _) => {}
+                (None, '"') | (None, '\'') => quote = Some(c),
+                (None, '>') => {
+                    let name = if first == b'!' { String::new() } else { name };
+                    return Some((start + name_end + offset + 1, name, closing));
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+
+    /// End of `</name>` at or after `from`, or the end of the input
+    fn find_close(html: &str, from: usize, name: &str) -> usize {
+        let lower = html[from..].to_ascii_lowercase();
+        let needle = format!("</{}", name);
+        match lower.find(&needle) {
+            Some(p) => {
+                let close = from + p;
+                html[close..].find('>').map_or(html.len(), |q| close + q + 1)
+            }
+            None => html.len(),
+        }
+    }
+
+    // Cases ported from WordPress's wpautop test suite
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn p(text: &str) -> String {
+            autop(text, &AutopOptions::default())
+        }
+
+        #[test]
+        fn test_paragraphs_and_line_breaks() {
+            assert_eq!(p("Hello\n\nWorld"), "<p>Hello</p>\n<p>World</p>");
+            assert_eq!(p("Line 1\nLine 2"), "<p>Line 1<br />\nLine 2</p>");
+            assert_eq!(p("One\r\n\r\nTwo\r\nThree"), "<p>One</p>\n<p>Two<br />\nThree</p>");
+            assert_eq!(p("Line 1<br>\nLine 2"), "<p>Line 1<br>\nLine 2</p>");
+            assert_eq!(p("a < b"), "<p>a < b</p>");
+            assert_eq!(p("  \n\n "), "");
+            assert_eq!(
+                autop("Line 1\nLine 2", &AutopOptions { br: false }),
+                "<p>Line 1\nLine 2</p>"
+            );
+        }
+
+        #[test]
+        fn test_block_elements_are_not_wrapped() {
+            for element in BLOCK_ELEMENTS.iter().filter(|e| !is_void(e) && **e != "blockquote") {
+                let html = format!("<{0}>Hello</{0}>", element);
+                assert_eq!(p(&html), html, "<{}>", element);
+            }
+            assert_eq!(p("Before\n<hr>\nAfter"), "<p>Before</p>\n<hr>\n<p>After</p>");
+        }
+
+        #[test]
+        fn test_inline_elements_are_wrapped() {
+            let inline = [
+                "a", "em", "strong", "small", "s", "cite", "q", "dfn", "abbr", "data", "time", "code",
+                "var", "samp", "kbd", "sub", "sup", "i", "b", "u", "mark", "span", "del", "ins",
+                "noscript", "select",
+            ];
+            for element in inline {
+                let html = format!("<{0}>Hello</{0}>", element);
+                assert_eq!(p(&html), format!("<p>{}</p>", html), "<{}>", element);
+            }
+        }
+
+        #[test]
+        fn test_lists() {
+            assert_eq!(
+                p("Intro\n<ul>\n<li>One</li>\n<li>Two</li>\n</ul>\nOutro"),
+                "<p>Intro</p>\n<ul>\n<li>One</li>\n<li>Two</li>\n</ul>\n<p>Outro</p>"
+            );
+            assert_eq!(
+                p("<ul>\n<li>One\n\nMore</li>\n</ul>"),
+                "<ul>\n<li><p>One</p>\n<p>More</p></li>\n</ul>"
+            );
+        }
+
+        #[test]
+        fn test_blockquote_content_gets_paragraphs() {
+            assert_eq!(p("<blockquote>Quote</blockquote>"), "<blockquote><p>Quote</p></blockquote>");
+            assert_eq!(
+                p("<blockquote>\nOne\n\nTwo\n</blockquote>"),
+                "<blockquote>\n<p>One</p>\n<p>Two</p>\n</blockquote>"
+            );
+        }
+
+        #[test]
+        fn test_divs_split_only_at_blank_lines() {
+            assert_eq!(p("<div>\nOne\nTwo\n</div>"), "<div>\nOne<br />\nTwo\n</div>");
+            assert_eq!(p("<div>\nOne\n\nTwo\n</div>"), "<div>\n<p>One</p>\n<p>Two</p>\n</div>");
+        }
+
+        #[test]
+        fn test_preformatted_content_is_untouched() {
+            let pre = "<pre>line 1\n\nline 2\nline 3</pre>";
+            assert_eq!(p(pre), pre);
+            assert_eq!(p(&format!("Before\n\n{}\n\nAfter", pre)), format!("<p>Before</p>\n{}\n<p>After</p>", pre));
+
+            let script = "<script>\nvar a = 1;\n\nvar b = a < 2;\n</script>";
+            assert_eq!(p(script), script);
+            assert_eq!(p("<style>\np { color: red; }\n\n</style>"), "<style>\np { color: red; }\n\n</style>");
+            assert_eq!(p("A <!-- one\n\ntwo --> B"), "<p>A <!-- one\n\ntwo --> B</p>");
+            assert_eq!(p("<textarea>a\n\nb</textarea>"), "<p><textarea>a\n\nb</textarea></p>");
+        }
+
+        #[test]
+        fn test_attributes_may_contain_markup_characters() {
+            assert_eq!(
+                p("<a title=\"a > b\n\nc\" href=\"/\">x</a>"),
+                "<p><a title=\"a > b\n\nc\" href=\"/\">x</a></p>"
+            );
+            assert_eq!(p("<div data-x='<p>'>Hi</div>"), "<div data-x='<p>'>Hi</div>");
+        }
+
+        #[test]
+        fn test_standalone_shortcodes_are_not_wrapped() {
+            assert_eq!(p("[gallery ids=\"1,2\"]\n\nText"), "[gallery ids=\"1,2\"]\n<p>Text</p>");
+            assert_eq!(p("[/caption]"), "[/caption]");
+            assert_eq!(p("Text [inline] here"), "<p>Text [inline] here</p>");
+        }
+    }
+}
+// ============================================
+// Typography Module
+// ============================================
+
+pub mod typography {
+    use lol_html::html_content::ContentType;
+    use lol_html::{doc_text, element, rewrite_str, RewriteStrSettings};
+    use std::cell::{Cell, RefCell};
+    use std::rc::Rc;
+
+    /// Elements whose text is left exactly as written
+    const SKIP_SELECTOR: &str = "code, pre, kbd, samp, var, tt, script, style, textarea";
+
+    /// Words that start with an apostrophe rather than an opening quote
+    const ELISIONS: &[&str] = &["tis", "twas", "til", "em", "cause", "round", "n"];
+
+    /// Curly quotes, dashes and ellipses in plain text
+    pub fn smarten(text: &str) -> String {
+        Typographer::default().convert(text)
+    }
+
+    /// Curly quotes, dashes and ellipses in the text of an HTML document
+    ///
+    /// Tags, attribute values and the contents of `<code>`, `<pre>` and other
+    /// literal elements are left untouched.
+    pub fn smarten_html(html: &str) -> Result<String, lol_html::errors::RewritingError> {
+        let skip_depth = Rc::new(Cell::new(0usize));
+        let typographer = RefCell::new(Typographer::default());
+        let buffer = RefCell::new(String::new());
+
+        let depth = skip_depth.clone();
+        rewrite_str(html, RewriteStrSettings {
+            element_content_handlers: vec![element!(SKIP_SELECTOR, move |el| {
+                if let Some(handlers) = el.end_tag_handlers() {
+                    let inner = depth.clone();
+                    handlers.push(Box::new(move |_| {
+                        inner.set(inner.get() - 1);
+                        Ok(())
+                    }));
+                    depth.set(depth.get() + 1);
+                }
+                Ok(())
+            })],
+            document_content_handlers: vec![doc_text!(|chunk| {
+                if skip_depth.get() > 0 {
+                    // A quote right after `<code>x</code>` still closes
+                    if let Some(last) = chunk.as_str().chars().last() {
+                        typographer.borrow_mut().prev = Some(last);
+                    }
+                    return Ok(());
+                }
+
+                // Chunks may split `--` or a word, so convert whole text nodes
+                buffer.borrow_mut().push_str(chunk.as_str());
+                if chunk.last_in_text_node() {
+                    let text = std::mem::take(&mut *buffer.borrow_mut());
+                    let converted = typographer.borrow_mut().convert(&text);
+                    chunk.replace(&converted, ContentType::Html);
+                } else {
+                    chunk.remove();
+                }
+                Ok(())
+            })],
+            ..RewriteStrSettings::default()
+        })
+    }
+
+    /// Converts text runs in order, remembering the character before each
+    /// run so quotes that follow inline markup open or close correctly
+    #[derive(Debug, Default)]
+    struct Typographer {
+        prev: Option<char>,
+    }
+
+    impl Typographer {
+        fn convert(&mut self, text: &str) -> String {
+            let chars: Vec<char> = text.chars().collect();
+            let mut out = String::with_capacity(text.len());
+            let mut i = 0;
+
+            while i < chars.len() {
+                let rest = &chars[i..];
+                let (replacement, consumed) = match rest[0] {
+                    '.' if rest.starts_with(&['.', '.', '.']) => ('\u{2026}', 3),
+                    '-' if rest.starts_with(&['-', '-', '-']) => ('\u{2014}', 3),
+                    '-' if rest.starts_with(&['-', '-']) => ('\u{2013}', 2),
+                    // A hyphen standing alone between words is a dash
+                    '-' if self.prev.is_some_and(char::is_whitespace)
+                        && rest.get(1).is_some_and(|c| c.is_whitespace()) =>
+                    {
+                        ('\u{2013}', 1)
+                    }
+                    '"' if self.opens() => ('\u{201C}', 1),
+                    '"' => ('\u{201D}', 1),
+                    '\'' if self.opens() && !is_elision(&rest[1..]) => ('\u{2018}', 1),
+                    // Apostrophes in contractions, possessives and elisions
+                    '\'' => ('\u{2019}', 1),
+                    c => (c, 1),
+                };
+
+                out.push(replacement);
+                self.prev = Some(replacement);
+                i += consumed;
+            }
+
+            out
+        }
+
+        /// Whether a quote here opens rather than closes
+        fn opens(&self) -> bool {
+            match self.prev {
+                None => true,
+                Some(c) => c.is_whitespace() || "([{\u{2013}\u{2014}/\u{201C}\u{2018}".contains(c),
+            }
+        }
+    }
+
+    /// `'90s`, `'tis`, `rock 'n' roll`
+    fn is_elision(after: &[char]) -> bool {
+        if after.len() >= 2 && after[0].is_ascii_digit() && after[1].is_ascii_digit() {
+            return true;
+        }
+
+        let word: String = after
+            .iter()
+            .take_while(|c| c.is_alphabetic())
+            .flat_map(|c| c.to_lowercase())
+            .collect();
+        ELISIONS.contains(&word.as_str())
+    }
+}
+
+// ============================================
+// Responsive Images Module
+// ============================================
+
+pub mod images {
+    use lol_html::{element, rewrite_str, RewriteStrSettings};
+    use std::collections::HashMap;
+    use uuid::Uuid;
+
+    /// Widths offered in `srcset`
+    pub const DEFAULT_WIDTHS: &[u32] = &[320, 640, 1024, 1280, 1920];
+
+    /// Widths `GET /img/:id/:transform` accepts by default (`[app.images]
+    /// allowed_widths`); candidates outside it would be refused
+    pub const DEFAULT_ALLOWED_WIDTHS: &[u32] = &[160, 320, 640, 800, 1024, 1280, 1920];
+
+    /// Where uploaded images are served: `<uuid>.<ext>` files
+    pub const DEFAULT_UPLOAD_PREFIX: &str = "/uploads/media/";
+
+    /// Path of the image transform route, without `/:id/:transform`
+    pub const DEFAULT_TRANSFORM_BASE: &str = "/api/blog/img";
+
+    /// Default `sizes` attribute for content images
+    pub const DEFAULT_SIZES: &str = "(max-width: 768px) 100vw, 768px";
+
+    /// Formats the transform route can resize
+    const RESIZABLE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "webp"];
+
+    /// Options controlling `<img>` rewriting
+    #[derive(Debug, Clone)]
+    pub struct ImageOptions {
+        /// Site origin used to recognise local media
+        pub site_url: String,
+        /// Widths offered in `srcset`
+        pub widths: Vec<u32>,
+        /// Widths the transform route accepts; others are left out of `srcset`
+        pub allowed_widths: Vec<u32>,
+        /// Path prefix of uploaded media; other images are left alone
+        pub upload_prefix: String,
+        /// Path of the transform route variants are requested from
+        pub transform_base: String,
+        /// Value for the `sizes` attribute
+        pub sizes: String,
+        /// Optional CDN origin replacing the site origin for media URLs
+        pub cdn_url: Option<String>,
+        /// Add `loading="lazy"` and `decoding="async"`
+        pub lazy: bool,
+    }
+
+    impl ImageOptions {
+        pub fn new(site_url: &str) -> Self {
+            Self {
+                site_url: site_url.trim_end_matches('/').to_string(),
+                widths: DEFAULT_WIDTHS.to_vec(),
+                allowed_widths: DEFAULT_ALLOWED_WIDTHS.to_vec(),
+                upload_prefix: DEFAULT_UPLOAD_PREFIX.to_string(),
+                transform_base: DEFAULT_TRANSFORM_BASE.to_string(),
+                sizes: DEFAULT_SIZES.to_string(),
+                cdn_url: None,
+                lazy: true,
+            }
+        }
+
+        /// Read `image_widths`, `image_allowed_widths`, `image_upload_prefix`,
+        /// `image_transform_base`, `image_sizes`, `image_cdn_url` and
+        /// `image_lazy` from filter arguments
+        pub fn from_filter_args(args: &HashMap<String, serde_json::Value>, site_url: &str) -> Self {
+            let mut options = Self::new(site_url);
+
+            if let Some(widths) = widths_arg(args, "image_widths") {
+                options.widths = widths;
+            }
+            if let Some(widths) = widths_arg(args, "image_allowed_widths") {
+                options.allowed_widths = widths;
+            }
+            if let Some(prefix) = args.get("image_upload_prefix").and_then(|v| v.as_str()) {
+                options.upload_prefix = prefix.to_string();
+            }
+            if let Some(base) = args.get("image_transform_base").and_then(|v| v.as_str()) {
+                options.transform_base = base.trim_end_matches('/').to_string();
+            }
+            if let Some(sizes) = args.get("image_sizes").and_then(|v| v.as_str()) {
+                options.sizes = sizes.to_string();
+            }
+            options.cdn_url = args.get("image_cdn_url")
+                .and_then(|v| v.as_str())
+                .filter(|url| !url.is_empty())
+                .map(|url| url.trim_end_matches('/').to_string());
+            if let Some(lazy) = args.get("image_lazy").and_then(|v| v.as_bool()) {
+                options.lazy = lazy;
+            }
+
+            options
+        }
+
+        /// Path of an uploaded image on this site, or `None` for external
+        /// images and local ones outside the upload prefix (theme assets)
+        fn upload_path<'a>(&self, src: &'a str) -> Option<&'a str> {
+            let path = if src.starts_with('/') && !src.starts_with("//") {
+                src
+            } else if self.site_url.is_empty() {
+                return None;
+            } else {
+                src.strip_prefix(self.site_url.as_str()).filter(|path| path.starts_with('/'))?
+            };
+            path.starts_with(&self.upload_prefix).then_some(path)
+        }
+
+        /// Public URL for a local media path, honouring the CDN origin
+        fn public_url(&self, path: &str) -> String {
+            match &self.cdn_url {
+                Some(cdn) => format!("{}{}", cdn, path),
+                None => format!("{}{}", self.site_url, path),
+            }
+        }
+    }
+
+    fn widths_arg(args: &HashMap<String, serde_json::Value>, key: &str) -> Option<Vec<u32>> {
+        let mut widths: Vec<u32> = args.get(key)?
+            .as_array()?
+            .iter()
+            .filter_map(|w| w.as_u64())
+            .map(|w| w as u32)
+            .filter(|w| *w > 0)
+            .collect();
+        widths.sort_unstable();
+        widths.dedup();
+        Some(widths)
+    }
+
+    /// Rewrite uploaded `<img>` tags to resized variants from the transform
+    /// route, and add native lazy loading to all images
+    pub fn rewrite_images(html: &str, options: &ImageOptions) -> Result<String, lol_html::errors::RewritingError> {
+        rewrite_str(html, RewriteStrSettings {
+            element_content_handlers: vec![element!("img", |el| {
+                let Some(src) = el.get_attribute("src") else {
+                    return Ok(());
+                };
+
+                if let Some(path) = options.upload_path(&src) {
+                    let dimensions = attr_dimensions(el.get_attribute("width"), el.get_attribute("height"))
+                        .or_else(|| filename_dimensions(path));
+
+                    if let Some((width, height)) = dimensions {
+                        if el.get_attribute("width").is_none() {
+                            el.set_attribute("width", &width.to_string())?;
+                        }
+                        if el.get_attribute("height").is_none() {
+                            el.set_attribute("height", &height.to_string())?;
+                        }
+                    }
+
+                    if el.get_attribute("srcset").is_none() {
+                        if let Some(srcset) = build_srcset(path, dimensions.map(|(w, _)| w), options) {
+                            el.set_attribute("srcset", &srcset)?;
+                            if el.get_attribute("sizes").is_none() {
+                                el.set_attribute("sizes", &options.sizes)?;
+                            }
+                        }
+                    }
+
+                    el.set_attribute("src", &options.public_url(path))?;
+                }
+
+                if options.lazy {
+                    if el.get_attribute("loading").is_none() {
+                        el.set_attribute("loading", "lazy")?;
+                    }
+                    if el.get_attribute("decoding").is_none() {
+                        el.set_attribute("decoding", "async")?;
+                    }
+                }
+
+                Ok(())
+            })],
+            ..RewriteStrSettings::default()
+        })
+    }
+
+    /// Media ID of an uploaded image the transform route can resize:
+    /// `/uploads/media/<uuid>.jpg` -> `<uuid>`
+    pub fn media_id(path: &str) -> Option<Uuid> {
+        let file = path.rsplit('/').next()?;
+        let (stem, ext) = file.rsplit_once('.')?;
+        if !RESIZABLE_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()) {
+            return None;
+        }
+        Uuid::parse_str(stem).ok()
+    }
+
+    /// Path of the `width` variant: `/api/blog/img/<uuid>/w_640`
+    pub fn transform_path(base: &str, id: Uuid, width: u32) -> String {
+        format!("{}/{}/w_{}", base, id, width)
+    }
+
+    fn build_srcset(path: &str, original_width: Option<u32>, options: &ImageOptions) -> Option<String> {
+        let id = media_id(path)?;
+
+        // Only widths the route serves, and never wider than the source
+        let mut candidates: Vec<String> = options.widths.iter()
+            .filter(|w| options.allowed_widths.contains(w))
+            .filter(|w| original_width.is_none_or(|max| **w < max))
+            .map(|w| format!("{} {}w", options.public_url(&transform_path(&options.transform_base, id, *w)), w))
+            .collect();
+
+        if candidates.is_empty() {
+            return None;
+        }
+        if let Some(width) = original_width {
+            candidates.push(format!("{} {}w", options.public_url(path), width));
+        }
+
+        Some(candidates.join(", "))
+    }
+
+    fn attr_dimensions(width: Option<String>, height: Option<String>) -> Option<(u32, u32)> {
+        let width = width?.trim().parse().ok()?;
+        let height = height?.trim().parse().ok()?;
+        Some((width, height))
+    }
+
+    /// Dimensions encoded as a `-{width}x{height}` filename suffix
+    fn filename_dimensions(path: &str) -> Option<(u32, u32)> {
+        let file = path.rsplit('/').next()?;
+        let stem = file.rsplit_once('.').map_or(file, |(stem, _)| stem);
+        let (_, suffix) = stem.rsplit_once('-')?;
+        let (width, height) = suffix.split_once('x')?;
+        Some((width.parse().ok()?, height.parse().ok()?))
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        const ID: &str = "0b7e3c1a-5d2f-4e8b-9a61-3f0c2d4e5b6a";
+
+        fn rewrite(html: &str, options: &ImageOptions) -> String {
+            rewrite_images(html, options).unwrap()
+        }
+
+        #[test]
+        fn test_uploads_get_transform_route_candidates() {
+            let options = ImageOptions::new("https://example.com");
+            let html = rewrite(&format!("<img src=\"/uploads/media/{}.jpg\" width=\"1500\" height=\"1000\">", ID), &options);
+
+            assert!(html.contains(&format!("src=\"https://example.com/uploads/media/{}.jpg\"", ID)));
+            assert!(html.contains(&format!(
+                "srcset=\"https://example.com/api/blog/img/{0}/w_320 320w, \
+                 https://example.com/api/blog/img/{0}/w_640 640w, \
+                 https://example.com/api/blog/img/{0}/w_1024 1024w, \
+                 https://example.com/api/blog/img/{0}/w_1280 1280w, \
+                 https://example.com/uploads/media/{0}.jpg 1500w\"",
+                ID
+            )));
+            assert!(html.contains("sizes=\"(max-width: 768px) 100vw, 768px\""));
+            assert!(html.contains("loading=\"lazy\" decoding=\"async\""));
+        }
+
+        #[test]
+        fn test_candidates_are_limited_to_allowed_widths() {
+            let mut args = HashMap::new();
+            args.insert("image_widths".to_string(), serde_json::json!([640, 960, 1280]));
+            let options = ImageOptions::from_filter_args(&args, "");
+
+            let html = rewrite(&format!("<img src=\"/uploads/media/{}.png\">", ID), &options);
+            assert!(html.contains(&format!("/api/blog/img/{}/w_640 640w, /api/blog/img/{}/w_1280 1280w\"", ID, ID)));
+            assert!(!html.contains("w_960"));
+
+            args.insert("image_allowed_widths".to_string(), serde_json::json!([960]));
+            let options = ImageOptions::from_filter_args(&args, "");
+            let html = rewrite(&format!("<img src=\"/uploads/media/{}.png\">", ID), &options);
+            assert!(html.contains(&format!("srcset=\"/api/blog/img/{}/w_960 960w\"", ID)));
+        }
+
+        #[test]
+        fn test_other_images_are_left_alone() {
+            let options = ImageOptions::new("https://example.com");
+            for html in [
+                "<img src=\"/themes/default/logo.png\">",
+                "<img src=\"https://other.example/uploads/media/photo.jpg\">",
+                "<img src=\"//cdn.example/photo.jpg\">",
+            ] {
+                let out = rewrite(html, &options);
+                assert!(!out.contains("srcset"), "{}", out);
+                assert!(out.contains(&html[..html.len() - 1]), "{}", out);
+            }
+
+            // SVGs and files that aren't media IDs keep their src, without candidates
+            let svg = rewrite(&format!("<img src=\"/uploads/media/{}.svg\">", ID), &options);
+            assert!(!svg.contains("srcset"));
+            let named = rewrite("<img src=\"/uploads/media/photo.jpg\">", &options);
+            assert!(!named.contains("srcset"));
+            assert!(named.contains("src=\"https://example.com/uploads/media/photo.jpg\""));
+        }
+
+        #[test]
+        fn test_existing_attributes_are_kept() {
+            let mut options = ImageOptions::new("");
+            options.lazy = false;
+            let html = format!("<img src=\"/uploads/media/{}.jpg\" srcset=\"a.jpg 1x\" loading=\"eager\">", ID);
+            let out = rewrite(&html, &options);
+            assert!(out.contains("srcset=\"a.jpg 1x\""));
+            assert!(out.contains("loading=\"eager\""));
+            assert!(!out.contains("decoding"));
+        }
+
+        #[test]
+        fn test_cdn_origin() {
+            let mut args = HashMap::new();
+            args.insert("image_cdn_url".to_string(), serde_json::json!("https://cdn.example.com/"));
+            let options = ImageOptions::from_filter_args(&args, "https://example.com");
+            let out = rewrite(&format!("<img src=\"https://example.com/uploads/media/{}-800x600.jpg\">", ID), &options);
+            assert!(out.contains("width=\"800\" height=\"600\""));
+            assert!(out.contains("src=\"https://cdn.example.com/uploads/media/"));
+        }
+
+        #[test]
+        fn test_media_id() {
+            assert_eq!(media_id(&format!("/uploads/media/{}.JPG", ID)), Some(ID.parse().unwrap()));
+            assert_eq!(media_id(&format!("/uploads/media/{}.gif", ID)), None);
+            assert_eq!(media_id("/uploads/media/photo.jpg"), None);
+        }
     }
 }
@@ -5,14 +5,27 @@
 //! - Action hooks (events)
 //! - Filter hooks (data transformation)
 //! - Lifecycle hooks
+//! - Request-scoped hook context
+//! - Handler timing audit
 //! - Utility functions
 
 use async_trait::async_trait;
+use axum::{
+    extract::{ConnectInfo, FromRequestParts, State},
+    http::{header, request::Parts, StatusCode},
+    middleware::Next,
+    response::Response,
+    routing::get,
+    Json, Router,
+};
+use serde::Serialize;
 use std::any::Any;
 use std::collections::HashMap;
 use std::future::Future;
+use std::net::{IpAddr, SocketAddr};
 use std::pin::Pin;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
 
 // ============================================
@@ -53,16 +66,274 @@ type FilterFn<T> = Arc<
 // Context Objects
 // ============================================
 
-#[derive(Clone)]
-pub struct ActionContext {
+/// Application-wide services, built once at startup and shared by every
+/// request's [`HookContext`]
+pub struct HookServices {
+    db: Option<Arc<dyn Any + Send + Sync>>,
+    cache: HookCache,
+    settings: HookSettings,
+}
+
+impl HookServices {
+    pub fn new() -> Self {
+        Self {
+            db: None,
+            cache: HookCache::default(),
+            settings: HookSettings::default(),
+        }
+    }
+
+    /// The host's database pool, handed out by [`HookContext::db`]
+    pub fn with_db<P: Any + Send + Sync>(mut self, pool: P) -> Self {
+        self.db = Some(Arc::new(pool));
+        self
+    }
+
+    /// Initial settings (site options)
+    pub fn with_settings(mut self, settings: HashMap<String, serde_json::Value>) -> Self {
+        self.settings = HookSettings {
+            values: RwLock::new(settings),
+        };
+        self
+    }
+}
+
+impl Default for HookServices {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Key/value cache shared by handlers
+#[derive(Default)]
+pub struct HookCache {
+    entries: RwLock<HashMap<String, serde_json::Value>>,
+}
+
+impl HookCache {
+    pub async fn get(&self, key: &str) -> Option<serde_json::Value> {
+        self.entries.read().await.get(key).cloned()
+    }
+
+    pub async fn set(&self, key: &str, value: serde_json::Value) {
+        self.entries.write().await.insert(key.to_string(), value);
+    }
+
+    pub async fn invalidate(&self, key: &str) {
+        self.entries.write().await.remove(key);
+    }
+}
+
+/// Site options readable by handlers
+#[derive(Default)]
+pub struct HookSettings {
+    values: RwLock<HashMap<String, serde_json::Value>>,
+}
+
+impl HookSettings {
+    pub async fn get(&self, name: &str) -> Option<serde_json::Value> {
+        self.values.read().await.get(name).cloned()
+    }
+
+    pub async fn set(&self, name: &str, value: serde_json::Value) {
+        self.values.write().await.insert(name.to_string(), value);
+    }
+}
+
+/// The signed-in user of a request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CurrentUser {
+    pub id: i64,
+    pub roles: Vec<String>,
+}
+
+impl CurrentUser {
+    pub fn has_role(&self, role: &str) -> bool {
+        self.roles.iter().any(|r| r == role)
+    }
+}
+
+/// The site a request is served for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Site {
+    pub id: i64,
+    pub name: String,
+    pub url: String,
+}
+
+/// Where a request came from and what it asked for
+#[derive(Debug, Clone)]
+pub struct RequestMeta {
     pub request_id: String,
-    pub user_id: Option<i64>,
+    pub method: String,
+    pub path: String,
+    pub ip: Option<IpAddr>,
+    pub user_agent: Option<String>,
+}
+
+impl RequestMeta {
+    /// Metadata for hooks fired outside a request (jobs, CLI, tests)
+    pub fn background(request_id: impl Into<String>) -> Self {
+        Self {
+            request_id: request_id.into(),
+            method: String::new(),
+            path: String::new(),
+            ip: None,
+            user_agent: None,
+        }
+    }
+}
+
+struct ContextInner {
+    services: Arc<HookServices>,
+    request: RequestMeta,
+    user: Option<CurrentUser>,
+    site: Option<Site>,
 }
 
+/// Everything a hook handler may need about the current request
+///
+/// Built once per request (see [`hook_context_layer`]) and handed to every
+/// action and filter handler it runs; clones share the same data.
 #[derive(Clone)]
-pub struct FilterContext {
-    pub request_id: String,
-    pub user_id: Option<i64>,
+pub struct HookContext {
+    inner: Arc<ContextInner>,
+}
+
+impl HookContext {
+    pub fn new(services: Arc<HookServices>, request: RequestMeta) -> Self {
+        Self::build(services, request, None, None)
+    }
+
+    fn build(services: Arc<HookServices>, request: RequestMeta, user: Option<CurrentUser>, site: Option<Site>) -> Self {
+        Self {
+            inner: Arc::new(ContextInner {
+                services,
+                request,
+                user,
+                site,
+            }),
+        }
+    }
+
+    pub fn with_user(self, user: CurrentUser) -> Self {
+        let inner = self.inner;
+        Self::build(inner.services.clone(), inner.request.clone(), Some(user), inner.site.clone())
+    }
+
+    pub fn with_site(self, site: Site) -> Self {
+        let inner = self.inner;
+        Self::build(inner.services.clone(), inner.request.clone(), inner.user.clone(), Some(site))
+    }
+
+    pub fn request(&self) -> &RequestMeta {
+        &self.inner.request
+    }
+
+    pub fn request_id(&self) -> &str {
+        &self.inner.request.request_id
+    }
+
+    pub fn user(&self) -> Option<&CurrentUser> {
+        self.inner.user.as_ref()
+    }
+
+    pub fn user_id(&self) -> Option<i64> {
+        self.user().map(|user| user.id)
+    }
+
+    pub fn site(&self) -> Option<&Site> {
+        self.inner.site.as_ref()
+    }
+
+    /// The database pool, if the host registered one of type `P`
+    pub fn db<P: Any + Send + Sync>(&self) -> Option<&P> {
+        self.inner.services.db.as_ref()?.downcast_ref()
+    }
+
+    pub fn cache(&self) -> &HookCache {
+        &self.inner.services.cache
+    }
+
+    pub fn settings(&self) -> &HookSettings {
+        &self.inner.services.settings
+    }
+
+    /// Shorthand for `settings().get(name)`
+    pub async fn get_option(&self, name: &str) -> Option<serde_json::Value> {
+        self.settings().get(name).await
+    }
+}
+
+/// Context of action handlers; the same type as [`HookContext`]
+pub type ActionContext = HookContext;
+
+/// Context of filter handlers; the same type as [`HookContext`]
+pub type FilterContext = HookContext;
+
+/// Header carrying the request ID, set when absent
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Middleware building the request's [`HookContext`] into its extensions
+///
+/// Picks up the [`CurrentUser`] and [`Site`] that earlier layers (auth, site
+/// resolution) put into the extensions, so it belongs inside them:
+///
+/// ```rust,ignore
+/// let app = routes
+///     .layer(axum::middleware::from_fn_with_state(services, hook_context_layer))
+///     .layer(site_layer)
+///     .layer(auth_layer);
+/// ```
+pub async fn hook_context_layer(
+    State(services): State<Arc<HookServices>>,
+    mut request: axum::extract::Request,
+    next: Next,
+) -> Response {
+    let headers = request.headers();
+    let request_id = headers
+        .get(REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|v| !v.is_empty())
+        .map(String::from)
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let meta = RequestMeta {
+        request_id,
+        method: request.method().to_string(),
+        path: request.uri().path().to_string(),
+        ip: request
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip()),
+        user_agent: headers
+            .get(header::USER_AGENT)
+            .and_then(|v| v.to_str().ok())
+            .map(String::from),
+    };
+
+    let extensions = request.extensions();
+    let ctx = HookContext::build(
+        services,
+        meta,
+        extensions.get::<CurrentUser>().cloned(),
+        extensions.get::<Site>().cloned(),
+    );
+    request.extensions_mut().insert(ctx);
+    next.run(request).await
+}
+
+/// Handlers take the context built by [`hook_context_layer`]
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for HookContext {
+    type Rejection = (StatusCode, &'static str);
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<HookContext>()
+            .cloned()
+            .ok_or((StatusCode::INTERNAL_SERVER_ERROR, "Hook context layer is not installed"))
+    }
 }
 
 // ============================================
@@ -70,19 +341,25 @@ pub struct FilterContext {
 // ============================================
 
 struct ActionHandler {
+    name: &'static str,
     callback: ActionFn,
     priority: i32,
 }
 
 struct FilterHandler<T> {
+    name: &'static str,
     callback: FilterFn<T>,
     priority: i32,
 }
 
+/// Default duration above which a handler call is logged as slow
+pub const DEFAULT_SLOW_THRESHOLD: Duration = Duration::from_millis(100);
+
 /// Simple hook registry for actions and string filters
 pub struct HookRegistry {
     actions: RwLock<HashMap<String, Vec<ActionHandler>>>,
     string_filters: RwLock<HashMap<String, Vec<FilterHandler<String>>>>,
+    timings: HookTimings,
 }
 
 impl HookRegistry {
@@ -90,9 +367,21 @@ impl HookRegistry {
         Self {
             actions: RwLock::new(HashMap::new()),
             string_filters: RwLock::new(HashMap::new()),
+            timings: HookTimings::new(DEFAULT_SLOW_THRESHOLD),
         }
     }
 
+    /// Log handler calls slower than `threshold` (default 100ms)
+    pub fn with_slow_threshold(mut self, threshold: Duration) -> Self {
+        self.timings = HookTimings::new(threshold);
+        self
+    }
+
+    /// Per-handler execution timings
+    pub fn timings(&self) -> &HookTimings {
+        &self.timings
+    }
+
     /// Register an action hook
     pub async fn add_action<F, Fut>(&self, hook: &str, callback: F, priority: i32)
     where
@@ -103,11 +392,12 @@ impl HookRegistry {
         let handlers = actions.entry(hook.to_string()).or_default();
 
         handlers.push(ActionHandler {
+            name: std::any::type_name::<F>(),
             callback: Arc::new(move |ctx, data| Box::pin(callback(ctx, data))),
             priority,
         });
 
-        handlers.sort_by(|a, b| b.priority.cmp(&a.priority));
+        handlers.sort_by_key(|h| std::cmp::Reverse(h.priority));
     }
 
     /// Execute an action hook
@@ -121,7 +411,11 @@ impl HookRegistry {
 
         if let Some(handlers) = actions.get(hook) {
             for handler in handlers {
-                (handler.callback)(ctx.clone(), Box::new(data.clone())).await?;
+                let started = Instant::now();
+                let result = (handler.callback)(ctx.clone(), Box::new(data.clone())).await;
+                self.timings
+                    .record(HookKind::Action, hook, handler.name, ctx.request_id(), started.elapsed());
+                result?;
             }
         }
 
@@ -138,11 +432,12 @@ impl HookRegistry {
         let handlers = filters.entry(hook.to_string()).or_default();
 
         handlers.push(FilterHandler {
+            name: std::any::type_name::<F>(),
             callback: Arc::new(move |ctx, value| Box::pin(callback(ctx, value))),
             priority,
         });
 
-        handlers.sort_by(|a, b| b.priority.cmp(&a.priority));
+        handlers.sort_by_key(|h| std::cmp::Reverse(h.priority));
     }
 
     /// Apply string filters
@@ -157,7 +452,11 @@ impl HookRegistry {
 
         if let Some(handlers) = filters.get(hook) {
             for handler in handlers {
-                result = (handler.callback)(ctx.clone(), result).await?;
+                let started = Instant::now();
+                let filtered = (handler.callback)(ctx.clone(), result).await;
+                self.timings
+                    .record(HookKind::Filter, hook, handler.name, ctx.request_id(), started.elapsed());
+                result = filtered?;
             }
         }
 
@@ -171,6 +470,144 @@ impl Default for HookRegistry {
     }
 }
 
+// ============================================
+// Hook Timings
+// ============================================
+
+/// Kind of hook a handler is registered on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HookKind {
+    Action,
+    Filter,
+}
+
+#[derive(Default)]
+struct TimingStats {
+    calls: u64,
+    slow_calls: u64,
+    total: Duration,
+    max: Duration,
+}
+
+/// Aggregated timings of one handler on one hook
+#[derive(Debug, Clone, Serialize)]
+pub struct HandlerTiming {
+    pub kind: HookKind,
+    pub hook: String,
+    pub handler: String,
+    pub calls: u64,
+    pub slow_calls: u64,
+    pub total_ms: f64,
+    pub avg_ms: f64,
+    pub max_ms: f64,
+}
+
+/// Per-handler duration audit
+///
+/// Every action and filter call is recorded; calls above the slow threshold
+/// are also logged with the hook, handler and request ID.
+pub struct HookTimings {
+    slow_threshold: Duration,
+    stats: Mutex<HashMap<(HookKind, String, &'static str), TimingStats>>,
+}
+
+impl HookTimings {
+    pub fn new(slow_threshold: Duration) -> Self {
+        Self {
+            slow_threshold,
+            stats: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn slow_threshold(&self) -> Duration {
+        self.slow_threshold
+    }
+
+    fn record(&self, kind: HookKind, hook: &str, handler: &'static str, request_id: &str, elapsed: Duration) {
+        let slow = elapsed > self.slow_threshold;
+        if slow {
+            tracing::warn!(
+                request_id = %request_id,
+                hook = %hook,
+                handler = %handler,
+                duration_ms = elapsed.as_secs_f64() * 1000.0,
+                threshold_ms = self.slow_threshold.as_secs_f64() * 1000.0,
+                "Slow hook handler"
+            );
+        }
+
+        let mut stats = self.stats.lock().unwrap_or_else(|e| e.into_inner());
+        let entry = stats.entry((kind, hook.to_string(), handler)).or_default();
+        entry.calls += 1;
+        entry.total += elapsed;
+        entry.max = entry.max.max(elapsed);
+        if slow {
+            entry.slow_calls += 1;
+        }
+    }
+
+    /// Aggregated timings, most total time first
+    pub fn snapshot(&self) -> Vec<HandlerTiming> {
+        let stats = self.stats.lock().unwrap_or_else(|e| e.into_inner());
+        let mut timings: Vec<HandlerTiming> = stats
+            .iter()
+            .map(|((kind, hook, handler), s)| HandlerTiming {
+                kind: *kind,
+                hook: hook.clone(),
+                handler: handler.to_string(),
+                calls: s.calls,
+                slow_calls: s.slow_calls,
+                total_ms: s.total.as_secs_f64() * 1000.0,
+                avg_ms: s.total.as_secs_f64() * 1000.0 / s.calls as f64,
+                max_ms: s.max.as_secs_f64() * 1000.0,
+            })
+            .collect();
+        timings.sort_by(|a, b| b.total_ms.total_cmp(&a.total_ms));
+        timings
+    }
+
+    /// Clear all recorded timings
+    pub fn reset(&self) {
+        self.stats.lock().unwrap_or_else(|e| e.into_inner()).clear();
+    }
+}
+
+/// Response of the timings endpoint
+#[derive(Debug, Serialize)]
+pub struct TimingsResponse {
+    pub slow_threshold_ms: f64,
+    pub handlers: Vec<HandlerTiming>,
+}
+
+/// Path of the timings endpoint
+pub const TIMINGS_PATH: &str = "/api/v1/system/hooks/timings";
+
+/// Routes exposing the registry's handler timings
+///
+/// `GET` returns the aggregation, `DELETE` starts a new measuring window.
+/// Mount behind the host's admin authentication.
+pub fn timings_routes(registry: Arc<HookRegistry>) -> Router {
+    Router::new()
+        .route(TIMINGS_PATH, get(get_timings).delete(reset_timings))
+        .with_state(registry)
+}
+
+/// GET /api/v1/system/hooks/timings
+async fn get_timings(State(registry): State<Arc<HookRegistry>>) -> Json<TimingsResponse> {
+    let timings = registry.timings();
+    Json(TimingsResponse {
+        slow_threshold_ms: timings.slow_threshold().as_secs_f64() * 1000.0,
+        handlers: timings.snapshot(),
+    })
+}
+
+/// DELETE /api/v1/system/hooks/timings
+async fn reset_timings(State(registry): State<Arc<HookRegistry>>) -> axum::http::StatusCode {
+    registry.timings().reset();
+    axum::http::StatusCode::NO_CONTENT
+}
+
 // ============================================
 // Sample Action Handlers
 // ============================================
@@ -182,7 +619,7 @@ pub async fn on_post_published(
 ) -> Result<(), HookError> {
     if let Some(post_id) = data.downcast_ref::<i64>() {
         tracing::info!(
-            request_id = %ctx.request_id,
+            request_id = %ctx.request_id(),
             post_id = %post_id,
             "Post published"
         );
@@ -197,7 +634,7 @@ pub async fn on_user_login(
 ) -> Result<(), HookError> {
     if let Some(user_id) = data.downcast_ref::<i64>() {
         tracing::info!(
-            request_id = %ctx.request_id,
+            request_id = %ctx.request_id(),
             user_id = %user_id,
             "User logged in"
         );
@@ -288,6 +725,12 @@ impl LifecycleHook for MyComponent {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use axum::body::Body;
+    use tower::ServiceExt;
+
+    fn test_context(request_id: &str) -> HookContext {
+        HookContext::new(Arc::new(HookServices::new()), RequestMeta::background(request_id))
+    }
 
     #[tokio::test]
     async fn test_action_hooks() {
@@ -299,10 +742,10 @@ mod tests {
             .await;
 
         // Execute action
-        let ctx = ActionContext {
-            request_id: "test-123".into(),
-            user_id: Some(1),
-        };
+        let ctx = test_context("test-123").with_user(CurrentUser {
+            id: 1,
+            roles: vec!["author".into()],
+        });
 
         let result = registry.do_action("post_publish", &ctx, 42i64).await;
         assert!(result.is_ok());
@@ -320,10 +763,7 @@ mod tests {
             .add_filter("content", filter_uppercase, priority::NORMAL)
             .await;
 
-        let ctx = FilterContext {
-            request_id: "test-456".into(),
-            user_id: None,
-        };
+        let ctx = test_context("test-456");
 
         let result = registry
             .apply_filters("content", &ctx, "hello world".into())
@@ -334,6 +774,124 @@ mod tests {
         assert_eq!(result, "[FILTERED] HELLO WORLD");
     }
 
+    #[tokio::test]
+    async fn test_handler_timings() {
+        let registry = HookRegistry::new().with_slow_threshold(Duration::from_millis(5));
+
+        registry
+            .add_filter("content", filter_uppercase, priority::NORMAL)
+            .await;
+        registry
+            .add_filter(
+                "content",
+                |_ctx, content: String| async move {
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                    Ok(content)
+                },
+                priority::LOW,
+            )
+            .await;
+
+        let ctx = test_context("test-789");
+        for _ in 0..2 {
+            registry
+                .apply_filters("content", &ctx, "hello".into())
+                .await
+                .unwrap();
+        }
+
+        let timings = registry.timings().snapshot();
+        assert_eq!(timings.len(), 2);
+
+        // The sleeping filter dominates and is flagged slow on every call
+        assert_eq!(timings[0].kind, HookKind::Filter);
+        assert_eq!(timings[0].calls, 2);
+        assert_eq!(timings[0].slow_calls, 2);
+        assert!(timings[0].max_ms >= 10.0);
+        assert!(timings[1].handler.ends_with("filter_uppercase"));
+        assert_eq!(timings[1].slow_calls, 0);
+
+        registry.timings().reset();
+        assert!(registry.timings().snapshot().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_context_services_are_shared() {
+        let services = Arc::new(
+            HookServices::new()
+                .with_db(String::from("pool"))
+                .with_settings(HashMap::from([("blogname".to_string(), serde_json::json!("RustPress"))])),
+        );
+        let registry = HookRegistry::new();
+
+        registry
+            .add_filter(
+                "title",
+                |ctx: FilterContext, title: String| async move {
+                    ctx.cache().set("seen", serde_json::json!(true)).await;
+                    let blogname = ctx.get_option("blogname").await.unwrap_or_default();
+                    Ok(format!("{} | {}", title, blogname.as_str().unwrap_or("")))
+                },
+                priority::HIGH,
+            )
+            .await;
+        registry
+            .add_filter(
+                "title",
+                |ctx: FilterContext, title: String| async move {
+                    assert_eq!(ctx.cache().get("seen").await, Some(serde_json::json!(true)));
+                    assert_eq!(ctx.db::<String>().map(String::as_str), Some("pool"));
+                    assert!(ctx.db::<i64>().is_none());
+                    Ok(title)
+                },
+                priority::LOW,
+            )
+            .await;
+
+        let ctx = HookContext::new(services, RequestMeta::background("test-ctx"));
+        let title = registry.apply_filters("title", &ctx, "Hello".into()).await.unwrap();
+        assert_eq!(title, "Hello | RustPress");
+    }
+
+    #[tokio::test]
+    async fn test_context_layer() {
+        async fn handler(ctx: HookContext) -> String {
+            format!(
+                "{} {} {} user={:?} site={:?}",
+                ctx.request_id(),
+                ctx.request().method,
+                ctx.request().path,
+                ctx.user_id(),
+                ctx.site().map(|site| site.name.as_str()),
+            )
+        }
+
+        let services = Arc::new(HookServices::new());
+        let app = Router::new()
+            .route("/posts", get(handler))
+            .layer(axum::middleware::from_fn_with_state(services, hook_context_layer))
+            .layer(axum::middleware::from_fn(|mut request: axum::extract::Request, next: Next| async move {
+                request.extensions_mut().insert(CurrentUser { id: 7, roles: vec![] });
+                next.run(request).await
+            }));
+
+        let request = axum::http::Request::builder()
+            .uri("/posts")
+            .header(REQUEST_ID_HEADER, "req-1")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(body, "req-1 GET /posts user=Some(7) site=None");
+
+        // Without the header an ID is generated
+        let request = axum::http::Request::builder().uri("/posts").body(Body::empty()).unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let request_id = String::from_utf8_lossy(&body).split(' ').next().unwrap().to_string();
+        assert!(uuid::Uuid::parse_str(&request_id).is_ok());
+    }
+
     #[tokio::test]
     async fn test_lifecycle() {
         let component = MyComponent::new("test");
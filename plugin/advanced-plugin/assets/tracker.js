@@ -0,0 +1,235 @@
+/*! RustPress Analytics tracker
+ * Options come from the data attributes of the script tag loading it:
+ * data-outbound, data-downloads, data-download-extensions, data-heartbeat,
+ * and data-key, the key hits are signed with when the site requires it.
+ *
+ * Before its first hit it asks /policy how to track the visitor: in full,
+ * cookieless (nothing kept in the browser's storage) or not at all. The
+ * answer is kept in rpAnalytics.policy and dispatched as an
+ * "rp-analytics:policy" event for consent banners. With the "consent" legal
+ * basis the tracker runs cookieless until rpAnalytics.grantConsent().
+ */
+(function() {
+    var script = document.currentScript;
+    var options = (script && script.dataset) || {};
+
+    var analytics = {
+        endpoint: options.endpoint || '/api/v1/rustpress-analytics/track',
+        policy: null,
+        // Whether ids are kept in localStorage / sessionStorage
+        stored: false,
+        visitorId: null,
+        sessionId: null,
+        siteId: (document.querySelector('meta[name="rustpress-site"]') || {}).content || null,
+        trackOutbound: options.outbound === 'true',
+        trackDownloads: options.downloads === 'true',
+        downloadExtensions: (options.downloadExtensions || '').split(',').filter(Boolean),
+        heartbeatInterval: parseInt(options.heartbeat || '0', 10),
+        key: options.key || null,
+        signingKey: null,
+        pageviewId: null,
+        engagedMs: 0,
+        visibleSince: document.visibilityState === 'visible' ? Date.now() : null,
+
+        init: function() {
+            fetch(this.endpoint.replace(/\/track$/, '/policy')).then(function(r) {
+                return r.json();
+            }).then(function(policy) {
+                analytics.applyPolicy(policy);
+            }, function() {
+                // Without an answer nothing is kept in the browser
+                analytics.applyPolicy({ mode: 'cookieless', legal_basis: 'consent' });
+            });
+        },
+
+        applyPolicy: function(policy) {
+            this.policy = policy;
+            document.dispatchEvent(new CustomEvent('rp-analytics:policy', { detail: policy }));
+            this.useStorage(this.storageAllowed());
+            if (policy.mode === 'disabled') return;
+
+            this.trackPageView();
+            if (this.trackOutbound) this.setupOutboundTracking();
+            if (this.trackDownloads) this.setupDownloadTracking();
+            if (this.heartbeatInterval > 0) this.setupEngagementTracking();
+        },
+
+        storageAllowed: function() {
+            if (!this.policy || this.policy.mode !== 'full') return false;
+            return this.policy.legal_basis !== 'consent' || localStorage.getItem('_rp_consent') === '1';
+        },
+
+        // Ids stay in memory for the page either way, for events and heartbeats
+        useStorage: function(on) {
+            this.stored = on;
+            if (on) {
+                this.visitorId = localStorage.getItem('_rp_vid') || this.visitorId;
+                this.sessionId = sessionStorage.getItem('_rp_sid') || this.sessionId;
+                if (this.visitorId) localStorage.setItem('_rp_vid', this.visitorId);
+                if (this.sessionId) sessionStorage.setItem('_rp_sid', this.sessionId);
+            } else {
+                localStorage.removeItem('_rp_vid');
+                sessionStorage.removeItem('_rp_sid');
+            }
+        },
+
+        // For consent banners
+        grantConsent: function() {
+            localStorage.setItem('_rp_consent', '1');
+            this.useStorage(this.storageAllowed());
+        },
+
+        revokeConsent: function() {
+            localStorage.removeItem('_rp_consent');
+            this.useStorage(this.storageAllowed());
+        },
+
+        track: function(data) {
+            if (!this.policy || this.policy.mode === 'disabled') return;
+            data.visitor_id = this.visitorId;
+            data.session_id = this.sessionId;
+            data.site_id = this.siteId;
+
+            var body = JSON.stringify(data);
+            // WebCrypto is only available on secure (https) pages
+            if (this.key && window.crypto && crypto.subtle) {
+                this.sign(body).then(function(headers) { analytics.send(body, headers); });
+            } else {
+                this.send(body, {});
+            }
+        },
+
+        send: function(body, headers) {
+            headers['Content-Type'] = 'application/json';
+            fetch(this.endpoint, {
+                method: 'POST',
+                headers: headers,
+                body: body,
+                keepalive: true
+            }).then(function(r) { return r.json(); }).then(function(d) {
+                if (d.visitor_id) {
+                    if (analytics.stored) localStorage.setItem('_rp_vid', d.visitor_id);
+                    analytics.visitorId = d.visitor_id;
+                }
+                if (d.session_id) {
+                    if (analytics.stored) sessionStorage.setItem('_rp_sid', d.session_id);
+                    analytics.sessionId = d.session_id;
+                }
+                if (d.pageview_id) {
+                    analytics.pageviewId = d.pageview_id;
+                }
+            });
+        },
+
+        // HMAC-SHA256 of "<timestamp>.<body>" under the tracker key
+        sign: function(body) {
+            var encoder = new TextEncoder();
+            var timestamp = String(Date.now());
+            if (!this.signingKey) {
+                this.signingKey = crypto.subtle.importKey(
+                    'raw', encoder.encode(this.key), { name: 'HMAC', hash: 'SHA-256' }, false, ['sign']);
+            }
+            return this.signingKey.then(function(key) {
+                return crypto.subtle.sign('HMAC', key, encoder.encode(timestamp + '.' + body));
+            }).then(function(signature) {
+                var hex = Array.prototype.map.call(new Uint8Array(signature), function(b) {
+                    return ('0' + b.toString(16)).slice(-2);
+                }).join('');
+                return { 'X-Analytics-Timestamp': timestamp, 'X-Analytics-Signature': hex };
+            });
+        },
+
+        trackPageView: function() {
+            // Engagement so far belongs to the previous page (SPAs)
+            this.sendHeartbeat();
+            this.pageviewId = null;
+            this.engagedMs = 0;
+            this.track({
+                event_type: 'pageview',
+                path: location.pathname,
+                title: document.title,
+                referrer: document.referrer,
+                utm_source: this.getParam('utm_source'),
+                utm_medium: this.getParam('utm_medium'),
+                utm_campaign: this.getParam('utm_campaign')
+            });
+        },
+
+        trackEvent: function(category, action, label, value) {
+            this.track({
+                event_type: 'event',
+                path: location.pathname,
+                category: category,
+                action: action,
+                label: label,
+                value: value
+            });
+        },
+
+        // Visible time is sent every heartbeatInterval seconds and when the
+        // page is hidden or left, so the last page of a visit is measured too
+        sendHeartbeat: function() {
+            if (this.visibleSince !== null) {
+                var now = Date.now();
+                this.engagedMs += now - this.visibleSince;
+                this.visibleSince = now;
+            }
+            var seconds = Math.floor(this.engagedMs / 1000);
+            if (!this.pageviewId || seconds < 1) return;
+            this.engagedMs -= seconds * 1000;
+            this.track({
+                event_type: 'heartbeat',
+                path: location.pathname,
+                pageview_id: this.pageviewId,
+                engaged_seconds: seconds
+            });
+        },
+
+        setupEngagementTracking: function() {
+            setInterval(function() {
+                if (analytics.visibleSince !== null) analytics.sendHeartbeat();
+            }, this.heartbeatInterval * 1000);
+            document.addEventListener('visibilitychange', function() {
+                if (document.visibilityState === 'visible') {
+                    analytics.visibleSince = Date.now();
+                } else {
+                    analytics.sendHeartbeat();
+                    analytics.visibleSince = null;
+                }
+            });
+            window.addEventListener('pagehide', function() {
+                analytics.sendHeartbeat();
+            });
+        },
+
+        setupOutboundTracking: function() {
+            document.addEventListener('click', function(e) {
+                var link = e.target.closest('a');
+                if (link && link.hostname !== location.hostname) {
+                    analytics.trackEvent('outbound', 'click', link.href);
+                }
+            });
+        },
+
+        setupDownloadTracking: function() {
+            var exts = this.downloadExtensions;
+            document.addEventListener('click', function(e) {
+                var link = e.target.closest('a');
+                if (link) {
+                    var ext = link.pathname.split('.').pop().toLowerCase();
+                    if (exts.indexOf(ext) > -1) {
+                        analytics.trackEvent('download', ext, link.pathname);
+                    }
+                }
+            });
+        },
+
+        getParam: function(name) {
+            var params = new URLSearchParams(location.search);
+            return params.get(name);
+        }
+    };
+
+    analytics.init();
+    window.rpAnalytics = analytics;
+})();
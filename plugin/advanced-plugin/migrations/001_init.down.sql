@@ -0,0 +1,3 @@
+DROP TABLE IF EXISTS analytics_daily_stats;
+DROP TABLE IF EXISTS analytics_pageviews;
+DROP TABLE IF EXISTS analytics_sessions;
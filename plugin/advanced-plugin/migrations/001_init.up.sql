@@ -1,4 +1,5 @@
 -- RustPress Analytics - Initial Schema
+-- Idempotent so databases created before the migration ledger are adopted.
 
 -- Sessions table
 CREATE TABLE IF NOT EXISTS analytics_sessions (
@@ -50,14 +51,14 @@ CREATE TABLE IF NOT EXISTS analytics_daily_stats (
 );
 
 -- Indexes
-CREATE INDEX idx_sessions_visitor ON analytics_sessions(visitor_id);
-CREATE INDEX idx_sessions_started ON analytics_sessions(started_at DESC);
-CREATE INDEX idx_sessions_ended ON analytics_sessions(ended_at DESC);
-CREATE INDEX idx_sessions_device ON analytics_sessions(device_type);
-CREATE INDEX idx_sessions_country ON analytics_sessions(country);
+CREATE INDEX IF NOT EXISTS idx_sessions_visitor ON analytics_sessions(visitor_id);
+CREATE INDEX IF NOT EXISTS idx_sessions_started ON analytics_sessions(started_at DESC);
+CREATE INDEX IF NOT EXISTS idx_sessions_ended ON analytics_sessions(ended_at DESC);
+CREATE INDEX IF NOT EXISTS idx_sessions_device ON analytics_sessions(device_type);
+CREATE INDEX IF NOT EXISTS idx_sessions_country ON analytics_sessions(country);
 
-CREATE INDEX idx_pageviews_session ON analytics_pageviews(session_id);
-CREATE INDEX idx_pageviews_visitor ON analytics_pageviews(visitor_id);
-CREATE INDEX idx_pageviews_path ON analytics_pageviews(path);
-CREATE INDEX idx_pageviews_created ON analytics_pageviews(created_at DESC);
-CREATE INDEX idx_pageviews_date ON analytics_pageviews((created_at::date));
+CREATE INDEX IF NOT EXISTS idx_pageviews_session ON analytics_pageviews(session_id);
+CREATE INDEX IF NOT EXISTS idx_pageviews_visitor ON analytics_pageviews(visitor_id);
+CREATE INDEX IF NOT EXISTS idx_pageviews_path ON analytics_pageviews(path);
+CREATE INDEX IF NOT EXISTS idx_pageviews_created ON analytics_pageviews(created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_pageviews_date ON analytics_pageviews((created_at::date));
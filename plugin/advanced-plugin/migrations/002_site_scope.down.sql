@@ -0,0 +1,5 @@
+DROP INDEX IF EXISTS idx_pageviews_site_created;
+DROP INDEX IF EXISTS idx_sessions_site_visitor;
+
+ALTER TABLE analytics_pageviews DROP COLUMN IF EXISTS site_id;
+ALTER TABLE analytics_sessions DROP COLUMN IF EXISTS site_id;
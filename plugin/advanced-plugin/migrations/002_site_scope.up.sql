@@ -0,0 +1,9 @@
+-- RustPress Analytics - Site Scoping
+-- Sessions and page views belong to the blog site that served them. NULL is
+-- used for deployments without multisite.
+
+ALTER TABLE analytics_sessions ADD COLUMN IF NOT EXISTS site_id UUID;
+ALTER TABLE analytics_pageviews ADD COLUMN IF NOT EXISTS site_id UUID;
+
+CREATE INDEX IF NOT EXISTS idx_sessions_site_visitor ON analytics_sessions(site_id, visitor_id);
+CREATE INDEX IF NOT EXISTS idx_pageviews_site_created ON analytics_pageviews(site_id, created_at DESC);
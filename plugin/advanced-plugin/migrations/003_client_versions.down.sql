@@ -0,0 +1,5 @@
+DROP INDEX IF EXISTS idx_sessions_started_os;
+DROP INDEX IF EXISTS idx_sessions_started_browser;
+
+ALTER TABLE analytics_sessions DROP COLUMN IF EXISTS os_version;
+ALTER TABLE analytics_sessions DROP COLUMN IF EXISTS browser_version;
@@ -0,0 +1,10 @@
+-- RustPress Analytics - Client Versions
+-- Browser and OS versions as reported by the user agent ("118.0.5993"), so
+-- reports can break browsers and operating systems down by major version.
+-- Sessions tracked before this migration have no version (NULL).
+
+ALTER TABLE analytics_sessions ADD COLUMN IF NOT EXISTS browser_version VARCHAR(50);
+ALTER TABLE analytics_sessions ADD COLUMN IF NOT EXISTS os_version VARCHAR(50);
+
+CREATE INDEX IF NOT EXISTS idx_sessions_started_browser ON analytics_sessions(started_at, browser);
+CREATE INDEX IF NOT EXISTS idx_sessions_started_os ON analytics_sessions(started_at, os);
@@ -0,0 +1 @@
+ALTER TABLE analytics_pageviews DROP COLUMN IF EXISTS engagement_seconds;
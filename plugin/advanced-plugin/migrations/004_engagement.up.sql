@@ -0,0 +1,6 @@
+-- RustPress Analytics - Measured Engagement
+-- Seconds a page was visible, summed from the tracker's heartbeat pings.
+-- NULL until the first ping arrives (and for page views tracked before this
+-- migration); reports fall back to the time until the next page view then.
+
+ALTER TABLE analytics_pageviews ADD COLUMN IF NOT EXISTS engagement_seconds INTEGER;
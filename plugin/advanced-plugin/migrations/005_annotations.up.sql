@@ -0,0 +1,17 @@
+-- RustPress Analytics - Annotations
+-- Notes on the time series (deploys, campaigns, outages). An annotation
+-- covers `date` through `end_date`, or just `date` when there is no end.
+
+CREATE TABLE IF NOT EXISTS analytics_annotations (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    date DATE NOT NULL,
+    end_date DATE,
+    label VARCHAR(200) NOT NULL,
+    author_id UUID NOT NULL,
+    author_name VARCHAR(255) NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CHECK (end_date IS NULL OR end_date >= date)
+);
+
+CREATE INDEX IF NOT EXISTS idx_annotations_date ON analytics_annotations(date, end_date);
@@ -0,0 +1,10 @@
+-- RustPress Analytics - Rejected Hits
+-- `/track` hits refused by the per-IP and per-visitor quotas or the
+-- signature check, counted per day and reason.
+
+CREATE TABLE IF NOT EXISTS analytics_rejections (
+    date DATE NOT NULL,
+    reason VARCHAR(32) NOT NULL,
+    count BIGINT NOT NULL DEFAULT 0,
+    PRIMARY KEY (date, reason)
+);
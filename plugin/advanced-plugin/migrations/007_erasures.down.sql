@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS idx_pageviews_ip;
+DROP TABLE IF EXISTS analytics_erasures;
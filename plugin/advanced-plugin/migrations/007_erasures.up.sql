@@ -0,0 +1,23 @@
+-- RustPress Analytics - Erasure Requests
+-- Right-to-erasure requests for a visitor id or IP address, worked off in
+-- the background. The subject's identifier is cleared from the request once
+-- it's done; the counts remain as its completion report.
+
+CREATE TABLE IF NOT EXISTS analytics_erasures (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    subject VARCHAR(16) NOT NULL,
+    visitor_id UUID,
+    ip_address VARCHAR(45),
+    status VARCHAR(16) NOT NULL DEFAULT 'pending',
+    requested_by UUID NOT NULL,
+    visitors BIGINT,
+    sessions BIGINT,
+    pageviews BIGINT,
+    events BIGINT,
+    error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    completed_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_erasures_status ON analytics_erasures(status) WHERE status IN ('pending', 'running');
+CREATE INDEX IF NOT EXISTS idx_pageviews_ip ON analytics_pageviews(ip_address);
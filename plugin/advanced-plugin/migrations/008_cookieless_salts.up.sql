@@ -0,0 +1,8 @@
+-- RustPress Analytics - Cookieless Salts
+-- The day's salt for hashing cookieless visitors, shared by all instances.
+-- Only today's row is kept, so earlier hashes can't be recomputed.
+
+CREATE TABLE IF NOT EXISTS analytics_salts (
+    date DATE PRIMARY KEY,
+    salt BYTEA NOT NULL
+);
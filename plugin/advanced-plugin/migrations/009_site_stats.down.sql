@@ -0,0 +1,6 @@
+DROP INDEX IF EXISTS idx_daily_stats_site_date;
+
+-- Only the deployment-wide rows fit the date key
+DELETE FROM analytics_daily_stats WHERE site_id IS NOT NULL;
+ALTER TABLE analytics_daily_stats DROP COLUMN IF EXISTS site_id;
+ALTER TABLE analytics_daily_stats ADD PRIMARY KEY (date);
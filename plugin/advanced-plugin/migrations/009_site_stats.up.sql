@@ -0,0 +1,9 @@
+-- RustPress Analytics - Site Daily Stats
+-- Daily stats are rolled up per site. Days of deployments without multisite
+-- keep `site_id = NULL`, which the key treats as one site.
+
+ALTER TABLE analytics_daily_stats ADD COLUMN IF NOT EXISTS site_id UUID;
+ALTER TABLE analytics_daily_stats DROP CONSTRAINT IF EXISTS analytics_daily_stats_pkey;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_daily_stats_site_date
+    ON analytics_daily_stats((COALESCE(site_id, '00000000-0000-0000-0000-000000000000'::uuid)), date);
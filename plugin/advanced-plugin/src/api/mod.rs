@@ -4,48 +4,156 @@ use crate::models::*;
 use crate::services::*;
 use crate::AnalyticsPlugin;
 use axum::{
-    extract::{ConnectInfo, Query, State},
-    http::{HeaderMap, StatusCode},
-    response::IntoResponse,
-    routing::{get, post},
-    Json, Router,
+    body::Bytes,
+    extract::{ConnectInfo, Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post, put},
+    Json,
 };
+use rustpress_auth::problem::ProblemDetails;
+use rustpress_auth::routes::PluginRoutes;
+use rustpress_auth::{AuthUser, ValidatedJson};
 use std::net::SocketAddr;
 use std::sync::Arc;
+use utoipa::OpenApi;
+use uuid::Uuid;
 
-/// Create API routes
-pub fn create_routes(plugin: &AnalyticsPlugin) -> Router {
-    Router::new()
-        // Public tracking endpoint
+/// Create API routes, mounted under `/api/v1/rustpress-analytics`
+pub fn create_routes(plugin: &AnalyticsPlugin) -> PluginRoutes {
+    PluginRoutes::new(plugin.info.id.clone())
+        // Public tracking endpoint and script
         .route("/track", post(track_event))
+        .route("/policy", get(get_policy))
+        // Server-to-server ingestion (API key)
+        .route("/collect", post(collect_events))
+        .route(crate::tracker::TRACKER_PATH, get(crate::tracker::serve_tracker))
         // Protected analytics endpoints
         .route("/pageviews", get(get_pageviews))
         .route("/visitors", get(get_visitors))
         .route("/realtime", get(get_realtime))
         .route("/reports/overview", get(get_overview_report))
         .route("/reports/pages", get(get_pages_report))
+        .route("/reports/landing-pages", get(get_landing_pages_report))
+        .route("/reports/landing-pages/utm", get(get_landing_page_utm_report))
+        .route("/reports/exit-pages", get(get_exit_pages_report))
         .route("/reports/referrers", get(get_referrers_report))
         .route("/reports/devices", get(get_devices_report))
+        .route("/reports/browsers", get(get_browsers_report))
+        .route("/reports/os", get(get_os_report))
         .route("/reports/geography", get(get_geography_report))
+        .route("/reports/rejections", get(get_rejections_report))
         .route("/reports/export", post(export_report))
+        // Annotations
+        .route("/annotations", get(list_annotations).post(create_annotation))
+        .route("/annotations/:id", put(update_annotation).delete(delete_annotation))
+        // Right to erasure (admin)
+        .route("/privacy/forget", post(forget))
+        .route("/privacy/forget/:id", get(get_erasure))
+        // Event sinks (admin)
+        .route("/sinks", get(list_sinks))
+        // API documentation
+        .route("/openapi.json", get(|| async { Json(ApiDoc::openapi()) }))
+        .route("/docs", get(swagger_ui))
+        .layer(axum::middleware::from_fn(rustpress_auth::problem::trace_id))
+}
+
+// ============================================
+// API Documentation
+// ============================================
+
+/// Analytics API specification, generated from the handler annotations
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "RustPress Analytics API"),
+    servers((url = "/api/v1/rustpress-analytics")),
+    paths(
+        track_event,
+        get_policy,
+        collect_events,
+        crate::tracker::serve_tracker,
+        get_pageviews,
+        get_visitors,
+        get_realtime,
+        get_overview_report,
+        get_pages_report,
+        get_landing_pages_report,
+        get_landing_page_utm_report,
+        get_exit_pages_report,
+        get_referrers_report,
+        get_devices_report,
+        get_browsers_report,
+        get_os_report,
+        get_geography_report,
+        get_rejections_report,
+        export_report,
+        list_annotations,
+        create_annotation,
+        update_annotation,
+        delete_annotation,
+        forget,
+        get_erasure,
+        list_sinks,
+    ),
+    tags(
+        (name = "analytics", description = "Tracking and reports"),
+        (name = "annotations", description = "Notes on the time series"),
+        (name = "privacy", description = "Right-to-erasure requests"),
+        (name = "sinks", description = "Mirroring to external systems"),
+    )
+)]
+pub struct ApiDoc;
+
+/// GET /api/v1/rustpress-analytics/docs
+async fn swagger_ui() -> Response {
+    rustpress_auth::openapi::swagger_ui("openapi.json")
 }
 
 // ============================================
 // Tracking Endpoint
 // ============================================
 
-/// POST /api/v1/analytics/track
+/// POST /api/v1/rustpress-analytics/track
+#[utoipa::path(
+    post,
+    path = "/track",
+    tag = "analytics",
+    request_body = TrackingInput,
+    params(
+        ("X-Analytics-Timestamp" = Option<String>, Header, description = "When the hit was signed, in Unix milliseconds"),
+        ("X-Analytics-Signature" = Option<String>, Header, description = "Hex HMAC-SHA256 of `<timestamp>.<body>` under the tracker key"),
+    ),
+    responses(
+        (status = 200, description = "Tracked, or skipped by exclusion or region rules", body = TrackResponse),
+        (status = 400, description = "Invalid event", body = ProblemDetails),
+        (status = 401, description = "Signature required and missing or invalid", body = ProblemDetails),
+        (status = 429, description = "Over the IP or visitor quota", body = ProblemDetails),
+        (status = 500, description = "Tracking failed", body = ProblemDetails),
+        (status = 503, description = "Service unavailable", body = ProblemDetails),
+    ),
+)]
 pub async fn track_event(
     State(plugin): State<Arc<AnalyticsPlugin>>,
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
     headers: HeaderMap,
-    Json(input): Json<TrackingInput>,
-) -> impl IntoResponse {
-    let Some(tracking) = plugin.tracking().await else {
-        return (StatusCode::SERVICE_UNAVAILABLE, Json(serde_json::json!({
-            "error": "Tracking service unavailable"
-        })));
-    };
+    body: Bytes,
+) -> Result<Json<TrackResponse>, ProblemDetails> {
+    let tracking = plugin
+        .tracking()
+        .await
+        .ok_or_else(|| ProblemDetails::unavailable("Tracking service unavailable"))?;
+
+    // Quotas and signatures are checked before the body is even parsed
+    let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+    tracking
+        .admit(Some(addr.ip()), header(SIGNATURE_HEADER), header(TIMESTAMP_HEADER), &body)
+        .map_err(rejected)?;
+
+    let input: TrackingInput = serde_json::from_slice(&body)
+        .map_err(|e| ProblemDetails::new(StatusCode::BAD_REQUEST, "invalid_event").detail(e.to_string()))?;
+    if let Some(visitor_id) = input.visitor_id {
+        tracking.admit_visitor(visitor_id).map_err(rejected)?;
+    }
 
     let user_agent = headers
         .get("user-agent")
@@ -55,50 +163,179 @@ pub async fn track_event(
     let ip = Some(addr.ip());
 
     match input.event_type.as_str() {
-        "pageview" => {
-            match tracking.track_pageview(&input, ip, user_agent).await {
-                Ok((visitor_id, session_id)) => {
-                    (StatusCode::OK, Json(serde_json::json!({
-                        "success": true,
-                        "visitor_id": visitor_id,
-                        "session_id": session_id
-                    })))
-                }
-                Err(TrackingError::Disabled) |
-                Err(TrackingError::ExcludedPath) |
-                Err(TrackingError::ExcludedIP) => {
-                    (StatusCode::OK, Json(serde_json::json!({
-                        "success": true,
-                        "tracked": false
-                    })))
-                }
-                Err(e) => {
-                    tracing::error!("Tracking error: {:?}", e);
-                    (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({
-                        "error": "Tracking failed"
-                    })))
-                }
+        "pageview" => match tracking.track_pageview(&input, ip, user_agent).await {
+            Ok((visitor_id, session_id, pageview_id)) => Ok(Json(TrackResponse {
+                success: true,
+                tracked: None,
+                visitor_id: Some(visitor_id),
+                session_id: Some(session_id),
+                pageview_id: Some(pageview_id),
+            })),
+            Err(TrackingError::Disabled)
+            | Err(TrackingError::ExcludedPath)
+            | Err(TrackingError::ExcludedIP)
+            | Err(TrackingError::RegionDisabled) => Ok(Json(TrackResponse {
+                success: true,
+                tracked: Some(false),
+                visitor_id: None,
+                session_id: None,
+                pageview_id: None,
+            })),
+            Err(e) => {
+                tracing::error!("Tracking error: {:?}", e);
+                Err(ProblemDetails::internal("Tracking failed"))
             }
-        }
-        "event" => {
-            match tracking.track_event(&input).await {
-                Ok(()) => {
-                    (StatusCode::OK, Json(serde_json::json!({
-                        "success": true
-                    })))
-                }
-                Err(e) => {
-                    tracing::error!("Event tracking error: {:?}", e);
-                    (StatusCode::BAD_REQUEST, Json(serde_json::json!({
-                        "error": format!("{}", e)
-                    })))
-                }
+        },
+        "event" => match tracking.track_event(&input, ip).await {
+            Ok(()) => Ok(Json(TrackResponse {
+                success: true,
+                tracked: None,
+                visitor_id: None,
+                session_id: None,
+                pageview_id: None,
+            })),
+            Err(TrackingError::Disabled) | Err(TrackingError::RegionDisabled) => Ok(Json(TrackResponse {
+                success: true,
+                tracked: Some(false),
+                visitor_id: None,
+                session_id: None,
+                pageview_id: None,
+            })),
+            Err(e) => {
+                tracing::error!("Event tracking error: {:?}", e);
+                Err(ProblemDetails::new(StatusCode::BAD_REQUEST, "invalid_event").detail(e.to_string()))
+            }
+        },
+        "heartbeat" => match tracking.track_heartbeat(&input, ip).await {
+            Ok(tracked) => Ok(Json(TrackResponse {
+                success: true,
+                tracked: Some(tracked),
+                visitor_id: None,
+                session_id: None,
+                pageview_id: None,
+            })),
+            Err(TrackingError::Disabled) | Err(TrackingError::RegionDisabled) => Ok(Json(TrackResponse {
+                success: true,
+                tracked: Some(false),
+                visitor_id: None,
+                session_id: None,
+                pageview_id: None,
+            })),
+            Err(TrackingError::Database(e)) => {
+                tracing::error!("Heartbeat tracking error: {}", e);
+                Err(ProblemDetails::internal("Tracking failed"))
             }
+            Err(e) => Err(ProblemDetails::new(StatusCode::BAD_REQUEST, "invalid_event").detail(e.to_string())),
+        },
+        _ => Err(ProblemDetails::new(StatusCode::BAD_REQUEST, "invalid_event_type")
+            .detail("event_type must be \"pageview\", \"event\" or \"heartbeat\"")),
+    }
+}
+
+/// GET /api/v1/rustpress-analytics/policy
+///
+/// The tracker asks before its first hit whether, and how, to track the
+/// visitor; consent banners can read the same answer from
+/// `rpAnalytics.policy`.
+#[utoipa::path(
+    get,
+    path = "/policy",
+    tag = "analytics",
+    responses(
+        (status = 200, description = "Tracking mode for the visitor's region and the site's legal basis", body = TrackingPolicy),
+        (status = 503, description = "Service unavailable", body = ProblemDetails),
+    ),
+)]
+pub async fn get_policy(
+    State(plugin): State<Arc<AnalyticsPlugin>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+) -> Result<Response, ProblemDetails> {
+    let tracking = plugin
+        .tracking()
+        .await
+        .ok_or_else(|| ProblemDetails::unavailable("Tracking service unavailable"))?;
+
+    let policy = tracking.policy(Some(addr.ip()));
+    Ok(([(header::CACHE_CONTROL, "private, max-age=300")], Json(policy)).into_response())
+}
+
+/// Header carrying the tracker's signature of a hit
+pub const SIGNATURE_HEADER: &str = "x-analytics-signature";
+
+/// Header carrying when a hit was signed, in Unix milliseconds
+pub const TIMESTAMP_HEADER: &str = "x-analytics-timestamp";
+
+fn rejected(reason: RejectionReason) -> ProblemDetails {
+    match reason {
+        RejectionReason::BadSignature => ProblemDetails::new(StatusCode::UNAUTHORIZED, "invalid_signature")
+            .detail("Hits must be signed by the tracker"),
+        RejectionReason::IpQuota | RejectionReason::VisitorQuota => {
+            ProblemDetails::new(StatusCode::TOO_MANY_REQUESTS, "quota_exceeded")
+                .detail(format!("Tracking quota exceeded ({})", reason.as_str()))
         }
-        _ => {
-            (StatusCode::BAD_REQUEST, Json(serde_json::json!({
-                "error": "Invalid event type"
-            })))
+    }
+}
+
+/// Header carrying a `/collect` API key
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// POST /api/v1/rustpress-analytics/collect
+///
+/// Batches of events from backends (cron jobs, payment webhooks) with their
+/// own timestamps and visitor ids, authenticated by an `X-Api-Key` from the
+/// `analytics.collect_keys` secret.
+#[utoipa::path(
+    post,
+    path = "/collect",
+    tag = "analytics",
+    request_body = CollectRequest,
+    params(("X-Api-Key" = String, Header, description = "Collection API key")),
+    responses(
+        (status = 200, description = "Valid events stored; invalid ones listed", body = CollectResponse),
+        (status = 400, description = "Empty or oversized batch", body = ProblemDetails),
+        (status = 401, description = "Missing or unknown API key", body = ProblemDetails),
+        (status = 500, description = "Ingestion failed", body = ProblemDetails),
+        (status = 503, description = "Collection not configured", body = ProblemDetails),
+    ),
+)]
+pub async fn collect_events(
+    State(plugin): State<Arc<AnalyticsPlugin>>,
+    headers: HeaderMap,
+    Json(request): Json<CollectRequest>,
+) -> Result<Json<CollectResponse>, ProblemDetails> {
+    let ingest = plugin
+        .ingest()
+        .await
+        .ok_or_else(|| ProblemDetails::unavailable("Ingest service unavailable"))?;
+
+    let key = headers
+        .get(API_KEY_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .ok_or_else(|| ProblemDetails::unauthorized("X-Api-Key header required"))?;
+    if !ingest.authorize(key).await? {
+        return Err(ProblemDetails::unauthorized("Invalid API key"));
+    }
+
+    if request.events.is_empty() || request.events.len() > MAX_COLLECT_BATCH {
+        return Err(ProblemDetails::new(StatusCode::BAD_REQUEST, "invalid_batch")
+            .detail(format!("events must hold 1 to {} events", MAX_COLLECT_BATCH)));
+    }
+
+    Ok(Json(ingest.collect(&request.events).await?))
+}
+
+impl From<IngestError> for ProblemDetails {
+    fn from(e: IngestError) -> Self {
+        match e {
+            IngestError::Disabled => ProblemDetails::unavailable("Server-side collection is not configured"),
+            IngestError::Secrets(msg) => {
+                tracing::error!("Failed to read collection keys: {}", msg);
+                ProblemDetails::internal("Ingestion failed")
+            }
+            IngestError::Database(e) => {
+                tracing::error!("Ingestion database error: {}", e);
+                ProblemDetails::internal("Ingestion failed")
+            }
         }
     }
 }
@@ -107,229 +344,750 @@ pub async fn track_event(
 // Analytics Endpoints
 // ============================================
 
-/// GET /api/v1/analytics/pageviews
+/// GET /api/v1/rustpress-analytics/pageviews
+#[utoipa::path(
+    get,
+    path = "/pageviews",
+    tag = "analytics",
+    params(ReportQuery),
+    responses(
+        (status = 200, description = "Page views in range", body = ListResponse<PageView>),
+        (status = 500, description = "Query failed", body = ProblemDetails),
+        (status = 503, description = "Service unavailable", body = ProblemDetails),
+    ),
+)]
 pub async fn get_pageviews(
     State(plugin): State<Arc<AnalyticsPlugin>>,
     Query(query): Query<ReportQuery>,
-) -> impl IntoResponse {
-    let Some(analytics) = plugin.analytics().await else {
-        return (StatusCode::SERVICE_UNAVAILABLE, Json(serde_json::json!({
-            "error": "Analytics service unavailable"
-        })));
-    };
-
-    match analytics.get_pageviews(&query).await {
-        Ok(views) => (StatusCode::OK, Json(serde_json::json!({
-            "data": views,
-            "count": views.len()
-        }))),
-        Err(e) => {
-            tracing::error!("Failed to get pageviews: {:?}", e);
-            (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({
-                "error": "Failed to fetch pageviews"
-            })))
-        }
-    }
+) -> Result<Json<ListResponse<PageView>>, ProblemDetails> {
+    let analytics = analytics_service(&plugin).await?;
+
+    let views = analytics.get_pageviews(&query).await.map_err(|e| {
+        tracing::error!("Failed to get pageviews: {:?}", e);
+        ProblemDetails::internal("Failed to fetch pageviews")
+    })?;
+
+    Ok(Json(ListResponse {
+        count: Some(views.len()),
+        data: views,
+        range: Some(analytics.range(&query)),
+    }))
 }
 
-/// GET /api/v1/analytics/visitors
+/// GET /api/v1/rustpress-analytics/visitors
+#[utoipa::path(
+    get,
+    path = "/visitors",
+    tag = "analytics",
+    params(ReportQuery),
+    responses(
+        (status = 200, description = "Visitor totals with the period's annotations", body = VisitorsResponse),
+        (status = 500, description = "Query failed", body = ProblemDetails),
+        (status = 503, description = "Service unavailable", body = ProblemDetails),
+    ),
+)]
 pub async fn get_visitors(
     State(plugin): State<Arc<AnalyticsPlugin>>,
     Query(query): Query<ReportQuery>,
-) -> impl IntoResponse {
-    let Some(analytics) = plugin.analytics().await else {
-        return (StatusCode::SERVICE_UNAVAILABLE, Json(serde_json::json!({
-            "error": "Analytics service unavailable"
-        })));
-    };
-
-    match analytics.get_daily_stats(&query).await {
-        Ok(stats) => {
-            let total_visitors: i64 = stats.iter().map(|s| s.unique_visitors).sum();
-            (StatusCode::OK, Json(serde_json::json!({
-                "total": total_visitors,
-                "daily": stats
-            })))
-        }
-        Err(e) => {
-            tracing::error!("Failed to get visitors: {:?}", e);
-            (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({
-                "error": "Failed to fetch visitors"
-            })))
-        }
-    }
+) -> Result<Json<VisitorsResponse>, ProblemDetails> {
+    let analytics = analytics_service(&plugin).await?;
+
+    let stats = analytics.get_daily_stats(&query).await.map_err(|e| {
+        tracing::error!("Failed to get visitors: {:?}", e);
+        ProblemDetails::internal("Failed to fetch visitors")
+    })?;
+
+    let annotations = analytics.get_annotations(&query).await.map_err(|e| {
+        tracing::error!("Failed to get annotations: {:?}", e);
+        ProblemDetails::internal("Failed to fetch visitors")
+    })?;
+
+    Ok(Json(VisitorsResponse {
+        range: analytics.range(&query),
+        total: stats.iter().map(|s| s.unique_visitors).sum(),
+        daily: stats,
+        annotations,
+    }))
 }
 
-/// GET /api/v1/analytics/realtime
+/// GET /api/v1/rustpress-analytics/realtime
+#[utoipa::path(
+    get,
+    path = "/realtime",
+    tag = "analytics",
+    params(SiteQuery),
+    responses(
+        (status = 200, description = "Active visitors", body = RealtimeResponse),
+        (status = 400, description = "Real-time tracking disabled", body = ProblemDetails),
+        (status = 500, description = "Query failed", body = ProblemDetails),
+        (status = 503, description = "Service unavailable", body = ProblemDetails),
+    ),
+)]
 pub async fn get_realtime(
     State(plugin): State<Arc<AnalyticsPlugin>>,
-) -> impl IntoResponse {
+    Query(query): Query<SiteQuery>,
+) -> Result<Json<RealtimeResponse>, ProblemDetails> {
     let config = plugin.config().await;
     if !config.realtime_enabled {
-        return (StatusCode::BAD_REQUEST, Json(serde_json::json!({
-            "error": "Real-time tracking is disabled"
-        })));
+        return Err(ProblemDetails::new(StatusCode::BAD_REQUEST, "realtime_disabled")
+            .detail("Real-time tracking is disabled"));
     }
 
-    let Some(analytics) = plugin.analytics().await else {
-        return (StatusCode::SERVICE_UNAVAILABLE, Json(serde_json::json!({
-            "error": "Analytics service unavailable"
-        })));
-    };
-
-    match analytics.get_realtime_visitors().await {
-        Ok(visitors) => (StatusCode::OK, Json(serde_json::json!({
-            "active_visitors": visitors.len(),
-            "visitors": visitors
-        }))),
-        Err(e) => {
-            tracing::error!("Failed to get realtime: {:?}", e);
-            (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({
-                "error": "Failed to fetch realtime data"
-            })))
-        }
-    }
+    let analytics = analytics_service(&plugin).await?;
+
+    let visitors = analytics.get_realtime_visitors(query.site_id).await.map_err(|e| {
+        tracing::error!("Failed to get realtime: {:?}", e);
+        ProblemDetails::internal("Failed to fetch realtime data")
+    })?;
+
+    Ok(Json(RealtimeResponse {
+        active_visitors: visitors.len(),
+        visitors,
+    }))
 }
 
 // ============================================
 // Report Endpoints
 // ============================================
 
-/// GET /api/v1/analytics/reports/overview
+/// GET /api/v1/rustpress-analytics/reports/overview
+#[utoipa::path(
+    get,
+    path = "/reports/overview",
+    tag = "analytics",
+    params(ReportQuery),
+    responses(
+        (status = 200, description = "Overview report with the period's annotations", body = OverviewReport),
+        (status = 500, description = "Report failed", body = ProblemDetails),
+        (status = 503, description = "Service unavailable, or a report query timed out", body = ProblemDetails),
+    ),
+)]
 pub async fn get_overview_report(
     State(plugin): State<Arc<AnalyticsPlugin>>,
     Query(query): Query<ReportQuery>,
-) -> impl IntoResponse {
-    let Some(reports) = plugin.reports().await else {
-        return (StatusCode::SERVICE_UNAVAILABLE, Json(serde_json::json!({
-            "error": "Report service unavailable"
-        })));
-    };
-
-    match reports.get_overview(&query).await {
-        Ok(report) => (StatusCode::OK, Json(report)),
-        Err(e) => {
-            tracing::error!("Failed to get overview report: {:?}", e);
-            (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({
-                "error": "Failed to generate report"
-            })))
-        }
-    }
+) -> Result<Json<OverviewReport>, ProblemDetails> {
+    let reports = report_service(&plugin).await?;
+
+    let data = reports.get_overview(&query).await.map_err(|e| report_problem("overview", e))?;
+
+    Ok(Json(data))
 }
 
-/// GET /api/v1/analytics/reports/pages
+/// GET /api/v1/rustpress-analytics/reports/pages
+#[utoipa::path(
+    get,
+    path = "/reports/pages",
+    tag = "analytics",
+    params(ReportQuery),
+    responses(
+        (status = 200, description = "Top pages", body = ListResponse<PageReport>),
+        (status = 500, description = "Report failed", body = ProblemDetails),
+        (status = 503, description = "Service unavailable, or a report query timed out", body = ProblemDetails),
+    ),
+)]
 pub async fn get_pages_report(
     State(plugin): State<Arc<AnalyticsPlugin>>,
     Query(query): Query<ReportQuery>,
-) -> impl IntoResponse {
-    let Some(reports) = plugin.reports().await else {
-        return (StatusCode::SERVICE_UNAVAILABLE, Json(serde_json::json!({
-            "error": "Report service unavailable"
-        })));
-    };
-
-    match reports.get_pages(&query).await {
-        Ok(pages) => (StatusCode::OK, Json(serde_json::json!({
-            "data": pages
-        }))),
-        Err(e) => {
-            tracing::error!("Failed to get pages report: {:?}", e);
-            (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({
-                "error": "Failed to generate report"
-            })))
-        }
+) -> Result<Json<ListResponse<PageReport>>, ProblemDetails> {
+    let reports = report_service(&plugin).await?;
+
+    let data = reports.get_pages(&query).await.map_err(|e| report_problem("pages", e))?;
+
+    Ok(Json(ListResponse {
+        data,
+        count: None,
+        range: Some(reports.range(&query)),
+    }))
+}
+
+/// GET /api/v1/rustpress-analytics/reports/landing-pages
+#[utoipa::path(
+    get,
+    path = "/reports/landing-pages",
+    tag = "analytics",
+    params(ReportQuery),
+    responses(
+        (status = 200, description = "Sessions by entry page", body = ListResponse<LandingPageReport>),
+        (status = 500, description = "Report failed", body = ProblemDetails),
+        (status = 503, description = "Service unavailable, or a report query timed out", body = ProblemDetails),
+    ),
+)]
+pub async fn get_landing_pages_report(
+    State(plugin): State<Arc<AnalyticsPlugin>>,
+    Query(query): Query<ReportQuery>,
+) -> Result<Json<ListResponse<LandingPageReport>>, ProblemDetails> {
+    let reports = report_service(&plugin).await?;
+
+    let data = reports.get_landing_pages(&query).await.map_err(|e| report_problem("landing pages", e))?;
+
+    Ok(Json(ListResponse {
+        data,
+        count: None,
+        range: Some(reports.range(&query)),
+    }))
+}
+
+/// GET /api/v1/rustpress-analytics/reports/landing-pages/utm
+#[utoipa::path(
+    get,
+    path = "/reports/landing-pages/utm",
+    tag = "analytics",
+    params(LandingPageQuery),
+    responses(
+        (status = 200, description = "UTM campaigns of one landing page", body = ListResponse<UtmReport>),
+        (status = 400, description = "Missing path", body = ProblemDetails),
+        (status = 500, description = "Report failed", body = ProblemDetails),
+        (status = 503, description = "Service unavailable, or a report query timed out", body = ProblemDetails),
+    ),
+)]
+pub async fn get_landing_page_utm_report(
+    State(plugin): State<Arc<AnalyticsPlugin>>,
+    Query(query): Query<LandingPageQuery>,
+) -> Result<Json<ListResponse<UtmReport>>, ProblemDetails> {
+    if query.path.is_empty() {
+        return Err(ProblemDetails::new(StatusCode::BAD_REQUEST, "invalid_path")
+            .detail("path must name a landing page"));
     }
+
+    let reports = report_service(&plugin).await?;
+
+    let data = reports.get_landing_page_utm(&query).await.map_err(|e| report_problem("landing page UTM", e))?;
+
+    Ok(Json(ListResponse {
+        data,
+        count: None,
+        range: Some(reports.range(&query.report_query())),
+    }))
 }
 
-/// GET /api/v1/analytics/reports/referrers
+/// GET /api/v1/rustpress-analytics/reports/exit-pages
+#[utoipa::path(
+    get,
+    path = "/reports/exit-pages",
+    tag = "analytics",
+    params(ReportQuery),
+    responses(
+        (status = 200, description = "Sessions by exit page", body = ListResponse<ExitPageReport>),
+        (status = 500, description = "Report failed", body = ProblemDetails),
+        (status = 503, description = "Service unavailable, or a report query timed out", body = ProblemDetails),
+    ),
+)]
+pub async fn get_exit_pages_report(
+    State(plugin): State<Arc<AnalyticsPlugin>>,
+    Query(query): Query<ReportQuery>,
+) -> Result<Json<ListResponse<ExitPageReport>>, ProblemDetails> {
+    let reports = report_service(&plugin).await?;
+
+    let data = reports.get_exit_pages(&query).await.map_err(|e| report_problem("exit pages", e))?;
+
+    Ok(Json(ListResponse {
+        data,
+        count: None,
+        range: Some(reports.range(&query)),
+    }))
+}
+
+/// GET /api/v1/rustpress-analytics/reports/referrers
+#[utoipa::path(
+    get,
+    path = "/reports/referrers",
+    tag = "analytics",
+    params(ReportQuery),
+    responses(
+        (status = 200, description = "Referrer sources", body = ListResponse<ReferrerReport>),
+        (status = 500, description = "Report failed", body = ProblemDetails),
+        (status = 503, description = "Service unavailable, or a report query timed out", body = ProblemDetails),
+    ),
+)]
 pub async fn get_referrers_report(
     State(plugin): State<Arc<AnalyticsPlugin>>,
     Query(query): Query<ReportQuery>,
-) -> impl IntoResponse {
-    let Some(reports) = plugin.reports().await else {
-        return (StatusCode::SERVICE_UNAVAILABLE, Json(serde_json::json!({
-            "error": "Report service unavailable"
-        })));
-    };
-
-    match reports.get_referrers(&query).await {
-        Ok(referrers) => (StatusCode::OK, Json(serde_json::json!({
-            "data": referrers
-        }))),
-        Err(e) => {
-            tracing::error!("Failed to get referrers report: {:?}", e);
-            (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({
-                "error": "Failed to generate report"
-            })))
-        }
-    }
+) -> Result<Json<ListResponse<ReferrerReport>>, ProblemDetails> {
+    let reports = report_service(&plugin).await?;
+
+    let data = reports.get_referrers(&query).await.map_err(|e| report_problem("referrers", e))?;
+
+    Ok(Json(ListResponse {
+        data,
+        count: None,
+        range: Some(reports.range(&query)),
+    }))
 }
 
-/// GET /api/v1/analytics/reports/devices
+/// GET /api/v1/rustpress-analytics/reports/devices
+#[utoipa::path(
+    get,
+    path = "/reports/devices",
+    tag = "analytics",
+    params(ReportQuery),
+    responses(
+        (status = 200, description = "Device breakdown", body = ListResponse<DeviceReport>),
+        (status = 500, description = "Report failed", body = ProblemDetails),
+        (status = 503, description = "Service unavailable, or a report query timed out", body = ProblemDetails),
+    ),
+)]
 pub async fn get_devices_report(
     State(plugin): State<Arc<AnalyticsPlugin>>,
     Query(query): Query<ReportQuery>,
-) -> impl IntoResponse {
-    let Some(reports) = plugin.reports().await else {
-        return (StatusCode::SERVICE_UNAVAILABLE, Json(serde_json::json!({
-            "error": "Report service unavailable"
-        })));
-    };
-
-    match reports.get_devices(&query).await {
-        Ok(devices) => (StatusCode::OK, Json(serde_json::json!({
-            "data": devices
-        }))),
-        Err(e) => {
-            tracing::error!("Failed to get devices report: {:?}", e);
-            (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({
-                "error": "Failed to generate report"
-            })))
-        }
-    }
+) -> Result<Json<ListResponse<DeviceReport>>, ProblemDetails> {
+    let reports = report_service(&plugin).await?;
+
+    let data = reports.get_devices(&query).await.map_err(|e| report_problem("devices", e))?;
+
+    Ok(Json(ListResponse {
+        data,
+        count: None,
+        range: Some(reports.range(&query)),
+    }))
+}
+
+/// GET /api/v1/rustpress-analytics/reports/browsers
+#[utoipa::path(
+    get,
+    path = "/reports/browsers",
+    tag = "analytics",
+    params(ReportQuery),
+    responses(
+        (status = 200, description = "Browsers by major version, with the previous period", body = ListResponse<BrowserReport>),
+        (status = 500, description = "Report failed", body = ProblemDetails),
+        (status = 503, description = "Service unavailable, or a report query timed out", body = ProblemDetails),
+    ),
+)]
+pub async fn get_browsers_report(
+    State(plugin): State<Arc<AnalyticsPlugin>>,
+    Query(query): Query<ReportQuery>,
+) -> Result<Json<ListResponse<BrowserReport>>, ProblemDetails> {
+    let reports = report_service(&plugin).await?;
+
+    let data = reports.get_browsers(&query).await.map_err(|e| report_problem("browsers", e))?;
+
+    Ok(Json(ListResponse {
+        data,
+        count: None,
+        range: Some(reports.range(&query)),
+    }))
 }
 
-/// GET /api/v1/analytics/reports/geography
+/// GET /api/v1/rustpress-analytics/reports/os
+#[utoipa::path(
+    get,
+    path = "/reports/os",
+    tag = "analytics",
+    params(ReportQuery),
+    responses(
+        (status = 200, description = "Operating systems by major version, with the previous period", body = ListResponse<OsReport>),
+        (status = 500, description = "Report failed", body = ProblemDetails),
+        (status = 503, description = "Service unavailable, or a report query timed out", body = ProblemDetails),
+    ),
+)]
+pub async fn get_os_report(
+    State(plugin): State<Arc<AnalyticsPlugin>>,
+    Query(query): Query<ReportQuery>,
+) -> Result<Json<ListResponse<OsReport>>, ProblemDetails> {
+    let reports = report_service(&plugin).await?;
+
+    let data = reports.get_operating_systems(&query).await.map_err(|e| report_problem("OS", e))?;
+
+    Ok(Json(ListResponse {
+        data,
+        count: None,
+        range: Some(reports.range(&query)),
+    }))
+}
+
+/// GET /api/v1/rustpress-analytics/reports/geography
+#[utoipa::path(
+    get,
+    path = "/reports/geography",
+    tag = "analytics",
+    params(ReportQuery),
+    responses(
+        (status = 200, description = "Geographic breakdown", body = ListResponse<GeoReport>),
+        (status = 500, description = "Report failed", body = ProblemDetails),
+        (status = 503, description = "Service unavailable, or a report query timed out", body = ProblemDetails),
+    ),
+)]
 pub async fn get_geography_report(
     State(plugin): State<Arc<AnalyticsPlugin>>,
     Query(query): Query<ReportQuery>,
-) -> impl IntoResponse {
-    let Some(reports) = plugin.reports().await else {
-        return (StatusCode::SERVICE_UNAVAILABLE, Json(serde_json::json!({
-            "error": "Report service unavailable"
-        })));
-    };
-
-    match reports.get_geography(&query).await {
-        Ok(geo) => (StatusCode::OK, Json(serde_json::json!({
-            "data": geo
-        }))),
-        Err(e) => {
-            tracing::error!("Failed to get geography report: {:?}", e);
-            (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({
-                "error": "Failed to generate report"
-            })))
+) -> Result<Json<ListResponse<GeoReport>>, ProblemDetails> {
+    let reports = report_service(&plugin).await?;
+
+    let data = reports.get_geography(&query).await.map_err(|e| report_problem("geography", e))?;
+
+    Ok(Json(ListResponse {
+        data,
+        count: None,
+        range: Some(reports.range(&query)),
+    }))
+}
+
+/// GET /api/v1/rustpress-analytics/reports/rejections
+///
+/// Hits to `/track` refused by the quotas or the signature check, per day and
+/// reason; up to date as of the request.
+#[utoipa::path(
+    get,
+    path = "/reports/rejections",
+    tag = "analytics",
+    params(ReportQuery),
+    responses(
+        (status = 200, description = "Refused hits per day and reason", body = ListResponse<RejectionReport>),
+        (status = 500, description = "Report failed", body = ProblemDetails),
+        (status = 503, description = "Service unavailable, or a report query timed out", body = ProblemDetails),
+    ),
+)]
+pub async fn get_rejections_report(
+    State(plugin): State<Arc<AnalyticsPlugin>>,
+    Query(query): Query<ReportQuery>,
+) -> Result<Json<ListResponse<RejectionReport>>, ProblemDetails> {
+    let reports = report_service(&plugin).await?;
+
+    if let Some(tracking) = plugin.tracking().await {
+        if let Err(e) = tracking.flush_rejections().await {
+            tracing::warn!("Failed to store rejected hit counts: {:?}", e);
         }
     }
+
+    let data = reports.get_rejections(&query).await.map_err(|e| report_problem("rejections", e))?;
+
+    Ok(Json(ListResponse {
+        data,
+        count: None,
+        range: Some(reports.range(&query)),
+    }))
 }
 
-/// POST /api/v1/analytics/reports/export
+/// POST /api/v1/rustpress-analytics/reports/export
+#[utoipa::path(
+    post,
+    path = "/reports/export",
+    tag = "analytics",
+    request_body = ExportParams,
+    responses(
+        (status = 200, description = "Export started", body = ExportResponse),
+    ),
+)]
 pub async fn export_report(
     State(plugin): State<Arc<AnalyticsPlugin>>,
     Json(params): Json<ExportParams>,
-) -> impl IntoResponse {
+) -> Json<ExportResponse> {
     // Export implementation
-    (StatusCode::OK, Json(serde_json::json!({
-        "message": "Export started",
-        "format": params.format,
-        "download_url": "/api/v1/analytics/exports/12345"
-    })))
+    Json(ExportResponse {
+        message: "Export started".to_string(),
+        format: params.format,
+        download_url: "/api/v1/rustpress-analytics/exports/12345".to_string(),
+    })
 }
 
-#[derive(serde::Deserialize)]
+#[derive(serde::Deserialize, utoipa::ToSchema)]
 pub struct ExportParams {
     pub format: String, // "csv" | "json" | "pdf"
     pub report_type: String,
     pub from: Option<chrono::NaiveDate>,
     pub to: Option<chrono::NaiveDate>,
 }
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct ExportResponse {
+    pub message: String,
+    pub format: String,
+    pub download_url: String,
+}
+
+// ============================================
+// Annotation Endpoints
+// ============================================
+
+/// GET /api/v1/rustpress-analytics/annotations
+#[utoipa::path(
+    get,
+    path = "/annotations",
+    tag = "annotations",
+    params(ReportQuery),
+    responses(
+        (status = 200, description = "Annotations overlapping the period, oldest first", body = ListResponse<Annotation>),
+        (status = 500, description = "Query failed", body = ProblemDetails),
+        (status = 503, description = "Service unavailable", body = ProblemDetails),
+    ),
+)]
+pub async fn list_annotations(
+    State(plugin): State<Arc<AnalyticsPlugin>>,
+    Query(query): Query<ReportQuery>,
+) -> Result<Json<ListResponse<Annotation>>, ProblemDetails> {
+    let analytics = analytics_service(&plugin).await?;
+
+    let annotations = analytics.get_annotations(&query).await.map_err(|e| {
+        tracing::error!("Failed to get annotations: {:?}", e);
+        ProblemDetails::internal("Failed to fetch annotations")
+    })?;
+
+    Ok(Json(ListResponse {
+        count: Some(annotations.len()),
+        data: annotations,
+        range: None,
+    }))
+}
+
+/// POST /api/v1/rustpress-analytics/annotations
+#[utoipa::path(
+    post,
+    path = "/annotations",
+    tag = "annotations",
+    request_body = AnnotationInput,
+    responses(
+        (status = 201, description = "Annotation created", body = Annotation),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 422, description = "Invalid annotation", body = ProblemDetails),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn create_annotation(
+    State(plugin): State<Arc<AnalyticsPlugin>>,
+    user: AuthUser,
+    ValidatedJson(input): ValidatedJson<AnnotationInput>,
+) -> Result<(StatusCode, Json<Annotation>), ProblemDetails> {
+    let annotation = annotation_service(&plugin)
+        .await?
+        .create(&input, user.id, &user.name)
+        .await?;
+    Ok((StatusCode::CREATED, Json(annotation)))
+}
+
+/// PUT /api/v1/rustpress-analytics/annotations/{id}
+#[utoipa::path(
+    put,
+    path = "/annotations/{id}",
+    tag = "annotations",
+    params(("id" = Uuid, Path, description = "Annotation ID")),
+    request_body = AnnotationInput,
+    responses(
+        (status = 200, description = "Annotation updated", body = Annotation),
+        (status = 403, description = "Neither the author nor an admin", body = ProblemDetails),
+        (status = 404, description = "Annotation not found", body = ProblemDetails),
+        (status = 422, description = "Invalid annotation", body = ProblemDetails),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn update_annotation(
+    State(plugin): State<Arc<AnalyticsPlugin>>,
+    user: AuthUser,
+    Path(id): Path<Uuid>,
+    ValidatedJson(input): ValidatedJson<AnnotationInput>,
+) -> Result<Json<Annotation>, ProblemDetails> {
+    let annotations = annotation_service(&plugin).await?;
+    require_author(&user, &annotations.get(id).await?)?;
+
+    Ok(Json(annotations.update(id, &input).await?))
+}
+
+/// DELETE /api/v1/rustpress-analytics/annotations/{id}
+#[utoipa::path(
+    delete,
+    path = "/annotations/{id}",
+    tag = "annotations",
+    params(("id" = Uuid, Path, description = "Annotation ID")),
+    responses(
+        (status = 204, description = "Annotation deleted"),
+        (status = 403, description = "Neither the author nor an admin", body = ProblemDetails),
+        (status = 404, description = "Annotation not found", body = ProblemDetails),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn delete_annotation(
+    State(plugin): State<Arc<AnalyticsPlugin>>,
+    user: AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<StatusCode, ProblemDetails> {
+    let annotations = annotation_service(&plugin).await?;
+    require_author(&user, &annotations.get(id).await?)?;
+
+    annotations.delete(id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+impl From<AnnotationError> for ProblemDetails {
+    fn from(e: AnnotationError) -> Self {
+        match e {
+            AnnotationError::NotFound(id) => ProblemDetails::not_found(format!("Annotation not found: {}", id)),
+            AnnotationError::Invalid(msg) => {
+                ProblemDetails::new(StatusCode::UNPROCESSABLE_ENTITY, "invalid_annotation").detail(msg)
+            }
+            AnnotationError::Database(e) => {
+                tracing::error!("Annotation database error: {}", e);
+                ProblemDetails::internal("Database error")
+            }
+        }
+    }
+}
+
+/// Annotations are changed by their author or an admin
+fn require_author(user: &AuthUser, annotation: &Annotation) -> Result<(), ProblemDetails> {
+    if user.id == annotation.author_id || user.is_admin() {
+        Ok(())
+    } else {
+        Err(ProblemDetails::forbidden("Only the author or an admin can change this annotation"))
+    }
+}
+
+// ============================================
+// Privacy Endpoints
+// ============================================
+
+/// POST /api/v1/rustpress-analytics/privacy/forget
+///
+/// Queue the erasure of a visitor's data, or of every visitor seen at an IP
+/// address; poll the returned request for its completion report.
+#[utoipa::path(
+    post,
+    path = "/privacy/forget",
+    tag = "privacy",
+    request_body = ForgetRequest,
+    responses(
+        (status = 202, description = "Erasure queued", body = Erasure),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Not an admin", body = ProblemDetails),
+        (status = 422, description = "Neither or both of visitor_id and ip, or an invalid ip", body = ProblemDetails),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn forget(
+    State(plugin): State<Arc<AnalyticsPlugin>>,
+    user: AuthUser,
+    Json(input): Json<ForgetRequest>,
+) -> Result<(StatusCode, Json<Erasure>), ProblemDetails> {
+    require_admin(&user)?;
+    let erasure = erasure_service(&plugin).await?.request(&input, user.id).await?;
+    Ok((StatusCode::ACCEPTED, Json(erasure)))
+}
+
+/// GET /api/v1/rustpress-analytics/privacy/forget/{id}
+#[utoipa::path(
+    get,
+    path = "/privacy/forget/{id}",
+    tag = "privacy",
+    params(("id" = Uuid, Path, description = "Erasure request ID")),
+    responses(
+        (status = 200, description = "The request, with row counts once completed", body = Erasure),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Not an admin", body = ProblemDetails),
+        (status = 404, description = "Erasure request not found", body = ProblemDetails),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn get_erasure(
+    State(plugin): State<Arc<AnalyticsPlugin>>,
+    user: AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Erasure>, ProblemDetails> {
+    require_admin(&user)?;
+    Ok(Json(erasure_service(&plugin).await?.get(id).await?))
+}
+
+impl From<ErasureError> for ProblemDetails {
+    fn from(e: ErasureError) -> Self {
+        match e {
+            ErasureError::NotFound(id) => ProblemDetails::not_found(format!("Erasure request not found: {}", id)),
+            ErasureError::Invalid(msg) => {
+                ProblemDetails::new(StatusCode::UNPROCESSABLE_ENTITY, "invalid_erasure").detail(msg)
+            }
+            ErasureError::Database(e) => {
+                tracing::error!("Erasure database error: {}", e);
+                ProblemDetails::internal("Database error")
+            }
+        }
+    }
+}
+
+fn require_admin(user: &AuthUser) -> Result<(), ProblemDetails> {
+    if user.is_admin() {
+        Ok(())
+    } else {
+        Err(ProblemDetails::forbidden("Only admins can erase analytics data"))
+    }
+}
+
+// ============================================
+// Sinks Endpoint
+// ============================================
+
+/// GET /api/v1/rustpress-analytics/sinks
+#[utoipa::path(
+    get,
+    path = "/sinks",
+    tag = "sinks",
+    responses(
+        (status = 200, description = "Delivery state of each configured sink", body = ListResponse<SinkStatus>),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Not an admin", body = ProblemDetails),
+        (status = 503, description = "Service unavailable", body = ProblemDetails),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn list_sinks(
+    State(plugin): State<Arc<AnalyticsPlugin>>,
+    user: AuthUser,
+) -> Result<Json<ListResponse<SinkStatus>>, ProblemDetails> {
+    if !user.is_admin() {
+        return Err(ProblemDetails::forbidden("Only admins can see sinks"));
+    }
+    let sinks = plugin
+        .sinks()
+        .await
+        .ok_or_else(|| ProblemDetails::unavailable("Sinks unavailable"))?;
+
+    let data = sinks.status();
+    Ok(Json(ListResponse {
+        count: Some(data.len()),
+        data,
+        range: None,
+    }))
+}
+
+// ============================================
+// Helpers
+// ============================================
+
+async fn analytics_service(plugin: &AnalyticsPlugin) -> Result<Arc<AnalyticsService>, ProblemDetails> {
+    plugin
+        .analytics()
+        .await
+        .ok_or_else(|| ProblemDetails::unavailable("Analytics service unavailable"))
+}
+
+async fn report_service(plugin: &AnalyticsPlugin) -> Result<Arc<ReportService>, ProblemDetails> {
+    plugin
+        .reports()
+        .await
+        .ok_or_else(|| ProblemDetails::unavailable("Report service unavailable"))
+}
+
+/// Problem for a failed report; a query over the time limit is a 503 so
+/// clients can retry later or with a shorter date range
+fn report_problem(report: &str, e: ReportError) -> ProblemDetails {
+    match e {
+        ReportError::Timeout(limit) => {
+            tracing::warn!("The {} report timed out after {:?}", report, limit);
+            ProblemDetails::unavailable("The report took too long; try a shorter date range")
+        }
+        e => {
+            tracing::error!("Failed to get {} report: {:?}", report, e);
+            ProblemDetails::internal("Failed to generate report")
+        }
+    }
+}
+
+
+async fn annotation_service(plugin: &AnalyticsPlugin) -> Result<Arc<AnnotationService>, ProblemDetails> {
+    plugin
+        .annotations()
+        .await
+        .ok_or_else(|| ProblemDetails::unavailable("Annotation service unavailable"))
+}
+
+async fn erasure_service(plugin: &AnalyticsPlugin) -> Result<Arc<ErasureService>, ProblemDetails> {
+    plugin
+        .erasures()
+        .await
+        .ok_or_else(|| ProblemDetails::unavailable("Erasure service unavailable"))
+}
@@ -1,5 +1,7 @@
 //! Analytics Hook Handlers
 
+use crate::models::{today, ReportRange};
+use crate::tracker;
 use crate::AnalyticsPlugin;
 use rustpress_plugins::prelude::*;
 use std::sync::Arc;
@@ -52,10 +54,13 @@ pub async fn track_user_login(
         // Track as event
         if let Some(tracking) = plugin.tracking().await {
             let input = crate::models::TrackingInput {
+                site_id: None,
                 visitor_id: None,
                 session_id: None,
                 event_type: "event".into(),
                 path: "/login".into(),
+                pageview_id: None,
+                engaged_seconds: None,
                 title: None,
                 referrer: None,
                 category: Some("user".into()),
@@ -67,7 +72,7 @@ pub async fn track_user_login(
                 utm_campaign: None,
             };
 
-            if let Err(e) = tracking.track_event(&input).await {
+            if let Err(e) = tracking.track_event(&input, None).await {
                 tracing::warn!("Failed to track login event: {:?}", e);
             }
         }
@@ -99,7 +104,13 @@ pub async fn track_content_publish(
     Ok(())
 }
 
-/// Inject tracking script into page footer
+/// Inject the tracker loader into the page footer
+///
+/// Pages get a small async `<script src>` tag with Subresource Integrity
+/// (see [`crate::tracker`]); with `inline_tracker` the script itself is
+/// inlined. Either way the tag carries the request's CSP nonce so pages
+/// don't need `'unsafe-inline'` in `script-src`, and the tracker key when
+/// one is configured.
 pub async fn inject_tracking_script(
     ctx: FilterContext,
     plugin: Arc<AnalyticsPlugin>,
@@ -118,122 +129,37 @@ pub async fn inject_tracking_script(
         }
     }
 
-    let script = format!(
-        r#"
-<script>
-(function() {{
-    var analytics = {{
-        endpoint: '/api/v1/analytics/track',
-        visitorId: localStorage.getItem('_rp_vid') || null,
-        sessionId: sessionStorage.getItem('_rp_sid') || null,
-        trackOutbound: {},
-        trackDownloads: {},
-        downloadExtensions: {:?},
-
-        init: function() {{
-            this.trackPageView();
-            if (this.trackOutbound) this.setupOutboundTracking();
-            if (this.trackDownloads) this.setupDownloadTracking();
-        }},
-
-        track: function(data) {{
-            data.visitor_id = this.visitorId;
-            data.session_id = this.sessionId;
-
-            fetch(this.endpoint, {{
-                method: 'POST',
-                headers: {{ 'Content-Type': 'application/json' }},
-                body: JSON.stringify(data),
-                keepalive: true
-            }}).then(function(r) {{ return r.json(); }}).then(function(d) {{
-                if (d.visitor_id) {{
-                    localStorage.setItem('_rp_vid', d.visitor_id);
-                    analytics.visitorId = d.visitor_id;
-                }}
-                if (d.session_id) {{
-                    sessionStorage.setItem('_rp_sid', d.session_id);
-                    analytics.sessionId = d.session_id;
-                }}
-            }});
-        }},
-
-        trackPageView: function() {{
-            this.track({{
-                event_type: 'pageview',
-                path: location.pathname,
-                title: document.title,
-                referrer: document.referrer,
-                utm_source: this.getParam('utm_source'),
-                utm_medium: this.getParam('utm_medium'),
-                utm_campaign: this.getParam('utm_campaign')
-            }});
-        }},
-
-        trackEvent: function(category, action, label, value) {{
-            this.track({{
-                event_type: 'event',
-                path: location.pathname,
-                category: category,
-                action: action,
-                label: label,
-                value: value
-            }});
-        }},
-
-        setupOutboundTracking: function() {{
-            document.addEventListener('click', function(e) {{
-                var link = e.target.closest('a');
-                if (link && link.hostname !== location.hostname) {{
-                    analytics.trackEvent('outbound', 'click', link.href);
-                }}
-            }});
-        }},
-
-        setupDownloadTracking: function() {{
-            var exts = this.downloadExtensions;
-            document.addEventListener('click', function(e) {{
-                var link = e.target.closest('a');
-                if (link) {{
-                    var ext = link.pathname.split('.').pop().toLowerCase();
-                    if (exts.indexOf(ext) > -1) {{
-                        analytics.trackEvent('download', ext, link.pathname);
-                    }}
-                }}
-            }});
-        }},
-
-        getParam: function(name) {{
-            var params = new URLSearchParams(location.search);
-            return params.get(name);
-        }}
-    }};
-
-    analytics.init();
-    window.rpAnalytics = analytics;
-}})();
-</script>
-"#,
-        config.track_outbound_links,
-        config.track_downloads,
-        config.download_extensions,
-    );
+    let tracking = plugin.tracking().await;
+    let key = tracking.as_ref().and_then(|t| t.tracker_key());
+    let script = if config.inline_tracker {
+        tracker::inline_snippet(&config, key)
+    } else {
+        tracker::loader_snippet(&config, key)
+    };
 
-    Ok(format!("{}{}", content, script))
+    Ok(format!("{}\n{}\n", content, script))
 }
 
 /// Cron job: Aggregate daily statistics
+///
+/// Days are counted in the report time zone; the job runs hourly so the day
+/// that just ended is aggregated soon after midnight there. Each site gets
+/// its own row, and visitors are new or returning to that site.
 pub async fn aggregate_daily_stats(
     ctx: CronContext,
     plugin: Arc<AnalyticsPlugin>,
 ) -> Result<(), HookError> {
     tracing::info!("Running daily stats aggregation");
 
-    let yesterday = chrono::Utc::now().date_naive() - chrono::Duration::days(1);
+    let tz = plugin.config().await.timezone;
+    let yesterday = today(tz) - chrono::Duration::days(1);
+    let range = ReportRange::new(yesterday, yesterday, tz);
 
     sqlx::query!(
         r#"
-        INSERT INTO analytics_daily_stats (date, page_views, unique_visitors, sessions, bounce_rate, avg_session_duration, new_visitors, returning_visitors)
+        INSERT INTO analytics_daily_stats (site_id, date, page_views, unique_visitors, sessions, bounce_rate, avg_session_duration, new_visitors, returning_visitors)
         SELECT
+            p.site_id,
             $1::date as date,
             COUNT(p.id) as page_views,
             COUNT(DISTINCT p.visitor_id) as unique_visitors,
@@ -242,16 +168,19 @@ pub async fn aggregate_daily_stats(
             AVG(s.duration_seconds),
             COUNT(DISTINCT p.visitor_id) FILTER (WHERE NOT EXISTS (
                 SELECT 1 FROM analytics_pageviews p2
-                WHERE p2.visitor_id = p.visitor_id AND p2.created_at < $1::date
+                WHERE p2.visitor_id = p.visitor_id AND p2.site_id IS NOT DISTINCT FROM p.site_id
+                    AND p2.created_at < $2
             )),
             COUNT(DISTINCT p.visitor_id) FILTER (WHERE EXISTS (
                 SELECT 1 FROM analytics_pageviews p2
-                WHERE p2.visitor_id = p.visitor_id AND p2.created_at < $1::date
+                WHERE p2.visitor_id = p.visitor_id AND p2.site_id IS NOT DISTINCT FROM p.site_id
+                    AND p2.created_at < $2
             ))
         FROM analytics_pageviews p
         JOIN analytics_sessions s ON s.id = p.session_id
-        WHERE p.created_at::date = $1
-        ON CONFLICT (date) DO UPDATE SET
+        WHERE p.created_at >= $2 AND p.created_at < $3
+        GROUP BY p.site_id
+        ON CONFLICT ((COALESCE(site_id, '00000000-0000-0000-0000-000000000000'::uuid)), date) DO UPDATE SET
             page_views = EXCLUDED.page_views,
             unique_visitors = EXCLUDED.unique_visitors,
             sessions = EXCLUDED.sessions,
@@ -261,6 +190,8 @@ pub async fn aggregate_daily_stats(
             returning_visitors = EXCLUDED.returning_visitors
         "#,
         yesterday,
+        range.start_utc(),
+        range.end_utc(),
     )
     .execute(&ctx.db)
     .await
@@ -307,11 +238,21 @@ pub async fn cleanup_old_data(
     .map_err(|e| HookError::Database(e.to_string()))?
     .rows_affected();
 
+    let deleted_rejections = sqlx::query!(
+        "DELETE FROM analytics_rejections WHERE date < $1",
+        cutoff.date_naive(),
+    )
+    .execute(&ctx.db)
+    .await
+    .map_err(|e| HookError::Database(e.to_string()))?
+    .rows_affected();
+
     tracing::info!(
-        "Cleanup complete: {} pageviews, {} sessions, {} events deleted",
+        "Cleanup complete: {} pageviews, {} sessions, {} events, {} rejection counts deleted",
         deleted_pageviews,
         deleted_sessions,
-        deleted_events
+        deleted_events,
+        deleted_rejections
     );
 
     Ok(())
@@ -4,19 +4,31 @@
 //! - Real-time visitor tracking
 //! - Page view and event analytics
 //! - Geographic and device reports
+//! - Time series annotations
 //! - Session management
-//! - Privacy-compliant data handling
+//! - Per-visitor and per-IP quotas and optional signed hits on `/track`
+//! - Per-region tracking rules (off or cookieless, e.g. in the EEA)
+//! - Privacy-compliant data handling, with right-to-erasure requests
 //! - Export capabilities
+//! - Mirroring to external sinks (GA4, HTTP endpoints, Kafka)
 
 pub mod api;
 pub mod hooks;
 pub mod models;
 pub mod services;
+pub mod tracker;
 
 use async_trait::async_trait;
+use rustpress_auth::db::{self, DbPools, ReplicaConfig};
+use rustpress_auth::migrations::PluginMigrations;
+use rustpress_auth::Config;
 use rustpress_plugins::prelude::*;
-use services::{AnalyticsService, ReportService, TrackingService};
+use services::{
+    AnalyticsService, AnnotationService, ErasureService, GeoIp, IngestMetrics, IngestService, ReportService, Sinks,
+    TrackingService, TRACKER_KEY_SECRET,
+};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::RwLock;
 
 // ============================================
@@ -35,8 +47,31 @@ pub struct AnalyticsConfig {
     pub track_downloads: bool,
     pub download_extensions: Vec<String>,
     pub realtime_enabled: bool,
+    /// Inline the tracker into pages instead of loading it from `tracker.js`
+    pub inline_tracker: bool,
+    /// Seconds between the tracker's engagement pings; 0 turns them off
+    pub heartbeat_interval_secs: u32,
     pub dashboard_refresh_rate: u32,
     pub default_date_range: String,
+    /// Zone report days are counted in
+    pub timezone: chrono_tz::Tz,
+    /// Events that count as a conversion: `category` or `category:action`
+    pub conversion_events: Vec<String>,
+    /// Seconds a report query may run before the report fails
+    pub report_query_timeout_secs: u32,
+    /// Hits accepted from one visitor per hour; 0 means no limit
+    pub visitor_hourly_quota: u32,
+    /// Hits accepted from one IP address per minute; 0 means no limit
+    pub ip_minute_quota: u32,
+    /// Refuse `/track` hits not signed with the tracker key
+    pub require_signature: bool,
+    /// Tracking modes by visitor country; the first matching rule applies
+    /// and visitors no rule matches are tracked in full
+    pub region_rules: Vec<models::RegionRule>,
+    /// Told to the tracker so consent banners know whether to ask
+    pub legal_basis: models::LegalBasis,
+    /// External systems stored hits are mirrored to
+    pub sinks: Vec<services::SinkConfig>,
 }
 
 impl Default for AnalyticsConfig {
@@ -55,8 +90,19 @@ impl Default for AnalyticsConfig {
                 .map(String::from)
                 .collect(),
             realtime_enabled: true,
+            inline_tracker: false,
+            heartbeat_interval_secs: 15,
             dashboard_refresh_rate: 30,
             default_date_range: "30d".into(),
+            timezone: chrono_tz::UTC,
+            conversion_events: vec!["conversion".into()],
+            report_query_timeout_secs: 30,
+            visitor_hourly_quota: 1000,
+            ip_minute_quota: 300,
+            require_signature: false,
+            region_rules: vec![],
+            legal_basis: models::LegalBasis::LegitimateInterest,
+            sinks: vec![],
         }
     }
 }
@@ -65,28 +111,62 @@ impl Default for AnalyticsConfig {
 // Main Plugin Struct
 // ============================================
 
+static SHARED_INGEST: std::sync::RwLock<Option<Arc<IngestService>>> = std::sync::RwLock::new(None);
+
+/// Ingestion service of the active plugin, for other surfaces of the same
+/// process, such as the blog's gRPC server
+pub fn shared_ingest() -> Option<Arc<IngestService>> {
+    SHARED_INGEST.read().unwrap().clone()
+}
+
+static SHARED_REPORTS: std::sync::RwLock<Option<Arc<ReportService>>> = std::sync::RwLock::new(None);
+
+/// Report service of the active plugin, for other surfaces of the same
+/// process, such as the blog's tool API
+pub fn shared_reports() -> Option<Arc<ReportService>> {
+    SHARED_REPORTS.read().unwrap().clone()
+}
+
 pub struct AnalyticsPlugin {
     info: PluginInfo,
     state: RwLock<PluginState>,
     config: RwLock<AnalyticsConfig>,
     tracking_service: RwLock<Option<Arc<TrackingService>>>,
+    ingest_service: RwLock<Option<Arc<IngestService>>>,
     analytics_service: RwLock<Option<Arc<AnalyticsService>>>,
     report_service: RwLock<Option<Arc<ReportService>>>,
+    annotation_service: RwLock<Option<Arc<AnnotationService>>>,
+    erasure_service: RwLock<Option<Arc<ErasureService>>>,
+    sinks: RwLock<Option<Arc<Sinks>>>,
+    /// The `ingest` and `reporting` pool partitions, when configured; closed
+    /// on deactivation
+    partitions: RwLock<Vec<db::DbPool>>,
 }
 
 impl AnalyticsPlugin {
+    /// Schema migrations shipped with the plugin (`migrations/`)
+    pub fn migrations() -> PluginMigrations {
+        PluginMigrations::new("rustpress-analytics", sqlx::migrate!("./migrations"))
+    }
+
     pub fn new() -> Self {
         Self {
             info: PluginInfo {
                 id: "rustpress-analytics".into(),
                 name: "RustPress Analytics".into(),
                 version: "2.0.0".into(),
+                needs: vec![Capability::Db, Capability::Redis, Capability::Cron],
             },
             state: RwLock::new(PluginState::Inactive),
             config: RwLock::new(AnalyticsConfig::default()),
             tracking_service: RwLock::new(None),
+            ingest_service: RwLock::new(None),
             analytics_service: RwLock::new(None),
             report_service: RwLock::new(None),
+            annotation_service: RwLock::new(None),
+            erasure_service: RwLock::new(None),
+            sinks: RwLock::new(None),
+            partitions: RwLock::new(Vec::new()),
         }
     }
 
@@ -98,6 +178,10 @@ impl AnalyticsPlugin {
         self.tracking_service.read().await.clone()
     }
 
+    pub async fn ingest(&self) -> Option<Arc<IngestService>> {
+        self.ingest_service.read().await.clone()
+    }
+
     pub async fn analytics(&self) -> Option<Arc<AnalyticsService>> {
         self.analytics_service.read().await.clone()
     }
@@ -106,6 +190,18 @@ impl AnalyticsPlugin {
         self.report_service.read().await.clone()
     }
 
+    pub async fn annotations(&self) -> Option<Arc<AnnotationService>> {
+        self.annotation_service.read().await.clone()
+    }
+
+    pub async fn erasures(&self) -> Option<Arc<ErasureService>> {
+        self.erasure_service.read().await.clone()
+    }
+
+    pub async fn sinks(&self) -> Option<Arc<Sinks>> {
+        self.sinks.read().await.clone()
+    }
+
     async fn load_config(&self, settings: &SettingsManager) -> Result<AnalyticsConfig, HookError> {
         let mut config = AnalyticsConfig::default();
 
@@ -127,6 +223,45 @@ impl AnalyticsPlugin {
         if let Some(v) = settings.get::<String>("rustpress-analytics", "excluded_paths").await? {
             config.excluded_paths = v.lines().map(String::from).collect();
         }
+        if let Some(v) = settings.get("rustpress-analytics", "inline_tracker").await? {
+            config.inline_tracker = v;
+        }
+        if let Some(v) = settings.get::<u32>("rustpress-analytics", "heartbeat_interval").await? {
+            config.heartbeat_interval_secs = v;
+        }
+        if let Some(v) = settings.get::<String>("rustpress-analytics", "timezone").await? {
+            config.timezone = v
+                .parse()
+                .map_err(|_| HookError::InvalidData(format!("Unknown time zone: {}", v)))?;
+        }
+        if let Some(v) = settings.get::<String>("rustpress-analytics", "conversion_events").await? {
+            config.conversion_events = v.lines().map(str::trim).filter(|l| !l.is_empty()).map(String::from).collect();
+        }
+        if let Some(v) = settings.get::<u32>("rustpress-analytics", "report_query_timeout").await? {
+            config.report_query_timeout_secs = v.max(1);
+        }
+        if let Some(v) = settings.get::<u32>("rustpress-analytics", "visitor_hourly_quota").await? {
+            config.visitor_hourly_quota = v;
+        }
+        if let Some(v) = settings.get::<u32>("rustpress-analytics", "ip_minute_quota").await? {
+            config.ip_minute_quota = v;
+        }
+        if let Some(v) = settings.get("rustpress-analytics", "require_signature").await? {
+            config.require_signature = v;
+        }
+        if let Some(v) = settings.get::<String>("rustpress-analytics", "region_rules").await? {
+            config.region_rules = services::parse_region_rules(&v).map_err(HookError::InvalidData)?;
+        }
+        if let Some(v) = settings.get::<String>("rustpress-analytics", "legal_basis").await? {
+            config.legal_basis = match v.as_str() {
+                "consent" => models::LegalBasis::Consent,
+                "legitimate_interest" => models::LegalBasis::LegitimateInterest,
+                other => return Err(HookError::InvalidData(format!("Unknown legal_basis: {}", other))),
+            };
+        }
+        if let Some(v) = settings.get::<String>("rustpress-analytics", "sinks").await? {
+            config.sinks = services::parse_sinks(&v).map_err(HookError::InvalidData)?;
+        }
 
         Ok(config)
     }
@@ -147,25 +282,109 @@ impl LifecycleHook for AnalyticsPlugin {
     async fn on_activate(&self, ctx: &ActivationContext) -> Result<(), HookError> {
         tracing::info!("Activating RustPress Analytics plugin");
 
+        // The dashboard service holds a Redis pool; fail before migrating
+        // when the host has none
+        ctx.require(&self.info.needs)?;
+
         // Run migrations
-        ctx.run_migrations().await
+        Self::migrations()
+            .up(&ctx.db, None, false)
+            .await
             .map_err(|e| HookError::Migration(e.to_string()))?;
 
         // Load configuration
         let config = self.load_config(&ctx.settings).await?;
         *self.config.write().await = config.clone();
 
+        // Ingest counters and pool sizes, exported at /metrics with
+        // plugin="rustpress-analytics"
+        let plugin_metrics = rustpress_auth::metrics::shared().plugin(self.info.id.clone());
+        let metrics = IngestMetrics::register(&plugin_metrics).map_err(|e| HookError::InvalidData(e.to_string()))?;
+
+        // Tracking writes and reports get their own pools when
+        // `[database.pools.ingest]` and `[database.pools.reporting]` are set,
+        // so a slow report can't starve tracking or logins
+        let ingest_pool = db::partition(db::INGEST_POOL, &plugin_metrics)
+            .await
+            .map_err(|e| HookError::Database(e.to_string()))?;
+        let reporting_pool = db::partition(db::REPORTING_POOL, &plugin_metrics)
+            .await
+            .map_err(|e| HookError::Database(e.to_string()))?;
+        let ingest_db = ingest_pool.clone().unwrap_or_else(|| ctx.db.clone());
+
+        // Reports and dashboards read from replicas; tracking writes to the primary
+        let app_config = Config::load().map_err(|e| HookError::InvalidData(e.to_string()))?;
+        let replicas = ReplicaConfig::from_config(&app_config).map_err(|e| HookError::InvalidData(e.to_string()))?;
+        let pools = DbPools::connect(reporting_pool.clone().unwrap_or_else(|| ctx.db.clone()), &replicas)
+            .await
+            .map_err(|e| HookError::Database(e.to_string()))?;
+
         // Initialize services
-        let tracking = Arc::new(TrackingService::new(ctx.db.clone(), config.clone()));
-        let analytics = Arc::new(AnalyticsService::new(ctx.db.clone(), ctx.redis.clone()));
-        let reports = Arc::new(ReportService::new(ctx.db.clone()));
+        // The GeoIP databases also locate logins for the auth plugin's alerts
+        let geoip = Arc::new(GeoIp::open());
+        rustpress_auth::anomaly::install_geo_lookup(Some(geoip.clone()));
+
+        // Backend ingestion keys are re-read at the secrets refresh interval
+        let secrets = rustpress_auth::secrets::shared()
+            .await
+            .map_err(|e| HookError::InvalidData(e.to_string()))?;
+
+        // Tracker hits are signed with a key handed to the tracker in the page
+        let tracker_key = secrets
+            .get(TRACKER_KEY_SECRET)
+            .await
+            .map_err(|e| HookError::InvalidData(e.to_string()))?
+            .filter(|key| !key.trim().is_empty());
+        if config.require_signature && tracker_key.is_none() {
+            return Err(HookError::InvalidData(format!(
+                "require_signature is set but the {} secret is empty",
+                TRACKER_KEY_SECRET
+            )));
+        }
+
+        // Stored hits are mirrored to the configured sinks in the background
+        let sinks = Sinks::start(&config.sinks, secrets.as_ref(), metrics.clone())
+            .await
+            .map_err(|e| HookError::InvalidData(e.to_string()))?;
+
+        let tracking = Arc::new(
+            TrackingService::new(ingest_db.clone(), config.clone(), geoip, metrics.clone(), tracker_key)
+                .with_sinks(sinks.clone()),
+        );
+        let refresh = rustpress_auth::secrets::refresh_interval(&app_config)
+            .map_err(|e| HookError::InvalidData(e.to_string()))?
+            .unwrap_or(Duration::MAX);
+        let ingest = Arc::new(
+            IngestService::new(ingest_db, config.clone(), secrets, refresh, metrics).with_sinks(sinks.clone()),
+        );
+        let analytics = Arc::new(AnalyticsService::new(pools.clone(), ctx.redis.clone(), config.timezone));
+        let reports = Arc::new(
+            ReportService::new(pools.clone(), config.conversion_events.clone(), config.timezone)
+                .with_query_timeout(Duration::from_secs(config.report_query_timeout_secs.into())),
+        );
+        let annotations = Arc::new(AnnotationService::new(pools));
+        // Resumes erasure requests a restart interrupted
+        let erasures = ErasureService::start(ctx.db.clone())
+            .await
+            .map_err(|e| HookError::Database(e.to_string()))?;
 
         *self.tracking_service.write().await = Some(tracking);
+        *SHARED_INGEST.write().unwrap() = Some(ingest.clone());
+        *self.ingest_service.write().await = Some(ingest);
         *self.analytics_service.write().await = Some(analytics);
+        *SHARED_REPORTS.write().unwrap() = Some(reports.clone());
         *self.report_service.write().await = Some(reports);
-
-        // Register routes
-        ctx.register_routes(api::create_routes(self)).await?;
+        *self.annotation_service.write().await = Some(annotations);
+        *self.erasure_service.write().await = Some(erasures);
+        *self.sinks.write().await = Some(sinks);
+        *self.partitions.write().await = ingest_pool.into_iter().chain(reporting_pool).collect();
+
+        // Register routes under /api/v1/<plugin-id>; fails when another
+        // plugin or the app already serves one of the paths
+        let routes = rustpress_auth::routes::shared()
+            .mount(api::create_routes(self))
+            .map_err(|e| HookError::InvalidData(e.to_string()))?;
+        ctx.register_routes(routes).await?;
 
         *self.state.write().await = PluginState::Active;
         tracing::info!("RustPress Analytics activated successfully");
@@ -177,10 +396,22 @@ impl LifecycleHook for AnalyticsPlugin {
 
         // Clear services
         *self.tracking_service.write().await = None;
+        *self.ingest_service.write().await = None;
+        SHARED_INGEST.write().unwrap().take();
         *self.analytics_service.write().await = None;
         *self.report_service.write().await = None;
+        SHARED_REPORTS.write().unwrap().take();
+        *self.annotation_service.write().await = None;
+        *self.erasure_service.write().await = None;
+        // Sink workers send what's queued, then stop
+        *self.sinks.write().await = None;
+        rustpress_auth::anomaly::install_geo_lookup(None);
+        for pool in self.partitions.write().await.drain(..) {
+            pool.close().await;
+        }
 
         // Unregister routes
+        rustpress_auth::routes::shared().unregister(&self.info.id);
         ctx.unregister_routes().await?;
 
         *self.state.write().await = PluginState::Inactive;
@@ -200,30 +431,23 @@ impl LifecycleHook for AnalyticsPlugin {
             tracing::info!("Migrating to session-based tracking");
         }
 
-        ctx.run_migrations().await?;
+        Self::migrations()
+            .up(&ctx.db, None, false)
+            .await
+            .map_err(|e| HookError::Migration(e.to_string()))?;
         Ok(())
     }
 
     async fn on_uninstall(&self, ctx: &UninstallContext) -> Result<(), HookError> {
         tracing::info!("Uninstalling RustPress Analytics");
 
-        // Remove all tables
-        sqlx::query("DROP TABLE IF EXISTS analytics_events CASCADE")
-            .execute(&ctx.db)
+        // Revert every migration, which also clears the ledger entries
+        Self::migrations()
+            .down(&ctx.db, 0, false)
             .await
-            .map_err(|e| HookError::Database(e.to_string()))?;
-
-        sqlx::query("DROP TABLE IF EXISTS analytics_pageviews CASCADE")
-            .execute(&ctx.db)
-            .await
-            .map_err(|e| HookError::Database(e.to_string()))?;
-
-        sqlx::query("DROP TABLE IF EXISTS analytics_sessions CASCADE")
-            .execute(&ctx.db)
-            .await
-            .map_err(|e| HookError::Database(e.to_string()))?;
+            .map_err(|e| HookError::Migration(e.to_string()))?;
 
-        sqlx::query("DROP TABLE IF EXISTS analytics_daily_stats CASCADE")
+        sqlx::query("DROP TABLE IF EXISTS analytics_events CASCADE")
             .execute(&ctx.db)
             .await
             .map_err(|e| HookError::Database(e.to_string()))?;
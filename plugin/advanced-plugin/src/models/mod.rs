@@ -1,12 +1,15 @@
 //! Analytics Data Models
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
+use utoipa::{IntoParams, ToSchema};
 use uuid::Uuid;
+use validator::Validate;
 
 /// A tracked page view
-#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
 pub struct PageView {
     pub id: i64,
     pub session_id: Uuid,
@@ -17,14 +20,17 @@ pub struct PageView {
     pub utm_source: Option<String>,
     pub utm_medium: Option<String>,
     pub utm_campaign: Option<String>,
+    /// Seconds the page was visible, from heartbeat pings
+    pub engagement_seconds: Option<i32>,
     pub created_at: DateTime<Utc>,
 }
 
 /// A visitor session
-#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
 pub struct Session {
     pub id: Uuid,
     pub visitor_id: Uuid,
+    pub site_id: Option<Uuid>,
     pub started_at: DateTime<Utc>,
     pub ended_at: Option<DateTime<Utc>>,
     pub page_views: i32,
@@ -33,14 +39,16 @@ pub struct Session {
     pub exit_page: Option<String>,
     pub device_type: String,
     pub browser: String,
+    pub browser_version: Option<String>,
     pub os: String,
+    pub os_version: Option<String>,
     pub country: Option<String>,
     pub city: Option<String>,
     pub is_bounce: bool,
 }
 
 /// A tracked event (clicks, downloads, etc.)
-#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
 pub struct Event {
     pub id: i64,
     pub session_id: Uuid,
@@ -54,7 +62,7 @@ pub struct Event {
 }
 
 /// Daily aggregated statistics
-#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
 pub struct DailyStats {
     pub date: chrono::NaiveDate,
     pub page_views: i64,
@@ -66,8 +74,78 @@ pub struct DailyStats {
     pub returning_visitors: i64,
 }
 
+/// A note on the time series, e.g. a deploy, campaign or outage
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct Annotation {
+    pub id: Uuid,
+    pub date: chrono::NaiveDate,
+    /// Last day covered; absent for single-day annotations
+    pub end_date: Option<chrono::NaiveDate>,
+    pub label: String,
+    pub author_id: Uuid,
+    pub author_name: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Deserialize, Validate, ToSchema)]
+pub struct AnnotationInput {
+    pub date: chrono::NaiveDate,
+    pub end_date: Option<chrono::NaiveDate>,
+    #[validate(length(min = 1, max = 200))]
+    pub label: String,
+}
+
+/// Whose data to erase: a visitor id or an IP address
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct ForgetRequest {
+    pub visitor_id: Option<Uuid>,
+    /// As stored: truncated when `anonymize_ip` is on (`203.0.113.0`)
+    pub ip: Option<String>,
+}
+
+/// A right-to-erasure request and, once done, its completion report
+#[derive(Debug, Clone, Serialize, FromRow, ToSchema)]
+pub struct Erasure {
+    pub id: Uuid,
+    /// `visitor` or `ip`
+    pub subject: String,
+    /// `pending`, `running`, `completed` or `failed`
+    pub status: String,
+    /// Admin who asked for it
+    pub requested_by: Uuid,
+    /// Visitors whose rows were scrubbed
+    pub visitors: Option<i64>,
+    pub sessions: Option<i64>,
+    pub pageviews: Option<i64>,
+    pub events: Option<i64>,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+/// Delivery state of an event sink since the plugin was activated
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
+pub struct SinkStatus {
+    pub name: String,
+    /// `ga4`, `http` or `kafka`
+    pub kind: String,
+    /// Events waiting to be sent
+    pub queued: usize,
+    pub sent: u64,
+    /// Events not sent: the queue was full, the sink paused or delivery failed
+    pub dropped: u64,
+    pub failed_batches: u64,
+    /// Failed batches since the last one delivered
+    pub consecutive_failures: u32,
+    pub last_error: Option<String>,
+    pub last_success_at: Option<DateTime<Utc>>,
+    /// Events are dropped until then after repeated failures
+    pub paused_until: Option<DateTime<Utc>>,
+}
+
 /// Real-time visitor data
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct RealtimeVisitor {
     pub visitor_id: Uuid,
     pub current_page: String,
@@ -80,9 +158,10 @@ pub struct RealtimeVisitor {
 }
 
 /// Report data structures
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct OverviewReport {
     pub period: String,
+    pub range: ReportRange,
     pub total_page_views: i64,
     pub unique_visitors: i64,
     pub total_sessions: i64,
@@ -91,16 +170,18 @@ pub struct OverviewReport {
     pub pages_per_session: f64,
     pub new_vs_returning: NewVsReturning,
     pub daily_stats: Vec<DailyStats>,
+    /// Annotations overlapping the period
+    pub annotations: Vec<Annotation>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct NewVsReturning {
     pub new_visitors: i64,
     pub returning_visitors: i64,
     pub new_percentage: f64,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct PageReport {
     pub path: String,
     pub title: Option<String>,
@@ -112,7 +193,42 @@ pub struct PageReport {
     pub exits: i64,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Sessions that started on a page
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct LandingPageReport {
+    pub path: String,
+    pub sessions: i64,
+    pub bounce_rate: f64,
+    /// Sessions with a conversion event
+    pub conversions: i64,
+    pub conversion_rate: f64,
+    pub avg_session_duration: f64,
+}
+
+/// Sessions that landed on one page from one UTM campaign; all three are
+/// absent for untagged traffic
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct UtmReport {
+    pub utm_source: Option<String>,
+    pub utm_medium: Option<String>,
+    pub utm_campaign: Option<String>,
+    pub sessions: i64,
+    pub bounce_rate: f64,
+    pub conversions: i64,
+    pub conversion_rate: f64,
+}
+
+/// Sessions that ended on a page
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ExitPageReport {
+    pub path: String,
+    pub exits: i64,
+    pub page_views: i64,
+    /// Share of the page's views that ended the session
+    pub exit_rate: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct ReferrerReport {
     pub referrer: String,
     pub sessions: i64,
@@ -121,21 +237,56 @@ pub struct ReferrerReport {
     pub avg_session_duration: f64,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct DeviceReport {
     pub device_type: String,
     pub sessions: i64,
     pub percentage: f64,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Sessions per browser, broken down by major version
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct BrowserReport {
     pub browser: String,
     pub sessions: i64,
+    /// Share of all sessions in the period
     pub percentage: f64,
+    /// Sessions in the preceding period of the same length
+    pub previous_sessions: i64,
+    /// Change against the previous period in percent; absent when there were
+    /// no sessions to compare with
+    pub change: Option<f64>,
+    pub versions: Vec<VersionReport>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Sessions per operating system, broken down by major version
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct OsReport {
+    pub os: String,
+    pub sessions: i64,
+    /// Share of all sessions in the period
+    pub percentage: f64,
+    /// Sessions in the preceding period of the same length
+    pub previous_sessions: i64,
+    /// Change against the previous period in percent; absent when there were
+    /// no sessions to compare with
+    pub change: Option<f64>,
+    pub versions: Vec<VersionReport>,
+}
+
+/// Sessions for one major version of a browser or OS
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct VersionReport {
+    /// Major version ("118"), or "Unknown"
+    pub version: String,
+    pub sessions: i64,
+    /// Share of the browser's or OS's sessions
+    pub percentage: f64,
+    pub previous_sessions: i64,
+    pub change: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct GeoReport {
     pub country: String,
     pub sessions: i64,
@@ -143,13 +294,91 @@ pub struct GeoReport {
     pub percentage: f64,
 }
 
+/// Why a `/track` hit was refused
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RejectionReason {
+    /// Over the per-IP quota (`ip_minute_quota`)
+    IpQuota,
+    /// Over the per-visitor quota (`visitor_hourly_quota`)
+    VisitorQuota,
+    /// Missing, stale or wrong signature with `require_signature` set
+    BadSignature,
+}
+
+impl RejectionReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RejectionReason::IpQuota => "ip_quota",
+            RejectionReason::VisitorQuota => "visitor_quota",
+            RejectionReason::BadSignature => "bad_signature",
+        }
+    }
+}
+
+/// Refused `/track` hits of one day and reason
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RejectionReport {
+    pub date: chrono::NaiveDate,
+    /// `ip_quota`, `visitor_quota` or `bad_signature`
+    pub reason: String,
+    pub count: i64,
+}
+
+/// How visitors of a region are tracked
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TrackingMode {
+    /// Visitor and session ids kept in the browser's storage
+    Full,
+    /// Nothing stored in the browser and no IP address stored; visitors are
+    /// told apart by a daily-rotating hash
+    Cookieless,
+    /// Hits are dropped before anything is written
+    Disabled,
+}
+
+/// Legal basis tracking relies on, for consent banners
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum LegalBasis {
+    /// Full tracking needs the visitor's consent; until it's given the
+    /// tracker runs cookieless
+    Consent,
+    LegitimateInterest,
+}
+
+/// A `region_rules` line: the mode for visitors from the listed regions
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RegionRule {
+    /// ISO country codes, the groups `EU` and `EEA`, or `*` for anywhere
+    pub regions: Vec<String>,
+    pub mode: TrackingMode,
+}
+
+/// Tracking policy for the requesting visitor, read by the tracker
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct TrackingPolicy {
+    pub mode: TrackingMode,
+    pub legal_basis: LegalBasis,
+    /// Country the visitor was located in
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub country: Option<String>,
+}
+
 /// Input for tracking events
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, ToSchema)]
 pub struct TrackingInput {
+    /// Blog site the hit belongs to (multisite deployments)
+    pub site_id: Option<Uuid>,
     pub visitor_id: Option<Uuid>,
     pub session_id: Option<Uuid>,
-    pub event_type: String, // "pageview" | "event"
+    pub event_type: String, // "pageview" | "event" | "heartbeat"
     pub path: String,
+    /// Page view a heartbeat belongs to, as returned when it was tracked
+    pub pageview_id: Option<i64>,
+    /// Seconds the page was visible since the previous heartbeat
+    pub engaged_seconds: Option<i32>,
     pub title: Option<String>,
     pub referrer: Option<String>,
     pub category: Option<String>,
@@ -161,9 +390,95 @@ pub struct TrackingInput {
     pub utm_campaign: Option<String>,
 }
 
+/// A batch of backend events for `/collect`
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct CollectRequest {
+    pub events: Vec<CollectEvent>,
+}
+
+/// An event recorded by a backend (cron job, payment webhook, ...)
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct CollectEvent {
+    /// When the event happened
+    pub timestamp: DateTime<Utc>,
+    /// Visitor the event belongs to, as issued by the tracker
+    pub visitor_id: Uuid,
+    /// Session to attach the event to; defaults to the visitor's latest
+    /// session started before `timestamp`
+    pub session_id: Option<Uuid>,
+    pub site_id: Option<Uuid>,
+    pub category: String,
+    pub action: String,
+    pub label: Option<String>,
+    pub value: Option<i32>,
+    /// Page the event relates to; `/` when absent
+    pub path: Option<String>,
+}
+
+/// Outcome of a `/collect` batch; valid events are stored even when
+/// others are rejected
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct CollectResponse {
+    pub accepted: usize,
+    pub rejected: Vec<RejectedEvent>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct RejectedEvent {
+    /// Position in the batch
+    pub index: usize,
+    pub error: String,
+}
+
+/// Unpaginated list response
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ListResponse<T> {
+    pub data: Vec<T>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub count: Option<usize>,
+    /// Period of reports
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub range: Option<ReportRange>,
+}
+
+/// Visitor totals with the daily breakdown
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct VisitorsResponse {
+    pub range: ReportRange,
+    pub total: i64,
+    pub daily: Vec<DailyStats>,
+    /// Annotations overlapping the period
+    pub annotations: Vec<Annotation>,
+}
+
+/// Visitors active in the last few minutes
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct RealtimeResponse {
+    pub active_visitors: usize,
+    pub visitors: Vec<RealtimeVisitor>,
+}
+
+/// Result of a tracking request
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct TrackResponse {
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tracked: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub visitor_id: Option<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<Uuid>,
+    /// Id to send heartbeats for the tracked page view with
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pageview_id: Option<i64>,
+}
+
 /// Query parameters for reports
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct ReportQuery {
+    /// Site to report on; hits stored without a site when absent
+    pub site_id: Option<Uuid>,
     pub from: Option<chrono::NaiveDate>,
     pub to: Option<chrono::NaiveDate>,
     pub period: Option<String>, // "7d", "30d", "90d", "365d", "custom"
@@ -171,9 +486,44 @@ pub struct ReportQuery {
     pub offset: Option<i64>,
 }
 
+/// Query parameters for the real-time visitors
+#[derive(Debug, Clone, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SiteQuery {
+    /// Site to report on; hits stored without a site when absent
+    pub site_id: Option<Uuid>,
+}
+
+/// Query parameters for the UTM breakdown of one landing page
+#[derive(Debug, Clone, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct LandingPageQuery {
+    /// Landing page path, as in the landing pages report
+    pub path: String,
+    pub site_id: Option<Uuid>,
+    pub from: Option<chrono::NaiveDate>,
+    pub to: Option<chrono::NaiveDate>,
+    pub period: Option<String>,
+    pub limit: Option<i64>,
+}
+
+impl LandingPageQuery {
+    pub fn report_query(&self) -> ReportQuery {
+        ReportQuery {
+            site_id: self.site_id,
+            from: self.from,
+            to: self.to,
+            period: self.period.clone(),
+            limit: self.limit,
+            offset: None,
+        }
+    }
+}
+
 impl ReportQuery {
-    pub fn date_range(&self) -> (chrono::NaiveDate, chrono::NaiveDate) {
-        let today = Utc::now().date_naive();
+    /// First and last day of the period; periods end today in `tz`
+    pub fn date_range(&self, tz: Tz) -> (NaiveDate, NaiveDate) {
+        let today = today(tz);
 
         if let (Some(from), Some(to)) = (self.from, self.to) {
             return (from, to);
@@ -188,4 +538,69 @@ impl ReportQuery {
 
         (today - chrono::Duration::days(days), today)
     }
+
+    /// The period in `tz`
+    pub fn range(&self, tz: Tz) -> ReportRange {
+        let (from, to) = self.date_range(tz);
+        ReportRange::new(from, to, tz)
+    }
+
+    /// The period of the same length ending the day before `range`
+    pub fn previous_range(&self, tz: Tz) -> ReportRange {
+        let (from, to) = self.date_range(tz);
+        let days = (to - from).num_days() + 1;
+
+        ReportRange::new(from - chrono::Duration::days(days), from - chrono::Duration::days(1), tz)
+    }
+}
+
+/// Today's date in `tz`
+pub fn today(tz: Tz) -> NaiveDate {
+    Utc::now().with_timezone(&tz).date_naive()
+}
+
+/// When `date` begins in `tz`; days starting with a DST jump begin when the
+/// clocks do
+pub fn day_start(date: NaiveDate, tz: Tz) -> DateTime<FixedOffset> {
+    let midnight = date.and_time(NaiveTime::MIN);
+    let start = (0..=2)
+        .find_map(|hours| tz.from_local_datetime(&(midnight + chrono::Duration::hours(hours))).earliest())
+        .unwrap_or_else(|| tz.from_utc_datetime(&midnight));
+    start.fixed_offset()
+}
+
+/// A report's period: whole days in the reporting time zone, and the
+/// instants they span with the zone's UTC offset at each end
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct ReportRange {
+    /// IANA zone days are counted in, e.g. `Europe/Berlin`
+    pub timezone: String,
+    /// First day
+    pub from: NaiveDate,
+    /// Last day, included
+    pub to: NaiveDate,
+    /// When `from` begins, e.g. `2026-03-01T00:00:00+01:00`
+    pub start: DateTime<FixedOffset>,
+    /// When the day after `to` begins
+    pub end: DateTime<FixedOffset>,
+}
+
+impl ReportRange {
+    pub fn new(from: NaiveDate, to: NaiveDate, tz: Tz) -> Self {
+        Self {
+            timezone: tz.name().to_string(),
+            from,
+            to,
+            start: day_start(from, tz),
+            end: day_start(to + chrono::Duration::days(1), tz),
+        }
+    }
+
+    pub fn start_utc(&self) -> DateTime<Utc> {
+        self.start.with_timezone(&Utc)
+    }
+
+    pub fn end_utc(&self) -> DateTime<Utc> {
+        self.end.with_timezone(&Utc)
+    }
 }
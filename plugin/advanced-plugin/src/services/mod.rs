@@ -1,14 +1,396 @@
 //! Analytics Services
 
+pub mod sinks;
+
 use crate::models::*;
 use crate::AnalyticsConfig;
-use chrono::{Duration, Utc};
+use chrono::{Duration, NaiveDate, Utc};
+use chrono_tz::Tz;
+use hmac::{Hmac, Mac};
+use rustpress_auth::db::DbPools;
+use rustpress_auth::metrics::{Counter, Gauge, MetricsError, PluginMetrics};
+use rustpress_auth::secrets::SecretProvider;
+use sha2::{Digest, Sha256};
 use sqlx::PgPool;
+use std::collections::{BTreeMap, HashMap};
 use std::net::IpAddr;
-use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::sync::{mpsc, RwLock};
 use uuid::Uuid;
 
+pub use sinks::{parse_sinks, SinkConfig, SinkEvent, Sinks};
+
+// ============================================
+// Ingest Metrics
+// ============================================
+
+/// Ingestion counters exported through the shared metrics registry
+#[derive(Clone)]
+pub struct IngestMetrics {
+    pageviews: Counter,
+    events: Counter,
+    collected: Counter,
+    skipped: Counter,
+    rejected: Counter,
+    in_flight: Gauge,
+    sink_sent: Counter,
+    sink_dropped: Counter,
+    sink_failed: Counter,
+}
+
+impl IngestMetrics {
+    pub fn register(metrics: &PluginMetrics) -> Result<Self, MetricsError> {
+        Ok(Self {
+            pageviews: metrics.counter("analytics_pageviews_total", "Page views stored")?,
+            events: metrics.counter("analytics_events_total", "Tracker events stored")?,
+            collected: metrics.counter("analytics_collected_events_total", "Backend events stored via /collect")?,
+            skipped: metrics.counter("analytics_skipped_hits_total", "Hits dropped by exclusion rules")?,
+            rejected: metrics.counter("analytics_rejected_hits_total", "Hits refused by quotas or signature checks")?,
+            in_flight: metrics.gauge("analytics_ingest_in_flight", "Hits being written")?,
+            sink_sent: metrics.counter("analytics_sink_events_total", "Events delivered to sinks")?,
+            sink_dropped: metrics.counter(
+                "analytics_sink_dropped_events_total",
+                "Events sinks dropped: queue full, paused or delivery failed",
+            )?,
+            sink_failed: metrics.counter("analytics_sink_failed_batches_total", "Batches sinks failed to deliver")?,
+        })
+    }
+
+    /// Count a write as in flight until the guard drops
+    fn start(&self) -> InFlight {
+        self.in_flight.inc();
+        InFlight(self.in_flight.clone())
+    }
+}
+
+struct InFlight(Gauge);
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.dec();
+    }
+}
+
+// ============================================
+// GeoIP
+// ============================================
+
+/// The GeoLite2 City and ASN databases in `data/`; lookups in a missing one
+/// find nothing
+pub struct GeoIp {
+    city: Option<maxminddb::Reader<Vec<u8>>>,
+    asn: Option<maxminddb::Reader<Vec<u8>>>,
+}
+
+impl GeoIp {
+    pub fn open() -> Self {
+        Self {
+            city: maxminddb::Reader::open_readfile("data/GeoLite2-City.mmdb").ok(),
+            asn: maxminddb::Reader::open_readfile("data/GeoLite2-ASN.mmdb").ok(),
+        }
+    }
+
+    /// Country code and English city name of `ip`
+    pub fn locate(&self, ip: Option<IpAddr>) -> (Option<String>, Option<String>) {
+        let Some(ip) = ip else {
+            return (None, None);
+        };
+
+        let Some(reader) = &self.city else {
+            return (None, None);
+        };
+
+        if let Ok(city) = reader.lookup::<maxminddb::geoip2::City>(ip) {
+            let country = city.country
+                .and_then(|c| c.iso_code)
+                .map(String::from);
+            let city_name = city.city
+                .and_then(|c| c.names)
+                .and_then(|n| n.get("en").copied())
+                .map(String::from);
+            return (country, city_name);
+        }
+
+        (None, None)
+    }
+}
+
+/// Locates logins for the auth plugin's new country / network checks
+impl rustpress_auth::anomaly::GeoLookup for GeoIp {
+    fn lookup(&self, ip: IpAddr) -> rustpress_auth::anomaly::GeoInfo {
+        let (country, _) = self.locate(Some(ip));
+        let asn = self
+            .asn
+            .as_ref()
+            .and_then(|reader| reader.lookup::<maxminddb::geoip2::Asn>(ip).ok())
+            .and_then(|asn| asn.autonomous_system_number)
+            .map(i64::from);
+        rustpress_auth::anomaly::GeoInfo { country, asn }
+    }
+}
+
+// ============================================
+// Region Rules
+// ============================================
+
+/// Members of the European Union
+const EU_COUNTRIES: [&str; 27] = [
+    "AT", "BE", "BG", "HR", "CY", "CZ", "DK", "EE", "FI", "FR", "DE", "GR", "HU", "IE", "IT", "LV", "LT", "LU",
+    "MT", "NL", "PL", "PT", "RO", "SK", "SI", "ES", "SE",
+];
+
+/// Members of the European Economic Area outside the EU
+const EEA_NON_EU_COUNTRIES: [&str; 3] = ["IS", "LI", "NO"];
+
+/// Parse the `region_rules` setting: one `<regions>: <mode>` rule per line,
+/// e.g. `EEA, CH: cookieless` or `*: disabled`
+///
+/// Regions are ISO country codes, the groups `EU` and `EEA`, or `*` for
+/// every visitor, including those who couldn't be located. Blank lines and
+/// lines starting with `#` are skipped.
+pub fn parse_region_rules(text: &str) -> Result<Vec<RegionRule>, String> {
+    let mut rules = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let invalid = |reason: String| format!("region_rules line {}: {}", number + 1, reason);
+
+        let (regions, mode) = line
+            .split_once(':')
+            .ok_or_else(|| invalid("expected <regions>: <mode>".into()))?;
+        let mode = match mode.trim() {
+            "full" => TrackingMode::Full,
+            "cookieless" => TrackingMode::Cookieless,
+            "disabled" => TrackingMode::Disabled,
+            other => return Err(invalid(format!("unknown mode {:?}", other))),
+        };
+        let regions = regions
+            .split(',')
+            .map(|region| region.trim().to_ascii_uppercase())
+            .filter(|region| !region.is_empty())
+            .map(|region| {
+                let known = matches!(region.as_str(), "*" | "EU" | "EEA")
+                    || (region.len() == 2 && region.bytes().all(|b| b.is_ascii_uppercase()));
+                if known {
+                    Ok(region)
+                } else {
+                    Err(invalid(format!("unknown region {:?}", region)))
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if regions.is_empty() {
+            return Err(invalid("no regions".into()));
+        }
+        rules.push(RegionRule { regions, mode });
+    }
+    Ok(rules)
+}
+
+fn region_matches(rule: &RegionRule, country: Option<&str>) -> bool {
+    rule.regions.iter().any(|region| match (region.as_str(), country) {
+        ("*", _) => true,
+        (_, None) => false,
+        ("EU", Some(country)) => EU_COUNTRIES.contains(&country),
+        ("EEA", Some(country)) => EU_COUNTRIES.contains(&country) || EEA_NON_EU_COUNTRIES.contains(&country),
+        (code, Some(country)) => code == country,
+    })
+}
+
+/// Salt of the day for cookieless visitor hashes, shared by every instance
+/// through `analytics_salts`; earlier days' salts are deleted so old hashes
+/// can't be recomputed
+struct DailySalt {
+    db: PgPool,
+    current: Mutex<Option<(NaiveDate, Vec<u8>)>>,
+}
+
+impl DailySalt {
+    fn new(db: PgPool) -> Self {
+        Self {
+            db,
+            current: Mutex::new(None),
+        }
+    }
+
+    async fn get(&self) -> Result<Vec<u8>, sqlx::Error> {
+        let today = Utc::now().date_naive();
+        if let Some((date, salt)) = &*self.current.lock().unwrap() {
+            if *date == today {
+                return Ok(salt.clone());
+            }
+        }
+
+        // The first instance to need today's salt picks it
+        let candidate: Vec<u8> = [Uuid::new_v4(), Uuid::new_v4()]
+            .iter()
+            .flat_map(|id| *id.as_bytes())
+            .collect();
+        sqlx::query!(
+            "INSERT INTO analytics_salts (date, salt) VALUES ($1, $2) ON CONFLICT (date) DO NOTHING",
+            today,
+            candidate,
+        )
+        .execute(&self.db)
+        .await?;
+        let salt = sqlx::query_scalar!("SELECT salt FROM analytics_salts WHERE date = $1", today)
+            .fetch_one(&self.db)
+            .await?;
+        sqlx::query!("DELETE FROM analytics_salts WHERE date < $1", today)
+            .execute(&self.db)
+            .await?;
+
+        *self.current.lock().unwrap() = Some((today, salt.clone()));
+        Ok(salt)
+    }
+}
+
+// ============================================
+// Ingest Quotas
+// ============================================
+
+/// Secret holding the key the tracker signs hits with
+pub const TRACKER_KEY_SECRET: &str = "analytics.tracker_key";
+
+/// How far a signed hit's timestamp may be from the server's clock
+const MAX_SIGNATURE_SKEW_MS: i64 = 5 * 60 * 1000;
+
+/// Counters kept before expired windows are dropped
+const MAX_QUOTA_KEYS: usize = 100_000;
+
+/// Seconds rejection counts are held in memory before being stored
+const REJECTION_FLUSH_SECS: u64 = 60;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Hits per key in fixed windows
+struct Quota {
+    /// Hits allowed per window; 0 means no limit
+    limit: u32,
+    window: std::time::Duration,
+    hits: Mutex<HashMap<String, (Instant, u32)>>,
+}
+
+impl Quota {
+    fn new(limit: u32, window: std::time::Duration) -> Self {
+        Self {
+            limit,
+            window,
+            hits: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Count a hit; false once the key is over its limit
+    fn admit(&self, key: String) -> bool {
+        if self.limit == 0 {
+            return true;
+        }
+
+        let now = Instant::now();
+        let mut hits = self.hits.lock().unwrap();
+        if hits.len() >= MAX_QUOTA_KEYS {
+            hits.retain(|_, (started, _)| now.duration_since(*started) < self.window);
+        }
+
+        let (started, count) = hits.entry(key).or_insert((now, 0));
+        if now.duration_since(*started) >= self.window {
+            *started = now;
+            *count = 0;
+        }
+        *count = count.saturating_add(1);
+        *count <= self.limit
+    }
+}
+
+/// IPv6 clients are counted per /64, which one host can rotate through
+fn ip_quota_key(ip: IpAddr) -> String {
+    match ip {
+        IpAddr::V4(v4) => v4.to_string(),
+        IpAddr::V6(v6) => {
+            let s = v6.segments();
+            format!("{:x}:{:x}:{:x}:{:x}::/64", s[0], s[1], s[2], s[3])
+        }
+    }
+}
+
+/// Rejected hits per day and reason, stored in batches so a flood of
+/// refused hits doesn't turn into a flood of writes
+struct Rejections {
+    db: PgPool,
+    /// Zone the days are counted in
+    timezone: Tz,
+    pending: Mutex<(Instant, HashMap<(NaiveDate, RejectionReason), i64>)>,
+}
+
+impl Rejections {
+    fn new(db: PgPool, timezone: Tz) -> Self {
+        Self {
+            db,
+            timezone,
+            pending: Mutex::new((Instant::now(), HashMap::new())),
+        }
+    }
+
+    /// Count a rejection, storing the counts in the background when they're due
+    fn record(&self, reason: RejectionReason) {
+        let mut pending = self.pending.lock().unwrap();
+        *pending.1.entry((today(self.timezone), reason)).or_insert(0) += 1;
+        if pending.0.elapsed().as_secs() < REJECTION_FLUSH_SECS {
+            return;
+        }
+
+        pending.0 = Instant::now();
+        let counts = std::mem::take(&mut pending.1);
+        let db = self.db.clone();
+        tokio::spawn(async move {
+            if let Err(e) = store_rejections(&db, counts).await {
+                tracing::warn!("Failed to store rejected hit counts: {}", e);
+            }
+        });
+    }
+
+    /// Store the counts held in memory now
+    async fn flush(&self) -> Result<(), sqlx::Error> {
+        let counts = {
+            let mut pending = self.pending.lock().unwrap();
+            pending.0 = Instant::now();
+            std::mem::take(&mut pending.1)
+        };
+        store_rejections(&self.db, counts).await
+    }
+}
+
+async fn store_rejections(db: &PgPool, counts: HashMap<(NaiveDate, RejectionReason), i64>) -> Result<(), sqlx::Error> {
+    for ((date, reason), count) in counts {
+        sqlx::query!(
+            r#"
+            INSERT INTO analytics_rejections (date, reason, count)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (date, reason) DO UPDATE SET count = analytics_rejections.count + EXCLUDED.count
+            "#,
+            date,
+            reason.as_str(),
+            count,
+        )
+        .execute(db)
+        .await?;
+    }
+    Ok(())
+}
+
+/// Decode a lower- or upper-case hex string
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| hex.get(i..i + 2).and_then(|pair| u8::from_str_radix(pair, 16).ok()))
+        .collect()
+}
+
 // ============================================
 // Tracking Service
 // ============================================
@@ -16,15 +398,175 @@ use uuid::Uuid;
 pub struct TrackingService {
     db: PgPool,
     config: AnalyticsConfig,
-    geoip: Option<maxminddb::Reader<Vec<u8>>>,
+    geoip: Arc<GeoIp>,
+    metrics: IngestMetrics,
+    /// From the `analytics.tracker_key` secret
+    tracker_key: Option<String>,
+    ip_quota: Quota,
+    visitor_quota: Quota,
+    rejections: Rejections,
+    salt: DailySalt,
+    sinks: Option<Arc<Sinks>>,
 }
 
 impl TrackingService {
-    pub fn new(db: PgPool, config: AnalyticsConfig) -> Self {
-        // Try to load GeoIP database
-        let geoip = maxminddb::Reader::open_readfile("data/GeoLite2-City.mmdb").ok();
+    pub fn new(
+        db: PgPool,
+        config: AnalyticsConfig,
+        geoip: Arc<GeoIp>,
+        metrics: IngestMetrics,
+        tracker_key: Option<String>,
+    ) -> Self {
+        let ip_quota = Quota::new(config.ip_minute_quota, std::time::Duration::from_secs(60));
+        let visitor_quota = Quota::new(config.visitor_hourly_quota, std::time::Duration::from_secs(3600));
+        Self {
+            rejections: Rejections::new(db.clone(), config.timezone),
+            salt: DailySalt::new(db.clone()),
+            db,
+            config,
+            geoip,
+            metrics,
+            tracker_key,
+            ip_quota,
+            visitor_quota,
+            sinks: None,
+        }
+    }
+
+    /// Mirror stored hits to `sinks`; cookieless hits aren't mirrored
+    pub fn with_sinks(mut self, sinks: Arc<Sinks>) -> Self {
+        self.sinks = Some(sinks);
+        self
+    }
+
+    /// Key the tracker signs hits with, handed to it in the page
+    ///
+    /// It's visible to anyone loading a page, so signatures only keep
+    /// scripts that didn't load the tracker from feeding it hits.
+    pub fn tracker_key(&self) -> Option<&str> {
+        self.tracker_key.as_deref()
+    }
+
+    /// Check a raw `/track` body against the signature requirement and the
+    /// IP quota before it's parsed
+    ///
+    /// The signature is the hex HMAC-SHA256 of `<timestamp>.<body>` under the
+    /// tracker key, the timestamp in Unix milliseconds. Refused hits are
+    /// counted for the rejections report.
+    pub fn admit(
+        &self,
+        ip: Option<IpAddr>,
+        signature: Option<&str>,
+        timestamp: Option<&str>,
+        body: &[u8],
+    ) -> Result<(), RejectionReason> {
+        if self.config.require_signature && !self.verify_signature(signature, timestamp, body) {
+            return Err(self.reject(RejectionReason::BadSignature));
+        }
+        if let Some(ip) = ip {
+            if !self.ip_quota.admit(ip_quota_key(ip)) {
+                return Err(self.reject(RejectionReason::IpQuota));
+            }
+        }
+        Ok(())
+    }
+
+    /// Count a hit against its visitor's hourly quota
+    pub fn admit_visitor(&self, visitor_id: Uuid) -> Result<(), RejectionReason> {
+        if !self.visitor_quota.admit(visitor_id.to_string()) {
+            return Err(self.reject(RejectionReason::VisitorQuota));
+        }
+        Ok(())
+    }
+
+    /// Store the rejection counts not written yet, e.g. before reporting
+    pub async fn flush_rejections(&self) -> Result<(), TrackingError> {
+        self.rejections
+            .flush()
+            .await
+            .map_err(|e| TrackingError::Database(e.to_string()))
+    }
+
+    fn verify_signature(&self, signature: Option<&str>, timestamp: Option<&str>, body: &[u8]) -> bool {
+        let (Some(key), Some(signature), Some(timestamp)) = (&self.tracker_key, signature, timestamp) else {
+            return false;
+        };
+        let fresh = timestamp
+            .parse::<i64>()
+            .is_ok_and(|ms| (Utc::now().timestamp_millis() - ms).abs() <= MAX_SIGNATURE_SKEW_MS);
+        let Some(signature) = decode_hex(signature).filter(|_| fresh) else {
+            return false;
+        };
+
+        let mut mac = HmacSha256::new_from_slice(key.as_bytes()).expect("HMAC accepts keys of any length");
+        mac.update(timestamp.as_bytes());
+        mac.update(b".");
+        mac.update(body);
+        mac.verify_slice(&signature).is_ok()
+    }
 
-        Self { db, config, geoip }
+    fn reject(&self, reason: RejectionReason) -> RejectionReason {
+        self.metrics.rejected.inc();
+        self.rejections.record(reason);
+        reason
+    }
+
+    /// The tracking mode for a visitor at `ip`, from the first matching
+    /// region rule
+    ///
+    /// Region rules only apply to visitors; hits without an address (e.g.
+    /// from hooks) are tracked in full.
+    pub fn policy(&self, ip: Option<IpAddr>) -> TrackingPolicy {
+        let country = self.geoip.locate(ip).0;
+        let mode = if !self.config.tracking_enabled {
+            TrackingMode::Disabled
+        } else if ip.is_none() {
+            TrackingMode::Full
+        } else {
+            self.config
+                .region_rules
+                .iter()
+                .find(|rule| region_matches(rule, country.as_deref()))
+                .map_or(TrackingMode::Full, |rule| rule.mode)
+        };
+        TrackingPolicy {
+            mode,
+            legal_basis: self.config.legal_basis,
+            country,
+        }
+    }
+
+    /// Drop hits from regions where tracking is off, before any write
+    fn check_region(&self, ip: Option<IpAddr>) -> Result<TrackingMode, TrackingError> {
+        match self.policy(ip).mode {
+            TrackingMode::Disabled => {
+                self.metrics.skipped.inc();
+                Err(TrackingError::RegionDisabled)
+            }
+            mode => Ok(mode),
+        }
+    }
+
+    /// Visitor id of a cookieless hit: a hash of the day's salt, the site,
+    /// the address and the user agent, so the same browser is one visitor
+    /// for the day and can't be followed across days
+    async fn cookieless_visitor(
+        &self,
+        site_id: Option<Uuid>,
+        ip: Option<IpAddr>,
+        user_agent: &str,
+    ) -> Result<Uuid, TrackingError> {
+        let salt = self.salt.get().await.map_err(|e| TrackingError::Database(e.to_string()))?;
+        let mut hasher = Sha256::new();
+        hasher.update(&salt);
+        hasher.update(site_id.unwrap_or_default().as_bytes());
+        hasher.update(ip.map(|ip| ip.to_string()).unwrap_or_default().as_bytes());
+        hasher.update(user_agent.as_bytes());
+        let digest = hasher.finalize();
+
+        let mut bytes = [0u8; 16];
+        bytes.copy_from_slice(&digest[..16]);
+        Ok(uuid::Builder::from_random_bytes(bytes).into_uuid())
     }
 
     /// Track a page view
@@ -33,7 +575,7 @@ impl TrackingService {
         input: &TrackingInput,
         ip: Option<IpAddr>,
         user_agent: &str,
-    ) -> Result<(Uuid, Uuid), TrackingError> {
+    ) -> Result<(Uuid, Uuid, i64), TrackingError> {
         // Check if tracking is enabled
         if !self.config.tracking_enabled {
             return Err(TrackingError::Disabled);
@@ -41,6 +583,7 @@ impl TrackingService {
 
         // Check excluded paths
         if self.config.excluded_paths.iter().any(|p| input.path.starts_with(p)) {
+            self.metrics.skipped.inc();
             return Err(TrackingError::ExcludedPath);
         }
 
@@ -48,43 +591,57 @@ impl TrackingService {
         if let Some(ip) = ip {
             let ip_str = ip.to_string();
             if self.config.excluded_ips.contains(&ip_str) {
+                self.metrics.skipped.inc();
                 return Err(TrackingError::ExcludedIP);
             }
         }
 
+        let cookieless = self.check_region(ip)? == TrackingMode::Cookieless;
+        let _in_flight = self.metrics.start();
+
         // Parse user agent
         let ua = user_agent_parser::parse(user_agent);
         let device_type = self.detect_device_type(&ua);
+        let browser_version = ua.browser.as_ref().and_then(|b| b.version).map(String::from);
+        let os_version = ua.os.as_ref().and_then(|o| o.version).map(String::from);
         let browser = ua.browser.map(|b| b.name).unwrap_or("Unknown").to_string();
         let os = ua.os.map(|o| o.name).unwrap_or("Unknown").to_string();
 
-        // Get or create visitor/session
-        let visitor_id = input.visitor_id.unwrap_or_else(Uuid::new_v4);
+        // Get or create visitor/session; cookieless hits carry no ids
+        let visitor_id = if cookieless {
+            self.cookieless_visitor(input.site_id, ip, user_agent).await?
+        } else {
+            input.visitor_id.unwrap_or_else(Uuid::new_v4)
+        };
         let session_id = self.get_or_create_session(
+            input.site_id,
             visitor_id,
             &input.path,
             &device_type,
-            &browser,
-            &os,
+            (&browser, browser_version.as_deref()),
+            (&os, os_version.as_deref()),
             ip,
         ).await?;
 
-        // Anonymize IP if configured
-        let stored_ip = if self.config.anonymize_ip {
+        // Anonymize IP if configured; cookieless hits don't keep it at all
+        let stored_ip = if cookieless {
+            None
+        } else if self.config.anonymize_ip {
             ip.map(|i| self.anonymize_ip(i))
         } else {
             ip.map(|i| i.to_string())
         };
 
         // Get geolocation
-        let (country, city) = self.get_geolocation(ip);
+        let (country, city) = self.geoip.locate(ip);
 
         // Insert page view
-        sqlx::query!(
+        let pageview_id = sqlx::query_scalar!(
             r#"
             INSERT INTO analytics_pageviews
-            (session_id, visitor_id, path, title, referrer, utm_source, utm_medium, utm_campaign, ip_address, country, city)
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+            (session_id, visitor_id, path, title, referrer, utm_source, utm_medium, utm_campaign, ip_address, country, city, site_id)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+            RETURNING id
             "#,
             session_id,
             visitor_id,
@@ -97,8 +654,9 @@ impl TrackingService {
             stored_ip,
             country,
             city,
+            input.site_id,
         )
-        .execute(&self.db)
+        .fetch_one(&self.db)
         .await
         .map_err(|e| TrackingError::Database(e.to_string()))?;
 
@@ -119,19 +677,85 @@ impl TrackingService {
         .await
         .map_err(|e| TrackingError::Database(e.to_string()))?;
 
-        Ok((visitor_id, session_id))
+        self.metrics.pageviews.inc();
+        if let Some(sinks) = self.sinks.as_ref().filter(|_| !cookieless) {
+            sinks.publish(&SinkEvent::pageview(input, visitor_id, session_id, country));
+        }
+        Ok((visitor_id, session_id, pageview_id))
+    }
+
+    /// Add visible time reported by the tracker to a page view and its
+    /// session; returns false when the page view isn't the visitor's
+    ///
+    /// A single ping counts for at most two heartbeat intervals, so a
+    /// misbehaving client can't inflate engagement.
+    pub async fn track_heartbeat(&self, input: &TrackingInput, ip: Option<IpAddr>) -> Result<bool, TrackingError> {
+        if !self.config.tracking_enabled || self.config.heartbeat_interval_secs == 0 {
+            return Err(TrackingError::Disabled);
+        }
+        self.check_region(ip)?;
+
+        let visitor_id = input.visitor_id.ok_or(TrackingError::MissingVisitorId)?;
+        let session_id = input.session_id.ok_or(TrackingError::MissingSessionId)?;
+        let pageview_id = input.pageview_id.ok_or(TrackingError::MissingPageviewId)?;
+
+        let max_seconds = (self.config.heartbeat_interval_secs * 2) as i32;
+        let seconds = match input.engaged_seconds {
+            Some(seconds) if seconds > 0 => seconds.min(max_seconds),
+            _ => return Err(TrackingError::InvalidEngagement),
+        };
+
+        let updated = sqlx::query!(
+            r#"
+            UPDATE analytics_pageviews
+            SET engagement_seconds = COALESCE(engagement_seconds, 0) + $1
+            WHERE id = $2 AND session_id = $3 AND visitor_id = $4
+            "#,
+            seconds,
+            pageview_id,
+            session_id,
+            visitor_id,
+        )
+        .execute(&self.db)
+        .await
+        .map_err(|e| TrackingError::Database(e.to_string()))?
+        .rows_affected();
+
+        if updated == 0 {
+            return Ok(false);
+        }
+
+        // Session duration is the sum of its pages' engagement
+        sqlx::query!(
+            r#"
+            UPDATE analytics_sessions
+            SET duration_seconds = COALESCE(duration_seconds, 0) + $1,
+                ended_at = NOW()
+            WHERE id = $2
+            "#,
+            seconds,
+            session_id,
+        )
+        .execute(&self.db)
+        .await
+        .map_err(|e| TrackingError::Database(e.to_string()))?;
+
+        Ok(true)
     }
 
     /// Track a custom event
     pub async fn track_event(
         &self,
         input: &TrackingInput,
+        ip: Option<IpAddr>,
     ) -> Result<(), TrackingError> {
         if !self.config.tracking_enabled {
             return Err(TrackingError::Disabled);
         }
+        let cookieless = self.check_region(ip)? == TrackingMode::Cookieless;
 
         let visitor_id = input.visitor_id.ok_or(TrackingError::MissingVisitorId)?;
+        let _in_flight = self.metrics.start();
         let session_id = input.session_id.ok_or(TrackingError::MissingSessionId)?;
 
         sqlx::query!(
@@ -152,16 +776,21 @@ impl TrackingService {
         .await
         .map_err(|e| TrackingError::Database(e.to_string()))?;
 
+        self.metrics.events.inc();
+        if let Some(sinks) = self.sinks.as_ref().filter(|_| !cookieless) {
+            sinks.publish(&SinkEvent::event(input, visitor_id, session_id));
+        }
         Ok(())
     }
 
     async fn get_or_create_session(
         &self,
+        site_id: Option<Uuid>,
         visitor_id: Uuid,
         entry_page: &str,
         device_type: &str,
-        browser: &str,
-        os: &str,
+        (browser, browser_version): (&str, Option<&str>),
+        (os, os_version): (&str, Option<&str>),
         ip: Option<IpAddr>,
     ) -> Result<Uuid, TrackingError> {
         // Check for existing active session (within last 30 minutes)
@@ -170,11 +799,12 @@ impl TrackingService {
         let existing = sqlx::query_scalar!(
             r#"
             SELECT id FROM analytics_sessions
-            WHERE visitor_id = $1 AND ended_at > $2
+            WHERE visitor_id = $1 AND ended_at > $2 AND site_id IS NOT DISTINCT FROM $3
             ORDER BY ended_at DESC LIMIT 1
             "#,
             visitor_id,
             cutoff,
+            site_id,
         )
         .fetch_optional(&self.db)
         .await
@@ -186,22 +816,26 @@ impl TrackingService {
 
         // Create new session
         let session_id = Uuid::new_v4();
-        let (country, city) = self.get_geolocation(ip);
+        let (country, city) = self.geoip.locate(ip);
 
         sqlx::query!(
             r#"
             INSERT INTO analytics_sessions
-            (id, visitor_id, entry_page, device_type, browser, os, country, city, page_views, is_bounce)
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, 0, true)
+            (id, visitor_id, entry_page, device_type, browser, browser_version, os, os_version,
+             country, city, page_views, is_bounce, site_id)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, 0, true, $11)
             "#,
             session_id,
             visitor_id,
             entry_page,
             device_type,
             browser,
+            browser_version,
             os,
+            os_version,
             country,
             city,
+            site_id,
         )
         .execute(&self.db)
         .await
@@ -234,47 +868,262 @@ impl TrackingService {
             }
         }
     }
+}
 
-    fn get_geolocation(&self, ip: Option<IpAddr>) -> (Option<String>, Option<String>) {
-        let Some(ip) = ip else {
-            return (None, None);
-        };
+// ============================================
+// Ingest Service
+// ============================================
 
-        let Some(reader) = &self.geoip else {
-            return (None, None);
-        };
+/// Secret holding the comma-separated keys accepted by `/collect`
+pub const COLLECT_KEYS_SECRET: &str = "analytics.collect_keys";
 
-        if let Ok(city) = reader.lookup::<maxminddb::geoip2::City>(ip) {
-            let country = city.country
-                .and_then(|c| c.iso_code)
-                .map(String::from);
-            let city_name = city.city
-                .and_then(|c| c.names)
-                .and_then(|n| n.get("en").copied())
-                .map(String::from);
-            return (country, city_name);
+/// Most events accepted in one `/collect` request
+pub const MAX_COLLECT_BATCH: usize = 500;
+
+/// How far in the future a backend clock may be
+const MAX_CLOCK_SKEW_MINUTES: i64 = 5;
+
+/// Server-to-server event ingestion, authenticated by API key
+///
+/// Keys come from the `analytics.collect_keys` secret and are re-read every
+/// `refresh` so a rotated key takes effect without re-activating the plugin;
+/// list both keys while backends switch over.
+pub struct IngestService {
+    db: PgPool,
+    config: AnalyticsConfig,
+    secrets: Arc<dyn SecretProvider>,
+    refresh: std::time::Duration,
+    metrics: IngestMetrics,
+    /// SHA-256 digests of the keys and when they were read
+    keys: RwLock<Option<(Instant, Vec<[u8; 32]>)>>,
+    sinks: Option<Arc<Sinks>>,
+}
+
+impl IngestService {
+    pub fn new(
+        db: PgPool,
+        config: AnalyticsConfig,
+        secrets: Arc<dyn SecretProvider>,
+        refresh: std::time::Duration,
+        metrics: IngestMetrics,
+    ) -> Self {
+        Self {
+            db,
+            config,
+            secrets,
+            refresh,
+            metrics,
+            keys: RwLock::new(None),
+            sinks: None,
         }
+    }
 
-        (None, None)
+    /// Mirror stored events to `sinks`
+    pub fn with_sinks(mut self, sinks: Arc<Sinks>) -> Self {
+        self.sinks = Some(sinks);
+        self
+    }
+
+    /// Whether `key` is one of the configured keys; `Disabled` when none are
+    pub async fn authorize(&self, key: &str) -> Result<bool, IngestError> {
+        let keys = self.keys().await?;
+        if keys.is_empty() {
+            return Err(IngestError::Disabled);
+        }
+
+        let digest: [u8; 32] = Sha256::digest(key.as_bytes()).into();
+        Ok(keys.iter().fold(false, |found, k| found | digests_eq(k, &digest)))
+    }
+
+    async fn keys(&self) -> Result<Vec<[u8; 32]>, IngestError> {
+        if let Some((read_at, keys)) = self.keys.read().await.as_ref() {
+            if read_at.elapsed() < self.refresh {
+                return Ok(keys.clone());
+            }
+        }
+
+        let value = self
+            .secrets
+            .get(COLLECT_KEYS_SECRET)
+            .await
+            .map_err(|e| IngestError::Secrets(e.to_string()))?
+            .unwrap_or_default();
+        let keys: Vec<[u8; 32]> = value
+            .split(',')
+            .map(str::trim)
+            .filter(|k| !k.is_empty())
+            .map(|k| Sha256::digest(k.as_bytes()).into())
+            .collect();
+
+        *self.keys.write().await = Some((Instant::now(), keys.clone()));
+        Ok(keys)
+    }
+
+    /// Store a batch of events with their own timestamps
+    ///
+    /// Each event is attached to its session, the visitor's latest session
+    /// started before it, or a new session with device type `server` for
+    /// visitors never seen in a browser. Invalid events are reported by
+    /// index and skipped.
+    pub async fn collect(&self, events: &[CollectEvent]) -> Result<CollectResponse, IngestError> {
+        let now = Utc::now();
+        let oldest = now - Duration::days(self.config.data_retention_days as i64);
+        let newest = now + Duration::minutes(MAX_CLOCK_SKEW_MINUTES);
+
+        let _in_flight = self.metrics.start();
+        let mut tx = self.db.begin().await?;
+        let mut accepted = Vec::new();
+        let mut rejected = Vec::new();
+
+        for (index, event) in events.iter().enumerate() {
+            let problem = if event.category.trim().is_empty() || event.category.len() > 100 {
+                Some("category must be 1 to 100 characters")
+            } else if event.action.trim().is_empty() || event.action.len() > 100 {
+                Some("action must be 1 to 100 characters")
+            } else if event.timestamp < oldest {
+                Some("timestamp is older than the data retention period")
+            } else if event.timestamp > newest {
+                Some("timestamp is in the future")
+            } else {
+                None
+            };
+            if let Some(error) = problem {
+                rejected.push(RejectedEvent {
+                    index,
+                    error: error.to_string(),
+                });
+                continue;
+            }
+
+            let path = event.path.as_deref().unwrap_or("/");
+            let session_id = match event.session_id {
+                Some(session_id) => {
+                    let found = sqlx::query_scalar!(
+                        "SELECT id FROM analytics_sessions WHERE id = $1 AND visitor_id = $2",
+                        session_id,
+                        event.visitor_id,
+                    )
+                    .fetch_optional(&mut *tx)
+                    .await?;
+                    match found {
+                        Some(id) => id,
+                        None => {
+                            rejected.push(RejectedEvent {
+                                index,
+                                error: "session_id is not a session of visitor_id".to_string(),
+                            });
+                            continue;
+                        }
+                    }
+                }
+                None => self.session_for(&mut tx, event, path).await?,
+            };
+
+            sqlx::query!(
+                r#"
+                INSERT INTO analytics_events
+                (session_id, visitor_id, category, action, label, value, path, created_at)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+                "#,
+                session_id,
+                event.visitor_id,
+                event.category,
+                event.action,
+                event.label,
+                event.value,
+                path,
+                event.timestamp,
+            )
+            .execute(&mut *tx)
+            .await?;
+            accepted.push((event, session_id));
+        }
+
+        tx.commit().await?;
+        self.metrics.collected.inc_by(accepted.len() as u64);
+        if let Some(sinks) = &self.sinks {
+            for (event, session_id) in &accepted {
+                sinks.publish(&SinkEvent::collected(event, *session_id));
+            }
+        }
+        Ok(CollectResponse {
+            accepted: accepted.len(),
+            rejected,
+        })
+    }
+
+    async fn session_for(
+        &self,
+        tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+        event: &CollectEvent,
+        path: &str,
+    ) -> Result<Uuid, IngestError> {
+        let latest = sqlx::query_scalar!(
+            r#"
+            SELECT id FROM analytics_sessions
+            WHERE visitor_id = $1 AND site_id IS NOT DISTINCT FROM $2 AND started_at <= $3
+            ORDER BY started_at DESC LIMIT 1
+            "#,
+            event.visitor_id,
+            event.site_id,
+            event.timestamp,
+        )
+        .fetch_optional(&mut **tx)
+        .await?;
+
+        if let Some(session_id) = latest {
+            return Ok(session_id);
+        }
+
+        let session_id = sqlx::query_scalar!(
+            r#"
+            INSERT INTO analytics_sessions
+            (visitor_id, started_at, ended_at, entry_page, exit_page, device_type, browser, os,
+             page_views, is_bounce, site_id)
+            VALUES ($1, $2, $2, $3, $3, 'server', 'Server', 'Server', 0, false, $4)
+            RETURNING id
+            "#,
+            event.visitor_id,
+            event.timestamp,
+            path,
+            event.site_id,
+        )
+        .fetch_one(&mut **tx)
+        .await?;
+
+        Ok(session_id)
     }
 }
 
+/// Compare digests without stopping at the first difference
+fn digests_eq(a: &[u8; 32], b: &[u8; 32]) -> bool {
+    a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
 // ============================================
 // Analytics Service
 // ============================================
 
+/// Dashboard queries; served from a read replica when one is in sync
 pub struct AnalyticsService {
-    db: PgPool,
+    db: Arc<DbPools>,
     redis: deadpool_redis::Pool,
+    /// Zone report days are counted in
+    timezone: Tz,
 }
 
 impl AnalyticsService {
-    pub fn new(db: PgPool, redis: deadpool_redis::Pool) -> Self {
-        Self { db, redis }
+    pub fn new(db: Arc<DbPools>, redis: deadpool_redis::Pool, timezone: Tz) -> Self {
+        Self { db, redis, timezone }
     }
 
-    /// Get real-time active visitors
-    pub async fn get_realtime_visitors(&self) -> Result<Vec<RealtimeVisitor>, AnalyticsError> {
+    /// The period `query` asks for, in the reporting time zone
+    pub fn range(&self, query: &ReportQuery) -> ReportRange {
+        query.range(self.timezone)
+    }
+
+    /// Get real-time active visitors of a site
+    pub async fn get_realtime_visitors(&self, site_id: Option<Uuid>) -> Result<Vec<RealtimeVisitor>, AnalyticsError> {
         let cutoff = Utc::now() - Duration::minutes(5);
 
         let visitors = sqlx::query_as!(
@@ -291,12 +1140,13 @@ impl AnalyticsService {
                 s.page_views
             FROM analytics_sessions s
             JOIN analytics_pageviews p ON p.session_id = s.id
-            WHERE s.ended_at > $1
+            WHERE s.ended_at > $1 AND s.site_id IS NOT DISTINCT FROM $2
             ORDER BY s.visitor_id, p.created_at DESC
             "#,
             cutoff,
+            site_id,
         )
-        .fetch_all(&self.db)
+        .fetch_all(self.db.read())
         .await
         .map_err(|e| AnalyticsError::Database(e.to_string()))?;
 
@@ -305,7 +1155,7 @@ impl AnalyticsService {
 
     /// Get page views for a period
     pub async fn get_pageviews(&self, query: &ReportQuery) -> Result<Vec<PageView>, AnalyticsError> {
-        let (from, to) = query.date_range();
+        let range = self.range(query);
         let limit = query.limit.unwrap_or(100).min(1000);
         let offset = query.offset.unwrap_or(0);
 
@@ -313,18 +1163,19 @@ impl AnalyticsService {
             PageView,
             r#"
             SELECT id, session_id, visitor_id, path, title, referrer,
-                   utm_source, utm_medium, utm_campaign, created_at
+                   utm_source, utm_medium, utm_campaign, engagement_seconds, created_at
             FROM analytics_pageviews
-            WHERE created_at::date BETWEEN $1 AND $2
+            WHERE created_at >= $1 AND created_at < $2 AND site_id IS NOT DISTINCT FROM $5
             ORDER BY created_at DESC
             LIMIT $3 OFFSET $4
             "#,
-            from,
-            to,
+            range.start_utc(),
+            range.end_utc(),
             limit,
             offset,
+            query.site_id,
         )
-        .fetch_all(&self.db)
+        .fetch_all(self.db.read())
         .await
         .map_err(|e| AnalyticsError::Database(e.to_string()))?;
 
@@ -333,7 +1184,7 @@ impl AnalyticsService {
 
     /// Get daily statistics
     pub async fn get_daily_stats(&self, query: &ReportQuery) -> Result<Vec<DailyStats>, AnalyticsError> {
-        let (from, to) = query.date_range();
+        let range = self.range(query);
 
         let stats = sqlx::query_as!(
             DailyStats,
@@ -341,74 +1192,503 @@ impl AnalyticsService {
             SELECT date, page_views, unique_visitors, sessions,
                    bounce_rate, avg_session_duration, new_visitors, returning_visitors
             FROM analytics_daily_stats
-            WHERE date BETWEEN $1 AND $2
+            WHERE date BETWEEN $1 AND $2 AND site_id IS NOT DISTINCT FROM $3
             ORDER BY date ASC
             "#,
-            from,
-            to,
+            range.from,
+            range.to,
+            query.site_id,
         )
-        .fetch_all(&self.db)
+        .fetch_all(self.db.read())
         .await
         .map_err(|e| AnalyticsError::Database(e.to_string()))?;
 
         Ok(stats)
     }
+
+    /// Get annotations overlapping a period
+    pub async fn get_annotations(&self, query: &ReportQuery) -> Result<Vec<Annotation>, AnalyticsError> {
+        let range = self.range(query);
+
+        annotations_between(self.db.read(), range.from, range.to)
+            .await
+            .map_err(|e| AnalyticsError::Database(e.to_string()))
+    }
 }
 
 // ============================================
-// Report Service
+// Annotation Service
 // ============================================
 
-pub struct ReportService {
-    db: PgPool,
+/// Notes on the time series; written to the primary
+pub struct AnnotationService {
+    db: Arc<DbPools>,
 }
 
-impl ReportService {
-    pub fn new(db: PgPool) -> Self {
+impl AnnotationService {
+    pub fn new(db: Arc<DbPools>) -> Self {
         Self { db }
     }
 
-    /// Generate overview report
-    pub async fn get_overview(&self, query: &ReportQuery) -> Result<OverviewReport, ReportError> {
-        let (from, to) = query.date_range();
+    pub async fn get(&self, id: Uuid) -> Result<Annotation, AnnotationError> {
+        sqlx::query_as!(
+            Annotation,
+            r#"
+            SELECT id, date, end_date, label, author_id, author_name, created_at, updated_at
+            FROM analytics_annotations
+            WHERE id = $1
+            "#,
+            id,
+        )
+        .fetch_optional(self.db.write())
+        .await?
+        .ok_or(AnnotationError::NotFound(id))
+    }
 
-        // Get totals
-        let totals = sqlx::query!(
+    pub async fn create(
+        &self,
+        input: &AnnotationInput,
+        author_id: Uuid,
+        author_name: &str,
+    ) -> Result<Annotation, AnnotationError> {
+        validate_span(input)?;
+
+        let annotation = sqlx::query_as!(
+            Annotation,
             r#"
-            SELECT
-                COALESCE(SUM(page_views), 0) as total_page_views,
-                COALESCE(SUM(unique_visitors), 0) as unique_visitors,
-                COALESCE(SUM(sessions), 0) as total_sessions,
-                COALESCE(AVG(bounce_rate), 0) as bounce_rate,
-                COALESCE(AVG(avg_session_duration), 0) as avg_session_duration,
-                COALESCE(SUM(new_visitors), 0) as new_visitors,
-                COALESCE(SUM(returning_visitors), 0) as returning_visitors
-            FROM analytics_daily_stats
-            WHERE date BETWEEN $1 AND $2
+            INSERT INTO analytics_annotations (date, end_date, label, author_id, author_name)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING id, date, end_date, label, author_id, author_name, created_at, updated_at
+            "#,
+            input.date,
+            input.end_date,
+            input.label,
+            author_id,
+            author_name,
+        )
+        .fetch_one(self.db.write())
+        .await?;
+
+        Ok(annotation)
+    }
+
+    /// Change the dates and label; the author stays the same
+    pub async fn update(&self, id: Uuid, input: &AnnotationInput) -> Result<Annotation, AnnotationError> {
+        validate_span(input)?;
+
+        sqlx::query_as!(
+            Annotation,
+            r#"
+            UPDATE analytics_annotations
+            SET date = $2, end_date = $3, label = $4, updated_at = NOW()
+            WHERE id = $1
+            RETURNING id, date, end_date, label, author_id, author_name, created_at, updated_at
+            "#,
+            id,
+            input.date,
+            input.end_date,
+            input.label,
+        )
+        .fetch_optional(self.db.write())
+        .await?
+        .ok_or(AnnotationError::NotFound(id))
+    }
+
+    pub async fn delete(&self, id: Uuid) -> Result<(), AnnotationError> {
+        let deleted = sqlx::query!("DELETE FROM analytics_annotations WHERE id = $1", id)
+            .execute(self.db.write())
+            .await?
+            .rows_affected();
+
+        if deleted == 0 {
+            return Err(AnnotationError::NotFound(id));
+        }
+        Ok(())
+    }
+}
+
+fn validate_span(input: &AnnotationInput) -> Result<(), AnnotationError> {
+    match input.end_date {
+        Some(end) if end < input.date => Err(AnnotationError::Invalid("end_date must not be before date".into())),
+        _ => Ok(()),
+    }
+}
+
+/// Annotations covering at least one day of `from..=to`, oldest first
+async fn annotations_between(
+    db: &PgPool,
+    from: chrono::NaiveDate,
+    to: chrono::NaiveDate,
+) -> Result<Vec<Annotation>, sqlx::Error> {
+    sqlx::query_as!(
+        Annotation,
+        r#"
+        SELECT id, date, end_date, label, author_id, author_name, created_at, updated_at
+        FROM analytics_annotations
+        WHERE date <= $2 AND COALESCE(end_date, date) >= $1
+        ORDER BY date ASC, created_at ASC
+        "#,
+        from,
+        to,
+    )
+    .fetch_all(db)
+    .await
+}
+
+// ============================================
+// Erasure Service
+// ============================================
+
+/// Audit action recorded when an erasure request completes
+pub const VISITOR_FORGOTTEN: &str = "analytics.visitor_forgotten";
+
+/// Right-to-erasure requests, worked off one at a time in the background
+///
+/// Rows aren't deleted, which would change past reports: the subject's
+/// visitor ids are replaced by fresh random ones (one per visitor, so visitor
+/// counts stay right) and their IP addresses and cities are cleared. What's
+/// left can't be linked back to the person. Daily aggregates never held
+/// identifiers and are untouched.
+pub struct ErasureService {
+    db: PgPool,
+    queue: mpsc::UnboundedSender<Uuid>,
+}
+
+impl ErasureService {
+    /// The service and its worker, which first resumes requests left
+    /// unfinished by a restart; the worker stops once the service is dropped
+    pub async fn start(db: PgPool) -> Result<Arc<Self>, ErasureError> {
+        let (queue, jobs) = mpsc::unbounded_channel();
+        let unfinished = sqlx::query_scalar!(
+            "SELECT id FROM analytics_erasures WHERE status IN ('pending', 'running') ORDER BY created_at"
+        )
+        .fetch_all(&db)
+        .await?;
+        for id in unfinished {
+            let _ = queue.send(id);
+        }
+
+        tokio::spawn(run_erasures(db.clone(), jobs));
+        Ok(Arc::new(Self { db, queue }))
+    }
+
+    /// Queue the erasure of a visitor's or an IP address's data
+    pub async fn request(&self, input: &ForgetRequest, requested_by: Uuid) -> Result<Erasure, ErasureError> {
+        let (subject, visitor_id, ip) = match (input.visitor_id, input.ip.as_deref().map(str::trim)) {
+            (Some(visitor_id), None) => ("visitor", Some(visitor_id), None),
+            (None, Some(ip)) if ip.parse::<IpAddr>().is_ok() => ("ip", None, Some(ip.to_string())),
+            (None, Some(ip)) => return Err(ErasureError::Invalid(format!("Not an IP address: {}", ip))),
+            _ => return Err(ErasureError::Invalid("Give either visitor_id or ip".into())),
+        };
+
+        let erasure = sqlx::query_as!(
+            Erasure,
+            r#"
+            INSERT INTO analytics_erasures (subject, visitor_id, ip_address, requested_by)
+            VALUES ($1, $2, $3, $4)
+            RETURNING id, subject, status, requested_by, visitors, sessions, pageviews, events,
+                      error, created_at, completed_at
             "#,
-            from,
-            to,
+            subject,
+            visitor_id,
+            ip,
+            requested_by,
         )
         .fetch_one(&self.db)
-        .await
-        .map_err(|e| ReportError::Database(e.to_string()))?;
+        .await?;
 
-        // Get daily breakdown
-        let daily_stats = sqlx::query_as!(
-            DailyStats,
+        // Closed only if the worker panicked; the request resumes on restart
+        let _ = self.queue.send(erasure.id);
+        Ok(erasure)
+    }
+
+    pub async fn get(&self, id: Uuid) -> Result<Erasure, ErasureError> {
+        sqlx::query_as!(
+            Erasure,
             r#"
-            SELECT date, page_views, unique_visitors, sessions,
-                   bounce_rate, avg_session_duration, new_visitors, returning_visitors
-            FROM analytics_daily_stats
-            WHERE date BETWEEN $1 AND $2
-            ORDER BY date ASC
+            SELECT id, subject, status, requested_by, visitors, sessions, pageviews, events,
+                   error, created_at, completed_at
+            FROM analytics_erasures
+            WHERE id = $1
             "#,
-            from,
-            to,
+            id,
         )
-        .fetch_all(&self.db)
-        .await
-        .map_err(|e| ReportError::Database(e.to_string()))?;
+        .fetch_optional(&self.db)
+        .await?
+        .ok_or(ErasureError::NotFound(id))
+    }
+}
+
+/// Rows scrubbed by one erasure
+struct Scrubbed {
+    visitors: i64,
+    sessions: i64,
+    pageviews: i64,
+    events: i64,
+}
+
+async fn run_erasures(db: PgPool, mut jobs: mpsc::UnboundedReceiver<Uuid>) {
+    while let Some(id) = jobs.recv().await {
+        if let Err(e) = run_erasure(&db, id).await {
+            tracing::error!(erasure = %id, "Erasure failed: {}", e);
+            let failed = sqlx::query!(
+                "UPDATE analytics_erasures SET status = 'failed', error = $2, completed_at = NOW() WHERE id = $1",
+                id,
+                e.to_string(),
+            )
+            .execute(&db)
+            .await;
+            if let Err(e) = failed {
+                tracing::error!(erasure = %id, "Failed to record erasure failure: {}", e);
+            }
+        }
+    }
+}
+
+async fn run_erasure(db: &PgPool, id: Uuid) -> Result<(), sqlx::Error> {
+    let Some(request) = sqlx::query!(
+        r#"
+        UPDATE analytics_erasures SET status = 'running'
+        WHERE id = $1 AND status IN ('pending', 'running')
+        RETURNING subject, visitor_id, ip_address, requested_by
+        "#,
+        id,
+    )
+    .fetch_optional(db)
+    .await?
+    else {
+        return Ok(());
+    };
+
+    let mut tx = db.begin().await?;
+    let scrubbed = scrub(&mut tx, request.visitor_id, request.ip_address.as_deref()).await?;
+
+    // The report keeps the counts, not whose data it was
+    sqlx::query!(
+        r#"
+        UPDATE analytics_erasures
+        SET status = 'completed', visitor_id = NULL, ip_address = NULL, visitors = $2, sessions = $3,
+            pageviews = $4, events = $5, completed_at = NOW()
+        WHERE id = $1
+        "#,
+        id,
+        scrubbed.visitors,
+        scrubbed.sessions,
+        scrubbed.pageviews,
+        scrubbed.events,
+    )
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    // The data is gone either way; a missing entry is only logged
+    let audited = rustpress_auth::audit::record(
+        db,
+        request.requested_by,
+        VISITOR_FORGOTTEN,
+        None,
+        serde_json::json!({
+            "erasure_id": id,
+            "subject": request.subject,
+            "visitors": scrubbed.visitors,
+            "sessions": scrubbed.sessions,
+            "pageviews": scrubbed.pageviews,
+            "events": scrubbed.events,
+        }),
+        None,
+    )
+    .await;
+    if let Err(e) = audited {
+        tracing::error!(erasure = %id, "Failed to audit erasure: {}", e);
+    }
+
+    tracing::info!(erasure = %id, visitors = scrubbed.visitors, "Erasure completed");
+    Ok(())
+}
+
+/// Pseudonymise the visitor, or every visitor seen at the IP address
+async fn scrub(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    visitor_id: Option<Uuid>,
+    ip: Option<&str>,
+) -> Result<Scrubbed, sqlx::Error> {
+    let visitors: Vec<Uuid> = match (visitor_id, ip) {
+        (Some(visitor_id), _) => vec![visitor_id],
+        (None, Some(ip)) => {
+            // Stored as given or in the canonical form, e.g. for IPv6
+            let forms: Vec<String> = std::iter::once(ip.to_string())
+                .chain(ip.parse::<IpAddr>().ok().map(|ip| ip.to_string()))
+                .collect();
+            sqlx::query_scalar!(
+                "SELECT DISTINCT visitor_id FROM analytics_pageviews WHERE ip_address = ANY($1)",
+                &forms,
+            )
+            .fetch_all(&mut **tx)
+            .await?
+        }
+        (None, None) => Vec::new(),
+    };
+    let replacements: Vec<Uuid> = visitors.iter().map(|_| Uuid::new_v4()).collect();
+
+    let sessions = sqlx::query!(
+        r#"
+        UPDATE analytics_sessions s SET visitor_id = m.new_id, city = NULL
+        FROM UNNEST($1::uuid[], $2::uuid[]) AS m(old_id, new_id)
+        WHERE s.visitor_id = m.old_id
+        "#,
+        &visitors,
+        &replacements,
+    )
+    .execute(&mut **tx)
+    .await?
+    .rows_affected() as i64;
+
+    let pageviews = sqlx::query!(
+        r#"
+        UPDATE analytics_pageviews p SET visitor_id = m.new_id, ip_address = NULL, city = NULL
+        FROM UNNEST($1::uuid[], $2::uuid[]) AS m(old_id, new_id)
+        WHERE p.visitor_id = m.old_id
+        "#,
+        &visitors,
+        &replacements,
+    )
+    .execute(&mut **tx)
+    .await?
+    .rows_affected() as i64;
+
+    let events = sqlx::query!(
+        r#"
+        UPDATE analytics_events e SET visitor_id = m.new_id
+        FROM UNNEST($1::uuid[], $2::uuid[]) AS m(old_id, new_id)
+        WHERE e.visitor_id = m.old_id
+        "#,
+        &visitors,
+        &replacements,
+    )
+    .execute(&mut **tx)
+    .await?
+    .rows_affected() as i64;
+
+    // Unknown visitor ids count as none
+    let found = sessions + pageviews + events > 0;
+    Ok(Scrubbed {
+        visitors: if found { visitors.len() as i64 } else { 0 },
+        sessions,
+        pageviews,
+        events,
+    })
+}
+
+// ============================================
+// Report Service
+// ============================================
+
+/// Time a report query may run before it is abandoned
+pub const DEFAULT_REPORT_QUERY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Report queries; read-only, so routed to replicas within the lag budget
+pub struct ReportService {
+    db: Arc<DbPools>,
+    /// `category` or `category:action` of events that count as conversions
+    conversion_events: Vec<String>,
+    /// Zone report days are counted in
+    timezone: Tz,
+    /// Each query fails after this
+    query_timeout: std::time::Duration,
+}
+
+impl ReportService {
+    pub fn new(db: Arc<DbPools>, conversion_events: Vec<String>, timezone: Tz) -> Self {
+        Self {
+            db,
+            conversion_events,
+            timezone,
+            query_timeout: DEFAULT_REPORT_QUERY_TIMEOUT,
+        }
+    }
+
+    /// Limit how long each report query may run
+    pub fn with_query_timeout(mut self, timeout: std::time::Duration) -> Self {
+        self.query_timeout = timeout;
+        self
+    }
+
+    /// Run a report query within the time limit
+    ///
+    /// A query over the limit fails the report right away. The server only
+    /// stops the statement, freeing its connection, at the reporting pool's
+    /// `statement_timeout_ms`; set that no higher than this limit.
+    async fn timed<T>(
+        &self,
+        query: impl std::future::Future<Output = Result<T, sqlx::Error>>,
+    ) -> Result<T, ReportError> {
+        match tokio::time::timeout(self.query_timeout, query).await {
+            Ok(result) => result.map_err(|e| ReportError::Database(e.to_string())),
+            Err(_) => Err(ReportError::Timeout(self.query_timeout)),
+        }
+    }
+
+    /// The period `query` asks for, in the reporting time zone
+    pub fn range(&self, query: &ReportQuery) -> ReportRange {
+        query.range(self.timezone)
+    }
+
+    /// The period of the same length before `range`
+    fn previous_range(&self, query: &ReportQuery) -> ReportRange {
+        query.previous_range(self.timezone)
+    }
+
+    /// Generate overview report
+    pub async fn get_overview(&self, query: &ReportQuery) -> Result<OverviewReport, ReportError> {
+        let range = self.range(query);
+
+        // Get totals
+        let totals = self
+            .timed(
+                sqlx::query!(
+                    r#"
+                    SELECT
+                        COALESCE(SUM(page_views), 0) as total_page_views,
+                        COALESCE(SUM(unique_visitors), 0) as unique_visitors,
+                        COALESCE(SUM(sessions), 0) as total_sessions,
+                        COALESCE(AVG(bounce_rate), 0) as bounce_rate,
+                        COALESCE(AVG(avg_session_duration), 0) as avg_session_duration,
+                        COALESCE(SUM(new_visitors), 0) as new_visitors,
+                        COALESCE(SUM(returning_visitors), 0) as returning_visitors
+                    FROM analytics_daily_stats
+                    WHERE date BETWEEN $1 AND $2 AND site_id IS NOT DISTINCT FROM $3
+                    "#,
+                    range.from,
+                    range.to,
+                    query.site_id,
+                )
+                .fetch_one(self.db.read()),
+            )
+            .await?;
+
+        // Get daily breakdown
+        let daily_stats = self
+            .timed(
+                sqlx::query_as!(
+                    DailyStats,
+                    r#"
+                    SELECT date, page_views, unique_visitors, sessions,
+                           bounce_rate, avg_session_duration, new_visitors, returning_visitors
+                    FROM analytics_daily_stats
+                    WHERE date BETWEEN $1 AND $2 AND site_id IS NOT DISTINCT FROM $3
+                    ORDER BY date ASC
+                    "#,
+                    range.from,
+                    range.to,
+                    query.site_id,
+                )
+                .fetch_all(self.db.read()),
+            )
+            .await?;
+
+        let annotations = self
+            .timed(annotations_between(self.db.read(), range.from, range.to))
+            .await?;
 
         let total_visitors = totals.new_visitors.unwrap_or(0) + totals.returning_visitors.unwrap_or(0);
         let new_percentage = if total_visitors > 0 {
@@ -438,131 +1718,476 @@ impl ReportService {
                 new_percentage,
             },
             daily_stats,
+            annotations,
+            range,
         })
     }
 
     /// Get top pages report
     pub async fn get_pages(&self, query: &ReportQuery) -> Result<Vec<PageReport>, ReportError> {
-        let (from, to) = query.date_range();
+        let range = self.range(query);
         let limit = query.limit.unwrap_or(20);
 
-        let pages = sqlx::query_as!(
-            PageReport,
-            r#"
-            SELECT
-                p.path,
-                MAX(p.title) as title,
-                COUNT(*) as page_views,
-                COUNT(DISTINCT p.visitor_id) as unique_visitors,
-                AVG(EXTRACT(EPOCH FROM (LEAD(p.created_at) OVER (PARTITION BY p.session_id ORDER BY p.created_at) - p.created_at))) as avg_time_on_page,
-                (COUNT(*) FILTER (WHERE s.is_bounce AND s.entry_page = p.path)::float / NULLIF(COUNT(*), 0)) * 100 as bounce_rate,
-                COUNT(*) FILTER (WHERE s.entry_page = p.path) as entrances,
-                COUNT(*) FILTER (WHERE s.exit_page = p.path) as exits
-            FROM analytics_pageviews p
-            JOIN analytics_sessions s ON s.id = p.session_id
-            WHERE p.created_at::date BETWEEN $1 AND $2
-            GROUP BY p.path
-            ORDER BY page_views DESC
-            LIMIT $3
-            "#,
-            from,
-            to,
-            limit,
-        )
-        .fetch_all(&self.db)
-        .await
-        .map_err(|e| ReportError::Database(e.to_string()))?;
+        // Time on page is the engagement measured by heartbeats; page views
+        // without any fall back to the gap until the session's next page view,
+        // and the last page of a session without heartbeats is left out
+        let pages = self
+            .timed(
+                sqlx::query_as!(
+                    PageReport,
+                    r#"
+                    WITH views AS (
+                        SELECT
+                            p.*,
+                            COALESCE(
+                                p.engagement_seconds::float,
+                                EXTRACT(EPOCH FROM (LEAD(p.created_at) OVER (PARTITION BY p.session_id ORDER BY p.created_at) - p.created_at))
+                            ) as time_on_page
+                        FROM analytics_pageviews p
+                        WHERE p.created_at >= $1 AND p.created_at < $2 AND p.site_id IS NOT DISTINCT FROM $4
+                    )
+                    SELECT
+                        p.path,
+                        MAX(p.title) as title,
+                        COUNT(*) as page_views,
+                        COUNT(DISTINCT p.visitor_id) as unique_visitors,
+                        COALESCE(AVG(p.time_on_page), 0) as avg_time_on_page,
+                        (COUNT(*) FILTER (WHERE s.is_bounce AND s.entry_page = p.path)::float / NULLIF(COUNT(*), 0)) * 100 as bounce_rate,
+                        COUNT(*) FILTER (WHERE s.entry_page = p.path) as entrances,
+                        COUNT(*) FILTER (WHERE s.exit_page = p.path) as exits
+                    FROM views p
+                    JOIN analytics_sessions s ON s.id = p.session_id
+                    GROUP BY p.path
+                    ORDER BY page_views DESC
+                    LIMIT $3
+                    "#,
+                    range.start_utc(),
+                    range.end_utc(),
+                    limit,
+                    query.site_id,
+                )
+                .fetch_all(self.db.read()),
+            )
+            .await?;
+
+        Ok(pages)
+    }
+
+    /// Get landing pages report: sessions by entry page
+    pub async fn get_landing_pages(&self, query: &ReportQuery) -> Result<Vec<LandingPageReport>, ReportError> {
+        let range = self.range(query);
+        let limit = query.limit.unwrap_or(20);
+
+        let pages = self
+            .timed(
+                sqlx::query_as!(
+                    LandingPageReport,
+                    r#"
+                    SELECT
+                        s.entry_page as path,
+                        COUNT(*) as sessions,
+                        (COUNT(*) FILTER (WHERE s.is_bounce)::float / NULLIF(COUNT(*), 0)) * 100 as bounce_rate,
+                        COUNT(c.session_id) as conversions,
+                        (COUNT(c.session_id)::float / NULLIF(COUNT(*), 0)) * 100 as conversion_rate,
+                        COALESCE(AVG(s.duration_seconds), 0)::float as avg_session_duration
+                    FROM analytics_sessions s
+                    LEFT JOIN (
+                        SELECT DISTINCT session_id FROM analytics_events
+                        WHERE category = ANY($3) OR category || ':' || action = ANY($3)
+                    ) c ON c.session_id = s.id
+                    WHERE s.started_at >= $1 AND s.started_at < $2 AND s.site_id IS NOT DISTINCT FROM $5
+                    GROUP BY s.entry_page
+                    ORDER BY sessions DESC
+                    LIMIT $4
+                    "#,
+                    range.start_utc(),
+                    range.end_utc(),
+                    &self.conversion_events,
+                    limit,
+                    query.site_id,
+                )
+                .fetch_all(self.db.read()),
+            )
+            .await?;
+
+        Ok(pages)
+    }
+
+    /// Get the UTM breakdown of one landing page, from the tags on each
+    /// session's first page view
+    pub async fn get_landing_page_utm(&self, query: &LandingPageQuery) -> Result<Vec<UtmReport>, ReportError> {
+        let range = self.range(&query.report_query());
+        let limit = query.limit.unwrap_or(20);
+
+        let campaigns = self
+            .timed(
+                sqlx::query_as!(
+                    UtmReport,
+                    r#"
+                    SELECT
+                        p.utm_source,
+                        p.utm_medium,
+                        p.utm_campaign,
+                        COUNT(*) as sessions,
+                        (COUNT(*) FILTER (WHERE s.is_bounce)::float / NULLIF(COUNT(*), 0)) * 100 as bounce_rate,
+                        COUNT(c.session_id) as conversions,
+                        (COUNT(c.session_id)::float / NULLIF(COUNT(*), 0)) * 100 as conversion_rate
+                    FROM analytics_sessions s
+                    JOIN LATERAL (
+                        SELECT utm_source, utm_medium, utm_campaign FROM analytics_pageviews
+                        WHERE session_id = s.id
+                        ORDER BY created_at ASC
+                        LIMIT 1
+                    ) p ON true
+                    LEFT JOIN (
+                        SELECT DISTINCT session_id FROM analytics_events
+                        WHERE category = ANY($4) OR category || ':' || action = ANY($4)
+                    ) c ON c.session_id = s.id
+                    WHERE s.started_at >= $1 AND s.started_at < $2 AND s.entry_page = $3
+                        AND s.site_id IS NOT DISTINCT FROM $6
+                    GROUP BY p.utm_source, p.utm_medium, p.utm_campaign
+                    ORDER BY sessions DESC
+                    LIMIT $5
+                    "#,
+                    range.start_utc(),
+                    range.end_utc(),
+                    query.path,
+                    &self.conversion_events,
+                    limit,
+                    query.site_id,
+                )
+                .fetch_all(self.db.read()),
+            )
+            .await?;
+
+        Ok(campaigns)
+    }
+
+    /// Get exit pages report: sessions by the page they ended on
+    pub async fn get_exit_pages(&self, query: &ReportQuery) -> Result<Vec<ExitPageReport>, ReportError> {
+        let range = self.range(query);
+        let limit = query.limit.unwrap_or(20);
+
+        let pages = self
+            .timed(
+                sqlx::query_as!(
+                    ExitPageReport,
+                    r#"
+                    WITH exits AS (
+                        SELECT exit_page as path, COUNT(*) as exits
+                        FROM analytics_sessions
+                        WHERE exit_page IS NOT NULL AND started_at >= $1 AND started_at < $2
+                            AND site_id IS NOT DISTINCT FROM $4
+                        GROUP BY exit_page
+                    ), views AS (
+                        SELECT path, COUNT(*) as page_views
+                        FROM analytics_pageviews
+                        WHERE created_at >= $1 AND created_at < $2 AND site_id IS NOT DISTINCT FROM $4
+                        GROUP BY path
+                    )
+                    SELECT
+                        e.path,
+                        e.exits,
+                        COALESCE(v.page_views, 0) as page_views,
+                        COALESCE((e.exits::float / NULLIF(v.page_views, 0)) * 100, 0) as exit_rate
+                    FROM exits e
+                    LEFT JOIN views v ON v.path = e.path
+                    ORDER BY e.exits DESC
+                    LIMIT $3
+                    "#,
+                    range.start_utc(),
+                    range.end_utc(),
+                    limit,
+                    query.site_id,
+                )
+                .fetch_all(self.db.read()),
+            )
+            .await?;
 
         Ok(pages)
     }
 
     /// Get referrers report
     pub async fn get_referrers(&self, query: &ReportQuery) -> Result<Vec<ReferrerReport>, ReportError> {
-        let (from, to) = query.date_range();
+        let range = self.range(query);
         let limit = query.limit.unwrap_or(20);
 
-        let referrers = sqlx::query_as!(
-            ReferrerReport,
-            r#"
-            SELECT
-                COALESCE(p.referrer, 'Direct') as referrer,
-                COUNT(DISTINCT p.session_id) as sessions,
-                COUNT(*) as page_views,
-                (COUNT(*) FILTER (WHERE s.is_bounce)::float / NULLIF(COUNT(DISTINCT p.session_id), 0)) * 100 as bounce_rate,
-                AVG(s.duration_seconds) as avg_session_duration
-            FROM analytics_pageviews p
-            JOIN analytics_sessions s ON s.id = p.session_id
-            WHERE p.created_at::date BETWEEN $1 AND $2
-            GROUP BY COALESCE(p.referrer, 'Direct')
-            ORDER BY sessions DESC
-            LIMIT $3
-            "#,
-            from,
-            to,
-            limit,
-        )
-        .fetch_all(&self.db)
-        .await
-        .map_err(|e| ReportError::Database(e.to_string()))?;
+        let referrers = self
+            .timed(
+                sqlx::query_as!(
+                    ReferrerReport,
+                    r#"
+                    SELECT
+                        COALESCE(p.referrer, 'Direct') as referrer,
+                        COUNT(DISTINCT p.session_id) as sessions,
+                        COUNT(*) as page_views,
+                        (COUNT(*) FILTER (WHERE s.is_bounce)::float / NULLIF(COUNT(DISTINCT p.session_id), 0)) * 100 as bounce_rate,
+                        AVG(s.duration_seconds) as avg_session_duration
+                    FROM analytics_pageviews p
+                    JOIN analytics_sessions s ON s.id = p.session_id
+                    WHERE p.created_at >= $1 AND p.created_at < $2 AND p.site_id IS NOT DISTINCT FROM $4
+                    GROUP BY COALESCE(p.referrer, 'Direct')
+                    ORDER BY sessions DESC
+                    LIMIT $3
+                    "#,
+                    range.start_utc(),
+                    range.end_utc(),
+                    limit,
+                    query.site_id,
+                )
+                .fetch_all(self.db.read()),
+            )
+            .await?;
 
         Ok(referrers)
     }
 
     /// Get device breakdown
     pub async fn get_devices(&self, query: &ReportQuery) -> Result<Vec<DeviceReport>, ReportError> {
-        let (from, to) = query.date_range();
-
-        let devices = sqlx::query_as!(
-            DeviceReport,
-            r#"
-            SELECT
-                device_type,
-                COUNT(*) as sessions,
-                (COUNT(*)::float / SUM(COUNT(*)) OVER ()) * 100 as percentage
-            FROM analytics_sessions
-            WHERE started_at::date BETWEEN $1 AND $2
-            GROUP BY device_type
-            ORDER BY sessions DESC
-            "#,
-            from,
-            to,
-        )
-        .fetch_all(&self.db)
-        .await
-        .map_err(|e| ReportError::Database(e.to_string()))?;
+        let range = self.range(query);
+
+        let devices = self
+            .timed(
+                sqlx::query_as!(
+                    DeviceReport,
+                    r#"
+                    SELECT
+                        device_type,
+                        COUNT(*) as sessions,
+                        (COUNT(*)::float / SUM(COUNT(*)) OVER ()) * 100 as percentage
+                    FROM analytics_sessions
+                    WHERE started_at >= $1 AND started_at < $2 AND site_id IS NOT DISTINCT FROM $3
+                    GROUP BY device_type
+                    ORDER BY sessions DESC
+                    "#,
+                    range.start_utc(),
+                    range.end_utc(),
+                    query.site_id,
+                )
+                .fetch_all(self.db.read()),
+            )
+            .await?;
 
         Ok(devices)
     }
 
+    /// Get browser breakdown by major version, with the previous period
+    pub async fn get_browsers(&self, query: &ReportQuery) -> Result<Vec<BrowserReport>, ReportError> {
+        let range = self.range(query);
+        let previous = self.previous_range(query);
+        let limit = query.limit.unwrap_or(20);
+
+        let rows = self
+            .timed(
+                sqlx::query_as!(
+                    ClientVersionRow,
+                    r#"
+                    SELECT
+                        COALESCE(browser, 'Unknown') as "name!",
+                        COALESCE(NULLIF(split_part(browser_version, '.', 1), ''), 'Unknown') as "version!",
+                        COUNT(*) FILTER (WHERE started_at >= $1 AND started_at < $2) as "sessions!",
+                        COUNT(*) FILTER (WHERE started_at >= $3 AND started_at < $4) as "previous_sessions!"
+                    FROM analytics_sessions
+                    WHERE started_at >= $3 AND started_at < $2 AND site_id IS NOT DISTINCT FROM $5
+                    GROUP BY 1, 2
+                    "#,
+                    range.start_utc(),
+                    range.end_utc(),
+                    previous.start_utc(),
+                    previous.end_utc(),
+                    query.site_id,
+                )
+                .fetch_all(self.db.read()),
+            )
+            .await?;
+
+        Ok(client_breakdown(rows, limit)
+            .into_iter()
+            .map(|c| BrowserReport {
+                browser: c.name,
+                sessions: c.sessions,
+                percentage: c.percentage,
+                previous_sessions: c.previous_sessions,
+                change: c.change,
+                versions: c.versions,
+            })
+            .collect())
+    }
+
+    /// Get operating system breakdown by major version, with the previous period
+    pub async fn get_operating_systems(&self, query: &ReportQuery) -> Result<Vec<OsReport>, ReportError> {
+        let range = self.range(query);
+        let previous = self.previous_range(query);
+        let limit = query.limit.unwrap_or(20);
+
+        let rows = self
+            .timed(
+                sqlx::query_as!(
+                    ClientVersionRow,
+                    r#"
+                    SELECT
+                        COALESCE(os, 'Unknown') as "name!",
+                        COALESCE(NULLIF(split_part(os_version, '.', 1), ''), 'Unknown') as "version!",
+                        COUNT(*) FILTER (WHERE started_at >= $1 AND started_at < $2) as "sessions!",
+                        COUNT(*) FILTER (WHERE started_at >= $3 AND started_at < $4) as "previous_sessions!"
+                    FROM analytics_sessions
+                    WHERE started_at >= $3 AND started_at < $2 AND site_id IS NOT DISTINCT FROM $5
+                    GROUP BY 1, 2
+                    "#,
+                    range.start_utc(),
+                    range.end_utc(),
+                    previous.start_utc(),
+                    previous.end_utc(),
+                    query.site_id,
+                )
+                .fetch_all(self.db.read()),
+            )
+            .await?;
+
+        Ok(client_breakdown(rows, limit)
+            .into_iter()
+            .map(|c| OsReport {
+                os: c.name,
+                sessions: c.sessions,
+                percentage: c.percentage,
+                previous_sessions: c.previous_sessions,
+                change: c.change,
+                versions: c.versions,
+            })
+            .collect())
+    }
+
     /// Get geography report
     pub async fn get_geography(&self, query: &ReportQuery) -> Result<Vec<GeoReport>, ReportError> {
-        let (from, to) = query.date_range();
+        let range = self.range(query);
         let limit = query.limit.unwrap_or(20);
 
-        let geo = sqlx::query_as!(
-            GeoReport,
-            r#"
-            SELECT
-                COALESCE(country, 'Unknown') as country,
-                COUNT(*) as sessions,
-                SUM(page_views) as page_views,
-                (COUNT(*)::float / SUM(COUNT(*)) OVER ()) * 100 as percentage
-            FROM analytics_sessions
-            WHERE started_at::date BETWEEN $1 AND $2
-            GROUP BY country
-            ORDER BY sessions DESC
-            LIMIT $3
-            "#,
-            from,
-            to,
-            limit,
-        )
-        .fetch_all(&self.db)
-        .await
-        .map_err(|e| ReportError::Database(e.to_string()))?;
+        let geo = self
+            .timed(
+                sqlx::query_as!(
+                    GeoReport,
+                    r#"
+                    SELECT
+                        COALESCE(country, 'Unknown') as country,
+                        COUNT(*) as sessions,
+                        SUM(page_views) as page_views,
+                        (COUNT(*)::float / SUM(COUNT(*)) OVER ()) * 100 as percentage
+                    FROM analytics_sessions
+                    WHERE started_at >= $1 AND started_at < $2 AND site_id IS NOT DISTINCT FROM $4
+                    GROUP BY country
+                    ORDER BY sessions DESC
+                    LIMIT $3
+                    "#,
+                    range.start_utc(),
+                    range.end_utc(),
+                    limit,
+                    query.site_id,
+                )
+                .fetch_all(self.db.read()),
+            )
+            .await?;
 
         Ok(geo)
     }
+
+    /// Refused `/track` hits per day and reason, for all sites
+    pub async fn get_rejections(&self, query: &ReportQuery) -> Result<Vec<RejectionReport>, ReportError> {
+        let range = self.range(query);
+
+        let rejections = self
+            .timed(
+                sqlx::query_as!(
+                    RejectionReport,
+                    r#"
+                    SELECT date, reason, count
+                    FROM analytics_rejections
+                    WHERE date BETWEEN $1 AND $2
+                    ORDER BY date, reason
+                    "#,
+                    range.from,
+                    range.to,
+                )
+                .fetch_all(self.db.read()),
+            )
+            .await?;
+
+        Ok(rejections)
+    }
+}
+
+/// Session counts for one browser or OS major version in the current and
+/// the previous period
+struct ClientVersionRow {
+    name: String,
+    version: String,
+    sessions: i64,
+    previous_sessions: i64,
+}
+
+/// A browser or OS with its versions, before it becomes a report row
+struct ClientBreakdown {
+    name: String,
+    sessions: i64,
+    percentage: f64,
+    previous_sessions: i64,
+    change: Option<f64>,
+    versions: Vec<VersionReport>,
+}
+
+/// Group version rows by browser or OS, most sessions first, keeping the
+/// top `limit`; shares are of all sessions in the period
+fn client_breakdown(rows: Vec<ClientVersionRow>, limit: i64) -> Vec<ClientBreakdown> {
+    let total: i64 = rows.iter().map(|r| r.sessions).sum();
+
+    let mut grouped: BTreeMap<String, Vec<ClientVersionRow>> = BTreeMap::new();
+    for row in rows {
+        grouped.entry(row.name.clone()).or_default().push(row);
+    }
+
+    let mut clients: Vec<ClientBreakdown> = grouped
+        .into_iter()
+        .map(|(name, rows)| {
+            let sessions: i64 = rows.iter().map(|r| r.sessions).sum();
+            let previous_sessions: i64 = rows.iter().map(|r| r.previous_sessions).sum();
+
+            let mut versions: Vec<VersionReport> = rows
+                .into_iter()
+                .map(|r| VersionReport {
+                    percentage: share(r.sessions, sessions),
+                    change: change(r.sessions, r.previous_sessions),
+                    version: r.version,
+                    sessions: r.sessions,
+                    previous_sessions: r.previous_sessions,
+                })
+                .collect();
+            versions.sort_by(|a, b| b.sessions.cmp(&a.sessions).then(b.previous_sessions.cmp(&a.previous_sessions)));
+
+            ClientBreakdown {
+                name,
+                sessions,
+                percentage: share(sessions, total),
+                previous_sessions,
+                change: change(sessions, previous_sessions),
+                versions,
+            }
+        })
+        .collect();
+
+    clients.sort_by(|a, b| b.sessions.cmp(&a.sessions).then(b.previous_sessions.cmp(&a.previous_sessions)));
+    clients.truncate(limit.max(0) as usize);
+    clients
+}
+
+fn share(part: i64, total: i64) -> f64 {
+    if total > 0 {
+        (part as f64 / total as f64) * 100.0
+    } else {
+        0.0
+    }
+}
+
+fn change(current: i64, previous: i64) -> Option<f64> {
+    (previous > 0).then(|| ((current - previous) as f64 / previous as f64) * 100.0)
 }
 
 // ============================================
@@ -577,24 +2202,62 @@ pub enum TrackingError {
     ExcludedPath,
     #[error("IP is excluded")]
     ExcludedIP,
+    #[error("Tracking is disabled in the visitor's region")]
+    RegionDisabled,
     #[error("Missing visitor ID")]
     MissingVisitorId,
     #[error("Missing session ID")]
     MissingSessionId,
+    #[error("Missing page view ID")]
+    MissingPageviewId,
+    #[error("engaged_seconds must be a positive number")]
+    InvalidEngagement,
     #[error("Database error: {0}")]
     Database(String),
 }
 
+#[derive(Debug, thiserror::Error)]
+pub enum IngestError {
+    #[error("Server-side collection is not configured")]
+    Disabled,
+    #[error("Secret error: {0}")]
+    Secrets(String),
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum AnalyticsError {
     #[error("Database error: {0}")]
     Database(String),
 }
 
+#[derive(Debug, thiserror::Error)]
+pub enum AnnotationError {
+    #[error("Annotation not found: {0}")]
+    NotFound(Uuid),
+    #[error("{0}")]
+    Invalid(String),
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ErasureError {
+    #[error("Erasure request not found: {0}")]
+    NotFound(Uuid),
+    #[error("{0}")]
+    Invalid(String),
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum ReportError {
     #[error("Database error: {0}")]
     Database(String),
+    #[error("Report query exceeded {0:?}")]
+    Timeout(std::time::Duration),
     #[error("Export error: {0}")]
     Export(String),
 }
@@ -0,0 +1,641 @@
+//! Event Sinks
+//!
+//! Stored page views and events can be mirrored to outside systems: Google
+//! Analytics 4 through the Measurement Protocol, any HTTP endpoint taking
+//! JSON batches, or Kafka through a Kafka REST Proxy. Sinks are listed in the
+//! `sinks` setting; each picks the events it gets, renames them and leaves
+//! out fields it shouldn't see.
+//!
+//! Every sink has its own bounded queue and worker, which sends batches and
+//! retries failed ones with backoff. Tracking only ever tries to queue an
+//! event: when a sink's queue is full the event is dropped for that sink,
+//! and a sink whose batches keep failing is paused for a while. A slow or
+//! unreachable third party therefore never holds up tracking, nor the other
+//! sinks. Dropped events are counted, not replayed; the local tables stay
+//! the source of truth.
+
+use super::IngestMetrics;
+use crate::models::{CollectEvent, SinkStatus, TrackingInput};
+use chrono::{DateTime, Utc};
+use rustpress_auth::secrets::SecretProvider;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::collections::{BTreeMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::MissedTickBehavior;
+use uuid::Uuid;
+
+const GA4_ENDPOINT: &str = "https://www.google-analytics.com/mp/collect";
+
+/// Events GA4 accepts in one request
+const GA4_MAX_EVENTS: usize = 25;
+
+/// Longest event name GA4 accepts
+const GA4_MAX_NAME_LENGTH: usize = 40;
+
+/// Largest batch a sink may send
+const MAX_BATCH_SIZE: usize = 500;
+
+/// Attempts at delivering a batch before it's dropped
+const MAX_ATTEMPTS: u32 = 3;
+
+/// Failed batches in a row after which a sink is paused
+const FAILURES_BEFORE_PAUSE: u32 = 5;
+
+/// How long a failing sink is paused
+const PAUSE_SECS: i64 = 300;
+
+const REQUEST_TIMEOUT_SECS: u64 = 10;
+
+// ============================================
+// Configuration
+// ============================================
+
+/// One entry of the `sinks` setting
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct SinkConfig {
+    /// Unique name, used in logs and the sink status
+    pub name: String,
+    #[serde(flatten)]
+    pub target: SinkTarget,
+    /// Secret holding the credential: the GA4 `api_secret`, or a bearer
+    /// token for HTTP and Kafka sinks
+    #[serde(default)]
+    pub secret: Option<String>,
+    /// Events sent: `pageview`, `category` or `category:action`; all when empty
+    #[serde(default)]
+    pub events: Vec<String>,
+    /// Names to send events under, by `pageview`, `category` or
+    /// `category:action`; the most specific match wins
+    #[serde(default)]
+    pub rename: BTreeMap<String, String>,
+    /// Fields left out, e.g. `referrer` or `country`
+    #[serde(default)]
+    pub omit: Vec<String>,
+    /// Most events sent in one batch
+    #[serde(default = "default_batch_size")]
+    pub batch_size: usize,
+    /// Seconds a partial batch waits before it's sent
+    #[serde(default = "default_flush_interval")]
+    pub flush_interval_secs: u64,
+    /// Events held while the sink is sending; more are dropped
+    #[serde(default = "default_queue_size")]
+    pub queue_size: usize,
+}
+
+fn default_batch_size() -> usize {
+    50
+}
+
+fn default_flush_interval() -> u64 {
+    5
+}
+
+fn default_queue_size() -> usize {
+    10_000
+}
+
+/// Where a sink sends events
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SinkTarget {
+    /// Google Analytics 4 Measurement Protocol; `base_url` turns paths into
+    /// the `page_location` URLs GA4 expects
+    Ga4 {
+        measurement_id: String,
+        #[serde(default)]
+        base_url: Option<String>,
+    },
+    /// JSON batches (`{"events": [...]}`) POSTed to `url`
+    Http { url: String },
+    /// Records produced to `topic` through a Kafka REST Proxy (v2 API)
+    Kafka { rest_url: String, topic: String },
+}
+
+impl SinkTarget {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            SinkTarget::Ga4 { .. } => "ga4",
+            SinkTarget::Http { .. } => "http",
+            SinkTarget::Kafka { .. } => "kafka",
+        }
+    }
+}
+
+/// Parse the `sinks` setting, a JSON list of sinks; empty means none
+pub fn parse_sinks(text: &str) -> Result<Vec<SinkConfig>, String> {
+    if text.trim().is_empty() {
+        return Ok(vec![]);
+    }
+
+    let sinks: Vec<SinkConfig> = serde_json::from_str(text).map_err(|e| format!("Invalid sinks: {}", e))?;
+    let mut names = HashSet::new();
+    for sink in &sinks {
+        let problem = if sink.name.trim().is_empty() {
+            Some("name must not be empty".to_string())
+        } else if !names.insert(sink.name.as_str()) {
+            Some("name is used by another sink".to_string())
+        } else if !(1..=MAX_BATCH_SIZE).contains(&sink.batch_size) {
+            Some(format!("batch_size must be 1 to {}", MAX_BATCH_SIZE))
+        } else if sink.queue_size < sink.batch_size {
+            Some("queue_size must be at least batch_size".to_string())
+        } else if sink.flush_interval_secs == 0 {
+            Some("flush_interval_secs must be at least 1".to_string())
+        } else {
+            match &sink.target {
+                SinkTarget::Ga4 { measurement_id, .. } if measurement_id.trim().is_empty() => {
+                    Some("measurement_id must not be empty".to_string())
+                }
+                SinkTarget::Ga4 { .. } if sink.secret.is_none() => {
+                    Some("GA4 sinks need the secret holding the api_secret".to_string())
+                }
+                SinkTarget::Http { url } if !is_http_url(url) => Some("url must be an http(s) URL".to_string()),
+                SinkTarget::Kafka { rest_url, .. } if !is_http_url(rest_url) => {
+                    Some("rest_url must be an http(s) URL".to_string())
+                }
+                SinkTarget::Kafka { topic, .. } if topic.trim().is_empty() => {
+                    Some("topic must not be empty".to_string())
+                }
+                _ => None,
+            }
+        };
+        if let Some(problem) = problem {
+            return Err(format!("Sink '{}': {}", sink.name, problem));
+        }
+    }
+    Ok(sinks)
+}
+
+fn is_http_url(url: &str) -> bool {
+    url.starts_with("https://") || url.starts_with("http://")
+}
+
+// ============================================
+// Events
+// ============================================
+
+/// A stored page view or event, as mirrored to sinks
+///
+/// IP addresses are never included.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SinkEvent {
+    /// `page_view`, or the event's action, unless the sink renames it
+    pub name: String,
+    pub visitor_id: Uuid,
+    pub session_id: Uuid,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub site_id: Option<Uuid>,
+    pub timestamp: DateTime<Utc>,
+    pub path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub referrer: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub utm_source: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub utm_medium: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub utm_campaign: Option<String>,
+    /// Event category; `None` for page views
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub action: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub country: Option<String>,
+}
+
+impl SinkEvent {
+    /// A page view stored from a tracker hit
+    pub fn pageview(input: &TrackingInput, visitor_id: Uuid, session_id: Uuid, country: Option<String>) -> Self {
+        Self {
+            name: "page_view".to_string(),
+            visitor_id,
+            session_id,
+            site_id: input.site_id,
+            timestamp: Utc::now(),
+            path: input.path.clone(),
+            title: input.title.clone(),
+            referrer: input.referrer.clone(),
+            utm_source: input.utm_source.clone(),
+            utm_medium: input.utm_medium.clone(),
+            utm_campaign: input.utm_campaign.clone(),
+            category: None,
+            action: None,
+            label: None,
+            value: None,
+            country,
+        }
+    }
+
+    /// An event stored from a tracker hit, with the defaults it's stored with
+    pub fn event(input: &TrackingInput, visitor_id: Uuid, session_id: Uuid) -> Self {
+        let action = input.action.clone().unwrap_or_else(|| "click".to_string());
+        Self {
+            name: action.clone(),
+            visitor_id,
+            session_id,
+            site_id: input.site_id,
+            timestamp: Utc::now(),
+            path: input.path.clone(),
+            title: None,
+            referrer: None,
+            utm_source: None,
+            utm_medium: None,
+            utm_campaign: None,
+            category: Some(input.category.clone().unwrap_or_else(|| "general".to_string())),
+            action: Some(action),
+            label: input.label.clone(),
+            value: input.value,
+            country: None,
+        }
+    }
+
+    /// An event stored from `/collect`, at its own time
+    pub fn collected(event: &CollectEvent, session_id: Uuid) -> Self {
+        Self {
+            name: event.action.clone(),
+            visitor_id: event.visitor_id,
+            session_id,
+            site_id: event.site_id,
+            timestamp: event.timestamp,
+            path: event.path.clone().unwrap_or_else(|| "/".to_string()),
+            title: None,
+            referrer: None,
+            utm_source: None,
+            utm_medium: None,
+            utm_campaign: None,
+            category: Some(event.category.clone()),
+            action: Some(event.action.clone()),
+            label: event.label.clone(),
+            value: event.value,
+            country: None,
+        }
+    }
+
+    /// Whether a `pageview`, `category` or `category:action` pattern matches
+    pub fn matches(&self, pattern: &str) -> bool {
+        let Some(category) = &self.category else {
+            return pattern == "pageview";
+        };
+        match pattern.split_once(':') {
+            Some((c, a)) => c == category && self.action.as_deref() == Some(a),
+            None => pattern == category,
+        }
+    }
+
+    /// The event as `sink` sends it: renamed, without the omitted fields
+    pub fn mapped(&self, sink: &SinkConfig) -> Map<String, Value> {
+        let mut fields = match serde_json::to_value(self) {
+            Ok(Value::Object(fields)) => fields,
+            _ => Map::new(),
+        };
+
+        let action = self.category.as_ref().zip(self.action.as_ref()).map(|(c, a)| format!("{}:{}", c, a));
+        let name = action
+            .and_then(|key| sink.rename.get(&key))
+            .or_else(|| sink.rename.get(self.category.as_deref().unwrap_or("pageview")))
+            .unwrap_or(&self.name);
+        fields.insert("name".to_string(), Value::from(name.as_str()));
+
+        for field in &sink.omit {
+            if field != "name" {
+                fields.remove(field);
+            }
+        }
+        fields
+    }
+}
+
+// ============================================
+// Sinks
+// ============================================
+
+/// The configured sinks and their workers; workers send what's queued and
+/// stop once this is dropped
+pub struct Sinks {
+    sinks: Vec<Sink>,
+    metrics: IngestMetrics,
+}
+
+struct Sink {
+    config: Arc<SinkConfig>,
+    queue: mpsc::Sender<SinkEvent>,
+    status: Arc<Mutex<SinkStatus>>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SinkError {
+    #[error("Secrets error: {0}")]
+    Secrets(String),
+    #[error("Sink '{0}': secret {1} is empty")]
+    MissingSecret(String, String),
+    #[error("HTTP client error: {0}")]
+    Client(#[from] reqwest::Error),
+}
+
+impl Sinks {
+    /// Read the sinks' credentials and start their workers
+    pub async fn start(
+        configs: &[SinkConfig],
+        secrets: &dyn SecretProvider,
+        metrics: IngestMetrics,
+    ) -> Result<Arc<Self>, SinkError> {
+        let http = reqwest::Client::builder()
+            .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
+            .build()?;
+
+        let mut sinks = Vec::with_capacity(configs.len());
+        for config in configs {
+            let credential = match &config.secret {
+                Some(name) => Some(
+                    secrets
+                        .get(name)
+                        .await
+                        .map_err(|e| SinkError::Secrets(e.to_string()))?
+                        .filter(|value| !value.trim().is_empty())
+                        .ok_or_else(|| SinkError::MissingSecret(config.name.clone(), name.clone()))?,
+                ),
+                None => None,
+            };
+
+            let config = Arc::new(config.clone());
+            let (queue, events) = mpsc::channel(config.queue_size);
+            let status = Arc::new(Mutex::new(SinkStatus {
+                name: config.name.clone(),
+                kind: config.target.kind().to_string(),
+                ..Default::default()
+            }));
+            let delivery = Delivery {
+                http: http.clone(),
+                config: config.clone(),
+                credential,
+            };
+            tokio::spawn(run_sink(delivery, events, status.clone(), metrics.clone()));
+            sinks.push(Sink { config, queue, status });
+        }
+        Ok(Arc::new(Self { sinks, metrics }))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sinks.is_empty()
+    }
+
+    /// Queue an event for the sinks that take it, without waiting
+    pub fn publish(&self, event: &SinkEvent) {
+        for sink in &self.sinks {
+            if !sink.config.events.is_empty() && !sink.config.events.iter().any(|p| event.matches(p)) {
+                continue;
+            }
+            if sink.queue.try_send(event.clone()).is_err() {
+                sink.status.lock().unwrap().dropped += 1;
+                self.metrics.sink_dropped.inc();
+            }
+        }
+    }
+
+    /// Delivery state of every sink
+    pub fn status(&self) -> Vec<SinkStatus> {
+        self.sinks
+            .iter()
+            .map(|sink| SinkStatus {
+                queued: sink.config.queue_size - sink.queue.capacity(),
+                ..sink.status.lock().unwrap().clone()
+            })
+            .collect()
+    }
+}
+
+/// Send queued events in batches until the queue closes
+async fn run_sink(
+    delivery: Delivery,
+    mut events: mpsc::Receiver<SinkEvent>,
+    status: Arc<Mutex<SinkStatus>>,
+    metrics: IngestMetrics,
+) {
+    let config = delivery.config.clone();
+    let mut batch = Vec::with_capacity(config.batch_size);
+    let mut flush = tokio::time::interval(Duration::from_secs(config.flush_interval_secs));
+    flush.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        tokio::select! {
+            event = events.recv() => {
+                let Some(event) = event else { break };
+                batch.push(event);
+                if batch.len() < config.batch_size {
+                    continue;
+                }
+            }
+            _ = flush.tick() => {
+                if batch.is_empty() {
+                    continue;
+                }
+            }
+        }
+        deliver(&delivery, std::mem::take(&mut batch), &status, &metrics).await;
+    }
+
+    if !batch.is_empty() {
+        deliver(&delivery, batch, &status, &metrics).await;
+    }
+}
+
+/// Send one batch, retrying with backoff; a batch that can't be sent is dropped
+async fn deliver(delivery: &Delivery, batch: Vec<SinkEvent>, status: &Mutex<SinkStatus>, metrics: &IngestMetrics) {
+    let count = batch.len() as u64;
+    {
+        let mut status = status.lock().unwrap();
+        if status.paused_until.is_some_and(|until| until > Utc::now()) {
+            status.dropped += count;
+            metrics.sink_dropped.inc_by(count);
+            return;
+        }
+    }
+
+    let mut attempt = 1;
+    let result = loop {
+        match delivery.send(&batch).await {
+            Err(DeliveryError::Retryable(_)) if attempt < MAX_ATTEMPTS => {
+                tokio::time::sleep(Duration::from_secs(1 << attempt)).await;
+                attempt += 1;
+            }
+            result => break result,
+        }
+    };
+
+    let mut status = status.lock().unwrap();
+    match result {
+        Ok(()) => {
+            status.sent += count;
+            status.last_success_at = Some(Utc::now());
+            status.consecutive_failures = 0;
+            status.paused_until = None;
+            metrics.sink_sent.inc_by(count);
+        }
+        Err(e) => {
+            tracing::warn!("Sink '{}' dropped {} events: {}", status.name, count, e);
+            status.dropped += count;
+            status.failed_batches += 1;
+            status.consecutive_failures += 1;
+            status.last_error = Some(e.to_string());
+            metrics.sink_dropped.inc_by(count);
+            metrics.sink_failed.inc();
+
+            // Once paused, each failure after the pause pauses it again
+            if status.consecutive_failures >= FAILURES_BEFORE_PAUSE {
+                status.paused_until = Some(Utc::now() + chrono::Duration::seconds(PAUSE_SECS));
+                tracing::warn!("Sink '{}' paused for {} seconds", status.name, PAUSE_SECS);
+            }
+        }
+    }
+}
+
+// ============================================
+// Delivery
+// ============================================
+
+struct Delivery {
+    http: reqwest::Client,
+    config: Arc<SinkConfig>,
+    credential: Option<String>,
+}
+
+#[derive(Debug, thiserror::Error)]
+enum DeliveryError {
+    /// Network errors, 429 and 5xx answers
+    #[error("{0}")]
+    Retryable(String),
+    #[error("{0}")]
+    Rejected(String),
+}
+
+impl Delivery {
+    async fn send(&self, batch: &[SinkEvent]) -> Result<(), DeliveryError> {
+        match &self.config.target {
+            SinkTarget::Ga4 { measurement_id, base_url } => {
+                // Requests carry one client's events
+                let mut clients: BTreeMap<Uuid, Vec<&SinkEvent>> = BTreeMap::new();
+                for event in batch {
+                    clients.entry(event.visitor_id).or_default().push(event);
+                }
+                for (client_id, events) in clients {
+                    for chunk in events.chunks(GA4_MAX_EVENTS) {
+                        let body = json!({
+                            "client_id": client_id.to_string(),
+                            "events": chunk
+                                .iter()
+                                .map(|event| ga4_event(event, &self.config, base_url.as_deref()))
+                                .collect::<Vec<_>>(),
+                        });
+                        let request = self.http.post(GA4_ENDPOINT).query(&[
+                            ("measurement_id", measurement_id.as_str()),
+                            ("api_secret", self.credential.as_deref().unwrap_or_default()),
+                        ]);
+                        self.post(request.json(&body)).await?;
+                    }
+                }
+                Ok(())
+            }
+            SinkTarget::Http { url } => {
+                let events: Vec<_> = batch.iter().map(|event| event.mapped(&self.config)).collect();
+                self.post(self.authorized(self.http.post(url)).json(&json!({ "events": events })))
+                    .await
+            }
+            SinkTarget::Kafka { rest_url, topic } => {
+                let records: Vec<_> = batch
+                    .iter()
+                    .map(|event| json!({ "key": event.visitor_id.to_string(), "value": event.mapped(&self.config) }))
+                    .collect();
+                let url = format!("{}/topics/{}", rest_url.trim_end_matches('/'), topic);
+                let body = serde_json::to_vec(&json!({ "records": records }))
+                    .map_err(|e| DeliveryError::Rejected(e.to_string()))?;
+                self.post(
+                    self.authorized(self.http.post(url))
+                        .header(reqwest::header::CONTENT_TYPE, "application/vnd.kafka.json.v2+json")
+                        .body(body),
+                )
+                .await
+            }
+        }
+    }
+
+    fn authorized(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match &self.credential {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+
+    async fn post(&self, request: reqwest::RequestBuilder) -> Result<(), DeliveryError> {
+        let response = request
+            .send()
+            .await
+            .map_err(|e| DeliveryError::Retryable(format!("Request failed: {}", e)))?;
+
+        let status = response.status();
+        if status.is_success() {
+            Ok(())
+        } else if status.as_u16() == 429 || status.is_server_error() {
+            Err(DeliveryError::Retryable(format!("Answered {}", status)))
+        } else {
+            Err(DeliveryError::Rejected(format!("Answered {}", status)))
+        }
+    }
+}
+
+/// A GA4 event: the mapped fields as parameters, with GA4's names for the
+/// page ones
+fn ga4_event(event: &SinkEvent, sink: &SinkConfig, base_url: Option<&str>) -> Value {
+    let mut params = event.mapped(sink);
+    let name = params.remove("name").and_then(|v| v.as_str().map(ga4_name)).unwrap_or_default();
+    params.remove("visitor_id");
+    params.remove("timestamp");
+
+    for (field, param) in [
+        ("path", "page_location"),
+        ("title", "page_title"),
+        ("referrer", "page_referrer"),
+        ("utm_source", "source"),
+        ("utm_medium", "medium"),
+        ("utm_campaign", "campaign"),
+    ] {
+        if let Some(value) = params.remove(field) {
+            let value = match (field, base_url, value.as_str()) {
+                ("path", Some(base), Some(path)) => Value::from(format!("{}{}", base.trim_end_matches('/'), path)),
+                _ => value,
+            };
+            params.insert(param.to_string(), value);
+        }
+    }
+    // Without it GA4 doesn't count the session as engaged
+    params.insert("engagement_time_msec".to_string(), Value::from(1));
+
+    json!({
+        "name": name,
+        "params": params,
+        "timestamp_micros": event.timestamp.timestamp_micros(),
+    })
+}
+
+/// GA4 event names are letters, digits and underscores, starting with a
+/// letter, at most 40 characters
+fn ga4_name(name: &str) -> String {
+    let mut sanitized: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .take(GA4_MAX_NAME_LENGTH)
+        .collect();
+    if !sanitized.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        sanitized.insert(0, 'e');
+        sanitized.truncate(GA4_MAX_NAME_LENGTH);
+    }
+    sanitized
+}
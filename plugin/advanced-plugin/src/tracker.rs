@@ -0,0 +1,162 @@
+//! Tracker Script
+//!
+//! The tracking script (`assets/tracker.js`) is served from
+//! `/api/v1/rustpress-analytics/tracker.js` and pages only get a short
+//! loader tag:
+//!
+//! ```html
+//! <script async src="/api/v1/rustpress-analytics/tracker.js?v=3f2a9c0d1e4b5a6c"
+//!     integrity="sha384-..." crossorigin="anonymous" data-heartbeat="15" ...></script>
+//! ```
+//!
+//! `v` is derived from the script's content, so a versioned URL can be cached
+//! for good and changes whenever the script does; `integrity` lets browsers
+//! refuse a tampered copy. Settings reach the script through the tag's
+//! `data-*` attributes, as does the tracker key it signs hits with
+//! (`data-key`, when the `analytics.tracker_key` secret is set). With
+//! `inline_tracker` set the same script is inlined instead, as before.
+//!
+//! Whether and how the script tracks a visitor is asked from `/policy` once
+//! it runs, as pages may be cached for visitors from other regions.
+
+use crate::AnalyticsConfig;
+use axum::{
+    extract::Query,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use sha2::{Digest, Sha384};
+use std::sync::OnceLock;
+
+/// Where the script is served, below the plugin's route prefix
+pub const TRACKER_PATH: &str = "/tracker.js";
+
+/// The tracking script
+pub const SCRIPT: &str = include_str!("../assets/tracker.js");
+
+/// Cache lifetime of the unversioned URL
+const UNVERSIONED_MAX_AGE: u32 = 300;
+
+/// Content-derived identifiers of [`SCRIPT`]
+pub struct TrackerAsset {
+    /// First 16 hex digits of the SHA-384 digest
+    pub version: String,
+    /// Subresource Integrity value (`sha384-<base64>`)
+    pub integrity: String,
+}
+
+/// Hashes of the script, computed once
+pub fn asset() -> &'static TrackerAsset {
+    static ASSET: OnceLock<TrackerAsset> = OnceLock::new();
+    ASSET.get_or_init(|| {
+        let digest = Sha384::digest(SCRIPT.as_bytes());
+        TrackerAsset {
+            version: digest[..8].iter().map(|b| format!("{:02x}", b)).collect(),
+            integrity: format!("sha384-{}", STANDARD.encode(digest)),
+        }
+    })
+}
+
+/// Tag loading the script for a page; carries the request's CSP nonce
+pub fn loader_snippet(config: &AnalyticsConfig, key: Option<&str>) -> String {
+    let asset = asset();
+    format!(
+        r#"<script async src="/api/v1/rustpress-analytics{}?v={}" integrity="{}" crossorigin="anonymous"{}{}></script>"#,
+        TRACKER_PATH,
+        asset.version,
+        asset.integrity,
+        rustpress_auth::security::nonce_attr(),
+        data_attrs(config, key),
+    )
+}
+
+/// The script inlined into the page, for `inline_tracker`
+pub fn inline_snippet(config: &AnalyticsConfig, key: Option<&str>) -> String {
+    format!(
+        "<script{}{}>\n{}</script>",
+        rustpress_auth::security::nonce_attr(),
+        data_attrs(config, key),
+        SCRIPT,
+    )
+}
+
+fn data_attrs(config: &AnalyticsConfig, key: Option<&str>) -> String {
+    let mut attrs = format!(
+        r#" data-outbound="{}" data-downloads="{}" data-download-extensions="{}" data-heartbeat="{}""#,
+        config.track_outbound_links,
+        config.track_downloads,
+        escape_attr(&config.download_extensions.join(",")),
+        config.heartbeat_interval_secs,
+    );
+    if let Some(key) = key {
+        attrs.push_str(&format!(r#" data-key="{}""#, escape_attr(key)));
+    }
+    attrs
+}
+
+fn escape_attr(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[derive(Debug, serde::Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TrackerQuery {
+    /// Content version from the loader tag
+    pub v: Option<String>,
+}
+
+/// GET /api/v1/rustpress-analytics/tracker.js
+///
+/// Cached for a year when requested with the current version, for a few
+/// minutes otherwise; answers `If-None-Match` with 304.
+#[utoipa::path(
+    get,
+    path = "/tracker.js",
+    tag = "analytics",
+    params(TrackerQuery),
+    responses(
+        (status = 200, description = "Tracking script", content_type = "application/javascript", body = String),
+        (status = 304, description = "Unchanged"),
+    ),
+)]
+pub async fn serve_tracker(Query(query): Query<TrackerQuery>, headers: HeaderMap) -> Response {
+    let asset = asset();
+    let etag = format!("\"{}\"", asset.version);
+
+    let cache_control = if query.v.as_deref() == Some(asset.version.as_str()) {
+        "public, max-age=31536000, immutable".to_string()
+    } else {
+        format!("public, max-age={}", UNVERSIONED_MAX_AGE)
+    };
+
+    let not_modified = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|tags| tags.split(',').any(|tag| tag.trim() == etag || tag.trim() == "*"));
+
+    let mut response = if not_modified {
+        StatusCode::NOT_MODIFIED.into_response()
+    } else {
+        (
+            [(header::CONTENT_TYPE, "application/javascript; charset=utf-8")],
+            SCRIPT,
+        )
+            .into_response()
+    };
+
+    let headers = response.headers_mut();
+    if let Ok(value) = HeaderValue::from_str(&etag) {
+        headers.insert(header::ETAG, value);
+    }
+    if let Ok(value) = HeaderValue::from_str(&cache_control) {
+        headers.insert(header::CACHE_CONTROL, value);
+    }
+    // Lets pages on other origins load it with crossorigin="anonymous"
+    headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, HeaderValue::from_static("*"));
+    response
+}
@@ -0,0 +1,26 @@
+-- RustPress Assistant - Initial Schema
+--
+-- One row per model call, whether it came from a post save or the editor's
+-- suggest button. Costs are in millionths of a US dollar, priced with the
+-- per-token prices configured at the time of the call.
+
+CREATE TABLE IF NOT EXISTS assistant_usage (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    site_id UUID,
+    user_id UUID REFERENCES users(id) ON DELETE SET NULL,
+    -- Not set for posts being created
+    post_id UUID,
+    -- excerpt, meta_description, terms or alt_text
+    task VARCHAR(32) NOT NULL,
+    -- save or suggest
+    source VARCHAR(16) NOT NULL,
+    model VARCHAR(100) NOT NULL,
+    input_tokens INTEGER NOT NULL DEFAULT 0,
+    output_tokens INTEGER NOT NULL DEFAULT 0,
+    cost_micros BIGINT NOT NULL DEFAULT 0,
+    succeeded BOOLEAN NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_assistant_usage_created ON assistant_usage(created_at);
+CREATE INDEX IF NOT EXISTS idx_assistant_usage_site ON assistant_usage(site_id, created_at);
@@ -0,0 +1,142 @@
+//! Assistant REST API Handlers
+
+use crate::models::*;
+use crate::services::*;
+use crate::AssistantPlugin;
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::Response,
+    routing::{get, post},
+    Json,
+};
+use rustpress_auth::problem::ProblemDetails;
+use rustpress_auth::routes::PluginRoutes;
+use rustpress_auth::{AuthUser, ValidatedJson};
+use std::sync::Arc;
+use utoipa::OpenApi;
+
+/// Create API routes, mounted under `/api/v1/rustpress-assistant`
+pub fn create_routes(plugin: &AssistantPlugin) -> PluginRoutes {
+    PluginRoutes::new(plugin.info.id.clone())
+        .route("/suggest", post(suggest))
+        .route("/usage", get(get_usage))
+        // API documentation
+        .route("/openapi.json", get(|| async { Json(ApiDoc::openapi()) }))
+        .route("/docs", get(swagger_ui))
+        .layer(axum::middleware::from_fn(rustpress_auth::problem::trace_id))
+}
+
+// ============================================
+// API Documentation
+// ============================================
+
+/// Assistant API specification, generated from the handler annotations
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "RustPress Assistant API"),
+    servers((url = "/api/v1/rustpress-assistant")),
+    paths(suggest, get_usage),
+    tags(
+        (name = "suggestions", description = "Suggestions for the editor"),
+        (name = "usage", description = "Model calls and their cost"),
+    )
+)]
+pub struct ApiDoc;
+
+/// GET /api/v1/rustpress-assistant/docs
+async fn swagger_ui() -> Response {
+    rustpress_auth::openapi::swagger_ui("openapi.json")
+}
+
+// ============================================
+// Suggestion Endpoints
+// ============================================
+
+/// POST /api/v1/rustpress-assistant/suggest
+///
+/// Suggestions for the post in the editor; nothing is saved. Tasks the model
+/// fails at are listed in `failed`.
+#[utoipa::path(
+    post,
+    path = "/suggest",
+    tag = "suggestions",
+    request_body = SuggestRequest,
+    responses(
+        (status = 200, description = "Suggestions and what they cost", body = Suggestions),
+        (status = 403, description = "Not allowed to write posts", body = ProblemDetails),
+        (status = 422, description = "Invalid post", body = ProblemDetails),
+        (status = 429, description = "Monthly budget spent", body = ProblemDetails),
+        (status = 503, description = "No model endpoint configured", body = ProblemDetails),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn suggest(
+    State(plugin): State<Arc<AssistantPlugin>>,
+    user: AuthUser,
+    ValidatedJson(input): ValidatedJson<SuggestRequest>,
+) -> Result<Json<Suggestions>, ProblemDetails> {
+    if !user.can_publish() {
+        return Err(ProblemDetails::forbidden("Suggestions require the author role"));
+    }
+
+    Ok(Json(assistant_service(&plugin).await?.suggest(&input, user.id).await?))
+}
+
+// ============================================
+// Usage Endpoints
+// ============================================
+
+/// GET /api/v1/rustpress-assistant/usage
+#[utoipa::path(
+    get,
+    path = "/usage",
+    tag = "usage",
+    params(UsageQuery),
+    responses(
+        (status = 200, description = "Calls, tokens and cost per task and source", body = UsageReport),
+        (status = 403, description = "Not an admin", body = ProblemDetails),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn get_usage(
+    State(plugin): State<Arc<AssistantPlugin>>,
+    user: AuthUser,
+    Query(query): Query<UsageQuery>,
+) -> Result<Json<UsageReport>, ProblemDetails> {
+    if !user.is_admin() {
+        return Err(ProblemDetails::forbidden("Usage reports require the admin role"));
+    }
+
+    Ok(Json(assistant_service(&plugin).await?.meter().report(&query).await?))
+}
+
+// ============================================
+// Helpers
+// ============================================
+
+impl From<AssistantError> for ProblemDetails {
+    fn from(e: AssistantError) -> Self {
+        match e {
+            AssistantError::NotConfigured => ProblemDetails::unavailable(e.to_string()),
+            AssistantError::BudgetExhausted => {
+                ProblemDetails::new(StatusCode::TOO_MANY_REQUESTS, "budget_exhausted").detail(e.to_string())
+            }
+            AssistantError::Model(e) => {
+                tracing::warn!("Model call failed: {}", e);
+                ProblemDetails::new(StatusCode::BAD_GATEWAY, "model_error").detail(e.to_string())
+            }
+            AssistantError::Database(msg) => {
+                tracing::error!("Assistant database error: {}", msg);
+                ProblemDetails::internal("Database error")
+            }
+        }
+    }
+}
+
+async fn assistant_service(plugin: &AssistantPlugin) -> Result<Arc<AssistantService>, ProblemDetails> {
+    plugin
+        .assistant()
+        .await
+        .ok_or_else(|| ProblemDetails::unavailable("Assistant service unavailable"))
+}
@@ -0,0 +1,68 @@
+//! Assistant Hook Handlers
+
+use crate::models::PostDraft;
+use crate::AssistantPlugin;
+use rustpress_plugins::prelude::*;
+use std::sync::Arc;
+
+/// Fill in what a post being saved is missing
+///
+/// Registered on the blog's `post_before_save` filter, which passes the post
+/// as JSON. Blank excerpts and meta descriptions are written, images without
+/// alt text described and, for new posts without any, tags and categories
+/// suggested, each as enabled in the settings. A draft this plugin can't
+/// read, a failing model or a spent budget leave the post as submitted.
+pub async fn fill_post_fields(
+    ctx: FilterContext,
+    plugin: Arc<AssistantPlugin>,
+    draft: serde_json::Value,
+) -> Result<serde_json::Value, HookError> {
+    let Some(assistant) = plugin.assistant().await else {
+        return Ok(draft);
+    };
+    if !assistant.enabled() {
+        return Ok(draft);
+    }
+
+    let parsed = match serde_json::from_value::<PostDraft>(draft.clone()) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            tracing::warn!(request_id = %ctx.request_id, "Unreadable post draft: {}", e);
+            return Ok(draft);
+        }
+    };
+
+    let filled = assistant.fill(parsed).await;
+    Ok(serde_json::to_value(filled)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_draft_keeps_unknown_fields() {
+        let value = json!({
+            "site_id": "7b1f0e5e-2f53-4d8f-9a4e-0d1c7f1d8a11",
+            "post_id": null,
+            "author_id": "1c0c9d1e-7e8b-4b0a-8d62-7f3c5c2b9e40",
+            "title": "Hello",
+            "content": "<p>World</p>",
+            "excerpt": null,
+            "meta_description": null,
+            "has_terms": false,
+            "suggested_tags": [],
+            "suggested_categories": [],
+            "featured_image": "/hero.png",
+        });
+
+        let mut draft: PostDraft = serde_json::from_value(value.clone()).unwrap();
+        assert_eq!(draft.other["featured_image"], "/hero.png");
+
+        draft.excerpt = Some("Hi".into());
+        let mut expected = value;
+        expected["excerpt"] = json!("Hi");
+        assert_eq!(serde_json::to_value(draft).unwrap(), expected);
+    }
+}
@@ -0,0 +1,259 @@
+//! RustPress Assistant Plugin
+//!
+//! Language model suggestions for posts:
+//! - Excerpts and meta descriptions
+//! - Tags and categories from the site's existing ones
+//! - Alt text for images without any
+//!
+//! Missing fields are filled in when a post is saved, through the blog's
+//! `post_before_save` filter, and the editor can ask for suggestions at any
+//! time. Every model call is metered with its token counts and cost, and
+//! calls stop once the monthly budget is spent.
+
+pub mod api;
+pub mod hooks;
+pub mod models;
+pub mod services;
+
+use async_trait::async_trait;
+use rustpress_auth::migrations::PluginMigrations;
+use rustpress_plugins::prelude::*;
+use services::{AssistantService, PromptTemplates};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+// ============================================
+// Plugin Configuration
+// ============================================
+
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct AssistantConfig {
+    /// OpenAI-compatible chat completions URL; nothing is generated when unset
+    pub endpoint: Option<String>,
+    pub api_key: Option<String>,
+    pub model: String,
+    pub timeout_secs: u64,
+    /// Post text sent with each prompt at most, in characters
+    pub max_content_chars: usize,
+    /// What's filled in on save
+    pub fill_excerpt: bool,
+    pub fill_meta_description: bool,
+    pub suggest_terms: bool,
+    pub fill_alt_text: bool,
+    /// US cents per million tokens
+    pub input_price_cents: u64,
+    pub output_price_cents: u64,
+    /// US cents a month; 0 for no limit
+    pub monthly_budget_cents: u64,
+    pub templates: PromptTemplates,
+}
+
+impl Default for AssistantConfig {
+    fn default() -> Self {
+        Self {
+            endpoint: None,
+            api_key: None,
+            model: "gpt-4o-mini".into(),
+            timeout_secs: 30,
+            max_content_chars: 6000,
+            fill_excerpt: true,
+            fill_meta_description: true,
+            suggest_terms: true,
+            fill_alt_text: false,
+            input_price_cents: 0,
+            output_price_cents: 0,
+            monthly_budget_cents: 0,
+            templates: PromptTemplates::default(),
+        }
+    }
+}
+
+// ============================================
+// Main Plugin Struct
+// ============================================
+
+pub struct AssistantPlugin {
+    info: PluginInfo,
+    state: RwLock<PluginState>,
+    config: RwLock<AssistantConfig>,
+    assistant_service: RwLock<Option<Arc<AssistantService>>>,
+}
+
+impl AssistantPlugin {
+    /// Schema migrations shipped with the plugin (`migrations/`)
+    pub fn migrations() -> PluginMigrations {
+        PluginMigrations::new("rustpress-assistant", sqlx::migrate!("./migrations"))
+    }
+
+    pub fn new() -> Self {
+        Self {
+            info: PluginInfo {
+                id: "rustpress-assistant".into(),
+                name: "RustPress Assistant".into(),
+                version: "1.0.0".into(),
+                needs: vec![Capability::Db],
+            },
+            state: RwLock::new(PluginState::Inactive),
+            config: RwLock::new(AssistantConfig::default()),
+            assistant_service: RwLock::new(None),
+        }
+    }
+
+    pub async fn config(&self) -> AssistantConfig {
+        self.config.read().await.clone()
+    }
+
+    pub async fn assistant(&self) -> Option<Arc<AssistantService>> {
+        self.assistant_service.read().await.clone()
+    }
+
+    async fn load_config(&self, settings: &SettingsManager) -> Result<AssistantConfig, HookError> {
+        let mut config = AssistantConfig::default();
+
+        if let Some(v) = settings.get::<String>("rustpress-assistant", "endpoint").await? {
+            config.endpoint = Some(v).filter(|v| !v.is_empty());
+        }
+        if let Some(v) = settings.get::<String>("rustpress-assistant", "api_key").await? {
+            config.api_key = Some(v).filter(|v| !v.is_empty());
+        }
+        if let Some(v) = settings.get("rustpress-assistant", "model").await? {
+            config.model = v;
+        }
+        if let Some(v) = settings.get("rustpress-assistant", "timeout_secs").await? {
+            config.timeout_secs = v;
+        }
+        if let Some(v) = settings.get("rustpress-assistant", "max_content_chars").await? {
+            config.max_content_chars = v;
+        }
+        if let Some(v) = settings.get("rustpress-assistant", "fill_excerpt").await? {
+            config.fill_excerpt = v;
+        }
+        if let Some(v) = settings.get("rustpress-assistant", "fill_meta_description").await? {
+            config.fill_meta_description = v;
+        }
+        if let Some(v) = settings.get("rustpress-assistant", "suggest_terms").await? {
+            config.suggest_terms = v;
+        }
+        if let Some(v) = settings.get("rustpress-assistant", "fill_alt_text").await? {
+            config.fill_alt_text = v;
+        }
+        if let Some(v) = settings.get("rustpress-assistant", "input_price_cents").await? {
+            config.input_price_cents = v;
+        }
+        if let Some(v) = settings.get("rustpress-assistant", "output_price_cents").await? {
+            config.output_price_cents = v;
+        }
+        if let Some(v) = settings.get("rustpress-assistant", "monthly_budget_cents").await? {
+            config.monthly_budget_cents = v;
+        }
+
+        // Prompt templates; an emptied one falls back to the default
+        let templates = &mut config.templates;
+        for (key, template) in [
+            ("prompt_excerpt", &mut templates.excerpt),
+            ("prompt_meta_description", &mut templates.meta_description),
+            ("prompt_terms", &mut templates.terms),
+            ("prompt_alt_text", &mut templates.alt_text),
+        ] {
+            if let Some(v) = settings.get::<String>("rustpress-assistant", key).await? {
+                if !v.trim().is_empty() {
+                    *template = v;
+                }
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+impl Default for AssistantPlugin {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// ============================================
+// Lifecycle Implementation
+// ============================================
+
+#[async_trait]
+impl LifecycleHook for AssistantPlugin {
+    async fn on_activate(&self, ctx: &ActivationContext) -> Result<(), HookError> {
+        tracing::info!("Activating RustPress Assistant plugin");
+
+        // Run migrations
+        Self::migrations()
+            .up(&ctx.db, None, false)
+            .await
+            .map_err(|e| HookError::Migration(e.to_string()))?;
+
+        // Load configuration
+        let config = self.load_config(&ctx.settings).await?;
+        *self.config.write().await = config.clone();
+
+        if config.endpoint.is_none() {
+            tracing::warn!("No model endpoint configured; posts are saved without suggestions");
+        }
+
+        // Initialize services
+        let assistant = AssistantService::new(ctx.db.clone(), config).map_err(|e| HookError::Config(e.to_string()))?;
+        *self.assistant_service.write().await = Some(Arc::new(assistant));
+
+        // Register routes under /api/v1/<plugin-id>; fails when another
+        // plugin or the app already serves one of the paths
+        let routes = rustpress_auth::routes::shared()
+            .mount(api::create_routes(self))
+            .map_err(|e| HookError::InvalidData(e.to_string()))?;
+        ctx.register_routes(routes).await?;
+
+        *self.state.write().await = PluginState::Active;
+        tracing::info!("RustPress Assistant activated successfully");
+        Ok(())
+    }
+
+    async fn on_deactivate(&self, ctx: &DeactivationContext) -> Result<(), HookError> {
+        tracing::info!("Deactivating RustPress Assistant");
+
+        // Posts are saved as submitted from now on
+        *self.assistant_service.write().await = None;
+
+        // Unregister routes
+        rustpress_auth::routes::shared().unregister(&self.info.id);
+        ctx.unregister_routes().await?;
+
+        *self.state.write().await = PluginState::Inactive;
+        Ok(())
+    }
+
+    async fn on_upgrade(&self, ctx: &UpgradeContext) -> Result<(), HookError> {
+        tracing::info!("Upgrading Assistant from {} to {}", ctx.from_version, ctx.to_version);
+
+        Self::migrations()
+            .up(&ctx.db, None, false)
+            .await
+            .map_err(|e| HookError::Migration(e.to_string()))?;
+        Ok(())
+    }
+
+    async fn on_uninstall(&self, ctx: &UninstallContext) -> Result<(), HookError> {
+        tracing::info!("Uninstalling RustPress Assistant");
+
+        // Generated text stays in the posts; only the usage records go
+        Self::migrations()
+            .down(&ctx.db, 0, false)
+            .await
+            .map_err(|e| HookError::Migration(e.to_string()))?;
+
+        // Remove settings
+        ctx.settings.remove_all("rustpress-assistant").await?;
+
+        tracing::info!("RustPress Assistant uninstalled");
+        Ok(())
+    }
+}
+
+// ============================================
+// Plugin Entry Point
+// ============================================
+
+rustpress_plugin!(AssistantPlugin);
@@ -0,0 +1,162 @@
+//! Assistant Data Models
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+use validator::Validate;
+
+// ============================================
+// Tasks
+// ============================================
+
+/// What the model is asked for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Task {
+    Excerpt,
+    MetaDescription,
+    /// Suggested tags and categories
+    Terms,
+    /// Alt text for images without any, one call per image
+    AltText,
+}
+
+impl Task {
+    pub const ALL: [Task; 4] = [Task::Excerpt, Task::MetaDescription, Task::Terms, Task::AltText];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Task::Excerpt => "excerpt",
+            Task::MetaDescription => "meta_description",
+            Task::Terms => "terms",
+            Task::AltText => "alt_text",
+        }
+    }
+}
+
+/// What triggered a model call
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    /// The blog's `post_before_save` filter
+    Save,
+    /// The editor's suggest endpoint
+    Suggest,
+}
+
+impl Source {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Source::Save => "save",
+            Source::Suggest => "suggest",
+        }
+    }
+}
+
+// ============================================
+// Post Drafts
+// ============================================
+
+/// A post being saved, as passed through the blog's `post_before_save`
+/// filter; fields this plugin doesn't know are passed back untouched
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PostDraft {
+    pub site_id: Uuid,
+    /// `None` for a post being created
+    pub post_id: Option<Uuid>,
+    pub author_id: Uuid,
+    pub title: String,
+    pub content: String,
+    pub excerpt: Option<String>,
+    pub meta_description: Option<String>,
+    /// Whether the author picked tags or categories; suggestions are only
+    /// made for posts without
+    #[serde(default)]
+    pub has_terms: bool,
+    #[serde(default)]
+    pub suggested_tags: Vec<String>,
+    #[serde(default)]
+    pub suggested_categories: Vec<String>,
+    #[serde(flatten)]
+    pub other: serde_json::Map<String, serde_json::Value>,
+}
+
+// ============================================
+// Suggestions
+// ============================================
+
+#[derive(Debug, Clone, Deserialize, Validate, ToSchema)]
+pub struct SuggestRequest {
+    #[validate(length(max = 200))]
+    pub title: String,
+    #[validate(length(min = 1, message = "Content is required"))]
+    pub content: String,
+    /// Post being edited, for usage reports
+    pub post_id: Option<Uuid>,
+    /// Site of the post, for usage reports
+    pub site_id: Option<Uuid>,
+    /// What to suggest; everything when left out
+    pub tasks: Option<Vec<Task>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct AltTextSuggestion {
+    /// `src` of the image
+    pub src: String,
+    pub alt: String,
+}
+
+/// Suggestions for the editor; nothing is saved
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct Suggestions {
+    pub excerpt: Option<String>,
+    pub meta_description: Option<String>,
+    pub tags: Vec<String>,
+    pub categories: Vec<String>,
+    /// For images without alt text
+    pub alt_text: Vec<AltTextSuggestion>,
+    /// Tasks the model failed at or the budget didn't allow
+    pub failed: Vec<Task>,
+    /// Cost of the calls, in millionths of a US dollar
+    pub cost_micros: i64,
+}
+
+// ============================================
+// Usage
+// ============================================
+
+/// Usage report parameters
+#[derive(Debug, Clone, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct UsageQuery {
+    /// Start of the period; the start of the current month by default
+    pub since: Option<DateTime<Utc>>,
+    /// End of the period; now by default
+    pub until: Option<DateTime<Utc>>,
+    pub site_id: Option<Uuid>,
+}
+
+/// Calls of one task from one source
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct UsageSummary {
+    pub task: String,
+    pub source: String,
+    pub calls: i64,
+    pub failed: i64,
+    pub input_tokens: i64,
+    pub output_tokens: i64,
+    pub cost_micros: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct UsageReport {
+    pub since: DateTime<Utc>,
+    pub until: DateTime<Utc>,
+    pub cost_micros: i64,
+    /// Spent this month, across all sites
+    pub month_cost_micros: i64,
+    /// `None` without a monthly budget
+    pub monthly_budget_micros: Option<i64>,
+    pub data: Vec<UsageSummary>,
+}
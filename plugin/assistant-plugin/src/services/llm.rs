@@ -0,0 +1,157 @@
+//! Model Endpoint
+//!
+//! Prompts are sent to an OpenAI-compatible chat completions endpoint
+//! (`POST <endpoint>` with `{"model", "messages", "max_tokens"}`), which most
+//! hosted and self-hosted model servers accept. Images are passed as an
+//! `image_url` content part, so alt text needs a model that reads images.
+//! The token counts in the response's `usage` are what calls are billed by.
+
+use serde_json::{json, Value};
+use std::time::Duration;
+
+/// Sent ahead of every prompt
+const SYSTEM_PROMPT: &str = "You help the editors of a blog. Follow the instructions exactly and reply \
+                             with the requested text only, without quotes or commentary.";
+
+pub struct LlmClient {
+    http: reqwest::Client,
+    endpoint: String,
+    api_key: Option<String>,
+    model: String,
+}
+
+/// A model reply and what it cost in tokens
+#[derive(Debug, Clone, PartialEq)]
+pub struct Completion {
+    pub text: String,
+    pub input_tokens: i32,
+    pub output_tokens: i32,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum LlmError {
+    #[error("Model request failed: {0}")]
+    Request(#[from] reqwest::Error),
+    #[error("Model endpoint returned {status}: {message}")]
+    Api { status: u16, message: String },
+    #[error("Model returned no text")]
+    Empty,
+}
+
+impl LlmClient {
+    pub fn new(endpoint: String, api_key: Option<String>, model: String, timeout: Duration) -> Result<Self, LlmError> {
+        Ok(Self {
+            http: reqwest::Client::builder().timeout(timeout).build()?,
+            endpoint,
+            api_key,
+            model,
+        })
+    }
+
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    /// Ask for at most `max_tokens` tokens in reply to `prompt`, about the
+    /// image at `image` if given
+    pub async fn complete(&self, prompt: &str, image: Option<&str>, max_tokens: u32) -> Result<Completion, LlmError> {
+        let mut request = self
+            .http
+            .post(&self.endpoint)
+            .json(&request_body(&self.model, prompt, image, max_tokens));
+        if let Some(key) = &self.api_key {
+            request = request.bearer_auth(key);
+        }
+
+        let response = request.send().await?;
+        let status = response.status();
+        if !status.is_success() {
+            let body: Value = response.json().await.unwrap_or_default();
+            return Err(LlmError::Api {
+                status: status.as_u16(),
+                message: body["error"]["message"].as_str().unwrap_or("unknown error").to_string(),
+            });
+        }
+
+        parse_response(&response.json().await?)
+    }
+}
+
+/// Chat completion request for `prompt`
+pub fn request_body(model: &str, prompt: &str, image: Option<&str>, max_tokens: u32) -> Value {
+    let content = match image {
+        Some(url) => json!([
+            {"type": "text", "text": prompt},
+            {"type": "image_url", "image_url": {"url": url}},
+        ]),
+        None => json!(prompt),
+    };
+
+    json!({
+        "model": model,
+        "messages": [
+            {"role": "system", "content": SYSTEM_PROMPT},
+            {"role": "user", "content": content},
+        ],
+        "max_tokens": max_tokens,
+        "temperature": 0.3,
+    })
+}
+
+/// The first choice's text and the token counts of a chat completion
+pub fn parse_response(body: &Value) -> Result<Completion, LlmError> {
+    let text = body["choices"][0]["message"]["content"]
+        .as_str()
+        .map(str::trim)
+        .filter(|text| !text.is_empty())
+        .ok_or(LlmError::Empty)?;
+
+    let tokens = |key: &str| body["usage"][key].as_i64().unwrap_or(0).clamp(0, i32::MAX as i64) as i32;
+    Ok(Completion {
+        text: text.to_string(),
+        input_tokens: tokens("prompt_tokens"),
+        output_tokens: tokens("completion_tokens"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_body() {
+        let body = request_body("small-model", "Summarize", None, 100);
+        assert_eq!(body["model"], "small-model");
+        assert_eq!(body["messages"][1]["content"], "Summarize");
+        assert_eq!(body["max_tokens"], 100);
+
+        let body = request_body("vision-model", "Describe", Some("https://example.com/a.png"), 60);
+        let parts = body["messages"][1]["content"].as_array().unwrap();
+        assert_eq!(parts[0]["text"], "Describe");
+        assert_eq!(parts[1]["image_url"]["url"], "https://example.com/a.png");
+    }
+
+    #[test]
+    fn test_parse_response() {
+        let body = json!({
+            "choices": [{"message": {"role": "assistant", "content": "  A short summary.\n"}}],
+            "usage": {"prompt_tokens": 412, "completion_tokens": 9},
+        });
+        assert_eq!(
+            parse_response(&body).unwrap(),
+            Completion {
+                text: "A short summary.".into(),
+                input_tokens: 412,
+                output_tokens: 9,
+            }
+        );
+
+        // Servers that don't report usage are billed nothing
+        let body = json!({"choices": [{"message": {"content": "Hi"}}]});
+        assert_eq!(parse_response(&body).unwrap().input_tokens, 0);
+
+        let body = json!({"choices": [{"message": {"content": "   "}}]});
+        assert!(matches!(parse_response(&body), Err(LlmError::Empty)));
+        assert!(matches!(parse_response(&json!({})), Err(LlmError::Empty)));
+    }
+}
@@ -0,0 +1,777 @@
+//! Assistant Services
+
+pub mod llm;
+
+use crate::models::*;
+use crate::AssistantConfig;
+use chrono::{DateTime, Datelike, TimeZone, Utc};
+use llm::{LlmClient, LlmError};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Longest text asked for, in characters
+pub const EXCERPT_CHARS: usize = 300;
+pub const META_DESCRIPTION_CHARS: usize = 160;
+pub const ALT_TEXT_CHARS: usize = 125;
+
+/// Suggested terms kept at most
+pub const MAX_TAGS: usize = 5;
+pub const MAX_CATEGORIES: usize = 2;
+
+/// Images described per post at most
+pub const MAX_IMAGES: usize = 10;
+
+// ============================================
+// Prompt Templates
+// ============================================
+
+/// Prompts per task, stored in the plugin's settings
+///
+/// `{title}` and `{content}` (the post as plain text, cut to
+/// `max_content_chars`) are replaced in every template, `{max_chars}` with
+/// the task's length limit, and `{image}` with the image URL for alt text.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PromptTemplates {
+    pub excerpt: String,
+    pub meta_description: String,
+    pub terms: String,
+    pub alt_text: String,
+}
+
+impl Default for PromptTemplates {
+    fn default() -> Self {
+        Self {
+            excerpt: "Write an excerpt of one or two sentences, at most {max_chars} characters, for this blog \
+                      post.\n\nTitle: {title}\n\n{content}"
+                .into(),
+            meta_description: "Write a search result description of at most {max_chars} characters for this \
+                               blog post.\n\nTitle: {title}\n\n{content}"
+                .into(),
+            terms: "Suggest up to 5 tags and up to 2 categories for this blog post. Reply with JSON only, as \
+                    {\"tags\": [...], \"categories\": [...]}.\n\nTitle: {title}\n\n{content}"
+                .into(),
+            alt_text: "Write alt text of at most {max_chars} characters for this image from the blog post \
+                       \"{title}\", describing what it shows."
+                .into(),
+        }
+    }
+}
+
+impl PromptTemplates {
+    pub fn get(&self, task: Task) -> &str {
+        match task {
+            Task::Excerpt => &self.excerpt,
+            Task::MetaDescription => &self.meta_description,
+            Task::Terms => &self.terms,
+            Task::AltText => &self.alt_text,
+        }
+    }
+}
+
+/// `template` with each `{name}` of `vars` replaced; other braces are kept
+pub fn render(template: &str, vars: &[(&str, &str)]) -> String {
+    let mut out = template.to_string();
+    for (name, value) in vars {
+        out = out.replace(&format!("{{{}}}", name), value);
+    }
+    out
+}
+
+// ============================================
+// Usage Meter
+// ============================================
+
+/// Who a model call is made for
+#[derive(Debug, Clone, Copy)]
+pub struct CallContext {
+    pub site_id: Option<Uuid>,
+    pub user_id: Option<Uuid>,
+    pub post_id: Option<Uuid>,
+    pub source: Source,
+}
+
+/// Records every call with its token counts and cost, and keeps spending
+/// within the monthly budget
+pub struct UsageMeter {
+    db: PgPool,
+    /// US cents per million tokens
+    input_price_cents: i64,
+    output_price_cents: i64,
+    /// Millionths of a dollar; `None` for no limit
+    monthly_budget_micros: Option<i64>,
+}
+
+impl UsageMeter {
+    pub fn new(db: PgPool, config: &AssistantConfig) -> Self {
+        Self {
+            db,
+            input_price_cents: config.input_price_cents as i64,
+            output_price_cents: config.output_price_cents as i64,
+            monthly_budget_micros: (config.monthly_budget_cents > 0).then(|| config.monthly_budget_cents as i64 * 10_000),
+        }
+    }
+
+    /// Cost of a call in millionths of a dollar
+    pub fn cost_micros(&self, input_tokens: i32, output_tokens: i32) -> i64 {
+        cost_micros(input_tokens, output_tokens, self.input_price_cents, self.output_price_cents)
+    }
+
+    pub fn monthly_budget_micros(&self) -> Option<i64> {
+        self.monthly_budget_micros
+    }
+
+    /// Fails once this month's calls have used up the budget
+    pub async fn check_budget(&self) -> Result<(), AssistantError> {
+        let Some(budget) = self.monthly_budget_micros else {
+            return Ok(());
+        };
+        if self.month_cost().await? >= budget {
+            return Err(AssistantError::BudgetExhausted);
+        }
+        Ok(())
+    }
+
+    /// Spent since the start of the month, across all sites
+    pub async fn month_cost(&self) -> Result<i64, AssistantError> {
+        let cost: i64 =
+            sqlx::query_scalar("SELECT COALESCE(SUM(cost_micros), 0)::BIGINT FROM assistant_usage WHERE created_at >= $1")
+                .bind(month_start(Utc::now()))
+                .fetch_one(&self.db)
+                .await?;
+        Ok(cost)
+    }
+
+    pub async fn record(
+        &self,
+        ctx: &CallContext,
+        task: Task,
+        model: &str,
+        tokens: Option<(i32, i32)>,
+    ) -> Result<i64, AssistantError> {
+        let (input_tokens, output_tokens) = tokens.unwrap_or((0, 0));
+        let cost = self.cost_micros(input_tokens, output_tokens);
+
+        sqlx::query(
+            r#"INSERT INTO assistant_usage
+               (site_id, user_id, post_id, task, source, model, input_tokens, output_tokens, cost_micros, succeeded)
+               VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)"#,
+        )
+        .bind(ctx.site_id)
+        .bind(ctx.user_id)
+        .bind(ctx.post_id)
+        .bind(task.as_str())
+        .bind(ctx.source.as_str())
+        .bind(model)
+        .bind(input_tokens)
+        .bind(output_tokens)
+        .bind(cost)
+        .bind(tokens.is_some())
+        .execute(&self.db)
+        .await?;
+
+        Ok(cost)
+    }
+
+    /// Calls and costs per task and source
+    pub async fn report(&self, query: &UsageQuery) -> Result<UsageReport, AssistantError> {
+        let until = query.until.unwrap_or_else(Utc::now);
+        let since = query.since.unwrap_or_else(|| month_start(until));
+
+        let data: Vec<UsageSummary> = sqlx::query_as(
+            r#"SELECT task, source, COUNT(*) AS calls,
+                      COUNT(*) FILTER (WHERE NOT succeeded) AS failed,
+                      COALESCE(SUM(input_tokens), 0)::BIGINT AS input_tokens,
+                      COALESCE(SUM(output_tokens), 0)::BIGINT AS output_tokens,
+                      COALESCE(SUM(cost_micros), 0)::BIGINT AS cost_micros
+               FROM assistant_usage
+               WHERE created_at >= $1 AND created_at < $2 AND ($3::UUID IS NULL OR site_id = $3)
+               GROUP BY task, source
+               ORDER BY task, source"#,
+        )
+        .bind(since)
+        .bind(until)
+        .bind(query.site_id)
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(UsageReport {
+            since,
+            until,
+            cost_micros: data.iter().map(|row| row.cost_micros).sum(),
+            month_cost_micros: self.month_cost().await?,
+            monthly_budget_micros: self.monthly_budget_micros,
+            data,
+        })
+    }
+}
+
+/// Cost of a call in millionths of a dollar at prices in cents per million
+/// tokens, rounded up
+pub fn cost_micros(input_tokens: i32, output_tokens: i32, input_price_cents: i64, output_price_cents: i64) -> i64 {
+    // A cent per million tokens is a hundredth of a micro-dollar per token
+    let hundredths = input_tokens.max(0) as i64 * input_price_cents + output_tokens.max(0) as i64 * output_price_cents;
+    (hundredths + 99) / 100
+}
+
+/// Midnight UTC on the first of `at`'s month
+fn month_start(at: DateTime<Utc>) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(at.year(), at.month(), 1, 0, 0, 0).unwrap()
+}
+
+// ============================================
+// Assistant Service
+// ============================================
+
+pub struct AssistantService {
+    config: AssistantConfig,
+    /// `None` until an endpoint is configured
+    llm: Option<LlmClient>,
+    meter: UsageMeter,
+}
+
+impl AssistantService {
+    pub fn new(db: PgPool, config: AssistantConfig) -> Result<Self, AssistantError> {
+        let llm = match &config.endpoint {
+            Some(endpoint) => Some(LlmClient::new(
+                endpoint.clone(),
+                config.api_key.clone(),
+                config.model.clone(),
+                Duration::from_secs(config.timeout_secs),
+            )?),
+            None => None,
+        };
+
+        Ok(Self {
+            meter: UsageMeter::new(db, &config),
+            config,
+            llm,
+        })
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.llm.is_some()
+    }
+
+    pub fn meter(&self) -> &UsageMeter {
+        &self.meter
+    }
+
+    /// Run `task`'s prompt; every call is metered, failed ones too
+    async fn ask(
+        &self,
+        ctx: &CallContext,
+        task: Task,
+        vars: &[(&str, &str)],
+        image: Option<&str>,
+        max_chars: usize,
+    ) -> Result<(String, i64), AssistantError> {
+        let llm = self.llm.as_ref().ok_or(AssistantError::NotConfigured)?;
+        self.meter.check_budget().await?;
+
+        let max = max_chars.to_string();
+        let mut vars = vars.to_vec();
+        vars.push(("max_chars", &max));
+        let prompt = render(self.config.templates.get(task), &vars);
+
+        // Room for the text asked for; a token is about four characters
+        let max_tokens = match task {
+            Task::Terms => 200,
+            _ => (max_chars / 3 + 16) as u32,
+        };
+
+        match llm.complete(&prompt, image, max_tokens).await {
+            Ok(completion) => {
+                let tokens = (completion.input_tokens, completion.output_tokens);
+                let cost = self.meter.record(ctx, task, llm.model(), Some(tokens)).await?;
+                Ok((completion.text, cost))
+            }
+            Err(e) => {
+                self.meter.record(ctx, task, llm.model(), None).await?;
+                Err(e.into())
+            }
+        }
+    }
+
+    /// Ask for the text of `task` about a post
+    async fn post_text(
+        &self,
+        ctx: &CallContext,
+        task: Task,
+        title: &str,
+        content: &str,
+        max_chars: usize,
+    ) -> Result<(String, i64), AssistantError> {
+        let text = plain_text(content, self.config.max_content_chars);
+        let (reply, cost) = self
+            .ask(ctx, task, &[("title", title), ("content", &text)], None, max_chars)
+            .await?;
+        Ok((clean(&reply, max_chars), cost))
+    }
+
+    async fn terms(&self, ctx: &CallContext, title: &str, content: &str) -> Result<(Terms, i64), AssistantError> {
+        let text = plain_text(content, self.config.max_content_chars);
+        let (reply, cost) = self
+            .ask(ctx, Task::Terms, &[("title", title), ("content", &text)], None, 0)
+            .await?;
+        Ok((parse_terms(&reply), cost))
+    }
+
+    async fn alt_text(&self, ctx: &CallContext, title: &str, src: &str) -> Result<(String, i64), AssistantError> {
+        let (reply, cost) = self
+            .ask(ctx, Task::AltText, &[("title", title), ("image", src)], Some(src), ALT_TEXT_CHARS)
+            .await?;
+        Ok((clean(&reply, ALT_TEXT_CHARS), cost))
+    }
+
+    /// Suggestions for the editor; tasks that fail are listed in `failed`
+    pub async fn suggest(&self, req: &SuggestRequest, user_id: Uuid) -> Result<Suggestions, AssistantError> {
+        if self.llm.is_none() {
+            return Err(AssistantError::NotConfigured);
+        }
+        let ctx = CallContext {
+            site_id: req.site_id,
+            user_id: Some(user_id),
+            post_id: req.post_id,
+            source: Source::Suggest,
+        };
+        let tasks = req.tasks.clone().unwrap_or_else(|| Task::ALL.to_vec());
+
+        let mut suggestions = Suggestions::default();
+        let mut answered = false;
+        for task in Task::ALL.into_iter().filter(|task| tasks.contains(task)) {
+            match self.suggest_task(&ctx, task, req, &mut suggestions).await {
+                Ok(()) => answered = true,
+                // Nothing to show for the call at all
+                Err(AssistantError::BudgetExhausted) if !answered => return Err(AssistantError::BudgetExhausted),
+                Err(e) => {
+                    tracing::warn!(task = task.as_str(), "Suggestion failed: {}", e);
+                    suggestions.failed.push(task);
+                }
+            }
+        }
+
+        Ok(suggestions)
+    }
+
+    async fn suggest_task(
+        &self,
+        ctx: &CallContext,
+        task: Task,
+        req: &SuggestRequest,
+        suggestions: &mut Suggestions,
+    ) -> Result<(), AssistantError> {
+        match task {
+            Task::Excerpt => {
+                let (text, cost) = self.post_text(ctx, task, &req.title, &req.content, EXCERPT_CHARS).await?;
+                suggestions.excerpt = Some(text);
+                suggestions.cost_micros += cost;
+            }
+            Task::MetaDescription => {
+                let (text, cost) = self
+                    .post_text(ctx, task, &req.title, &req.content, META_DESCRIPTION_CHARS)
+                    .await?;
+                suggestions.meta_description = Some(text);
+                suggestions.cost_micros += cost;
+            }
+            Task::Terms => {
+                let (terms, cost) = self.terms(ctx, &req.title, &req.content).await?;
+                suggestions.tags = terms.tags;
+                suggestions.categories = terms.categories;
+                suggestions.cost_micros += cost;
+            }
+            Task::AltText => {
+                for src in images_without_alt(&req.content).into_iter().take(MAX_IMAGES) {
+                    let (alt, cost) = self.alt_text(ctx, &req.title, &src).await?;
+                    suggestions.alt_text.push(AltTextSuggestion { src, alt });
+                    suggestions.cost_micros += cost;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Fill in what a post being saved is missing, as far as the model and
+    /// the budget allow; the draft is never rejected
+    pub async fn fill(&self, mut draft: PostDraft) -> PostDraft {
+        if self.llm.is_none() {
+            return draft;
+        }
+        let ctx = CallContext {
+            site_id: Some(draft.site_id),
+            user_id: Some(draft.author_id),
+            post_id: draft.post_id,
+            source: Source::Save,
+        };
+        let config = &self.config;
+
+        if config.fill_excerpt && is_blank(&draft.excerpt) {
+            match self.post_text(&ctx, Task::Excerpt, &draft.title, &draft.content, EXCERPT_CHARS).await {
+                Ok((text, _)) => draft.excerpt = Some(text),
+                Err(e) => return skipped(draft, Task::Excerpt, e),
+            }
+        }
+
+        if config.fill_meta_description && is_blank(&draft.meta_description) {
+            match self
+                .post_text(&ctx, Task::MetaDescription, &draft.title, &draft.content, META_DESCRIPTION_CHARS)
+                .await
+            {
+                Ok((text, _)) => draft.meta_description = Some(text),
+                Err(e) => return skipped(draft, Task::MetaDescription, e),
+            }
+        }
+
+        if config.suggest_terms && !draft.has_terms && draft.post_id.is_none() {
+            match self.terms(&ctx, &draft.title, &draft.content).await {
+                Ok((terms, _)) => {
+                    draft.suggested_tags = terms.tags;
+                    draft.suggested_categories = terms.categories;
+                }
+                Err(e) => return skipped(draft, Task::Terms, e),
+            }
+        }
+
+        if config.fill_alt_text {
+            let mut described = Vec::new();
+            for src in images_without_alt(&draft.content).into_iter().take(MAX_IMAGES) {
+                match self.alt_text(&ctx, &draft.title, &src).await {
+                    Ok((alt, _)) => described.push(AltTextSuggestion { src, alt }),
+                    Err(e) => {
+                        tracing::warn!(post_id = ?draft.post_id, "Alt text not generated: {}", e);
+                        break;
+                    }
+                }
+            }
+            draft.content = set_alt(&draft.content, &described);
+        }
+
+        draft
+    }
+}
+
+/// Give up on the remaining fields of a draft after `task` failed
+fn skipped(draft: PostDraft, task: Task, e: AssistantError) -> PostDraft {
+    tracing::warn!(post_id = ?draft.post_id, task = task.as_str(), "Post saved without suggestions: {}", e);
+    draft
+}
+
+fn is_blank(value: &Option<String>) -> bool {
+    value.as_deref().map_or(true, |v| v.trim().is_empty())
+}
+
+// ============================================
+// Text Helpers
+// ============================================
+
+/// Tags and categories suggested for a post
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Terms {
+    pub tags: Vec<String>,
+    pub categories: Vec<String>,
+}
+
+/// The terms in a `{"tags": [...], "categories": [...]}` reply, which may be
+/// wrapped in prose or a code block; nothing when there's no such object
+pub fn parse_terms(reply: &str) -> Terms {
+    let json = match (reply.find('{'), reply.rfind('}')) {
+        (Some(start), Some(end)) if start < end => &reply[start..=end],
+        _ => return Terms::default(),
+    };
+    let Ok(value) = serde_json::from_str::<serde_json::Value>(json) else {
+        return Terms::default();
+    };
+
+    let names = |key: &str, max: usize| {
+        let mut names: Vec<String> = Vec::new();
+        for name in value[key].as_array().into_iter().flatten().filter_map(|v| v.as_str()) {
+            let name = clean(name, 50);
+            if !name.is_empty() && !names.iter().any(|n| n.eq_ignore_ascii_case(&name)) {
+                names.push(name);
+            }
+        }
+        names.truncate(max);
+        names
+    };
+
+    Terms {
+        tags: names("tags", MAX_TAGS),
+        categories: names("categories", MAX_CATEGORIES),
+    }
+}
+
+/// `html` as plain text, cut to `max_chars` characters
+pub fn plain_text(html: &str, max_chars: usize) -> String {
+    let mut text = String::with_capacity(html.len().min(max_chars * 4));
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => {
+                in_tag = false;
+                text.push(' ');
+            }
+            _ if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    cut(&text, max_chars).to_string()
+}
+
+/// A model reply as a single line of at most `max_chars` characters, without
+/// surrounding quotes; cut at a word when longer
+pub fn clean(reply: &str, max_chars: usize) -> String {
+    let text = reply.split_whitespace().collect::<Vec<_>>().join(" ");
+    let text = text.trim_matches(|c: char| matches!(c, '"' | '\'' | '“' | '”' | '`')).trim();
+    if max_chars == 0 || text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+
+    let cut = cut(text, max_chars);
+    match cut.rfind(' ') {
+        Some(space) if space > 0 => cut[..space].trim_end_matches([',', ';', ':', ' ']).to_string(),
+        _ => cut.to_string(),
+    }
+}
+
+/// The first `max_chars` characters of `text`
+fn cut(text: &str, max_chars: usize) -> &str {
+    match text.char_indices().nth(max_chars) {
+        Some((end, _)) => &text[..end],
+        None => text,
+    }
+}
+
+/// Byte ranges of the `<img>` tags in `html`
+fn img_tags(html: &str) -> Vec<(usize, usize)> {
+    let lower = html.to_ascii_lowercase();
+    let mut tags = Vec::new();
+    let mut from = 0;
+    while let Some(i) = lower[from..].find("<img") {
+        let start = from + i;
+        let Some(len) = lower[start..].find('>') else {
+            break;
+        };
+        let end = start + len + 1;
+        // `<imgx>` is another element
+        if lower[start + 4..].starts_with(|c: char| c.is_ascii_whitespace() || c == '/' || c == '>') {
+            tags.push((start, end));
+        }
+        from = end;
+    }
+    tags
+}
+
+/// Attributes of an element's start tag, names lowercased; attributes
+/// without a value have an empty one
+fn attributes(tag: &str) -> Vec<(String, &str)> {
+    let bytes = tag.as_bytes();
+    let mut attributes = Vec::new();
+    // Past the element name
+    let mut i = tag.find(|c: char| c.is_ascii_whitespace() || c == '/' || c == '>').unwrap_or(tag.len());
+    loop {
+        while i < bytes.len() && (bytes[i].is_ascii_whitespace() || bytes[i] == b'/') {
+            i += 1;
+        }
+        if i >= bytes.len() || bytes[i] == b'>' {
+            break;
+        }
+
+        let start = i;
+        while i < bytes.len() && !bytes[i].is_ascii_whitespace() && !matches!(bytes[i], b'=' | b'>' | b'/') {
+            i += 1;
+        }
+        let name = tag[start..i].to_ascii_lowercase();
+
+        let mut j = i;
+        while j < bytes.len() && bytes[j].is_ascii_whitespace() {
+            j += 1;
+        }
+        if j >= bytes.len() || bytes[j] != b'=' {
+            attributes.push((name, ""));
+            continue;
+        }
+
+        j += 1;
+        while j < bytes.len() && bytes[j].is_ascii_whitespace() {
+            j += 1;
+        }
+        if j < bytes.len() && matches!(bytes[j], b'"' | b'\'') {
+            let quote = bytes[j] as char;
+            let value_end = tag[j + 1..].find(quote).map_or(tag.len(), |end| j + 1 + end);
+            attributes.push((name, &tag[j + 1..value_end]));
+            i = (value_end + 1).min(tag.len());
+        } else {
+            let value_start = j;
+            while j < bytes.len() && !bytes[j].is_ascii_whitespace() && bytes[j] != b'>' {
+                j += 1;
+            }
+            attributes.push((name, &tag[value_start..j]));
+            i = j;
+        }
+    }
+    attributes
+}
+
+/// Value of attribute `name` in `tag`
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    attributes(tag).into_iter().find(|(n, _)| n == name).map(|(_, value)| value)
+}
+
+/// `src` of each image without an `alt` attribute; `alt=""` marks an image
+/// as decorative and is left alone
+pub fn images_without_alt(html: &str) -> Vec<String> {
+    let mut sources: Vec<String> = Vec::new();
+    for (start, end) in img_tags(html) {
+        let tag = &html[start..end];
+        if attribute(tag, "alt").is_some() {
+            continue;
+        }
+        if let Some(src) = attribute(tag, "src").filter(|src| !src.is_empty()) {
+            if !sources.iter().any(|s| s == src) {
+                sources.push(src.to_string());
+            }
+        }
+    }
+    sources
+}
+
+/// `html` with the alt text of `described` added to the images without any
+pub fn set_alt(html: &str, described: &[AltTextSuggestion]) -> String {
+    if described.is_empty() {
+        return html.to_string();
+    }
+
+    let mut out = String::with_capacity(html.len());
+    let mut copied = 0;
+    for (start, end) in img_tags(html) {
+        let tag = &html[start..end];
+        if attribute(tag, "alt").is_some() {
+            continue;
+        }
+        let Some(suggestion) = attribute(tag, "src").and_then(|src| described.iter().find(|d| d.src == src)) else {
+            continue;
+        };
+
+        out.push_str(&html[copied..start + 4]);
+        out.push_str(&format!(r#" alt="{}""#, escape(&suggestion.alt)));
+        copied = start + 4;
+    }
+    out.push_str(&html[copied..]);
+    out
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// ============================================
+// Error Types
+// ============================================
+
+#[derive(Debug, thiserror::Error)]
+pub enum AssistantError {
+    #[error("No model endpoint is configured")]
+    NotConfigured,
+    #[error("The monthly budget has been spent")]
+    BudgetExhausted,
+    #[error(transparent)]
+    Model(#[from] LlmError),
+    #[error("Database error: {0}")]
+    Database(String),
+}
+
+impl From<sqlx::Error> for AssistantError {
+    fn from(e: sqlx::Error) -> Self {
+        AssistantError::Database(e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_keeps_other_braces() {
+        let rendered = render(
+            r#"Reply as {"tags": []} for {title}: {content} ({max_chars})"#,
+            &[("title", "Hello"), ("content", "Body"), ("max_chars", "160")],
+        );
+        assert_eq!(rendered, r#"Reply as {"tags": []} for Hello: Body (160)"#);
+    }
+
+    #[test]
+    fn test_parse_terms() {
+        let reply = "Sure!\n```json\n{\"tags\": [\"Rust\", \"rust\", \"Async\", \"\"], \"categories\": [\"Programming\"]}\n```";
+        assert_eq!(
+            parse_terms(reply),
+            Terms {
+                tags: vec!["Rust".into(), "Async".into()],
+                categories: vec!["Programming".into()],
+            }
+        );
+
+        let many = r#"{"tags": ["a", "b", "c", "d", "e", "f"], "categories": ["x", "y", "z"]}"#;
+        let terms = parse_terms(many);
+        assert_eq!((terms.tags.len(), terms.categories.len()), (MAX_TAGS, MAX_CATEGORIES));
+
+        assert_eq!(parse_terms("rust, async"), Terms::default());
+        assert_eq!(parse_terms("{not json}"), Terms::default());
+    }
+
+    #[test]
+    fn test_clean() {
+        assert_eq!(clean("  \"A tidy\n summary.\"  ", 160), "A tidy summary.");
+        assert_eq!(clean("one two three four", 12), "one two");
+        assert_eq!(clean("abcdefghij", 4), "abcd");
+    }
+
+    #[test]
+    fn test_plain_text() {
+        assert_eq!(plain_text("<h2>Intro</h2><p>Hello <b>world</b>.</p>", 100), "Intro Hello world .");
+        assert_eq!(plain_text("<p>Grüße aus Köln</p>", 5), "Grüße");
+    }
+
+    #[test]
+    fn test_images_without_alt() {
+        let html = r#"<p><img src="/a.png"><IMG class="wide" SRC='/b.png' /><img src="/c.png" alt="C">
+            <img src="/d.png" alt=""><img src="/e.png" alt><img data-alternate="x" src=/f.png>
+            <imgx src="/g.png"><img src="/a.png"></p>"#;
+        assert_eq!(images_without_alt(html), ["/a.png", "/b.png", "/f.png"]);
+    }
+
+    #[test]
+    fn test_set_alt() {
+        let html = r#"<img src="/a.png"><img src="/b.png" alt="Kept"><img src="/c.png"><img src="/a.png">"#;
+        let described = [AltTextSuggestion {
+            src: "/a.png".into(),
+            alt: r#"A "red" kite"#.into(),
+        }];
+        assert_eq!(
+            set_alt(html, &described),
+            r#"<img alt="A &quot;red&quot; kite" src="/a.png"><img src="/b.png" alt="Kept"><img src="/c.png"><img alt="A &quot;red&quot; kite" src="/a.png">"#
+        );
+        assert_eq!(set_alt(html, &[]), html);
+    }
+
+    #[test]
+    fn test_cost_is_rounded_up() {
+        // $0.15 and $0.60 per million tokens
+        assert_eq!(cost_micros(1_000_000, 0, 15, 60), 150_000);
+        assert_eq!(cost_micros(1000, 100, 15, 60), 210);
+        assert_eq!(cost_micros(1, 0, 15, 60), 1);
+        assert_eq!(cost_micros(0, 0, 15, 60), 0);
+    }
+
+    #[test]
+    fn test_month_start() {
+        let at = Utc.with_ymd_and_hms(2026, 10, 16, 13, 45, 0).unwrap();
+        assert_eq!(month_start(at), Utc.with_ymd_and_hms(2026, 10, 1, 0, 0, 0).unwrap());
+    }
+}
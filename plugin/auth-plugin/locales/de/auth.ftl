@@ -0,0 +1,86 @@
+# RustPress Authentication - German
+
+## Errors
+
+auth-invalid-credentials = Ungültige Anmeldedaten
+auth-account-locked = Das Konto ist gesperrt. Bitte später erneut versuchen
+auth-account-not-active = Das Konto ist nicht aktiv
+auth-email-not-verified = E-Mail-Adresse nicht bestätigt
+auth-login-confirmation-required = Anmeldung von einem neuen Ort oder Gerät. Bestätige sie über den Link in der E-Mail und melde dich dann erneut an
+auth-invalid-token = Ungültiges oder abgelaufenes Token
+auth-token-revoked = Das Token wurde widerrufen
+auth-user-not-found = Benutzer nicht gefunden
+auth-email-exists = Diese E-Mail-Adresse ist bereits registriert
+auth-weak-password = Das Passwort erfüllt die Anforderungen nicht
+auth-password-expired = Das Passwort ist abgelaufen. Wähle ein neues, um dich anzumelden
+auth-password-reused = Dieses Passwort wurde kürzlich verwendet. Wähle ein anderes
+auth-internal-error = Ein interner Fehler ist aufgetreten
+auth-configuration-error = Fehler in der Serverkonfiguration
+auth-authentication-required = Anmeldung erforderlich
+auth-invalid-authorization-header = Ungültiges Format des Authorization-Headers
+auth-session-ended = Die Sitzung wurde beendet
+auth-admin-required = Administratorrechte erforderlich
+auth-insufficient-permissions = Unzureichende Berechtigungen
+auth-unsupported-locale = Nicht unterstützte Sprache „{ $locale }“. Verfügbar: { $available }
+
+## Validation, by validator rule
+
+validation-failed = Validierung der Anfrage fehlgeschlagen
+validation-invalid = Ungültiger Wert ({ $code })
+validation-required = Dieses Feld ist erforderlich
+validation-email = Ungültige E-Mail-Adresse
+validation-url = Ungültige URL
+validation-length = Muss zwischen { $min } und { $max } Zeichen lang sein
+validation-length-min = Muss mindestens { $min } Zeichen lang sein
+validation-length-max = Darf höchstens { $max } Zeichen lang sein
+validation-length-equal = Muss genau { $equal } Zeichen lang sein
+validation-range = Muss zwischen { $min } und { $max } liegen
+validation-range-min = Muss mindestens { $min } sein
+validation-range-max = Darf höchstens { $max } sein
+validation-must-match = Muss mit { $other } übereinstimmen
+
+## Responses
+
+msg-registered = Registrierung erfolgreich. Bitte bestätige deine E-Mail-Adresse.
+msg-logged-out = Erfolgreich abgemeldet
+msg-reset-requested = Falls ein Konto mit dieser E-Mail-Adresse existiert, wurde ein Link zum Zurücksetzen des Passworts gesendet.
+msg-password-reset = Passwort erfolgreich zurückgesetzt. Bitte melde dich mit deinem neuen Passwort an.
+msg-password-changed = Passwort erfolgreich geändert. Bitte melde dich auf allen Geräten erneut an.
+msg-expired-password-changed = Passwort erfolgreich geändert. Bitte melde dich mit deinem neuen Passwort an.
+msg-email-verified = E-Mail-Adresse erfolgreich bestätigt
+msg-login-confirmed = Anmeldung bestätigt. Du kannst dich jetzt anmelden.
+msg-already-verified = Die E-Mail-Adresse ist bereits bestätigt
+msg-verification-sent = Bestätigungs-E-Mail gesendet
+
+## Mail
+
+email-verification-subject = Bestätige deine E-Mail-Adresse
+email-verification-body =
+    Hallo { $name },
+
+    bitte bestätige deine E-Mail-Adresse, indem du diesen Link öffnest:
+
+    { $link }
+
+email-password-reset-subject = Setze dein Passwort zurück
+email-password-reset-body =
+    Jemand hat angefordert, das Passwort deines Kontos zurückzusetzen.
+
+    Öffne diesen Link, um ein neues zu wählen:
+
+    { $link }
+
+    Falls du das nicht warst, ignoriere diese Nachricht.
+
+email-login-alert-subject = Neue Anmeldung bei deinem Konto
+email-login-alert-body =
+    Hallo { $name },
+
+    bei deinem Konto wurde sich gerade von einem neuen Ort aus angemeldet: { $origin }.
+email-login-alert-confirm =
+    Falls du das warst, bestätige die Anmeldung über diesen Link und melde dich dann erneut an:
+
+    { $link }
+
+    Falls nicht, öffne den Link nicht und ändere dein Passwort.
+email-login-alert-change-password = Falls du das nicht warst, ändere sofort dein Passwort.
@@ -0,0 +1,89 @@
+# RustPress Authentication - English (default)
+#
+# Every message the plugin shows to clients or mails to users. Other
+# locales may leave messages out; they fall back to these.
+
+## Errors
+
+auth-invalid-credentials = Invalid credentials
+auth-account-locked = Account is locked. Try again later
+auth-account-not-active = Account is not active
+auth-email-not-verified = Email not verified
+auth-login-confirmation-required = Login from a new location or device. Confirm it with the link sent by email, then log in again
+auth-invalid-token = Invalid or expired token
+auth-token-revoked = Token has been revoked
+auth-user-not-found = User not found
+auth-email-exists = Email already registered
+auth-weak-password = Password does not meet requirements
+auth-password-expired = Password has expired. Choose a new one to log in
+auth-password-reused = Password was used recently. Choose a different one
+auth-internal-error = An internal error occurred
+auth-configuration-error = Server configuration error
+auth-authentication-required = Authentication required
+auth-invalid-authorization-header = Invalid authorization header format
+auth-session-ended = Session has ended
+auth-admin-required = Admin access required
+auth-insufficient-permissions = Insufficient permissions
+auth-unsupported-locale = Unsupported locale "{ $locale }". Available: { $available }
+
+## Validation, by validator rule
+
+validation-failed = Request validation failed
+validation-invalid = Invalid value ({ $code })
+validation-required = This field is required
+validation-email = Invalid email address
+validation-url = Invalid URL
+validation-length = Must be between { $min } and { $max } characters
+validation-length-min = Must be at least { $min } characters
+validation-length-max = Must be at most { $max } characters
+validation-length-equal = Must be exactly { $equal } characters
+validation-range = Must be between { $min } and { $max }
+validation-range-min = Must be at least { $min }
+validation-range-max = Must be at most { $max }
+validation-must-match = Must match { $other }
+
+## Responses
+
+msg-registered = Registration successful. Please verify your email.
+msg-logged-out = Logged out successfully
+msg-reset-requested = If an account with that email exists, a password reset link has been sent.
+msg-password-reset = Password reset successful. Please login with your new password.
+msg-password-changed = Password changed successfully. Please login again on all devices.
+msg-expired-password-changed = Password changed successfully. Please login with your new password.
+msg-email-verified = Email verified successfully
+msg-login-confirmed = Login confirmed. You can now log in.
+msg-already-verified = Email is already verified
+msg-verification-sent = Verification email sent
+
+## Mail
+
+email-verification-subject = Verify your email address
+email-verification-body =
+    Hello { $name },
+
+    Please confirm your email address by opening this link:
+
+    { $link }
+
+email-password-reset-subject = Reset your password
+email-password-reset-body =
+    Someone asked to reset the password of your account.
+
+    Open this link to choose a new one:
+
+    { $link }
+
+    If this wasn't you, ignore this message.
+
+email-login-alert-subject = New login to your account
+email-login-alert-body =
+    Hello { $name },
+
+    Your account was just logged in to from a new place: { $origin }.
+email-login-alert-confirm =
+    If this was you, confirm the login by opening this link, then log in again:
+
+    { $link }
+
+    If it wasn't, don't open the link and change your password.
+email-login-alert-change-password = If this wasn't you, change your password right away.
@@ -0,0 +1,86 @@
+# RustPress Authentication - French
+
+## Errors
+
+auth-invalid-credentials = Identifiants invalides
+auth-account-locked = Le compte est verrouillé. Réessayez plus tard
+auth-account-not-active = Le compte n'est pas actif
+auth-email-not-verified = Adresse e-mail non vérifiée
+auth-login-confirmation-required = Connexion depuis un nouveau lieu ou appareil. Confirmez-la avec le lien envoyé par e-mail, puis reconnectez-vous
+auth-invalid-token = Jeton invalide ou expiré
+auth-token-revoked = Le jeton a été révoqué
+auth-user-not-found = Utilisateur introuvable
+auth-email-exists = Adresse e-mail déjà enregistrée
+auth-weak-password = Le mot de passe ne respecte pas les exigences
+auth-password-expired = Le mot de passe a expiré. Choisissez-en un nouveau pour vous connecter
+auth-password-reused = Ce mot de passe a été utilisé récemment. Choisissez-en un autre
+auth-internal-error = Une erreur interne est survenue
+auth-configuration-error = Erreur de configuration du serveur
+auth-authentication-required = Authentification requise
+auth-invalid-authorization-header = Format de l'en-tête Authorization invalide
+auth-session-ended = La session est terminée
+auth-admin-required = Accès administrateur requis
+auth-insufficient-permissions = Permissions insuffisantes
+auth-unsupported-locale = Langue non prise en charge « { $locale } ». Disponibles : { $available }
+
+## Validation, by validator rule
+
+validation-failed = La validation de la requête a échoué
+validation-invalid = Valeur invalide ({ $code })
+validation-required = Ce champ est obligatoire
+validation-email = Adresse e-mail invalide
+validation-url = URL invalide
+validation-length = Doit contenir entre { $min } et { $max } caractères
+validation-length-min = Doit contenir au moins { $min } caractères
+validation-length-max = Doit contenir au plus { $max } caractères
+validation-length-equal = Doit contenir exactement { $equal } caractères
+validation-range = Doit être compris entre { $min } et { $max }
+validation-range-min = Doit être au moins { $min }
+validation-range-max = Doit être au plus { $max }
+validation-must-match = Doit correspondre à { $other }
+
+## Responses
+
+msg-registered = Inscription réussie. Veuillez vérifier votre adresse e-mail.
+msg-logged-out = Déconnexion réussie
+msg-reset-requested = Si un compte existe avec cette adresse e-mail, un lien de réinitialisation du mot de passe a été envoyé.
+msg-password-reset = Mot de passe réinitialisé. Veuillez vous connecter avec votre nouveau mot de passe.
+msg-password-changed = Mot de passe modifié. Veuillez vous reconnecter sur tous vos appareils.
+msg-expired-password-changed = Mot de passe modifié. Veuillez vous connecter avec votre nouveau mot de passe.
+msg-email-verified = Adresse e-mail vérifiée
+msg-login-confirmed = Connexion confirmée. Vous pouvez maintenant vous connecter.
+msg-already-verified = L'adresse e-mail est déjà vérifiée
+msg-verification-sent = E-mail de vérification envoyé
+
+## Mail
+
+email-verification-subject = Vérifiez votre adresse e-mail
+email-verification-body =
+    Bonjour { $name },
+
+    Veuillez confirmer votre adresse e-mail en ouvrant ce lien :
+
+    { $link }
+
+email-password-reset-subject = Réinitialisez votre mot de passe
+email-password-reset-body =
+    Quelqu'un a demandé la réinitialisation du mot de passe de votre compte.
+
+    Ouvrez ce lien pour en choisir un nouveau :
+
+    { $link }
+
+    Si ce n'était pas vous, ignorez ce message.
+
+email-login-alert-subject = Nouvelle connexion à votre compte
+email-login-alert-body =
+    Bonjour { $name },
+
+    Votre compte vient d'être utilisé depuis un nouveau lieu : { $origin }.
+email-login-alert-confirm =
+    Si c'était vous, confirmez la connexion en ouvrant ce lien, puis reconnectez-vous :
+
+    { $link }
+
+    Sinon, n'ouvrez pas le lien et changez votre mot de passe.
+email-login-alert-change-password = Si ce n'était pas vous, changez votre mot de passe immédiatement.
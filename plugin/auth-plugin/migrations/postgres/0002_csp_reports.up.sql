@@ -0,0 +1,18 @@
+-- Content Security Policy violation reports (see `security`)
+
+CREATE TABLE IF NOT EXISTS csp_reports (
+    id UUID PRIMARY KEY,
+    document_uri TEXT NOT NULL,
+    effective_directive TEXT NOT NULL,
+    blocked_uri TEXT,
+    source_file TEXT,
+    line_number BIGINT,
+    column_number BIGINT,
+    sample TEXT,
+    disposition TEXT NOT NULL,
+    user_agent TEXT,
+    created_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_csp_reports_created_at ON csp_reports(created_at);
+CREATE INDEX IF NOT EXISTS idx_csp_reports_directive ON csp_reports(effective_directive, created_at);
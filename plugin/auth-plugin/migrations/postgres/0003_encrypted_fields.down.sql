@@ -0,0 +1,4 @@
+-- Encrypted values don't fit the old column and are dropped
+
+ALTER TABLE users ALTER COLUMN last_login_ip TYPE VARCHAR(45)
+    USING CASE WHEN last_login_ip LIKE 'enc:v%' THEN NULL ELSE last_login_ip END;
@@ -0,0 +1,3 @@
+-- Encrypted values (see `crypto`) outgrow VARCHAR(45)
+
+ALTER TABLE users ALTER COLUMN last_login_ip TYPE TEXT;
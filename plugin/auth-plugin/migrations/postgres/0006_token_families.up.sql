@@ -0,0 +1,7 @@
+-- Refresh tokens rotated from one login share its family, so sessions can be
+-- counted and ended per device (see `auth.max_sessions`)
+
+ALTER TABLE refresh_tokens ADD COLUMN family_id UUID;
+UPDATE refresh_tokens SET family_id = id;
+
+CREATE INDEX IF NOT EXISTS idx_refresh_tokens_family ON refresh_tokens(family_id);
@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS auth_login_confirmations;
+DROP TABLE IF EXISTS auth_login_history;
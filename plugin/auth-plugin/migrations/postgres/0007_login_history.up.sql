@@ -0,0 +1,28 @@
+-- Where users logged in from, to spot logins from new places (see `anomaly`)
+
+CREATE TABLE IF NOT EXISTS auth_login_history (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    country VARCHAR(100),
+    asn BIGINT,
+    device VARCHAR(100),
+    created_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_auth_login_history_user ON auth_login_history(user_id, created_at);
+
+-- Suspicious logins waiting for the user to confirm them by email
+
+CREATE TABLE IF NOT EXISTS auth_login_confirmations (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    token_hash VARCHAR(255) NOT NULL UNIQUE,
+    country VARCHAR(100),
+    asn BIGINT,
+    device VARCHAR(100),
+    expires_at TIMESTAMPTZ NOT NULL,
+    confirmed_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_auth_login_confirmations_user ON auth_login_confirmations(user_id);
@@ -0,0 +1,11 @@
+-- Earlier password hashes, so recent passwords can't be reused
+-- (`auth.password_history`)
+
+CREATE TABLE IF NOT EXISTS auth_password_history (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    password_hash VARCHAR(255) NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_auth_password_history_user ON auth_password_history(user_id, created_at);
@@ -0,0 +1,4 @@
+-- Locale a user chose for messages and mail (see `i18n`); NULL negotiates
+-- it from each request's Accept-Language
+
+ALTER TABLE users ADD COLUMN locale VARCHAR(35);
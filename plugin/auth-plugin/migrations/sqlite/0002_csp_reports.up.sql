@@ -0,0 +1,18 @@
+-- Content Security Policy violation reports (see `security`)
+
+CREATE TABLE IF NOT EXISTS csp_reports (
+    id BLOB PRIMARY KEY NOT NULL,
+    document_uri TEXT NOT NULL,
+    effective_directive TEXT NOT NULL,
+    blocked_uri TEXT,
+    source_file TEXT,
+    line_number INTEGER,
+    column_number INTEGER,
+    sample TEXT,
+    disposition TEXT NOT NULL,
+    user_agent TEXT,
+    created_at TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_csp_reports_created_at ON csp_reports(created_at);
+CREATE INDEX IF NOT EXISTS idx_csp_reports_directive ON csp_reports(effective_directive, created_at);
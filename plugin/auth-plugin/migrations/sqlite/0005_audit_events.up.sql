@@ -0,0 +1,15 @@
+-- Administrative changes to accounts (see `audit`)
+
+CREATE TABLE IF NOT EXISTS auth_audit_events (
+    id BLOB PRIMARY KEY NOT NULL,
+    actor_id BLOB REFERENCES users(id) ON DELETE SET NULL,
+    action TEXT NOT NULL,
+    target_id BLOB REFERENCES users(id) ON DELETE SET NULL,
+    details TEXT NOT NULL,
+    ip_address TEXT,
+    trace_id TEXT,
+    created_at TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_auth_audit_events_target ON auth_audit_events(target_id, created_at);
+CREATE INDEX IF NOT EXISTS idx_auth_audit_events_created_at ON auth_audit_events(created_at);
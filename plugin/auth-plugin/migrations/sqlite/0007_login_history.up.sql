@@ -0,0 +1,28 @@
+-- Where users logged in from, to spot logins from new places (see `anomaly`)
+
+CREATE TABLE IF NOT EXISTS auth_login_history (
+    id BLOB PRIMARY KEY NOT NULL,
+    user_id BLOB NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    country TEXT,
+    asn INTEGER,
+    device TEXT,
+    created_at TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_auth_login_history_user ON auth_login_history(user_id, created_at);
+
+-- Suspicious logins waiting for the user to confirm them by email
+
+CREATE TABLE IF NOT EXISTS auth_login_confirmations (
+    id BLOB PRIMARY KEY NOT NULL,
+    user_id BLOB NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    token_hash TEXT NOT NULL UNIQUE,
+    country TEXT,
+    asn INTEGER,
+    device TEXT,
+    expires_at TEXT NOT NULL,
+    confirmed_at TEXT,
+    created_at TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_auth_login_confirmations_user ON auth_login_confirmations(user_id);
@@ -0,0 +1,11 @@
+-- Earlier password hashes, so recent passwords can't be reused
+-- (`auth.password_history`)
+
+CREATE TABLE IF NOT EXISTS auth_password_history (
+    id BLOB PRIMARY KEY NOT NULL,
+    user_id BLOB NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    password_hash TEXT NOT NULL,
+    created_at TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_auth_password_history_user ON auth_password_history(user_id, created_at);
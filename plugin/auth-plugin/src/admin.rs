@@ -0,0 +1,266 @@
+//! Admin User Management
+//!
+//! Endpoints for admins to find users and manage their accounts:
+//!
+//! - `GET /auth/admin/users`: search by email or name, filter by role and
+//!   status, paginated with `page` / `per_page`
+//! - `PATCH /auth/admin/users/:id`: change the role or status, or unlock an
+//!   account locked after failed logins
+//! - `POST /auth/admin/users/:id/force-logout`: end every session of a user
+//! - `GET /auth/admin/tokens/:family`: the rotation chain of a refresh token
+//!   family (one login), with the devices each token went to and any replays
+//!   of rotated tokens, for investigating a suspected token theft
+//!
+//! All require the admin role, and each admin may make `auth.admin_rate_limit`
+//! requests per minute (60 by default). Changes are written to the audit log
+//! (see [`crate::audit`]).
+
+use crate::error::AuthError;
+use crate::extractors::{AuthUser, ClientInfo, ValidatedJson};
+use crate::middleware::require_admin;
+use crate::models::*;
+use crate::problem::ProblemDetails;
+use crate::service::AuthService;
+
+use axum::{
+    extract::{Path, Query, Request, State},
+    http::{header, HeaderValue, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{get, patch, post},
+    Json, Router,
+};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+/// Path of the user listing below the API version prefix
+pub const USERS_PATH: &str = "/auth/admin/users";
+
+/// Length of a rate limit window
+const WINDOW: Duration = Duration::from_secs(60);
+
+/// Fixed-window request counter per admin
+#[derive(Debug)]
+pub struct AdminRateLimit {
+    /// Requests per window, 0 for no limit
+    limit: u32,
+    windows: Mutex<HashMap<Uuid, (Instant, u32)>>,
+}
+
+impl AdminRateLimit {
+    pub fn new(limit: u32) -> Self {
+        Self {
+            limit,
+            windows: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Count a request by `admin_id`; the requests left in the window, or
+    /// how long until the next one is allowed
+    pub fn check(&self, admin_id: Uuid, now: Instant) -> Result<u32, Duration> {
+        if self.limit == 0 {
+            return Ok(u32::MAX);
+        }
+
+        let mut windows = self.windows.lock().unwrap();
+        windows.retain(|_, (start, _)| now.duration_since(*start) < WINDOW);
+        let (start, count) = windows.entry(admin_id).or_insert((now, 0));
+        if *count >= self.limit {
+            return Err(WINDOW.saturating_sub(now.duration_since(*start)));
+        }
+        *count += 1;
+        Ok(self.limit - *count)
+    }
+}
+
+/// Rejects admins over their limit with 429 and a `Retry-After` header
+async fn rate_limit(State(limiter): State<Arc<AdminRateLimit>>, req: Request, next: Next) -> Response {
+    // `require_admin` runs first and leaves the claims
+    let admin_id = req
+        .extensions()
+        .get::<AccessTokenClaims>()
+        .map(|claims| claims.sub)
+        .unwrap_or_default();
+
+    match limiter.check(admin_id, Instant::now()) {
+        Ok(remaining) => {
+            let mut response = next.run(req).await;
+            if limiter.limit > 0 {
+                let headers = response.headers_mut();
+                headers.insert("x-ratelimit-limit", HeaderValue::from(limiter.limit));
+                headers.insert("x-ratelimit-remaining", HeaderValue::from(remaining));
+            }
+            response
+        }
+        Err(retry_after) => {
+            let secs = retry_after.as_secs().max(1);
+            (
+                [(header::RETRY_AFTER, secs.to_string())],
+                ProblemDetails::new(StatusCode::TOO_MANY_REQUESTS, "rate_limited")
+                    .detail(format!("Too many requests. Please try again in {} seconds.", secs)),
+            )
+                .into_response()
+        }
+    }
+}
+
+/// GET /auth/admin/users
+///
+/// Search and page through users (admin only)
+#[utoipa::path(
+    get,
+    path = "/auth/admin/users",
+    tag = "admin",
+    params(AdminUserQuery),
+    responses(
+        (status = 200, description = "Users, newest first", body = AdminUserPage),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Not an admin", body = ProblemDetails),
+        (status = 429, description = "Rate limit exceeded", body = ProblemDetails)
+    ),
+    security(("bearer_auth" = []))
+)]
+pub async fn list_users(
+    State(auth): State<Arc<AuthService>>,
+    Query(query): Query<AdminUserQuery>,
+) -> Result<Json<AdminUserPage>, AuthError> {
+    Ok(Json(auth.list_users(&query).await?))
+}
+
+/// PATCH /auth/admin/users/{id}
+///
+/// Change a user's role or status, or unlock the account (admin only)
+#[utoipa::path(
+    patch,
+    path = "/auth/admin/users/{id}",
+    tag = "admin",
+    params(("id" = Uuid, Path, description = "User ID")),
+    request_body = AdminUpdateUserRequest,
+    responses(
+        (status = 200, description = "Updated user", body = AdminUserResponse),
+        (status = 400, description = "Invalid change, e.g. an admin demoting themselves", body = ProblemDetails),
+        (status = 404, description = "No such user", body = ProblemDetails),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Not an admin", body = ProblemDetails),
+        (status = 429, description = "Rate limit exceeded", body = ProblemDetails)
+    ),
+    security(("bearer_auth" = []))
+)]
+pub async fn update_user(
+    State(auth): State<Arc<AuthService>>,
+    admin: AuthUser,
+    ClientInfo { ip, .. }: ClientInfo,
+    Path(id): Path<Uuid>,
+    ValidatedJson(req): ValidatedJson<AdminUpdateUserRequest>,
+) -> Result<Json<AdminUserResponse>, AuthError> {
+    let user = auth.update_user(admin.id, id, req, ip).await?;
+    Ok(Json(AdminUserResponse::from(user)))
+}
+
+/// POST /auth/admin/users/{id}/force-logout
+///
+/// End every session of a user (admin only)
+#[utoipa::path(
+    post,
+    path = "/auth/admin/users/{id}/force-logout",
+    tag = "admin",
+    params(("id" = Uuid, Path, description = "User ID")),
+    responses(
+        (status = 200, description = "Sessions ended", body = ForceLogoutResponse),
+        (status = 404, description = "No such user", body = ProblemDetails),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Not an admin", body = ProblemDetails),
+        (status = 429, description = "Rate limit exceeded", body = ProblemDetails)
+    ),
+    security(("bearer_auth" = []))
+)]
+pub async fn force_logout(
+    State(auth): State<Arc<AuthService>>,
+    admin: AuthUser,
+    ClientInfo { ip, .. }: ClientInfo,
+    Path(id): Path<Uuid>,
+) -> Result<Json<ForceLogoutResponse>, AuthError> {
+    let revoked_tokens = auth.force_logout(admin.id, id, ip).await?;
+    Ok(Json(ForceLogoutResponse { revoked_tokens }))
+}
+
+/// GET /auth/admin/tokens/{family}
+///
+/// The tokens of a refresh token family and replays of its rotated tokens
+/// (admin only)
+#[utoipa::path(
+    get,
+    path = "/auth/admin/tokens/{family}",
+    tag = "admin",
+    params(("family" = Uuid, Path, description = "Token family ID, as in `session.evicted` and `token.reuse_detected` audit events")),
+    responses(
+        (status = 200, description = "Rotation chain of the family", body = TokenFamilyResponse),
+        (status = 404, description = "No such token family", body = ProblemDetails),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Not an admin", body = ProblemDetails),
+        (status = 429, description = "Rate limit exceeded", body = ProblemDetails)
+    ),
+    security(("bearer_auth" = []))
+)]
+pub async fn get_token_family(
+    State(auth): State<Arc<AuthService>>,
+    Path(family): Path<Uuid>,
+) -> Result<Json<TokenFamilyResponse>, ProblemDetails> {
+    auth.token_family(family)
+        .await?
+        .map(Json)
+        .ok_or_else(|| ProblemDetails::not_found("No such token family"))
+}
+
+/// User management routes, limited to `auth.admin_rate_limit` requests per
+/// admin and minute
+pub fn routes(auth_service: Arc<AuthService>) -> Router {
+    let limiter = Arc::new(AdminRateLimit::new(auth_service.config().admin_rate_limit));
+
+    Router::new()
+        .route(USERS_PATH, get(list_users))
+        .route("/auth/admin/users/:id", patch(update_user))
+        .route("/auth/admin/users/:id/force-logout", post(force_logout))
+        .route("/auth/admin/tokens/:family", get(get_token_family))
+        .layer(middleware::from_fn_with_state(limiter, rate_limit))
+        .layer(middleware::from_fn(require_admin))
+        .with_state(auth_service)
+}
+
+// ============================================
+// Tests
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_limit_per_admin() {
+        let limiter = AdminRateLimit::new(2);
+        let (alice, bob) = (Uuid::new_v4(), Uuid::new_v4());
+        let start = Instant::now();
+
+        assert_eq!(limiter.check(alice, start), Ok(1));
+        assert_eq!(limiter.check(alice, start), Ok(0));
+        assert_eq!(
+            limiter.check(alice, start + Duration::from_secs(20)),
+            Err(Duration::from_secs(40))
+        );
+        assert_eq!(limiter.check(bob, start), Ok(1));
+
+        // A new window starts once the old one is over
+        assert_eq!(limiter.check(alice, start + WINDOW), Ok(1));
+    }
+
+    #[test]
+    fn test_rate_limit_disabled() {
+        let limiter = AdminRateLimit::new(0);
+        let admin = Uuid::new_v4();
+        for _ in 0..100 {
+            assert!(limiter.check(admin, Instant::now()).is_ok());
+        }
+    }
+}
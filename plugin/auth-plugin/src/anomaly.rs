@@ -0,0 +1,329 @@
+//! Login Anomalies
+//!
+//! Every successful login is remembered in `auth_login_history` with where it
+//! came from: the country and network (ASN) of the client address, looked up
+//! through the installed [`GeoLookup`], and the device, the browser and OS
+//! named by the `User-Agent`. A login from the user's last address passes;
+//! otherwise a country, network or device the user hasn't logged in from
+//! before makes it suspicious, and:
+//!
+//! - a `suspicious_login` audit event is recorded (see [`crate::audit`]) and
+//!   `auth_suspicious_logins_total` incremented
+//! - the user is mailed an alert (outbox kind `login_alert`)
+//! - with `auth.confirm_suspicious_logins`, no tokens are issued: the login
+//!   fails with 403 `login_confirmation_required` and the alert carries a
+//!   link (`mail.confirm_login_url`). Confirming it through
+//!   `POST /auth/confirm-login` remembers the new place, so logging in again
+//!   from there goes through.
+//!
+//! A user's first login has nothing to compare with and is never suspicious.
+//! Without a GeoIP lookup only devices are compared; the analytics plugin
+//! installs its GeoLite2 databases on activation, other plugins can install
+//! their own:
+//!
+//! ```rust,ignore
+//! rustpress_auth::anomaly::install_geo_lookup(Some(Arc::new(MyGeoIp::open()?)));
+//! ```
+
+use crate::db::DbPool;
+use crate::models::User;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::net::IpAddr;
+use std::sync::{Arc, RwLock};
+use uuid::Uuid;
+
+/// Past logins compared with a new one
+const HISTORY: i64 = 50;
+
+static GEO_LOOKUP: RwLock<Option<Arc<dyn GeoLookup>>> = RwLock::new(None);
+
+/// Where an address is
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GeoInfo {
+    /// ISO 3166-1 alpha-2 code
+    pub country: Option<String>,
+    /// Autonomous system number of the network
+    pub asn: Option<i64>,
+}
+
+/// GeoIP database lookups
+pub trait GeoLookup: Send + Sync {
+    fn lookup(&self, ip: IpAddr) -> GeoInfo;
+}
+
+/// Use `lookup` to locate logins; `None` compares devices only
+pub fn install_geo_lookup(lookup: Option<Arc<dyn GeoLookup>>) {
+    *GEO_LOOKUP.write().unwrap() = lookup;
+}
+
+fn geo_lookup() -> Option<Arc<dyn GeoLookup>> {
+    GEO_LOOKUP.read().unwrap().clone()
+}
+
+/// Where a login came from
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct LoginOrigin {
+    pub country: Option<String>,
+    pub asn: Option<i64>,
+    /// Browser and OS, e.g. "Firefox on Linux"
+    pub device: Option<String>,
+}
+
+impl LoginOrigin {
+    /// Locate a login from the client address and `User-Agent`
+    pub fn locate(ip_address: Option<&str>, user_agent: Option<&str>) -> Self {
+        let geo = ip_address
+            .and_then(|ip| ip.parse::<IpAddr>().ok())
+            .zip(geo_lookup())
+            .map(|(ip, lookup)| lookup.lookup(ip))
+            .unwrap_or_default();
+
+        Self {
+            country: geo.country,
+            asn: geo.asn,
+            device: user_agent.map(device_label),
+        }
+    }
+
+    /// Short description for the alert mail, e.g. "Firefox on Linux in DE"
+    pub fn describe(&self) -> String {
+        let mut out = self.device.clone().unwrap_or_else(|| "An unknown device".to_string());
+        if let Some(country) = &self.country {
+            out.push_str(" in ");
+            out.push_str(country);
+        }
+        if let Some(asn) = self.asn {
+            out.push_str(&format!(" (AS{})", asn));
+        }
+        out
+    }
+}
+
+/// What is new about a login
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Anomaly {
+    NewCountry,
+    NewNetwork,
+    NewDevice,
+}
+
+/// Browser and OS named by a `User-Agent`; versions are left out so updates
+/// don't make a new device
+pub fn device_label(user_agent: &str) -> String {
+    const BROWSERS: &[(&str, &str)] = &[
+        ("Edg/", "Edge"),
+        ("OPR/", "Opera"),
+        ("Firefox/", "Firefox"),
+        ("Chrome/", "Chrome"),
+        ("CriOS/", "Chrome"),
+        ("Safari/", "Safari"),
+    ];
+    const SYSTEMS: &[(&str, &str)] = &[
+        ("Android", "Android"),
+        ("iPhone", "iOS"),
+        ("iPad", "iOS"),
+        ("Windows", "Windows"),
+        ("Mac OS X", "macOS"),
+        ("CrOS", "ChromeOS"),
+        ("Linux", "Linux"),
+    ];
+
+    let find = |table: &[(&str, &'static str)]| {
+        table
+            .iter()
+            .find(|(needle, _)| user_agent.contains(needle))
+            .map(|(_, name)| *name)
+    };
+
+    match (find(BROWSERS), find(SYSTEMS)) {
+        (Some(browser), Some(system)) => format!("{} on {}", browser, system),
+        (Some(browser), None) => browser.to_string(),
+        (None, Some(system)) => format!("Browser on {}", system),
+        // Non-browser clients: the product name without its version
+        (None, None) => user_agent
+            .split(['/', ' '])
+            .next()
+            .filter(|name| !name.is_empty())
+            .unwrap_or("Unknown")
+            .chars()
+            .take(64)
+            .collect(),
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct HistoryRow {
+    country: Option<String>,
+    asn: Option<i64>,
+    device: Option<String>,
+}
+
+/// What is new about a login by `user` from `origin`; empty when nothing is,
+/// or when there are no past logins to compare with
+pub async fn assess(
+    db: &DbPool,
+    user: &User,
+    ip_address: Option<&str>,
+    origin: &LoginOrigin,
+) -> Result<Vec<Anomaly>, sqlx::Error> {
+    if ip_address.is_some() && user.last_login_ip.as_deref() == ip_address {
+        return Ok(Vec::new());
+    }
+
+    let history: Vec<HistoryRow> = sqlx::query_as(
+        r#"
+        SELECT country, asn, device FROM auth_login_history
+        WHERE user_id = $1
+        ORDER BY created_at DESC
+        LIMIT $2
+        "#,
+    )
+    .bind(user.id)
+    .bind(HISTORY)
+    .fetch_all(db)
+    .await?;
+
+    if history.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut anomalies = Vec::new();
+    if origin.country.is_some() && !history.iter().any(|past| past.country == origin.country) {
+        anomalies.push(Anomaly::NewCountry);
+    }
+    if origin.asn.is_some() && !history.iter().any(|past| past.asn == origin.asn) {
+        anomalies.push(Anomaly::NewNetwork);
+    }
+    if origin.device.is_some() && !history.iter().any(|past| past.device == origin.device) {
+        anomalies.push(Anomaly::NewDevice);
+    }
+    Ok(anomalies)
+}
+
+/// Remember a login by `user_id` from `origin`
+pub async fn remember(db: &DbPool, user_id: Uuid, origin: &LoginOrigin) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO auth_login_history (id, user_id, country, asn, device, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        "#,
+    )
+    .bind(Uuid::new_v4())
+    .bind(user_id)
+    .bind(&origin.country)
+    .bind(origin.asn)
+    .bind(&origin.device)
+    .bind(Utc::now())
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+/// Store a login waiting for confirmation, under the hash of its token
+pub async fn request_confirmation(
+    db: &DbPool,
+    user_id: Uuid,
+    token_hash: &str,
+    origin: &LoginOrigin,
+    expires_at: DateTime<Utc>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO auth_login_confirmations (id, user_id, token_hash, country, asn, device, expires_at, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        "#,
+    )
+    .bind(Uuid::new_v4())
+    .bind(user_id)
+    .bind(token_hash)
+    .bind(&origin.country)
+    .bind(origin.asn)
+    .bind(&origin.device)
+    .bind(expires_at)
+    .bind(Utc::now())
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+#[derive(sqlx::FromRow)]
+struct ConfirmationRow {
+    id: Uuid,
+    user_id: Uuid,
+    country: Option<String>,
+    asn: Option<i64>,
+    device: Option<String>,
+}
+
+/// Confirm the pending login with `token_hash` and remember where it came
+/// from; the user it belongs to, `None` when there is no such pending login
+pub async fn confirm(db: &DbPool, token_hash: &str) -> Result<Option<Uuid>, sqlx::Error> {
+    let now = Utc::now();
+    let pending: Option<ConfirmationRow> = sqlx::query_as(
+        r#"
+        SELECT id, user_id, country, asn, device FROM auth_login_confirmations
+        WHERE token_hash = $1 AND expires_at > $2 AND confirmed_at IS NULL
+        "#,
+    )
+    .bind(token_hash)
+    .bind(now)
+    .fetch_optional(db)
+    .await?;
+
+    let Some(pending) = pending else {
+        return Ok(None);
+    };
+
+    sqlx::query("UPDATE auth_login_confirmations SET confirmed_at = $2 WHERE id = $1")
+        .bind(pending.id)
+        .bind(now)
+        .execute(db)
+        .await?;
+
+    let origin = LoginOrigin {
+        country: pending.country,
+        asn: pending.asn,
+        device: pending.device,
+    };
+    remember(db, pending.user_id, &origin).await?;
+    Ok(Some(pending.user_id))
+}
+
+// ============================================
+// Tests
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_device_label() {
+        let firefox = "Mozilla/5.0 (X11; Ubuntu; Linux x86_64; rv:128.0) Gecko/20100101 Firefox/128.0";
+        let chrome = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 \
+                      (KHTML, like Gecko) Chrome/126.0.0.0 Safari/537.36";
+        let safari = "Mozilla/5.0 (iPhone; CPU iPhone OS 17_5 like Mac OS X) AppleWebKit/605.1.15 \
+                      (KHTML, like Gecko) Version/17.5 Mobile/15E148 Safari/604.1";
+
+        assert_eq!(device_label(firefox), "Firefox on Linux");
+        assert_eq!(device_label(chrome), "Chrome on Windows");
+        assert_eq!(device_label(&chrome.replace("126.0.0.0", "127.0.6533.72")), "Chrome on Windows");
+        assert_eq!(device_label(safari), "Safari on iOS");
+        assert_eq!(device_label("curl/8.5.0"), "curl");
+        assert_eq!(device_label(""), "Unknown");
+    }
+
+    #[test]
+    fn test_describe_origin() {
+        let origin = LoginOrigin {
+            country: Some("DE".into()),
+            asn: Some(3320),
+            device: Some("Firefox on Linux".into()),
+        };
+        assert_eq!(origin.describe(), "Firefox on Linux in DE (AS3320)");
+        assert_eq!(LoginOrigin::default().describe(), "An unknown device");
+    }
+}
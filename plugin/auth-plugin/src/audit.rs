@@ -0,0 +1,147 @@
+//! Audit Log
+//!
+//! Changes admins make to other accounts, sessions ended on a user's behalf
+//! and suspicious logins are recorded in `auth_audit_events`: who acted, on which user,
+//! what changed (`details`, a JSON object), from which address (stored
+//! encrypted, see `crypto`) and the request's trace ID, so an entry can be
+//! matched with the request's logs.
+//!
+//! Actions recorded by this plugin:
+//!
+//! - `user.updated`: role or status changed, or the account unlocked;
+//!   `details` holds `{"role": {"from": .., "to": ..}, ...}` for each change
+//! - `user.force_logout`: every session of the user ended;
+//!   `details.revoked_tokens` counts the refresh tokens revoked
+//! - `session.evicted`: a login went over `auth.max_sessions` and ended the
+//!   user's oldest session; `details` holds its `family_id`, `user_agent` and
+//!   `started_at`
+//! - `token.reuse_detected`: a rotated refresh token was presented again, so
+//!   every session of the user was ended; `details` holds the token's
+//!   `family_id`, `token_id` and `replaced_by`, the presenting `user_agent`
+//!   and `revoked_tokens`
+//! - `suspicious_login`: a login from a new country, network or device;
+//!   `details` holds the `anomalies`, the login's `origin` and whether
+//!   `confirmation_required`
+
+use crate::crypto::Encrypted;
+use crate::db::DbPool;
+use crate::problem::current_trace_id;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Role, status or lock changes made by an admin
+pub const USER_UPDATED: &str = "user.updated";
+
+/// Sessions ended by an admin
+pub const USER_FORCE_LOGOUT: &str = "user.force_logout";
+
+/// Oldest session ended by a login over the session limit
+pub const SESSION_EVICTED: &str = "session.evicted";
+
+/// A rotated refresh token presented again, ending all the user's sessions
+pub const TOKEN_REUSED: &str = "token.reuse_detected";
+
+/// Login from a new country, network or device (see `anomaly`)
+pub const SUSPICIOUS_LOGIN: &str = "suspicious_login";
+
+/// A recorded action
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct AuditEvent {
+    pub id: Uuid,
+    /// Admin who acted; unset once their account is deleted
+    pub actor_id: Option<Uuid>,
+    pub action: String,
+    /// Affected user
+    pub target_id: Option<Uuid>,
+    pub details: serde_json::Value,
+    pub ip_address: Option<String>,
+    pub trace_id: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(sqlx::FromRow)]
+struct AuditRow {
+    id: Uuid,
+    actor_id: Option<Uuid>,
+    action: String,
+    target_id: Option<Uuid>,
+    details: String,
+    ip_address: Option<Encrypted>,
+    trace_id: Option<String>,
+    created_at: DateTime<Utc>,
+}
+
+impl From<AuditRow> for AuditEvent {
+    fn from(row: AuditRow) -> Self {
+        Self {
+            id: row.id,
+            actor_id: row.actor_id,
+            action: row.action,
+            target_id: row.target_id,
+            details: serde_json::from_str(&row.details).unwrap_or(serde_json::Value::Null),
+            ip_address: row.ip_address.map(|ip| ip.0),
+            trace_id: row.trace_id,
+            created_at: row.created_at,
+        }
+    }
+}
+
+/// Record `action` by `actor_id` on `target_id`, tagged with the current
+/// trace ID
+pub async fn record(
+    db: &DbPool,
+    actor_id: Uuid,
+    action: &str,
+    target_id: Option<Uuid>,
+    details: serde_json::Value,
+    ip_address: Option<String>,
+) -> Result<Uuid, sqlx::Error> {
+    let id = Uuid::new_v4();
+    sqlx::query(
+        r#"
+        INSERT INTO auth_audit_events (id, actor_id, action, target_id, details, ip_address, trace_id, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        "#,
+    )
+    .bind(id)
+    .bind(actor_id)
+    .bind(action)
+    .bind(target_id)
+    .bind(details.to_string())
+    .bind(ip_address.map(Encrypted))
+    .bind(current_trace_id())
+    .bind(Utc::now())
+    .execute(db)
+    .await?;
+
+    tracing::info!(actor_id = %actor_id, target_id = ?target_id, action, "Audit event recorded");
+    Ok(id)
+}
+
+/// Recorded actions on `target_id`, newest first, optionally only `action`
+pub async fn for_user(
+    db: &DbPool,
+    target_id: Uuid,
+    action: Option<&str>,
+    limit: i64,
+) -> Result<Vec<AuditEvent>, sqlx::Error> {
+    let rows = sqlx::query_as::<_, AuditRow>(
+        r#"
+        SELECT id, actor_id, action, target_id, details, ip_address, trace_id, created_at
+        FROM auth_audit_events
+        WHERE target_id = $1 AND ($2 IS NULL OR action = $2)
+        ORDER BY created_at DESC
+        LIMIT $3
+        "#,
+    )
+    .bind(target_id)
+    .bind(action)
+    .bind(limit.clamp(1, 500))
+    .fetch_all(db)
+    .await?;
+
+    Ok(rows.into_iter().map(AuditEvent::from).collect())
+}
@@ -0,0 +1,100 @@
+//! Deployment Doctor CLI
+//!
+//! ```text
+//! rustpress-doctor [--json] [--plugins-dir <path>] [--config <path>] [--set <key>=<value>]
+//! ```
+//!
+//! Runs the checks of `rustpress_auth::doctor` with the `[doctor]` settings
+//! and prints one line per check, with the fix for each warning and failure.
+//! Exits with 1 when a check failed, so deploy scripts can gate on it.
+
+use rustpress_auth::doctor::{CheckStatus, Doctor, DoctorConfig, DoctorReport};
+use rustpress_auth::Config;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+const USAGE: &str = "usage: rustpress-doctor [--json] [--plugins-dir <path>] [--config <path>] [--set <key>=<value>]";
+
+struct Args {
+    json: bool,
+    plugins_dir: Option<PathBuf>,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut json = false;
+    let mut plugins_dir = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--json" => json = true,
+            "--plugins-dir" => plugins_dir = Some(PathBuf::from(args.next().ok_or("--plugins-dir needs a path")?)),
+            // Read by `rustpress_auth::Config::load`
+            "--config" | "--set" => {
+                args.next();
+            }
+            other if other.starts_with("--config=") || other.starts_with("--set=") => {}
+            other => return Err(format!("Unexpected argument: {}", other)),
+        }
+    }
+
+    Ok(Args { json, plugins_dir })
+}
+
+fn print_report(report: &DoctorReport) {
+    for check in &report.checks {
+        let status = match check.status {
+            CheckStatus::Pass => "ok",
+            CheckStatus::Warn => "WARN",
+            CheckStatus::Fail => "FAIL",
+            CheckStatus::Skip => "skip",
+        };
+        println!("{:<5} {:<32} {}", status, check.name, check.message);
+        if let Some(fix) = &check.fix {
+            println!("{:<5} {:<32} -> {}", "", "", fix);
+        }
+    }
+    println!();
+    println!("{}", if report.healthy { "Healthy" } else { "Unhealthy" });
+}
+
+async fn run(args: Args) -> Result<DoctorReport, String> {
+    let config = Config::load().map_err(|e| e.to_string())?;
+    let mut doctor_config = DoctorConfig::from_config(&config).map_err(|e| e.to_string())?;
+    if let Some(dir) = args.plugins_dir {
+        doctor_config.plugins_dir = dir;
+    }
+    let secrets = rustpress_auth::secrets::shared().await.map_err(|e| e.to_string())?;
+    Ok(Doctor::new(doctor_config, secrets).run().await)
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    let json = args.json;
+    match run(args).await {
+        Ok(report) => {
+            if json {
+                println!("{}", serde_json::to_string_pretty(&report).unwrap_or_default());
+            } else {
+                print_report(&report);
+            }
+            if report.healthy {
+                ExitCode::SUCCESS
+            } else {
+                ExitCode::FAILURE
+            }
+        }
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
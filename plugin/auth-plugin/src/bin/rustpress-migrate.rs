@@ -0,0 +1,152 @@
+//! Plugin Migration CLI
+//!
+//! ```text
+//! rustpress-migrate --plugin <id> [--dir <path>] <status|verify|up|down> [--to <version>] [--dry-run]
+//!                   [--config <path>] [--set <key>=<value>]
+//! ```
+//!
+//! Reads `database.url` through the configured secret provider
+//! (`rustpress.toml` or `DATABASE_URL` by default). `--dir` defaults to `plugins/<id>/migrations`
+//! (its `postgres/` or `sqlite/` subdirectory when present);
+//! `down` without `--to` reverts only the latest applied migration.
+//! `status` also lists the plugin's expand/contract changes with their
+//! backfill progress.
+
+use rustpress_auth::migrations::{MigrationStep, PluginMigrations};
+use rustpress_auth::schema_changes::SchemaChange;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+const USAGE: &str = "usage: rustpress-migrate --plugin <id> [--dir <path>] <status|verify|up|down> [--to <version>] \
+                     [--dry-run] [--config <path>] [--set <key>=<value>]";
+
+struct Args {
+    plugin: String,
+    dir: PathBuf,
+    command: String,
+    to: Option<i64>,
+    dry_run: bool,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut plugin = None;
+    let mut dir = None;
+    let mut command = None;
+    let mut to = None;
+    let mut dry_run = false;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--plugin" => plugin = args.next(),
+            "--dir" => dir = args.next().map(PathBuf::from),
+            "--to" => {
+                let value = args.next().ok_or("--to needs a version")?;
+                to = Some(value.parse().map_err(|_| format!("Invalid version: {}", value))?);
+            }
+            "--dry-run" => dry_run = true,
+            // Read by `rustpress_auth::Config::load`
+            "--config" | "--set" => {
+                args.next();
+            }
+            other if other.starts_with("--config=") || other.starts_with("--set=") => {}
+            "status" | "verify" | "up" | "down" if command.is_none() => command = Some(arg),
+            other => return Err(format!("Unexpected argument: {}", other)),
+        }
+    }
+
+    let plugin = plugin.ok_or("--plugin is required")?;
+    Ok(Args {
+        dir: dir.unwrap_or_else(|| PathBuf::from("plugins").join(&plugin).join("migrations")),
+        plugin,
+        command: command.ok_or("Missing command")?,
+        to,
+        dry_run,
+    })
+}
+
+fn print_steps(steps: &[MigrationStep], dry_run: bool) {
+    if steps.is_empty() {
+        println!("Nothing to do");
+    }
+    for step in steps {
+        let verb = if dry_run { "would run" } else { "ran" };
+        println!("{} {:?} {} {}", verb, step.direction, step.version, step.description);
+    }
+}
+
+async fn run(args: Args) -> Result<(), String> {
+    let secrets = rustpress_auth::secrets::shared().await.map_err(|e| e.to_string())?;
+    let db = rustpress_auth::db::connect_secret(secrets.as_ref())
+        .await
+        .map_err(|e| e.to_string())?;
+    let migrations = PluginMigrations::from_dir(&args.plugin, &args.dir)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    match args.command.as_str() {
+        "status" => {
+            for status in migrations.status(&db).await.map_err(|e| e.to_string())? {
+                let state = match (status.applied_at, status.modified) {
+                    (Some(_), true) => "MODIFIED".to_string(),
+                    (Some(at), false) => format!("applied {}", at.to_rfc3339()),
+                    (None, _) => "pending".to_string(),
+                };
+                println!("{:>6}  {:<40} {}", status.version, status.description, state);
+            }
+            let changes = SchemaChange::list(&db, &args.plugin).await.map_err(|e| e.to_string())?;
+            if !changes.is_empty() {
+                println!();
+            }
+            for change in changes {
+                println!(
+                    "{:>6}  {:<40} {} ({:.0}%, {}/{} rows)",
+                    change.expanded_version,
+                    change.change,
+                    change.phase,
+                    change.progress() * 100.0,
+                    change.rows_done,
+                    change.rows_total
+                );
+            }
+        }
+        "verify" => {
+            migrations.verify(&db).await.map_err(|e| e.to_string())?;
+            println!("All applied migrations match their scripts");
+        }
+        "up" => {
+            let steps = migrations.up(&db, args.to, args.dry_run).await.map_err(|e| e.to_string())?;
+            print_steps(&steps, args.dry_run);
+        }
+        "down" => {
+            let steps = match args.to {
+                Some(to) => migrations.down(&db, to, args.dry_run).await,
+                None => migrations.rollback(&db, args.dry_run).await,
+            }
+            .map_err(|e| e.to_string())?;
+            print_steps(&steps, args.dry_run);
+        }
+        _ => unreachable!(),
+    }
+
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    match run(args).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
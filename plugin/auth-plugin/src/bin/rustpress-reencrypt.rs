@@ -0,0 +1,113 @@
+//! Field Re-encryption CLI
+//!
+//! ```text
+//! rustpress-reencrypt [--column <table>.<column>] [--id <column>] [--dry-run]
+//! ```
+//!
+//! Rewrites encrypted columns (see `rustpress_auth::crypto`) with the newest
+//! key in `encryption.keys`, encrypting plaintext left from before encryption
+//! was enabled. Without `--column` the auth plugin's columns are processed;
+//! plugins name their own with `--column`, keyed by a UUID `id` column unless
+//! `--id` says otherwise. Run it after adding a key version and before
+//! removing the old one.
+
+use rustpress_auth::crypto::{EncryptedColumn, FieldCipher, ENCRYPTED_COLUMNS};
+use std::process::ExitCode;
+
+const USAGE: &str = "usage: rustpress-reencrypt [--column <table>.<column>] [--id <column>] [--dry-run] \
+                     [--config <path>] [--set <key>=<value>]";
+
+struct Args {
+    /// `(table, id, column)`
+    columns: Vec<(String, String, String)>,
+    dry_run: bool,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut named = Vec::new();
+    let mut id = "id".to_string();
+    let mut dry_run = false;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--column" => named.push(args.next().ok_or("--column needs <table>.<column>")?),
+            "--id" => id = args.next().ok_or("--id needs a column")?,
+            "--dry-run" => dry_run = true,
+            // Read by `rustpress_auth::Config::load`
+            "--config" | "--set" => {
+                args.next();
+            }
+            other if other.starts_with("--config=") || other.starts_with("--set=") => {}
+            other => return Err(format!("Unexpected argument: {}", other)),
+        }
+    }
+
+    if named.is_empty() {
+        let columns = ENCRYPTED_COLUMNS
+            .iter()
+            .map(|c| (c.table.to_string(), c.id.to_string(), c.column.to_string()))
+            .collect();
+        return Ok(Args { columns, dry_run });
+    }
+
+    let identifier = |name: &str| !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    let mut columns = Vec::new();
+    for name in named {
+        let (table, column) = name
+            .split_once('.')
+            .filter(|(table, column)| identifier(table) && identifier(column) && identifier(&id))
+            .ok_or_else(|| format!("Invalid column: {}", name))?;
+        columns.push((table.to_string(), id.clone(), column.to_string()));
+    }
+    Ok(Args { columns, dry_run })
+}
+
+async fn run(args: Args) -> Result<(), String> {
+    let secrets = rustpress_auth::secrets::shared().await.map_err(|e| e.to_string())?;
+    let cipher = FieldCipher::load(secrets.as_ref())
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("encryption.keys is not set")?;
+    let db = rustpress_auth::db::connect_secret(secrets.as_ref())
+        .await
+        .map_err(|e| e.to_string())?;
+
+    for (table, id, column) in &args.columns {
+        let column = EncryptedColumn { table, id, column };
+        let rewritten = cipher
+            .reencrypt(&db, &column, args.dry_run)
+            .await
+            .map_err(|e| e.to_string())?;
+        let verb = if args.dry_run { "would rewrite" } else { "rewrote" };
+        println!(
+            "{}.{}: {} {} value(s) to key version {}",
+            column.table,
+            column.column,
+            verb,
+            rewritten,
+            cipher.current_version()
+        );
+    }
+
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    match run(args).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
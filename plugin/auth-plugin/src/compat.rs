@@ -0,0 +1,200 @@
+//! Version Compatibility Shims
+//!
+//! Handlers are written once against the latest API shape. Types that changed
+//! between versions implement `Compat` to rewrite request bodies from older
+//! clients (`upgrade`) and responses sent back to them (`downgrade`);
+//! `VersionedJson` applies both based on the requested `ApiVersion`.
+
+use crate::problem::ProblemDetails;
+use crate::versioning::ApiVersion;
+
+use axum::{
+    async_trait,
+    extract::{FromRequest, Request},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+/// Conversions between the latest JSON shape and older versions
+///
+/// Both methods default to passing the value through unchanged.
+pub trait Compat {
+    /// Rewrite a request body sent by a `version` client into the latest shape
+    fn upgrade(value: Value, _version: ApiVersion) -> Value {
+        value
+    }
+
+    /// Rewrite the latest response shape into what `version` clients expect
+    fn downgrade(value: Value, _version: ApiVersion) -> Value {
+        value
+    }
+}
+
+/// JSON body/response converted through `Compat` for the requested version
+///
+/// As an extractor it reads the version set by `VersionedRouter`; as a
+/// response it downgrades the value for the version it carries.
+#[derive(Debug, Clone)]
+pub struct VersionedJson<T> {
+    pub version: ApiVersion,
+    pub value: T,
+}
+
+impl<T> VersionedJson<T> {
+    pub fn new(version: ApiVersion, value: T) -> Self {
+        Self { version, value }
+    }
+}
+
+#[async_trait]
+impl<T, S> FromRequest<S> for VersionedJson<T>
+where
+    T: DeserializeOwned + Compat,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let version = req
+            .extensions()
+            .get::<ApiVersion>()
+            .copied()
+            .unwrap_or(ApiVersion::V1);
+
+        let Json(raw) = Json::<Value>::from_request(req, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+
+        let value = serde_json::from_value(T::upgrade(raw, version)).map_err(|e| {
+            ProblemDetails::new(StatusCode::UNPROCESSABLE_ENTITY, "invalid_body")
+                .detail(e.to_string())
+                .into_response()
+        })?;
+
+        Ok(Self { version, value })
+    }
+}
+
+impl<T> IntoResponse for VersionedJson<T>
+where
+    T: Serialize + Compat,
+{
+    fn into_response(self) -> Response {
+        match serde_json::to_value(&self.value) {
+            Ok(value) => Json(T::downgrade(value, self.version)).into_response(),
+            Err(e) => {
+                tracing::error!("Failed to serialize response: {}", e);
+                ProblemDetails::internal("Failed to serialize response").into_response()
+            }
+        }
+    }
+}
+
+/// Rename object keys `(from, to)`, recursing into arrays
+pub fn rename_fields(value: Value, renames: &[(&str, &str)]) -> Value {
+    match value {
+        Value::Object(mut map) => {
+            for (from, to) in renames {
+                if let Some(field) = map.remove(*from) {
+                    map.insert((*to).to_string(), field);
+                }
+            }
+            Value::Object(map)
+        }
+        Value::Array(items) => Value::Array(items.into_iter().map(|v| rename_fields(v, renames)).collect()),
+        other => other,
+    }
+}
+
+/// Drop object keys, recursing into arrays
+pub fn remove_fields(value: Value, fields: &[&str]) -> Value {
+    match value {
+        Value::Object(mut map) => {
+            for field in fields {
+                map.remove(*field);
+            }
+            Value::Object(map)
+        }
+        Value::Array(items) => Value::Array(items.into_iter().map(|v| remove_fields(v, fields)).collect()),
+        other => other,
+    }
+}
+
+// ============================================
+// Tests
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::versioning::VersionedRouter;
+    use axum::{body::Body, routing::post, Router};
+    use serde::Deserialize;
+    use tower::ServiceExt;
+
+    /// v2 renamed `name` to `display_name`
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Profile {
+        display_name: String,
+    }
+
+    impl Compat for Profile {
+        fn upgrade(value: Value, version: ApiVersion) -> Value {
+            match version {
+                ApiVersion::V1 => rename_fields(value, &[("name", "display_name")]),
+                _ => value,
+            }
+        }
+
+        fn downgrade(value: Value, version: ApiVersion) -> Value {
+            match version {
+                ApiVersion::V1 => rename_fields(value, &[("display_name", "name")]),
+                _ => value,
+            }
+        }
+    }
+
+    async fn echo(VersionedJson { version, value }: VersionedJson<Profile>) -> VersionedJson<Profile> {
+        VersionedJson::new(version, value)
+    }
+
+    async fn post_json(router: Router, uri: &str, body: &str) -> Value {
+        let response = router
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri(uri)
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_one_handler_serves_both_versions() {
+        let router = VersionedRouter::new().route("/profile", post(echo)).into_router();
+
+        let v1 = post_json(router.clone(), "/api/v1/profile", r#"{"name":"Ada"}"#).await;
+        assert_eq!(v1, serde_json::json!({ "name": "Ada" }));
+
+        let v2 = post_json(router, "/api/v2/profile", r#"{"display_name":"Ada"}"#).await;
+        assert_eq!(v2, serde_json::json!({ "display_name": "Ada" }));
+    }
+
+    #[test]
+    fn test_field_helpers_recurse_into_arrays() {
+        let value = serde_json::json!([{ "a": 1, "b": 2 }, { "a": 3, "b": 4 }]);
+        assert_eq!(
+            rename_fields(value.clone(), &[("a", "x")]),
+            serde_json::json!([{ "x": 1, "b": 2 }, { "x": 3, "b": 4 }])
+        );
+        assert_eq!(remove_fields(value, &["b"]), serde_json::json!([{ "a": 1 }, { "a": 3 }]));
+    }
+}
@@ -0,0 +1,468 @@
+//! Layered Configuration
+//!
+//! One configuration for every app and plugin in a process, read from three
+//! layers; a value in a later layer wins:
+//!
+//! 1. `rustpress.toml` - the file named by `--config <path>` or
+//!    `RUSTPRESS_CONFIG`, otherwise `./rustpress.toml` if it exists
+//! 2. Environment variables - a key's variable is the key upper-cased with
+//!    dots replaced by underscores (`jwt.secret` → `JWT_SECRET`). Values
+//!    that parse as TOML numbers, booleans or arrays are typed accordingly
+//!    unless the key expects a string; anything else is a string.
+//! 3. Command-line overrides - `--set jwt.issuer=example`
+//!
+//! Secrets can live in files, as container platforms mount them: `key_file`
+//! in the file, or `KEY_FILE` in the environment, names a file whose
+//! contents (without the trailing newline) are the value of `key`.
+//!
+//! ```toml
+//! [jwt]
+//! secret_file = "/run/secrets/jwt_secret"
+//! issuer = "rustpress"
+//!
+//! [database]
+//! url = "postgres://localhost/rustpress"
+//! replica_urls = ["postgres://replica-1/rustpress"]
+//! ```
+//!
+//! Environment variables also override keys inside a table read as a whole
+//! (`config.get::<ImageConfig>("app.images")`), but only keys the file sets.
+
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+use toml::{Table, Value};
+
+/// Config file used when neither `--config` nor `RUSTPRESS_CONFIG` names one
+pub const DEFAULT_PATH: &str = "rustpress.toml";
+
+/// Environment variable naming the config file
+pub const PATH_ENV: &str = "RUSTPRESS_CONFIG";
+
+/// Variable names that predate the config file, still honoured after the
+/// derived name (`auth.min_password_length` → `AUTH_MIN_PASSWORD_LENGTH`)
+const ENV_ALIASES: &[(&str, &str)] = &[
+    ("auth.max_login_attempts", "MAX_LOGIN_ATTEMPTS"),
+    ("auth.lockout_duration", "LOCKOUT_DURATION"),
+    ("auth.password_reset_expiration", "PASSWORD_RESET_EXPIRATION"),
+    ("auth.email_verification_expiration", "EMAIL_VERIFICATION_EXPIRATION"),
+    ("auth.min_password_length", "MIN_PASSWORD_LENGTH"),
+    ("auth.require_email_verification", "REQUIRE_EMAIL_VERIFICATION"),
+    ("cors.max_age_secs", "CORS_MAX_AGE"),
+    ("secrets.vault.addr", "VAULT_ADDR"),
+    ("secrets.vault.token", "VAULT_TOKEN"),
+];
+
+static SHARED: OnceLock<Arc<Config>> = OnceLock::new();
+
+/// Configuration loading errors
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("Cannot read {path}: {message}")]
+    Io { path: PathBuf, message: String },
+
+    #[error("Invalid config file {path}: {message}")]
+    Parse { path: PathBuf, message: String },
+
+    #[error("Missing configuration value: {0} (set it in rustpress.toml or {1})")]
+    Missing(String, String),
+
+    #[error("Invalid configuration value {key}: {message}")]
+    Invalid { key: String, message: String },
+}
+
+/// Merged configuration layers
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    path: Option<PathBuf>,
+    /// File contents with command-line overrides merged in
+    file: Table,
+    overrides: Table,
+    /// `--set` values as given, for string keys whose value looks typed
+    raw_overrides: HashMap<String, String>,
+    env: HashMap<String, String>,
+}
+
+impl Config {
+    /// The process-wide configuration, loaded from the file, the environment
+    /// and the command line on first use
+    ///
+    /// Apps and plugins share the instance; failures aren't cached, so a
+    /// broken file is reported to every caller.
+    pub fn load() -> Result<Arc<Config>, ConfigError> {
+        if let Some(config) = SHARED.get() {
+            return Ok(config.clone());
+        }
+
+        let config = Arc::new(Self::from_sources(std::env::vars(), std::env::args().skip(1))?);
+        Ok(SHARED.get_or_init(|| config).clone())
+    }
+
+    /// Configuration from explicit environment variables and arguments
+    ///
+    /// Arguments other than `--config <path>` and `--set <key>=<value>` are
+    /// ignored, so binaries can mix them with their own.
+    pub fn from_sources(
+        env: impl IntoIterator<Item = (String, String)>,
+        args: impl IntoIterator<Item = String>,
+    ) -> Result<Self, ConfigError> {
+        let env: HashMap<String, String> = env.into_iter().collect();
+
+        let mut path = None;
+        let mut overrides = Table::new();
+        let mut raw_overrides = HashMap::new();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let (flag, inline) = match arg.split_once('=') {
+                Some((flag, value)) if flag.starts_with("--") => (flag.to_string(), Some(value.to_string())),
+                _ => (arg.clone(), None),
+            };
+            match flag.as_str() {
+                "--config" => path = inline.or_else(|| args.next()).map(PathBuf::from),
+                "--set" => {
+                    let assignment = inline.or_else(|| args.next()).unwrap_or_default();
+                    let (key, value) = assignment.split_once('=').ok_or_else(|| ConfigError::Invalid {
+                        key: assignment.clone(),
+                        message: "--set takes <key>=<value>".to_string(),
+                    })?;
+                    insert(&mut overrides, key.trim(), parse_value(value));
+                    raw_overrides.insert(key.trim().to_string(), value.to_string());
+                }
+                _ => {}
+            }
+        }
+
+        let explicit = path.is_some() || env.contains_key(PATH_ENV);
+        let path = path
+            .or_else(|| env.get(PATH_ENV).map(PathBuf::from))
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_PATH));
+
+        let (path, mut file) = if explicit || path.exists() {
+            let table = read_file(&path)?;
+            (Some(path), table)
+        } else {
+            (None, Table::new())
+        };
+        merge(&mut file, &overrides);
+
+        Ok(Self {
+            path,
+            file,
+            overrides,
+            raw_overrides,
+            env,
+        })
+    }
+
+    /// Configuration from a TOML document alone (tests, embedded defaults)
+    pub fn from_toml(toml: &str) -> Result<Self, ConfigError> {
+        let file = toml.parse::<Table>().map_err(|e| ConfigError::Parse {
+            path: PathBuf::from("<inline>"),
+            message: e.to_string(),
+        })?;
+        Ok(Self {
+            file,
+            ..Self::default()
+        })
+    }
+
+    /// The config file in use, if any
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// A value, or `None` when no layer sets it
+    ///
+    /// Tables deserialize into structs (with `#[serde(default)]` for partial
+    /// tables).
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>, ConfigError> {
+        let Some(value) = self.resolve(key)? else {
+            return Ok(None);
+        };
+
+        value.try_into().map(Some).or_else(|e: toml::de::Error| {
+            // `JWT_SECRET=12345` is a string to a string key
+            match self.raw_override(key) {
+                Some(raw) => Value::String(raw).try_into().map(Some).map_err(|_| e),
+                None => Err(e),
+            }
+            .map_err(|e| ConfigError::Invalid {
+                key: key.to_string(),
+                message: e.to_string(),
+            })
+        })
+    }
+
+    /// A value, or `default` when no layer sets it
+    pub fn get_or<T: DeserializeOwned>(&self, key: &str, default: T) -> Result<T, ConfigError> {
+        Ok(self.get(key)?.unwrap_or(default))
+    }
+
+    /// A value every deployment must set
+    pub fn require<T: DeserializeOwned>(&self, key: &str) -> Result<T, ConfigError> {
+        self.get(key)?
+            .ok_or_else(|| ConfigError::Missing(key.to_string(), env_name(key)))
+    }
+
+    /// A list: a TOML array, or a comma-separated string (as environment
+    /// variables usually carry them)
+    pub fn list(&self, key: &str) -> Result<Option<Vec<String>>, ConfigError> {
+        match self.resolve(key)? {
+            Some(Value::String(s)) => Ok(Some(
+                s.split(',').map(str::trim).filter(|s| !s.is_empty()).map(String::from).collect(),
+            )),
+            Some(value) => value.try_into().map(Some).map_err(|e: toml::de::Error| ConfigError::Invalid {
+                key: key.to_string(),
+                message: e.to_string(),
+            }),
+            None => Ok(None),
+        }
+    }
+
+    fn resolve(&self, key: &str) -> Result<Option<Value>, ConfigError> {
+        if let Some(value) = self.override_for(key)? {
+            return Ok(Some(value));
+        }
+
+        match lookup(&self.file, key) {
+            Some(Value::Table(table)) => {
+                let mut table = table.clone();
+                self.overlay(key, &mut table)?;
+                Ok(Some(Value::Table(table)))
+            }
+            Some(value) => Ok(Some(value.clone())),
+            None => match lookup(&self.file, &format!("{}_file", key)) {
+                Some(Value::String(path)) => read_secret(Path::new(path)).map(Some),
+                Some(_) => Err(ConfigError::Invalid {
+                    key: format!("{}_file", key),
+                    message: "expected a file path".to_string(),
+                }),
+                None => Ok(None),
+            },
+        }
+    }
+
+    /// Command-line and environment values for a key, highest first
+    fn override_for(&self, key: &str) -> Result<Option<Value>, ConfigError> {
+        if let Some(value) = lookup(&self.overrides, key) {
+            return Ok(Some(value.clone()));
+        }
+
+        let aliases = ENV_ALIASES.iter().filter(|(k, _)| *k == key).map(|(_, env)| env.to_string());
+        for name in std::iter::once(env_name(key)).chain(aliases) {
+            if let Some(value) = self.env.get(&name) {
+                return Ok(Some(parse_value(value)));
+            }
+            if let Some(path) = self.env.get(&format!("{}_FILE", name)) {
+                return read_secret(Path::new(path)).map(Some);
+            }
+        }
+        Ok(None)
+    }
+
+    /// The command-line or environment value for a key, unparsed
+    fn raw_override(&self, key: &str) -> Option<String> {
+        if let Some(raw) = self.raw_overrides.get(key) {
+            return Some(raw.clone());
+        }
+        let aliases = ENV_ALIASES.iter().filter(|(k, _)| *k == key).map(|(_, env)| env.to_string());
+        std::iter::once(env_name(key))
+            .chain(aliases)
+            .find_map(|name| self.env.get(&name).cloned())
+    }
+
+    /// Apply overrides and secret files to the keys of a table read whole
+    fn overlay(&self, prefix: &str, table: &mut Table) -> Result<(), ConfigError> {
+        let names: Vec<String> = table.keys().cloned().collect();
+        for name in names {
+            let key = format!("{}.{}", prefix, name);
+            if let Some(Value::Table(inner)) = table.get_mut(&name) {
+                self.overlay(&key, inner)?;
+                continue;
+            }
+
+            if let Some(field) = name.strip_suffix("_file") {
+                let field_key = format!("{}.{}", prefix, field);
+                if !table.contains_key(field) && self.override_for(&field_key)?.is_none() {
+                    if let Some(Value::String(path)) = table.remove(&name) {
+                        table.insert(field.to_string(), read_secret(Path::new(&path))?);
+                    }
+                }
+                continue;
+            }
+
+            if let Some(value) = self.override_for(&key)? {
+                table.insert(name, value);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Environment variable for a key: `database.replica_urls` → `DATABASE_REPLICA_URLS`
+pub fn env_name(key: &str) -> String {
+    key.replace(['.', '-'], "_").to_uppercase()
+}
+
+fn read_file(path: &Path) -> Result<Table, ConfigError> {
+    let contents = std::fs::read_to_string(path).map_err(|e| ConfigError::Io {
+        path: path.to_path_buf(),
+        message: e.to_string(),
+    })?;
+    contents.parse::<Table>().map_err(|e| ConfigError::Parse {
+        path: path.to_path_buf(),
+        message: e.to_string(),
+    })
+}
+
+fn read_secret(path: &Path) -> Result<Value, ConfigError> {
+    let contents = std::fs::read_to_string(path).map_err(|e| ConfigError::Io {
+        path: path.to_path_buf(),
+        message: e.to_string(),
+    })?;
+    Ok(Value::String(contents.trim_end_matches(['\r', '\n']).to_string()))
+}
+
+/// Numbers, booleans and arrays keep their type; anything else is a string
+fn parse_value(raw: &str) -> Value {
+    match format!("value = {}", raw).parse::<Table>() {
+        Ok(mut table) => match table.remove("value") {
+            Some(value @ (Value::Integer(_) | Value::Float(_) | Value::Boolean(_) | Value::Array(_))) => value,
+            _ => Value::String(raw.to_string()),
+        },
+        Err(_) => Value::String(raw.to_string()),
+    }
+}
+
+fn lookup<'a>(table: &'a Table, key: &str) -> Option<&'a Value> {
+    let mut parts = key.split('.');
+    let mut value = table.get(parts.next()?)?;
+    for part in parts {
+        value = value.as_table()?.get(part)?;
+    }
+    Some(value)
+}
+
+fn insert(table: &mut Table, key: &str, value: Value) {
+    match key.split_once('.') {
+        Some((head, rest)) => {
+            let entry = table.entry(head.to_string()).or_insert_with(|| Value::Table(Table::new()));
+            if !entry.is_table() {
+                *entry = Value::Table(Table::new());
+            }
+            if let Value::Table(inner) = entry {
+                insert(inner, rest, value);
+            }
+        }
+        None => {
+            table.insert(key.to_string(), value);
+        }
+    }
+}
+
+/// Deep-merge `overrides` into `base`
+fn merge(base: &mut Table, overrides: &Table) {
+    for (key, value) in overrides {
+        match (base.get_mut(key), value) {
+            (Some(Value::Table(base)), Value::Table(overrides)) => merge(base, overrides),
+            _ => {
+                base.insert(key.clone(), value.clone());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(toml: &str, env: &[(&str, &str)], args: &[&str]) -> Config {
+        let mut config = Config::from_sources(
+            env.iter().map(|(k, v)| (k.to_string(), v.to_string())),
+            args.iter().map(|a| a.to_string()),
+        )
+        .unwrap();
+        let mut file = toml.parse::<Table>().unwrap();
+        merge(&mut file, &config.overrides);
+        config.file = file;
+        config
+    }
+
+    #[test]
+    fn test_layers_in_order() {
+        let toml = "[jwt]\nissuer = \"file\"\naudience = \"file\"\nsecret = \"file\"";
+        let config = config(
+            toml,
+            &[("JWT_ISSUER", "env"), ("JWT_AUDIENCE", "env")],
+            &["--set", "jwt.issuer=cli"],
+        );
+
+        assert_eq!(config.get::<String>("jwt.issuer").unwrap().as_deref(), Some("cli"));
+        assert_eq!(config.get::<String>("jwt.audience").unwrap().as_deref(), Some("env"));
+        assert_eq!(config.get::<String>("jwt.secret").unwrap().as_deref(), Some("file"));
+        assert_eq!(config.get::<String>("jwt.missing").unwrap(), None);
+    }
+
+    #[test]
+    fn test_env_values_are_typed() {
+        let config = config(
+            "",
+            &[("AUTH_MIN_PASSWORD_LENGTH", "12"), ("REQUIRE_EMAIL_VERIFICATION", "true"), ("JWT_SECRET", "12345")],
+            &[],
+        );
+
+        assert_eq!(config.get::<usize>("auth.min_password_length").unwrap(), Some(12));
+        // Pre-config-file variable name
+        assert_eq!(config.get::<bool>("auth.require_email_verification").unwrap(), Some(true));
+        assert!(config.get::<usize>("auth.require_email_verification").is_err());
+        assert_eq!(config.get::<String>("jwt.secret").unwrap().as_deref(), Some("12345"));
+    }
+
+    #[test]
+    fn test_secret_files() {
+        let path = std::env::temp_dir().join(format!("rustpress-config-test-{}", std::process::id()));
+        std::fs::write(&path, "from-file\n").unwrap();
+        let path = path.to_string_lossy().to_string();
+
+        let from_toml = config(&format!("[jwt]\nsecret_file = {:?}", path), &[], &[]);
+        assert_eq!(from_toml.require::<String>("jwt.secret").unwrap(), "from-file");
+
+        let from_env = config("", &[("JWT_SECRET_FILE", path.as_str())], &[]);
+        assert_eq!(from_env.require::<String>("jwt.secret").unwrap(), "from-file");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_tables_and_lists() {
+        #[derive(serde::Deserialize)]
+        struct Database {
+            url: String,
+            max_connections: u32,
+        }
+
+        let toml = "[database]\nurl = \"postgres://file\"\nmax_connections = 5\nreplica_urls = [\"a\", \"b\"]";
+        let config = config(toml, &[("DATABASE_MAX_CONNECTIONS", "20"), ("CORS_ALLOWED_ORIGINS", "x, y")], &[]);
+
+        let database: Database = config.require("database").unwrap();
+        assert_eq!(database.url, "postgres://file");
+        assert_eq!(database.max_connections, 20);
+
+        assert_eq!(config.list("database.replica_urls").unwrap(), Some(vec!["a".into(), "b".into()]));
+        assert_eq!(config.list("cors.allowed_origins").unwrap(), Some(vec!["x".into(), "y".into()]));
+    }
+
+    #[test]
+    fn test_missing_required_value_names_variable() {
+        let err = config("", &[], &[]).require::<String>("jwt.secret").unwrap_err();
+        assert!(err.to_string().contains("JWT_SECRET"));
+    }
+
+    #[test]
+    fn test_unrelated_arguments_ignored() {
+        let config = config("", &[], &["--plugin", "x", "--set=jwt.issuer=cli", "up"]);
+        assert_eq!(config.get::<String>("jwt.issuer").unwrap().as_deref(), Some("cli"));
+        assert!(Config::from_sources(Vec::new(), vec!["--set".to_string(), "novalue".to_string()]).is_err());
+    }
+}
@@ -0,0 +1,283 @@
+//! Authentication Configuration
+//!
+//! Values come from the layered [`Config`] (`rustpress.toml`, environment
+//! variables, `--set` overrides). No hardcoded secrets or sensitive data.
+
+mod loader;
+
+pub use loader::{env_name, Config, ConfigError, DEFAULT_PATH, PATH_ENV};
+
+use crate::cors::CorsPolicy;
+use crate::error::AuthError;
+use crate::mail::MailConfig;
+use crate::registration::RegistrationPolicy;
+use crate::secrets;
+
+/// JWT issuer when `jwt.issuer` is unset
+pub const DEFAULT_ISSUER: &str = "rustpress";
+
+/// JWT audience when `jwt.audience` is unset
+pub const DEFAULT_AUDIENCE: &str = "rustpress-api";
+
+/// The configuration keys operators tune, as a `[settings.schema]` manifest
+/// table, so admin UIs can render them (read-only: they're set in
+/// `rustpress.toml`)
+pub const SETTINGS_SCHEMA: &str = include_str!("settings.toml");
+
+/// Authentication configuration
+#[derive(Debug, Clone)]
+pub struct AuthConfig {
+    /// JWT secret key for signing tokens (`jwt.secret`, read through the
+    /// secret provider)
+    pub jwt_secret: String,
+
+    /// JWT access token expiration in seconds (`jwt.access_expiration`)
+    pub access_token_expiration: i64,
+
+    /// JWT refresh token expiration in seconds (`jwt.refresh_expiration`)
+    pub refresh_token_expiration: i64,
+
+    /// JWT issuer (`jwt.issuer`)
+    pub jwt_issuer: String,
+
+    /// JWT audience (`jwt.audience`)
+    pub jwt_audience: String,
+
+    /// Argon2 memory cost in KiB (`argon2.memory_cost`)
+    pub argon2_memory_cost: u32,
+
+    /// Argon2 time cost (iterations) (`argon2.time_cost`)
+    pub argon2_time_cost: u32,
+
+    /// Argon2 parallelism (`argon2.parallelism`)
+    pub argon2_parallelism: u32,
+
+    /// Maximum failed login attempts before lockout (`auth.max_login_attempts`)
+    pub max_login_attempts: i32,
+
+    /// Account lockout duration in seconds (`auth.lockout_duration`)
+    pub lockout_duration: i64,
+
+    /// Password reset token expiration in seconds (`auth.password_reset_expiration`)
+    pub password_reset_expiration: i64,
+
+    /// Email verification token expiration in seconds (`auth.email_verification_expiration`)
+    pub email_verification_expiration: i64,
+
+    /// Minimum password length (`auth.min_password_length`)
+    pub min_password_length: usize,
+
+    /// Require email verification before login (`auth.require_email_verification`)
+    pub require_email_verification: bool,
+
+    /// Days until a password must be changed (`auth.password_max_age_days`),
+    /// 0 for never
+    pub password_max_age_days: u32,
+
+    /// Recent passwords that can't be chosen again, the current one included
+    /// (`auth.password_history`), 0 to allow any
+    pub password_history: u32,
+
+    /// Mail users about logins from a new country, network or device
+    /// (`auth.login_alerts`)
+    pub login_alerts: bool,
+
+    /// Refuse such logins until confirmed by email
+    /// (`auth.confirm_suspicious_logins`); see `crate::anomaly`
+    pub confirm_suspicious_logins: bool,
+
+    /// Active sessions (logins with a valid refresh token) per user; a login
+    /// over the limit ends the oldest (`auth.max_sessions`), 0 for no limit
+    pub max_sessions: u32,
+
+    /// Requests per minute each admin may make to the user management API
+    /// (`auth.admin_rate_limit`), 0 for no limit
+    pub admin_rate_limit: u32,
+
+    /// Email domains that may register (`[registration]`, see
+    /// `crate::registration`)
+    pub registration: RegistrationPolicy,
+
+    /// Cross-origin access to the auth endpoints (`[cors]`, see
+    /// `CorsPolicy::from_config`); same-origin only by default
+    pub cors: CorsPolicy,
+
+    /// Verification and reset mail delivery (`[mail]`, see `crate::mail`)
+    pub mail: MailConfig,
+}
+
+impl AuthConfig {
+    /// Load configuration from the shared [`Config`], and the JWT secret from
+    /// the configured [`SecretProvider`](crate::secrets::SecretProvider)
+    pub async fn load() -> Result<Self, AuthError> {
+        let jwt_secret = secrets::shared().await?.require("jwt.secret").await?;
+        Self::from_config(&*Config::load()?, jwt_secret)
+    }
+
+    /// Read configuration values, applying defaults for optional ones
+    pub fn from_config(config: &Config, jwt_secret: String) -> Result<Self, AuthError> {
+        Ok(Self {
+            jwt_secret,
+            access_token_expiration: config.get_or("jwt.access_expiration", 900)?, // 15 minutes
+            refresh_token_expiration: config.get_or("jwt.refresh_expiration", 604800)?, // 7 days
+            jwt_issuer: config.get_or("jwt.issuer", DEFAULT_ISSUER.to_string())?,
+            jwt_audience: config.get_or("jwt.audience", DEFAULT_AUDIENCE.to_string())?,
+            argon2_memory_cost: config.get_or("argon2.memory_cost", 65536)?, // 64 MiB
+            argon2_time_cost: config.get_or("argon2.time_cost", 3)?,
+            argon2_parallelism: config.get_or("argon2.parallelism", 4)?,
+            max_login_attempts: config.get_or("auth.max_login_attempts", 5)?,
+            lockout_duration: config.get_or("auth.lockout_duration", 900)?, // 15 minutes
+            password_reset_expiration: config.get_or("auth.password_reset_expiration", 3600)?, // 1 hour
+            email_verification_expiration: config.get_or("auth.email_verification_expiration", 86400)?, // 24 hours
+            min_password_length: config.get_or("auth.min_password_length", 8)?,
+            require_email_verification: config.get_or("auth.require_email_verification", false)?,
+            password_max_age_days: config.get_or("auth.password_max_age_days", 0)?,
+            password_history: config.get_or("auth.password_history", 0)?,
+            login_alerts: config.get_or("auth.login_alerts", true)?,
+            confirm_suspicious_logins: config.get_or("auth.confirm_suspicious_logins", false)?,
+            max_sessions: config.get_or("auth.max_sessions", 0)?,
+            admin_rate_limit: config.get_or("auth.admin_rate_limit", 60)?,
+            registration: RegistrationPolicy::from_config(config)?,
+            cors: CorsPolicy::from_config(config, "cors", CorsPolicy::same_origin())?,
+            mail: MailConfig::from_config(config)?,
+        })
+    }
+
+    /// Validate the configuration
+    pub fn validate(&self) -> Result<(), AuthError> {
+        if self.jwt_secret.len() < 32 {
+            return Err(AuthError::Config(
+                "jwt.secret must be at least 32 characters".to_string(),
+            ));
+        }
+
+        if self.access_token_expiration <= 0 {
+            return Err(AuthError::Config(
+                "jwt.access_expiration must be positive".to_string(),
+            ));
+        }
+
+        if self.refresh_token_expiration <= self.access_token_expiration {
+            return Err(AuthError::Config(
+                "jwt.refresh_expiration must be greater than jwt.access_expiration".to_string(),
+            ));
+        }
+
+        self.cors.validate()?;
+        self.mail.validate()?;
+
+        if self.min_password_length < 8 {
+            return Err(AuthError::Config(
+                "auth.min_password_length must be at least 8".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_validation() {
+        let config = AuthConfig {
+            jwt_secret: "a".repeat(32),
+            access_token_expiration: 900,
+            refresh_token_expiration: 604800,
+            jwt_issuer: "test".to_string(),
+            jwt_audience: "test".to_string(),
+            argon2_memory_cost: 65536,
+            argon2_time_cost: 3,
+            argon2_parallelism: 4,
+            max_login_attempts: 5,
+            lockout_duration: 900,
+            password_reset_expiration: 3600,
+            email_verification_expiration: 86400,
+            min_password_length: 8,
+            require_email_verification: false,
+            password_max_age_days: 0,
+            password_history: 0,
+            login_alerts: true,
+            confirm_suspicious_logins: false,
+            max_sessions: 0,
+            admin_rate_limit: 60,
+            registration: RegistrationPolicy::default(),
+            cors: CorsPolicy::default(),
+            mail: MailConfig::default(),
+        };
+
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_from_config() {
+        let config = Config::from_toml("[auth]\nmin_password_length = 12").unwrap();
+
+        let auth = AuthConfig::from_config(&config, "a".repeat(32)).unwrap();
+        assert_eq!(auth.min_password_length, 12);
+        assert_eq!(auth.jwt_issuer, DEFAULT_ISSUER);
+        assert!(auth.validate().is_ok());
+
+        let config = Config::from_toml("[auth]\nmin_password_length = \"twelve\"").unwrap();
+        assert!(AuthConfig::from_config(&config, "a".repeat(32)).is_err());
+    }
+
+    #[test]
+    fn test_settings_schema_defaults() {
+        // Every listed key, set to the default the schema shows, reads back
+        // as the built-in default
+        let schema: toml::Table = SETTINGS_SCHEMA.parse().unwrap();
+        let entries = schema["settings"]["schema"].as_table().unwrap();
+        assert!(!entries.is_empty());
+
+        let mut document = toml::Table::new();
+        for (key, entry) in entries {
+            let (section, name) = key.split_once('.').unwrap();
+            document
+                .entry(section)
+                .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+                .as_table_mut()
+                .unwrap()
+                .insert(name.to_string(), entry["default"].clone());
+        }
+        let listed = Config::from_toml(&document.to_string()).unwrap();
+
+        let secret = "a".repeat(32);
+        let from_schema = AuthConfig::from_config(&listed, secret.clone()).unwrap();
+        let built_in = AuthConfig::from_config(&Config::default(), secret).unwrap();
+        assert_eq!(format!("{:?}", from_schema), format!("{:?}", built_in));
+    }
+
+    #[test]
+    fn test_config_validation_short_secret() {
+        let config = AuthConfig {
+            jwt_secret: "short".to_string(),
+            access_token_expiration: 900,
+            refresh_token_expiration: 604800,
+            jwt_issuer: "test".to_string(),
+            jwt_audience: "test".to_string(),
+            argon2_memory_cost: 65536,
+            argon2_time_cost: 3,
+            argon2_parallelism: 4,
+            max_login_attempts: 5,
+            lockout_duration: 900,
+            password_reset_expiration: 3600,
+            email_verification_expiration: 86400,
+            min_password_length: 8,
+            require_email_verification: false,
+            password_max_age_days: 0,
+            password_history: 0,
+            login_alerts: true,
+            confirm_suspicious_logins: false,
+            max_sessions: 0,
+            admin_rate_limit: 60,
+            registration: RegistrationPolicy::default(),
+            cors: CorsPolicy::default(),
+            mail: MailConfig::default(),
+        };
+
+        assert!(config.validate().is_err());
+    }
+}
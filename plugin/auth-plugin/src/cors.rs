@@ -0,0 +1,289 @@
+//! CORS
+//!
+//! Cross-origin policies for groups of routes. Each group (public reads,
+//! authenticated endpoints, admin) gets its own `CorsPolicy`, applied with
+//! `CorsPolicy::layer` on the group's router. The defaults open anonymous read
+//! endpoints to every origin and keep everything else same-origin until
+//! origins are configured.
+//!
+//! When a path is served by more than one group (`GET /posts` public,
+//! `POST /posts` authenticated), preflight requests are answered by the group
+//! merged last, so apps merge their public routes first.
+
+use crate::config::Config;
+use crate::error::AuthError;
+
+use axum::http::{HeaderName, HeaderValue, Method};
+use serde::Deserialize;
+use std::time::Duration;
+use tower_http::cors::{AllowHeaders, AllowOrigin, Any, CorsLayer};
+
+/// Wildcard accepted in origin and header lists
+pub const WILDCARD: &str = "*";
+
+/// CORS policy of one route group
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct CorsPolicy {
+    /// Origins (`scheme://host[:port]`) allowed to call the group, or `["*"]`.
+    /// Empty means same-origin only.
+    pub allowed_origins: Vec<String>,
+    pub allowed_methods: Vec<String>,
+    /// Request headers clients may send, or `["*"]`
+    pub allowed_headers: Vec<String>,
+    /// Response headers scripts may read
+    pub exposed_headers: Vec<String>,
+    /// Let browsers send cookies and read credentialed responses; requires
+    /// explicit origins
+    pub allow_credentials: bool,
+    /// How long browsers may cache a preflight response
+    pub max_age_secs: u64,
+}
+
+impl Default for CorsPolicy {
+    fn default() -> Self {
+        Self::same_origin()
+    }
+}
+
+impl CorsPolicy {
+    /// No cross-origin access
+    pub fn same_origin() -> Self {
+        Self {
+            allowed_origins: Vec::new(),
+            allowed_methods: strings(&["GET", "POST", "PUT", "PATCH", "DELETE"]),
+            allowed_headers: strings(&["authorization", "content-type", "accept", "x-request-id"]),
+            exposed_headers: strings(&["x-request-id", "link", "x-total-count", "x-total-pages"]),
+            allow_credentials: false,
+            max_age_secs: 600,
+        }
+    }
+
+    /// Anonymous reads from any origin
+    pub fn public_read() -> Self {
+        Self {
+            allowed_origins: strings(&[WILDCARD]),
+            allowed_methods: strings(&["GET", "HEAD"]),
+            allowed_headers: strings(&["content-type", "accept", "x-request-id"]),
+            ..Self::same_origin()
+        }
+    }
+
+    /// Load a policy from the `<section>` table of the [`Config`]
+    /// (`allowed_origins`, `allowed_methods`, `allowed_headers`,
+    /// `allow_credentials`, `max_age_secs`), falling back to `default` for
+    /// unset keys. Environment variables override them as usual, with lists
+    /// comma separated: `CORS_ALLOWED_ORIGINS=https://a.example,https://b.example`.
+    pub fn from_config(config: &Config, section: &str, default: CorsPolicy) -> Result<Self, AuthError> {
+        let key = |name: &str| format!("{}.{}", section, name);
+
+        Ok(Self {
+            allowed_origins: config.list(&key("allowed_origins"))?.unwrap_or(default.allowed_origins),
+            allowed_methods: config.list(&key("allowed_methods"))?.unwrap_or(default.allowed_methods),
+            allowed_headers: config.list(&key("allowed_headers"))?.unwrap_or(default.allowed_headers),
+            exposed_headers: config.list(&key("exposed_headers"))?.unwrap_or(default.exposed_headers),
+            allow_credentials: config.get_or(&key("allow_credentials"), default.allow_credentials)?,
+            max_age_secs: config.get_or(&key("max_age_secs"), default.max_age_secs)?,
+        })
+    }
+
+    /// Check the policy; `layer` would otherwise panic on some combinations
+    pub fn validate(&self) -> Result<(), AuthError> {
+        let wildcard_origin = self.allowed_origins.iter().any(|o| o == WILDCARD);
+        let wildcard_headers = self.allowed_headers.iter().any(|h| h == WILDCARD);
+
+        if self.allow_credentials && (wildcard_origin || wildcard_headers) {
+            return Err(AuthError::Config(
+                "CORS credentials require explicit origins and headers, not \"*\"".to_string(),
+            ));
+        }
+        if wildcard_origin && self.allowed_origins.len() > 1 {
+            return Err(AuthError::Config(
+                "CORS origins must be \"*\" alone or a list of origins".to_string(),
+            ));
+        }
+        for origin in self.allowed_origins.iter().filter(|o| *o != WILDCARD) {
+            let valid = (origin.starts_with("https://") || origin.starts_with("http://"))
+                && !origin.ends_with('/')
+                && HeaderValue::from_str(origin).is_ok();
+            if !valid {
+                return Err(AuthError::Config(format!("Invalid CORS origin: {}", origin)));
+            }
+        }
+        for method in &self.allowed_methods {
+            Method::from_bytes(method.as_bytes())
+                .map_err(|_| AuthError::Config(format!("Invalid CORS method: {}", method)))?;
+        }
+        for name in self.allowed_headers.iter().chain(&self.exposed_headers) {
+            if name != WILDCARD && HeaderName::from_bytes(name.as_bytes()).is_err() {
+                return Err(AuthError::Config(format!("Invalid CORS header: {}", name)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Layer enforcing the policy
+    ///
+    /// Invalid entries are skipped (call `validate` at startup to reject them);
+    /// requests without an `Origin` header pass through untouched.
+    pub fn layer(&self) -> CorsLayer {
+        let wildcard_origin = self.allowed_origins.iter().any(|o| o == WILDCARD);
+        let wildcard_headers = self.allowed_headers.iter().any(|h| h == WILDCARD);
+
+        let origin = if wildcard_origin {
+            AllowOrigin::from(Any)
+        } else {
+            AllowOrigin::list(self.allowed_origins.iter().filter_map(|o| HeaderValue::from_str(o).ok()))
+        };
+        let headers = if wildcard_headers {
+            AllowHeaders::from(Any)
+        } else {
+            AllowHeaders::list(self.allowed_headers.iter().filter_map(|h| HeaderName::from_bytes(h.as_bytes()).ok()))
+        };
+
+        CorsLayer::new()
+            .allow_origin(origin)
+            .allow_methods(
+                self.allowed_methods
+                    .iter()
+                    .filter_map(|m| Method::from_bytes(m.as_bytes()).ok())
+                    .collect::<Vec<_>>(),
+            )
+            .allow_headers(headers)
+            .expose_headers(
+                self.exposed_headers
+                    .iter()
+                    .filter_map(|h| HeaderName::from_bytes(h.as_bytes()).ok())
+                    .collect::<Vec<_>>(),
+            )
+            // tower-http rejects credentials alongside wildcards
+            .allow_credentials(self.allow_credentials && !wildcard_origin && !wildcard_headers)
+            .max_age(Duration::from_secs(self.max_age_secs))
+    }
+}
+
+/// Policies for the standard route groups
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct CorsConfig {
+    /// Anonymous read endpoints
+    pub public: CorsPolicy,
+    /// Endpoints that require a signed-in user
+    pub protected: CorsPolicy,
+    /// Admin endpoints
+    pub admin: CorsPolicy,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        Self {
+            public: CorsPolicy::public_read(),
+            protected: CorsPolicy::same_origin(),
+            admin: CorsPolicy::same_origin(),
+        }
+    }
+}
+
+impl CorsConfig {
+    pub fn validate(&self) -> Result<(), AuthError> {
+        for (group, policy) in [("public", &self.public), ("protected", &self.protected), ("admin", &self.admin)] {
+            policy
+                .validate()
+                .map_err(|e| AuthError::Config(format!("{} (CORS group `{}`)", e, group)))?;
+        }
+        Ok(())
+    }
+}
+
+fn strings(values: &[&str]) -> Vec<String> {
+    values.iter().map(|v| v.to_string()).collect()
+}
+
+// ============================================
+// Tests
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, extract::Request, http::header, routing::post, Router};
+    use tower::ServiceExt;
+
+    fn credentialed() -> CorsPolicy {
+        CorsPolicy {
+            allowed_origins: strings(&["https://app.example.com"]),
+            allow_credentials: true,
+            ..CorsPolicy::same_origin()
+        }
+    }
+
+    async fn preflight(policy: &CorsPolicy, origin: &str) -> axum::response::Response {
+        Router::new()
+            .route("/auth/login", post(|| async { "ok" }))
+            .layer(policy.layer())
+            .oneshot(
+                Request::builder()
+                    .method("OPTIONS")
+                    .uri("/auth/login")
+                    .header(header::ORIGIN, origin)
+                    .header(header::ACCESS_CONTROL_REQUEST_METHOD, "POST")
+                    .header(header::ACCESS_CONTROL_REQUEST_HEADERS, "content-type")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_credentialed_preflight() {
+        let response = preflight(&credentialed(), "https://app.example.com").await;
+        let headers = response.headers();
+
+        assert_eq!(headers[header::ACCESS_CONTROL_ALLOW_ORIGIN], "https://app.example.com");
+        assert_eq!(headers[header::ACCESS_CONTROL_ALLOW_CREDENTIALS], "true");
+        assert!(headers[header::ACCESS_CONTROL_ALLOW_METHODS].to_str().unwrap().contains("POST"));
+        assert_eq!(headers[header::ACCESS_CONTROL_MAX_AGE], "600");
+    }
+
+    #[tokio::test]
+    async fn test_unlisted_origin_gets_no_grant() {
+        let response = preflight(&credentialed(), "https://evil.example").await;
+        assert!(!response.headers().contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN));
+
+        let response = preflight(&CorsPolicy::same_origin(), "https://app.example.com").await;
+        assert!(!response.headers().contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN));
+    }
+
+    #[tokio::test]
+    async fn test_public_read_allows_any_origin() {
+        let response = preflight(&CorsPolicy::public_read(), "https://anywhere.example").await;
+        assert_eq!(response.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN], "*");
+        assert!(!response.headers().contains_key(header::ACCESS_CONTROL_ALLOW_CREDENTIALS));
+    }
+
+    #[test]
+    fn test_validation() {
+        assert!(CorsConfig::default().validate().is_ok());
+        assert!(credentialed().validate().is_ok());
+
+        let wildcard_credentials = CorsPolicy {
+            allow_credentials: true,
+            ..CorsPolicy::public_read()
+        };
+        assert!(wildcard_credentials.validate().is_err());
+
+        let trailing_slash = CorsPolicy {
+            allowed_origins: strings(&["https://app.example.com/"]),
+            ..CorsPolicy::same_origin()
+        };
+        assert!(trailing_slash.validate().is_err());
+
+        let config = CorsConfig {
+            admin: wildcard_credentials,
+            ..Default::default()
+        };
+        let error = config.validate().unwrap_err().to_string();
+        assert!(error.contains("admin"), "{}", error);
+    }
+}
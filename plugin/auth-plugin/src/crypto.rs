@@ -0,0 +1,376 @@
+//! Field Encryption
+//!
+//! Sensitive column values (`users.last_login_ip`, `email_outbox.body`, and
+//! whatever plugins declare) are stored encrypted with AES-256-GCM. Model
+//! fields of type [`Encrypted`] are encrypted when bound and decrypted when
+//! read, so queries stay unchanged.
+//!
+//! Keys come from the `encryption.keys` secret (see [`crate::secrets`]), a
+//! comma-separated list of versioned base64 keys:
+//!
+//! ```text
+//! ENCRYPTION_KEYS=2:q6Jx...=,1:Z0pV...=
+//! ```
+//!
+//! The highest version encrypts; every listed version decrypts. Stored
+//! values are tagged with their key version (`enc:v2:<base64 nonce and
+//! ciphertext>`), so to rotate: add a new version, run `rustpress-reencrypt`
+//! to rewrite older values, then drop the old key. Values without the tag
+//! are plaintext from before encryption was enabled and are read as is.
+//! Without `encryption.keys`, new values are stored in plaintext.
+
+use crate::db::{Db, DbPool};
+use crate::error::AuthError;
+use crate::secrets::SecretProvider;
+
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use arc_swap::ArcSwapOption;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::Deref;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Prefix of encrypted values
+pub const PREFIX: &str = "enc:v";
+
+/// AES-GCM nonce length
+const NONCE_LEN: usize = 12;
+
+/// Rows re-encrypted per query
+const BATCH_SIZE: i64 = 500;
+
+/// Columns encrypted by this plugin
+pub const ENCRYPTED_COLUMNS: &[EncryptedColumn<'static>] = &[
+    EncryptedColumn {
+        table: "users",
+        id: "id",
+        column: "last_login_ip",
+    },
+    EncryptedColumn {
+        table: "email_outbox",
+        id: "id",
+        column: "body",
+    },
+    EncryptedColumn {
+        table: "auth_audit_events",
+        id: "id",
+        column: "ip_address",
+    },
+];
+
+static INSTALLED: ArcSwapOption<FieldCipher> = ArcSwapOption::const_empty();
+static REFRESHING: AtomicBool = AtomicBool::new(false);
+
+/// A column holding [`Encrypted`] values, keyed by a UUID
+#[derive(Debug, Clone, Copy)]
+pub struct EncryptedColumn<'a> {
+    pub table: &'a str,
+    pub id: &'a str,
+    pub column: &'a str,
+}
+
+/// Versioned AES-256-GCM keys
+pub struct FieldCipher {
+    current: u32,
+    keys: BTreeMap<u32, Aes256Gcm>,
+}
+
+impl fmt::Debug for FieldCipher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FieldCipher")
+            .field("current", &self.current)
+            .field("versions", &self.keys.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl FieldCipher {
+    /// Parse `version:base64key` pairs, comma separated
+    pub fn parse(keys: &str) -> Result<Self, AuthError> {
+        let invalid = |message: &str| AuthError::Config(format!("encryption.keys: {}", message));
+
+        let mut parsed = BTreeMap::new();
+        for entry in keys.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (version, key) = entry
+                .split_once(':')
+                .ok_or_else(|| invalid("expected <version>:<base64 key>"))?;
+            let version: u32 = version
+                .trim_start_matches('v')
+                .parse()
+                .map_err(|_| invalid("key versions are numbers"))?;
+            let key = BASE64.decode(key).map_err(|_| invalid("keys are base64"))?;
+            if key.len() != 32 {
+                return Err(invalid("keys are 32 bytes"));
+            }
+            if parsed
+                .insert(version, Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key)))
+                .is_some()
+            {
+                return Err(invalid("duplicate key version"));
+            }
+        }
+
+        let current = *parsed.keys().next_back().ok_or_else(|| invalid("no keys"))?;
+        Ok(Self { current, keys: parsed })
+    }
+
+    /// The cipher for the provider's `encryption.keys`, `None` when unset
+    pub async fn load(secrets: &dyn SecretProvider) -> Result<Option<Self>, AuthError> {
+        match secrets.get("encryption.keys").await? {
+            Some(keys) => Self::parse(&keys).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Version new values are encrypted with
+    pub fn current_version(&self) -> u32 {
+        self.current
+    }
+
+    pub fn encrypt(&self, plaintext: &str) -> String {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self.keys[&self.current]
+            .encrypt(&nonce, plaintext.as_bytes())
+            .expect("AES-GCM encryption of an in-memory buffer");
+
+        let mut payload = nonce.to_vec();
+        payload.extend_from_slice(&ciphertext);
+        format!("{}{}:{}", PREFIX, self.current, BASE64.encode(payload))
+    }
+
+    /// Decrypt a stored value; untagged values are returned unchanged
+    pub fn decrypt(&self, stored: &str) -> Result<String, AuthError> {
+        let Some((version, payload)) = split(stored)? else {
+            return Ok(stored.to_string());
+        };
+
+        let cipher = self
+            .keys
+            .get(&version)
+            .ok_or_else(|| AuthError::Config(format!("No encryption key for version {}", version)))?;
+        if payload.len() < NONCE_LEN {
+            return Err(corrupt());
+        }
+        let (nonce, ciphertext) = payload.split_at(NONCE_LEN);
+        let plaintext = cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| corrupt())?;
+        String::from_utf8(plaintext).map_err(|_| corrupt())
+    }
+
+    /// Whether a stored value is encrypted with the current key
+    pub fn is_current(&self, stored: &str) -> bool {
+        matches!(split(stored), Ok(Some((version, _))) if version == self.current)
+    }
+
+    /// Rewrite values of a column that are plaintext or use an older key;
+    /// returns how many rows were (or, with `dry_run`, would be) rewritten
+    pub async fn reencrypt(&self, db: &DbPool, column: &EncryptedColumn<'_>, dry_run: bool) -> Result<u64, AuthError> {
+        let EncryptedColumn { table, id, column } = column;
+        let select = format!(
+            "SELECT {id}, {column} FROM {table} WHERE {column} IS NOT NULL AND {id} > $1 ORDER BY {id} LIMIT $2"
+        );
+        let update = format!("UPDATE {table} SET {column} = $1 WHERE {id} = $2 AND {column} = $3");
+
+        let mut rewritten = 0;
+        let mut after = Uuid::nil();
+        loop {
+            let rows: Vec<(Uuid, String)> = sqlx::query_as(&select)
+                .bind(after)
+                .bind(BATCH_SIZE)
+                .fetch_all(db)
+                .await?;
+            let Some((last, _)) = rows.last() else {
+                break;
+            };
+            after = *last;
+
+            for (row_id, stored) in rows.iter().filter(|(_, stored)| !self.is_current(stored)) {
+                let plaintext = self.decrypt(stored)?;
+                if !dry_run {
+                    // Skipped when the row changed since it was read
+                    sqlx::query(&update)
+                        .bind(self.encrypt(&plaintext))
+                        .bind(row_id)
+                        .bind(stored)
+                        .execute(db)
+                        .await?;
+                }
+                rewritten += 1;
+            }
+        }
+        Ok(rewritten)
+    }
+}
+
+/// Use `cipher` for [`Encrypted`] fields; `None` stores new values in plaintext
+pub fn install(cipher: Option<Arc<FieldCipher>>) {
+    INSTALLED.store(cipher);
+}
+
+/// The cipher used for [`Encrypted`] fields
+pub fn installed() -> Option<Arc<FieldCipher>> {
+    INSTALLED.load_full()
+}
+
+/// Load and install the provider's keys, then reload them every `interval`
+/// so added key versions are picked up without a restart
+pub async fn init(secrets: Arc<dyn SecretProvider>, interval: Option<Duration>) -> Result<(), AuthError> {
+    let cipher = FieldCipher::load(secrets.as_ref()).await?;
+    if cipher.is_none() {
+        tracing::warn!("encryption.keys is not set; sensitive fields are stored in plaintext");
+    }
+    install(cipher.map(Arc::new));
+
+    // One reload task per process, however often plugins are reactivated
+    if REFRESHING.swap(true, Ordering::SeqCst) {
+        return Ok(());
+    }
+    if let Some(interval) = interval {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                match FieldCipher::load(secrets.as_ref()).await {
+                    Ok(Some(cipher)) => install(Some(Arc::new(cipher))),
+                    Ok(None) => {}
+                    Err(e) => tracing::warn!("Encryption key reload failed: {}", e),
+                }
+            }
+        });
+    }
+    Ok(())
+}
+
+/// `(version, payload)` of an encrypted value, `None` for plaintext
+fn split(stored: &str) -> Result<Option<(u32, Vec<u8>)>, AuthError> {
+    let Some(rest) = stored.strip_prefix(PREFIX) else {
+        return Ok(None);
+    };
+    let (version, payload) = rest.split_once(':').ok_or_else(corrupt)?;
+    let version = version.parse().map_err(|_| corrupt())?;
+    let payload = BASE64.decode(payload).map_err(|_| corrupt())?;
+    Ok(Some((version, payload)))
+}
+
+fn corrupt() -> AuthError {
+    AuthError::Config("Encrypted value is corrupt or was encrypted with a different key".to_string())
+}
+
+// ============================================
+// Model Type
+// ============================================
+
+/// A string column stored encrypted with the installed [`FieldCipher`]
+///
+/// Holds the plaintext; serializes as the plaintext too, so models that
+/// expose a field keep their JSON shape.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Encrypted(pub String);
+
+impl fmt::Debug for Encrypted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Encrypted(..)")
+    }
+}
+
+impl Deref for Encrypted {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<String> for Encrypted {
+    fn from(value: String) -> Self {
+        Self(value)
+    }
+}
+
+impl sqlx::Type<Db> for Encrypted {
+    fn type_info() -> <Db as sqlx::Database>::TypeInfo {
+        <String as sqlx::Type<Db>>::type_info()
+    }
+
+    fn compatible(ty: &<Db as sqlx::Database>::TypeInfo) -> bool {
+        <String as sqlx::Type<Db>>::compatible(ty)
+    }
+}
+
+impl<'q> sqlx::Encode<'q, Db> for Encrypted {
+    fn encode_by_ref(&self, buf: &mut <Db as sqlx::database::HasArguments<'q>>::ArgumentBuffer) -> sqlx::encode::IsNull {
+        let stored = match installed() {
+            Some(cipher) => cipher.encrypt(&self.0),
+            None => self.0.clone(),
+        };
+        <String as sqlx::Encode<'q, Db>>::encode(stored, buf)
+    }
+}
+
+impl<'r> sqlx::Decode<'r, Db> for Encrypted {
+    fn decode(value: <Db as sqlx::database::HasValueRef<'r>>::ValueRef) -> Result<Self, sqlx::error::BoxDynError> {
+        let stored = <String as sqlx::Decode<'r, Db>>::decode(value)?;
+        match installed() {
+            Some(cipher) => Ok(Self(cipher.decrypt(&stored)?)),
+            None if stored.starts_with(PREFIX) => {
+                Err("encrypted column read without encryption.keys".into())
+            }
+            None => Ok(Self(stored)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(byte: u8) -> String {
+        BASE64.encode([byte; 32])
+    }
+
+    #[test]
+    fn test_round_trip_and_versions() {
+        let old = FieldCipher::parse(&format!("1:{}", key(1))).unwrap();
+        let stored = old.encrypt("203.0.113.7");
+        assert!(stored.starts_with("enc:v1:"));
+        assert_ne!(old.encrypt("203.0.113.7"), stored);
+        assert_eq!(old.decrypt(&stored).unwrap(), "203.0.113.7");
+
+        let rotated = FieldCipher::parse(&format!("2:{},1:{}", key(2), key(1))).unwrap();
+        assert_eq!(rotated.current_version(), 2);
+        assert_eq!(rotated.decrypt(&stored).unwrap(), "203.0.113.7");
+        assert!(!rotated.is_current(&stored));
+        assert!(rotated.is_current(&rotated.encrypt("x")));
+
+        // Plaintext from before encryption was enabled
+        assert_eq!(rotated.decrypt("198.51.100.1").unwrap(), "198.51.100.1");
+    }
+
+    #[test]
+    fn test_wrong_or_missing_key() {
+        let cipher = FieldCipher::parse(&format!("1:{}", key(1))).unwrap();
+        let other = FieldCipher::parse(&format!("1:{}", key(9))).unwrap();
+        let stored = cipher.encrypt("secret");
+
+        assert!(other.decrypt(&stored).is_err());
+        assert!(FieldCipher::parse(&format!("2:{}", key(2))).unwrap().decrypt(&stored).is_err());
+    }
+
+    #[test]
+    fn test_invalid_keys() {
+        assert!(FieldCipher::parse("").is_err());
+        assert!(FieldCipher::parse("1:c2hvcnQ=").is_err());
+        assert!(FieldCipher::parse(&format!("1:{},1:{}", key(1), key(2))).is_err());
+        assert!(FieldCipher::parse(&key(1)).is_err());
+    }
+}
@@ -0,0 +1,572 @@
+//! Database Backend
+//!
+//! PostgreSQL is the default backend. Building with
+//! `--no-default-features --features sqlite` swaps in SQLite for development
+//! and small single-node sites. Exactly one backend is compiled in; code that
+//! touches the database goes through the aliases below instead of naming
+//! `PgPool` or `SqlitePool` directly, and queries stick to SQL both dialects
+//! accept (`$N` placeholders, timestamps bound from Rust rather than `NOW()`).
+//!
+//! [`DbPools`] adds optional read replicas: services pick [`DbPools::read`]
+//! for report and listing queries that tolerate slightly stale data and
+//! [`DbPools::write`] for everything else, including transactions.
+//!
+//! [`partition`] gives a workload its own pool when
+//! `[database.pools.<name>]` is configured, so a slow report can't take the
+//! connections logins need. Partitions carry their own size, acquire timeout
+//! and (on PostgreSQL) statement timeout; without the section the workload
+//! shares the main pool.
+
+use crate::config::{Config, ConfigError};
+use crate::error::AuthError;
+use crate::metrics::{MetricsError, PluginMetrics};
+use crate::secrets::SecretProvider;
+
+use serde::Deserialize;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+
+#[cfg(all(feature = "postgres", feature = "sqlite"))]
+compile_error!("features `postgres` and `sqlite` are mutually exclusive");
+
+#[cfg(not(any(feature = "postgres", feature = "sqlite")))]
+compile_error!("enable exactly one database backend: `postgres` or `sqlite`");
+
+/// The compiled-in database
+#[cfg(feature = "postgres")]
+pub type Db = sqlx::Postgres;
+
+/// The compiled-in database
+#[cfg(feature = "sqlite")]
+pub type Db = sqlx::Sqlite;
+
+/// Connection pool for the compiled-in database
+pub type DbPool = sqlx::Pool<Db>;
+
+/// Single connection for the compiled-in database
+pub type DbConnection = <Db as sqlx::Database>::Connection;
+
+/// Backend name, also the migration subdirectory holding its scripts
+#[cfg(feature = "postgres")]
+pub const BACKEND: &str = "postgres";
+
+/// Backend name, also the migration subdirectory holding its scripts
+#[cfg(feature = "sqlite")]
+pub const BACKEND: &str = "sqlite";
+
+/// Connection options for the compiled-in database
+pub type DbConnectOptions = <DbConnection as sqlx::Connection>::Options;
+
+/// Options for a `DATABASE_URL`-style connection string
+#[cfg(feature = "postgres")]
+pub fn connect_options(url: &str) -> Result<DbConnectOptions, sqlx::Error> {
+    use std::str::FromStr;
+
+    DbConnectOptions::from_str(url)
+}
+
+/// Options for a `DATABASE_URL`-style connection string
+///
+/// The database file is created when missing and opened in WAL mode so
+/// readers don't block the single writer. Foreign keys are enforced.
+#[cfg(feature = "sqlite")]
+pub fn connect_options(url: &str) -> Result<DbConnectOptions, sqlx::Error> {
+    use sqlx::sqlite::SqliteJournalMode;
+    use std::str::FromStr;
+
+    Ok(DbConnectOptions::from_str(url)?
+        .create_if_missing(true)
+        .journal_mode(SqliteJournalMode::Wal)
+        .foreign_keys(true))
+}
+
+/// Connect to `DATABASE_URL`-style connection string
+pub async fn connect(url: &str) -> Result<DbPool, sqlx::Error> {
+    DbPool::connect_with(connect_options(url)?).await
+}
+
+/// Connect to the `database.url` secret
+pub async fn connect_secret(secrets: &dyn SecretProvider) -> Result<DbPool, AuthError> {
+    let url = secrets.require("database.url").await?;
+    Ok(connect(&url).await?)
+}
+
+/// Re-read `database.url` every `interval` until the pool is closed; when the
+/// credentials change, new connections use them while open ones finish their
+/// work and are retired as usual
+pub fn rotate_credentials(
+    pool: &DbPool,
+    secrets: Arc<dyn SecretProvider>,
+    interval: Duration,
+) -> tokio::task::JoinHandle<()> {
+    let pool = pool.clone();
+    tokio::spawn(async move {
+        let mut current = secrets.get("database.url").await.ok().flatten();
+        let mut ticker = tokio::time::interval(interval);
+        ticker.tick().await;
+        while !pool.is_closed() {
+            ticker.tick().await;
+            let url = match secrets.get("database.url").await {
+                Ok(Some(url)) if Some(&url) != current.as_ref() => url,
+                Ok(_) => continue,
+                Err(e) => {
+                    tracing::warn!("Database credential reload failed: {}", e);
+                    continue;
+                }
+            };
+            match connect_options(&url) {
+                Ok(options) => {
+                    pool.set_connect_options(options);
+                    current = Some(url);
+                    tracing::info!("Database credentials rotated");
+                }
+                Err(e) => tracing::warn!("Rotated database.url is invalid: {}", e),
+            }
+        }
+    })
+}
+
+// ============================================
+// Read Replicas
+// ============================================
+
+/// Lag recorded for a replica that hasn't been measured or can't be reached
+const UNKNOWN_LAG: u64 = u64::MAX;
+
+/// Replica routing settings
+#[derive(Debug, Clone)]
+pub struct ReplicaConfig {
+    /// Replica connection strings (`database.replica_urls`)
+    pub urls: Vec<String>,
+    /// Replicas further behind than this serve no reads (`database.replica_max_lag_ms`)
+    pub max_lag: Duration,
+    /// How often replica lag is measured (`database.replica_check_secs`)
+    pub check_interval: Duration,
+}
+
+impl ReplicaConfig {
+    /// Load replica settings from `[database]` in the [`Config`]
+    pub fn from_config(config: &Config) -> Result<Self, ConfigError> {
+        Ok(Self {
+            urls: config.list("database.replica_urls")?.unwrap_or_default(),
+            max_lag: Duration::from_millis(config.get_or("database.replica_max_lag_ms", 2000)?),
+            check_interval: Duration::from_secs(config.get_or("database.replica_check_secs", 5)?),
+        })
+    }
+}
+
+struct Replica {
+    pool: DbPool,
+    /// Last measured replication lag in milliseconds
+    lag_ms: AtomicU64,
+}
+
+/// Primary pool plus optional read replicas
+///
+/// Without replicas (or when all of them lag too far behind) reads go to the
+/// primary, so callers never need to handle a missing replica.
+pub struct DbPools {
+    primary: DbPool,
+    replicas: Vec<Replica>,
+    max_lag: Duration,
+    next: AtomicUsize,
+}
+
+impl DbPools {
+    /// Pools with no replicas; every call hits `primary`
+    pub fn new(primary: DbPool) -> Self {
+        Self {
+            primary,
+            replicas: Vec::new(),
+            max_lag: Duration::from_secs(2),
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    /// Add a replica; it serves reads once its lag has been measured
+    pub fn with_replica(mut self, pool: DbPool) -> Self {
+        self.replicas.push(Replica {
+            pool,
+            lag_ms: AtomicU64::new(UNKNOWN_LAG),
+        });
+        self
+    }
+
+    /// Set the maximum replication lag a replica may have and still serve reads
+    pub fn max_lag(mut self, max_lag: Duration) -> Self {
+        self.max_lag = max_lag;
+        self
+    }
+
+    /// Connect the replicas in `config`, measure their lag once and keep
+    /// measuring it in the background
+    pub async fn connect(primary: DbPool, config: &ReplicaConfig) -> Result<Arc<Self>, sqlx::Error> {
+        let mut pools = Self::new(primary).max_lag(config.max_lag);
+        for url in &config.urls {
+            pools = pools.with_replica(connect(url).await?);
+        }
+
+        let pools = Arc::new(pools);
+        if !pools.replicas.is_empty() {
+            pools.refresh_lag().await;
+            pools.spawn_lag_monitor(config.check_interval);
+        }
+        Ok(pools)
+    }
+
+    /// The primary, for writes, transactions and reads that must see them
+    pub fn write(&self) -> &DbPool {
+        &self.primary
+    }
+
+    /// A replica within the lag budget (round robin), else the primary
+    pub fn read(&self) -> &DbPool {
+        let count = self.replicas.len();
+        if count == 0 {
+            return &self.primary;
+        }
+
+        let max_lag = self.max_lag.as_millis() as u64;
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+        (0..count)
+            .map(|offset| &self.replicas[(start + offset) % count])
+            .find(|replica| replica.lag_ms.load(Ordering::Relaxed) <= max_lag)
+            .map(|replica| &replica.pool)
+            .unwrap_or(&self.primary)
+    }
+
+    /// How long a write may stay invisible to `read`: the lag budget of the
+    /// replicas in rotation, zero without replicas
+    pub fn staleness(&self) -> Duration {
+        if self.replicas.is_empty() {
+            Duration::ZERO
+        } else {
+            self.max_lag
+        }
+    }
+
+    /// Begin a transaction; always on the primary
+    pub async fn begin(&self) -> Result<sqlx::Transaction<'static, Db>, sqlx::Error> {
+        self.primary.begin().await
+    }
+
+    /// Replication lag of each replica, `None` when unknown or unreachable
+    pub fn replica_lag(&self) -> Vec<Option<Duration>> {
+        self.replicas
+            .iter()
+            .map(|replica| match replica.lag_ms.load(Ordering::Relaxed) {
+                UNKNOWN_LAG => None,
+                ms => Some(Duration::from_millis(ms)),
+            })
+            .collect()
+    }
+
+    /// Measure every replica's lag now
+    pub async fn refresh_lag(&self) {
+        for (index, replica) in self.replicas.iter().enumerate() {
+            let lag_ms = match measure_lag(&replica.pool).await {
+                Ok(lag) => lag.as_millis() as u64,
+                Err(e) => {
+                    tracing::warn!(replica = index, "Replica lag check failed: {}", e);
+                    UNKNOWN_LAG
+                }
+            };
+
+            let previous = replica.lag_ms.swap(lag_ms, Ordering::Relaxed);
+            let max_lag = self.max_lag.as_millis() as u64;
+            if (previous <= max_lag) != (lag_ms <= max_lag) {
+                tracing::info!(replica = index, lag_ms, in_rotation = lag_ms <= max_lag, "Replica rotation changed");
+            }
+        }
+    }
+
+    /// Re-measure lag every `interval` until the pools are dropped
+    pub fn spawn_lag_monitor(self: &Arc<Self>, interval: Duration) -> tokio::task::JoinHandle<()> {
+        let pools: Weak<Self> = Arc::downgrade(self);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                match pools.upgrade() {
+                    Some(pools) => pools.refresh_lag().await,
+                    None => break,
+                }
+            }
+        })
+    }
+}
+
+impl From<DbPool> for DbPools {
+    fn from(primary: DbPool) -> Self {
+        Self::new(primary)
+    }
+}
+
+/// Replay lag of a streaming replica; zero when it has replayed everything it
+/// received (an idle primary produces no new transactions to compare against)
+#[cfg(feature = "postgres")]
+async fn measure_lag(pool: &DbPool) -> Result<Duration, sqlx::Error> {
+    let lag_ms: i64 = sqlx::query_scalar(
+        r#"
+        SELECT CASE
+            WHEN NOT pg_is_in_recovery() THEN 0
+            WHEN pg_last_wal_receive_lsn() = pg_last_wal_replay_lsn() THEN 0
+            ELSE COALESCE(EXTRACT(EPOCH FROM NOW() - pg_last_xact_replay_timestamp()) * 1000, 0)
+        END::BIGINT
+        "#,
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(Duration::from_millis(lag_ms.max(0) as u64))
+}
+
+/// SQLite has no replication; a replica here is a read-only copy kept in sync
+/// externally, so it is only checked for reachability
+#[cfg(feature = "sqlite")]
+async fn measure_lag(pool: &DbPool) -> Result<Duration, sqlx::Error> {
+    sqlx::query("SELECT 1").execute(pool).await?;
+    Ok(Duration::ZERO)
+}
+
+// ============================================
+// Pool Partitions
+// ============================================
+
+/// Partition for logins, token refreshes and the rest of the auth plugin
+pub const AUTH_POOL: &str = "auth";
+
+/// Partition for tracking hits and other high-volume writes
+pub const INGEST_POOL: &str = "ingest";
+
+/// Partition for report and dashboard queries
+pub const REPORTING_POOL: &str = "reporting";
+
+/// How often [`watch_pool`] samples a pool
+const POOL_SAMPLE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Limits of one pool partition (`[database.pools.<name>]`)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct PoolConfig {
+    pub max_connections: u32,
+    pub min_connections: u32,
+    /// How long a query waits for a free connection before failing
+    pub acquire_timeout_ms: u64,
+    /// Idle connections above `min_connections` are closed after this; 0 keeps them
+    pub idle_timeout_secs: u64,
+    /// The server cancels statements running longer; 0 for no limit.
+    /// PostgreSQL only
+    pub statement_timeout_ms: u64,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            max_connections: 10,
+            min_connections: 0,
+            acquire_timeout_ms: 30_000,
+            idle_timeout_secs: 600,
+            statement_timeout_ms: 0,
+        }
+    }
+}
+
+impl PoolConfig {
+    /// The `[database.pools.<name>]` table; `None` when the partition isn't
+    /// configured and the workload shares the main pool
+    pub fn from_config(config: &Config, name: &str) -> Result<Option<Self>, ConfigError> {
+        config.get(&format!("database.pools.{}", name))
+    }
+
+    fn pool_options(&self) -> sqlx::pool::PoolOptions<Db> {
+        let options = sqlx::pool::PoolOptions::new()
+            .max_connections(self.max_connections.max(1))
+            .min_connections(self.min_connections)
+            .acquire_timeout(Duration::from_millis(self.acquire_timeout_ms))
+            .idle_timeout((self.idle_timeout_secs > 0).then(|| Duration::from_secs(self.idle_timeout_secs)));
+
+        // Set per connection rather than in the connect options, which
+        // credential rotation replaces
+        #[cfg(feature = "postgres")]
+        let options = match self.statement_timeout_ms {
+            0 => options,
+            ms => options.after_connect(move |conn, _| {
+                Box::pin(async move {
+                    let sql = format!("SET statement_timeout = {}", ms);
+                    sqlx::query(&sql).execute(conn).await?;
+                    Ok(())
+                })
+            }),
+        };
+
+        options
+    }
+
+    /// Connect a pool with these limits
+    pub async fn connect(&self, url: &str) -> Result<DbPool, sqlx::Error> {
+        self.connect_with(connect_options(url)?).await
+    }
+
+    /// Connect a pool with these limits from connection options
+    pub async fn connect_with(&self, options: DbConnectOptions) -> Result<DbPool, sqlx::Error> {
+        self.pool_options().connect_with(options).await
+    }
+}
+
+/// A dedicated pool for the `name` workload when `[database.pools.<name>]`
+/// is set; `None` when the workload should share the main pool
+///
+/// The pool connects to the `database.url` secret, follows credential
+/// rotations and exports its connection counts through `metrics`. Close it
+/// when the workload stops; the background tasks hold it open until then.
+pub async fn partition(name: &str, metrics: &PluginMetrics) -> Result<Option<DbPool>, AuthError> {
+    let config = Config::load()?;
+    let Some(pool_config) = PoolConfig::from_config(&config, name)? else {
+        return Ok(None);
+    };
+
+    let secrets = crate::secrets::shared().await?;
+    let pool = pool_config.connect(&secrets.require("database.url").await?).await?;
+    if let Some(interval) = crate::secrets::refresh_interval(&config)? {
+        rotate_credentials(&pool, secrets, interval);
+    }
+    watch_pool(&pool, name, metrics).map_err(|e| AuthError::Config(e.to_string()))?;
+
+    tracing::info!(
+        pool = name,
+        max_connections = pool_config.max_connections,
+        statement_timeout_ms = pool_config.statement_timeout_ms,
+        "Database pool partition connected"
+    );
+    Ok(Some(pool))
+}
+
+/// Export the open, idle and maximum connections of `pool` as the
+/// `db_pool_<name>_connections`, `db_pool_<name>_idle_connections` and
+/// `db_pool_<name>_max_connections` gauges, sampled until the pool is closed
+pub fn watch_pool(
+    pool: &DbPool,
+    name: &str,
+    metrics: &PluginMetrics,
+) -> Result<tokio::task::JoinHandle<()>, MetricsError> {
+    let open = metrics.gauge(
+        &format!("db_pool_{}_connections", name),
+        &format!("Open connections in the {} database pool", name),
+    )?;
+    let idle = metrics.gauge(
+        &format!("db_pool_{}_idle_connections", name),
+        &format!("Idle connections in the {} database pool", name),
+    )?;
+    let max = metrics.gauge(
+        &format!("db_pool_{}_max_connections", name),
+        &format!("Connection limit of the {} database pool", name),
+    )?;
+
+    let sample = move |pool: &DbPool| {
+        open.set(pool.size() as f64);
+        idle.set(pool.num_idle() as f64);
+        max.set(pool.options().get_max_connections() as f64);
+    };
+    sample(pool);
+
+    let pool = pool.clone();
+    Ok(tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(POOL_SAMPLE_INTERVAL);
+        ticker.tick().await;
+        while !pool.is_closed() {
+            ticker.tick().await;
+            sample(&pool);
+        }
+    }))
+}
+
+// ============================================
+// Tests
+// ============================================
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+    use super::*;
+
+    async fn memory_pool() -> DbPool {
+        sqlx::sqlite::SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_reads_use_primary_without_replicas() {
+        let pools = DbPools::new(memory_pool().await);
+        assert!(std::ptr::eq(pools.read(), pools.write()));
+    }
+
+    #[tokio::test]
+    async fn test_unmeasured_and_lagging_replicas_are_skipped() {
+        let pools = DbPools::new(memory_pool().await)
+            .with_replica(memory_pool().await)
+            .with_replica(memory_pool().await)
+            .max_lag(Duration::from_millis(500));
+
+        // Not measured yet
+        assert!(std::ptr::eq(pools.read(), pools.write()));
+
+        pools.refresh_lag().await;
+        assert_eq!(pools.replica_lag(), vec![Some(Duration::ZERO); 2]);
+        let first = pools.read() as *const DbPool;
+        let second = pools.read() as *const DbPool;
+        assert_ne!(first, second);
+        assert!(!std::ptr::eq(first, pools.write()));
+
+        pools.replicas[0].lag_ms.store(1_000, Ordering::Relaxed);
+        for _ in 0..4 {
+            assert!(std::ptr::eq(pools.read(), &pools.replicas[1].pool));
+        }
+
+        pools.replicas[1].lag_ms.store(UNKNOWN_LAG, Ordering::Relaxed);
+        assert!(std::ptr::eq(pools.read(), pools.write()));
+    }
+
+    #[test]
+    fn test_pool_config_from_config() {
+        let config = Config::from_toml(
+            r#"
+            [database.pools.reporting]
+            max_connections = 4
+            statement_timeout_ms = 15000
+            "#,
+        )
+        .unwrap();
+
+        let reporting = PoolConfig::from_config(&config, REPORTING_POOL).unwrap().unwrap();
+        assert_eq!(reporting.max_connections, 4);
+        assert_eq!(reporting.statement_timeout_ms, 15_000);
+        assert_eq!(reporting.acquire_timeout_ms, PoolConfig::default().acquire_timeout_ms);
+        assert!(PoolConfig::from_config(&config, AUTH_POOL).unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_watch_pool_exports_connection_counts() {
+        let pool_config = PoolConfig {
+            max_connections: 3,
+            ..PoolConfig::default()
+        };
+        let pool = pool_config.connect("sqlite::memory:").await.unwrap();
+        let registry = Arc::new(crate::metrics::MetricsRegistry::default());
+        let plugin = registry.plugin("shop");
+
+        let held = pool.acquire().await.unwrap();
+        let sampler = watch_pool(&pool, "test", &plugin).unwrap();
+        let rendered = registry.render();
+        assert!(rendered.contains("db_pool_test_connections{plugin=\"shop\"} 1"));
+        assert!(rendered.contains("db_pool_test_idle_connections{plugin=\"shop\"} 0"));
+        assert!(rendered.contains("db_pool_test_max_connections{plugin=\"shop\"} 3"));
+
+        assert!(watch_pool(&pool, "no-dashes", &plugin).is_err());
+        drop(held);
+        sampler.abort();
+    }
+}
@@ -1,11 +1,16 @@
 //! Authentication Error Types
 //!
 //! Centralized error handling for all authentication operations.
+//!
+//! `Display` gives the English message for logs; problem details carry the
+//! message in the request's locale (see `i18n`).
+
+use crate::i18n;
+use crate::problem::{field_errors, FieldError, ProblemDetails};
 
 use axum::{
     http::StatusCode,
     response::{IntoResponse, Response},
-    Json,
 };
 
 /// Authentication errors
@@ -23,6 +28,9 @@ pub enum AuthError {
     #[error("Email not verified")]
     EmailNotVerified,
 
+    #[error("Login from a new location or device. Confirm it with the link sent by email, then log in again")]
+    LoginConfirmationRequired,
+
     #[error("Invalid or expired token")]
     InvalidToken,
 
@@ -35,12 +43,24 @@ pub enum AuthError {
     #[error("Email already registered")]
     EmailExists,
 
+    #[error("{0}")]
+    RegistrationRejected(String),
+
     #[error("Password does not meet requirements")]
     WeakPassword,
 
+    #[error("Password has expired. Choose a new one to log in")]
+    PasswordExpired,
+
+    #[error("Password was used recently. Choose a different one")]
+    PasswordReused,
+
     #[error("Validation error: {0}")]
     Validation(String),
 
+    #[error("Validation failed")]
+    InvalidFields(Vec<FieldError>),
+
     #[error("Database error: {0}")]
     Database(String),
 
@@ -51,74 +71,71 @@ pub enum AuthError {
     Internal,
 }
 
+impl AuthError {
+    /// ID of the client-facing message in the `i18n` catalogs; `None` for
+    /// errors whose message is their payload
+    pub fn message_id(&self) -> Option<&'static str> {
+        Some(match self {
+            AuthError::InvalidCredentials => "auth-invalid-credentials",
+            AuthError::AccountLocked => "auth-account-locked",
+            AuthError::AccountNotActive => "auth-account-not-active",
+            AuthError::EmailNotVerified => "auth-email-not-verified",
+            AuthError::LoginConfirmationRequired => "auth-login-confirmation-required",
+            AuthError::InvalidToken => "auth-invalid-token",
+            AuthError::TokenRevoked => "auth-token-revoked",
+            AuthError::UserNotFound => "auth-user-not-found",
+            AuthError::EmailExists => "auth-email-exists",
+            AuthError::WeakPassword => "auth-weak-password",
+            AuthError::PasswordExpired => "auth-password-expired",
+            AuthError::PasswordReused => "auth-password-reused",
+            AuthError::InvalidFields(_) => "validation-failed",
+            AuthError::Database(_) | AuthError::Internal => "auth-internal-error",
+            AuthError::RegistrationRejected(_) | AuthError::Validation(_) | AuthError::Config(_) => return None,
+        })
+    }
+}
+
+impl From<AuthError> for ProblemDetails {
+    fn from(err: AuthError) -> Self {
+        let (status, code) = match &err {
+            AuthError::InvalidCredentials => (StatusCode::UNAUTHORIZED, "invalid_credentials"),
+            AuthError::AccountLocked => (StatusCode::FORBIDDEN, "account_locked"),
+            AuthError::AccountNotActive => (StatusCode::FORBIDDEN, "account_not_active"),
+            AuthError::EmailNotVerified => (StatusCode::FORBIDDEN, "email_not_verified"),
+            AuthError::LoginConfirmationRequired => (StatusCode::FORBIDDEN, "login_confirmation_required"),
+            AuthError::InvalidToken | AuthError::TokenRevoked => (StatusCode::UNAUTHORIZED, "invalid_token"),
+            AuthError::UserNotFound => (StatusCode::NOT_FOUND, "user_not_found"),
+            AuthError::EmailExists => (StatusCode::CONFLICT, "email_exists"),
+            AuthError::RegistrationRejected(_) => (StatusCode::FORBIDDEN, "registration_rejected"),
+            AuthError::WeakPassword => (StatusCode::BAD_REQUEST, "weak_password"),
+            AuthError::PasswordExpired => (StatusCode::FORBIDDEN, "password_expired"),
+            AuthError::PasswordReused => (StatusCode::BAD_REQUEST, "password_reused"),
+            AuthError::Validation(_) | AuthError::InvalidFields(_) => (StatusCode::BAD_REQUEST, "validation_error"),
+            AuthError::Config(_) => (StatusCode::INTERNAL_SERVER_ERROR, "configuration_error"),
+            AuthError::Database(_) | AuthError::Internal => (StatusCode::INTERNAL_SERVER_ERROR, "internal_error"),
+        };
+
+        let problem = ProblemDetails::new(status, code);
+        let detail = err.message_id().map(i18n::t);
+        match err {
+            AuthError::InvalidFields(errors) => problem.detail(detail.unwrap_or_default()).with_errors(errors),
+            AuthError::RegistrationRejected(msg) | AuthError::Validation(msg) | AuthError::Config(msg) => {
+                problem.detail(msg)
+            }
+            _ => problem.detail(detail.unwrap_or_default()),
+        }
+    }
+}
+
 impl IntoResponse for AuthError {
     fn into_response(self) -> Response {
-        let (status, error_code, message) = match &self {
-            AuthError::InvalidCredentials => (
-                StatusCode::UNAUTHORIZED,
-                "invalid_credentials",
-                self.to_string(),
-            ),
-            AuthError::AccountLocked => (
-                StatusCode::FORBIDDEN,
-                "account_locked",
-                self.to_string(),
-            ),
-            AuthError::AccountNotActive => (
-                StatusCode::FORBIDDEN,
-                "account_not_active",
-                self.to_string(),
-            ),
-            AuthError::EmailNotVerified => (
-                StatusCode::FORBIDDEN,
-                "email_not_verified",
-                self.to_string(),
-            ),
-            AuthError::InvalidToken | AuthError::TokenRevoked => (
-                StatusCode::UNAUTHORIZED,
-                "invalid_token",
-                self.to_string(),
-            ),
-            AuthError::UserNotFound => (
-                StatusCode::NOT_FOUND,
-                "user_not_found",
-                self.to_string(),
-            ),
-            AuthError::EmailExists => (
-                StatusCode::CONFLICT,
-                "email_exists",
-                self.to_string(),
-            ),
-            AuthError::WeakPassword => (
-                StatusCode::BAD_REQUEST,
-                "weak_password",
-                self.to_string(),
-            ),
-            AuthError::Validation(msg) => (
-                StatusCode::BAD_REQUEST,
-                "validation_error",
-                msg.clone(),
-            ),
-            AuthError::Config(msg) => (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                "configuration_error",
-                msg.clone(),
-            ),
-            AuthError::Database(_) | AuthError::Internal => (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                "internal_error",
-                "An internal error occurred".to_string(),
-            ),
-        };
+        ProblemDetails::from(self).into_response()
+    }
+}
 
-        (
-            status,
-            Json(serde_json::json!({
-                "error": error_code,
-                "message": message
-            })),
-        )
-            .into_response()
+impl From<validator::ValidationErrors> for AuthError {
+    fn from(errors: validator::ValidationErrors) -> Self {
+        AuthError::InvalidFields(field_errors(&errors))
     }
 }
 
@@ -142,3 +159,9 @@ impl From<jsonwebtoken::errors::Error> for AuthError {
         AuthError::InvalidToken
     }
 }
+
+impl From<crate::config::ConfigError> for AuthError {
+    fn from(err: crate::config::ConfigError) -> Self {
+        AuthError::Config(err.to_string())
+    }
+}
@@ -1,19 +1,22 @@
 //! Authentication Extractors
 //!
-//! Axum extractors for authentication and request metadata.
+//! Axum extractors for authentication, request metadata and validated
+//! request bodies.
 
+use crate::middleware::{is_revoked, jwt_decoding};
 use crate::models::AccessTokenClaims;
+use crate::problem::ProblemDetails;
 
 use axum::{
     async_trait,
-    extract::FromRequestParts,
+    extract::{rejection::JsonRejection, FromRequest, FromRequestParts, Request},
     http::{request::Parts, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
-use jsonwebtoken::{decode, DecodingKey, Validation};
-use std::env;
+use serde::de::DeserializeOwned;
 use uuid::Uuid;
+use validator::Validate;
 
 /// Authenticated user information extracted from JWT claims
 #[derive(Debug, Clone)]
@@ -71,63 +74,35 @@ where
             .and_then(|h| h.to_str().ok());
 
         let header = auth_header.ok_or_else(|| {
-            (
-                StatusCode::UNAUTHORIZED,
-                Json(serde_json::json!({
-                    "error": "unauthorized",
-                    "message": "Authentication required"
-                })),
-            )
+            ProblemDetails::new(StatusCode::UNAUTHORIZED, "unauthorized")
+                .detail("Authentication required")
                 .into_response()
         })?;
 
         if !header.starts_with("Bearer ") {
-            return Err((
-                StatusCode::UNAUTHORIZED,
-                Json(serde_json::json!({
-                    "error": "unauthorized",
-                    "message": "Invalid authorization header format"
-                })),
-            )
+            return Err(ProblemDetails::new(StatusCode::UNAUTHORIZED, "unauthorized")
+                .detail("Invalid authorization header format")
                 .into_response());
         }
 
         let token = header.trim_start_matches("Bearer ");
 
-        // Get JWT configuration from environment
-        let secret = env::var("JWT_SECRET").map_err(|_| {
-            tracing::error!("JWT_SECRET environment variable not set");
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(serde_json::json!({
-                    "error": "configuration_error",
-                    "message": "Server configuration error"
-                })),
-            )
-                .into_response()
-        })?;
-
-        let issuer = env::var("JWT_ISSUER").unwrap_or_else(|_| "rustpress".to_string());
-        let audience = env::var("JWT_AUDIENCE").unwrap_or_else(|_| "rustpress-api".to_string());
-
-        let decoding_key = DecodingKey::from_secret(secret.as_bytes());
-        let mut validation = Validation::default();
-        validation.set_issuer(&[issuer]);
-        validation.set_audience(&[audience]);
+        let (keys, validation) = jwt_decoding().await?;
 
         let token_data =
-            decode::<AccessTokenClaims>(token, &decoding_key, &validation).map_err(|e| {
+            keys.decode::<AccessTokenClaims>(token, &validation).map_err(|e| {
                 tracing::debug!("JWT validation failed: {:?}", e);
-                (
-                    StatusCode::UNAUTHORIZED,
-                    Json(serde_json::json!({
-                        "error": "invalid_token",
-                        "message": "Invalid or expired token"
-                    })),
-                )
+                ProblemDetails::new(StatusCode::UNAUTHORIZED, "invalid_token")
+                    .detail("Invalid or expired token")
                     .into_response()
             })?;
 
+        if is_revoked(&token_data.claims) {
+            return Err(ProblemDetails::new(StatusCode::UNAUTHORIZED, "invalid_token")
+                .detail("Session has ended")
+                .into_response());
+        }
+
         Ok(AuthUser::from_claims(&token_data.claims))
     }
 }
@@ -177,3 +152,104 @@ where
         Ok(ClientInfo { ip, user_agent })
     }
 }
+
+// ============================================
+// Validated JSON
+// ============================================
+
+/// JSON body that has passed `validator::Validate`
+///
+/// Malformed bodies are rejected with `invalid_body`, bodies failing
+/// validation with `validation_error` listing every invalid field.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ValidatedJson<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for ValidatedJson<T>
+where
+    T: DeserializeOwned + Validate,
+    S: Send + Sync,
+{
+    type Rejection = ProblemDetails;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let Json(value) = Json::<T>::from_request(req, state)
+            .await
+            .map_err(|rejection: JsonRejection| {
+                ProblemDetails::new(rejection.status(), "invalid_body").detail(rejection.body_text())
+            })?;
+
+        value.validate()?;
+
+        Ok(ValidatedJson(value))
+    }
+}
+
+// ============================================
+// Tests
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, routing::post, Router};
+    use serde::Deserialize;
+    use tower::ServiceExt;
+
+    #[derive(Debug, Deserialize, Validate)]
+    struct Comment {
+        #[validate(length(min = 1, max = 10))]
+        body: String,
+    }
+
+    async fn create(ValidatedJson(comment): ValidatedJson<Comment>) -> String {
+        comment.body
+    }
+
+    async fn post_body(body: &str) -> (StatusCode, Vec<u8>) {
+        let response = Router::new()
+            .route("/", post(create))
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/")
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, bytes.to_vec())
+    }
+
+    #[tokio::test]
+    async fn test_validated_json_accepts_valid_body() {
+        let (status, body) = post_body(r#"{"body":"hello"}"#).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, b"hello");
+    }
+
+    #[tokio::test]
+    async fn test_validated_json_reports_field_errors() {
+        let (status, body) = post_body(r#"{"body":"far too long here"}"#).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let problem: ProblemDetails = serde_json::from_slice(&body).unwrap();
+        assert_eq!(problem.code, "validation_error");
+        assert_eq!(problem.errors.len(), 1);
+        assert_eq!(problem.errors[0].field, "body");
+        assert_eq!(problem.errors[0].code, "length");
+        assert_eq!(problem.errors[0].rejected_value, Some(serde_json::json!("far too long here")));
+    }
+
+    #[tokio::test]
+    async fn test_validated_json_rejects_malformed_body() {
+        let (status, body) = post_body("{").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let problem: ProblemDetails = serde_json::from_slice(&body).unwrap();
+        assert_eq!(problem.code, "invalid_body");
+    }
+}
@@ -3,7 +3,10 @@
 //! REST API endpoints for authentication operations.
 
 use crate::error::AuthError;
-use crate::extractors::{AuthUser, ClientInfo};
+use crate::extractors::{AuthUser, ClientInfo, ValidatedJson};
+use crate::i18n::{self, t};
+use crate::mail;
+use crate::problem::ProblemDetails;
 use crate::middleware;
 use crate::models::*;
 use crate::service::AuthService;
@@ -17,7 +20,6 @@ use axum::{
     Json, Router,
 };
 use std::sync::Arc;
-use validator::Validate;
 
 /// Shared auth service state
 pub type AuthState = Arc<AuthService>;
@@ -36,18 +38,25 @@ pub fn create_routes(auth_service: Arc<AuthService>) -> Router {
         .route("/auth/refresh", post(refresh_token))
         .route("/auth/forgot-password", post(forgot_password))
         .route("/auth/reset-password", post(reset_password))
-        .route("/auth/verify-email", post(verify_email));
+        .route("/auth/change-expired-password", post(change_expired_password))
+        .route("/auth/verify-email", post(verify_email))
+        .route("/auth/confirm-login", post(confirm_login));
 
     // Protected routes (require authentication)
     let protected = Router::new()
         .route("/auth/me", get(get_current_user))
+        .route("/auth/me/locale", get(get_locale).put(set_locale))
         .route("/auth/change-password", post(change_password))
         .route("/auth/resend-verification", post(resend_verification))
         .layer(axum_middleware::from_fn(middleware::require_auth));
 
+    // Account endpoints share one policy (CORS_* env vars)
+    let cors = auth_service.config().cors.layer();
+
     Router::new()
         .merge(public)
         .merge(protected)
+        .layer(cors)
         .with_state(auth_service)
 }
 
@@ -58,32 +67,46 @@ pub fn create_routes(auth_service: Arc<AuthService>) -> Router {
 /// POST /auth/register
 ///
 /// Register a new user account
+#[utoipa::path(
+    post,
+    path = "/auth/register",
+    tag = "auth",
+    request_body = RegisterRequest,
+    responses(
+        (status = 201, description = "User registered", body = UserResponse),
+        (status = 400, description = "Validation failed", body = ProblemDetails),
+        (status = 403, description = "Email domain not allowed to register", body = ProblemDetails),
+        (status = 409, description = "Email already registered", body = ProblemDetails)
+    )
+)]
 pub async fn register(
     State(auth): State<AuthState>,
-    Json(req): Json<RegisterRequest>,
+    ValidatedJson(req): ValidatedJson<RegisterRequest>,
 ) -> Result<impl IntoResponse, AuthError> {
-    // Validate request
-    req.validate()
-        .map_err(|e| AuthError::Validation(e.to_string()))?;
-
     // Register user
     let user = auth.register(req).await?;
 
-    // Create email verification token
+    // Queue the verification mail; the outbox worker delivers it
     let verification_token = auth.create_email_verification(user.id).await?;
+    let email = mail::verification_email(
+        &auth.config().mail,
+        &user.mail_locale(),
+        &user.email,
+        &user.name,
+        &verification_token,
+    );
+    mail::enqueue(auth.db(), &email).await?;
 
     tracing::info!(
         user_id = %user.id,
-        "User registered, verification token created"
+        "User registered, verification mail queued"
     );
 
     Ok((
         StatusCode::CREATED,
         Json(serde_json::json!({
-            "message": "Registration successful. Please verify your email.",
-            "user": UserResponse::from(user),
-            // In production, don't return this - send via email
-            "verification_token": verification_token
+            "message": t("msg-registered"),
+            "user": UserResponse::from(user)
         })),
     ))
 }
@@ -95,15 +118,22 @@ pub async fn register(
 /// POST /auth/login
 ///
 /// Authenticate user and return access/refresh tokens
+#[utoipa::path(
+    post,
+    path = "/auth/login",
+    tag = "auth",
+    request_body = LoginRequest,
+    responses(
+        (status = 200, description = "Authenticated", body = AuthResponse),
+        (status = 401, description = "Invalid credentials", body = ProblemDetails),
+        (status = 403, description = "Account locked or inactive, password expired, or a suspicious login awaiting confirmation", body = ProblemDetails)
+    )
+)]
 pub async fn login(
     State(auth): State<AuthState>,
     ClientInfo { ip, user_agent }: ClientInfo,
-    Json(req): Json<LoginRequest>,
+    ValidatedJson(req): ValidatedJson<LoginRequest>,
 ) -> Result<impl IntoResponse, AuthError> {
-    // Validate request
-    req.validate()
-        .map_err(|e| AuthError::Validation(e.to_string()))?;
-
     // Attempt login
     let response = auth.login(req, ip, user_agent).await?;
 
@@ -113,13 +143,22 @@ pub async fn login(
 /// POST /auth/logout
 ///
 /// Revoke refresh token and logout user
+#[utoipa::path(
+    post,
+    path = "/auth/logout",
+    tag = "auth",
+    request_body = RefreshTokenRequest,
+    responses(
+        (status = 200, description = "Logged out", body = MessageResponse)
+    )
+)]
 pub async fn logout(
     State(auth): State<AuthState>,
     Json(req): Json<RefreshTokenRequest>,
 ) -> Result<impl IntoResponse, AuthError> {
     auth.logout(&req.refresh_token).await?;
 
-    Ok(Json(MessageResponse::new("Logged out successfully")))
+    Ok(Json(MessageResponse::new(t("msg-logged-out"))))
 }
 
 // ============================================
@@ -129,14 +168,21 @@ pub async fn logout(
 /// POST /auth/refresh
 ///
 /// Refresh access token using refresh token
+#[utoipa::path(
+    post,
+    path = "/auth/refresh",
+    tag = "auth",
+    request_body = RefreshTokenRequest,
+    responses(
+        (status = 200, description = "Tokens rotated", body = TokenResponse),
+        (status = 401, description = "Invalid or revoked refresh token", body = ProblemDetails)
+    )
+)]
 pub async fn refresh_token(
     State(auth): State<AuthState>,
     ClientInfo { ip, user_agent }: ClientInfo,
-    Json(req): Json<RefreshTokenRequest>,
+    ValidatedJson(req): ValidatedJson<RefreshTokenRequest>,
 ) -> Result<impl IntoResponse, AuthError> {
-    req.validate()
-        .map_err(|e| AuthError::Validation(e.to_string()))?;
-
     let response = auth.refresh_tokens(&req.refresh_token, ip, user_agent).await?;
 
     Ok(Json(response))
@@ -149,59 +195,102 @@ pub async fn refresh_token(
 /// POST /auth/forgot-password
 ///
 /// Initiate password reset process
+#[utoipa::path(
+    post,
+    path = "/auth/forgot-password",
+    tag = "auth",
+    request_body = ForgotPasswordRequest,
+    responses(
+        (status = 200, description = "Reset initiated if the account exists", body = MessageResponse)
+    )
+)]
 pub async fn forgot_password(
     State(auth): State<AuthState>,
-    Json(req): Json<ForgotPasswordRequest>,
+    ValidatedJson(req): ValidatedJson<ForgotPasswordRequest>,
 ) -> Result<impl IntoResponse, AuthError> {
-    req.validate()
-        .map_err(|e| AuthError::Validation(e.to_string()))?;
-
-    // Generate reset token
+    // Generate reset token; empty when no account has the address
     let token = auth.forgot_password(&req.email).await?;
+    if !token.is_empty() {
+        let locale = match auth.get_user_by_email(&req.email).await? {
+            Some(user) => user.mail_locale(),
+            None => i18n::current_locale(),
+        };
+        let email = mail::password_reset_email(&auth.config().mail, &locale, &req.email, &token);
+        mail::enqueue(auth.db(), &email).await?;
+    }
 
-    // In production, send token via email, don't return it
-    // Always return success to prevent email enumeration
-
-    Ok(Json(serde_json::json!({
-        "message": "If an account with that email exists, a password reset link has been sent.",
-        // In production, remove this line - send via email
-        "reset_token": if !token.is_empty() { Some(token) } else { None }
-    })))
+    // Same response either way to prevent email enumeration
+    Ok(Json(MessageResponse::new(t("msg-reset-requested"))))
 }
 
 /// POST /auth/reset-password
 ///
 /// Complete password reset with token
+#[utoipa::path(
+    post,
+    path = "/auth/reset-password",
+    tag = "auth",
+    request_body = ResetPasswordRequest,
+    responses(
+        (status = 200, description = "Password reset", body = MessageResponse),
+        (status = 401, description = "Invalid or expired token", body = ProblemDetails)
+    )
+)]
 pub async fn reset_password(
     State(auth): State<AuthState>,
-    Json(req): Json<ResetPasswordRequest>,
+    ValidatedJson(req): ValidatedJson<ResetPasswordRequest>,
 ) -> Result<impl IntoResponse, AuthError> {
-    req.validate()
-        .map_err(|e| AuthError::Validation(e.to_string()))?;
-
     auth.reset_password(req).await?;
 
-    Ok(Json(MessageResponse::new(
-        "Password reset successful. Please login with your new password.",
-    )))
+    Ok(Json(MessageResponse::new(t("msg-password-reset"))))
 }
 
 /// POST /auth/change-password
 ///
 /// Change password for authenticated user
+#[utoipa::path(
+    post,
+    path = "/auth/change-password",
+    tag = "auth",
+    request_body = ChangePasswordRequest,
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Password changed", body = MessageResponse),
+        (status = 400, description = "One of the last passwords reused", body = ProblemDetails),
+        (status = 401, description = "Not authenticated or wrong password", body = ProblemDetails)
+    )
+)]
 pub async fn change_password(
     State(auth): State<AuthState>,
     user: AuthUser,
-    Json(req): Json<ChangePasswordRequest>,
+    ValidatedJson(req): ValidatedJson<ChangePasswordRequest>,
 ) -> Result<impl IntoResponse, AuthError> {
-    req.validate()
-        .map_err(|e| AuthError::Validation(e.to_string()))?;
-
     auth.change_password(user.id, req).await?;
 
-    Ok(Json(MessageResponse::new(
-        "Password changed successfully. Please login again on all devices.",
-    )))
+    Ok(Json(MessageResponse::new(t("msg-password-changed"))))
+}
+
+/// POST /auth/change-expired-password
+///
+/// Change an expired password, which blocks login
+#[utoipa::path(
+    post,
+    path = "/auth/change-expired-password",
+    tag = "auth",
+    request_body = ExpiredPasswordChangeRequest,
+    responses(
+        (status = 200, description = "Password changed", body = MessageResponse),
+        (status = 400, description = "One of the last passwords reused", body = ProblemDetails),
+        (status = 401, description = "Invalid credentials", body = ProblemDetails)
+    )
+)]
+pub async fn change_expired_password(
+    State(auth): State<AuthState>,
+    ValidatedJson(req): ValidatedJson<ExpiredPasswordChangeRequest>,
+) -> Result<impl IntoResponse, AuthError> {
+    auth.change_expired_password(req).await?;
+
+    Ok(Json(MessageResponse::new(t("msg-expired-password-changed"))))
 }
 
 // ============================================
@@ -211,24 +300,63 @@ pub async fn change_password(
 /// POST /auth/verify-email
 ///
 /// Verify email address with token
+#[utoipa::path(
+    post,
+    path = "/auth/verify-email",
+    tag = "auth",
+    request_body = VerifyEmailRequest,
+    responses(
+        (status = 200, description = "Email verified", body = UserResponse),
+        (status = 401, description = "Invalid or expired token", body = ProblemDetails)
+    )
+)]
 pub async fn verify_email(
     State(auth): State<AuthState>,
-    Json(req): Json<VerifyEmailRequest>,
+    ValidatedJson(req): ValidatedJson<VerifyEmailRequest>,
 ) -> Result<impl IntoResponse, AuthError> {
-    req.validate()
-        .map_err(|e| AuthError::Validation(e.to_string()))?;
-
     let user = auth.verify_email(&req.token).await?;
 
     Ok(Json(serde_json::json!({
-        "message": "Email verified successfully",
+        "message": t("msg-email-verified"),
         "user": UserResponse::from(user)
     })))
 }
 
+/// POST /auth/confirm-login
+///
+/// Confirm a login from a new country, network or device with the token from
+/// its alert mail; the user can then log in from there
+#[utoipa::path(
+    post,
+    path = "/auth/confirm-login",
+    tag = "auth",
+    request_body = ConfirmLoginRequest,
+    responses(
+        (status = 200, description = "Login confirmed", body = MessageResponse),
+        (status = 401, description = "Invalid or expired token", body = ProblemDetails)
+    )
+)]
+pub async fn confirm_login(
+    State(auth): State<AuthState>,
+    ValidatedJson(req): ValidatedJson<ConfirmLoginRequest>,
+) -> Result<Json<MessageResponse>, AuthError> {
+    auth.confirm_login(&req.token).await?;
+    Ok(Json(MessageResponse::new(t("msg-login-confirmed"))))
+}
+
 /// POST /auth/resend-verification
 ///
 /// Resend email verification token
+#[utoipa::path(
+    post,
+    path = "/auth/resend-verification",
+    tag = "auth",
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Verification email sent", body = MessageResponse),
+        (status = 401, description = "Not authenticated", body = ProblemDetails)
+    )
+)]
 pub async fn resend_verification(
     State(auth): State<AuthState>,
     user: AuthUser,
@@ -241,17 +369,22 @@ pub async fn resend_verification(
 
     if full_user.email_verified_at.is_some() {
         return Ok(Json(serde_json::json!({
-            "message": "Email is already verified"
+            "message": t("msg-already-verified")
         })));
     }
 
     let token = auth.create_email_verification(user.id).await?;
+    let email = mail::verification_email(
+        &auth.config().mail,
+        &full_user.mail_locale(),
+        &full_user.email,
+        &full_user.name,
+        &token,
+    );
+    mail::enqueue(auth.db(), &email).await?;
 
-    // In production, send via email
     Ok(Json(serde_json::json!({
-        "message": "Verification email sent",
-        // In production, remove this - send via email
-        "verification_token": token
+        "message": t("msg-verification-sent")
     })))
 }
 
@@ -262,6 +395,16 @@ pub async fn resend_verification(
 /// GET /auth/me
 ///
 /// Get current user profile
+#[utoipa::path(
+    get,
+    path = "/auth/me",
+    tag = "auth",
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Current user"),
+        (status = 401, description = "Not authenticated", body = ProblemDetails)
+    )
+)]
 pub async fn get_current_user(user: AuthUser) -> Result<impl IntoResponse, AuthError> {
     Ok(Json(serde_json::json!({
         "user": {
@@ -272,3 +415,46 @@ pub async fn get_current_user(user: AuthUser) -> Result<impl IntoResponse, AuthE
         }
     })))
 }
+
+/// GET /auth/me/locale
+///
+/// The current user's preferred locale and the available ones
+#[utoipa::path(
+    get,
+    path = "/auth/me/locale",
+    tag = "auth",
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Preferred locale", body = LocaleResponse),
+        (status = 401, description = "Not authenticated", body = ProblemDetails)
+    )
+)]
+pub async fn get_locale(State(auth): State<AuthState>, user: AuthUser) -> Result<Json<LocaleResponse>, AuthError> {
+    let user = auth.get_user(user.id).await?.ok_or(AuthError::UserNotFound)?;
+    Ok(Json(LocaleResponse::new(user.locale)))
+}
+
+/// PUT /auth/me/locale
+///
+/// Set the current user's preferred locale, or clear it with `null` to
+/// negotiate it from `Accept-Language`; applies from the next token refresh
+#[utoipa::path(
+    put,
+    path = "/auth/me/locale",
+    tag = "auth",
+    request_body = UpdateLocaleRequest,
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Preferred locale changed", body = LocaleResponse),
+        (status = 400, description = "Locale not available", body = ProblemDetails),
+        (status = 401, description = "Not authenticated", body = ProblemDetails)
+    )
+)]
+pub async fn set_locale(
+    State(auth): State<AuthState>,
+    user: AuthUser,
+    Json(req): Json<UpdateLocaleRequest>,
+) -> Result<Json<LocaleResponse>, AuthError> {
+    let user = auth.set_locale(user.id, req.locale.as_deref()).await?;
+    Ok(Json(LocaleResponse::new(user.locale)))
+}
@@ -0,0 +1,406 @@
+//! Localization
+//!
+//! Problem details, response messages, validation errors and emails are
+//! looked up by message ID in Fluent (`.ftl`) catalogs. English, German and
+//! French are bundled from `locales/`; apps and plugins add locales or
+//! messages with [`add_resource`]. Catalogs use the Fluent subset this module
+//! parses: `id = text` messages, indented continuation lines, comments, and
+//! `{ $variable }` / `{ "literal" }` placeables.
+//!
+//! Each request is handled in one locale: the user's preferred one (stored
+//! in `users.locale`, carried by the access token and applied by the auth
+//! middleware), else the best match for `Accept-Language` (see
+//! [`negotiate_locale`]), else English. Messages missing from a locale fall
+//! back to its language (`pt-br` to `pt`), then to English.
+
+use axum::{
+    extract::Request,
+    http::{header, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{OnceLock, RwLock};
+
+/// Locale used when nothing better is known, and the last fallback
+pub const DEFAULT_LOCALE: &str = "en";
+
+/// Catalogs shipped with the plugin
+const BUNDLED: &[(&str, &str)] = &[
+    ("en", include_str!("../locales/en/auth.ftl")),
+    ("de", include_str!("../locales/de/auth.ftl")),
+    ("fr", include_str!("../locales/fr/auth.ftl")),
+];
+
+tokio::task_local! {
+    static LOCALE: String;
+}
+
+/// A catalog that failed to parse
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("line {line}: {message}")]
+pub struct ParseError {
+    pub line: usize,
+    pub message: String,
+}
+
+/// Piece of a message
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Text(String),
+    Variable(String),
+}
+
+type Catalog = HashMap<String, Vec<Part>>;
+
+fn catalogs() -> &'static RwLock<HashMap<String, Catalog>> {
+    static CATALOGS: OnceLock<RwLock<HashMap<String, Catalog>>> = OnceLock::new();
+    CATALOGS.get_or_init(|| {
+        let catalogs = BUNDLED
+            .iter()
+            .map(|(locale, source)| {
+                let catalog = parse(source).unwrap_or_else(|e| panic!("locales/{}/auth.ftl: {}", locale, e));
+                (locale.to_string(), catalog)
+            })
+            .collect();
+        RwLock::new(catalogs)
+    })
+}
+
+/// Canonical form of a language tag (`pt_BR` -> `pt-br`)
+fn normalize(locale: &str) -> String {
+    locale.trim().replace('_', "-").to_ascii_lowercase()
+}
+
+/// Add the messages of a Fluent resource to `locale`, replacing messages
+/// with the same IDs; adding to a new locale makes it available
+pub fn add_resource(locale: &str, source: &str) -> Result<(), ParseError> {
+    let messages = parse(source)?;
+    catalogs()
+        .write()
+        .unwrap()
+        .entry(normalize(locale))
+        .or_default()
+        .extend(messages);
+    Ok(())
+}
+
+/// Locales with a catalog, sorted
+pub fn available_locales() -> Vec<String> {
+    let mut locales: Vec<String> = catalogs().read().unwrap().keys().cloned().collect();
+    locales.sort();
+    locales
+}
+
+/// Whether `locale` has a catalog
+pub fn is_supported(locale: &str) -> bool {
+    catalogs().read().unwrap().contains_key(&normalize(locale))
+}
+
+/// Locale of the request being handled
+pub fn current_locale() -> String {
+    LOCALE.try_with(|locale| locale.clone()).unwrap_or_else(|_| DEFAULT_LOCALE.to_string())
+}
+
+/// Run `future` in `locale`
+pub async fn in_locale<F: Future>(locale: &str, future: F) -> F::Output {
+    LOCALE.scope(normalize(locale), future).await
+}
+
+/// Run a request handler in `locale` and label the response with it
+pub async fn localized(locale: &str, response: impl Future<Output = Response>) -> Response {
+    let locale = normalize(locale);
+    let mut response = LOCALE.scope(locale.clone(), response).await;
+    if let Ok(value) = HeaderValue::from_str(&locale) {
+        response.headers_mut().insert(header::CONTENT_LANGUAGE, value);
+    }
+    response
+}
+
+/// Message `id` in the current locale
+pub fn t(id: &str) -> String {
+    translate(&current_locale(), id, &[])
+}
+
+/// Message `id` in the current locale, with variables
+pub fn t_args(id: &str, args: &[(&str, &str)]) -> String {
+    translate(&current_locale(), id, args)
+}
+
+/// Message `id` in `locale`, falling back to English and then to the ID
+pub fn translate(locale: &str, id: &str, args: &[(&str, &str)]) -> String {
+    lookup(locale, id, args)
+        .or_else(|| lookup(DEFAULT_LOCALE, id, args))
+        .unwrap_or_else(|| id.to_string())
+}
+
+/// Message `id` in `locale` or its language, without the English fallback
+pub fn lookup(locale: &str, id: &str, args: &[(&str, &str)]) -> Option<String> {
+    let locale = normalize(locale);
+    let catalogs = catalogs().read().unwrap();
+    let find = |locale: &str| catalogs.get(locale).and_then(|catalog| catalog.get(id));
+    let parts = find(&locale).or_else(|| locale.split_once('-').and_then(|(language, _)| find(language)))?;
+    Some(format(parts, args))
+}
+
+fn format(parts: &[Part], args: &[(&str, &str)]) -> String {
+    let mut out = String::new();
+    for part in parts {
+        match part {
+            Part::Text(text) => out.push_str(text),
+            Part::Variable(name) => match args.iter().find(|(key, _)| key == name) {
+                Some((_, value)) => out.push_str(value),
+                // As Fluent does, show what's missing rather than nothing
+                None => {
+                    out.push_str("{$");
+                    out.push_str(name);
+                    out.push('}');
+                }
+            },
+        }
+    }
+    out
+}
+
+// ============================================
+// Negotiation
+// ============================================
+
+/// Best available locale for an `Accept-Language` header
+///
+/// Ranges are tried by descending `q`, each exactly and then by its
+/// language (`de-AT` matches `de`). `*` and `q=0` ranges are ignored.
+pub fn negotiate(accept_language: &str) -> Option<String> {
+    let mut ranges: Vec<(String, f32)> = accept_language
+        .split(',')
+        .filter_map(|range| {
+            let mut params = range.split(';');
+            let tag = normalize(params.next()?);
+            let q = params
+                .find_map(|p| p.trim().strip_prefix("q="))
+                .map_or(Some(1.0), |q| q.trim().parse::<f32>().ok())?;
+            (!tag.is_empty() && tag != "*" && q > 0.0).then_some((tag, q))
+        })
+        .collect();
+    // Stable, so equal weights keep the client's order
+    ranges.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    ranges.into_iter().find_map(|(tag, _)| {
+        if is_supported(&tag) {
+            return Some(tag);
+        }
+        let (language, _) = tag.split_once('-')?;
+        is_supported(language).then(|| language.to_string())
+    })
+}
+
+/// Middleware running each request in the locale negotiated from its
+/// `Accept-Language` header
+///
+/// The response carries `Content-Language` (unless a signed-in user's
+/// preference already set it) and `Vary: Accept-Language`.
+pub async fn negotiate_locale(req: Request, next: Next) -> Response {
+    let locale = req
+        .headers()
+        .get(header::ACCEPT_LANGUAGE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(negotiate)
+        .unwrap_or_else(|| DEFAULT_LOCALE.to_string());
+
+    let mut response = LOCALE.scope(locale.clone(), next.run(req)).await;
+    let headers = response.headers_mut();
+    if let Ok(value) = HeaderValue::from_str(&locale) {
+        headers.entry(header::CONTENT_LANGUAGE).or_insert(value);
+    }
+    headers.append(header::VARY, HeaderValue::from_static("accept-language"));
+    response
+}
+
+// ============================================
+// Parsing
+// ============================================
+
+fn is_identifier(id: &str) -> bool {
+    let mut chars = id.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Parse a Fluent resource
+fn parse(source: &str) -> Result<Catalog, ParseError> {
+    let mut catalog = Catalog::new();
+    let mut lines = source.lines().enumerate().peekable();
+
+    while let Some((index, line)) = lines.next() {
+        let line_number = index + 1;
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let error = |message: &str| ParseError {
+            line: line_number,
+            message: message.to_string(),
+        };
+        if line.starts_with(char::is_whitespace) {
+            return Err(error("indented line outside of a message"));
+        }
+        let (id, inline) = line.split_once('=').ok_or_else(|| error("expected `id = value`"))?;
+        let id = id.trim();
+        if !is_identifier(id) {
+            return Err(error(&format!("invalid message ID `{}`", id)));
+        }
+
+        // Continuation lines are indented; blank lines between them belong
+        // to the message, blank lines after it don't
+        let mut block: Vec<&str> = Vec::new();
+        while let Some((_, next)) = lines.peek() {
+            if next.trim().is_empty() || next.starts_with(char::is_whitespace) {
+                block.push(next);
+                lines.next();
+            } else {
+                break;
+            }
+        }
+        while block.last().is_some_and(|l| l.trim().is_empty()) {
+            block.pop();
+        }
+        let indent = block
+            .iter()
+            .filter(|l| !l.trim().is_empty())
+            .map(|l| l.len() - l.trim_start().len())
+            .min()
+            .unwrap_or(0);
+
+        let mut text: Vec<&str> = Vec::new();
+        if !inline.trim().is_empty() {
+            text.push(inline.trim());
+        }
+        text.extend(block.iter().map(|l| l.get(indent..).unwrap_or("").trim_end()));
+        if text.is_empty() {
+            return Err(error(&format!("message `{}` has no value", id)));
+        }
+
+        let parts = parse_pattern(&text.join("\n")).map_err(|message| error(&message))?;
+        catalog.insert(id.to_string(), parts);
+    }
+
+    Ok(catalog)
+}
+
+/// Split message text into text and placeables
+fn parse_pattern(text: &str) -> Result<Vec<Part>, String> {
+    let mut parts = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find('{') {
+        // A string literal may hold braces, so the placeable ends after it
+        let inner = &rest[start + 1..];
+        let from = match inner.trim_start().strip_prefix('"') {
+            Some(literal) => {
+                let quoted = inner.len() - literal.len();
+                quoted + literal.find('"').ok_or_else(|| "unclosed string literal".to_string())? + 1
+            }
+            None => 0,
+        };
+        let end = inner[from..]
+            .find('}')
+            .map(|end| start + 1 + from + end)
+            .ok_or_else(|| "unclosed `{`".to_string())?;
+        if start > 0 {
+            parts.push(Part::Text(rest[..start].to_string()));
+        }
+        let expression = rest[start + 1..end].trim();
+        if let Some(name) = expression.strip_prefix('$').filter(|name| is_identifier(name)) {
+            parts.push(Part::Variable(name.to_string()));
+        } else if let Some(literal) = expression
+            .strip_prefix('"')
+            .and_then(|e| e.strip_suffix('"'))
+            .filter(|l| !l.contains('"'))
+        {
+            parts.push(Part::Text(literal.to_string()));
+        } else {
+            return Err(format!("unsupported expression `{{ {} }}`", expression));
+        }
+        rest = &rest[end + 1..];
+    }
+    if !rest.is_empty() {
+        parts.push(Part::Text(rest.to_string()));
+    }
+    Ok(parts)
+}
+
+// ============================================
+// Tests
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_messages() {
+        let catalog = parse(
+            "# comment\n\
+             greeting = Hello { $name }!\n\
+             \n\
+             body =\n    First line\n\n      indented\n    { \"{\" }braces{ \"}\" }\n\n\
+             next = Done\n",
+        )
+        .unwrap();
+
+        assert_eq!(format(&catalog["greeting"], &[("name", "Ada")]), "Hello Ada!");
+        assert_eq!(format(&catalog["greeting"], &[]), "Hello {$name}!");
+        assert_eq!(format(&catalog["body"], &[]), "First line\n\n  indented\n{braces}");
+        assert_eq!(format(&catalog["next"], &[]), "Done");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(parse("ok = Fine\nno value here").unwrap_err().line, 2);
+        assert_eq!(parse("empty =\n").unwrap_err().line, 1);
+        assert_eq!(parse("a = { $name").unwrap_err().message, "unclosed `{`");
+        assert!(parse("a = { NUMBER($n) }").is_err());
+        assert!(parse("1a = Hello").is_err());
+    }
+
+    #[test]
+    fn test_bundled_catalogs_are_complete() {
+        let catalogs = catalogs().read().unwrap();
+        let english = &catalogs[DEFAULT_LOCALE];
+        for (locale, _) in BUNDLED {
+            let missing: Vec<&String> = english.keys().filter(|id| !catalogs[*locale].contains_key(*id)).collect();
+            assert!(missing.is_empty(), "{} is missing {:?}", locale, missing);
+        }
+    }
+
+    #[test]
+    fn test_negotiate() {
+        assert_eq!(negotiate("de-AT, en;q=0.5").as_deref(), Some("de"));
+        assert_eq!(negotiate("es, fr;q=0.8, de;q=0.9").as_deref(), Some("de"));
+        assert_eq!(negotiate("fr;q=0, en;q=0.1").as_deref(), Some("en"));
+        assert_eq!(negotiate("FR_ca").as_deref(), Some("fr"));
+        assert_eq!(negotiate("es, *"), None);
+        assert_eq!(negotiate(""), None);
+    }
+
+    #[test]
+    fn test_fallbacks() {
+        add_resource("pt-BR", "auth-invalid-credentials = Credenciais inválidas").unwrap();
+        add_resource("pt", "auth-user-not-found = Usuário não encontrado").unwrap();
+
+        assert!(is_supported("pt_br"));
+        assert_eq!(translate("pt-br", "auth-invalid-credentials", &[]), "Credenciais inválidas");
+        // Language, then English, then the ID
+        assert_eq!(translate("pt-br", "auth-user-not-found", &[]), "Usuário não encontrado");
+        assert_eq!(translate("pt-br", "auth-email-exists", &[]), "Email already registered");
+        assert_eq!(translate("pt-br", "no-such-message", &[]), "no-such-message");
+        assert_eq!(lookup("pt-br", "auth-email-exists", &[]), None);
+    }
+
+    #[tokio::test]
+    async fn test_current_locale() {
+        assert_eq!(current_locale(), DEFAULT_LOCALE);
+        let message = in_locale("de", async { t("auth-user-not-found") }).await;
+        assert_eq!(message, "Benutzer nicht gefunden");
+        assert_eq!(t("auth-user-not-found"), "User not found");
+    }
+}
@@ -0,0 +1,220 @@
+//! JWT Keys
+//!
+//! Signing and verification keys derived from the `jwt.secret` secret,
+//! swapped atomically when the secret provider returns a new value. After a
+//! rotation new tokens are signed with the new key, and tokens signed with
+//! the previous key keep validating until they expire, so signed-in users
+//! aren't logged out.
+
+use crate::config::{Config, DEFAULT_AUDIENCE, DEFAULT_ISSUER};
+use crate::error::AuthError;
+use crate::secrets::{self, SecretProvider};
+
+use arc_swap::ArcSwap;
+use jsonwebtoken::errors::ErrorKind;
+use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, TokenData, Validation};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+use tokio::sync::OnceCell;
+
+/// Shortest accepted signing secret
+pub const MIN_SECRET_LEN: usize = 32;
+
+static SHARED: OnceCell<Arc<JwtKeys>> = OnceCell::const_new();
+
+struct KeySet {
+    secret: String,
+    encoding: EncodingKey,
+    decoding: DecodingKey,
+    /// Key of the secret before the last rotation
+    previous: Option<DecodingKey>,
+}
+
+impl KeySet {
+    fn new(secret: String, previous: Option<DecodingKey>) -> Self {
+        Self {
+            encoding: EncodingKey::from_secret(secret.as_bytes()),
+            decoding: DecodingKey::from_secret(secret.as_bytes()),
+            secret,
+            previous,
+        }
+    }
+}
+
+/// Current JWT keys, reloadable from a [`SecretProvider`]
+pub struct JwtKeys {
+    provider: Option<Arc<dyn SecretProvider>>,
+    keys: ArcSwap<KeySet>,
+}
+
+impl JwtKeys {
+    /// Keys for a fixed secret, never reloaded
+    pub fn from_secret(secret: &str) -> Self {
+        Self {
+            provider: None,
+            keys: ArcSwap::from_pointee(KeySet::new(secret.to_string(), None)),
+        }
+    }
+
+    /// Keys for the provider's current `jwt.secret`
+    pub async fn load(provider: Arc<dyn SecretProvider>) -> Result<Self, AuthError> {
+        let secret = checked(provider.require("jwt.secret").await?)?;
+        Ok(Self {
+            provider: Some(provider),
+            keys: ArcSwap::from_pointee(KeySet::new(secret, None)),
+        })
+    }
+
+    /// The process-wide keys for the shared secret provider, reloaded every
+    /// `secrets.refresh_secs`
+    pub async fn shared() -> Result<Arc<Self>, AuthError> {
+        SHARED
+            .get_or_try_init(|| async {
+                let keys = Arc::new(Self::load(secrets::shared().await?).await?);
+                if let Some(interval) = secrets::refresh_interval(&*Config::load()?)? {
+                    keys.spawn_refresh(interval);
+                }
+                Ok::<_, AuthError>(keys)
+            })
+            .await
+            .cloned()
+    }
+
+    /// Re-read the secret; `true` when it changed and the keys were swapped
+    pub async fn reload(&self) -> Result<bool, AuthError> {
+        let Some(provider) = &self.provider else {
+            return Ok(false);
+        };
+
+        let secret = checked(provider.require("jwt.secret").await?)?;
+        let current = self.keys.load();
+        if current.secret == secret {
+            return Ok(false);
+        }
+
+        self.keys
+            .store(Arc::new(KeySet::new(secret, Some(current.decoding.clone()))));
+        tracing::info!("JWT signing key rotated");
+        Ok(true)
+    }
+
+    /// Reload every `interval` until the keys are dropped; failures keep the
+    /// current keys
+    pub fn spawn_refresh(self: &Arc<Self>, interval: Duration) -> tokio::task::JoinHandle<()> {
+        let keys: Weak<Self> = Arc::downgrade(self);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                let Some(keys) = keys.upgrade() else {
+                    break;
+                };
+                if let Err(e) = keys.reload().await {
+                    tracing::warn!("JWT key reload failed: {}", e);
+                }
+            }
+        })
+    }
+
+    /// Sign claims with the current key
+    pub fn encode<T: Serialize>(&self, claims: &T) -> Result<String, jsonwebtoken::errors::Error> {
+        encode(&Header::default(), claims, &self.keys.load().encoding)
+    }
+
+    /// Verify a token with the current key, or the previous one
+    pub fn decode<T: DeserializeOwned>(
+        &self,
+        token: &str,
+        validation: &Validation,
+    ) -> Result<TokenData<T>, jsonwebtoken::errors::Error> {
+        let keys = self.keys.load();
+        match decode(token, &keys.decoding, validation) {
+            Err(e) if matches!(e.kind(), ErrorKind::InvalidSignature) => match &keys.previous {
+                Some(previous) => decode(token, previous, validation),
+                None => Err(e),
+            },
+            result => result,
+        }
+    }
+}
+
+/// Access token validation with the `jwt.issuer` and `jwt.audience` in use
+pub fn access_validation(config: &Config) -> Result<Validation, AuthError> {
+    let issuer: String = config.get_or("jwt.issuer", DEFAULT_ISSUER.to_string())?;
+    let audience: String = config.get_or("jwt.audience", DEFAULT_AUDIENCE.to_string())?;
+
+    let mut validation = Validation::default();
+    validation.set_issuer(&[issuer]);
+    validation.set_audience(&[audience]);
+    Ok(validation)
+}
+
+fn checked(secret: String) -> Result<String, AuthError> {
+    if secret.len() < MIN_SECRET_LEN {
+        return Err(AuthError::Config(format!(
+            "jwt.secret must be at least {} characters",
+            MIN_SECRET_LEN
+        )));
+    }
+    Ok(secret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::secrets::StaticSecrets;
+
+    #[derive(Debug, Serialize, serde::Deserialize)]
+    struct Claims {
+        sub: String,
+        exp: i64,
+    }
+
+    fn claims() -> Claims {
+        Claims {
+            sub: "user".to_string(),
+            exp: chrono::Utc::now().timestamp() + 60,
+        }
+    }
+
+    fn validation() -> Validation {
+        let mut validation = Validation::default();
+        validation.validate_aud = false;
+        validation
+    }
+
+    #[tokio::test]
+    async fn test_rotation_keeps_previous_key() {
+        let provider = Arc::new(StaticSecrets::new([("jwt.secret", "a".repeat(32))]));
+        let keys = JwtKeys::load(provider.clone()).await.unwrap();
+        let old_token = keys.encode(&claims()).unwrap();
+
+        assert!(!keys.reload().await.unwrap());
+        provider.set("jwt.secret", "b".repeat(32));
+        assert!(keys.reload().await.unwrap());
+
+        let new_token = keys.encode(&claims()).unwrap();
+        assert_ne!(old_token, new_token);
+        assert!(keys.decode::<Claims>(&old_token, &validation()).is_ok());
+        assert!(keys.decode::<Claims>(&new_token, &validation()).is_ok());
+
+        // Two rotations later the original key is gone
+        provider.set("jwt.secret", "c".repeat(32));
+        assert!(keys.reload().await.unwrap());
+        assert!(keys.decode::<Claims>(&old_token, &validation()).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_short_secret_rejected_on_reload() {
+        let provider = Arc::new(StaticSecrets::new([("jwt.secret", "a".repeat(32))]));
+        let keys = JwtKeys::load(provider.clone()).await.unwrap();
+        let token = keys.encode(&claims()).unwrap();
+
+        provider.set("jwt.secret", "short".to_string());
+        assert!(keys.reload().await.is_err());
+        assert!(keys.decode::<Claims>(&token, &validation()).is_ok());
+    }
+}
@@ -1,24 +1,123 @@
 //! RustPress Authentication Plugin
 //!
 //! Core authentication system for RustPress providing:
-//! - User registration and login
+//! - User registration and login, with email domain allow/deny lists,
+//!   disposable address blocking and registration filters for plugins
 //! - JWT access and refresh token management
 //! - Argon2id password hashing
-//! - Refresh token rotation
-//! - Password reset flow
+//! - Refresh token rotation, with an optional limit on active sessions per
+//!   user
+//! - Password reset flow, with optional password expiry and reuse history
 //! - Email verification
 //! - Account lockout protection
-//! - Role-based access control
+//! - Alerts on logins from a new country, network or device, optionally
+//!   held until confirmed by email
+//! - Role-based access control, with declarative per-resource policies
+//!   checked by the `Authorize<Policy>` extractor
+//! - API versioning with deprecation headers
+//! - Per-route-group CORS policies
+//! - RFC 9457 problem+json error responses
+//! - Security headers with per-request CSP nonces and violation reporting
+//! - Per-plugin versioned migrations (`plugin_migrations` ledger), with
+//!   expand/contract changes for hot tables: dual-write shims, tracked
+//!   backfills and a gate holding back the contract step; see
+//!   [`schema_changes`]
+//! - Database pool partitions (auth, ingest, reporting) with their own
+//!   limits and statement timeouts, and pool gauges at `/metrics`; see
+//!   [`db`]
+//! - Secrets from env/files, Vault or AWS Secrets Manager, with JWT key and
+//!   database credential rotation without restarts
+//! - AES-GCM encryption of sensitive columns with versioned keys
+//! - Route registry mounting plugin APIs under `/api/v1/<plugin-id>` with
+//!   collision detection
+//! - Request and response filters plugins register on route patterns of
+//!   any API, run in priority order
+//! - Queued verification and reset mail with retries, per-domain throttling
+//!   and a bounce/complaint suppression list
+//! - A shared metrics registry exported at `/metrics` in the Prometheus
+//!   format, with per-plugin labels
+//! - Rate-limited admin user management (search, role/status changes,
+//!   unlock, force logout) with an audit log
+//! - Localized problem details, messages and mail (English, German, French),
+//!   in the user's preferred locale or the one negotiated from
+//!   `Accept-Language`; see [`i18n`]
+//! - A deployment doctor (secrets, database, clock, migrations, Redis,
+//!   storage, GeoIP) run at activation, by `rustpress-doctor` and at
+//!   `/api/v1/system/doctor`; see [`doctor`]
 //!
 //! # Configuration
 //!
-//! All configuration is loaded from environment variables:
-//! - `JWT_SECRET` - Secret key for signing JWTs (required, min 32 chars)
-//! - `JWT_ACCESS_EXPIRATION` - Access token expiration in seconds (default: 900)
-//! - `JWT_REFRESH_EXPIRATION` - Refresh token expiration in seconds (default: 604800)
-//! - `JWT_ISSUER` - JWT issuer claim (default: "rustpress")
-//! - `JWT_AUDIENCE` - JWT audience claim (default: "rustpress-api")
-//! - `DATABASE_URL` - PostgreSQL connection string (required)
+//! Apps and plugins share one [`Config`], loaded by `Config::load()` from
+//! `rustpress.toml` (or the file named by `--config` / `RUSTPRESS_CONFIG`),
+//! then environment variables, then `--set key=value` arguments; see
+//! [`config`]. Every key can be set through its environment variable, the key
+//! upper-cased with dots as underscores, and secrets through a `_FILE`
+//! variant naming a file (`JWT_SECRET_FILE=/run/secrets/jwt`). `jwt.secret`
+//! and `database.url` can instead come from a secrets manager; see
+//! [`secrets`]. The `encryption.keys` secret enables encryption of sensitive
+//! columns; see [`crypto`].
+//!
+//! ```toml
+//! [jwt]
+//! secret = "..."              # required, min 32 chars (JWT_SECRET)
+//! access_expiration = 900     # seconds
+//! refresh_expiration = 604800 # seconds
+//! issuer = "rustpress"
+//! audience = "rustpress-api"
+//!
+//! [database]
+//! url = "postgres://localhost/rustpress" # or sqlite:// with the `sqlite` feature
+//! replica_urls = []           # read replicas for `DbPools`
+//! replica_max_lag_ms = 2000   # lag before a replica stops serving reads
+//! replica_check_secs = 5
+//!
+//! [database.pools.auth]        # optional pool partitions; see `db::partition`
+//! max_connections = 10         # unset partitions share the main pool
+//! acquire_timeout_ms = 30000
+//!
+//! [database.pools.reporting]
+//! max_connections = 4
+//! statement_timeout_ms = 30000 # PostgreSQL cancels longer statements; 0 = no limit
+//!
+//! [cors]
+//! allowed_origins = []        # same-origin only; see `cors`
+//! allow_credentials = false
+//!
+//! [auth]
+//! max_login_attempts = 5
+//! min_password_length = 8
+//! require_email_verification = false
+//! password_max_age_days = 0   # days until a password must be changed; 0 = never
+//! password_history = 0        # recent passwords that can't be reused; 0 = any
+//! login_alerts = true         # mail users about logins from a new country, network or device
+//! confirm_suspicious_logins = false # hold such logins until confirmed by email; see `anomaly`
+//! max_sessions = 0            # active logins per user, the oldest ended when exceeded; 0 = no limit
+//! admin_rate_limit = 60       # user management requests per admin and minute
+//!
+//! [registration]
+//! allowed_domains = []        # only these email domains may register; any when empty
+//! denied_domains = []
+//! block_disposable = true     # refuse throwaway mail services; see `registration`
+//!
+//! [metrics]
+//! token = "..."               # bearer token for /metrics (METRICS_TOKEN); open when unset
+//!
+//! [mail]
+//! transport = "log"           # or "smtp" with the `smtp` feature; see `mail`
+//! from = "RustPress <no-reply@example.com>"
+//! verify_url = "https://example.com/verify-email?token={token}"
+//! reset_url = "https://example.com/reset-password?token={token}"
+//! confirm_login_url = "https://example.com/confirm-login?token={token}"
+//!
+//! [doctor]
+//! redis_url = "redis://127.0.0.1/" # checked by the doctor; see `doctor`
+//! storage_dir = "uploads"
+//! ```
+//!
+//! # Database Backends
+//!
+//! PostgreSQL is the default. Build with `--no-default-features --features
+//! sqlite` to run on SQLite; see [`db`].
 //!
 //! # Usage
 //!
@@ -27,35 +126,76 @@
 //!
 //! // Initialize plugin
 //! let plugin = AuthPlugin::new();
-//! plugin.activate(&db_pool).await?;
+//! plugin.activate(db_pool).await?;
+//!
+//! // Mount its routes like any other plugin's
+//! let app = app.merge(plugin.routes().await?.unwrap_or_default());
 //!
 //! // Use auth service
 //! let auth = plugin.auth_service().await.unwrap();
 //! let response = auth.login(login_request, ip, user_agent).await?;
 //! ```
 
+pub mod admin;
+pub mod anomaly;
+pub mod audit;
+pub mod compat;
 pub mod config;
+pub mod cors;
+pub mod crypto;
+pub mod db;
+pub mod doctor;
 pub mod error;
 pub mod extractors;
 pub mod handlers;
+pub mod i18n;
+pub mod keys;
+pub mod mail;
+pub mod metrics;
 pub mod middleware;
+pub mod migrations;
 pub mod models;
+pub mod openapi;
+pub mod policy;
+pub mod problem;
+pub mod registration;
+pub mod route_filters;
+pub mod routes;
+pub mod schema_changes;
+pub mod secrets;
+pub mod security;
 pub mod service;
+pub mod versioning;
 
 // Re-export commonly used types
-pub use config::AuthConfig;
+pub use config::{AuthConfig, Config, ConfigError};
+pub use cors::{CorsConfig, CorsPolicy};
+pub use db::{DbPool, DbPools};
 pub use error::AuthError;
-pub use extractors::{AuthUser, ClientInfo};
+pub use extractors::{AuthUser, ClientInfo, ValidatedJson};
 pub use handlers::AuthState;
+pub use keys::JwtKeys;
+pub use mail::{MailConfig, MailWorker};
+pub use metrics::{MetricsRegistry, PluginMetrics};
+pub use migrations::PluginMigrations;
 pub use models::*;
+pub use policy::{Authorize, Policies, Policy};
+pub use problem::{FieldError, ProblemDetails};
+pub use route_filters::{RequestFilter, ResponseFilter, RouteFilters};
+pub use routes::{PluginRoutes, RouteRegistry};
+pub use secrets::SecretProvider;
+pub use security::{CspNonce, SecurityHeaders};
 pub use service::AuthService;
+pub use versioning::{ApiVersion, Deprecation, VersionedRouter};
 
 use async_trait::async_trait;
 use axum::Router;
-use sqlx::PgPool;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
+/// Plugin id, also the owner of the auth routes in the route registry
+pub const PLUGIN_ID: &str = "rustpress-auth";
+
 // ============================================
 // Plugin Types (Standalone - no external deps)
 // ============================================
@@ -87,13 +227,16 @@ pub trait Plugin: Send + Sync {
     async fn state(&self) -> PluginState;
 
     /// Activate the plugin
-    async fn activate(&self, db: PgPool) -> Result<(), AuthError>;
+    async fn activate(&self, db: DbPool) -> Result<(), AuthError>;
 
     /// Deactivate the plugin
     async fn deactivate(&self) -> Result<(), AuthError>;
 
-    /// Get plugin routes
-    fn routes(&self) -> Option<Router>;
+    /// Get plugin routes, built from the state set up by `activate`
+    ///
+    /// `None` while the plugin is inactive or when it serves no routes; hosts
+    /// call this after activation and merge the router into their own.
+    async fn routes(&self) -> Result<Option<Router>, AuthError>;
 }
 
 // ============================================
@@ -108,7 +251,10 @@ pub struct AuthPlugin {
     state: RwLock<PluginState>,
     config: RwLock<Option<AuthConfig>>,
     auth_service: RwLock<Option<Arc<AuthService>>>,
-    db: RwLock<Option<PgPool>>,
+    db: RwLock<Option<DbPool>>,
+    /// The `auth` pool partition, when configured; closed on deactivation
+    auth_pool: RwLock<Option<DbPool>>,
+    mail_worker: RwLock<Option<Arc<MailWorker>>>,
 }
 
 impl AuthPlugin {
@@ -116,7 +262,7 @@ impl AuthPlugin {
     pub fn new() -> Self {
         Self {
             info: PluginInfo {
-                id: "rustpress-auth".into(),
+                id: PLUGIN_ID.into(),
                 name: "RustPress Authentication".into(),
                 version: env!("CARGO_PKG_VERSION").into(),
                 description: "Core authentication system for RustPress".into(),
@@ -125,6 +271,8 @@ impl AuthPlugin {
             config: RwLock::new(None),
             auth_service: RwLock::new(None),
             db: RwLock::new(None),
+            auth_pool: RwLock::new(None),
+            mail_worker: RwLock::new(None),
         }
     }
 
@@ -138,134 +286,25 @@ impl AuthPlugin {
         self.auth_service.read().await.clone()
     }
 
+    /// Schema migrations shipped with the plugin (`migrations/<backend>/`)
+    pub fn migrations() -> PluginMigrations {
+        #[cfg(feature = "postgres")]
+        let migrator = sqlx::migrate!("./migrations/postgres");
+        #[cfg(feature = "sqlite")]
+        let migrator = sqlx::migrate!("./migrations/sqlite");
+        PluginMigrations::new(PLUGIN_ID, migrator)
+    }
+
     /// Run database migrations
-    async fn run_migrations(&self, db: &PgPool) -> Result<(), AuthError> {
+    async fn run_migrations(&self, db: &DbPool) -> Result<(), AuthError> {
         tracing::info!("Running authentication database migrations");
 
-        // Create user role enum
-        sqlx::query(
-            r#"
-            DO $$ BEGIN
-                CREATE TYPE user_role AS ENUM ('user', 'author', 'editor', 'admin');
-            EXCEPTION
-                WHEN duplicate_object THEN null;
-            END $$;
-            "#,
-        )
-        .execute(db)
-        .await?;
-
-        // Create user status enum
-        sqlx::query(
-            r#"
-            DO $$ BEGIN
-                CREATE TYPE user_status AS ENUM ('pending', 'active', 'suspended', 'deleted');
-            EXCEPTION
-                WHEN duplicate_object THEN null;
-            END $$;
-            "#,
-        )
-        .execute(db)
-        .await?;
-
-        // Create users table
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS users (
-                id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
-                email VARCHAR(255) NOT NULL UNIQUE,
-                password_hash VARCHAR(255) NOT NULL,
-                name VARCHAR(100) NOT NULL,
-                role user_role DEFAULT 'user',
-                status user_status DEFAULT 'pending',
-                avatar VARCHAR(500),
-                bio TEXT,
-                website VARCHAR(500),
-                email_verified_at TIMESTAMPTZ,
-                last_login_at TIMESTAMPTZ,
-                last_login_ip VARCHAR(45),
-                failed_login_attempts INTEGER DEFAULT 0,
-                locked_until TIMESTAMPTZ,
-                password_changed_at TIMESTAMPTZ DEFAULT NOW(),
-                created_at TIMESTAMPTZ DEFAULT NOW(),
-                updated_at TIMESTAMPTZ DEFAULT NOW()
-            );
-            "#,
-        )
-        .execute(db)
-        .await?;
-
-        // Create indexes for users
-        sqlx::query("CREATE INDEX IF NOT EXISTS idx_users_email ON users(email);")
-            .execute(db)
-            .await?;
-        sqlx::query("CREATE INDEX IF NOT EXISTS idx_users_status ON users(status);")
-            .execute(db)
-            .await?;
-
-        // Create refresh tokens table
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS refresh_tokens (
-                id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
-                user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
-                token_hash VARCHAR(255) NOT NULL UNIQUE,
-                expires_at TIMESTAMPTZ NOT NULL,
-                issued_at TIMESTAMPTZ DEFAULT NOW(),
-                revoked_at TIMESTAMPTZ,
-                replaced_by UUID REFERENCES refresh_tokens(id),
-                user_agent TEXT,
-                ip_address VARCHAR(45),
-                created_at TIMESTAMPTZ DEFAULT NOW()
-            );
-            "#,
-        )
-        .execute(db)
-        .await?;
-
-        // Create indexes for refresh tokens
-        sqlx::query("CREATE INDEX IF NOT EXISTS idx_refresh_tokens_user ON refresh_tokens(user_id);")
-            .execute(db)
-            .await?;
-        sqlx::query(
-            "CREATE INDEX IF NOT EXISTS idx_refresh_tokens_expires ON refresh_tokens(expires_at);",
-        )
-        .execute(db)
-        .await?;
-
-        // Create password reset tokens table
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS password_reset_tokens (
-                id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
-                user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
-                token_hash VARCHAR(255) NOT NULL UNIQUE,
-                expires_at TIMESTAMPTZ NOT NULL,
-                used_at TIMESTAMPTZ,
-                created_at TIMESTAMPTZ DEFAULT NOW()
-            );
-            "#,
-        )
-        .execute(db)
-        .await?;
-
-        // Create email verification tokens table
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS email_verification_tokens (
-                id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
-                user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
-                token_hash VARCHAR(255) NOT NULL UNIQUE,
-                expires_at TIMESTAMPTZ NOT NULL,
-                used_at TIMESTAMPTZ,
-                created_at TIMESTAMPTZ DEFAULT NOW()
-            );
-            "#,
-        )
-        .execute(db)
-        .await?;
-
-        tracing::info!("Authentication migrations completed successfully");
+        let applied = Self::migrations()
+            .up(db, None, false)
+            .await
+            .map_err(|e| AuthError::Database(e.to_string()))?;
+
+        tracing::info!(applied = applied.len(), "Authentication migrations completed successfully");
         Ok(())
     }
 }
@@ -286,21 +325,47 @@ impl Plugin for AuthPlugin {
         *self.state.read().await
     }
 
-    async fn activate(&self, db: PgPool) -> Result<(), AuthError> {
+    async fn activate(&self, db: DbPool) -> Result<(), AuthError> {
         tracing::info!("Activating RustPress Authentication plugin");
 
         // Run migrations
         self.run_migrations(&db).await?;
 
-        // Load configuration from environment
-        let config = AuthConfig::from_env();
+        // Load configuration (rustpress.toml, environment, --set overrides)
+        // and the JWT secret from the secret provider
+        let config = AuthConfig::load().await?;
         config.validate()?;
 
-        // Initialize auth service
-        let auth_service = Arc::new(AuthService::new(db.clone(), config.clone()));
+        // Field encryption keys (`encryption.keys`)
+        let secrets = secrets::shared().await?;
+        crypto::init(secrets.clone(), secrets::refresh_interval(&*Config::load()?)?).await?;
+
+        // Logins and mail get their own pool when `[database.pools.auth]` is
+        // set; pool sizes are exported at /metrics
+        let metrics = metrics::shared().plugin(PLUGIN_ID);
+        db::watch_pool(&db, "main", &metrics).map_err(|e| AuthError::Config(e.to_string()))?;
+        let auth_pool = db::partition(db::AUTH_POOL, &metrics).await?;
+        let auth_db = auth_pool.clone().unwrap_or_else(|| db.clone());
+
+        // Initialize auth service; keys follow secret rotations
+        let keys = JwtKeys::shared().await?;
+        let auth_service = Arc::new(AuthService::with_keys(auth_db.clone(), config.clone(), keys));
+
+        // Deliver queued mail in the background; workers stop when dropped
+        let transport = mail::transport_from_config(&config.mail, secrets.as_ref()).await?;
+        let mail_worker = Arc::new(MailWorker::new(auth_db, transport, config.mail.clone()));
+        for _ in 0..config.mail.workers.max(1) {
+            mail_worker.spawn();
+        }
+
+        // Self-check in the background; problems are logged, not fatal
+        let doctor = doctor::Doctor::from_shared().await?.with_db(db.clone());
+        tokio::spawn(async move { doctor.run().await.log() });
 
         // Store state
+        *self.mail_worker.write().await = Some(mail_worker);
         *self.db.write().await = Some(db);
+        *self.auth_pool.write().await = auth_pool;
         *self.config.write().await = Some(config);
         *self.auth_service.write().await = Some(auth_service);
         *self.state.write().await = PluginState::Active;
@@ -312,7 +377,12 @@ impl Plugin for AuthPlugin {
     async fn deactivate(&self) -> Result<(), AuthError> {
         tracing::info!("Deactivating RustPress Authentication plugin");
 
+        routes::shared().unregister(PLUGIN_ID);
+        *self.mail_worker.write().await = None;
         *self.auth_service.write().await = None;
+        if let Some(pool) = self.auth_pool.write().await.take() {
+            pool.close().await;
+        }
         *self.config.write().await = None;
         *self.db.write().await = None;
         *self.state.write().await = PluginState::Inactive;
@@ -321,18 +391,67 @@ impl Plugin for AuthPlugin {
         Ok(())
     }
 
-    fn routes(&self) -> Option<Router> {
-        // Routes are created dynamically when auth_service is available
-        // Use create_routes() function instead
-        None
+    async fn routes(&self) -> Result<Option<Router>, AuthError> {
+        match self.auth_service().await {
+            Some(auth_service) => create_routes(auth_service).map(Some),
+            None => Ok(None),
+        }
     }
 }
 
 /// Create authentication routes
 ///
-/// Call this after activating the plugin to get the router with all auth endpoints.
-pub fn create_routes(auth_service: Arc<AuthService>) -> Router {
-    handlers::create_routes(auth_service)
+/// [`AuthPlugin::routes`] builds these once the plugin is active; call this
+/// directly to serve an `AuthService` set up without the plugin.
+/// Endpoints are served under every API version (`/api/v1/auth/*`,
+/// `/api/v2/auth/*`). Includes `/api/v1/openapi.json`, a Swagger UI at
+/// `/api/v1/docs`, the CSP report endpoint (`/api/v1/security/csp-reports`),
+/// the mail delivery status (`/api/v1/admin/emails`), the route listing
+/// (`/api/v1/system/routes`), the doctor report (`/api/v1/system/doctor`),
+/// the Prometheus metrics (`/metrics`, behind
+/// `metrics.token` when set) and, with `mail.webhook_secret` set, the bounce
+/// webhook (`/api/v1/mail/events`). The paths are recorded in the shared
+/// route registry so plugins can't mount over them.
+pub fn create_routes(auth_service: Arc<AuthService>) -> Result<Router, AuthError> {
+    use utoipa::OpenApi;
+
+    let spec = openapi::ApiDoc::openapi();
+    let webhook = auth_service.config().mail.webhook_secret.is_some();
+    let paths = ApiVersion::ALL
+        .iter()
+        .flat_map(|version| {
+            spec.paths
+                .paths
+                .keys()
+                .filter(|path| webhook || path.as_str() != mail::EVENTS_PATH)
+                .map(move |path| format!("{}{}", version.prefix(), path))
+        })
+        .chain([
+            openapi::OPENAPI_PATH.to_string(),
+            openapi::DOCS_PATH.to_string(),
+            metrics::METRICS_PATH.to_string(),
+        ]);
+    let registry = routes::shared();
+    registry
+        .register(PLUGIN_ID, paths)
+        .map_err(|e| AuthError::Config(e.to_string()))?;
+
+    let metrics_token: Option<String> = Config::load()?.get("metrics.token")?;
+
+    let db = auth_service.db().clone();
+    let mail_routes = mail::routes(db.clone(), &auth_service.config().mail);
+    Ok(VersionedRouter::new()
+        .merge(admin::routes(auth_service.clone()))
+        .merge(handlers::create_routes(auth_service))
+        .merge(security::report_routes(db.clone(), security::ReportLimits::default()))
+        .merge(mail_routes)
+        .merge(routes::system_routes(registry))
+        .merge(doctor::routes(db))
+        .into_router()
+        .merge(openapi::docs_routes(spec))
+        .merge(metrics::routes(metrics::shared(), metrics_token))
+        .layer(axum::middleware::from_fn(i18n::negotiate_locale))
+        .layer(axum::middleware::from_fn(problem::trace_id)))
 }
 
 // ============================================
@@ -354,5 +473,6 @@ mod tests {
     async fn test_plugin_initial_state() {
         let plugin = AuthPlugin::new();
         assert_eq!(plugin.state().await, PluginState::Inactive);
+        assert!(plugin.routes().await.unwrap().is_none());
     }
 }
@@ -0,0 +1,347 @@
+//! Metrics
+//!
+//! A process-wide [`MetricsRegistry`] that the auth plugin, other plugins and
+//! apps register counters and gauges with, all exported through one
+//! Prometheus endpoint (`GET /metrics`). Each owner registers through its
+//! [`PluginMetrics`] handle, which labels every series with `plugin="<id>"`:
+//!
+//! ```rust,ignore
+//! // On activation
+//! let metrics = rustpress_auth::metrics::shared().plugin("rustpress-shop");
+//! let orders = metrics.counter("shop_orders_total", "Orders placed")?;
+//!
+//! orders.inc();
+//! ```
+//!
+//! Plugins may share a metric name as long as they agree on its kind; the
+//! series are told apart by the label. Registering again (re-activation)
+//! returns the existing series, so counts survive a plugin restart.
+//!
+//! With `metrics.token` set, scrapers must send it as a bearer token.
+
+use crate::problem::ProblemDetails;
+
+use axum::{
+    extract::State,
+    http::{header, HeaderMap},
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock, RwLock};
+
+/// Path of the Prometheus endpoint
+pub const METRICS_PATH: &str = "/metrics";
+
+static SHARED: OnceLock<Arc<MetricsRegistry>> = OnceLock::new();
+
+/// The process-wide registry shared by apps and plugins
+pub fn shared() -> Arc<MetricsRegistry> {
+    SHARED.get_or_init(|| Arc::new(MetricsRegistry::default())).clone()
+}
+
+/// Why a metric could not be registered
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum MetricsError {
+    #[error("Invalid metric name \"{0}\" (use letters, digits and '_', not starting with a digit)")]
+    InvalidName(String),
+
+    #[error("Metric {name} is already registered as a {existing}")]
+    KindMismatch { name: String, existing: &'static str },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Counter,
+    Gauge,
+}
+
+impl Kind {
+    fn as_str(self) -> &'static str {
+        match self {
+            Kind::Counter => "counter",
+            Kind::Gauge => "gauge",
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Family {
+    help: String,
+    kind: Kind,
+    /// Value bits by plugin id (`u64` for counters, `f64` for gauges)
+    series: BTreeMap<String, Arc<AtomicU64>>,
+}
+
+/// Counters and gauges of every plugin, by metric name
+#[derive(Debug, Default)]
+pub struct MetricsRegistry {
+    families: RwLock<BTreeMap<String, Family>>,
+}
+
+impl MetricsRegistry {
+    /// Handle registering metrics labelled with `plugin`
+    pub fn plugin(self: &Arc<Self>, plugin: impl Into<String>) -> PluginMetrics {
+        PluginMetrics {
+            registry: self.clone(),
+            plugin: plugin.into(),
+        }
+    }
+
+    fn series(&self, plugin: &str, name: &str, help: &str, kind: Kind) -> Result<Arc<AtomicU64>, MetricsError> {
+        let valid_start = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_');
+        if !valid_start || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(MetricsError::InvalidName(name.to_string()));
+        }
+
+        let mut families = self.families.write().unwrap();
+        let family = families.entry(name.to_string()).or_insert_with(|| Family {
+            help: help.to_string(),
+            kind,
+            series: BTreeMap::new(),
+        });
+        if family.kind != kind {
+            return Err(MetricsError::KindMismatch {
+                name: name.to_string(),
+                existing: family.kind.as_str(),
+            });
+        }
+
+        let initial = match kind {
+            Kind::Counter => 0,
+            Kind::Gauge => 0f64.to_bits(),
+        };
+        Ok(family
+            .series
+            .entry(plugin.to_string())
+            .or_insert_with(|| Arc::new(AtomicU64::new(initial)))
+            .clone())
+    }
+
+    /// Every series in the Prometheus text format, sorted by name and plugin
+    pub fn render(&self) -> String {
+        let families = self.families.read().unwrap();
+        let mut out = String::new();
+        for (name, family) in families.iter() {
+            let _ = writeln!(out, "# HELP {} {}", name, escape_help(&family.help));
+            let _ = writeln!(out, "# TYPE {} {}", name, family.kind.as_str());
+            for (plugin, value) in &family.series {
+                let bits = value.load(Ordering::Relaxed);
+                let value = match family.kind {
+                    Kind::Counter => bits.to_string(),
+                    Kind::Gauge => format_float(f64::from_bits(bits)),
+                };
+                let _ = writeln!(out, "{}{{plugin=\"{}\"}} {}", name, escape_label(plugin), value);
+            }
+        }
+        out
+    }
+}
+
+/// Registers metrics on behalf of one plugin or app
+#[derive(Debug, Clone)]
+pub struct PluginMetrics {
+    registry: Arc<MetricsRegistry>,
+    plugin: String,
+}
+
+impl PluginMetrics {
+    pub fn plugin_id(&self) -> &str {
+        &self.plugin
+    }
+
+    /// A monotonically increasing count (`<name>_total` by convention)
+    pub fn counter(&self, name: &str, help: &str) -> Result<Counter, MetricsError> {
+        self.registry
+            .series(&self.plugin, name, help, Kind::Counter)
+            .map(Counter)
+    }
+
+    /// A value that goes up and down
+    pub fn gauge(&self, name: &str, help: &str) -> Result<Gauge, MetricsError> {
+        self.registry
+            .series(&self.plugin, name, help, Kind::Gauge)
+            .map(Gauge)
+    }
+}
+
+/// Handle to a counter series; cheap to clone
+#[derive(Debug, Clone)]
+pub struct Counter(Arc<AtomicU64>);
+
+impl Counter {
+    pub fn inc(&self) {
+        self.inc_by(1);
+    }
+
+    pub fn inc_by(&self, n: u64) {
+        self.0.fetch_add(n, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Handle to a gauge series; cheap to clone
+#[derive(Debug, Clone)]
+pub struct Gauge(Arc<AtomicU64>);
+
+impl Gauge {
+    pub fn set(&self, value: f64) {
+        self.0.store(value.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn add(&self, delta: f64) {
+        let _ = self.0.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bits| {
+            Some((f64::from_bits(bits) + delta).to_bits())
+        });
+    }
+
+    pub fn inc(&self) {
+        self.add(1.0);
+    }
+
+    pub fn dec(&self) {
+        self.add(-1.0);
+    }
+
+    pub fn get(&self) -> f64 {
+        f64::from_bits(self.0.load(Ordering::Relaxed))
+    }
+}
+
+fn format_float(value: f64) -> String {
+    if value.is_nan() {
+        "NaN".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 { "+Inf" } else { "-Inf" }.to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+fn escape_help(help: &str) -> String {
+    help.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+fn escape_label(value: &str) -> String {
+    escape_help(value).replace('"', "\\\"")
+}
+
+// ============================================
+// Endpoint
+// ============================================
+
+#[derive(Clone)]
+struct MetricsState {
+    registry: Arc<MetricsRegistry>,
+    token: Option<String>,
+}
+
+/// GET /metrics
+async fn export(State(state): State<MetricsState>, headers: HeaderMap) -> Response {
+    if let Some(token) = &state.token {
+        let presented = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "));
+        if presented != Some(token.as_str()) {
+            return ProblemDetails::unauthorized("Metrics token required").into_response();
+        }
+    }
+
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4; charset=utf-8")],
+        state.registry.render(),
+    )
+        .into_response()
+}
+
+/// The Prometheus endpoint; `token` (from `metrics.token`) protects it
+pub fn routes(registry: Arc<MetricsRegistry>, token: Option<String>) -> Router {
+    Router::new()
+        .route(METRICS_PATH, get(export))
+        .with_state(MetricsState { registry, token })
+}
+
+// ============================================
+// Tests
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::Request, http::StatusCode};
+    use tower::ServiceExt;
+
+    #[test]
+    fn test_series_are_labelled_by_plugin() {
+        let registry = Arc::new(MetricsRegistry::default());
+        let auth = registry.plugin("rustpress-auth");
+        let shop = registry.plugin("rustpress-shop");
+
+        auth.counter("logins_total", "Successful logins").unwrap().inc_by(3);
+        shop.counter("logins_total", "Successful logins").unwrap().inc();
+        let depth = shop.gauge("queue_depth", "Queued jobs").unwrap();
+        depth.set(4.0);
+        depth.dec();
+
+        assert_eq!(
+            registry.render(),
+            "# HELP logins_total Successful logins\n\
+             # TYPE logins_total counter\n\
+             logins_total{plugin=\"rustpress-auth\"} 3\n\
+             logins_total{plugin=\"rustpress-shop\"} 1\n\
+             # HELP queue_depth Queued jobs\n\
+             # TYPE queue_depth gauge\n\
+             queue_depth{plugin=\"rustpress-shop\"} 3\n"
+        );
+
+        // Re-registration returns the same series
+        assert_eq!(auth.counter("logins_total", "Successful logins").unwrap().get(), 3);
+    }
+
+    #[test]
+    fn test_invalid_registrations() {
+        let registry = Arc::new(MetricsRegistry::default());
+        let metrics = registry.plugin("rustpress-auth");
+        metrics.counter("lockouts_total", "Lockouts").unwrap();
+
+        assert_eq!(
+            metrics.gauge("lockouts_total", "Lockouts").unwrap_err(),
+            MetricsError::KindMismatch {
+                name: "lockouts_total".into(),
+                existing: "counter"
+            }
+        );
+        assert!(matches!(metrics.counter("9lives", ""), Err(MetricsError::InvalidName(_))));
+        assert!(matches!(metrics.counter("cache-hits", ""), Err(MetricsError::InvalidName(_))));
+    }
+
+    #[tokio::test]
+    async fn test_endpoint_requires_token_when_set() {
+        let registry = Arc::new(MetricsRegistry::default());
+        registry.plugin("blog-api").counter("hits_total", "Hits").unwrap().inc();
+        let app = routes(registry, Some("scrape-secret".into()));
+
+        let request = |auth: Option<&str>| {
+            let mut builder = Request::builder().uri(METRICS_PATH);
+            if let Some(auth) = auth {
+                builder = builder.header(header::AUTHORIZATION, auth);
+            }
+            builder.body(Body::empty()).unwrap()
+        };
+
+        let response = app.clone().oneshot(request(None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = app.oneshot(request(Some("Bearer scrape-secret"))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert!(String::from_utf8_lossy(&body).contains("hits_total{plugin=\"blog-api\"} 1"));
+    }
+}
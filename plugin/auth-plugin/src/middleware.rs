@@ -1,89 +1,123 @@
 //! Authentication Middleware
 //!
 //! JWT token validation middleware using real cryptographic verification.
+//!
+//! Access tokens issued to a user before [`revoke_access_tokens`] was called
+//! for them are rejected, so a forced logout or suspension takes effect before
+//! the tokens expire. Revocations are kept in memory: other instances keep
+//! accepting such tokens until they expire, but can't refresh them.
+//!
+//! Requests of users who chose a locale are handled in it (see `i18n`).
 
+use crate::config::Config;
+use crate::error::AuthError;
+use crate::i18n::{self, t};
+use crate::keys::{access_validation, JwtKeys};
 use crate::models::AccessTokenClaims;
+use crate::problem::ProblemDetails;
 
 use axum::{
     extract::Request,
     http::StatusCode,
     middleware::Next,
     response::{IntoResponse, Response},
-    Json,
 };
-use jsonwebtoken::{decode, DecodingKey, Validation};
-use std::env;
-
-/// Get JWT decoding key from environment
-fn get_decoding_key() -> Result<DecodingKey, Response> {
-    let secret = env::var("JWT_SECRET").map_err(|_| {
-        tracing::error!("JWT_SECRET environment variable not set");
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(serde_json::json!({
-                "error": "configuration_error",
-                "message": "Server configuration error"
-            })),
-        )
-            .into_response()
-    })?;
-    Ok(DecodingKey::from_secret(secret.as_bytes()))
+use chrono::Utc;
+use jsonwebtoken::Validation;
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock, RwLock};
+use uuid::Uuid;
+
+/// How long a revocation is kept; longer than any access token lives
+const REVOCATION_TTL_SECS: i64 = 86400;
+
+/// When each user's access tokens were last revoked (Unix seconds)
+fn revocations() -> &'static RwLock<HashMap<Uuid, i64>> {
+    static REVOCATIONS: OnceLock<RwLock<HashMap<Uuid, i64>>> = OnceLock::new();
+    REVOCATIONS.get_or_init(Default::default)
+}
+
+/// Reject the access tokens issued to `user_id` so far
+pub fn revoke_access_tokens(user_id: Uuid) {
+    let now = Utc::now().timestamp();
+    let mut revocations = revocations().write().unwrap();
+    revocations.retain(|_, at| now - *at < REVOCATION_TTL_SECS);
+    revocations.insert(user_id, now);
 }
 
-/// Get JWT validation settings from environment
-fn get_validation() -> Validation {
-    let issuer = env::var("JWT_ISSUER").unwrap_or_else(|_| "rustpress".to_string());
-    let audience = env::var("JWT_AUDIENCE").unwrap_or_else(|_| "rustpress-api".to_string());
+/// Whether the token was issued before its user's tokens were revoked
+pub(crate) fn is_revoked(claims: &AccessTokenClaims) -> bool {
+    revocations()
+        .read()
+        .unwrap()
+        .get(&claims.sub)
+        .is_some_and(|at| claims.iat <= *at)
+}
 
-    let mut validation = Validation::default();
-    validation.set_issuer(&[issuer]);
-    validation.set_audience(&[audience]);
-    validation
+/// Current JWT keys and validation settings
+#[allow(clippy::result_large_err)]
+pub(crate) async fn jwt_decoding() -> Result<(Arc<JwtKeys>, Validation), Response> {
+    let configuration_error = |e: AuthError| {
+        tracing::error!("JWT configuration: {}", e);
+        ProblemDetails::new(StatusCode::INTERNAL_SERVER_ERROR, "configuration_error")
+            .detail(t("auth-configuration-error"))
+            .into_response()
+    };
+
+    let keys = JwtKeys::shared().await.map_err(configuration_error)?;
+    let validation = Config::load()
+        .map_err(AuthError::from)
+        .and_then(|config| access_validation(&config))
+        .map_err(configuration_error)?;
+    Ok((keys, validation))
 }
 
 /// Extract and validate JWT token from Authorization header
-fn validate_token(auth_header: Option<&str>) -> Result<AccessTokenClaims, Response> {
+#[allow(clippy::result_large_err)]
+async fn validate_token(auth_header: Option<&str>) -> Result<AccessTokenClaims, Response> {
     let header = auth_header.ok_or_else(|| {
-        (
-            StatusCode::UNAUTHORIZED,
-            Json(serde_json::json!({
-                "error": "unauthorized",
-                "message": "Authentication required"
-            })),
-        )
+        ProblemDetails::new(StatusCode::UNAUTHORIZED, "unauthorized")
+            .detail(t("auth-authentication-required"))
             .into_response()
     })?;
 
     if !header.starts_with("Bearer ") {
-        return Err((
-            StatusCode::UNAUTHORIZED,
-            Json(serde_json::json!({
-                "error": "unauthorized",
-                "message": "Invalid authorization header format"
-            })),
-        )
+        return Err(ProblemDetails::new(StatusCode::UNAUTHORIZED, "unauthorized")
+            .detail(t("auth-invalid-authorization-header"))
             .into_response());
     }
 
     let token = header.trim_start_matches("Bearer ");
-    let decoding_key = get_decoding_key()?;
-    let validation = get_validation();
+    let (keys, validation) = jwt_decoding().await?;
 
-    let token_data = decode::<AccessTokenClaims>(token, &decoding_key, &validation).map_err(|e| {
+    let token_data = keys.decode::<AccessTokenClaims>(token, &validation).map_err(|e| {
         tracing::debug!("JWT validation failed: {:?}", e);
-        (
-            StatusCode::UNAUTHORIZED,
-            Json(serde_json::json!({
-                "error": "invalid_token",
-                "message": "Invalid or expired token"
-            })),
-        )
+        ProblemDetails::new(StatusCode::UNAUTHORIZED, "invalid_token")
+            .detail(t("auth-invalid-token"))
             .into_response()
     })?;
 
+    if is_revoked(&token_data.claims) {
+        return Err(ProblemDetails::new(StatusCode::UNAUTHORIZED, "invalid_token")
+            .detail(t("auth-session-ended"))
+            .into_response());
+    }
+
     Ok(token_data.claims)
 }
 
+/// Handle the rest of the request in the user's preferred locale, if they
+/// chose one that's still available
+async fn in_user_locale(
+    locale: Option<String>,
+    rest: impl std::future::Future<Output = Result<Response, Response>>,
+) -> Result<Response, Response> {
+    match locale.filter(|locale| i18n::is_supported(locale)) {
+        Some(locale) => Ok(i18n::localized(&locale, async { rest.await.unwrap_or_else(|e| e) }).await),
+        None => rest.await,
+    }
+}
+
 /// Require authenticated user
 ///
 /// Validates the JWT token from the Authorization header and stores
@@ -94,12 +128,13 @@ pub async fn require_auth(mut req: Request, next: Next) -> Result<Response, Resp
         .get("Authorization")
         .and_then(|h| h.to_str().ok());
 
-    let claims = validate_token(auth_header)?;
+    let claims = validate_token(auth_header).await?;
+    let locale = claims.locale.clone();
 
     // Store claims in request extensions for extractors
     req.extensions_mut().insert(claims);
 
-    Ok(next.run(req).await)
+    in_user_locale(locale, async { Ok(next.run(req).await) }).await
 }
 
 /// Require admin role
@@ -111,24 +146,22 @@ pub async fn require_admin(mut req: Request, next: Next) -> Result<Response, Res
         .get("Authorization")
         .and_then(|h| h.to_str().ok());
 
-    let claims = validate_token(auth_header)?;
-
-    // Check admin role from JWT claims
-    if claims.role != "admin" {
-        return Err((
-            StatusCode::FORBIDDEN,
-            Json(serde_json::json!({
-                "error": "forbidden",
-                "message": "Admin access required"
-            })),
-        )
-            .into_response());
-    }
+    let claims = validate_token(auth_header).await?;
 
-    // Store claims in request extensions for extractors
-    req.extensions_mut().insert(claims);
+    in_user_locale(claims.locale.clone(), async move {
+        // Check admin role from JWT claims
+        if claims.role != "admin" {
+            return Err(ProblemDetails::new(StatusCode::FORBIDDEN, "forbidden")
+                .detail(t("auth-admin-required"))
+                .into_response());
+        }
+
+        // Store claims in request extensions for extractors
+        req.extensions_mut().insert(claims);
 
-    Ok(next.run(req).await)
+        Ok(next.run(req).await)
+    })
+    .await
 }
 
 /// Require specific role
@@ -146,24 +179,22 @@ pub fn require_role(
                 .get("Authorization")
                 .and_then(|h| h.to_str().ok());
 
-            let claims = validate_token(auth_header)?;
-
-            // Check if user has any of the required roles
-            if !roles.contains(&claims.role.as_str()) {
-                return Err((
-                    StatusCode::FORBIDDEN,
-                    Json(serde_json::json!({
-                        "error": "forbidden",
-                        "message": "Insufficient permissions"
-                    })),
-                )
-                    .into_response());
-            }
-
-            // Store claims in request extensions for extractors
-            req.extensions_mut().insert(claims);
+            let claims = validate_token(auth_header).await?;
+
+            in_user_locale(claims.locale.clone(), async move {
+                // Check if user has any of the required roles
+                if !roles.contains(&claims.role.as_str()) {
+                    return Err(ProblemDetails::new(StatusCode::FORBIDDEN, "forbidden")
+                        .detail(t("auth-insufficient-permissions"))
+                        .into_response());
+                }
+
+                // Store claims in request extensions for extractors
+                req.extensions_mut().insert(claims);
 
-            Ok(next.run(req).await)
+                Ok(next.run(req).await)
+            })
+            .await
         })
     }
 }
@@ -173,15 +204,19 @@ pub fn require_role(
 /// Attempts to validate the JWT but doesn't fail if not present.
 /// Stores claims in extensions if valid token is provided.
 pub async fn optional_auth(mut req: Request, next: Next) -> Response {
+    let mut locale = None;
     if let Some(auth_header) = req
         .headers()
         .get("Authorization")
         .and_then(|h| h.to_str().ok())
     {
-        if let Ok(claims) = validate_token(Some(auth_header)) {
+        if let Ok(claims) = validate_token(Some(auth_header)).await {
+            locale = claims.locale.clone();
             req.extensions_mut().insert(claims);
         }
     }
 
-    next.run(req).await
+    in_user_locale(locale, async { Ok(next.run(req).await) })
+        .await
+        .unwrap_or_else(|e| e)
 }
@@ -0,0 +1,740 @@
+//! Plugin Migrations
+//!
+//! Versioned schema migrations kept per plugin. Each plugin ships a
+//! `migrations/` directory of `<version>_<description>.up.sql` /
+//! `.down.sql` pairs (plain `.sql` files are up-only), usually embedded with
+//! `sqlx::migrate!`. Applied versions are recorded in the shared
+//! `plugin_migrations` ledger together with a SHA-384 checksum of the script,
+//! so an edited migration is refused instead of silently diverging.
+//!
+//! Scripts are written per dialect: a `migrations/postgres/` or
+//! `migrations/sqlite/` subdirectory matching the compiled-in backend is
+//! preferred over the top-level directory.
+//!
+//! Changes to hot tables that can't wait for writers to stop are split into
+//! expand and contract migrations; see [`schema_changes`](crate::schema_changes).
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::migrate::{Migration, Migrator};
+use crate::db::{DbConnection, DbPool, BACKEND};
+use crate::schema_changes::{self, Directive, Phase};
+use sqlx::{Connection, Executor};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::time::Instant;
+
+/// Ledger of applied migrations, shared by all plugins
+pub const LEDGER_TABLE: &str = "plugin_migrations";
+
+#[cfg(feature = "postgres")]
+const CREATE_LEDGER: &str = r#"
+    CREATE TABLE IF NOT EXISTS plugin_migrations (
+        plugin_id VARCHAR(100) NOT NULL,
+        version BIGINT NOT NULL,
+        description TEXT NOT NULL,
+        checksum BYTEA NOT NULL,
+        execution_ms BIGINT NOT NULL,
+        applied_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+        PRIMARY KEY (plugin_id, version)
+    );
+"#;
+
+#[cfg(feature = "sqlite")]
+const CREATE_LEDGER: &str = r#"
+    CREATE TABLE IF NOT EXISTS plugin_migrations (
+        plugin_id TEXT NOT NULL,
+        version INTEGER NOT NULL,
+        description TEXT NOT NULL,
+        checksum BLOB NOT NULL,
+        execution_ms INTEGER NOT NULL,
+        applied_at TEXT NOT NULL,
+        PRIMARY KEY (plugin_id, version)
+    );
+"#;
+
+/// Migration errors
+#[derive(Debug, thiserror::Error)]
+pub enum MigrationError {
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+
+    #[error("Invalid migration source: {0}")]
+    Source(String),
+
+    #[error("Migration {0} was applied but has been modified since")]
+    ChecksumMismatch(i64),
+
+    #[error("Migration {0} was applied but is missing from the migration source")]
+    Missing(i64),
+
+    #[error("Migration {0} has no down script")]
+    Irreversible(i64),
+
+    #[error("Unknown migration version {0}")]
+    UnknownVersion(i64),
+
+    #[error("Migration {version} contracts {change}, which is {phase}; its backfill must complete first")]
+    ContractBlocked { version: i64, change: String, phase: String },
+
+    #[error("Unknown schema change {0}")]
+    UnknownChange(String),
+}
+
+/// Direction of a migration run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    Up,
+    Down,
+}
+
+/// A migration version as known to the source and the ledger
+#[derive(Debug, Clone, Serialize)]
+pub struct MigrationStatus {
+    pub version: i64,
+    pub description: String,
+    pub applied_at: Option<DateTime<Utc>>,
+    pub reversible: bool,
+    /// Applied, but the script no longer matches the recorded checksum
+    pub modified: bool,
+}
+
+/// One migration executed (or, in a dry run, planned)
+#[derive(Debug, Clone, Serialize)]
+pub struct MigrationStep {
+    pub version: i64,
+    pub description: String,
+    pub direction: Direction,
+    /// `None` for dry runs
+    pub execution_ms: Option<i64>,
+}
+
+#[derive(Debug, Clone)]
+struct AppliedMigration {
+    version: i64,
+    checksum: Vec<u8>,
+    applied_at: DateTime<Utc>,
+}
+
+/// Up/down scripts of one version
+#[derive(Debug, Clone)]
+struct VersionScripts {
+    up: Migration,
+    down: Option<Migration>,
+}
+
+/// Migrations of one plugin
+#[derive(Debug)]
+pub struct PluginMigrations {
+    plugin_id: String,
+    versions: BTreeMap<i64, VersionScripts>,
+}
+
+impl PluginMigrations {
+    /// Migrations embedded with `sqlx::migrate!("./migrations")`
+    pub fn new(plugin_id: &str, migrator: Migrator) -> Self {
+        Self::from_migrations(plugin_id, migrator.iter().cloned())
+    }
+
+    /// Migrations read from a plugin's directory at runtime
+    ///
+    /// Uses `<dir>/<backend>/` when the plugin ships dialect-specific scripts.
+    pub async fn from_dir(plugin_id: &str, dir: &Path) -> Result<Self, MigrationError> {
+        let dialect_dir = dir.join(BACKEND);
+        let dir = if dialect_dir.is_dir() { dialect_dir.as_path() } else { dir };
+        let migrator = Migrator::new(dir)
+            .await
+            .map_err(|e| MigrationError::Source(format!("{}: {}", dir.display(), e)))?;
+        Ok(Self::new(plugin_id, migrator))
+    }
+
+    fn from_migrations(plugin_id: &str, migrations: impl IntoIterator<Item = Migration>) -> Self {
+        let mut ups = BTreeMap::new();
+        let mut downs = HashMap::new();
+        for migration in migrations {
+            if migration.migration_type.is_down_migration() {
+                downs.insert(migration.version, migration);
+            } else {
+                ups.insert(migration.version, migration);
+            }
+        }
+
+        let versions = ups
+            .into_iter()
+            .map(|(version, up)| {
+                let down = downs.remove(&version);
+                (version, VersionScripts { up, down })
+            })
+            .collect();
+
+        Self {
+            plugin_id: plugin_id.to_string(),
+            versions,
+        }
+    }
+
+    pub fn plugin_id(&self) -> &str {
+        &self.plugin_id
+    }
+
+    /// Create the ledger tables if they don't exist
+    pub async fn ensure_ledger(db: &DbPool) -> Result<(), MigrationError> {
+        db.execute(CREATE_LEDGER).await?;
+        db.execute(schema_changes::CREATE_TABLE).await?;
+        Ok(())
+    }
+
+    /// Every known version with whether and when it was applied
+    pub async fn status(&self, db: &DbPool) -> Result<Vec<MigrationStatus>, MigrationError> {
+        Self::ensure_ledger(db).await?;
+        let mut conn = db.acquire().await?;
+        let applied = self.applied(&mut conn).await?;
+        Ok(self.status_from(&applied))
+    }
+
+    /// Check every applied version still exists with an unchanged script
+    pub async fn verify(&self, db: &DbPool) -> Result<(), MigrationError> {
+        Self::ensure_ledger(db).await?;
+        let mut conn = db.acquire().await?;
+        let applied = self.applied(&mut conn).await?;
+        self.check_applied(&applied)
+    }
+
+    /// Apply pending migrations up to `target` (all when `None`)
+    ///
+    /// A contract migration whose change isn't backfilled yet stops a run
+    /// without a target before it, and fails a run with a target past it.
+    pub async fn up(&self, db: &DbPool, target: Option<i64>, dry_run: bool) -> Result<Vec<MigrationStep>, MigrationError> {
+        self.run(db, Direction::Up, target, dry_run).await
+    }
+
+    /// Revert applied migrations newer than `target` (`0` reverts everything)
+    pub async fn down(&self, db: &DbPool, target: i64, dry_run: bool) -> Result<Vec<MigrationStep>, MigrationError> {
+        self.run(db, Direction::Down, Some(target), dry_run).await
+    }
+
+    /// Revert only the latest applied migration
+    pub async fn rollback(&self, db: &DbPool, dry_run: bool) -> Result<Vec<MigrationStep>, MigrationError> {
+        self.run(db, Direction::Down, None, dry_run).await
+    }
+
+    async fn run(
+        &self,
+        db: &DbPool,
+        direction: Direction,
+        target: Option<i64>,
+        dry_run: bool,
+    ) -> Result<Vec<MigrationStep>, MigrationError> {
+        Self::ensure_ledger(db).await?;
+
+        let mut conn = db.acquire().await?;
+        lock(&mut conn, &self.plugin_id).await?;
+        let result = self.run_locked(&mut conn, direction, target, dry_run).await;
+        unlock(&mut conn, &self.plugin_id).await?;
+        result
+    }
+
+    async fn run_locked(
+        &self,
+        conn: &mut DbConnection,
+        direction: Direction,
+        target: Option<i64>,
+        dry_run: bool,
+    ) -> Result<Vec<MigrationStep>, MigrationError> {
+        let applied = self.applied(conn).await?;
+        let plan = match direction {
+            Direction::Up => {
+                let phases = schema_changes::phases(conn, &self.plugin_id).await?;
+                self.gate_contracts(self.plan_up(&applied, target)?, &phases, target.is_some())?
+            }
+            Direction::Down => self.plan_down(&applied, target.unwrap_or_else(|| previous(&applied)))?,
+        };
+
+        let mut steps = Vec::with_capacity(plan.len());
+        for scripts in plan {
+            let execution_ms = if dry_run {
+                None
+            } else {
+                Some(self.execute(conn, scripts, direction).await?)
+            };
+            steps.push(MigrationStep {
+                version: scripts.up.version,
+                description: scripts.up.description.to_string(),
+                direction,
+                execution_ms,
+            });
+        }
+
+        Ok(steps)
+    }
+
+    /// Run one script and update the ledger in the same transaction
+    async fn execute(&self, conn: &mut DbConnection, scripts: &VersionScripts, direction: Direction) -> Result<i64, MigrationError> {
+        let up = &scripts.up;
+        let started = Instant::now();
+        let mut tx = conn.begin().await?;
+
+        match direction {
+            Direction::Up => {
+                tx.execute(&*up.sql).await?;
+                self.record_phase(&mut tx, up, Direction::Up).await?;
+                let execution_ms = started.elapsed().as_millis() as i64;
+                sqlx::query(
+                    "INSERT INTO plugin_migrations (plugin_id, version, description, checksum, execution_ms, applied_at) VALUES ($1, $2, $3, $4, $5, $6)",
+                )
+                .bind(&self.plugin_id)
+                .bind(up.version)
+                .bind(&*up.description)
+                .bind(&*up.checksum)
+                .bind(execution_ms)
+                .bind(Utc::now())
+                .execute(&mut *tx)
+                .await?;
+            }
+            Direction::Down => {
+                let down = scripts.down.as_ref().ok_or(MigrationError::Irreversible(up.version))?;
+                tx.execute(&*down.sql).await?;
+                self.record_phase(&mut tx, up, Direction::Down).await?;
+                sqlx::query("DELETE FROM plugin_migrations WHERE plugin_id = $1 AND version = $2")
+                    .bind(&self.plugin_id)
+                    .bind(up.version)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+        }
+
+        tx.commit().await?;
+        let execution_ms = started.elapsed().as_millis() as i64;
+        tracing::info!(
+            plugin = %self.plugin_id,
+            version = up.version,
+            direction = ?direction,
+            execution_ms,
+            "Applied migration {}",
+            up.description
+        );
+        Ok(execution_ms)
+    }
+
+    /// Move the change an expand or contract migration belongs to into its
+    /// next phase (or back, when reverted)
+    async fn record_phase(&self, conn: &mut DbConnection, up: &Migration, direction: Direction) -> Result<(), MigrationError> {
+        let query = match (Directive::parse(&up.sql), direction) {
+            (None, _) => return Ok(()),
+            (Some(Directive::Expand(change)), Direction::Up) => sqlx::query(
+                "INSERT INTO plugin_schema_changes (plugin_id, change, phase, expanded_version, updated_at) \
+                 VALUES ($1, $2, 'expanded', $3, $4)",
+            )
+            .bind(&self.plugin_id)
+            .bind(change)
+            .bind(up.version)
+            .bind(Utc::now()),
+            (Some(Directive::Expand(change)), Direction::Down) => {
+                sqlx::query("DELETE FROM plugin_schema_changes WHERE plugin_id = $1 AND change = $2")
+                    .bind(&self.plugin_id)
+                    .bind(change)
+            }
+            (Some(Directive::Contract(change)), Direction::Up) => sqlx::query(
+                "UPDATE plugin_schema_changes SET phase = 'contracted', contracted_at = $3, updated_at = $3 \
+                 WHERE plugin_id = $1 AND change = $2",
+            )
+            .bind(&self.plugin_id)
+            .bind(change)
+            .bind(Some(Utc::now())),
+            (Some(Directive::Contract(change)), Direction::Down) => sqlx::query(
+                "UPDATE plugin_schema_changes SET phase = 'backfilled', contracted_at = NULL, updated_at = $3 \
+                 WHERE plugin_id = $1 AND change = $2",
+            )
+            .bind(&self.plugin_id)
+            .bind(change)
+            .bind(Some(Utc::now())),
+        };
+        query.execute(conn).await?;
+        Ok(())
+    }
+
+    async fn applied(&self, conn: &mut DbConnection) -> Result<Vec<AppliedMigration>, MigrationError> {
+        let rows: Vec<(i64, Vec<u8>, DateTime<Utc>)> = sqlx::query_as(
+            "SELECT version, checksum, applied_at FROM plugin_migrations WHERE plugin_id = $1 ORDER BY version",
+        )
+        .bind(&self.plugin_id)
+        .fetch_all(conn)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(version, checksum, applied_at)| AppliedMigration {
+                version,
+                checksum,
+                applied_at,
+            })
+            .collect())
+    }
+
+    fn status_from(&self, applied: &[AppliedMigration]) -> Vec<MigrationStatus> {
+        let applied: HashMap<i64, &AppliedMigration> = applied.iter().map(|a| (a.version, a)).collect();
+        self.versions
+            .values()
+            .map(|scripts| {
+                let record = applied.get(&scripts.up.version);
+                MigrationStatus {
+                    version: scripts.up.version,
+                    description: scripts.up.description.to_string(),
+                    applied_at: record.map(|a| a.applied_at),
+                    reversible: scripts.down.is_some(),
+                    modified: record.is_some_and(|a| a.checksum != *scripts.up.checksum),
+                }
+            })
+            .collect()
+    }
+
+    fn check_applied(&self, applied: &[AppliedMigration]) -> Result<(), MigrationError> {
+        for record in applied {
+            let scripts = self
+                .versions
+                .get(&record.version)
+                .ok_or(MigrationError::Missing(record.version))?;
+            if record.checksum != *scripts.up.checksum {
+                return Err(MigrationError::ChecksumMismatch(record.version));
+            }
+        }
+        Ok(())
+    }
+
+    /// Pending versions up to `target`, oldest first
+    fn plan_up(&self, applied: &[AppliedMigration], target: Option<i64>) -> Result<Vec<&VersionScripts>, MigrationError> {
+        self.check_applied(applied)?;
+        if let Some(target) = target {
+            if !self.versions.contains_key(&target) {
+                return Err(MigrationError::UnknownVersion(target));
+            }
+        }
+
+        Ok(self
+            .versions
+            .values()
+            .filter(|s| target.is_none_or(|t| s.up.version <= t))
+            .filter(|s| !applied.iter().any(|a| a.version == s.up.version))
+            .collect())
+    }
+
+    /// Cut an up plan before the first contract migration whose change isn't
+    /// backfilled; `explicit` targets past it are refused instead
+    fn gate_contracts<'a>(
+        &self,
+        plan: Vec<&'a VersionScripts>,
+        phases: &HashMap<String, Phase>,
+        explicit: bool,
+    ) -> Result<Vec<&'a VersionScripts>, MigrationError> {
+        let blocked = plan.iter().position(|scripts| match Directive::parse(&scripts.up.sql) {
+            Some(Directive::Contract(change)) => phases.get(change) != Some(&Phase::Backfilled),
+            _ => false,
+        });
+        let Some(index) = blocked else {
+            return Ok(plan);
+        };
+
+        let up = &plan[index].up;
+        let Some(Directive::Contract(change)) = Directive::parse(&up.sql) else {
+            unreachable!("blocked migrations contract a change");
+        };
+        let phase = phases.get(change).map_or("not expanded".to_string(), |phase| phase.to_string());
+        if explicit {
+            return Err(MigrationError::ContractBlocked {
+                version: up.version,
+                change: change.to_string(),
+                phase,
+            });
+        }
+        tracing::info!(
+            plugin = %self.plugin_id,
+            version = up.version,
+            change,
+            phase = %phase,
+            "Holding back contract migration until its backfill completes"
+        );
+        Ok(plan[..index].to_vec())
+    }
+
+    /// Applied versions newer than `target`, newest first
+    fn plan_down(&self, applied: &[AppliedMigration], target: i64) -> Result<Vec<&VersionScripts>, MigrationError> {
+        self.check_applied(applied)?;
+        if target != 0 && !self.versions.contains_key(&target) {
+            return Err(MigrationError::UnknownVersion(target));
+        }
+
+        let mut plan = Vec::new();
+        for record in applied.iter().rev().filter(|a| a.version > target) {
+            let scripts = &self.versions[&record.version];
+            if scripts.down.is_none() {
+                return Err(MigrationError::Irreversible(record.version));
+            }
+            plan.push(scripts);
+        }
+        Ok(plan)
+    }
+}
+
+/// Version below the latest applied one, which a rollback reverts to
+fn previous(applied: &[AppliedMigration]) -> i64 {
+    applied.iter().rev().nth(1).map_or(0, |a| a.version)
+}
+
+/// Serialize runs for one plugin across processes
+#[cfg(feature = "postgres")]
+async fn lock(conn: &mut DbConnection, plugin_id: &str) -> Result<(), MigrationError> {
+    sqlx::query("SELECT pg_advisory_lock(hashtext('plugin_migrations:' || $1))")
+        .bind(plugin_id)
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+#[cfg(feature = "postgres")]
+async fn unlock(conn: &mut DbConnection, plugin_id: &str) -> Result<(), MigrationError> {
+    sqlx::query("SELECT pg_advisory_unlock(hashtext('plugin_migrations:' || $1))")
+        .bind(plugin_id)
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+/// SQLite allows a single writer per database file and each script runs in
+/// its own transaction; a concurrent run trips over the ledger's primary key
+/// and rolls back, so no explicit lock is taken.
+#[cfg(feature = "sqlite")]
+async fn lock(_conn: &mut DbConnection, _plugin_id: &str) -> Result<(), MigrationError> {
+    Ok(())
+}
+
+#[cfg(feature = "sqlite")]
+async fn unlock(_conn: &mut DbConnection, _plugin_id: &str) -> Result<(), MigrationError> {
+    Ok(())
+}
+
+// ============================================
+// Tests
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::migrate::MigrationType;
+
+    fn migration(version: i64, description: &str, direction: Direction, sql: &str) -> Migration {
+        let migration_type = match direction {
+            Direction::Up => MigrationType::ReversibleUp,
+            Direction::Down => MigrationType::ReversibleDown,
+        };
+        Migration::new(version, description.to_string().into(), migration_type, sql.to_string().into())
+    }
+
+    fn migrations() -> PluginMigrations {
+        PluginMigrations::from_migrations(
+            "test-plugin",
+            [
+                migration(1, "init", Direction::Up, "CREATE TABLE a (id INT);"),
+                migration(1, "init", Direction::Down, "DROP TABLE a;"),
+                migration(2, "add b", Direction::Up, "CREATE TABLE b (id INT);"),
+                migration(3, "add c", Direction::Up, "CREATE TABLE c (id INT);"),
+                migration(3, "add c", Direction::Down, "DROP TABLE c;"),
+            ],
+        )
+    }
+
+    fn applied(set: &PluginMigrations, versions: &[i64]) -> Vec<AppliedMigration> {
+        versions
+            .iter()
+            .map(|v| AppliedMigration {
+                version: *v,
+                checksum: set.versions[v].up.checksum.to_vec(),
+                applied_at: Utc::now(),
+            })
+            .collect()
+    }
+
+    fn versions(plan: &[&VersionScripts]) -> Vec<i64> {
+        plan.iter().map(|s| s.up.version).collect()
+    }
+
+    #[test]
+    fn test_plan_up_runs_pending_in_order() {
+        let set = migrations();
+        let applied = applied(&set, &[1]);
+
+        assert_eq!(versions(&set.plan_up(&applied, None).unwrap()), vec![2, 3]);
+        assert_eq!(versions(&set.plan_up(&applied, Some(2)).unwrap()), vec![2]);
+        assert!(matches!(set.plan_up(&applied, Some(9)), Err(MigrationError::UnknownVersion(9))));
+    }
+
+    #[test]
+    fn test_plan_down_requires_down_scripts() {
+        let set = migrations();
+        let applied = applied(&set, &[1, 2, 3]);
+
+        assert_eq!(versions(&set.plan_down(&applied, 2).unwrap()), vec![3]);
+        assert!(matches!(set.plan_down(&applied, 0), Err(MigrationError::Irreversible(2))));
+    }
+
+    #[test]
+    fn test_rollback_reverts_only_the_latest() {
+        let set = migrations();
+        assert_eq!(previous(&applied(&set, &[1, 2, 3])), 2);
+        assert_eq!(previous(&applied(&set, &[1])), 0);
+        assert_eq!(previous(&[]), 0);
+
+        let applied = applied(&set, &[1, 2, 3]);
+        assert_eq!(versions(&set.plan_down(&applied, previous(&applied)).unwrap()), vec![3]);
+    }
+
+    #[test]
+    fn test_modified_migration_is_refused() {
+        let set = migrations();
+        let mut applied = applied(&set, &[1, 2]);
+        applied[1].checksum = vec![0; 48];
+
+        assert!(matches!(set.plan_up(&applied, None), Err(MigrationError::ChecksumMismatch(2))));
+
+        let status = set.status_from(&applied);
+        assert!(!status[0].modified);
+        assert!(status[1].modified);
+        assert!(status[2].applied_at.is_none());
+    }
+
+    #[test]
+    fn test_applied_version_missing_from_source() {
+        let set = migrations();
+        let mut applied = applied(&set, &[1]);
+        applied.push(AppliedMigration {
+            version: 7,
+            checksum: Vec::new(),
+            applied_at: Utc::now(),
+        });
+
+        assert!(matches!(set.check_applied(&applied), Err(MigrationError::Missing(7))));
+    }
+
+    #[test]
+    fn test_contract_waits_for_backfill() {
+        let set = PluginMigrations::from_migrations(
+            "test-plugin",
+            [
+                migration(1, "init", Direction::Up, "CREATE TABLE a (id INT, name TEXT);"),
+                migration(2, "expand", Direction::Up, "-- expand: a_title\nALTER TABLE a ADD COLUMN title TEXT;"),
+                migration(3, "contract", Direction::Up, "-- contract: a_title\nALTER TABLE a DROP COLUMN name;"),
+                migration(4, "add b", Direction::Up, "CREATE TABLE b (id INT);"),
+            ],
+        );
+        let applied = applied(&set, &[1]);
+        let plan = || set.plan_up(&applied, None).unwrap();
+
+        // Without a target the run stops before the contract
+        let mut phases = HashMap::new();
+        assert_eq!(versions(&set.gate_contracts(plan(), &phases, false).unwrap()), vec![2]);
+        phases.insert("a_title".to_string(), Phase::Backfilling);
+        assert_eq!(versions(&set.gate_contracts(plan(), &phases, false).unwrap()), vec![2]);
+        assert!(matches!(
+            set.gate_contracts(plan(), &phases, true),
+            Err(MigrationError::ContractBlocked { version: 3, .. })
+        ));
+
+        phases.insert("a_title".to_string(), Phase::Backfilled);
+        assert_eq!(versions(&set.gate_contracts(plan(), &phases, true).unwrap()), vec![2, 3, 4]);
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn test_expand_backfill_contract_on_sqlite() {
+        use crate::schema_changes::{Backfill, DualWrite, SchemaChange};
+        use std::time::Duration;
+
+        let db = sqlx::sqlite::SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        let shim = DualWrite::new("hits", "hits_path_lower").set("path_lower", "lower(NEW.path)");
+        let set = PluginMigrations::from_migrations(
+            "test-plugin",
+            [
+                migration(1, "init", Direction::Up, "CREATE TABLE hits (id INTEGER PRIMARY KEY, path TEXT NOT NULL);"),
+                migration(
+                    2,
+                    "expand",
+                    Direction::Up,
+                    &format!("-- expand: hits_path_lower\nALTER TABLE hits ADD COLUMN path_lower TEXT;\n{}", shim.install_sql()),
+                ),
+                migration(2, "expand", Direction::Down, &format!("{}ALTER TABLE hits DROP COLUMN path_lower;", shim.remove_sql())),
+                migration(
+                    3,
+                    "contract",
+                    Direction::Up,
+                    &format!("-- contract: hits_path_lower\n{}ALTER TABLE hits DROP COLUMN path;", shim.remove_sql()),
+                ),
+                migration(3, "contract", Direction::Down, "ALTER TABLE hits ADD COLUMN path TEXT;"),
+            ],
+        );
+
+        set.up(&db, Some(1), false).await.unwrap();
+        for path in ["/A", "/B", "/C"] {
+            sqlx::query("INSERT INTO hits (path) VALUES ($1)").bind(path).execute(&db).await.unwrap();
+        }
+
+        // The contract is held back while the change is only expanded
+        assert_eq!(set.up(&db, None, false).await.unwrap().len(), 1);
+        assert!(matches!(set.up(&db, Some(3), true).await, Err(MigrationError::ContractBlocked { .. })));
+
+        // Writes during the backfill go through the shim
+        sqlx::query("INSERT INTO hits (path) VALUES ('/D')").execute(&db).await.unwrap();
+        let backfill = Backfill::new("test-plugin", "hits_path_lower", "hits", "path_lower = lower(path)", "path_lower IS NULL")
+            .batch_size(2)
+            .pause(Duration::ZERO);
+        let change = backfill.step(&db).await.unwrap();
+        assert_eq!(change.phase, Phase::Backfilling);
+        assert_eq!((change.rows_done, change.rows_total), (2, 3));
+
+        let change = backfill.run(&db).await.unwrap();
+        assert_eq!(change.phase, Phase::Backfilled);
+        assert_eq!(change.rows_done, 3);
+        let lowered: Vec<(String,)> = sqlx::query_as("SELECT path_lower FROM hits ORDER BY id").fetch_all(&db).await.unwrap();
+        assert_eq!(lowered, [("/a".into(),), ("/b".into(),), ("/c".into(),), ("/d".into(),)]);
+
+        assert_eq!(set.up(&db, None, false).await.unwrap().len(), 1);
+        let changes = SchemaChange::list(&db, "test-plugin").await.unwrap();
+        assert_eq!(changes[0].phase, Phase::Contracted);
+
+        // Reverting the contract reopens it; reverting the expand forgets it
+        set.down(&db, 2, false).await.unwrap();
+        assert_eq!(SchemaChange::list(&db, "test-plugin").await.unwrap()[0].phase, Phase::Backfilled);
+        set.down(&db, 1, false).await.unwrap();
+        assert!(SchemaChange::list(&db, "test-plugin").await.unwrap().is_empty());
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn test_up_and_down_on_sqlite() {
+        let db = sqlx::sqlite::SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        let set = crate::AuthPlugin::migrations();
+
+        let steps = set.up(&db, None, false).await.unwrap();
+        assert_eq!(steps.len(), 8);
+        assert!(set.up(&db, None, false).await.unwrap().is_empty());
+        set.verify(&db).await.unwrap();
+        assert!(set.status(&db).await.unwrap().iter().all(|s| s.applied_at.is_some()));
+
+        // A rollback takes back the latest migration only
+        let steps = set.rollback(&db, false).await.unwrap();
+        assert_eq!(steps.len(), 1);
+        let status = set.status(&db).await.unwrap();
+        assert!(status.last().unwrap().applied_at.is_none());
+        assert!(status[..status.len() - 1].iter().all(|s| s.applied_at.is_some()));
+
+        set.down(&db, 0, false).await.unwrap();
+        assert!(set.status(&db).await.unwrap().iter().all(|s| s.applied_at.is_none()));
+    }
+}
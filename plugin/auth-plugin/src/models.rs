@@ -2,10 +2,13 @@
 //!
 //! Data structures for authentication requests, responses, and database entities.
 
+use crate::crypto::Encrypted;
+
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use uuid::Uuid;
+use utoipa::{IntoParams, ToSchema};
 use validator::Validate;
 
 // ============================================
@@ -13,7 +16,7 @@ use validator::Validate;
 // ============================================
 
 /// User role enum matching database type
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, sqlx::Type, ToSchema)]
 #[sqlx(type_name = "user_role", rename_all = "lowercase")]
 #[serde(rename_all = "lowercase")]
 pub enum UserRole {
@@ -53,7 +56,7 @@ impl std::fmt::Display for UserRole {
 }
 
 /// User status enum matching database type
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, sqlx::Type, ToSchema)]
 #[sqlx(type_name = "user_status", rename_all = "lowercase")]
 #[serde(rename_all = "lowercase")]
 pub enum UserStatus {
@@ -63,6 +66,17 @@ pub enum UserStatus {
     Deleted,
 }
 
+impl UserStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UserStatus::Pending => "pending",
+            UserStatus::Active => "active",
+            UserStatus::Suspended => "suspended",
+            UserStatus::Deleted => "deleted",
+        }
+    }
+}
+
 /// User entity from database
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct User {
@@ -78,15 +92,23 @@ pub struct User {
     pub website: Option<String>,
     pub email_verified_at: Option<DateTime<Utc>>,
     pub last_login_at: Option<DateTime<Utc>>,
-    pub last_login_ip: Option<String>,
+    /// Stored encrypted (see `crypto`)
+    pub last_login_ip: Option<Encrypted>,
     pub failed_login_attempts: i32,
     pub locked_until: Option<DateTime<Utc>>,
     pub password_changed_at: DateTime<Utc>,
+    /// Preferred locale (see `i18n`); negotiated per request when unset
+    pub locale: Option<String>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
 impl User {
+    /// Locale to mail the user in: their preference, else the request's
+    pub fn mail_locale(&self) -> String {
+        self.locale.clone().unwrap_or_else(crate::i18n::current_locale)
+    }
+
     /// Check if user account is locked
     pub fn is_locked(&self) -> bool {
         if let Some(locked_until) = self.locked_until {
@@ -125,6 +147,8 @@ pub struct RefreshToken {
     pub user_agent: Option<String>,
     pub ip_address: Option<String>,
     pub created_at: DateTime<Utc>,
+    /// First token of the login this one was rotated from
+    pub family_id: Option<Uuid>,
 }
 
 impl RefreshToken {
@@ -142,6 +166,11 @@ impl RefreshToken {
     pub fn is_valid(&self) -> bool {
         !self.is_expired() && !self.is_revoked()
     }
+
+    /// The token family (one per login), kept across rotations
+    pub fn family(&self) -> Uuid {
+        self.family_id.unwrap_or(self.id)
+    }
 }
 
 // ============================================
@@ -149,7 +178,7 @@ impl RefreshToken {
 // ============================================
 
 /// Login request
-#[derive(Debug, Clone, Deserialize, Validate)]
+#[derive(Debug, Clone, Deserialize, Validate, ToSchema)]
 pub struct LoginRequest {
     #[validate(email(message = "Invalid email format"))]
     pub email: String,
@@ -159,7 +188,7 @@ pub struct LoginRequest {
 }
 
 /// Registration request
-#[derive(Debug, Clone, Deserialize, Validate)]
+#[derive(Debug, Clone, Deserialize, Validate, ToSchema)]
 pub struct RegisterRequest {
     #[validate(email(message = "Invalid email format"))]
     pub email: String,
@@ -175,21 +204,21 @@ pub struct RegisterRequest {
 }
 
 /// Refresh token request
-#[derive(Debug, Clone, Deserialize, Validate)]
+#[derive(Debug, Clone, Deserialize, Validate, ToSchema)]
 pub struct RefreshTokenRequest {
     #[validate(length(min = 1, message = "Refresh token is required"))]
     pub refresh_token: String,
 }
 
 /// Password reset request (initiate)
-#[derive(Debug, Clone, Deserialize, Validate)]
+#[derive(Debug, Clone, Deserialize, Validate, ToSchema)]
 pub struct ForgotPasswordRequest {
     #[validate(email(message = "Invalid email format"))]
     pub email: String,
 }
 
 /// Password reset request (complete)
-#[derive(Debug, Clone, Deserialize, Validate)]
+#[derive(Debug, Clone, Deserialize, Validate, ToSchema)]
 pub struct ResetPasswordRequest {
     #[validate(length(min = 1, message = "Token is required"))]
     pub token: String,
@@ -202,7 +231,7 @@ pub struct ResetPasswordRequest {
 }
 
 /// Change password request (for authenticated users)
-#[derive(Debug, Clone, Deserialize, Validate)]
+#[derive(Debug, Clone, Deserialize, Validate, ToSchema)]
 pub struct ChangePasswordRequest {
     #[validate(length(min = 1, message = "Current password is required"))]
     pub current_password: String,
@@ -214,19 +243,43 @@ pub struct ChangePasswordRequest {
     pub new_password_confirm: String,
 }
 
+/// Password change for a user whose password has expired, who can't log in
+/// to use `ChangePasswordRequest`
+#[derive(Debug, Clone, Deserialize, Validate, ToSchema)]
+pub struct ExpiredPasswordChangeRequest {
+    #[validate(email(message = "Invalid email format"))]
+    pub email: String,
+
+    #[validate(length(min = 1, message = "Current password is required"))]
+    pub current_password: String,
+
+    #[validate(length(min = 8, message = "New password must be at least 8 characters"))]
+    pub new_password: String,
+
+    #[validate(must_match(other = "new_password", message = "Passwords do not match"))]
+    pub new_password_confirm: String,
+}
+
 /// Email verification request
-#[derive(Debug, Clone, Deserialize, Validate)]
+#[derive(Debug, Clone, Deserialize, Validate, ToSchema)]
 pub struct VerifyEmailRequest {
     #[validate(length(min = 1, message = "Token is required"))]
     pub token: String,
 }
 
+/// Suspicious login confirmation request
+#[derive(Debug, Clone, Deserialize, Validate, ToSchema)]
+pub struct ConfirmLoginRequest {
+    #[validate(length(min = 1, message = "Token is required"))]
+    pub token: String,
+}
+
 // ============================================
 // Response DTOs
 // ============================================
 
 /// User response (public user data without sensitive fields)
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, ToSchema)]
 pub struct UserResponse {
     pub id: Uuid,
     pub email: String,
@@ -273,17 +326,35 @@ impl From<&User> for UserResponse {
 }
 
 /// Authentication response with tokens
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, ToSchema)]
 pub struct AuthResponse {
     pub user: UserResponse,
     pub access_token: String,
     pub refresh_token: String,
     pub token_type: String,
     pub expires_in: i64,
+    /// Seconds until the password must be changed, when passwords expire
+    /// (`auth.password_max_age_days`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password_expires_in: Option<i64>,
+    /// Sessions ended because the login went over `auth.max_sessions`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub evicted_sessions: Vec<SessionInfo>,
+}
+
+/// A device's session: the refresh tokens rotated from one login
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct SessionInfo {
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    /// When the device logged in
+    pub started_at: DateTime<Utc>,
+    /// When its tokens were last refreshed
+    pub last_used_at: DateTime<Utc>,
 }
 
 /// Token refresh response
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, ToSchema)]
 pub struct TokenResponse {
     pub access_token: String,
     pub refresh_token: String,
@@ -292,7 +363,7 @@ pub struct TokenResponse {
 }
 
 /// Simple message response
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, ToSchema)]
 pub struct MessageResponse {
     pub message: String,
 }
@@ -305,6 +376,165 @@ impl MessageResponse {
     }
 }
 
+/// Preferred locale of the current user
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct LocaleResponse {
+    /// Chosen locale; `null` when negotiated from `Accept-Language`
+    pub locale: Option<String>,
+    /// Locales that can be chosen
+    pub available: Vec<String>,
+}
+
+impl LocaleResponse {
+    pub fn new(locale: Option<String>) -> Self {
+        Self {
+            locale,
+            available: crate::i18n::available_locales(),
+        }
+    }
+}
+
+/// Change of the preferred locale
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct UpdateLocaleRequest {
+    /// Locale such as `de` or `pt-br`; `null` to negotiate it per request
+    pub locale: Option<String>,
+}
+
+// ============================================
+// Admin DTOs
+// ============================================
+
+/// Query parameters for listing users
+#[derive(Debug, Clone, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AdminUserQuery {
+    /// Case-insensitive substring of the email or name
+    pub search: Option<String>,
+    pub role: Option<UserRole>,
+    pub status: Option<UserStatus>,
+    /// Page number, from 1
+    pub page: Option<i64>,
+    /// Users per page (default 20, at most 100)
+    pub per_page: Option<i64>,
+}
+
+impl AdminUserQuery {
+    pub const DEFAULT_PER_PAGE: i64 = 20;
+    pub const MAX_PER_PAGE: i64 = 100;
+
+    pub fn page(&self) -> i64 {
+        self.page.unwrap_or(1).max(1)
+    }
+
+    pub fn per_page(&self) -> i64 {
+        self.per_page
+            .unwrap_or(Self::DEFAULT_PER_PAGE)
+            .clamp(1, Self::MAX_PER_PAGE)
+    }
+}
+
+/// Account changes an admin can make; omitted fields are left alone
+#[derive(Debug, Clone, Default, Deserialize, Validate, ToSchema)]
+pub struct AdminUpdateUserRequest {
+    pub role: Option<UserRole>,
+    pub status: Option<UserStatus>,
+    /// Clear failed login attempts and any lockout
+    #[serde(default)]
+    pub unlock: bool,
+}
+
+/// A user as seen by admins
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct AdminUserResponse {
+    pub id: Uuid,
+    pub email: String,
+    pub name: String,
+    pub role: UserRole,
+    pub status: UserStatus,
+    pub email_verified: bool,
+    pub locked_until: Option<DateTime<Utc>>,
+    pub failed_login_attempts: i32,
+    pub last_login_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<User> for AdminUserResponse {
+    fn from(user: User) -> Self {
+        let email_verified = user.is_email_verified();
+        let locked_until = user.locked_until.filter(|_| user.is_locked());
+        Self {
+            id: user.id,
+            email: user.email,
+            name: user.name,
+            role: user.role,
+            status: user.status,
+            email_verified,
+            locked_until,
+            failed_login_attempts: user.failed_login_attempts,
+            last_login_at: user.last_login_at,
+            created_at: user.created_at,
+        }
+    }
+}
+
+/// One page of users
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct AdminUserPage {
+    pub users: Vec<AdminUserResponse>,
+    pub page: i64,
+    pub per_page: i64,
+    /// Users matching the query across all pages
+    pub total: i64,
+}
+
+/// Result of ending a user's sessions
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ForceLogoutResponse {
+    /// Refresh tokens revoked
+    pub revoked_tokens: u64,
+}
+
+/// The refresh tokens rotated from one login, for investigating their use
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct TokenFamilyResponse {
+    pub family_id: Uuid,
+    pub user_id: Uuid,
+    /// Tokens in the order issued; each rotated one names its successor
+    pub tokens: Vec<TokenChainEntry>,
+    /// Rotated tokens of this family presented again, newest first
+    pub replays: Vec<crate::audit::AuditEvent>,
+}
+
+/// One refresh token of a family
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct TokenChainEntry {
+    pub id: Uuid,
+    pub issued_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    /// When it was rotated, logged out or revoked
+    pub revoked_at: Option<DateTime<Utc>>,
+    /// The token it was rotated into
+    pub replaced_by: Option<Uuid>,
+    /// Device that requested it
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+impl From<RefreshToken> for TokenChainEntry {
+    fn from(token: RefreshToken) -> Self {
+        Self {
+            id: token.id,
+            issued_at: token.issued_at,
+            expires_at: token.expires_at,
+            revoked_at: token.revoked_at,
+            replaced_by: token.replaced_by,
+            ip_address: token.ip_address,
+            user_agent: token.user_agent,
+        }
+    }
+}
+
 // ============================================
 // JWT Claims
 // ============================================
@@ -330,6 +560,9 @@ pub struct AccessTokenClaims {
     pub aud: String,
     /// JWT ID (unique identifier)
     pub jti: Uuid,
+    /// User's preferred locale, if they chose one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub locale: Option<String>,
 }
 
 /// JWT claims for refresh tokens
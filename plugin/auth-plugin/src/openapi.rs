@@ -0,0 +1,189 @@
+//! OpenAPI Documentation
+//!
+//! The specification is derived at compile time from the `#[utoipa::path]`
+//! annotations on the handlers and the `ToSchema` derives on the models, so it
+//! cannot drift from the code.
+
+use crate::admin;
+use crate::doctor;
+use crate::handlers;
+use crate::mail;
+use crate::routes;
+use crate::security;
+
+use axum::{
+    http::header,
+    response::{Html, IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{Modify, OpenApi};
+
+/// Path of the generated specification
+pub const OPENAPI_PATH: &str = "/api/v1/openapi.json";
+
+/// Path of the Swagger UI page
+pub const DOCS_PATH: &str = "/api/v1/docs";
+
+/// Authentication API specification
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "RustPress Authentication API"),
+    servers((url = "/api/v1"), (url = "/api/v2")),
+    paths(
+        handlers::register,
+        handlers::login,
+        handlers::logout,
+        handlers::refresh_token,
+        handlers::forgot_password,
+        handlers::reset_password,
+        handlers::change_password,
+        handlers::change_expired_password,
+        handlers::verify_email,
+        handlers::confirm_login,
+        handlers::resend_verification,
+        handlers::get_current_user,
+        handlers::get_locale,
+        handlers::set_locale,
+        admin::list_users,
+        admin::update_user,
+        admin::force_logout,
+        admin::get_token_family,
+        security::receive_csp_reports,
+        security::get_csp_reports,
+        mail::receive_mail_events,
+        mail::get_emails,
+        mail::get_suppressions,
+        mail::delete_suppression,
+        routes::list_routes,
+        doctor::get_doctor_report,
+    ),
+    modifiers(&BearerAuth),
+    tags(
+        (name = "auth", description = "Registration, login, tokens and passwords"),
+        (name = "admin", description = "User management for admins"),
+        (name = "security", description = "Content Security Policy violation reports"),
+        (name = "mail", description = "Mail delivery status, bounces and suppressions"),
+        (name = "system", description = "Mounted routes and their owners, deployment self-checks")
+    )
+)]
+pub struct ApiDoc;
+
+/// Registers the `bearer_auth` JWT security scheme
+///
+/// Apps that protect routes with this plugin's middleware can reuse it in
+/// their own `#[openapi(modifiers(...))]`.
+pub struct BearerAuth;
+
+impl Modify for BearerAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer_auth",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .bearer_format("JWT")
+                    .build(),
+            ),
+        );
+    }
+}
+
+/// Routes serving the specification and a Swagger UI page
+///
+/// Pass the merged specification when serving several APIs from one host.
+pub fn docs_routes(spec: utoipa::openapi::OpenApi) -> Router {
+    Router::new()
+        .route(OPENAPI_PATH, get(move || async move { Json(spec) }))
+        .route(DOCS_PATH, get(|| async { swagger_ui(OPENAPI_PATH) }))
+}
+
+/// Swagger UI page with its own Content Security Policy
+///
+/// The page loads its assets from unpkg, which the site-wide policy doesn't
+/// allow; `security_headers` keeps a policy set by the handler.
+pub fn swagger_ui(spec_url: &str) -> Response {
+    let nonce = security::current_csp_nonce().unwrap_or_default();
+    let policy = format!(
+        "default-src 'self'; script-src 'nonce-{}'; style-src 'self' 'unsafe-inline' https://unpkg.com; \
+         img-src 'self' data: https://unpkg.com; object-src 'none'; base-uri 'self'",
+        nonce
+    );
+    ([(header::CONTENT_SECURITY_POLICY, policy)], Html(swagger_ui_html(spec_url))).into_response()
+}
+
+/// Swagger UI page loaded from a CDN, pointed at `spec_url`
+///
+/// Scripts carry the request's CSP nonce when `security_headers` is installed.
+pub fn swagger_ui_html(spec_url: &str) -> String {
+    let nonce = security::nonce_attr();
+    format!(
+        r##"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta charset="utf-8">
+    <title>API Documentation</title>
+    <link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5/swagger-ui.css">
+</head>
+<body>
+    <div id="swagger-ui"></div>
+    <script src="https://unpkg.com/swagger-ui-dist@5/swagger-ui-bundle.js"{nonce}></script>
+    <script{nonce}>
+        window.ui = SwaggerUIBundle({{ url: "{spec_url}", dom_id: "#swagger-ui" }});
+    </script>
+</body>
+</html>"##
+    )
+}
+
+// ============================================
+// Tests
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spec_includes_all_routes() {
+        let spec = ApiDoc::openapi();
+        for path in [
+            "/auth/register",
+            "/auth/login",
+            "/auth/logout",
+            "/auth/refresh",
+            "/auth/forgot-password",
+            "/auth/reset-password",
+            "/auth/change-password",
+            "/auth/change-expired-password",
+            "/auth/verify-email",
+            "/auth/confirm-login",
+            "/auth/resend-verification",
+            "/auth/me",
+            "/auth/me/locale",
+            "/auth/admin/users",
+            "/auth/admin/users/{id}",
+            "/auth/admin/users/{id}/force-logout",
+            "/auth/admin/tokens/{family}",
+            "/security/csp-reports",
+            "/mail/events",
+            "/admin/emails",
+            "/admin/emails/suppressions",
+            "/system/routes",
+            "/system/doctor",
+        ] {
+            assert!(spec.paths.paths.contains_key(path), "missing {}", path);
+        }
+    }
+
+    #[test]
+    fn test_spec_includes_schemas_and_security() {
+        let spec = ApiDoc::openapi();
+        let components = spec.components.expect("components");
+        assert!(components.schemas.contains_key("LoginRequest"));
+        assert!(components.schemas.contains_key("AuthResponse"));
+        assert!(components.security_schemes.contains_key("bearer_auth"));
+    }
+}
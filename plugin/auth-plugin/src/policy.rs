@@ -0,0 +1,641 @@
+//! Authorization Policies
+//!
+//! Access rules are named policies written in a small expression language
+//! and checked by the [`Authorize`] extractor before a handler runs:
+//!
+//! ```text
+//! posts.update = owner || role in [editor, admin]
+//! posts.delete = owner || role == admin
+//! comments.approve = authenticated && resource.status != "spam"
+//! ```
+//!
+//! - `owner` — the user owns the resource ([`Resource::owner_id`])
+//! - `authenticated`, `true`, `false`
+//! - `role == x`, `role != x`, `role in [x, y]`
+//! - `resource.<attr> == x` (and `!=`, `in`) on [`Resource::attribute`]
+//! - `!`, `&&`, `||` and parentheses
+//!
+//! Apps define their policies with defaults in code; a deployment overrides
+//! any of them in `rustpress.toml` (or `POLICIES_POSTS_UPDATE`):
+//!
+//! ```toml
+//! [policies.posts]
+//! update = "owner || role in [editor, admin]"
+//! ```
+//!
+//! A handler names the policy with a marker type; the resource is loaded
+//! through [`ResourceLoader`] and handed to the handler once allowed:
+//!
+//! ```rust,ignore
+//! struct UpdatePost;
+//!
+//! impl Policy for UpdatePost {
+//!     const NAME: &'static str = "posts.update";
+//!     type Resource = Post;
+//! }
+//!
+//! async fn update_post(auth: Authorize<UpdatePost>, ...) {
+//!     let post = auth.resource;
+//! }
+//! ```
+//!
+//! Denied requests get 401 when anonymous and 403 otherwise. Checking a
+//! policy nobody defined is a server error, never an allow.
+
+use crate::config::{Config, ConfigError};
+use crate::extractors::AuthUser;
+use crate::models::AccessTokenClaims;
+use crate::problem::ProblemDetails;
+
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{request::Parts, StatusCode},
+    response::{IntoResponse, Response},
+};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::marker::PhantomData;
+use std::str::FromStr;
+use std::sync::{Arc, OnceLock, RwLock};
+use uuid::Uuid;
+
+static SHARED: OnceLock<Arc<Policies>> = OnceLock::new();
+
+/// The process-wide policies checked by [`Authorize`]
+pub fn shared() -> Arc<Policies> {
+    SHARED.get_or_init(|| Arc::new(Policies::default())).clone()
+}
+
+/// Policy definition and evaluation errors
+#[derive(Debug, thiserror::Error)]
+pub enum PolicyError {
+    #[error("Invalid policy rule \"{rule}\": {message}")]
+    Parse { rule: String, message: String },
+
+    #[error("Invalid policy {name}: {message}")]
+    Invalid { name: String, message: String },
+
+    #[error("Unknown policy: {0}")]
+    Unknown(String),
+
+    #[error(transparent)]
+    Config(#[from] ConfigError),
+}
+
+/// Who is asking
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Subject {
+    pub id: Option<Uuid>,
+    pub role: Option<String>,
+}
+
+impl Subject {
+    pub fn anonymous() -> Self {
+        Self::default()
+    }
+
+    pub fn is_authenticated(&self) -> bool {
+        self.id.is_some()
+    }
+}
+
+impl From<&AuthUser> for Subject {
+    fn from(user: &AuthUser) -> Self {
+        Self {
+            id: Some(user.id),
+            role: Some(user.role.clone()),
+        }
+    }
+}
+
+/// What a rule can see of the resource being accessed
+pub trait Resource {
+    /// The user owning the resource, for `owner`
+    fn owner_id(&self) -> Option<Uuid> {
+        None
+    }
+
+    /// A named attribute, for `resource.<name>`
+    fn attribute(&self, _name: &str) -> Option<String> {
+        None
+    }
+}
+
+/// Policies that don't look at a resource
+impl Resource for () {}
+
+/// Loads the resource a request targets (usually from a path parameter)
+#[async_trait]
+pub trait ResourceLoader<S: Sync>: Sized {
+    async fn load(parts: &mut Parts, state: &S) -> Result<Self, Response>;
+
+    /// The role `role` rules see for `user`: the account's role, unless the
+    /// app grants roles per resource scope (e.g. per site)
+    async fn role(_parts: &mut Parts, _state: &S, user: &AuthUser) -> Result<Option<String>, Response> {
+        Ok(Some(user.role.clone()))
+    }
+}
+
+#[async_trait]
+impl<S> ResourceLoader<S> for ()
+where
+    S: Send + Sync,
+{
+    async fn load(_parts: &mut Parts, _state: &S) -> Result<Self, Response> {
+        Ok(())
+    }
+}
+
+/// A named policy, used as the type parameter of [`Authorize`]
+pub trait Policy {
+    const NAME: &'static str;
+    type Resource: Resource + Send;
+}
+
+// ============================================
+// Rules
+// ============================================
+
+/// A parsed rule
+#[derive(Debug, Clone)]
+pub struct Rule {
+    source: String,
+    expr: Expr,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Bool(bool),
+    Owner,
+    Authenticated,
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Compare {
+        operand: Operand,
+        negate: bool,
+        values: Vec<String>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Operand {
+    Role,
+    Attribute(String),
+}
+
+impl Rule {
+    /// Whether `subject` may access `resource`
+    pub fn allows(&self, subject: &Subject, resource: &dyn Resource) -> bool {
+        self.expr.eval(subject, resource)
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+impl FromStr for Rule {
+    type Err = PolicyError;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        let error = |message: String| PolicyError::Parse {
+            rule: source.to_string(),
+            message,
+        };
+
+        let tokens = tokenize(source).map_err(error)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let expr = parser.or().map_err(error)?;
+        if let Some(token) = parser.peek() {
+            return Err(error(format!("unexpected {}", token)));
+        }
+
+        Ok(Self {
+            source: source.to_string(),
+            expr,
+        })
+    }
+}
+
+impl Expr {
+    fn eval(&self, subject: &Subject, resource: &dyn Resource) -> bool {
+        match self {
+            Expr::Bool(value) => *value,
+            Expr::Owner => subject.id.is_some() && subject.id == resource.owner_id(),
+            Expr::Authenticated => subject.is_authenticated(),
+            Expr::Not(expr) => !expr.eval(subject, resource),
+            Expr::And(a, b) => a.eval(subject, resource) && b.eval(subject, resource),
+            Expr::Or(a, b) => a.eval(subject, resource) || b.eval(subject, resource),
+            Expr::Compare {
+                operand,
+                negate,
+                values,
+            } => {
+                let actual = match operand {
+                    Operand::Role => subject.role.clone(),
+                    Operand::Attribute(name) => resource.attribute(name),
+                };
+                // A missing value matches nothing, so `!=` holds
+                let matched = actual.is_some_and(|actual| values.contains(&actual));
+                matched != *negate
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Symbol(&'static str),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Ident(s) => write!(f, "'{}'", s),
+            Token::Str(s) => write!(f, "\"{}\"", s),
+            Token::Symbol(s) => write!(f, "'{}'", s),
+        }
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    const SYMBOLS: &[&str] = &["||", "&&", "==", "!=", "!", "(", ")", "[", "]", ","];
+    let ident = |c: char| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.');
+
+    let mut tokens = Vec::new();
+    let mut rest = source.trim_start();
+    while let Some(c) = rest.chars().next() {
+        if let Some(symbol) = SYMBOLS.iter().find(|s| rest.starts_with(**s)) {
+            tokens.push(Token::Symbol(symbol));
+            rest = &rest[symbol.len()..];
+        } else if c == '"' || c == '\'' {
+            let end = rest[1..]
+                .find(c)
+                .ok_or_else(|| "unterminated string".to_string())?;
+            tokens.push(Token::Str(rest[1..=end].to_string()));
+            rest = &rest[end + 2..];
+        } else if ident(c) {
+            let end = rest.find(|c| !ident(c)).unwrap_or(rest.len());
+            tokens.push(Token::Ident(rest[..end].to_string()));
+            rest = &rest[end..];
+        } else {
+            return Err(format!("unexpected character '{}'", c));
+        }
+        rest = rest.trim_start();
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, symbol: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Symbol(s)) if *s == symbol) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, symbol: &str) -> Result<(), String> {
+        if self.eat(symbol) {
+            return Ok(());
+        }
+        match self.peek() {
+            Some(token) => Err(format!("expected '{}', found {}", symbol, token)),
+            None => Err(format!("expected '{}' at end of rule", symbol)),
+        }
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut expr = self.and()?;
+        while self.eat("||") {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut expr = self.unary()?;
+        while self.eat("&&") {
+            expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.eat("!") {
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        if self.eat("(") {
+            let expr = self.or()?;
+            self.expect(")")?;
+            return Ok(expr);
+        }
+
+        match self.next() {
+            Some(Token::Ident(name)) => match name.as_str() {
+                "true" => Ok(Expr::Bool(true)),
+                "false" => Ok(Expr::Bool(false)),
+                "owner" => Ok(Expr::Owner),
+                "authenticated" => Ok(Expr::Authenticated),
+                "role" => self.compare(Operand::Role),
+                _ => match name.strip_prefix("resource.") {
+                    Some(attr) if !attr.is_empty() => self.compare(Operand::Attribute(attr.to_string())),
+                    _ => Err(format!("unknown name '{}'", name)),
+                },
+            },
+            Some(token) => Err(format!("unexpected {}", token)),
+            None => Err("unexpected end of rule".to_string()),
+        }
+    }
+
+    fn compare(&mut self, operand: Operand) -> Result<Expr, String> {
+        let (negate, values) = if self.eat("==") {
+            (false, vec![self.value()?])
+        } else if self.eat("!=") {
+            (true, vec![self.value()?])
+        } else if matches!(self.peek(), Some(Token::Ident(s)) if s == "in") {
+            self.pos += 1;
+            self.expect("[")?;
+            let mut values = Vec::new();
+            if !self.eat("]") {
+                loop {
+                    values.push(self.value()?);
+                    if self.eat("]") {
+                        break;
+                    }
+                    self.expect(",")?;
+                }
+            }
+            (false, values)
+        } else {
+            return Err("expected '==', '!=' or 'in'".to_string());
+        };
+
+        Ok(Expr::Compare {
+            operand,
+            negate,
+            values,
+        })
+    }
+
+    fn value(&mut self) -> Result<String, String> {
+        match self.next() {
+            Some(Token::Ident(s) | Token::Str(s)) => Ok(s),
+            Some(token) => Err(format!("expected a value, found {}", token)),
+            None => Err("expected a value at end of rule".to_string()),
+        }
+    }
+}
+
+// ============================================
+// Registry
+// ============================================
+
+/// Named rules
+#[derive(Debug, Default)]
+pub struct Policies {
+    rules: RwLock<BTreeMap<String, Rule>>,
+}
+
+impl Policies {
+    /// Define policies from `(name, default rule)` pairs, taking the rule
+    /// from `policies.<name>` in `config` when it is set
+    ///
+    /// Nothing is defined when any rule fails to parse.
+    pub fn define(&self, config: &Config, defaults: &[(&str, &str)]) -> Result<(), PolicyError> {
+        let mut defined = Vec::with_capacity(defaults.len());
+        for (name, default) in defaults {
+            let source = config
+                .get::<String>(&format!("policies.{}", name))?
+                .unwrap_or_else(|| default.to_string());
+            let rule = source.parse::<Rule>().map_err(|e| PolicyError::Invalid {
+                name: name.to_string(),
+                message: e.to_string(),
+            })?;
+            defined.push((name.to_string(), rule));
+        }
+
+        let mut rules = self.rules.write().unwrap();
+        for (name, rule) in defined {
+            tracing::debug!(policy = %name, rule = %rule, "Defined policy");
+            rules.insert(name, rule);
+        }
+        Ok(())
+    }
+
+    /// Set one policy's rule
+    pub fn set(&self, name: &str, rule: Rule) {
+        self.rules.write().unwrap().insert(name.to_string(), rule);
+    }
+
+    /// Forget policies, e.g. when the app defining them is deactivated
+    pub fn remove<'a>(&self, names: impl IntoIterator<Item = &'a str>) {
+        let mut rules = self.rules.write().unwrap();
+        for name in names {
+            rules.remove(name);
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<Rule> {
+        self.rules.read().unwrap().get(name).cloned()
+    }
+
+    /// Whether `subject` may access `resource` under the named policy
+    pub fn check(&self, name: &str, subject: &Subject, resource: &dyn Resource) -> Result<bool, PolicyError> {
+        let rules = self.rules.read().unwrap();
+        let rule = rules.get(name).ok_or_else(|| PolicyError::Unknown(name.to_string()))?;
+        Ok(rule.allows(subject, resource))
+    }
+}
+
+// ============================================
+// Extractor
+// ============================================
+
+/// A request allowed by policy `P`, with the resource it targets
+///
+/// The user is optional: requests without an `Authorization` header are
+/// checked as anonymous, while a bad or expired token is still rejected.
+pub struct Authorize<P: Policy> {
+    pub subject: Subject,
+    pub resource: P::Resource,
+    policy: PhantomData<P>,
+}
+
+impl<P: Policy> Authorize<P> {
+    /// The user's id; `None` when a policy allows anonymous access
+    pub fn user_id(&self) -> Option<Uuid> {
+        self.subject.id
+    }
+}
+
+#[async_trait]
+impl<S, P> FromRequestParts<S> for Authorize<P>
+where
+    S: Send + Sync,
+    P: Policy,
+    P::Resource: ResourceLoader<S>,
+{
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let subject = if parts.extensions.get::<AccessTokenClaims>().is_some()
+            || parts.headers.contains_key("Authorization")
+        {
+            let user = AuthUser::from_request_parts(parts, state).await?;
+            Subject {
+                id: Some(user.id),
+                role: P::Resource::role(parts, state, &user).await?,
+            }
+        } else {
+            Subject::anonymous()
+        };
+
+        let resource = P::Resource::load(parts, state).await?;
+
+        match shared().check(P::NAME, &subject, &resource) {
+            Ok(true) => Ok(Self {
+                subject,
+                resource,
+                policy: PhantomData,
+            }),
+            Ok(false) if !subject.is_authenticated() => {
+                Err(ProblemDetails::unauthorized("Authentication required").into_response())
+            }
+            Ok(false) => Err(ProblemDetails::new(StatusCode::FORBIDDEN, "permission_denied")
+                .detail("You don't have permission to perform this action")
+                .into_response()),
+            Err(e) => {
+                tracing::error!(policy = P::NAME, "Policy check failed: {}", e);
+                Err(ProblemDetails::internal("Authorization is not configured").into_response())
+            }
+        }
+    }
+}
+
+// ============================================
+// Tests
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Doc {
+        owner: Uuid,
+        status: &'static str,
+    }
+
+    impl Resource for Doc {
+        fn owner_id(&self) -> Option<Uuid> {
+            Some(self.owner)
+        }
+
+        fn attribute(&self, name: &str) -> Option<String> {
+            (name == "status").then(|| self.status.to_string())
+        }
+    }
+
+    fn user(id: Uuid, role: &str) -> Subject {
+        Subject {
+            id: Some(id),
+            role: Some(role.to_string()),
+        }
+    }
+
+    fn allows(rule: &str, subject: &Subject, resource: &dyn Resource) -> bool {
+        rule.parse::<Rule>().unwrap().allows(subject, resource)
+    }
+
+    #[test]
+    fn test_owner_or_role() {
+        let owner = Uuid::new_v4();
+        let doc = Doc { owner, status: "draft" };
+        let rule = "owner || role in [editor, admin]";
+
+        assert!(allows(rule, &user(owner, "author"), &doc));
+        assert!(allows(rule, &user(Uuid::new_v4(), "editor"), &doc));
+        assert!(!allows(rule, &user(Uuid::new_v4(), "author"), &doc));
+        assert!(!allows(rule, &Subject::anonymous(), &doc));
+        assert!(!allows("owner", &Subject::anonymous(), &()));
+    }
+
+    #[test]
+    fn test_precedence_and_attributes() {
+        let owner = Uuid::new_v4();
+        let doc = Doc { owner, status: "published" };
+        let other = user(Uuid::new_v4(), "admin");
+
+        // `&&` binds tighter than `||`
+        assert!(allows("role == admin || owner && false", &other, &doc));
+        assert!(!allows("(role == admin || owner) && false", &other, &doc));
+
+        assert!(allows("resource.status == \"published\"", &other, &doc));
+        assert!(allows("!(resource.status in ['draft', scheduled])", &other, &doc));
+        assert!(allows("resource.missing != x", &other, &doc));
+        assert!(!allows("resource.missing in [x]", &other, &doc));
+        assert!(allows("authenticated && role != subscriber", &other, &doc));
+        assert!(!allows("role in []", &other, &doc));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for rule in ["", "owner ||", "role", "role in [a b]", "(owner", "owner)", "user == x", "resource. == x", "\"x", "owner $"] {
+            let err = rule.parse::<Rule>().unwrap_err();
+            assert!(matches!(err, PolicyError::Parse { .. }), "{}: {}", rule, err);
+        }
+    }
+
+    #[test]
+    fn test_define_reads_overrides() {
+        let config = Config::from_toml("[policies.posts]\ndelete = \"role == admin\"").unwrap();
+        let policies = Policies::default();
+        policies
+            .define(&config, &[("posts.update", "owner"), ("posts.delete", "owner")])
+            .unwrap();
+
+        let owner = Uuid::new_v4();
+        let doc = Doc { owner, status: "draft" };
+        assert!(policies.check("posts.update", &user(owner, "author"), &doc).unwrap());
+        assert!(!policies.check("posts.delete", &user(owner, "author"), &doc).unwrap());
+        assert!(matches!(
+            policies.check("posts.publish", &user(owner, "admin"), &doc),
+            Err(PolicyError::Unknown(_))
+        ));
+
+        // A bad override defines nothing
+        let config = Config::from_toml("[policies.tags]\ndelete = \"role ==\"").unwrap();
+        let err = policies.define(&config, &[("tags.create", "true"), ("tags.delete", "true")]);
+        assert!(matches!(err, Err(PolicyError::Invalid { ref name, .. }) if name == "tags.delete"), "{:?}", err);
+        assert!(policies.get("tags.create").is_none());
+
+        policies.remove(["posts.update"]);
+        assert!(policies.get("posts.update").is_none());
+    }
+}
@@ -0,0 +1,446 @@
+//! Problem Details
+//!
+//! RFC 9457 `application/problem+json` error responses shared by this plugin
+//! and the apps and plugins built on it. Every error carries a stable `code`,
+//! the request's trace ID (see `trace_id`) and, for validation failures, one
+//! entry per invalid field.
+//!
+//! The `trace_id` middleware also runs each request inside a `request` span
+//! with a `request_id` field. Everything logged while handling it, including
+//! service spans and the statements sqlx logs under `sqlx::query`, carries
+//! that field, so the ID quoted from an error response finds every line of the
+//! request. Work spawned from a handler keeps it through [`in_current_trace`].
+//!
+//! Details and field messages are in the request's locale; see `i18n`.
+
+use crate::i18n;
+
+use axum::{
+    extract::Request,
+    http::{header, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use tracing::Instrument;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Media type of problem responses
+pub const PROBLEM_CONTENT_TYPE: &str = "application/problem+json";
+
+/// Base URI of the documented problem types
+pub const PROBLEM_TYPE_BASE: &str = "https://rustpress.dev/problems";
+
+/// Header carrying the trace ID in requests and responses
+pub const TRACE_ID_HEADER: &str = "x-request-id";
+
+tokio::task_local! {
+    static TRACE_ID: String;
+}
+
+/// RFC 9457 problem details
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ProblemDetails {
+    /// URI identifying the problem type
+    #[serde(rename = "type")]
+    pub problem_type: String,
+    /// Short summary of the problem type
+    pub title: String,
+    /// HTTP status code
+    pub status: u16,
+    /// Explanation specific to this occurrence
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    /// URI of the request that failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instance: Option<String>,
+    /// Machine-readable error code
+    pub code: String,
+    /// Trace ID to quote when reporting the error
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trace_id: Option<String>,
+    /// Per-field validation failures
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<FieldError>,
+}
+
+/// One invalid field
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct FieldError {
+    /// Field name as sent by the client
+    pub field: String,
+    /// Validation rule that failed (e.g. `length`, `email`)
+    pub code: String,
+    pub message: String,
+    /// Value that was rejected; omitted for secrets such as passwords
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rejected_value: Option<serde_json::Value>,
+}
+
+impl ProblemDetails {
+    /// Problem with the given status and error code
+    pub fn new(status: StatusCode, code: &str) -> Self {
+        Self {
+            problem_type: format!("{}/{}", PROBLEM_TYPE_BASE, code.replace('_', "-")),
+            title: status.canonical_reason().unwrap_or("Error").to_string(),
+            status: status.as_u16(),
+            detail: None,
+            instance: None,
+            code: code.to_string(),
+            trace_id: current_trace_id(),
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn detail(mut self, detail: impl Into<String>) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+
+    pub fn instance(mut self, instance: impl Into<String>) -> Self {
+        self.instance = Some(instance.into());
+        self
+    }
+
+    pub fn with_errors(mut self, errors: Vec<FieldError>) -> Self {
+        self.errors = errors;
+        self
+    }
+
+    /// 400 `validation_error`
+    pub fn validation(detail: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, "validation_error").detail(detail)
+    }
+
+    /// 404 `not_found`
+    pub fn not_found(detail: impl Into<String>) -> Self {
+        Self::new(StatusCode::NOT_FOUND, "not_found").detail(detail)
+    }
+
+    /// 401 `unauthorized`
+    pub fn unauthorized(detail: impl Into<String>) -> Self {
+        Self::new(StatusCode::UNAUTHORIZED, "unauthorized").detail(detail)
+    }
+
+    /// 403 `forbidden`
+    pub fn forbidden(detail: impl Into<String>) -> Self {
+        Self::new(StatusCode::FORBIDDEN, "forbidden").detail(detail)
+    }
+
+    /// 500 `internal_error`; the detail is meant for clients, log the cause separately
+    pub fn internal(detail: impl Into<String>) -> Self {
+        Self::new(StatusCode::INTERNAL_SERVER_ERROR, "internal_error").detail(detail)
+    }
+
+    /// 503 `service_unavailable`
+    pub fn unavailable(detail: impl Into<String>) -> Self {
+        Self::new(StatusCode::SERVICE_UNAVAILABLE, "service_unavailable").detail(detail)
+    }
+
+    pub fn status_code(&self) -> StatusCode {
+        StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+}
+
+impl From<validator::ValidationErrors> for ProblemDetails {
+    fn from(errors: validator::ValidationErrors) -> Self {
+        Self::validation(i18n::t("validation-failed")).with_errors(field_errors(&errors))
+    }
+}
+
+impl IntoResponse for ProblemDetails {
+    fn into_response(self) -> Response {
+        let mut response = (self.status_code(), Json(self)).into_response();
+        response
+            .headers_mut()
+            .insert(header::CONTENT_TYPE, HeaderValue::from_static(PROBLEM_CONTENT_TYPE));
+        response
+    }
+}
+
+/// Flatten validator errors into sorted per-field entries
+///
+/// Nested structs and lists are reported with dotted / indexed paths
+/// (`address.city`, `items[2].name`). The rejected value is echoed back
+/// unless the field name looks like a secret.
+pub fn field_errors(errors: &validator::ValidationErrors) -> Vec<FieldError> {
+    let mut fields = Vec::new();
+    collect_field_errors(errors, "", &mut fields);
+    fields.sort_by(|a, b| a.field.cmp(&b.field));
+    fields
+}
+
+fn collect_field_errors(errors: &validator::ValidationErrors, prefix: &str, out: &mut Vec<FieldError>) {
+    use validator::ValidationErrorsKind;
+
+    for (field, kind) in errors.errors() {
+        let path = if prefix.is_empty() {
+            field.to_string()
+        } else {
+            format!("{}.{}", prefix, field)
+        };
+
+        match kind {
+            ValidationErrorsKind::Field(list) => {
+                out.extend(list.iter().map(|e| FieldError {
+                    field: path.clone(),
+                    code: e.code.to_string(),
+                    message: field_message(e),
+                    rejected_value: if is_secret_field(field) {
+                        None
+                    } else {
+                        e.params.get("value").cloned()
+                    },
+                }));
+            }
+            ValidationErrorsKind::Struct(nested) => collect_field_errors(nested, &path, out),
+            ValidationErrorsKind::List(items) => {
+                for (index, nested) in items {
+                    collect_field_errors(nested, &format!("{}[{}]", path, index), out);
+                }
+            }
+        }
+    }
+}
+
+/// Message for a failed rule, in the request's locale
+///
+/// A validator's own message is English, so it's used as is in English and
+/// where the catalogs have no message for the rule; other locales get the
+/// rule's message (`validation-length-min` etc.) with its parameters.
+fn field_message(error: &validator::ValidationError) -> String {
+    let locale = i18n::current_locale();
+    let explicit = error.message.as_ref().map(|m| m.to_string());
+    if locale == i18n::DEFAULT_LOCALE {
+        if let Some(message) = explicit {
+            return message;
+        }
+    }
+
+    let params: Vec<(String, String)> = error
+        .params
+        .iter()
+        .filter(|(name, _)| *name != "value")
+        .map(|(name, value)| {
+            let value = value.as_str().map(String::from).unwrap_or_else(|| value.to_string());
+            (name.to_string(), value)
+        })
+        .collect();
+    let has = |name: &str| params.iter().any(|(n, _)| n == name);
+    let id = match error.code.as_ref() {
+        "length" if has("equal") => "validation-length-equal".to_string(),
+        // `length(min = 1)` is how validator spells "required"
+        "length" if !has("max") && params.iter().any(|(n, v)| n == "min" && v == "1") => {
+            "validation-required".to_string()
+        }
+        code @ ("length" | "range") => match (has("min"), has("max")) {
+            (true, true) => format!("validation-{}", code),
+            (true, false) => format!("validation-{}-min", code),
+            _ => format!("validation-{}-max", code),
+        },
+        code => format!("validation-{}", code.replace('_', "-")),
+    };
+
+    let mut args: Vec<(&str, &str)> = params.iter().map(|(n, v)| (n.as_str(), v.as_str())).collect();
+    args.push(("code", error.code.as_ref()));
+    i18n::lookup(&locale, &id, &args)
+        .or(explicit)
+        .or_else(|| i18n::lookup(i18n::DEFAULT_LOCALE, &id, &args))
+        .unwrap_or_else(|| i18n::translate(&locale, "validation-invalid", &args))
+}
+
+fn is_secret_field(field: &str) -> bool {
+    let field = field.to_ascii_lowercase();
+    ["password", "secret", "token"].iter().any(|s| field.contains(s))
+}
+
+/// Trace ID of the request being handled, if `trace_id` middleware is installed
+pub fn current_trace_id() -> Option<String> {
+    TRACE_ID.try_with(|id| id.clone()).ok()
+}
+
+/// Run `future` with the current trace ID and span, for work spawned while
+/// handling a request
+pub fn in_current_trace<F: Future>(future: F) -> impl Future<Output = F::Output> {
+    let span = tracing::Span::current();
+    let id = current_trace_id();
+    async move {
+        match id {
+            Some(id) => TRACE_ID.scope(id, future).await,
+            None => future.await,
+        }
+    }
+    .instrument(span)
+}
+
+/// Middleware assigning each request a trace ID
+///
+/// Reuses a client-supplied `X-Request-Id` (printable ASCII, at most 128
+/// characters) or generates one, makes it available to `ProblemDetails` while
+/// the request is handled, runs the request in a `request` span carrying it as
+/// `request_id`, and echoes it in the response.
+pub async fn trace_id(req: Request, next: Next) -> Response {
+    let id = req
+        .headers()
+        .get(TRACE_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|v| !v.is_empty() && v.len() <= 128 && v.bytes().all(|b| b.is_ascii_graphic()))
+        .map(String::from)
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+
+    let span = tracing::info_span!(
+        "request",
+        request_id = %id,
+        method = %req.method(),
+        path = %req.uri().path(),
+    );
+    let mut response = TRACE_ID.scope(id.clone(), next.run(req)).instrument(span.clone()).await;
+    if response.status().is_server_error() {
+        span.in_scope(|| tracing::warn!(status = response.status().as_u16(), "Request failed"));
+    }
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        response.headers_mut().insert(TRACE_ID_HEADER, value);
+    }
+    response
+}
+
+// ============================================
+// Tests
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, middleware, routing::get, Router};
+    use tower::ServiceExt;
+    use validator::Validate;
+
+    #[derive(Validate)]
+    struct Signup {
+        #[validate(email(message = "Invalid email format"))]
+        email: String,
+        #[validate(length(min = 8))]
+        password: String,
+    }
+
+    #[test]
+    fn test_problem_shape() {
+        let problem = ProblemDetails::not_found("Post not found");
+        let json = serde_json::to_value(&problem).unwrap();
+
+        assert_eq!(json["type"], "https://rustpress.dev/problems/not-found");
+        assert_eq!(json["title"], "Not Found");
+        assert_eq!(json["status"], 404);
+        assert_eq!(json["code"], "not_found");
+        assert_eq!(json["detail"], "Post not found");
+        assert!(json.get("errors").is_none());
+        assert!(json.get("trace_id").is_none());
+    }
+
+    #[test]
+    fn test_validation_field_errors() {
+        let signup = Signup {
+            email: "nope".into(),
+            password: "short".into(),
+        };
+        let problem = ProblemDetails::from(signup.validate().unwrap_err());
+
+        assert_eq!(problem.status, 400);
+        assert_eq!(problem.errors.len(), 2);
+        assert_eq!(problem.errors[0].field, "email");
+        assert_eq!(problem.errors[0].message, "Invalid email format");
+        assert_eq!(problem.errors[1].field, "password");
+        assert_eq!(problem.errors[1].code, "length");
+    }
+
+    #[tokio::test]
+    async fn test_field_errors_are_localized() {
+        let signup = Signup {
+            email: "nope".into(),
+            password: "short".into(),
+        };
+        let errors = signup.validate().unwrap_err();
+
+        // English keeps the validator's own messages
+        let english = field_errors(&errors);
+        assert_eq!(english[0].message, "Invalid email format");
+        assert_eq!(english[1].message, "Must be at least 8 characters");
+
+        let german = i18n::in_locale("de", async { ProblemDetails::from(errors) }).await;
+        assert_eq!(german.detail.as_deref(), Some("Validierung der Anfrage fehlgeschlagen"));
+        assert_eq!(german.errors[0].message, "Ungültige E-Mail-Adresse");
+        assert_eq!(german.errors[1].message, "Muss mindestens 8 Zeichen lang sein");
+    }
+
+    #[test]
+    fn test_rejected_values_skip_secrets() {
+        let signup = Signup {
+            email: "nope".into(),
+            password: "short".into(),
+        };
+        let errors = field_errors(&signup.validate().unwrap_err());
+
+        assert_eq!(errors[0].rejected_value, Some(serde_json::json!("nope")));
+        assert_eq!(errors[1].rejected_value, None);
+    }
+
+    #[tokio::test]
+    async fn test_response_carries_trace_id() {
+        let router = Router::new()
+            .route("/", get(|| async { ProblemDetails::forbidden("No") }))
+            .layer(middleware::from_fn(trace_id));
+
+        let response = router
+            .oneshot(
+                Request::builder()
+                    .uri("/")
+                    .header(TRACE_ID_HEADER, "abc-123")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], PROBLEM_CONTENT_TYPE);
+        assert_eq!(response.headers()[TRACE_ID_HEADER], "abc-123");
+
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let problem: ProblemDetails = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(problem.trace_id.as_deref(), Some("abc-123"));
+    }
+
+    #[tokio::test]
+    async fn test_spawned_work_keeps_trace_id() {
+        let router = Router::new()
+            .route(
+                "/",
+                get(|| async {
+                    tokio::spawn(in_current_trace(async { current_trace_id().unwrap_or_default() }))
+                        .await
+                        .unwrap()
+                }),
+            )
+            .layer(middleware::from_fn(trace_id));
+
+        let response = router
+            .oneshot(
+                Request::builder()
+                    .uri("/")
+                    .header(TRACE_ID_HEADER, "bad id")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        // Unprintable IDs are replaced with a generated one
+        let id = response.headers()[TRACE_ID_HEADER].to_str().unwrap().to_string();
+        assert!(Uuid::parse_str(&id).is_ok());
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(String::from_utf8_lossy(&bytes), id);
+    }
+}
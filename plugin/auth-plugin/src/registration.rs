@@ -0,0 +1,282 @@
+//! Registration Policy
+//!
+//! `register` checks the domain of a new account's email address before
+//! creating it:
+//!
+//! - with `registration.allowed_domains` set, only those domains may register
+//! - `registration.denied_domains` may never register
+//! - with `registration.block_disposable` (the default), neither may the
+//!   throwaway mail services bundled in `data/disposable-domains.txt`
+//!
+//! A listed domain covers its subdomains too (`example.com` matches
+//! `mail.example.com`). Refused registrations fail with 403
+//! `registration_rejected`.
+//!
+//! ```toml
+//! [registration]
+//! allowed_domains = ["example.com"] # REGISTRATION_ALLOWED_DOMAINS=example.com,example.org
+//! denied_domains = []
+//! block_disposable = true
+//! ```
+//!
+//! Plugins then see each registration through the filters installed with
+//! [`install_filter`], which may veto it or change it, e.g. to give staff
+//! addresses a role without a separate SSO setup:
+//!
+//! ```rust,ignore
+//! struct StaffEditors;
+//!
+//! #[async_trait]
+//! impl RegistrationFilter for StaffEditors {
+//!     async fn filter(&self, mut registration: Registration) -> Result<Registration, String> {
+//!         if registration.domain() == "staff.example.com" {
+//!             registration.role = UserRole::Editor;
+//!         }
+//!         Ok(registration)
+//!     }
+//! }
+//!
+//! registration::install_filter("my-plugin", Some(Arc::new(StaffEditors)));
+//! ```
+//!
+//! Filters run in the order of the names they were installed under, each
+//! seeing the changes of the ones before.
+
+use crate::config::{Config, ConfigError};
+use crate::error::AuthError;
+use crate::models::{UserRole, UserStatus};
+
+use async_trait::async_trait;
+use std::collections::{BTreeMap, HashSet};
+use std::sync::{Arc, OnceLock, RwLock};
+
+/// Bundled disposable mail domains, one per line with `#` comments
+const DISPOSABLE_DOMAINS: &str = include_str!("../data/disposable-domains.txt");
+
+static FILTERS: RwLock<BTreeMap<String, Arc<dyn RegistrationFilter>>> = RwLock::new(BTreeMap::new());
+
+/// Which email domains may register (`[registration]`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegistrationPolicy {
+    /// Domains that may register; any when empty
+    pub allowed_domains: Vec<String>,
+    /// Domains that may not register
+    pub denied_domains: Vec<String>,
+    /// Refuse the bundled disposable mail domains
+    pub block_disposable: bool,
+}
+
+impl Default for RegistrationPolicy {
+    fn default() -> Self {
+        Self {
+            allowed_domains: Vec::new(),
+            denied_domains: Vec::new(),
+            block_disposable: true,
+        }
+    }
+}
+
+impl RegistrationPolicy {
+    /// Load the policy from the `[registration]` table of the [`Config`]
+    pub fn from_config(config: &Config) -> Result<Self, ConfigError> {
+        let domains = |key: &str| -> Result<Vec<String>, ConfigError> {
+            Ok(config
+                .list(key)?
+                .unwrap_or_default()
+                .iter()
+                .map(|domain| normalize(domain))
+                .filter(|domain| !domain.is_empty())
+                .collect())
+        };
+
+        Ok(Self {
+            allowed_domains: domains("registration.allowed_domains")?,
+            denied_domains: domains("registration.denied_domains")?,
+            block_disposable: config.get_or("registration.block_disposable", true)?,
+        })
+    }
+
+    /// Refuse `email` when its domain may not register
+    pub fn check(&self, email: &str) -> Result<(), AuthError> {
+        let domain = domain_of(email);
+
+        let allowed = self.allowed_domains.is_empty() || listed(&domain, &self.allowed_domains);
+        if !allowed || listed(&domain, &self.denied_domains) {
+            return Err(AuthError::RegistrationRejected(format!(
+                "Registration is not open to {} addresses",
+                domain
+            )));
+        }
+        if self.block_disposable && is_disposable(&domain) {
+            return Err(AuthError::RegistrationRejected(
+                "Disposable email addresses can't be used to register".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Lower-cased domain of an email address
+pub fn domain_of(email: &str) -> String {
+    normalize(email.rsplit_once('@').map_or("", |(_, domain)| domain))
+}
+
+fn normalize(domain: &str) -> String {
+    domain
+        .trim()
+        .trim_start_matches("*.")
+        .trim_start_matches('@')
+        .trim_end_matches('.')
+        .to_ascii_lowercase()
+}
+
+/// Whether `domain` is one of `domains` or a subdomain of one
+fn listed<S: AsRef<str>>(domain: &str, domains: &[S]) -> bool {
+    domains.iter().any(|listed| {
+        let listed = listed.as_ref();
+        domain == listed
+            || domain
+                .strip_suffix(listed)
+                .is_some_and(|prefix| prefix.ends_with('.'))
+    })
+}
+
+/// Whether `domain` belongs to a bundled disposable mail service
+pub fn is_disposable(domain: &str) -> bool {
+    static DOMAINS: OnceLock<HashSet<&'static str>> = OnceLock::new();
+    let domains = DOMAINS.get_or_init(|| {
+        DISPOSABLE_DOMAINS
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .collect()
+    });
+
+    // The domain itself and each parent domain
+    let mut candidate = domain;
+    loop {
+        if domains.contains(candidate) {
+            return true;
+        }
+        match candidate.split_once('.') {
+            Some((_, parent)) if parent.contains('.') => candidate = parent,
+            _ => return false,
+        }
+    }
+}
+
+/// A registration about to be stored
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Registration {
+    pub email: String,
+    pub name: String,
+    /// `user` unless a filter assigns another
+    pub role: UserRole,
+    /// `active` unless a filter holds the account, e.g. `pending` approval
+    pub status: UserStatus,
+}
+
+impl Registration {
+    /// Lower-cased domain of the email address
+    pub fn domain(&self) -> String {
+        domain_of(&self.email)
+    }
+}
+
+/// Hook run on every registration after the domain policy
+#[async_trait]
+pub trait RegistrationFilter: Send + Sync {
+    /// The registration to store, or why it is refused
+    async fn filter(&self, registration: Registration) -> Result<Registration, String>;
+}
+
+/// Install `filter` under `name`, replacing the one installed under it
+/// before; `None` removes it
+pub fn install_filter(name: &str, filter: Option<Arc<dyn RegistrationFilter>>) {
+    let mut filters = FILTERS.write().unwrap();
+    match filter {
+        Some(filter) => filters.insert(name.to_string(), filter),
+        None => filters.remove(name),
+    };
+}
+
+/// Run `registration` through the installed filters
+pub async fn apply_filters(mut registration: Registration) -> Result<Registration, AuthError> {
+    let filters: Vec<_> = FILTERS.read().unwrap().values().cloned().collect();
+    for filter in filters {
+        registration = filter
+            .filter(registration)
+            .await
+            .map_err(AuthError::RegistrationRejected)?;
+    }
+    Ok(registration)
+}
+
+// ============================================
+// Tests
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_domain_of() {
+        assert_eq!(domain_of("Ada@Example.COM"), "example.com");
+        assert_eq!(domain_of("\"a@b\"@example.org"), "example.org");
+        assert_eq!(domain_of("nobody"), "");
+    }
+
+    #[test]
+    fn test_listed_covers_subdomains() {
+        let domains = ["example.com"];
+        assert!(listed("example.com", &domains));
+        assert!(listed("mail.example.com", &domains));
+        assert!(!listed("badexample.com", &domains));
+        assert!(!listed("example.com.evil.org", &domains));
+    }
+
+    #[test]
+    fn test_disposable_domains() {
+        assert!(is_disposable("mailinator.com"));
+        assert!(is_disposable("eu.mailinator.com"));
+        assert!(!is_disposable("example.com"));
+        assert!(!is_disposable("com"));
+    }
+
+    #[test]
+    fn test_policy_check() {
+        let open = RegistrationPolicy::default();
+        open.check("ada@example.com").unwrap();
+        assert!(matches!(
+            open.check("ada@yopmail.com"),
+            Err(AuthError::RegistrationRejected(_))
+        ));
+
+        let policy = RegistrationPolicy {
+            allowed_domains: vec!["example.com".to_string(), "yopmail.com".to_string()],
+            denied_domains: vec!["guests.example.com".to_string()],
+            block_disposable: false,
+        };
+        policy.check("ada@staff.example.com").unwrap();
+        policy.check("ada@yopmail.com").unwrap();
+        assert!(policy.check("ada@example.org").is_err());
+        assert!(policy.check("ada@guests.example.com").is_err());
+    }
+
+    #[test]
+    fn test_policy_from_config() {
+        let config = Config::from_toml(
+            r#"
+            [registration]
+            allowed_domains = ["@Example.com", "*.example.org"]
+            block_disposable = false
+            "#,
+        )
+        .unwrap();
+        let policy = RegistrationPolicy::from_config(&config).unwrap();
+        assert_eq!(policy.allowed_domains, ["example.com", "example.org"]);
+        assert!(policy.denied_domains.is_empty());
+        assert!(!policy.block_disposable);
+    }
+}
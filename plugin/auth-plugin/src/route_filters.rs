@@ -0,0 +1,481 @@
+//! Route Filters
+//!
+//! Plugins hook into requests for routes they don't own through the shared
+//! [`RouteFilters`]: request filters run before the handler and may change
+//! the request or answer it themselves, response filters run after it and
+//! may change the response. Each is registered on a path pattern:
+//!
+//! - `/api/v1/posts` matches that path only
+//! - `:name` (or `{name}`) matches any one segment: `/api/v1/posts/:id`
+//! - a trailing `*` matches the rest of the path, including nothing:
+//!   `/api/v1/rustpress-analytics/reports/*` covers `/reports` and
+//!   everything below it
+//!
+//! ```rust,ignore
+//! let filters = rustpress_auth::route_filters::shared();
+//!
+//! // Only partners may read reports
+//! filters.on_request(
+//!     "my-plugin",
+//!     "/api/v1/rustpress-analytics/reports/*",
+//!     10,
+//!     Arc::new(ApiKey::new(HeaderName::from_static("x-api-key"), ["..."])),
+//! )?;
+//!
+//! // Tell clients where the docs are
+//! let headers = HeaderMap::from_iter([(HeaderName::from_static("x-docs"), HeaderValue::from_static("/docs"))]);
+//! filters.on_response("my-plugin", "/api/v1/*", 10, Arc::new(AddHeaders(headers)))?;
+//! ```
+//!
+//! Filters run in ascending priority, those with the same priority in the
+//! order they were registered; each sees what the ones before made of the
+//! request or response. When a request filter answers a request, the
+//! remaining request filters and the handler are skipped, but the response
+//! filters still run.
+//!
+//! Plugin APIs mounted through the [`routes`](crate::routes) registry are
+//! filtered; apps apply [`apply`] to their own routers. Patterns are matched
+//! against the path as it reaches that router. Plugins remove their filters
+//! with [`RouteFilters::unregister`] when deactivated.
+
+use crate::problem::ProblemDetails;
+
+use async_trait::async_trait;
+use axum::{
+    extract::Request,
+    http::{HeaderMap, HeaderName, Method, Uri},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::collections::HashSet;
+use std::sync::{Arc, OnceLock, RwLock};
+
+static SHARED: OnceLock<Arc<RouteFilters>> = OnceLock::new();
+
+/// The process-wide filters shared by apps and plugins
+pub fn shared() -> Arc<RouteFilters> {
+    SHARED.get_or_init(|| Arc::new(RouteFilters::default())).clone()
+}
+
+/// Why a filter could not be registered
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum PatternError {
+    #[error("Route pattern \"{0}\" must start with '/'")]
+    NotAbsolute(String),
+
+    #[error("Route pattern \"{0}\" may only end with '*'")]
+    MisplacedWildcard(String),
+}
+
+/// Hook run before the handlers of matching routes
+#[async_trait]
+pub trait RequestFilter: Send + Sync {
+    /// The request to pass on, or the response to answer it with instead
+    async fn filter(&self, request: Request) -> Result<Request, Response>;
+}
+
+/// Hook run on the responses of matching routes
+#[async_trait]
+pub trait ResponseFilter: Send + Sync {
+    /// The response to send
+    async fn filter(&self, request: &RequestHead, response: Response) -> Response;
+}
+
+/// What response filters know of the request: as it reached the handler,
+/// or as it arrived when a request filter answered it
+#[derive(Debug, Clone)]
+pub struct RequestHead {
+    pub method: Method,
+    pub uri: Uri,
+    pub headers: HeaderMap,
+}
+
+impl RequestHead {
+    fn of(request: &Request) -> Self {
+        Self {
+            method: request.method().clone(),
+            uri: request.uri().clone(),
+            headers: request.headers().clone(),
+        }
+    }
+}
+
+/// A path pattern filters are registered on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoutePattern {
+    pattern: String,
+    segments: Vec<Segment>,
+    /// Ends with `*`
+    rest: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    Param,
+}
+
+impl RoutePattern {
+    pub fn parse(pattern: &str) -> Result<Self, PatternError> {
+        let Some(path) = pattern.strip_prefix('/') else {
+            return Err(PatternError::NotAbsolute(pattern.to_string()));
+        };
+
+        let parts: Vec<&str> = path.split('/').filter(|part| !part.is_empty()).collect();
+        let rest = parts.last().is_some_and(|part| part.starts_with('*'));
+        let fixed = if rest { &parts[..parts.len() - 1] } else { &parts[..] };
+
+        let mut segments = Vec::with_capacity(fixed.len());
+        for part in fixed {
+            segments.push(match part.chars().next() {
+                Some('*') => return Err(PatternError::MisplacedWildcard(pattern.to_string())),
+                Some(':' | '{') => Segment::Param,
+                _ => Segment::Literal(part.to_string()),
+            });
+        }
+
+        Ok(Self {
+            pattern: pattern.to_string(),
+            segments,
+            rest,
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+
+    pub fn matches(&self, path: &str) -> bool {
+        let mut parts = path.split('/').filter(|part| !part.is_empty());
+        for segment in &self.segments {
+            match (segment, parts.next()) {
+                (Segment::Literal(literal), Some(part)) if literal == part => {}
+                (Segment::Param, Some(_)) => {}
+                _ => return false,
+            }
+        }
+        self.rest || parts.next().is_none()
+    }
+}
+
+#[derive(Clone)]
+enum Filter {
+    Request(Arc<dyn RequestFilter>),
+    Response(Arc<dyn ResponseFilter>),
+}
+
+#[derive(Clone)]
+struct Entry {
+    owner: String,
+    pattern: RoutePattern,
+    priority: i32,
+    filter: Filter,
+}
+
+/// Registered request and response filters
+#[derive(Default)]
+pub struct RouteFilters {
+    /// Sorted by priority, then registration
+    entries: RwLock<Vec<Entry>>,
+}
+
+impl RouteFilters {
+    /// Run `filter` before the handlers of routes matching `pattern`
+    pub fn on_request(
+        &self,
+        owner: &str,
+        pattern: &str,
+        priority: i32,
+        filter: Arc<dyn RequestFilter>,
+    ) -> Result<(), PatternError> {
+        self.add(owner, pattern, priority, Filter::Request(filter))
+    }
+
+    /// Run `filter` on the responses of routes matching `pattern`
+    pub fn on_response(
+        &self,
+        owner: &str,
+        pattern: &str,
+        priority: i32,
+        filter: Arc<dyn ResponseFilter>,
+    ) -> Result<(), PatternError> {
+        self.add(owner, pattern, priority, Filter::Response(filter))
+    }
+
+    fn add(&self, owner: &str, pattern: &str, priority: i32, filter: Filter) -> Result<(), PatternError> {
+        let pattern = RoutePattern::parse(pattern)?;
+        let mut entries = self.entries.write().unwrap();
+        let at = entries.partition_point(|entry| entry.priority <= priority);
+        entries.insert(
+            at,
+            Entry {
+                owner: owner.to_string(),
+                pattern,
+                priority,
+                filter,
+            },
+        );
+        Ok(())
+    }
+
+    /// Remove an owner's filters; returns how many were removed
+    pub fn unregister(&self, owner: &str) -> usize {
+        let mut entries = self.entries.write().unwrap();
+        let before = entries.len();
+        entries.retain(|entry| entry.owner != owner);
+        before - entries.len()
+    }
+
+    /// Run a request through the filters matching its path and `next`
+    pub async fn handle(&self, request: Request, next: Next) -> Response {
+        let path = request.uri().path().to_string();
+        let filters: Vec<Entry> = self
+            .entries
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|entry| entry.pattern.matches(&path))
+            .cloned()
+            .collect();
+        if filters.is_empty() {
+            return next.run(request).await;
+        }
+
+        let responds = filters.iter().any(|entry| matches!(entry.filter, Filter::Response(_)));
+        let mut head = responds.then(|| RequestHead::of(&request));
+
+        // The request, or the response a filter answered it with
+        let mut outcome = Ok(request);
+        for entry in &filters {
+            let Filter::Request(filter) = &entry.filter else {
+                continue;
+            };
+            outcome = match outcome {
+                Ok(request) => filter.filter(request).await,
+                answered => answered,
+            };
+            if outcome.is_err() {
+                tracing::debug!(owner = %entry.owner, pattern = entry.pattern.as_str(), "Request answered by a route filter");
+                break;
+            }
+        }
+
+        let mut response = match outcome {
+            Ok(request) => {
+                if responds {
+                    head = Some(RequestHead::of(&request));
+                }
+                next.run(request).await
+            }
+            Err(response) => response,
+        };
+
+        let Some(head) = head else {
+            return response;
+        };
+        for entry in &filters {
+            if let Filter::Response(filter) = &entry.filter {
+                response = filter.filter(&head, response).await;
+            }
+        }
+        response
+    }
+}
+
+/// Middleware applying the [`shared`] filters
+pub async fn apply(request: Request, next: Next) -> Response {
+    shared().handle(request, next).await
+}
+
+// ============================================
+// Filters
+// ============================================
+
+/// Answers requests without one of the keys in a header with 401
+pub struct ApiKey {
+    header: HeaderName,
+    keys: HashSet<String>,
+}
+
+impl ApiKey {
+    pub fn new(header: HeaderName, keys: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self {
+            header,
+            keys: keys.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+#[async_trait]
+impl RequestFilter for ApiKey {
+    async fn filter(&self, request: Request) -> Result<Request, Response> {
+        let presented = request.headers().get(&self.header).and_then(|v| v.to_str().ok());
+        match presented {
+            Some(key) if self.keys.contains(key) => Ok(request),
+            _ => Err(ProblemDetails::unauthorized(format!("A valid {} header is required", self.header)).into_response()),
+        }
+    }
+}
+
+/// Sets headers on responses, replacing those the handler set
+pub struct AddHeaders(pub HeaderMap);
+
+#[async_trait]
+impl ResponseFilter for AddHeaders {
+    async fn filter(&self, _request: &RequestHead, mut response: Response) -> Response {
+        for (name, value) in &self.0 {
+            response.headers_mut().insert(name, value.clone());
+        }
+        response
+    }
+}
+
+// ============================================
+// Tests
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::HeaderValue, http::StatusCode, middleware, routing::get, Router};
+    use tower::ServiceExt;
+
+    fn router(filters: Arc<RouteFilters>) -> Router {
+        Router::new()
+            .route("/reports/:id", get(|| async { "report" }))
+            .route("/posts", get(|| async { "posts" }))
+            .layer(middleware::from_fn(move |request: Request, next: Next| {
+                let filters = filters.clone();
+                async move { filters.handle(request, next).await }
+            }))
+    }
+
+    async fn send(router: &Router, request: Request) -> (Response, String) {
+        let response = router.clone().oneshot(request).await.unwrap();
+        let (parts, body) = response.into_parts();
+        let body = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+        (Response::from_parts(parts, Body::empty()), String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    fn get_request(uri: &str) -> Request {
+        Request::builder().uri(uri).body(Body::empty()).unwrap()
+    }
+
+    /// Appends its name to the `x-trace` header of requests or responses
+    struct Mark(&'static str);
+
+    fn append(headers: &mut HeaderMap, name: &str) {
+        let trace = match headers.get("x-trace").and_then(|v| v.to_str().ok()) {
+            Some(trace) => format!("{},{}", trace, name),
+            None => name.to_string(),
+        };
+        headers.insert("x-trace", HeaderValue::from_str(&trace).unwrap());
+    }
+
+    #[async_trait]
+    impl RequestFilter for Mark {
+        async fn filter(&self, mut request: Request) -> Result<Request, Response> {
+            append(request.headers_mut(), self.0);
+            Ok(request)
+        }
+    }
+
+    #[async_trait]
+    impl ResponseFilter for Mark {
+        async fn filter(&self, request: &RequestHead, mut response: Response) -> Response {
+            if let Some(trace) = request.headers.get("x-trace") {
+                response.headers_mut().insert("x-request-trace", trace.clone());
+            }
+            append(response.headers_mut(), self.0);
+            response
+        }
+    }
+
+    #[test]
+    fn test_patterns() {
+        let exact = RoutePattern::parse("/api/v1/posts").unwrap();
+        assert!(exact.matches("/api/v1/posts"));
+        assert!(exact.matches("/api/v1/posts/"));
+        assert!(!exact.matches("/api/v1/posts/1"));
+        assert!(!exact.matches("/api/v1"));
+
+        let param = RoutePattern::parse("/posts/:id/comments").unwrap();
+        assert!(param.matches("/posts/42/comments"));
+        assert!(!param.matches("/posts/comments"));
+        assert_eq!(RoutePattern::parse("/posts/{id}/comments").unwrap().segments, param.segments);
+
+        let rest = RoutePattern::parse("/reports/*").unwrap();
+        assert!(rest.matches("/reports"));
+        assert!(rest.matches("/reports/pages/utm"));
+        assert!(!rest.matches("/report"));
+        assert!(RoutePattern::parse("/*").unwrap().matches("/anything/at/all"));
+
+        assert_eq!(
+            RoutePattern::parse("reports"),
+            Err(PatternError::NotAbsolute("reports".to_string()))
+        );
+        assert!(matches!(
+            RoutePattern::parse("/reports/*/pages"),
+            Err(PatternError::MisplacedWildcard(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_filters_run_in_priority_order_on_matching_routes() {
+        let filters = Arc::new(RouteFilters::default());
+        filters.on_request("b", "/reports/*", 20, Arc::new(Mark("late"))).unwrap();
+        filters.on_request("a", "/reports/:id", 10, Arc::new(Mark("early"))).unwrap();
+        filters.on_request("a", "/*", 20, Arc::new(Mark("late-too"))).unwrap();
+        filters.on_response("a", "/reports/*", 5, Arc::new(Mark("first"))).unwrap();
+        filters.on_response("b", "/reports/*", 5, Arc::new(Mark("second"))).unwrap();
+        let router = router(filters.clone());
+
+        let (response, body) = send(&router, get_request("/reports/1")).await;
+        assert_eq!(body, "report");
+        assert_eq!(response.headers()["x-request-trace"], "early,late,late-too");
+        assert_eq!(response.headers()["x-trace"], "first,second");
+
+        // Only the catch-all matches here
+        let (response, _) = send(&router, get_request("/posts")).await;
+        assert!(!response.headers().contains_key("x-trace"));
+
+        assert_eq!(filters.unregister("b"), 2);
+        let (response, _) = send(&router, get_request("/reports/1")).await;
+        assert_eq!(response.headers()["x-request-trace"], "early,late-too");
+        assert_eq!(response.headers()["x-trace"], "first");
+    }
+
+    #[tokio::test]
+    async fn test_api_key_answers_before_the_handler() {
+        let filters = Arc::new(RouteFilters::default());
+        let key = HeaderName::from_static("x-api-key");
+        filters.on_request("a", "/reports/*", 10, Arc::new(ApiKey::new(key, ["secret"]))).unwrap();
+        filters.on_request("a", "/reports/*", 20, Arc::new(Mark("checked"))).unwrap();
+        filters.on_response("a", "/reports/*", 20, Arc::new(Mark("seen"))).unwrap();
+        let headers = HeaderMap::from_iter([(HeaderName::from_static("x-served-by"), HeaderValue::from_static("a"))]);
+        filters.on_response("a", "/*", 10, Arc::new(AddHeaders(headers))).unwrap();
+        let router = router(filters);
+
+        let (response, body) = send(&router, get_request("/reports/1")).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert!(body.contains("x-api-key"));
+        assert_eq!(response.headers()["x-served-by"], "a");
+        // Response filters see the request as it arrived
+        assert!(!response.headers().contains_key("x-request-trace"));
+        assert_eq!(response.headers()["x-trace"], "seen");
+
+        let request = Request::builder()
+            .uri("/reports/1")
+            .header("x-api-key", "secret")
+            .body(Body::empty())
+            .unwrap();
+        let (response, body) = send(&router, request).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(body, "report");
+        assert_eq!(response.headers()["x-request-trace"], "checked");
+
+        let (response, _) = send(&router, get_request("/posts")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["x-served-by"], "a");
+    }
+}
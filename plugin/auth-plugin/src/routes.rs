@@ -0,0 +1,362 @@
+//! Route Registry
+//!
+//! Plugins build their API with [`PluginRoutes`] and mount it through the
+//! shared [`RouteRegistry`], which nests it under `/api/v1/<plugin-id>` and
+//! records every path with its owner:
+//!
+//! ```rust,ignore
+//! let routes = PluginRoutes::new("rustpress-shop")
+//!     .route("/products", get(list_products))
+//!     .route("/products/:id", get(get_product));
+//!
+//! // Serves /api/v1/rustpress-shop/products, ...
+//! let router = rustpress_auth::routes::shared().mount(routes)?;
+//! ctx.register_routes(router).await?;
+//! ```
+//!
+//! Mounting fails with a [`RouteError`] naming both owners when a path is
+//! already registered by someone else (parameter names don't matter:
+//! `/posts/:id` and `/posts/:slug` collide) or when another owner has routes
+//! below the plugin's prefix. Apps and the auth plugin register their own
+//! paths with [`RouteRegistry::register`] (OpenAPI `{id}` parameters are
+//! understood too) so plugins can't shadow them.
+//! `GET /api/v1/system/routes` lists everything registered (admin only).
+//! Mounted routes run through the shared [`route_filters`](crate::route_filters).
+
+use crate::middleware::require_admin;
+use crate::problem::ProblemDetails;
+use crate::route_filters;
+
+use axum::{
+    extract::{Request, State},
+    middleware,
+    response::IntoResponse,
+    routing::{get, MethodRouter, Route},
+    Json, Router,
+};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::sync::{Arc, OnceLock, RwLock};
+use tower::{Layer, Service};
+use utoipa::ToSchema;
+
+/// Prefix under which plugin APIs are mounted
+pub const PLUGIN_API_PREFIX: &str = "/api/v1";
+
+/// Path of the route listing below the API version prefix
+pub const SYSTEM_ROUTES_PATH: &str = "/system/routes";
+
+static SHARED: OnceLock<Arc<RouteRegistry>> = OnceLock::new();
+
+/// The process-wide registry shared by apps and plugins
+pub fn shared() -> Arc<RouteRegistry> {
+    SHARED.get_or_init(|| Arc::new(RouteRegistry::default())).clone()
+}
+
+/// Why routes could not be registered
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum RouteError {
+    #[error("Route {path} of {owner} collides with {existing} registered by {existing_owner}")]
+    Collision {
+        owner: String,
+        path: String,
+        existing: String,
+        existing_owner: String,
+    },
+
+    #[error("Cannot mount {owner} under {prefix}: {existing} is registered by {existing_owner}")]
+    PrefixTaken {
+        owner: String,
+        prefix: String,
+        existing: String,
+        existing_owner: String,
+    },
+
+    #[error("Invalid plugin id \"{0}\" (use lowercase letters, digits, '-' and '_')")]
+    InvalidPluginId(String),
+}
+
+/// A registered route
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct RouteEntry {
+    /// Full path, as passed to the router (`/api/v1/rustpress-shop/products/:id`)
+    pub path: String,
+    /// Plugin or app id
+    pub owner: String,
+}
+
+/// A plugin's routes, recorded for the registry as they are added
+pub struct PluginRoutes<S = ()> {
+    plugin_id: String,
+    router: Router<S>,
+    paths: Vec<String>,
+}
+
+impl<S> PluginRoutes<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    pub fn new(plugin_id: impl Into<String>) -> Self {
+        Self {
+            plugin_id: plugin_id.into(),
+            router: Router::new(),
+            paths: Vec::new(),
+        }
+    }
+
+    /// Add a route; the path is relative to the plugin's prefix
+    pub fn route(mut self, path: &str, method_router: MethodRouter<S>) -> Self {
+        if !self.paths.iter().any(|p| p == path) {
+            self.paths.push(path.to_string());
+        }
+        self.router = self.router.route(path, method_router);
+        self
+    }
+
+    /// Apply a middleware layer to every route added so far
+    pub fn layer<L>(mut self, layer: L) -> Self
+    where
+        L: Layer<Route> + Clone + Send + 'static,
+        L::Service: Service<Request> + Clone + Send + 'static,
+        <L::Service as Service<Request>>::Response: IntoResponse + 'static,
+        <L::Service as Service<Request>>::Error: Into<Infallible> + 'static,
+        <L::Service as Service<Request>>::Future: Send + 'static,
+    {
+        self.router = self.router.layer(layer);
+        self
+    }
+
+    pub fn plugin_id(&self) -> &str {
+        &self.plugin_id
+    }
+
+    /// Where the routes are mounted (`/api/v1/<plugin-id>`)
+    pub fn prefix(&self) -> String {
+        format!("{}/{}", PLUGIN_API_PREFIX, self.plugin_id)
+    }
+
+    /// Full paths of the routes
+    pub fn full_paths(&self) -> Vec<String> {
+        let prefix = self.prefix();
+        self.paths
+            .iter()
+            .map(|path| match path.as_str() {
+                "/" | "" => prefix.clone(),
+                path => format!("{}{}", prefix, path),
+            })
+            .collect()
+    }
+}
+
+/// Mounted routes and their owners
+#[derive(Debug, Default)]
+pub struct RouteRegistry {
+    /// Keyed by the path with parameter names removed
+    routes: RwLock<BTreeMap<String, RouteEntry>>,
+}
+
+impl RouteRegistry {
+    /// Record an app's or plugin's own full paths, replacing whatever the
+    /// owner registered before; nothing is recorded when one collides
+    pub fn register(&self, owner: &str, paths: impl IntoIterator<Item = String>) -> Result<(), RouteError> {
+        let mut routes = self.routes.write().unwrap();
+
+        let mut added = BTreeMap::new();
+        for path in paths {
+            let key = route_key(&path);
+            if let Some(existing) = routes.get(&key).filter(|e| e.owner != owner) {
+                return Err(RouteError::Collision {
+                    owner: owner.to_string(),
+                    path,
+                    existing: existing.path.clone(),
+                    existing_owner: existing.owner.clone(),
+                });
+            }
+            added.insert(
+                key,
+                RouteEntry {
+                    path,
+                    owner: owner.to_string(),
+                },
+            );
+        }
+
+        routes.retain(|_, entry| entry.owner != owner);
+        routes.extend(added);
+        Ok(())
+    }
+
+    /// Check a plugin's routes, record them and return them nested under
+    /// `/api/v1/<plugin-id>`, behind the shared route filters
+    ///
+    /// Mounting again (re-activation) replaces the plugin's previous routes.
+    pub fn mount<S>(&self, routes: PluginRoutes<S>) -> Result<Router<S>, RouteError>
+    where
+        S: Clone + Send + Sync + 'static,
+    {
+        let owner = routes.plugin_id.as_str();
+        let valid = |c: char| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_';
+        if owner.is_empty() || !owner.chars().all(valid) {
+            return Err(RouteError::InvalidPluginId(owner.to_string()));
+        }
+
+        let prefix = routes.prefix();
+        let below = format!("{}/", prefix);
+        let taken = self
+            .routes
+            .read()
+            .unwrap()
+            .values()
+            .find(|e| e.owner != owner && (e.path == prefix || e.path.starts_with(&below)))
+            .cloned();
+        if let Some(existing) = taken {
+            return Err(RouteError::PrefixTaken {
+                owner: owner.to_string(),
+                prefix,
+                existing: existing.path,
+                existing_owner: existing.owner,
+            });
+        }
+
+        self.register(owner, routes.full_paths())?;
+        tracing::info!(plugin = owner, routes = routes.paths.len(), "Mounted plugin routes under {}", prefix);
+        Ok(Router::new()
+            .nest(&prefix, routes.router)
+            .layer(middleware::from_fn(route_filters::apply)))
+    }
+
+    /// Forget an owner's routes; returns how many were removed
+    pub fn unregister(&self, owner: &str) -> usize {
+        let mut routes = self.routes.write().unwrap();
+        let before = routes.len();
+        routes.retain(|_, entry| entry.owner != owner);
+        before - routes.len()
+    }
+
+    /// Every registered route, sorted by path
+    pub fn list(&self) -> Vec<RouteEntry> {
+        let mut entries: Vec<RouteEntry> = self.routes.read().unwrap().values().cloned().collect();
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+        entries
+    }
+}
+
+/// A path with parameter names dropped, so routes that the router would
+/// treat as the same compare equal
+fn route_key(path: &str) -> String {
+    let trimmed = path.trim_end_matches('/');
+    let segments: Vec<&str> = trimmed
+        .split('/')
+        .map(|segment| match segment.chars().next() {
+            // `{id}` is the OpenAPI spelling of `:id`
+            Some(':' | '{') => ":",
+            Some('*') => "*",
+            _ => segment,
+        })
+        .collect();
+    match segments.join("/") {
+        key if key.is_empty() => "/".to_string(),
+        key => key,
+    }
+}
+
+/// GET /system/routes
+///
+/// Every mounted route and the plugin or app owning it (admin only)
+#[utoipa::path(
+    get,
+    path = "/system/routes",
+    tag = "system",
+    responses(
+        (status = 200, description = "Routes sorted by path", body = Vec<RouteEntry>),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Not an admin", body = ProblemDetails)
+    ),
+    security(("bearer_auth" = []))
+)]
+pub async fn list_routes(State(registry): State<Arc<RouteRegistry>>) -> Json<Vec<RouteEntry>> {
+    Json(registry.list())
+}
+
+/// The route listing (admin only)
+pub fn system_routes(registry: Arc<RouteRegistry>) -> Router {
+    Router::new()
+        .route(SYSTEM_ROUTES_PATH, get(list_routes))
+        .layer(middleware::from_fn(require_admin))
+        .with_state(registry)
+}
+
+// ============================================
+// Tests
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plugin(id: &str, paths: &[&str]) -> PluginRoutes {
+        paths
+            .iter()
+            .fold(PluginRoutes::new(id), |routes, path| routes.route(path, get(|| async { "ok" })))
+    }
+
+    #[test]
+    fn test_mount_prefixes_and_lists_routes() {
+        let registry = RouteRegistry::default();
+        let _ = registry.mount(plugin("rustpress-shop", &["/products", "/products/:id", "/"])).unwrap();
+
+        let paths: Vec<String> = registry.list().into_iter().map(|e| e.path).collect();
+        assert_eq!(
+            paths,
+            ["/api/v1/rustpress-shop", "/api/v1/rustpress-shop/products", "/api/v1/rustpress-shop/products/:id"]
+        );
+        assert!(registry.list().iter().all(|e| e.owner == "rustpress-shop"));
+
+        // Re-activation replaces the previous routes
+        let _ = registry.mount(plugin("rustpress-shop", &["/orders"])).unwrap();
+        assert_eq!(registry.list().len(), 1);
+
+        assert_eq!(registry.unregister("rustpress-shop"), 1);
+        assert!(registry.list().is_empty());
+    }
+
+    #[test]
+    fn test_collisions_are_reported() {
+        let registry = RouteRegistry::default();
+        registry
+            .register("blog-api", ["/api/v1/posts/:slug".to_string()])
+            .unwrap();
+
+        // Same path with another parameter name
+        let err = registry
+            .register("other", ["/api/v1/posts/:id".to_string()])
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Route /api/v1/posts/:id of other collides with /api/v1/posts/:slug registered by blog-api"
+        );
+
+        // A plugin whose prefix is used by someone else
+        let err = registry.mount(plugin("posts", &["/feed"])).unwrap_err();
+        assert!(matches!(err, RouteError::PrefixTaken { ref existing_owner, .. } if existing_owner == "blog-api"));
+
+        // Nothing is recorded for a failed registration
+        registry.register("other", ["/api/v1/other".to_string(), "/api/v1/posts/:id".to_string()]).unwrap_err();
+        assert_eq!(registry.list().len(), 1);
+
+        assert!(matches!(
+            registry.mount(plugin("Bad Id", &["/x"])),
+            Err(RouteError::InvalidPluginId(_))
+        ));
+    }
+
+    #[test]
+    fn test_route_key() {
+        assert_eq!(route_key("/a/:id/b"), route_key("/a/:slug/b"));
+        assert_eq!(route_key("/a/{id}/b"), route_key("/a/:slug/b"));
+        assert_eq!(route_key("/a/"), "/a");
+        assert_eq!(route_key("/"), "/");
+        assert_eq!(route_key("/files/*path"), "/files/*");
+    }
+}
@@ -0,0 +1,568 @@
+//! Expand/Contract Schema Changes
+//!
+//! Restructuring a table that's written to all the time (renaming or
+//! retyping a column, moving data to a new table) can't stop the writers, so
+//! the change is spread over several migrations and releases:
+//!
+//! 1. **Expand**: a migration whose first lines include
+//!    `-- expand: <change>` adds the new structure next to the old one and
+//!    installs a [`DualWrite`] shim, so rows written from then on fill both.
+//!    Applying it records the change in `plugin_schema_changes`.
+//! 2. **Backfill**: a [`Backfill`] job copies the existing rows in small
+//!    batches, recording its progress. It picks up where it stopped after a
+//!    restart.
+//! 3. Code switches its reads, then its writes, to the new structure.
+//! 4. **Contract**: a migration headed `-- contract: <change>` removes the
+//!    shim and the old structure. [`PluginMigrations::up`] holds it back until
+//!    the change's backfill has completed: without a target it applies the
+//!    migrations before it and stops, with a target past it the run fails.
+//!
+//! ```sql
+//! -- 009_path_ids.up.sql
+//! -- expand: pageviews_path_id
+//! ALTER TABLE analytics_pageviews ADD COLUMN path_id BIGINT;
+//! -- DualWrite::new("analytics_pageviews", "pageviews_path_id")
+//! --     .set("path_id", "path_id(NEW.path)").install_sql()
+//! ...
+//!
+//! -- 010_drop_paths.up.sql
+//! -- contract: pageviews_path_id
+//! DROP TRIGGER IF EXISTS dual_write_pageviews_path_id ON analytics_pageviews;
+//! ALTER TABLE analytics_pageviews DROP COLUMN path;
+//! ```
+//!
+//! [`PluginMigrations::up`]: crate::migrations::PluginMigrations::up
+
+use crate::db::DbPool;
+use crate::migrations::MigrationError;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::Executor;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Phases of the changes of all plugins
+#[cfg(feature = "postgres")]
+pub(crate) const CREATE_TABLE: &str = r#"
+    CREATE TABLE IF NOT EXISTS plugin_schema_changes (
+        plugin_id VARCHAR(100) NOT NULL,
+        change VARCHAR(100) NOT NULL,
+        phase VARCHAR(20) NOT NULL,
+        expanded_version BIGINT NOT NULL,
+        rows_total BIGINT NOT NULL DEFAULT 0,
+        rows_done BIGINT NOT NULL DEFAULT 0,
+        updated_at TIMESTAMPTZ NOT NULL,
+        backfilled_at TIMESTAMPTZ,
+        contracted_at TIMESTAMPTZ,
+        PRIMARY KEY (plugin_id, change)
+    );
+"#;
+
+#[cfg(feature = "sqlite")]
+pub(crate) const CREATE_TABLE: &str = r#"
+    CREATE TABLE IF NOT EXISTS plugin_schema_changes (
+        plugin_id TEXT NOT NULL,
+        change TEXT NOT NULL,
+        phase TEXT NOT NULL,
+        expanded_version INTEGER NOT NULL,
+        rows_total INTEGER NOT NULL DEFAULT 0,
+        rows_done INTEGER NOT NULL DEFAULT 0,
+        updated_at TEXT NOT NULL,
+        backfilled_at TEXT,
+        contracted_at TEXT,
+        PRIMARY KEY (plugin_id, change)
+    );
+"#;
+
+/// Where a change stands
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Phase {
+    /// New structure in place, old rows not copied yet
+    Expanded,
+    Backfilling,
+    /// Every row copied; the contract migration may run
+    Backfilled,
+    /// Old structure removed
+    Contracted,
+}
+
+impl Phase {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Phase::Expanded => "expanded",
+            Phase::Backfilling => "backfilling",
+            Phase::Backfilled => "backfilled",
+            Phase::Contracted => "contracted",
+        }
+    }
+
+    fn parse(phase: &str) -> Self {
+        match phase {
+            "backfilling" => Phase::Backfilling,
+            "backfilled" => Phase::Backfilled,
+            "contracted" => Phase::Contracted,
+            _ => Phase::Expanded,
+        }
+    }
+}
+
+impl std::fmt::Display for Phase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Step of an expand/contract change a migration performs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Directive<'a> {
+    Expand(&'a str),
+    Contract(&'a str),
+}
+
+impl<'a> Directive<'a> {
+    /// The `-- expand: <change>` or `-- contract: <change>` line among the
+    /// comments a script starts with
+    pub fn parse(sql: &'a str) -> Option<Self> {
+        sql.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .take_while(|line| line.starts_with("--"))
+            .find_map(|line| {
+                let line = line.trim_start_matches('-').trim();
+                let (step, change) = line.split_once(':')?;
+                let change = change.trim();
+                if change.is_empty() {
+                    return None;
+                }
+                match step.trim() {
+                    "expand" => Some(Directive::Expand(change)),
+                    "contract" => Some(Directive::Contract(change)),
+                    _ => None,
+                }
+            })
+    }
+}
+
+/// A change with its backfill progress
+#[derive(Debug, Clone, Serialize)]
+pub struct SchemaChange {
+    pub plugin_id: String,
+    pub change: String,
+    pub phase: Phase,
+    /// Migration that started the change
+    pub expanded_version: i64,
+    /// Rows needing a backfill when it started
+    pub rows_total: i64,
+    pub rows_done: i64,
+    pub updated_at: DateTime<Utc>,
+    pub backfilled_at: Option<DateTime<Utc>>,
+    pub contracted_at: Option<DateTime<Utc>>,
+}
+
+type ChangeRow = (
+    String,
+    String,
+    String,
+    i64,
+    i64,
+    i64,
+    DateTime<Utc>,
+    Option<DateTime<Utc>>,
+    Option<DateTime<Utc>>,
+);
+
+const SELECT_CHANGES: &str = "SELECT plugin_id, change, phase, expanded_version, rows_total, rows_done, updated_at, \
+                              backfilled_at, contracted_at FROM plugin_schema_changes";
+
+impl SchemaChange {
+    /// Share of the rows backfilled, 0 to 1
+    pub fn progress(&self) -> f64 {
+        match self.phase {
+            Phase::Expanded => 0.0,
+            Phase::Backfilled | Phase::Contracted => 1.0,
+            Phase::Backfilling if self.rows_total <= 0 => 0.0,
+            Phase::Backfilling => (self.rows_done as f64 / self.rows_total as f64).min(1.0),
+        }
+    }
+
+    /// Changes of a plugin, oldest first
+    pub async fn list(db: &DbPool, plugin_id: &str) -> Result<Vec<Self>, MigrationError> {
+        db.execute(CREATE_TABLE).await?;
+        let rows: Vec<ChangeRow> = sqlx::query_as(&format!(
+            "{} WHERE plugin_id = $1 ORDER BY expanded_version, change",
+            SELECT_CHANGES
+        ))
+        .bind(plugin_id)
+        .fetch_all(db)
+        .await?;
+        Ok(rows.into_iter().map(Self::from_row).collect())
+    }
+
+    pub async fn get(db: &DbPool, plugin_id: &str, change: &str) -> Result<Option<Self>, MigrationError> {
+        db.execute(CREATE_TABLE).await?;
+        let row: Option<ChangeRow> = sqlx::query_as(&format!("{} WHERE plugin_id = $1 AND change = $2", SELECT_CHANGES))
+            .bind(plugin_id)
+            .bind(change)
+            .fetch_optional(db)
+            .await?;
+        Ok(row.map(Self::from_row))
+    }
+
+    fn from_row(row: ChangeRow) -> Self {
+        let (plugin_id, change, phase, expanded_version, rows_total, rows_done, updated_at, backfilled_at, contracted_at) = row;
+        Self {
+            plugin_id,
+            change,
+            phase: Phase::parse(&phase),
+            expanded_version,
+            rows_total,
+            rows_done,
+            updated_at,
+            backfilled_at,
+            contracted_at,
+        }
+    }
+}
+
+/// Phases by change, for the migration planner
+pub(crate) async fn phases(
+    conn: &mut crate::db::DbConnection,
+    plugin_id: &str,
+) -> Result<HashMap<String, Phase>, MigrationError> {
+    let rows: Vec<(String, String)> =
+        sqlx::query_as("SELECT change, phase FROM plugin_schema_changes WHERE plugin_id = $1")
+            .bind(plugin_id)
+            .fetch_all(conn)
+            .await?;
+    Ok(rows
+        .into_iter()
+        .map(|(change, phase)| (change, Phase::parse(&phase)))
+        .collect())
+}
+
+/// Trigger keeping the new structure of a change filled while the old one
+/// is still written
+///
+/// Expressions are evaluated per written row and refer to its columns as
+/// `NEW.<column>`, in both dialects. The generated SQL goes in the expand
+/// migration, [`remove_sql`](Self::remove_sql) in the contract migration.
+#[derive(Debug, Clone)]
+pub struct DualWrite {
+    table: String,
+    change: String,
+    columns: Vec<(String, String)>,
+}
+
+impl DualWrite {
+    pub fn new(table: &str, change: &str) -> Self {
+        Self {
+            table: table.to_string(),
+            change: change.to_string(),
+            columns: Vec::new(),
+        }
+    }
+
+    /// Fill `column` with `expression` on every insert and update
+    pub fn set(mut self, column: &str, expression: &str) -> Self {
+        self.columns.push((column.to_string(), expression.to_string()));
+        self
+    }
+
+    /// Trigger (and function) name: `dual_write_<change>`
+    pub fn name(&self) -> String {
+        let change: String = self
+            .change
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' })
+            .collect();
+        format!("dual_write_{}", change)
+    }
+
+    /// SQL installing the shim, replacing an earlier one
+    #[cfg(feature = "postgres")]
+    pub fn install_sql(&self) -> String {
+        let name = self.name();
+        let assignments: String = self
+            .columns
+            .iter()
+            .map(|(column, expression)| format!("    NEW.{} := {};\n", column, expression))
+            .collect();
+        format!(
+            "CREATE OR REPLACE FUNCTION {name}() RETURNS trigger AS $$\nBEGIN\n{assignments}    RETURN NEW;\nEND;\n$$ LANGUAGE plpgsql;\n\
+             DROP TRIGGER IF EXISTS {name} ON {table};\n\
+             CREATE TRIGGER {name} BEFORE INSERT OR UPDATE ON {table} FOR EACH ROW EXECUTE FUNCTION {name}();\n",
+            name = name,
+            assignments = assignments,
+            table = self.table,
+        )
+    }
+
+    /// SQL removing the shim
+    #[cfg(feature = "postgres")]
+    pub fn remove_sql(&self) -> String {
+        let name = self.name();
+        format!(
+            "DROP TRIGGER IF EXISTS {name} ON {table};\nDROP FUNCTION IF EXISTS {name}();\n",
+            name = name,
+            table = self.table,
+        )
+    }
+
+    /// SQL installing the shim, replacing an earlier one
+    ///
+    /// SQLite triggers can't assign `NEW`, so the row is updated after the
+    /// write; with `recursive_triggers` off (the default) that update doesn't
+    /// fire the trigger again.
+    #[cfg(feature = "sqlite")]
+    pub fn install_sql(&self) -> String {
+        let name = self.name();
+        let assignments = self
+            .columns
+            .iter()
+            .map(|(column, expression)| format!("{} = {}", column, expression))
+            .collect::<Vec<_>>()
+            .join(", ");
+        ["insert", "update"]
+            .iter()
+            .map(|event| {
+                format!(
+                    "DROP TRIGGER IF EXISTS {name}_{event};\n\
+                     CREATE TRIGGER {name}_{event} AFTER {upper} ON {table} FOR EACH ROW BEGIN \
+                     UPDATE {table} SET {assignments} WHERE rowid = NEW.rowid; END;\n",
+                    name = name,
+                    event = event,
+                    upper = event.to_ascii_uppercase(),
+                    table = self.table,
+                    assignments = assignments,
+                )
+            })
+            .collect()
+    }
+
+    /// SQL removing the shim
+    #[cfg(feature = "sqlite")]
+    pub fn remove_sql(&self) -> String {
+        let name = self.name();
+        format!("DROP TRIGGER IF EXISTS {name}_insert;\nDROP TRIGGER IF EXISTS {name}_update;\n", name = name)
+    }
+
+    /// Install the shim outside a migration
+    pub async fn install(&self, db: &DbPool) -> Result<(), MigrationError> {
+        db.execute(&*self.install_sql()).await?;
+        Ok(())
+    }
+
+    pub async fn remove(&self, db: &DbPool) -> Result<(), MigrationError> {
+        db.execute(&*self.remove_sql()).await?;
+        Ok(())
+    }
+}
+
+/// Batched copy of existing rows into the new structure of a change
+///
+/// Each batch updates up to `batch_size` rows matching `pending` with
+/// `set`, so `pending` must stop matching a row once it's done (typically
+/// `<new column> IS NULL`). On PostgreSQL rows locked by writers are skipped
+/// and picked up by a later batch rather than waited for.
+#[derive(Debug, Clone)]
+pub struct Backfill {
+    plugin_id: String,
+    change: String,
+    table: String,
+    key: String,
+    set: String,
+    pending: String,
+    batch_size: i64,
+    pause: Duration,
+}
+
+impl Backfill {
+    /// Backfill `table` with `set` (`path_id = path_id(path)`) where
+    /// `pending` (`path_id IS NULL`) holds
+    pub fn new(plugin_id: &str, change: &str, table: &str, set: &str, pending: &str) -> Self {
+        Self {
+            plugin_id: plugin_id.to_string(),
+            change: change.to_string(),
+            table: table.to_string(),
+            key: "id".to_string(),
+            set: set.to_string(),
+            pending: pending.to_string(),
+            batch_size: 1000,
+            pause: Duration::from_millis(100),
+        }
+    }
+
+    /// Unique column batches are selected by (default `id`)
+    pub fn key(mut self, column: &str) -> Self {
+        self.key = column.to_string();
+        self
+    }
+
+    pub fn batch_size(mut self, rows: i64) -> Self {
+        self.batch_size = rows.max(1);
+        self
+    }
+
+    /// Wait between batches, leaving room for the regular writes
+    pub fn pause(mut self, pause: Duration) -> Self {
+        self.pause = pause;
+        self
+    }
+
+    /// Run batches until every row is backfilled
+    pub async fn run(&self, db: &DbPool) -> Result<SchemaChange, MigrationError> {
+        loop {
+            let change = self.step(db).await?;
+            if matches!(change.phase, Phase::Backfilled | Phase::Contracted) {
+                tracing::info!(
+                    plugin = %self.plugin_id,
+                    change = %self.change,
+                    rows = change.rows_done,
+                    "Backfill completed"
+                );
+                return Ok(change);
+            }
+            tokio::time::sleep(self.pause).await;
+        }
+    }
+
+    /// Run one batch
+    pub async fn step(&self, db: &DbPool) -> Result<SchemaChange, MigrationError> {
+        let change = SchemaChange::get(db, &self.plugin_id, &self.change)
+            .await?
+            .ok_or_else(|| MigrationError::UnknownChange(self.change.clone()))?;
+        match change.phase {
+            Phase::Backfilled | Phase::Contracted => return Ok(change),
+            Phase::Expanded => {
+                let (total,): (i64,) =
+                    sqlx::query_as(&format!("SELECT COUNT(*) FROM {} WHERE {}", self.table, self.pending))
+                        .fetch_one(db)
+                        .await?;
+                sqlx::query(
+                    "UPDATE plugin_schema_changes SET phase = 'backfilling', rows_total = $3, rows_done = 0, updated_at = $4 \
+                     WHERE plugin_id = $1 AND change = $2 AND phase = 'expanded'",
+                )
+                .bind(&self.plugin_id)
+                .bind(&self.change)
+                .bind(total)
+                .bind(Utc::now())
+                .execute(db)
+                .await?;
+            }
+            Phase::Backfilling => {}
+        }
+
+        let updated = sqlx::query(&self.batch_sql())
+            .bind(self.batch_size)
+            .execute(db)
+            .await?
+            .rows_affected() as i64;
+
+        // A short batch may be the last one; rows skipped because they were
+        // locked still count as pending
+        let done = if updated < self.batch_size {
+            let (remaining,): (bool,) = sqlx::query_as(&format!(
+                "SELECT EXISTS (SELECT 1 FROM {} WHERE {})",
+                self.table, self.pending
+            ))
+            .fetch_one(db)
+            .await?;
+            !remaining
+        } else {
+            false
+        };
+
+        let now = Utc::now();
+        sqlx::query(
+            "UPDATE plugin_schema_changes SET rows_done = rows_done + $3, phase = $4, updated_at = $5, backfilled_at = $6 \
+             WHERE plugin_id = $1 AND change = $2 AND phase = 'backfilling'",
+        )
+        .bind(&self.plugin_id)
+        .bind(&self.change)
+        .bind(updated)
+        .bind(if done { Phase::Backfilled } else { Phase::Backfilling }.as_str())
+        .bind(now)
+        .bind(done.then_some(now))
+        .execute(db)
+        .await?;
+
+        SchemaChange::get(db, &self.plugin_id, &self.change)
+            .await?
+            .ok_or_else(|| MigrationError::UnknownChange(self.change.clone()))
+    }
+
+    #[cfg(feature = "postgres")]
+    fn batch_sql(&self) -> String {
+        format!(
+            "UPDATE {table} SET {set} WHERE {key} IN \
+             (SELECT {key} FROM {table} WHERE {pending} LIMIT $1 FOR UPDATE SKIP LOCKED)",
+            table = self.table,
+            set = self.set,
+            key = self.key,
+            pending = self.pending,
+        )
+    }
+
+    #[cfg(feature = "sqlite")]
+    fn batch_sql(&self) -> String {
+        format!(
+            "UPDATE {table} SET {set} WHERE {key} IN (SELECT {key} FROM {table} WHERE {pending} LIMIT $1)",
+            table = self.table,
+            set = self.set,
+            key = self.key,
+            pending = self.pending,
+        )
+    }
+}
+
+// ============================================
+// Tests
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_directive_in_leading_comments() {
+        let sql = "-- Analytics - path ids\n--\n-- expand: pageviews_path_id\nALTER TABLE t ADD COLUMN c INT;";
+        assert_eq!(Directive::parse(sql), Some(Directive::Expand("pageviews_path_id")));
+        assert_eq!(
+            Directive::parse("\n-- contract: pageviews_path_id \nDROP TABLE t;"),
+            Some(Directive::Contract("pageviews_path_id"))
+        );
+
+        // Only the header counts
+        assert_eq!(Directive::parse("CREATE TABLE t (id INT);\n-- expand: late"), None);
+        assert_eq!(Directive::parse("-- note: something\n-- expand:\nSELECT 1;"), None);
+    }
+
+    #[test]
+    fn test_dual_write_names() {
+        let shim = DualWrite::new("analytics_pageviews", "Pageviews path-id").set("path_id", "NEW.id");
+        assert_eq!(shim.name(), "dual_write_pageviews_path_id");
+        assert!(shim.install_sql().contains("NEW.id"));
+        assert!(shim.remove_sql().contains("DROP TRIGGER IF EXISTS dual_write_pageviews_path_id"));
+    }
+
+    #[test]
+    fn test_progress() {
+        let mut change = SchemaChange {
+            plugin_id: "p".into(),
+            change: "c".into(),
+            phase: Phase::Backfilling,
+            expanded_version: 2,
+            rows_total: 200,
+            rows_done: 50,
+            updated_at: Utc::now(),
+            backfilled_at: None,
+            contracted_at: None,
+        };
+        assert_eq!(change.progress(), 0.25);
+        change.rows_done = 250;
+        assert_eq!(change.progress(), 1.0);
+        change.phase = Phase::Expanded;
+        assert_eq!(change.progress(), 0.0);
+    }
+}
@@ -0,0 +1,320 @@
+//! Secrets
+//!
+//! The JWT signing secret and database credentials are read through a
+//! [`SecretProvider`] chosen by `secrets.provider`:
+//!
+//! - `env` (default) - the shared [`Config`]: `rustpress.toml`, environment
+//!   variables and `_FILE` variants (`JWT_SECRET_FILE`), re-read on every
+//!   lookup so a rewritten secret file is picked up
+//! - `file` - one file per secret in `secrets.file.dir` (`/run/secrets`),
+//!   named like the variable in lower case (`jwt_secret`, `database_url`)
+//! - `vault` - a HashiCorp Vault KV v2 secret (feature `vault`)
+//! - `aws` - an AWS Secrets Manager JSON secret (feature `aws`)
+//!
+//! Vault and AWS secrets hold one field per secret, named like the files.
+//!
+//! ```toml
+//! [secrets]
+//! provider = "vault"
+//! refresh_secs = 300 # re-read interval for rotation; 0 disables
+//!
+//! [secrets.vault]
+//! addr = "https://vault.internal:8200" # VAULT_ADDR
+//! token_file = "/run/secrets/vault_token" # or VAULT_TOKEN
+//! mount = "secret"
+//! path = "rustpress"
+//!
+//! [secrets.aws]
+//! secret_id = "prod/rustpress"
+//! ```
+//!
+//! Secrets are re-read every `refresh_secs`; see [`crate::keys::JwtKeys`]
+//! and [`crate::db::rotate_credentials`] for what changes without a restart.
+
+use crate::config::{env_name, Config};
+use crate::error::AuthError;
+
+use async_trait::async_trait;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::OnceCell;
+
+/// Re-read interval when `secrets.refresh_secs` is unset
+pub const DEFAULT_REFRESH_SECS: u64 = 300;
+
+static SHARED: OnceCell<Arc<dyn SecretProvider>> = OnceCell::const_new();
+
+/// Source of secret values
+#[async_trait]
+pub trait SecretProvider: Send + Sync {
+    /// Provider name for logs (`env`, `file`, `vault`, `aws`)
+    fn name(&self) -> &'static str;
+
+    /// A secret by configuration key (`jwt.secret`), `None` when unset
+    async fn get(&self, key: &str) -> Result<Option<String>, AuthError>;
+
+    /// A secret every deployment must set
+    async fn require(&self, key: &str) -> Result<String, AuthError> {
+        self.get(key).await?.ok_or_else(|| {
+            AuthError::Config(format!("Secret {} is not set (provider: {})", key, self.name()))
+        })
+    }
+}
+
+/// The process-wide provider configured by `[secrets]`
+pub async fn shared() -> Result<Arc<dyn SecretProvider>, AuthError> {
+    SHARED
+        .get_or_try_init(|| async {
+            let config = Config::load()?;
+            from_config(config).await
+        })
+        .await
+        .cloned()
+}
+
+/// The provider configured by `[secrets]`
+pub async fn from_config(config: Arc<Config>) -> Result<Arc<dyn SecretProvider>, AuthError> {
+    let provider: String = config.get_or("secrets.provider", "env".to_string())?;
+    match provider.as_str() {
+        "env" => Ok(Arc::new(ConfigSecrets::new(config))),
+        "file" => Ok(Arc::new(FileSecrets::new(
+            config.get_or("secrets.file.dir", PathBuf::from("/run/secrets"))?,
+        ))),
+        #[cfg(feature = "vault")]
+        "vault" => Ok(Arc::new(VaultSecrets::from_config(&config)?)),
+        #[cfg(feature = "aws")]
+        "aws" => Ok(Arc::new(AwsSecrets::from_config(&config).await?)),
+        other => Err(AuthError::Config(format!(
+            "Unknown or disabled secrets.provider \"{}\" (the vault and aws providers need their crate features)",
+            other
+        ))),
+    }
+}
+
+/// How often secrets are re-read for rotation; `None` when disabled
+pub fn refresh_interval(config: &Config) -> Result<Option<Duration>, AuthError> {
+    let secs: u64 = config.get_or("secrets.refresh_secs", DEFAULT_REFRESH_SECS)?;
+    Ok((secs > 0).then(|| Duration::from_secs(secs)))
+}
+
+/// Field or file name of a secret: `jwt.secret` → `jwt_secret`
+pub fn field_name(key: &str) -> String {
+    env_name(key).to_lowercase()
+}
+
+/// Secrets from the shared configuration
+pub struct ConfigSecrets {
+    config: Arc<Config>,
+}
+
+impl ConfigSecrets {
+    pub fn new(config: Arc<Config>) -> Self {
+        Self { config }
+    }
+}
+
+#[async_trait]
+impl SecretProvider for ConfigSecrets {
+    fn name(&self) -> &'static str {
+        "env"
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<String>, AuthError> {
+        Ok(self.config.get(key)?)
+    }
+}
+
+/// One file per secret, as mounted by Docker and Kubernetes
+pub struct FileSecrets {
+    dir: PathBuf,
+}
+
+impl FileSecrets {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+}
+
+#[async_trait]
+impl SecretProvider for FileSecrets {
+    fn name(&self) -> &'static str {
+        "file"
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<String>, AuthError> {
+        let path = self.dir.join(field_name(key));
+        match tokio::fs::read_to_string(&path).await {
+            Ok(value) => Ok(Some(value.trim_end_matches(['\r', '\n']).to_string())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(AuthError::Config(format!("Cannot read {}: {}", path.display(), e))),
+        }
+    }
+}
+
+/// A HashiCorp Vault KV version 2 secret
+#[cfg(feature = "vault")]
+pub struct VaultSecrets {
+    client: reqwest::Client,
+    url: String,
+    token: String,
+}
+
+#[cfg(feature = "vault")]
+impl VaultSecrets {
+    pub fn new(addr: &str, token: String, mount: &str, path: &str) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url: format!("{}/v1/{}/data/{}", addr.trim_end_matches('/'), mount, path.trim_start_matches('/')),
+            token,
+        }
+    }
+
+    fn from_config(config: &Config) -> Result<Self, AuthError> {
+        Ok(Self::new(
+            &config.require::<String>("secrets.vault.addr")?,
+            config.require("secrets.vault.token")?,
+            &config.get_or("secrets.vault.mount", "secret".to_string())?,
+            &config.get_or("secrets.vault.path", "rustpress".to_string())?,
+        ))
+    }
+}
+
+#[cfg(feature = "vault")]
+#[async_trait]
+impl SecretProvider for VaultSecrets {
+    fn name(&self) -> &'static str {
+        "vault"
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<String>, AuthError> {
+        let unavailable = |e: reqwest::Error| AuthError::Config(format!("Vault request failed: {}", e));
+
+        let response = self
+            .client
+            .get(&self.url)
+            .header("X-Vault-Token", &self.token)
+            .send()
+            .await
+            .map_err(unavailable)?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let body: serde_json::Value = response.error_for_status().map_err(unavailable)?.json().await.map_err(unavailable)?;
+
+        Ok(body["data"]["data"][field_name(key)].as_str().map(String::from))
+    }
+}
+
+/// An AWS Secrets Manager secret holding a JSON object
+#[cfg(feature = "aws")]
+pub struct AwsSecrets {
+    client: aws_sdk_secretsmanager::Client,
+    secret_id: String,
+}
+
+#[cfg(feature = "aws")]
+impl AwsSecrets {
+    /// Credentials and region come from the standard AWS environment
+    pub async fn new(secret_id: String, region: Option<String>) -> Self {
+        let mut loader = aws_config::defaults(aws_config::BehaviorVersion::latest());
+        if let Some(region) = region {
+            loader = loader.region(aws_config::Region::new(region));
+        }
+        Self {
+            client: aws_sdk_secretsmanager::Client::new(&loader.load().await),
+            secret_id,
+        }
+    }
+
+    async fn from_config(config: &Config) -> Result<Self, AuthError> {
+        Ok(Self::new(config.require("secrets.aws.secret_id")?, config.get("secrets.aws.region")?).await)
+    }
+}
+
+#[cfg(feature = "aws")]
+#[async_trait]
+impl SecretProvider for AwsSecrets {
+    fn name(&self) -> &'static str {
+        "aws"
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<String>, AuthError> {
+        let output = self
+            .client
+            .get_secret_value()
+            .secret_id(&self.secret_id)
+            .send()
+            .await
+            .map_err(|e| AuthError::Config(format!("Secrets Manager request failed: {}", e)))?;
+
+        let Some(secret) = output.secret_string() else {
+            return Ok(None);
+        };
+        let fields: serde_json::Value = serde_json::from_str(secret)
+            .map_err(|e| AuthError::Config(format!("Secret {} is not a JSON object: {}", self.secret_id, e)))?;
+        Ok(fields[field_name(key)].as_str().map(String::from))
+    }
+}
+
+/// Fixed secrets, for tests and embedding
+#[derive(Default)]
+pub struct StaticSecrets {
+    values: std::sync::RwLock<std::collections::HashMap<String, String>>,
+}
+
+impl StaticSecrets {
+    pub fn new(values: impl IntoIterator<Item = (&'static str, String)>) -> Self {
+        Self {
+            values: std::sync::RwLock::new(values.into_iter().map(|(k, v)| (k.to_string(), v)).collect()),
+        }
+    }
+
+    /// Replace a value, as an external rotation would
+    pub fn set(&self, key: &str, value: String) {
+        self.values.write().unwrap().insert(key.to_string(), value);
+    }
+}
+
+#[async_trait]
+impl SecretProvider for StaticSecrets {
+    fn name(&self) -> &'static str {
+        "static"
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<String>, AuthError> {
+        Ok(self.values.read().unwrap().get(key).cloned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_file_secrets() {
+        let dir = std::env::temp_dir().join(format!("rustpress-secrets-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("jwt_secret"), "from-file\n").unwrap();
+
+        let secrets = FileSecrets::new(&dir);
+        assert_eq!(secrets.get("jwt.secret").await.unwrap().as_deref(), Some("from-file"));
+        assert_eq!(secrets.get("database.url").await.unwrap(), None);
+        assert!(secrets.require("database.url").await.is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_provider_selection() {
+        let config = Arc::new(Config::from_toml("[secrets]\nprovider = \"file\"").unwrap());
+        assert_eq!(from_config(config).await.unwrap().name(), "file");
+
+        let config = Arc::new(Config::from_toml("[jwt]\nsecret = \"s\"").unwrap());
+        let provider = from_config(config).await.unwrap();
+        assert_eq!(provider.require("jwt.secret").await.unwrap(), "s");
+
+        let config = Arc::new(Config::from_toml("[secrets]\nprovider = \"nope\"").unwrap());
+        assert!(from_config(config).await.is_err());
+    }
+}
@@ -0,0 +1,779 @@
+//! Security Headers
+//!
+//! `security_headers` adds a Content Security Policy and the usual hardening
+//! headers (HSTS, `X-Content-Type-Options`, `Referrer-Policy`,
+//! `Permissions-Policy`) to every response. Each request gets a fresh nonce
+//! that is allowed in `script-src`; code emitting `<script>` tags reads it with
+//! `current_csp_nonce` (or the `CspNonce` extractor) and tags its scripts
+//! instead of the policy needing `'unsafe-inline'`.
+//!
+//! Browsers post violations to the policy's report URI; `report_routes`
+//! stores them in `csp_reports` for admins to review. The endpoint is public,
+//! so each client address may post `ReportLimits::per_ip_per_minute` times a
+//! minute and only the newest `ReportLimits::max_stored` reports are kept.
+
+use crate::db::DbPool;
+use crate::extractors::ClientInfo;
+use crate::middleware::require_admin;
+use crate::problem::ProblemDetails;
+
+use axum::{
+    async_trait,
+    body::Bytes,
+    extract::{DefaultBodyLimit, FromRequestParts, Query, Request, State},
+    http::{header, request::Parts, HeaderMap, HeaderName, HeaderValue, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use chrono::{DateTime, Utc};
+use rand::distributions::{Alphanumeric, DistString};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+/// Path of the report endpoint below the API version prefix
+pub const CSP_REPORTS_PATH: &str = "/security/csp-reports";
+
+/// Longest stored value of any report field
+const MAX_FIELD_LEN: usize = 2048;
+
+/// Most reports accepted in one request
+const MAX_REPORTS_PER_REQUEST: usize = 20;
+
+/// Largest report request body
+const MAX_REPORT_BODY: usize = 64 * 1024;
+
+/// Length of a report rate limit window
+const REPORT_WINDOW: Duration = Duration::from_secs(60);
+
+tokio::task_local! {
+    static CSP_NONCE: String;
+}
+
+// ============================================
+// Configuration
+// ============================================
+
+/// Security header settings
+///
+/// Deserializable so apps can expose it as a config section; every field has
+/// a default.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SecurityHeaders {
+    /// CSP directives and their sources; the request nonce is added to
+    /// `script-src`. An empty map disables the CSP header.
+    pub csp: BTreeMap<String, Vec<String>>,
+    /// Send `Content-Security-Policy-Report-Only` instead of enforcing
+    pub csp_report_only: bool,
+    /// Where browsers post violation reports
+    pub csp_report_uri: Option<String>,
+    /// `Strict-Transport-Security` max-age in seconds; 0 disables HSTS
+    pub hsts_max_age: u64,
+    pub hsts_include_subdomains: bool,
+    pub hsts_preload: bool,
+    /// Send `X-Content-Type-Options: nosniff`
+    pub nosniff: bool,
+    pub referrer_policy: Option<String>,
+    pub permissions_policy: Option<String>,
+}
+
+impl Default for SecurityHeaders {
+    fn default() -> Self {
+        let csp = [
+            ("default-src", vec!["'self'"]),
+            ("script-src", vec!["'self'"]),
+            // Themes rely on inline `style` attributes
+            ("style-src", vec!["'self'", "'unsafe-inline'"]),
+            ("img-src", vec!["'self'", "data:", "https:"]),
+            ("object-src", vec!["'none'"]),
+            ("base-uri", vec!["'self'"]),
+            ("frame-ancestors", vec!["'self'"]),
+            ("form-action", vec!["'self'"]),
+        ]
+        .into_iter()
+        .map(|(name, sources)| (name.to_string(), sources.into_iter().map(String::from).collect()))
+        .collect();
+
+        Self {
+            csp,
+            csp_report_only: false,
+            csp_report_uri: Some(format!("/api/v1{}", CSP_REPORTS_PATH)),
+            hsts_max_age: 31_536_000,
+            hsts_include_subdomains: true,
+            hsts_preload: false,
+            nosniff: true,
+            referrer_policy: Some("strict-origin-when-cross-origin".to_string()),
+            permissions_policy: Some("camera=(), microphone=(), geolocation=(), payment=()".to_string()),
+        }
+    }
+}
+
+impl SecurityHeaders {
+    /// CSP header value for a request with `nonce`
+    pub fn csp_value(&self, nonce: &str) -> Option<String> {
+        if self.csp.is_empty() {
+            return None;
+        }
+
+        let mut directives: Vec<String> = self
+            .csp
+            .iter()
+            .map(|(name, sources)| {
+                let mut parts = vec![name.clone()];
+                parts.extend(sources.iter().cloned());
+                if name == "script-src" {
+                    parts.push(format!("'nonce-{}'", nonce));
+                }
+                parts.join(" ")
+            })
+            .collect();
+
+        if !self.csp.contains_key("script-src") {
+            // Without it scripts fall back to `default-src`, which the nonce
+            // would widen for every other resource type too
+            let mut parts = vec!["script-src".to_string()];
+            parts.extend(self.csp.get("default-src").cloned().unwrap_or_default());
+            parts.push(format!("'nonce-{}'", nonce));
+            directives.push(parts.join(" "));
+        }
+
+        if let Some(uri) = &self.csp_report_uri {
+            directives.push(format!("report-uri {}", uri));
+        }
+
+        Some(directives.join("; "))
+    }
+
+    /// Add the configured headers to `headers`, keeping any a handler set
+    /// itself (such as a page-specific CSP)
+    pub fn apply(&self, nonce: &str, headers: &mut HeaderMap) {
+        let csp_header = if self.csp_report_only {
+            header::CONTENT_SECURITY_POLICY_REPORT_ONLY
+        } else {
+            header::CONTENT_SECURITY_POLICY
+        };
+        let hsts = (self.hsts_max_age > 0).then(|| {
+            let mut value = format!("max-age={}", self.hsts_max_age);
+            if self.hsts_include_subdomains {
+                value.push_str("; includeSubDomains");
+            }
+            if self.hsts_preload {
+                value.push_str("; preload");
+            }
+            value
+        });
+
+        let values = [
+            (csp_header, self.csp_value(nonce)),
+            (header::STRICT_TRANSPORT_SECURITY, hsts),
+            (header::X_CONTENT_TYPE_OPTIONS, self.nosniff.then(|| "nosniff".to_string())),
+            (header::REFERRER_POLICY, self.referrer_policy.clone()),
+            (HeaderName::from_static("permissions-policy"), self.permissions_policy.clone()),
+        ];
+        for (name, value) in values {
+            let Some(value) = value.and_then(|v| HeaderValue::from_str(&v).ok()) else {
+                continue;
+            };
+            headers.entry(name).or_insert(value);
+        }
+    }
+}
+
+// ============================================
+// Middleware
+// ============================================
+
+/// Per-request CSP nonce, set by `security_headers`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CspNonce(pub String);
+
+impl CspNonce {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for CspNonce {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for CspNonce
+where
+    S: Send + Sync,
+{
+    type Rejection = ProblemDetails;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<CspNonce>()
+            .cloned()
+            .ok_or_else(|| ProblemDetails::internal("security_headers middleware is not installed"))
+    }
+}
+
+/// CSP nonce of the request being handled, if `security_headers` is installed
+pub fn current_csp_nonce() -> Option<String> {
+    CSP_NONCE.try_with(|nonce| nonce.clone()).ok()
+}
+
+/// ` nonce="..."` attribute for a `<script>` tag, or nothing outside a request
+pub fn nonce_attr() -> String {
+    current_csp_nonce()
+        .map(|nonce| format!(r#" nonce="{}""#, nonce))
+        .unwrap_or_default()
+}
+
+/// 128-bit random nonce
+fn generate_nonce() -> String {
+    Alphanumeric.sample_string(&mut rand::thread_rng(), 22)
+}
+
+/// Middleware adding security headers
+///
+/// Install with `middleware::from_fn_with_state(Arc::new(config), security_headers)`.
+pub async fn security_headers(State(config): State<Arc<SecurityHeaders>>, mut req: Request, next: Next) -> Response {
+    let nonce = generate_nonce();
+    req.extensions_mut().insert(CspNonce(nonce.clone()));
+
+    let mut response = CSP_NONCE.scope(nonce.clone(), next.run(req)).await;
+    config.apply(&nonce, response.headers_mut());
+    response
+}
+
+// ============================================
+// Violation Reports
+// ============================================
+
+/// Stored CSP violation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, sqlx::FromRow, ToSchema)]
+pub struct CspReport {
+    pub id: Uuid,
+    pub document_uri: String,
+    pub effective_directive: String,
+    pub blocked_uri: Option<String>,
+    pub source_file: Option<String>,
+    pub line_number: Option<i64>,
+    pub column_number: Option<i64>,
+    /// First characters of the offending script or style, when the policy
+    /// asks for samples (`'report-sample'`)
+    pub sample: Option<String>,
+    /// `enforce` or `report`
+    pub disposition: String,
+    pub user_agent: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Parse a report body
+///
+/// Accepts both the legacy `application/csp-report` shape
+/// (`{"csp-report": {...}}`) and Reporting API batches
+/// (`[{"type": "csp-violation", "body": {...}}]`). Entries that aren't CSP
+/// violations or lack a document URI are skipped.
+pub fn parse_reports(body: &[u8], user_agent: Option<&str>) -> Vec<CspReport> {
+    let Ok(value) = serde_json::from_slice::<Value>(body) else {
+        return Vec::new();
+    };
+
+    let entries: Vec<(&Value, Option<&str>)> = match &value {
+        Value::Object(object) => object
+            .get("csp-report")
+            .map(|report| vec![(report, user_agent)])
+            .unwrap_or_default(),
+        Value::Array(items) => items
+            .iter()
+            .filter(|item| item.get("type").and_then(Value::as_str) == Some("csp-violation"))
+            .filter_map(|item| {
+                let agent = item.get("user_agent").and_then(Value::as_str).or(user_agent);
+                item.get("body").map(|body| (body, agent))
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    let now = Utc::now();
+    entries
+        .into_iter()
+        .take(MAX_REPORTS_PER_REQUEST)
+        .filter_map(|(report, agent)| {
+            let text = |keys: &[&str]| {
+                keys.iter()
+                    .find_map(|key| report.get(*key).and_then(Value::as_str))
+                    .filter(|v| !v.is_empty())
+                    .map(truncate)
+            };
+            let number = |keys: &[&str]| keys.iter().find_map(|key| report.get(*key).and_then(Value::as_i64));
+
+            Some(CspReport {
+                id: Uuid::new_v4(),
+                document_uri: text(&["document-uri", "documentURL"])?,
+                effective_directive: text(&["effective-directive", "effectiveDirective", "violated-directive"])
+                    .unwrap_or_else(|| "unknown".to_string()),
+                blocked_uri: text(&["blocked-uri", "blockedURL"]),
+                source_file: text(&["source-file", "sourceFile"]),
+                line_number: number(&["line-number", "lineNumber"]),
+                column_number: number(&["column-number", "columnNumber"]),
+                sample: text(&["script-sample", "sample"]),
+                disposition: text(&["disposition"]).unwrap_or_else(|| "enforce".to_string()),
+                user_agent: agent.map(truncate),
+                created_at: now,
+            })
+        })
+        .collect()
+}
+
+fn truncate(value: &str) -> String {
+    match value.char_indices().nth(MAX_FIELD_LEN) {
+        Some((end, _)) => value[..end].to_string(),
+        None => value.to_string(),
+    }
+}
+
+/// Store a violation report
+pub async fn record_report(db: &DbPool, report: &CspReport) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO csp_reports (
+            id, document_uri, effective_directive, blocked_uri, source_file,
+            line_number, column_number, sample, disposition, user_agent, created_at
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+        "#,
+    )
+    .bind(report.id)
+    .bind(&report.document_uri)
+    .bind(&report.effective_directive)
+    .bind(&report.blocked_uri)
+    .bind(&report.source_file)
+    .bind(report.line_number)
+    .bind(report.column_number)
+    .bind(&report.sample)
+    .bind(&report.disposition)
+    .bind(&report.user_agent)
+    .bind(report.created_at)
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+/// Query parameters for listing reports
+#[derive(Debug, Default, Deserialize, IntoParams)]
+pub struct CspReportQuery {
+    /// Only reports for this directive (e.g. `script-src-elem`)
+    pub directive: Option<String>,
+    /// Most reports returned, newest first (default 100, max 500)
+    pub limit: Option<i64>,
+}
+
+/// Stored reports, newest first
+pub async fn list_reports(db: &DbPool, query: &CspReportQuery) -> Result<Vec<CspReport>, sqlx::Error> {
+    let limit = query.limit.unwrap_or(100).clamp(1, 500);
+
+    sqlx::query_as::<_, CspReport>(
+        r#"
+        SELECT id, document_uri, effective_directive, blocked_uri, source_file,
+               line_number, column_number, sample, disposition, user_agent, created_at
+        FROM csp_reports
+        WHERE $1 IS NULL OR effective_directive = $1
+        ORDER BY created_at DESC
+        LIMIT $2
+        "#,
+    )
+    .bind(&query.directive)
+    .bind(limit)
+    .fetch_all(db)
+    .await
+}
+
+/// Remove all but the newest `keep` reports; the number removed
+pub async fn prune_reports(db: &DbPool, keep: i64) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query(
+        r#"
+        DELETE FROM csp_reports
+        WHERE created_at < (SELECT created_at FROM csp_reports ORDER BY created_at DESC LIMIT 1 OFFSET $1)
+        "#,
+    )
+    .bind(keep.max(1) - 1)
+    .execute(db)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+/// Limits on the public report endpoint
+#[derive(Debug, Clone, Copy)]
+pub struct ReportLimits {
+    /// Report requests per client address and minute, 0 for no limit
+    pub per_ip_per_minute: u32,
+    /// Reports kept; older ones are removed as new ones arrive
+    pub max_stored: i64,
+}
+
+impl Default for ReportLimits {
+    fn default() -> Self {
+        Self {
+            per_ip_per_minute: 30,
+            max_stored: 10_000,
+        }
+    }
+}
+
+/// Fixed-window report counter per client address
+#[derive(Debug)]
+pub struct ReportRateLimit {
+    /// Requests per window, 0 for no limit
+    limit: u32,
+    windows: Mutex<HashMap<String, (Instant, u32)>>,
+}
+
+impl ReportRateLimit {
+    pub fn new(limit: u32) -> Self {
+        Self {
+            limit,
+            windows: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Count a request from `ip`; how long until the next one is allowed
+    /// when over the limit
+    pub fn check(&self, ip: &str, now: Instant) -> Result<(), Duration> {
+        if self.limit == 0 {
+            return Ok(());
+        }
+
+        let mut windows = self.windows.lock().unwrap();
+        windows.retain(|_, (start, _)| now.duration_since(*start) < REPORT_WINDOW);
+        let (start, count) = windows.entry(ip.to_string()).or_insert((now, 0));
+        if *count >= self.limit {
+            return Err(REPORT_WINDOW.saturating_sub(now.duration_since(*start)));
+        }
+        *count += 1;
+        Ok(())
+    }
+}
+
+/// State of the report endpoint
+pub struct ReportIngest {
+    db: DbPool,
+    max_stored: i64,
+    limiter: ReportRateLimit,
+}
+
+impl ReportIngest {
+    pub fn new(db: DbPool, limits: ReportLimits) -> Self {
+        Self {
+            db,
+            max_stored: limits.max_stored,
+            limiter: ReportRateLimit::new(limits.per_ip_per_minute),
+        }
+    }
+}
+
+/// POST /security/csp-reports
+///
+/// Receive violation reports from browsers
+#[utoipa::path(
+    post,
+    path = "/security/csp-reports",
+    tag = "security",
+    request_body(content = Object, content_type = "application/csp-report"),
+    responses(
+        (status = 204, description = "Reports stored"),
+        (status = 429, description = "Too many reports from this address", body = ProblemDetails)
+    )
+)]
+pub async fn receive_csp_reports(
+    State(ingest): State<Arc<ReportIngest>>,
+    client: ClientInfo,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    // Requests without a known address share one budget
+    if let Err(retry_after) = ingest.limiter.check(client.ip.as_deref().unwrap_or_default(), Instant::now()) {
+        let secs = retry_after.as_secs().max(1);
+        return (
+            [(header::RETRY_AFTER, secs.to_string())],
+            ProblemDetails::new(StatusCode::TOO_MANY_REQUESTS, "rate_limited")
+                .detail(format!("Too many reports. Please try again in {} seconds.", secs)),
+        )
+            .into_response();
+    }
+
+    let user_agent = headers.get(header::USER_AGENT).and_then(|v| v.to_str().ok());
+    let reports = parse_reports(&body, user_agent);
+    for report in &reports {
+        if let Err(e) = record_report(&ingest.db, report).await {
+            tracing::warn!("Failed to store CSP report: {}", e);
+        }
+    }
+    if !reports.is_empty() {
+        if let Err(e) = prune_reports(&ingest.db, ingest.max_stored).await {
+            tracing::warn!("Failed to prune CSP reports: {}", e);
+        }
+    }
+
+    // Browsers ignore the response; never give them a reason to retry
+    StatusCode::NO_CONTENT.into_response()
+}
+
+/// GET /security/csp-reports
+///
+/// Stored violation reports for review (admin only)
+#[utoipa::path(
+    get,
+    path = "/security/csp-reports",
+    tag = "security",
+    params(CspReportQuery),
+    responses(
+        (status = 200, description = "Reports, newest first", body = Vec<CspReport>),
+        (status = 401, description = "Not authenticated", body = ProblemDetails),
+        (status = 403, description = "Not an admin", body = ProblemDetails)
+    ),
+    security(("bearer_auth" = []))
+)]
+pub async fn get_csp_reports(
+    State(db): State<DbPool>,
+    Query(query): Query<CspReportQuery>,
+) -> Result<Json<Vec<CspReport>>, ProblemDetails> {
+    list_reports(&db, &query).await.map(Json).map_err(|e| {
+        tracing::error!("Failed to list CSP reports: {}", e);
+        ProblemDetails::internal("Failed to list CSP reports")
+    })
+}
+
+/// Report ingestion (public, within `limits`) and review (admin) routes
+pub fn report_routes(db: DbPool, limits: ReportLimits) -> Router {
+    let ingest = Router::new()
+        .route(CSP_REPORTS_PATH, post(receive_csp_reports))
+        .layer(DefaultBodyLimit::max(MAX_REPORT_BODY))
+        .with_state(Arc::new(ReportIngest::new(db.clone(), limits)));
+
+    let review = Router::new()
+        .route(CSP_REPORTS_PATH, get(get_csp_reports))
+        .layer(middleware::from_fn(require_admin))
+        .with_state(db);
+
+    Router::new().merge(ingest).merge(review)
+}
+
+// ============================================
+// Tests
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use tower::ServiceExt;
+
+    fn router(config: SecurityHeaders) -> Router {
+        Router::new()
+            .route("/", get(|nonce: CspNonce| async move { nonce.0 }))
+            .route(
+                "/own-policy",
+                get(|| async { ([(header::CONTENT_SECURITY_POLICY, "default-src 'none'")], "ok") }),
+            )
+            .layer(middleware::from_fn_with_state(Arc::new(config), security_headers))
+    }
+
+    async fn send(router: &Router, uri: &str) -> Response {
+        router
+            .clone()
+            .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_headers_and_fresh_nonce_per_request() {
+        let router = router(SecurityHeaders::default());
+
+        let first = send(&router, "/").await;
+        let headers = first.headers().clone();
+        let nonce = axum::body::to_bytes(first.into_body(), usize::MAX).await.unwrap();
+        let nonce = std::str::from_utf8(&nonce).unwrap();
+
+        let csp = headers[header::CONTENT_SECURITY_POLICY].to_str().unwrap();
+        assert!(csp.contains(&format!("script-src 'self' 'nonce-{}'", nonce)));
+        assert!(csp.contains("object-src 'none'"));
+        assert!(csp.ends_with("report-uri /api/v1/security/csp-reports"));
+        assert_eq!(headers[header::STRICT_TRANSPORT_SECURITY], "max-age=31536000; includeSubDomains");
+        assert_eq!(headers[header::X_CONTENT_TYPE_OPTIONS], "nosniff");
+        assert_eq!(headers[header::REFERRER_POLICY], "strict-origin-when-cross-origin");
+        assert!(headers.contains_key("permissions-policy"));
+
+        let second = send(&router, "/").await;
+        let second_csp = second.headers()[header::CONTENT_SECURITY_POLICY].to_str().unwrap();
+        assert!(!second_csp.contains(nonce));
+    }
+
+    #[tokio::test]
+    async fn test_handler_policy_and_report_only() {
+        let response = send(&router(SecurityHeaders::default()), "/own-policy").await;
+        assert_eq!(response.headers()[header::CONTENT_SECURITY_POLICY], "default-src 'none'");
+
+        let config = SecurityHeaders {
+            csp_report_only: true,
+            hsts_max_age: 0,
+            ..Default::default()
+        };
+        let response = send(&router(config), "/").await;
+        assert!(response.headers().contains_key(header::CONTENT_SECURITY_POLICY_REPORT_ONLY));
+        assert!(!response.headers().contains_key(header::CONTENT_SECURITY_POLICY));
+        assert!(!response.headers().contains_key(header::STRICT_TRANSPORT_SECURITY));
+    }
+
+    #[test]
+    fn test_nonce_added_without_script_src() {
+        let config = SecurityHeaders {
+            csp: BTreeMap::from([("default-src".to_string(), vec!["'self'".to_string()])]),
+            csp_report_uri: None,
+            ..Default::default()
+        };
+        assert_eq!(
+            config.csp_value("abc").unwrap(),
+            "default-src 'self'; script-src 'self' 'nonce-abc'"
+        );
+    }
+
+    #[test]
+    fn test_parse_legacy_report() {
+        let body = br#"{"csp-report": {
+            "document-uri": "https://example.com/post",
+            "violated-directive": "script-src-elem",
+            "effective-directive": "script-src-elem",
+            "blocked-uri": "inline",
+            "line-number": 12,
+            "disposition": "report"
+        }}"#;
+        let reports = parse_reports(body, Some("Firefox"));
+
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].document_uri, "https://example.com/post");
+        assert_eq!(reports[0].effective_directive, "script-src-elem");
+        assert_eq!(reports[0].blocked_uri.as_deref(), Some("inline"));
+        assert_eq!(reports[0].line_number, Some(12));
+        assert_eq!(reports[0].disposition, "report");
+        assert_eq!(reports[0].user_agent.as_deref(), Some("Firefox"));
+    }
+
+    #[test]
+    fn test_parse_reporting_api_batch() {
+        let body = br#"[
+            {"type": "csp-violation", "user_agent": "Chrome", "body": {
+                "documentURL": "https://example.com/",
+                "effectiveDirective": "img-src",
+                "blockedURL": "https://tracker.example/pixel.gif",
+                "disposition": "enforce"
+            }},
+            {"type": "deprecation", "body": {"id": "x"}},
+            {"type": "csp-violation", "body": {"effectiveDirective": "img-src"}}
+        ]"#;
+        let reports = parse_reports(body, None);
+
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].effective_directive, "img-src");
+        assert_eq!(reports[0].user_agent.as_deref(), Some("Chrome"));
+        assert!(parse_reports(b"not json", None).is_empty());
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn test_reports_round_trip() {
+        let db = sqlx::sqlite::SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        crate::AuthPlugin::migrations().up(&db, None, false).await.unwrap();
+
+        let body = br#"{"csp-report": {"document-uri": "https://example.com/", "effective-directive": "script-src-elem"}}"#;
+        let response = report_routes(db.clone(), ReportLimits::default())
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri(CSP_REPORTS_PATH)
+                    .header(header::CONTENT_TYPE, "application/csp-report")
+                    .body(Body::from(&body[..]))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+        let reports = list_reports(&db, &CspReportQuery::default()).await.unwrap();
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].effective_directive, "script-src-elem");
+
+        let query = CspReportQuery {
+            directive: Some("img-src".to_string()),
+            limit: None,
+        };
+        assert!(list_reports(&db, &query).await.unwrap().is_empty());
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn test_reports_limited_per_ip_and_capped() {
+        let db = sqlx::sqlite::SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        crate::AuthPlugin::migrations().up(&db, None, false).await.unwrap();
+        let routes = report_routes(db.clone(), ReportLimits {
+            per_ip_per_minute: 3,
+            max_stored: 2,
+        });
+        let report = |ip: &str, page: &str| {
+            let body = format!(r#"{{"csp-report": {{"document-uri": "https://example.com/{}"}}}}"#, page);
+            let request = Request::builder()
+                .method("POST")
+                .uri(CSP_REPORTS_PATH)
+                .header("X-Forwarded-For", ip)
+                .body(Body::from(body))
+                .unwrap();
+            routes.clone().oneshot(request)
+        };
+
+        for page in ["one", "two", "three"] {
+            assert_eq!(report("203.0.113.7", page).await.unwrap().status(), StatusCode::NO_CONTENT);
+        }
+        let response = report("203.0.113.7", "four").await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(response.headers().contains_key(header::RETRY_AFTER));
+        assert_eq!(report("198.51.100.1", "five").await.unwrap().status(), StatusCode::NO_CONTENT);
+
+        // Only the newest reports are kept
+        let reports = list_reports(&db, &CspReportQuery::default()).await.unwrap();
+        let pages: Vec<_> = reports.iter().map(|r| r.document_uri.as_str()).collect();
+        assert_eq!(pages, ["https://example.com/five", "https://example.com/three"]);
+    }
+
+    #[test]
+    fn test_report_rate_limit_per_ip() {
+        let limiter = ReportRateLimit::new(2);
+        let start = Instant::now();
+
+        assert!(limiter.check("203.0.113.7", start).is_ok());
+        assert!(limiter.check("203.0.113.7", start).is_ok());
+        assert_eq!(
+            limiter.check("203.0.113.7", start + Duration::from_secs(20)),
+            Err(Duration::from_secs(40))
+        );
+        assert!(limiter.check("198.51.100.1", start).is_ok());
+
+        // A new window starts once the old one is over
+        assert!(limiter.check("203.0.113.7", start + REPORT_WINDOW).is_ok());
+    }
+}
@@ -3,44 +3,95 @@
 //! Core authentication logic including password hashing, JWT generation,
 //! and token management.
 
+use crate::anomaly::{self, Anomaly, LoginOrigin};
+use crate::audit;
 use crate::config::AuthConfig;
+use crate::crypto::Encrypted;
 use crate::error::AuthError;
+use crate::i18n;
+use crate::keys::JwtKeys;
+use crate::mail;
+use crate::metrics::{self, Counter};
 use crate::models::*;
+use crate::registration::{self, Registration};
+use crate::PLUGIN_ID;
 
 use argon2::{
     password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
     Argon2, Params,
 };
-use chrono::{Duration, Utc};
-use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use chrono::{DateTime, Duration, Utc};
+use jsonwebtoken::{decode, DecodingKey, Validation};
 use rand::Rng;
-use sqlx::PgPool;
+use crate::db::DbPool;
+use std::sync::Arc;
 use uuid::Uuid;
 
 /// Authentication service
 pub struct AuthService {
-    db: PgPool,
+    db: DbPool,
     config: AuthConfig,
-    encoding_key: EncodingKey,
-    decoding_key: DecodingKey,
+    keys: Arc<JwtKeys>,
+    metrics: AuthMetrics,
+}
+
+/// Login counters exported through the shared metrics registry
+struct AuthMetrics {
+    logins: Option<Counter>,
+    login_failures: Option<Counter>,
+    lockouts: Option<Counter>,
+    suspicious_logins: Option<Counter>,
+}
+
+impl AuthMetrics {
+    fn register() -> Self {
+        let metrics = metrics::shared().plugin(PLUGIN_ID);
+        let counter = |name: &str, help: &str| {
+            metrics
+                .counter(name, help)
+                .map_err(|e| tracing::warn!("Auth metric not registered: {}", e))
+                .ok()
+        };
+        Self {
+            logins: counter("auth_logins_total", "Successful logins"),
+            login_failures: counter("auth_login_failures_total", "Logins rejected for a wrong password"),
+            lockouts: counter("auth_lockouts_total", "Accounts locked after too many failed logins"),
+            suspicious_logins: counter(
+                "auth_suspicious_logins_total",
+                "Logins from a new country, network or device",
+            ),
+        }
+    }
+}
+
+fn inc(counter: &Option<Counter>) {
+    if let Some(counter) = counter {
+        counter.inc();
+    }
 }
 
 impl AuthService {
-    /// Create a new authentication service
-    pub fn new(db: PgPool, config: AuthConfig) -> Self {
-        let encoding_key = EncodingKey::from_secret(config.jwt_secret.as_bytes());
-        let decoding_key = DecodingKey::from_secret(config.jwt_secret.as_bytes());
+    /// Create a new authentication service signing with `config.jwt_secret`
+    pub fn new(db: DbPool, config: AuthConfig) -> Self {
+        let keys = Arc::new(JwtKeys::from_secret(&config.jwt_secret));
+        Self::with_keys(db, config, keys)
+    }
 
+    /// Create a new authentication service signing with rotating keys
+    ///
+    /// Refresh tokens are stored hashed with `config.jwt_secret`, so they
+    /// survive key rotations but not a restart with a different secret.
+    pub fn with_keys(db: DbPool, config: AuthConfig, keys: Arc<JwtKeys>) -> Self {
         Self {
             db,
             config,
-            encoding_key,
-            decoding_key,
+            keys,
+            metrics: AuthMetrics::register(),
         }
     }
 
     /// Get reference to the database pool
-    pub fn db(&self) -> &PgPool {
+    pub fn db(&self) -> &DbPool {
         &self.db
     }
 
@@ -130,19 +181,36 @@ impl AuthService {
             iss: self.config.jwt_issuer.clone(),
             aud: self.config.jwt_audience.clone(),
             jti: Uuid::new_v4(),
+            locale: user.locale.clone(),
         };
 
-        let token = encode(&Header::default(), &claims, &self.encoding_key)?;
+        let token = self.keys.encode(&claims)?;
         Ok(token)
     }
 
-    /// Generate a refresh token
+    /// Generate a refresh token, continuing `family_id` (a rotation) or
+    /// starting a new family (a login)
     pub async fn generate_refresh_token(
         &self,
         user_id: Uuid,
+        family_id: Option<Uuid>,
         ip_address: Option<String>,
         user_agent: Option<String>,
     ) -> Result<String, AuthError> {
+        let (_, token) = self
+            .issue_refresh_token(user_id, family_id, ip_address, user_agent)
+            .await?;
+        Ok(token)
+    }
+
+    /// Store a refresh token; its ID and the token
+    async fn issue_refresh_token(
+        &self,
+        user_id: Uuid,
+        family_id: Option<Uuid>,
+        ip_address: Option<String>,
+        user_agent: Option<String>,
+    ) -> Result<(Uuid, String), AuthError> {
         let token_id = Uuid::new_v4();
         let now = Utc::now();
         let exp = now + Duration::seconds(self.config.refresh_token_expiration);
@@ -157,8 +225,8 @@ impl AuthService {
         // Store in database
         sqlx::query(
             r#"
-            INSERT INTO refresh_tokens (id, user_id, token_hash, expires_at, ip_address, user_agent)
-            VALUES ($1, $2, $3, $4, $5, $6)
+            INSERT INTO refresh_tokens (id, user_id, token_hash, expires_at, ip_address, user_agent, issued_at, created_at, family_id)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $7, $8)
             "#,
         )
         .bind(token_id)
@@ -167,6 +235,8 @@ impl AuthService {
         .bind(exp)
         .bind(&ip_address)
         .bind(&user_agent)
+        .bind(now)
+        .bind(family_id.unwrap_or(token_id))
         .execute(&self.db)
         .await?;
 
@@ -179,10 +249,10 @@ impl AuthService {
             iss: self.config.jwt_issuer.clone(),
         };
 
-        let jwt = encode(&Header::default(), &claims, &self.encoding_key)?;
+        let jwt = self.keys.encode(&claims)?;
 
         // Return combined token (JWT + random string for extra verification)
-        Ok(format!("{}.{}", jwt, token_string))
+        Ok((token_id, format!("{}.{}", jwt, token_string)))
     }
 
     /// Validate an access token
@@ -191,7 +261,7 @@ impl AuthService {
         validation.set_issuer(&[&self.config.jwt_issuer]);
         validation.set_audience(&[&self.config.jwt_audience]);
 
-        let token_data = decode::<AccessTokenClaims>(token, &self.decoding_key, &validation)?;
+        let token_data = self.keys.decode::<AccessTokenClaims>(token, &validation)?;
 
         Ok(token_data.claims)
     }
@@ -216,10 +286,20 @@ impl AuthService {
         // Validate password strength
         self.validate_password(&req.password)?;
 
+        // Check the email domain, then let plugins veto or adjust
+        self.config.registration.check(&req.email)?;
+        let registration = registration::apply_filters(Registration {
+            email: req.email,
+            name: req.name,
+            role: UserRole::User,
+            status: UserStatus::Active,
+        })
+        .await?;
+
         // Check if email exists
         let existing: Option<(Uuid,)> =
             sqlx::query_as("SELECT id FROM users WHERE email = $1")
-                .bind(&req.email)
+                .bind(&registration.email)
                 .fetch_optional(&self.db)
                 .await?;
 
@@ -233,14 +313,18 @@ impl AuthService {
         // Insert user
         let user = sqlx::query_as::<_, User>(
             r#"
-            INSERT INTO users (email, password_hash, name, status)
-            VALUES ($1, $2, $3, 'active')
+            INSERT INTO users (id, email, password_hash, name, role, status, password_changed_at, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $7, $7)
             RETURNING *
             "#,
         )
-        .bind(&req.email)
+        .bind(Uuid::new_v4())
+        .bind(&registration.email)
         .bind(&password_hash)
-        .bind(&req.name)
+        .bind(&registration.name)
+        .bind(&registration.role)
+        .bind(&registration.status)
+        .bind(Utc::now())
         .fetch_one(&self.db)
         .await?;
 
@@ -279,7 +363,10 @@ impl AuthService {
         // Verify password
         if !self.verify_password(&req.password, &user.password_hash)? {
             // Increment failed attempts
-            self.increment_failed_attempts(user.id).await?;
+            inc(&self.metrics.login_failures);
+            if self.increment_failed_attempts(user.id).await? {
+                inc(&self.metrics.lockouts);
+            }
             return Err(AuthError::InvalidCredentials);
         }
 
@@ -288,14 +375,35 @@ impl AuthService {
             return Err(AuthError::EmailNotVerified);
         }
 
+        // Expired passwords have to be changed first
+        let password_expires_in = self
+            .password_expires_at(&user)
+            .map(|expires_at| (expires_at - Utc::now()).num_seconds());
+        if password_expires_in.is_some_and(|secs| secs <= 0) {
+            return Err(AuthError::PasswordExpired);
+        }
+
+        // Compare with where the user logged in from before
+        let origin = LoginOrigin::locate(ip_address.as_deref(), user_agent.as_deref());
+        let anomalies = anomaly::assess(&self.db, &user, ip_address.as_deref(), &origin).await?;
+        if !anomalies.is_empty() {
+            self.flag_suspicious_login(&user, &origin, &anomalies, ip_address.clone())
+                .await?;
+        }
+
         // Reset failed attempts and update last login
         self.record_successful_login(user.id, ip_address.clone())
             .await?;
+        anomaly::remember(&self.db, user.id, &origin).await?;
+        inc(&self.metrics.logins);
+
+        // Make room for this session
+        let evicted_sessions = self.evict_sessions(user.id, ip_address.clone()).await?;
 
         // Generate tokens
         let access_token = self.generate_access_token(&user)?;
         let refresh_token = self
-            .generate_refresh_token(user.id, ip_address, user_agent)
+            .generate_refresh_token(user.id, None, ip_address, user_agent)
             .await?;
 
         Ok(AuthResponse {
@@ -304,9 +412,82 @@ impl AuthService {
             refresh_token,
             token_type: "Bearer".to_string(),
             expires_in: self.config.access_token_expiration,
+            password_expires_in,
+            evicted_sessions,
         })
     }
 
+    /// Record a suspicious login and alert the user; fails with
+    /// `LoginConfirmationRequired` when such logins need confirming
+    async fn flag_suspicious_login(
+        &self,
+        user: &User,
+        origin: &LoginOrigin,
+        anomalies: &[Anomaly],
+        ip_address: Option<String>,
+    ) -> Result<(), AuthError> {
+        let confirm = self.config.confirm_suspicious_logins;
+        inc(&self.metrics.suspicious_logins);
+        tracing::warn!(
+            user_id = %user.id,
+            anomalies = ?anomalies,
+            country = origin.country.as_deref().unwrap_or("-"),
+            device = origin.device.as_deref().unwrap_or("-"),
+            confirmation_required = confirm,
+            "Suspicious login"
+        );
+
+        audit::record(
+            &self.db,
+            user.id,
+            audit::SUSPICIOUS_LOGIN,
+            Some(user.id),
+            serde_json::json!({
+                "anomalies": anomalies,
+                "origin": origin,
+                "confirmation_required": confirm,
+            }),
+            ip_address,
+        )
+        .await?;
+
+        let token = if confirm {
+            let token_bytes: [u8; 32] = rand::thread_rng().gen();
+            let token = base64_url_encode(&token_bytes);
+            let expires_at = Utc::now() + Duration::seconds(LOGIN_CONFIRMATION_EXPIRATION);
+            anomaly::request_confirmation(&self.db, user.id, &self.hash_token(&token), origin, expires_at).await?;
+            Some(token)
+        } else {
+            None
+        };
+
+        if self.config.login_alerts || confirm {
+            let email = mail::login_alert_email(
+                &self.config.mail,
+                &user.mail_locale(),
+                &user.email,
+                &user.name,
+                &origin.describe(),
+                token.as_deref(),
+            );
+            mail::enqueue(&self.db, &email).await?;
+        }
+
+        if confirm {
+            return Err(AuthError::LoginConfirmationRequired);
+        }
+        Ok(())
+    }
+
+    /// Confirm a suspicious login from the link in its alert, so logging in
+    /// from there again goes through
+    pub async fn confirm_login(&self, token: &str) -> Result<(), AuthError> {
+        anomaly::confirm(&self.db, &self.hash_token(token))
+            .await?
+            .map(|_| ())
+            .ok_or(AuthError::InvalidToken)
+    }
+
     /// Logout by revoking refresh token
     pub async fn logout(&self, refresh_token: &str) -> Result<(), AuthError> {
         // Parse the refresh token
@@ -322,11 +503,13 @@ impl AuthService {
         validation.set_issuer(&[&self.config.jwt_issuer]);
         validation.insecure_disable_signature_validation();
 
-        let token_data = decode::<RefreshTokenClaims>(jwt_part, &self.decoding_key, &validation)?;
+        // The signature isn't checked, so any key decodes
+        let token_data = decode::<RefreshTokenClaims>(jwt_part, &DecodingKey::from_secret(&[]), &validation)?;
 
         // Revoke the token
-        sqlx::query("UPDATE refresh_tokens SET revoked_at = NOW() WHERE id = $1")
+        sqlx::query("UPDATE refresh_tokens SET revoked_at = $2 WHERE id = $1")
             .bind(token_data.claims.tid)
+            .bind(Utc::now())
             .execute(&self.db)
             .await?;
 
@@ -356,7 +539,7 @@ impl AuthService {
         let mut validation = Validation::default();
         validation.set_issuer(&[&self.config.jwt_issuer]);
 
-        let token_data = decode::<RefreshTokenClaims>(jwt_part, &self.decoding_key, &validation)?;
+        let token_data = self.keys.decode::<RefreshTokenClaims>(jwt_part, &validation)?;
         let claims = token_data.claims;
 
         // Verify token in database
@@ -372,13 +555,41 @@ impl AuthService {
         let stored_token = stored_token.ok_or(AuthError::InvalidToken)?;
 
         if !stored_token.is_valid() {
-            // Token reuse detected - revoke all tokens for this user
-            if stored_token.is_revoked() {
+            // Token reuse detected - revoke all tokens for this user. A
+            // revoked token that was never rotated (its session was logged
+            // out or evicted) is just rejected.
+            let rotated: i64 = sqlx::query_scalar(
+                "SELECT COUNT(*) FROM refresh_tokens WHERE family_id = $1 AND issued_at > $2",
+            )
+            .bind(stored_token.family())
+            .bind(stored_token.issued_at)
+            .fetch_one(&self.db)
+            .await?;
+
+            if stored_token.is_revoked() && rotated > 0 {
                 tracing::warn!(
                     user_id = %claims.sub,
+                    family_id = %stored_token.family(),
+                    token_id = %stored_token.id,
+                    ip = ip_address.as_deref().unwrap_or("-"),
                     "Refresh token reuse detected, revoking all tokens"
                 );
-                self.revoke_all_tokens(claims.sub).await?;
+                let revoked = self.revoke_all_tokens(claims.sub).await?;
+                audit::record(
+                    &self.db,
+                    claims.sub,
+                    audit::TOKEN_REUSED,
+                    Some(claims.sub),
+                    serde_json::json!({
+                        "family_id": stored_token.family(),
+                        "token_id": stored_token.id,
+                        "replaced_by": stored_token.replaced_by,
+                        "user_agent": user_agent,
+                        "revoked_tokens": revoked,
+                    }),
+                    ip_address,
+                )
+                .await?;
             }
             return Err(AuthError::TokenRevoked);
         }
@@ -396,13 +607,15 @@ impl AuthService {
 
         // Generate new tokens
         let new_access_token = self.generate_access_token(&user)?;
-        let new_refresh_token = self
-            .generate_refresh_token(user.id, ip_address, user_agent)
+        let (new_token_id, new_refresh_token) = self
+            .issue_refresh_token(user.id, Some(stored_token.family()), ip_address, user_agent)
             .await?;
 
-        // Revoke old refresh token (rotation)
-        sqlx::query("UPDATE refresh_tokens SET revoked_at = NOW() WHERE id = $1")
+        // Revoke old refresh token (rotation), linking it to its successor
+        sqlx::query("UPDATE refresh_tokens SET revoked_at = $2, replaced_by = $3 WHERE id = $1")
             .bind(claims.tid)
+            .bind(Utc::now())
+            .bind(new_token_id)
             .execute(&self.db)
             .await?;
 
@@ -414,16 +627,89 @@ impl AuthService {
         })
     }
 
-    /// Revoke all refresh tokens for a user
-    async fn revoke_all_tokens(&self, user_id: Uuid) -> Result<(), AuthError> {
-        sqlx::query(
-            "UPDATE refresh_tokens SET revoked_at = NOW() WHERE user_id = $1 AND revoked_at IS NULL",
+    /// Revoke all refresh tokens for a user; returns how many were still valid
+    async fn revoke_all_tokens(&self, user_id: Uuid) -> Result<u64, AuthError> {
+        let result = sqlx::query(
+            "UPDATE refresh_tokens SET revoked_at = $2 WHERE user_id = $1 AND revoked_at IS NULL",
         )
         .bind(user_id)
+        .bind(Utc::now())
         .execute(&self.db)
         .await?;
 
-        Ok(())
+        Ok(result.rows_affected())
+    }
+
+    /// End the oldest sessions of a user so a new one stays within
+    /// `max_sessions`; each is recorded in the audit log
+    ///
+    /// Access tokens already issued to an ended session stay valid until
+    /// they expire.
+    async fn evict_sessions(
+        &self,
+        user_id: Uuid,
+        ip_address: Option<String>,
+    ) -> Result<Vec<SessionInfo>, AuthError> {
+        let limit = self.config.max_sessions as usize;
+        if limit == 0 {
+            return Ok(Vec::new());
+        }
+
+        let mut sessions: Vec<SessionRow> = sqlx::query_as(
+            r#"
+            SELECT t.family_id, t.user_agent, t.ip_address, f.issued_at AS started_at, t.issued_at AS last_used_at
+            FROM refresh_tokens t
+            JOIN refresh_tokens f ON f.id = t.family_id
+            WHERE t.user_id = $1 AND t.revoked_at IS NULL AND t.expires_at > $2
+            ORDER BY f.issued_at, t.family_id, t.issued_at DESC
+            "#,
+        )
+        .bind(user_id)
+        .bind(Utc::now())
+        .fetch_all(&self.db)
+        .await?;
+        // A family briefly has two valid tokens while rotating
+        sessions.dedup_by_key(|session| session.family_id);
+
+        let excess = (sessions.len() + 1).saturating_sub(limit);
+        let mut evicted = Vec::with_capacity(excess);
+        for session in sessions.into_iter().take(excess) {
+            sqlx::query("UPDATE refresh_tokens SET revoked_at = $2 WHERE family_id = $1 AND revoked_at IS NULL")
+                .bind(session.family_id)
+                .bind(Utc::now())
+                .execute(&self.db)
+                .await?;
+
+            audit::record(
+                &self.db,
+                user_id,
+                audit::SESSION_EVICTED,
+                Some(user_id),
+                serde_json::json!({
+                    "family_id": session.family_id,
+                    "user_agent": session.user_agent,
+                    "started_at": session.started_at,
+                }),
+                ip_address.clone(),
+            )
+            .await?;
+
+            evicted.push(SessionInfo {
+                user_agent: session.user_agent,
+                ip_address: session.ip_address,
+                started_at: session.started_at,
+                last_used_at: session.last_used_at,
+            });
+        }
+
+        if !evicted.is_empty() {
+            tracing::info!(
+                user_id = %user_id,
+                evicted = evicted.len(),
+                "Session limit reached, ended the oldest sessions"
+            );
+        }
+        Ok(evicted)
     }
 
     // ============================================
@@ -452,18 +738,21 @@ impl AuthService {
         let expires_at = Utc::now() + Duration::seconds(self.config.password_reset_expiration);
 
         // Invalidate existing tokens
-        sqlx::query("UPDATE password_reset_tokens SET used_at = NOW() WHERE user_id = $1 AND used_at IS NULL")
+        sqlx::query("UPDATE password_reset_tokens SET used_at = $2 WHERE user_id = $1 AND used_at IS NULL")
             .bind(user.id)
+            .bind(Utc::now())
             .execute(&self.db)
             .await?;
 
         // Store new token
         sqlx::query(
-            "INSERT INTO password_reset_tokens (user_id, token_hash, expires_at) VALUES ($1, $2, $3)",
+            "INSERT INTO password_reset_tokens (id, user_id, token_hash, expires_at, created_at) VALUES ($1, $2, $3, $4, $5)",
         )
+        .bind(Uuid::new_v4())
         .bind(user.id)
         .bind(&token_hash)
         .bind(expires_at)
+        .bind(Utc::now())
         .execute(&self.db)
         .await?;
 
@@ -481,36 +770,27 @@ impl AuthService {
         let token_record: Option<(Uuid, Uuid)> = sqlx::query_as(
             r#"
             SELECT id, user_id FROM password_reset_tokens
-            WHERE token_hash = $1 AND expires_at > NOW() AND used_at IS NULL
+            WHERE token_hash = $1 AND expires_at > $2 AND used_at IS NULL
             "#,
         )
         .bind(&token_hash)
+        .bind(Utc::now())
         .fetch_optional(&self.db)
         .await?;
 
         let (token_id, user_id) = token_record.ok_or(AuthError::InvalidToken)?;
-
-        // Hash new password
-        let password_hash = self.hash_password(&req.password)?;
+        let user = self.get_user(user_id).await?.ok_or(AuthError::UserNotFound)?;
 
         // Update user password
-        sqlx::query(
-            "UPDATE users SET password_hash = $1, password_changed_at = NOW(), updated_at = NOW() WHERE id = $2",
-        )
-        .bind(&password_hash)
-        .bind(user_id)
-        .execute(&self.db)
-        .await?;
+        self.set_password(&user, &req.password).await?;
 
         // Mark token as used
-        sqlx::query("UPDATE password_reset_tokens SET used_at = NOW() WHERE id = $1")
+        sqlx::query("UPDATE password_reset_tokens SET used_at = $2 WHERE id = $1")
             .bind(token_id)
+            .bind(Utc::now())
             .execute(&self.db)
             .await?;
 
-        // Revoke all refresh tokens
-        self.revoke_all_tokens(user_id).await?;
-
         Ok(())
     }
 
@@ -532,23 +812,104 @@ impl AuthService {
             return Err(AuthError::InvalidCredentials);
         }
 
-        // Validate new password
-        self.validate_password(&req.new_password)?;
+        self.set_password(&user, &req.new_password).await
+    }
+
+    /// Change an expired password; the user can't log in to use
+    /// `change_password`, so the current password is checked like a login's
+    pub async fn change_expired_password(&self, req: ExpiredPasswordChangeRequest) -> Result<(), AuthError> {
+        let user = self
+            .get_user_by_email(&req.email)
+            .await?
+            .ok_or(AuthError::InvalidCredentials)?;
+
+        if user.is_locked() {
+            return Err(AuthError::AccountLocked);
+        }
+        if user.status != UserStatus::Active {
+            return Err(AuthError::AccountNotActive);
+        }
+
+        if !self.verify_password(&req.current_password, &user.password_hash)? {
+            inc(&self.metrics.login_failures);
+            if self.increment_failed_attempts(user.id).await? {
+                inc(&self.metrics.lockouts);
+            }
+            return Err(AuthError::InvalidCredentials);
+        }
+
+        self.set_password(&user, &req.new_password).await
+    }
+
+    /// When the password of `user` expires, if passwords do
+    pub fn password_expires_at(&self, user: &User) -> Option<DateTime<Utc>> {
+        match self.config.password_max_age_days {
+            0 => None,
+            days => Some(user.password_changed_at + Duration::days(days.into())),
+        }
+    }
+
+    /// Replace the password of `user` and end their sessions; fails with
+    /// `PasswordReused` for one of the last `password_history` passwords
+    async fn set_password(&self, user: &User, password: &str) -> Result<(), AuthError> {
+        self.validate_password(password)?;
+
+        let remembered = self.config.password_history.saturating_sub(1) as i64;
+        if self.config.password_history > 0 {
+            let previous: Vec<String> = sqlx::query_scalar(
+                "SELECT password_hash FROM auth_password_history WHERE user_id = $1 ORDER BY created_at DESC LIMIT $2",
+            )
+            .bind(user.id)
+            .bind(remembered)
+            .fetch_all(&self.db)
+            .await?;
 
-        // Hash new password
-        let password_hash = self.hash_password(&req.new_password)?;
+            for hash in std::iter::once(&user.password_hash).chain(&previous) {
+                if self.verify_password(password, hash)? {
+                    return Err(AuthError::PasswordReused);
+                }
+            }
+        }
+
+        let password_hash = self.hash_password(password)?;
+        let now = Utc::now();
 
-        // Update password
         sqlx::query(
-            "UPDATE users SET password_hash = $1, password_changed_at = NOW(), updated_at = NOW() WHERE id = $2",
+            "UPDATE users SET password_hash = $1, password_changed_at = $3, updated_at = $3 WHERE id = $2",
         )
         .bind(&password_hash)
-        .bind(user_id)
+        .bind(user.id)
+        .bind(now)
+        .execute(&self.db)
+        .await?;
+
+        // Remember the old password, forgetting those no longer checked
+        if remembered > 0 {
+            sqlx::query(
+                "INSERT INTO auth_password_history (id, user_id, password_hash, created_at) VALUES ($1, $2, $3, $4)",
+            )
+            .bind(Uuid::new_v4())
+            .bind(user.id)
+            .bind(&user.password_hash)
+            .bind(now)
+            .execute(&self.db)
+            .await?;
+        }
+        sqlx::query(
+            r#"
+            DELETE FROM auth_password_history
+            WHERE user_id = $1 AND id NOT IN (
+                SELECT id FROM auth_password_history WHERE user_id = $1 ORDER BY created_at DESC LIMIT $2
+            )
+            "#,
+        )
+        .bind(user.id)
+        .bind(remembered)
         .execute(&self.db)
         .await?;
 
         // Revoke all refresh tokens
-        self.revoke_all_tokens(user_id).await?;
+        self.revoke_all_tokens(user.id).await?;
 
         Ok(())
     }
@@ -568,19 +929,22 @@ impl AuthService {
 
         // Invalidate existing tokens
         sqlx::query(
-            "UPDATE email_verification_tokens SET used_at = NOW() WHERE user_id = $1 AND used_at IS NULL",
+            "UPDATE email_verification_tokens SET used_at = $2 WHERE user_id = $1 AND used_at IS NULL",
         )
         .bind(user_id)
+        .bind(Utc::now())
         .execute(&self.db)
         .await?;
 
         // Store new token
         sqlx::query(
-            "INSERT INTO email_verification_tokens (user_id, token_hash, expires_at) VALUES ($1, $2, $3)",
+            "INSERT INTO email_verification_tokens (id, user_id, token_hash, expires_at, created_at) VALUES ($1, $2, $3, $4, $5)",
         )
+        .bind(Uuid::new_v4())
         .bind(user_id)
         .bind(&token_hash)
         .bind(expires_at)
+        .bind(Utc::now())
         .execute(&self.db)
         .await?;
 
@@ -595,10 +959,11 @@ impl AuthService {
         let token_record: Option<(Uuid, Uuid)> = sqlx::query_as(
             r#"
             SELECT id, user_id FROM email_verification_tokens
-            WHERE token_hash = $1 AND expires_at > NOW() AND used_at IS NULL
+            WHERE token_hash = $1 AND expires_at > $2 AND used_at IS NULL
             "#,
         )
         .bind(&token_hash)
+        .bind(Utc::now())
         .fetch_optional(&self.db)
         .await?;
 
@@ -606,15 +971,17 @@ impl AuthService {
 
         // Update user
         let user: User = sqlx::query_as(
-            "UPDATE users SET email_verified_at = NOW(), status = 'active', updated_at = NOW() WHERE id = $1 RETURNING *",
+            "UPDATE users SET email_verified_at = $2, status = 'active', updated_at = $2 WHERE id = $1 RETURNING *",
         )
         .bind(user_id)
+        .bind(Utc::now())
         .fetch_one(&self.db)
         .await?;
 
         // Mark token as used
-        sqlx::query("UPDATE email_verification_tokens SET used_at = NOW() WHERE id = $1")
+        sqlx::query("UPDATE email_verification_tokens SET used_at = $2 WHERE id = $1")
             .bind(token_id)
+            .bind(Utc::now())
             .execute(&self.db)
             .await?;
 
@@ -643,32 +1010,243 @@ impl AuthService {
         Ok(user)
     }
 
-    /// Increment failed login attempts
-    async fn increment_failed_attempts(&self, user_id: Uuid) -> Result<(), AuthError> {
-        let result = sqlx::query(
+    /// Set or clear (`None`) a user's preferred locale; access tokens carry
+    /// it from the next refresh on
+    pub async fn set_locale(&self, user_id: Uuid, locale: Option<&str>) -> Result<User, AuthError> {
+        let locale = match locale.map(str::trim).filter(|l| !l.is_empty()) {
+            Some(locale) if i18n::is_supported(locale) => Some(locale.replace('_', "-").to_ascii_lowercase()),
+            Some(locale) => {
+                return Err(AuthError::Validation(i18n::t_args(
+                    "auth-unsupported-locale",
+                    &[("locale", locale), ("available", &i18n::available_locales().join(", "))],
+                )))
+            }
+            None => None,
+        };
+
+        sqlx::query_as("UPDATE users SET locale = $2, updated_at = $3 WHERE id = $1 RETURNING *")
+            .bind(user_id)
+            .bind(locale)
+            .bind(Utc::now())
+            .fetch_optional(&self.db)
+            .await?
+            .ok_or(AuthError::UserNotFound)
+    }
+
+    // ============================================
+    // Admin User Management
+    // ============================================
+
+    /// Users matching `query`, newest first
+    pub async fn list_users(&self, query: &AdminUserQuery) -> Result<AdminUserPage, AuthError> {
+        let search = query
+            .search
+            .as_deref()
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(|s| format!("%{}%", escape_like(&s.to_lowercase())));
+        let (page, per_page) = (query.page(), query.per_page());
+
+        const FILTER: &str = r#"
+            ($1 IS NULL OR LOWER(email) LIKE $1 ESCAPE '\' OR LOWER(name) LIKE $1 ESCAPE '\')
+            AND ($2 IS NULL OR role = $2)
+            AND ($3 IS NULL OR status = $3)
+        "#;
+
+        let total: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*) FROM users WHERE {}", FILTER))
+            .bind(&search)
+            .bind(&query.role)
+            .bind(&query.status)
+            .fetch_one(&self.db)
+            .await?;
+
+        let users: Vec<User> = sqlx::query_as(&format!(
+            "SELECT * FROM users WHERE {} ORDER BY created_at DESC, id LIMIT $4 OFFSET $5",
+            FILTER
+        ))
+        .bind(&search)
+        .bind(&query.role)
+        .bind(&query.status)
+        .bind(per_page)
+        .bind((page - 1).saturating_mul(per_page))
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(AdminUserPage {
+            users: users.into_iter().map(AdminUserResponse::from).collect(),
+            page,
+            per_page,
+            total,
+        })
+    }
+
+    /// Change a user's role or status, or unlock the account, on behalf of
+    /// `actor_id`
+    ///
+    /// Admins can't change their own role or status. A new role or a status
+    /// other than active ends the user's sessions, so old tokens don't keep
+    /// the previous access. Changes are recorded in the audit log.
+    pub async fn update_user(
+        &self,
+        actor_id: Uuid,
+        user_id: Uuid,
+        req: AdminUpdateUserRequest,
+        ip_address: Option<String>,
+    ) -> Result<User, AuthError> {
+        let user = self.get_user(user_id).await?.ok_or(AuthError::UserNotFound)?;
+
+        let role = req.role.filter(|role| *role != user.role);
+        let status = req.status.filter(|status| *status != user.status);
+        let unlock = req.unlock && (user.failed_login_attempts > 0 || user.locked_until.is_some());
+
+        if actor_id == user_id && (role.is_some() || status.is_some()) {
+            return Err(AuthError::Validation(
+                "Admins can't change their own role or status".to_string(),
+            ));
+        }
+
+        let mut details = serde_json::Map::new();
+        if let Some(role) = &role {
+            details.insert("role".into(), serde_json::json!({ "from": user.role, "to": role }));
+        }
+        if let Some(status) = &status {
+            details.insert("status".into(), serde_json::json!({ "from": user.status, "to": status }));
+        }
+        if unlock {
+            details.insert("unlocked".into(), serde_json::Value::Bool(true));
+        }
+        if details.is_empty() {
+            return Ok(user);
+        }
+
+        let updated: User = sqlx::query_as(
+            r#"
+            UPDATE users SET
+                role = $2,
+                status = $3,
+                failed_login_attempts = $4,
+                locked_until = $5,
+                updated_at = $6
+            WHERE id = $1
+            RETURNING *
+            "#,
+        )
+        .bind(user_id)
+        .bind(role.as_ref().unwrap_or(&user.role))
+        .bind(status.as_ref().unwrap_or(&user.status))
+        .bind(if unlock { 0 } else { user.failed_login_attempts })
+        .bind(if unlock { None } else { user.locked_until })
+        .bind(Utc::now())
+        .fetch_one(&self.db)
+        .await?;
+
+        if role.is_some() || status.is_some_and(|status| status != UserStatus::Active) {
+            self.end_sessions(user_id).await?;
+        }
+
+        audit::record(
+            &self.db,
+            actor_id,
+            audit::USER_UPDATED,
+            Some(user_id),
+            serde_json::Value::Object(details),
+            ip_address,
+        )
+        .await?;
+
+        Ok(updated)
+    }
+
+    /// End every session of a user on behalf of `actor_id`: refresh tokens
+    /// are revoked and access tokens rejected; returns the number of refresh
+    /// tokens revoked
+    pub async fn force_logout(
+        &self,
+        actor_id: Uuid,
+        user_id: Uuid,
+        ip_address: Option<String>,
+    ) -> Result<u64, AuthError> {
+        self.get_user(user_id).await?.ok_or(AuthError::UserNotFound)?;
+
+        let revoked = self.end_sessions(user_id).await?;
+
+        audit::record(
+            &self.db,
+            actor_id,
+            audit::USER_FORCE_LOGOUT,
+            Some(user_id),
+            serde_json::json!({ "revoked_tokens": revoked }),
+            ip_address,
+        )
+        .await?;
+
+        Ok(revoked)
+    }
+
+    /// Every token of a refresh token family in the order issued, and the
+    /// attempts to reuse its rotated tokens
+    pub async fn token_family(&self, family_id: Uuid) -> Result<Option<TokenFamilyResponse>, AuthError> {
+        let tokens: Vec<RefreshToken> =
+            sqlx::query_as("SELECT * FROM refresh_tokens WHERE family_id = $1 ORDER BY issued_at, id")
+                .bind(family_id)
+                .fetch_all(&self.db)
+                .await?;
+        let Some(user_id) = tokens.first().map(|token| token.user_id) else {
+            return Ok(None);
+        };
+
+        let family = family_id.to_string();
+        let replays = audit::for_user(&self.db, user_id, Some(audit::TOKEN_REUSED), REPLAY_HISTORY)
+            .await?
+            .into_iter()
+            .filter(|event| event.details["family_id"] == family.as_str())
+            .collect();
+
+        Ok(Some(TokenFamilyResponse {
+            family_id,
+            user_id,
+            tokens: tokens.into_iter().map(TokenChainEntry::from).collect(),
+            replays,
+        }))
+    }
+
+    async fn end_sessions(&self, user_id: Uuid) -> Result<u64, AuthError> {
+        let revoked = self.revoke_all_tokens(user_id).await?;
+        crate::middleware::revoke_access_tokens(user_id);
+        Ok(revoked)
+    }
+
+    /// Increment failed login attempts; true when this attempt locked the account
+    async fn increment_failed_attempts(&self, user_id: Uuid) -> Result<bool, AuthError> {
+        let now = Utc::now();
+        let attempts: Option<i32> = sqlx::query_scalar(
             r#"
             UPDATE users SET
                 failed_login_attempts = failed_login_attempts + 1,
                 locked_until = CASE
                     WHEN failed_login_attempts + 1 >= $2
-                    THEN NOW() + INTERVAL '1 second' * $3
+                    THEN $3
                     ELSE locked_until
                 END,
-                updated_at = NOW()
+                updated_at = $4
             WHERE id = $1
+            RETURNING failed_login_attempts
             "#,
         )
         .bind(user_id)
         .bind(self.config.max_login_attempts)
-        .bind(self.config.lockout_duration)
-        .execute(&self.db)
+        .bind(now + Duration::seconds(self.config.lockout_duration))
+        .bind(now)
+        .fetch_optional(&self.db)
         .await?;
 
-        if result.rows_affected() == 0 {
-            tracing::warn!(user_id = %user_id, "Failed to increment login attempts");
+        match attempts {
+            Some(attempts) => Ok(attempts == self.config.max_login_attempts),
+            None => {
+                tracing::warn!(user_id = %user_id, "Failed to increment login attempts");
+                Ok(false)
+            }
         }
-
-        Ok(())
     }
 
     /// Record successful login
@@ -682,14 +1260,15 @@ impl AuthService {
             UPDATE users SET
                 failed_login_attempts = 0,
                 locked_until = NULL,
-                last_login_at = NOW(),
+                last_login_at = $3,
                 last_login_ip = $2,
-                updated_at = NOW()
+                updated_at = $3
             WHERE id = $1
             "#,
         )
         .bind(user_id)
-        .bind(ip_address)
+        .bind(ip_address.map(Encrypted))
+        .bind(Utc::now())
         .execute(&self.db)
         .await?;
 
@@ -697,6 +1276,27 @@ impl AuthService {
     }
 }
 
+/// Seconds a suspicious login can be confirmed for
+const LOGIN_CONFIRMATION_EXPIRATION: i64 = 3600;
+
+/// Reuse events searched for those of one token family
+const REPLAY_HISTORY: i64 = 500;
+
+/// A valid token of a session, with its family's start
+#[derive(sqlx::FromRow)]
+struct SessionRow {
+    family_id: Uuid,
+    user_agent: Option<String>,
+    ip_address: Option<String>,
+    started_at: DateTime<Utc>,
+    last_used_at: DateTime<Utc>,
+}
+
+/// Escape `LIKE` wildcards, for patterns using `ESCAPE '\'`
+fn escape_like(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+}
+
 /// URL-safe base64 encoding
 fn base64_url_encode(data: &[u8]) -> String {
     use std::fmt::Write;
@@ -706,3 +1306,452 @@ fn base64_url_encode(data: &[u8]) -> String {
     }
     result
 }
+
+// ============================================
+// Tests
+// ============================================
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+    use super::*;
+
+    async fn service() -> AuthService {
+        let db = sqlx::sqlite::SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        crate::AuthPlugin::migrations().up(&db, None, false).await.unwrap();
+
+        let config = AuthConfig {
+            jwt_secret: "s".repeat(32),
+            access_token_expiration: 900,
+            refresh_token_expiration: 604800,
+            jwt_issuer: "test".to_string(),
+            jwt_audience: "test".to_string(),
+            argon2_memory_cost: 8,
+            argon2_time_cost: 1,
+            argon2_parallelism: 1,
+            max_login_attempts: 2,
+            lockout_duration: 900,
+            password_reset_expiration: 3600,
+            email_verification_expiration: 86400,
+            min_password_length: 8,
+            require_email_verification: false,
+            password_max_age_days: 90,
+            password_history: 3,
+            login_alerts: true,
+            confirm_suspicious_logins: false,
+            max_sessions: 2,
+            admin_rate_limit: 60,
+            registration: crate::registration::RegistrationPolicy::default(),
+            cors: crate::cors::CorsPolicy::default(),
+            mail: crate::mail::MailConfig::default(),
+        };
+        AuthService::new(db, config)
+    }
+
+    fn register_request() -> RegisterRequest {
+        RegisterRequest {
+            email: "ada@example.com".to_string(),
+            password: "Secret123".to_string(),
+            password_confirm: "Secret123".to_string(),
+            name: "Ada".to_string(),
+        }
+    }
+
+    fn login_request(password: &str) -> LoginRequest {
+        LoginRequest {
+            email: "ada@example.com".to_string(),
+            password: password.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_register_login_and_rotate_on_sqlite() {
+        let auth = service().await;
+
+        let user = auth.register(register_request()).await.unwrap();
+        assert_eq!(user.role, UserRole::User);
+        assert_eq!(user.status, UserStatus::Active);
+        assert!(matches!(auth.register(register_request()).await, Err(AuthError::EmailExists)));
+
+        let login = auth.login(login_request("Secret123"), None, None).await.unwrap();
+        let rotated = auth.refresh_tokens(&login.refresh_token, None, None).await.unwrap();
+        assert!(matches!(
+            auth.refresh_tokens(&login.refresh_token, None, None).await,
+            Err(AuthError::TokenRevoked)
+        ));
+        assert!(matches!(
+            auth.refresh_tokens(&rotated.refresh_token, None, None).await,
+            Err(AuthError::TokenRevoked)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_lockout_and_password_reset_on_sqlite() {
+        let auth = service().await;
+        auth.register(register_request()).await.unwrap();
+
+        for _ in 0..2 {
+            assert!(auth.login(login_request("Wrong1234"), None, None).await.is_err());
+        }
+        assert!(matches!(
+            auth.login(login_request("Secret123"), None, None).await,
+            Err(AuthError::AccountLocked)
+        ));
+
+        let token = auth.forgot_password("ada@example.com").await.unwrap();
+        auth.reset_password(ResetPasswordRequest {
+            token: token.clone(),
+            password: "Changed123".to_string(),
+            password_confirm: "Changed123".to_string(),
+        })
+        .await
+        .unwrap();
+        assert!(matches!(
+            auth.reset_password(ResetPasswordRequest {
+                token,
+                password: "Again1234".to_string(),
+                password_confirm: "Again1234".to_string(),
+            })
+            .await,
+            Err(AuthError::InvalidToken)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_admin_user_management_on_sqlite() {
+        let auth = service().await;
+        let admin = auth.register(register_request()).await.unwrap();
+        let user = auth
+            .register(RegisterRequest {
+                email: "grace@example.com".to_string(),
+                name: "Grace 100%".to_string(),
+                ..register_request()
+            })
+            .await
+            .unwrap();
+
+        let list = |search: Option<&str>, status: Option<UserStatus>| AdminUserQuery {
+            search: search.map(str::to_string),
+            role: None,
+            status,
+            page: None,
+            per_page: None,
+        };
+        let page = auth.list_users(&list(None, None)).await.unwrap();
+        assert_eq!((page.total, page.page, page.per_page), (2, 1, 20));
+        let page = auth.list_users(&list(Some("GRACE"), None)).await.unwrap();
+        assert_eq!(page.users.iter().map(|u| u.id).collect::<Vec<_>>(), vec![user.id]);
+        assert_eq!(auth.list_users(&list(Some("_"), None)).await.unwrap().total, 0);
+        assert_eq!(auth.list_users(&list(Some("100%"), None)).await.unwrap().total, 1);
+
+        // Admins can't demote or suspend themselves
+        let demote = AdminUpdateUserRequest {
+            role: Some(UserRole::User),
+            status: Some(UserStatus::Suspended),
+            unlock: false,
+        };
+        assert!(matches!(
+            auth.update_user(admin.id, admin.id, demote.clone(), None).await,
+            Err(AuthError::Validation(_))
+        ));
+
+        let login = auth
+            .login(
+                LoginRequest {
+                    email: "grace@example.com".to_string(),
+                    password: "Secret123".to_string(),
+                },
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        let updated = auth
+            .update_user(
+                admin.id,
+                user.id,
+                AdminUpdateUserRequest {
+                    role: None,
+                    ..demote
+                },
+                Some("203.0.113.9".to_string()),
+            )
+            .await
+            .unwrap();
+        assert_eq!(updated.status, UserStatus::Suspended);
+        assert!(matches!(
+            auth.refresh_tokens(&login.refresh_token, None, None).await,
+            Err(AuthError::TokenRevoked)
+        ));
+        assert_eq!(
+            auth.list_users(&list(None, Some(UserStatus::Suspended))).await.unwrap().total,
+            1
+        );
+
+        assert_eq!(auth.force_logout(admin.id, user.id, None).await.unwrap(), 0);
+        assert!(matches!(
+            auth.force_logout(admin.id, Uuid::new_v4(), None).await,
+            Err(AuthError::UserNotFound)
+        ));
+
+        let events = audit::for_user(&auth.db, user.id, None, 10).await.unwrap();
+        let actions: Vec<_> = events.iter().map(|e| e.action.as_str()).collect();
+        assert_eq!(actions, [audit::USER_FORCE_LOGOUT, audit::USER_UPDATED]);
+        assert_eq!(events[1].actor_id, Some(admin.id));
+        assert_eq!(events[1].details["status"]["to"], "suspended");
+        assert_eq!(events[1].ip_address.as_deref(), Some("203.0.113.9"));
+    }
+
+    #[tokio::test]
+    async fn test_login_over_session_limit_evicts_oldest_on_sqlite() {
+        let auth = service().await;
+        let user = auth.register(register_request()).await.unwrap();
+        let login = |device: &str| auth.login(login_request("Secret123"), None, Some(device.to_string()));
+
+        let phone = login("phone").await.unwrap();
+        let laptop = login("laptop").await.unwrap();
+        assert!(laptop.evicted_sessions.is_empty());
+
+        // Rotation keeps the phone's session, and its place in line
+        let phone = auth.refresh_tokens(&phone.refresh_token, None, Some("phone".into())).await.unwrap();
+
+        let tablet = login("tablet").await.unwrap();
+        let evicted: Vec<_> = tablet.evicted_sessions.iter().map(|s| s.user_agent.as_deref()).collect();
+        assert_eq!(evicted, [Some("phone")]);
+        assert!(tablet.evicted_sessions[0].last_used_at > tablet.evicted_sessions[0].started_at);
+        assert!(matches!(
+            auth.refresh_tokens(&phone.refresh_token, None, None).await,
+            Err(AuthError::TokenRevoked)
+        ));
+        auth.refresh_tokens(&laptop.refresh_token, None, None).await.unwrap();
+
+        let events = audit::for_user(&auth.db, user.id, Some(audit::SESSION_EVICTED), 10).await.unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].details["user_agent"], "phone");
+    }
+
+    #[tokio::test]
+    async fn test_token_family_records_rotation_chain_and_replays_on_sqlite() {
+        let auth = service().await;
+        let user = auth.register(register_request()).await.unwrap();
+
+        let login = auth.login(login_request("Secret123"), None, Some("phone".into())).await.unwrap();
+        let rotated = auth
+            .refresh_tokens(&login.refresh_token, Some("198.51.100.7".into()), Some("phone".into()))
+            .await
+            .unwrap();
+        auth.refresh_tokens(&login.refresh_token, Some("203.0.113.66".into()), Some("curl".into()))
+            .await
+            .unwrap_err();
+
+        let family_id: Uuid = sqlx::query_scalar("SELECT family_id FROM refresh_tokens LIMIT 1")
+            .fetch_one(&auth.db)
+            .await
+            .unwrap();
+        let family = auth.token_family(family_id).await.unwrap().unwrap();
+        assert_eq!(family.user_id, user.id);
+        assert_eq!(family.tokens.len(), 2);
+        assert_eq!(family.tokens[0].id, family_id);
+        assert_eq!(family.tokens[0].replaced_by, Some(family.tokens[1].id));
+        assert_eq!(family.tokens[1].ip_address.as_deref(), Some("198.51.100.7"));
+        assert!(family.tokens.iter().all(|token| token.revoked_at.is_some()));
+
+        assert_eq!(family.replays.len(), 1);
+        assert_eq!(family.replays[0].ip_address.as_deref(), Some("203.0.113.66"));
+        assert_eq!(family.replays[0].details["user_agent"], "curl");
+        assert_eq!(family.replays[0].details["token_id"], family_id.to_string());
+
+        assert!(matches!(
+            auth.refresh_tokens(&rotated.refresh_token, None, None).await,
+            Err(AuthError::TokenRevoked)
+        ));
+        assert!(auth.token_family(Uuid::new_v4()).await.unwrap().is_none());
+    }
+
+    struct FakeGeoIp;
+
+    impl anomaly::GeoLookup for FakeGeoIp {
+        fn lookup(&self, ip: std::net::IpAddr) -> anomaly::GeoInfo {
+            let (country, asn) = match ip.to_string().as_str() {
+                "198.51.100.1" | "198.51.100.2" => ("DE", 3320),
+                "203.0.113.5" => ("FR", 3215),
+                _ => ("JP", 2516),
+            };
+            anomaly::GeoInfo {
+                country: Some(country.to_string()),
+                asn: Some(asn),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_suspicious_login_alerts_and_confirmation_on_sqlite() {
+        anomaly::install_geo_lookup(Some(Arc::new(FakeGeoIp)));
+        let auth = service().await;
+        let user = auth.register(register_request()).await.unwrap();
+        let firefox = "Mozilla/5.0 (X11; Linux x86_64; rv:128.0) Gecko/20100101 Firefox/128.0";
+        async fn login(auth: &AuthService, ip: &str, agent: &str) -> Result<AuthResponse, AuthError> {
+            auth.login(login_request("Secret123"), Some(ip.to_string()), Some(agent.to_string()))
+                .await
+        }
+        let alerts = || async {
+            let bodies: Vec<String> =
+                sqlx::query_scalar("SELECT body FROM email_outbox WHERE kind = 'login_alert' ORDER BY created_at")
+                    .fetch_all(&auth.db)
+                    .await
+                    .unwrap();
+            bodies
+        };
+
+        // The first login has nothing to compare with; the same network and
+        // device next time are familiar
+        login(&auth, "198.51.100.1", firefox).await.unwrap();
+        login(&auth, "198.51.100.2", firefox).await.unwrap();
+        assert!(alerts().await.is_empty());
+
+        login(&auth, "203.0.113.5", firefox).await.unwrap();
+        let sent = alerts().await;
+        assert_eq!(sent.len(), 1);
+        assert!(sent[0].contains("Firefox on Linux in FR (AS3215)"));
+        let events = audit::for_user(&auth.db, user.id, Some(audit::SUSPICIOUS_LOGIN), 10).await.unwrap();
+        assert_eq!(events[0].details["anomalies"], serde_json::json!(["new_country", "new_network"]));
+
+        let strict = AuthService::new(
+            auth.db.clone(),
+            AuthConfig {
+                confirm_suspicious_logins: true,
+                ..auth.config.clone()
+            },
+        );
+        assert!(matches!(
+            login(&strict, "192.0.2.9", "curl/8.5.0").await,
+            Err(AuthError::LoginConfirmationRequired)
+        ));
+        let sent = alerts().await;
+        let token = sent[1].split("token=").nth(1).unwrap().split_whitespace().next().unwrap();
+
+        strict.confirm_login(token).await.unwrap();
+        assert!(matches!(strict.confirm_login(token).await, Err(AuthError::InvalidToken)));
+        login(&strict, "192.0.2.9", "curl/8.5.0").await.unwrap();
+        assert_eq!(alerts().await.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_password_expiry_and_history_on_sqlite() {
+        let auth = service().await;
+        let user = auth.register(register_request()).await.unwrap();
+
+        let login = auth.login(login_request("Secret123"), None, None).await.unwrap();
+        let expires_in = login.password_expires_in.unwrap();
+        assert!(expires_in > 89 * 86400 && expires_in <= 90 * 86400);
+
+        let change = |current: &str, new: &str| ChangePasswordRequest {
+            current_password: current.to_string(),
+            new_password: new.to_string(),
+            new_password_confirm: new.to_string(),
+        };
+        assert!(matches!(
+            auth.change_password(user.id, change("Secret123", "Secret123")).await,
+            Err(AuthError::PasswordReused)
+        ));
+        auth.change_password(user.id, change("Secret123", "Second123")).await.unwrap();
+        auth.change_password(user.id, change("Second123", "Third1234")).await.unwrap();
+        assert!(matches!(
+            auth.change_password(user.id, change("Third1234", "Secret123")).await,
+            Err(AuthError::PasswordReused)
+        ));
+        // Only the last three passwords are remembered
+        auth.change_password(user.id, change("Third1234", "Fourth123")).await.unwrap();
+        let remembered: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM auth_password_history")
+            .fetch_one(&auth.db)
+            .await
+            .unwrap();
+        assert_eq!(remembered, 2);
+        auth.change_password(user.id, change("Fourth123", "Secret123")).await.unwrap();
+
+        sqlx::query("UPDATE users SET password_changed_at = $1")
+            .bind(Utc::now() - Duration::days(91))
+            .execute(&auth.db)
+            .await
+            .unwrap();
+        assert!(matches!(
+            auth.login(login_request("Secret123"), None, None).await,
+            Err(AuthError::PasswordExpired)
+        ));
+
+        let expired = |current: &str, new: &str| ExpiredPasswordChangeRequest {
+            email: "ada@example.com".to_string(),
+            current_password: current.to_string(),
+            new_password: new.to_string(),
+            new_password_confirm: new.to_string(),
+        };
+        assert!(matches!(
+            auth.change_expired_password(expired("Wrong1234", "Fifth1234")).await,
+            Err(AuthError::InvalidCredentials)
+        ));
+        assert!(matches!(
+            auth.change_expired_password(expired("Secret123", "Fourth123")).await,
+            Err(AuthError::PasswordReused)
+        ));
+        auth.change_expired_password(expired("Secret123", "Fifth1234")).await.unwrap();
+        auth.login(login_request("Fifth1234"), None, None).await.unwrap();
+    }
+
+    struct CorpFilter;
+
+    #[async_trait::async_trait]
+    impl registration::RegistrationFilter for CorpFilter {
+        async fn filter(&self, mut registration: Registration) -> Result<Registration, String> {
+            match registration.domain().as_str() {
+                "corp.test" => registration.role = UserRole::Editor,
+                "contractors.corp.test" => return Err("Contractors are invited by an admin".to_string()),
+                _ => {}
+            }
+            registration.email = registration.email.to_lowercase();
+            Ok(registration)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_registration_policy_and_filters_on_sqlite() {
+        let auth = service().await;
+        let request = |email: &str| RegisterRequest {
+            email: email.to_string(),
+            ..register_request()
+        };
+
+        assert!(matches!(
+            auth.register(request("ada@mailinator.com")).await,
+            Err(AuthError::RegistrationRejected(_))
+        ));
+
+        registration::install_filter("corp", Some(Arc::new(CorpFilter)));
+        let user = auth.register(request("Grace@CORP.test")).await.unwrap();
+        assert_eq!(user.email, "grace@corp.test");
+        assert_eq!(user.role, UserRole::Editor);
+        assert!(matches!(
+            auth.register(request("bob@contractors.corp.test")).await,
+            Err(AuthError::RegistrationRejected(reason)) if reason.contains("invited")
+        ));
+        registration::install_filter("corp", None);
+
+        let strict = AuthService::new(
+            auth.db.clone(),
+            AuthConfig {
+                registration: registration::RegistrationPolicy {
+                    allowed_domains: vec!["example.com".to_string()],
+                    ..Default::default()
+                },
+                ..auth.config.clone()
+            },
+        );
+        assert!(matches!(
+            strict.register(request("linus@corp.test")).await,
+            Err(AuthError::RegistrationRejected(_))
+        ));
+        strict.register(register_request()).await.unwrap();
+    }
+}
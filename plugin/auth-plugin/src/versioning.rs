@@ -0,0 +1,343 @@
+//! API Versioning
+//!
+//! `VersionedRouter` mounts routes under `/api/v1`, `/api/v2`, ... so a route
+//! registered once is served as an alias in every version, while routes that
+//! change can be registered per version. Deprecation metadata attached to a
+//! route or a whole version is emitted as `Deprecation`, `Sunset` and `Link`
+//! response headers. Handlers read the requested version with the
+//! `ApiVersion` extractor (see `compat` for reshaping payloads).
+
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Request},
+    http::{header, request::Parts, HeaderMap, HeaderValue},
+    middleware::{self, Next},
+    response::Response,
+    routing::MethodRouter,
+    Extension, Router,
+};
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::fmt;
+
+/// Published API versions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ApiVersion {
+    V1,
+    V2,
+}
+
+impl ApiVersion {
+    /// Every version, oldest first
+    pub const ALL: [ApiVersion; 2] = [ApiVersion::V1, ApiVersion::V2];
+
+    /// Newest version; handlers build this shape and downgrade for older clients
+    pub const LATEST: ApiVersion = ApiVersion::V2;
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApiVersion::V1 => "v1",
+            ApiVersion::V2 => "v2",
+        }
+    }
+
+    /// Path prefix the version is mounted at
+    pub fn prefix(&self) -> String {
+        format!("/api/{}", self.as_str())
+    }
+}
+
+impl fmt::Display for ApiVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Requested API version, set by `VersionedRouter`
+///
+/// Requests that did not go through a versioned router are treated as v1.
+#[async_trait]
+impl<S> FromRequestParts<S> for ApiVersion
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(parts
+            .extensions
+            .get::<ApiVersion>()
+            .copied()
+            .unwrap_or(ApiVersion::V1))
+    }
+}
+
+/// Deprecation metadata for a route or version
+#[derive(Debug, Clone, PartialEq)]
+pub struct Deprecation {
+    /// When the route was deprecated
+    pub since: DateTime<Utc>,
+    /// When the route stops being served
+    pub sunset: Option<DateTime<Utc>>,
+    /// Migration guide
+    pub link: Option<String>,
+}
+
+impl Deprecation {
+    pub fn since(since: DateTime<Utc>) -> Self {
+        Self {
+            since,
+            sunset: None,
+            link: None,
+        }
+    }
+
+    pub fn sunset(mut self, sunset: DateTime<Utc>) -> Self {
+        self.sunset = Some(sunset);
+        self
+    }
+
+    pub fn link(mut self, link: impl Into<String>) -> Self {
+        self.link = Some(link.into());
+        self
+    }
+
+    /// Add the headers unless a more specific deprecation already set them
+    ///
+    /// `Deprecation` uses the RFC 9745 structured date (`@<unix seconds>`),
+    /// `Sunset` the RFC 8594 HTTP-date.
+    pub fn apply(&self, headers: &mut HeaderMap) {
+        if headers.contains_key("deprecation") {
+            return;
+        }
+
+        if let Ok(value) = HeaderValue::from_str(&format!("@{}", self.since.timestamp())) {
+            headers.insert("deprecation", value);
+        }
+        if let Some(sunset) = self.sunset {
+            let date = sunset.format("%a, %d %b %Y %H:%M:%S GMT").to_string();
+            if let Ok(value) = HeaderValue::from_str(&date) {
+                headers.insert("sunset", value);
+            }
+        }
+        if let Some(ref link) = self.link {
+            if let Ok(value) = HeaderValue::from_str(&format!("<{}>; rel=\"deprecation\"", link)) {
+                headers.append(header::LINK, value);
+            }
+        }
+    }
+}
+
+struct VersionedRoute<S> {
+    path: String,
+    versions: Vec<ApiVersion>,
+    method_router: MethodRouter<S>,
+    deprecations: HashMap<ApiVersion, Deprecation>,
+}
+
+/// Router builder that mounts routes under every API version prefix
+pub struct VersionedRouter<S = ()> {
+    routes: Vec<VersionedRoute<S>>,
+    merged: Vec<(Vec<ApiVersion>, Router<S>)>,
+    version_deprecations: HashMap<ApiVersion, Deprecation>,
+}
+
+impl<S> Default for VersionedRouter<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S> VersionedRouter<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    pub fn new() -> Self {
+        Self {
+            routes: Vec::new(),
+            merged: Vec::new(),
+            version_deprecations: HashMap::new(),
+        }
+    }
+
+    /// Serve a route in every version
+    pub fn route(self, path: &str, method_router: MethodRouter<S>) -> Self {
+        self.route_versions(&ApiVersion::ALL, path, method_router)
+    }
+
+    /// Serve a route only in the given versions (e.g. a v2 rewrite)
+    pub fn route_versions(mut self, versions: &[ApiVersion], path: &str, method_router: MethodRouter<S>) -> Self {
+        self.routes.push(VersionedRoute {
+            path: path.to_string(),
+            versions: versions.to_vec(),
+            method_router,
+            deprecations: HashMap::new(),
+        });
+        self
+    }
+
+    /// Serve every route of an existing router in every version
+    pub fn merge(mut self, router: Router<S>) -> Self {
+        self.merged.push((ApiVersion::ALL.to_vec(), router));
+        self
+    }
+
+    /// Mark a route as deprecated in one version
+    pub fn deprecate_route(mut self, version: ApiVersion, path: &str, deprecation: Deprecation) -> Self {
+        for route in self.routes.iter_mut().filter(|r| r.path == path) {
+            route.deprecations.insert(version, deprecation.clone());
+        }
+        self
+    }
+
+    /// Mark every route of a version as deprecated
+    pub fn deprecate_version(mut self, version: ApiVersion, deprecation: Deprecation) -> Self {
+        self.version_deprecations.insert(version, deprecation);
+        self
+    }
+
+    /// Build the router with each version nested under its prefix
+    pub fn into_router(self) -> Router<S> {
+        let mut router = Router::new();
+
+        for version in ApiVersion::ALL {
+            let mut versioned = Router::new();
+            let mut has_routes = false;
+
+            for route in self.routes.iter().filter(|r| r.versions.contains(&version)) {
+                let mut method_router = route.method_router.clone();
+                if let Some(deprecation) = route.deprecations.get(&version).cloned() {
+                    method_router = method_router.layer(middleware::from_fn(move |req: Request, next: Next| {
+                        let deprecation = deprecation.clone();
+                        async move { deprecated(deprecation, req, next).await }
+                    }));
+                }
+                versioned = versioned.route(&route.path, method_router);
+                has_routes = true;
+            }
+
+            for (versions, merged) in &self.merged {
+                if versions.contains(&version) {
+                    versioned = versioned.merge(merged.clone());
+                    has_routes = true;
+                }
+            }
+
+            if !has_routes {
+                continue;
+            }
+
+            if let Some(deprecation) = self.version_deprecations.get(&version).cloned() {
+                versioned = versioned.layer(middleware::from_fn(move |req: Request, next: Next| {
+                    let deprecation = deprecation.clone();
+                    async move { deprecated(deprecation, req, next).await }
+                }));
+            }
+
+            router = router.nest(&version.prefix(), versioned.layer(Extension(version)));
+        }
+
+        router
+    }
+}
+
+async fn deprecated(deprecation: Deprecation, req: Request, next: Next) -> Response {
+    let mut response = next.run(req).await;
+    deprecation.apply(response.headers_mut());
+    response
+}
+
+// ============================================
+// Tests
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::StatusCode, routing::get};
+    use chrono::TimeZone;
+    use tower::ServiceExt;
+
+    async fn version(version: ApiVersion) -> String {
+        version.to_string()
+    }
+
+    async fn get_response(router: Router, uri: &str) -> Response {
+        router
+            .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+    }
+
+    async fn body_text(response: Response) -> String {
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_routes_are_aliased_in_every_version() {
+        let router = VersionedRouter::new().route("/version", get(version)).into_router();
+
+        let v1 = get_response(router.clone(), "/api/v1/version").await;
+        assert_eq!(body_text(v1).await, "v1");
+
+        let v2 = get_response(router, "/api/v2/version").await;
+        assert_eq!(body_text(v2).await, "v2");
+    }
+
+    #[tokio::test]
+    async fn test_version_specific_routes() {
+        let router = VersionedRouter::new()
+            .route_versions(&[ApiVersion::V2], "/new", get(version))
+            .into_router();
+
+        assert_eq!(get_response(router.clone(), "/api/v1/new").await.status(), StatusCode::NOT_FOUND);
+        assert_eq!(get_response(router, "/api/v2/new").await.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_route_deprecation_headers() {
+        let since = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let sunset = Utc.with_ymd_and_hms(2024, 12, 31, 23, 59, 59).unwrap();
+
+        let router = VersionedRouter::new()
+            .route("/old", get(version))
+            .deprecate_route(
+                ApiVersion::V1,
+                "/old",
+                Deprecation::since(since).sunset(sunset).link("https://example.com/migrate"),
+            )
+            .into_router();
+
+        let v1 = get_response(router.clone(), "/api/v1/old").await;
+        assert_eq!(v1.headers()["deprecation"], "@1704067200");
+        assert_eq!(v1.headers()["sunset"], "Tue, 31 Dec 2024 23:59:59 GMT");
+        assert_eq!(v1.headers()["link"], "<https://example.com/migrate>; rel=\"deprecation\"");
+
+        let v2 = get_response(router, "/api/v2/old").await;
+        assert!(!v2.headers().contains_key("deprecation"));
+    }
+
+    #[tokio::test]
+    async fn test_route_deprecation_overrides_version_deprecation() {
+        let version_since = Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap();
+        let route_since = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+
+        let router = VersionedRouter::new()
+            .route("/a", get(version))
+            .route("/b", get(version))
+            .deprecate_version(ApiVersion::V1, Deprecation::since(version_since))
+            .deprecate_route(ApiVersion::V1, "/b", Deprecation::since(route_since))
+            .into_router();
+
+        let a = get_response(router.clone(), "/api/v1/a").await;
+        assert_eq!(a.headers()["deprecation"], "@1717200000");
+
+        let b = get_response(router, "/api/v1/b").await;
+        assert_eq!(b.headers()["deprecation"], "@1704067200");
+    }
+}
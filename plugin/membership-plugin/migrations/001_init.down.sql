@@ -0,0 +1,10 @@
+-- Removing the plugin makes its posts public again
+UPDATE blog_posts SET required_level = NULL
+WHERE id IN (SELECT post_id FROM membership_post_tiers);
+
+DELETE FROM user_meta WHERE meta_key IN ('membership_level', 'membership_expires_at');
+
+DROP TABLE IF EXISTS membership_post_tiers;
+DROP TABLE IF EXISTS membership_members;
+DROP TYPE IF EXISTS membership_status;
+DROP TABLE IF EXISTS membership_tiers;
@@ -0,0 +1,48 @@
+-- RustPress Membership - Initial Schema
+--
+-- Tiers are ordered by `level`; a member of a tier can read every post whose
+-- required level is at or below it. The blog reads two things written here:
+-- `blog_posts.required_level` for gated posts, and the `membership_level` /
+-- `membership_expires_at` user meta entries for each active member.
+
+CREATE TABLE IF NOT EXISTS membership_tiers (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    name VARCHAR(100) NOT NULL,
+    slug VARCHAR(120) NOT NULL UNIQUE,
+    level INTEGER NOT NULL UNIQUE CHECK (level > 0),
+    description TEXT,
+    -- Informational; payment is handled elsewhere
+    price_cents BIGINT CHECK (price_cents >= 0),
+    active BOOLEAN NOT NULL DEFAULT true,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+DO $$ BEGIN
+    CREATE TYPE membership_status AS ENUM ('active', 'cancelled', 'expired');
+EXCEPTION
+    WHEN duplicate_object THEN null;
+END $$;
+
+-- One membership per user; changing tier replaces it
+CREATE TABLE IF NOT EXISTS membership_members (
+    user_id UUID PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    tier_id UUID NOT NULL REFERENCES membership_tiers(id) ON DELETE RESTRICT,
+    status membership_status NOT NULL DEFAULT 'active',
+    started_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    -- NULL for memberships that don't lapse
+    expires_at TIMESTAMPTZ,
+    granted_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_membership_members_expiry
+    ON membership_members(expires_at) WHERE status = 'active';
+
+-- Which tier a post was assigned to, so a tier's new level reaches its posts
+CREATE TABLE IF NOT EXISTS membership_post_tiers (
+    post_id UUID PRIMARY KEY REFERENCES blog_posts(id) ON DELETE CASCADE,
+    tier_id UUID NOT NULL REFERENCES membership_tiers(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_membership_post_tiers_tier ON membership_post_tiers(tier_id);
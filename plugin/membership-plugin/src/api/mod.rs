@@ -0,0 +1,401 @@
+//! Membership REST API Handlers
+
+use crate::models::*;
+use crate::services::*;
+use crate::MembershipPlugin;
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::Response,
+    routing::{get, put},
+    Json,
+};
+use rustpress_auth::problem::ProblemDetails;
+use rustpress_auth::routes::PluginRoutes;
+use rustpress_auth::{AuthUser, ValidatedJson};
+use std::sync::Arc;
+use utoipa::OpenApi;
+use uuid::Uuid;
+
+/// Create API routes, mounted under `/api/v1/rustpress-membership`
+pub fn create_routes(plugin: &MembershipPlugin) -> PluginRoutes {
+    PluginRoutes::new(plugin.info.id.clone())
+        // Tiers
+        .route("/tiers", get(list_tiers).post(create_tier))
+        .route("/tiers/:id", put(update_tier).delete(delete_tier))
+        // Members
+        .route("/me", get(get_my_membership))
+        .route("/members", get(list_members))
+        .route("/members/:user_id", get(get_member).put(grant_membership).delete(revoke_membership))
+        // Posts
+        .route("/posts/:post_id/tier", get(get_post_tier).put(set_post_tier))
+        // API documentation
+        .route("/openapi.json", get(|| async { Json(ApiDoc::openapi()) }))
+        .route("/docs", get(swagger_ui))
+        .layer(axum::middleware::from_fn(rustpress_auth::problem::trace_id))
+}
+
+// ============================================
+// API Documentation
+// ============================================
+
+/// Membership API specification, generated from the handler annotations
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "RustPress Membership API"),
+    servers((url = "/api/v1/rustpress-membership")),
+    paths(
+        list_tiers,
+        create_tier,
+        update_tier,
+        delete_tier,
+        get_my_membership,
+        list_members,
+        get_member,
+        grant_membership,
+        revoke_membership,
+        get_post_tier,
+        set_post_tier,
+    ),
+    tags(
+        (name = "tiers", description = "Subscription tiers"),
+        (name = "members", description = "Memberships"),
+        (name = "posts", description = "Members-only posts"),
+    )
+)]
+pub struct ApiDoc;
+
+/// GET /api/v1/rustpress-membership/docs
+async fn swagger_ui() -> Response {
+    rustpress_auth::openapi::swagger_ui("openapi.json")
+}
+
+// ============================================
+// Tier Endpoints
+// ============================================
+
+/// GET /api/v1/rustpress-membership/tiers
+#[utoipa::path(
+    get,
+    path = "/tiers",
+    tag = "tiers",
+    params(TierQuery),
+    responses(
+        (status = 200, description = "Tiers by level", body = ListResponse<Tier>),
+        (status = 403, description = "Retired tiers requested by a non-admin", body = ProblemDetails),
+    ),
+)]
+pub async fn list_tiers(
+    State(plugin): State<Arc<MembershipPlugin>>,
+    user: Option<AuthUser>,
+    Query(query): Query<TierQuery>,
+) -> Result<Json<ListResponse<Tier>>, ProblemDetails> {
+    let include_inactive = query.include_inactive.unwrap_or(false);
+    if include_inactive {
+        require_admin(user.as_ref())?;
+    }
+
+    let tiers = tier_service(&plugin).await?.list(include_inactive).await?;
+
+    Ok(Json(ListResponse {
+        count: Some(tiers.len()),
+        data: tiers,
+    }))
+}
+
+/// POST /api/v1/rustpress-membership/tiers
+#[utoipa::path(
+    post,
+    path = "/tiers",
+    tag = "tiers",
+    request_body = TierInput,
+    responses(
+        (status = 201, description = "Tier created", body = Tier),
+        (status = 403, description = "Not an admin", body = ProblemDetails),
+        (status = 409, description = "Name or level already in use", body = ProblemDetails),
+        (status = 422, description = "Invalid tier", body = ProblemDetails),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn create_tier(
+    State(plugin): State<Arc<MembershipPlugin>>,
+    user: AuthUser,
+    ValidatedJson(input): ValidatedJson<TierInput>,
+) -> Result<(StatusCode, Json<Tier>), ProblemDetails> {
+    require_admin(Some(&user))?;
+
+    let tier = tier_service(&plugin).await?.create(&input).await?;
+    Ok((StatusCode::CREATED, Json(tier)))
+}
+
+/// PUT /api/v1/rustpress-membership/tiers/{id}
+///
+/// A changed level applies to the tier's members and posts at once.
+#[utoipa::path(
+    put,
+    path = "/tiers/{id}",
+    tag = "tiers",
+    params(("id" = Uuid, Path, description = "Tier ID")),
+    request_body = TierInput,
+    responses(
+        (status = 200, description = "Tier updated", body = Tier),
+        (status = 403, description = "Not an admin", body = ProblemDetails),
+        (status = 404, description = "Tier not found", body = ProblemDetails),
+        (status = 409, description = "Level already in use", body = ProblemDetails),
+        (status = 422, description = "Invalid tier", body = ProblemDetails),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn update_tier(
+    State(plugin): State<Arc<MembershipPlugin>>,
+    user: AuthUser,
+    Path(id): Path<Uuid>,
+    ValidatedJson(input): ValidatedJson<TierInput>,
+) -> Result<Json<Tier>, ProblemDetails> {
+    require_admin(Some(&user))?;
+
+    Ok(Json(tier_service(&plugin).await?.update(id, &input).await?))
+}
+
+/// DELETE /api/v1/rustpress-membership/tiers/{id}
+#[utoipa::path(
+    delete,
+    path = "/tiers/{id}",
+    tag = "tiers",
+    params(("id" = Uuid, Path, description = "Tier ID")),
+    responses(
+        (status = 204, description = "Tier deleted; its posts are public again"),
+        (status = 403, description = "Not an admin", body = ProblemDetails),
+        (status = 404, description = "Tier not found", body = ProblemDetails),
+        (status = 409, description = "Tier still has members", body = ProblemDetails),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn delete_tier(
+    State(plugin): State<Arc<MembershipPlugin>>,
+    user: AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<StatusCode, ProblemDetails> {
+    require_admin(Some(&user))?;
+
+    tier_service(&plugin).await?.delete(id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// ============================================
+// Member Endpoints
+// ============================================
+
+/// GET /api/v1/rustpress-membership/me
+#[utoipa::path(
+    get,
+    path = "/me",
+    tag = "members",
+    responses(
+        (status = 200, description = "The current user's membership", body = Membership),
+        (status = 404, description = "Not a member", body = ProblemDetails),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn get_my_membership(
+    State(plugin): State<Arc<MembershipPlugin>>,
+    user: AuthUser,
+) -> Result<Json<Membership>, ProblemDetails> {
+    Ok(Json(member_service(&plugin).await?.get(user.id).await?))
+}
+
+/// GET /api/v1/rustpress-membership/members
+#[utoipa::path(
+    get,
+    path = "/members",
+    tag = "members",
+    params(MemberQuery),
+    responses(
+        (status = 200, description = "Memberships, newest first", body = ListResponse<Membership>),
+        (status = 403, description = "Not an admin", body = ProblemDetails),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn list_members(
+    State(plugin): State<Arc<MembershipPlugin>>,
+    user: AuthUser,
+    Query(query): Query<MemberQuery>,
+) -> Result<Json<ListResponse<Membership>>, ProblemDetails> {
+    require_admin(Some(&user))?;
+
+    let members = member_service(&plugin).await?.list(&query).await?;
+
+    Ok(Json(ListResponse {
+        count: Some(members.len()),
+        data: members,
+    }))
+}
+
+/// GET /api/v1/rustpress-membership/members/{user_id}
+#[utoipa::path(
+    get,
+    path = "/members/{user_id}",
+    tag = "members",
+    params(("user_id" = Uuid, Path, description = "User ID")),
+    responses(
+        (status = 200, description = "The user's membership", body = Membership),
+        (status = 403, description = "Not an admin", body = ProblemDetails),
+        (status = 404, description = "Not a member", body = ProblemDetails),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn get_member(
+    State(plugin): State<Arc<MembershipPlugin>>,
+    user: AuthUser,
+    Path(user_id): Path<Uuid>,
+) -> Result<Json<Membership>, ProblemDetails> {
+    require_admin(Some(&user))?;
+
+    Ok(Json(member_service(&plugin).await?.get(user_id).await?))
+}
+
+/// PUT /api/v1/rustpress-membership/members/{user_id}
+///
+/// Grants the tier, replacing the user's current membership if any.
+#[utoipa::path(
+    put,
+    path = "/members/{user_id}",
+    tag = "members",
+    params(("user_id" = Uuid, Path, description = "User ID")),
+    request_body = GrantInput,
+    responses(
+        (status = 200, description = "Membership granted", body = Membership),
+        (status = 403, description = "Not an admin", body = ProblemDetails),
+        (status = 404, description = "User or tier not found", body = ProblemDetails),
+        (status = 409, description = "Tier is retired", body = ProblemDetails),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn grant_membership(
+    State(plugin): State<Arc<MembershipPlugin>>,
+    user: AuthUser,
+    Path(user_id): Path<Uuid>,
+    Json(input): Json<GrantInput>,
+) -> Result<Json<Membership>, ProblemDetails> {
+    require_admin(Some(&user))?;
+
+    Ok(Json(member_service(&plugin).await?.grant(user_id, &input, user.id).await?))
+}
+
+/// DELETE /api/v1/rustpress-membership/members/{user_id}
+#[utoipa::path(
+    delete,
+    path = "/members/{user_id}",
+    tag = "members",
+    params(("user_id" = Uuid, Path, description = "User ID")),
+    responses(
+        (status = 200, description = "Membership cancelled", body = Membership),
+        (status = 403, description = "Not an admin", body = ProblemDetails),
+        (status = 404, description = "No active membership", body = ProblemDetails),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn revoke_membership(
+    State(plugin): State<Arc<MembershipPlugin>>,
+    user: AuthUser,
+    Path(user_id): Path<Uuid>,
+) -> Result<Json<Membership>, ProblemDetails> {
+    require_admin(Some(&user))?;
+
+    Ok(Json(member_service(&plugin).await?.revoke(user_id, user.id).await?))
+}
+
+// ============================================
+// Post Endpoints
+// ============================================
+
+/// GET /api/v1/rustpress-membership/posts/{post_id}/tier
+#[utoipa::path(
+    get,
+    path = "/posts/{post_id}/tier",
+    tag = "posts",
+    params(("post_id" = Uuid, Path, description = "Post ID")),
+    responses(
+        (status = 200, description = "Tier needed to read the post; null when public", body = PostAccess),
+    ),
+)]
+pub async fn get_post_tier(
+    State(plugin): State<Arc<MembershipPlugin>>,
+    Path(post_id): Path<Uuid>,
+) -> Result<Json<PostAccess>, ProblemDetails> {
+    Ok(Json(post_access_service(&plugin).await?.get(post_id).await?))
+}
+
+/// PUT /api/v1/rustpress-membership/posts/{post_id}/tier
+#[utoipa::path(
+    put,
+    path = "/posts/{post_id}/tier",
+    tag = "posts",
+    params(("post_id" = Uuid, Path, description = "Post ID")),
+    request_body = PostTierInput,
+    responses(
+        (status = 200, description = "Post access updated", body = PostAccess),
+        (status = 403, description = "Not an editor", body = ProblemDetails),
+        (status = 404, description = "Post or tier not found", body = ProblemDetails),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn set_post_tier(
+    State(plugin): State<Arc<MembershipPlugin>>,
+    user: AuthUser,
+    Path(post_id): Path<Uuid>,
+    Json(input): Json<PostTierInput>,
+) -> Result<Json<PostAccess>, ProblemDetails> {
+    if !user.can_moderate() {
+        return Err(ProblemDetails::forbidden("Changing post access requires the editor role"));
+    }
+
+    Ok(Json(post_access_service(&plugin).await?.set_tier(post_id, input.tier_id).await?))
+}
+
+// ============================================
+// Helpers
+// ============================================
+
+impl From<MembershipError> for ProblemDetails {
+    fn from(e: MembershipError) -> Self {
+        match e {
+            MembershipError::NotFound(msg) => ProblemDetails::not_found(msg),
+            MembershipError::Conflict(msg) => ProblemDetails::new(StatusCode::CONFLICT, "conflict").detail(msg),
+            MembershipError::Database(msg) => {
+                tracing::error!("Membership database error: {}", msg);
+                ProblemDetails::internal("Database error")
+            }
+        }
+    }
+}
+
+/// Tiers and memberships are managed by admins
+fn require_admin(user: Option<&AuthUser>) -> Result<(), ProblemDetails> {
+    match user {
+        Some(user) if user.is_admin() => Ok(()),
+        Some(_) => Err(ProblemDetails::forbidden("Membership management requires the admin role")),
+        None => Err(ProblemDetails::unauthorized("Authentication required")),
+    }
+}
+
+async fn tier_service(plugin: &MembershipPlugin) -> Result<Arc<TierService>, ProblemDetails> {
+    plugin
+        .tiers()
+        .await
+        .ok_or_else(|| ProblemDetails::unavailable("Tier service unavailable"))
+}
+
+async fn member_service(plugin: &MembershipPlugin) -> Result<Arc<MemberService>, ProblemDetails> {
+    plugin
+        .members()
+        .await
+        .ok_or_else(|| ProblemDetails::unavailable("Member service unavailable"))
+}
+
+async fn post_access_service(plugin: &MembershipPlugin) -> Result<Arc<PostAccessService>, ProblemDetails> {
+    plugin
+        .post_access()
+        .await
+        .ok_or_else(|| ProblemDetails::unavailable("Post access service unavailable"))
+}
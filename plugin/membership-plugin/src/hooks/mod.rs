@@ -0,0 +1,108 @@
+//! Membership Hook Handlers
+
+use crate::MembershipPlugin;
+use rustpress_plugins::prelude::*;
+use std::sync::Arc;
+
+/// Truncate members-only content for readers below the post's level
+///
+/// Registered on the blog's `members_only_content` filter, which only runs
+/// for posts the reader can't view. Keeps the first `teaser_paragraphs`
+/// paragraphs (or `teaser_words` words for posts without paragraphs) and
+/// appends the sign-up prompt.
+pub async fn members_only_teaser(
+    ctx: FilterContext,
+    plugin: Arc<MembershipPlugin>,
+    content: String,
+) -> Result<String, HookError> {
+    let config = plugin.config().await;
+
+    let teaser = teaser(&content, config.teaser_paragraphs, config.teaser_words);
+
+    Ok(format!(
+        r#"{}
+<div class="rp-paywall">
+    <p>{}</p>
+    <a class="rp-paywall-join" href="{}">{}</a>
+</div>"#,
+        teaser,
+        escape(&config.paywall_message),
+        escape(&config.join_url),
+        escape(&config.join_label),
+    ))
+}
+
+/// Cron job: Expire lapsed memberships
+pub async fn expire_memberships(ctx: CronContext, plugin: Arc<MembershipPlugin>) -> Result<(), HookError> {
+    let Some(members) = plugin.members().await else {
+        return Ok(());
+    };
+
+    let expired = members
+        .expire_due()
+        .await
+        .map_err(|e| HookError::Database(e.to_string()))?;
+
+    tracing::info!("Expired {} memberships", expired);
+    Ok(())
+}
+
+/// The opening of `html`: its first `paragraphs` paragraphs when the post
+/// has more than that, otherwise its first `words` words as plain text
+fn teaser(html: &str, paragraphs: usize, words: usize) -> String {
+    let ends: Vec<usize> = html.match_indices("</p>").map(|(i, _)| i + "</p>".len()).collect();
+    if paragraphs > 0 && ends.len() > paragraphs {
+        return html[..ends[paragraphs - 1]].to_string();
+    }
+
+    let mut text = String::with_capacity(html.len());
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => {
+                in_tag = false;
+                text.push(' ');
+            }
+            _ if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+
+    let all: Vec<&str> = text.split_whitespace().collect();
+    // Never give away a short post in full
+    let keep = words.min(all.len() / 2);
+    format!("<p>{}…</p>", all[..keep].join(" "))
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_teaser_keeps_leading_paragraphs() {
+        let html = "<p>One.</p><p>Two.</p><p>Three.</p>";
+        assert_eq!(teaser(html, 2, 55), "<p>One.</p><p>Two.</p>");
+    }
+
+    #[test]
+    fn test_teaser_never_returns_whole_post() {
+        // Not more paragraphs than the teaser length: fall back to words
+        let html = "<p>alpha beta gamma delta</p><p>epsilon zeta</p>";
+        assert_eq!(teaser(html, 2, 55), "<p>alpha beta gamma…</p>");
+
+        assert_eq!(teaser("one two three four five six", 2, 2), "<p>one two…</p>");
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape(r#"<a href="x">&"#), "&lt;a href=&quot;x&quot;&gt;&amp;");
+    }
+}
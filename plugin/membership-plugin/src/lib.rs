@@ -0,0 +1,217 @@
+//! RustPress Membership Plugin
+//!
+//! Members-only content for the blog:
+//! - Subscription tiers ordered by level
+//! - Granting, revoking and expiring memberships
+//! - Marking posts members-only by tier
+//! - Teasers with a sign-up prompt for everyone else
+//!
+//! The blog enforces access itself (its `access` module); this plugin owns
+//! the tiers and tells the blog each post's required level and each member's
+//! level.
+
+pub mod api;
+pub mod hooks;
+pub mod models;
+pub mod services;
+
+use async_trait::async_trait;
+use rustpress_auth::migrations::PluginMigrations;
+use rustpress_plugins::prelude::*;
+use services::{MemberService, PostAccessService, TierService};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+// ============================================
+// Plugin Configuration
+// ============================================
+
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct MembershipConfig {
+    /// Paragraphs of a members-only post shown to everyone
+    pub teaser_paragraphs: usize,
+    /// Word limit for posts without paragraph markup
+    pub teaser_words: usize,
+    pub paywall_message: String,
+    pub join_label: String,
+    /// Where the sign-up button points
+    pub join_url: String,
+}
+
+impl Default for MembershipConfig {
+    fn default() -> Self {
+        Self {
+            teaser_paragraphs: 2,
+            teaser_words: 55,
+            paywall_message: "The rest of this post is for members.".into(),
+            join_label: "Become a member".into(),
+            join_url: "/membership".into(),
+        }
+    }
+}
+
+// ============================================
+// Main Plugin Struct
+// ============================================
+
+pub struct MembershipPlugin {
+    info: PluginInfo,
+    state: RwLock<PluginState>,
+    config: RwLock<MembershipConfig>,
+    tier_service: RwLock<Option<Arc<TierService>>>,
+    member_service: RwLock<Option<Arc<MemberService>>>,
+    post_access_service: RwLock<Option<Arc<PostAccessService>>>,
+}
+
+impl MembershipPlugin {
+    /// Schema migrations shipped with the plugin (`migrations/`)
+    pub fn migrations() -> PluginMigrations {
+        PluginMigrations::new("rustpress-membership", sqlx::migrate!("./migrations"))
+    }
+
+    pub fn new() -> Self {
+        Self {
+            info: PluginInfo {
+                id: "rustpress-membership".into(),
+                name: "RustPress Membership".into(),
+                version: "1.0.0".into(),
+                needs: vec![Capability::Db, Capability::Cron],
+            },
+            state: RwLock::new(PluginState::Inactive),
+            config: RwLock::new(MembershipConfig::default()),
+            tier_service: RwLock::new(None),
+            member_service: RwLock::new(None),
+            post_access_service: RwLock::new(None),
+        }
+    }
+
+    pub async fn config(&self) -> MembershipConfig {
+        self.config.read().await.clone()
+    }
+
+    pub async fn tiers(&self) -> Option<Arc<TierService>> {
+        self.tier_service.read().await.clone()
+    }
+
+    pub async fn members(&self) -> Option<Arc<MemberService>> {
+        self.member_service.read().await.clone()
+    }
+
+    pub async fn post_access(&self) -> Option<Arc<PostAccessService>> {
+        self.post_access_service.read().await.clone()
+    }
+
+    async fn load_config(&self, settings: &SettingsManager) -> Result<MembershipConfig, HookError> {
+        let mut config = MembershipConfig::default();
+
+        if let Some(v) = settings.get::<usize>("rustpress-membership", "teaser_paragraphs").await? {
+            config.teaser_paragraphs = v;
+        }
+        if let Some(v) = settings.get::<usize>("rustpress-membership", "teaser_words").await? {
+            config.teaser_words = v;
+        }
+        if let Some(v) = settings.get("rustpress-membership", "paywall_message").await? {
+            config.paywall_message = v;
+        }
+        if let Some(v) = settings.get("rustpress-membership", "join_label").await? {
+            config.join_label = v;
+        }
+        if let Some(v) = settings.get("rustpress-membership", "join_url").await? {
+            config.join_url = v;
+        }
+
+        Ok(config)
+    }
+}
+
+impl Default for MembershipPlugin {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// ============================================
+// Lifecycle Implementation
+// ============================================
+
+#[async_trait]
+impl LifecycleHook for MembershipPlugin {
+    async fn on_activate(&self, ctx: &ActivationContext) -> Result<(), HookError> {
+        tracing::info!("Activating RustPress Membership plugin");
+
+        // Run migrations
+        Self::migrations()
+            .up(&ctx.db, None, false)
+            .await
+            .map_err(|e| HookError::Migration(e.to_string()))?;
+
+        // Load configuration
+        let config = self.load_config(&ctx.settings).await?;
+        *self.config.write().await = config;
+
+        // Initialize services
+        *self.tier_service.write().await = Some(Arc::new(TierService::new(ctx.db.clone())));
+        *self.member_service.write().await = Some(Arc::new(MemberService::new(ctx.db.clone())));
+        *self.post_access_service.write().await = Some(Arc::new(PostAccessService::new(ctx.db.clone())));
+
+        // Register routes under /api/v1/<plugin-id>; fails when another
+        // plugin or the app already serves one of the paths
+        let routes = rustpress_auth::routes::shared()
+            .mount(api::create_routes(self))
+            .map_err(|e| HookError::InvalidData(e.to_string()))?;
+        ctx.register_routes(routes).await?;
+
+        *self.state.write().await = PluginState::Active;
+        tracing::info!("RustPress Membership activated successfully");
+        Ok(())
+    }
+
+    async fn on_deactivate(&self, ctx: &DeactivationContext) -> Result<(), HookError> {
+        tracing::info!("Deactivating RustPress Membership");
+
+        // Members-only posts stay gated; the blog falls back to its own teaser
+        *self.tier_service.write().await = None;
+        *self.member_service.write().await = None;
+        *self.post_access_service.write().await = None;
+
+        // Unregister routes
+        rustpress_auth::routes::shared().unregister(&self.info.id);
+        ctx.unregister_routes().await?;
+
+        *self.state.write().await = PluginState::Inactive;
+        Ok(())
+    }
+
+    async fn on_upgrade(&self, ctx: &UpgradeContext) -> Result<(), HookError> {
+        tracing::info!("Upgrading Membership from {} to {}", ctx.from_version, ctx.to_version);
+
+        Self::migrations()
+            .up(&ctx.db, None, false)
+            .await
+            .map_err(|e| HookError::Migration(e.to_string()))?;
+        Ok(())
+    }
+
+    async fn on_uninstall(&self, ctx: &UninstallContext) -> Result<(), HookError> {
+        tracing::info!("Uninstalling RustPress Membership");
+
+        // Reverting the migrations also makes gated posts public again and
+        // removes the membership user meta
+        Self::migrations()
+            .down(&ctx.db, 0, false)
+            .await
+            .map_err(|e| HookError::Migration(e.to_string()))?;
+
+        // Remove settings
+        ctx.settings.remove_all("rustpress-membership").await?;
+
+        tracing::info!("RustPress Membership uninstalled");
+        Ok(())
+    }
+}
+
+// ============================================
+// Plugin Entry Point
+// ============================================
+
+rustpress_plugin!(MembershipPlugin);
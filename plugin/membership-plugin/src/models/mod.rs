@@ -0,0 +1,118 @@
+//! Membership Data Models
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+use validator::Validate;
+
+// ============================================
+// Tiers
+// ============================================
+
+/// Subscription tier; higher levels include everything below them
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct Tier {
+    pub id: Uuid,
+    pub name: String,
+    pub slug: String,
+    pub level: i32,
+    pub description: Option<String>,
+    pub price_cents: Option<i64>,
+    pub active: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Deserialize, Validate, ToSchema)]
+pub struct TierInput {
+    #[validate(length(min = 1, max = 100))]
+    pub name: String,
+    #[validate(range(min = 1))]
+    pub level: i32,
+    #[validate(length(max = 2000))]
+    pub description: Option<String>,
+    #[validate(range(min = 0))]
+    pub price_cents: Option<i64>,
+    pub active: Option<bool>,
+}
+
+/// Tier listing parameters
+#[derive(Debug, Clone, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TierQuery {
+    /// Include retired tiers (admins only)
+    pub include_inactive: Option<bool>,
+}
+
+// ============================================
+// Members
+// ============================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type, ToSchema)]
+#[sqlx(type_name = "membership_status", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum MembershipStatus {
+    Active,
+    Cancelled,
+    Expired,
+}
+
+/// A user's membership with its tier
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct Membership {
+    pub user_id: Uuid,
+    pub tier_id: Uuid,
+    pub tier_name: String,
+    pub level: i32,
+    pub status: MembershipStatus,
+    pub started_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub granted_by: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct GrantInput {
+    pub tier_id: Uuid,
+    /// Leave out for a membership that doesn't lapse
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// Member listing parameters
+#[derive(Debug, Clone, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct MemberQuery {
+    pub status: Option<MembershipStatus>,
+    pub tier_id: Option<Uuid>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+// ============================================
+// Posts
+// ============================================
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct PostTierInput {
+    /// Tier readers need; `null` makes the post public
+    pub tier_id: Option<Uuid>,
+}
+
+/// A post's access requirement
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct PostAccess {
+    pub post_id: Uuid,
+    pub tier: Option<Tier>,
+}
+
+// ============================================
+// Responses
+// ============================================
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ListResponse<T> {
+    pub data: Vec<T>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub count: Option<usize>,
+}
@@ -0,0 +1,404 @@
+//! Membership Services
+
+use crate::models::*;
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+const MEMBERSHIP_SELECT: &str = r#"
+    SELECT m.user_id, m.tier_id, t.name AS tier_name, t.level, m.status,
+           m.started_at, m.expires_at, m.granted_by
+    FROM membership_members m
+    JOIN membership_tiers t ON t.id = m.tier_id"#;
+
+// ============================================
+// Tier Service
+// ============================================
+
+pub struct TierService {
+    db: PgPool,
+}
+
+impl TierService {
+    pub fn new(db: PgPool) -> Self {
+        Self { db }
+    }
+
+    pub async fn list(&self, include_inactive: bool) -> Result<Vec<Tier>, MembershipError> {
+        let tiers = sqlx::query_as("SELECT * FROM membership_tiers WHERE active OR $1 ORDER BY level")
+            .bind(include_inactive)
+            .fetch_all(&self.db)
+            .await?;
+        Ok(tiers)
+    }
+
+    pub async fn get(&self, id: Uuid) -> Result<Tier, MembershipError> {
+        sqlx::query_as("SELECT * FROM membership_tiers WHERE id = $1")
+            .bind(id)
+            .fetch_optional(&self.db)
+            .await?
+            .ok_or_else(|| MembershipError::NotFound(format!("Tier not found: {}", id)))
+    }
+
+    pub async fn create(&self, input: &TierInput) -> Result<Tier, MembershipError> {
+        sqlx::query_as(
+            r#"INSERT INTO membership_tiers (name, slug, level, description, price_cents, active)
+               VALUES ($1, $2, $3, $4, $5, $6)
+               RETURNING *"#,
+        )
+        .bind(&input.name)
+        .bind(slug::slugify(&input.name))
+        .bind(input.level)
+        .bind(&input.description)
+        .bind(input.price_cents)
+        .bind(input.active.unwrap_or(true))
+        .fetch_one(&self.db)
+        .await
+        .map_err(|e| unique_violation(e, "A tier with this name or level already exists"))
+    }
+
+    /// Update a tier; a new level is applied to its members and posts
+    pub async fn update(&self, id: Uuid, input: &TierInput) -> Result<Tier, MembershipError> {
+        let mut tx = self.db.begin().await?;
+
+        let tier: Tier = sqlx::query_as(
+            r#"UPDATE membership_tiers
+               SET name = $2, level = $3, description = $4, price_cents = $5,
+                   active = COALESCE($6, active), updated_at = NOW()
+               WHERE id = $1
+               RETURNING *"#,
+        )
+        .bind(id)
+        .bind(&input.name)
+        .bind(input.level)
+        .bind(&input.description)
+        .bind(input.price_cents)
+        .bind(input.active)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| unique_violation(e, "A tier with this level already exists"))?
+        .ok_or_else(|| MembershipError::NotFound(format!("Tier not found: {}", id)))?;
+
+        sqlx::query(
+            r#"UPDATE blog_posts p SET required_level = $2
+               FROM membership_post_tiers pt
+               WHERE pt.post_id = p.id AND pt.tier_id = $1"#,
+        )
+        .bind(id)
+        .bind(tier.level)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            r#"UPDATE user_meta um SET meta_value = $2::text, updated_at = NOW()
+               FROM membership_members m
+               WHERE m.tier_id = $1 AND m.status = 'active'
+                 AND um.user_id = m.user_id AND um.meta_key = 'membership_level'"#,
+        )
+        .bind(id)
+        .bind(tier.level)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(tier)
+    }
+
+    /// Delete a tier nobody holds; its posts become public
+    pub async fn delete(&self, id: Uuid) -> Result<(), MembershipError> {
+        let mut tx = self.db.begin().await?;
+
+        let held: bool = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM membership_members WHERE tier_id = $1)")
+            .bind(id)
+            .fetch_one(&mut *tx)
+            .await?;
+        if held {
+            return Err(MembershipError::Conflict(
+                "Tier still has members; retire it by setting active = false".into(),
+            ));
+        }
+
+        sqlx::query(
+            r#"UPDATE blog_posts SET required_level = NULL
+               WHERE id IN (SELECT post_id FROM membership_post_tiers WHERE tier_id = $1)"#,
+        )
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+
+        let deleted = sqlx::query("DELETE FROM membership_tiers WHERE id = $1")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        if deleted == 0 {
+            return Err(MembershipError::NotFound(format!("Tier not found: {}", id)));
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+// ============================================
+// Member Service
+// ============================================
+
+/// Grants and revokes memberships
+///
+/// The blog only sees the `membership_level` and `membership_expires_at`
+/// user meta entries, which are rewritten on every change here.
+pub struct MemberService {
+    db: PgPool,
+}
+
+impl MemberService {
+    pub fn new(db: PgPool) -> Self {
+        Self { db }
+    }
+
+    pub async fn get(&self, user_id: Uuid) -> Result<Membership, MembershipError> {
+        sqlx::query_as(&format!("{} WHERE m.user_id = $1", MEMBERSHIP_SELECT))
+            .bind(user_id)
+            .fetch_optional(&self.db)
+            .await?
+            .ok_or_else(|| MembershipError::NotFound("No membership".into()))
+    }
+
+    pub async fn list(&self, query: &MemberQuery) -> Result<Vec<Membership>, MembershipError> {
+        let members = sqlx::query_as(&format!(
+            r#"{}
+               WHERE ($1::membership_status IS NULL OR m.status = $1)
+                 AND ($2::uuid IS NULL OR m.tier_id = $2)
+               ORDER BY m.started_at DESC
+               LIMIT $3 OFFSET $4"#,
+            MEMBERSHIP_SELECT
+        ))
+        .bind(query.status)
+        .bind(query.tier_id)
+        .bind(query.limit.unwrap_or(50).min(200))
+        .bind(query.offset.unwrap_or(0))
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(members)
+    }
+
+    /// Give `user_id` the tier, replacing any membership they had
+    pub async fn grant(&self, user_id: Uuid, input: &GrantInput, actor: Uuid) -> Result<Membership, MembershipError> {
+        let mut tx = self.db.begin().await?;
+
+        let active: Option<bool> = sqlx::query_scalar("SELECT active FROM membership_tiers WHERE id = $1")
+            .bind(input.tier_id)
+            .fetch_optional(&mut *tx)
+            .await?;
+        match active {
+            None => return Err(MembershipError::NotFound(format!("Tier not found: {}", input.tier_id))),
+            Some(false) => return Err(MembershipError::Conflict("Tier is retired".into())),
+            Some(true) => {}
+        }
+
+        sqlx::query(
+            r#"INSERT INTO membership_members (user_id, tier_id, status, expires_at, granted_by)
+               VALUES ($1, $2, 'active', $3, $4)
+               ON CONFLICT (user_id) DO UPDATE SET
+                   tier_id = EXCLUDED.tier_id,
+                   status = 'active',
+                   started_at = NOW(),
+                   expires_at = EXCLUDED.expires_at,
+                   granted_by = EXCLUDED.granted_by,
+                   updated_at = NOW()"#,
+        )
+        .bind(user_id)
+        .bind(input.tier_id)
+        .bind(input.expires_at)
+        .bind(actor)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| match &e {
+            sqlx::Error::Database(db) if db.is_foreign_key_violation() => {
+                MembershipError::NotFound(format!("User not found: {}", user_id))
+            }
+            _ => e.into(),
+        })?;
+
+        sync_user_meta(&mut tx, user_id).await?;
+        tx.commit().await?;
+
+        tracing::info!(%user_id, tier_id = %input.tier_id, %actor, "Membership granted");
+        self.get(user_id).await
+    }
+
+    /// End a membership now
+    pub async fn revoke(&self, user_id: Uuid, actor: Uuid) -> Result<Membership, MembershipError> {
+        let mut tx = self.db.begin().await?;
+
+        let updated = sqlx::query(
+            "UPDATE membership_members SET status = 'cancelled', updated_at = NOW() WHERE user_id = $1 AND status = 'active'",
+        )
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+        if updated == 0 {
+            return Err(MembershipError::NotFound("No active membership".into()));
+        }
+
+        sync_user_meta(&mut tx, user_id).await?;
+        tx.commit().await?;
+
+        tracing::info!(%user_id, %actor, "Membership revoked");
+        self.get(user_id).await
+    }
+
+    /// Mark lapsed memberships expired; returns how many
+    ///
+    /// The blog already ignores a level past `membership_expires_at`; this
+    /// keeps the member list and user meta tidy.
+    pub async fn expire_due(&self) -> Result<usize, MembershipError> {
+        let mut tx = self.db.begin().await?;
+
+        let expired: Vec<Uuid> = sqlx::query_scalar(
+            r#"UPDATE membership_members SET status = 'expired', updated_at = NOW()
+               WHERE status = 'active' AND expires_at <= NOW()
+               RETURNING user_id"#,
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+
+        for user_id in &expired {
+            sync_user_meta(&mut tx, *user_id).await?;
+        }
+
+        tx.commit().await?;
+        Ok(expired.len())
+    }
+}
+
+/// Rewrite the user meta the blog reads from the user's membership
+async fn sync_user_meta(tx: &mut Transaction<'_, Postgres>, user_id: Uuid) -> Result<(), MembershipError> {
+    sqlx::query("DELETE FROM user_meta WHERE user_id = $1 AND meta_key IN ('membership_level', 'membership_expires_at')")
+        .bind(user_id)
+        .execute(&mut **tx)
+        .await?;
+
+    sqlx::query(
+        r#"INSERT INTO user_meta (user_id, meta_key, meta_value)
+           SELECT m.user_id, 'membership_level', t.level::text
+           FROM membership_members m JOIN membership_tiers t ON t.id = m.tier_id
+           WHERE m.user_id = $1 AND m.status = 'active'
+           UNION ALL
+           SELECT m.user_id, 'membership_expires_at', to_char(m.expires_at AT TIME ZONE 'UTC', 'YYYY-MM-DD"T"HH24:MI:SS"Z"')
+           FROM membership_members m
+           WHERE m.user_id = $1 AND m.status = 'active' AND m.expires_at IS NOT NULL"#,
+    )
+    .bind(user_id)
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
+
+// ============================================
+// Post Access Service
+// ============================================
+
+/// Assigns posts to tiers by setting their `required_level`
+pub struct PostAccessService {
+    db: PgPool,
+}
+
+impl PostAccessService {
+    pub fn new(db: PgPool) -> Self {
+        Self { db }
+    }
+
+    pub async fn get(&self, post_id: Uuid) -> Result<PostAccess, MembershipError> {
+        let tier = sqlx::query_as(
+            r#"SELECT t.* FROM membership_tiers t
+               JOIN membership_post_tiers pt ON pt.tier_id = t.id
+               WHERE pt.post_id = $1"#,
+        )
+        .bind(post_id)
+        .fetch_optional(&self.db)
+        .await?;
+
+        Ok(PostAccess { post_id, tier })
+    }
+
+    /// Make a post members-only for `tier_id` and above, or public with `None`
+    pub async fn set_tier(&self, post_id: Uuid, tier_id: Option<Uuid>) -> Result<PostAccess, MembershipError> {
+        let mut tx = self.db.begin().await?;
+
+        let level: Option<i32> = match tier_id {
+            Some(tier_id) => {
+                let level = sqlx::query_scalar("SELECT level FROM membership_tiers WHERE id = $1")
+                    .bind(tier_id)
+                    .fetch_optional(&mut *tx)
+                    .await?
+                    .ok_or_else(|| MembershipError::NotFound(format!("Tier not found: {}", tier_id)))?;
+                Some(level)
+            }
+            None => None,
+        };
+
+        let updated = sqlx::query("UPDATE blog_posts SET required_level = $2, updated_at = NOW() WHERE id = $1")
+            .bind(post_id)
+            .bind(level)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        if updated == 0 {
+            return Err(MembershipError::NotFound(format!("Post not found: {}", post_id)));
+        }
+
+        match tier_id {
+            Some(tier_id) => {
+                sqlx::query(
+                    r#"INSERT INTO membership_post_tiers (post_id, tier_id) VALUES ($1, $2)
+                       ON CONFLICT (post_id) DO UPDATE SET tier_id = EXCLUDED.tier_id"#,
+                )
+                .bind(post_id)
+                .bind(tier_id)
+                .execute(&mut *tx)
+                .await?;
+            }
+            None => {
+                sqlx::query("DELETE FROM membership_post_tiers WHERE post_id = $1")
+                    .bind(post_id)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+        }
+
+        tx.commit().await?;
+        self.get(post_id).await
+    }
+}
+
+// ============================================
+// Error Types
+// ============================================
+
+#[derive(Debug, thiserror::Error)]
+pub enum MembershipError {
+    #[error("{0}")]
+    NotFound(String),
+    #[error("{0}")]
+    Conflict(String),
+    #[error("Database error: {0}")]
+    Database(String),
+}
+
+impl From<sqlx::Error> for MembershipError {
+    fn from(e: sqlx::Error) -> Self {
+        MembershipError::Database(e.to_string())
+    }
+}
+
+/// Map unique constraint violations to a conflict with `message`
+fn unique_violation(e: sqlx::Error, message: &str) -> MembershipError {
+    match &e {
+        sqlx::Error::Database(db) if db.is_unique_violation() => MembershipError::Conflict(message.to_string()),
+        _ => e.into(),
+    }
+}
@@ -8,7 +8,7 @@
 //! - Shortcode rendering
 
 use async_trait::async_trait;
-use axum::{extract::State, Json};
+use axum::{extract::State, routing::get, Json};
 use chrono::Utc;
 use rustpress_plugins::prelude::*;
 use serde::{Deserialize, Serialize};
@@ -22,7 +22,7 @@ use tokio::sync::RwLock;
 pub struct HelloWorldPlugin {
     info: PluginInfo,
     state: RwLock<PluginState>,
-    greeting: RwLock<String>,
+    greeting: Arc<RwLock<String>>,
 }
 
 impl HelloWorldPlugin {
@@ -32,11 +32,27 @@ impl HelloWorldPlugin {
                 id: "hello-world".into(),
                 name: "Hello World".into(),
                 version: "1.0.0".into(),
+                needs: Vec::new(),
             },
             state: RwLock::new(PluginState::Inactive),
-            greeting: RwLock::new("Hello, World!".into()),
+            greeting: Arc::new(RwLock::new("Hello, World!".into())),
         }
     }
+
+    pub fn info(&self) -> &PluginInfo {
+        &self.info
+    }
+
+    pub async fn state(&self) -> PluginState {
+        *self.state.read().await
+    }
+
+    /// API routes, sharing the plugin's greeting
+    fn routes(&self) -> Router {
+        Router::new()
+            .route("/api/v1/hello-world/greet", get(get_greeting).post(set_greeting))
+            .with_state(self.greeting.clone())
+    }
 }
 
 impl Default for HelloWorldPlugin {
@@ -55,17 +71,21 @@ impl LifecycleHook for HelloWorldPlugin {
         tracing::info!("Activating Hello World plugin");
 
         // Load greeting from settings
-        if let Some(greeting) = ctx.settings.get::<String>("hello-world", "greeting").await? {
+        if let Some(greeting) = ctx.settings.get::<String>(&self.info.id, "greeting").await? {
             *self.greeting.write().await = greeting;
         }
 
+        // Serve the API
+        ctx.register_routes(self.routes()).await?;
+
         *self.state.write().await = PluginState::Active;
         tracing::info!("Hello World plugin activated!");
         Ok(())
     }
 
-    async fn on_deactivate(&self, _ctx: &DeactivationContext) -> Result<(), HookError> {
+    async fn on_deactivate(&self, ctx: &DeactivationContext) -> Result<(), HookError> {
         tracing::info!("Deactivating Hello World plugin");
+        ctx.unregister_routes().await?;
         *self.state.write().await = PluginState::Inactive;
         Ok(())
     }
@@ -81,7 +101,7 @@ impl LifecycleHook for HelloWorldPlugin {
 
     async fn on_uninstall(&self, ctx: &UninstallContext) -> Result<(), HookError> {
         tracing::info!("Uninstalling Hello World plugin");
-        ctx.settings.remove_all("hello-world").await?;
+        ctx.settings.remove_all(&self.info.id).await?;
         Ok(())
     }
 }
@@ -107,9 +127,9 @@ pub struct SetGreetingRequest {
 
 /// GET /api/v1/hello-world/greet
 pub async fn get_greeting(
-    State(plugin): State<Arc<HelloWorldPlugin>>,
+    State(greeting): State<Arc<RwLock<String>>>,
 ) -> Json<GreetingResponse> {
-    let greeting = plugin.greeting.read().await.clone();
+    let greeting = greeting.read().await.clone();
     let show_date = true; // Would come from settings
 
     Json(GreetingResponse {
@@ -124,10 +144,10 @@ pub async fn get_greeting(
 
 /// POST /api/v1/hello-world/greet
 pub async fn set_greeting(
-    State(plugin): State<Arc<HelloWorldPlugin>>,
+    State(greeting): State<Arc<RwLock<String>>>,
     Json(input): Json<SetGreetingRequest>,
 ) -> Json<GreetingResponse> {
-    *plugin.greeting.write().await = input.message.clone();
+    *greeting.write().await = input.message.clone();
 
     Json(GreetingResponse {
         message: input.message,
@@ -154,3 +174,55 @@ pub fn render_hello(attrs: &ShortcodeAttributes, _content: Option<&str>) -> Stri
 // ============================================
 
 rustpress_plugin!(HelloWorldPlugin);
+
+// ============================================
+// Tests
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::Request};
+    use tower::ServiceExt;
+
+    async fn greet(app: Router, request: Request<Body>) -> (u16, serde_json::Value) {
+        let response = app.oneshot(request).await.unwrap();
+        let status = response.status().as_u16();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or_default())
+    }
+
+    #[tokio::test]
+    async fn test_lifecycle_serves_the_configured_greeting() {
+        let host = PluginHost::for_tests();
+        host.settings.set("hello-world", "greeting", "Hi there!").await.unwrap();
+
+        let plugin = rustpress_plugin_create();
+        plugin.on_activate(&host.activation_context("hello-world")).await.unwrap();
+
+        let get = || Request::get("/api/v1/hello-world/greet").body(Body::empty()).unwrap();
+        let (status, body) = greet(host.router().await, get()).await;
+        assert_eq!(status, 200);
+        assert_eq!(body["message"], "Hi there!");
+
+        let set = Request::post("/api/v1/hello-world/greet")
+            .header("content-type", "application/json")
+            .body(Body::from(r#"{"message": "Howdy"}"#))
+            .unwrap();
+        greet(host.router().await, set).await;
+        assert_eq!(greet(host.router().await, get()).await.1["message"], "Howdy");
+
+        plugin.on_deactivate(&host.deactivation_context("hello-world")).await.unwrap();
+        assert_eq!(greet(host.router().await, get()).await.0, 404);
+
+        plugin.on_uninstall(&host.uninstall_context("hello-world")).await.unwrap();
+        assert!(host.settings.get::<String>("hello-world", "greeting").await.unwrap().is_none());
+    }
+
+    #[test]
+    fn test_render_hello() {
+        let attrs = parse_attributes(r#"name="Ada""#);
+        assert!(render_hello(&attrs, None).contains("Hello, Ada!"));
+        assert!(render_hello(&ShortcodeAttributes::new(), None).contains("Hello, World!"));
+    }
+}
@@ -0,0 +1,9 @@
+DROP TABLE IF EXISTS shop_webhook_events;
+DROP TABLE IF EXISTS shop_order_events;
+DROP TABLE IF EXISTS shop_order_items;
+DROP TABLE IF EXISTS shop_orders;
+DROP TYPE IF EXISTS shop_order_status;
+DROP TABLE IF EXISTS shop_cart_items;
+DROP TABLE IF EXISTS shop_carts;
+DROP TABLE IF EXISTS shop_variants;
+DROP TABLE IF EXISTS shop_products;
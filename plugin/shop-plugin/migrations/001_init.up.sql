@@ -0,0 +1,106 @@
+-- RustPress Shop - Initial Schema
+--
+-- Prices are stored in the smallest currency unit (cents). Orders copy the
+-- product name, SKU and price at checkout so later catalog edits don't
+-- rewrite history.
+
+CREATE TABLE IF NOT EXISTS shop_products (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    name VARCHAR(200) NOT NULL,
+    slug VARCHAR(220) NOT NULL UNIQUE,
+    description TEXT,
+    image_url VARCHAR(500),
+    active BOOLEAN NOT NULL DEFAULT true,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS shop_variants (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    product_id UUID NOT NULL REFERENCES shop_products(id) ON DELETE CASCADE,
+    sku VARCHAR(64) NOT NULL UNIQUE,
+    -- e.g. "Large / Blue"
+    name VARCHAR(200) NOT NULL,
+    price_cents BIGINT NOT NULL CHECK (price_cents >= 0),
+    stock INTEGER NOT NULL DEFAULT 0 CHECK (stock >= 0),
+    active BOOLEAN NOT NULL DEFAULT true,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_shop_variants_product ON shop_variants(product_id);
+
+-- One cart per browser session (the `rp_cart` cookie)
+CREATE TABLE IF NOT EXISTS shop_carts (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    expires_at TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_shop_carts_expires ON shop_carts(expires_at);
+
+CREATE TABLE IF NOT EXISTS shop_cart_items (
+    cart_id UUID NOT NULL REFERENCES shop_carts(id) ON DELETE CASCADE,
+    variant_id UUID NOT NULL REFERENCES shop_variants(id) ON DELETE CASCADE,
+    quantity INTEGER NOT NULL CHECK (quantity > 0),
+    PRIMARY KEY (cart_id, variant_id)
+);
+
+DO $$ BEGIN
+    CREATE TYPE shop_order_status AS ENUM (
+        'pending', 'paid', 'fulfilled', 'cancelled', 'refunded'
+    );
+EXCEPTION
+    WHEN duplicate_object THEN null;
+END $$;
+
+CREATE TABLE IF NOT EXISTS shop_orders (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    -- NULL for guest checkouts
+    user_id UUID REFERENCES users(id) ON DELETE SET NULL,
+    email VARCHAR(255) NOT NULL,
+    status shop_order_status NOT NULL DEFAULT 'pending',
+    currency CHAR(3) NOT NULL,
+    total_cents BIGINT NOT NULL,
+    payment_intent_id VARCHAR(255) UNIQUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_shop_orders_status ON shop_orders(status, created_at);
+CREATE INDEX IF NOT EXISTS idx_shop_orders_user ON shop_orders(user_id, created_at DESC);
+
+CREATE TABLE IF NOT EXISTS shop_order_items (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    order_id UUID NOT NULL REFERENCES shop_orders(id) ON DELETE CASCADE,
+    -- Kept when the variant is deleted; name, SKU and price are copies
+    variant_id UUID REFERENCES shop_variants(id) ON DELETE SET NULL,
+    product_name VARCHAR(200) NOT NULL,
+    variant_name VARCHAR(200) NOT NULL,
+    sku VARCHAR(64) NOT NULL,
+    unit_price_cents BIGINT NOT NULL,
+    quantity INTEGER NOT NULL CHECK (quantity > 0)
+);
+
+CREATE INDEX IF NOT EXISTS idx_shop_order_items_order ON shop_order_items(order_id);
+
+-- Status history; `actor` is a user ID, "stripe" or "system"
+CREATE TABLE IF NOT EXISTS shop_order_events (
+    id BIGSERIAL PRIMARY KEY,
+    order_id UUID NOT NULL REFERENCES shop_orders(id) ON DELETE CASCADE,
+    from_status shop_order_status,
+    to_status shop_order_status NOT NULL,
+    actor VARCHAR(64) NOT NULL,
+    note TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_shop_order_events_order ON shop_order_events(order_id, created_at);
+
+-- Stripe webhook deliveries already handled (Stripe retries and may deliver
+-- an event more than once)
+CREATE TABLE IF NOT EXISTS shop_webhook_events (
+    event_id VARCHAR(255) PRIMARY KEY,
+    event_type VARCHAR(100) NOT NULL,
+    received_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,633 @@
+//! Shop REST API Handlers
+
+use crate::models::*;
+use crate::services::stripe::{self, StripeEvent};
+use crate::services::*;
+use crate::ShopPlugin;
+use axum::{
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post, put},
+    Json,
+};
+use rustpress_auth::problem::ProblemDetails;
+use rustpress_auth::routes::PluginRoutes;
+use rustpress_auth::{AuthUser, ValidatedJson};
+use std::sync::Arc;
+use utoipa::OpenApi;
+use uuid::Uuid;
+
+/// Cookie holding the session's cart ID
+const CART_COOKIE: &str = "rp_cart";
+
+/// Create API routes, mounted under `/api/v1/rustpress-shop`
+pub fn create_routes(plugin: &ShopPlugin) -> PluginRoutes {
+    PluginRoutes::new(plugin.info.id.clone())
+        // Catalog
+        .route("/products", get(list_products).post(create_product))
+        // GET takes a slug, PUT an ID; the router needs one parameter name
+        .route("/products/:id", get(get_product).put(update_product))
+        .route("/products/:id/variants", post(create_variant))
+        .route("/variants/:id", put(update_variant))
+        // Cart
+        .route("/cart", get(get_cart))
+        .route("/cart/items", post(add_cart_item))
+        .route("/cart/items/:variant_id", put(update_cart_item).delete(remove_cart_item))
+        // Orders
+        .route("/checkout", post(checkout))
+        .route("/orders", get(list_orders))
+        .route("/orders/:id", get(get_order))
+        .route("/orders/:id/status", post(update_order_status))
+        // Payments
+        .route("/webhooks/stripe", post(stripe_webhook))
+        // API documentation
+        .route("/openapi.json", get(|| async { Json(ApiDoc::openapi()) }))
+        .route("/docs", get(swagger_ui))
+        .layer(axum::middleware::from_fn(rustpress_auth::problem::trace_id))
+}
+
+// ============================================
+// API Documentation
+// ============================================
+
+/// Shop API specification, generated from the handler annotations
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "RustPress Shop API"),
+    servers((url = "/api/v1/rustpress-shop")),
+    paths(
+        list_products,
+        get_product,
+        create_product,
+        update_product,
+        create_variant,
+        update_variant,
+        get_cart,
+        add_cart_item,
+        update_cart_item,
+        remove_cart_item,
+        checkout,
+        list_orders,
+        get_order,
+        update_order_status,
+        stripe_webhook,
+    ),
+    tags(
+        (name = "catalog", description = "Products and variants"),
+        (name = "cart", description = "Session carts"),
+        (name = "orders", description = "Checkout and order management"),
+        (name = "payments", description = "Payment provider webhooks"),
+    )
+)]
+pub struct ApiDoc;
+
+/// GET /api/v1/rustpress-shop/docs
+async fn swagger_ui() -> Response {
+    rustpress_auth::openapi::swagger_ui("openapi.json")
+}
+
+// ============================================
+// Catalog Endpoints
+// ============================================
+
+/// GET /api/v1/rustpress-shop/products
+#[utoipa::path(
+    get,
+    path = "/products",
+    tag = "catalog",
+    params(ProductQuery),
+    responses(
+        (status = 200, description = "Products with their variants", body = ListResponse<ProductWithVariants>),
+        (status = 403, description = "Inactive products requested by a non-manager", body = ProblemDetails),
+        (status = 503, description = "Service unavailable", body = ProblemDetails),
+    ),
+)]
+pub async fn list_products(
+    State(plugin): State<Arc<ShopPlugin>>,
+    user: Option<AuthUser>,
+    Query(query): Query<ProductQuery>,
+) -> Result<Json<ListResponse<ProductWithVariants>>, ProblemDetails> {
+    if query.include_inactive.unwrap_or(false) {
+        require_manager(user.as_ref())?;
+    }
+
+    let products = catalog_service(&plugin).await?.list(&query).await?;
+
+    Ok(Json(ListResponse {
+        count: Some(products.len()),
+        data: products,
+    }))
+}
+
+/// GET /api/v1/rustpress-shop/products/{slug}
+#[utoipa::path(
+    get,
+    path = "/products/{slug}",
+    tag = "catalog",
+    params(("slug" = String, Path, description = "Product slug")),
+    responses(
+        (status = 200, description = "Product with its variants", body = ProductWithVariants),
+        (status = 404, description = "Product not found", body = ProblemDetails),
+    ),
+)]
+pub async fn get_product(
+    State(plugin): State<Arc<ShopPlugin>>,
+    Path(slug): Path<String>,
+) -> Result<Json<ProductWithVariants>, ProblemDetails> {
+    Ok(Json(catalog_service(&plugin).await?.get_by_slug(&slug).await?))
+}
+
+/// POST /api/v1/rustpress-shop/products
+#[utoipa::path(
+    post,
+    path = "/products",
+    tag = "catalog",
+    request_body = ProductInput,
+    responses(
+        (status = 201, description = "Product created", body = Product),
+        (status = 403, description = "Not a shop manager", body = ProblemDetails),
+        (status = 409, description = "Slug already in use", body = ProblemDetails),
+        (status = 422, description = "Invalid product", body = ProblemDetails),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn create_product(
+    State(plugin): State<Arc<ShopPlugin>>,
+    user: AuthUser,
+    ValidatedJson(input): ValidatedJson<ProductInput>,
+) -> Result<(StatusCode, Json<Product>), ProblemDetails> {
+    require_manager(Some(&user))?;
+
+    let product = catalog_service(&plugin).await?.create_product(&input).await?;
+    Ok((StatusCode::CREATED, Json(product)))
+}
+
+/// PUT /api/v1/rustpress-shop/products/{id}
+#[utoipa::path(
+    put,
+    path = "/products/{id}",
+    tag = "catalog",
+    params(("id" = Uuid, Path, description = "Product ID")),
+    request_body = ProductInput,
+    responses(
+        (status = 200, description = "Product updated", body = Product),
+        (status = 403, description = "Not a shop manager", body = ProblemDetails),
+        (status = 404, description = "Product not found", body = ProblemDetails),
+        (status = 422, description = "Invalid product", body = ProblemDetails),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn update_product(
+    State(plugin): State<Arc<ShopPlugin>>,
+    user: AuthUser,
+    Path(id): Path<Uuid>,
+    ValidatedJson(input): ValidatedJson<ProductInput>,
+) -> Result<Json<Product>, ProblemDetails> {
+    require_manager(Some(&user))?;
+
+    Ok(Json(catalog_service(&plugin).await?.update_product(id, &input).await?))
+}
+
+/// POST /api/v1/rustpress-shop/products/{id}/variants
+#[utoipa::path(
+    post,
+    path = "/products/{id}/variants",
+    tag = "catalog",
+    params(("id" = Uuid, Path, description = "Product ID")),
+    request_body = VariantInput,
+    responses(
+        (status = 201, description = "Variant created", body = Variant),
+        (status = 403, description = "Not a shop manager", body = ProblemDetails),
+        (status = 404, description = "Product not found", body = ProblemDetails),
+        (status = 409, description = "SKU already in use", body = ProblemDetails),
+        (status = 422, description = "Invalid variant", body = ProblemDetails),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn create_variant(
+    State(plugin): State<Arc<ShopPlugin>>,
+    user: AuthUser,
+    Path(id): Path<Uuid>,
+    ValidatedJson(input): ValidatedJson<VariantInput>,
+) -> Result<(StatusCode, Json<Variant>), ProblemDetails> {
+    require_manager(Some(&user))?;
+
+    let variant = catalog_service(&plugin).await?.create_variant(id, &input).await?;
+    Ok((StatusCode::CREATED, Json(variant)))
+}
+
+/// PUT /api/v1/rustpress-shop/variants/{id}
+#[utoipa::path(
+    put,
+    path = "/variants/{id}",
+    tag = "catalog",
+    params(("id" = Uuid, Path, description = "Variant ID")),
+    request_body = VariantInput,
+    responses(
+        (status = 200, description = "Variant updated, including its stock", body = Variant),
+        (status = 403, description = "Not a shop manager", body = ProblemDetails),
+        (status = 404, description = "Variant not found", body = ProblemDetails),
+        (status = 409, description = "SKU already in use", body = ProblemDetails),
+        (status = 422, description = "Invalid variant", body = ProblemDetails),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn update_variant(
+    State(plugin): State<Arc<ShopPlugin>>,
+    user: AuthUser,
+    Path(id): Path<Uuid>,
+    ValidatedJson(input): ValidatedJson<VariantInput>,
+) -> Result<Json<Variant>, ProblemDetails> {
+    require_manager(Some(&user))?;
+
+    Ok(Json(catalog_service(&plugin).await?.update_variant(id, &input).await?))
+}
+
+// ============================================
+// Cart Endpoints
+// ============================================
+
+/// GET /api/v1/rustpress-shop/cart
+///
+/// The cart is identified by the `rp_cart` cookie, or an `X-Cart-Id` header
+/// for clients without cookies.
+#[utoipa::path(
+    get,
+    path = "/cart",
+    tag = "cart",
+    responses(
+        (status = 200, description = "The session's cart", body = Cart),
+        (status = 404, description = "No cart, or it expired", body = ProblemDetails),
+    ),
+)]
+pub async fn get_cart(State(plugin): State<Arc<ShopPlugin>>, headers: HeaderMap) -> Result<Json<Cart>, ProblemDetails> {
+    let carts = cart_service(&plugin).await?;
+
+    let cart = match cart_id(&headers) {
+        Some(id) => carts.get(id).await?,
+        None => None,
+    };
+
+    cart.map(Json)
+        .ok_or_else(|| ProblemDetails::not_found("No active cart"))
+}
+
+/// POST /api/v1/rustpress-shop/cart/items
+///
+/// Creates the cart on first use and (re)sets the cart cookie.
+#[utoipa::path(
+    post,
+    path = "/cart/items",
+    tag = "cart",
+    request_body = CartItemInput,
+    responses(
+        (status = 200, description = "Updated cart", body = Cart),
+        (status = 404, description = "Variant not found", body = ProblemDetails),
+        (status = 409, description = "Not enough stock", body = ProblemDetails),
+        (status = 422, description = "Invalid quantity", body = ProblemDetails),
+    ),
+)]
+pub async fn add_cart_item(
+    State(plugin): State<Arc<ShopPlugin>>,
+    headers: HeaderMap,
+    ValidatedJson(input): ValidatedJson<CartItemInput>,
+) -> Result<Response, ProblemDetails> {
+    let carts = cart_service(&plugin).await?;
+
+    let id = carts.get_or_create(cart_id(&headers)).await?;
+    carts.add_item(id, &input).await?;
+
+    cart_response(&plugin, &carts, id).await
+}
+
+/// PUT /api/v1/rustpress-shop/cart/items/{variant_id}
+#[utoipa::path(
+    put,
+    path = "/cart/items/{variant_id}",
+    tag = "cart",
+    params(("variant_id" = Uuid, Path, description = "Variant ID")),
+    request_body = CartQuantityInput,
+    responses(
+        (status = 200, description = "Updated cart", body = Cart),
+        (status = 404, description = "Item not in the cart", body = ProblemDetails),
+        (status = 409, description = "Not enough stock", body = ProblemDetails),
+        (status = 422, description = "Invalid quantity", body = ProblemDetails),
+    ),
+)]
+pub async fn update_cart_item(
+    State(plugin): State<Arc<ShopPlugin>>,
+    headers: HeaderMap,
+    Path(variant_id): Path<Uuid>,
+    ValidatedJson(input): ValidatedJson<CartQuantityInput>,
+) -> Result<Response, ProblemDetails> {
+    let carts = cart_service(&plugin).await?;
+
+    let id = carts.get_or_create(cart_id(&headers)).await?;
+    carts.set_quantity(id, variant_id, input.quantity).await?;
+
+    cart_response(&plugin, &carts, id).await
+}
+
+/// DELETE /api/v1/rustpress-shop/cart/items/{variant_id}
+#[utoipa::path(
+    delete,
+    path = "/cart/items/{variant_id}",
+    tag = "cart",
+    params(("variant_id" = Uuid, Path, description = "Variant ID")),
+    responses(
+        (status = 200, description = "Updated cart", body = Cart),
+    ),
+)]
+pub async fn remove_cart_item(
+    State(plugin): State<Arc<ShopPlugin>>,
+    headers: HeaderMap,
+    Path(variant_id): Path<Uuid>,
+) -> Result<Response, ProblemDetails> {
+    let carts = cart_service(&plugin).await?;
+
+    let id = carts.get_or_create(cart_id(&headers)).await?;
+    carts.set_quantity(id, variant_id, 0).await?;
+
+    cart_response(&plugin, &carts, id).await
+}
+
+// ============================================
+// Order Endpoints
+// ============================================
+
+/// POST /api/v1/rustpress-shop/checkout
+///
+/// Guests may check out; signed-in users get the order linked to their
+/// account.
+#[utoipa::path(
+    post,
+    path = "/checkout",
+    tag = "orders",
+    request_body = CheckoutInput,
+    responses(
+        (status = 201, description = "Pending order and the Stripe client secret", body = CheckoutResponse),
+        (status = 409, description = "Empty cart or not enough stock", body = ProblemDetails),
+        (status = 422, description = "Invalid email", body = ProblemDetails),
+        (status = 502, description = "Payment could not be started", body = ProblemDetails),
+    ),
+)]
+pub async fn checkout(
+    State(plugin): State<Arc<ShopPlugin>>,
+    user: Option<AuthUser>,
+    headers: HeaderMap,
+    ValidatedJson(input): ValidatedJson<CheckoutInput>,
+) -> Result<(StatusCode, Json<CheckoutResponse>), ProblemDetails> {
+    let orders = order_service(&plugin).await?;
+
+    let cart_id = cart_id(&headers).ok_or(ShopError::EmptyCart)?;
+    let response = orders
+        .checkout(cart_id, user.map(|u| u.id), &input)
+        .await?;
+
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+/// GET /api/v1/rustpress-shop/orders
+#[utoipa::path(
+    get,
+    path = "/orders",
+    tag = "orders",
+    params(OrderQuery),
+    responses(
+        (status = 200, description = "Orders, newest first", body = ListResponse<Order>),
+        (status = 403, description = "Not a shop manager", body = ProblemDetails),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn list_orders(
+    State(plugin): State<Arc<ShopPlugin>>,
+    user: AuthUser,
+    Query(query): Query<OrderQuery>,
+) -> Result<Json<ListResponse<Order>>, ProblemDetails> {
+    require_manager(Some(&user))?;
+
+    let orders = order_service(&plugin).await?.list(&query).await?;
+
+    Ok(Json(ListResponse {
+        count: Some(orders.len()),
+        data: orders,
+    }))
+}
+
+/// GET /api/v1/rustpress-shop/orders/{id}
+///
+/// Customers can see their own orders; managers can see all of them.
+#[utoipa::path(
+    get,
+    path = "/orders/{id}",
+    tag = "orders",
+    params(("id" = Uuid, Path, description = "Order ID")),
+    responses(
+        (status = 200, description = "Order with items and status history", body = OrderDetail),
+        (status = 404, description = "Order not found", body = ProblemDetails),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn get_order(
+    State(plugin): State<Arc<ShopPlugin>>,
+    user: AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<Json<OrderDetail>, ProblemDetails> {
+    let order = order_service(&plugin).await?.get(id).await?;
+
+    // Someone else's order is reported as missing rather than forbidden
+    if order.order.user_id != Some(user.id) && require_manager(Some(&user)).is_err() {
+        return Err(ProblemDetails::not_found(format!("Order not found: {}", id)));
+    }
+
+    Ok(Json(order))
+}
+
+/// POST /api/v1/rustpress-shop/orders/{id}/status
+#[utoipa::path(
+    post,
+    path = "/orders/{id}/status",
+    tag = "orders",
+    params(("id" = Uuid, Path, description = "Order ID")),
+    request_body = StatusInput,
+    responses(
+        (status = 200, description = "Order moved to the new status", body = Order),
+        (status = 403, description = "Not a shop manager", body = ProblemDetails),
+        (status = 404, description = "Order not found", body = ProblemDetails),
+        (status = 409, description = "Transition not allowed", body = ProblemDetails),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn update_order_status(
+    State(plugin): State<Arc<ShopPlugin>>,
+    user: AuthUser,
+    Path(id): Path<Uuid>,
+    Json(input): Json<StatusInput>,
+) -> Result<Json<Order>, ProblemDetails> {
+    require_manager(Some(&user))?;
+
+    let order = order_service(&plugin)
+        .await?
+        .transition(id, input.status, &user.id.to_string(), input.note.as_deref())
+        .await?;
+
+    Ok(Json(order))
+}
+
+// ============================================
+// Payment Webhooks
+// ============================================
+
+/// POST /api/v1/rustpress-shop/webhooks/stripe
+///
+/// The raw body is needed to check the `Stripe-Signature` header, so it is
+/// only parsed after verification.
+#[utoipa::path(
+    post,
+    path = "/webhooks/stripe",
+    tag = "payments",
+    request_body(content = String, description = "Stripe event, as sent by Stripe", content_type = "application/json"),
+    responses(
+        (status = 204, description = "Event handled or ignored"),
+        (status = 400, description = "Missing or invalid signature", body = ProblemDetails),
+        (status = 503, description = "Stripe webhooks not configured", body = ProblemDetails),
+    ),
+)]
+pub async fn stripe_webhook(
+    State(plugin): State<Arc<ShopPlugin>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<StatusCode, ProblemDetails> {
+    let secret = plugin
+        .config()
+        .await
+        .stripe_webhook_secret
+        .ok_or_else(|| ProblemDetails::unavailable("Stripe webhooks are not configured"))?;
+
+    let signature = headers
+        .get("stripe-signature")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("");
+    if !stripe::verify_signature(&body, signature, &secret, chrono::Utc::now().timestamp()) {
+        return Err(ProblemDetails::new(StatusCode::BAD_REQUEST, "invalid_signature")
+            .detail("Stripe signature verification failed"));
+    }
+
+    let event: StripeEvent = serde_json::from_slice(&body)
+        .map_err(|e| ProblemDetails::new(StatusCode::BAD_REQUEST, "invalid_event").detail(e.to_string()))?;
+
+    order_service(&plugin).await?.handle_stripe_event(&event).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// ============================================
+// Helpers
+// ============================================
+
+impl From<ShopError> for ProblemDetails {
+    fn from(e: ShopError) -> Self {
+        match e {
+            ShopError::NotFound(msg) => ProblemDetails::not_found(msg),
+            ShopError::Conflict(msg) => ProblemDetails::new(StatusCode::CONFLICT, "conflict").detail(msg),
+            ShopError::EmptyCart => ProblemDetails::new(StatusCode::CONFLICT, "empty_cart").detail(e.to_string()),
+            ShopError::OutOfStock(_) => ProblemDetails::new(StatusCode::CONFLICT, "out_of_stock").detail(e.to_string()),
+            ShopError::InvalidTransition { .. } => {
+                ProblemDetails::new(StatusCode::CONFLICT, "invalid_transition").detail(e.to_string())
+            }
+            ShopError::Payment(_) => {
+                ProblemDetails::new(StatusCode::BAD_GATEWAY, "payment_failed").detail("Payment could not be started")
+            }
+            ShopError::Database(msg) => {
+                tracing::error!("Shop database error: {}", msg);
+                ProblemDetails::internal("Database error")
+            }
+        }
+    }
+}
+
+/// Catalog and order management is limited to editors and admins
+fn require_manager(user: Option<&AuthUser>) -> Result<(), ProblemDetails> {
+    match user {
+        Some(user) if user.can_moderate() => Ok(()),
+        Some(_) => Err(ProblemDetails::forbidden("Shop management requires the editor role")),
+        None => Err(ProblemDetails::unauthorized("Authentication required")),
+    }
+}
+
+/// Cart ID from the cart cookie or `X-Cart-Id` header
+fn cart_id(headers: &HeaderMap) -> Option<Uuid> {
+    let from_cookie = headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .find_map(|pair| pair.trim().strip_prefix(CART_COOKIE)?.strip_prefix('='))
+        .map(str::to_string);
+
+    from_cookie
+        .or_else(|| headers.get("x-cart-id").and_then(|v| v.to_str().ok()).map(str::to_string))
+        .and_then(|v| v.parse().ok())
+}
+
+/// The cart as JSON, refreshing the cart cookie
+async fn cart_response(plugin: &ShopPlugin, carts: &CartService, id: Uuid) -> Result<Response, ProblemDetails> {
+    let cart = carts
+        .get(id)
+        .await?
+        .ok_or_else(|| ProblemDetails::not_found("No active cart"))?;
+
+    let max_age = plugin.config().await.cart_ttl_hours * 3600;
+    let cookie = format!("{}={}; Path=/; HttpOnly; SameSite=Lax; Max-Age={}", CART_COOKIE, id, max_age);
+
+    let mut response = Json(cart).into_response();
+    if let Ok(value) = HeaderValue::from_str(&cookie) {
+        response.headers_mut().insert(header::SET_COOKIE, value);
+    }
+    Ok(response)
+}
+
+async fn catalog_service(plugin: &ShopPlugin) -> Result<Arc<CatalogService>, ProblemDetails> {
+    plugin
+        .catalog()
+        .await
+        .ok_or_else(|| ProblemDetails::unavailable("Catalog service unavailable"))
+}
+
+async fn cart_service(plugin: &ShopPlugin) -> Result<Arc<CartService>, ProblemDetails> {
+    plugin
+        .carts()
+        .await
+        .ok_or_else(|| ProblemDetails::unavailable("Cart service unavailable"))
+}
+
+async fn order_service(plugin: &ShopPlugin) -> Result<Arc<OrderService>, ProblemDetails> {
+    plugin
+        .orders()
+        .await
+        .ok_or_else(|| ProblemDetails::unavailable("Order service unavailable"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cart_id_from_cookie_or_header() {
+        let id = Uuid::new_v4();
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::COOKIE, format!("theme=dark; rp_cart={}", id).parse().unwrap());
+        assert_eq!(cart_id(&headers), Some(id));
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-cart-id", id.to_string().parse().unwrap());
+        assert_eq!(cart_id(&headers), Some(id));
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::COOKIE, "rp_cart_old=1; rp_cart=not-a-uuid".parse().unwrap());
+        assert_eq!(cart_id(&headers), None);
+    }
+}
@@ -0,0 +1,65 @@
+//! Shop Hook Handlers
+
+use crate::models::{OrderStatus, OrderStatusChanged};
+use crate::ShopPlugin;
+use rustpress_plugins::prelude::*;
+use std::sync::Arc;
+
+/// `shop_order_status_changed` listener: record the change in the log
+pub async fn log_order_status_change(
+    ctx: ActionContext,
+    data: Box<dyn std::any::Any + Send>,
+) -> Result<(), HookError> {
+    if let Some(change) = data.downcast_ref::<OrderStatusChanged>() {
+        tracing::info!(
+            request_id = %ctx.request_id,
+            order_id = %change.order_id,
+            actor = %change.actor,
+            "Order {} -> {}",
+            change.from.as_str(),
+            change.to.as_str()
+        );
+    }
+
+    Ok(())
+}
+
+/// Cron job: Delete expired carts
+pub async fn expire_carts(ctx: CronContext, plugin: Arc<ShopPlugin>) -> Result<(), HookError> {
+    let Some(carts) = plugin.carts().await else {
+        return Ok(());
+    };
+
+    let deleted = carts
+        .delete_expired()
+        .await
+        .map_err(|e| HookError::Database(e.to_string()))?;
+
+    tracing::info!("Deleted {} expired carts", deleted);
+    Ok(())
+}
+
+/// Cron job: Cancel orders left unpaid, releasing their stock
+pub async fn cancel_abandoned_orders(ctx: CronContext, plugin: Arc<ShopPlugin>) -> Result<(), HookError> {
+    let Some(orders) = plugin.orders().await else {
+        return Ok(());
+    };
+
+    let abandoned = orders
+        .abandoned()
+        .await
+        .map_err(|e| HookError::Database(e.to_string()))?;
+
+    for id in &abandoned {
+        // An order may have been paid since it was listed; skip it then
+        if let Err(e) = orders
+            .transition(*id, OrderStatus::Cancelled, "system", Some("Not paid in time"))
+            .await
+        {
+            tracing::warn!(order_id = %id, "Failed to cancel abandoned order: {}", e);
+        }
+    }
+
+    tracing::info!("Cancelled {} abandoned orders", abandoned.len());
+    Ok(())
+}
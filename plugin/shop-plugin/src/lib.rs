@@ -0,0 +1,228 @@
+//! RustPress Shop Plugin
+//!
+//! A small e-commerce example featuring:
+//! - Products with variants, prices and stock
+//! - Per-session carts
+//! - Checkout into orders with a guarded status lifecycle
+//! - Order status changes fired as action hooks
+//! - Stripe PaymentIntents and signed webhooks
+
+pub mod api;
+pub mod hooks;
+pub mod models;
+pub mod services;
+
+use async_trait::async_trait;
+use rustpress_auth::migrations::PluginMigrations;
+use rustpress_plugins::prelude::*;
+use services::{CartService, CatalogService, OrderService, ORDER_STATUS_CHANGED};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+// ============================================
+// Plugin Configuration
+// ============================================
+
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct ShopConfig {
+    /// ISO 4217 code all prices are in
+    pub currency: String,
+    /// Stripe secret key; checkout works without payments when unset
+    pub stripe_secret_key: Option<String>,
+    /// Signing secret of the Stripe webhook endpoint
+    pub stripe_webhook_secret: Option<String>,
+    pub cart_ttl_hours: i64,
+    /// Unpaid orders are cancelled, and their stock released, after this long
+    pub pending_order_ttl_hours: i64,
+}
+
+impl Default for ShopConfig {
+    fn default() -> Self {
+        Self {
+            currency: "usd".into(),
+            stripe_secret_key: None,
+            stripe_webhook_secret: None,
+            cart_ttl_hours: 72,
+            pending_order_ttl_hours: 24,
+        }
+    }
+}
+
+// ============================================
+// Main Plugin Struct
+// ============================================
+
+pub struct ShopPlugin {
+    info: PluginInfo,
+    state: RwLock<PluginState>,
+    config: RwLock<ShopConfig>,
+    catalog_service: RwLock<Option<Arc<CatalogService>>>,
+    cart_service: RwLock<Option<Arc<CartService>>>,
+    order_service: RwLock<Option<Arc<OrderService>>>,
+}
+
+impl ShopPlugin {
+    /// Schema migrations shipped with the plugin (`migrations/`)
+    pub fn migrations() -> PluginMigrations {
+        PluginMigrations::new("rustpress-shop", sqlx::migrate!("./migrations"))
+    }
+
+    pub fn new() -> Self {
+        Self {
+            info: PluginInfo {
+                id: "rustpress-shop".into(),
+                name: "RustPress Shop".into(),
+                version: "1.0.0".into(),
+                needs: vec![Capability::Db, Capability::Cron],
+            },
+            state: RwLock::new(PluginState::Inactive),
+            config: RwLock::new(ShopConfig::default()),
+            catalog_service: RwLock::new(None),
+            cart_service: RwLock::new(None),
+            order_service: RwLock::new(None),
+        }
+    }
+
+    pub async fn config(&self) -> ShopConfig {
+        self.config.read().await.clone()
+    }
+
+    pub async fn catalog(&self) -> Option<Arc<CatalogService>> {
+        self.catalog_service.read().await.clone()
+    }
+
+    pub async fn carts(&self) -> Option<Arc<CartService>> {
+        self.cart_service.read().await.clone()
+    }
+
+    pub async fn orders(&self) -> Option<Arc<OrderService>> {
+        self.order_service.read().await.clone()
+    }
+
+    async fn load_config(&self, settings: &SettingsManager) -> Result<ShopConfig, HookError> {
+        let mut config = ShopConfig::default();
+
+        if let Some(v) = settings.get::<String>("rustpress-shop", "currency").await? {
+            config.currency = v.to_lowercase();
+        }
+        if let Some(v) = settings.get::<String>("rustpress-shop", "stripe_secret_key").await? {
+            config.stripe_secret_key = Some(v).filter(|v| !v.is_empty());
+        }
+        if let Some(v) = settings.get::<String>("rustpress-shop", "stripe_webhook_secret").await? {
+            config.stripe_webhook_secret = Some(v).filter(|v| !v.is_empty());
+        }
+        if let Some(v) = settings.get::<i64>("rustpress-shop", "cart_ttl_hours").await? {
+            config.cart_ttl_hours = v;
+        }
+        if let Some(v) = settings.get::<i64>("rustpress-shop", "pending_order_ttl_hours").await? {
+            config.pending_order_ttl_hours = v;
+        }
+
+        Ok(config)
+    }
+}
+
+impl Default for ShopPlugin {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// ============================================
+// Lifecycle Implementation
+// ============================================
+
+#[async_trait]
+impl LifecycleHook for ShopPlugin {
+    async fn on_activate(&self, ctx: &ActivationContext) -> Result<(), HookError> {
+        tracing::info!("Activating RustPress Shop plugin");
+
+        // Run migrations
+        Self::migrations()
+            .up(&ctx.db, None, false)
+            .await
+            .map_err(|e| HookError::Migration(e.to_string()))?;
+
+        // Load configuration
+        let config = self.load_config(&ctx.settings).await?;
+        *self.config.write().await = config.clone();
+
+        if config.stripe_secret_key.is_some() && config.stripe_webhook_secret.is_none() {
+            tracing::warn!("Stripe is configured without a webhook secret; orders will never be marked paid");
+        }
+
+        // Initialize services
+        let catalog = Arc::new(CatalogService::new(ctx.db.clone()));
+        let carts = Arc::new(CartService::new(ctx.db.clone(), config.clone()));
+        let orders = Arc::new(OrderService::new(ctx.db.clone(), config, ctx.hooks.clone()));
+
+        *self.catalog_service.write().await = Some(catalog);
+        *self.cart_service.write().await = Some(carts);
+        *self.order_service.write().await = Some(orders);
+
+        // Log every status change; other plugins hook the same action to
+        // send receipts, ship orders, ...
+        ctx.hooks
+            .add_action(ORDER_STATUS_CHANGED, hooks::log_order_status_change, 0)
+            .await;
+
+        // Register routes under /api/v1/<plugin-id>; fails when another
+        // plugin or the app already serves one of the paths
+        let routes = rustpress_auth::routes::shared()
+            .mount(api::create_routes(self))
+            .map_err(|e| HookError::InvalidData(e.to_string()))?;
+        ctx.register_routes(routes).await?;
+
+        *self.state.write().await = PluginState::Active;
+        tracing::info!("RustPress Shop activated successfully");
+        Ok(())
+    }
+
+    async fn on_deactivate(&self, ctx: &DeactivationContext) -> Result<(), HookError> {
+        tracing::info!("Deactivating RustPress Shop");
+
+        // Clear services
+        *self.catalog_service.write().await = None;
+        *self.cart_service.write().await = None;
+        *self.order_service.write().await = None;
+
+        // Unregister routes
+        rustpress_auth::routes::shared().unregister(&self.info.id);
+        ctx.unregister_routes().await?;
+
+        *self.state.write().await = PluginState::Inactive;
+        Ok(())
+    }
+
+    async fn on_upgrade(&self, ctx: &UpgradeContext) -> Result<(), HookError> {
+        tracing::info!("Upgrading Shop from {} to {}", ctx.from_version, ctx.to_version);
+
+        Self::migrations()
+            .up(&ctx.db, None, false)
+            .await
+            .map_err(|e| HookError::Migration(e.to_string()))?;
+        Ok(())
+    }
+
+    async fn on_uninstall(&self, ctx: &UninstallContext) -> Result<(), HookError> {
+        tracing::info!("Uninstalling RustPress Shop");
+
+        // Revert every migration, which also clears the ledger entries
+        Self::migrations()
+            .down(&ctx.db, 0, false)
+            .await
+            .map_err(|e| HookError::Migration(e.to_string()))?;
+
+        // Remove settings
+        ctx.settings.remove_all("rustpress-shop").await?;
+
+        tracing::info!("RustPress Shop uninstalled");
+        Ok(())
+    }
+}
+
+// ============================================
+// Plugin Entry Point
+// ============================================
+
+rustpress_plugin!(ShopPlugin);
@@ -0,0 +1,275 @@
+//! Shop Data Models
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+use validator::Validate;
+
+// ============================================
+// Catalog
+// ============================================
+
+/// A product; what is bought is one of its variants
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct Product {
+    pub id: Uuid,
+    pub name: String,
+    pub slug: String,
+    pub description: Option<String>,
+    pub image_url: Option<String>,
+    pub active: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A purchasable variant (size, colour, ...) with its own price and stock
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct Variant {
+    pub id: Uuid,
+    pub product_id: Uuid,
+    pub sku: String,
+    pub name: String,
+    /// Price in the smallest currency unit
+    pub price_cents: i64,
+    pub stock: i32,
+    pub active: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Product with its variants
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ProductWithVariants {
+    #[serde(flatten)]
+    pub product: Product,
+    pub variants: Vec<Variant>,
+}
+
+#[derive(Debug, Clone, Deserialize, Validate, ToSchema)]
+pub struct ProductInput {
+    #[validate(length(min = 1, max = 200))]
+    pub name: String,
+    #[validate(length(max = 10000))]
+    pub description: Option<String>,
+    #[validate(url)]
+    pub image_url: Option<String>,
+    pub active: Option<bool>,
+}
+
+#[derive(Debug, Clone, Deserialize, Validate, ToSchema)]
+pub struct VariantInput {
+    #[validate(length(min = 1, max = 64))]
+    pub sku: String,
+    #[validate(length(min = 1, max = 200))]
+    pub name: String,
+    #[validate(range(min = 0))]
+    pub price_cents: i64,
+    #[validate(range(min = 0))]
+    pub stock: i32,
+    pub active: Option<bool>,
+}
+
+/// Catalog listing parameters
+#[derive(Debug, Clone, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ProductQuery {
+    /// Include inactive products (shop managers only)
+    pub include_inactive: Option<bool>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+// ============================================
+// Cart
+// ============================================
+
+/// Line in a cart, priced at the variant's current price
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct CartItem {
+    pub variant_id: Uuid,
+    pub product_name: String,
+    pub variant_name: String,
+    pub sku: String,
+    pub unit_price_cents: i64,
+    pub quantity: i32,
+    /// Units currently in stock
+    pub stock: i32,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Cart {
+    pub id: Uuid,
+    pub items: Vec<CartItem>,
+    pub currency: String,
+    pub total_cents: i64,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Deserialize, Validate, ToSchema)]
+pub struct CartItemInput {
+    pub variant_id: Uuid,
+    #[validate(range(min = 1, max = 100))]
+    pub quantity: i32,
+}
+
+#[derive(Debug, Clone, Deserialize, Validate, ToSchema)]
+pub struct CartQuantityInput {
+    /// 0 removes the line
+    #[validate(range(min = 0, max = 100))]
+    pub quantity: i32,
+}
+
+// ============================================
+// Orders
+// ============================================
+
+/// Order lifecycle
+///
+/// `pending` → `paid` (payment confirmed by Stripe) → `fulfilled`; pending
+/// orders can be `cancelled`, paid ones `refunded`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type, ToSchema)]
+#[sqlx(type_name = "shop_order_status", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum OrderStatus {
+    Pending,
+    Paid,
+    Fulfilled,
+    Cancelled,
+    Refunded,
+}
+
+impl OrderStatus {
+    /// Whether an order may move from `self` to `next`
+    pub fn can_transition_to(self, next: OrderStatus) -> bool {
+        use OrderStatus::*;
+        matches!(
+            (self, next),
+            (Pending, Paid) | (Pending, Cancelled) | (Paid, Fulfilled) | (Paid, Refunded) | (Fulfilled, Refunded)
+        )
+    }
+
+    /// Whether the order's stock is back on the shelves after this status
+    pub fn releases_stock(self) -> bool {
+        matches!(self, OrderStatus::Cancelled | OrderStatus::Refunded)
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            OrderStatus::Pending => "pending",
+            OrderStatus::Paid => "paid",
+            OrderStatus::Fulfilled => "fulfilled",
+            OrderStatus::Cancelled => "cancelled",
+            OrderStatus::Refunded => "refunded",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct Order {
+    pub id: Uuid,
+    pub user_id: Option<Uuid>,
+    pub email: String,
+    pub status: OrderStatus,
+    pub currency: String,
+    pub total_cents: i64,
+    pub payment_intent_id: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct OrderItem {
+    pub id: Uuid,
+    pub variant_id: Option<Uuid>,
+    pub product_name: String,
+    pub variant_name: String,
+    pub sku: String,
+    pub unit_price_cents: i64,
+    pub quantity: i32,
+}
+
+/// Status history entry
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct OrderEvent {
+    pub from_status: Option<OrderStatus>,
+    pub to_status: OrderStatus,
+    /// User ID, `stripe` or `system`
+    pub actor: String,
+    pub note: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct OrderDetail {
+    #[serde(flatten)]
+    pub order: Order,
+    pub items: Vec<OrderItem>,
+    pub history: Vec<OrderEvent>,
+}
+
+#[derive(Debug, Clone, Deserialize, Validate, ToSchema)]
+pub struct CheckoutInput {
+    #[validate(email)]
+    pub email: String,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct CheckoutResponse {
+    pub order: OrderDetail,
+    /// Passed to Stripe.js to confirm the payment in the browser
+    pub client_secret: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct StatusInput {
+    pub status: OrderStatus,
+    pub note: Option<String>,
+}
+
+/// Order listing parameters
+#[derive(Debug, Clone, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct OrderQuery {
+    pub status: Option<OrderStatus>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+/// Payload of the `shop_order_status_changed` action
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrderStatusChanged {
+    pub order_id: Uuid,
+    pub from: OrderStatus,
+    pub to: OrderStatus,
+    pub actor: String,
+}
+
+// ============================================
+// Responses
+// ============================================
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ListResponse<T> {
+    pub data: Vec<T>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub count: Option<usize>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OrderStatus::*;
+
+    #[test]
+    fn test_order_transitions() {
+        assert!(Pending.can_transition_to(Paid));
+        assert!(Pending.can_transition_to(Cancelled));
+        assert!(Paid.can_transition_to(Fulfilled));
+        assert!(Fulfilled.can_transition_to(Refunded));
+
+        assert!(!Pending.can_transition_to(Fulfilled));
+        assert!(!Cancelled.can_transition_to(Paid));
+        assert!(!Refunded.can_transition_to(Paid));
+        assert!(!Paid.can_transition_to(Pending));
+    }
+}
@@ -0,0 +1,638 @@
+//! Shop Services
+
+pub mod stripe;
+
+use crate::models::*;
+use crate::ShopConfig;
+use chrono::{Duration, Utc};
+use rustpress_plugins::prelude::*;
+use sqlx::PgPool;
+use std::sync::Arc;
+use stripe::{StripeClient, StripeEvent};
+use uuid::Uuid;
+
+/// Fired after every order status change with an [`OrderStatusChanged`]
+pub const ORDER_STATUS_CHANGED: &str = "shop_order_status_changed";
+
+// ============================================
+// Catalog Service
+// ============================================
+
+pub struct CatalogService {
+    db: PgPool,
+}
+
+impl CatalogService {
+    pub fn new(db: PgPool) -> Self {
+        Self { db }
+    }
+
+    pub async fn list(&self, query: &ProductQuery) -> Result<Vec<ProductWithVariants>, ShopError> {
+        let products: Vec<Product> = sqlx::query_as(
+            "SELECT * FROM shop_products WHERE active OR $1 ORDER BY name LIMIT $2 OFFSET $3",
+        )
+        .bind(query.include_inactive.unwrap_or(false))
+        .bind(query.limit.unwrap_or(50).min(200))
+        .bind(query.offset.unwrap_or(0))
+        .fetch_all(&self.db)
+        .await?;
+
+        let ids: Vec<Uuid> = products.iter().map(|p| p.id).collect();
+        let mut variants: Vec<Variant> = sqlx::query_as(
+            "SELECT * FROM shop_variants WHERE product_id = ANY($1) ORDER BY price_cents, name",
+        )
+        .bind(&ids)
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(products
+            .into_iter()
+            .map(|product| {
+                let (own, rest): (Vec<Variant>, Vec<Variant>) =
+                    variants.drain(..).partition(|v| v.product_id == product.id);
+                variants = rest;
+                ProductWithVariants { product, variants: own }
+            })
+            .collect())
+    }
+
+    /// Active product by slug, with its active variants
+    pub async fn get_by_slug(&self, slug: &str) -> Result<ProductWithVariants, ShopError> {
+        let product: Product = sqlx::query_as("SELECT * FROM shop_products WHERE slug = $1 AND active")
+            .bind(slug)
+            .fetch_optional(&self.db)
+            .await?
+            .ok_or_else(|| ShopError::NotFound(format!("Product not found: {}", slug)))?;
+
+        let variants = sqlx::query_as(
+            "SELECT * FROM shop_variants WHERE product_id = $1 AND active ORDER BY price_cents, name",
+        )
+        .bind(product.id)
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(ProductWithVariants { product, variants })
+    }
+
+    pub async fn create_product(&self, input: &ProductInput) -> Result<Product, ShopError> {
+        let product = sqlx::query_as(
+            r#"INSERT INTO shop_products (name, slug, description, image_url, active)
+               VALUES ($1, $2, $3, $4, $5)
+               RETURNING *"#,
+        )
+        .bind(&input.name)
+        .bind(slug::slugify(&input.name))
+        .bind(&input.description)
+        .bind(&input.image_url)
+        .bind(input.active.unwrap_or(true))
+        .fetch_one(&self.db)
+        .await
+        .map_err(|e| unique_violation(e, "A product with this name already exists"))?;
+
+        Ok(product)
+    }
+
+    pub async fn update_product(&self, id: Uuid, input: &ProductInput) -> Result<Product, ShopError> {
+        sqlx::query_as(
+            r#"UPDATE shop_products
+               SET name = $2, description = $3, image_url = $4, active = COALESCE($5, active), updated_at = NOW()
+               WHERE id = $1
+               RETURNING *"#,
+        )
+        .bind(id)
+        .bind(&input.name)
+        .bind(&input.description)
+        .bind(&input.image_url)
+        .bind(input.active)
+        .fetch_optional(&self.db)
+        .await?
+        .ok_or_else(|| ShopError::NotFound(format!("Product not found: {}", id)))
+    }
+
+    pub async fn create_variant(&self, product_id: Uuid, input: &VariantInput) -> Result<Variant, ShopError> {
+        sqlx::query_as(
+            r#"INSERT INTO shop_variants (product_id, sku, name, price_cents, stock, active)
+               SELECT id, $2, $3, $4, $5, $6 FROM shop_products WHERE id = $1
+               RETURNING *"#,
+        )
+        .bind(product_id)
+        .bind(&input.sku)
+        .bind(&input.name)
+        .bind(input.price_cents)
+        .bind(input.stock)
+        .bind(input.active.unwrap_or(true))
+        .fetch_optional(&self.db)
+        .await
+        .map_err(|e| unique_violation(e, "SKU already in use"))?
+        .ok_or_else(|| ShopError::NotFound(format!("Product not found: {}", product_id)))
+    }
+
+    pub async fn update_variant(&self, id: Uuid, input: &VariantInput) -> Result<Variant, ShopError> {
+        sqlx::query_as(
+            r#"UPDATE shop_variants
+               SET sku = $2, name = $3, price_cents = $4, stock = $5, active = COALESCE($6, active)
+               WHERE id = $1
+               RETURNING *"#,
+        )
+        .bind(id)
+        .bind(&input.sku)
+        .bind(&input.name)
+        .bind(input.price_cents)
+        .bind(input.stock)
+        .bind(input.active)
+        .fetch_optional(&self.db)
+        .await
+        .map_err(|e| unique_violation(e, "SKU already in use"))?
+        .ok_or_else(|| ShopError::NotFound(format!("Variant not found: {}", id)))
+    }
+}
+
+// ============================================
+// Cart Service
+// ============================================
+
+/// Carts belong to a browser session and expire `cart_ttl_hours` after
+/// their last change. Stock is checked when items are added but only
+/// reserved at checkout.
+pub struct CartService {
+    db: PgPool,
+    config: ShopConfig,
+}
+
+impl CartService {
+    pub fn new(db: PgPool, config: ShopConfig) -> Self {
+        Self { db, config }
+    }
+
+    /// The session's cart if it exists and hasn't expired
+    pub async fn get(&self, cart_id: Uuid) -> Result<Option<Cart>, ShopError> {
+        let expires_at: Option<chrono::DateTime<Utc>> =
+            sqlx::query_scalar("SELECT expires_at FROM shop_carts WHERE id = $1 AND expires_at > NOW()")
+                .bind(cart_id)
+                .fetch_optional(&self.db)
+                .await?;
+        let Some(expires_at) = expires_at else {
+            return Ok(None);
+        };
+
+        let items: Vec<CartItem> = sqlx::query_as(
+            r#"SELECT ci.variant_id, p.name AS product_name, v.name AS variant_name, v.sku,
+                      v.price_cents AS unit_price_cents, ci.quantity, v.stock
+               FROM shop_cart_items ci
+               JOIN shop_variants v ON v.id = ci.variant_id
+               JOIN shop_products p ON p.id = v.product_id
+               WHERE ci.cart_id = $1
+               ORDER BY p.name, v.name"#,
+        )
+        .bind(cart_id)
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(Some(Cart {
+            id: cart_id,
+            total_cents: items.iter().map(|i| i.unit_price_cents * i.quantity as i64).sum(),
+            items,
+            currency: self.config.currency.to_uppercase(),
+            expires_at,
+        }))
+    }
+
+    /// The session's cart, or a new one when it has none or it expired
+    pub async fn get_or_create(&self, cart_id: Option<Uuid>) -> Result<Uuid, ShopError> {
+        let expires_at = Utc::now() + Duration::hours(self.config.cart_ttl_hours);
+
+        if let Some(cart_id) = cart_id {
+            let touched = sqlx::query("UPDATE shop_carts SET expires_at = $2, updated_at = NOW() WHERE id = $1 AND expires_at > NOW()")
+                .bind(cart_id)
+                .bind(expires_at)
+                .execute(&self.db)
+                .await?
+                .rows_affected();
+            if touched == 1 {
+                return Ok(cart_id);
+            }
+        }
+
+        let id = sqlx::query_scalar("INSERT INTO shop_carts (expires_at) VALUES ($1) RETURNING id")
+            .bind(expires_at)
+            .fetch_one(&self.db)
+            .await?;
+        Ok(id)
+    }
+
+    pub async fn add_item(&self, cart_id: Uuid, input: &CartItemInput) -> Result<(), ShopError> {
+        let in_cart: i32 = sqlx::query_scalar(
+            "SELECT COALESCE(SUM(quantity), 0)::int FROM shop_cart_items WHERE cart_id = $1 AND variant_id = $2",
+        )
+        .bind(cart_id)
+        .bind(input.variant_id)
+        .fetch_one(&self.db)
+        .await?;
+
+        self.check_stock(input.variant_id, in_cart + input.quantity).await?;
+
+        sqlx::query(
+            r#"INSERT INTO shop_cart_items (cart_id, variant_id, quantity) VALUES ($1, $2, $3)
+               ON CONFLICT (cart_id, variant_id) DO UPDATE SET quantity = shop_cart_items.quantity + EXCLUDED.quantity"#,
+        )
+        .bind(cart_id)
+        .bind(input.variant_id)
+        .bind(input.quantity)
+        .execute(&self.db)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Set a line's quantity; 0 removes it
+    pub async fn set_quantity(&self, cart_id: Uuid, variant_id: Uuid, quantity: i32) -> Result<(), ShopError> {
+        if quantity == 0 {
+            sqlx::query("DELETE FROM shop_cart_items WHERE cart_id = $1 AND variant_id = $2")
+                .bind(cart_id)
+                .bind(variant_id)
+                .execute(&self.db)
+                .await?;
+            return Ok(());
+        }
+
+        self.check_stock(variant_id, quantity).await?;
+
+        let updated = sqlx::query("UPDATE shop_cart_items SET quantity = $3 WHERE cart_id = $1 AND variant_id = $2")
+            .bind(cart_id)
+            .bind(variant_id)
+            .bind(quantity)
+            .execute(&self.db)
+            .await?
+            .rows_affected();
+        if updated == 0 {
+            return Err(ShopError::NotFound("Item is not in the cart".into()));
+        }
+
+        Ok(())
+    }
+
+    /// Delete expired carts; returns how many were removed
+    pub async fn delete_expired(&self) -> Result<u64, ShopError> {
+        let deleted = sqlx::query("DELETE FROM shop_carts WHERE expires_at <= NOW()")
+            .execute(&self.db)
+            .await?
+            .rows_affected();
+        Ok(deleted)
+    }
+
+    async fn check_stock(&self, variant_id: Uuid, quantity: i32) -> Result<(), ShopError> {
+        let variant: Variant = sqlx::query_as("SELECT * FROM shop_variants WHERE id = $1 AND active")
+            .bind(variant_id)
+            .fetch_optional(&self.db)
+            .await?
+            .ok_or_else(|| ShopError::NotFound(format!("Variant not found: {}", variant_id)))?;
+
+        if variant.stock < quantity {
+            return Err(ShopError::OutOfStock(variant.sku));
+        }
+        Ok(())
+    }
+}
+
+// ============================================
+// Order Service
+// ============================================
+
+/// Checkout and the order lifecycle
+///
+/// Every status change goes through [`OrderService::transition`], which
+/// enforces [`OrderStatus::can_transition_to`], records the change in
+/// `shop_order_events` and fires [`ORDER_STATUS_CHANGED`].
+pub struct OrderService {
+    db: PgPool,
+    config: ShopConfig,
+    hooks: Arc<HookRegistry>,
+    stripe: Option<StripeClient>,
+}
+
+impl OrderService {
+    pub fn new(db: PgPool, config: ShopConfig, hooks: Arc<HookRegistry>) -> Self {
+        let stripe = config.stripe_secret_key.clone().map(StripeClient::new);
+        Self { db, config, hooks, stripe }
+    }
+
+    /// Turn a cart into a pending order
+    ///
+    /// Stock is taken in the same transaction as the order is created, so two
+    /// shoppers can't buy the last unit twice. The cart is emptied on
+    /// success. When Stripe is configured the order gets a PaymentIntent
+    /// whose client secret is returned for the browser to confirm.
+    pub async fn checkout(
+        &self,
+        cart_id: Uuid,
+        user_id: Option<Uuid>,
+        input: &CheckoutInput,
+    ) -> Result<CheckoutResponse, ShopError> {
+        let mut tx = self.db.begin().await?;
+
+        let items: Vec<CartItem> = sqlx::query_as(
+            r#"SELECT ci.variant_id, p.name AS product_name, v.name AS variant_name, v.sku,
+                      v.price_cents AS unit_price_cents, ci.quantity, v.stock
+               FROM shop_cart_items ci
+               JOIN shop_carts c ON c.id = ci.cart_id AND c.expires_at > NOW()
+               JOIN shop_variants v ON v.id = ci.variant_id AND v.active
+               JOIN shop_products p ON p.id = v.product_id AND p.active
+               WHERE ci.cart_id = $1
+               ORDER BY v.id
+               FOR UPDATE OF v"#,
+        )
+        .bind(cart_id)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        if items.is_empty() {
+            return Err(ShopError::EmptyCart);
+        }
+
+        for item in &items {
+            if item.stock < item.quantity {
+                return Err(ShopError::OutOfStock(item.sku.clone()));
+            }
+            sqlx::query("UPDATE shop_variants SET stock = stock - $2 WHERE id = $1")
+                .bind(item.variant_id)
+                .bind(item.quantity)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        let total: i64 = items.iter().map(|i| i.unit_price_cents * i.quantity as i64).sum();
+        let order: Order = sqlx::query_as(
+            r#"INSERT INTO shop_orders (user_id, email, currency, total_cents)
+               VALUES ($1, $2, $3, $4)
+               RETURNING *"#,
+        )
+        .bind(user_id)
+        .bind(&input.email)
+        .bind(self.config.currency.to_uppercase())
+        .bind(total)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        for item in &items {
+            sqlx::query(
+                r#"INSERT INTO shop_order_items
+                   (order_id, variant_id, product_name, variant_name, sku, unit_price_cents, quantity)
+                   VALUES ($1, $2, $3, $4, $5, $6, $7)"#,
+            )
+            .bind(order.id)
+            .bind(item.variant_id)
+            .bind(&item.product_name)
+            .bind(&item.variant_name)
+            .bind(&item.sku)
+            .bind(item.unit_price_cents)
+            .bind(item.quantity)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        let actor = user_id.map(|id| id.to_string()).unwrap_or_else(|| "guest".into());
+        sqlx::query("INSERT INTO shop_order_events (order_id, to_status, actor) VALUES ($1, 'pending', $2)")
+            .bind(order.id)
+            .bind(&actor)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("DELETE FROM shop_cart_items WHERE cart_id = $1")
+            .bind(cart_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        let client_secret = match &self.stripe {
+            Some(stripe) => match stripe.create_payment_intent(&order).await {
+                Ok(intent) => {
+                    sqlx::query("UPDATE shop_orders SET payment_intent_id = $2 WHERE id = $1")
+                        .bind(order.id)
+                        .bind(&intent.id)
+                        .execute(&self.db)
+                        .await?;
+                    intent.client_secret
+                }
+                Err(e) => {
+                    // Give the stock back rather than leave an unpayable order
+                    tracing::error!(order_id = %order.id, "Failed to create payment intent: {}", e);
+                    self.transition(order.id, OrderStatus::Cancelled, "system", Some("Payment could not be started"))
+                        .await?;
+                    return Err(ShopError::Payment(e.to_string()));
+                }
+            },
+            None => None,
+        };
+
+        Ok(CheckoutResponse {
+            order: self.get(order.id).await?,
+            client_secret,
+        })
+    }
+
+    pub async fn get(&self, id: Uuid) -> Result<OrderDetail, ShopError> {
+        let order: Order = sqlx::query_as("SELECT * FROM shop_orders WHERE id = $1")
+            .bind(id)
+            .fetch_optional(&self.db)
+            .await?
+            .ok_or_else(|| ShopError::NotFound(format!("Order not found: {}", id)))?;
+
+        let items = sqlx::query_as(
+            r#"SELECT id, variant_id, product_name, variant_name, sku, unit_price_cents, quantity
+               FROM shop_order_items WHERE order_id = $1 ORDER BY product_name, variant_name"#,
+        )
+        .bind(id)
+        .fetch_all(&self.db)
+        .await?;
+
+        let history = sqlx::query_as(
+            r#"SELECT from_status, to_status, actor, note, created_at
+               FROM shop_order_events WHERE order_id = $1 ORDER BY id"#,
+        )
+        .bind(id)
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(OrderDetail { order, items, history })
+    }
+
+    pub async fn list(&self, query: &OrderQuery) -> Result<Vec<Order>, ShopError> {
+        let orders = sqlx::query_as(
+            r#"SELECT * FROM shop_orders
+               WHERE $1::shop_order_status IS NULL OR status = $1
+               ORDER BY created_at DESC
+               LIMIT $2 OFFSET $3"#,
+        )
+        .bind(query.status)
+        .bind(query.limit.unwrap_or(50).min(200))
+        .bind(query.offset.unwrap_or(0))
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(orders)
+    }
+
+    /// Move an order to `to`
+    ///
+    /// Cancelling, or refunding an order that hasn't shipped, returns its
+    /// units to stock. Moving an order to the status it already has is a
+    /// no-op, so repeated webhook deliveries are harmless.
+    pub async fn transition(
+        &self,
+        id: Uuid,
+        to: OrderStatus,
+        actor: &str,
+        note: Option<&str>,
+    ) -> Result<Order, ShopError> {
+        let mut tx = self.db.begin().await?;
+
+        let current: Order = sqlx::query_as("SELECT * FROM shop_orders WHERE id = $1 FOR UPDATE")
+            .bind(id)
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or_else(|| ShopError::NotFound(format!("Order not found: {}", id)))?;
+
+        if current.status == to {
+            return Ok(current);
+        }
+        if !current.status.can_transition_to(to) {
+            return Err(ShopError::InvalidTransition { from: current.status, to });
+        }
+
+        let order: Order = sqlx::query_as("UPDATE shop_orders SET status = $2, updated_at = NOW() WHERE id = $1 RETURNING *")
+            .bind(id)
+            .bind(to)
+            .fetch_one(&mut *tx)
+            .await?;
+
+        sqlx::query(
+            "INSERT INTO shop_order_events (order_id, from_status, to_status, actor, note) VALUES ($1, $2, $3, $4, $5)",
+        )
+        .bind(id)
+        .bind(current.status)
+        .bind(to)
+        .bind(actor)
+        .bind(note)
+        .execute(&mut *tx)
+        .await?;
+
+        if to.releases_stock() && current.status != OrderStatus::Fulfilled {
+            sqlx::query(
+                r#"UPDATE shop_variants v SET stock = v.stock + i.quantity
+                   FROM shop_order_items i
+                   WHERE i.order_id = $1 AND i.variant_id = v.id"#,
+            )
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        let change = OrderStatusChanged {
+            order_id: id,
+            from: current.status,
+            to,
+            actor: actor.to_string(),
+        };
+        // Listeners can't undo the change; failures are only logged
+        let mut ctx = ActionContext::default();
+        if let Some(id) = rustpress_auth::problem::current_trace_id() {
+            ctx.request_id = id;
+        }
+        if let Err(e) = self.hooks.do_action(ORDER_STATUS_CHANGED, &ctx, change).await {
+            tracing::warn!(order_id = %id, "{} listener failed: {}", ORDER_STATUS_CHANGED, e);
+        }
+
+        Ok(order)
+    }
+
+    /// Apply a verified Stripe webhook event
+    ///
+    /// `payment_intent.succeeded` marks the order paid and `charge.refunded`
+    /// refunded; other events are acknowledged and ignored.
+    pub async fn handle_stripe_event(&self, event: &StripeEvent) -> Result<(), ShopError> {
+        let seen: bool = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM shop_webhook_events WHERE event_id = $1)")
+            .bind(&event.id)
+            .fetch_one(&self.db)
+            .await?;
+        if seen {
+            return Ok(());
+        }
+
+        let target = match event.event_type.as_str() {
+            "payment_intent.succeeded" => Some((OrderStatus::Paid, "Payment confirmed")),
+            "charge.refunded" => Some((OrderStatus::Refunded, "Refunded in Stripe")),
+            "payment_intent.payment_failed" => {
+                tracing::info!(event_id = %event.id, "Payment attempt failed; order stays pending");
+                None
+            }
+            _ => None,
+        };
+
+        if let (Some((status, note)), Some(intent_id)) = (target, event.payment_intent_id()) {
+            let order_id: Uuid = sqlx::query_scalar("SELECT id FROM shop_orders WHERE payment_intent_id = $1")
+                .bind(intent_id)
+                .fetch_optional(&self.db)
+                .await?
+                .ok_or_else(|| ShopError::NotFound(format!("No order for payment intent {}", intent_id)))?;
+            self.transition(order_id, status, "stripe", Some(note)).await?;
+        }
+
+        // Recorded only once handled, so a failure is retried by Stripe
+        sqlx::query("INSERT INTO shop_webhook_events (event_id, event_type) VALUES ($1, $2) ON CONFLICT DO NOTHING")
+            .bind(&event.id)
+            .bind(&event.event_type)
+            .execute(&self.db)
+            .await?;
+
+        Ok(())
+    }
+
+    /// IDs of pending orders older than `pending_order_ttl_hours`
+    pub async fn abandoned(&self) -> Result<Vec<Uuid>, ShopError> {
+        let cutoff = Utc::now() - Duration::hours(self.config.pending_order_ttl_hours);
+        let ids = sqlx::query_scalar("SELECT id FROM shop_orders WHERE status = 'pending' AND created_at < $1")
+            .bind(cutoff)
+            .fetch_all(&self.db)
+            .await?;
+        Ok(ids)
+    }
+}
+
+// ============================================
+// Error Types
+// ============================================
+
+#[derive(Debug, thiserror::Error)]
+pub enum ShopError {
+    #[error("{0}")]
+    NotFound(String),
+    #[error("{0}")]
+    Conflict(String),
+    #[error("The cart is empty")]
+    EmptyCart,
+    #[error("Not enough stock for {0}")]
+    OutOfStock(String),
+    #[error("Cannot move an order from {} to {}", from.as_str(), to.as_str())]
+    InvalidTransition { from: OrderStatus, to: OrderStatus },
+    #[error("Payment error: {0}")]
+    Payment(String),
+    #[error("Database error: {0}")]
+    Database(String),
+}
+
+impl From<sqlx::Error> for ShopError {
+    fn from(e: sqlx::Error) -> Self {
+        ShopError::Database(e.to_string())
+    }
+}
+
+/// Map unique constraint violations to a conflict with `message`
+fn unique_violation(e: sqlx::Error, message: &str) -> ShopError {
+    match &e {
+        sqlx::Error::Database(db) if db.is_unique_violation() => ShopError::Conflict(message.to_string()),
+        _ => e.into(),
+    }
+}
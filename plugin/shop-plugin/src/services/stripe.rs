@@ -0,0 +1,201 @@
+//! Stripe Integration
+//!
+//! Checkout creates a PaymentIntent for the order total; the browser confirms
+//! it with Stripe.js and Stripe reports the outcome to the webhook endpoint.
+//! Webhook payloads are only trusted once their `Stripe-Signature` (an
+//! HMAC-SHA256 of `"{timestamp}.{body}"` keyed with the endpoint secret) has
+//! been verified.
+
+use crate::models::Order;
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::Sha256;
+
+const API_BASE: &str = "https://api.stripe.com/v1";
+
+/// Oldest signature timestamp accepted, in seconds, to limit replays
+pub const SIGNATURE_TOLERANCE_SECS: i64 = 300;
+
+pub struct StripeClient {
+    http: reqwest::Client,
+    secret_key: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PaymentIntent {
+    pub id: String,
+    pub client_secret: Option<String>,
+}
+
+/// Webhook event envelope
+#[derive(Debug, Clone, Deserialize)]
+pub struct StripeEvent {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub event_type: String,
+    pub data: StripeEventData,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct StripeEventData {
+    /// The PaymentIntent, Charge, ... the event is about
+    pub object: serde_json::Value,
+}
+
+impl StripeEvent {
+    /// PaymentIntent the event refers to: the object itself for
+    /// `payment_intent.*` events, its `payment_intent` field for charges
+    pub fn payment_intent_id(&self) -> Option<&str> {
+        let object = &self.data.object;
+        if object["object"] == "payment_intent" {
+            object["id"].as_str()
+        } else {
+            object["payment_intent"].as_str()
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum StripeError {
+    #[error("Stripe request failed: {0}")]
+    Request(#[from] reqwest::Error),
+    #[error("Stripe returned {status}: {message}")]
+    Api { status: u16, message: String },
+}
+
+impl StripeClient {
+    pub fn new(secret_key: String) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            secret_key,
+        }
+    }
+
+    /// Create the PaymentIntent for an order
+    ///
+    /// The order ID is the idempotency key, so retrying a checkout never
+    /// creates a second charge.
+    pub async fn create_payment_intent(&self, order: &Order) -> Result<PaymentIntent, StripeError> {
+        let order_id = order.id.to_string();
+        let amount = order.total_cents.to_string();
+        let currency = order.currency.to_lowercase();
+        let params = [
+            ("amount", amount.as_str()),
+            ("currency", currency.as_str()),
+            ("receipt_email", order.email.as_str()),
+            ("metadata[order_id]", order_id.as_str()),
+            ("automatic_payment_methods[enabled]", "true"),
+        ];
+
+        let response = self
+            .http
+            .post(format!("{}/payment_intents", API_BASE))
+            .basic_auth(&self.secret_key, None::<&str>)
+            .header("Idempotency-Key", &order_id)
+            .form(&params)
+            .send()
+            .await?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let body: serde_json::Value = response.json().await.unwrap_or_default();
+            return Err(StripeError::Api {
+                status: status.as_u16(),
+                message: body["error"]["message"].as_str().unwrap_or("unknown error").to_string(),
+            });
+        }
+
+        Ok(response.json().await?)
+    }
+}
+
+/// Check a `Stripe-Signature` header (`t=<unix>,v1=<hex>[,v1=...]`) against
+/// the raw request body
+pub fn verify_signature(payload: &[u8], header: &str, secret: &str, now: i64) -> bool {
+    let mut timestamp = None;
+    let mut signatures = Vec::new();
+    for part in header.split(',') {
+        match part.split_once('=') {
+            Some(("t", value)) => timestamp = value.parse::<i64>().ok(),
+            Some(("v1", value)) => signatures.push(value),
+            _ => {}
+        }
+    }
+
+    let Some(timestamp) = timestamp else {
+        return false;
+    };
+    if (now - timestamp).abs() > SIGNATURE_TOLERANCE_SECS {
+        return false;
+    }
+
+    signatures.into_iter().any(|signature| {
+        let Ok(expected) = hex::decode(signature) else {
+            return false;
+        };
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+        mac.update(timestamp.to_string().as_bytes());
+        mac.update(b".");
+        mac.update(payload);
+        mac.verify_slice(&expected).is_ok()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sign(payload: &[u8], secret: &str, timestamp: i64) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(format!("{}.", timestamp).as_bytes());
+        mac.update(payload);
+        format!("t={},v1={}", timestamp, hex::encode(mac.finalize().into_bytes()))
+    }
+
+    #[test]
+    fn test_verify_signature() {
+        let payload = br#"{"id":"evt_1","type":"payment_intent.succeeded"}"#;
+        let header = sign(payload, "whsec_test", 1_700_000_000);
+
+        assert!(verify_signature(payload, &header, "whsec_test", 1_700_000_010));
+        assert!(!verify_signature(payload, &header, "whsec_other", 1_700_000_010));
+        assert!(!verify_signature(b"{}", &header, "whsec_test", 1_700_000_010));
+    }
+
+    #[test]
+    fn test_verify_signature_rejects_stale_timestamps() {
+        let payload = b"{}";
+        let header = sign(payload, "whsec_test", 1_700_000_000);
+
+        assert!(!verify_signature(payload, &header, "whsec_test", 1_700_000_000 + SIGNATURE_TOLERANCE_SECS + 1));
+    }
+
+    #[test]
+    fn test_verify_signature_accepts_any_v1() {
+        let payload = b"{}";
+        let valid = sign(payload, "whsec_test", 1_700_000_000);
+        let header = format!("{},v1={}", valid.replace("v1=", "v1=00,v1="), "ff");
+
+        assert!(verify_signature(payload, &header, "whsec_test", 1_700_000_000));
+        assert!(!verify_signature(payload, "v1=00", "whsec_test", 1_700_000_000));
+    }
+
+    #[test]
+    fn test_payment_intent_id() {
+        let event: StripeEvent = serde_json::from_value(serde_json::json!({
+            "id": "evt_1",
+            "type": "charge.refunded",
+            "data": { "object": { "object": "charge", "id": "ch_1", "payment_intent": "pi_1" } }
+        }))
+        .unwrap();
+        assert_eq!(event.payment_intent_id(), Some("pi_1"));
+
+        let event: StripeEvent = serde_json::from_value(serde_json::json!({
+            "id": "evt_2",
+            "type": "payment_intent.succeeded",
+            "data": { "object": { "object": "payment_intent", "id": "pi_2" } }
+        }))
+        .unwrap();
+        assert_eq!(event.payment_intent_id(), Some("pi_2"));
+    }
+}
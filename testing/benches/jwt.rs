@@ -0,0 +1,42 @@
+//! Access token validation, directly and through the auth middleware
+
+use axum::{http::StatusCode, middleware, routing::get, Router};
+use criterion::Criterion;
+use rustpress_auth::{AuthPlugin, UserRole};
+use rustpress_testing::{bench, TestClient, TestEnv, TestUser};
+
+fn bench_jwt(c: &mut Criterion, runtime: &tokio::runtime::Runtime, env: &TestEnv) {
+    let (auth, user) = runtime.block_on(async {
+        env.migrate(&AuthPlugin::migrations()).await;
+        let auth = env.auth_service().await;
+        let user = TestUser::create(&auth, "bench@example.com", UserRole::Author).await;
+        (auth, user)
+    });
+
+    let router = Router::new()
+        .route("/me", get(|| async { StatusCode::NO_CONTENT }))
+        .layer(middleware::from_fn(rustpress_auth::middleware::require_auth));
+    let client = TestClient::new(router).authenticated(&user);
+
+    let mut group = c.benchmark_group("jwt");
+    group.bench_function("validate_access_token", |b| {
+        b.iter(|| auth.validate_access_token(&user.access_token).unwrap())
+    });
+    group.bench_function("require_auth", |b| {
+        b.to_async(runtime).iter(|| async {
+            assert_eq!(client.get("/me").await.status, StatusCode::NO_CONTENT);
+        })
+    });
+    group.finish();
+}
+
+fn main() {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let env = runtime.block_on(TestEnv::start());
+
+    let mut criterion = Criterion::default().configure_from_args();
+    bench_jwt(&mut criterion, &runtime, &env);
+    criterion.final_summary();
+
+    bench::report("jwt");
+}
@@ -0,0 +1,86 @@
+//! Published post listing of the blog, from Redis and from the database
+
+use criterion::Criterion;
+use rustpress_apps::prelude::HookRegistry;
+use rustpress_auth::db::DbPools;
+use rustpress_auth::{AuthPlugin, UserRole};
+use rustpress_blog_api::access::{ContentAccess, Viewer};
+use rustpress_blog_api::activity::ContentHooks;
+use rustpress_blog_api::cache::{self, Cache, CacheConfig, CacheDriver};
+use rustpress_blog_api::digests::{DigestConfig, DigestService};
+use rustpress_blog_api::mentions::{MentionConfig, MentionService};
+use rustpress_blog_api::models::PostQuery;
+use rustpress_blog_api::services::PostService;
+use rustpress_blog_api::sites::SiteService;
+use rustpress_blog_api::BlogApp;
+use rustpress_testing::{bench, TestEnv, TestUser};
+use serde_json::{from_value, json};
+use std::sync::Arc;
+
+/// Published posts, one page of them listed
+const POSTS: usize = 50;
+const PAGE: usize = 20;
+
+fn bench_posts(c: &mut Criterion, runtime: &tokio::runtime::Runtime, env: &TestEnv) {
+    let (posts, cache, site) = runtime.block_on(async {
+        env.migrate(&AuthPlugin::migrations()).await;
+        BlogApp::migrations().run(&env.db).await.expect("blog migrations failed");
+
+        let auth = env.auth_service().await;
+        let author = TestUser::create(&auth, "bench@example.com", UserRole::Author).await;
+
+        let cache = cache::connect(&CacheConfig {
+            driver: CacheDriver::Redis,
+            redis_url: env.redis_url().to_string(),
+            ..Default::default()
+        })
+        .await
+        .expect("failed to connect to Redis");
+        let pools = Arc::new(DbPools::new(env.db.clone()));
+        let access = Arc::new(ContentAccess::new(pools.clone(), Arc::new(HookRegistry::new())));
+        let digests = Arc::new(DigestService::new(env.db.clone(), DigestConfig::default()));
+        let mentions = Arc::new(MentionService::new(env.db.clone(), digests, MentionConfig::default()));
+        let posts = PostService::new(pools, cache.clone(), Arc::new(ContentHooks::default()), access, mentions, 200);
+
+        let sites = SiteService::load(env.db.clone()).await.unwrap();
+        let (site, _) = sites.resolve(None, "/").await.expect("no default site");
+
+        for i in 0..POSTS {
+            let request = json!({ "title": format!("Benchmark post {}", i), "content": "Lorem ipsum ".repeat(200) });
+            let post = posts.create(&site, author.user.id, from_value(request).unwrap(), None).await.unwrap();
+            posts.publish(&site, post.id, author.user.id).await.unwrap();
+        }
+        (posts, cache, site)
+    });
+
+    let query: PostQuery = from_value(json!({ "per_page": PAGE })).unwrap();
+    let viewer = Viewer::anonymous();
+
+    let mut group = c.benchmark_group("posts");
+    group.bench_function("list_cached", |b| {
+        b.to_async(runtime).iter(|| async {
+            let page = posts.list_published(&site, &query, &viewer).await.unwrap();
+            assert_eq!(page.data.len(), PAGE);
+        })
+    });
+    // Includes the invalidation, which is one Redis script call
+    group.bench_function("list_uncached", |b| {
+        b.to_async(runtime).iter(|| async {
+            cache.delete_pattern(&site.cache_key("posts:*")).await;
+            let page = posts.list_published(&site, &query, &viewer).await.unwrap();
+            assert_eq!(page.data.len(), PAGE);
+        })
+    });
+    group.finish();
+}
+
+fn main() {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let env = runtime.block_on(TestEnv::with_redis());
+
+    let mut criterion = Criterion::default().configure_from_args();
+    bench_posts(&mut criterion, &runtime, &env);
+    criterion.final_summary();
+
+    bench::report("posts");
+}
@@ -0,0 +1,75 @@
+//! Tracking and backend ingestion of the analytics plugin
+
+use chrono::Utc;
+use criterion::Criterion;
+use rustpress_analytics::models::{CollectEvent, TrackingInput};
+use rustpress_analytics::services::{GeoIp, IngestMetrics, IngestService, TrackingService};
+use rustpress_analytics::{AnalyticsConfig, AnalyticsPlugin};
+use rustpress_auth::secrets::StaticSecrets;
+use rustpress_auth::MetricsRegistry;
+use rustpress_testing::{bench, TestEnv};
+use serde_json::{from_value, json};
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Events in a `/collect` batch
+const BATCH: usize = 100;
+
+const USER_AGENT: &str = "Mozilla/5.0 (X11; Linux x86_64) Firefox/130.0";
+
+fn bench_tracking(c: &mut Criterion, runtime: &tokio::runtime::Runtime, env: &TestEnv) {
+    runtime.block_on(env.migrate(&AnalyticsPlugin::migrations()));
+
+    let config = AnalyticsConfig::default();
+    let metrics = IngestMetrics::register(&Arc::new(MetricsRegistry::default()).plugin("rustpress-analytics")).unwrap();
+    let tracking = TrackingService::new(env.db.clone(), config.clone(), Arc::new(GeoIp::open()), metrics.clone(), None);
+    let ingest = IngestService::new(
+        env.db.clone(),
+        config,
+        Arc::new(StaticSecrets::default()),
+        std::time::Duration::MAX,
+        metrics,
+    );
+
+    let hit: TrackingInput = from_value(json!({ "event_type": "pageview", "path": "/pricing" })).unwrap();
+    let visitor_id = Uuid::new_v4();
+    let batch: Vec<CollectEvent> = (0..BATCH)
+        .map(|i| {
+            from_value(json!({
+                "timestamp": Utc::now(),
+                "visitor_id": visitor_id,
+                "category": "billing",
+                "action": "invoice_paid",
+                "value": i,
+            }))
+            .unwrap()
+        })
+        .collect();
+
+    let mut group = c.benchmark_group("tracking");
+    group.bench_function("pageview", |b| {
+        b.to_async(runtime).iter(|| async {
+            tracking
+                .track_pageview(&hit, Some([203, 0, 113, 7].into()), USER_AGENT)
+                .await
+                .unwrap()
+        })
+    });
+    group.bench_function(format!("collect_{}", BATCH), |b| {
+        b.to_async(runtime).iter(|| async {
+            assert_eq!(ingest.collect(&batch).await.unwrap().accepted, BATCH);
+        })
+    });
+    group.finish();
+}
+
+fn main() {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let env = runtime.block_on(TestEnv::start());
+
+    let mut criterion = Criterion::default().configure_from_args();
+    bench_tracking(&mut criterion, &runtime, &env);
+    criterion.final_summary();
+
+    bench::report("tracking");
+}
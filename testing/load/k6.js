@@ -0,0 +1,70 @@
+// Load profile of the benchmarked hot paths, for a running site
+//
+//   k6 run -e BASE_URL=http://localhost:8080 -e TOKEN=<access token> k6.js
+//
+// Page views are posted to the analytics tracker, anonymous readers list
+// posts (served from the cache once warm) and signed-in clients call an
+// endpoint behind the auth middleware. The run fails when a threshold is
+// crossed; they mirror benches/thresholds.toml with network overhead added.
+
+import http from 'k6/http';
+import { check } from 'k6';
+
+const BASE_URL = __ENV.BASE_URL || 'http://localhost:8080';
+const TOKEN = __ENV.TOKEN || '';
+
+export const options = {
+  scenarios: {
+    tracking: {
+      executor: 'constant-arrival-rate',
+      exec: 'tracking',
+      rate: 200,
+      timeUnit: '1s',
+      duration: '1m',
+      preAllocatedVUs: 50,
+    },
+    posts: {
+      executor: 'constant-arrival-rate',
+      exec: 'posts',
+      rate: 100,
+      timeUnit: '1s',
+      duration: '1m',
+      preAllocatedVUs: 50,
+    },
+    auth: {
+      executor: 'constant-arrival-rate',
+      exec: 'auth',
+      rate: 100,
+      timeUnit: '1s',
+      duration: '1m',
+      preAllocatedVUs: 20,
+    },
+  },
+  thresholds: {
+    'http_req_failed': ['rate<0.01'],
+    'http_req_duration{scenario:tracking}': ['p(95)<50'],
+    'http_req_duration{scenario:posts}': ['p(95)<30'],
+    'http_req_duration{scenario:auth}': ['p(95)<20'],
+  },
+};
+
+const hit = open('./track.json');
+
+export function tracking() {
+  const res = http.post(`${BASE_URL}/api/v1/rustpress-analytics/track`, hit, {
+    headers: { 'Content-Type': 'application/json', 'User-Agent': 'k6 load test' },
+  });
+  check(res, { 'tracked': (r) => r.status === 200 });
+}
+
+export function posts() {
+  const res = http.get(`${BASE_URL}/api/blog/posts?per_page=20`);
+  check(res, { 'listed': (r) => r.status === 200 });
+}
+
+export function auth() {
+  const res = http.get(`${BASE_URL}/api/v1/auth/me`, {
+    headers: { Authorization: `Bearer ${TOKEN}` },
+  });
+  check(res, { 'authenticated': (r) => r.status === 200 });
+}
@@ -0,0 +1,150 @@
+//! Benchmark Thresholds
+//!
+//! The criterion benches in `benches/` finish by calling [`report`] for
+//! their group. It reads the mean of each benchmark criterion just measured,
+//! compares it with the limit in `benches/thresholds.toml`, prints a table,
+//! writes it to `target/criterion/<group>/report.md` and panics when a
+//! benchmark is over its limit, so `cargo bench` fails on a regression.
+//!
+//! Limits are for a developer laptop; set `BENCH_THRESHOLD_SCALE` (e.g. `2`)
+//! on slower machines. Benchmarks filtered out of a run are reported as not
+//! run, and `cargo bench -- --test` skips the check.
+
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::PathBuf;
+
+/// Mean time limits in microseconds, by group and benchmark
+pub const THRESHOLDS: &str = include_str!("../benches/thresholds.toml");
+
+/// Multiplier applied to every limit
+pub const SCALE_VAR: &str = "BENCH_THRESHOLD_SCALE";
+
+/// A benchmark's mean against its limit
+#[derive(Debug, Clone, PartialEq)]
+pub struct Outcome {
+    pub name: String,
+    /// `None` when the benchmark didn't run
+    pub mean_us: Option<f64>,
+    pub limit_us: f64,
+}
+
+impl Outcome {
+    pub fn passed(&self) -> bool {
+        self.mean_us.map_or(true, |mean| mean <= self.limit_us)
+    }
+}
+
+/// Limits of a group, scaled by [`SCALE_VAR`]
+pub fn limits(group: &str) -> BTreeMap<String, f64> {
+    let thresholds: toml::Table = THRESHOLDS.parse().expect("invalid benches/thresholds.toml");
+    let scale: f64 = std::env::var(SCALE_VAR).ok().and_then(|s| s.parse().ok()).unwrap_or(1.0);
+
+    thresholds
+        .get(group)
+        .and_then(|limits| limits.as_table())
+        .unwrap_or_else(|| panic!("no [{}] limits in benches/thresholds.toml", group))
+        .iter()
+        .map(|(name, limit)| {
+            let limit = limit
+                .as_float()
+                .or_else(|| limit.as_integer().map(|l| l as f64))
+                .unwrap_or_else(|| panic!("limit of {}/{} is not a number", group, name));
+            (name.clone(), limit * scale)
+        })
+        .collect()
+}
+
+/// Where criterion writes its results
+pub fn criterion_dir() -> PathBuf {
+    if let Ok(home) = std::env::var("CRITERION_HOME") {
+        return PathBuf::from(home);
+    }
+    std::env::var("CARGO_TARGET_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("target"))
+        .join("criterion")
+}
+
+/// Mean of the latest run of `group/name`, in microseconds
+pub fn mean_us(group: &str, name: &str) -> Option<f64> {
+    let path = criterion_dir().join(group).join(name).join("new").join("estimates.json");
+    let estimates: Value = serde_json::from_slice(&std::fs::read(path).ok()?).ok()?;
+    estimates["mean"]["point_estimate"].as_f64().map(|ns| ns / 1000.0)
+}
+
+/// Compare a group's benchmarks with their limits
+pub fn outcomes(group: &str) -> Vec<Outcome> {
+    limits(group)
+        .into_iter()
+        .map(|(name, limit_us)| Outcome {
+            mean_us: mean_us(group, &name),
+            name,
+            limit_us,
+        })
+        .collect()
+}
+
+/// Markdown table of outcomes
+pub fn table(group: &str, outcomes: &[Outcome]) -> String {
+    let mut table = format!("## {}\n\n| Benchmark | Mean (µs) | Limit (µs) | |\n|---|---:|---:|---|\n", group);
+    for outcome in outcomes {
+        let (mean, verdict) = match outcome.mean_us {
+            Some(mean) if outcome.passed() => (format!("{:.1}", mean), "ok"),
+            Some(mean) => (format!("{:.1}", mean), "**over**"),
+            None => ("-".to_string(), "not run"),
+        };
+        let _ = writeln!(table, "| {} | {} | {:.0} | {} |", outcome.name, mean, outcome.limit_us, verdict);
+    }
+    table
+}
+
+/// Check a group after its benches ran; panics when one is over its limit
+pub fn report(group: &str) {
+    if std::env::args().any(|arg| arg == "--test" || arg == "--list") {
+        return;
+    }
+
+    let outcomes = outcomes(group);
+    let table = table(group, &outcomes);
+    println!("\n{}", table);
+
+    let dir = criterion_dir().join(group);
+    if std::fs::create_dir_all(&dir).is_ok() {
+        let _ = std::fs::write(dir.join("report.md"), &table);
+    }
+
+    let over: Vec<&str> = outcomes.iter().filter(|o| !o.passed()).map(|o| o.name.as_str()).collect();
+    assert!(over.is_empty(), "{} over their thresholds: {}", group, over.join(", "));
+}
+
+// ============================================
+// Tests
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_group_has_limits() {
+        for group in ["jwt", "tracking", "posts"] {
+            assert!(!limits(group).is_empty(), "{}", group);
+        }
+    }
+
+    #[test]
+    fn test_table_marks_regressions() {
+        let outcomes = [
+            Outcome { name: "fast".into(), mean_us: Some(10.0), limit_us: 50.0 },
+            Outcome { name: "slow".into(), mean_us: Some(80.0), limit_us: 50.0 },
+            Outcome { name: "skipped".into(), mean_us: None, limit_us: 50.0 },
+        ];
+        let table = table("demo", &outcomes);
+        assert!(table.contains("| fast | 10.0 | 50 | ok |"));
+        assert!(table.contains("| slow | 80.0 | 50 | **over** |"));
+        assert!(table.contains("| skipped | - | 50 | not run |"));
+        assert!(!outcomes[1].passed() && outcomes[2].passed());
+    }
+}
@@ -0,0 +1,113 @@
+//! In-Process HTTP Client
+//!
+//! Requests go straight to the router with `tower::ServiceExt::oneshot`; no
+//! socket is opened. Handlers extracting `ConnectInfo<SocketAddr>` see
+//! [`CLIENT_ADDR`].
+
+use crate::users::TestUser;
+
+use axum::body::{Body, Bytes};
+use axum::extract::connect_info::MockConnectInfo;
+use axum::http::{header, HeaderMap, HeaderName, HeaderValue, Method, Request, StatusCode};
+use axum::Router;
+use serde::{de::DeserializeOwned, Serialize};
+use std::net::SocketAddr;
+use tower::ServiceExt;
+
+/// Peer address of test requests
+pub const CLIENT_ADDR: ([u8; 4], u16) = ([127, 0, 0, 1], 40000);
+
+/// Client for one router, optionally signed in
+#[derive(Clone)]
+pub struct TestClient {
+    router: Router,
+    headers: HeaderMap,
+}
+
+impl TestClient {
+    pub fn new(router: Router) -> Self {
+        Self {
+            router: router.layer(MockConnectInfo(SocketAddr::from(CLIENT_ADDR))),
+            headers: HeaderMap::new(),
+        }
+    }
+
+    /// Send `user`'s access token with every request
+    pub fn authenticated(self, user: &TestUser) -> Self {
+        self.with_token(&user.access_token)
+    }
+
+    /// Send a bearer token with every request
+    pub fn with_token(self, token: &str) -> Self {
+        self.with_header(header::AUTHORIZATION, &format!("Bearer {}", token))
+    }
+
+    /// Send a header with every request
+    pub fn with_header(mut self, name: HeaderName, value: &str) -> Self {
+        self.headers.insert(name, HeaderValue::from_str(value).expect("invalid header value"));
+        self
+    }
+
+    pub async fn get(&self, path: &str) -> TestResponse {
+        self.request(Method::GET, path, Body::empty(), None).await
+    }
+
+    pub async fn delete(&self, path: &str) -> TestResponse {
+        self.request(Method::DELETE, path, Body::empty(), None).await
+    }
+
+    pub async fn post<T: Serialize + ?Sized>(&self, path: &str, body: &T) -> TestResponse {
+        self.json(Method::POST, path, body).await
+    }
+
+    pub async fn put<T: Serialize + ?Sized>(&self, path: &str, body: &T) -> TestResponse {
+        self.json(Method::PUT, path, body).await
+    }
+
+    async fn json<T: Serialize + ?Sized>(&self, method: Method, path: &str, body: &T) -> TestResponse {
+        let body = serde_json::to_vec(body).expect("unserializable request body");
+        self.request(method, path, Body::from(body), Some("application/json")).await
+    }
+
+    async fn request(&self, method: Method, path: &str, body: Body, content_type: Option<&str>) -> TestResponse {
+        let mut request = Request::builder().method(method).uri(path);
+        for (name, value) in &self.headers {
+            request = request.header(name, value);
+        }
+        if let Some(content_type) = content_type {
+            request = request.header(header::CONTENT_TYPE, content_type);
+        }
+        self.send(request.body(body).expect("invalid request")).await
+    }
+
+    /// Send a request as is, without the client's headers
+    pub async fn send(&self, request: Request<Body>) -> TestResponse {
+        let response = self.router.clone().oneshot(request).await.expect("router failed");
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .expect("failed to read the response body");
+        TestResponse { status, headers, body }
+    }
+}
+
+/// A buffered response
+#[derive(Debug)]
+pub struct TestResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+}
+
+impl TestResponse {
+    /// Body as JSON; panics with the body when it doesn't parse
+    pub fn json<T: DeserializeOwned>(&self) -> T {
+        serde_json::from_slice(&self.body)
+            .unwrap_or_else(|e| panic!("invalid JSON response ({}): {}", e, String::from_utf8_lossy(&self.body)))
+    }
+
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+}
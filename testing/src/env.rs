@@ -0,0 +1,128 @@
+//! Containers and Configuration
+//!
+//! A [`TestEnv`] owns a Postgres container, and a Redis container when
+//! started with [`TestEnv::with_redis`]. Both are removed when it drops.
+//! [`TestEnv::with_pgvector`] starts Postgres with the pgvector extension
+//! available.
+
+use rustpress_auth::migrations::PluginMigrations;
+use rustpress_auth::{AuthConfig, AuthService, JwtKeys};
+use sqlx::postgres::{PgPool, PgPoolOptions};
+use std::sync::{Arc, Once};
+use testcontainers::runners::AsyncRunner;
+use testcontainers::{ContainerAsync, ImageExt};
+use testcontainers_modules::postgres::Postgres;
+use testcontainers_modules::redis::{Redis, REDIS_PORT};
+
+/// Signing secret of test tokens
+pub const TEST_JWT_SECRET: &str = "rustpress-test-secret-0123456789abcdef";
+
+/// Environment variables set before the shared configuration loads
+///
+/// Cheap password hashing keeps registration and login fast, and login
+/// alerts are off since no mail is delivered.
+pub const TEST_SETTINGS: &[(&str, &str)] = &[
+    ("JWT_SECRET", TEST_JWT_SECRET),
+    ("ARGON2_MEMORY_COST", "1024"),
+    ("ARGON2_TIME_COST", "1"),
+    ("ARGON2_PARALLELISM", "1"),
+    ("AUTH_LOGIN_ALERTS", "false"),
+];
+
+static SETTINGS: Once = Once::new();
+
+/// Apply [`TEST_SETTINGS`]; must run before anything calls `Config::load`
+pub fn init() {
+    SETTINGS.call_once(|| {
+        for (name, value) in TEST_SETTINGS {
+            std::env::set_var(name, value);
+        }
+    });
+}
+
+/// Throwaway databases for one test
+pub struct TestEnv {
+    pub db: PgPool,
+    redis_url: Option<String>,
+    _postgres: ContainerAsync<Postgres>,
+    _redis: Option<ContainerAsync<Redis>>,
+}
+
+impl TestEnv {
+    /// Start Postgres
+    pub async fn start() -> Self {
+        init();
+        Self::connect(Postgres::default().start().await.expect("failed to start Postgres")).await
+    }
+
+    /// Start Postgres from the pgvector image
+    pub async fn with_pgvector() -> Self {
+        init();
+        let postgres = Postgres::default()
+            .with_name("pgvector/pgvector")
+            .with_tag("pg16")
+            .start()
+            .await
+            .expect("failed to start Postgres");
+        Self::connect(postgres).await
+    }
+
+    async fn connect(postgres: ContainerAsync<Postgres>) -> Self {
+        let url = format!(
+            "postgres://postgres:postgres@{}:{}/postgres",
+            postgres.get_host().await.expect("no Postgres host"),
+            postgres.get_host_port_ipv4(5432).await.expect("no Postgres port"),
+        );
+        let db = PgPoolOptions::new()
+            .max_connections(5)
+            .connect(&url)
+            .await
+            .expect("failed to connect to Postgres");
+
+        Self {
+            db,
+            redis_url: None,
+            _postgres: postgres,
+            _redis: None,
+        }
+    }
+
+    /// Start Postgres and Redis
+    pub async fn with_redis() -> Self {
+        let mut env = Self::start().await;
+
+        let redis = Redis::default().start().await.expect("failed to start Redis");
+        env.redis_url = Some(format!(
+            "redis://{}:{}/",
+            redis.get_host().await.expect("no Redis host"),
+            redis.get_host_port_ipv4(REDIS_PORT).await.expect("no Redis port"),
+        ));
+        env._redis = Some(redis);
+        env
+    }
+
+    /// URL of the Redis container
+    ///
+    /// Panics when the environment was started without Redis.
+    pub fn redis_url(&self) -> &str {
+        self.redis_url.as_deref().expect("started without Redis; use TestEnv::with_redis")
+    }
+
+    /// Apply every migration of a plugin
+    pub async fn migrate(&self, migrations: &PluginMigrations) {
+        migrations
+            .up(&self.db, None, false)
+            .await
+            .unwrap_or_else(|e| panic!("migrations of {} failed: {}", migrations.plugin_id(), e));
+    }
+
+    /// An auth service on the test database, signing with the shared keys
+    /// the auth middleware of apps and plugins verifies against
+    ///
+    /// Needs the auth plugin's migrations.
+    pub async fn auth_service(&self) -> Arc<AuthService> {
+        let config = AuthConfig::load().await.expect("invalid test auth config");
+        let keys = JwtKeys::shared().await.expect("no test JWT keys");
+        Arc::new(AuthService::with_keys(self.db.clone(), config, keys))
+    }
+}
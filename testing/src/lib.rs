@@ -0,0 +1,48 @@
+//! RustPress Test Harness
+//!
+//! Shared setup for integration tests that need the real services:
+//! - [`TestEnv`] starts Postgres (and optionally Redis) in throwaway
+//!   containers and runs plugin migrations against them
+//! - [`TestUser`] registers users through the auth plugin and signs access
+//!   tokens for them
+//! - [`TestClient`] sends requests to an Axum router in process, optionally
+//!   as one of those users
+//!
+//! ```rust,ignore
+//! #[tokio::test]
+//! async fn test_me() {
+//!     let env = TestEnv::start().await;
+//!     env.migrate(&AuthPlugin::migrations()).await;
+//!
+//!     let auth = env.auth_service().await;
+//!     let user = TestUser::create(&auth, "ada@example.com", UserRole::Author).await;
+//!
+//!     let client = TestClient::new(create_routes(auth)).authenticated(&user);
+//!     assert_eq!(client.get("/auth/me").await.status, 200);
+//! }
+//! ```
+//!
+//! Containers need a Docker daemon and are removed when the [`TestEnv`] is
+//! dropped, so every test gets empty databases. Apps and plugins read the
+//! process-wide [`Config`](rustpress_auth::Config); the harness fills it with
+//! test settings (see [`env::TEST_SETTINGS`]) before anything loads it.
+//!
+//! The tests in `tests/` cover the auth, blog and analytics flows
+//! end-to-end: `cargo test` in this directory.
+//!
+//! # Benchmarks
+//!
+//! `cargo bench` runs criterion benchmarks of JWT validation, tracking and
+//! ingestion, and post listing with and without the cache, each against
+//! containers, and fails when one is slower than its limit in
+//! `benches/thresholds.toml`; see [`bench`]. `load/` has the matching load
+//! profiles for k6 and vegeta, run against a deployed site.
+
+pub mod bench;
+pub mod client;
+pub mod env;
+pub mod users;
+
+pub use client::{TestClient, TestResponse};
+pub use env::TestEnv;
+pub use users::{TestUser, TEST_PASSWORD};
@@ -0,0 +1,50 @@
+//! Test Users
+//!
+//! Users are registered through [`AuthService::register`], so they pass the
+//! same checks and hooks as real sign-ups, then given their role directly.
+
+use rustpress_auth::{AuthService, RegisterRequest, User, UserRole};
+
+/// Password of every test user
+pub const TEST_PASSWORD: &str = "correct horse battery staple";
+
+/// A registered user with a valid access token
+#[derive(Debug, Clone)]
+pub struct TestUser {
+    pub user: User,
+    pub access_token: String,
+}
+
+impl TestUser {
+    /// Register `email` with [`TEST_PASSWORD`] and the given role
+    pub async fn create(auth: &AuthService, email: &str, role: UserRole) -> Self {
+        let user = auth
+            .register(RegisterRequest {
+                email: email.to_string(),
+                password: TEST_PASSWORD.to_string(),
+                password_confirm: TEST_PASSWORD.to_string(),
+                name: email.split('@').next().unwrap_or(email).to_string(),
+            })
+            .await
+            .unwrap_or_else(|e| panic!("failed to register {}: {}", email, e));
+
+        let user: User = if role == UserRole::User {
+            user
+        } else {
+            sqlx::query_as("UPDATE users SET role = $1 WHERE id = $2 RETURNING *")
+                .bind(&role)
+                .bind(user.id)
+                .fetch_one(auth.db())
+                .await
+                .expect("failed to set the test user's role")
+        };
+
+        let access_token = auth.generate_access_token(&user).expect("failed to sign a test token");
+        Self { user, access_token }
+    }
+
+    /// `Authorization` header value
+    pub fn bearer(&self) -> String {
+        format!("Bearer {}", self.access_token)
+    }
+}
@@ -0,0 +1,110 @@
+//! Visitors erased on request without changing past numbers
+
+use rustpress_analytics::services::{
+    ErasureService, GeoIp, IngestMetrics, ReportService, TrackingService, VISITOR_FORGOTTEN,
+};
+use rustpress_analytics::{AnalyticsConfig, AnalyticsPlugin};
+use rustpress_auth::db::DbPools;
+use rustpress_auth::{AuthPlugin, MetricsRegistry, UserRole};
+use rustpress_testing::{TestEnv, TestUser};
+use serde_json::{from_value, json};
+use std::sync::Arc;
+use std::time::Duration;
+
+#[tokio::test]
+async fn test_forget_by_ip_keeps_aggregates() {
+    let env = TestEnv::start().await;
+    env.migrate(&AuthPlugin::migrations()).await;
+    env.migrate(&AnalyticsPlugin::migrations()).await;
+
+    let config = AnalyticsConfig {
+        anonymize_ip: false,
+        ..Default::default()
+    };
+    let metrics = IngestMetrics::register(&Arc::new(MetricsRegistry::default()).plugin("rustpress-analytics")).unwrap();
+    let tracking = TrackingService::new(env.db.clone(), config.clone(), Arc::new(GeoIp::open()), metrics, None);
+    let reports = ReportService::new(
+        Arc::new(DbPools::new(env.db.clone())),
+        config.conversion_events.clone(),
+        config.timezone,
+    );
+
+    // Two visitors, one of them on two pages
+    let firefox = "Mozilla/5.0 (X11; Linux x86_64) Firefox/130.0";
+    let home = from_value(json!({ "event_type": "pageview", "path": "/" })).unwrap();
+    let (forgotten, _, _) = tracking
+        .track_pageview(&home, Some([203, 0, 113, 7].into()), firefox)
+        .await
+        .unwrap();
+    let pricing = from_value(json!({ "event_type": "pageview", "path": "/pricing", "visitor_id": forgotten })).unwrap();
+    tracking
+        .track_pageview(&pricing, Some([203, 0, 113, 7].into()), firefox)
+        .await
+        .unwrap();
+    let (kept, _, _) = tracking
+        .track_pageview(&home, Some([198, 51, 100, 1].into()), firefox)
+        .await
+        .unwrap();
+
+    let auth = env.auth_service().await;
+    let admin = TestUser::create(&auth, "dpo@example.com", UserRole::Admin).await.user.id;
+    let erasures = ErasureService::start(env.db.clone()).await.unwrap();
+
+    let invalid = from_value(json!({ "ip": "not-an-ip" })).unwrap();
+    assert!(erasures.request(&invalid, admin).await.is_err());
+
+    let request = erasures
+        .request(&from_value(json!({ "ip": "203.0.113.7" })).unwrap(), admin)
+        .await
+        .unwrap();
+    assert_eq!(request.status, "pending");
+
+    let mut erasure = erasures.get(request.id).await.unwrap();
+    for _ in 0..50 {
+        if erasure.status == "completed" {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        erasure = erasures.get(request.id).await.unwrap();
+    }
+    assert_eq!(erasure.status, "completed");
+    assert_eq!(erasure.visitors, Some(1));
+    assert_eq!(erasure.sessions, Some(1));
+    assert_eq!(erasure.pageviews, Some(2));
+
+    // Nothing links back to the visitor or the address
+    let remaining: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM analytics_pageviews WHERE visitor_id = $1 OR ip_address = '203.0.113.7'",
+    )
+    .bind(forgotten)
+    .fetch_one(&env.db)
+    .await
+    .unwrap();
+    assert_eq!(remaining, 0);
+    let (visitor_id, ip): (Option<uuid::Uuid>, Option<String>) =
+        sqlx::query_as("SELECT visitor_id, ip_address FROM analytics_erasures WHERE id = $1")
+            .bind(request.id)
+            .fetch_one(&env.db)
+            .await
+            .unwrap();
+    assert_eq!((visitor_id, ip), (None, None));
+
+    // The other visitor is untouched, and the pages keep their views
+    let kept_views: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM analytics_pageviews WHERE visitor_id = $1")
+        .bind(kept)
+        .fetch_one(&env.db)
+        .await
+        .unwrap();
+    assert_eq!(kept_views, 1);
+    let pages = reports.get_pages(&from_value(json!({ "period": "7d" })).unwrap()).await.unwrap();
+    let home_views = pages.iter().find(|p| p.path == "/").unwrap().page_views;
+    assert_eq!(home_views, 2);
+
+    let audited: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM auth_audit_events WHERE action = $1 AND actor_id = $2")
+        .bind(VISITOR_FORGOTTEN)
+        .bind(admin)
+        .fetch_one(&env.db)
+        .await
+        .unwrap();
+    assert_eq!(audited, 1);
+}
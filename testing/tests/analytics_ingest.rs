@@ -0,0 +1,255 @@
+//! Page views and backend events ingested, then reported
+
+use chrono::{Duration, Utc};
+use hmac::{Hmac, Mac};
+use rustpress_analytics::models::{LegalBasis, RejectionReason, TrackingMode};
+use rustpress_analytics::services::{
+    parse_region_rules, GeoIp, IngestError, IngestMetrics, IngestService, ReportService, TrackingError,
+    TrackingService, COLLECT_KEYS_SECRET,
+};
+use rustpress_analytics::{AnalyticsConfig, AnalyticsPlugin};
+use rustpress_auth::db::DbPools;
+use rustpress_auth::secrets::StaticSecrets;
+use rustpress_auth::{AuthPlugin, MetricsRegistry};
+use rustpress_testing::TestEnv;
+use serde_json::{from_value, json};
+use std::sync::Arc;
+
+#[tokio::test]
+async fn test_ingest_then_report() {
+    let env = TestEnv::start().await;
+    env.migrate(&AuthPlugin::migrations()).await;
+    env.migrate(&AnalyticsPlugin::migrations()).await;
+
+    let config = AnalyticsConfig::default();
+    let metrics = IngestMetrics::register(&Arc::new(MetricsRegistry::default()).plugin("rustpress-analytics")).unwrap();
+    let secrets = Arc::new(StaticSecrets::new([(COLLECT_KEYS_SECRET, "old-key, new-key".to_string())]));
+    let tracking = TrackingService::new(env.db.clone(), config.clone(), Arc::new(GeoIp::open()), metrics.clone(), None);
+    let ingest = IngestService::new(env.db.clone(), config.clone(), secrets, std::time::Duration::MAX, metrics);
+    let reports = ReportService::new(
+        Arc::new(DbPools::new(env.db.clone())),
+        config.conversion_events.clone(),
+        config.timezone,
+    );
+
+    // A visitor lands on the pricing page in a browser
+    let hit = from_value(json!({ "event_type": "pageview", "path": "/pricing", "title": "Pricing" })).unwrap();
+    let (visitor_id, session_id, _) = tracking
+        .track_pageview(&hit, Some([203, 0, 113, 7].into()), "Mozilla/5.0 (X11; Linux x86_64) Firefox/130.0")
+        .await
+        .unwrap();
+
+    // The payment backend reports their purchase; the second event is
+    // stamped too far in the future
+    assert!(ingest.authorize("new-key").await.unwrap());
+    assert!(!ingest.authorize("stolen-key").await.unwrap());
+    let events = from_value::<Vec<_>>(json!([
+        {
+            "timestamp": Utc::now(),
+            "visitor_id": visitor_id,
+            "category": "conversion",
+            "action": "purchase",
+            "value": 49,
+            "path": "/pricing",
+        },
+        {
+            "timestamp": Utc::now() + Duration::hours(1),
+            "visitor_id": visitor_id,
+            "category": "conversion",
+            "action": "purchase",
+        },
+    ]))
+    .unwrap();
+    let collected = ingest.collect(&events).await.unwrap();
+    assert_eq!(collected.accepted, 1);
+    assert_eq!(collected.rejected.len(), 1);
+    assert_eq!(collected.rejected[0].index, 1);
+
+    // The event joined the browser session
+    let stored: uuid::Uuid = sqlx::query_scalar("SELECT session_id FROM analytics_events WHERE visitor_id = $1")
+        .bind(visitor_id)
+        .fetch_one(&env.db)
+        .await
+        .unwrap();
+    assert_eq!(stored, session_id);
+
+    // And shows up as a conversion of the landing page
+    let query = from_value(json!({ "period": "7d" })).unwrap();
+    let landing = reports.get_landing_pages(&query).await.unwrap();
+    assert_eq!(landing.len(), 1);
+    assert_eq!(landing[0].path, "/pricing");
+    assert_eq!(landing[0].sessions, 1);
+    assert_eq!(landing[0].conversions, 1);
+
+    let pages = reports.get_pages(&query).await.unwrap();
+    assert_eq!(pages[0].path, "/pricing");
+    assert_eq!(pages[0].page_views, 1);
+}
+
+#[tokio::test]
+async fn test_collection_without_keys_is_disabled() {
+    let env = TestEnv::start().await;
+    env.migrate(&AnalyticsPlugin::migrations()).await;
+
+    let metrics = IngestMetrics::register(&Arc::new(MetricsRegistry::default()).plugin("rustpress-analytics")).unwrap();
+    let ingest = IngestService::new(
+        env.db.clone(),
+        AnalyticsConfig::default(),
+        Arc::new(StaticSecrets::default()),
+        std::time::Duration::MAX,
+        metrics,
+    );
+    assert!(matches!(ingest.authorize("any-key").await, Err(IngestError::Disabled)));
+}
+
+#[tokio::test]
+async fn test_quotas_and_signatures() {
+    let env = TestEnv::start().await;
+    env.migrate(&AnalyticsPlugin::migrations()).await;
+
+    let config = AnalyticsConfig {
+        ip_minute_quota: 3,
+        visitor_hourly_quota: 2,
+        require_signature: true,
+        ..Default::default()
+    };
+    let metrics = IngestMetrics::register(&Arc::new(MetricsRegistry::default()).plugin("rustpress-analytics")).unwrap();
+    let tracking = TrackingService::new(
+        env.db.clone(),
+        config.clone(),
+        Arc::new(GeoIp::open()),
+        metrics,
+        Some("tracker-key".to_string()),
+    );
+    let reports = ReportService::new(
+        Arc::new(DbPools::new(env.db.clone())),
+        config.conversion_events.clone(),
+        config.timezone,
+    );
+
+    let body = br#"{"event_type":"pageview","path":"/"}"#;
+    let sign = |key: &str, timestamp: i64| {
+        let mut mac = Hmac::<sha2::Sha256>::new_from_slice(key.as_bytes()).unwrap();
+        mac.update(format!("{}.", timestamp).as_bytes());
+        mac.update(body);
+        let signature: String = mac.finalize().into_bytes().iter().map(|b| format!("{:02x}", b)).collect();
+        (signature, timestamp.to_string())
+    };
+    let ip = Some([203, 0, 113, 7].into());
+    let now = Utc::now().timestamp_millis();
+
+    // Unsigned, forged and stale hits are refused
+    let (forged, timestamp) = sign("guessed-key", now);
+    assert_eq!(tracking.admit(ip, None, None, body), Err(RejectionReason::BadSignature));
+    assert_eq!(
+        tracking.admit(ip, Some(&forged), Some(&timestamp), body),
+        Err(RejectionReason::BadSignature)
+    );
+    let (stale, timestamp) = sign("tracker-key", now - 10 * 60 * 1000);
+    assert_eq!(
+        tracking.admit(ip, Some(&stale), Some(&timestamp), body),
+        Err(RejectionReason::BadSignature)
+    );
+
+    // Signed hits count against the IP's quota
+    let (signature, timestamp) = sign("tracker-key", now);
+    for _ in 0..3 {
+        tracking.admit(ip, Some(&signature), Some(&timestamp), body).unwrap();
+    }
+    assert_eq!(
+        tracking.admit(ip, Some(&signature), Some(&timestamp), body),
+        Err(RejectionReason::IpQuota)
+    );
+    tracking
+        .admit(Some([198, 51, 100, 1].into()), Some(&signature), Some(&timestamp), body)
+        .unwrap();
+
+    // And against the visitor's
+    let visitor_id = uuid::Uuid::new_v4();
+    tracking.admit_visitor(visitor_id).unwrap();
+    tracking.admit_visitor(visitor_id).unwrap();
+    assert_eq!(tracking.admit_visitor(visitor_id), Err(RejectionReason::VisitorQuota));
+
+    tracking.flush_rejections().await.unwrap();
+    let query = from_value(json!({ "period": "7d" })).unwrap();
+    let rejections = reports.get_rejections(&query).await.unwrap();
+    let counts: Vec<(&str, i64)> = rejections.iter().map(|r| (r.reason.as_str(), r.count)).collect();
+    assert_eq!(counts, [("bad_signature", 3), ("ip_quota", 1), ("visitor_quota", 1)]);
+}
+
+#[tokio::test]
+async fn test_region_rules() {
+    let env = TestEnv::start().await;
+    env.migrate(&AnalyticsPlugin::migrations()).await;
+
+    assert!(parse_region_rules("EEA cookieless").is_err());
+    assert!(parse_region_rules("Europe: cookieless").is_err());
+    assert!(parse_region_rules("DE: sometimes").is_err());
+    let rules = parse_region_rules("# Visitors GeoIP can't place fall through to *\nde, EEA: disabled\n\n*: cookieless").unwrap();
+    assert_eq!(rules[0].regions, ["DE", "EEA"]);
+    assert_eq!(rules[1].mode, TrackingMode::Cookieless);
+
+    let tracking = |rules: &str| {
+        let config = AnalyticsConfig {
+            region_rules: parse_region_rules(rules).unwrap(),
+            legal_basis: LegalBasis::Consent,
+            ..Default::default()
+        };
+        let metrics = IngestMetrics::register(&Arc::new(MetricsRegistry::default()).plugin("rustpress-analytics")).unwrap();
+        TrackingService::new(env.db.clone(), config, Arc::new(GeoIp::open()), metrics, None)
+    };
+    let ip = Some([203, 0, 113, 7].into());
+    let firefox = "Mozilla/5.0 (X11; Linux x86_64) Firefox/130.0";
+
+    // Cookieless: the ids sent are ignored and no address is kept, but the
+    // same browser stays one visitor for the day
+    let cookieless = tracking("*: cookieless");
+    let policy = cookieless.policy(ip);
+    assert_eq!(policy.mode, TrackingMode::Cookieless);
+    assert_eq!(policy.legal_basis, LegalBasis::Consent);
+    assert_eq!(cookieless.policy(None).mode, TrackingMode::Full);
+
+    let hit = |visitor_id: uuid::Uuid| {
+        from_value(json!({ "event_type": "pageview", "path": "/", "visitor_id": visitor_id })).unwrap()
+    };
+    let sent = uuid::Uuid::new_v4();
+    let (first, session_id, _) = cookieless.track_pageview(&hit(sent), ip, firefox).await.unwrap();
+    let (second, _, _) = cookieless.track_pageview(&hit(uuid::Uuid::new_v4()), ip, firefox).await.unwrap();
+    assert_ne!(first, sent);
+    assert_eq!(first, second);
+    let (other, _, _) = cookieless
+        .track_pageview(&hit(sent), Some([198, 51, 100, 1].into()), firefox)
+        .await
+        .unwrap();
+    assert_ne!(other, first);
+
+    let stored: Vec<Option<String>> = sqlx::query_scalar("SELECT ip_address FROM analytics_pageviews")
+        .fetch_all(&env.db)
+        .await
+        .unwrap();
+    assert_eq!(stored, [None, None, None]);
+
+    // Disabled: nothing is written, including events and heartbeats
+    let disabled = tracking("*: disabled");
+    assert!(matches!(
+        disabled.track_pageview(&hit(sent), ip, firefox).await,
+        Err(TrackingError::RegionDisabled)
+    ));
+    let event = from_value(json!({
+        "event_type": "event",
+        "path": "/",
+        "visitor_id": first,
+        "session_id": session_id,
+        "category": "videos",
+    }))
+    .unwrap();
+    assert!(matches!(disabled.track_event(&event, ip).await, Err(TrackingError::RegionDisabled)));
+    let pageviews: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM analytics_pageviews")
+        .fetch_one(&env.db)
+        .await
+        .unwrap();
+    assert_eq!(pageviews, 3);
+
+    // Hooks track without an address and aren't subject to region rules
+    disabled.track_event(&event, None).await.unwrap();
+}
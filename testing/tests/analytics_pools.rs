@@ -0,0 +1,76 @@
+//! Reporting pool statement timeouts and report query time limits
+
+use rustpress_analytics::models::ReportQuery;
+use rustpress_analytics::services::{ReportError, ReportService};
+use rustpress_analytics::{AnalyticsConfig, AnalyticsPlugin};
+use rustpress_auth::db::{watch_pool, DbPools, PoolConfig, REPORTING_POOL};
+use rustpress_auth::{AuthPlugin, MetricsRegistry};
+use rustpress_testing::TestEnv;
+use std::sync::Arc;
+use std::time::Duration;
+
+fn query() -> ReportQuery {
+    ReportQuery {
+        site_id: None,
+        from: None,
+        to: None,
+        period: Some("365d".into()),
+        limit: None,
+        offset: None,
+    }
+}
+
+#[tokio::test]
+async fn test_reporting_pool_cancels_long_statements() {
+    let env = TestEnv::start().await;
+    let pool_config = PoolConfig {
+        max_connections: 2,
+        statement_timeout_ms: 200,
+        ..PoolConfig::default()
+    };
+    let reporting = pool_config
+        .connect_with((*env.db.connect_options()).clone())
+        .await
+        .unwrap();
+
+    // The server cancels the statement (query_canceled) and the connection is reusable
+    let err = sqlx::query("SELECT pg_sleep(5)").execute(&reporting).await.unwrap_err();
+    assert_eq!(err.as_database_error().and_then(|e| e.code()).as_deref(), Some("57014"));
+    let one: i32 = sqlx::query_scalar("SELECT 1").fetch_one(&reporting).await.unwrap();
+    assert_eq!(one, 1);
+
+    // The main pool has no limit
+    sqlx::query("SELECT pg_sleep(0.3)").execute(&env.db).await.unwrap();
+
+    let registry = Arc::new(MetricsRegistry::default());
+    watch_pool(&reporting, REPORTING_POOL, &registry.plugin("rustpress-analytics")).unwrap();
+    assert!(registry
+        .render()
+        .contains("db_pool_reporting_max_connections{plugin=\"rustpress-analytics\"} 2"));
+}
+
+#[tokio::test]
+async fn test_report_query_time_limit() {
+    let env = TestEnv::start().await;
+    env.migrate(&AuthPlugin::migrations()).await;
+    env.migrate(&AnalyticsPlugin::migrations()).await;
+
+    let config = AnalyticsConfig::default();
+    let reports = |timeout: Duration| {
+        ReportService::new(
+            Arc::new(DbPools::new(env.db.clone())),
+            config.conversion_events.clone(),
+            config.timezone,
+        )
+        .with_query_timeout(timeout)
+    };
+
+    assert!(reports(Duration::from_secs(30)).get_overview(&query()).await.is_ok());
+
+    // No query finishes in no time
+    let err = reports(Duration::ZERO).get_pages(&query()).await.unwrap_err();
+    assert!(matches!(err, ReportError::Timeout(limit) if limit == Duration::ZERO));
+
+    // The pool isn't left short of connections
+    assert!(reports(Duration::from_secs(30)).get_pages(&query()).await.is_ok());
+}
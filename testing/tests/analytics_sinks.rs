@@ -0,0 +1,170 @@
+//! Stored hits mirrored to external sinks
+
+use axum::{extract::State, http::HeaderMap, http::StatusCode, routing::post, Json, Router};
+use rustpress_analytics::models::TrackingInput;
+use rustpress_analytics::services::{parse_sinks, IngestMetrics, SinkEvent, Sinks};
+use rustpress_auth::secrets::StaticSecrets;
+use rustpress_auth::MetricsRegistry;
+use serde_json::{from_value, json, Value};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use uuid::Uuid;
+
+fn hit(body: Value) -> TrackingInput {
+    from_value(body).unwrap()
+}
+
+#[test]
+fn test_parse_sinks() {
+    assert!(parse_sinks("").unwrap().is_empty());
+
+    let sinks = parse_sinks(
+        r#"[
+            {"name": "ga", "type": "ga4", "measurement_id": "G-TEST", "secret": "analytics.sinks.ga"},
+            {"name": "stream", "type": "kafka", "rest_url": "http://kafka-rest:8082", "topic": "hits", "batch_size": 200}
+        ]"#,
+    )
+    .unwrap();
+    assert_eq!(sinks.len(), 2);
+    assert_eq!(sinks[0].target.kind(), "ga4");
+    assert_eq!(sinks[0].batch_size, 50);
+    assert_eq!(sinks[1].batch_size, 200);
+
+    for (sinks, error) in [
+        (r#"[{"name": "ga", "type": "ga4", "measurement_id": "G-TEST"}]"#, "api_secret"),
+        (r#"[{"name": "a", "type": "http", "url": "ftp://example.com"}]"#, "url"),
+        (
+            r#"[{"name": "a", "type": "http", "url": "https://a.example"}, {"name": "a", "type": "http", "url": "https://b.example"}]"#,
+            "another sink",
+        ),
+        (r#"[{"name": "a", "type": "http", "url": "https://a.example", "batch_size": 0}]"#, "batch_size"),
+        (r#"[{"name": "a", "type": "smoke_signals"}]"#, "Invalid sinks"),
+    ] {
+        let message = parse_sinks(sinks).unwrap_err();
+        assert!(message.contains(error), "{}: {}", sinks, message);
+    }
+}
+
+#[test]
+fn test_mapping_rules() {
+    let sink = &parse_sinks(
+        r#"[{"name": "crm", "type": "http", "url": "https://crm.example/hits",
+             "rename": {"pageview": "visit", "shop": "shop_event", "shop:purchase": "order"},
+             "omit": ["referrer", "name"]}]"#,
+    )
+    .unwrap()[0];
+    let (visitor, session) = (Uuid::new_v4(), Uuid::new_v4());
+
+    let pageview = SinkEvent::pageview(
+        &hit(json!({"event_type": "pageview", "path": "/pricing", "referrer": "https://search.example"})),
+        visitor,
+        session,
+        Some("DE".into()),
+    );
+    assert!(pageview.matches("pageview"));
+    assert!(!pageview.matches("shop"));
+    let fields = pageview.mapped(sink);
+    assert_eq!(fields["name"], "visit");
+    assert_eq!(fields["path"], "/pricing");
+    assert_eq!(fields["country"], "DE");
+    assert!(!fields.contains_key("referrer"));
+
+    let purchase = SinkEvent::event(
+        &hit(json!({"event_type": "event", "path": "/cart", "category": "shop", "action": "purchase", "value": 42})),
+        visitor,
+        session,
+    );
+    assert!(purchase.matches("shop") && purchase.matches("shop:purchase") && !purchase.matches("pageview"));
+    assert_eq!(purchase.mapped(sink)["name"], "order");
+    assert_eq!(purchase.mapped(sink)["value"], 42);
+
+    let view = SinkEvent::event(
+        &hit(json!({"event_type": "event", "path": "/cart", "category": "shop", "action": "view"})),
+        visitor,
+        session,
+    );
+    assert_eq!(view.mapped(sink)["name"], "shop_event");
+}
+
+#[tokio::test]
+async fn test_http_sink_batches_and_isolates_failures() {
+    // A collector recording the batches and tokens it gets
+    let received: Arc<Mutex<Vec<(Option<String>, Value)>>> = Arc::default();
+    let app = Router::new()
+        .route(
+            "/events",
+            post(
+                |State(received): State<Arc<Mutex<Vec<(Option<String>, Value)>>>>,
+                 headers: HeaderMap,
+                 Json(body): Json<Value>| async move {
+                    let token = headers.get("authorization").and_then(|v| v.to_str().ok()).map(String::from);
+                    received.lock().unwrap().push((token, body));
+                    StatusCode::NO_CONTENT
+                },
+            ),
+        )
+        .with_state(received.clone());
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let collector = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    // Nothing listens here
+    let down = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap();
+
+    let configs = parse_sinks(
+        &json!([
+            {
+                "name": "warehouse", "type": "http", "url": format!("http://{}/events", collector),
+                "secret": "analytics.sinks.warehouse", "events": ["pageview", "shop:purchase"],
+                "batch_size": 2, "flush_interval_secs": 1
+            },
+            {"name": "down", "type": "http", "url": format!("http://{}/events", down), "batch_size": 1, "queue_size": 1}
+        ])
+        .to_string(),
+    )
+    .unwrap();
+    let secrets = StaticSecrets::new([("analytics.sinks.warehouse", "s3cret".to_string())]);
+    let metrics = IngestMetrics::register(&Arc::new(MetricsRegistry::default()).plugin("rustpress-analytics")).unwrap();
+    let sinks = Sinks::start(&configs, &secrets, metrics).await.unwrap();
+
+    let (visitor, session) = (Uuid::new_v4(), Uuid::new_v4());
+    let started = std::time::Instant::now();
+    sinks.publish(&SinkEvent::pageview(
+        &hit(json!({"event_type": "pageview", "path": "/"})),
+        visitor,
+        session,
+        None,
+    ));
+    for action in ["view", "purchase"] {
+        sinks.publish(&SinkEvent::event(
+            &hit(json!({"event_type": "event", "path": "/cart", "category": "shop", "action": action})),
+            visitor,
+            session,
+        ));
+    }
+    // Publishing never waits for a sink, even one that's down
+    assert!(started.elapsed() < Duration::from_millis(100));
+
+    // The page view and the purchase arrive together; the view isn't sent
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while sinks.status()[0].sent < 2 {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await
+    .expect("warehouse got nothing");
+    let batches = received.lock().unwrap().clone();
+    assert_eq!(batches.len(), 1);
+    assert_eq!(batches[0].0.as_deref(), Some("Bearer s3cret"));
+    let names: Vec<&str> = batches[0].1["events"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|event| event["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, ["page_view", "purchase"]);
+
+    let status = sinks.status();
+    assert_eq!((status[0].name.as_str(), status[0].sent, status[0].dropped), ("warehouse", 2, 0));
+    assert_eq!((status[1].name.as_str(), status[1].sent), ("down", 0));
+}
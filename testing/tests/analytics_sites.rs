@@ -0,0 +1,77 @@
+//! Reports and daily stats kept apart per site
+
+use rustpress_analytics::services::{GeoIp, IngestMetrics, ReportService, TrackingService};
+use rustpress_analytics::{AnalyticsConfig, AnalyticsPlugin};
+use rustpress_auth::db::DbPools;
+use rustpress_auth::{AuthPlugin, MetricsRegistry};
+use rustpress_testing::TestEnv;
+use serde_json::{from_value, json};
+use std::sync::Arc;
+use uuid::Uuid;
+
+#[tokio::test]
+async fn test_sites_page_views_stay_separate() {
+    let env = TestEnv::start().await;
+    env.migrate(&AuthPlugin::migrations()).await;
+    env.migrate(&AnalyticsPlugin::migrations()).await;
+
+    let config = AnalyticsConfig::default();
+    let metrics = IngestMetrics::register(&Arc::new(MetricsRegistry::default()).plugin("rustpress-analytics")).unwrap();
+    let tracking = TrackingService::new(env.db.clone(), config.clone(), Arc::new(GeoIp::open()), metrics, None);
+    let reports = ReportService::new(
+        Arc::new(DbPools::new(env.db.clone())),
+        config.conversion_events.clone(),
+        config.timezone,
+    );
+
+    // Two views of the first site's home page, one of the second's and one without a site
+    let (blog, shop) = (Uuid::new_v4(), Uuid::new_v4());
+    let firefox = "Mozilla/5.0 (X11; Linux x86_64) Firefox/130.0";
+    for (site, ip) in [(Some(blog), [203, 0, 113, 7]), (Some(blog), [203, 0, 113, 8]), (Some(shop), [198, 51, 100, 1]), (None, [198, 51, 100, 2])] {
+        let hit = from_value(json!({ "event_type": "pageview", "path": "/", "site_id": site })).unwrap();
+        tracking.track_pageview(&hit, Some(ip.into()), firefox).await.unwrap();
+    }
+
+    let home_views = |site: Option<Uuid>| {
+        let reports = &reports;
+        async move {
+            let query = from_value(json!({ "period": "7d", "site_id": site })).unwrap();
+            let pages = reports.get_pages(&query).await.unwrap();
+            assert_eq!(pages.len(), 1);
+            pages[0].page_views
+        }
+    };
+    assert_eq!(home_views(Some(blog)).await, 2);
+    assert_eq!(home_views(Some(shop)).await, 1);
+    assert_eq!(home_views(None).await, 1);
+    assert_eq!(home_views(Some(Uuid::new_v4())).await, 0);
+
+    // Each site has its own row for the day, and a second rollup replaces it
+    let yesterday = chrono::Utc::now().date_naive() - chrono::Duration::days(1);
+    for (site, views) in [(Some(blog), 2i64), (Some(shop), 1), (None, 1), (Some(blog), 3)] {
+        sqlx::query(
+            "INSERT INTO analytics_daily_stats (site_id, date, page_views) VALUES ($1, $2, $3)
+             ON CONFLICT ((COALESCE(site_id, '00000000-0000-0000-0000-000000000000'::uuid)), date)
+             DO UPDATE SET page_views = EXCLUDED.page_views",
+        )
+        .bind(site)
+        .bind(yesterday)
+        .bind(views)
+        .execute(&env.db)
+        .await
+        .unwrap();
+    }
+
+    let overview = |site: Option<Uuid>| {
+        let reports = &reports;
+        async move {
+            let query = from_value(json!({ "period": "7d", "site_id": site })).unwrap();
+            reports.get_overview(&query).await.unwrap()
+        }
+    };
+    let blog_overview = overview(Some(blog)).await;
+    assert_eq!(blog_overview.total_page_views, 3);
+    assert_eq!(blog_overview.daily_stats.len(), 1);
+    assert_eq!(overview(Some(shop)).await.total_page_views, 1);
+    assert_eq!(overview(None).await.total_page_views, 1);
+}
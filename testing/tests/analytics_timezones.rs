@@ -0,0 +1,62 @@
+//! Report periods counted in the report time zone
+
+use chrono::{NaiveDate, Utc};
+use rustpress_analytics::models::{day_start, today, ReportQuery, ReportRange};
+use serde_json::json;
+
+fn date(s: &str) -> NaiveDate {
+    s.parse().unwrap()
+}
+
+#[test]
+fn test_range_spans_local_days_across_dst() {
+    let berlin: chrono_tz::Tz = "Europe/Berlin".parse().unwrap();
+    let range = ReportRange::new(date("2026-03-01"), date("2026-03-31"), berlin);
+
+    // Clocks go forward on March 29th, so the period ends an hour earlier in UTC
+    assert_eq!(range.start.to_rfc3339(), "2026-03-01T00:00:00+01:00");
+    assert_eq!(range.end.to_rfc3339(), "2026-04-01T00:00:00+02:00");
+    assert_eq!(range.start_utc().to_rfc3339(), "2026-02-28T23:00:00+00:00");
+    assert_eq!(range.end_utc().to_rfc3339(), "2026-03-31T22:00:00+00:00");
+
+    assert_eq!(
+        serde_json::to_value(&range).unwrap(),
+        json!({
+            "timezone": "Europe/Berlin",
+            "from": "2026-03-01",
+            "to": "2026-03-31",
+            "start": "2026-03-01T00:00:00+01:00",
+            "end": "2026-04-01T00:00:00+02:00",
+        })
+    );
+}
+
+#[test]
+fn test_day_starting_with_dst_jump_begins_when_clocks_do() {
+    // Santiago skips from midnight to 1am when summer time starts
+    let santiago: chrono_tz::Tz = "America/Santiago".parse().unwrap();
+    assert_eq!(day_start(date("2026-09-06"), santiago).to_rfc3339(), "2026-09-06T01:00:00-03:00");
+}
+
+#[test]
+fn test_periods_end_today_in_zone() {
+    let query = ReportQuery {
+        site_id: None,
+        from: None,
+        to: None,
+        period: Some("7d".into()),
+        limit: None,
+        offset: None,
+    };
+
+    for tz in [chrono_tz::Pacific::Kiritimati, chrono_tz::Pacific::Pago_Pago] {
+        let range = query.range(tz);
+        assert_eq!(range.to, today(tz));
+        assert_eq!(range.to, Utc::now().with_timezone(&tz).date_naive());
+        assert_eq!((range.to - range.from).num_days(), 7);
+
+        let previous = query.previous_range(tz);
+        assert_eq!(previous.to, range.from - chrono::Duration::days(1));
+        assert_eq!(previous.to - previous.from, range.to - range.from);
+    }
+}
@@ -0,0 +1,67 @@
+//! Login and refresh token rotation over HTTP
+
+use rustpress_auth::{audit, handlers, AuthPlugin, UserRole};
+use rustpress_testing::{TestClient, TestEnv, TestUser, TEST_PASSWORD};
+use serde_json::{json, Value};
+
+#[tokio::test]
+async fn test_login_refresh_rotation_and_reuse() {
+    let env = TestEnv::start().await;
+    env.migrate(&AuthPlugin::migrations()).await;
+
+    let auth = env.auth_service().await;
+    let user = TestUser::create(&auth, "ada@example.com", UserRole::User).await;
+    let client = TestClient::new(handlers::create_routes(auth.clone()));
+
+    // Wrong and right passwords
+    let login = |password: &str| json!({ "email": "ada@example.com", "password": password });
+    assert_eq!(client.post("/auth/login", &login("wrong password")).await.status, 401);
+    let response = client.post("/auth/login", &login(TEST_PASSWORD)).await;
+    assert_eq!(response.status, 200, "{}", response.text());
+    let first: Value = response.json();
+
+    // The access token opens protected routes
+    let me = client.clone().with_token(first["access_token"].as_str().unwrap()).get("/auth/me").await;
+    assert_eq!(me.status, 200);
+    assert_eq!(me.json::<Value>()["user"]["email"], "ada@example.com");
+    assert_eq!(client.get("/auth/me").await.status, 401);
+
+    // Each refresh rotates the refresh token
+    let refresh = |token: &Value| json!({ "refresh_token": token });
+    let second: Value = client.post("/auth/refresh", &refresh(&first["refresh_token"])).await.json();
+    assert_ne!(second["refresh_token"], first["refresh_token"]);
+    let third = client.post("/auth/refresh", &refresh(&second["refresh_token"])).await;
+    assert_eq!(third.status, 200);
+    let third: Value = third.json();
+
+    // Replaying a rotated token revokes the whole family, including the
+    // newest token, and is audited
+    assert_eq!(client.post("/auth/refresh", &refresh(&first["refresh_token"])).await.status, 401);
+    assert_eq!(client.post("/auth/refresh", &refresh(&third["refresh_token"])).await.status, 401);
+
+    let reuses: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM auth_audit_events WHERE action = $1 AND actor_id = $2")
+        .bind(audit::TOKEN_REUSED)
+        .bind(user.user.id)
+        .fetch_one(&env.db)
+        .await
+        .unwrap();
+    assert_eq!(reuses, 1);
+
+    // Signing in again starts a new family
+    let again: Value = client.post("/auth/login", &login(TEST_PASSWORD)).await.json();
+    assert_eq!(client.post("/auth/refresh", &refresh(&again["refresh_token"])).await.status, 200);
+}
+
+#[tokio::test]
+async fn test_test_users_are_signed_in() {
+    let env = TestEnv::start().await;
+    env.migrate(&AuthPlugin::migrations()).await;
+
+    let auth = env.auth_service().await;
+    let editor = TestUser::create(&auth, "grace@example.com", UserRole::Editor).await;
+    let client = TestClient::new(handlers::create_routes(auth)).authenticated(&editor);
+
+    let me: Value = client.get("/auth/me").await.json();
+    assert_eq!(me["user"]["email"], "grace@example.com");
+    assert_eq!(me["user"]["role"], "editor");
+}
@@ -0,0 +1,148 @@
+//! Alt text suggestions: queueing, backfill and editors' reviews
+
+use rustpress_auth::{AuthPlugin, UserRole};
+use rustpress_blog_api::alt_text::{clean, AltTextConfig, AltTextService};
+use rustpress_blog_api::models::{AltSuggestionQuery, AltSuggestionStatus, Media};
+use rustpress_blog_api::sites::{Site, SiteService};
+use rustpress_blog_api::BlogApp;
+use rustpress_testing::{TestEnv, TestUser};
+use uuid::Uuid;
+
+async fn insert_media(env: &TestEnv, site: &Site, uploader: Uuid, name: &str, mime_type: &str, alt_text: Option<&str>) -> Media {
+    sqlx::query_as(
+        "INSERT INTO blog_media (site_id, uploader_id, filename, original_name, mime_type, size, alt_text, url)
+         VALUES ($1, $2, $3, $3, $4, 1024, $5, '/uploads/' || $3) RETURNING *",
+    )
+    .bind(site.id)
+    .bind(uploader)
+    .bind(name)
+    .bind(mime_type)
+    .bind(alt_text)
+    .fetch_one(&env.db)
+    .await
+    .unwrap()
+}
+
+/// Stand in for the job having described the image
+async fn suggest(env: &TestEnv, media: &Media, suggestion: &str) {
+    sqlx::query(
+        "UPDATE blog_media_alt_suggestions
+         SET status = 'pending', suggestion = $2, model = 'test', generated_at = NOW()
+         WHERE media_id = $1",
+    )
+    .bind(media.id)
+    .bind(suggestion)
+    .execute(&env.db)
+    .await
+    .unwrap();
+}
+
+async fn alt_text(env: &TestEnv, media: &Media) -> Option<String> {
+    sqlx::query_scalar("SELECT alt_text FROM blog_media WHERE id = $1")
+        .bind(media.id)
+        .fetch_one(&env.db)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_backfill_accept_and_reject() {
+    let env = TestEnv::start().await;
+    env.migrate(&AuthPlugin::migrations()).await;
+    BlogApp::migrations().run(&env.db).await.expect("blog migrations failed");
+
+    let auth = env.auth_service().await;
+    let author = TestUser::create(&auth, "ada@example.com", UserRole::Author).await.user.id;
+    let editor = TestUser::create(&auth, "ed@example.com", UserRole::Editor).await.user.id;
+    let sites = SiteService::load(env.db.clone()).await.unwrap();
+    let (mut site, _) = sites.resolve(None, "/").await.expect("no default site");
+
+    let harbour = insert_media(&env, &site, author, "harbour.jpg", "image/jpeg", None).await;
+    let chart = insert_media(&env, &site, author, "chart.png", "image/png", None).await;
+    insert_media(&env, &site, author, "logo.png", "image/png", Some("Company logo")).await;
+    insert_media(&env, &site, author, "report.pdf", "application/pdf", None).await;
+
+    // Never called: suggestions are set below as if the job had run
+    let service = AltTextService::new(
+        env.db.clone(),
+        AltTextConfig {
+            enabled: true,
+            endpoint: Some("http://127.0.0.1:9/v1".into()),
+            ..AltTextConfig::default()
+        },
+    )
+    .unwrap();
+
+    // Sites opt in
+    assert!(service.backfill(&site).await.is_err());
+    site.config.alt_text_suggestions = true;
+
+    // Only images without alt text, and only once
+    assert_eq!(service.backfill(&site).await.unwrap(), 2);
+    assert_eq!(service.backfill(&site).await.unwrap(), 0);
+
+    suggest(&env, &harbour, "Fishing boats moored in a harbour at dusk").await;
+    suggest(&env, &chart, "A bar chart").await;
+
+    let pending = AltSuggestionQuery { status: None, limit: None };
+    assert_eq!(service.list(&site, author, false, &pending).await.unwrap().len(), 2);
+    assert!(service.list(&site, editor, false, &pending).await.unwrap().is_empty());
+    assert_eq!(service.list(&site, editor, true, &pending).await.unwrap().len(), 2);
+
+    // Accepting saves the suggestion; rejecting leaves the alt text alone
+    let saved = service.accept(&harbour, editor, None).await.unwrap();
+    assert_eq!(saved, "Fishing boats moored in a harbour at dusk");
+    assert_eq!(alt_text(&env, &harbour).await.as_deref(), Some(saved.as_str()));
+    service.reject(&chart, editor).await.unwrap();
+    assert_eq!(alt_text(&env, &chart).await, None);
+
+    // Reviewed suggestions can't be reviewed again
+    assert!(service.accept(&chart, editor, None).await.is_err());
+    assert!(service.reject(&harbour, editor).await.is_err());
+
+    let accepted = AltSuggestionQuery {
+        status: Some(AltSuggestionStatus::Accepted),
+        limit: None,
+    };
+    let reviewed = service.list(&site, editor, true, &accepted).await.unwrap();
+    assert_eq!(reviewed.len(), 1);
+    assert_eq!(reviewed[0].reviewed_by, Some(editor));
+}
+
+#[tokio::test]
+async fn test_accept_edited_alt_text() {
+    let env = TestEnv::start().await;
+    env.migrate(&AuthPlugin::migrations()).await;
+    BlogApp::migrations().run(&env.db).await.expect("blog migrations failed");
+
+    let auth = env.auth_service().await;
+    let author = TestUser::create(&auth, "ada@example.com", UserRole::Author).await.user.id;
+    let sites = SiteService::load(env.db.clone()).await.unwrap();
+    let (mut site, _) = sites.resolve(None, "/").await.expect("no default site");
+    site.config.alt_text_suggestions = true;
+
+    let photo = insert_media(&env, &site, author, "team.jpg", "image/jpeg", None).await;
+    let service = AltTextService::new(
+        env.db.clone(),
+        AltTextConfig {
+            enabled: true,
+            endpoint: Some("http://127.0.0.1:9/v1".into()),
+            ..AltTextConfig::default()
+        },
+    )
+    .unwrap();
+    service.queue_upload(&site, &photo).await;
+    suggest(&env, &photo, "People standing in an office").await;
+
+    assert!(service.accept(&photo, author, Some("   ".into())).await.is_err());
+    let saved = service.accept(&photo, author, Some(" The team at the 2026 offsite ".into())).await.unwrap();
+    assert_eq!(saved, "The team at the 2026 offsite");
+    assert_eq!(alt_text(&env, &photo).await.as_deref(), Some("The team at the 2026 offsite"));
+}
+
+#[test]
+fn test_clean_model_reply() {
+    assert_eq!(clean("\"A red bicycle.\"\n", 125).as_deref(), Some("A red bicycle."));
+    assert_eq!(clean("  ", 125), None);
+    assert!(clean(&"word ".repeat(100), 125).unwrap().chars().count() <= 125);
+}
@@ -0,0 +1,142 @@
+//! Backups: table dumps with their point in time, verification, retention
+//! and restore steps
+
+use chrono::Utc;
+use rustpress_apps::prelude::{MemoryStorage, Storage};
+use rustpress_auth::{AuthPlugin, PLUGIN_ID};
+use rustpress_blog_api::backups::{restore_instructions, BackupConfig, BackupService};
+use rustpress_blog_api::models::{Backup, BackupStatus, PointInTime};
+use rustpress_blog_api::{BlogApp, APP_ID};
+use rustpress_testing::TestEnv;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use uuid::Uuid;
+
+async fn setup(config: BackupConfig) -> (TestEnv, Arc<MemoryStorage>, BackupService) {
+    let env = TestEnv::start().await;
+    env.migrate(&AuthPlugin::migrations()).await;
+    BlogApp::migrations().run(&env.db).await.expect("blog migrations failed");
+
+    let storage = Arc::new(MemoryStorage::default());
+    let backups = BackupService::new(env.db.clone(), storage.clone(), config);
+    (env, storage, backups)
+}
+
+#[tokio::test]
+async fn test_logical_backup_and_verify() {
+    let (_env, storage, backups) = setup(BackupConfig::default()).await;
+
+    let backup = backups.backup(None).await.unwrap();
+    assert_eq!(backup.status, BackupStatus::Completed);
+    assert_eq!(backup.method, "logical");
+    assert_eq!(backup.storage_prefix, format!("backups/{}", backup.id));
+
+    // Point in time: the app's and the auth plugin's migrations
+    let point = &backup.point_in_time;
+    assert!(point.taken_at.is_some());
+    assert_eq!(point.migrations[APP_ID], 23);
+    assert!(point.migrations.contains_key(PLUGIN_ID));
+    assert!(point.tables.iter().any(|table| table == "blog_posts"));
+    assert!(!point.tables.iter().any(|table| table == "_sqlx_migrations"));
+
+    let paths: Vec<&str> = backup.artifacts.iter().map(|artifact| artifact.path.as_str()).collect();
+    assert!(paths.contains(&"tables/blog_posts.copy"));
+    assert!(paths.contains(&"media.json"));
+    let stored = storage.list(&format!("{}/", backup.storage_prefix)).await.unwrap();
+    assert!(stored.contains(&format!("{}/manifest.json", backup.storage_prefix)));
+    assert_eq!(backup.size_bytes, backup.artifacts.iter().map(|artifact| artifact.size).sum::<i64>());
+
+    let verification = backups.verify(backup.id).await.unwrap();
+    assert!(verification.verified);
+
+    // A changed file fails verification
+    storage
+        .put(&format!("{}/media.json", backup.storage_prefix), b"[]")
+        .await
+        .unwrap();
+    let verification = backups.verify(backup.id).await.unwrap();
+    assert!(!verification.verified);
+    assert_eq!(verification.mismatched, ["media.json"]);
+    assert!(backups.verify(Uuid::new_v4()).await.is_err());
+
+    let listed = backups.list().await.unwrap();
+    assert_eq!(listed.len(), 1);
+    assert_eq!(listed[0].backup.verified, Some(false));
+    let restore = listed[0].restore.as_ref().unwrap();
+    assert_eq!(restore.migrations[APP_ID], 23);
+    assert!(restore.steps.iter().any(|step| step.contains("\\copy \"blog_posts\"")));
+}
+
+#[tokio::test]
+async fn test_excluded_tables() {
+    let config = BackupConfig {
+        exclude_tables: vec!["admin_post_views".to_string()],
+        ..BackupConfig::default()
+    };
+    let (_env, _storage, backups) = setup(config).await;
+
+    let backup = backups.backup(None).await.unwrap();
+    assert!(!backup.point_in_time.tables.iter().any(|table| table == "admin_post_views"));
+    assert!(!backup
+        .artifacts
+        .iter()
+        .any(|artifact| artifact.path == "tables/admin_post_views.copy"));
+}
+
+#[tokio::test]
+async fn test_retention() {
+    let config = BackupConfig {
+        keep_last: 1,
+        max_age_days: 0,
+        ..BackupConfig::default()
+    };
+    let (_env, storage, backups) = setup(config).await;
+
+    let old = backups.backup(None).await.unwrap();
+    let new = backups.backup(None).await.unwrap();
+    assert_eq!(backups.prune().await.unwrap(), 1);
+
+    let listed = backups.list().await.unwrap();
+    let status = |id: Uuid| listed.iter().find(|entry| entry.backup.id == id).unwrap().backup.status;
+    assert_eq!(status(old.id), BackupStatus::Pruned);
+    assert_eq!(status(new.id), BackupStatus::Completed);
+    assert!(storage
+        .list(&format!("{}/", old.storage_prefix))
+        .await
+        .unwrap()
+        .is_empty());
+    assert!(listed.iter().find(|entry| entry.backup.id == old.id).unwrap().restore.is_none());
+
+    // The newest backup is kept whatever its age
+    assert_eq!(backups.prune().await.unwrap(), 0);
+}
+
+#[test]
+fn test_pg_dump_restore_steps() {
+    let backup = Backup {
+        id: Uuid::new_v4(),
+        status: BackupStatus::Completed,
+        method: "pg_dump".to_string(),
+        storage_prefix: "backups/b1".to_string(),
+        size_bytes: 0,
+        artifacts: Vec::new(),
+        point_in_time: PointInTime {
+            taken_at: Some(Utc::now()),
+            wal_lsn: Some("0/1A2B3C4D".to_string()),
+            migrations: BTreeMap::from([(APP_ID.to_string(), 23)]),
+            tables: Vec::new(),
+        },
+        error: None,
+        started_by: None,
+        started_at: Utc::now(),
+        completed_at: Some(Utc::now()),
+        verified_at: None,
+        verified: None,
+        pruned_at: None,
+    };
+
+    let restore = restore_instructions(&backup);
+    assert!(restore.steps.iter().any(|step| step.starts_with("pg_restore") && step.contains("backups/b1/database.dump")));
+    assert!(restore.steps.iter().any(|step| step.contains("0/1A2B3C4D")));
+    assert_eq!(restore.media_manifest, "backups/b1/media.json");
+}
@@ -0,0 +1,233 @@
+//! Content sync: idempotent imports, resuming from stored hashes after a
+//! restart, and edits made on both sides
+
+use rustpress_apps::prelude::HookRegistry;
+use rustpress_auth::db::DbPools;
+use rustpress_auth::{AuthPlugin, UserRole};
+use rustpress_blog_api::access::ContentAccess;
+use rustpress_blog_api::activity::ContentHooks;
+use rustpress_blog_api::cache::{self, CacheConfig};
+use rustpress_blog_api::content_sync::{parse, ContentSync, SyncConfig, SyncReport, SyncServices};
+use rustpress_blog_api::digests::{DigestConfig, DigestService};
+use rustpress_blog_api::mentions::{MentionConfig, MentionService};
+use rustpress_blog_api::services::{CategoryService, PostService, TagService};
+use rustpress_blog_api::sites::{Site, SiteService};
+use rustpress_blog_api::BlogApp;
+use rustpress_testing::{TestEnv, TestUser};
+use serde_json::{from_value, json};
+use std::path::PathBuf;
+use std::sync::Arc;
+use uuid::Uuid;
+
+struct Setup {
+    env: TestEnv,
+    posts: PostService,
+    categories: CategoryService,
+    tags: TagService,
+    site: Site,
+    author: Uuid,
+    /// The `rust` tag
+    tag: Uuid,
+    config: SyncConfig,
+}
+
+impl Setup {
+    async fn start() -> Self {
+        let env = TestEnv::start().await;
+        env.migrate(&AuthPlugin::migrations()).await;
+        BlogApp::migrations().run(&env.db).await.expect("blog migrations failed");
+
+        let auth = env.auth_service().await;
+        let author = TestUser::create(&auth, "ada@example.com", UserRole::Author).await.user.id;
+
+        let cache = cache::connect(&CacheConfig::default()).await.unwrap();
+        let pools = Arc::new(DbPools::new(env.db.clone()));
+        let hooks = Arc::new(ContentHooks::default());
+        let access = Arc::new(ContentAccess::new(pools.clone(), Arc::new(HookRegistry::new())));
+        let digests = Arc::new(DigestService::new(env.db.clone(), DigestConfig::default()));
+        let mentions = Arc::new(MentionService::new(env.db.clone(), digests, MentionConfig::default()));
+        let posts = PostService::new(pools, cache.clone(), hooks.clone(), access, mentions, 200);
+        let categories = CategoryService::new(env.db.clone(), cache.clone(), hooks);
+        let tags = TagService::new(env.db.clone(), cache);
+
+        let sites = SiteService::load(env.db.clone()).await.unwrap();
+        let (site, _) = sites.resolve(None, "/").await.expect("no default site");
+        let tag = tags.create(&site, from_value(json!({ "name": "rust" })).unwrap()).await.unwrap().id;
+
+        let config = SyncConfig {
+            enabled: true,
+            dir: std::env::temp_dir().join(format!("rustpress-sync-{}", Uuid::new_v4())),
+            default_author: Some(author),
+            ..Default::default()
+        };
+        std::fs::create_dir_all(config.dir.join(&site.slug)).unwrap();
+        Self { env, posts, categories, tags, site, author, tag, config }
+    }
+
+    fn services(&self) -> SyncServices<'_> {
+        SyncServices {
+            posts: &self.posts,
+            categories: &self.categories,
+            tags: &self.tags,
+        }
+    }
+
+    /// A sync as a freshly started instance has it: only the database is shared
+    fn sync(&self) -> ContentSync {
+        ContentSync::new(self.env.db.clone(), self.config.clone())
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.config.dir.join(&self.site.slug).join(name)
+    }
+
+    fn write(&self, name: &str, text: &str) {
+        std::fs::write(self.path(name), text).unwrap();
+    }
+
+    fn read(&self, name: &str) -> String {
+        std::fs::read_to_string(self.path(name)).unwrap()
+    }
+
+    async fn content(&self, id: Uuid) -> String {
+        self.posts.get_by_id(&self.site, id).await.unwrap().content
+    }
+}
+
+impl Drop for Setup {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.config.dir);
+    }
+}
+
+fn counts(report: &SyncReport) -> (usize, usize, usize, usize) {
+    (report.created, report.updated, report.deleted, report.errors.len())
+}
+
+#[tokio::test]
+async fn test_resync_changes_nothing() {
+    let setup = Setup::start().await;
+    let sync = setup.sync();
+    setup.write("kites.md", "+++\ntitle = \"Kites\"\ntags = [\"rust\"]\n+++\n\nFly them high\n");
+
+    let report = sync.import_site(setup.services(), &setup.site).await.unwrap();
+    assert_eq!(counts(&report), (1, 0, 0, 0));
+
+    // The same files again: nothing to do
+    let report = sync.import_site(setup.services(), &setup.site).await.unwrap();
+    assert_eq!(counts(&report), (0, 0, 0, 0));
+
+    // Exporting writes the file in canonical form, with the new post's id
+    let id: Uuid = sqlx::query_scalar("SELECT id FROM blog_posts WHERE site_id = $1")
+        .bind(setup.site.id)
+        .fetch_one(&setup.env.db)
+        .await
+        .unwrap();
+    assert_eq!(sync.export_site(setup.services(), &setup.site).await.unwrap(), 1);
+    let (front, content) = parse(&setup.read("kites.md")).unwrap();
+    assert_eq!(front.id, Some(id));
+    assert_eq!(front.author, Some(setup.author));
+    assert_eq!(front.tags, ["rust"]);
+    assert_eq!(content, "Fly them high");
+
+    // ... which neither a second export nor an import picks up as a change
+    assert_eq!(sync.export_site(setup.services(), &setup.site).await.unwrap(), 0);
+    let report = sync.import_site(setup.services(), &setup.site).await.unwrap();
+    assert_eq!(counts(&report), (0, 0, 0, 0));
+    let posts: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM blog_posts WHERE site_id = $1")
+        .bind(setup.site.id)
+        .fetch_one(&setup.env.db)
+        .await
+        .unwrap();
+    assert_eq!(posts, 1);
+}
+
+#[tokio::test]
+async fn test_restart_resumes_from_stored_hashes() {
+    let setup = Setup::start().await;
+    for name in ["one", "two", "three"] {
+        setup.write(&format!("{name}.md"), &format!("+++\ntitle = \"{name}\"\n+++\n\nPost {name}\n"));
+    }
+    let report = setup.sync().import_site(setup.services(), &setup.site).await.unwrap();
+    assert_eq!(counts(&report), (3, 0, 0, 0));
+    let two: Uuid = sqlx::query_scalar("SELECT post_id FROM content_sync_files WHERE site_id = $1 AND path = 'two.md'")
+        .bind(setup.site.id)
+        .fetch_one(&setup.env.db)
+        .await
+        .unwrap();
+
+    // While no instance runs: one file edited, one removed, one added
+    setup.write("two.md", "+++\ntitle = \"two\"\n+++\n\nPost two, edited\n");
+    std::fs::remove_file(setup.path("three.md")).unwrap();
+    setup.write("four.md", "+++\ntitle = \"four\"\n+++\n\nPost four\n");
+
+    // A new instance picks up exactly those changes
+    let sync = setup.sync();
+    let report = sync.import_site(setup.services(), &setup.site).await.unwrap();
+    assert_eq!(counts(&report), (1, 1, 1, 0));
+    assert_eq!(setup.content(two).await, "Post two, edited");
+    let tracked: Vec<String> = sqlx::query_scalar("SELECT path FROM content_sync_files WHERE site_id = $1 ORDER BY path")
+        .bind(setup.site.id)
+        .fetch_all(&setup.env.db)
+        .await
+        .unwrap();
+    assert_eq!(tracked, ["four.md", "one.md", "two.md"]);
+
+    let report = setup.sync().import_site(setup.services(), &setup.site).await.unwrap();
+    assert_eq!(counts(&report), (0, 0, 0, 0));
+
+    // An emptied directory is taken for a mistake, not a wish to delete everything
+    for name in tracked {
+        std::fs::remove_file(setup.path(&name)).unwrap();
+    }
+    let report = setup.sync().import_site(setup.services(), &setup.site).await.unwrap();
+    assert_eq!(counts(&report), (0, 0, 0, 0));
+    assert_eq!(setup.content(two).await, "Post two, edited");
+}
+
+#[tokio::test]
+async fn test_edits_on_both_sides() {
+    let setup = Setup::start().await;
+    let sync = setup.sync();
+    let request = json!({ "title": "Kites", "content": "First draft", "tag_ids": [setup.tag] });
+    let post = setup.posts.create(&setup.site, setup.author, from_value(request).unwrap(), None).await.unwrap();
+    assert_eq!(sync.export_posts(setup.services(), &setup.site, &[post.id]).await.unwrap(), 1);
+
+    // Edited in the database only: the import leaves the post alone and the
+    // export brings the file up to date
+    let existing = setup.posts.get_by_id(&setup.site, post.id).await.unwrap();
+    let request = json!({ "content": "Edited in the admin" });
+    setup.posts.update(&setup.site, existing, setup.author, from_value(request).unwrap()).await.unwrap();
+    let report = sync.import_site(setup.services(), &setup.site).await.unwrap();
+    assert_eq!(counts(&report), (0, 0, 0, 0));
+    assert_eq!(setup.content(post.id).await, "Edited in the admin");
+    assert_eq!(sync.export_posts(setup.services(), &setup.site, &[post.id]).await.unwrap(), 1);
+    assert_eq!(parse(&setup.read("kites.md")).unwrap().1, "Edited in the admin");
+
+    // Edited on both sides before either was synced: the changed file wins
+    let existing = setup.posts.get_by_id(&setup.site, post.id).await.unwrap();
+    let request = json!({ "content": "Edited in the admin again" });
+    setup.posts.update(&setup.site, existing, setup.author, from_value(request).unwrap()).await.unwrap();
+    let edited = setup.read("kites.md").replace("Edited in the admin", "Edited in the file");
+    setup.write("kites.md", &edited);
+    let report = sync.import_site(setup.services(), &setup.site).await.unwrap();
+    assert_eq!(counts(&report), (0, 1, 0, 0));
+    assert_eq!(setup.content(post.id).await, "Edited in the file");
+    assert_eq!(sync.export_posts(setup.services(), &setup.site, &[post.id]).await.unwrap(), 0);
+
+    // A file that can't be applied is reported, leaves the post as it was,
+    // and is retried until fixed
+    let broken = setup.read("kites.md").replace("Edited in the file", "Tagged").replace("tags = [\"rust\"]", "tags = [\"go\"]");
+    setup.write("kites.md", &broken);
+    for _ in 0..2 {
+        let report = sync.import_site(setup.services(), &setup.site).await.unwrap();
+        assert_eq!(counts(&report), (0, 0, 0, 1));
+        assert_eq!(report.errors[0].path, "kites.md");
+        assert!(report.errors[0].message.contains("Unknown tag: go"));
+    }
+    assert_eq!(setup.content(post.id).await, "Edited in the file");
+    setup.write("kites.md", &broken.replace("\"go\"", "\"rust\""));
+    let report = sync.import_site(setup.services(), &setup.site).await.unwrap();
+    assert_eq!(counts(&report), (0, 1, 0, 0));
+    assert_eq!(setup.content(post.id).await, "Tagged");
+}
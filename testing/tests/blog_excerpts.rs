@@ -0,0 +1,50 @@
+//! Generated excerpts cut at sentences and words, keeping inline markup
+//! balanced however the content's tags are nested or left open
+
+use rustpress_blog_api::excerpts::generate;
+
+#[test]
+fn test_word_cut_closes_nested_tags() {
+    let html = "<p>Kites <em>fly <strong>high above</strong> the windy</em> hills all day</p>";
+    assert_eq!(generate(html, 16), "Kites <em>fly <strong>high…</strong></em>");
+    assert_eq!(generate(html, 8), "Kites…");
+    assert_eq!(generate(html, 100), "Kites <em>fly <strong>high above</strong> the windy</em> hills all day");
+
+    // Attributes of kept elements survive; a word longer than the excerpt is cut inside
+    let html = r#"<p>Visible <a href="/x?a=1&amp;b=2">link text here</a> after</p>"#;
+    assert_eq!(generate(html, 18), r#"Visible <a href="/x?a=1&amp;b=2">link text…</a>"#);
+    assert_eq!(generate("<p>Supercalifragilistic</p>", 5), "Super…");
+}
+
+#[test]
+fn test_unclosed_and_misnested_tags() {
+    // Left open by the content, closed by the excerpt
+    let html = "<p>Kites <em>fly <strong>high above the windy hills";
+    assert_eq!(generate(html, 15), "Kites <em>fly <strong>high…</strong></em>");
+    let html = r#"<p>Short <em>and <a href="/more">open"#;
+    assert_eq!(generate(html, 100), r#"Short <em>and <a href="/more">open</a></em>"#);
+
+    // Closing an outer element closes the ones opened inside it
+    let html = "<p>Kites <em>fly <strong>high</em> above</strong> the hills</p>";
+    assert_eq!(generate(html, 20), "Kites <em>fly <strong>high</strong></em> above…");
+
+    // Inline elements don't run on past the end of their block
+    let html = "<p>x < y and <b>bold</p><p>next para</p>";
+    assert_eq!(generate(html, 100), "x < y and <b>bold</b> next para");
+}
+
+#[test]
+fn test_sentences_entities_and_skipped_content() {
+    let html = "<p>One sentence here. Another one follows and is long.</p>";
+    assert_eq!(generate(html, 30), "One sentence here.");
+
+    // An entity is one character and keeps its `;`; other trailing punctuation goes
+    assert_eq!(generate("<p>Tom &amp; Jerry&hellip; run away</p>", 14), "Tom &amp; Jerry&hellip;…");
+    assert_eq!(generate("<p>Wait, what; really: yes</p>", 11), "Wait, what…");
+
+    // Markup inside scripts isn't content
+    let html = "<script>document.write('<em>')</script><ul><li>one</li><li>two</li></ul>";
+    assert_eq!(generate(html, 50), "one two");
+
+    assert_eq!(generate("Intro <em>text<!--more-->Rest</em> of it", 3), "Intro <em>text</em>");
+}
@@ -0,0 +1,120 @@
+//! Token validation and analytics ingestion over gRPC
+
+use chrono::Utc;
+use rustpress_analytics::services::{IngestMetrics, IngestService, COLLECT_KEYS_SECRET};
+use rustpress_analytics::{AnalyticsConfig, AnalyticsPlugin};
+use rustpress_auth::secrets::StaticSecrets;
+use rustpress_auth::{AuthPlugin, MetricsRegistry, UserRole};
+use rustpress_blog_api::grpc::proto::analytics::ingest_server::Ingest;
+use rustpress_blog_api::grpc::proto::analytics::{CollectRequest, Event};
+use rustpress_blog_api::grpc::proto::auth::tokens_server::Tokens;
+use rustpress_blog_api::grpc::proto::auth::ValidateTokenRequest;
+use rustpress_blog_api::grpc::{IngestApi, TokensApi};
+use rustpress_testing::{TestEnv, TestUser};
+use std::sync::Arc;
+use tonic::{Code, Request};
+use uuid::Uuid;
+
+#[tokio::test]
+async fn test_validate_token() {
+    let env = TestEnv::start().await;
+    env.migrate(&AuthPlugin::migrations()).await;
+
+    let auth = env.auth_service().await;
+    let ada = TestUser::create(&auth, "ada@example.com", UserRole::Editor).await;
+
+    // With or without the scheme, as clients copy it from a header
+    for token in [ada.access_token.clone(), ada.bearer()] {
+        let response = TokensApi
+            .validate_token(Request::new(ValidateTokenRequest { token }))
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(response.valid, "{}", response.error);
+        let claims = response.claims.unwrap();
+        assert_eq!(claims.user_id, ada.user.id.to_string());
+        assert_eq!(claims.email, "ada@example.com");
+        assert!(claims.expires_at.unwrap().seconds > Utc::now().timestamp());
+    }
+
+    // Bad tokens are answered, not failed
+    let response = TokensApi
+        .validate_token(Request::new(ValidateTokenRequest {
+            token: "not.a.token".to_string(),
+        }))
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(!response.valid);
+    assert!(!response.error.is_empty());
+    assert!(response.claims.is_none());
+}
+
+#[tokio::test]
+async fn test_collect() {
+    let env = TestEnv::start().await;
+    env.migrate(&AuthPlugin::migrations()).await;
+    env.migrate(&AnalyticsPlugin::migrations()).await;
+
+    let metrics = IngestMetrics::register(&Arc::new(MetricsRegistry::default()).plugin("rustpress-analytics")).unwrap();
+    let secrets = Arc::new(StaticSecrets::new([(COLLECT_KEYS_SECRET, "backend-key".to_string())]));
+    let ingest = IngestService::new(
+        env.db.clone(),
+        AnalyticsConfig::default(),
+        secrets,
+        std::time::Duration::MAX,
+        metrics,
+    );
+    let api = IngestApi::new(Arc::new(ingest));
+
+    let event = |visitor_id: &str| Event {
+        timestamp: Some(prost_types::Timestamp {
+            seconds: Utc::now().timestamp(),
+            nanos: 0,
+        }),
+        visitor_id: visitor_id.to_string(),
+        category: "conversion".to_string(),
+        action: "purchase".to_string(),
+        value: Some(49),
+        ..Default::default()
+    };
+    let request = |key: Option<&str>, events: Vec<Event>| {
+        let mut request = Request::new(CollectRequest { events });
+        if let Some(key) = key {
+            request.metadata_mut().insert("x-api-key", key.parse().unwrap());
+        }
+        request
+    };
+    let visitor = Uuid::new_v4().to_string();
+
+    // Keys as over HTTP
+    let status = api.collect(request(None, vec![event(&visitor)])).await.unwrap_err();
+    assert_eq!(status.code(), Code::Unauthenticated);
+    let status = api.collect(request(Some("stolen-key"), vec![event(&visitor)])).await.unwrap_err();
+    assert_eq!(status.code(), Code::Unauthenticated);
+
+    let response = api
+        .collect(request(Some("backend-key"), vec![event(&visitor)]))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(response.accepted, 1);
+    assert!(response.rejected.is_empty());
+
+    // Malformed events name their field
+    let status = api
+        .collect(request(Some("backend-key"), vec![event(&visitor), event("someone")]))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+    assert!(status.message().contains("events[1].visitor_id"), "{}", status.message());
+    let status = api.collect(request(Some("backend-key"), vec![])).await.unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+
+    // Without an active analytics plugin there's nothing to ingest into
+    let status = IngestApi::shared()
+        .collect(request(Some("backend-key"), vec![event(&visitor)]))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::Unavailable);
+}
@@ -0,0 +1,78 @@
+//! Request IDs in the context of the actions content changes run
+
+use axum::body::Body;
+use axum::http::Request;
+use axum::routing::post;
+use axum::{middleware, Router};
+use rustpress_apps::prelude::HookRegistry;
+use rustpress_auth::problem::{trace_id, TRACE_ID_HEADER};
+use rustpress_blog_api::activity::{ContentEvent, ContentHooks, CONTENT_CHANGED};
+use rustpress_blog_api::models::{ContentAction, ContentObject};
+use serde_json::Value;
+use std::sync::{Arc, Mutex};
+use tower::ServiceExt;
+use uuid::Uuid;
+
+#[tokio::test]
+async fn test_content_changed_action_sees_request_id() {
+    // Stands in for a plugin: records what each run of the action sees
+    let seen: Arc<Mutex<Vec<(String, Value)>>> = Arc::default();
+    let registry = Arc::new(HookRegistry::new());
+    registry
+        .add_action(
+            CONTENT_CHANGED,
+            {
+                let seen = seen.clone();
+                move |ctx, data| {
+                    let seen = seen.clone();
+                    async move {
+                        let event = *data.downcast::<Value>().unwrap();
+                        seen.lock().unwrap().push((ctx.request_id, event));
+                        Ok(())
+                    }
+                }
+            },
+            0,
+        )
+        .await;
+    let hooks = Arc::new(ContentHooks::with_actions(registry));
+
+    let post_id = Uuid::new_v4();
+    let event = ContentEvent {
+        site_id: Uuid::new_v4(),
+        actor_id: None,
+        object_type: ContentObject::Post,
+        object_id: post_id,
+        action: ContentAction::Published,
+        label: Some("Kites".into()),
+        changes: None,
+    };
+    let app = Router::new()
+        .route(
+            "/publish",
+            post({
+                let (hooks, event) = (hooks.clone(), event.clone());
+                move || {
+                    let (hooks, event) = (hooks.clone(), event.clone());
+                    async move { hooks.emit(event).await }
+                }
+            }),
+        )
+        .layer(middleware::from_fn(trace_id));
+
+    let request = Request::post("/publish")
+        .header(TRACE_ID_HEADER, "abc-123")
+        .body(Body::empty())
+        .unwrap();
+    app.oneshot(request).await.unwrap();
+
+    // Outside a request the ID is empty
+    hooks.emit(event).await;
+
+    let seen = seen.lock().unwrap();
+    assert_eq!(seen.len(), 2);
+    assert_eq!(seen[0].0, "abc-123");
+    assert_eq!(seen[0].1["object_id"], post_id.to_string());
+    assert_eq!(seen[0].1["action"], "published");
+    assert_eq!(seen[1].0, "");
+}
@@ -0,0 +1,65 @@
+//! Post edit locks: heartbeats, conflicts, takeover and expiry
+
+use rustpress_auth::{AuthPlugin, UserRole};
+use rustpress_blog_api::edit_locks::{EditLockConfig, EditLockService};
+use rustpress_blog_api::services::ServiceError;
+use rustpress_blog_api::sites::SiteService;
+use rustpress_blog_api::BlogApp;
+use rustpress_testing::{TestEnv, TestUser};
+use std::time::Duration;
+use uuid::Uuid;
+
+#[tokio::test]
+async fn test_edit_locks() {
+    let env = TestEnv::start().await;
+    env.migrate(&AuthPlugin::migrations()).await;
+    BlogApp::migrations().run(&env.db).await.expect("blog migrations failed");
+
+    let auth = env.auth_service().await;
+    let ada = TestUser::create(&auth, "ada@example.com", UserRole::Editor).await.user.id;
+    let grace = TestUser::create(&auth, "grace@example.com", UserRole::Editor).await.user.id;
+    let admin = TestUser::create(&auth, "admin@example.com", UserRole::Admin).await.user.id;
+
+    let sites = SiteService::load(env.db.clone()).await.unwrap();
+    let (site, _) = sites.resolve(None, "/").await.expect("no default site");
+    let post_id: Uuid = sqlx::query_scalar(
+        "INSERT INTO blog_posts (author_id, title, slug, content) VALUES ($1, 'Draft', 'draft', 'Hi') RETURNING id",
+    )
+    .bind(ada)
+    .fetch_one(&env.db)
+    .await
+    .unwrap();
+
+    let locks = EditLockService::new(env.db.clone(), EditLockConfig { ttl_secs: 1 });
+
+    // Heartbeats renew the holder's lock; others are told who holds it
+    let taken = locks.heartbeat(&site, post_id, ada).await.unwrap();
+    assert_eq!(taken.user_name, "ada");
+    let renewed = locks.heartbeat(&site, post_id, ada).await.unwrap();
+    assert_eq!(renewed.acquired_at, taken.acquired_at);
+    assert!(renewed.expires_at >= taken.expires_at);
+    match locks.heartbeat(&site, post_id, grace).await {
+        Err(ServiceError::Locked(lock)) => assert_eq!(lock.user_id, ada),
+        other => panic!("expected a conflict, got {:?}", other),
+    }
+    assert!(matches!(locks.ensure_unlocked(&site, post_id, grace).await, Err(ServiceError::Locked(_))));
+    locks.ensure_unlocked(&site, post_id, ada).await.unwrap();
+
+    // Admins take over; the previous holder is locked out
+    let taken_over = locks.take_over(&site, post_id, admin).await.unwrap();
+    assert_eq!(taken_over.user_id, admin);
+    assert!(matches!(locks.heartbeat(&site, post_id, ada).await, Err(ServiceError::Locked(_))));
+
+    // Releasing only works for the holder
+    locks.release(&site, post_id, ada).await.unwrap();
+    assert!(locks.current(&site, post_id).await.unwrap().is_some());
+    locks.release(&site, post_id, admin).await.unwrap();
+    assert!(locks.current(&site, post_id).await.unwrap().is_none());
+
+    // Stale locks lapse
+    locks.heartbeat(&site, post_id, grace).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(1200)).await;
+    assert!(locks.current(&site, post_id).await.unwrap().is_none());
+    let taken = locks.heartbeat(&site, post_id, ada).await.unwrap();
+    assert_eq!(taken.user_id, ada);
+}
@@ -0,0 +1,95 @@
+//! Marketplace signatures and plugin artifact installs
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use ed25519_dalek::{Signer, SigningKey};
+use rustpress_blog_api::marketplace::{unpack, verify_signature, verifying_key};
+use std::io::{Cursor, Write};
+use std::path::PathBuf;
+use uuid::Uuid;
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+fn artifact(files: &[(&str, &str)]) -> Vec<u8> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    for (name, body) in files {
+        zip.start_file(*name, SimpleFileOptions::default()).unwrap();
+        zip.write_all(body.as_bytes()).unwrap();
+    }
+    zip.finish().unwrap().into_inner()
+}
+
+fn manifest(id: &str, version: &str) -> String {
+    format!("[plugin]\nid = \"{}\"\nname = \"SEO\"\nversion = \"{}\"\n", id, version)
+}
+
+fn plugins_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("rustpress-plugins-{}", Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn test_signatures() {
+    let signer = SigningKey::from_bytes(&[7; 32]);
+    let other = SigningKey::from_bytes(&[9; 32]);
+    let key = verifying_key(&STANDARD.encode(signer.verifying_key().as_bytes())).unwrap();
+    assert!(verifying_key("bm90IGEga2V5").is_err());
+
+    let index = br#"{"plugins": []}"#;
+    let signature = STANDARD.encode(signer.sign(index).to_bytes());
+    assert!(verify_signature(&[key], index, &signature));
+
+    // Tampered data, another key's signature and garbage are refused
+    assert!(!verify_signature(&[key], br#"{"plugins": [{}]}"#, &signature));
+    let forged = STANDARD.encode(other.sign(index).to_bytes());
+    assert!(!verify_signature(&[key], index, &forged));
+    assert!(!verify_signature(&[key], index, "not base64!"));
+    assert!(!verify_signature(&[], index, &signature));
+}
+
+#[test]
+fn test_unpack_replaces_plugin() {
+    let dir = plugins_dir();
+
+    let v1 = artifact(&[("plugin.toml", &manifest("seo", "1.0.0")), ("migrations/001_init.up.sql", "SELECT 1;")]);
+    unpack(&v1, &dir, "seo", "1.0.0").unwrap();
+    assert!(dir.join("seo/migrations/001_init.up.sql").exists());
+
+    let v2 = artifact(&[("plugin.toml", &manifest("seo", "1.1.0"))]);
+    unpack(&v2, &dir, "seo", "1.1.0").unwrap();
+    let installed = std::fs::read_to_string(dir.join("seo/plugin.toml")).unwrap();
+    assert!(installed.contains("1.1.0"));
+    assert!(!dir.join("seo/migrations").exists());
+
+    // Nothing but the plugin is left behind
+    let entries = std::fs::read_dir(&dir).unwrap().count();
+    assert_eq!(entries, 1);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_unpack_refuses_bad_artifacts() {
+    let dir = plugins_dir();
+    let v1 = artifact(&[("plugin.toml", &manifest("seo", "1.0.0"))]);
+    unpack(&v1, &dir, "seo", "1.0.0").unwrap();
+
+    // Not the version the registry promised
+    let mislabeled = artifact(&[("plugin.toml", &manifest("seo", "9.9.9"))]);
+    assert!(unpack(&mislabeled, &dir, "seo", "1.1.0").is_err());
+    // Another plugin
+    let other = artifact(&[("plugin.toml", &manifest("spam", "1.1.0"))]);
+    assert!(unpack(&other, &dir, "seo", "1.1.0").is_err());
+    // Paths out of the plugin's directory
+    let escaping = artifact(&[("plugin.toml", &manifest("seo", "1.1.0")), ("../escaped.txt", "x")]);
+    assert!(unpack(&escaping, &dir, "seo", "1.1.0").is_err());
+    assert!(!dir.join("escaped.txt").exists());
+    assert!(unpack(b"not a zip", &dir, "seo", "1.1.0").is_err());
+    assert!(unpack(&v1, &dir, "../seo", "1.0.0").is_err());
+
+    // The installed version is untouched and no staging is left behind
+    let installed = std::fs::read_to_string(dir.join("seo/plugin.toml")).unwrap();
+    assert!(installed.contains("1.0.0"));
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,92 @@
+//! Mentions recorded, notified and linked as posts are saved and published
+
+use rustpress_apps::prelude::HookRegistry;
+use rustpress_auth::db::DbPools;
+use rustpress_auth::{AuthPlugin, UserRole};
+use rustpress_blog_api::access::{ContentAccess, Viewer};
+use rustpress_blog_api::activity::ContentHooks;
+use rustpress_blog_api::cache::{self, CacheConfig};
+use rustpress_blog_api::digests::{DigestConfig, DigestService};
+use rustpress_blog_api::mentions::{MentionConfig, MentionService};
+use rustpress_blog_api::models::{ContentObject, MentionQuery};
+use rustpress_blog_api::services::{ensure_author_slug, PostService};
+use rustpress_blog_api::sites::SiteService;
+use rustpress_blog_api::BlogApp;
+use rustpress_testing::{TestEnv, TestUser};
+use serde_json::{from_value, json};
+use std::sync::Arc;
+
+#[tokio::test]
+async fn test_post_mentions() {
+    let env = TestEnv::start().await;
+    env.migrate(&AuthPlugin::migrations()).await;
+    BlogApp::migrations().run(&env.db).await.expect("blog migrations failed");
+
+    let auth = env.auth_service().await;
+    let author = TestUser::create(&auth, "ada@example.com", UserRole::Author).await;
+    let mentioned = TestUser::create(&auth, "grace@example.com", UserRole::User).await;
+    ensure_author_slug(&env.db, mentioned.user.id).await.unwrap();
+    let actor = author.user.id;
+
+    let cache = cache::connect(&CacheConfig::default()).await.unwrap();
+    let pools = Arc::new(DbPools::new(env.db.clone()));
+    let access = Arc::new(ContentAccess::new(pools.clone(), Arc::new(HookRegistry::new())));
+    let digests = Arc::new(DigestService::new(env.db.clone(), DigestConfig::default()));
+    let mentions = Arc::new(MentionService::new(env.db.clone(), digests, MentionConfig::default()));
+    let hooks = Arc::new(ContentHooks::default());
+    hooks.listen(mentions.clone()).await;
+    let posts = PostService::new(pools, cache, hooks, access, mentions.clone(), 200);
+
+    let sites = SiteService::load(env.db.clone()).await.unwrap();
+    let (site, _) = sites.resolve(None, "/").await.expect("no default site");
+    let unread = MentionQuery { unread: true };
+    let db = &env.db;
+    let pending = || async move {
+        sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM pending_notifications WHERE kind = 'mention'")
+            .fetch_one(db)
+            .await
+            .unwrap()
+    };
+
+    // Drafts record mentions without notifying them; code, addresses and
+    // unknown names are skipped
+    let content = "<p>Thanks @Grace and @nobody! Try <code>@grace</code> or grace@example.com</p>";
+    let request = json!({ "title": "Credits", "content": content });
+    let post = posts.create(&site, actor, from_value(request).unwrap(), None).await.unwrap();
+    assert!(mentions.list(&site, mentioned.user.id, &unread).await.unwrap().is_empty());
+    assert_eq!(pending().await, 0);
+
+    // Publishing notifies in-app and queues the digest entry
+    posts.publish(&site, post.id, actor).await.unwrap();
+    let listed = mentions.list(&site, mentioned.user.id, &unread).await.unwrap();
+    assert_eq!(listed.len(), 1);
+    assert_eq!(listed[0].object_type, ContentObject::Post);
+    assert_eq!(listed[0].actor_name, "ada");
+    assert_eq!(pending().await, 1);
+
+    // Read back with the mention marked up; no author page yet, so no link
+    let read = posts.get_by_slug(&site, "credits", &Viewer::anonymous()).await.unwrap();
+    assert_eq!(
+        read.post.content,
+        "<p>Thanks <span class=\"mention\">@Grace</span> and @nobody! Try <code>@grace</code> or grace@example.com</p>"
+    );
+
+    // Saving again doesn't notify twice
+    let existing = posts.get_by_id(&site, post.id).await.unwrap();
+    let request = json!({ "content": format!("{} Again, @grace.", content) });
+    posts.update(&site, existing, actor, from_value(request).unwrap()).await.unwrap();
+    assert_eq!(mentions.list(&site, mentioned.user.id, &unread).await.unwrap().len(), 1);
+    assert_eq!(pending().await, 1);
+
+    mentions.mark_read(&site, mentioned.user.id).await.unwrap();
+    assert!(mentions.list(&site, mentioned.user.id, &unread).await.unwrap().is_empty());
+    let all = MentionQuery::default();
+    assert!(mentions.list(&site, mentioned.user.id, &all).await.unwrap()[0].read_at.is_some());
+
+    // Removing the mention withdraws it
+    let existing = posts.get_by_id(&site, post.id).await.unwrap();
+    let request = json!({ "content": "<p>Thanks everyone</p>" });
+    posts.update(&site, existing, actor, from_value(request).unwrap()).await.unwrap();
+    assert!(mentions.list(&site, mentioned.user.id, &all).await.unwrap().is_empty());
+    assert_eq!(pending().await, 0);
+}
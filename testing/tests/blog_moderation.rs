@@ -0,0 +1,61 @@
+//! Content policy rules and language packs applied to comments and posts
+
+use rustpress_apps::prelude::SettingsManager;
+use rustpress_auth::AuthPlugin;
+use rustpress_blog_api::models::ModerationAction;
+use rustpress_blog_api::moderation::{ModerationService, ModerationTarget};
+use rustpress_blog_api::sites::SiteService;
+use rustpress_blog_api::{BlogApp, APP_ID};
+use rustpress_testing::TestEnv;
+
+#[tokio::test]
+async fn test_content_policy() {
+    let env = TestEnv::start().await;
+    env.migrate(&AuthPlugin::migrations()).await;
+    BlogApp::migrations().run(&env.db).await.expect("blog migrations failed");
+
+    let sites = SiteService::load(env.db.clone()).await.unwrap();
+    let (site, _) = sites.resolve(None, "/").await.expect("no default site");
+    let namespace = site.settings_namespace(APP_ID);
+    let settings = SettingsManager::memory();
+    let moderation = ModerationService::new(settings.clone());
+
+    let rules = "darn\nhold: free money\nreject: /https?://\\S*\\.example/i\ncensor: /([/";
+    settings.set(&namespace, "moderation_rules", rules).await.unwrap();
+    settings
+        .set(&namespace, "moderation_packs", "[en]\nheck\n[fr]\nzut")
+        .await
+        .unwrap();
+
+    // Whole words only, in any case, censored in place
+    let comment = moderation
+        .check(&site, ModerationTarget::Comment, None, "Darn it, darned heck!")
+        .await
+        .unwrap();
+    assert_eq!(comment.action, Some(ModerationAction::Censor));
+    assert_eq!(comment.content, "**** it, darned ****!");
+    assert_eq!(comment.matches[1].pack.as_deref(), Some("en"));
+
+    // The strictest action wins
+    let text = "Free money at HTTP://win.example, darn";
+    let comment = moderation.check(&site, ModerationTarget::Comment, None, text).await.unwrap();
+    assert_eq!(comment.action, Some(ModerationAction::Reject));
+
+    // Posts are only checked once enabled
+    let post = moderation.check(&site, ModerationTarget::Post, None, "free money").await.unwrap();
+    assert_eq!(post.action, None);
+    settings.set(&namespace, "moderation_posts", &true).await.unwrap();
+    let post = moderation.check(&site, ModerationTarget::Post, None, "free money").await.unwrap();
+    assert_eq!(post.action, Some(ModerationAction::Hold));
+
+    // Packs follow the site's languages; broken lines are reported
+    settings.set(&namespace, "moderation_languages", "fr").await.unwrap();
+    let tested = moderation.test(&site, "heck, zut").await.unwrap();
+    assert_eq!(tested.content, "heck, ***");
+    assert_eq!(tested.errors.len(), 1);
+    assert!(tested.errors[0].starts_with("rules line 4"));
+
+    settings.set(&namespace, "moderation_enabled", &false).await.unwrap();
+    let comment = moderation.check(&site, ModerationTarget::Comment, None, "zut").await.unwrap();
+    assert_eq!(comment.content, "zut");
+}
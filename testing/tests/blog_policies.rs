@@ -0,0 +1,62 @@
+//! Policy rules see the user's role on the current site, not their account role
+
+use rustpress_auth::policy::{Policy, Rule, Subject};
+use rustpress_auth::{AuthPlugin, UserRole};
+use rustpress_blog_api::policies::{UpdatePost, DEFAULTS};
+use rustpress_blog_api::sites::{Site, SiteService};
+use rustpress_blog_api::BlogApp;
+use rustpress_testing::{TestEnv, TestUser};
+use serde_json::{from_value, json};
+use uuid::Uuid;
+
+/// Whether the user may edit a post they don't own on `site`
+async fn may_edit(sites: &SiteService, site: &Site, user_id: Uuid, account_role: &str) -> bool {
+    let (_, rule) = DEFAULTS.iter().find(|(name, _)| *name == UpdatePost::NAME).unwrap();
+    let rule: Rule = rule.parse().unwrap();
+    let subject = Subject {
+        id: Some(user_id),
+        role: sites.role(site, user_id, account_role).await.unwrap(),
+    };
+    rule.allows(&subject, &())
+}
+
+#[tokio::test]
+async fn test_roles_differ_per_site() {
+    let env = TestEnv::start().await;
+    env.migrate(&AuthPlugin::migrations()).await;
+    BlogApp::migrations().run(&env.db).await.expect("blog migrations failed");
+
+    let auth = env.auth_service().await;
+    let ada = TestUser::create(&auth, "ada@example.com", UserRole::User).await.user.id;
+    let admin = TestUser::create(&auth, "admin@example.com", UserRole::Admin).await.user.id;
+
+    let sites = SiteService::load(env.db.clone()).await.unwrap();
+    let (main, _) = sites.resolve(None, "/").await.expect("no default site");
+    let mut others = Vec::new();
+    for slug in ["kites", "cooking"] {
+        let req = from_value(json!({ "slug": slug, "name": slug, "path_prefix": format!("/{slug}") })).unwrap();
+        others.push(sites.create(req).await.unwrap());
+    }
+    let (kites, cooking) = (&others[0], &others[1]);
+
+    // An editor on one site and a subscriber on the other
+    sites.set_member(kites.id, ada, "editor").await.unwrap();
+    sites.set_member(cooking.id, ada, "subscriber").await.unwrap();
+    assert_eq!(sites.role(kites, ada, "user").await.unwrap().as_deref(), Some("editor"));
+    assert_eq!(sites.role(cooking, ada, "user").await.unwrap().as_deref(), Some("subscriber"));
+    assert!(may_edit(&sites, kites, ada, "user").await);
+    assert!(!may_edit(&sites, cooking, ada, "user").await);
+
+    // A global editor role doesn't carry over to a site where they're a subscriber
+    assert!(!may_edit(&sites, cooking, ada, "editor").await);
+
+    // Without a membership: the account role on the default site, none elsewhere
+    sites.remove_member(kites.id, ada).await.unwrap();
+    assert_eq!(sites.role(kites, ada, "editor").await.unwrap(), None);
+    assert!(!may_edit(&sites, kites, ada, "editor").await);
+    assert_eq!(sites.role(&main, ada, "editor").await.unwrap().as_deref(), Some("editor"));
+    assert!(may_edit(&sites, &main, ada, "editor").await);
+
+    // Admins are admins on every site
+    assert!(may_edit(&sites, cooking, admin, "admin").await);
+}
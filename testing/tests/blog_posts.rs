@@ -0,0 +1,232 @@
+//! Post CRUD through the post service, cached in Redis, and the categories
+//! and tags a post may be filed under
+
+use rustpress_apps::prelude::HookRegistry;
+use rustpress_auth::db::DbPools;
+use rustpress_auth::{AuthPlugin, UserRole};
+use rustpress_blog_api::access::{ContentAccess, Viewer};
+use rustpress_blog_api::activity::ContentHooks;
+use rustpress_blog_api::cache::{self, CacheConfig, CacheDriver};
+use rustpress_blog_api::digests::{DigestConfig, DigestService};
+use rustpress_blog_api::mentions::{MentionConfig, MentionService};
+use rustpress_blog_api::services::{CategoryService, PostService, ServiceError, TagService};
+use rustpress_blog_api::sites::{Site, SiteService};
+use rustpress_blog_api::BlogApp;
+use rustpress_testing::{TestEnv, TestUser};
+use serde_json::{from_value, json};
+use sqlx::postgres::PgPoolOptions;
+use sqlx::{Executor, PgPool};
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Contents of the published posts
+async fn published(posts: &PostService, site: &Site, viewer: &Viewer) -> Vec<String> {
+    let query = from_value(json!({})).unwrap();
+    let page = posts.list_published(site, &query, viewer).await.unwrap();
+    page.data.into_iter().map(|post| post.post.content).collect()
+}
+
+#[tokio::test]
+async fn test_post_crud_with_redis_cache() {
+    let env = TestEnv::with_redis().await;
+    env.migrate(&AuthPlugin::migrations()).await;
+    BlogApp::migrations().run(&env.db).await.expect("blog migrations failed");
+
+    let auth = env.auth_service().await;
+    let author = TestUser::create(&auth, "ada@example.com", UserRole::Author).await;
+    let actor = author.user.id;
+
+    let cache = cache::connect(&CacheConfig {
+        driver: CacheDriver::Redis,
+        redis_url: env.redis_url().to_string(),
+        ..Default::default()
+    })
+    .await
+    .expect("failed to connect to Redis");
+    let pools = Arc::new(DbPools::new(env.db.clone()));
+    let access = Arc::new(ContentAccess::new(pools.clone(), Arc::new(HookRegistry::new())));
+    let digests = Arc::new(DigestService::new(env.db.clone(), DigestConfig::default()));
+    let mentions = Arc::new(MentionService::new(env.db.clone(), digests, MentionConfig::default()));
+    let posts = PostService::new(pools, cache, Arc::new(ContentHooks::default()), access, mentions, 200);
+
+    let sites = SiteService::load(env.db.clone()).await.unwrap();
+    let (site, _) = sites.resolve(None, "/").await.expect("no default site");
+    let viewer = Viewer::anonymous();
+
+    // Drafts aren't public
+    let request = json!({ "title": "Hello Cache", "content": "First version" });
+    let post = posts.create(&site, actor, from_value(request).unwrap(), None).await.unwrap();
+    assert_eq!(post.slug, "hello-cache");
+    assert!(matches!(
+        posts.get_by_slug(&site, "hello-cache", &viewer).await,
+        Err(ServiceError::NotFound(_))
+    ));
+    assert!(published(&posts, &site, &viewer).await.is_empty());
+
+    // Publishing invalidates the cached listing
+    posts.publish(&site, post.id, actor).await.unwrap();
+    assert_eq!(published(&posts, &site, &viewer).await, ["First version"]);
+    let read = posts.get_by_slug(&site, "hello-cache", &viewer).await.unwrap();
+    assert_eq!(read.post.content, "First version");
+
+    // Reads are served from the cache, not the database
+    sqlx::query("UPDATE blog_posts SET content = 'Edited behind the cache' WHERE id = $1")
+        .bind(post.id)
+        .execute(&env.db)
+        .await
+        .unwrap();
+    let read = posts.get_by_slug(&site, "hello-cache", &viewer).await.unwrap();
+    assert_eq!(read.post.content, "First version");
+
+    // Updates through the service invalidate
+    let existing = posts.get_by_id(&site, post.id).await.unwrap();
+    let request = json!({ "content": "Second version" });
+    posts.update(&site, existing, actor, from_value(request).unwrap()).await.unwrap();
+    let read = posts.get_by_slug(&site, "hello-cache", &viewer).await.unwrap();
+    assert_eq!(read.post.content, "Second version");
+    assert_eq!(published(&posts, &site, &viewer).await, ["Second version"]);
+
+    // So do deletes
+    let existing = posts.get_by_id(&site, post.id).await.unwrap();
+    posts.delete(&site, existing, actor).await.unwrap();
+    assert!(matches!(
+        posts.get_by_slug(&site, "hello-cache", &viewer).await,
+        Err(ServiceError::NotFound(_))
+    ));
+    assert!(published(&posts, &site, &viewer).await.is_empty());
+}
+
+/// Number of categories and tags of the post with `slug`, if there is one
+async fn filed_under(db: &PgPool, slug: &str) -> Option<(i64, i64)> {
+    sqlx::query_as(
+        "SELECT (SELECT COUNT(*) FROM blog_post_categories WHERE post_id = p.id),
+                (SELECT COUNT(*) FROM blog_post_tags WHERE post_id = p.id)
+         FROM blog_posts p WHERE slug = $1",
+    )
+    .bind(slug)
+    .fetch_optional(db)
+    .await
+    .unwrap()
+}
+
+#[tokio::test]
+async fn test_other_sites_categories_and_tags_are_rejected() {
+    let env = TestEnv::start().await;
+    env.migrate(&AuthPlugin::migrations()).await;
+    BlogApp::migrations().run(&env.db).await.expect("blog migrations failed");
+
+    let auth = env.auth_service().await;
+    let actor = TestUser::create(&auth, "ada@example.com", UserRole::Author).await.user.id;
+
+    let cache = cache::connect(&CacheConfig::default()).await.unwrap();
+    let pools = Arc::new(DbPools::new(env.db.clone()));
+    let hooks = Arc::new(ContentHooks::default());
+    let access = Arc::new(ContentAccess::new(pools.clone(), Arc::new(HookRegistry::new())));
+    let digests = Arc::new(DigestService::new(env.db.clone(), DigestConfig::default()));
+    let mentions = Arc::new(MentionService::new(env.db.clone(), digests, MentionConfig::default()));
+    let posts = PostService::new(pools, cache.clone(), hooks.clone(), access, mentions, 200);
+    let categories = CategoryService::new(env.db.clone(), cache.clone(), hooks);
+    let tags = TagService::new(env.db.clone(), cache);
+
+    let sites = SiteService::load(env.db.clone()).await.unwrap();
+    let (site, _) = sites.resolve(None, "/").await.expect("no default site");
+    let request = json!({ "slug": "kites", "name": "Kites", "path_prefix": "/kites" });
+    let other = sites.create(from_value(request).unwrap()).await.unwrap();
+
+    let news = categories.create(&site, actor, from_value(json!({ "name": "News" })).unwrap()).await.unwrap().id;
+    let rust = tags.create(&site, from_value(json!({ "name": "rust" })).unwrap()).await.unwrap().id;
+    let elsewhere = categories.create(&other, actor, from_value(json!({ "name": "News" })).unwrap()).await.unwrap().id;
+    let other_tag = tags.create(&other, from_value(json!({ "name": "rust" })).unwrap()).await.unwrap().id;
+
+    let rejected = |err: ServiceError, field: &str, ids: &[Uuid]| match err {
+        ServiceError::InvalidFields(errors) => {
+            assert_eq!(errors.len(), 1);
+            assert_eq!(errors[0].field, field);
+            assert_eq!(errors[0].rejected_value, Some(json!(ids.iter().map(Uuid::to_string).collect::<Vec<_>>())));
+        }
+        err => panic!("expected invalid fields, got {:?}", err),
+    };
+
+    // Another site's category, or one that doesn't exist, fails the whole create
+    let missing = Uuid::new_v4();
+    let request = json!({ "title": "Kites", "content": "Fly", "category_ids": [news, elsewhere, missing] });
+    let err = posts.create(&site, actor, from_value(request).unwrap(), None).await.unwrap_err();
+    rejected(err, "category_ids", &[elsewhere, missing]);
+    let request = json!({ "title": "Kites", "content": "Fly", "tag_ids": [other_tag] });
+    let err = posts.create(&site, actor, from_value(request).unwrap(), None).await.unwrap_err();
+    rejected(err, "tag_ids", &[other_tag]);
+    assert_eq!(filed_under(&env.db, "kites").await, None);
+
+    // The site's own are attached
+    let request = json!({ "title": "Kites", "content": "Fly", "category_ids": [news], "tag_ids": [rust, rust] });
+    let post = posts.create(&site, actor, from_value(request).unwrap(), None).await.unwrap();
+    assert_eq!(filed_under(&env.db, "kites").await, Some((1, 1)));
+
+    // A rejected update leaves the post and its relations as they were
+    let existing = posts.get_by_id(&site, post.id).await.unwrap();
+    let request = json!({ "title": "Renamed", "category_ids": [elsewhere], "tag_ids": [] });
+    let err = posts.update(&site, existing, actor, from_value(request).unwrap()).await.unwrap_err();
+    rejected(err, "category_ids", &[elsewhere]);
+    assert_eq!(posts.get_by_id(&site, post.id).await.unwrap().title, "Kites");
+    assert_eq!(filed_under(&env.db, "kites").await, Some((1, 1)));
+}
+
+#[tokio::test]
+async fn test_refills_after_a_write_skip_lagging_replicas() {
+    let env = TestEnv::start().await;
+    env.migrate(&AuthPlugin::migrations()).await;
+    BlogApp::migrations().run(&env.db).await.expect("blog migrations failed");
+
+    let auth = env.auth_service().await;
+    let actor = TestUser::create(&auth, "ada@example.com", UserRole::Author).await.user.id;
+    let sites = SiteService::load(env.db.clone()).await.unwrap();
+    let (site, _) = sites.resolve(None, "/").await.expect("no default site");
+
+    // A replica that stopped replaying posts: its `blog_posts` is a copy that
+    // never changes, everything else is the primary's
+    env.db.execute("CREATE SCHEMA stale").await.unwrap();
+    let replica = PgPoolOptions::new()
+        .max_connections(2)
+        .after_connect(|conn, _| {
+            Box::pin(async move {
+                conn.execute("SET search_path TO stale, public").await?;
+                Ok(())
+            })
+        })
+        .connect_with((*env.db.connect_options()).clone())
+        .await
+        .unwrap();
+    let pools = Arc::new(DbPools::new(env.db.clone()).max_lag(Duration::from_secs(1)).with_replica(replica));
+
+    let cache = cache::connect(&CacheConfig::default()).await.unwrap();
+    let access = Arc::new(ContentAccess::new(pools.clone(), Arc::new(HookRegistry::new())));
+    let digests = Arc::new(DigestService::new(env.db.clone(), DigestConfig::default()));
+    let mentions = Arc::new(MentionService::new(env.db.clone(), digests, MentionConfig::default()));
+    let posts = PostService::new(pools.clone(), cache, Arc::new(ContentHooks::default()), access, mentions, 200);
+    let viewer = Viewer::anonymous();
+
+    let request = json!({ "title": "Kites", "content": "First version" });
+    let post = posts.create(&site, actor, from_value(request).unwrap(), None).await.unwrap();
+    posts.publish(&site, post.id, actor).await.unwrap();
+    env.db.execute("CREATE TABLE stale.blog_posts AS SELECT * FROM blog_posts").await.unwrap();
+    pools.refresh_lag().await;
+    assert_eq!(pools.replica_lag(), [Some(Duration::ZERO)]);
+
+    // Right after the edit the permalink is refilled from the primary, so the
+    // cache doesn't keep the replica's old row
+    let existing = posts.get_by_id(&site, post.id).await.unwrap();
+    let request = json!({ "content": "Second version" });
+    posts.update(&site, existing, actor, from_value(request).unwrap()).await.unwrap();
+    let read = posts.get_by_slug(&site, "kites", &viewer).await.unwrap();
+    assert_eq!(read.post.content, "Second version");
+    assert_eq!(published(&posts, &site, &viewer).await, ["Second version"]);
+
+    // Once the lag budget has passed, refills read from the replica again
+    tokio::time::sleep(Duration::from_millis(2500)).await;
+    let query = from_value(json!({ "per_page": 5 })).unwrap();
+    let page = posts.list_published(&site, &query, &viewer).await.unwrap();
+    assert_eq!(page.data[0].post.content, "First version");
+    let read = posts.get_by_slug(&site, "kites", &viewer).await.unwrap();
+    assert_eq!(read.post.content, "Second version");
+}
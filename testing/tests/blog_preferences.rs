@@ -0,0 +1,64 @@
+//! User display preferences over app and site defaults
+
+use rustpress_auth::{AuthPlugin, UserRole};
+use rustpress_blog_api::preferences::{PreferenceConfig, PreferenceService};
+use rustpress_blog_api::services::ServiceError;
+use rustpress_blog_api::sites::SiteService;
+use rustpress_blog_api::BlogApp;
+use rustpress_testing::{TestEnv, TestUser};
+use serde_json::json;
+use std::collections::BTreeMap;
+
+#[tokio::test]
+async fn test_user_preferences() {
+    let env = TestEnv::start().await;
+    env.migrate(&AuthPlugin::migrations()).await;
+    BlogApp::migrations().run(&env.db).await.expect("blog migrations failed");
+
+    let auth = env.auth_service().await;
+    let ada = TestUser::create(&auth, "ada@example.com", UserRole::User).await.user.id;
+
+    let sites = SiteService::load(env.db.clone()).await.unwrap();
+    let (site, _) = sites.resolve(None, "/").await.expect("no default site");
+    let mut site = (*site).clone();
+    site.config.preferences.insert("font_size".to_string(), json!(16));
+
+    let preferences = PreferenceService::new(env.db.clone(), PreferenceConfig::default());
+
+    // Visitors and new users get the defaults
+    let defaults = preferences.resolve(&site, None).await.unwrap();
+    assert_eq!(defaults.get("color_scheme"), Some(&json!("auto")));
+    assert_eq!(defaults.get("font_size"), Some(&json!(16)));
+    let current = preferences.get(&site, ada).await.unwrap();
+    assert_eq!(current.preferences, defaults);
+    assert!(current.customized.is_empty());
+
+    let changes = BTreeMap::from([("color_scheme".to_string(), json!("dark"))]);
+    let current = preferences.update(&site, ada, &changes).await.unwrap();
+    assert_eq!(current.preferences.get("color_scheme"), Some(&json!("dark")));
+    assert_eq!(current.customized, vec!["color_scheme"]);
+    let resolved = preferences.resolve(&site, Some(ada)).await.unwrap();
+    assert_eq!(resolved.get("color_scheme"), Some(&json!("dark")));
+
+    // Unknown keys, wrong types and unknown schemes are rejected together
+    let invalid = BTreeMap::from([
+        ("color_scheme".to_string(), json!("sepia")),
+        ("font_size".to_string(), json!("large")),
+        ("sidebar".to_string(), json!(true)),
+    ]);
+    match preferences.update(&site, ada, &invalid).await {
+        Err(ServiceError::InvalidFields(errors)) => {
+            let codes: Vec<&str> = errors.iter().map(|e| e.code.as_str()).collect();
+            assert_eq!(codes, vec!["one_of", "type", "unknown"]);
+        }
+        other => panic!("expected field errors, got {:?}", other),
+    }
+    let current = preferences.get(&site, ada).await.unwrap();
+    assert_eq!(current.preferences.get("color_scheme"), Some(&json!("dark")));
+
+    // `null` goes back to the default
+    let reset = BTreeMap::from([("color_scheme".to_string(), json!(null))]);
+    let current = preferences.update(&site, ada, &reset).await.unwrap();
+    assert_eq!(current.preferences.get("color_scheme"), Some(&json!("auto")));
+    assert!(current.customized.is_empty());
+}
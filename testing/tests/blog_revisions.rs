@@ -0,0 +1,92 @@
+//! Revisions recorded as posts are saved, and diffs between them
+
+use rustpress_apps::prelude::HookRegistry;
+use rustpress_auth::db::DbPools;
+use rustpress_auth::{AuthPlugin, UserRole};
+use rustpress_blog_api::access::ContentAccess;
+use rustpress_blog_api::activity::ContentHooks;
+use rustpress_blog_api::cache::{self, CacheConfig};
+use rustpress_blog_api::diff::html_hunks;
+use rustpress_blog_api::digests::{DigestConfig, DigestService};
+use rustpress_blog_api::mentions::{MentionConfig, MentionService};
+use rustpress_blog_api::models::{DiffChange, DiffOp, DiffQuery};
+use rustpress_blog_api::revisions::RevisionService;
+use rustpress_blog_api::services::{PostService, ServiceError};
+use rustpress_blog_api::sites::SiteService;
+use rustpress_blog_api::BlogApp;
+use rustpress_testing::{TestEnv, TestUser};
+use serde_json::{from_value, json};
+use std::sync::Arc;
+
+#[tokio::test]
+async fn test_post_revisions_and_diffs() {
+    let env = TestEnv::start().await;
+    env.migrate(&AuthPlugin::migrations()).await;
+    BlogApp::migrations().run(&env.db).await.expect("blog migrations failed");
+
+    let auth = env.auth_service().await;
+    let author = TestUser::create(&auth, "ada@example.com", UserRole::Author).await;
+    let actor = author.user.id;
+
+    let cache = cache::connect(&CacheConfig::default()).await.unwrap();
+    let pools = Arc::new(DbPools::new(env.db.clone()));
+    let access = Arc::new(ContentAccess::new(pools.clone(), Arc::new(HookRegistry::new())));
+    let digests = Arc::new(DigestService::new(env.db.clone(), DigestConfig::default()));
+    let mentions = Arc::new(MentionService::new(env.db.clone(), digests, MentionConfig::default()));
+    let revisions = Arc::new(RevisionService::new(env.db.clone()));
+    let hooks = Arc::new(ContentHooks::default());
+    hooks.listen(revisions.clone()).await;
+    let posts = PostService::new(pools, cache, hooks, access, mentions, 200);
+
+    let sites = SiteService::load(env.db.clone()).await.unwrap();
+    let (site, _) = sites.resolve(None, "/").await.expect("no default site");
+
+    let request = json!({ "title": "Hello", "content": "<p>Old text &amp; more</p>", "excerpt": "Hi" });
+    let post = posts.create(&site, actor, from_value(request).unwrap(), None).await.unwrap();
+
+    // Saves without new text don't add revisions
+    let existing = posts.get_by_id(&site, post.id).await.unwrap();
+    posts.update(&site, existing, actor, from_value(json!({})).unwrap()).await.unwrap();
+    let existing = posts.get_by_id(&site, post.id).await.unwrap();
+    let request = json!({ "title": "Hello, world", "content": "<p><em>New</em> text &amp; more</p>" });
+    posts.update(&site, existing, actor, from_value(request).unwrap()).await.unwrap();
+
+    let listed = revisions.list(&site, post.id).await.unwrap();
+    assert_eq!(listed.iter().map(|r| r.number).collect::<Vec<_>>(), vec![2, 1]);
+    assert_eq!(listed[0].author_name.as_deref(), Some("ada"));
+
+    // Only changed fields, content as hunks that keep tags and entities whole
+    let diff = revisions.diff(&site, post.id, &DiffQuery::default()).await.unwrap();
+    assert_eq!((diff.from, diff.to), (1, 2));
+    let fields: Vec<&str> = diff.fields.iter().map(|f| f.field.as_str()).collect();
+    assert_eq!(fields, vec!["title", "slug", "content"]);
+    assert_eq!(diff.fields[0].old.as_deref(), Some("Hello"));
+    assert_eq!(diff.fields[0].new.as_deref(), Some("Hello, world"));
+
+    let content = &diff.fields[2];
+    assert_eq!(content.old, None);
+    assert_eq!(content.hunks.len(), 1);
+    let change = |op, text: &str| DiffChange { op, text: text.to_string() };
+    assert_eq!(
+        content.hunks[0].changes,
+        vec![
+            change(DiffOp::Equal, "<p>"),
+            change(DiffOp::Delete, "Old"),
+            change(DiffOp::Insert, "<em>New</em>"),
+            change(DiffOp::Equal, " text &amp; more</p>"),
+        ]
+    );
+
+    let invalid = DiffQuery { from: Some(2), to: Some(1) };
+    assert!(matches!(revisions.diff(&site, post.id, &invalid).await, Err(ServiceError::Validation(_))));
+    let missing = DiffQuery { from: Some(1), to: Some(9) };
+    assert!(matches!(revisions.diff(&site, post.id, &missing).await, Err(ServiceError::NotFound(_))));
+
+    // Distant changes get their own hunks; attribute changes replace the tag
+    let old = "<p class=\"a\">one two three four five six seven eight nine ten eleven twelve</p>";
+    let new = "<p class=\"b\">one two three four five six seven eight nine ten eleven 12</p>";
+    let hunks = html_hunks(old, new, 2);
+    assert_eq!(hunks.len(), 2);
+    assert_eq!(hunks[0].changes[0], change(DiffOp::Delete, "<p class=\"a\">"));
+    assert_eq!(&new[hunks[1].new_start..hunks[1].new_end], "eleven 12</p>");
+}
@@ -0,0 +1,126 @@
+//! Post fields filled in by the post_before_save filter
+
+use rustpress_apps::prelude::{HookError, HookRegistry};
+use rustpress_auth::{AuthPlugin, UserRole};
+use rustpress_blog_api::models::{CreatePostRequest, Post, UpdatePostRequest};
+use rustpress_blog_api::save_filter::{SaveFilter, SAVE_FILTER};
+use rustpress_blog_api::sites::SiteService;
+use rustpress_blog_api::BlogApp;
+use rustpress_testing::{TestEnv, TestUser};
+use serde_json::{from_value, json, Value};
+use std::sync::Arc;
+use uuid::Uuid;
+
+#[tokio::test]
+async fn test_filter_fills_missing_fields() {
+    let env = TestEnv::start().await;
+    env.migrate(&AuthPlugin::migrations()).await;
+    BlogApp::migrations().run(&env.db).await.expect("blog migrations failed");
+
+    let auth = env.auth_service().await;
+    let author = TestUser::create(&auth, "ada@example.com", UserRole::Author).await.user.id;
+    let sites = SiteService::load(env.db.clone()).await.unwrap();
+    let (site, _) = sites.resolve(None, "/").await.expect("no default site");
+
+    let rust: Uuid = sqlx::query_scalar("INSERT INTO blog_tags (site_id, name, slug) VALUES ($1, 'Rust', 'rust') RETURNING id")
+        .bind(site.id)
+        .fetch_one(&env.db)
+        .await
+        .unwrap();
+
+    // Stands in for a plugin: fills blanks and suggests terms
+    let hooks = Arc::new(HookRegistry::new());
+    hooks
+        .add_filter(
+            SAVE_FILTER,
+            |_ctx, mut draft: Value| async move {
+                if draft["excerpt"].is_null() {
+                    draft["excerpt"] = json!("A generated excerpt.");
+                }
+                draft["meta_description"] = json!("word ".repeat(50));
+                draft["content"] = json!(draft["content"].as_str().unwrap().replace("<img ", r#"<img alt="A kite" "#));
+                draft["suggested_tags"] = json!(["rust", "Unknown"]);
+                Ok(draft)
+            },
+            0,
+        )
+        .await;
+    let filter = SaveFilter::new(env.db.clone(), hooks);
+
+    let mut req: CreatePostRequest = from_value(json!({
+        "title": "Kites",
+        "content": r#"<p><img src="/kite.png"></p>"#,
+    }))
+    .unwrap();
+    filter.create(&site, author, &mut req).await.unwrap();
+    assert_eq!(req.excerpt.as_deref(), Some("A generated excerpt."));
+    assert_eq!(req.content, r#"<p><img alt="A kite" src="/kite.png"></p>"#);
+    // Cut to the request limit, and only existing tags are attached
+    assert!(req.meta_description.unwrap().chars().count() <= 160);
+    assert_eq!(req.tag_ids, Some(vec![rust]));
+    assert_eq!(req.category_ids, None);
+
+    // The author's own terms are kept
+    let chosen = Uuid::new_v4();
+    let mut req: CreatePostRequest = from_value(json!({
+        "title": "Kites",
+        "content": "<p>Up high</p>",
+        "excerpt": "Mine",
+        "tag_ids": [chosen],
+    }))
+    .unwrap();
+    filter.create(&site, author, &mut req).await.unwrap();
+    assert_eq!(req.excerpt.as_deref(), Some("Mine"));
+    assert_eq!(req.tag_ids, Some(vec![chosen]));
+
+    // Updates only carry what the filter changed
+    let post: Post = sqlx::query_as(
+        "INSERT INTO blog_posts (site_id, author_id, title, slug, content, excerpt, status)
+         VALUES ($1, $2, 'Saved', 'saved', '<p>Saved</p>', 'Saved excerpt', 'draft') RETURNING *",
+    )
+    .bind(site.id)
+    .bind(author)
+    .fetch_one(&env.db)
+    .await
+    .unwrap();
+    let mut req: UpdatePostRequest = from_value(json!({ "title": "Renamed" })).unwrap();
+    filter.update(&site, &post, &mut req).await.unwrap();
+    assert_eq!(req.title.as_deref(), Some("Renamed"));
+    assert_eq!(req.excerpt, None);
+    assert_eq!(req.content, None);
+    assert!(req.meta_description.is_some());
+}
+
+#[tokio::test]
+async fn test_failing_filter_saves_as_submitted() {
+    let env = TestEnv::start().await;
+    env.migrate(&AuthPlugin::migrations()).await;
+    BlogApp::migrations().run(&env.db).await.expect("blog migrations failed");
+
+    let sites = SiteService::load(env.db.clone()).await.unwrap();
+    let (site, _) = sites.resolve(None, "/").await.expect("no default site");
+
+    let hooks = Arc::new(HookRegistry::new());
+    hooks
+        .add_filter(
+            SAVE_FILTER,
+            |_ctx, _draft: Value| async move { Err(HookError::ExternalService("model unavailable".into())) },
+            0,
+        )
+        .await;
+    let filter = SaveFilter::new(env.db.clone(), hooks);
+
+    let mut req: CreatePostRequest = from_value(json!({ "title": "Kites", "content": "<p>Up high</p>" })).unwrap();
+    filter.create(&site, Uuid::new_v4(), &mut req).await.unwrap();
+    assert_eq!(req.excerpt, None);
+    assert_eq!(req.content, "<p>Up high</p>");
+
+    // A filter returning a different shape is ignored too
+    let hooks = Arc::new(HookRegistry::new());
+    hooks
+        .add_filter(SAVE_FILTER, |_ctx, _draft: Value| async move { Ok(json!("nope")) }, 0)
+        .await;
+    let filter = SaveFilter::new(env.db.clone(), hooks);
+    filter.create(&site, Uuid::new_v4(), &mut req).await.unwrap();
+    assert_eq!(req.content, "<p>Up high</p>");
+}
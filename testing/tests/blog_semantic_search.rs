@@ -0,0 +1,128 @@
+//! Post embeddings, semantic search and related posts
+
+use axum::async_trait;
+use rustpress_auth::{AuthPlugin, UserRole};
+use rustpress_blog_api::embeddings::{EmbeddingConfig, EmbeddingError, EmbeddingProvider, EmbeddingService};
+use rustpress_blog_api::models::Post;
+use rustpress_blog_api::sites::{Site, SiteService};
+use rustpress_blog_api::BlogApp;
+use rustpress_testing::{TestEnv, TestUser};
+use std::sync::Arc;
+use uuid::Uuid;
+
+async fn insert_post(env: &TestEnv, site: &Site, author: Uuid, title: &str, content: &str, status: &str) -> Post {
+    let slug = title.to_lowercase().replace(' ', "-");
+    sqlx::query_as(
+        "INSERT INTO blog_posts (site_id, author_id, title, slug, content, status, published_at)
+         VALUES ($1, $2, $3, $4, $5, $6::post_status, NOW()) RETURNING *",
+    )
+    .bind(site.id)
+    .bind(author)
+    .bind(title)
+    .bind(slug)
+    .bind(content)
+    .bind(status)
+    .fetch_one(&env.db)
+    .await
+    .unwrap()
+}
+
+fn config() -> EmbeddingConfig {
+    EmbeddingConfig {
+        enabled: true,
+        ..EmbeddingConfig::default()
+    }
+}
+
+#[tokio::test]
+async fn test_embed_search_and_related() {
+    let env = TestEnv::with_pgvector().await;
+    env.migrate(&AuthPlugin::migrations()).await;
+    BlogApp::migrations().run(&env.db).await.expect("blog migrations failed");
+
+    let auth = env.auth_service().await;
+    let author = TestUser::create(&auth, "ada@example.com", UserRole::Author).await.user.id;
+    let sites = SiteService::load(env.db.clone()).await.unwrap();
+    let (site, _) = sites.resolve(None, "/").await.expect("no default site");
+
+    let brewing = insert_post(&env, &site, author, "Brewing coffee", "<p>Grind coffee beans for espresso at home.</p>", "published").await;
+    let machines = insert_post(&env, &site, author, "Espresso machines", "<p>Espresso machines, coffee and crema compared.</p>", "published").await;
+    let tomatoes = insert_post(&env, &site, author, "Growing tomatoes", "<p>Tomatoes need sun, soil and water.</p>", "published").await;
+    insert_post(&env, &site, author, "Coffee draft", "<p>Espresso coffee notes.</p>", "draft").await;
+
+    // Built-in hashing provider
+    let embeddings = EmbeddingService::new(env.db.clone(), config()).unwrap();
+    embeddings.prepare().await.expect("pgvector unavailable");
+    assert_eq!(embeddings.backfill().await.unwrap(), 3);
+    assert_eq!(embeddings.backfill().await.unwrap(), 0);
+
+    // Drafts are never found; posts matching by vector and keywords come first
+    let found = embeddings.search(&site, "espresso coffee", 10).await.unwrap();
+    assert_eq!(found.len(), 3);
+    assert_eq!(found[2], tomatoes.id);
+
+    // The post itself is left out
+    let related = embeddings.related(&site, &brewing, 5).await.unwrap();
+    assert_eq!(related, vec![machines.id, tomatoes.id]);
+
+    // Saves without text changes aren't embedded again
+    assert_eq!(embeddings.sync(&[brewing.id]).await.unwrap(), 0);
+    sqlx::query("UPDATE blog_posts SET content = '<p>Pour-over coffee.</p>' WHERE id = $1")
+        .bind(brewing.id)
+        .execute(&env.db)
+        .await
+        .unwrap();
+    assert_eq!(embeddings.sync(&[brewing.id]).await.unwrap(), 1);
+
+    // Unpublished posts leave the rankings
+    sqlx::query("UPDATE blog_posts SET status = 'draft' WHERE id = $1")
+        .bind(machines.id)
+        .execute(&env.db)
+        .await
+        .unwrap();
+    embeddings.sync(&[machines.id]).await.unwrap();
+    let stored: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM blog_post_embeddings")
+        .fetch_one(&env.db)
+        .await
+        .unwrap();
+    assert_eq!(stored, 2);
+    assert!(!embeddings.search(&site, "espresso coffee", 10).await.unwrap().contains(&machines.id));
+}
+
+/// A model endpoint that's down
+struct Unavailable;
+
+#[async_trait]
+impl EmbeddingProvider for Unavailable {
+    fn model(&self) -> &str {
+        "unavailable"
+    }
+
+    async fn embed(&self, _texts: &[String]) -> Result<Vec<Vec<f32>>, EmbeddingError> {
+        Err(EmbeddingError::Api {
+            status: 503,
+            message: "down".into(),
+        })
+    }
+}
+
+#[tokio::test]
+async fn test_search_falls_back_to_keywords() {
+    let env = TestEnv::with_pgvector().await;
+    env.migrate(&AuthPlugin::migrations()).await;
+    BlogApp::migrations().run(&env.db).await.expect("blog migrations failed");
+
+    let auth = env.auth_service().await;
+    let author = TestUser::create(&auth, "ada@example.com", UserRole::Author).await.user.id;
+    let sites = SiteService::load(env.db.clone()).await.unwrap();
+    let (site, _) = sites.resolve(None, "/").await.expect("no default site");
+
+    let post = insert_post(&env, &site, author, "Kite flying", "<p>Kites on a windy beach.</p>", "published").await;
+    insert_post(&env, &site, author, "Baking bread", "<p>Flour, water, salt.</p>", "published").await;
+
+    let embeddings = EmbeddingService::with_provider(env.db.clone(), Arc::new(Unavailable), config());
+    embeddings.prepare().await.expect("pgvector unavailable");
+    assert!(embeddings.backfill().await.is_err());
+
+    assert_eq!(embeddings.search(&site, "windy kites", 10).await.unwrap(), vec![post.id]);
+}
@@ -0,0 +1,115 @@
+//! Settings schemas with form hints, for plugin manifests and the auth
+//! plugin's read-only configuration
+
+use rustpress_apps::prelude::SettingsManager;
+use rustpress_auth::{AuthPlugin, Config, PLUGIN_ID};
+use rustpress_blog_api::extractors::User;
+use rustpress_blog_api::settings::{SettingsRegistry, SettingsService, SettingsSource};
+use rustpress_blog_api::sites::SiteService;
+use rustpress_blog_api::BlogApp;
+use rustpress_testing::TestEnv;
+use serde_json::{json, Map};
+use uuid::Uuid;
+
+const ANALYTICS: &str = include_str!("../../plugin/advanced-plugin/plugin.toml");
+const HELLO_WORLD: &str = include_str!("../../plugin/sample-plugin/plugin.toml");
+
+fn user(role: &str) -> User {
+    User {
+        id: Uuid::new_v4(),
+        email: format!("{}@example.com", role),
+        name: role.to_string(),
+        role: role.to_string(),
+        email_verified_at: None,
+    }
+}
+
+#[tokio::test]
+async fn test_manifest_form() {
+    let registry = SettingsRegistry::default();
+    registry
+        .register_manifest("rustpress-analytics", ANALYTICS, "admin")
+        .await
+        .unwrap();
+    registry.register_manifest("hello-world", HELLO_WORLD, "admin").await.unwrap();
+
+    let analytics = registry.get("rustpress-analytics").await.unwrap();
+    let schema = analytics.json_schema();
+    assert_eq!(schema["properties"]["data_retention_days"]["minimum"], 1);
+    assert_eq!(
+        schema["properties"]["legal_basis"]["enum"],
+        json!(["legitimate_interest", "consent"])
+    );
+    assert_eq!(schema["readOnly"], false);
+
+    let ui = analytics.ui_schema();
+    let sections: Vec<&str> = ui["sections"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|section| section["id"].as_str().unwrap())
+        .collect();
+    assert_eq!(sections, ["general", "privacy", "tracking"]);
+    assert_eq!(ui["sections"][1]["title"], "Privacy");
+    assert_eq!(ui["fields"]["legal_basis"]["widget"], "select");
+    assert_eq!(ui["fields"]["excluded_ips"]["widget"], "textarea");
+    assert!(ui["fields"]["excluded_ips"]["placeholder"].is_string());
+
+    // Settings without a section are grouped under `general`
+    let hello = registry.get("hello-world").await.unwrap().ui_schema();
+    assert_eq!(hello["sections"], json!([{ "id": "general", "title": "General", "fields": ["greeting", "show_date"] }]));
+    assert_eq!(hello["fields"]["show_date"]["widget"], "toggle");
+}
+
+#[tokio::test]
+async fn test_auth_config_read_only() {
+    let registry = SettingsRegistry::default();
+    registry
+        .register_config(PLUGIN_ID, rustpress_auth::config::SETTINGS_SCHEMA)
+        .await
+        .unwrap();
+    let auth = registry.get(PLUGIN_ID).await.unwrap();
+    assert_eq!(auth.source, SettingsSource::Config);
+    assert_eq!(auth.json_schema()["readOnly"], true);
+    assert_eq!(auth.ui_schema()["fields"]["auth.max_login_attempts"]["read_only"], true);
+    assert!(!auth.settings.contains_key("jwt.secret"));
+
+    // Values come from the configuration, defaults for keys it doesn't set
+    let config = Config::from_toml("[auth]\nmax_login_attempts = 10").unwrap();
+    let values = auth.config_values(&config).unwrap();
+    assert_eq!(values["auth.max_login_attempts"], 10);
+    assert_eq!(values["jwt.access_expiration"], 900);
+}
+
+#[tokio::test]
+async fn test_schema_access_and_writes() {
+    let env = TestEnv::start().await;
+    env.migrate(&AuthPlugin::migrations()).await;
+    BlogApp::migrations().run(&env.db).await.expect("blog migrations failed");
+
+    let sites = SiteService::load(env.db.clone()).await.unwrap();
+    let (site, _) = sites.resolve(None, "/").await.expect("no default site");
+    let registry = SettingsRegistry::default();
+    registry.register_manifest("hello-world", HELLO_WORLD, "admin").await.unwrap();
+    registry
+        .register_config(PLUGIN_ID, rustpress_auth::config::SETTINGS_SCHEMA)
+        .await
+        .unwrap();
+    let settings = SettingsService::new(env.db.clone(), SettingsManager::memory(), registry);
+    let admin = user("admin");
+
+    let document = settings.schema(&admin, "hello-world").await.unwrap();
+    assert_eq!(document["namespace"], "hello-world");
+    assert_eq!(document["source"], "store");
+    assert_eq!(document["schema"]["properties"]["greeting"]["default"], "Hello, World!");
+    assert!(settings.schema(&user("editor"), "hello-world").await.is_err());
+    assert!(settings.schema(&admin, "unknown").await.is_err());
+
+    // Configuration namespaces can't be changed, imported or exported
+    let mut changes = Map::new();
+    changes.insert("auth.max_login_attempts".into(), json!(3));
+    assert!(settings.update(&site, &admin, PLUGIN_ID, changes).await.is_err());
+    let export = settings.export(&site, &admin).await.unwrap();
+    assert!(export.namespaces.contains_key("hello-world"));
+    assert!(!export.namespaces.contains_key(PLUGIN_ID));
+}
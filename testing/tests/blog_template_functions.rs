@@ -0,0 +1,110 @@
+//! Template functions: escaping, failures, per-call timeouts and the page budget
+
+use axum::async_trait;
+use rustpress_apps::prelude::HookRegistry;
+use rustpress_auth::AuthPlugin;
+use rustpress_blog_api::services::ServiceError;
+use rustpress_blog_api::sites::SiteService;
+use rustpress_blog_api::template_functions::{
+    Escaping, TemplateFunction, TemplateFunctionConfig, TemplateFunctionContext, TemplateFunctionRegistry,
+};
+use rustpress_blog_api::BlogApp;
+use rustpress_testing::TestEnv;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tera::{Context, Tera};
+
+/// Sleeps `ms`, then returns `<b>done</b>`
+struct Slow(&'static str, Escaping);
+
+#[async_trait]
+impl TemplateFunction for Slow {
+    fn name(&self) -> &'static str {
+        self.0
+    }
+
+    fn escaping(&self) -> Escaping {
+        self.1
+    }
+
+    async fn call(&self, _: &TemplateFunctionContext, args: &HashMap<String, Value>) -> Result<Value, ServiceError> {
+        let ms = args.get("ms").and_then(Value::as_u64).unwrap_or(0);
+        tokio::time::sleep(Duration::from_millis(ms)).await;
+        Ok(json!("<b>done</b>"))
+    }
+}
+
+struct Failing;
+
+#[async_trait]
+impl TemplateFunction for Failing {
+    fn name(&self) -> &'static str {
+        "failing"
+    }
+
+    async fn call(&self, _: &TemplateFunctionContext, _: &HashMap<String, Value>) -> Result<Value, ServiceError> {
+        Err(ServiceError::Validation("broken".into()))
+    }
+}
+
+async fn render(registry: &Arc<TemplateFunctionRegistry>, ctx: &TemplateFunctionContext, template: &str) -> String {
+    let mut tera = Tera::default();
+    tera.add_raw_template("page.html", template).unwrap();
+    registry.install(&mut tera).await;
+
+    let (registry, ctx) = (registry.clone(), ctx.clone());
+    let handle = tokio::runtime::Handle::current();
+    tokio::task::spawn_blocking(move || {
+        registry.scoped(ctx, handle, || tera.render("page.html", &Context::new()).unwrap())
+    })
+    .await
+    .unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_template_functions() {
+    let env = TestEnv::start().await;
+    env.migrate(&AuthPlugin::migrations()).await;
+    BlogApp::migrations().run(&env.db).await.expect("blog migrations failed");
+    let sites = SiteService::load(env.db.clone()).await.unwrap();
+    let (site, _) = sites.resolve(None, "/").await.expect("no default site");
+    let ctx = TemplateFunctionContext {
+        db: env.db.clone(),
+        site,
+        user: None,
+        hooks: Arc::new(HookRegistry::new()),
+    };
+
+    let config = TemplateFunctionConfig {
+        timeout_ms: 100,
+        render_budget_ms: 200,
+    };
+    let registry = Arc::new(TemplateFunctionRegistry::with_builtins(config));
+    registry.register(Arc::new(Slow("slow", Escaping::Text))).await;
+    registry.register(Arc::new(Slow("trusted", Escaping::Html))).await;
+    registry.register(Arc::new(Failing)).await;
+    // Tera's own functions stay
+    registry.register(Arc::new(Slow("range", Escaping::Html))).await;
+    assert_eq!(registry.names().await, vec!["failing", "recent_posts", "slow", "trusted"]);
+
+    // Text output is escaped; HTML output is trusted
+    let html = render(&registry, &ctx, "{{ slow() }}|{{ trusted() }}|{{ range(end=2) | length }}").await;
+    assert_eq!(html, "&lt;b&gt;done&lt;&#x2F;b&gt;|<b>done</b>|2");
+
+    // Failures and timeouts render as nothing
+    let html = render(&registry, &ctx, "[{{ failing() }}][{{ slow(ms=500) }}][{{ slow(ms=10) }}]").await;
+    assert_eq!(html, "[][][&lt;b&gt;done&lt;&#x2F;b&gt;]");
+
+    // The page's budget is shared: the third call has no time left
+    let html = render(&registry, &ctx, "[{{ trusted(ms=80) }}][{{ trusted(ms=80) }}][{{ trusted(ms=80) }}]").await;
+    assert_eq!(html, "[<b>done</b>][<b>done</b>][]");
+
+    // Built in: no published posts yet
+    assert_eq!(render(&registry, &ctx, "{{ recent_posts(n=3) | length }}").await, "0");
+
+    let version = registry.version();
+    registry.unregister("failing").await;
+    assert!(registry.version() > version);
+}
@@ -0,0 +1,17 @@
+//! Body and post classes after filters
+
+use rustpress_blog_api::theme::unique_classes;
+
+fn classes(names: &[&str]) -> Vec<String> {
+    names.iter().map(|name| name.to_string()).collect()
+}
+
+#[test]
+fn test_repeated_classes_are_dropped_in_order() {
+    // A filter appending a class that is already present further back
+    let filtered = classes(&["post", "post-1", "category-news", "featured", "post", "category-news"]);
+    assert_eq!(unique_classes(filtered), classes(&["post", "post-1", "category-news", "featured"]));
+
+    assert_eq!(unique_classes(classes(&["home", "home", "logged-in"])), classes(&["home", "logged-in"]));
+    assert!(unique_classes(Vec::new()).is_empty());
+}
@@ -0,0 +1,78 @@
+//! Site and user time zones, and times read in them
+
+use chrono::{TimeZone, Utc};
+use rustpress_auth::{AuthPlugin, UserRole};
+use rustpress_blog_api::preferences::{PreferenceConfig, PreferenceService};
+use rustpress_blog_api::services::ServiceError;
+use rustpress_blog_api::sites::SiteService;
+use rustpress_blog_api::timezones::{resolve_time, ZonedTime};
+use rustpress_blog_api::BlogApp;
+use rustpress_testing::{TestEnv, TestUser};
+use serde_json::json;
+use std::collections::BTreeMap;
+
+#[test]
+fn test_local_times_are_read_in_zone() {
+    let berlin: chrono_tz::Tz = "Europe/Berlin".parse().unwrap();
+
+    // Offsets are kept, local times take the zone's offset on that day
+    let at = resolve_time("scheduled_for", "2026-03-01T09:00:00Z", berlin).unwrap();
+    assert_eq!(at, Utc.with_ymd_and_hms(2026, 3, 1, 9, 0, 0).unwrap());
+    let at = resolve_time("scheduled_for", "2026-03-01T09:00", berlin).unwrap();
+    assert_eq!(at, Utc.with_ymd_and_hms(2026, 3, 1, 8, 0, 0).unwrap());
+    let at = resolve_time("scheduled_for", "2026-07-01 09:00", berlin).unwrap();
+    assert_eq!(at, Utc.with_ymd_and_hms(2026, 7, 1, 7, 0, 0).unwrap());
+
+    // 02:30 is skipped when clocks go forward, so it's 03:30
+    let at = resolve_time("scheduled_for", "2026-03-29T02:30", berlin).unwrap();
+    assert_eq!(at, Utc.with_ymd_and_hms(2026, 3, 29, 1, 30, 0).unwrap());
+
+    match resolve_time("scheduled_for", "next tuesday", berlin) {
+        Err(ServiceError::InvalidFields(errors)) => {
+            assert_eq!(errors[0].field, "scheduled_for");
+            assert_eq!(errors[0].code, "datetime");
+        }
+        other => panic!("expected a field error, got {:?}", other),
+    }
+
+    let zoned = ZonedTime::new(Utc.with_ymd_and_hms(2026, 7, 1, 7, 0, 0).unwrap(), berlin);
+    assert_eq!(
+        serde_json::to_value(&zoned).unwrap(),
+        json!({
+            "at": "2026-07-01T09:00:00+02:00",
+            "timezone": "Europe/Berlin",
+            "utc_offset": "+02:00",
+        })
+    );
+}
+
+#[tokio::test]
+async fn test_user_timezone_over_site_timezone() {
+    let env = TestEnv::start().await;
+    env.migrate(&AuthPlugin::migrations()).await;
+    BlogApp::migrations().run(&env.db).await.expect("blog migrations failed");
+
+    let auth = env.auth_service().await;
+    let ada = TestUser::create(&auth, "ada@example.com", UserRole::User).await.user.id;
+
+    let sites = SiteService::load(env.db.clone()).await.unwrap();
+    let (site, _) = sites.resolve(None, "/").await.expect("no default site");
+    let mut site = (*site).clone();
+    let preferences = PreferenceService::new(env.db.clone(), PreferenceConfig::default());
+
+    // UTC until the site picks a zone
+    assert_eq!(preferences.timezone(&site, None).await.unwrap(), chrono_tz::UTC);
+    site.config.timezone = Some("America/New_York".to_string());
+    assert_eq!(preferences.timezone(&site, None).await.unwrap(), chrono_tz::America::New_York);
+    assert_eq!(preferences.timezone(&site, Some(ada)).await.unwrap(), chrono_tz::America::New_York);
+
+    let changes = BTreeMap::from([("timezone".to_string(), json!("Asia/Tokyo"))]);
+    preferences.update(&site, ada, &changes).await.unwrap();
+    assert_eq!(preferences.timezone(&site, Some(ada)).await.unwrap(), chrono_tz::Asia::Tokyo);
+
+    let invalid = BTreeMap::from([("timezone".to_string(), json!("Mars/Olympus_Mons"))]);
+    match preferences.update(&site, ada, &invalid).await {
+        Err(ServiceError::InvalidFields(errors)) => assert_eq!(errors[0].code, "timezone"),
+        other => panic!("expected field errors, got {:?}", other),
+    }
+}
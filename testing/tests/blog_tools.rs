@@ -0,0 +1,158 @@
+//! Tool API keys, scopes and tool descriptions
+
+use rustpress_analytics::services::{GeoIp, IngestMetrics, ReportService, TrackingService};
+use rustpress_analytics::{AnalyticsConfig, AnalyticsPlugin};
+use rustpress_auth::db::DbPools;
+use rustpress_auth::problem::ProblemDetails;
+use rustpress_auth::{AuthPlugin, MetricsRegistry, UserRole};
+use rustpress_blog_api::models::{CreateToolKeyRequest, ToolScope};
+use rustpress_blog_api::services::ServiceError;
+use rustpress_blog_api::sites::SiteService;
+use rustpress_blog_api::tools::{summarize_analytics, Tool, ToolCaller, ToolConfig, ToolService};
+use rustpress_blog_api::BlogApp;
+use rustpress_testing::{TestEnv, TestUser};
+use serde_json::{from_value, json};
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+#[test]
+fn test_tool_definitions() {
+    for tool in Tool::ALL {
+        assert_eq!(Tool::from_name(tool.name()), Some(tool));
+        let definition = tool.definition();
+        assert_eq!(definition.input_schema["type"], "object", "{}", tool.name());
+    }
+    assert_eq!(Tool::from_name("publish_post"), None);
+
+    let create = Tool::CreateDraft.definition();
+    assert!(create.writes);
+    let required = create.input_schema["required"].as_array().unwrap();
+    assert!(required.contains(&"title".into()) && required.contains(&"content".into()));
+    assert!(create.input_schema["properties"].get("scheduled_for").is_none());
+    assert!(!Tool::SummarizeAnalytics.definition().writes);
+}
+
+#[tokio::test]
+async fn test_tool_keys() {
+    let env = TestEnv::start().await;
+    env.migrate(&AuthPlugin::migrations()).await;
+    BlogApp::migrations().run(&env.db).await.expect("blog migrations failed");
+
+    let auth = env.auth_service().await;
+    let admin = TestUser::create(&auth, "admin@example.com", UserRole::Admin).await.user.id;
+    let ada = TestUser::create(&auth, "ada@example.com", UserRole::Author).await.user.id;
+
+    let sites = SiteService::load(env.db.clone()).await.unwrap();
+    let (site, _) = sites.resolve(None, "/").await.expect("no default site");
+    let tools = ToolService::new(env.db.clone(), ToolConfig { enabled: true });
+
+    // Scopes must suit the role of the user the key acts as
+    let request = |user_id, scopes: Vec<ToolScope>| CreateToolKeyRequest {
+        name: "agent".to_string(),
+        user_id: Some(user_id),
+        scopes,
+    };
+    match tools
+        .create_key(&site, admin, request(ada, vec![ToolScope::PostsWrite, ToolScope::AnalyticsRead]))
+        .await
+    {
+        Err(ServiceError::InvalidFields(errors)) => {
+            assert_eq!(errors.len(), 1);
+            assert_eq!(errors[0].field, "scopes[1]");
+        }
+        other => panic!("expected field errors, got {:?}", other.map(|key| key.key)),
+    }
+
+    let created = tools
+        .create_key(&site, admin, request(ada, vec![ToolScope::PostsWrite, ToolScope::PostsWrite]))
+        .await
+        .unwrap();
+    assert!(created.secret.starts_with("rpt_"));
+    assert!(created.secret.starts_with(&created.key.prefix));
+    assert_eq!(created.key.scopes, [ToolScope::PostsWrite]);
+
+    // Only the hash is kept
+    let stored: String = sqlx::query_scalar("SELECT key_hash FROM blog_tool_keys WHERE id = $1")
+        .bind(created.key.id)
+        .fetch_one(&env.db)
+        .await
+        .unwrap();
+    assert_ne!(stored, created.secret);
+
+    // The key acts as its user, within its scopes
+    let (key, user) = tools.authenticate(&site, &created.secret).await.unwrap().unwrap();
+    assert_eq!(user.id, ada);
+    assert!(key.last_used_at.is_some());
+    let caller = ToolCaller { key, user };
+    assert!(caller.may_use(Tool::CreateDraft) && caller.may_use(Tool::UpdatePost));
+    assert!(!caller.may_use(Tool::ListPendingComments));
+    let names: Vec<String> = caller.tools().into_iter().map(|tool| tool.name).collect();
+    assert_eq!(names, ["create_draft", "update_post"]);
+
+    assert!(tools.authenticate(&site, "rpt_guessed").await.unwrap().is_none());
+
+    // Revoked keys and keys of suspended users stop working
+    let other = tools
+        .create_key(&site, admin, request(ada, vec![ToolScope::PostsWrite]))
+        .await
+        .unwrap();
+    tools.revoke_key(&site, created.key.id).await.unwrap();
+    assert!(tools.authenticate(&site, &created.secret).await.unwrap().is_none());
+    sqlx::query("UPDATE users SET status = 'suspended' WHERE id = $1")
+        .bind(ada)
+        .execute(&env.db)
+        .await
+        .unwrap();
+    assert!(tools.authenticate(&site, &other.secret).await.unwrap().is_none());
+
+    let keys = tools.list_keys(&site).await.unwrap();
+    assert_eq!(keys.len(), 2);
+    assert!(keys.iter().any(|key| key.revoked_at.is_some()));
+}
+
+#[tokio::test]
+async fn test_summarize_analytics_reports_on_the_keys_site() {
+    let env = TestEnv::start().await;
+    env.migrate(&AuthPlugin::migrations()).await;
+    env.migrate(&AnalyticsPlugin::migrations()).await;
+    BlogApp::migrations().run(&env.db).await.expect("blog migrations failed");
+
+    let sites = SiteService::load(env.db.clone()).await.unwrap();
+    let (site, _) = sites.resolve(None, "/").await.expect("no default site");
+    let config = AnalyticsConfig::default();
+    let metrics = IngestMetrics::register(&Arc::new(MetricsRegistry::default()).plugin("rustpress-analytics")).unwrap();
+    let tracking = TrackingService::new(env.db.clone(), config.clone(), Arc::new(GeoIp::open()), metrics, None);
+    let reports = ReportService::new(
+        Arc::new(DbPools::new(env.db.clone())),
+        config.conversion_events.clone(),
+        config.timezone,
+    );
+
+    // The site's home page twice; another site's pages and hits without a site don't count
+    let firefox = "Mozilla/5.0 (X11; Linux x86_64) Firefox/130.0";
+    let other = Some(Uuid::new_v4());
+    for (site_id, path, ip) in [
+        (Some(site.id), "/", [203, 0, 113, 7]),
+        (Some(site.id), "/", [203, 0, 113, 8]),
+        (other, "/shop", [198, 51, 100, 1]),
+        (other, "/shop", [198, 51, 100, 2]),
+        (other, "/shop", [198, 51, 100, 3]),
+        (None, "/", [198, 51, 100, 4]),
+    ] {
+        let hit = from_value(json!({ "event_type": "pageview", "path": path, "site_id": site_id })).unwrap();
+        tracking.track_pageview(&hit, Some(ip.into()), firefox).await.unwrap();
+    }
+
+    let input = from_value(json!({ "period": "7d" })).unwrap();
+    let summary = summarize_analytics(&reports, &site, input).await.unwrap();
+    assert_eq!(summary["period"], "7d");
+    assert_eq!(summary["top_pages"], json!([{ "path": "/", "title": null, "page_views": 2, "unique_visitors": 2 }]));
+
+    // A report over its time limit is a 503 to retry, not a database error
+    let reports = reports.with_query_timeout(Duration::ZERO);
+    let input = from_value(json!({})).unwrap();
+    let err = summarize_analytics(&reports, &site, input).await.unwrap_err();
+    assert!(matches!(err, ServiceError::Unavailable(_)), "{:?}", err);
+    assert_eq!(ProblemDetails::from(err).status, 503);
+}
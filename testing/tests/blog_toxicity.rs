@@ -0,0 +1,170 @@
+//! Comment toxicity scores, thresholds and their storage with comments
+
+use axum::async_trait;
+use rustpress_auth::{AuthPlugin, UserRole};
+use rustpress_blog_api::activity::ContentHooks;
+use rustpress_blog_api::extractors::{ClientInfo, User};
+use rustpress_blog_api::models::{CommentStatus, CreateCommentRequest};
+use rustpress_blog_api::services::{CommentService, NewComment};
+use rustpress_blog_api::sites::SiteService;
+use rustpress_blog_api::toxicity::{
+    perspective_scores, ModerationProvider, RulesProvider, ToxicityConfig, ToxicityError, ToxicityService,
+};
+use rustpress_blog_api::BlogApp;
+use rustpress_testing::{TestEnv, TestUser};
+use serde_json::{from_value, json};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use uuid::Uuid;
+
+fn config() -> ToxicityConfig {
+    ToxicityConfig {
+        enabled: true,
+        ..ToxicityConfig::default()
+    }
+}
+
+fn user(role: &str) -> User {
+    User {
+        id: Uuid::new_v4(),
+        email: format!("{}@example.com", role),
+        name: role.to_string(),
+        role: role.to_string(),
+        email_verified_at: None,
+    }
+}
+
+#[test]
+fn test_rules_scores() {
+    let terms = BTreeMap::from([("idiot".to_string(), 0.5), ("shut up".to_string(), 0.6)]);
+    let rules = RulesProvider::new(&terms).unwrap();
+
+    assert_eq!(rules.toxicity("Thanks, great post"), 0.0);
+    // Whole words only, each term once
+    assert_eq!(rules.toxicity("Idiotic idea"), 0.0);
+    assert_eq!(rules.toxicity("IDIOT, idiot!"), 0.5);
+    assert!((rules.toxicity("Shut up, idiot") - 0.8).abs() < 1e-6);
+}
+
+#[test]
+fn test_perspective_response() {
+    let body = json!({
+        "attributeScores": {
+            "TOXICITY": { "summaryScore": { "value": 0.82, "type": "PROBABILITY" } },
+            "INSULT": { "summaryScore": { "value": 0.4, "type": "PROBABILITY" } }
+        },
+        "languages": ["en"]
+    });
+    let scores = perspective_scores(&body).unwrap();
+    assert_eq!(scores.len(), 2);
+    assert!((scores["toxicity"] - 0.82).abs() < 1e-6);
+
+    assert!(perspective_scores(&json!({ "error": "quota" })).is_err());
+}
+
+#[tokio::test]
+async fn test_thresholds() {
+    let toxicity = ToxicityService::new(config()).unwrap();
+
+    let benign = toxicity.assess(None, "Lovely photos").await;
+    assert_eq!(benign.score.unwrap().score, 0.0);
+    assert_eq!(benign.route, None);
+
+    let rude = toxicity.assess(None, "What an idiot. Shut up").await;
+    assert_eq!(rude.route, Some(CommentStatus::Pending));
+    assert_eq!(rude.score.as_ref().unwrap().model, "rules");
+
+    let abusive = toxicity.assess(Some(&user("user")), "Kill yourself").await;
+    assert_eq!(abusive.route, Some(CommentStatus::Spam));
+
+    // Moderators aren't scored
+    let editor = toxicity.assess(Some(&user("editor")), "Kill yourself").await;
+    assert!(editor.score.is_none());
+    assert_eq!(editor.route, None);
+
+    let off = ToxicityService::new(ToxicityConfig::default()).unwrap();
+    assert!(off.assess(None, "Kill yourself").await.score.is_none());
+}
+
+/// A classifier that's down
+struct Unavailable;
+
+#[async_trait]
+impl ModerationProvider for Unavailable {
+    fn model(&self) -> &str {
+        "unavailable"
+    }
+
+    async fn score(&self, _text: &str) -> Result<BTreeMap<String, f32>, ToxicityError> {
+        Err(ToxicityError::Api {
+            status: 503,
+            message: "down".into(),
+        })
+    }
+}
+
+#[tokio::test]
+async fn test_provider_failures() {
+    let lenient = ToxicityService::with_provider(Arc::new(Unavailable), config());
+    let assessed = lenient.assess(None, "Hello").await;
+    assert!(assessed.score.is_none());
+    assert_eq!(assessed.route, None);
+
+    let strict = ToxicityService::with_provider(
+        Arc::new(Unavailable),
+        ToxicityConfig {
+            hold_on_error: true,
+            ..config()
+        },
+    );
+    assert_eq!(strict.assess(None, "Hello").await.route, Some(CommentStatus::Pending));
+}
+
+#[tokio::test]
+async fn test_score_stored_with_comment() {
+    let env = TestEnv::start().await;
+    env.migrate(&AuthPlugin::migrations()).await;
+    BlogApp::migrations().run(&env.db).await.expect("blog migrations failed");
+
+    let auth = env.auth_service().await;
+    let author = TestUser::create(&auth, "ada@example.com", UserRole::Author).await.user.id;
+    let sites = SiteService::load(env.db.clone()).await.unwrap();
+    let (site, _) = sites.resolve(None, "/").await.expect("no default site");
+    let post_id: Uuid = sqlx::query_scalar(
+        "INSERT INTO blog_posts (author_id, title, slug, content, status) VALUES ($1, 'Hello', 'hello', 'Hi', 'published') RETURNING id",
+    )
+    .bind(author)
+    .fetch_one(&env.db)
+    .await
+    .unwrap();
+
+    let comments = CommentService::new(env.db.clone(), Arc::new(ContentHooks::default()));
+    let toxicity = ToxicityService::new(config()).unwrap();
+    let text = "Shut up, idiot";
+    let assessed = toxicity.assess(None, text).await;
+    let request: CreateCommentRequest =
+        from_value(json!({ "author_name": "Troll", "author_email": "troll@example.com", "content": text })).unwrap();
+
+    let comment = comments
+        .create(
+            &site,
+            post_id,
+            None,
+            request,
+            NewComment {
+                client: ClientInfo::default(),
+                status: assessed.route.unwrap(),
+                toxicity: assessed.score.as_ref(),
+            },
+        )
+        .await
+        .unwrap();
+    assert_eq!(comment.status, CommentStatus::Pending);
+    assert!((comment.toxicity.unwrap() - 0.8).abs() < 1e-6);
+    assert_eq!(comment.toxicity_model.as_deref(), Some("rules"));
+    assert!(comment.toxicity_attributes.unwrap()["toxicity"].is_number());
+
+    // Moderators see it with the comments they review
+    let pending = comments.list_pending(&site, Some(post_id), 10).await.unwrap();
+    assert_eq!(pending[0].toxicity, comment.toxicity);
+}
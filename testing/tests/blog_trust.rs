@@ -0,0 +1,123 @@
+//! Trusted commenters matched by verified email, overrides and the
+//! auto-approval audit
+
+use rustpress_auth::{AuthPlugin, UserRole};
+use rustpress_blog_api::activity::ContentHooks;
+use rustpress_blog_api::extractors::{ClientInfo, User};
+use rustpress_blog_api::models::{AutoApprovalQuery, CommentStatus, CreateCommentRequest, TrustReason};
+use rustpress_blog_api::services::{CommentService, NewComment};
+use rustpress_blog_api::sites::SiteService;
+use rustpress_blog_api::trust::{Trust, TrustConfig, TrustService};
+use rustpress_blog_api::BlogApp;
+use rustpress_testing::{TestEnv, TestUser};
+use serde_json::{from_value, json};
+use std::sync::Arc;
+use uuid::Uuid;
+
+#[tokio::test]
+async fn test_trusted_commenters() {
+    let env = TestEnv::start().await;
+    env.migrate(&AuthPlugin::migrations()).await;
+    BlogApp::migrations().run(&env.db).await.expect("blog migrations failed");
+
+    let auth = env.auth_service().await;
+    let admin = TestUser::create(&auth, "admin@example.com", UserRole::Admin).await;
+    let regular = TestUser::create(&auth, "grace@example.com", UserRole::User).await;
+    let actor = admin.user.id;
+
+    let sites = SiteService::load(env.db.clone()).await.unwrap();
+    let (site, _) = sites.resolve(None, "/").await.expect("no default site");
+    let post_id: Uuid = sqlx::query_scalar(
+        "INSERT INTO blog_posts (author_id, title, slug, content, status) VALUES ($1, 'Hello', 'hello', 'Hi', 'published') RETURNING id",
+    )
+    .bind(actor)
+    .fetch_one(&env.db)
+    .await
+    .unwrap();
+
+    let comments = CommentService::new(env.db.clone(), Arc::new(ContentHooks::default()));
+    let trust = TrustService::new(
+        env.db.clone(),
+        TrustConfig {
+            approved_comments: 2,
+            ..Default::default()
+        },
+    );
+    let comment = |email: &str| -> CreateCommentRequest {
+        from_value(json!({ "author_name": "Grace", "author_email": email, "content": "Nice post" })).unwrap()
+    };
+    let moderated = |status: CommentStatus| NewComment {
+        client: ClientInfo::default(),
+        status,
+        toxicity: None,
+    };
+
+    // Approved comments don't count for an unverified address
+    for _ in 0..2 {
+        let held = comments
+            .create(&site, post_id, None, comment("grace@example.com"), moderated(CommentStatus::Pending))
+            .await
+            .unwrap();
+        comments.approve(&site, held.id, actor).await.unwrap();
+    }
+    let evaluated = trust.evaluate(&site, None, "Grace@Example.com").await.unwrap();
+    assert_eq!(evaluated, Trust::Unknown);
+
+    // Once verified, they do, in any case
+    sqlx::query("UPDATE users SET email_verified_at = NOW() WHERE id = $1")
+        .bind(regular.user.id)
+        .execute(&env.db)
+        .await
+        .unwrap();
+    let Trust::Trusted(grant) = trust.evaluate(&site, None, "Grace@Example.com").await.unwrap() else {
+        panic!("commenter not trusted");
+    };
+    assert_eq!(grant.user_id, regular.user.id);
+    assert_eq!(grant.reason, TrustReason::History);
+    assert_eq!(grant.approved_comments, 2);
+
+    let approved = comments
+        .create(&site, post_id, None, comment("grace@example.com"), moderated(CommentStatus::Approved))
+        .await
+        .unwrap();
+    trust.record(&site, &approved, &grant).await.unwrap();
+
+    // Spot checks sample unreviewed auto-approvals; not keeping one is recorded
+    let unreviewed = AutoApprovalQuery {
+        unreviewed: true,
+        sample: Some(5),
+    };
+    let sampled = trust.audit(&site, &unreviewed).await.unwrap();
+    assert_eq!(sampled.len(), 1);
+    assert_eq!(sampled[0].comment_id, approved.id);
+    let reviewed = trust.review(&site, approved.id, actor, false).await.unwrap();
+    assert_eq!(reviewed.kept, Some(false));
+    assert_eq!(reviewed.reviewed_by, Some(actor));
+    assert_eq!(reviewed.status, CommentStatus::Approved);
+    assert!(trust.audit(&site, &unreviewed).await.unwrap().is_empty());
+
+    // Overrides win over history either way
+    let distrusted = trust.set(&site, regular.user.id, Some(false), actor).await.unwrap();
+    assert!(!distrusted.auto_approved);
+    assert_eq!(distrusted.approved_comments, 3);
+    let evaluated = trust.evaluate(&site, None, "grace@example.com").await.unwrap();
+    assert_eq!(evaluated, Trust::Distrusted);
+
+    // Signed-in commenters are matched by account
+    trust.set(&site, admin.user.id, Some(true), actor).await.unwrap();
+    let signed_in = User {
+        id: admin.user.id,
+        email: "admin@example.com".into(),
+        name: "admin".into(),
+        role: "admin".into(),
+        email_verified_at: None,
+    };
+    let Trust::Trusted(grant) = trust.evaluate(&site, Some(&signed_in), "admin@example.com").await.unwrap() else {
+        panic!("override ignored");
+    };
+    assert_eq!(grant.reason, TrustReason::Override);
+
+    let cleared = trust.set(&site, regular.user.id, None, actor).await.unwrap();
+    assert_eq!(cleared.trusted, None);
+    assert!(cleared.auto_approved);
+}
@@ -0,0 +1,223 @@
+//! Comment votes: one per voter, changed or taken back, rate limited, and
+//! downvotes only from established accounts
+
+use rustpress_auth::{AuthPlugin, UserRole};
+use rustpress_blog_api::activity::ContentHooks;
+use rustpress_blog_api::extractors::ClientInfo;
+use rustpress_blog_api::models::{CommentStatus, CommentVotes, CreateCommentRequest, VoteStyle};
+use rustpress_blog_api::services::{CommentService, NewComment, ServiceError};
+use rustpress_blog_api::sites::{Site, SiteService};
+use rustpress_blog_api::votes::{VoteConfig, VoteService, Voter};
+use rustpress_blog_api::BlogApp;
+use rustpress_testing::{TestEnv, TestUser};
+use serde_json::{from_value, json};
+use std::sync::Arc;
+use uuid::Uuid;
+
+struct Setup {
+    env: TestEnv,
+    site: Site,
+    comments: CommentService,
+    post_id: Uuid,
+    /// Accounts old enough to downvote
+    voters: Vec<Uuid>,
+    /// An account created just now
+    newcomer: Uuid,
+}
+
+impl Setup {
+    async fn start() -> Self {
+        let env = TestEnv::start().await;
+        env.migrate(&AuthPlugin::migrations()).await;
+        BlogApp::migrations().run(&env.db).await.expect("blog migrations failed");
+
+        let auth = env.auth_service().await;
+        let author = TestUser::create(&auth, "admin@example.com", UserRole::Admin).await.user.id;
+        let mut voters = Vec::new();
+        for email in ["ada@example.com", "grace@example.com"] {
+            let id = TestUser::create(&auth, email, UserRole::User).await.user.id;
+            sqlx::query("UPDATE users SET created_at = NOW() - INTERVAL '30 days' WHERE id = $1")
+                .bind(id)
+                .execute(&env.db)
+                .await
+                .unwrap();
+            voters.push(id);
+        }
+        let newcomer = TestUser::create(&auth, "new@example.com", UserRole::User).await.user.id;
+
+        let sites = SiteService::load(env.db.clone()).await.unwrap();
+        let (site, _) = sites.resolve(None, "/").await.expect("no default site");
+        let post_id: Uuid = sqlx::query_scalar(
+            "INSERT INTO blog_posts (author_id, title, slug, content, status) VALUES ($1, 'Hello', 'hello', 'Hi', 'published') RETURNING id",
+        )
+        .bind(author)
+        .fetch_one(&env.db)
+        .await
+        .unwrap();
+        let comments = CommentService::new(env.db.clone(), Arc::new(ContentHooks::default()));
+        Self { env, site, comments, post_id, voters, newcomer }
+    }
+
+    async fn comment(&self, status: CommentStatus) -> Uuid {
+        let req: CreateCommentRequest =
+            from_value(json!({ "author_name": "Grace", "author_email": "grace@example.com", "content": "Nice post" })).unwrap();
+        let new = NewComment {
+            client: ClientInfo::default(),
+            status,
+            toxicity: None,
+        };
+        self.comments.create(&self.site, self.post_id, None, req, new).await.unwrap().id
+    }
+
+    fn votes(&self, config: VoteConfig) -> VoteService {
+        VoteService::new(self.env.db.clone(), config)
+    }
+
+    /// Score, upvotes and downvotes as stored on the comment
+    async fn stored(&self, comment_id: Uuid) -> (i32, i32, i32) {
+        sqlx::query_as("SELECT score, upvotes, downvotes FROM blog_comments WHERE id = $1")
+            .bind(comment_id)
+            .fetch_one(&self.env.db)
+            .await
+            .unwrap()
+    }
+}
+
+fn tally(votes: &CommentVotes) -> (i32, i32, i32, Option<i16>) {
+    (votes.score, votes.upvotes, votes.downvotes, votes.viewer_vote)
+}
+
+fn guest(site: &Site, ip: &str, user_agent: &str) -> Voter {
+    let client = ClientInfo {
+        ip: Some(ip.to_string()),
+        user_agent: Some(user_agent.to_string()),
+    };
+    Voter::new(site, None, &client)
+}
+
+#[tokio::test]
+async fn test_votes_count_once_and_can_change() {
+    let setup = Setup::start().await;
+    let votes = setup.votes(VoteConfig::default());
+    let comment = setup.comment(CommentStatus::Approved).await;
+    let (ada, grace) = (Voter::User(setup.voters[0]), Voter::User(setup.voters[1]));
+
+    // Voting the same way twice is one vote
+    for _ in 0..2 {
+        let counted = votes.vote(&setup.site, comment, &ada, 1).await.unwrap();
+        assert_eq!(tally(&counted), (1, 1, 0, Some(1)));
+    }
+
+    // Voting the other way replaces the vote
+    let counted = votes.vote(&setup.site, comment, &ada, -1).await.unwrap();
+    assert_eq!(tally(&counted), (-1, 0, 1, Some(-1)));
+    let counted = votes.vote(&setup.site, comment, &grace, 1).await.unwrap();
+    assert_eq!(tally(&counted), (0, 1, 1, Some(1)));
+
+    // A guest is the same voter while their address and browser are
+    let phone = guest(&setup.site, "203.0.113.7", "Mobile Safari");
+    assert_eq!(phone, guest(&setup.site, "203.0.113.7", "Mobile Safari"));
+    votes.vote(&setup.site, comment, &phone, 1).await.unwrap();
+    let counted = votes
+        .vote(&setup.site, comment, &guest(&setup.site, "203.0.113.7", "Mobile Safari"), 1)
+        .await
+        .unwrap();
+    assert_eq!(tally(&counted), (1, 2, 1, Some(1)));
+    let laptop = guest(&setup.site, "203.0.113.7", "Firefox");
+    let counted = votes.vote(&setup.site, comment, &laptop, 1).await.unwrap();
+    assert_eq!(tally(&counted), (2, 3, 1, Some(1)));
+
+    // Taking votes back, including one never cast, leaves the others
+    let counted = votes.vote(&setup.site, comment, &ada, 0).await.unwrap();
+    assert_eq!(tally(&counted), (3, 3, 0, None));
+    let counted = votes.vote(&setup.site, comment, &ada, 0).await.unwrap();
+    assert_eq!(tally(&counted), (3, 3, 0, None));
+    assert_eq!(setup.stored(comment).await, (3, 3, 0));
+
+    // Only approved comments take votes
+    let pending = setup.comment(CommentStatus::Pending).await;
+    let err = votes.vote(&setup.site, pending, &grace, 1).await.unwrap_err();
+    assert!(matches!(err, ServiceError::NotFound(_)), "{:?}", err);
+    assert_eq!(setup.stored(pending).await, (0, 0, 0));
+
+    // Sites with hearts take no downvotes, and sites with votes off none at all
+    let mut hearts = setup.site.clone();
+    hearts.config.comment_votes = Some(VoteStyle::Hearts);
+    let err = votes.vote(&hearts, comment, &grace, -1).await.unwrap_err();
+    assert!(matches!(err, ServiceError::Validation(_)), "{:?}", err);
+    let mut off = setup.site.clone();
+    off.config.comment_votes = Some(VoteStyle::Off);
+    let err = votes.vote(&off, comment, &grace, 1).await.unwrap_err();
+    assert!(matches!(err, ServiceError::PermissionDenied), "{:?}", err);
+    assert_eq!(setup.stored(comment).await, (3, 3, 0));
+}
+
+#[tokio::test]
+async fn test_new_accounts_and_guests_cannot_downvote() {
+    let setup = Setup::start().await;
+    let votes = setup.votes(VoteConfig::default());
+    let comment = setup.comment(CommentStatus::Approved).await;
+    let newcomer = Voter::User(setup.newcomer);
+
+    let err = votes.vote(&setup.site, comment, &newcomer, -1).await.unwrap_err();
+    assert!(matches!(err, ServiceError::PermissionDenied), "{:?}", err);
+    let counted = votes.vote(&setup.site, comment, &newcomer, 1).await.unwrap();
+    assert_eq!(tally(&counted), (1, 1, 0, Some(1)));
+
+    // Old enough once the account is `downvote_min_account_days` old
+    sqlx::query("UPDATE users SET created_at = NOW() - INTERVAL '6 days' WHERE id = $1")
+        .bind(setup.newcomer)
+        .execute(&setup.env.db)
+        .await
+        .unwrap();
+    assert!(votes.vote(&setup.site, comment, &newcomer, -1).await.is_err());
+    sqlx::query("UPDATE users SET created_at = NOW() - INTERVAL '7 days' WHERE id = $1")
+        .bind(setup.newcomer)
+        .execute(&setup.env.db)
+        .await
+        .unwrap();
+    let counted = votes.vote(&setup.site, comment, &newcomer, -1).await.unwrap();
+    assert_eq!(tally(&counted), (-1, 0, 1, Some(-1)));
+
+    // Guests may only upvote, and only where guest votes are on
+    let visitor = guest(&setup.site, "198.51.100.1", "Firefox");
+    let err = votes.vote(&setup.site, comment, &visitor, -1).await.unwrap_err();
+    assert!(matches!(err, ServiceError::PermissionDenied), "{:?}", err);
+    let members_only = setup.votes(VoteConfig {
+        guest_votes: false,
+        ..Default::default()
+    });
+    let err = members_only.vote(&setup.site, comment, &visitor, 1).await.unwrap_err();
+    assert!(matches!(err, ServiceError::PermissionDenied), "{:?}", err);
+    assert_eq!(setup.stored(comment).await, (-1, 0, 1));
+}
+
+#[tokio::test]
+async fn test_votes_are_rate_limited_per_voter() {
+    let setup = Setup::start().await;
+    let votes = setup.votes(VoteConfig {
+        per_minute: 3,
+        ..Default::default()
+    });
+    let comment = setup.comment(CommentStatus::Approved).await;
+    let (ada, grace) = (Voter::User(setup.voters[0]), Voter::User(setup.voters[1]));
+
+    // Changing a vote counts against the limit like casting one
+    for value in [1, -1, 1] {
+        votes.vote(&setup.site, comment, &ada, value).await.unwrap();
+    }
+    let err = votes.vote(&setup.site, comment, &ada, -1).await.unwrap_err();
+    assert!(matches!(err, ServiceError::RateLimited(_)), "{:?}", err);
+    assert_eq!(setup.stored(comment).await, (1, 1, 0));
+
+    // Other voters have limits of their own
+    let counted = votes.vote(&setup.site, comment, &grace, 1).await.unwrap();
+    assert_eq!(tally(&counted), (2, 2, 0, Some(1)));
+    let visitor = guest(&setup.site, "198.51.100.1", "Firefox");
+    for _ in 0..3 {
+        votes.vote(&setup.site, comment, &visitor, 1).await.unwrap();
+    }
+    let err = votes.vote(&setup.site, comment, &visitor, 0).await.unwrap_err();
+    assert!(matches!(err, ServiceError::RateLimited(_)), "{:?}", err);
+    assert_eq!(setup.stored(comment).await, (3, 3, 0));
+}